@@ -1,6 +1,6 @@
 use crate::bidi_class::BidiClass;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Direction {
     LeftToRight,
     RightToLeft,
@@ -27,6 +27,9 @@ pub enum ParagraphDirectionHint {
     AutoLeftToRight,
     /// Attempt to auto-detect but fall back to RTL
     AutoRightToLeft,
+    /// Auto-detect the direction of each logical line from its first
+    /// strong character, falling back to LTR
+    Auto,
 }
 
 impl Default for ParagraphDirectionHint {
@@ -40,14 +43,43 @@ impl ParagraphDirectionHint {
     /// of the auto-detection state.
     pub fn direction(self) -> Direction {
         match self {
-            ParagraphDirectionHint::AutoLeftToRight | ParagraphDirectionHint::LeftToRight => {
-                Direction::LeftToRight
-            }
+            ParagraphDirectionHint::Auto
+            | ParagraphDirectionHint::AutoLeftToRight
+            | ParagraphDirectionHint::LeftToRight => Direction::LeftToRight,
             ParagraphDirectionHint::AutoRightToLeft | ParagraphDirectionHint::RightToLeft => {
                 Direction::RightToLeft
             }
         }
     }
+
+    /// Returns true if the direction is to be detected from the text
+    pub fn is_auto(self) -> bool {
+        !matches!(
+            self,
+            ParagraphDirectionHint::LeftToRight | ParagraphDirectionHint::RightToLeft
+        )
+    }
+
+    /// Resolves the direction of `paragraph`.
+    /// For the auto-detecting hints this is the direction of its first
+    /// strong character, per rules P2 and P3, falling back to the
+    /// direction of the hint when it has none.
+    pub fn resolve_direction(self, paragraph: impl IntoIterator<Item = char>) -> Direction {
+        if !self.is_auto() {
+            return self.direction();
+        }
+        let types: Vec<BidiClass> = paragraph.into_iter().map(bidi_class_for_char).collect();
+        paragraph_level(&types, false, self.direction()).direction()
+    }
+}
+
+impl From<Direction> for ParagraphDirectionHint {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::LeftToRight => ParagraphDirectionHint::LeftToRight,
+            Direction::RightToLeft => ParagraphDirectionHint::RightToLeft,
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -454,7 +486,7 @@ impl BidiContext {
         self.base_level = match hint {
             ParagraphDirectionHint::LeftToRight => Level(0),
             ParagraphDirectionHint::RightToLeft => Level(1),
-            ParagraphDirectionHint::AutoLeftToRight => {
+            ParagraphDirectionHint::Auto | ParagraphDirectionHint::AutoLeftToRight => {
                 paragraph_level(&self.char_types, false, Direction::LeftToRight)
             }
             ParagraphDirectionHint::AutoRightToLeft => {
@@ -2084,4 +2116,34 @@ mod tests {
         ];
         assert_eq!(reordered, explicit_ltr);
     }
+
+    #[test]
+    fn resolve_direction() {
+        let hint = ParagraphDirectionHint::Auto;
+        assert_eq!(
+            hint.resolve_direction("ls -l".chars()),
+            Direction::LeftToRight
+        );
+        assert_eq!(
+            hint.resolve_direction("123 אבג abc".chars()),
+            Direction::RightToLeft
+        );
+        // Text within an isolate doesn't decide the direction
+        assert_eq!(
+            hint.resolve_direction("\u{2067}אבג\u{2069} abc".chars()),
+            Direction::LeftToRight
+        );
+        assert_eq!(
+            hint.resolve_direction("123 ...".chars()),
+            Direction::LeftToRight
+        );
+        assert_eq!(
+            ParagraphDirectionHint::AutoRightToLeft.resolve_direction("123 ...".chars()),
+            Direction::RightToLeft
+        );
+        assert_eq!(
+            ParagraphDirectionHint::LeftToRight.resolve_direction("אבג".chars()),
+            Direction::LeftToRight
+        );
+    }
 }
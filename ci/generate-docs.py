@@ -458,10 +458,12 @@ TOC = [
         children=[
             Gen("wezterm cli", "cli/cli"),
             Page("wezterm connect", "cli/connect.md"),
+            Page("wezterm gen-schema", "cli/gen-schema.md"),
             Page("wezterm imgcat", "cli/imgcat.md"),
             Page("wezterm ls-fonts", "cli/ls-fonts.md"),
             Page("wezterm record", "cli/record.md"),
             Page("wezterm replay", "cli/replay.md"),
+            Page("wezterm restore", "cli/restore.md"),
             Page("wezterm serial", "cli/serial.md"),
             Page("wezterm set-working-directory", "cli/set-working-directory.md"),
             Page("wezterm show-keys", "cli/show-keys.md"),
@@ -38,9 +38,15 @@ git submodule foreach | while read entering path; do
 done
 
 echo $TAG_NAME > .tag
-tar --owner root --group root --transform "s,^,$NAME_PREFIX/," -c -f tmp.tar .tag
+MARKERS=.tag
+if [[ "$BUILD_REASON" == "Schedule" ]] ; then
+  # Nightly versions look like release tags; let build.rs tell them apart
+  touch .nightly
+  MARKERS="$MARKERS .nightly"
+fi
+tar --owner root --group root --transform "s,^,$NAME_PREFIX/," -c -f tmp.tar $MARKERS
 tar --concatenate --file=${TAR_NAME} tmp.tar
-rm tmp.tar .tag
+rm -f tmp.tar .tag .nightly
 
 # Remove bulky bits that are not required to build from source; this helps
 # to keep the source tarball small!
@@ -12,18 +12,19 @@
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::range_plus_one))]
 
 use anyhow::{bail, Context as _, Error};
-use config::keyassignment::{PaneDirection, ScrollbackEraseMode};
+use config::keyassignment::{PaneDirection, PaneLinkOptions, ScrollbackEraseMode};
 use mux::client::{ClientId, ClientInfo};
+use mux::domain::{DomainId, InheritEnvironment};
 use mux::pane::PaneId;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
-use mux::tab::{PaneNode, SerdeUrl, SplitRequest, TabId};
+use mux::tab::{FloatingPaneGeometry, PaneEntry, PaneNode, SerdeUrl, SplitRequest, TabId};
 use mux::window::WindowId;
 use portable_pty::CommandBuilder;
 use rangeset::*;
 use serde::{Deserialize, Serialize};
 use smol::io::AsyncWriteExt;
 use smol::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::io::Cursor;
 use std::ops::Range;
@@ -34,7 +35,10 @@ use termwiz::image::{ImageData, TextureCoordinate};
 use termwiz::surface::{Line, SequenceNo};
 use thiserror::Error;
 use wezterm_term::color::ColorPalette;
-use wezterm_term::{Alert, ClipboardSelection, StableRowIndex, TerminalSize};
+use wezterm_term::{
+    Alert, ClipboardSelection, MousePointerShape, SemanticZone, StableRowIndex, TerminalSize,
+    VisibleRowIndex,
+};
 
 #[derive(Error, Debug)]
 #[error("Corrupt Response: {0}")]
@@ -441,7 +445,7 @@ macro_rules! pdu {
 /// The overall version of the codec.
 /// This must be bumped when backwards incompatible changes
 /// are made to the types and protocol.
-pub const CODEC_VERSION: usize = 43;
+pub const CODEC_VERSION: usize = 75;
 
 // Defines the Pdu enum.
 // Each struct has an explicit identifying number.
@@ -502,6 +506,38 @@ pdu! {
     GetPaneDirection: 60,
     GetPaneDirectionResponse: 61,
     AdjustPaneSize: 62,
+    RespawnPane: 63,
+    GetSemanticZones: 64,
+    GetSemanticZonesResponse: 65,
+    SetPaneSubscription: 66,
+    PaneOutputSummary: 67,
+    AdoptTab: 68,
+    AdoptTabResponse: 69,
+    MoveTabToDomain: 70,
+    SetPaneSilenceThreshold: 71,
+    ListDomains: 72,
+    ListDomainsResponse: 73,
+    AttachDomain: 74,
+    DetachDomain: 75,
+    DomainStateChangeResponse: 76,
+    FetchImageData: 77,
+    FetchImageDataResponse: 78,
+    LinkPanes: 79,
+    PauseOutput: 80,
+    KillClient: 81,
+    DisconnectedByServer: 82,
+    AddPaneWatch: 83,
+    RemovePaneWatch: 84,
+    ListClosed: 85,
+    ListClosedResponse: 86,
+    GetLogEntries: 87,
+    GetLogEntriesResponse: 88,
+    SpawnFloatingPane: 89,
+    PrependScrollback: 90,
+    ResolveInheritedEnv: 91,
+    ResolveInheritedEnvResponse: 92,
+    GetPanePalette: 93,
+    GetPanePaletteResponse: 94,
 }
 
 impl Pdu {
@@ -517,7 +553,19 @@ impl Pdu {
             | Self::Resize(_)
             | Self::SetClipboard(_)
             | Self::SetPaneZoomed(_)
-            | Self::SpawnV2(_) => true,
+            | Self::SpawnV2(_)
+            | Self::SplitPane(_)
+            | Self::SpawnFloatingPane(_)
+            | Self::KillPane(_)
+            | Self::RespawnPane(_)
+            | Self::SetFocusedPane(_)
+            | Self::ActivatePaneDirection(_)
+            | Self::AdjustPaneSize(_)
+            | Self::MovePaneToNewTab(_)
+            | Self::SetWindowWorkspace(_)
+            | Self::RenameWorkspace(_)
+            | Self::EraseScrollbackRequest(_)
+            | Self::MoveTabToDomain(_) => true,
             _ => false,
         }
     }
@@ -595,6 +643,7 @@ impl Pdu {
             | Pdu::NotifyAlert(NotifyAlert { pane_id, .. })
             | Pdu::SetClipboard(SetClipboard { pane_id, .. })
             | Pdu::PaneFocused(PaneFocused { pane_id })
+            | Pdu::PaneOutputSummary(PaneOutputSummary { pane_id, .. })
             | Pdu::PaneRemoved(PaneRemoved { pane_id }) => Some(*pane_id),
             _ => None,
         }
@@ -645,8 +694,16 @@ pub struct ListPanes {}
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct ListPanesResponse {
     pub tabs: Vec<PaneNode>,
+    /// The floating panes of each of the tabs
+    pub floating_panes: Vec<Vec<PaneEntry>>,
+    /// The geometry of each of `floating_panes`
+    pub floating_pane_geometries: Vec<Vec<FloatingPaneGeometry>>,
     pub tab_titles: Vec<String>,
     pub window_titles: HashMap<WindowId, String>,
+    /// Whether each of `tab_titles` was set by the user
+    pub tab_title_overrides: Vec<bool>,
+    /// The windows whose title was set by the user
+    pub window_title_overrides: HashSet<WindowId>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -656,11 +713,23 @@ pub struct SplitPane {
     pub command: Option<CommandBuilder>,
     pub command_dir: Option<String>,
     pub domain: config::keyassignment::SpawnTabDomain,
+    /// The environment that the command takes from the pane
+    /// that is being split
+    pub inherit_env: InheritEnvironment,
     /// Instead of spawning a command, move the specified
     /// pane into the new split target
     pub move_pane_id: Option<PaneId>,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SpawnFloatingPane {
+    pub tab_id: TabId,
+    pub geometry: FloatingPaneGeometry,
+    pub command: Option<CommandBuilder>,
+    pub command_dir: Option<String>,
+    pub domain: config::keyassignment::SpawnTabDomain,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct MovePaneToNewTab {
     pub pane_id: PaneId,
@@ -683,6 +752,9 @@ pub struct SpawnV2 {
     pub command_dir: Option<String>,
     pub size: TerminalSize,
     pub workspace: String,
+    /// The name of an entry in the `profiles` config of the server
+    /// to apply to the new pane
+    pub profile: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -811,12 +883,18 @@ pub struct TabResized {
 pub struct TabTitleChanged {
     pub tab_id: TabId,
     pub title: String,
+    /// true if the title was set by the user rather than by an
+    /// application.  Setting an empty override clears it.
+    pub user_override: bool,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct WindowTitleChanged {
     pub window_id: WindowId,
     pub title: String,
+    /// true if the title was set by the user rather than by an
+    /// application.  Setting an empty override clears it.
+    pub user_override: bool,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -834,6 +912,8 @@ pub struct WindowWorkspaceChanged {
 pub struct SetClientId {
     pub client_id: ClientId,
     pub is_proxy: bool,
+    /// The CODEC_VERSION of the client
+    pub codec_vers: usize,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -849,6 +929,20 @@ pub struct GetClientListResponse {
     pub clients: Vec<ClientInfo>,
 }
 
+/// Asks the server to disconnect the client whose
+/// `ClientInfo::session_id` is `session_id`
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct KillClient {
+    pub session_id: usize,
+}
+
+/// Sent by the server to a client just before it
+/// closes the connection to that client
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct DisconnectedByServer {
+    pub reason: String,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct Resize {
     pub containing_tab_id: TabId,
@@ -876,6 +970,201 @@ pub struct AdjustPaneSize {
     pub amount: usize,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct RespawnPane {
+    pub pane_id: PaneId,
+    /// If None, the command originally used to spawn the pane is re-used
+    pub command: Option<CommandBuilder>,
+    pub command_dir: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetSemanticZones {
+    pub pane_id: PaneId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetSemanticZonesResponse {
+    pub zones: Vec<SemanticZone>,
+}
+
+/// Advises the server of the set of panes that the client is
+/// currently rendering.  Full render deltas are only pushed for
+/// those panes; other panes produce a `PaneOutputSummary` instead.
+/// Until a client sends this, it is subscribed to all panes.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetPaneSubscription {
+    pub pane_ids: Vec<PaneId>,
+}
+
+/// Sent by the server in place of `GetPaneRenderChangesResponse`
+/// for panes that the client has not subscribed to.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct PaneOutputSummary {
+    pub pane_id: PaneId,
+    pub seqno: SequenceNo,
+    /// The number of lines that changed since the client last
+    /// received a full delta for this pane
+    pub lines_changed: usize,
+    pub title: String,
+}
+
+/// Describes a pane whose pty is being handed off to the server
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct AdoptedPane {
+    /// The id of the pane in the client
+    pub pane_id: PaneId,
+    pub pid: u32,
+    pub tty_name: Option<PathBuf>,
+    pub command_description: String,
+    pub size: TerminalSize,
+    /// The primary screen, including scrollback
+    pub lines: SerializedLines,
+    /// The alternate screen, if it is active
+    pub alt_lines: Option<SerializedLines>,
+    pub cursor_x: usize,
+    pub cursor_y: VisibleRowIndex,
+    pub title: String,
+    pub working_dir: Option<SerdeUrl>,
+    pub user_vars: HashMap<String, String>,
+}
+
+#[cfg(unix)]
+impl AdoptedPane {
+    pub fn from_handoff(handoff: &mux::handoff::PaneHandoff) -> Self {
+        fn serialize(lines: &[Line]) -> SerializedLines {
+            lines
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(idx, line)| (idx as StableRowIndex, line))
+                .collect::<Vec<_>>()
+                .into()
+        }
+
+        let snapshot = &handoff.snapshot;
+        Self {
+            pane_id: handoff.pane_id,
+            pid: handoff.pid,
+            tty_name: handoff.tty_name.clone(),
+            command_description: handoff.command_description.clone(),
+            size: handoff.size,
+            lines: serialize(&snapshot.lines),
+            alt_lines: snapshot.alt_lines.as_deref().map(serialize),
+            cursor_x: snapshot.cursor_x,
+            cursor_y: snapshot.cursor_y,
+            title: snapshot.title.clone(),
+            working_dir: snapshot.current_dir.clone().map(|url| SerdeUrl { url }),
+            user_vars: snapshot.user_vars.clone(),
+        }
+    }
+
+    /// Reconstitutes the handoff, given the pty that was received for it.
+    /// Images are not transferred, so any image cells are dropped.
+    pub fn into_handoff(self, fd: std::os::fd::OwnedFd) -> mux::handoff::PaneHandoff {
+        fn deserialize(lines: SerializedLines) -> Vec<Line> {
+            let (lines, _images) = lines.extract_data();
+            lines.into_iter().map(|(_, line)| line).collect()
+        }
+
+        mux::handoff::PaneHandoff {
+            pane_id: self.pane_id,
+            fd,
+            pid: self.pid,
+            tty_name: self.tty_name,
+            command_description: self.command_description,
+            size: self.size,
+            snapshot: wezterm_term::TerminalSnapshot {
+                lines: deserialize(self.lines),
+                alt_lines: self.alt_lines.map(deserialize),
+                cursor_x: self.cursor_x,
+                cursor_y: self.cursor_y,
+                title: self.title,
+                current_dir: self.working_dir.map(|dir| dir.url),
+                user_vars: self.user_vars,
+            },
+        }
+    }
+}
+
+/// Asks the server to adopt panes that were spawned by the client,
+/// placing them into a new tab with the layout described by `tree`.
+/// The client listens on `handoff_path` and, once the server connects,
+/// sends the pty of each pane in the same order as `panes`.
+/// This is only possible when the client and server are on the
+/// same machine.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct AdoptTab {
+    pub window_id: Option<WindowId>,
+    pub workspace: String,
+    pub size: TerminalSize,
+    pub tree: PaneNode,
+    pub panes: Vec<AdoptedPane>,
+    pub handoff_path: PathBuf,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct AdoptTabResponse {
+    pub window_id: WindowId,
+    pub tab_id: TabId,
+    /// Maps the pane ids of the client to the new pane ids
+    pub pane_ids: HashMap<PaneId, PaneId>,
+}
+
+/// Asks the recipient to move a tab into the named client domain,
+/// handing its panes off to the mux server for that domain
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MoveTabToDomain {
+    pub tab_id: TabId,
+    pub domain: String,
+}
+
+/// Overrides the `monitor_silence` configuration for a pane.
+/// `None` reverts to the configured value.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetPaneSilenceThreshold {
+    pub pane_id: PaneId,
+    pub seconds: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListDomains {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct DomainInfo {
+    pub domain_id: DomainId,
+    pub name: String,
+    /// The kind of domain, such as `local`, `unix` or `ssh`
+    pub domain_type: String,
+    pub attached: bool,
+    pub num_panes: usize,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListDomainsResponse {
+    pub domains: Vec<DomainInfo>,
+}
+
+/// Asks the recipient to attach the named domain, as
+/// the `AttachDomain` key assignment does
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct AttachDomain {
+    pub name: String,
+}
+
+/// Asks the recipient to detach the named domain, as
+/// the `DetachDomain` key assignment does
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct DetachDomain {
+    pub name: String,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct DomainStateChangeResponse {
+    /// false if the domain was already in the requested state
+    pub changed: bool,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct GetPaneDirectionResponse {
     pub pane_id: Option<PaneId>,
@@ -925,6 +1214,18 @@ pub struct GetPaneRenderChangesResponse {
 
     pub input_serial: Option<InputSerial>,
     pub seqno: SequenceNo,
+    /// The kitty keyboard protocol flags that are currently
+    /// active in the pane, or None if it is not in use
+    pub kitty_keyboard_flags: Option<u16>,
+    /// Whether DEC private mode 2027, grapheme cluster
+    /// width measurement, is enabled in the pane
+    pub grapheme_clustering: bool,
+    /// The shape of the mouse pointer that the application
+    /// in the pane requested using OSC 22
+    pub mouse_pointer_shape: Option<MousePointerShape>,
+    /// Whether the application in the pane has enabled
+    /// bracketed paste
+    pub bracketed_paste: bool,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -1142,10 +1443,171 @@ pub struct GetImageCellResponse {
     pub data: Option<Arc<ImageData>>,
 }
 
+/// The maximum number of bytes of image data that are sent
+/// in a single FetchImageDataResponse
+pub const IMAGE_DATA_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Requests a chunk of the serialized image data with the
+/// specified hash.  `line_idx` and `cell_idx` identify a cell
+/// that was known to reference the image, to help the server
+/// locate it.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct FetchImageData {
+    pub pane_id: PaneId,
+    pub line_idx: StableRowIndex,
+    pub cell_idx: usize,
+    pub data_hash: [u8; 32],
+    pub offset: usize,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct FetchImageDataResponse {
+    pub data_hash: [u8; 32],
+    /// The total size of the serialized image data,
+    /// or None if the image could not be found
+    pub total_len: Option<usize>,
+    pub offset: usize,
+    pub chunk: Vec<u8>,
+}
+
+/// Links two panes, or removes the link between them if
+/// `options` has neither behavior set
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct LinkPanes {
+    pub pane_id: PaneId,
+    pub other_pane_id: PaneId,
+    pub options: PaneLinkOptions,
+}
+
+/// Pauses or resumes reading the output of a pane, so that the
+/// server applies backpressure to the program in the pane rather
+/// than buffering its output
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct PauseOutput {
+    pub pane_id: PaneId,
+    pub paused: bool,
+}
+
+/// Starts watching the output of a pane for a pattern, on behalf
+/// of `pane:watch`.  Matches are reported via NotifyAlert.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct AddPaneWatch {
+    pub pane_id: PaneId,
+    pub id: String,
+    pub pattern: String,
+    pub regex: bool,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct RemovePaneWatch {
+    pub pane_id: PaneId,
+    pub id: String,
+}
+
+/// Resolves the environment that a command spawned from a pane
+/// inherits from it, as the foreground process of the pane is
+/// only accessible where the pane lives
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ResolveInheritedEnv {
+    pub pane_id: PaneId,
+    pub inherit_env: InheritEnvironment,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ResolveInheritedEnvResponse {
+    pub env: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPanePalette {
+    pub pane_id: PaneId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetPanePaletteResponse {
+    pub palette: ColorPalette,
+}
+
+/// Places the scrollback of a closed pane above the output of the
+/// pane that reopened it.  Images are not transferred.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct PrependScrollback {
+    pub pane_id: PaneId,
+    pub lines: SerializedLines,
+}
+
+/// Asks for the panes and tabs that were recently closed
+/// and can be reopened
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListClosed {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ListClosedResponse {
+    /// Most recently closed first
+    pub items: Vec<mux::closed::ClosedItemInfo>,
+}
+
+/// Asks for the log entries that the server process has retained
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetLogEntries {
+    /// Only entries whose seqno is greater than this are returned;
+    /// pass the seqno of the last entry received to follow the log
+    pub after: u64,
+    /// Only entries at least as severe as this level are returned;
+    /// one of "error", "warn", "info", "debug" or "trace"
+    pub level: String,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetLogEntriesResponse {
+    /// Oldest first
+    pub entries: Vec<LogEntry>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct LogEntry {
+    pub seqno: u64,
+    /// Milliseconds since the unix epoch
+    pub timestamp_ms: i64,
+    pub level: String,
+    pub target: String,
+    pub msg: String,
+}
+
+/// Serializes image data for transfer via FetchImageData.
+/// The transfer is compressed along with the rest of the Pdu.
+pub fn serialize_image_data(data: &ImageData) -> anyhow::Result<Vec<u8>> {
+    let mut result = Vec::new();
+    let mut encode = varbincode::Serializer::new(&mut result);
+    data.serialize(&mut encode)?;
+    Ok(result)
+}
+
+pub fn deserialize_image_data(mut bytes: &[u8]) -> anyhow::Result<ImageData> {
+    let mut decode = varbincode::Deserializer::new(&mut bytes);
+    ImageData::deserialize(&mut decode).map_err(Into::into)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_image_data_chunks() {
+        use termwiz::image::ImageDataType;
+
+        let data =
+            ImageData::with_data(ImageDataType::new_single_frame(2, 2, vec![0xff; 2 * 2 * 4]));
+        let bytes = serialize_image_data(&data).unwrap();
+
+        let mut received = vec![];
+        for chunk in bytes.chunks(5) {
+            received.extend_from_slice(chunk);
+        }
+        let decoded = deserialize_image_data(&received).unwrap();
+        assert_eq!(decoded.hash(), data.hash());
+    }
+
     #[test]
     fn test_frame() {
         let mut encoded = Vec::new();
@@ -1173,6 +1635,32 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_pdu_spawn_floating_pane() {
+        use config::keyassignment::{FloatingPanePosition, SpawnTabDomain};
+        use mux::tab::SplitSize;
+
+        let pdu = Pdu::SpawnFloatingPane(SpawnFloatingPane {
+            tab_id: 3,
+            geometry: FloatingPaneGeometry {
+                width: SplitSize::Cells(20),
+                height: SplitSize::Percent(50),
+                position: FloatingPanePosition::BottomRight,
+            },
+            command: None,
+            command_dir: Some("/tmp".to_string()),
+            domain: SpawnTabDomain::CurrentPaneDomain,
+        });
+        assert!(pdu.is_user_input());
+
+        let mut encoded = Vec::new();
+        pdu.encode(&mut encoded, 0x40).unwrap();
+        assert_eq!(
+            Pdu::decode(encoded.as_slice()).unwrap(),
+            DecodedPdu { serial: 0x40, pdu }
+        );
+    }
+
     #[test]
     fn test_pdu_ping() {
         let mut encoded = Vec::new();
@@ -1187,6 +1675,36 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_pdu_kill_client() {
+        let client_id = ClientId {
+            hostname: "host".to_string(),
+            username: "me".to_string(),
+            pid: 42,
+            epoch: 0,
+            id: 1,
+            ssh_auth_sock: None,
+        };
+        for pdu in [
+            Pdu::SetClientId(SetClientId {
+                client_id,
+                is_proxy: false,
+                codec_vers: CODEC_VERSION,
+            }),
+            Pdu::KillClient(KillClient { session_id: 3 }),
+            Pdu::DisconnectedByServer(DisconnectedByServer {
+                reason: "detached by server".to_string(),
+            }),
+        ] {
+            let mut encoded = Vec::new();
+            pdu.encode(&mut encoded, 0x40).unwrap();
+            assert_eq!(
+                Pdu::decode(encoded.as_slice()).unwrap(),
+                DecodedPdu { serial: 0x40, pdu }
+            );
+        }
+    }
+
     #[test]
     fn stream_decode() {
         let mut encoded = Vec::new();
@@ -1250,6 +1768,60 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_underline_attributes_round_trip() {
+        use termwiz::cell::{CellAttributes, Underline};
+        use termwiz::color::{ColorAttribute, RgbColor};
+        use termwiz::surface::SEQ_ZERO;
+
+        let mut attrs = CellAttributes::default();
+        for underline in [
+            Underline::Single,
+            Underline::Double,
+            Underline::Curly,
+            Underline::Dotted,
+            Underline::Dashed,
+        ] {
+            attrs.set_underline(underline);
+            attrs.set_underline_color(ColorAttribute::TrueColorWithDefaultFallback(
+                RgbColor::new_8bpc(0xff, 0x00, 0x80).into(),
+            ));
+            let line = Line::from_text("wavy", &attrs, SEQ_ZERO, None);
+
+            let pdu = Pdu::GetLinesResponse(GetLinesResponse {
+                pane_id: 1,
+                lines: vec![(0, line)].into(),
+            });
+            let mut encoded = Vec::new();
+            pdu.encode(&mut encoded, 0x42).unwrap();
+
+            let decoded = match Pdu::decode(encoded.as_slice()).unwrap().pdu {
+                Pdu::GetLinesResponse(response) => response,
+                other => panic!("unexpected {other:?}"),
+            };
+            let (lines, _images) = decoded.lines.extract_data();
+            let cell = lines[0].1.get_cell(0).unwrap();
+            assert_eq!(cell.attrs().underline(), underline);
+            assert_eq!(cell.attrs().underline_color(), attrs.underline_color());
+        }
+    }
+
+    #[test]
+    fn test_bell_alert_round_trip() {
+        // The gui relies on the bell reaching it from the mux server
+        // in order to route it to the tab bar, notifications and lua
+        let pdu = Pdu::NotifyAlert(NotifyAlert {
+            pane_id: 3,
+            alert: Alert::Bell,
+        });
+        let mut encoded = Vec::new();
+        pdu.encode(&mut encoded, 0x42).unwrap();
+        assert_eq!(
+            DecodedPdu { pdu, serial: 0x42 },
+            Pdu::decode(encoded.as_slice()).unwrap()
+        );
+    }
+
     #[test]
     fn test_bogus_pdu() {
         let mut encoded = Vec::new();
@@ -274,6 +274,11 @@ impl ToDynamic for SrgbaTuple {
 }
 
 impl FromDynamic for SrgbaTuple {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        let _ = registry;
+        wezterm_dynamic::Schema::String
+    }
+
     fn from_dynamic(
         value: &Value,
         options: FromDynamicOptions,
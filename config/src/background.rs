@@ -26,6 +26,13 @@ impl std::ops::Deref for ImageFileSourceWrap {
 }
 
 impl FromDynamic for ImageFileSourceWrap {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        wezterm_dynamic::Schema::OneOf(vec![
+            wezterm_dynamic::Schema::String,
+            ImageFileSource::describe_schema(registry),
+        ])
+    }
+
     fn from_dynamic(
         value: &Value,
         options: FromDynamicOptions,
@@ -149,6 +156,10 @@ pub enum BackgroundSize {
 }
 
 impl FromDynamic for BackgroundSize {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        PixelUnit::describe_schema(registry)
+    }
+
     fn from_dynamic(
         value: &Value,
         options: FromDynamicOptions,
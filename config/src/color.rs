@@ -374,6 +374,11 @@ pub struct TabBarColors {
 
     #[dynamic(default)]
     pub inactive_tab_edge_hover: Option<RgbaColor>,
+
+    /// Styling for a tab that is highlighted because one of
+    /// its panes rang the bell
+    #[dynamic(default)]
+    pub bell_tab: Option<TabBarColor>,
 }
 
 impl TabBarColors {
@@ -407,6 +412,10 @@ impl TabBarColors {
             .unwrap_or_else(default_inactive_tab_hover)
     }
 
+    pub fn bell_tab(&self) -> TabBarColor {
+        self.bell_tab.clone().unwrap_or_else(default_bell_tab)
+    }
+
     pub fn inactive_tab_edge(&self) -> RgbaColor {
         self.inactive_tab_edge
             .unwrap_or_else(default_inactive_tab_edge)
@@ -436,6 +445,7 @@ impl TabBarColors {
             inactive_tab_edge_hover: overlay!(inactive_tab_edge_hover),
             new_tab: overlay!(new_tab),
             new_tab_hover: overlay!(new_tab_hover),
+            bell_tab: overlay!(bell_tab),
         }
     }
 }
@@ -496,6 +506,13 @@ fn default_inactive_tab_hover() -> TabBarColor {
         ..TabBarColor::default()
     }
 }
+fn default_bell_tab() -> TabBarColor {
+    TabBarColor {
+        bg_color: (0x8a, 0x5a, 0x00).into(),
+        fg_color: (0xf0, 0xf0, 0xf0).into(),
+        ..TabBarColor::default()
+    }
+}
 fn default_active_tab() -> TabBarColor {
     TabBarColor {
         bg_color: (0x00, 0x00, 0x00).into(),
@@ -690,7 +707,7 @@ pub struct ColorSchemeFile {
 }
 impl_lua_conversion_dynamic!(ColorSchemeFile);
 
-fn dynamic_to_toml(value: Value) -> anyhow::Result<toml::Value> {
+pub(crate) fn dynamic_to_toml(value: Value) -> anyhow::Result<toml::Value> {
     Ok(match value {
         Value::Null => anyhow::bail!("cannot map Null to toml"),
         Value::Bool(b) => toml::Value::Boolean(b),
@@ -698,7 +715,13 @@ fn dynamic_to_toml(value: Value) -> anyhow::Result<toml::Value> {
         Value::Array(a) => {
             let mut arr = vec![];
             for v in a {
-                arr.push(dynamic_to_toml(v)?);
+                arr.push(match v {
+                    // Unlike a null field of a table, a null element
+                    // can't be omitted without moving those that follow
+                    // it, so it is written as an empty table
+                    Value::Null => toml::Value::Table(toml::map::Map::new()),
+                    other => dynamic_to_toml(other)?,
+                });
             }
             toml::Value::Array(arr)
         }
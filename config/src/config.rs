@@ -13,13 +13,16 @@ use crate::frontend::FrontEndSelection;
 use crate::keyassignment::{
     KeyAssignment, KeyTable, KeyTableEntry, KeyTables, MouseEventTrigger, SpawnCommand,
 };
-use crate::keys::{Key, LeaderKey, Mouse};
+use crate::keys::{AutoKeyTable, Key, LeaderKey, Mouse};
 use crate::lua::make_lua_context;
-use crate::ssh::{SshBackend, SshDomain};
+use crate::presentation::PresentationModeConfig;
+use crate::ssh::{SshBackend, SshDomain, TerminfoAssist};
 use crate::tls::{TlsDomainClient, TlsDomainServer};
-use crate::units::Dimension;
+use crate::units::{Dimension, GeometryOrigin};
 use crate::unix::UnixDomain;
+use crate::workspace_style::WorkspaceStyle;
 use crate::wsl::WslDomain;
+use crate::zen::ZenModeConfig;
 use crate::{
     default_config_with_overrides_applied, default_one_point_oh, default_one_point_oh_f64,
     default_true, default_win32_acrylic_accent_color, GpuInfo, IntegratedTitleButtonColor,
@@ -78,6 +81,13 @@ pub struct Config {
     #[dynamic(default)]
     pub allow_square_glyphs_to_overflow_width: AllowSquareGlyphOverflow,
 
+    /// When set, Nerd Font icons are scaled by this factor relative
+    /// to their natural size, constrained to fit within their cells,
+    /// and vertically centered when they would otherwise poke out
+    /// of the cell
+    #[dynamic(default)]
+    pub nerd_font_scale: Option<f64>,
+
     #[dynamic(default)]
     pub window_decorations: WindowDecorations,
 
@@ -87,6 +97,11 @@ pub struct Config {
     #[dynamic(default)]
     pub log_unknown_escape_sequences: bool,
 
+    /// Whether the iTerm2 SetColors and RequestAttention
+    /// escape sequences are honored
+    #[dynamic(default = "default_true")]
+    pub accept_iterm2_osc: bool,
+
     #[dynamic(default)]
     pub integrated_title_button_alignment: IntegratedTitleButtonAlignment,
 
@@ -164,12 +179,43 @@ pub struct Config {
     #[dynamic(default = "default_pane_select_font_size")]
     pub pane_select_font_size: f64,
 
+    #[dynamic(default = "default_tab_overview_font_size")]
+    pub tab_overview_font_size: f64,
+
     #[dynamic(default = "default_pane_select_fg_color")]
     pub pane_select_fg_color: RgbaColor,
 
     #[dynamic(default = "default_pane_select_bg_color")]
     pub pane_select_bg_color: RgbaColor,
 
+    /// The color of the border drawn around panes that are
+    /// receiving broadcast input
+    #[dynamic(default = "default_broadcast_input_border_color")]
+    pub broadcast_input_border_color: RgbaColor,
+
+    /// Whether to ask before pasting into a pane whose input is
+    /// broadcast to other panes
+    #[dynamic(default = "default_true")]
+    pub broadcast_paste_confirmation: bool,
+
+    /// The default background color of persistent highlights
+    #[dynamic(default = "default_pane_highlight_color")]
+    pub pane_highlight_color: RgbaColor,
+
+    /// The default color of the markers of line annotations
+    #[dynamic(default = "default_pane_annotation_color")]
+    pub pane_annotation_color: RgbaColor,
+
+    /// Vertical guides drawn at the specified columns of each pane.
+    /// They are not drawn while the pane is using the alternate screen.
+    #[dynamic(default)]
+    pub column_guides: Vec<ColumnGuide>,
+
+    /// If true, ClearSelection also removes the persistent
+    /// highlights from the pane
+    #[dynamic(default)]
+    pub clear_highlights_with_selection: bool,
+
     #[dynamic(default)]
     pub tab_bar_style: TabBarStyle,
 
@@ -210,6 +256,13 @@ pub struct Config {
     /// info!)
     pub default_cwd: Option<PathBuf>,
 
+    /// The order in which the sources of the current working directory
+    /// of a pane are consulted.  An OSC 7 report that predates a change
+    /// of the foreground process is considered to be stale, and is
+    /// only used if none of the other sources have an answer.
+    #[dynamic(default = "default_cwd_source_priority")]
+    pub cwd_source_priority: Vec<CwdSource>,
+
     #[dynamic(default)]
     pub exit_behavior: ExitBehavior,
 
@@ -219,6 +272,32 @@ pub struct Config {
     #[dynamic(default = "default_clean_exits")]
     pub clean_exit_codes: Vec<u32>,
 
+    /// Policies for dealing with panes that have had no input or
+    /// output for a long time, keyed by domain name.
+    #[dynamic(default)]
+    pub idle_pane_policy: HashMap<String, IdlePanePolicy>,
+
+    /// When true, raise a `pane-output-activity` alert when output
+    /// arrives in a pane that doesn't have focus
+    #[dynamic(default)]
+    pub monitor_activity: bool,
+
+    /// The minimum number of milliseconds between successive
+    /// `pane-output-activity` alerts for the same pane
+    #[dynamic(default = "default_monitor_activity_interval")]
+    pub monitor_activity_interval: u64,
+
+    /// The number of seconds that a pane that was producing output
+    /// must be quiet for before raising a `pane-silence` alert.
+    /// 0 disables the alert.
+    #[dynamic(default)]
+    pub monitor_silence: u64,
+
+    /// Limits the rate at which output is consumed from a pane,
+    /// to keep runaway output from locking up rendering
+    #[dynamic(default)]
+    pub output_rate_limit: OutputRateLimit,
+
     #[dynamic(default = "default_true")]
     pub detect_password_input: bool,
 
@@ -256,6 +335,11 @@ pub struct Config {
     #[dynamic(default = "default_term")]
     pub term: String,
 
+    /// Whether to check that the terminfo entry for `term` is
+    /// installed on the remote host of ssh sessions
+    #[dynamic(default)]
+    pub terminfo_assist: TerminfoAssist,
+
     #[dynamic(default)]
     pub font_locator: FontLocatorSelection,
     #[dynamic(default)]
@@ -353,11 +437,26 @@ pub struct Config {
     #[dynamic(default)]
     pub ssh_backend: SshBackend,
 
+    /// Whether the commands of `Match exec` blocks in the ssh config
+    /// are run when resolving the options for a host
+    #[dynamic(default)]
+    pub ssh_config_allow_exec: bool,
+
     /// When running in server mode, defines configuration for
     /// each of the endpoints that we'll listen for connections
     #[dynamic(default)]
     pub tls_servers: Vec<TlsDomainServer>,
 
+    /// When running in server mode, serves a read-only JSON
+    /// description of the mux over HTTP
+    #[dynamic(default)]
+    pub mux_http_status: Option<MuxHttpStatus>,
+
+    /// Whether text copied from panes in a tmux control mode
+    /// session is shared with the remote tmux paste buffer
+    #[dynamic(default)]
+    pub sync_tmux_clipboard: TmuxClipboardSync,
+
     /// The set of tls domains that we can connect to as a client
     #[dynamic(default)]
     pub tls_clients: Vec<TlsDomainClient>,
@@ -393,11 +492,29 @@ pub struct Config {
     #[dynamic(default = "default_mux_env_remove")]
     pub mux_env_remove: Vec<String>,
 
+    /// The amount of memory, in megabytes, used to cache image
+    /// data that has been fetched from multiplexer servers
+    #[dynamic(default = "default_mux_image_cache_memory_mb")]
+    pub mux_image_cache_memory_mb: usize,
+
     #[dynamic(default)]
     pub keys: Vec<Key>,
     #[dynamic(default)]
     pub key_tables: HashMap<String, Vec<Key>>,
 
+    /// Key tables that are activated while the active pane matches
+    /// their condition, beneath any explicitly activated tables
+    #[dynamic(default)]
+    pub auto_key_tables: Vec<AutoKeyTable>,
+
+    /// Keys that are registered with the system so that they
+    /// perform their action even when wezterm doesn't have focus
+    #[dynamic(default, validate = "validate_global_keys")]
+    pub global_keys: Vec<Key>,
+
+    #[dynamic(default)]
+    pub drop_down_window: DropDownWindowConfig,
+
     #[dynamic(default = "default_bypass_mouse_reporting_modifiers")]
     pub bypass_mouse_reporting_modifiers: Modifiers,
 
@@ -407,6 +524,11 @@ pub struct Config {
     #[dynamic(default)]
     pub normalize_output_to_unicode_nfc: bool,
 
+    /// The initial state of DEC private mode 2027, which controls
+    /// whether the width of text is measured by grapheme cluster
+    #[dynamic(default = "default_true")]
+    pub grapheme_clustering: bool,
+
     #[dynamic(default)]
     pub disable_default_key_bindings: bool,
     pub leader: Option<LeaderKey>,
@@ -456,6 +578,16 @@ pub struct Config {
     #[dynamic(default)]
     pub tab_bar_at_bottom: bool,
 
+    /// Which edge of the window the tab bar is placed along.
+    /// When not set, tab_bar_at_bottom selects between Top and Bottom.
+    #[dynamic(default)]
+    pub tab_bar_position: Option<TabBarPosition>,
+
+    /// The width, measured in cells, of the tab bar when
+    /// tab_bar_position is Left or Right.
+    #[dynamic(default = "default_vertical_tab_bar_width")]
+    pub vertical_tab_bar_width: usize,
+
     #[dynamic(default = "default_true")]
     pub mouse_wheel_scrolls_tabs: bool,
 
@@ -482,6 +614,17 @@ pub struct Config {
     #[dynamic(default = "default_tab_max_width")]
     pub tab_max_width: usize,
 
+    /// Specifies the minimum width that a tab can have in the
+    /// tab bar when tab_bar_overflow is set to Scroll or Wrap.
+    /// Defaults to 8 glyphs in width.
+    #[dynamic(default = "default_tab_min_width")]
+    pub tab_min_width: usize,
+
+    /// Controls what happens when there are more tabs than
+    /// can fit in the tab bar at their preferred width.
+    #[dynamic(default)]
+    pub tab_bar_overflow: TabBarOverflow,
+
     /// If true, hide the tab bar if the window only has a single tab.
     #[dynamic(default)]
     pub hide_tab_bar_if_only_one_tab: bool,
@@ -534,6 +677,17 @@ pub struct Config {
     #[dynamic(default)]
     pub foreground_text_hsb: HsbTransform,
 
+    /// When set, the foreground color of text is nudged toward white
+    /// or black as needed so that it has at least this WCAG contrast
+    /// ratio against its background
+    #[dynamic(default)]
+    pub minimum_contrast_ratio: Option<f32>,
+
+    /// When true, text drawn in the default foreground color on the
+    /// default background color is exempt from minimum_contrast_ratio
+    #[dynamic(default)]
+    pub minimum_contrast_ignores_default_colors: bool,
+
     #[dynamic(default)]
     pub background: Vec<BackgroundLayer>,
 
@@ -593,6 +747,15 @@ pub struct Config {
     #[dynamic(default = "default_inactive_pane_hsb")]
     pub inactive_pane_hsb: HsbTransform,
 
+    /// Whether each pane is drawn with a title bar above it
+    #[dynamic(default)]
+    pub pane_decorations: PaneDecorations,
+
+    /// The strftime format used for the arrival times shown in the
+    /// gutter enabled by ToggleLineTimestamps
+    #[dynamic(default = "default_line_timestamp_format")]
+    pub line_timestamp_format: String,
+
     #[dynamic(default = "default_one_point_oh")]
     pub text_background_opacity: f32,
 
@@ -680,12 +843,35 @@ pub struct Config {
     #[dynamic(default)]
     pub notification_handling: NotificationHandling,
 
+    /// Whether the buttons of OSC 777 notifications are shown, and
+    /// clicking one of them is reported back to the pane
+    #[dynamic(default)]
+    pub notification_replies: bool,
+
+    /// The adjustments applied by TogglePresentationMode
+    #[dynamic(default)]
+    pub presentation_mode: PresentationModeConfig,
+
+    /// The chrome hidden by ToggleZenMode
+    #[dynamic(default)]
+    pub zen_mode: ZenModeConfig,
+
     #[dynamic(default = "default_true")]
     pub use_dead_keys: bool,
 
     #[dynamic(default)]
     pub launch_menu: Vec<SpawnCommand>,
 
+    /// Named sets of configuration overrides that are selected by
+    /// the `profile` of a SpawnCommand
+    #[dynamic(default)]
+    pub profiles: HashMap<String, wezterm_dynamic::Value>,
+
+    /// Styling that is applied to the windows whose workspace
+    /// has the same name as the key
+    #[dynamic(default)]
+    pub workspace_styles: HashMap<String, WorkspaceStyle>,
+
     #[dynamic(default)]
     pub use_box_model_render: bool,
 
@@ -702,8 +888,8 @@ pub struct Config {
     )]
     pub show_update_window: bool,
 
-    #[dynamic(default = "default_update_interval")]
-    pub check_for_updates_interval_seconds: u64,
+    #[dynamic(default)]
+    pub check_for_updates_interval_seconds: UpdateCheckInterval,
 
     /// When set to true, use the CSI-U encoding scheme as described
     /// in http://www.leonerd.org.uk/hacks/fixterms/
@@ -721,6 +907,12 @@ pub struct Config {
     #[dynamic(default = "default_word_boundary")]
     pub selection_word_boundary: String,
 
+    /// The word boundaries used by successive double clicks on the
+    /// same spot, from the narrowest to the widest.
+    /// See `Config::word_boundary_levels` for the default.
+    #[dynamic(default)]
+    pub selection_word_boundary_levels: Option<Vec<String>>,
+
     #[dynamic(default = "default_enq_answerback")]
     pub enq_answerback: String,
 
@@ -736,12 +928,36 @@ pub struct Config {
     #[dynamic(default = "default_alternate_buffer_wheel_scroll_speed")]
     pub alternate_buffer_wheel_scroll_speed: u8,
 
+    /// Multiplies the distance scrolled by touchpads and touchscreens
+    #[dynamic(default = "default_one_point_oh_f64")]
+    pub scroll_speed: f64,
+
+    /// How much faster scrolling becomes as touchpad and touchscreen
+    /// gestures become faster. 0.0 scrolls at a constant speed.
+    #[dynamic(default)]
+    pub scroll_acceleration: f64,
+
+    /// Whether to keep scrolling, slowing down gradually, after
+    /// a touchpad or touchscreen fling
+    #[dynamic(default = "default_true")]
+    pub kinetic_scrolling: bool,
+
     #[dynamic(default = "default_status_update_interval")]
     pub status_update_interval: u64,
 
+    /// Whether to show the latency of the connection to the remote
+    /// mux of the active pane at the end of the right status
+    #[dynamic(default)]
+    pub show_mux_latency: bool,
+
     #[dynamic(default)]
     pub experimental_pixel_positioning: bool,
 
+    /// Round the position of each glyph, cursor and underline to
+    /// whole device pixels, so that screen recordings scale cleanly
+    #[dynamic(default)]
+    pub snap_glyphs_to_pixel_grid: bool,
+
     #[dynamic(default)]
     pub ignore_svg_fonts: bool,
 
@@ -757,6 +973,11 @@ pub struct Config {
     #[dynamic(default = "default_true")]
     pub quit_when_all_windows_are_closed: bool,
 
+    /// When true, the layout of the windows, tabs and panes is saved
+    /// when the gui exits, so that it can be restored later
+    #[dynamic(default)]
+    pub save_session_on_exit: bool,
+
     #[dynamic(default = "default_true")]
     pub warn_about_missing_glyphs: bool,
 
@@ -778,17 +999,55 @@ pub struct Config {
     #[dynamic(default)]
     pub pane_focus_follows_mouse: bool,
 
+    #[dynamic(default)]
+    pub pane_focus_follows_mouse_delay_ms: u64,
+
     #[dynamic(default = "default_true")]
     pub unzoom_on_switch_pane: bool,
 
+    /// When the application in the active pane has set the WEZTERM_NAV
+    /// user var, ActivatePaneDirection and AdjustPaneSize first pass
+    /// the key that triggered them to the application, and only act
+    /// on the wezterm panes if it replies that it is at its edge
+    #[dynamic(default = "default_true")]
+    pub smart_pane_navigation: bool,
+
+    /// How long to wait for the application to reply, in milliseconds
+    #[dynamic(default = "default_smart_pane_navigation_timeout_ms")]
+    pub smart_pane_navigation_timeout_ms: u64,
+
+    /// How long a pane or tab that the user closed can be reopened
+    /// by ReopenClosedPane or ReopenClosedTab.  Setting this to 0
+    /// disables keeping closed panes, including their scrollback.
+    #[dynamic(default = "default_closed_pane_retention_seconds")]
+    pub closed_pane_retention_seconds: u64,
+
+    /// The number of closed panes and tabs kept for each window
+    #[dynamic(default = "default_closed_pane_retention_max_entries")]
+    pub closed_pane_retention_max_entries: usize,
+
+    /// The approximate number of bytes of scrollback that are kept
+    /// for all of the closed panes and tabs together
+    #[dynamic(default = "default_closed_pane_retention_max_bytes")]
+    pub closed_pane_retention_max_bytes: usize,
+
     #[dynamic(default = "default_max_fps")]
     pub max_fps: u8,
 
+    /// When set, output that arrives within this many milliseconds
+    /// of a key press is painted immediately rather than waiting
+    /// for the next frame
+    #[dynamic(default)]
+    pub max_input_latency: Option<u64>,
+
     #[dynamic(default = "default_shape_cache_size")]
     pub shape_cache_size: usize,
     #[dynamic(default = "default_line_state_cache_size")]
     pub line_state_cache_size: usize,
-    #[dynamic(default = "default_line_quad_cache_size")]
+    #[dynamic(
+        default = "default_line_quad_cache_size",
+        deprecated = "this option no longer does anything and will be removed in a future release"
+    )]
     pub line_quad_cache_size: usize,
     #[dynamic(default = "default_line_to_ele_shape_cache_size")]
     pub line_to_ele_shape_cache_size: usize,
@@ -801,9 +1060,33 @@ pub struct Config {
     #[dynamic(default)]
     pub audible_bell: AudibleBell,
 
+    /// How long a tab is highlighted in the tab bar after one of
+    /// its panes rings the bell. 0 disables the highlight.
+    #[dynamic(default)]
+    pub tab_bell_flash_duration_ms: u64,
+
+    /// Whether a bell should post a desktop notification
+    #[dynamic(default = "default_bell_notification_handling")]
+    pub bell_notification_handling: NotificationHandling,
+
     #[dynamic(default)]
     pub canonicalize_pasted_newlines: Option<NewlineCanon>,
 
+    /// When copying the selection, remove trailing whitespace
+    /// from each line
+    #[dynamic(default = "default_true")]
+    pub copy_trim_trailing_whitespace: bool,
+
+    /// When copying the selection, join lines that were wrapped
+    /// because they were too long for the terminal width
+    #[dynamic(default = "default_true")]
+    pub copy_join_wrapped_lines: bool,
+
+    /// When copying the selection, re-wrap lines that are wider
+    /// than this many columns.  0 disables re-wrapping.
+    #[dynamic(default)]
+    pub copy_rewrap_column: usize,
+
     #[dynamic(default = "default_unicode_version")]
     pub unicode_version: u8,
 
@@ -837,6 +1120,10 @@ pub struct Config {
     #[dynamic(default)]
     pub quote_dropped_files: DroppedFileQuoting,
 
+    /// What happens when files are dropped onto a pane
+    #[dynamic(default)]
+    pub drop_behavior: DropBehavior,
+
     #[dynamic(default)]
     pub ui_key_cap_rendering: UIKeyCapRendering,
 
@@ -900,6 +1187,30 @@ impl Config {
         }
     }
 
+    /// Returns the word boundaries used by successive double clicks.
+    /// Unless configured otherwise, the first level also stops at the
+    /// punctuation found in paths and urls, and the second level uses
+    /// `selection_word_boundary`, so that it selects the whole path.
+    pub fn word_boundary_levels(&self) -> Vec<String> {
+        match &self.selection_word_boundary_levels {
+            Some(levels) if !levels.is_empty() => levels.clone(),
+            _ => vec![
+                format!("{}{}", self.selection_word_boundary, PATH_PUNCTUATION),
+                self.selection_word_boundary.clone(),
+            ],
+        }
+    }
+
+    /// Returns the edge of the window along which the tab bar is placed,
+    /// taking the legacy tab_bar_at_bottom option into account
+    pub fn effective_tab_bar_position(&self) -> TabBarPosition {
+        match self.tab_bar_position {
+            Some(position) => position,
+            None if self.tab_bar_at_bottom => TabBarPosition::Bottom,
+            None => TabBarPosition::Top,
+        }
+    }
+
     pub fn update_ulimit(&self) -> anyhow::Result<()> {
         #[cfg(unix)]
         {
@@ -1182,6 +1493,41 @@ impl Config {
         Ok(())
     }
 
+    /// Returns the style of the workspace named `workspace`, if any
+    pub fn workspace_style(&self, workspace: &str) -> Option<&WorkspaceStyle> {
+        self.workspace_styles.get(workspace)
+    }
+
+    /// Returns the overrides of the profile named `name`
+    pub fn profile(&self, name: &str) -> anyhow::Result<&wezterm_dynamic::Value> {
+        self.profiles.get(name).ok_or_else(|| {
+            let mut names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            names.sort();
+            if names.is_empty() {
+                anyhow::anyhow!("unknown profile `{name}`: no profiles are defined in the config")
+            } else {
+                anyhow::anyhow!(
+                    "unknown profile `{name}`: the defined profiles are {}",
+                    names.join(", ")
+                )
+            }
+        })
+    }
+
+    /// Returns the `default_domain` that the profile named `name`
+    /// sets, if it sets one
+    pub fn profile_default_domain(&self, name: &str) -> Option<String> {
+        match self.profiles.get(name)? {
+            wezterm_dynamic::Value::Object(overrides) => {
+                match overrides.get_by_str("default_domain") {
+                    Some(wezterm_dynamic::Value::String(domain)) => Some(domain.clone()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
     fn check_domain_consistency(&self) -> anyhow::Result<()> {
         let mut domains = HashMap::new();
 
@@ -1567,6 +1913,22 @@ fn default_pane_select_bg_color() -> RgbaColor {
     SrgbaTuple(0., 0., 0., 0.5).into()
 }
 
+fn default_broadcast_input_border_color() -> RgbaColor {
+    SrgbaTuple(0.8, 0.2, 0.2, 1.0).into()
+}
+
+fn default_pane_highlight_color() -> RgbaColor {
+    SrgbaTuple(1.0, 0.8, 0.0, 0.4).into()
+}
+
+fn default_pane_annotation_color() -> RgbaColor {
+    SrgbaTuple(1.0, 0.5, 0.0, 1.0).into()
+}
+
+fn default_column_guide_color() -> RgbaColor {
+    SrgbaTuple(0.5, 0.5, 0.5, 0.3).into()
+}
+
 fn default_pane_select_font_size() -> f64 {
     36.0
 }
@@ -1580,6 +1942,10 @@ fn default_char_select_font_size() -> f64 {
     18.0
 }
 
+fn default_tab_overview_font_size() -> f64 {
+    4.0
+}
+
 fn default_char_select_fg_color() -> RgbaColor {
     SrgbaTuple(0.75, 0.75, 0.75, 1.0).into()
 }
@@ -1612,6 +1978,10 @@ fn default_mux_output_parser_buffer_size() -> usize {
     128 * 1024
 }
 
+fn default_mux_image_cache_memory_mb() -> usize {
+    256
+}
+
 fn default_ratelimit_line_prefetches_per_second() -> u32 {
     50
 }
@@ -1712,6 +2082,10 @@ fn default_bypass_mouse_reporting_modifiers() -> Modifiers {
     Modifiers::SHIFT
 }
 
+fn default_cwd_source_priority() -> Vec<CwdSource> {
+    vec![CwdSource::Osc7, CwdSource::Process]
+}
+
 fn default_gui_startup_args() -> Vec<String> {
     vec!["start".to_string()]
 }
@@ -1769,6 +2143,10 @@ fn default_stateless_process_list() -> Vec<String> {
     .collect()
 }
 
+fn default_monitor_activity_interval() -> u64 {
+    5_000
+}
+
 fn default_status_update_interval() -> u64 {
     1_000
 }
@@ -1777,6 +2155,10 @@ fn default_alternate_buffer_wheel_scroll_speed() -> u8 {
     3
 }
 
+fn default_line_timestamp_format() -> String {
+    "%H:%M:%S".to_string()
+}
+
 fn default_alphabet() -> String {
     "asdfqwerzxcvjklmiuopghtybn".to_string()
 }
@@ -1785,6 +2167,9 @@ fn default_word_boundary() -> String {
     " \t\n{[}]()\"'`".to_string()
 }
 
+/// The characters that separate the words of a path or url
+const PATH_PUNCTUATION: &str = "/\\.-~:;,=@?&#<>|";
+
 fn default_enq_answerback() -> String {
     "".to_string()
 }
@@ -1793,6 +2178,14 @@ fn default_tab_max_width() -> usize {
     16
 }
 
+fn default_vertical_tab_bar_width() -> usize {
+    24
+}
+
+fn default_tab_min_width() -> usize {
+    8
+}
+
 fn default_update_interval() -> u64 {
     86400
 }
@@ -1933,6 +2326,169 @@ pub enum ExitBehaviorMessaging {
     None,
 }
 
+/// What to do with a pane that has been idle for longer than
+/// its `IdlePanePolicy` allows
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq, Default)]
+#[dynamic(try_from = "String", into = "String")]
+pub enum IdlePaneAction {
+    /// Emit the `idle-pane` event, or show a notification if
+    /// there is no handler for that event
+    #[default]
+    Notify,
+    /// Kill the pane
+    Close,
+}
+
+impl From<&IdlePaneAction> for String {
+    fn from(action: &IdlePaneAction) -> String {
+        match action {
+            IdlePaneAction::Notify => "notify",
+            IdlePaneAction::Close => "close",
+        }
+        .to_string()
+    }
+}
+
+impl TryFrom<String> for IdlePaneAction {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value.eq_ignore_ascii_case("notify") {
+            Ok(Self::Notify)
+        } else if value.eq_ignore_ascii_case("close") {
+            Ok(Self::Close)
+        } else {
+            Err(format!(
+                "invalid idle pane action `{value}`; expected `notify` or `close`"
+            ))
+        }
+    }
+}
+
+#[derive(Debug, FromDynamic, ToDynamic, Clone, PartialEq)]
+pub struct IdlePanePolicy {
+    /// How long the pane must have been without input or output
+    /// before it is considered to be idle
+    pub after_hours: f64,
+    #[dynamic(default)]
+    pub action: IdlePaneAction,
+    /// Only consider the pane to be idle if its foreground process
+    /// is a shell, rather than eg: an editor or a long running job
+    #[dynamic(default)]
+    pub only_if_shell: bool,
+}
+
+impl IdlePanePolicy {
+    pub fn idle_duration(&self) -> Duration {
+        Duration::try_from_secs_f64((self.after_hours * 3600.).max(0.)).unwrap_or(Duration::MAX)
+    }
+}
+
+/// What to do when a pane produces output faster than
+/// `output_rate_limit` allows
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputRateLimitAction {
+    /// Stop reading from the pty until the rate falls within budget,
+    /// which applies backpressure to the process via the kernel buffer
+    #[default]
+    Throttle,
+    /// Keep reading at full speed, but only apply the output to the
+    /// terminal model in snapshots at the `max_fps` rate
+    DropRender,
+}
+
+/// Configures the read-only HTTP status endpoint of the mux server
+#[derive(Debug, FromDynamic, ToDynamic, Clone, PartialEq)]
+pub struct MuxHttpStatus {
+    /// The address to listen on, such as "127.0.0.1:8377"
+    pub bind: String,
+    /// A file holding the token that requests must present
+    /// in their `Authorization: Bearer` header
+    pub auth_token_file: PathBuf,
+}
+
+/// Configures the window shown by `ToggleDropDownWindow`
+#[derive(Debug, FromDynamic, ToDynamic, Clone, PartialEq)]
+pub struct DropDownWindowConfig {
+    /// The height of the window, as a fraction of the height of the screen
+    #[dynamic(default = "default_drop_down_height")]
+    pub height: f32,
+    /// Which screen the window drops down from
+    #[dynamic(default = "default_drop_down_screen")]
+    pub screen: GeometryOrigin,
+    /// How long the window takes to slide into or out of view;
+    /// 0 shows and hides it immediately
+    #[dynamic(default = "default_drop_down_animation_duration")]
+    pub animation_duration_ms: u64,
+    /// Whether the window is hidden when it loses focus
+    #[dynamic(default = "default_true")]
+    pub hide_on_focus_loss: bool,
+    /// The workspace that holds the tabs of the window, so that
+    /// they are kept apart from those of the normal windows
+    #[dynamic(default = "default_drop_down_workspace")]
+    pub workspace: String,
+}
+
+impl Default for DropDownWindowConfig {
+    fn default() -> Self {
+        Self {
+            height: default_drop_down_height(),
+            screen: default_drop_down_screen(),
+            animation_duration_ms: default_drop_down_animation_duration(),
+            hide_on_focus_loss: true,
+            workspace: default_drop_down_workspace(),
+        }
+    }
+}
+
+fn default_drop_down_height() -> f32 {
+    0.4
+}
+
+fn default_drop_down_screen() -> GeometryOrigin {
+    GeometryOrigin::ActiveScreen
+}
+
+fn default_drop_down_animation_duration() -> u64 {
+    150
+}
+
+fn default_drop_down_workspace() -> String {
+    "dropdown".to_string()
+}
+
+/// A vertical line drawn at the left edge of a column
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnGuide {
+    /// The number of columns to the left of the guide
+    pub column: usize,
+    #[dynamic(default = "default_column_guide_color")]
+    pub color: RgbaColor,
+}
+impl_lua_conversion_dynamic!(ColumnGuide);
+
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
+pub struct OutputRateLimit {
+    /// The budget; 0 disables the limit
+    #[dynamic(default = "default_output_rate_limit_bytes_per_second")]
+    pub bytes_per_second: u64,
+    #[dynamic(default)]
+    pub action: OutputRateLimitAction,
+}
+
+impl Default for OutputRateLimit {
+    fn default() -> Self {
+        Self {
+            bytes_per_second: default_output_rate_limit_bytes_per_second(),
+            action: OutputRateLimitAction::default(),
+        }
+    }
+}
+
+fn default_output_rate_limit_bytes_per_second() -> u64 {
+    16_000_000
+}
+
 #[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
 pub enum DroppedFileQuoting {
     /// No quoting is performed, the file name is passed through as-is
@@ -1945,6 +2501,11 @@ pub enum DroppedFileQuoting {
     Windows,
     /// Always double quote the file name
     WindowsAlwaysQuoted,
+    /// Single quote the file name, doubling any quotes within it,
+    /// as PowerShell expects
+    PowerShell,
+    /// Pick the quoting that suits the shell that is running in the pane
+    Auto,
 }
 
 impl Default for DroppedFileQuoting {
@@ -1958,8 +2519,37 @@ impl Default for DroppedFileQuoting {
 }
 
 impl DroppedFileQuoting {
+    /// Resolves `Auto` to the quoting that suits the program running
+    /// in the pane, given the name or path of its executable
+    pub fn for_process(self, process_name: Option<&str>) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+        let name = process_name.map(|path| {
+            let name = path
+                .rsplit(|c| c == '/' || c == '\\')
+                .next()
+                .unwrap_or(path);
+            let name = name.to_ascii_lowercase();
+            match name.strip_suffix(".exe") {
+                Some(name) => name.to_string(),
+                None => name,
+            }
+        });
+        match name.as_deref() {
+            Some("pwsh" | "powershell") => Self::PowerShell,
+            Some("cmd") => Self::Windows,
+            Some("bash" | "zsh" | "fish" | "sh" | "dash" | "ksh" | "nu" | "xonsh" | "wsl") => {
+                Self::Posix
+            }
+            _ if cfg!(windows) => Self::Windows,
+            _ => Self::Posix,
+        }
+    }
+
     pub fn escape(self, s: &str) -> String {
         match self {
+            Self::Auto => self.for_process(None).escape(s),
             Self::None => s.to_string(),
             Self::SpacesOnly => s.replace(" ", "\\ "),
             // https://docs.rs/shlex/latest/shlex/fn.quote.html
@@ -1975,10 +2565,62 @@ impl DroppedFileQuoting {
                 }
             }
             Self::WindowsAlwaysQuoted => format!("\"{}\"", s),
+            Self::PowerShell => {
+                let is_plain = !s.is_empty()
+                    && s.chars().all(|c| {
+                        c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | '\\' | ':')
+                    });
+                if is_plain {
+                    s.to_string()
+                } else {
+                    // PowerShell also treats the typographic single
+                    // quotes as quotes, so those are doubled too
+                    let mut quoted = String::with_capacity(s.len() + 2);
+                    quoted.push('\'');
+                    for c in s.chars() {
+                        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}') {
+                            quoted.push(c);
+                        }
+                        quoted.push(c);
+                    }
+                    quoted.push('\'');
+                    quoted
+                }
+            }
         }
     }
 }
 
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropBehavior {
+    /// Paste the quoted paths, separated by spaces
+    #[default]
+    PastePaths,
+    /// Paste a `cd` command that changes to the directory
+    /// that holds the first of the files
+    ChangeDirectory,
+    /// For panes in an ssh domain, offer to upload the files to the
+    /// working directory of the pane and paste their remote paths.
+    /// Other panes paste the local paths.
+    Upload,
+}
+
+fn default_smart_pane_navigation_timeout_ms() -> u64 {
+    100
+}
+
+fn default_closed_pane_retention_seconds() -> u64 {
+    300
+}
+
+fn default_closed_pane_retention_max_entries() -> usize {
+    10
+}
+
+fn default_closed_pane_retention_max_bytes() -> usize {
+    16 * 1024 * 1024
+}
+
 fn default_glyph_cache_image_cache_size() -> usize {
     256
 }
@@ -1991,6 +2633,10 @@ fn default_line_state_cache_size() -> usize {
     1024
 }
 
+fn default_bell_notification_handling() -> NotificationHandling {
+    NotificationHandling::NeverShow
+}
+
 fn default_line_quad_cache_size() -> usize {
     1024
 }
@@ -2011,6 +2657,14 @@ pub enum BoldBrightening {
 }
 
 impl FromDynamic for BoldBrightening {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        let _ = registry;
+        wezterm_dynamic::Schema::OneOf(vec![
+            wezterm_dynamic::Schema::String,
+            wezterm_dynamic::Schema::Bool,
+        ])
+    }
+
     fn from_dynamic(
         value: &wezterm_dynamic::Value,
         options: wezterm_dynamic::FromDynamicOptions,
@@ -2033,6 +2687,156 @@ impl FromDynamic for BoldBrightening {
     }
 }
 
+/// Which stream of builds a version belongs to, and so which
+/// release the update checker compares it against
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateChannel {
+    Release,
+    Nightly,
+}
+
+impl UpdateChannel {
+    /// Release versions are the name of their tag, which has the form
+    /// `YYYYMMDD-HHMMSS-hash`.  Nightly builds have versions of the same
+    /// form, so `tagged` says whether the build was made from a release
+    /// tag.  Anything else, such as a version that a package has marked
+    /// as `nightly`, or one with a suffix added by a local build, follows
+    /// the nightly builds.
+    pub fn detect(version: &str, tagged: bool) -> Self {
+        if !tagged {
+            return Self::Nightly;
+        }
+
+        fn digits(s: &str, len: usize) -> bool {
+            s.len() == len && s.bytes().all(|b| b.is_ascii_digit())
+        }
+
+        let mut fields = version.split('-');
+        match (fields.next(), fields.next(), fields.next(), fields.next()) {
+            (Some(date), Some(time), Some(hash), None)
+                if digits(date, 8)
+                    && digits(time, 6)
+                    && !hash.is_empty()
+                    && hash.bytes().all(|b| b.is_ascii_hexdigit()) =>
+            {
+                Self::Release
+            }
+            _ => Self::Nightly,
+        }
+    }
+}
+
+/// How often to check for updates, which can be different for
+/// each channel.  This may be configured either as a number of
+/// seconds that applies to both channels, or as a table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateCheckInterval {
+    pub release: u64,
+    pub nightly: u64,
+}
+
+impl Default for UpdateCheckInterval {
+    fn default() -> Self {
+        Self {
+            release: default_update_interval(),
+            nightly: default_update_interval(),
+        }
+    }
+}
+
+impl UpdateCheckInterval {
+    pub fn for_channel(&self, channel: UpdateChannel) -> Duration {
+        Duration::from_secs(match channel {
+            UpdateChannel::Release => self.release,
+            UpdateChannel::Nightly => self.nightly,
+        })
+    }
+}
+
+#[derive(FromDynamic, ToDynamic)]
+struct UpdateCheckIntervalTable {
+    #[dynamic(default = "default_update_interval")]
+    release: u64,
+    #[dynamic(default = "default_update_interval")]
+    nightly: u64,
+}
+
+impl ToDynamic for UpdateCheckInterval {
+    /// Uses the plain number of seconds when it is the same for both
+    /// channels, so that configs that read the value keep working
+    fn to_dynamic(&self) -> wezterm_dynamic::Value {
+        if self.release == self.nightly {
+            self.release.to_dynamic()
+        } else {
+            UpdateCheckIntervalTable {
+                release: self.release,
+                nightly: self.nightly,
+            }
+            .to_dynamic()
+        }
+    }
+}
+
+impl FromDynamic for UpdateCheckInterval {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        wezterm_dynamic::Schema::OneOf(vec![
+            wezterm_dynamic::Schema::Integer,
+            UpdateCheckIntervalTable::describe_schema(registry),
+        ])
+    }
+
+    fn from_dynamic(
+        value: &wezterm_dynamic::Value,
+        options: wezterm_dynamic::FromDynamicOptions,
+    ) -> Result<Self, wezterm_dynamic::Error> {
+        match value {
+            wezterm_dynamic::Value::Object(_) => {
+                let table = UpdateCheckIntervalTable::from_dynamic(value, options)?;
+                Ok(Self {
+                    release: table.release,
+                    nightly: table.nightly,
+                })
+            }
+            _ => {
+                let seconds = u64::from_dynamic(value, options)?;
+                Ok(Self {
+                    release: seconds,
+                    nightly: seconds,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TabBarOverflow {
+    /// Reduce the width of the tabs so that they all fit
+    #[default]
+    Shrink,
+    /// Keep tabs at least tab_min_width wide and allow
+    /// scrolling the tabs horizontally
+    Scroll,
+    /// Keep tabs at least tab_min_width wide and grow
+    /// the tab bar to use as many rows as are needed
+    Wrap,
+}
+
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
+pub enum TabBarPosition {
+    Top,
+    Bottom,
+    /// A vertical strip along the left edge of the window
+    Left,
+    /// A vertical strip along the right edge of the window
+    Right,
+}
+
+impl TabBarPosition {
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Self::Left | Self::Right)
+    }
+}
+
 #[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq, Default)]
 pub enum ImePreeditRendering {
     /// IME preedit is rendered by WezTerm itself
@@ -2052,6 +2856,48 @@ pub enum NotificationHandling {
     SuppressFromFocusedWindow,
 }
 
+/// Where the current working directory of a pane comes from
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq)]
+pub enum CwdSource {
+    /// Reported by the shell integration using OSC 7
+    Osc7,
+    /// Queried from the foreground process of the pane, where the
+    /// operating system makes that possible
+    Process,
+}
+
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaneDecorations {
+    #[default]
+    None,
+    /// A one cell high strip above each pane shows its title,
+    /// along with buttons to close or zoom the pane
+    TitleBar,
+}
+
+impl PaneDecorations {
+    /// Returns the number of rows at the top of each pane that are
+    /// occupied by the decorations
+    pub fn rows(self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::TitleBar => 1,
+        }
+    }
+}
+
+#[derive(Debug, FromDynamic, ToDynamic, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TmuxClipboardSync {
+    /// The tmux paste buffer and the local clipboard are independent
+    #[default]
+    Disabled,
+    /// Text copied in wezterm is also stored in the tmux paste buffer
+    ToTmux,
+    /// As ToTmux, and changes to the tmux paste buffer are also
+    /// placed on the local clipboard
+    Bidirectional,
+}
+
 fn validate_row_or_col(value: &u16) -> Result<(), String> {
     if *value < 1 {
         Err("initial_cols and initial_rows must be non-zero".to_string())
@@ -2070,6 +2916,14 @@ fn validate_line_height(value: &f64) -> Result<(), String> {
     }
 }
 
+fn validate_global_keys(keys: &[Key]) -> Result<(), String> {
+    if !keys.is_empty() && !cfg!(all(unix, not(target_os = "macos"))) {
+        Err("global_keys are only supported on X11".to_string())
+    } else {
+        Ok(())
+    }
+}
+
 pub(crate) fn validate_domain_name(name: &str) -> Result<(), String> {
     if name == "local" {
         Err(format!(
@@ -2092,3 +2946,199 @@ fn default_macos_forward_mods() -> Modifiers {
 fn default_colr_rasterizer() -> FontRasterizerSelection {
     FontRasterizerSelection::Harfbuzz
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn update_check_interval() {
+        fn parse(value: wezterm_dynamic::Value) -> UpdateCheckInterval {
+            UpdateCheckInterval::from_dynamic(&value, Default::default()).unwrap()
+        }
+        let day = default_update_interval();
+
+        let seconds = parse(wezterm_dynamic::Value::I64(3600));
+        assert_eq!(
+            seconds,
+            UpdateCheckInterval {
+                release: 3600,
+                nightly: 3600
+            }
+        );
+        assert_eq!(seconds.to_dynamic(), wezterm_dynamic::Value::U64(3600));
+        assert_eq!(parse(seconds.to_dynamic()), seconds);
+
+        let table = parse(wezterm_dynamic::Value::Object(
+            [(
+                wezterm_dynamic::Value::String("nightly".to_string()),
+                wezterm_dynamic::Value::I64(3600),
+            )]
+            .into_iter()
+            .collect(),
+        ));
+        assert_eq!(
+            table,
+            UpdateCheckInterval {
+                release: day,
+                nightly: 3600
+            }
+        );
+        assert_eq!(parse(table.to_dynamic()), table);
+        assert_eq!(
+            table.for_channel(UpdateChannel::Nightly),
+            Duration::from_secs(3600)
+        );
+        assert_eq!(
+            table.for_channel(UpdateChannel::Release),
+            Duration::from_secs(day)
+        );
+
+        assert!(UpdateCheckInterval::from_dynamic(
+            &wezterm_dynamic::Value::String("daily".to_string()),
+            Default::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn dropped_file_quoting() {
+        let name = "it's a \"file\".txt";
+        assert_eq!(
+            shlex::split(&DroppedFileQuoting::Posix.escape(name)),
+            Some(vec![name.to_string()])
+        );
+        assert_eq!(
+            DroppedFileQuoting::PowerShell.escape(name),
+            "'it''s a \"file\".txt'"
+        );
+        assert_eq!(
+            DroppedFileQuoting::PowerShell.escape(r"C:\Users\me\notes.txt"),
+            r"C:\Users\me\notes.txt"
+        );
+
+        let auto = DroppedFileQuoting::Auto;
+        assert_eq!(
+            auto.for_process(Some(r"C:\Program Files\PowerShell\7\pwsh.exe")),
+            DroppedFileQuoting::PowerShell
+        );
+        assert_eq!(
+            auto.for_process(Some("/usr/bin/zsh")),
+            DroppedFileQuoting::Posix
+        );
+        assert_eq!(
+            DroppedFileQuoting::Windows.for_process(Some("/usr/bin/zsh")),
+            DroppedFileQuoting::Windows
+        );
+
+        // Auto is opt-in
+        assert_eq!(
+            DroppedFileQuoting::default(),
+            if cfg!(windows) {
+                DroppedFileQuoting::Windows
+            } else {
+                DroppedFileQuoting::SpacesOnly
+            }
+        );
+    }
+
+    fn overrides(pairs: &[(&str, &str)]) -> wezterm_dynamic::Value {
+        wezterm_dynamic::Value::Object(
+            pairs
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        wezterm_dynamic::Value::String(k.to_string()),
+                        wezterm_dynamic::Value::String(v.to_string()),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn profile() {
+        let mut config = Config::default_config();
+        let err = config.profile("jump").unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "unknown profile `jump`: no profiles are defined in the config"
+        );
+
+        config
+            .profiles
+            .insert("jump".to_string(), overrides(&[("default_domain", "unix")]));
+        config.profiles.insert(
+            "light".to_string(),
+            overrides(&[("color_scheme", "Builtin Solarized Light")]),
+        );
+        assert_eq!(
+            config.profile("jump").unwrap(),
+            &overrides(&[("default_domain", "unix")])
+        );
+        let err = config.profile("dark").unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "unknown profile `dark`: the defined profiles are jump, light"
+        );
+
+        assert_eq!(
+            config.profile_default_domain("jump"),
+            Some("unix".to_string())
+        );
+        assert_eq!(config.profile_default_domain("light"), None);
+        assert_eq!(config.profile_default_domain("dark"), None);
+    }
+
+    #[test]
+    fn idle_pane_policy() {
+        let parse = |pairs: &[(&str, wezterm_dynamic::Value)]| {
+            IdlePanePolicy::from_dynamic(
+                &wezterm_dynamic::Value::Object(
+                    pairs
+                        .iter()
+                        .map(|(k, v)| (wezterm_dynamic::Value::String(k.to_string()), v.clone()))
+                        .collect(),
+                ),
+                Default::default(),
+            )
+        };
+        let hours = |h: f64| ("after_hours", wezterm_dynamic::Value::F64(h.into()));
+        let action = |a: &str| ("action", wezterm_dynamic::Value::String(a.to_string()));
+
+        let policy = parse(&[hours(72.)]).unwrap();
+        assert_eq!(policy.action, IdlePaneAction::Notify);
+        assert!(!policy.only_if_shell);
+        assert_eq!(policy.idle_duration(), Duration::from_secs(72 * 3600));
+
+        assert_eq!(
+            parse(&[hours(1.), action("close")]).unwrap().action,
+            IdlePaneAction::Close
+        );
+        assert_eq!(
+            parse(&[hours(1.), action("notify")]).unwrap().action,
+            IdlePaneAction::Notify
+        );
+        // The capitalized spelling continues to work
+        assert_eq!(
+            parse(&[hours(1.), action("Close")]).unwrap().action,
+            IdlePaneAction::Close
+        );
+        assert!(parse(&[hours(1.), action("kill")]).is_err());
+
+        assert_eq!(
+            parse(&[hours(0.5)]).unwrap().idle_duration(),
+            Duration::from_secs(1800)
+        );
+        assert_eq!(
+            parse(&[hours(-1.)]).unwrap().idle_duration(),
+            Duration::ZERO
+        );
+        assert_eq!(
+            parse(&[hours(f64::INFINITY)]).unwrap().idle_duration(),
+            Duration::MAX
+        );
+
+        assert_eq!(String::from(&IdlePaneAction::Close), "close".to_string());
+    }
+}
@@ -0,0 +1,64 @@
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// The character encoding used to talk to the program running in
+/// a pane.  Panes normally use UTF-8; the other encodings are useful
+/// when connecting to legacy systems.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+#[dynamic(try_from = "String", into = "String")]
+pub enum PaneEncoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1
+    Latin1,
+    /// The original IBM PC code page, which includes box drawing characters
+    Cp437,
+}
+
+impl PaneEncoding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Latin1 => "latin-1",
+            Self::Cp437 => "cp437",
+        }
+    }
+}
+
+impl std::fmt::Display for PaneEncoding {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for PaneEncoding {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Self::Utf8),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(Self::Latin1),
+            "cp437" | "ibm437" => Ok(Self::Cp437),
+            _ => anyhow::bail!(
+                "unknown encoding {s}; expected one of \"utf-8\", \"latin-1\" or \"cp437\""
+            ),
+        }
+    }
+}
+
+impl TryFrom<String> for PaneEncoding {
+    type Error = anyhow::Error;
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<&PaneEncoding> for String {
+    fn from(val: &PaneEncoding) -> Self {
+        val.as_str().to_string()
+    }
+}
+
+impl From<PaneEncoding> for String {
+    fn from(val: PaneEncoding) -> Self {
+        val.as_str().to_string()
+    }
+}
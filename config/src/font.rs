@@ -144,6 +144,14 @@ impl ToDynamic for FontWeight {
 }
 
 impl FromDynamic for FontWeight {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        let _ = registry;
+        wezterm_dynamic::Schema::OneOf(vec![
+            wezterm_dynamic::Schema::String,
+            wezterm_dynamic::Schema::Integer,
+        ])
+    }
+
     fn from_dynamic(
         value: &Value,
         _options: FromDynamicOptions,
@@ -378,6 +386,8 @@ pub struct FontAttributes {
     #[dynamic(default)]
     pub scale: Option<NotNan<f64>>,
     #[dynamic(default)]
+    pub line_height: Option<NotNan<f64>>,
+    #[dynamic(default)]
     pub assume_emoji_presentation: Option<bool>,
 }
 impl_lua_conversion_dynamic!(FontAttributes);
@@ -406,6 +416,7 @@ impl FontAttributes {
             freetype_render_target: None,
             freetype_load_flags: None,
             scale: None,
+            line_height: None,
             assume_emoji_presentation: None,
         }
     }
@@ -423,6 +434,7 @@ impl FontAttributes {
             freetype_render_target: None,
             freetype_load_flags: None,
             scale: None,
+            line_height: None,
             assume_emoji_presentation: None,
         }
     }
@@ -442,6 +454,7 @@ impl Default for FontAttributes {
             freetype_render_target: None,
             freetype_load_flags: None,
             scale: None,
+            line_height: None,
             assume_emoji_presentation: None,
         }
     }
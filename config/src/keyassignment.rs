@@ -31,6 +31,7 @@ bitflags::bitflags! {
         const KEY_ASSIGNMENTS = 16;
         const WORKSPACES = 32;
         const COMMANDS = 64;
+        const PROFILES = 128;
     }
 }
 
@@ -70,6 +71,9 @@ impl ToString for LauncherFlags {
         if self.contains(Self::COMMANDS) {
             s.push("COMMANDS");
         }
+        if self.contains(Self::PROFILES) {
+            s.push("PROFILES");
+        }
         s.join("|")
     }
 }
@@ -89,6 +93,7 @@ impl TryFrom<String> for LauncherFlags {
                 "KEY_ASSIGNMENTS" => flags |= Self::KEY_ASSIGNMENTS,
                 "WORKSPACES" => flags |= Self::WORKSPACES,
                 "COMMANDS" => flags |= Self::COMMANDS,
+                "PROFILES" => flags |= Self::PROFILES,
                 _ => {
                     return Err(format!("invalid LauncherFlags `{}` in `{}`", ele, s));
                 }
@@ -191,10 +196,34 @@ pub struct SpawnCommand {
     #[dynamic(default)]
     pub set_environment_variables: HashMap<String, String>,
 
+    /// When true, each of the user vars of the pane from which the
+    /// command is spawned is set in its environment.
+    #[dynamic(default)]
+    pub inherit_user_vars: bool,
+
+    /// The names of environment variables to take from the pane from
+    /// which the command is spawned; each is looked up in the user
+    /// vars of that pane, and then in the environment of its
+    /// foreground process.
+    /// `set_environment_variables` takes precedence over these.
+    #[dynamic(default)]
+    pub inherit_env: Vec<String>,
+
     #[dynamic(default)]
     pub domain: SpawnTabDomain,
 
     pub position: Option<crate::GuiPosition>,
+
+    /// The character encoding used by the spawned program.
+    /// If omitted, the default for the domain is used, which
+    /// is typically UTF-8.
+    pub encoding: Option<crate::PaneEncoding>,
+
+    /// The name of an entry in the `profiles` config whose overrides
+    /// apply to the spawned pane, and to its window if one is created.
+    /// If omitted, the profile of the pane from which the command is
+    /// spawned is used.
+    pub profile: Option<String>,
 }
 impl_lua_conversion_dynamic!(SpawnCommand);
 
@@ -220,6 +249,15 @@ impl std::fmt::Display for SpawnCommand {
         for (k, v) in &self.set_environment_variables {
             write!(fmt, " {}={}", k, v)?;
         }
+        if self.inherit_user_vars {
+            write!(fmt, " inherit_user_vars")?;
+        }
+        if !self.inherit_env.is_empty() {
+            write!(fmt, " inherit_env={:?}", self.inherit_env)?;
+        }
+        if let Some(profile) = &self.profile {
+            write!(fmt, " profile={}", profile)?;
+        }
         Ok(())
     }
 }
@@ -259,6 +297,7 @@ impl SpawnCommand {
             set_environment_variables,
             cwd,
             position: None,
+            ..Default::default()
         })
     }
 }
@@ -318,6 +357,64 @@ impl Default for ClipboardCopyDestination {
     }
 }
 
+/// The argument to CopyTo, which can be either just the destination,
+/// or a table that also holds options that transform the copied text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ToDynamic)]
+pub struct CopyToOptions {
+    pub destination: ClipboardCopyDestination,
+    /// Overrides `copy_rewrap_column` when set; 0 disables re-wrapping
+    pub rewrap_at: Option<usize>,
+    /// Prefixes each line with its arrival time, as shown in the
+    /// gutter enabled by ToggleLineTimestamps
+    pub with_timestamps: bool,
+}
+
+impl From<ClipboardCopyDestination> for CopyToOptions {
+    fn from(destination: ClipboardCopyDestination) -> Self {
+        Self {
+            destination,
+            rewrap_at: None,
+            with_timestamps: false,
+        }
+    }
+}
+
+#[derive(FromDynamic)]
+struct CopyToTable {
+    #[dynamic(default)]
+    destination: ClipboardCopyDestination,
+    #[dynamic(default)]
+    rewrap_at: Option<usize>,
+    #[dynamic(default)]
+    with_timestamps: bool,
+}
+
+impl FromDynamic for CopyToOptions {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        wezterm_dynamic::Schema::OneOf(vec![
+            ClipboardCopyDestination::describe_schema(registry),
+            CopyToTable::describe_schema(registry),
+        ])
+    }
+
+    fn from_dynamic(
+        value: &Value,
+        options: FromDynamicOptions,
+    ) -> Result<Self, wezterm_dynamic::Error> {
+        match value {
+            Value::String(_) => Ok(ClipboardCopyDestination::from_dynamic(value, options)?.into()),
+            _ => {
+                let table = CopyToTable::from_dynamic(value, options)?;
+                Ok(Self {
+                    destination: table.destination,
+                    rewrap_at: table.rewrap_at,
+                    with_timestamps: table.with_timestamps,
+                })
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
 pub enum ClipboardPasteSource {
     Clipboard,
@@ -337,6 +434,10 @@ pub enum PaneSelectMode {
     SwapWithActiveKeepFocus,
     MoveToNewTab,
     MoveToNewWindow,
+    /// Pick several panes to receive broadcast input
+    ToggleBroadcastInput,
+    /// Link the picked pane with the active pane
+    LinkWithActive(PaneLinkOptions),
 }
 
 impl Default for PaneSelectMode {
@@ -358,6 +459,47 @@ pub struct PaneSelectArguments {
     pub show_pane_ids: bool,
 }
 
+/// How a pair of linked panes affect each other.
+/// A link with neither option set is removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromDynamic, ToDynamic)]
+pub struct PaneLinkOptions {
+    /// Closing either pane closes both of them
+    #[dynamic(default = "default_true")]
+    pub close_together: bool,
+    /// Focusing either pane scrolls the other to the bottom
+    /// and marks its output as seen
+    #[dynamic(default)]
+    pub activate_together: bool,
+}
+impl_lua_conversion_dynamic!(PaneLinkOptions);
+
+impl Default for PaneLinkOptions {
+    fn default() -> Self {
+        Self {
+            close_together: true,
+            activate_together: false,
+        }
+    }
+}
+
+impl PaneLinkOptions {
+    pub fn is_empty(&self) -> bool {
+        !self.close_together && !self.activate_together
+    }
+}
+
+/// Which panes receive the input typed into the active pane
+/// when `ToggleBroadcastInput` is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum BroadcastInputScope {
+    /// The panes in the current tab
+    Tab,
+    /// The panes in all of the tabs in the current window
+    Window,
+    /// Panes that are picked using the pane selector
+    SelectPanes,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
 pub enum CharSelectGroup {
     RecentlyUsed,
@@ -454,6 +596,137 @@ pub struct QuickSelectArguments {
     pub scope_lines: Option<usize>,
 }
 
+#[derive(Debug, Clone, PartialEq, FromDynamic, ToDynamic, Default)]
+pub struct RespawnPane {
+    /// The command line to use.
+    /// If omitted, the command that was originally used
+    /// to spawn the pane will be used again.
+    #[dynamic(default)]
+    pub args: Option<Vec<String>>,
+
+    /// Specifies the current working directory for the command.
+    /// If omitted, the directory used when the command was
+    /// originally spawned will be used.
+    #[dynamic(default)]
+    pub cwd: Option<PathBuf>,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+#[dynamic(try_from = "String", into = "String")]
+pub enum ScrollbackExportFormat {
+    /// Plain text, without any attributes
+    #[default]
+    Text,
+    /// Text with SGR escape sequences to reproduce colors and styles
+    Ansi,
+    /// A standalone HTML document
+    Html,
+}
+
+impl ScrollbackExportFormat {
+    /// The conventional file extension for this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Ansi => "ans",
+            Self::Html => "html",
+        }
+    }
+}
+
+impl From<&ScrollbackExportFormat> for String {
+    fn from(val: &ScrollbackExportFormat) -> Self {
+        match val {
+            ScrollbackExportFormat::Text => "text",
+            ScrollbackExportFormat::Ansi => "ansi",
+            ScrollbackExportFormat::Html => "html",
+        }
+        .to_string()
+    }
+}
+
+impl TryFrom<String> for ScrollbackExportFormat {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value.eq_ignore_ascii_case("text") {
+            Ok(Self::Text)
+        } else if value.eq_ignore_ascii_case("ansi") {
+            Ok(Self::Ansi)
+        } else if value.eq_ignore_ascii_case("html") {
+            Ok(Self::Html)
+        } else {
+            Err(format!(
+                "invalid scrollback format `{value}`; expected one of \
+                 `text`, `ansi` or `html`"
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, FromDynamic, ToDynamic, Default)]
+pub struct SaveScrollback {
+    /// Where to save the scrollback. If omitted, a timestamped
+    /// file is created in the user's download directory.
+    #[dynamic(default)]
+    pub path: Option<PathBuf>,
+    #[dynamic(default)]
+    pub format: ScrollbackExportFormat,
+}
+
+/// Selects which semantic prompt zones are considered
+/// by ScrollToMark
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, FromDynamic, ToDynamic)]
+#[dynamic(try_from = "String", into = "String")]
+pub enum ScrollMarkKind {
+    /// Any prompt
+    #[default]
+    Prompt,
+    /// Prompts for commands that reported a non-zero exit status
+    FailedCommand,
+    /// Lines that have annotations; see `pane:add_annotation`
+    Annotation,
+}
+
+impl TryFrom<String> for ScrollMarkKind {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, String> {
+        match s.as_str() {
+            "prompt" => Ok(Self::Prompt),
+            "failed-command" => Ok(Self::FailedCommand),
+            "annotation" => Ok(Self::Annotation),
+            _ => Err(format!(
+                "`{s}` is not valid, use one of `prompt`, `failed-command` or `annotation`"
+            )),
+        }
+    }
+}
+
+impl From<&ScrollMarkKind> for String {
+    fn from(kind: &ScrollMarkKind) -> String {
+        match kind {
+            ScrollMarkKind::Prompt => "prompt",
+            ScrollMarkKind::FailedCommand => "failed-command",
+            ScrollMarkKind::Annotation => "annotation",
+        }
+        .to_string()
+    }
+}
+
+fn default_scroll_to_mark_amount() -> isize {
+    -1
+}
+
+#[derive(Debug, Clone, PartialEq, FromDynamic, ToDynamic)]
+pub struct ScrollToMark {
+    #[dynamic(default)]
+    pub kind: ScrollMarkKind,
+    /// How many marks to move by; negative values scroll
+    /// up and positive values scroll down
+    #[dynamic(default = "default_scroll_to_mark_amount")]
+    pub amount: isize,
+}
+
 #[derive(Debug, Clone, PartialEq, FromDynamic, ToDynamic)]
 pub struct PromptInputLine {
     pub action: Box<KeyAssignment>,
@@ -509,12 +782,13 @@ pub enum KeyAssignment {
     ToggleAlwaysOnTop,
     ToggleAlwaysOnBottom,
     SetWindowLevel(WindowLevel),
-    CopyTo(ClipboardCopyDestination),
+    CopyTo(CopyToOptions),
     CopyTextTo {
         text: String,
         destination: ClipboardCopyDestination,
     },
     PasteFrom(ClipboardPasteSource),
+    PasteAsBlock(ClipboardPasteSource),
     ActivateTabRelative(isize),
     ActivateTabRelativeNoWrap(isize),
     IncreaseFontSize,
@@ -543,8 +817,10 @@ pub enum KeyAssignment {
     ScrollToBottom,
     ShowTabNavigator,
     ShowDebugOverlay,
+    ShowUpdateOverlay,
     HideApplication,
     QuitApplication,
+    RestoreSession,
     SpawnCommandInNewTab(SpawnCommand),
     SpawnCommandInNewWindow(SpawnCommand),
     SplitHorizontal(SpawnCommand),
@@ -554,6 +830,9 @@ pub enum KeyAssignment {
     ClearScrollback(ScrollbackEraseMode),
     Search(Pattern),
     ActivateCopyMode,
+    ToggleIME,
+    ToggleBroadcastInput(BroadcastInputScope),
+    HighlightSelection,
 
     SelectTextAtMouseCursor(SelectionMode),
     ExtendSelectionToMouseCursor(SelectionMode),
@@ -568,6 +847,8 @@ pub enum KeyAssignment {
     ActivatePaneByIndex(usize),
     TogglePaneZoomState,
     SetPaneZoomState(bool),
+    TogglePaneOutputPause,
+    ToggleLineTimestamps,
     CloseCurrentPane {
         confirm: bool,
     },
@@ -582,6 +863,7 @@ pub enum KeyAssignment {
         spawn: Option<SpawnCommand>,
     },
     SwitchWorkspaceRelative(isize),
+    SwitchToLastWorkspace(usize),
 
     ActivateKeyTable {
         name: String,
@@ -613,8 +895,26 @@ pub enum KeyAssignment {
     ActivateWindow(usize),
     ActivateWindowRelative(isize),
     ActivateWindowRelativeNoWrap(isize),
+    ActivateLastWindow,
     PromptInputLine(PromptInputLine),
     InputSelector(InputSelector),
+    RespawnPane(RespawnPane),
+    SaveScrollback(SaveScrollback),
+    TogglePresentationMode,
+    ToggleZenMode,
+    ToggleIntegerScaling,
+    Disconnect,
+    ScrollToMark(ScrollToMark),
+    ShowWorkspaceSelector,
+    ShowTabOverview,
+    FocusTabBar,
+    MoveTabToDomain(String),
+    ToggleDropDownWindow,
+    LinkPanes(PaneLinkOptions),
+    SpawnFloatingPane(SpawnFloatingPane),
+    ToggleFloatingPane,
+    ReopenClosedPane,
+    ReopenClosedTab,
 }
 impl_lua_conversion_dynamic!(KeyAssignment);
 
@@ -627,6 +927,10 @@ pub struct SplitPane {
     pub command: SpawnCommand,
     #[dynamic(default)]
     pub top_level: bool,
+    /// Seed the new pane with a copy of the scrollback of the pane
+    /// that is being split
+    #[dynamic(default)]
+    pub inherit_scrollback: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
@@ -641,6 +945,50 @@ impl Default for SplitSize {
     }
 }
 
+/// Where a floating pane is placed within its tab
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic, Serialize, Deserialize,
+)]
+pub enum FloatingPanePosition {
+    #[default]
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+fn default_floating_pane_size() -> SplitSize {
+    SplitSize::Percent(60)
+}
+
+#[derive(Debug, Clone, PartialEq, FromDynamic, ToDynamic)]
+pub struct SpawnFloatingPane {
+    #[dynamic(default = "default_floating_pane_size")]
+    pub width: SplitSize,
+    #[dynamic(default = "default_floating_pane_size")]
+    pub height: SplitSize,
+    #[dynamic(default)]
+    pub position: FloatingPanePosition,
+    #[dynamic(default)]
+    pub command: SpawnCommand,
+}
+
+impl Default for SpawnFloatingPane {
+    fn default() -> Self {
+        Self {
+            width: default_floating_pane_size(),
+            height: default_floating_pane_size(),
+            position: FloatingPanePosition::default(),
+            command: SpawnCommand::default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, FromDynamic, ToDynamic)]
 pub enum RotationDirection {
     Clockwise,
@@ -689,6 +1037,55 @@ pub enum CopyModeAssignment {
     JumpBackward { prev_char: bool },
     JumpAgain,
     JumpReverse,
+    /// Selects the text object around the cursor.
+    /// If object is omitted, the next key press specifies
+    /// the object in the same way as vim's `iw`, `a"` and so on.
+    SelectTextObject {
+        #[dynamic(default)]
+        object: Option<TextObject>,
+        #[dynamic(default)]
+        around: bool,
+    },
+    /// Grows the selection to the next enclosing text object
+    ExpandSelection,
+    /// Adds an annotation to the line of the cursor,
+    /// or removes the annotations that it has
+    ToggleAnnotation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum TextObject {
+    /// A run of word characters, or of other non-blank characters
+    Word,
+    /// A run of non-blank characters
+    BigWord,
+    DoubleQuote,
+    SingleQuote,
+    Backtick,
+    Parentheses,
+    Brackets,
+    Braces,
+    /// The semantic zone (eg: command output) reported via OSC 133
+    SemanticZone,
+}
+
+impl TextObject {
+    /// Maps a key following `i` or `a` to a text object, using vim's
+    /// conventions
+    pub fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            'w' => Self::Word,
+            'W' => Self::BigWord,
+            '"' => Self::DoubleQuote,
+            '\'' => Self::SingleQuote,
+            '`' => Self::Backtick,
+            '(' | ')' | 'b' => Self::Parentheses,
+            '[' | ']' => Self::Brackets,
+            '{' | '}' | 'B' => Self::Braces,
+            'z' => Self::SemanticZone,
+            _ => return None,
+        })
+    }
 }
 
 pub type KeyTable = HashMap<(KeyCode, Modifiers), KeyTableEntry>;
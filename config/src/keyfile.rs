@@ -0,0 +1,583 @@
+//! Reads and writes key bindings as a portable TOML file, so that a
+//! set of bindings can be shared without sharing a whole configuration.
+//!
+//! The file has the same shape as the `keys` and `key_tables` options,
+//! with each action written in the form that `wezterm.action` accepts:
+//!
+//! ```toml
+//! [[keys]]
+//! key = "t"
+//! mods = "CTRL|SHIFT"
+//! action = { SpawnTab = "CurrentPaneDomain" }
+//!
+//! [[key_tables.resize_pane]]
+//! key = "LeftArrow"
+//! action = { AdjustPaneSize = ["Left", 1] }
+//! ```
+//!
+//! TOML has no null, so a field whose value is null is omitted, and an
+//! array element that is null is written as an empty table.
+use crate::color::dynamic_to_toml;
+use crate::keys::Key;
+use crate::lua::CALLBACK_EVENT_PREFIX;
+use crate::toml_to_dynamic;
+use anyhow::Context;
+use std::collections::BTreeMap;
+use wezterm_dynamic::{FromDynamic, FromDynamicOptions, ToDynamic, UnknownFieldAction, Value};
+
+#[derive(Debug, Clone, Default)]
+pub struct KeyBindingsFile {
+    pub keys: Vec<Key>,
+    pub key_tables: BTreeMap<String, Vec<Key>>,
+}
+
+/// Returns true if `value` contains an action that emits the event
+/// of a `wezterm.action_callback`.  Such events are numbered in the
+/// order that the configuration created them, so they don't refer
+/// to the same callback in any other configuration.
+fn has_callback(value: &Value) -> bool {
+    match value {
+        Value::Object(obj) => obj.iter().any(|(k, v)| match (k, v) {
+            (Value::String(k), Value::String(event))
+                if k == "EmitEvent" && event.starts_with(CALLBACK_EVENT_PREFIX) =>
+            {
+                true
+            }
+            _ => has_callback(v),
+        }),
+        Value::Array(array) => array.iter().any(has_callback),
+        _ => false,
+    }
+}
+
+/// Describes the entry at `idx` of the list called `list`, for use
+/// in error messages.  Entries are numbered from 1, as in lua.
+fn entry_name(list: &str, idx: usize, value: &Value) -> String {
+    match value {
+        Value::Object(obj) => match obj.get_by_str("key") {
+            Some(Value::String(key)) => format!("{list}[{}] (key={key:?})", idx + 1),
+            _ => format!("{list}[{}]", idx + 1),
+        },
+        _ => format!("{list}[{}]", idx + 1),
+    }
+}
+
+fn keys_to_toml(list: &str, keys: &[Key]) -> anyhow::Result<toml::Value> {
+    let mut entries = vec![];
+    for (idx, key) in keys.iter().enumerate() {
+        let value = key.to_dynamic();
+        let name = entry_name(list, idx, &value);
+        anyhow::ensure!(
+            !has_callback(&value),
+            "{name}: the action uses wezterm.action_callback, \
+             which is a lua function that cannot be exported"
+        );
+        entries.push(dynamic_to_toml(value).with_context(|| name.clone())?);
+    }
+    Ok(toml::Value::Array(entries))
+}
+
+/// Reverses the mapping of null array elements to empty tables that
+/// `dynamic_to_toml` makes.  No array in a key binding can otherwise
+/// hold an empty table.
+fn restore_nulls(value: Value) -> Value {
+    match value {
+        Value::Array(array) => Value::Array(
+            array
+                .into_iter()
+                .map(|v| match v {
+                    Value::Object(obj) if obj.is_empty() => Value::Null,
+                    other => restore_nulls(other),
+                })
+                .collect::<Vec<_>>()
+                .into(),
+        ),
+        Value::Object(obj) => Value::Object(
+            obj.into_iter()
+                .map(|(k, v)| (k, restore_nulls(v)))
+                .collect::<BTreeMap<_, _>>()
+                .into(),
+        ),
+        other => other,
+    }
+}
+
+fn keys_from_toml(list: &str, value: &toml::Value) -> anyhow::Result<Vec<Key>> {
+    let entries = value
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("{list} must be an array of tables"))?;
+    let options = FromDynamicOptions {
+        unknown_fields: UnknownFieldAction::Deny,
+        deprecated_fields: UnknownFieldAction::Warn,
+    };
+    let mut keys = vec![];
+    for (idx, entry) in entries.iter().enumerate() {
+        let value = restore_nulls(toml_to_dynamic(entry));
+        let name = entry_name(list, idx, &value);
+        anyhow::ensure!(
+            !has_callback(&value),
+            "{name}: EmitEvent names of the form {CALLBACK_EVENT_PREFIX}N \
+             refer to wezterm.action_callback functions and cannot be imported"
+        );
+        let key =
+            Key::from_dynamic(&value, options).map_err(|err| anyhow::anyhow!("{name}: {err}"))?;
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+impl KeyBindingsFile {
+    pub fn to_toml_string(&self) -> anyhow::Result<String> {
+        let mut file = toml::map::Map::new();
+        if !self.keys.is_empty() {
+            file.insert("keys".to_string(), keys_to_toml("keys", &self.keys)?);
+        }
+        if !self.key_tables.is_empty() {
+            let mut tables = toml::map::Map::new();
+            for (name, keys) in &self.key_tables {
+                tables.insert(
+                    name.to_string(),
+                    keys_to_toml(&format!("key_tables.{name}"), keys)?,
+                );
+            }
+            file.insert("key_tables".to_string(), toml::Value::Table(tables));
+        }
+        Ok(toml::to_string_pretty(&toml::Value::Table(file))?)
+    }
+
+    pub fn from_toml_str(s: &str) -> anyhow::Result<Self> {
+        let file: toml::Value = toml::from_str(s)?;
+        let file = file
+            .as_table()
+            .ok_or_else(|| anyhow::anyhow!("expected a table"))?;
+
+        let mut result = Self::default();
+        for (field, value) in file {
+            match field.as_str() {
+                "keys" => result.keys = keys_from_toml("keys", value)?,
+                "key_tables" => {
+                    let tables = value
+                        .as_table()
+                        .ok_or_else(|| anyhow::anyhow!("key_tables must be a table"))?;
+                    for (name, keys) in tables {
+                        result.key_tables.insert(
+                            name.to_string(),
+                            keys_from_toml(&format!("key_tables.{name}"), keys)?,
+                        );
+                    }
+                }
+                _ => anyhow::bail!("unknown field {field:?}; only keys and key_tables are allowed"),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns the bindings in the shape of the `keys` and `key_tables`
+    /// config options
+    pub fn to_config_value(&self) -> Value {
+        let mut obj = BTreeMap::new();
+        obj.insert(Value::String("keys".to_string()), self.keys.to_dynamic());
+        obj.insert(
+            Value::String("key_tables".to_string()),
+            Value::Object(
+                self.key_tables
+                    .iter()
+                    .map(|(name, keys)| (Value::String(name.to_string()), keys.to_dynamic()))
+                    .collect::<BTreeMap<_, _>>()
+                    .into(),
+            ),
+        );
+        Value::Object(obj.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::keyassignment::*;
+    use crate::window::WindowLevel;
+    use crate::PaneEncoding;
+    use ordered_float::NotNan;
+    use std::collections::HashSet;
+
+    const BINDINGS: &str = r#"
+[[keys]]
+key = "t"
+mods = "CTRL|SHIFT"
+action = { SpawnTab = "CurrentPaneDomain" }
+
+[[keys]]
+key = "m"
+mods = "LEADER"
+action = { Multiple = [
+    { ActivateKeyTable = { name = "resize_pane", one_shot = false } },
+    { SendString = "hello" },
+    "ClearKeyTableStack",
+] }
+
+[[key_tables.resize_pane]]
+key = "LeftArrow"
+action = { AdjustPaneSize = ["Left", 1] }
+
+[[key_tables.resize_pane]]
+key = "Escape"
+action = "PopKeyTable"
+"#;
+
+    fn actions(keys: &[Key]) -> Vec<KeyAssignment> {
+        keys.iter().map(|key| key.action.clone()).collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = KeyBindingsFile::from_toml_str(BINDINGS).unwrap();
+        assert_eq!(file.keys.len(), 2);
+        assert_eq!(file.key_tables["resize_pane"].len(), 2);
+
+        let exported = file.to_toml_string().unwrap();
+        let imported = KeyBindingsFile::from_toml_str(&exported).unwrap();
+        assert_eq!(actions(&imported.keys), actions(&file.keys));
+        assert_eq!(
+            actions(&imported.key_tables["resize_pane"]),
+            actions(&file.key_tables["resize_pane"])
+        );
+        assert_eq!(imported.to_config_value(), file.to_config_value());
+    }
+
+    /// Returns an action for each variant of KeyAssignment, with the
+    /// optional parts of their arguments filled in where that is
+    /// possible
+    fn every_action() -> Vec<KeyAssignment> {
+        use KeyAssignment::*;
+        let spawn = SpawnCommand {
+            label: Some("label".to_string()),
+            args: Some(vec!["top".to_string(), "-d".to_string()]),
+            cwd: Some("/tmp".into()),
+            set_environment_variables: [("FOO".to_string(), "bar".to_string())].into(),
+            inherit_user_vars: true,
+            inherit_env: vec!["SSH_AUTH_SOCK".to_string()],
+            domain: SpawnTabDomain::DomainName("unix".to_string()),
+            encoding: Some(PaneEncoding::Latin1),
+            profile: Some("work".to_string()),
+            ..Default::default()
+        };
+        let send_key = KeyBindingsFile::from_toml_str(
+            r#"
+[[keys]]
+key = "a"
+mods = "CTRL"
+action = "Nop"
+"#,
+        )
+        .unwrap()
+        .keys
+        .remove(0)
+        .key;
+        let nested = Multiple(vec![
+            SendString("first".to_string()),
+            PromptInputLine(crate::keyassignment::PromptInputLine {
+                action: Box::new(EmitEvent("inner".to_string())),
+                description: "Inner".to_string(),
+            }),
+        ]);
+
+        vec![
+            SpawnTab(SpawnTabDomain::DomainId(2)),
+            SpawnWindow,
+            ToggleFullScreen,
+            ToggleAlwaysOnTop,
+            ToggleAlwaysOnBottom,
+            SetWindowLevel(WindowLevel::AlwaysOnTop),
+            CopyTo(CopyToOptions {
+                destination: ClipboardCopyDestination::PrimarySelection,
+                rewrap_at: Some(80),
+                with_timestamps: true,
+            }),
+            CopyTextTo {
+                text: "text".to_string(),
+                destination: ClipboardCopyDestination::Clipboard,
+            },
+            PasteFrom(ClipboardPasteSource::PrimarySelection),
+            PasteAsBlock(ClipboardPasteSource::Clipboard),
+            ActivateTabRelative(-1),
+            ActivateTabRelativeNoWrap(1),
+            IncreaseFontSize,
+            DecreaseFontSize,
+            ResetFontSize,
+            ResetFontAndWindowSize,
+            ActivateTab(-1),
+            ActivateLastTab,
+            SendString("\x1b[A".to_string()),
+            SendKey(send_key),
+            Nop,
+            DisableDefaultAssignment,
+            Hide,
+            Show,
+            CloseCurrentTab { confirm: true },
+            ReloadConfiguration,
+            MoveTabRelative(-2),
+            MoveTab(3),
+            ScrollByPage(NotNan::new(-0.5).unwrap()),
+            ScrollByLine(-3),
+            ScrollByCurrentEventWheelDelta,
+            ScrollToPrompt(1),
+            ScrollToTop,
+            ScrollToBottom,
+            ShowTabNavigator,
+            ShowDebugOverlay,
+            ShowUpdateOverlay,
+            HideApplication,
+            QuitApplication,
+            RestoreSession,
+            SpawnCommandInNewTab(spawn.clone()),
+            SpawnCommandInNewWindow(SpawnCommand::default()),
+            SplitHorizontal(spawn.clone()),
+            SplitVertical(SpawnCommand::default()),
+            ShowLauncher,
+            ShowLauncherArgs(LauncherActionArgs {
+                flags: LauncherFlags::FUZZY | LauncherFlags::TABS,
+                title: Some("title".to_string()),
+            }),
+            ClearScrollback(ScrollbackEraseMode::ScrollbackAndViewport),
+            Search(Pattern::Regex("[a-f0-9]{6,}".to_string())),
+            ActivateCopyMode,
+            ToggleIME,
+            ToggleBroadcastInput(BroadcastInputScope::SelectPanes),
+            HighlightSelection,
+            SelectTextAtMouseCursor(SelectionMode::SemanticZone),
+            ExtendSelectionToMouseCursor(SelectionMode::Block),
+            OpenLinkAtMouseCursor,
+            ClearSelection,
+            CompleteSelection(ClipboardCopyDestination::ClipboardAndPrimarySelection),
+            CompleteSelectionOrOpenLinkAtMouseCursor(ClipboardCopyDestination::Clipboard),
+            StartWindowDrag,
+            AdjustPaneSize(PaneDirection::Left, 5),
+            ActivatePaneDirection(PaneDirection::Next),
+            ActivatePaneByIndex(0),
+            TogglePaneZoomState,
+            SetPaneZoomState(false),
+            TogglePaneOutputPause,
+            ToggleLineTimestamps,
+            CloseCurrentPane { confirm: false },
+            EmitEvent("my-event".to_string()),
+            QuickSelect,
+            QuickSelectArgs(QuickSelectArguments {
+                alphabet: "asdf".to_string(),
+                patterns: vec!["https?://\\S+".to_string()],
+                action: Some(Box::new(nested.clone())),
+                label: "open url".to_string(),
+                scope_lines: Some(100),
+            }),
+            nested.clone(),
+            SwitchToWorkspace {
+                name: Some("work".to_string()),
+                spawn: Some(spawn.clone()),
+            },
+            SwitchToWorkspace {
+                name: None,
+                spawn: None,
+            },
+            SwitchWorkspaceRelative(-1),
+            SwitchToLastWorkspace(1),
+            ActivateKeyTable {
+                name: "resize_pane".to_string(),
+                timeout_milliseconds: Some(1000),
+                replace_current: true,
+                one_shot: false,
+                until_unknown: true,
+                prevent_fallback: true,
+            },
+            PopKeyTable,
+            ClearKeyTableStack,
+            DetachDomain(SpawnTabDomain::CurrentPaneDomain),
+            AttachDomain("unix".to_string()),
+            CopyMode(CopyModeAssignment::SetSelectionMode(Some(
+                SelectionMode::Line,
+            ))),
+            RotatePanes(RotationDirection::CounterClockwise),
+            SplitPane(crate::keyassignment::SplitPane {
+                direction: PaneDirection::Down,
+                size: SplitSize::Cells(10),
+                command: spawn.clone(),
+                top_level: true,
+                inherit_scrollback: true,
+            }),
+            PaneSelect(PaneSelectArguments {
+                alphabet: "1234".to_string(),
+                mode: PaneSelectMode::LinkWithActive(PaneLinkOptions::default()),
+                show_pane_ids: true,
+            }),
+            CharSelect(CharSelectArguments {
+                group: Some(CharSelectGroup::NerdFonts),
+                copy_on_select: false,
+                copy_to: ClipboardCopyDestination::PrimarySelection,
+            }),
+            ResetTerminal,
+            OpenUri("https://wezfurlong.org/wezterm/".to_string()),
+            ActivateCommandPalette,
+            ActivateWindow(1),
+            ActivateWindowRelative(-1),
+            ActivateWindowRelativeNoWrap(1),
+            ActivateLastWindow,
+            PromptInputLine(crate::keyassignment::PromptInputLine {
+                action: Box::new(InputSelector(crate::keyassignment::InputSelector {
+                    action: Box::new(nested.clone()),
+                    title: "Pick".to_string(),
+                    choices: vec![
+                        InputSelectorEntry {
+                            label: "with id".to_string(),
+                            id: Some("id".to_string()),
+                        },
+                        InputSelectorEntry {
+                            label: "without id".to_string(),
+                            id: None,
+                        },
+                    ],
+                    fuzzy: true,
+                    alphabet: "abc".to_string(),
+                    description: "Choose".to_string(),
+                    fuzzy_description: "Search: ".to_string(),
+                })),
+                description: "Name".to_string(),
+            }),
+            InputSelector(crate::keyassignment::InputSelector {
+                action: Box::new(PromptInputLine(crate::keyassignment::PromptInputLine {
+                    action: Box::new(nested),
+                    description: String::new(),
+                })),
+                title: String::new(),
+                choices: vec![],
+                fuzzy: false,
+                alphabet: "123".to_string(),
+                description: String::new(),
+                fuzzy_description: String::new(),
+            }),
+            RespawnPane(crate::keyassignment::RespawnPane {
+                args: Some(vec!["bash".to_string()]),
+                cwd: Some("/home".into()),
+            }),
+            SaveScrollback(crate::keyassignment::SaveScrollback {
+                path: Some("/tmp/scrollback.html".into()),
+                format: ScrollbackExportFormat::Html,
+            }),
+            TogglePresentationMode,
+            ToggleZenMode,
+            ToggleIntegerScaling,
+            Disconnect,
+            ScrollToMark(crate::keyassignment::ScrollToMark {
+                kind: ScrollMarkKind::FailedCommand,
+                amount: -2,
+            }),
+            ShowWorkspaceSelector,
+            ShowTabOverview,
+            FocusTabBar,
+            MoveTabToDomain("unix".to_string()),
+            ToggleDropDownWindow,
+            LinkPanes(PaneLinkOptions {
+                close_together: false,
+                activate_together: true,
+            }),
+            SpawnFloatingPane(crate::keyassignment::SpawnFloatingPane {
+                width: SplitSize::Cells(80),
+                height: SplitSize::Percent(40),
+                position: FloatingPanePosition::BottomRight,
+                command: spawn,
+            }),
+            ToggleFloatingPane,
+            ReopenClosedPane,
+            ReopenClosedTab,
+        ]
+    }
+
+    fn variant_name(action: &KeyAssignment) -> String {
+        match action.to_dynamic() {
+            Value::String(name) => name,
+            Value::Object(obj) => match obj.keys().next() {
+                Some(Value::String(name)) => name.to_string(),
+                other => panic!("unexpected variant {:?}", other),
+            },
+            other => panic!("unexpected variant {:?}", other),
+        }
+    }
+
+    #[test]
+    fn round_trip_every_action() {
+        let template = KeyBindingsFile::from_toml_str(BINDINGS).unwrap().keys[0].clone();
+        let file = KeyBindingsFile {
+            keys: every_action()
+                .into_iter()
+                .map(|action| Key {
+                    action,
+                    ..template.clone()
+                })
+                .collect(),
+            key_tables: Default::default(),
+        };
+
+        let covered: HashSet<String> = file.keys.iter().map(|k| variant_name(&k.action)).collect();
+        let missing: Vec<&str> = KeyAssignment::variants()
+            .iter()
+            .copied()
+            .filter(|name| !covered.contains(*name))
+            .collect();
+        assert!(missing.is_empty(), "not covered: {:?}", missing);
+
+        let exported = file.to_toml_string().unwrap();
+        let imported = KeyBindingsFile::from_toml_str(&exported)
+            .unwrap_or_else(|err| panic!("{:#}\n{}", err, exported));
+        assert_eq!(actions(&imported.keys), actions(&file.keys));
+        assert_eq!(imported.to_toml_string().unwrap(), exported);
+    }
+
+    #[test]
+    fn null_array_elements() {
+        let value = Value::Array(
+            vec![
+                Value::String("a".to_string()),
+                Value::Null,
+                Value::Array(vec![Value::Null].into()),
+            ]
+            .into(),
+        );
+        let toml = dynamic_to_toml(value.clone()).unwrap();
+        let empty = || toml::Value::Table(toml::map::Map::new());
+        assert_eq!(
+            toml,
+            toml::Value::Array(vec![
+                toml::Value::String("a".to_string()),
+                empty(),
+                toml::Value::Array(vec![empty()]),
+            ])
+        );
+        assert_eq!(restore_nulls(toml_to_dynamic(&toml)), value);
+    }
+
+    #[test]
+    fn errors_name_the_entry() {
+        let err = KeyBindingsFile::from_toml_str(
+            r#"
+[[key_tables.resize_pane]]
+key = "LeftArrow"
+action = "PopKeyTable"
+
+[[key_tables.resize_pane]]
+key = "Escape"
+action = "NoSuchAction"
+"#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(
+            err.starts_with("key_tables.resize_pane[2] (key=\"Escape\"):"),
+            "{}",
+            err
+        );
+
+        let mut file = KeyBindingsFile::from_toml_str(BINDINGS).unwrap();
+        file.keys[1].action = KeyAssignment::Multiple(vec![KeyAssignment::EmitEvent(format!(
+            "{CALLBACK_EVENT_PREFIX}0"
+        ))]);
+        let err = file.to_toml_string().unwrap_err().to_string();
+        assert!(err.starts_with("keys[2] (key=\"m\"):"), "{}", err);
+    }
+}
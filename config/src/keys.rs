@@ -125,6 +125,27 @@ pub struct Key {
     pub action: KeyAssignment,
 }
 
+/// Activates a key table automatically while the active pane
+/// matches `when`
+#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+pub struct AutoKeyTable {
+    pub table: String,
+    pub when: AutoKeyTableCondition,
+}
+
+/// Each field that is set must match.  The values are regular
+/// expressions that must match the whole of the name.
+#[derive(Debug, Clone, Default, FromDynamic, ToDynamic)]
+pub struct AutoKeyTableCondition {
+    /// Matched against the file name of the foreground process of
+    /// the pane, without any `.exe` extension
+    #[dynamic(default)]
+    pub process: Option<String>,
+    /// Matched against the name of the domain of the pane
+    #[dynamic(default)]
+    pub domain: Option<String>,
+}
+
 #[derive(Debug, Clone, FromDynamic, ToDynamic)]
 pub struct LeaderKey {
     #[dynamic(flatten)]
@@ -153,6 +174,14 @@ pub enum MouseEventAltScreen {
 }
 
 impl FromDynamic for MouseEventAltScreen {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        let _ = registry;
+        wezterm_dynamic::Schema::OneOf(vec![
+            wezterm_dynamic::Schema::Bool,
+            wezterm_dynamic::Schema::String,
+        ])
+    }
+
     fn from_dynamic(value: &Value, _options: FromDynamicOptions) -> Result<Self, DynError> {
         match value {
             Value::Bool(true) => Ok(Self::True),
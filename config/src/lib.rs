@@ -24,33 +24,43 @@ mod bell;
 mod color;
 mod config;
 mod daemon;
+mod encoding;
 mod exec_domain;
 mod font;
 mod frontend;
 pub mod keyassignment;
+mod keyfile;
 mod keys;
 pub mod lua;
 pub mod meta;
+mod presentation;
+pub mod schema;
 mod scheme_data;
 mod serial;
 mod ssh;
+pub mod statefile;
 mod terminal;
 mod tls;
 mod units;
 mod unix;
 mod version;
 pub mod window;
+mod workspace_style;
 mod wsl;
+mod zen;
 
 pub use crate::config::*;
 pub use background::*;
 pub use bell::*;
 pub use color::*;
 pub use daemon::*;
+pub use encoding::*;
 pub use exec_domain::*;
 pub use font::*;
 pub use frontend::*;
+pub use keyfile::*;
 pub use keys::*;
+pub use presentation::*;
 pub use serial::*;
 pub use ssh::*;
 pub use terminal::*;
@@ -58,7 +68,9 @@ pub use tls::*;
 pub use units::*;
 pub use unix::*;
 pub use version::*;
+pub use workspace_style::*;
 pub use wsl::*;
+pub use zen::*;
 
 type ErrorCallback = fn(&str);
 
@@ -444,6 +456,13 @@ pub fn overridden_config(overrides: &wezterm_dynamic::Value) -> Result<ConfigHan
     CONFIG.overridden(overrides)
 }
 
+/// Returns a version of the config (loaded from the config file)
+/// with the overrides of the profile named `name` applied
+pub fn profile_config(name: &str) -> Result<ConfigHandle, Error> {
+    let overrides = configuration().profile(name)?.clone();
+    CONFIG.overridden(&overrides)
+}
+
 pub fn reload() {
     CONFIG.reload();
 }
@@ -793,6 +812,17 @@ impl ConfigHandle {
             generation: 0,
         }
     }
+
+    /// Returns a copy of this configuration with `func` applied to it.
+    /// The generation number is preserved.
+    pub fn with_modifications<F: FnOnce(&mut Config)>(&self, func: F) -> Self {
+        let mut config = (*self.config).clone();
+        func(&mut config);
+        Self {
+            config: Arc::new(config),
+            generation: self.generation,
+        }
+    }
 }
 
 impl std::ops::Deref for ConfigHandle {
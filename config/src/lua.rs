@@ -1,8 +1,8 @@
 use crate::exec_domain::{ExecDomain, ValueOrFunc};
 use crate::keyassignment::KeyAssignment;
 use crate::{
-    Config, FontAttributes, FontStretch, FontStyle, FontWeight, FreeTypeLoadTarget, RgbaColor,
-    TextStyle,
+    Config, FontAttributes, FontStretch, FontStyle, FontWeight, FreeTypeLoadTarget,
+    KeyBindingsFile, RgbaColor, TextStyle,
 };
 use anyhow::{anyhow, Context};
 use luahelper::{from_lua_value_dynamic, lua_value_to_dynamic, to_lua};
@@ -353,6 +353,10 @@ end
         lua.set_named_registry_value(LUA_REGISTRY_USER_CALLBACK_COUNT, 0)?;
         wezterm_mod.set("action_callback", lua.create_function(action_callback)?)?;
         wezterm_mod.set("exec_domain", lua.create_function(exec_domain)?)?;
+        wezterm_mod.set(
+            "import_key_bindings",
+            lua.create_function(import_key_bindings)?,
+        )?;
 
         wezterm_mod.set("utf16_to_utf8", lua.create_function(utf16_to_utf8)?)?;
         wezterm_mod.set("split_by_newlines", lua.create_function(split_by_newlines)?)?;
@@ -497,6 +501,8 @@ struct LuaFontAttributes {
     #[dynamic(default)]
     pub scale: Option<NotNan<f64>>,
     #[dynamic(default)]
+    pub line_height: Option<NotNan<f64>>,
+    #[dynamic(default)]
     pub assume_emoji_presentation: Option<bool>,
 }
 impl<'lua> FromLua<'lua> for LuaFontAttributes {
@@ -584,6 +590,7 @@ fn font<'lua>(
                 None => None,
             },
             scale: attrs.scale,
+            line_height: attrs.line_height,
             assume_emoji_presentation: attrs.assume_emoji_presentation,
         }));
 
@@ -633,6 +640,7 @@ fn font_with_fallback<'lua>(
                     None => None,
                 },
                 scale: attrs.scale,
+                line_height: attrs.line_height,
                 assume_emoji_presentation: attrs.assume_emoji_presentation,
             }));
     }
@@ -640,9 +648,12 @@ fn font_with_fallback<'lua>(
     Ok(text_style)
 }
 
+/// The prefix of the names of the events that wrap_callback registers
+pub(crate) const CALLBACK_EVENT_PREFIX: &str = "user-defined-";
+
 pub fn wrap_callback<'lua>(lua: &'lua Lua, callback: mlua::Function) -> mlua::Result<String> {
     let callback_count: i32 = lua.named_registry_value(LUA_REGISTRY_USER_CALLBACK_COUNT)?;
-    let user_event_id = format!("user-defined-{}", callback_count);
+    let user_event_id = format!("{CALLBACK_EVENT_PREFIX}{callback_count}");
     lua.set_named_registry_value(LUA_REGISTRY_USER_CALLBACK_COUNT, callback_count + 1)?;
     register_event(lua, (user_event_id.clone(), callback))?;
     Ok(user_event_id)
@@ -653,6 +664,19 @@ fn action_callback<'lua>(lua: &'lua Lua, callback: mlua::Function) -> mlua::Resu
     Ok(KeyAssignment::EmitEvent(user_event_id))
 }
 
+/// Loads a file written by `wezterm cli export-keys`, returning a table
+/// with `keys` and `key_tables` fields to merge into the config
+fn import_key_bindings<'lua>(lua: &'lua Lua, path: String) -> mlua::Result<Value<'lua>> {
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("reading key bindings from {path}"))
+        .map_err(mlua::Error::external)?;
+    let file = KeyBindingsFile::from_toml_str(&text)
+        .with_context(|| format!("importing key bindings from {path}"))
+        .map_err(mlua::Error::external)?;
+    add_to_config_reload_watch_list(lua, vec![path].into_iter().collect())?;
+    luahelper::dynamic_to_lua_value(lua, file.to_config_value())
+}
+
 fn exec_domain<'lua>(
     lua: &'lua Lua,
     (name, fixup_command, label): (String, mlua::Function, Option<mlua::Value>),
@@ -792,6 +816,17 @@ pub async fn emit_event<'lua>(
     }
 }
 
+/// Returns true if at least one handler has been registered for
+/// the named event via `wezterm.on`.
+pub fn has_event_handler<'lua>(lua: &'lua Lua, name: &str) -> mlua::Result<bool> {
+    let decorated_name = format!("wezterm-event-{}", name);
+    let tbl: mlua::Value = lua.named_registry_value(&decorated_name)?;
+    match tbl {
+        mlua::Value::Table(tbl) => Ok(tbl.raw_len() > 0),
+        _ => Ok(false),
+    }
+}
+
 pub fn emit_sync_callback<'lua, A>(
     lua: &'lua Lua,
     (name, args): (String, A),
@@ -0,0 +1,79 @@
+use crate::*;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// The adjustments that are applied to a window while
+/// presentation mode is active
+#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+pub struct PresentationModeConfig {
+    /// The font size is multiplied by this amount
+    #[dynamic(default = "default_font_size_multiplier")]
+    pub font_size_multiplier: f64,
+
+    /// Overrides cursor_thickness
+    #[dynamic(
+        try_from = "crate::units::OptPixelUnit",
+        default = "default_cursor_thickness"
+    )]
+    pub cursor_thickness: Option<Dimension>,
+
+    /// Overrides the cursor background and border colors
+    #[dynamic(default = "default_cursor_color")]
+    pub cursor_color: Option<RgbaColor>,
+
+    /// Whether to display recently pressed keys in the
+    /// bottom right corner of the window
+    #[dynamic(default = "default_true")]
+    pub show_key_presses: bool,
+
+    /// How long each pressed key remains on screen
+    #[dynamic(default = "default_key_press_duration_ms")]
+    pub key_press_duration_ms: u64,
+
+    /// Key presses that are sent to a pane whose foreground
+    /// process has one of these names are never displayed
+    #[dynamic(default = "default_key_press_process_blocklist")]
+    pub key_press_process_blocklist: Vec<String>,
+
+    /// Whether toast notifications are suppressed
+    #[dynamic(default = "default_true")]
+    pub suppress_notifications: bool,
+}
+
+impl Default for PresentationModeConfig {
+    fn default() -> Self {
+        Self {
+            font_size_multiplier: default_font_size_multiplier(),
+            cursor_thickness: default_cursor_thickness(),
+            cursor_color: default_cursor_color(),
+            show_key_presses: true,
+            key_press_duration_ms: default_key_press_duration_ms(),
+            key_press_process_blocklist: default_key_press_process_blocklist(),
+            suppress_notifications: true,
+        }
+    }
+}
+
+fn default_font_size_multiplier() -> f64 {
+    1.5
+}
+
+fn default_cursor_thickness() -> Option<Dimension> {
+    Some(Dimension::Pixels(3.))
+}
+
+fn default_cursor_color() -> Option<RgbaColor> {
+    Some(RgbColor::new_8bpc(0xff, 0x8c, 0x00).into())
+}
+
+fn default_key_press_duration_ms() -> u64 {
+    1500
+}
+
+fn default_key_press_process_blocklist() -> Vec<String> {
+    [
+        "sudo", "su", "doas", "passwd", "ssh", "ssh-add", "gpg", "pinentry",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
@@ -0,0 +1,400 @@
+//! Exports the schema of the configuration for use by external tooling,
+//! such as editors that validate and complete config files.
+//! The schema is taken from the FromDynamic implementations of the
+//! config types, so that it describes exactly what is accepted,
+//! and the documentation and defaults of the top level options
+//! are taken from the ConfigMeta of Config.
+use crate::meta::ConfigMeta;
+use crate::Config;
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use wezterm_dynamic::{FieldSchema, Schema, SchemaRegistry, TypeSchema, Value, VariantSchema};
+
+/// Describes Config and all of the types that it contains
+pub fn config_registry() -> SchemaRegistry {
+    let mut registry = SchemaRegistry::new();
+    registry.describe::<Config>();
+    registry
+}
+
+/// Returns the doc comment and default value of each top level option
+fn config_option_meta() -> Vec<(&'static str, &'static str, Option<Value>)> {
+    Config::default()
+        .get_config_options()
+        .iter()
+        .map(|option| {
+            (
+                option.name,
+                option.doc,
+                option.default_value.map(|default_value| default_value()),
+            )
+        })
+        .collect()
+}
+
+fn dynamic_to_json(value: &Value) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Bool(b) => JsonValue::Bool(*b),
+        Value::String(s) => JsonValue::String(s.clone()),
+        Value::U64(n) => json!(n),
+        Value::I64(n) => json!(n),
+        Value::F64(n) => json!(n.into_inner()),
+        Value::Array(a) => JsonValue::Array(a.iter().map(dynamic_to_json).collect()),
+        Value::Object(o) => JsonValue::Object(
+            o.iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        Value::String(s) => s.clone(),
+                        other => dynamic_to_json(other).to_string(),
+                    };
+                    (key, dynamic_to_json(v))
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Removes the space that follows the `///` of each line of a doc comment
+fn trim_doc(doc: &str) -> String {
+    doc.lines().map(str::trim).collect::<Vec<_>>().join("\n")
+}
+
+fn json_schema(schema: &Schema) -> JsonValue {
+    match schema {
+        Schema::Any => json!({}),
+        Schema::Null => json!({"type": "null"}),
+        Schema::Bool => json!({"type": "boolean"}),
+        Schema::Integer => json!({"type": "integer"}),
+        Schema::Number => json!({"type": "number"}),
+        Schema::String => json!({"type": "string"}),
+        Schema::Optional(inner) => json!({"anyOf": [json_schema(inner), {"type": "null"}]}),
+        Schema::Array(inner) => json!({"type": "array", "items": json_schema(inner)}),
+        Schema::Map(key, value) => {
+            let mut map = json!({
+                "type": "object",
+                "additionalProperties": json_schema(value),
+            });
+            // Object keys are always strings in JSON
+            if matches!(key.strip_optional(), Schema::Integer) {
+                map["propertyNames"] = json!({"pattern": "^-?[0-9]+$"});
+            }
+            map
+        }
+        // The alternatives may overlap, as integers are also numbers
+        Schema::OneOf(alternatives) => {
+            json!({"anyOf": alternatives.iter().map(json_schema).collect::<Vec<_>>()})
+        }
+        Schema::Named(name) => json!({"$ref": format!("#/$defs/{}", name)}),
+    }
+}
+
+fn json_object(fields: &[FieldSchema]) -> JsonValue {
+    let mut properties = JsonMap::new();
+    let mut required = vec![];
+    for field in fields {
+        let mut property = json_schema(&field.schema);
+        if let Some(reason) = field.deprecated {
+            property["deprecated"] = json!(true);
+            property["description"] = json!(reason);
+        }
+        properties.insert(field.name.to_string(), property);
+        if field.required {
+            required.push(field.name);
+        }
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+/// A single key object, which is how an enum variant
+/// that holds values is specified
+fn json_variant(name: &str, value: JsonValue) -> JsonValue {
+    json!({
+        "type": "object",
+        "properties": {name: value},
+        "required": [name],
+        "additionalProperties": false,
+    })
+}
+
+fn json_type(schema: &TypeSchema) -> JsonValue {
+    match schema {
+        TypeSchema::Struct(fields) => json_object(fields),
+        TypeSchema::Enum(variants) => json!({
+            "oneOf": variants
+                .iter()
+                .map(|variant| match variant {
+                    VariantSchema::Unit(name) => json!({"const": name}),
+                    VariantSchema::Newtype(name, schema) => json_variant(name, json_schema(schema)),
+                    VariantSchema::Tuple(name, schemas) => json_variant(
+                        name,
+                        json!({
+                            "type": "array",
+                            "prefixItems": schemas.iter().map(json_schema).collect::<Vec<_>>(),
+                            "minItems": schemas.len(),
+                            "maxItems": schemas.len(),
+                        }),
+                    ),
+                    VariantSchema::Struct(name, fields) => json_variant(name, json_object(fields)),
+                })
+                .collect::<Vec<_>>()
+        }),
+        TypeSchema::Alias(schema) => json_schema(schema),
+    }
+}
+
+/// Returns the schema of the configuration as a JSON Schema document
+pub fn json_config_schema() -> JsonValue {
+    let registry = config_registry();
+
+    let mut defs = JsonMap::new();
+    for (name, schema) in registry.iter() {
+        defs.insert(name.to_string(), json_type(schema));
+    }
+
+    if let Some(config) = defs.get_mut("Config") {
+        for (name, doc, default) in config_option_meta() {
+            if let Some(property) = config["properties"].get_mut(name) {
+                if !doc.is_empty() && property.get("description").is_none() {
+                    property["description"] = json!(trim_doc(doc));
+                }
+                if let Some(default) = default {
+                    property["default"] = dynamic_to_json(&default);
+                }
+            }
+        }
+    }
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "wezterm configuration",
+        "$ref": "#/$defs/Config",
+        "$defs": defs,
+    })
+}
+
+fn lua_type(schema: &Schema) -> String {
+    match schema {
+        Schema::Any => "any".to_string(),
+        Schema::Null => "nil".to_string(),
+        Schema::Bool => "boolean".to_string(),
+        Schema::Integer => "integer".to_string(),
+        Schema::Number => "number".to_string(),
+        Schema::String => "string".to_string(),
+        Schema::Optional(inner) => format!("{}|nil", lua_type(inner)),
+        Schema::Array(inner) => match inner.as_ref() {
+            Schema::Optional(_) | Schema::OneOf(_) => format!("({})[]", lua_type(inner)),
+            _ => format!("{}[]", lua_type(inner)),
+        },
+        Schema::Map(key, value) => format!("table<{}, {}>", lua_type(key), lua_type(value)),
+        Schema::OneOf(alternatives) => alternatives
+            .iter()
+            .map(lua_type)
+            .collect::<Vec<_>>()
+            .join("|"),
+        Schema::Named(name) => name.to_string(),
+    }
+}
+
+fn lua_table_type(fields: &[FieldSchema]) -> String {
+    let fields = fields
+        .iter()
+        .map(|field| {
+            if field.required {
+                format!("{}: {}", field.name, lua_type(&field.schema))
+            } else {
+                format!(
+                    "{}?: {}",
+                    field.name,
+                    lua_type(field.schema.strip_optional())
+                )
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("{{ {} }}", fields.join(", "))
+}
+
+fn lua_variant_type(variant: &VariantSchema) -> String {
+    match variant {
+        VariantSchema::Unit(name) => format!("{:?}", name),
+        VariantSchema::Newtype(name, schema) => format!("{{ {}: {} }}", name, lua_type(schema)),
+        VariantSchema::Tuple(name, schemas) => format!(
+            "{{ {}: [{}] }}",
+            name,
+            schemas.iter().map(lua_type).collect::<Vec<_>>().join(", ")
+        ),
+        VariantSchema::Struct(name, fields) => {
+            format!("{{ {}: {} }}", name, lua_table_type(fields))
+        }
+    }
+}
+
+/// Collapses a doc comment onto a single line
+fn one_line(doc: &str) -> String {
+    doc.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Returns the schema of the configuration as type annotations
+/// for the lua-language-server
+pub fn lua_config_annotations() -> String {
+    let registry = config_registry();
+    let docs = config_option_meta();
+
+    let mut out = String::new();
+    out.push_str("---@meta\n");
+    out.push_str("-- The types of the wezterm configuration.\n");
+    out.push_str("-- Generated by `wezterm gen-schema --format lua-annotations`.\n");
+
+    for (name, schema) in registry.iter() {
+        out.push('\n');
+        match schema {
+            TypeSchema::Struct(fields) => {
+                out.push_str(&format!("---@class {}\n", name));
+                for field in fields {
+                    let doc = match field.deprecated {
+                        Some(reason) => format!("Deprecated: {}", one_line(reason)),
+                        None if name == "Config" => docs
+                            .iter()
+                            .find(|(option, _, _)| *option == field.name)
+                            .map(|(_, doc, _)| one_line(doc))
+                            .unwrap_or_default(),
+                        None => String::new(),
+                    };
+                    let (field_name, ty) = if field.required {
+                        (field.name.to_string(), lua_type(&field.schema))
+                    } else {
+                        (
+                            format!("{}?", field.name),
+                            lua_type(field.schema.strip_optional()),
+                        )
+                    };
+                    if doc.is_empty() {
+                        out.push_str(&format!("---@field {} {}\n", field_name, ty));
+                    } else {
+                        out.push_str(&format!("---@field {} {} {}\n", field_name, ty, doc));
+                    }
+                }
+            }
+            TypeSchema::Enum(variants) => {
+                out.push_str(&format!("---@alias {}\n", name));
+                for variant in variants {
+                    out.push_str(&format!("---| {}\n", lua_variant_type(variant)));
+                }
+            }
+            TypeSchema::Alias(schema) => {
+                out.push_str(&format!("---@alias {} {}\n", name, lua_type(schema)));
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn registry_has_nested_types() {
+        let registry = config_registry();
+        for name in [
+            "Config",
+            "SshDomain",
+            "TabBarColors",
+            "PaneSelectArguments",
+            "KeyAssignment",
+        ] {
+            assert!(registry.get(name).is_some(), "{} is not described", name);
+        }
+
+        match registry.get("KeyAssignment") {
+            Some(TypeSchema::Enum(variants)) => {
+                let send_string = variants.iter().find(|v| v.name() == "SendString");
+                assert_eq!(
+                    send_string,
+                    Some(&VariantSchema::Newtype("SendString", Schema::String))
+                );
+            }
+            other => panic!("KeyAssignment is not an enum: {:?}", other),
+        }
+    }
+
+    /// The types and options that are compared against the snapshots
+    /// in tests/fixtures/schema.  Between them they cover enums,
+    /// structs, alternatives, references, docs, defaults and deprecation.
+    const SNAPSHOT_TYPES: &[&str] = &["DroppedFileQuoting", "UpdateCheckIntervalTable"];
+    const SNAPSHOT_OPTIONS: &[&str] = &[
+        "bold_brightens_ansi_colors",
+        "automatically_reload_config",
+        "show_update_window",
+        "check_for_updates_interval_seconds",
+    ];
+
+    #[test]
+    fn json_matches_snapshot() {
+        let schema = json_config_schema();
+        assert_eq!(schema["$ref"], json!("#/$defs/Config"));
+
+        let mut defs = JsonMap::new();
+        for name in SNAPSHOT_TYPES {
+            defs.insert(name.to_string(), schema["$defs"][name].clone());
+        }
+        let mut options = JsonMap::new();
+        for name in SNAPSHOT_OPTIONS {
+            options.insert(
+                name.to_string(),
+                schema["$defs"]["Config"]["properties"][name].clone(),
+            );
+        }
+        let actual = json!({"$defs": defs, "config_properties": options});
+
+        let expected: JsonValue =
+            serde_json::from_str(include_str!("../tests/fixtures/schema/schema.json")).unwrap();
+        assert!(
+            actual == expected,
+            "schema differs from tests/fixtures/schema/schema.json:\n{}",
+            serde_json::to_string_pretty(&actual).unwrap()
+        );
+    }
+
+    /// Returns the name of the type or field that a line annotates
+    fn annotated_name(line: &str) -> &str {
+        line.split_whitespace()
+            .nth(1)
+            .unwrap_or("")
+            .trim_end_matches('?')
+    }
+
+    #[test]
+    fn lua_matches_snapshot() {
+        let annotations = lua_config_annotations();
+
+        let mut blocks = vec![];
+        for block in annotations.split("\n\n") {
+            let mut lines = block.lines();
+            let header = lines.next().unwrap_or("");
+            if header == "---@meta" || SNAPSHOT_TYPES.contains(&annotated_name(header)) {
+                blocks.push(block.trim_end().to_string());
+            } else if header == "---@class Config" {
+                let fields = lines.filter(|line| SNAPSHOT_OPTIONS.contains(&annotated_name(line)));
+                blocks.push(
+                    std::iter::once(header)
+                        .chain(fields)
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                );
+            }
+        }
+        let actual = format!("{}\n", blocks.join("\n\n"));
+
+        assert_eq!(
+            actual,
+            include_str!("../tests/fixtures/schema/annotations.lua"),
+            "annotations differ from tests/fixtures/schema/annotations.lua"
+        );
+    }
+}
@@ -16,4 +16,15 @@ pub struct SerialDomain {
 
     /// Set the baud rate.  The default is 9600 baud.
     pub baud: Option<usize>,
+
+    /// If true, a read or write error on the port (for example, when
+    /// a USB serial adapter is unplugged) doesn't close the pane;
+    /// instead the port is periodically re-opened with the same
+    /// settings until it reappears.
+    #[dynamic(default)]
+    pub reconnect: bool,
+
+    /// How often to try to re-open the port when `reconnect`
+    /// is enabled.  The default is 1000 milliseconds.
+    pub reconnect_interval_ms: Option<u64>,
 }
@@ -47,6 +47,68 @@ impl Default for Shell {
     }
 }
 
+/// How to treat host keys that are not present in the known hosts file.
+/// Mirrors the `StrictHostKeyChecking` ssh_config option.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum StrictHostKeyChecking {
+    /// Never trust unknown host keys; refuse to connect
+    Yes,
+    /// Trust and record unknown host keys without asking
+    No,
+    /// Like `No`, but spelled the way that OpenSSH does
+    AcceptNew,
+    /// Ask the user to confirm unknown host keys
+    Ask,
+}
+
+impl StrictHostKeyChecking {
+    /// Returns the equivalent ssh_config option value
+    pub fn as_ssh_option(self) -> &'static str {
+        match self {
+            Self::Yes => "yes",
+            Self::No => "no",
+            Self::AcceptNew => "accept-new",
+            Self::Ask => "ask",
+        }
+    }
+}
+
+/// Controls what happens when an ssh session is established with a
+/// host that doesn't have the wezterm terminfo entry installed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+pub enum TerminfoAssist {
+    /// Offer to install it, or to use xterm-256color for that host
+    Prompt,
+    /// Install it without asking
+    Auto,
+    /// Don't check for it
+    Off,
+}
+
+impl Default for TerminfoAssist {
+    fn default() -> Self {
+        Self::Prompt
+    }
+}
+
+/// A host through which the connection to an SshDomain is tunnelled
+#[derive(Default, Debug, Clone, FromDynamic, ToDynamic)]
+pub struct SshJumpHost {
+    /// identifies the host:port pair of the jump host.
+    pub remote_address: String,
+
+    /// The username to use for authenticating with the jump host
+    pub username: Option<String>,
+
+    /// Whether agent forwarding should be requested from this hop
+    #[dynamic(default)]
+    pub forward_agent: bool,
+
+    /// ssh_config option values for this hop
+    #[dynamic(default)]
+    pub ssh_option: HashMap<String, String>,
+}
+
 #[derive(Default, Debug, Clone, FromDynamic, ToDynamic)]
 pub struct SshDomain {
     /// The name of this specific domain.  Must be unique amongst
@@ -64,6 +126,11 @@ pub struct SshDomain {
     /// The username to use for authenticating with the remote host
     pub username: Option<String>,
 
+    /// The chain of hosts through which to tunnel the connection;
+    /// the first element is the first hop.
+    #[dynamic(default)]
+    pub proxy_jump: Vec<SshJumpHost>,
+
     /// If true, connect to this domain automatically at startup
     #[dynamic(default)]
     pub connect_automatically: bool,
@@ -100,10 +167,19 @@ pub struct SshDomain {
     #[dynamic(default)]
     pub ssh_option: HashMap<String, String>,
 
+    /// How to treat host keys that are not yet known. Takes
+    /// precedence over any StrictHostKeyChecking in ssh_option
+    /// or the ssh config files.
+    pub strict_host_key_checking: Option<StrictHostKeyChecking>,
+
     pub default_prog: Option<Vec<String>>,
 
     #[dynamic(default)]
     pub assume_shell: Shell,
+
+    /// The character encoding used by the remote host.
+    /// Only applies when `multiplexing = "None"`.
+    pub encoding: Option<PaneEncoding>,
 }
 impl_lua_conversion_dynamic!(SshDomain);
 
@@ -0,0 +1,225 @@
+//! Helpers for reading and writing the small state files that wezterm
+//! keeps in the runtime and data directories, such as the recently
+//! used commands and the saved session.
+//!
+//! More than one wezterm process may share those directories, so
+//! updates are serialized using an advisory lock on a sibling `.lock`
+//! file, and the new content is written to a temporary file that is
+//! then renamed over the original, so that a reader never observes a
+//! partially written file.
+use anyhow::Context;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// How long to wait for another process to release the lock.
+/// Updates are small, so if the lock is held for longer than this,
+/// its holder is most likely stuck, and we would rather report an
+/// error than hang the caller, which may be the gui thread.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Holds an exclusive advisory lock on a state file until dropped
+pub struct StateFileLock {
+    _file: File,
+}
+
+fn lock_file_name(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+impl StateFileLock {
+    /// Waits until the lock for `path` has been acquired, or
+    /// fails if that takes longer than `LOCK_TIMEOUT`
+    pub fn acquire(path: &Path) -> anyhow::Result<Self> {
+        if let Some(parent) = path.parent() {
+            crate::create_user_owned_dirs(parent)?;
+        }
+        let lock_name = lock_file_name(path);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(&lock_name)
+            .with_context(|| format!("opening lock file {}", lock_name.display()))?;
+        let deadline = Instant::now() + LOCK_TIMEOUT;
+        while !try_lock_exclusive(&file)
+            .with_context(|| format!("locking {}", lock_name.display()))?
+        {
+            if Instant::now() >= deadline {
+                anyhow::bail!(
+                    "timed out waiting for another process to release {}",
+                    lock_name.display()
+                );
+            }
+            std::thread::sleep(LOCK_RETRY_INTERVAL);
+        }
+        Ok(Self { _file: file })
+    }
+}
+
+/// Tries to lock `file` without blocking.
+/// Returns false if another process holds the lock.
+#[cfg(unix)]
+fn try_lock_exclusive(file: &File) -> std::io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+    loop {
+        // The lock is released when the file is closed
+        let res = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if res == 0 {
+            return Ok(true);
+        }
+        let err = std::io::Error::last_os_error();
+        match err.kind() {
+            std::io::ErrorKind::Interrupted => continue,
+            std::io::ErrorKind::WouldBlock => return Ok(false),
+            _ => return Err(err),
+        }
+    }
+}
+
+/// Tries to lock `file` without blocking.
+/// Returns false if another process holds the lock.
+#[cfg(windows)]
+fn try_lock_exclusive(file: &File) -> std::io::Result<bool> {
+    use std::os::windows::io::AsRawHandle;
+    use winapi::shared::winerror::ERROR_LOCK_VIOLATION;
+    use winapi::um::fileapi::LockFileEx;
+    use winapi::um::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED};
+    // The lock is released when the handle is closed
+    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+    let res = unsafe {
+        LockFileEx(
+            file.as_raw_handle() as _,
+            LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY,
+            0,
+            !0,
+            !0,
+            &mut overlapped,
+        )
+    };
+    if res != 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(ERROR_LOCK_VIOLATION as i32) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+/// Writes `data` to `path` by way of a temporary file in the same
+/// directory. The caller is expected to hold the lock for `path`.
+fn write_atomic(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("{} has no file name", path.display()))?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(
+        ".tmp-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = std::fs::write(&temp_path, data)
+        .with_context(|| format!("writing {}", temp_path.display()))
+        .and_then(|_| {
+            std::fs::rename(&temp_path, path)
+                .with_context(|| format!("renaming {} to {}", temp_path.display(), path.display()))
+        });
+    if result.is_err() {
+        std::fs::remove_file(&temp_path).ok();
+    }
+    result
+}
+
+/// Replaces the content of `path` with `data`
+pub fn write(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let _lock = StateFileLock::acquire(path)?;
+    write_atomic(path, data)
+}
+
+/// Reads the current content of `path`, if any, and replaces it with
+/// the value returned by `update`. Other processes that update the
+/// same file wait until this one is done, so that their changes are
+/// not lost.
+pub fn update<F>(path: &Path, update: F) -> anyhow::Result<()>
+where
+    F: FnOnce(Option<Vec<u8>>) -> anyhow::Result<Vec<u8>>,
+{
+    let _lock = StateFileLock::acquire(path)?;
+    let current = match std::fs::read(path) {
+        Ok(data) => Some(data),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err).context(format!("reading {}", path.display())),
+    };
+    let data = update(current)?;
+    write_atomic(path, &data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn update_and_replace() {
+        let dir = std::env::temp_dir().join(format!("wezterm-statefile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+
+        update(&path, |current| {
+            assert_eq!(current, None);
+            Ok(b"1".to_vec())
+        })
+        .unwrap();
+        update(&path, |current| {
+            let mut data = current.unwrap();
+            data.extend_from_slice(b"2");
+            Ok(data)
+        })
+        .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"12");
+
+        write(&path, b"3").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"3");
+
+        // No temporary files are left behind
+        let mut names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["state.json", "state.json.lock"]);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn lock_times_out() {
+        let dir = std::env::temp_dir().join(format!("wezterm-statelock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+
+        let lock = StateFileLock::acquire(&path).unwrap();
+        // A second lock on the same file is held by a different open
+        // file description, so it conflicts even within this process
+        let start = Instant::now();
+        let err = write(&path, b"1").unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{err:#}");
+        assert!(start.elapsed() >= LOCK_TIMEOUT);
+        assert!(!path.exists());
+
+        drop(lock);
+        write(&path, b"1").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"1");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -10,6 +10,9 @@ use wezterm_term::config::BidiMode;
 pub struct TermConfig {
     config: Mutex<Option<ConfigHandle>>,
     client_palette: Mutex<Option<ColorPalette>>,
+    /// The profile whose overrides are applied to the config,
+    /// which is re-resolved whenever the config is reloaded
+    profile: Option<String>,
 }
 
 impl TermConfig {
@@ -17,6 +20,7 @@ impl TermConfig {
         Self {
             config: Mutex::new(None),
             client_palette: Mutex::new(None),
+            profile: None,
         }
     }
 
@@ -24,9 +28,21 @@ impl TermConfig {
         Self {
             config: Mutex::new(Some(config)),
             client_palette: Mutex::new(None),
+            profile: None,
         }
     }
 
+    /// Returns a config that follows the config file with the
+    /// overrides of the profile named `name` applied
+    pub fn with_profile(name: &str) -> anyhow::Result<Self> {
+        let config = crate::profile_config(name)?;
+        Ok(Self {
+            config: Mutex::new(Some(config)),
+            client_palette: Mutex::new(None),
+            profile: Some(name.to_string()),
+        })
+    }
+
     pub fn set_config(&self, config: ConfigHandle) {
         self.config.lock().unwrap().replace(config);
     }
@@ -36,7 +52,23 @@ impl TermConfig {
     }
 
     fn configuration(&self) -> ConfigHandle {
-        match self.config.lock().unwrap().as_ref() {
+        let mut config = self.config.lock().unwrap();
+        if let Some(name) = &self.profile {
+            let generation = configuration().generation();
+            if config.as_ref().map(|h| h.generation()) != Some(generation) {
+                match crate::profile_config(name) {
+                    Ok(h) => {
+                        config.replace(h);
+                    }
+                    Err(err) => {
+                        // The profile was removed from the config
+                        log::error!("{:#}", err);
+                        config.replace(configuration());
+                    }
+                }
+            }
+        }
+        match config.as_ref() {
             Some(h) => h.clone(),
             None => configuration(),
         }
@@ -116,10 +148,18 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
         self.configuration().log_unknown_escape_sequences
     }
 
+    fn accept_iterm2_osc(&self) -> bool {
+        self.configuration().accept_iterm2_osc
+    }
+
     fn normalize_output_to_unicode_nfc(&self) -> bool {
         self.configuration().normalize_output_to_unicode_nfc
     }
 
+    fn grapheme_clustering(&self) -> bool {
+        self.configuration().grapheme_clustering
+    }
+
     fn bidi_mode(&self) -> BidiMode {
         let config = self.configuration();
         BidiMode {
@@ -72,6 +72,13 @@ pub struct TlsDomainClient {
     /// should not normally need to override this value.
     pub expected_cn: Option<String>,
 
+    /// If non-empty, the SHA-256 fingerprints of the certificates that
+    /// the server is allowed to present. The connection is refused if
+    /// the server presents any other certificate, rather than asking
+    /// whether to trust it.
+    #[dynamic(default)]
+    pub expected_cert_fingerprints: Vec<String>,
+
     /// If true, connect to this domain automatically at startup
     #[dynamic(default)]
     pub connect_automatically: bool,
@@ -5,6 +5,10 @@ use wezterm_dynamic::{FromDynamic, FromDynamicOptions, ToDynamic, Value};
 pub struct OptPixelUnit(Option<Dimension>);
 
 impl FromDynamic for OptPixelUnit {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        wezterm_dynamic::Schema::Optional(Box::new(PixelUnit::describe_schema(registry)))
+    }
+
     fn from_dynamic(
         value: &Value,
         _options: FromDynamicOptions,
@@ -32,6 +36,15 @@ impl From<PixelUnit> for Dimension {
 }
 
 impl FromDynamic for PixelUnit {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        let _ = registry;
+        // A number of pixels, or a string like "1.5cell"
+        wezterm_dynamic::Schema::OneOf(vec![
+            wezterm_dynamic::Schema::Number,
+            wezterm_dynamic::Schema::String,
+        ])
+    }
+
     fn from_dynamic(
         value: &Value,
         _options: FromDynamicOptions,
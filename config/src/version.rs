@@ -2,10 +2,12 @@ use once_cell::sync::OnceCell;
 
 static VERSION: OnceCell<&'static str> = OnceCell::new();
 static TRIPLE: OnceCell<&'static str> = OnceCell::new();
+static RELEASE: OnceCell<bool> = OnceCell::new();
 
-pub fn assign_version_info(version: &'static str, triple: &'static str) {
+pub fn assign_version_info(version: &'static str, triple: &'static str, is_release: bool) {
     VERSION.set(version).unwrap();
     TRIPLE.set(triple).unwrap();
+    RELEASE.set(is_release).unwrap();
 }
 
 pub fn wezterm_version() -> &'static str {
@@ -14,6 +16,12 @@ pub fn wezterm_version() -> &'static str {
         .unwrap_or(&"someone forgot to call assign_version_info")
 }
 
+/// Returns true if this is a build of a tagged release,
+/// rather than a nightly or local build
+pub fn wezterm_is_release() -> bool {
+    RELEASE.get().copied().unwrap_or(false)
+}
+
 pub fn wezterm_target_triple() -> &'static str {
     TRIPLE
         .get()
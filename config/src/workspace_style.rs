@@ -0,0 +1,16 @@
+use crate::*;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// The styling that is applied to a window while its workspace
+/// is the one that the style is keyed by in workspace_styles
+#[derive(Default, Debug, Clone, PartialEq, FromDynamic, ToDynamic)]
+pub struct WorkspaceStyle {
+    /// Overlaid on top of the tab bar colors of the color scheme
+    /// and of the `colors` section
+    #[dynamic(default)]
+    pub tab_bar: Option<TabBarColors>,
+
+    /// Prepended to the title of the window
+    #[dynamic(default)]
+    pub window_title_prefix: Option<String>,
+}
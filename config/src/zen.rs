@@ -0,0 +1,38 @@
+use crate::*;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// The chrome that is hidden from a window while zen mode is active
+#[derive(Debug, Clone, FromDynamic, ToDynamic)]
+pub struct ZenModeConfig {
+    /// Whether the tab bar is hidden
+    #[dynamic(default = "default_true")]
+    pub hide_tab_bar: bool,
+
+    /// Whether the scroll bar is hidden
+    #[dynamic(default = "default_true")]
+    pub hide_scroll_bar: bool,
+
+    /// The padding that is used on every side of the window
+    /// in place of window_padding
+    #[dynamic(try_from = "crate::units::PixelUnit", default = "default_padding")]
+    pub padding: Dimension,
+
+    /// Whether the window is made full screen
+    #[dynamic(default)]
+    pub fullscreen: bool,
+}
+
+impl Default for ZenModeConfig {
+    fn default() -> Self {
+        Self {
+            hide_tab_bar: true,
+            hide_scroll_bar: true,
+            padding: default_padding(),
+            fullscreen: false,
+        }
+    }
+}
+
+fn default_padding() -> Dimension {
+    Dimension::Pixels(4.)
+}
@@ -0,0 +1,22 @@
+---@meta
+-- The types of the wezterm configuration.
+-- Generated by `wezterm gen-schema --format lua-annotations`.
+
+---@class Config
+---@field bold_brightens_ansi_colors? string|boolean When true (the default), PaletteIndex 0-7 are shifted to bright when the font intensity is bold. The brightening doesn't apply to text that is the default color.
+---@field automatically_reload_config? boolean When true, watch the config file and reload it automatically when it is detected as changing.
+---@field show_update_window? boolean Deprecated: this option no longer does anything and will be removed in a future release
+---@field check_for_updates_interval_seconds? integer|UpdateCheckIntervalTable
+
+---@alias DroppedFileQuoting
+---| "None"
+---| "SpacesOnly"
+---| "Posix"
+---| "Windows"
+---| "WindowsAlwaysQuoted"
+---| "PowerShell"
+---| "Auto"
+
+---@class UpdateCheckIntervalTable
+---@field release? integer
+---@field nightly? integer
@@ -6,135 +6,113 @@
 use chrono::prelude::*;
 use env_logger::filter::{Builder as FilterBuilder, Filter};
 use log::{Level, LevelFilter, Log, Record};
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Mutex;
 use termwiz::istty::IsTty;
 
+/// The number of entries that are retained for each level.
+/// Each level has its own ring so that a burst of debug logging
+/// doesn't push out the warnings and errors.
+const RING_SIZE: usize = 256;
+
 lazy_static::lazy_static! {
-    static ref RINGS: Mutex<Rings> = Mutex::new(Rings::new());
+    // One ring per level, indexed by `level as usize - 1`
+    static ref RINGS: Vec<LevelRing> =
+        (0..Level::Trace as usize).map(|_| LevelRing::new()).collect();
+    static ref LOG_FILE_NAME: Mutex<Option<PathBuf>> = Mutex::new(None);
 }
 
+/// Assigns each entry its position in the overall sequence of entries
+static NEXT_SEQNO: AtomicU64 = AtomicU64::new(1);
+/// The seqno of the newest entry that has been written to its ring
+/// along with all of those before it.  Readers don't look past it,
+/// so that an entry that is still being written when a newer one is
+/// collected doesn't get skipped by `get_entries_after`.
+static COMMITTED_SEQNO: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Entry {
     pub then: DateTime<Local>,
     pub level: Level,
     pub target: String,
     pub msg: String,
+    /// Increases with each entry that is logged; useful for
+    /// collecting only the entries that are newer than those
+    /// that have already been seen
+    pub seqno: u64,
 }
 
+/// A fixed size ring of entries.
+/// Logging can happen on any thread, including the render path,
+/// so rather than locking the whole ring, each writer claims the
+/// next slot with an atomic increment and then locks just that
+/// slot.  Writers only contend if they wrap around onto the same slot.
 struct LevelRing {
-    entries: Vec<Entry>,
-    first: usize,
-    last: usize,
+    slots: Vec<Mutex<Option<Entry>>>,
+    next: AtomicUsize,
 }
 
 impl LevelRing {
-    fn new(level: Level) -> Self {
-        let mut entries = vec![];
-        let now = Local::now();
-        for _ in 0..16 {
-            entries.push(Entry {
-                then: now,
-                level,
-                target: String::new(),
-                msg: String::new(),
-            });
-        }
+    fn new() -> Self {
         Self {
-            entries,
-            first: 0,
-            last: 0,
+            slots: (0..RING_SIZE).map(|_| Mutex::new(None)).collect(),
+            next: AtomicUsize::new(0),
         }
     }
 
-    // Returns the number of entries in the ring
-    fn len(&self) -> usize {
-        if self.last >= self.first {
-            self.last - self.first
-        } else {
-            // Wrapped around.
-            (self.entries.len() - self.first) + self.last
-        }
+    fn push(&self, entry: Entry) {
+        let idx = self.next.fetch_add(1, Ordering::Relaxed) % self.slots.len();
+        self.slots[idx].lock().unwrap().replace(entry);
     }
 
-    fn rolling_inc(&self, value: usize) -> usize {
-        let incremented = value + 1;
-        if incremented >= self.entries.len() {
-            0
-        } else {
-            incremented
-        }
-    }
-
-    fn push(&mut self, entry: Entry) {
-        if self.len() == self.entries.len() {
-            // We are full; effectively pop the first entry to
-            // make room
-            self.entries[self.first] = entry;
-            self.first = self.rolling_inc(self.first);
-        } else {
-            self.entries[self.last] = entry;
-        }
-        self.last = self.rolling_inc(self.last);
-    }
-
-    fn append_to_vec(&self, target: &mut Vec<Entry>) {
-        if self.last >= self.first {
-            target.extend_from_slice(&self.entries[self.first..self.last]);
-        } else {
-            target.extend_from_slice(&self.entries[self.first..]);
-            target.extend_from_slice(&self.entries[..self.last]);
+    fn append_to_vec(&self, after: u64, upto: u64, target: &mut Vec<Entry>) {
+        for slot in &self.slots {
+            if let Some(entry) = slot.lock().unwrap().as_ref() {
+                if entry.seqno > after && entry.seqno <= upto {
+                    target.push(entry.clone());
+                }
+            }
         }
     }
 }
 
-struct Rings {
-    rings: HashMap<Level, LevelRing>,
+fn ring_for_level(level: Level) -> &'static LevelRing {
+    &RINGS[level as usize - 1]
 }
 
-impl Rings {
-    fn new() -> Self {
-        let mut rings = HashMap::new();
-        for level in &[
-            Level::Error,
-            Level::Warn,
-            Level::Info,
-            Level::Debug,
-            Level::Trace,
-        ] {
-            rings.insert(*level, LevelRing::new(*level));
-        }
-        Self { rings }
-    }
-
-    fn get_entries(&self) -> Vec<Entry> {
-        let mut results = vec![];
-        for ring in self.rings.values() {
-            ring.append_to_vec(&mut results);
-        }
-        results
-    }
-
-    fn log(&mut self, record: &Record) {
-        if let Some(ring) = self.rings.get_mut(&record.level()) {
-            ring.push(Entry {
-                then: Local::now(),
-                level: record.level(),
-                target: record.target().to_string(),
-                msg: record.args().to_string(),
-            });
-        }
+/// Retains an entry in the ring for its level, returning its seqno
+fn retain(then: DateTime<Local>, level: Level, target: String, msg: String) -> u64 {
+    let seqno = NEXT_SEQNO.fetch_add(1, Ordering::Relaxed);
+    ring_for_level(level).push(Entry {
+        then,
+        level,
+        target,
+        msg,
+        seqno,
+    });
+    // Publish the entry only once those before it have been published
+    while COMMITTED_SEQNO
+        .compare_exchange_weak(seqno - 1, seqno, Ordering::Release, Ordering::Relaxed)
+        .is_err()
+    {
+        std::thread::yield_now();
     }
+    seqno
 }
 
 struct Logger {
     file_name: PathBuf,
     file: Mutex<Option<BufWriter<File>>>,
+    /// Selects the entries that are written to stderr and the log file
     filter: Filter,
+    /// Selects the entries that are retained in the rings.  This is
+    /// more permissive than `filter`, so that `show-logs --level debug`
+    /// and the debug overlay have something to show without having
+    /// to restart with `WEZTERM_LOG` set.
+    ring_filter: Filter,
     padding: AtomicUsize,
     is_tty: bool,
 }
@@ -147,7 +125,7 @@ impl Drop for Logger {
 
 impl log::Log for Logger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        self.filter.enabled(metadata)
+        self.filter.enabled(metadata) || self.ring_filter.enabled(metadata)
     }
 
     fn flush(&self) {
@@ -158,12 +136,19 @@ impl log::Log for Logger {
     }
 
     fn log(&self, record: &Record) {
-        if self.filter.matches(record) {
-            RINGS.lock().unwrap().log(record);
-            let ts = Local::now().format("%H:%M:%S%.3f").to_string();
+        let show = self.filter.matches(record);
+        if !show && !self.ring_filter.matches(record) {
+            return;
+        }
+
+        let then = Local::now();
+        let target = record.target().to_string();
+        let msg = record.args().to_string();
+        retain(then, record.level(), target.clone(), msg.clone());
+
+        if show {
+            let ts = then.format("%H:%M:%S%.3f").to_string();
             let level = record.level().as_str();
-            let target = record.target().to_string();
-            let msg = record.args().to_string();
 
             let padding = self.padding.fetch_max(target.len(), Ordering::SeqCst);
 
@@ -231,8 +216,18 @@ impl log::Log for Logger {
 
 /// Returns the current set of log information, sorted by time
 pub fn get_entries() -> Vec<Entry> {
-    let mut entries = RINGS.lock().unwrap().get_entries();
-    entries.sort();
+    get_entries_after(0)
+}
+
+/// Returns the retained entries whose seqno is greater than `after`,
+/// sorted by time
+pub fn get_entries_after(after: u64) -> Vec<Entry> {
+    let upto = COMMITTED_SEQNO.load(Ordering::Acquire);
+    let mut entries = vec![];
+    for ring in RINGS.iter() {
+        ring.append_to_vec(after, upto, &mut entries);
+    }
+    entries.sort_by_key(|entry| entry.seqno);
     entries
 }
 
@@ -276,24 +271,35 @@ fn setup_pretty() -> (LevelFilter, Logger) {
         libc::getpid()
     }));
 
-    let mut filters = FilterBuilder::new();
-    for (module, level) in [
-        ("wgpu_core", LevelFilter::Error),
-        ("wgpu_hal", LevelFilter::Error),
-        ("gfx_backend_metal", LevelFilter::Error),
-        ("tracing", LevelFilter::Error),
-        ("zbus", LevelFilter::Error),
-    ] {
-        filters.filter_module(module, level);
-    }
+    let quiet_modules = |filters: &mut FilterBuilder| {
+        for (module, level) in [
+            ("wgpu_core", LevelFilter::Error),
+            ("wgpu_hal", LevelFilter::Error),
+            ("gfx_backend_metal", LevelFilter::Error),
+            ("tracing", LevelFilter::Error),
+            ("zbus", LevelFilter::Error),
+        ] {
+            filters.filter_module(module, level);
+        }
+    };
 
+    let mut filters = FilterBuilder::new();
+    quiet_modules(&mut filters);
     if let Ok(s) = std::env::var("WEZTERM_LOG") {
         filters.parse(&s);
     } else {
         filters.filter_level(LevelFilter::Info);
     }
     let filter = filters.build();
-    let max_level = filter.filter();
+
+    let mut ring_filters = FilterBuilder::new();
+    quiet_modules(&mut ring_filters);
+    ring_filters.filter_level(LevelFilter::Debug);
+    let ring_filter = ring_filters.build();
+
+    let max_level = filter.filter().max(ring_filter.filter());
+
+    LOG_FILE_NAME.lock().unwrap().replace(log_file_name.clone());
 
     (
         max_level,
@@ -301,15 +307,94 @@ fn setup_pretty() -> (LevelFilter, Logger) {
             file_name: log_file_name,
             file: Mutex::new(None),
             filter,
+            ring_filter,
             padding: AtomicUsize::new(0),
             is_tty: std::io::stderr().is_tty(),
         },
     )
 }
 
+/// Returns the name of the file that this process logs to,
+/// if the logger has been set up
+pub fn log_file_name() -> Option<PathBuf> {
+    LOG_FILE_NAME.lock().unwrap().clone()
+}
+
 pub fn setup_logger() {
     let (max_level, logger) = setup_pretty();
     if log::set_boxed_logger(Box::new(logger)).is_ok() {
         log::set_max_level(max_level);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(seqno: u64) -> Entry {
+        Entry {
+            then: Local::now(),
+            level: Level::Info,
+            target: "ringlog".to_string(),
+            msg: format!("entry {seqno}"),
+            seqno,
+        }
+    }
+
+    fn seqnos(entries: &[Entry]) -> Vec<u64> {
+        entries.iter().map(|entry| entry.seqno).collect()
+    }
+
+    #[test]
+    fn ring_wraps() {
+        let ring = LevelRing::new();
+        for seqno in 1..=(RING_SIZE as u64 + 10) {
+            ring.push(entry(seqno));
+        }
+
+        // Only the newest RING_SIZE entries are retained
+        let mut entries = vec![];
+        ring.append_to_vec(0, u64::MAX, &mut entries);
+        entries.sort_by_key(|entry| entry.seqno);
+        assert_eq!(
+            seqnos(&entries),
+            (11..=(RING_SIZE as u64 + 10)).collect::<Vec<_>>()
+        );
+
+        // Entries outside of the requested range are left out
+        let mut entries = vec![];
+        ring.append_to_vec(20, 25, &mut entries);
+        entries.sort_by_key(|entry| entry.seqno);
+        assert_eq!(seqnos(&entries), vec![21, 22, 23, 24, 25]);
+    }
+
+    #[test]
+    fn entries_after() {
+        let target = "ringlog::test::entries_after";
+        let retain_msg =
+            |level, msg: &str| retain(Local::now(), level, target.to_string(), msg.to_string());
+        let ours = |after| -> Vec<(Level, String)> {
+            get_entries_after(after)
+                .into_iter()
+                .filter(|entry| entry.target == target)
+                .map(|entry| (entry.level, entry.msg))
+                .collect()
+        };
+
+        let first = retain_msg(Level::Warn, "first");
+        let second = retain_msg(Level::Debug, "second");
+        assert!(second > first);
+
+        // Entries from all levels are collected, in the order that
+        // they were logged
+        assert_eq!(
+            ours(first - 1),
+            vec![
+                (Level::Warn, "first".to_string()),
+                (Level::Debug, "second".to_string()),
+            ]
+        );
+        assert_eq!(ours(first), vec![(Level::Debug, "second".to_string())]);
+        assert_eq!(ours(second), vec![]);
+    }
+}
@@ -1,20 +1,23 @@
-use config::keyassignment::SpawnTabDomain;
+use config::keyassignment::{SpawnCommand, SpawnTabDomain};
 use config::lua::mlua::{self, Lua, UserData, UserDataMethods, Value as LuaValue};
 use config::lua::{get_or_create_module, get_or_create_sub_module};
 use luahelper::impl_lua_conversion_dynamic;
 use mlua::UserDataRef;
-use mux::domain::{DomainId, SplitSource};
+use mux::domain::{ConnectionStats, DomainId, InheritEnvironment, SplitSource};
 use mux::pane::{Pane, PaneId};
+use mux::session::SessionState;
 use mux::tab::{SplitDirection, SplitRequest, SplitSize, Tab, TabId};
 use mux::window::{Window, WindowId};
 use mux::Mux;
 use portable_pty::CommandBuilder;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use wezterm_dynamic::{FromDynamic, ToDynamic};
 use wezterm_term::TerminalSize;
 
 mod domain;
+mod output_stream;
 mod pane;
 mod tab;
 mod window;
@@ -107,6 +110,21 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
         lua.create_async_function(|_, spawn: SpawnWindow| async move { spawn.spawn().await })?,
     )?;
 
+    mux_mod.set(
+        "restore_session",
+        lua.create_async_function(|_, path: Option<String>| async move {
+            let path = path
+                .map(PathBuf::from)
+                .unwrap_or_else(mux::session::default_session_path);
+            let session = SessionState::load(&path).map_err(mlua::Error::external)?;
+            let window_ids = session.restore().await.map_err(mlua::Error::external)?;
+            Ok(window_ids
+                .into_iter()
+                .map(MuxWindow)
+                .collect::<Vec<MuxWindow>>())
+        })?,
+    )?;
+
     mux_mod.set(
         "all_windows",
         lua.create_function(|_, _: ()| {
@@ -158,6 +176,17 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
         })?,
     )?;
 
+    mux_mod.set(
+        "get_domain_stats",
+        lua.create_function(|_, name: String| {
+            let mux = get_mux()?;
+            let domain = mux
+                .get_domain_by_name(&name)
+                .ok_or_else(|| mlua::Error::external(format!("domain {name} not found in mux")))?;
+            Ok(domain.connection_stats().map(DomainStats::from))
+        })?,
+    )?;
+
     mux_mod.set(
         "set_default_domain",
         lua.create_function(|_, domain: UserDataRef<MuxDomain>| {
@@ -171,6 +200,28 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// The health of the connection of a domain to a remote mux,
+/// as returned by `wezterm.mux.get_domain_stats`
+#[derive(Debug, Default, FromDynamic, ToDynamic)]
+struct DomainStats {
+    connected: bool,
+    rtt_ms: Option<f64>,
+    last_traffic_secs_ago: Option<f64>,
+    reconnects: usize,
+}
+impl_lua_conversion_dynamic!(DomainStats);
+
+impl From<ConnectionStats> for DomainStats {
+    fn from(stats: ConnectionStats) -> Self {
+        Self {
+            connected: stats.connected,
+            rtt_ms: stats.rtt.map(|rtt| rtt.as_secs_f64() * 1000.),
+            last_traffic_secs_ago: stats.last_traffic.map(|when| when.elapsed().as_secs_f64()),
+            reconnects: stats.reconnects,
+        }
+    }
+}
+
 #[derive(Debug, Default, FromDynamic, ToDynamic)]
 struct CommandBuilderFrag {
     args: Option<Vec<String>>,
@@ -194,9 +245,19 @@ impl CommandBuilderFrag {
             (None, self.cwd.clone())
         }
     }
+
+    fn to_spawn_command(&self, domain: &SpawnTabDomain) -> SpawnCommand {
+        SpawnCommand {
+            args: self.args.clone(),
+            cwd: self.cwd.as_ref().map(Into::into),
+            set_environment_variables: self.set_environment_variables.clone(),
+            domain: domain.clone(),
+            ..Default::default()
+        }
+    }
 }
 
-#[derive(Debug, FromDynamic, ToDynamic)]
+#[derive(Debug, Clone, Copy, FromDynamic, ToDynamic)]
 enum HandySplitDirection {
     Left,
     Right,
@@ -241,7 +302,12 @@ impl SpawnWindow {
             _ => config::configuration().initial_size(0, None),
         };
 
+        let spawn_command = SpawnCommand {
+            position: self.position.clone(),
+            ..self.cmd_builder.to_spawn_command(&self.domain)
+        };
         let (cmd_builder, cwd) = self.cmd_builder.to_command_builder();
+        let _spawn_command = mux.with_spawn_command(spawn_command);
         let (tab, pane, window_id) = mux
             .spawn_tab_or_window(
                 None,
@@ -291,8 +357,10 @@ impl SpawnTab {
                 .and_then(|tab| tab.get_active_pane().map(|pane| pane.pane_id()));
         };
 
+        let spawn_command = self.cmd_builder.to_spawn_command(&self.domain);
         let (cmd_builder, cwd) = self.cmd_builder.to_command_builder();
 
+        let _spawn_command = mux.with_spawn_command(spawn_command);
         let (tab, pane, window_id) = mux
             .spawn_tab_or_window(
                 Some(window.0),
@@ -0,0 +1,87 @@
+use super::*;
+use mux::output_stream::{OutputStream, StreamStart, DEFAULT_BUFFER_LINES};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, FromDynamic, ToDynamic)]
+#[dynamic(try_from = "String", into = "String")]
+pub enum StreamFrom {
+    #[default]
+    Now,
+    StartOfScrollback,
+}
+
+impl TryFrom<String> for StreamFrom {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "now" => Ok(Self::Now),
+            "start-of-scrollback" => Ok(Self::StartOfScrollback),
+            _ => Err(format!(
+                "invalid output stream start `{value}`; expected one of \
+                 `now` or `start-of-scrollback`"
+            )),
+        }
+    }
+}
+
+impl From<&StreamFrom> for String {
+    fn from(val: &StreamFrom) -> Self {
+        match val {
+            StreamFrom::Now => "now",
+            StreamFrom::StartOfScrollback => "start-of-scrollback",
+        }
+        .to_string()
+    }
+}
+
+fn default_buffer_lines() -> usize {
+    DEFAULT_BUFFER_LINES
+}
+
+#[derive(Debug, FromDynamic, ToDynamic)]
+pub struct OpenOutputStream {
+    #[dynamic(default)]
+    from: StreamFrom,
+    #[dynamic(default = "default_buffer_lines")]
+    buffer_lines: usize,
+}
+impl_lua_conversion_dynamic!(OpenOutputStream);
+
+impl Default for OpenOutputStream {
+    fn default() -> Self {
+        Self {
+            from: StreamFrom::default(),
+            buffer_lines: default_buffer_lines(),
+        }
+    }
+}
+
+impl OpenOutputStream {
+    pub fn open(&self, pane: &Arc<dyn Pane>) -> mlua::Result<MuxOutputStream> {
+        let start = match self.from {
+            StreamFrom::Now => StreamStart::Now,
+            StreamFrom::StartOfScrollback => StreamStart::StartOfScrollback,
+        };
+        OutputStream::open(pane, start, self.buffer_lines)
+            .map(MuxOutputStream)
+            .map_err(|e| mlua::Error::external(format!("{:#}", e)))
+    }
+}
+
+/// The handle returned by `pane:open_output_stream`
+#[derive(Clone)]
+pub struct MuxOutputStream(OutputStream);
+
+impl UserData for MuxOutputStream {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_async_method("read_lines", |_, this, max: Option<usize>| async move {
+            Ok(this.0.read_lines(max.unwrap_or(usize::MAX)).await)
+        });
+        methods.add_method("dropped_count", |_, this, _: ()| Ok(this.0.dropped_count()));
+        methods.add_method("is_closed", |_, this, _: ()| Ok(this.0.is_closed()));
+        methods.add_method("close", |_, this, _: ()| {
+            this.0.close();
+            Ok(())
+        });
+    }
+}
@@ -1,13 +1,20 @@
 use super::*;
+use crate::output_stream::OpenOutputStream;
+use config::keyassignment::{PaneLinkOptions, ScrollbackExportFormat};
 use luahelper::{dynamic_to_lua_value, from_lua, to_lua};
-use mlua::Value;
+use mlua::{UserDataRef, Value};
+use mux::annotation::{Annotation, DEFAULT_ANNOTATION_KIND};
 use mux::pane::CachePolicy;
+use mux::pane_watch::PaneWatch;
+use mux::MuxNotification;
 use std::cmp::Ordering;
+use std::path::PathBuf;
 use std::sync::Arc;
 use termwiz::cell::SemanticType;
 use termwiz_funcs::lines_to_escapes;
 use url_funcs::Url;
-use wezterm_term::{SemanticZone, StableRowIndex};
+use wezterm_bidi::ParagraphDirectionHint;
+use wezterm_term::{CommandRecord, CommandStatus, SemanticZone, StableRowIndex};
 
 #[derive(Clone, Copy, Debug)]
 pub struct MuxPane(pub PaneId);
@@ -92,10 +99,50 @@ impl UserData for MuxPane {
         });
         methods.add_method("pane_id", |_, this, _: ()| Ok(this.0));
 
+        methods.add_method("get_nesting_depth", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            Ok(pane.get_nesting_depth())
+        });
+
         methods.add_async_method("split", |_, this, args: Option<SplitPane>| async move {
             args.unwrap_or_default().run(this).await
         });
 
+        methods.add_method("open_mirror", |_, this, args: Option<OpenMirror>| {
+            let args = args.unwrap_or_default();
+            let request = split_request(args.direction, args.top_level, args.size);
+            let mux = get_mux()?;
+            let pane = mux
+                .open_mirror(this.0, request)
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))?;
+            Ok(MuxPane(pane.pane_id()))
+        });
+
+        methods.add_method(
+            "open_output_stream",
+            |_, this, args: Option<OpenOutputStream>| {
+                let mux = get_mux()?;
+                let pane = this.resolve(&mux)?;
+                args.unwrap_or_default().open(&pane)
+            },
+        );
+
+        methods.add_async_method("respawn", |_, this, args: Option<RespawnPane>| async move {
+            args.unwrap_or_default().run(this).await
+        });
+
+        methods.add_async_method(
+            "save_scrollback",
+            |_, this, args: SaveScrollback| async move {
+                let mux = get_mux()?;
+                let pane = this.resolve(&mux)?;
+                mux::scrollback::save_scrollback(&pane, &args.path, args.format)
+                    .await
+                    .map_err(|e| mlua::Error::external(format!("{:#}", e)))
+            },
+        );
+
         methods.add_method("send_paste", |_, this, text: String| {
             let mux = get_mux()?;
             let pane = this.resolve(&mux)?;
@@ -153,6 +200,22 @@ impl UserData for MuxPane {
                 .map(|url| Url { url }))
         });
 
+        methods.add_method("get_location", |lua, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            let location = match pane.get_location(CachePolicy::FetchImmediate) {
+                Some(location) => location,
+                None => return Ok(None),
+            };
+            let result = lua.create_table()?;
+            result.set("url", Url { url: location.url })?;
+            if let Some(source) = location.source {
+                result.set("source", format!("{source:?}"))?;
+            }
+            result.set("stale", location.stale)?;
+            Ok(Some(result))
+        });
+
         methods.add_method("get_metadata", |lua, this, _: ()| {
             let mux = get_mux()?;
             let pane = this.resolve(&mux)?;
@@ -196,6 +259,235 @@ impl UserData for MuxPane {
             Ok(pane.has_unseen_output())
         });
 
+        methods.add_method("get_silence_threshold", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            Ok(pane.get_silence_threshold().map(|t| t.as_secs()))
+        });
+
+        methods.add_method("set_silence_threshold", |_, this, seconds: Option<u64>| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            pane.set_silence_threshold(seconds.map(std::time::Duration::from_secs));
+            Ok(())
+        });
+
+        methods.add_method("get_output_rate", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            Ok(pane.get_output_rate())
+        });
+
+        methods.add_method("is_pinned", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            Ok(pane.is_pinned())
+        });
+
+        methods.add_method("set_pinned", |_, this, pinned: bool| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            pane.set_pinned(pinned);
+            Ok(())
+        });
+
+        methods.add_method("is_ime_enabled", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            Ok(pane.is_ime_enabled())
+        });
+
+        methods.add_method("set_ime_enabled", |_, this, enabled: bool| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            pane.set_ime_enabled(enabled);
+            // Prompt the GUI to re-evaluate the IME state for the window
+            mux.notify(MuxNotification::PaneOutput(pane.pane_id()));
+            Ok(())
+        });
+
+        methods.add_method("set_bidi", |_, this, args: SetBidi| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            pane.set_bidi_mode(args.enabled, args.direction);
+            // Repaint with the lines in their new order
+            mux.notify(MuxNotification::PaneOutput(pane.pane_id()));
+            Ok(())
+        });
+
+        methods.add_method("add_highlight", |_, this, args: AddHighlight| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            let highlights = pane.highlights().ok_or_else(|| {
+                mlua::Error::external("this pane doesn't support highlights".to_string())
+            })?;
+            let id = args.id.unwrap_or_else(|| args.regex.clone());
+            let color = args
+                .color
+                .unwrap_or_else(|| config::configuration().pane_highlight_color);
+            highlights
+                .lock()
+                .add(id.clone(), &args.regex, color)
+                .map_err(|err| mlua::Error::external(format!("{err:#}")))?;
+            // Prompt the GUI to repaint the pane
+            mux.notify(MuxNotification::PaneOutput(pane.pane_id()));
+            Ok(id)
+        });
+
+        methods.add_method("remove_highlight", |_, this, id: String| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            let removed = match pane.highlights() {
+                Some(highlights) => highlights.lock().remove(&id),
+                None => false,
+            };
+            if removed {
+                mux.notify(MuxNotification::PaneOutput(pane.pane_id()));
+            }
+            Ok(removed)
+        });
+
+        methods.add_method("add_annotation", |_, this, args: AddAnnotation| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            let annotations = pane.annotations().ok_or_else(|| {
+                mlua::Error::external("this pane doesn't support annotations".to_string())
+            })?;
+            let dims = pane.get_dimensions();
+            let end = dims.physical_top + dims.viewport_rows as StableRowIndex;
+            if !(dims.scrollback_top..end).contains(&args.line) {
+                return Err(mlua::Error::external(format!(
+                    "line {} is not in the scrollback or on the screen of the pane; \
+                     it must be in the range {}..{}",
+                    args.line, dims.scrollback_top, end
+                )));
+            }
+            let mut annotations = annotations.lock();
+            annotations.prune(dims.scrollback_top);
+            let id = annotations
+                .add(Annotation {
+                    id: 0,
+                    line: args.line,
+                    kind: args
+                        .kind
+                        .unwrap_or_else(|| DEFAULT_ANNOTATION_KIND.to_string()),
+                    color: args
+                        .color
+                        .unwrap_or_else(|| config::configuration().pane_annotation_color),
+                    note: args.note,
+                    tint: args.tint,
+                })
+                .map_err(|err| mlua::Error::external(format!("{err:#}")))?;
+            // Prompt the GUI to repaint the pane
+            mux.notify(MuxNotification::PaneOutput(pane.pane_id()));
+            Ok(id)
+        });
+
+        methods.add_method("remove_annotation", |_, this, id: usize| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            let removed = match pane.annotations() {
+                Some(annotations) => annotations.lock().remove(id),
+                None => false,
+            };
+            if removed {
+                mux.notify(MuxNotification::PaneOutput(pane.pane_id()));
+            }
+            Ok(removed)
+        });
+
+        methods.add_method("clear_annotations", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            if let Some(annotations) = pane.annotations() {
+                annotations.lock().clear();
+                mux.notify(MuxNotification::PaneOutput(pane.pane_id()));
+            }
+            Ok(())
+        });
+
+        methods.add_method("get_annotations", |lua, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            let result = lua.create_table()?;
+            if let Some(annotations) = pane.annotations() {
+                let mut annotations = annotations.lock();
+                annotations.prune(pane.get_dimensions().scrollback_top);
+                for (idx, annotation) in annotations.iter().enumerate() {
+                    result.set(idx + 1, dynamic_to_lua_value(lua, annotation.to_dynamic())?)?;
+                }
+            }
+            Ok(result)
+        });
+
+        methods.add_method("watch", |_, this, args: WatchPane| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            let id = args.id.unwrap_or_else(|| args.pattern.clone());
+            PaneWatch::new(id.clone(), args.pattern, args.regex)
+                .and_then(|watch| pane.add_watch(watch))
+                .map_err(|err| mlua::Error::external(format!("{err:#}")))?;
+            Ok(id)
+        });
+
+        methods.add_method("unwatch", |_, this, id: String| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            Ok(pane.remove_watch(&id))
+        });
+
+        methods.add_method("set_input_encoding", |_, this, encoding: String| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            let encoding: config::PaneEncoding = encoding
+                .parse()
+                .map_err(|err| mlua::Error::external(format!("{err:#}")))?;
+            let current = pane.encoding().ok_or_else(|| {
+                mlua::Error::external("this pane doesn't support changing its encoding".to_string())
+            })?;
+            *current.lock() = encoding;
+            Ok(())
+        });
+
+        methods.add_method("get_input_encoding", |_, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+            Ok(pane
+                .encoding()
+                .map(|encoding| *encoding.lock())
+                .unwrap_or_default()
+                .to_string())
+        });
+
+        methods.add_method(
+            "set_column_guides",
+            |_, this, guides: Option<Vec<config::ColumnGuide>>| {
+                let mux = get_mux()?;
+                let pane = this.resolve(&mux)?;
+                let current = pane.column_guides().ok_or_else(|| {
+                    mlua::Error::external("this pane doesn't support column guides".to_string())
+                })?;
+                *current.lock() = guides;
+                // Prompt the GUI to repaint the pane
+                mux.notify(MuxNotification::PaneOutput(pane.pane_id()));
+                Ok(())
+            },
+        );
+
+        methods.add_method(
+            "link_with",
+            |_, this, (other, options): (UserDataRef<MuxPane>, Option<PaneLinkOptions>)| {
+                let mux = get_mux()?;
+                mux.link_panes(this.0, other.0, options.unwrap_or_default())
+                    .map_err(mlua::Error::external)
+            },
+        );
+
+        methods.add_method("get_links", |_, this, _: ()| {
+            let mux = get_mux()?;
+            Ok(mux.get_pane_links(this.0))
+        });
+
         methods.add_method("is_alt_screen_active", |_, this, _: ()| {
             let mux = get_mux()?;
             let pane = this.resolve(&mux)?;
@@ -353,6 +645,26 @@ impl UserData for MuxPane {
             },
         );
 
+        methods.add_method("get_command_history", |lua, this, _: ()| {
+            let mux = get_mux()?;
+            let pane = this.resolve(&mux)?;
+
+            let zones = pane
+                .get_semantic_zones()
+                .map_err(|e| mlua::Error::external(format!("{:#}", e)))?;
+
+            let mut history = vec![];
+            for record in CommandRecord::from_zones(&zones) {
+                history.push(CommandHistoryEntry {
+                    command: this.get_text_from_semantic_zone(record.input)?,
+                    input: record.input,
+                    output: record.output,
+                    command_status: record.status,
+                });
+            }
+            to_lua(lua, history)
+        });
+
         methods.add_method("get_text_from_semantic_zone", |_lua, this, zone: Value| {
             let zone: SemanticZone = from_lua(zone)?;
             this.get_text_from_semantic_zone(zone)
@@ -366,6 +678,7 @@ impl UserData for MuxPane {
                 end_y,
                 // semantic_type is not used by get_text_from_semantic_zone
                 semantic_type: SemanticType::Output,
+                command_status: None,
             };
             this.get_text_from_semantic_zone(zone)
         });
@@ -428,10 +741,23 @@ impl UserData for MuxPane {
     }
 }
 
+/// An entry in the list returned by `pane:get_command_history()`
+#[derive(Debug, ToDynamic)]
+struct CommandHistoryEntry {
+    command: String,
+    input: SemanticZone,
+    output: Option<SemanticZone>,
+    command_status: Option<CommandStatus>,
+}
+
 #[derive(Debug, Default, FromDynamic, ToDynamic)]
 struct SplitPane {
     #[dynamic(flatten)]
     cmd_builder: CommandBuilderFrag,
+    #[dynamic(default)]
+    inherit_user_vars: bool,
+    #[dynamic(default)]
+    inherit_env: Vec<String>,
     #[dynamic(default = "spawn_tab_default_domain")]
     domain: SpawnTabDomain,
     #[dynamic(default)]
@@ -443,42 +769,132 @@ struct SplitPane {
 }
 impl_lua_conversion_dynamic!(SplitPane);
 
+#[derive(Debug, Default, FromDynamic, ToDynamic)]
+struct OpenMirror {
+    #[dynamic(default)]
+    direction: HandySplitDirection,
+    #[dynamic(default)]
+    top_level: bool,
+    #[dynamic(default = "default_split_size")]
+    size: f32,
+}
+impl_lua_conversion_dynamic!(OpenMirror);
+
+#[derive(Debug, FromDynamic, ToDynamic)]
+struct AddAnnotation {
+    line: StableRowIndex,
+    #[dynamic(default)]
+    kind: Option<String>,
+    #[dynamic(default)]
+    color: Option<config::RgbaColor>,
+    #[dynamic(default)]
+    note: Option<String>,
+    #[dynamic(default)]
+    tint: bool,
+}
+impl_lua_conversion_dynamic!(AddAnnotation);
+
+#[derive(Debug, FromDynamic, ToDynamic)]
+struct AddHighlight {
+    regex: String,
+    #[dynamic(default)]
+    color: Option<config::RgbaColor>,
+    #[dynamic(default)]
+    id: Option<String>,
+}
+impl_lua_conversion_dynamic!(AddHighlight);
+
+#[derive(Debug, FromDynamic, ToDynamic)]
+struct SetBidi {
+    #[dynamic(default)]
+    enabled: Option<bool>,
+    #[dynamic(default)]
+    direction: Option<ParagraphDirectionHint>,
+}
+impl_lua_conversion_dynamic!(SetBidi);
+
+#[derive(Debug, FromDynamic, ToDynamic)]
+struct WatchPane {
+    pattern: String,
+    #[dynamic(default)]
+    regex: bool,
+    #[dynamic(default)]
+    id: Option<String>,
+}
+impl_lua_conversion_dynamic!(WatchPane);
+
+#[derive(Debug, FromDynamic, ToDynamic)]
+struct SaveScrollback {
+    path: PathBuf,
+    #[dynamic(default)]
+    format: ScrollbackExportFormat,
+}
+impl_lua_conversion_dynamic!(SaveScrollback);
+
+#[derive(Debug, Default, FromDynamic, ToDynamic)]
+struct RespawnPane {
+    #[dynamic(flatten)]
+    cmd_builder: CommandBuilderFrag,
+}
+impl_lua_conversion_dynamic!(RespawnPane);
+
+impl RespawnPane {
+    async fn run(&self, pane: &MuxPane) -> mlua::Result<()> {
+        let (command, command_dir) = self.cmd_builder.to_command_builder();
+        let mux = Mux::get();
+        mux.respawn_pane(pane.0, command, command_dir)
+            .await
+            .map_err(|e| mlua::Error::external(format!("{:#?}", e)))
+    }
+}
+
 fn default_split_size() -> f32 {
     0.5
 }
 
+fn split_request(direction: HandySplitDirection, top_level: bool, size: f32) -> SplitRequest {
+    let size = if size == 0.0 {
+        SplitSize::Percent(50)
+    } else if size < 1.0 {
+        SplitSize::Percent((size * 100.).floor() as u8)
+    } else {
+        SplitSize::Cells(size as usize)
+    };
+
+    SplitRequest {
+        direction: match direction {
+            HandySplitDirection::Right | HandySplitDirection::Left => SplitDirection::Horizontal,
+            HandySplitDirection::Top | HandySplitDirection::Bottom => SplitDirection::Vertical,
+        },
+        target_is_second: match direction {
+            HandySplitDirection::Top | HandySplitDirection::Left => false,
+            HandySplitDirection::Bottom | HandySplitDirection::Right => true,
+        },
+        top_level,
+        size,
+    }
+}
+
 impl SplitPane {
     async fn run(&self, pane: &MuxPane) -> mlua::Result<MuxPane> {
         let (command, command_dir) = self.cmd_builder.to_command_builder();
         let source = SplitSource::Spawn {
             command,
             command_dir,
-        };
-
-        let size = if self.size == 0.0 {
-            SplitSize::Percent(50)
-        } else if self.size < 1.0 {
-            SplitSize::Percent((self.size * 100.).floor() as u8)
-        } else {
-            SplitSize::Cells(self.size as usize)
-        };
-
-        let direction = match self.direction {
-            HandySplitDirection::Right | HandySplitDirection::Left => SplitDirection::Horizontal,
-            HandySplitDirection::Top | HandySplitDirection::Bottom => SplitDirection::Vertical,
-        };
-
-        let request = SplitRequest {
-            direction,
-            target_is_second: match self.direction {
-                HandySplitDirection::Top | HandySplitDirection::Left => false,
-                HandySplitDirection::Bottom | HandySplitDirection::Right => true,
+            inherit_env: InheritEnvironment {
+                user_vars: self.inherit_user_vars,
+                names: self.inherit_env.clone(),
             },
-            top_level: self.top_level,
-            size,
         };
 
+        let request = split_request(self.direction, self.top_level, self.size);
+
         let mux = get_mux()?;
+        let _spawn_command = mux.with_spawn_command(SpawnCommand {
+            inherit_user_vars: self.inherit_user_vars,
+            inherit_env: self.inherit_env.clone(),
+            ..self.cmd_builder.to_spawn_command(&self.domain)
+        });
         let (pane, _size) = mux
             .split_pane(pane.0, request, source, self.domain.clone())
             .await
@@ -20,6 +20,16 @@ impl MuxWindow {
         mux.get_window_mut(self.0)
             .ok_or_else(|| mlua::Error::external(format!("window id {} not found in mux", self.0)))
     }
+
+    /// Returns the entry of workspace_styles in the global config
+    /// that matches the workspace of the window, if any
+    fn configured_workspace_style(&self) -> mlua::Result<Option<config::WorkspaceStyle>> {
+        let mux = get_mux()?;
+        let window = self.resolve(&mux)?;
+        Ok(config::configuration()
+            .workspace_style(window.get_workspace())
+            .cloned())
+    }
 }
 
 impl UserData for MuxWindow {
@@ -51,6 +61,28 @@ impl UserData for MuxWindow {
             let mut window = this.resolve_mut(&mux)?;
             Ok(window.set_workspace(&new_name))
         });
+        methods.add_async_method("effective_workspace_style", |lua, this, _: ()| async move {
+            // Config overrides are held by the gui window, so ask it
+            // when there is one
+            let gui_window = async {
+                let wezterm_mod = get_or_create_module(lua, "wezterm").ok()?;
+                let gui: mlua::Table = wezterm_mod.get("gui").ok()?;
+                let func: mlua::Function = gui.get("gui_window_for_mux_window").ok()?;
+                match func.call_async::<_, mlua::Value>(this.0).await.ok()? {
+                    LuaValue::UserData(gui_window) => Some(gui_window),
+                    _ => None,
+                }
+            };
+            if let Some(gui_window) = gui_window.await {
+                return gui_window
+                    .call_async_method::<_, LuaValue>("effective_workspace_style", ())
+                    .await;
+            }
+            match this.configured_workspace_style()? {
+                Some(style) => luahelper::dynamic_to_lua_value(lua, style.to_dynamic()),
+                None => Ok(LuaValue::Nil),
+            }
+        });
         methods.add_async_method("spawn_tab", |_, this, spawn: SpawnTab| async move {
             spawn.spawn(this).await
         });
@@ -0,0 +1,111 @@
+//! Serializes lines as text together with the escape sequences that
+//! reproduce their attributes and hyperlinks, so that the output can
+//! be replayed into a terminal to reproduce the original content.
+use crate::new_wezterm_terminfo_renderer;
+use std::io::Write;
+use termwiz::cell::CellAttributes;
+use termwiz::render::terminfo::TerminfoRenderer;
+use termwiz::render::RenderTty;
+use termwiz::surface::change::Change;
+use termwiz::surface::Line;
+
+pub struct EscapeEncoder {
+    renderer: TerminfoRenderer,
+    /// The attributes in effect at the end of the previous line
+    attr: CellAttributes,
+}
+
+impl EscapeEncoder {
+    pub fn new() -> Self {
+        Self {
+            renderer: new_wezterm_terminfo_renderer(),
+            attr: CellAttributes::blank(),
+        }
+    }
+
+    /// Writes `line` followed by `eol`.
+    /// Trailing blank cells that have the default background are
+    /// emitted as a clear-to-end-of-line sequence rather than as spaces.
+    pub fn line<W: Write>(&mut self, out: &mut W, line: &Line, eol: &str) -> anyhow::Result<()> {
+        let mut changes = line.changes(&self.attr);
+        if !eol.is_empty() {
+            changes.push(Change::Text(eol.to_string()));
+        }
+        if let Some(attr) = line.visible_cells().last().map(|cell| cell.attrs().clone()) {
+            self.attr = attr;
+        }
+        self.changes(out, &changes)
+    }
+
+    /// Writes an arbitrary sequence of changes, such as cursor movement
+    pub fn changes<W: Write>(&mut self, out: &mut W, changes: &[Change]) -> anyhow::Result<()> {
+        self.renderer
+            .render_to(changes, &mut RenderTarget { out })?;
+        Ok(())
+    }
+
+    /// Resets the attributes so that the terminal isn't left styled
+    /// after the output has been replayed
+    pub fn finish<W: Write>(&mut self, out: &mut W) -> anyhow::Result<()> {
+        self.attr = CellAttributes::blank();
+        self.changes(out, &[Change::AllAttributes(CellAttributes::blank())])
+    }
+}
+
+struct RenderTarget<'a, W: Write> {
+    out: &'a mut W,
+}
+
+impl<'a, W: Write> Write for RenderTarget<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl<'a, W: Write> RenderTty for RenderTarget<'a, W> {
+    fn get_size_in_cells(&mut self) -> termwiz::Result<(usize, usize)> {
+        Ok((80, 24))
+    }
+}
+
+/// Renders the lines with escapes, terminating each of them with CRLF
+pub fn lines_to_escapes(lines: Vec<Line>) -> anyhow::Result<String> {
+    let mut encoder = EscapeEncoder::new();
+    let mut out = vec![];
+    for line in &lines {
+        encoder.line(&mut out, line, "\r\n")?;
+    }
+    encoder.finish(&mut out)?;
+    Ok(String::from_utf8(out)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use termwiz::hyperlink::Hyperlink;
+    use termwiz::surface::SEQ_ZERO;
+
+    #[test]
+    fn hyperlinks_and_trailing_blanks() {
+        let mut attr = CellAttributes::blank();
+        attr.set_hyperlink(Some(Arc::new(Hyperlink::new("https://example.com"))));
+        let mut line = Line::from_text("link", &attr, SEQ_ZERO, None);
+        line.append_line(
+            Line::from_text("  plain   ", &CellAttributes::blank(), SEQ_ZERO, None),
+            SEQ_ZERO,
+        );
+
+        let text = lines_to_escapes(vec![line]).unwrap();
+        assert!(
+            text.contains("\x1b]8;;https://example.com\x1b\\link"),
+            "{text:?}"
+        );
+        // The trailing spaces are replaced by clearing to the end of the line
+        assert!(text.contains("  plain\x1b[K\r\n"), "{text:?}");
+    }
+}
@@ -1,11 +1,14 @@
 use chrono::prelude::*;
+use chrono::LocalResult;
 use config::lua::mlua::{self, Lua, MetaMethod, UserData, UserDataMethods, UserDataRef};
 use config::lua::{
     emit_event, get_or_create_module, get_or_create_sub_module, is_event_emission, wrap_callback,
 };
 use config::ConfigSubscription;
 use std::rc::Rc;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 lazy_static::lazy_static! {
     static ref CONFIG_SUBSCRIPTION: Mutex<Option<ConfigSubscription>> = Mutex::new(None);
@@ -20,10 +23,16 @@ fn schedule_all(lua: Option<Rc<mlua::Lua>>) -> mlua::Result<()> {
         let scheduled_events: Vec<UserDataRef<ScheduledEvent>> =
             lua.named_registry_value(SCHEDULED_EVENTS)?;
         lua.set_named_registry_value(SCHEDULED_EVENTS, Vec::<ScheduledEvent>::new())?;
+        let recurring_events: Vec<UserDataRef<RecurringEvent>> =
+            lua.named_registry_value(RECURRING_EVENTS)?;
+        lua.set_named_registry_value(RECURRING_EVENTS, Vec::<RecurringEvent>::new())?;
         let generation = config::configuration().generation();
         for event in scheduled_events {
             event.clone().schedule(generation);
         }
+        for event in recurring_events {
+            event.clone().schedule(generation);
+        }
     }
     Ok(())
 }
@@ -111,6 +120,142 @@ impl UserData for ScheduledEvent {
 }
 
 const SCHEDULED_EVENTS: &str = "wezterm-scheduled-events";
+const RECURRING_EVENTS: &str = "wezterm-recurring-events";
+
+/// Keeps track of `schedule` state
+#[derive(Debug, Clone)]
+struct RecurringEvent {
+    /// The name of the registry entry that will resolve to
+    /// their callback function
+    user_event_id: String,
+    interval: Duration,
+    /// Each call is delayed by a random amount up to this duration
+    jitter: Duration,
+    /// Shared with the handle returned to the user
+    cancelled: Arc<AtomicBool>,
+}
+
+impl RecurringEvent {
+    /// Schedule the recurring task with the scheduler runtime.
+    /// Unlike `ScheduledEvent`, we don't hold on to the lua context
+    /// between calls; we take a fresh reference each time that we
+    /// fire and stop as soon as the configuration generation changes,
+    /// which is what invalidates the handle on reload.
+    fn schedule(self, generation: usize) {
+        promise::spawn::spawn(async move {
+            let mut next = Instant::now() + self.interval;
+            loop {
+                smol::Timer::at(next + self.random_jitter()).await;
+                if !self.is_live(generation) {
+                    break;
+                }
+
+                let user_event_id = self.user_event_id.clone();
+                if let Err(err) = config::with_lua_config_on_main_thread(move |lua| async move {
+                    if let Some(lua) = lua {
+                        call_for_each_gui_window(&lua, user_event_id).await?;
+                    }
+                    Ok(())
+                })
+                .await
+                {
+                    log::error!("while running wezterm.time.schedule callback: {err:#}");
+                }
+
+                // Fire on multiples of the interval so that we don't
+                // drift, but skip any that were missed while the
+                // callback was running rather than calling it back
+                // to back.
+                let now = Instant::now();
+                next += self.interval;
+                while next <= now {
+                    next += self.interval;
+                }
+            }
+        })
+        .detach();
+    }
+
+    fn is_live(&self, generation: usize) -> bool {
+        !self.cancelled.load(Ordering::Relaxed)
+            && config::configuration().generation() == generation
+    }
+
+    fn random_jitter(&self) -> Duration {
+        let jitter_ms = self.jitter.as_millis() as u64;
+        if jitter_ms == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(fastrand::u64(0..=jitter_ms))
+        }
+    }
+}
+
+impl UserData for RecurringEvent {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(_methods: &mut M) {}
+}
+
+/// The handle returned by `wezterm.time.schedule`
+#[derive(Clone)]
+struct ScheduleHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl UserData for ScheduleHandle {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("cancel", |_, this, _: ()| {
+            this.cancelled.store(true, Ordering::Relaxed);
+            Ok(())
+        });
+    }
+}
+
+/// Calls the callback once for each gui window that is still alive,
+/// passing the window as its parameter.  The windows are obtained via
+/// `wezterm.gui.gui_windows()` so that this works without any knowledge
+/// of the gui; when there is no gui, or no windows, the callback is
+/// called once without a window.
+async fn call_for_each_gui_window(lua: &Lua, user_event_id: String) -> mlua::Result<()> {
+    let wezterm_mod = get_or_create_module(lua, "wezterm").map_err(mlua::Error::external)?;
+    let windows = match wezterm_mod.get::<_, Option<mlua::Table>>("gui")? {
+        Some(gui) => match gui.get::<_, Option<mlua::Function>>("gui_windows")? {
+            Some(gui_windows) => gui_windows.call::<_, Vec<mlua::Value>>(())?,
+            None => vec![],
+        },
+        None => vec![],
+    };
+
+    if windows.is_empty() {
+        let args = lua.pack_multi(())?;
+        emit_event(lua, (user_event_id, args)).await?;
+    } else {
+        for window in windows {
+            let args = lua.pack_multi(window)?;
+            emit_event(lua, (user_event_id.clone(), args)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Accepts either a number of seconds, or a string such as "1m"
+/// or "1h 30m"
+fn duration_from_lua(value: mlua::Value, what: &str) -> mlua::Result<Duration> {
+    match value {
+        mlua::Value::Integer(n) if n >= 0 => Ok(Duration::from_secs(n as u64)),
+        mlua::Value::Number(n) if n >= 0. && n.is_finite() => Ok(Duration::from_secs_f64(n)),
+        mlua::Value::String(s) => {
+            let s = s.to_str()?;
+            humantime::parse_duration(s).map_err(|err| {
+                mlua::Error::external(format!("{err:#} while parsing {what} {s:?} as a duration"))
+            })
+        }
+        other => Err(mlua::Error::external(format!(
+            "expected {what} to be a number of seconds or a duration string, \
+             but got a value of type {}",
+            other.type_name()
+        ))),
+    }
+}
 
 pub fn register(lua: &Lua) -> anyhow::Result<()> {
     {
@@ -120,6 +265,7 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
         }
     }
     lua.set_named_registry_value(SCHEDULED_EVENTS, Vec::<ScheduledEvent>::new())?;
+    lua.set_named_registry_value(RECURRING_EVENTS, Vec::<RecurringEvent>::new())?;
     let time_mod = get_or_create_sub_module(lua, "time")?;
 
     time_mod.set(
@@ -140,10 +286,10 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
     time_mod.set(
         "parse",
         lua.create_function(|_, (s, fmt): (String, String)| {
-            let time = DateTime::parse_from_str(&s, &fmt).map_err(|err| {
+            let utc = parse_time(&s, &fmt).map_err(|err| {
                 mlua::Error::external(format!("{err:#} while parsing {s} using format {fmt}"))
             })?;
-            Ok(Time { utc: time.into() })
+            Ok(Time { utc })
         })?,
     )?;
 
@@ -172,6 +318,43 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
         })?,
     )?;
 
+    time_mod.set(
+        "schedule",
+        lua.create_function(|lua, params: mlua::Table| {
+            let interval = duration_from_lua(params.get("interval")?, "interval")?;
+            if interval.is_zero() {
+                return Err(mlua::Error::external("interval must be greater than zero"));
+            }
+            let jitter = match params.get::<_, mlua::Value>("jitter")? {
+                mlua::Value::Nil => Duration::ZERO,
+                value => duration_from_lua(value, "jitter")?,
+            };
+            let callback: mlua::Function = params.get("callback")?;
+            let user_event_id = wrap_callback(lua, callback)?;
+
+            let cancelled = Arc::new(AtomicBool::new(false));
+            let event = RecurringEvent {
+                user_event_id,
+                interval,
+                jitter,
+                cancelled: Arc::clone(&cancelled),
+            };
+
+            if is_event_emission(lua)? {
+                let generation = config::configuration().generation();
+                event.schedule(generation);
+            } else {
+                let recurring_events: Vec<UserDataRef<RecurringEvent>> =
+                    lua.named_registry_value(RECURRING_EVENTS)?;
+                let mut recurring_events: Vec<RecurringEvent> =
+                    recurring_events.into_iter().map(|e| e.clone()).collect();
+                recurring_events.push(event);
+                lua.set_named_registry_value(RECURRING_EVENTS, recurring_events)?;
+            }
+            Ok(ScheduleHandle { cancelled })
+        })?,
+    )?;
+
     // For backwards compatibility
     let wezterm_mod = get_or_create_module(lua, "wezterm")?;
     wezterm_mod.set("sleep_ms", lua.create_async_function(sleep_ms)?)?;
@@ -180,6 +363,30 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Parses s according to fmt.  When fmt doesn't include a timezone,
+/// the time is interpreted as local time, and when it doesn't include
+/// a time of day, midnight is assumed.
+fn parse_time(s: &str, fmt: &str) -> anyhow::Result<DateTime<Utc>> {
+    let err = match DateTime::parse_from_str(s, fmt) {
+        Ok(time) => return Ok(time.into()),
+        Err(err) => err,
+    };
+
+    let naive = match NaiveDateTime::parse_from_str(s, fmt) {
+        Ok(naive) => naive,
+        Err(_) => match NaiveDate::parse_from_str(s, fmt) {
+            Ok(date) => date.and_time(NaiveTime::MIN),
+            Err(_) => return Err(err.into()),
+        },
+    };
+    match Local.from_local_datetime(&naive) {
+        LocalResult::Single(local) => Ok(local.into()),
+        // During a DST transition, pick the earlier of the two
+        LocalResult::Ambiguous(earlier, _) => Ok(earlier.into()),
+        LocalResult::None => anyhow::bail!("{s} doesn't exist in the local timezone"),
+    }
+}
+
 fn strftime_utc<'lua>(_: &'lua Lua, format: String) -> mlua::Result<String> {
     let local: DateTime<Utc> = Utc::now();
     Ok(local.format(&format).to_string())
@@ -215,49 +422,7 @@ impl UserData for Time {
             Ok(this.utc.format(&format).to_string())
         });
         methods.add_method("sun_times", |lua, this, (lat, lon): (f64, f64)| {
-            let info = spa::calc_sunrise_and_set(this.utc, lat, lon)
-                .map_err(|err| mlua::Error::external(format!("{err:#}")))?;
-
-            let times = match info {
-                spa::SunriseAndSet::PolarNight => SunTimes {
-                    rise: None,
-                    set: None,
-                    up: false,
-                    progression: 0.,
-                },
-                spa::SunriseAndSet::PolarDay => SunTimes {
-                    rise: None,
-                    set: None,
-                    up: true,
-                    progression: 0.,
-                },
-                spa::SunriseAndSet::Daylight(rise, set) => {
-                    let progression;
-                    let up = this.utc >= rise && this.utc <= set;
-                    let day_duration = set - rise;
-                    let night_duration = chrono::Duration::days(1) - day_duration;
-                    if this.utc < rise {
-                        // Sun hasn't yet risen
-                        progression = (night_duration - (rise - this.utc)).num_minutes() as f64
-                            / night_duration.num_minutes() as f64;
-                    } else if up {
-                        // Sun is up
-                        progression = (this.utc - rise).num_minutes() as f64
-                            / day_duration.num_minutes() as f64;
-                    } else {
-                        // time is after sunset
-                        progression = (this.utc - set).num_minutes() as f64
-                            / night_duration.num_minutes() as f64;
-                    };
-                    SunTimes {
-                        rise: Some(Time { utc: rise }),
-                        set: Some(Time { utc: set }),
-                        up,
-                        progression,
-                    }
-                }
-            };
-
+            let times = this.sun_times(lat, lon)?;
             let tbl = lua.create_table()?;
             tbl.set("rise", times.rise)?;
             tbl.set("set", times.set)?;
@@ -265,6 +430,57 @@ impl UserData for Time {
             tbl.set("progression", times.progression)?;
             Ok(tbl)
         });
+        methods.add_method("sunrise_sunset", |_, this, (lat, lon): (f64, f64)| {
+            let times = this.sun_times(lat, lon)?;
+            Ok((times.rise, times.set))
+        });
+    }
+}
+
+impl Time {
+    fn sun_times(&self, lat: f64, lon: f64) -> mlua::Result<SunTimes> {
+        let info = spa::calc_sunrise_and_set(self.utc, lat, lon)
+            .map_err(|err| mlua::Error::external(format!("{err:#}")))?;
+
+        Ok(match info {
+            spa::SunriseAndSet::PolarNight => SunTimes {
+                rise: None,
+                set: None,
+                up: false,
+                progression: 0.,
+            },
+            spa::SunriseAndSet::PolarDay => SunTimes {
+                rise: None,
+                set: None,
+                up: true,
+                progression: 0.,
+            },
+            spa::SunriseAndSet::Daylight(rise, set) => {
+                let progression;
+                let up = self.utc >= rise && self.utc <= set;
+                let day_duration = set - rise;
+                let night_duration = chrono::Duration::days(1) - day_duration;
+                if self.utc < rise {
+                    // Sun hasn't yet risen
+                    progression = (night_duration - (rise - self.utc)).num_minutes() as f64
+                        / night_duration.num_minutes() as f64;
+                } else if up {
+                    // Sun is up
+                    progression =
+                        (self.utc - rise).num_minutes() as f64 / day_duration.num_minutes() as f64;
+                } else {
+                    // time is after sunset
+                    progression =
+                        (self.utc - set).num_minutes() as f64 / night_duration.num_minutes() as f64;
+                };
+                SunTimes {
+                    rise: Some(Time { utc: rise }),
+                    set: Some(Time { utc: set }),
+                    up,
+                    progression,
+                }
+            }
+        })
     }
 }
 
@@ -274,3 +490,51 @@ struct SunTimes {
     up: bool,
     progression: f64,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn local(y: i32, m: u32, d: u32, hh: u32, mm: u32, ss: u32) -> DateTime<Utc> {
+        Local
+            .with_ymd_and_hms(y, m, d, hh, mm, ss)
+            .earliest()
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn parse_with_timezone() {
+        assert_eq!(
+            parse_time("2024-03-10 12:30:00 +0200", "%Y-%m-%d %H:%M:%S %z").unwrap(),
+            Utc.with_ymd_and_hms(2024, 3, 10, 10, 30, 0).unwrap()
+        );
+        assert_eq!(
+            parse_time("2024-03-10T12:30:00-05:00", "%+").unwrap(),
+            Utc.with_ymd_and_hms(2024, 3, 10, 17, 30, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_without_timezone_is_local() {
+        assert_eq!(
+            parse_time("2024-07-01 08:15:30", "%Y-%m-%d %H:%M:%S").unwrap(),
+            local(2024, 7, 1, 8, 15, 30)
+        );
+    }
+
+    #[test]
+    fn parse_without_time_is_midnight() {
+        assert_eq!(
+            parse_time("2024-07-01", "%Y-%m-%d").unwrap(),
+            local(2024, 7, 1, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse_time("not a time", "%Y-%m-%d").is_err());
+        assert!(parse_time("2024-02-30", "%Y-%m-%d").is_err());
+        assert!(parse_time("2024-07-01", "%Y-%m-%d %H:%M:%S").is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! Per-pane annotations that draw attention to lines without
+//! changing the terminal content.
+//! Each annotation is attached to a line by its stable row index, so
+//! it moves along with the line as it scrolls, and it is dropped once
+//! that line is no longer part of the scrollback.
+//! Annotations are kept in memory only.
+use config::RgbaColor;
+use std::ops::Range;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+use wezterm_term::StableRowIndex;
+
+/// The maximum number of annotations that a pane may have
+pub const MAX_ANNOTATIONS: usize = 256;
+
+/// The kind of annotation that is added when none is specified
+pub const DEFAULT_ANNOTATION_KIND: &str = "flag";
+
+fn default_kind() -> String {
+    DEFAULT_ANNOTATION_KIND.to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, FromDynamic, ToDynamic)]
+pub struct Annotation {
+    /// Assigned when the annotation is added to a pane
+    #[dynamic(default)]
+    pub id: usize,
+    pub line: StableRowIndex,
+    /// A label chosen by the user, which can be used to tell
+    /// annotations apart
+    #[dynamic(default = "default_kind")]
+    pub kind: String,
+    /// The color of the marker in the gutter
+    pub color: RgbaColor,
+    #[dynamic(default)]
+    pub note: Option<String>,
+    /// Whether the background of the line is tinted with `color`
+    #[dynamic(default)]
+    pub tint: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct PaneAnnotations {
+    /// Ordered by line, and then by the order in which they were added
+    annotations: Vec<Annotation>,
+    next_id: usize,
+    /// Incremented whenever the set of annotations changes
+    generation: usize,
+}
+
+impl PaneAnnotations {
+    /// Adds an annotation, returning the id that was assigned to it
+    pub fn add(&mut self, mut annotation: Annotation) -> anyhow::Result<usize> {
+        anyhow::ensure!(
+            self.annotations.len() < MAX_ANNOTATIONS,
+            "a pane may have at most {MAX_ANNOTATIONS} annotations"
+        );
+        annotation.id = self.next_id;
+        self.next_id += 1;
+        let idx = self
+            .annotations
+            .partition_point(|existing| existing.line <= annotation.line);
+        self.annotations.insert(idx, annotation);
+        self.generation += 1;
+        Ok(self.next_id - 1)
+    }
+
+    /// Removes the annotation with the specified id.
+    /// Returns true if there was such an annotation.
+    pub fn remove(&mut self, id: usize) -> bool {
+        let len = self.annotations.len();
+        self.annotations.retain(|a| a.id != id);
+        if self.annotations.len() == len {
+            return false;
+        }
+        self.generation += 1;
+        true
+    }
+
+    /// Removes the annotations of `annotation.line` if it has any,
+    /// otherwise adds `annotation`.
+    /// Returns true if the annotation was added.
+    pub fn toggle(&mut self, annotation: Annotation) -> anyhow::Result<bool> {
+        let len = self.annotations.len();
+        self.annotations.retain(|a| a.line != annotation.line);
+        if self.annotations.len() != len {
+            self.generation += 1;
+            return Ok(false);
+        }
+        self.add(annotation)?;
+        Ok(true)
+    }
+
+    pub fn clear(&mut self) {
+        if !self.annotations.is_empty() {
+            self.annotations.clear();
+            self.generation += 1;
+        }
+    }
+
+    /// Drops the annotations of lines above `first_row`,
+    /// which have left the scrollback
+    pub fn prune(&mut self, first_row: StableRowIndex) {
+        let count = self.annotations.partition_point(|a| a.line < first_row);
+        if count > 0 {
+            self.annotations.drain(0..count);
+            self.generation += 1;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.annotations.is_empty()
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Annotation> {
+        self.annotations.iter()
+    }
+
+    /// Returns the annotations of the lines in `range`
+    pub fn in_range(&self, range: Range<StableRowIndex>) -> &[Annotation] {
+        let start = self.annotations.partition_point(|a| a.line < range.start);
+        let end = self.annotations.partition_point(|a| a.line < range.end);
+        &self.annotations[start..end.max(start)]
+    }
+
+    /// Returns the lines that have annotations, in order
+    pub fn lines(&self) -> Vec<StableRowIndex> {
+        let mut lines: Vec<StableRowIndex> = self.annotations.iter().map(|a| a.line).collect();
+        lines.dedup();
+        lines
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn flag(line: StableRowIndex) -> Annotation {
+        Annotation {
+            id: 0,
+            line,
+            kind: default_kind(),
+            color: RgbaColor::default(),
+            note: None,
+            tint: false,
+        }
+    }
+
+    #[test]
+    fn track_lines() {
+        let mut annotations = PaneAnnotations::default();
+        let a = annotations.add(flag(10)).unwrap();
+        let b = annotations.add(flag(2)).unwrap();
+        let c = annotations.add(flag(10)).unwrap();
+        assert_eq!(annotations.lines(), vec![2, 10]);
+        assert_eq!(
+            annotations
+                .in_range(5..11)
+                .iter()
+                .map(|a| a.id)
+                .collect::<Vec<_>>(),
+            vec![a, c]
+        );
+        assert!(annotations.in_range(3..5).is_empty());
+
+        // Toggling removes every annotation of the line
+        assert!(!annotations.toggle(flag(10)).unwrap());
+        assert_eq!(annotations.lines(), vec![2]);
+        assert!(annotations.toggle(flag(4)).unwrap());
+
+        // Lines that leave the scrollback lose their annotations
+        annotations.prune(3);
+        assert_eq!(annotations.lines(), vec![4]);
+        assert!(!annotations.remove(b));
+    }
+
+    #[test]
+    fn capped() {
+        let mut annotations = PaneAnnotations::default();
+        for line in 0..MAX_ANNOTATIONS {
+            annotations.add(flag(line as StableRowIndex)).unwrap();
+        }
+        assert!(annotations.add(flag(0)).is_err());
+        let generation = annotations.generation();
+        annotations.clear();
+        assert!(annotations.is_empty());
+        assert_ne!(annotations.generation(), generation);
+    }
+}
@@ -7,6 +7,7 @@ use std::sync::Arc;
 use std::time::SystemTime;
 
 static CLIENT_ID: AtomicUsize = AtomicUsize::new(0);
+static SESSION_ID: AtomicUsize = AtomicUsize::new(1);
 lazy_static::lazy_static! {
     static ref EPOCH: u64 = SystemTime::now()
                                 .duration_since(SystemTime::UNIX_EPOCH)
@@ -42,6 +43,14 @@ impl ClientId {
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
 pub struct ClientInfo {
     pub client_id: Arc<ClientId>,
+    /// Identifies the connection of this client to the server,
+    /// for use with `wezterm cli kill-client`
+    pub session_id: usize,
+    /// Describes how the client is connected, such as the
+    /// address of a TLS client
+    pub peer: Option<String>,
+    /// The version of the mux protocol that the client speaks
+    pub protocol_version: Option<usize>,
     /// The time this client last connected
     #[serde(with = "ts_seconds")]
     pub connected_at: DateTime<Utc>,
@@ -58,6 +67,9 @@ impl ClientInfo {
     pub fn new(client_id: Arc<ClientId>) -> Self {
         Self {
             client_id,
+            session_id: SESSION_ID.fetch_add(1, Ordering::Relaxed),
+            peer: None,
+            protocol_version: None,
             connected_at: Utc::now(),
             active_workspace: None,
             last_input: Utc::now(),
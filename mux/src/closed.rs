@@ -0,0 +1,738 @@
+//! Keeps the panes and tabs that the user closed recently, so that
+//! one that was closed by mistake can be reopened by ReopenClosedPane
+//! or ReopenClosedTab.
+//!
+//! Only closes that the user asked for are recorded; panes whose
+//! process exited are not.  Reopening spawns the command afresh in the
+//! place that the pane occupied and restores the old scrollback above
+//! the output of the new process.  The history is bounded by age, by
+//! the number of entries for each window and by the approximate size
+//! of the scrollback that it holds; see `closed_pane_retention_seconds`.
+use crate::domain::DomainId;
+use crate::pane::{CachePolicy, Pane, PaneId};
+use crate::tab::{PaneNode, SplitDirection, TabId};
+use crate::window::WindowId;
+use config::keyassignment::SpawnCommand;
+use config::ConfigHandle;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use termwiz::cell::{CellAttributes, Intensity};
+use termwiz::surface::SEQ_ZERO;
+use url::Url;
+use wezterm_term::{Line, StableRowIndex, TerminalSize};
+
+/// Where a closed pane was in the split tree of its tab
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PanePlacement {
+    /// The pane next to it
+    pub neighbour: PaneId,
+    pub direction: SplitDirection,
+    /// true if it was to the right of or below its neighbour
+    pub second: bool,
+    /// Its width or height in cells, depending on the direction
+    pub cells: usize,
+    /// true if it was split from the whole of the tab
+    pub top_level: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClosedPane {
+    pub pane_id: PaneId,
+    pub domain_id: DomainId,
+    pub title: String,
+    pub working_dir: Option<Url>,
+    /// The command that spawned the pane, if it is known
+    pub command: Option<SpawnCommand>,
+    pub placement: Option<PanePlacement>,
+    /// The scrollback and screen of the pane, oldest first
+    pub lines: Vec<Line>,
+}
+
+/// The arrangement of the panes of a closed tab
+#[derive(Debug, Clone)]
+pub enum ClosedLayout {
+    Pane(ClosedPane),
+    Split {
+        direction: SplitDirection,
+        /// The size of the second part, in cells
+        second_cells: usize,
+        first: Box<ClosedLayout>,
+        second: Box<ClosedLayout>,
+    },
+}
+
+impl ClosedLayout {
+    /// Returns the panes in the order that they appear in the tree
+    pub fn panes(&self) -> Vec<&ClosedPane> {
+        match self {
+            Self::Pane(pane) => vec![pane],
+            Self::Split { first, second, .. } => {
+                let mut panes = first.panes();
+                panes.extend(second.panes());
+                panes
+            }
+        }
+    }
+
+    fn panes_mut(&mut self) -> Vec<&mut ClosedPane> {
+        match self {
+            Self::Pane(pane) => vec![pane],
+            Self::Split { first, second, .. } => {
+                let mut panes = first.panes_mut();
+                panes.extend(second.panes_mut());
+                panes
+            }
+        }
+    }
+
+    /// Returns the pane that will occupy the space of the whole
+    /// layout before it is split up
+    pub fn first_pane(&self) -> &ClosedPane {
+        match self {
+            Self::Pane(pane) => pane,
+            Self::Split { first, .. } => first.first_pane(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClosedTab {
+    pub title: String,
+    /// Whether the title was set by the user
+    pub title_is_override: bool,
+    /// The position of the tab in its window
+    pub tab_index: usize,
+    pub layout: ClosedLayout,
+}
+
+#[derive(Debug, Clone)]
+pub enum ClosedKind {
+    Pane(ClosedPane),
+    Tab(ClosedTab),
+}
+
+#[derive(Debug, Clone)]
+pub struct ClosedItem {
+    /// Identifies the item in listings
+    pub id: usize,
+    pub window_id: WindowId,
+    pub tab_id: TabId,
+    pub closed_at: Instant,
+    pub kind: ClosedKind,
+    bytes: usize,
+}
+
+impl ClosedItem {
+    fn panes(&self) -> Vec<&ClosedPane> {
+        match &self.kind {
+            ClosedKind::Pane(pane) => vec![pane],
+            ClosedKind::Tab(tab) => tab.layout.panes(),
+        }
+    }
+
+    fn panes_mut(&mut self) -> Vec<&mut ClosedPane> {
+        match &mut self.kind {
+            ClosedKind::Pane(pane) => vec![pane],
+            ClosedKind::Tab(tab) => tab.layout.panes_mut(),
+        }
+    }
+
+    /// Discards the oldest lines of scrollback until the
+    /// item is estimated to use no more than `max_bytes`
+    fn trim_to(&mut self, max_bytes: usize) {
+        let mut bytes = self.bytes;
+        for pane in self.panes_mut() {
+            let mut drop_count = 0;
+            for line in &pane.lines {
+                if bytes <= max_bytes {
+                    break;
+                }
+                bytes -= line_bytes(line);
+                drop_count += 1;
+            }
+            pane.lines.drain(0..drop_count);
+        }
+        self.bytes = bytes;
+    }
+}
+
+/// A summary of a closed pane or tab, as reported by
+/// `wezterm cli list-closed`.
+/// This type is used directly by the codec, take care to bump
+/// the codec version if you change this
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClosedItemInfo {
+    pub id: usize,
+    pub window_id: WindowId,
+    pub tab_id: TabId,
+    pub is_tab: bool,
+    pub title: String,
+    pub working_dir: Option<String>,
+    pub num_panes: usize,
+    pub num_lines: usize,
+    pub bytes: usize,
+    pub closed_secs_ago: u64,
+    pub expires_in_secs: u64,
+}
+
+/// How much of the history is kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionLimits {
+    pub duration: Duration,
+    /// The number of items kept for each window
+    pub max_entries: usize,
+    /// The size of all of the items together
+    pub max_bytes: usize,
+}
+
+impl RetentionLimits {
+    pub fn from_config(config: &ConfigHandle) -> Self {
+        Self {
+            duration: Duration::from_secs(config.closed_pane_retention_seconds),
+            max_entries: config.closed_pane_retention_max_entries,
+            max_bytes: config.closed_pane_retention_max_bytes,
+        }
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.duration.is_zero() || self.max_entries == 0
+    }
+}
+
+/// The approximate number of bytes used to keep `line`,
+/// once it has been compressed for the scrollback
+fn line_bytes(line: &Line) -> usize {
+    let mut attribute_runs = 0;
+    let mut last_attrs: Option<CellAttributes> = None;
+    for cell in line.visible_cells() {
+        if last_attrs.as_ref() != Some(cell.attrs()) {
+            attribute_runs += 1;
+            last_attrs = Some(cell.attrs().clone());
+        }
+    }
+    std::mem::size_of::<Line>()
+        + line.as_str().len()
+        + attribute_runs * std::mem::size_of::<CellAttributes>()
+}
+
+/// How many rows `capture_pane` fetches from the pane at a time
+const CAPTURE_CHUNK_ROWS: StableRowIndex = 256;
+
+#[derive(Debug, Default)]
+pub struct ClosedHistory {
+    /// Oldest first
+    items: VecDeque<ClosedItem>,
+    next_id: usize,
+}
+
+impl ClosedHistory {
+    pub fn push(
+        &mut self,
+        window_id: WindowId,
+        tab_id: TabId,
+        kind: ClosedKind,
+        limits: RetentionLimits,
+        now: Instant,
+    ) {
+        if limits.is_disabled() {
+            self.items.clear();
+            return;
+        }
+        let mut item = ClosedItem {
+            id: self.next_id,
+            window_id,
+            tab_id,
+            closed_at: now,
+            kind,
+            bytes: 0,
+        };
+        self.next_id += 1;
+        item.bytes = item
+            .panes()
+            .iter()
+            .flat_map(|pane| pane.lines.iter())
+            .map(line_bytes)
+            .sum();
+        item.trim_to(limits.max_bytes);
+        self.items.push_back(item);
+        self.prune(limits, now);
+    }
+
+    /// Discards the items that have expired or that exceed the limits,
+    /// oldest first
+    pub fn prune(&mut self, limits: RetentionLimits, now: Instant) {
+        if limits.is_disabled() {
+            self.items.clear();
+            return;
+        }
+        self.items
+            .retain(|item| now.saturating_duration_since(item.closed_at) < limits.duration);
+
+        let mut idx = 0;
+        while idx < self.items.len() {
+            let window_id = self.items[idx].window_id;
+            let newer_in_window = self
+                .items
+                .iter()
+                .skip(idx)
+                .filter(|item| item.window_id == window_id)
+                .count();
+            if newer_in_window > limits.max_entries {
+                self.items.remove(idx);
+            } else {
+                idx += 1;
+            }
+        }
+
+        let mut total: usize = self.items.iter().map(|item| item.bytes).sum();
+        while total > limits.max_bytes {
+            match self.items.pop_front() {
+                Some(item) => total -= item.bytes,
+                None => break,
+            }
+        }
+    }
+
+    /// Removes and returns the most recently closed pane, or tab if
+    /// `tab` is true, of `window_id`
+    pub fn take_latest(
+        &mut self,
+        window_id: WindowId,
+        tab: bool,
+        limits: RetentionLimits,
+        now: Instant,
+    ) -> Option<ClosedItem> {
+        self.prune(limits, now);
+        let idx = self.items.iter().rposition(|item| {
+            item.window_id == window_id && matches!(item.kind, ClosedKind::Tab(_)) == tab
+        })?;
+        self.items.remove(idx)
+    }
+
+    pub fn list(&mut self, limits: RetentionLimits, now: Instant) -> Vec<ClosedItemInfo> {
+        self.prune(limits, now);
+        self.items
+            .iter()
+            .rev()
+            .map(|item| {
+                let panes = item.panes();
+                let age = now.saturating_duration_since(item.closed_at);
+                let (is_tab, title) = match &item.kind {
+                    ClosedKind::Pane(pane) => (false, pane.title.clone()),
+                    ClosedKind::Tab(tab) if !tab.title.is_empty() => (true, tab.title.clone()),
+                    ClosedKind::Tab(tab) => (true, tab.layout.first_pane().title.clone()),
+                };
+                ClosedItemInfo {
+                    id: item.id,
+                    window_id: item.window_id,
+                    tab_id: item.tab_id,
+                    is_tab,
+                    title,
+                    working_dir: panes[0].working_dir.as_ref().map(|url| url.to_string()),
+                    num_panes: panes.len(),
+                    num_lines: panes.iter().map(|pane| pane.lines.len()).sum(),
+                    bytes: item.bytes,
+                    closed_secs_ago: age.as_secs(),
+                    expires_in_secs: limits.duration.saturating_sub(age).as_secs(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Takes a copy of what is needed to reopen `pane`.
+/// The scrollback is copied from the bottom up, a chunk at a time,
+/// and copying stops once about `max_bytes` have been taken, so that
+/// a large scrollback isn't cloned only to be trimmed afterwards.
+pub fn capture_pane(
+    pane: &Arc<dyn Pane>,
+    command: Option<SpawnCommand>,
+    placement: Option<PanePlacement>,
+    max_bytes: usize,
+) -> ClosedPane {
+    let dims = pane.get_dimensions();
+    let mut end = dims.physical_top + dims.viewport_rows as StableRowIndex;
+    // Newest first until the end
+    let mut lines = vec![];
+    let mut bytes = 0;
+    // The blank rows below the output aren't worth keeping
+    let mut skipping_blank = true;
+    'capture: while end > dims.scrollback_top {
+        let start = end
+            .saturating_sub(CAPTURE_CHUNK_ROWS)
+            .max(dims.scrollback_top);
+        let (_first_row, chunk) = pane.get_lines(start..end);
+        for mut line in chunk.into_iter().rev() {
+            if skipping_blank && line.is_whitespace() {
+                continue;
+            }
+            skipping_blank = false;
+            if bytes >= max_bytes {
+                break 'capture;
+            }
+            line.compress_for_scrollback();
+            bytes += line_bytes(&line);
+            lines.push(line);
+        }
+        end = start;
+    }
+    lines.reverse();
+    ClosedPane {
+        pane_id: pane.pane_id(),
+        domain_id: pane.domain_id(),
+        title: pane.get_title(),
+        working_dir: pane.get_current_working_dir(CachePolicy::AllowStale),
+        command,
+        placement,
+        lines,
+    }
+}
+
+/// Places the scrollback of a closed pane above the output of `target`,
+/// the pane that reopened it, followed by a dimmed line that marks where
+/// the restored scrollback ends
+pub fn restore_scrollback(target: &Arc<dyn Pane>, mut lines: Vec<Line>) -> anyhow::Result<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let mut attrs = CellAttributes::default();
+    attrs.set_intensity(Intensity::Half);
+    lines.push(Line::from_text(
+        "── restored from closed pane ──",
+        &attrs,
+        SEQ_ZERO,
+        None,
+    ));
+    target.prepend_scrollback(lines)
+}
+
+fn cells(direction: SplitDirection, size: &TerminalSize) -> usize {
+    match direction {
+        SplitDirection::Horizontal => size.cols,
+        SplitDirection::Vertical => size.rows,
+    }
+}
+
+fn is_leaf(node: &PaneNode, pane_id: PaneId) -> bool {
+    matches!(node, PaneNode::Leaf(entry) if entry.pane_id == pane_id)
+}
+
+fn first_leaf(node: &PaneNode) -> Option<PaneId> {
+    match node {
+        PaneNode::Empty => None,
+        PaneNode::Leaf(entry) => Some(entry.pane_id),
+        PaneNode::Split { left, .. } => first_leaf(left),
+    }
+}
+
+fn last_leaf(node: &PaneNode) -> Option<PaneId> {
+    match node {
+        PaneNode::Empty => None,
+        PaneNode::Leaf(entry) => Some(entry.pane_id),
+        PaneNode::Split { right, .. } => last_leaf(right),
+    }
+}
+
+/// Works out where `pane_id` is in `tree`, the split tree of its
+/// tab, so that a pane can be put back there once it has closed
+pub fn placement_of(tree: &PaneNode, pane_id: PaneId) -> Option<PanePlacement> {
+    fn placement_in(node: &PaneNode, pane_id: PaneId, top_level: bool) -> Option<PanePlacement> {
+        let (left, right, split) = match node {
+            PaneNode::Split { left, right, node } => (left, right, node),
+            _ => return None,
+        };
+        if is_leaf(left, pane_id) {
+            return Some(PanePlacement {
+                neighbour: first_leaf(right)?,
+                direction: split.direction,
+                second: false,
+                cells: cells(split.direction, &split.first),
+                top_level,
+            });
+        }
+        if is_leaf(right, pane_id) {
+            return Some(PanePlacement {
+                neighbour: last_leaf(left)?,
+                direction: split.direction,
+                second: true,
+                cells: cells(split.direction, &split.second),
+                top_level,
+            });
+        }
+        placement_in(left, pane_id, false).or_else(|| placement_in(right, pane_id, false))
+    }
+    placement_in(tree, pane_id, true)
+}
+
+/// Captures the panes of `tree` along with their arrangement.
+/// `capture` is called for each pane that still exists.
+pub fn capture_layout(
+    tree: &PaneNode,
+    capture: &mut dyn FnMut(PaneId) -> Option<ClosedPane>,
+) -> Option<ClosedLayout> {
+    match tree {
+        PaneNode::Empty => None,
+        PaneNode::Leaf(entry) => capture(entry.pane_id).map(ClosedLayout::Pane),
+        PaneNode::Split { left, right, node } => {
+            let first = capture_layout(left, capture);
+            let second = capture_layout(right, capture);
+            match (first, second) {
+                (Some(first), Some(second)) => Some(ClosedLayout::Split {
+                    direction: node.direction,
+                    second_cells: cells(node.direction, &node.second),
+                    first: Box::new(first),
+                    second: Box::new(second),
+                }),
+                (first, second) => first.or(second),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::renderable::StableCursorPosition;
+    use crate::tab::{PaneEntry, SplitDirectionAndSize};
+
+    fn size(cols: usize, rows: usize) -> TerminalSize {
+        TerminalSize {
+            cols,
+            rows,
+            ..TerminalSize::default()
+        }
+    }
+
+    fn leaf(pane_id: PaneId, cols: usize, rows: usize) -> PaneNode {
+        PaneNode::Leaf(PaneEntry {
+            window_id: 0,
+            tab_id: 0,
+            pane_id,
+            title: String::new(),
+            size: size(cols, rows),
+            working_dir: None,
+            is_active_pane: false,
+            is_zoomed_pane: false,
+            is_floating: false,
+            workspace: "default".to_string(),
+            cursor_pos: StableCursorPosition::default(),
+            physical_top: 0,
+            top_row: 0,
+            left_col: 0,
+            tty_name: None,
+            links: vec![],
+            nesting_depth: 0,
+        })
+    }
+
+    fn split(direction: SplitDirection, left: PaneNode, right: PaneNode) -> PaneNode {
+        let (first, second) = match (&left, &right) {
+            (PaneNode::Leaf(first), PaneNode::Leaf(second)) => (first.size, second.size),
+            (PaneNode::Leaf(first), _) => (first.size, size(39, 24)),
+            _ => unreachable!(),
+        };
+        PaneNode::Split {
+            left: Box::new(left),
+            right: Box::new(right),
+            node: SplitDirectionAndSize {
+                direction,
+                first,
+                second,
+            },
+        }
+    }
+
+    /// Pane 1 on the left, with pane 2 above pane 3 on the right
+    fn tree() -> PaneNode {
+        split(
+            SplitDirection::Horizontal,
+            leaf(1, 40, 24),
+            split(SplitDirection::Vertical, leaf(2, 39, 10), leaf(3, 39, 13)),
+        )
+    }
+
+    fn pane(title: &str, lines: usize) -> ClosedPane {
+        ClosedPane {
+            pane_id: 0,
+            domain_id: 0,
+            title: title.to_string(),
+            working_dir: None,
+            command: None,
+            placement: None,
+            lines: (0..lines)
+                .map(|idx| Line::from_text(&idx.to_string(), &CellAttributes::blank(), 1, None))
+                .collect(),
+        }
+    }
+
+    fn limits(max_entries: usize, max_bytes: usize) -> RetentionLimits {
+        RetentionLimits {
+            duration: Duration::from_secs(60),
+            max_entries,
+            max_bytes,
+        }
+    }
+
+    fn titles(history: &mut ClosedHistory, limits: RetentionLimits, now: Instant) -> Vec<String> {
+        history
+            .list(limits, now)
+            .into_iter()
+            .map(|info| info.title)
+            .collect()
+    }
+
+    #[test]
+    fn bounded_by_count_and_age() {
+        let now = Instant::now();
+        let limits = limits(2, usize::MAX);
+        let mut history = ClosedHistory::default();
+        for title in ["a", "b", "c"] {
+            history.push(0, 0, ClosedKind::Pane(pane(title, 1)), limits, now);
+        }
+        history.push(1, 0, ClosedKind::Pane(pane("other", 1)), limits, now);
+        // The count is per window
+        assert_eq!(titles(&mut history, limits, now), vec!["other", "c", "b"]);
+
+        let later = now + Duration::from_secs(61);
+        assert!(titles(&mut history, limits, later).is_empty());
+    }
+
+    #[test]
+    fn bounded_by_bytes() {
+        let now = Instant::now();
+        let per_line = line_bytes(&pane("", 1).lines[0]);
+        let limits = limits(10, per_line * 3);
+        let mut history = ClosedHistory::default();
+
+        // A single item that is too large loses its oldest lines
+        history.push(0, 0, ClosedKind::Pane(pane("big", 5)), limits, now);
+        let item = history.take_latest(0, false, limits, now).unwrap();
+        match item.kind {
+            ClosedKind::Pane(pane) => assert_eq!(pane.lines[0].as_str(), "2"),
+            _ => unreachable!(),
+        }
+
+        history.push(0, 0, ClosedKind::Pane(pane("a", 2)), limits, now);
+        history.push(0, 0, ClosedKind::Pane(pane("b", 2)), limits, now);
+        assert_eq!(titles(&mut history, limits, now), vec!["b"]);
+    }
+
+    #[test]
+    fn placement() {
+        let tree = tree();
+        assert_eq!(
+            placement_of(&tree, 1),
+            Some(PanePlacement {
+                neighbour: 2,
+                direction: SplitDirection::Horizontal,
+                second: false,
+                cells: 40,
+                top_level: true,
+            })
+        );
+        assert_eq!(
+            placement_of(&tree, 2),
+            Some(PanePlacement {
+                neighbour: 3,
+                direction: SplitDirection::Vertical,
+                second: false,
+                cells: 10,
+                top_level: false,
+            })
+        );
+        assert_eq!(
+            placement_of(&tree, 3),
+            Some(PanePlacement {
+                neighbour: 2,
+                direction: SplitDirection::Vertical,
+                second: true,
+                cells: 13,
+                top_level: false,
+            })
+        );
+        assert_eq!(placement_of(&tree, 4), None);
+        // A pane that has the whole tab to itself has no placement
+        assert_eq!(placement_of(&leaf(1, 80, 24), 1), None);
+        assert_eq!(placement_of(&PaneNode::Empty, 1), None);
+    }
+
+    #[test]
+    fn layout() {
+        let mut capture = |pane_id: PaneId| {
+            Some(ClosedPane {
+                pane_id,
+                ..pane("", 0)
+            })
+        };
+        let layout = capture_layout(&tree(), &mut capture).unwrap();
+        let ids: Vec<PaneId> = layout.panes().iter().map(|pane| pane.pane_id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        match &layout {
+            ClosedLayout::Split {
+                direction: SplitDirection::Horizontal,
+                second_cells: 39,
+                second,
+                ..
+            } => assert!(matches!(
+                **second,
+                ClosedLayout::Split {
+                    direction: SplitDirection::Vertical,
+                    second_cells: 13,
+                    ..
+                }
+            )),
+            _ => panic!("unexpected layout {:?}", layout),
+        }
+
+        // A pane that no longer exists is left out, and its sibling
+        // takes the place of the split that held them
+        let mut capture = |pane_id: PaneId| {
+            if pane_id == 2 {
+                None
+            } else {
+                Some(ClosedPane {
+                    pane_id,
+                    ..pane("", 0)
+                })
+            }
+        };
+        let layout = capture_layout(&tree(), &mut capture).unwrap();
+        assert_eq!(layout.first_pane().pane_id, 1);
+        match &layout {
+            ClosedLayout::Split {
+                direction: SplitDirection::Horizontal,
+                second,
+                ..
+            } => assert!(matches!(&**second, ClosedLayout::Pane(pane) if pane.pane_id == 3)),
+            _ => panic!("unexpected layout {:?}", layout),
+        }
+
+        assert!(capture_layout(&tree(), &mut |_| None).is_none());
+        assert!(capture_layout(&PaneNode::Empty, &mut capture).is_none());
+    }
+
+    #[test]
+    fn bytes_include_attributes() {
+        let plain = Line::from_text("hello", &CellAttributes::blank(), 1, None);
+        let mut styled = plain.clone();
+        let mut attrs = CellAttributes::blank();
+        attrs.set_intensity(Intensity::Bold);
+        styled.overlay_text_with_attribute(2, "llo", attrs, 1);
+        assert_eq!(styled.as_str(), "hello");
+        assert!(line_bytes(&styled) > line_bytes(&plain));
+    }
+
+    #[test]
+    fn disabled() {
+        let now = Instant::now();
+        let mut history = ClosedHistory::default();
+        let disabled = RetentionLimits {
+            duration: Duration::ZERO,
+            ..limits(10, usize::MAX)
+        };
+        history.push(0, 0, ClosedKind::Pane(pane("a", 1)), disabled, now);
+        assert!(history.take_latest(0, false, disabled, now).is_none());
+    }
+}
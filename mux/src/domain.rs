@@ -5,23 +5,27 @@
 //! container or actually remote, running on the other end
 //! of an ssh session somewhere.
 
+use crate::encoding::Encoder;
 use crate::localpane::LocalPane;
 use crate::pane::{alloc_pane_id, Pane, PaneId};
-use crate::tab::{SplitRequest, Tab, TabId};
+use crate::tab::{FloatingPaneGeometry, SplitRequest, Tab, TabId};
 use crate::window::WindowId;
 use crate::Mux;
 use anyhow::{bail, Context, Error};
 use async_trait::async_trait;
 use config::keyassignment::{SpawnCommand, SpawnTabDomain};
-use config::{configuration, ExecDomain, SerialDomain, ValueOrFunc, WslDomain};
+use config::{ExecDomain, PaneEncoding, SerialDomain, ValueOrFunc, WslDomain};
 use downcast_rs::{impl_downcast, Downcast};
 use parking_lot::Mutex;
 use portable_pty::{native_pty_system, CommandBuilder, ExitStatus, MasterPty, PtySize, PtySystem};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::ffi::OsString;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use wezterm_term::TerminalSize;
 
 static DOMAIN_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
@@ -33,6 +37,20 @@ pub enum DomainState {
     Attached,
 }
 
+/// The health of the connection between a domain and the remote mux
+/// that it represents.  It is kept up to date as traffic flows, so that
+/// reading it doesn't require a round trip.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionStats {
+    /// The most recently measured round trip time
+    pub rtt: Option<Duration>,
+    /// When data was last received from the remote mux
+    pub last_traffic: Option<Instant>,
+    /// The number of times that the connection has been re-established
+    pub reconnects: usize,
+    pub connected: bool,
+}
+
 pub fn alloc_domain_id() -> DomainId {
     DOMAIN_ID.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed)
 }
@@ -42,10 +60,109 @@ pub enum SplitSource {
     Spawn {
         command: Option<CommandBuilder>,
         command_dir: Option<String>,
+        inherit_env: InheritEnvironment,
     },
     MovePane(PaneId),
 }
 
+/// The environment that a spawned command takes from the pane
+/// from which it was spawned
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InheritEnvironment {
+    /// Export all of the user vars of the source pane
+    pub user_vars: bool,
+    /// The variables to take from the user vars of the source pane,
+    /// or failing that, from the environment of its foreground process
+    pub names: Vec<String>,
+}
+
+impl InheritEnvironment {
+    pub fn from_spawn_command(spawn: &SpawnCommand) -> Self {
+        Self {
+            user_vars: spawn.inherit_user_vars,
+            names: spawn.inherit_env.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.user_vars && self.names.is_empty()
+    }
+
+    /// Resolves the variables that are inherited from `pane`
+    pub fn resolve(&self, pane: &dyn Pane) -> HashMap<String, String> {
+        if self.is_empty() {
+            return HashMap::new();
+        }
+        self.resolve_from(pane.copy_user_vars(), &|| pane.get_foreground_process_env())
+    }
+
+    /// Resolves the inherited variables from the user vars of the
+    /// source pane and the environment of its foreground process
+    fn resolve_from(
+        &self,
+        user_vars: HashMap<String, String>,
+        foreground_process_env: &dyn Fn() -> Option<HashMap<String, String>>,
+    ) -> HashMap<String, String> {
+        let mut env = HashMap::new();
+        if self.user_vars {
+            env.extend(user_vars.clone());
+        }
+
+        let mut process_env = None;
+        for name in &self.names {
+            if let Some(value) = user_vars.get(name) {
+                env.insert(name.clone(), value.clone());
+                continue;
+            }
+            // Reading the environment of the process is comparatively
+            // expensive, so only do it if a user var is missing
+            let process_env =
+                process_env.get_or_insert_with(|| foreground_process_env().unwrap_or_default());
+            if let Some(value) = process_env.get(name) {
+                env.insert(name.clone(), value.clone());
+            }
+        }
+
+        // User vars may have names that can't be used in the environment
+        env.retain(|name, _| !name.is_empty() && !name.contains(['=', '\0']));
+        env
+    }
+
+    /// Merges the variables that are inherited from `pane` into `command`
+    pub fn apply(
+        &self,
+        pane: &dyn Pane,
+        command: Option<CommandBuilder>,
+    ) -> Option<CommandBuilder> {
+        Self::merge(self.resolve(pane), command)
+    }
+
+    /// Merges `env`, the inherited variables, into `command`, producing
+    /// a command for the default program if there is none.  Variables
+    /// that were explicitly set on `command` take precedence over those
+    /// that are inherited.
+    pub fn merge(
+        env: HashMap<String, String>,
+        command: Option<CommandBuilder>,
+    ) -> Option<CommandBuilder> {
+        if env.is_empty() {
+            return command;
+        }
+
+        let mut command = command.unwrap_or_else(CommandBuilder::new_default_prog);
+        let explicit: Vec<String> = command
+            .iter_extra_env_as_str()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        for (name, value) in env {
+            if !explicit.contains(&name) {
+                command.env(name, value);
+            }
+        }
+        Some(command)
+    }
+}
+
 #[async_trait(?Send)]
 pub trait Domain: Downcast + Send + Sync {
     /// Spawn a new command within this domain
@@ -102,7 +219,14 @@ pub trait Domain: Downcast + Send + Sync {
             SplitSource::Spawn {
                 command,
                 command_dir,
+                inherit_env,
             } => {
+                // The pane that is being split is the source of
+                // any inherited environment
+                let command = match mux.get_pane(pane_id) {
+                    Some(pane) => inherit_env.apply(&*pane, command),
+                    None => command,
+                };
                 self.spawn_pane(split_size.second, command, command_dir)
                     .await?
             }
@@ -131,6 +255,24 @@ pub trait Domain: Downcast + Send + Sync {
         Ok(pane)
     }
 
+    /// Spawns a pane of `size` that floats above the panes of `tab_id`,
+    /// placed according to `geometry`
+    async fn spawn_floating_pane(
+        &self,
+        tab_id: TabId,
+        geometry: FloatingPaneGeometry,
+        size: TerminalSize,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+    ) -> anyhow::Result<Arc<dyn Pane>> {
+        let tab = Mux::get()
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow::anyhow!("Invalid tab id {}", tab_id))?;
+        let pane = self.spawn_pane(size, command, command_dir).await?;
+        tab.add_floating_pane(&pane, geometry);
+        Ok(pane)
+    }
+
     async fn spawn_pane(
         &self,
         size: TerminalSize,
@@ -151,6 +293,32 @@ pub trait Domain: Downcast + Send + Sync {
         Ok(None)
     }
 
+    /// Replace the process running in the specified pane with a
+    /// newly spawned one, keeping the pane, its scrollback and its
+    /// position in the tab.  If command is None, the command that
+    /// was originally used to spawn the pane is re-used.
+    async fn respawn_pane(
+        &self,
+        pane_id: PaneId,
+        _command: Option<CommandBuilder>,
+        _command_dir: Option<String>,
+    ) -> anyhow::Result<()> {
+        anyhow::bail!(
+            "Unable to respawn pane {pane_id}: domain {} does not support respawning panes",
+            self.domain_name()
+        );
+    }
+
+    /// Resolves the variables that a command spawned from `pane`, a pane
+    /// of this domain, inherits from it according to `inherit_env`
+    async fn resolve_inherited_env(
+        &self,
+        pane: &Arc<dyn Pane>,
+        inherit_env: &InheritEnvironment,
+    ) -> anyhow::Result<HashMap<String, String>> {
+        Ok(inherit_env.resolve(&**pane))
+    }
+
     /// Returns false if the `spawn` method will never succeed.
     /// There are some internal placeholder domains that are
     /// pre-created with local UI that we do not want to allow
@@ -173,6 +341,10 @@ pub trait Domain: Downcast + Send + Sync {
     /// Should be a short identifier.
     fn domain_name(&self) -> &str;
 
+    /// Returns the kind of domain, such as `local` or `unix`.
+    /// This is reported by `wezterm cli list-domains`.
+    fn domain_type(&self) -> &'static str;
+
     /// Returns a label describing the domain.
     async fn domain_label(&self) -> String {
         self.domain_name().to_string()
@@ -186,6 +358,16 @@ pub trait Domain: Downcast + Send + Sync {
 
     /// Indicates the state of the domain
     fn state(&self) -> DomainState;
+
+    /// Returns the health of the connection to the remote mux, for
+    /// domains that connect to one
+    fn connection_stats(&self) -> Option<ConnectionStats> {
+        None
+    }
+
+    /// Called when the user copies `text` from `pane_id`, which
+    /// belongs to this domain, to the local clipboard
+    fn text_copied_from_pane(&self, _pane_id: PaneId, _text: &str) {}
 }
 impl_downcast!(Domain);
 
@@ -239,6 +421,10 @@ impl LocalDomain {
         if let Some(baud) = serial_domain.baud {
             serial.set_baud_rate(serial::BaudRate::from_speed(baud));
         }
+        if serial_domain.reconnect {
+            let interval = serial_domain.reconnect_interval_ms.unwrap_or(1000).max(100);
+            serial.set_reconnect_interval(Some(std::time::Duration::from_millis(interval)));
+        }
         let pty_system = Box::new(serial);
         Ok(Self::with_pty_system(&serial_domain.name, pty_system))
     }
@@ -248,6 +434,50 @@ impl LocalDomain {
         false
     }
 
+    /// Creates a pane for a process whose pty was handed off to us by
+    /// another wezterm process, such as when a tab is moved from the gui
+    /// into the mux server.  The terminal is restored from the snapshot
+    /// before any further output is read from the pty.
+    #[cfg(unix)]
+    pub fn adopt_pane(
+        &self,
+        handoff: crate::handoff::PaneHandoff,
+    ) -> anyhow::Result<Arc<dyn Pane>> {
+        let pane_id = alloc_pane_id();
+        let master = portable_pty::unix::master_pty_from_fd(
+            filedescriptor::FileDescriptor::new(handoff.fd),
+            handoff.tty_name,
+        )?;
+        let writer = WriterWrapper::new(master.take_writer()?);
+
+        let mut terminal = wezterm_term::Terminal::new(
+            handoff.size,
+            std::sync::Arc::new(config::TermConfig::new()),
+            "WezTerm",
+            config::wezterm_version(),
+            Box::new(writer.clone()),
+        );
+        terminal.restore_snapshot(handoff.snapshot);
+
+        let pane: Arc<dyn Pane> = Arc::new(
+            LocalPane::new(
+                pane_id,
+                terminal,
+                Box::new(crate::handoff::AdoptedChild::new(handoff.pid)),
+                master,
+                Box::new(writer.clone()),
+                self.id,
+                handoff.command_description,
+            )
+            .with_encoding_of(&writer),
+        );
+
+        let mux = Mux::get();
+        mux.add_pane(&pane)?;
+
+        Ok(pane)
+    }
+
     #[cfg(windows)]
     fn is_conpty(&self) -> bool {
         let pty_system = self.pty_system.lock();
@@ -324,6 +554,7 @@ impl LocalDomain {
                 set_environment_variables,
                 cwd,
                 position: None,
+                ..Default::default()
             };
 
             let spawn_command = config::with_lua_config_on_main_thread(|lua| async {
@@ -442,15 +673,15 @@ impl LocalDomain {
         command_dir: Option<String>,
         pane_id: PaneId,
     ) -> anyhow::Result<CommandBuilder> {
-        let config = configuration();
+        let config = Mux::get().spawn_config();
         let mut cmd = match command {
-            Some(mut cmd) => {
+            Some(mut cmd) if !cmd.is_default_prog() => {
                 config.apply_cmd_defaults(&mut cmd, config.default_cwd.as_ref());
                 cmd
             }
-            None => {
+            command => {
                 let wsl = self.resolve_wsl_domain();
-                config.build_prog(
+                let mut cmd = config.build_prog(
                     None,
                     wsl.as_ref()
                         .map(|wsl| wsl.default_prog.as_ref())
@@ -458,7 +689,22 @@ impl LocalDomain {
                     wsl.as_ref()
                         .map(|wsl| wsl.default_cwd.as_ref())
                         .unwrap_or(config.default_cwd.as_ref()),
-                )?
+                )?;
+                // A command for the default program can still carry
+                // environment, such as that inherited from another pane.
+                // The environment from the config takes precedence over
+                // it, as it does for an explicit command.
+                if let Some(command) = command {
+                    for (name, value) in command.iter_extra_env_as_str() {
+                        if !config.set_environment_variables.contains_key(name) {
+                            cmd.env(name, value);
+                        }
+                    }
+                    if let Some(cwd) = command.get_cwd() {
+                        cmd.cwd(cwd);
+                    }
+                }
+                cmd
             }
         };
         if let Some(dir) = command_dir {
@@ -476,27 +722,60 @@ impl LocalDomain {
     }
 }
 
+/// Returns the command line of `command`, unless it stands in for
+/// the default program
+fn explicit_argv(command: Option<&CommandBuilder>) -> Option<Vec<String>> {
+    command.filter(|cmd| !cmd.is_default_prog()).map(|cmd| {
+        cmd.get_argv()
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    })
+}
+
 /// Allows sharing the writer between the Pane and the Terminal.
 /// This could potentially be eliminated in the future if we can
 /// teach the Pane impl to reference the writer in the Termninal,
 /// but the Pane trait returns a RefMut and that makes it a bit
 /// awkward at the moment.
+/// It also encodes input according to the encoding selected for the pane.
 #[derive(Clone)]
 pub(crate) struct WriterWrapper {
     writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    encoding: Arc<Mutex<PaneEncoding>>,
+    encoder: Arc<Mutex<Encoder>>,
 }
 
 impl WriterWrapper {
     pub fn new(writer: Box<dyn Write + Send>) -> Self {
         Self {
             writer: Arc::new(Mutex::new(writer)),
+            encoding: Arc::new(Mutex::new(PaneEncoding::default())),
+            encoder: Arc::new(Mutex::new(Encoder::default())),
         }
     }
+
+    /// Replace the underlying writer for all clones of this wrapper
+    pub fn replace(&self, writer: Box<dyn Write + Send>) {
+        *self.writer.lock() = writer;
+    }
+
+    /// Returns the encoding shared by all clones of this wrapper
+    pub fn encoding(&self) -> Arc<Mutex<PaneEncoding>> {
+        Arc::clone(&self.encoding)
+    }
 }
 
 impl std::io::Write for WriterWrapper {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.writer.lock().write(buf)
+        let encoding = *self.encoding.lock();
+        let mut encoder = self.encoder.lock();
+        let encoded = encoder.encode(encoding, buf);
+        if let Cow::Borrowed(_) = encoded {
+            return self.writer.lock().write(buf);
+        }
+        self.writer.lock().write_all(&encoded)?;
+        Ok(buf.len())
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
@@ -582,6 +861,7 @@ impl Domain for LocalDomain {
         command_dir: Option<String>,
     ) -> anyhow::Result<Arc<dyn Pane>> {
         let pane_id = alloc_pane_id();
+        let spawn_argv = explicit_argv(command.as_ref());
         let cmd = self
             .build_command(command, command_dir, pane_id)
             .await
@@ -603,7 +883,7 @@ impl Domain for LocalDomain {
             },
             self.name
         );
-        let child_result = pair.slave.spawn_command(cmd);
+        let child_result = pair.slave.spawn_command(cmd.clone());
         let mut writer = WriterWrapper::new(pair.master.take_writer()?);
 
         let mut terminal = wezterm_term::Terminal::new(
@@ -618,15 +898,21 @@ impl Domain for LocalDomain {
         }
 
         let pane: Arc<dyn Pane> = match child_result {
-            Ok(child) => Arc::new(LocalPane::new(
-                pane_id,
-                terminal,
-                child,
-                pair.master,
-                Box::new(writer),
-                self.id,
-                command_description,
-            )),
+            Ok(child) => {
+                let pane = LocalPane::new(
+                    pane_id,
+                    terminal,
+                    child,
+                    pair.master,
+                    Box::new(writer.clone()),
+                    self.id,
+                    command_description,
+                )
+                .with_encoding_of(&writer);
+                pane.set_respawn_info(cmd, writer);
+                pane.set_spawn_argv(spawn_argv);
+                Arc::new(pane)
+            }
             Err(err) => {
                 // Show the error to the user in the new pane
                 write!(writer, "{err:#}").ok();
@@ -652,6 +938,68 @@ impl Domain for LocalDomain {
         Ok(pane)
     }
 
+    async fn respawn_pane(
+        &self,
+        pane_id: PaneId,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+    ) -> anyhow::Result<()> {
+        let mux = Mux::get();
+        let pane = mux
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow::anyhow!("pane {pane_id} not found"))?;
+        let local_pane = pane
+            .downcast_ref::<LocalPane>()
+            .ok_or_else(|| anyhow::anyhow!("pane {pane_id} is not a local pane"))?;
+
+        // Only an explicit command replaces the recorded command line
+        let spawn_argv = command.as_ref().map(|command| explicit_argv(Some(command)));
+        let cmd = match command {
+            Some(command) => self
+                .build_command(Some(command), command_dir, pane_id)
+                .await
+                .context("build_command")?,
+            None => {
+                // The retained command has already been through
+                // build_command, so only apply the cwd override
+                let mut cmd = local_pane.respawn_command().ok_or_else(|| {
+                    anyhow::anyhow!("pane {pane_id} was not spawned with a command")
+                })?;
+                if let Some(dir) = command_dir {
+                    cmd.cwd(dir);
+                }
+                cmd
+            }
+        };
+
+        let pair = self.pty_system.lock().openpty(local_pane.pty_size()?)?;
+        let command_description = format!(
+            "\"{}\" in domain \"{}\"",
+            cmd.as_unix_command_line()
+                .ok()
+                .filter(|line| !line.is_empty())
+                .unwrap_or_else(|| cmd.get_shell()),
+            self.name
+        );
+        let writer = pair.master.take_writer()?;
+
+        local_pane.terminate_for_respawn().await?;
+        let child = match pair.slave.spawn_command(cmd.clone()) {
+            Ok(child) => child,
+            Err(err) => {
+                local_pane.abandon_respawn();
+                return Err(err);
+            }
+        };
+
+        local_pane.respawn(child, pair.master, writer, cmd, command_description)?;
+        if let Some(argv) = spawn_argv {
+            local_pane.set_spawn_argv(argv);
+        }
+
+        Ok(())
+    }
+
     fn domain_id(&self) -> DomainId {
         self.id
     }
@@ -660,6 +1008,16 @@ impl Domain for LocalDomain {
         &self.name
     }
 
+    fn domain_type(&self) -> &'static str {
+        if self.resolve_exec_domain().is_some() {
+            "exec"
+        } else if self.resolve_wsl_domain().is_some() {
+            "wsl"
+        } else {
+            "local"
+        }
+    }
+
     async fn domain_label(&self) -> String {
         if let Some(ed) = self.resolve_exec_domain() {
             match &ed.label {
@@ -718,3 +1076,85 @@ impl Domain for LocalDomain {
         DomainState::Attached
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn inherit(user_vars: bool, names: &[&str]) -> InheritEnvironment {
+        InheritEnvironment {
+            user_vars,
+            names: names.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn resolve_prefers_user_vars() {
+        let reads = Cell::new(0);
+        let process_env = || {
+            reads.set(reads.get() + 1);
+            Some(vars(&[("FOO", "process"), ("BAR", "process")]))
+        };
+        let user_vars = vars(&[("FOO", "user"), ("OTHER", "user")]);
+
+        let env = inherit(false, &["FOO"]).resolve_from(user_vars.clone(), &process_env);
+        assert_eq!(env, vars(&[("FOO", "user")]));
+        // The process isn't consulted when the user vars suffice
+        assert_eq!(reads.get(), 0);
+
+        let env = inherit(false, &["FOO", "BAR", "MISSING"])
+            .resolve_from(user_vars.clone(), &process_env);
+        assert_eq!(env, vars(&[("FOO", "user"), ("BAR", "process")]));
+        // and it is consulted once however many names are missing
+        assert_eq!(reads.get(), 1);
+
+        let env = inherit(true, &["BAR"]).resolve_from(user_vars, &process_env);
+        assert_eq!(
+            env,
+            vars(&[("FOO", "user"), ("OTHER", "user"), ("BAR", "process")])
+        );
+    }
+
+    #[test]
+    fn resolve_skips_invalid_names() {
+        let user_vars = vars(&[
+            ("", "empty"),
+            ("A=B", "equals"),
+            ("NUL\0", "nul"),
+            ("OK", "ok"),
+        ]);
+        let env = inherit(true, &[]).resolve_from(user_vars, &|| None);
+        assert_eq!(env, vars(&[("OK", "ok")]));
+    }
+
+    #[test]
+    fn merge_keeps_explicit_env() {
+        let mut command = CommandBuilder::new("top");
+        command.env("FOO", "explicit");
+        let command = InheritEnvironment::merge(
+            vars(&[("FOO", "inherited"), ("BAR", "inherited")]),
+            Some(command),
+        )
+        .unwrap();
+        assert_eq!(command.get_argv(), &vec![OsString::from("top")]);
+        assert_eq!(command.get_env("FOO").unwrap(), "explicit");
+        assert_eq!(command.get_env("BAR").unwrap(), "inherited");
+    }
+
+    #[test]
+    fn merge_into_default_prog() {
+        assert!(InheritEnvironment::merge(HashMap::new(), None).is_none());
+
+        let command = InheritEnvironment::merge(vars(&[("FOO", "inherited")]), None).unwrap();
+        assert!(command.is_default_prog());
+        assert_eq!(command.get_env("FOO").unwrap(), "inherited");
+    }
+}
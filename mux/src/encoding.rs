@@ -0,0 +1,215 @@
+//! Transcoding between UTF-8 and the legacy single byte encodings
+//! that may be selected for a pane via `PaneEncoding`.
+//! Output from the pty is decoded to UTF-8 before it is parsed,
+//! so the terminal model and scrollback only ever hold UTF-8.
+//! Input sent to the pty is encoded where the characters are
+//! representable, and replaced with `?` otherwise; it is encoded as
+//! a stream, so that a character split across two writes is encoded
+//! intact.
+//!
+//! The supported legacy encodings are stateless and map ASCII to
+//! itself, so switching encodings at runtime never disturbs a
+//! partially received escape sequence.
+use config::PaneEncoding;
+use std::borrow::Cow;
+
+/// The characters that correspond to bytes 0x80-0xff in code page 437.
+/// Bytes below 0x80 are treated as ASCII, so that control characters
+/// and escape sequences keep working.
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', //
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', //
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', //
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', //
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', //
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', //
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', //
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+fn decode_byte(encoding: PaneEncoding, b: u8) -> char {
+    match encoding {
+        PaneEncoding::Cp437 if b >= 0x80 => CP437_HIGH[(b - 0x80) as usize],
+        _ => b as char,
+    }
+}
+
+fn encode_char(encoding: PaneEncoding, c: char) -> Option<u8> {
+    if c.is_ascii() {
+        return Some(c as u8);
+    }
+    match encoding {
+        PaneEncoding::Utf8 => None,
+        PaneEncoding::Latin1 => u8::try_from(c as u32).ok(),
+        PaneEncoding::Cp437 => CP437_HIGH
+            .iter()
+            .position(|&h| h == c)
+            .map(|idx| idx as u8 + 0x80),
+    }
+}
+
+/// Decodes output read from the pty into UTF-8
+pub fn decode(encoding: PaneEncoding, data: &[u8]) -> Cow<[u8]> {
+    if encoding == PaneEncoding::Utf8 || data.is_ascii() {
+        return Cow::Borrowed(data);
+    }
+    let text: String = data.iter().map(|&b| decode_byte(encoding, b)).collect();
+    Cow::Owned(text.into_bytes())
+}
+
+/// Encodes UTF-8 input for sending to the pty.
+/// The input may be written in pieces that split a character, so the
+/// incomplete tail of one piece is held back until the next one
+/// completes it.
+#[derive(Debug, Default)]
+pub struct Encoder {
+    pending: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn encode<'a>(&mut self, encoding: PaneEncoding, data: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.pending.is_empty() && (encoding == PaneEncoding::Utf8 || data.is_ascii()) {
+            return Cow::Borrowed(data);
+        }
+
+        let mut input = std::mem::take(&mut self.pending);
+        input.extend_from_slice(data);
+
+        let mut result = Vec::with_capacity(input.len());
+        let mut remaining = &input[..];
+        while !remaining.is_empty() {
+            let (valid, rest) = match std::str::from_utf8(remaining) {
+                Ok(text) => (text, &[][..]),
+                Err(err) => {
+                    let (valid, rest) = remaining.split_at(err.valid_up_to());
+                    let valid = std::str::from_utf8(valid).expect("validated above");
+                    match err.error_len() {
+                        // The input ends part way through a character
+                        None => {
+                            self.pending = rest.to_vec();
+                            (valid, &[][..])
+                        }
+                        Some(len) => {
+                            log::warn!("invalid UTF-8 in input; sending '?' instead");
+                            self.push_str(encoding, valid, &mut result);
+                            result.push(b'?');
+                            remaining = &rest[len..];
+                            continue;
+                        }
+                    }
+                }
+            };
+            self.push_str(encoding, valid, &mut result);
+            remaining = rest;
+        }
+        Cow::Owned(result)
+    }
+
+    fn push_str(&self, encoding: PaneEncoding, text: &str, result: &mut Vec<u8>) {
+        if encoding == PaneEncoding::Utf8 {
+            result.extend_from_slice(text.as_bytes());
+            return;
+        }
+        for c in text.chars() {
+            match encode_char(encoding, c) {
+                Some(b) => result.push(b),
+                None => {
+                    log::warn!("{c:?} cannot be represented in {encoding}; sending '?' instead");
+                    result.push(b'?');
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cp437_box_drawing() {
+        let decoded = decode(PaneEncoding::Cp437, b"\x1b[1m\xc9\xcd\xbb\x1b[0m");
+        assert_eq!(
+            std::str::from_utf8(&decoded).unwrap(),
+            "\u{1b}[1m╔═╗\u{1b}[0m"
+        );
+        assert_eq!(
+            Encoder::default()
+                .encode(PaneEncoding::Cp437, "╔═╗".as_bytes())
+                .as_ref(),
+            b"\xc9\xcd\xbb"
+        );
+    }
+
+    #[test]
+    fn latin1_round_trip() {
+        let decoded = decode(PaneEncoding::Latin1, b"caf\xe9");
+        assert_eq!(std::str::from_utf8(&decoded).unwrap(), "café");
+        let mut encoder = Encoder::default();
+        assert_eq!(
+            encoder.encode(PaneEncoding::Latin1, &decoded).as_ref(),
+            b"caf\xe9"
+        );
+        assert_eq!(
+            encoder
+                .encode(PaneEncoding::Latin1, "日".as_bytes())
+                .as_ref(),
+            b"?"
+        );
+        assert_eq!(
+            decode(PaneEncoding::Utf8, "日".as_bytes()).as_ref(),
+            "日".as_bytes()
+        );
+    }
+
+    #[test]
+    fn split_characters() {
+        let mut encoder = Encoder::default();
+        let e_acute = "é".as_bytes();
+        assert_eq!(
+            encoder
+                .encode(PaneEncoding::Latin1, &[b'a', e_acute[0]])
+                .as_ref(),
+            b"a"
+        );
+        assert_eq!(
+            encoder
+                .encode(PaneEncoding::Latin1, &[e_acute[1], b'b'])
+                .as_ref(),
+            b"\xe9b"
+        );
+
+        let box_char = "╔".as_bytes();
+        assert_eq!(
+            encoder.encode(PaneEncoding::Cp437, &box_char[..1]).as_ref(),
+            b""
+        );
+        assert_eq!(
+            encoder
+                .encode(PaneEncoding::Cp437, &box_char[1..2])
+                .as_ref(),
+            b""
+        );
+        assert_eq!(
+            encoder.encode(PaneEncoding::Cp437, &box_char[2..]).as_ref(),
+            b"\xc9"
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        let mut encoder = Encoder::default();
+        assert_eq!(
+            encoder.encode(PaneEncoding::Latin1, b"a\xffb").as_ref(),
+            b"a?b"
+        );
+        // A character that was held back is completed even after
+        // switching to UTF-8
+        let e_acute = "é".as_bytes();
+        encoder.encode(PaneEncoding::Latin1, &e_acute[..1]);
+        assert_eq!(
+            encoder.encode(PaneEncoding::Utf8, &e_acute[1..]).as_ref(),
+            e_acute
+        );
+    }
+}
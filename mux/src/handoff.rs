@@ -0,0 +1,85 @@
+//! Support for handing a running pane off to another wezterm process,
+//! such as moving a tab from the gui into a mux server that is running
+//! on the same machine.
+//! The pty master is passed between the processes, so the program
+//! running in the pane keeps running and is not aware of the move.
+use crate::pane::PaneId;
+use portable_pty::{Child, ChildKiller, ExitStatus};
+use std::io::Result as IoResult;
+use std::os::fd::OwnedFd;
+use std::path::PathBuf;
+use std::time::Duration;
+use wezterm_term::{TerminalSize, TerminalSnapshot};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Everything needed to reconstruct a local pane in another process
+pub struct PaneHandoff {
+    /// The id of the pane in the process that is handing it off
+    pub pane_id: PaneId,
+    /// The pty master
+    pub fd: OwnedFd,
+    /// The process that was spawned into the pty
+    pub pid: u32,
+    pub tty_name: Option<PathBuf>,
+    pub command_description: String,
+    pub size: TerminalSize,
+    pub snapshot: TerminalSnapshot,
+}
+
+/// Represents a process whose pty was handed off to us by the process
+/// that spawned it.  We cannot wait for a process that is not our child,
+/// so we poll for its existence instead.  Its exit status is not
+/// available to us, so it is reported as successful.
+#[derive(Debug)]
+pub struct AdoptedChild {
+    pid: u32,
+}
+
+impl AdoptedChild {
+    pub fn new(pid: u32) -> Self {
+        Self { pid }
+    }
+}
+
+impl ChildKiller for AdoptedChild {
+    fn kill(&mut self) -> IoResult<()> {
+        // Same as dropping the pty for a regular child
+        if unsafe { libc::kill(self.pid as libc::pid_t, libc::SIGHUP) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+        Box::new(Self { pid: self.pid })
+    }
+}
+
+impl Child for AdoptedChild {
+    fn try_wait(&mut self) -> IoResult<Option<ExitStatus>> {
+        if unsafe { libc::kill(self.pid as libc::pid_t, 0) } == 0 {
+            return Ok(None);
+        }
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::ESRCH) {
+            Ok(Some(ExitStatus::with_exit_code(0)))
+        } else {
+            // Most likely EPERM, which means that it is still running
+            Ok(None)
+        }
+    }
+
+    fn wait(&mut self) -> IoResult<ExitStatus> {
+        loop {
+            if let Some(status) = self.try_wait()? {
+                return Ok(status);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        Some(self.pid)
+    }
+}
@@ -0,0 +1,167 @@
+//! Persistent per-pane highlighting of text that matches a regex.
+//! Unlike search, highlights don't capture input and remain in
+//! effect until they are explicitly removed.
+use config::RgbaColor;
+use fancy_regex::Regex;
+use std::ops::Range;
+use termwiz::surface::Line;
+
+/// The maximum number of highlights that a pane may have
+pub const MAX_HIGHLIGHTS: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct Highlight {
+    pub id: String,
+    pub regex: Regex,
+    pub color: RgbaColor,
+}
+
+#[derive(Debug, Default)]
+pub struct PaneHighlights {
+    highlights: Vec<Highlight>,
+    /// Incremented whenever the set of highlights changes, so that
+    /// renderers know to recompute their cached matches
+    generation: usize,
+}
+
+impl PaneHighlights {
+    /// Adds a highlight, replacing any existing highlight with the same id
+    pub fn add(&mut self, id: String, pattern: &str, color: RgbaColor) -> anyhow::Result<()> {
+        let regex = Regex::new(pattern)?;
+        if let Some(existing) = self.highlights.iter_mut().find(|h| h.id == id) {
+            existing.regex = regex;
+            existing.color = color;
+        } else {
+            anyhow::ensure!(
+                self.highlights.len() < MAX_HIGHLIGHTS,
+                "a pane may have at most {MAX_HIGHLIGHTS} highlights"
+            );
+            self.highlights.push(Highlight { id, regex, color });
+        }
+        self.generation += 1;
+        Ok(())
+    }
+
+    /// Removes the highlight with the specified id.
+    /// Returns true if there was such a highlight.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let len = self.highlights.len();
+        self.highlights.retain(|h| h.id != id);
+        if self.highlights.len() == len {
+            return false;
+        }
+        self.generation += 1;
+        true
+    }
+
+    /// Removes the highlight with the specified id if it exists,
+    /// otherwise adds it. Returns true if the highlight was added.
+    pub fn toggle(&mut self, id: String, pattern: &str, color: RgbaColor) -> anyhow::Result<bool> {
+        if self.remove(&id) {
+            Ok(false)
+        } else {
+            self.add(id, pattern, color)?;
+            Ok(true)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        if !self.highlights.is_empty() {
+            self.highlights.clear();
+            self.generation += 1;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.highlights.is_empty()
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Returns the ranges of cells in `line` that match the highlights,
+    /// together with the color to use for each range.
+    /// Later highlights are drawn over earlier ones.
+    pub fn matches(&self, line: &Line) -> Vec<(Range<usize>, RgbaColor)> {
+        let mut result = vec![];
+        if self.highlights.is_empty() {
+            return result;
+        }
+
+        // Map byte offsets in the text of the line to cell indices
+        let mut text = String::new();
+        let mut cells: Vec<(usize, Range<usize>)> = vec![];
+        for cell in line.visible_cells() {
+            let idx = cell.cell_index();
+            cells.push((text.len(), idx..idx + cell.width().max(1)));
+            text.push_str(cell.str());
+        }
+        if text.trim().is_empty() {
+            return result;
+        }
+
+        let cell_at = |byte_idx: usize| -> usize {
+            match cells.binary_search_by(|(start, _)| start.cmp(&byte_idx)) {
+                Ok(i) => i,
+                Err(i) => i.saturating_sub(1),
+            }
+        };
+
+        for highlight in &self.highlights {
+            for m in highlight.regex.find_iter(&text) {
+                let m = match m {
+                    Ok(m) => m,
+                    Err(_) => break,
+                };
+                if m.start() == m.end() {
+                    continue;
+                }
+                let first = &cells[cell_at(m.start())].1;
+                let last = &cells[cell_at(m.end() - 1)].1;
+                result.push((first.start..last.end, highlight.color));
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::cell::CellAttributes;
+    use termwiz::surface::SEQ_ZERO;
+
+    #[test]
+    fn highlight_matches() {
+        let line = Line::from_text(
+            "foo bar 日本 bar",
+            &CellAttributes::default(),
+            SEQ_ZERO,
+            None,
+        );
+        let red: RgbaColor = termwiz::color::SrgbaTuple(1., 0., 0., 1.).into();
+
+        let mut highlights = PaneHighlights::default();
+        assert!(highlights.matches(&line).is_empty());
+
+        highlights.add("bar".to_string(), "bar", red).unwrap();
+        assert_eq!(
+            highlights
+                .matches(&line)
+                .into_iter()
+                .map(|(r, _)| r)
+                .collect::<Vec<_>>(),
+            vec![4..7, 13..16]
+        );
+
+        // Double width cells cover both of their columns
+        assert!(highlights.toggle("wide".to_string(), "日本", red).unwrap());
+        assert_eq!(highlights.matches(&line)[2].0, 8..12);
+
+        assert!(!highlights.toggle("wide".to_string(), "日本", red).unwrap());
+        assert!(highlights.remove("bar"));
+        assert!(!highlights.remove("bar"));
+        assert!(highlights.is_empty());
+    }
+}
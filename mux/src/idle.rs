@@ -0,0 +1,278 @@
+//! Evaluates the `idle_pane_policy` configuration, notifying about or
+//! closing panes that have had no input or output for a long time.
+use crate::pane::{CachePolicy, Pane, PaneId};
+use crate::{Mux, MuxNotification};
+use config::{IdlePaneAction, IdlePanePolicy};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use wezterm_term::Alert;
+
+/// How often the policy is evaluated.  The policies are expressed
+/// in hours, so there is no need to be more precise than this.
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+fn is_shell(process_name: &str) -> bool {
+    let base = process_name
+        .rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .unwrap_or(process_name);
+    let base = base.strip_suffix(".exe").unwrap_or(base).to_lowercase();
+    let base = base.strip_prefix('-').unwrap_or(&base);
+    matches!(
+        base,
+        "sh" | "bash"
+            | "zsh"
+            | "fish"
+            | "dash"
+            | "ksh"
+            | "mksh"
+            | "csh"
+            | "tcsh"
+            | "nu"
+            | "xonsh"
+            | "elvish"
+            | "pwsh"
+            | "powershell"
+            | "cmd"
+    )
+}
+
+/// Starts the periodic evaluation of `idle_pane_policy`.
+/// This must be called on the main thread after the mux has been
+/// set up.  The evaluation is a no-op while no policies are configured.
+pub fn start_idle_pane_monitor() {
+    promise::spawn::spawn(async move {
+        let mut notified = HashSet::new();
+        loop {
+            smol::Timer::after(CHECK_INTERVAL).await;
+            match Mux::try_get() {
+                Some(mux) => check_idle_panes(&mux, &mut notified),
+                None => break,
+            }
+        }
+    })
+    .detach();
+}
+
+/// Decides what, if anything, `policy` requires to be done with a pane
+/// that has been idle for `idle`.  `foreground_process_name` is only
+/// consulted when the policy needs it, as it can be relatively expensive.
+fn idle_pane_action(
+    policy: &IdlePanePolicy,
+    idle: Option<Duration>,
+    pinned: bool,
+    dead: bool,
+    foreground_process_name: impl FnOnce() -> Option<String>,
+) -> Option<IdlePaneAction> {
+    match idle {
+        Some(idle) if idle >= policy.idle_duration() => {}
+        _ => return None,
+    }
+    if pinned || dead {
+        return None;
+    }
+    if policy.only_if_shell {
+        match foreground_process_name() {
+            Some(name) if is_shell(&name) => {}
+            _ => return None,
+        }
+    }
+    Some(policy.action)
+}
+
+fn check_idle_panes(mux: &Arc<Mux>, notified: &mut HashSet<PaneId>) {
+    let config = config::configuration();
+    if config.idle_pane_policy.is_empty() {
+        notified.clear();
+        return;
+    }
+
+    let mut still_idle = HashSet::new();
+    for pane in mux.iter_panes() {
+        let domain = match mux.get_domain(pane.domain_id()) {
+            Some(domain) => domain,
+            None => continue,
+        };
+        let policy = match config.idle_pane_policy.get(domain.domain_name()) {
+            Some(policy) => policy,
+            None => continue,
+        };
+        let idle = pane.idle_duration();
+        let action = match idle_pane_action(policy, idle, pane.is_pinned(), pane.is_dead(), || {
+            pane.get_foreground_process_name(CachePolicy::AllowStale)
+        }) {
+            Some(action) => action,
+            None => continue,
+        };
+        let idle = idle.unwrap_or_default();
+
+        let pane_id = pane.pane_id();
+        match action {
+            IdlePaneAction::Close => {
+                log::info!(
+                    "closing pane {pane_id} in domain {} which has been idle for {:?}",
+                    domain.domain_name(),
+                    idle
+                );
+                mux.remove_pane(pane_id);
+            }
+            IdlePaneAction::Notify => {
+                still_idle.insert(pane_id);
+                // Only notify once for each period of idleness
+                if !notified.contains(&pane_id) {
+                    notify_idle_pane(&pane, domain.domain_name(), idle, policy);
+                }
+            }
+        }
+    }
+    *notified = still_idle;
+}
+
+fn notify_idle_pane(
+    pane: &Arc<dyn Pane>,
+    domain_name: &str,
+    idle: Duration,
+    policy: &IdlePanePolicy,
+) {
+    let pane_id = pane.pane_id();
+    let title = pane.get_title();
+    let domain_name = domain_name.to_string();
+    let hours = policy.after_hours;
+
+    promise::spawn::spawn(async move {
+        let handled = config::with_lua_config_on_main_thread(|lua| async {
+            let lua = match lua {
+                Some(lua) => lua,
+                None => return Ok(false),
+            };
+            if !config::lua::has_event_handler(&lua, "idle-pane")? {
+                return Ok(false);
+            }
+            config::lua::emit_async_callback(
+                &*lua,
+                (
+                    "idle-pane".to_string(),
+                    (pane_id, idle.as_secs(), domain_name.clone()),
+                ),
+            )
+            .await?;
+            Ok(true)
+        })
+        .await;
+
+        match handled {
+            Ok(true) => {}
+            Ok(false) => {
+                Mux::get().notify(MuxNotification::Alert {
+                    pane_id,
+                    alert: Alert::ToastNotification {
+                        title: Some("Idle pane".to_string()),
+                        body: format!(
+                            "Pane {pane_id} ({title}) in domain {domain_name} \
+                             has been idle for more than {hours} hours"
+                        ),
+                        focus: true,
+                        actions: vec![],
+                    },
+                });
+            }
+            Err(err) => log::error!("while emitting idle-pane event: {err:#}"),
+        }
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shell_names() {
+        assert!(is_shell("/bin/bash"));
+        assert!(is_shell("/usr/local/bin/fish"));
+        assert!(is_shell("-zsh"));
+        assert!(is_shell("C:\\Windows\\System32\\cmd.exe"));
+        assert!(is_shell("pwsh.exe"));
+        assert!(!is_shell("/usr/bin/vim"));
+        assert!(!is_shell("/usr/bin/bashtop"));
+        assert!(!is_shell("ssh"));
+    }
+
+    fn policy(action: IdlePaneAction, only_if_shell: bool) -> IdlePanePolicy {
+        IdlePanePolicy {
+            after_hours: 2.,
+            action,
+            only_if_shell,
+        }
+    }
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn acts_once_idle_for_long_enough() {
+        let notify = policy(IdlePaneAction::Notify, false);
+        let no_name = || -> Option<String> { panic!("process name is not needed") };
+        assert_eq!(idle_pane_action(&notify, None, false, false, no_name), None);
+        assert_eq!(
+            idle_pane_action(&notify, Some(HOUR), false, false, no_name),
+            None
+        );
+        assert_eq!(
+            idle_pane_action(&notify, Some(2 * HOUR), false, false, no_name),
+            Some(IdlePaneAction::Notify)
+        );
+        assert_eq!(
+            idle_pane_action(
+                &policy(IdlePaneAction::Close, false),
+                Some(3 * HOUR),
+                false,
+                false,
+                no_name
+            ),
+            Some(IdlePaneAction::Close)
+        );
+    }
+
+    #[test]
+    fn skips_pinned_and_dead_panes() {
+        let close = policy(IdlePaneAction::Close, false);
+        let no_name = || -> Option<String> { panic!("process name is not needed") };
+        assert_eq!(
+            idle_pane_action(&close, Some(3 * HOUR), true, false, no_name),
+            None
+        );
+        assert_eq!(
+            idle_pane_action(&close, Some(3 * HOUR), false, true, no_name),
+            None
+        );
+    }
+
+    #[test]
+    fn only_if_shell() {
+        let close = policy(IdlePaneAction::Close, true);
+        let name = |name: &str| {
+            let name = name.to_string();
+            move || Some(name)
+        };
+        assert_eq!(
+            idle_pane_action(&close, Some(3 * HOUR), false, false, name("/bin/zsh")),
+            Some(IdlePaneAction::Close)
+        );
+        assert_eq!(
+            idle_pane_action(&close, Some(3 * HOUR), false, false, name("/usr/bin/vim")),
+            None
+        );
+        assert_eq!(
+            idle_pane_action(&close, Some(3 * HOUR), false, false, || None),
+            None
+        );
+        // Not idle yet, so the process isn't even looked up
+        assert_eq!(
+            idle_pane_action(&close, Some(HOUR), false, false, || -> Option<String> {
+                panic!("process name is not needed")
+            }),
+            None
+        );
+    }
+}
@@ -1,11 +1,20 @@
 use crate::client::{ClientId, ClientInfo};
+use crate::closed::{
+    ClosedHistory, ClosedItem, ClosedItemInfo, ClosedKind, ClosedTab, RetentionLimits,
+};
+use crate::links::{PaneLink, PaneLinks};
+use crate::localpane::LocalPane;
+use crate::mirror::MirrorPane;
+use crate::outputrate::{OutputRate, OutputRateConfig};
 use crate::pane::{CachePolicy, Pane, PaneId};
 use crate::ssh_agent::AgentProxy;
-use crate::tab::{SplitRequest, Tab, TabId};
+use crate::tab::{FloatingPaneGeometry, SplitRequest, Tab, TabId};
 use crate::window::{Window, WindowId};
 use anyhow::{anyhow, Context, Error};
-use config::keyassignment::SpawnTabDomain;
-use config::{configuration, ExitBehavior, GuiPosition};
+use config::keyassignment::{PaneLinkOptions, SpawnCommand, SpawnTabDomain};
+use config::{
+    configuration, ConfigHandle, ExitBehavior, GuiPosition, OutputRateLimitAction, TermConfig,
+};
 use domain::{Domain, DomainId, DomainState, SplitSource};
 use filedescriptor::{poll, pollfd, socketpair, AsRawSocketDescriptor, FileDescriptor, POLLIN};
 #[cfg(unix)]
@@ -17,6 +26,7 @@ use parking_lot::{
 };
 use percent_encoding::percent_decode_str;
 use portable_pty::{CommandBuilder, ExitStatus, PtySize};
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::io::{Read, Write};
@@ -32,15 +42,37 @@ use wezterm_term::{Clipboard, ClipboardSelection, DownloadHandler, TerminalSize}
 use winapi::um::winsock2::{SOL_SOCKET, SO_RCVBUF, SO_SNDBUF};
 
 pub mod activity;
+pub mod annotation;
 pub mod client;
+pub mod closed;
 pub mod connui;
 pub mod domain;
+pub mod encoding;
+#[cfg(unix)]
+pub mod handoff;
+pub mod highlight;
+pub mod idle;
+mod line_buffer;
+pub mod links;
+pub mod listing;
 pub mod localpane;
+pub mod location;
+pub mod mirror;
+pub mod monitor;
+pub mod nesting;
+pub mod output_stream;
+pub mod outputpause;
+pub mod outputrate;
 pub mod pane;
+pub mod pane_watch;
+pub mod power;
 pub mod renderable;
+pub mod scrollback;
+pub mod session;
 pub mod ssh;
 pub mod ssh_agent;
 pub mod tab;
+pub mod terminfo_assist;
 pub mod termwiztermtab;
 pub mod tmux;
 pub mod tmux_commands;
@@ -59,8 +91,12 @@ pub enum MuxNotification {
     WindowCreated(WindowId),
     WindowRemoved(WindowId),
     WindowInvalidated(WindowId),
+    /// The gui window that shows the window was moved or resized
+    WindowGeometryChanged(WindowId),
     WindowWorkspaceChanged(WindowId),
     ActiveWorkspaceChanged(Arc<ClientId>),
+    /// The server session of this client should disconnect it
+    KillClient(Arc<ClientId>),
     Alert {
         pane_id: PaneId,
         alert: wezterm_term::Alert,
@@ -84,15 +120,18 @@ pub enum MuxNotification {
     TabTitleChanged {
         tab_id: TabId,
         title: String,
+        user_override: bool,
     },
     WindowTitleChanged {
         window_id: WindowId,
         title: String,
+        user_override: bool,
     },
     WorkspaceRenamed {
         old_workspace: String,
         new_workspace: String,
     },
+    PowerEvent(power::PowerEvent),
 }
 
 static SUB_ID: AtomicUsize = AtomicUsize::new(0);
@@ -109,11 +148,21 @@ pub struct Mux {
     clients: RwLock<HashMap<ClientId, ClientInfo>>,
     identity: RwLock<Option<Arc<ClientId>>>,
     num_panes_by_workspace: RwLock<HashMap<String, usize>>,
+    pane_links: RwLock<PaneLinks>,
+    spawn_commands: RwLock<HashMap<PaneId, SpawnCommand>>,
+    /// Recorded as the SpawnCommand of the next pane that is added
+    pending_spawn_command: RwLock<Option<SpawnCommand>>,
+    /// The profile that was selected for each pane that has one
+    pane_profiles: RwLock<HashMap<PaneId, String>>,
+    closed: Mutex<ClosedHistory>,
     main_thread_id: std::thread::ThreadId,
     agent: Option<AgentProxy>,
 }
 
 const BUFSIZE: usize = 1024 * 1024;
+/// How often a reader whose output is paused checks whether
+/// its pane is still alive
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 /// This function applies parsed actions to the pane and notifies any
 /// mux subscribers about the output event
@@ -135,7 +184,12 @@ fn send_actions_to_mux(pane: &Weak<dyn Pane>, dead: &Arc<AtomicBool>, actions: V
     histogram!("send_actions_to_mux.rate").record(1.);
 }
 
-fn parse_buffered_data(pane: Weak<dyn Pane>, dead: &Arc<AtomicBool>, mut rx: FileDescriptor) {
+fn parse_buffered_data(
+    pane: Weak<dyn Pane>,
+    dead: &Arc<AtomicBool>,
+    mut rx: FileDescriptor,
+    output_rate: Option<Arc<Mutex<OutputRate>>>,
+) {
     let mut buf = vec![0; configuration().mux_output_parser_buffer_size];
     let mut parser = termwiz::escape::parser::Parser::new();
     let mut actions = vec![];
@@ -143,6 +197,7 @@ fn parse_buffered_data(pane: Weak<dyn Pane>, dead: &Arc<AtomicBool>, mut rx: Fil
     let mut action_size = 0;
     let mut delay = Duration::from_millis(configuration().mux_output_parser_coalesce_delay_ms);
     let mut deadline = None;
+    let mut rate_config = OutputRateConfig::new();
 
     loop {
         match rx.read(&mut buf) {
@@ -190,11 +245,27 @@ fn parse_buffered_data(pane: Weak<dyn Pane>, dead: &Arc<AtomicBool>, mut rx: Fil
                 });
                 action_size += size;
                 if !actions.is_empty() && !hold {
+                    // When a runaway pane exceeds its output budget and
+                    // `output_rate_limit` is set to `DropRender`, only
+                    // apply its output in snapshots at the frame rate
+                    let snapshot_interval = output_rate.as_ref().and_then(|rate| {
+                        let interval = rate_config.snapshot_interval()?;
+                        let limit = rate_config.limit();
+                        rate.lock()
+                            .exceeds(limit.bytes_per_second, Instant::now())
+                            .then_some(interval)
+                    });
+
                     // If we haven't accumulated too much data,
                     // pause for a short while to increase the chances
                     // that we coalesce a full "frame" from an unoptimized
                     // TUI program
-                    if action_size < buf.len() {
+                    let coalesce_delay = match snapshot_interval {
+                        Some(interval) => Some(interval),
+                        None if action_size < buf.len() => Some(delay),
+                        None => None,
+                    };
+                    if let Some(delay) = coalesce_delay {
                         let poll_delay = match deadline {
                             None => {
                                 deadline.replace(Instant::now() + delay);
@@ -280,10 +351,20 @@ fn read_from_pane_pty(
     // or in the main mux thread.  If `true`, this thread will terminate.
     let dead = Arc::new(AtomicBool::new(false));
 
-    let (pane_id, exit_behavior) = match pane.upgrade() {
-        Some(pane) => (pane.pane_id(), pane.exit_behavior()),
-        None => return,
-    };
+    let (pane_id, exit_behavior, generation, output_rate, output_pause, encoding) =
+        match pane.upgrade() {
+            Some(pane) => (
+                pane.pane_id(),
+                pane.exit_behavior(),
+                pane.pty_generation(),
+                pane.output_rate(),
+                pane.output_pause(),
+                pane.encoding(),
+            ),
+            None => return,
+        };
+
+    let mut rate_config = OutputRateConfig::new();
 
     let (mut tx, rx) = match allocate_socketpair() {
         Ok(pair) => pair,
@@ -300,9 +381,11 @@ fn read_from_pane_pty(
         }
     };
 
-    std::thread::spawn({
+    let parser = std::thread::spawn({
         let dead = Arc::clone(&dead);
-        move || parse_buffered_data(pane, &dead, rx)
+        let pane = pane.clone();
+        let output_rate = output_rate.clone();
+        move || parse_buffered_data(pane, &dead, rx, output_rate)
     });
 
     if let Some(banner) = banner {
@@ -310,6 +393,11 @@ fn read_from_pane_pty(
     }
 
     while !dead.load(Ordering::Relaxed) {
+        if let Some(output_pause) = &output_pause {
+            output_pause.wait_while_paused(PAUSE_CHECK_INTERVAL, || {
+                !dead.load(Ordering::Relaxed) && pane.strong_count() > 0
+            });
+        }
         match reader.read(&mut buf) {
             Ok(size) if size == 0 => {
                 log::trace!("read_pty EOF: pane_id {}", pane_id);
@@ -322,17 +410,57 @@ fn read_from_pane_pty(
             Ok(size) => {
                 histogram!("read_from_pane_pty.bytes.rate").record(size as f64);
                 log::trace!("read_pty pane {pane_id} read {size} bytes");
-                if let Err(err) = tx.write_all(&buf[..size]) {
+                let data = match &encoding {
+                    Some(encoding) => crate::encoding::decode(*encoding.lock(), &buf[..size]),
+                    None => Cow::Borrowed(&buf[..size]),
+                };
+                if let Err(err) = tx.write_all(&data) {
                     error!(
                         "read_pty failed to write to parser: pane {} {:?}",
                         pane_id, err
                     );
                     break;
                 }
+
+                if let Some(output_rate) = &output_rate {
+                    let limit = rate_config.limit();
+                    let now = Instant::now();
+                    let delay = {
+                        let mut output_rate = output_rate.lock();
+                        output_rate.record(size, now);
+                        match limit.action {
+                            OutputRateLimitAction::Throttle => {
+                                output_rate.throttle_delay(limit.bytes_per_second, now)
+                            }
+                            OutputRateLimitAction::DropRender => None,
+                        }
+                    };
+                    if let Some(delay) = delay {
+                        // Stop reading for a while; the kernel buffer
+                        // fills up and applies backpressure to the child
+                        log::trace!("read_pty pane {pane_id} throttled for {delay:?}");
+                        std::thread::sleep(delay);
+                    }
+                }
             }
         }
     }
 
+    let respawned = pane
+        .upgrade()
+        .map(|pane| pane.pty_generation() != generation)
+        .unwrap_or(false);
+    if respawned {
+        // The process was replaced or the pty is being handed off to
+        // another process.  Let the parser finish applying what we
+        // have read so far; whoever owns the pty now takes it from here.
+        log::trace!("read_pty EOF on pane {pane_id} after pty generation change");
+        drop(tx);
+        parser.join().ok();
+        dead.store(true, Ordering::Relaxed);
+        return;
+    }
+
     match exit_behavior.unwrap_or_else(|| configuration().exit_behavior) {
         ExitBehavior::Hold | ExitBehavior::CloseOnCleanExit => {
             // We don't know if we can unilaterally close
@@ -439,6 +567,11 @@ impl Mux {
             clients: RwLock::new(HashMap::new()),
             identity: RwLock::new(None),
             num_panes_by_workspace: RwLock::new(HashMap::new()),
+            pane_links: RwLock::new(PaneLinks::default()),
+            spawn_commands: RwLock::new(HashMap::new()),
+            pending_spawn_command: RwLock::new(None),
+            pane_profiles: RwLock::new(HashMap::new()),
+            closed: Mutex::new(ClosedHistory::default()),
             main_thread_id: std::thread::current().id(),
             agent,
         }
@@ -563,6 +696,34 @@ impl Mux {
             .insert((*client_id).clone(), ClientInfo::new(client_id));
     }
 
+    /// Records how a client that was registered via `register_client`
+    /// is connected to this server
+    pub fn set_client_connection_info(
+        &self,
+        client_id: &ClientId,
+        peer: Option<String>,
+        protocol_version: usize,
+    ) {
+        if let Some(info) = self.clients.write().get_mut(client_id) {
+            info.peer = peer;
+            info.protocol_version.replace(protocol_version);
+        }
+    }
+
+    /// Asks the server session of the client with the specified
+    /// session id to disconnect it
+    pub fn kill_client(&self, session_id: usize) -> anyhow::Result<()> {
+        let client_id = self
+            .clients
+            .read()
+            .values()
+            .find(|info| info.session_id == session_id)
+            .map(|info| Arc::clone(&info.client_id))
+            .ok_or_else(|| anyhow::anyhow!("no client with id {session_id}"))?;
+        self.notify(MuxNotification::KillClient(client_id));
+        Ok(())
+    }
+
     pub fn iter_clients(&self) -> Vec<ClientInfo> {
         self.clients
             .read()
@@ -760,6 +921,177 @@ impl Mux {
         self.panes.read().get(&pane_id).map(Arc::clone)
     }
 
+    /// Arranges for `command` to be recorded as the SpawnCommand of
+    /// the next pane that is added to the mux, before any subscriber
+    /// is told about that pane.
+    /// Returns `SpawnCommandHolder`, which discards the command if no
+    /// pane has been added by the time that it is dropped.
+    pub fn with_spawn_command(&self, command: SpawnCommand) -> SpawnCommandHolder {
+        self.pending_spawn_command.write().replace(command);
+        SpawnCommandHolder {}
+    }
+
+    /// Returns the SpawnCommand that was used to spawn `pane_id`.
+    /// Panes that were not spawned from a SpawnCommand, such as those
+    /// that were spawned by `wezterm cli spawn` or that belong to a
+    /// remote mux that we attached to, have none.
+    pub fn get_spawn_command(&self, pane_id: PaneId) -> Option<SpawnCommand> {
+        self.spawn_commands.read().get(&pane_id).cloned()
+    }
+
+    /// Returns the config with which to build the command of the pane
+    /// that is being spawned: that of the profile of the command that
+    /// was passed to `with_spawn_command`, if it has one, or otherwise
+    /// the config file
+    pub fn spawn_config(&self) -> ConfigHandle {
+        let profile = self
+            .pending_spawn_command
+            .read()
+            .as_ref()
+            .and_then(|command| command.profile.clone());
+        match profile {
+            Some(name) => config::profile_config(&name).unwrap_or_else(|err| {
+                log::error!("{:#}", err);
+                configuration()
+            }),
+            None => configuration(),
+        }
+    }
+
+    /// Returns the domain in which to spawn a pane with `profile`;
+    /// a `default_domain` set by the profile stands in for the
+    /// default domain
+    pub fn resolve_profile_domain(
+        &self,
+        profile: Option<&str>,
+        domain: SpawnTabDomain,
+    ) -> SpawnTabDomain {
+        match (profile, domain) {
+            (Some(name), SpawnTabDomain::DefaultDomain) => {
+                match configuration().profile_default_domain(name) {
+                    Some(name) => SpawnTabDomain::DomainName(name),
+                    None => SpawnTabDomain::DefaultDomain,
+                }
+            }
+            (_, domain) => domain,
+        }
+    }
+
+    /// Resolves the profile of a pane that is about to be spawned:
+    /// the `requested` profile, which must be defined in the config,
+    /// or otherwise the profile of the pane from which it is spawned
+    pub fn resolve_spawn_profile(
+        &self,
+        requested: Option<&str>,
+        source_pane_id: Option<PaneId>,
+    ) -> anyhow::Result<Option<String>> {
+        resolve_spawn_profile(
+            &configuration(),
+            requested,
+            source_pane_id.and_then(|pane_id| self.get_pane_profile(pane_id)),
+        )
+    }
+
+    /// Applies the overrides of the profile named `name` to `pane`,
+    /// and records it so that the panes spawned from it inherit it
+    pub fn set_pane_profile(&self, pane: &Arc<dyn Pane>, name: &str) -> anyhow::Result<()> {
+        pane.set_config(Arc::new(TermConfig::with_profile(name)?));
+        self.pane_profiles
+            .write()
+            .insert(pane.pane_id(), name.to_string());
+        Ok(())
+    }
+
+    /// Returns the name of the profile that was selected for `pane_id`
+    pub fn get_pane_profile(&self, pane_id: PaneId) -> Option<String> {
+        self.pane_profiles.read().get(&pane_id).cloned()
+    }
+
+    /// Records what is needed to reopen `pane_id`, which the user is
+    /// about to close, in the history of closed panes
+    pub fn retain_closed_pane(&self, pane_id: PaneId) {
+        let limits = RetentionLimits::from_config(&configuration());
+        if limits.is_disabled() {
+            return;
+        }
+        let (pane, (_domain_id, window_id, tab_id)) =
+            match (self.get_pane(pane_id), self.resolve_pane_id(pane_id)) {
+                (Some(pane), Some(location)) => (pane, location),
+                _ => return,
+            };
+        let placement = self
+            .get_tab(tab_id)
+            .and_then(|tab| closed::placement_of(&tab.codec_pane_tree(), pane_id));
+        let closed = closed::capture_pane(
+            &pane,
+            self.get_spawn_command(pane_id),
+            placement,
+            limits.max_bytes,
+        );
+        self.closed.lock().push(
+            window_id,
+            tab_id,
+            ClosedKind::Pane(closed),
+            limits,
+            Instant::now(),
+        );
+    }
+
+    /// Records what is needed to reopen `tab_id`, which the user is
+    /// about to close, in the history of closed panes
+    pub fn retain_closed_tab(&self, tab_id: TabId) {
+        let limits = RetentionLimits::from_config(&configuration());
+        if limits.is_disabled() {
+            return;
+        }
+        let (tab, window_id) = match (self.get_tab(tab_id), self.window_containing_tab(tab_id)) {
+            (Some(tab), Some(window_id)) => (tab, window_id),
+            _ => return,
+        };
+        let tab_index = self
+            .get_window(window_id)
+            .and_then(|window| window.idx_by_id(tab_id))
+            .unwrap_or(0);
+        let layout = closed::capture_layout(&tab.codec_pane_tree(), &mut |pane_id| {
+            let pane = self.get_pane(pane_id)?;
+            Some(closed::capture_pane(
+                &pane,
+                self.get_spawn_command(pane_id),
+                None,
+                limits.max_bytes,
+            ))
+        });
+        if let Some(layout) = layout {
+            self.closed.lock().push(
+                window_id,
+                tab_id,
+                ClosedKind::Tab(ClosedTab {
+                    title: tab.get_title(),
+                    title_is_override: tab.has_title_override(),
+                    tab_index,
+                    layout,
+                }),
+                limits,
+                Instant::now(),
+            );
+        }
+    }
+
+    /// Removes and returns the pane, or the tab if `tab` is true, that
+    /// was most recently closed in `window_id`
+    pub fn take_closed(&self, window_id: WindowId, tab: bool) -> Option<ClosedItem> {
+        let limits = RetentionLimits::from_config(&configuration());
+        self.closed
+            .lock()
+            .take_latest(window_id, tab, limits, Instant::now())
+    }
+
+    /// Returns the panes and tabs that can be reopened, most recent first
+    pub fn list_closed(&self) -> Vec<ClosedItemInfo> {
+        let limits = RetentionLimits::from_config(&configuration());
+        self.closed.lock().list(limits, Instant::now())
+    }
+
     pub fn get_tab(&self, tab_id: TabId) -> Option<Arc<Tab>> {
         self.tabs.read().get(&tab_id).map(Arc::clone)
     }
@@ -777,15 +1109,68 @@ impl Mux {
         let downloader: Arc<dyn DownloadHandler> = Arc::new(MuxDownloader {});
         pane.set_download_handler(&downloader);
 
-        self.panes.write().insert(pane.pane_id(), Arc::clone(pane));
         let pane_id = pane.pane_id();
+        if let Some(command) = self.pending_spawn_command.write().take() {
+            // The encoding must be in place before the reader starts,
+            // so that the first output of the program is decoded
+            if let Some(encoding) = command.encoding {
+                match pane.encoding() {
+                    Some(current) => *current.lock() = encoding,
+                    None => log::warn!(
+                        "pane {pane_id} doesn't support changing its encoding to {encoding}"
+                    ),
+                }
+            }
+            self.spawn_commands.write().insert(pane_id, command);
+        }
+        self.panes.write().insert(pane_id, Arc::clone(pane));
+        let banner = self.banner.read().clone();
+        self.start_pane_reader(pane, banner)?;
+        self.recompute_pane_count();
+        self.notify(MuxNotification::PaneAdded(pane_id));
+        Ok(())
+    }
+
+    /// Replace the process running in the specified pane, keeping
+    /// the pane itself (and its scrollback) in place.
+    /// If command is None, the command originally used to spawn
+    /// the pane is used again.
+    pub async fn respawn_pane(
+        &self,
+        pane_id: PaneId,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+    ) -> anyhow::Result<()> {
+        let pane = self
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane id {} not found", pane_id))?;
+        let domain = self
+            .get_domain(pane.domain_id())
+            .ok_or_else(|| anyhow!("domain {} of pane {pane_id} not found", pane.domain_id()))?;
+
+        domain
+            .respawn_pane(pane_id, command, command_dir)
+            .await
+            .with_context(|| format!("respawning pane {pane_id}"))?;
+
+        // The pane now has a new pty; start reading from it
+        self.start_pane_reader(&pane, None)?;
+        self.notify(MuxNotification::PaneOutput(pane_id));
+        Ok(())
+    }
+
+    /// Spawns a thread to read and apply the output from the pane's pty.
+    /// This is done when the pane is added to the mux, but may be needed
+    /// again if the pty is replaced or its reader was suspended.
+    pub fn start_pane_reader(
+        &self,
+        pane: &Arc<dyn Pane>,
+        banner: Option<String>,
+    ) -> anyhow::Result<()> {
         if let Some(reader) = pane.reader()? {
-            let banner = self.banner.read().clone();
             let pane = Arc::downgrade(pane);
             thread::spawn(move || read_from_pane_pty(pane, banner, reader));
         }
-        self.recompute_pane_count();
-        self.notify(MuxNotification::PaneAdded(pane_id));
         Ok(())
     }
 
@@ -804,6 +1189,9 @@ impl Mux {
 
     fn remove_pane_internal(&self, pane_id: PaneId) {
         log::debug!("removing pane {}", pane_id);
+        self.pane_links.write().remove_pane(pane_id);
+        self.spawn_commands.write().remove(&pane_id);
+        self.pane_profiles.write().remove(&pane_id);
         if let Some(pane) = self.panes.write().remove(&pane_id).clone() {
             log::debug!("killing pane {}", pane_id);
             pane.kill();
@@ -827,6 +1215,9 @@ impl Mux {
         for pos in tab.iter_panes_ignoring_zoom() {
             pane_ids.push(pos.pane.pane_id());
         }
+        for pane in tab.floating_panes() {
+            pane_ids.push(pane.pane_id());
+        }
         log::debug!("panes to remove: {pane_ids:?}");
         for pane_id in pane_ids {
             self.remove_pane_internal(pane_id);
@@ -876,6 +1267,70 @@ impl Mux {
         self.prune_dead_windows();
     }
 
+    /// Removes `pane_id` together with the panes that are linked
+    /// with it by `close_together`
+    pub fn remove_pane_and_linked(&self, pane_id: PaneId) {
+        let linked = self.panes_closed_with(pane_id);
+        self.remove_pane_internal(pane_id);
+        for pane in linked {
+            self.remove_pane_internal(pane.pane_id());
+        }
+        self.prune_dead_windows();
+    }
+
+    /// Links `pane_id` with `other_pane_id`, replacing any existing
+    /// link between them. Options with neither behavior set remove
+    /// the link. The domain of the pane is advised so that it can
+    /// record the link too.
+    pub fn link_panes(
+        &self,
+        pane_id: PaneId,
+        other_pane_id: PaneId,
+        options: PaneLinkOptions,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            pane_id != other_pane_id,
+            "a pane cannot be linked with itself"
+        );
+        let pane = self
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane {pane_id} not found"))?;
+        let other = self
+            .get_pane(other_pane_id)
+            .ok_or_else(|| anyhow!("pane {other_pane_id} not found"))?;
+        self.record_pane_link(pane_id, other_pane_id, options);
+        pane.link_with(&other, options);
+        Ok(())
+    }
+
+    /// Records a link between two panes without advising their domain;
+    /// used when the link was reported by the domain itself
+    pub fn record_pane_link(
+        &self,
+        pane_id: PaneId,
+        other_pane_id: PaneId,
+        options: PaneLinkOptions,
+    ) {
+        self.pane_links
+            .write()
+            .link(pane_id, other_pane_id, options);
+    }
+
+    pub fn get_pane_links(&self, pane_id: PaneId) -> Vec<PaneLink> {
+        self.pane_links.read().links_of(pane_id)
+    }
+
+    /// Returns the live panes that are linked with `pane_id`
+    /// by `close_together`
+    pub fn panes_closed_with(&self, pane_id: PaneId) -> Vec<Arc<dyn Pane>> {
+        self.get_pane_links(pane_id)
+            .into_iter()
+            .filter(|link| link.close_together)
+            .filter_map(|link| self.get_pane(link.pane_id))
+            .filter(|pane| !pane.is_dead())
+            .collect()
+    }
+
     pub fn remove_tab(&self, tab_id: TabId) -> Option<Arc<Tab>> {
         let tab = self.remove_tab_internal(tab_id);
         self.prune_dead_windows();
@@ -1062,6 +1517,12 @@ impl Mux {
                     break;
                 }
             }
+            for pane in tab.floating_panes() {
+                if pane.pane_id() == pane_id {
+                    ids = Some((tab.tab_id(), pane.domain_id()));
+                    break;
+                }
+            }
         }
         let (tab_id, domain_id) = ids?;
         let window_id = self.window_containing_tab(tab_id)?;
@@ -1143,29 +1604,11 @@ impl Mux {
         target_domain: DomainId,
         policy: CachePolicy,
     ) -> Option<String> {
-        command_dir.or_else(|| {
-            match pane {
-                Some(pane) if pane.domain_id() == target_domain => pane
-                    .get_current_working_dir(policy)
-                    .and_then(|url| {
-                        percent_decode_str(url.path())
-                            .decode_utf8()
-                            .ok()
-                            .map(|path| path.into_owned())
-                    })
-                    .map(|path| {
-                        // On Windows the file URI can produce a path like:
-                        // `/C:\Users` which is valid in a file URI, but the leading slash
-                        // is not liked by the windows file APIs, so we strip it off here.
-                        let bytes = path.as_bytes();
-                        if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
-                            path[1..].to_owned()
-                        } else {
-                            path
-                        }
-                    }),
-                _ => None,
-            }
+        command_dir.or_else(|| match pane {
+            Some(pane) if pane.domain_id() == target_domain => pane
+                .get_current_working_dir(policy)
+                .and_then(|url| url_to_cwd(&url)),
+            _ => None,
         })
     }
 
@@ -1198,8 +1641,10 @@ impl Mux {
             SplitSource::Spawn {
                 command,
                 command_dir,
+                inherit_env,
             } => SplitSource::Spawn {
                 command,
+                inherit_env,
                 command_dir: self.resolve_cwd(
                     command_dir,
                     Some(Arc::clone(&current_pane)),
@@ -1230,6 +1675,102 @@ impl Mux {
         Ok((pane, size))
     }
 
+    /// Spawns a pane that floats above the panes of `tab_id`,
+    /// sized and placed according to `geometry`, and focuses it
+    pub async fn spawn_floating_pane(
+        &self,
+        tab_id: TabId,
+        geometry: FloatingPaneGeometry,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+        domain: SpawnTabDomain,
+    ) -> anyhow::Result<Arc<dyn Pane>> {
+        let tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("Invalid tab id {}", tab_id))?;
+        let window_id = self
+            .window_containing_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {} is not in a window", tab_id))?;
+        let current_pane = tab.get_active_pane();
+
+        let domain = self
+            .resolve_spawn_tab_domain(current_pane.as_ref().map(|p| p.pane_id()), &domain)
+            .context("resolve_spawn_tab_domain")?;
+        if domain.state() == DomainState::Detached {
+            domain.attach(Some(window_id)).await?;
+        }
+
+        let tab_size = tab.get_size();
+        let (_left, _top, cols, rows) = geometry.place(tab_size.cols, tab_size.rows);
+        let cell_width = tab_size.pixel_width.checked_div(tab_size.cols).unwrap_or(0);
+        let cell_height = tab_size
+            .pixel_height
+            .checked_div(tab_size.rows)
+            .unwrap_or(0);
+        let size = TerminalSize {
+            cols,
+            rows,
+            pixel_width: cols * cell_width,
+            pixel_height: rows * cell_height,
+            dpi: tab_size.dpi,
+        };
+
+        let term_config = current_pane.as_ref().and_then(|p| p.get_config());
+        let cwd = self.resolve_cwd(
+            command_dir,
+            current_pane,
+            domain.domain_id(),
+            CachePolicy::FetchImmediate,
+        );
+
+        let pane = domain
+            .spawn_floating_pane(tab_id, geometry, size, command, cwd)
+            .await?;
+        if let Some(config) = term_config {
+            pane.set_config(config);
+        }
+        Ok(pane)
+    }
+
+    /// Splits the pane identified by `pane_id`, placing a read-only
+    /// mirror of it into the new space
+    pub fn open_mirror(
+        &self,
+        pane_id: PaneId,
+        request: SplitRequest,
+    ) -> anyhow::Result<Arc<dyn Pane>> {
+        let source = self
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane_id {} is invalid", pane_id))?;
+        if source.downcast_ref::<LocalPane>().is_none() {
+            anyhow::bail!("pane {pane_id} cannot be mirrored because it is not a local pane");
+        }
+
+        let (_domain_id, _window_id, tab_id) = self
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("pane_id {} invalid", pane_id))?;
+        let tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("Invalid tab id {}", tab_id))?;
+
+        let pane_index = match tab
+            .iter_panes_ignoring_zoom()
+            .iter()
+            .find(|p| p.pane.pane_id() == pane_id)
+        {
+            Some(p) => p.index,
+            None => anyhow::bail!("invalid pane id {}", pane_id),
+        };
+        let split_size = tab
+            .compute_split_size(pane_index, request)
+            .ok_or_else(|| anyhow!("invalid pane index {}", pane_index))?;
+
+        let pane: Arc<dyn Pane> = Arc::new(MirrorPane::new(source, split_size.second));
+        tab.split_and_insert(pane_index, request, Arc::clone(&pane))?;
+        self.add_pane(&pane)?;
+        Ok(pane)
+    }
+
     pub async fn move_pane_to_new_tab(
         &self,
         pane_id: PaneId,
@@ -1384,6 +1925,26 @@ impl Mux {
     }
 }
 
+/// Converts the file URL reported as the working directory of a pane
+/// into a path that can be used to spawn a command
+pub fn url_to_cwd(url: &url::Url) -> Option<String> {
+    percent_decode_str(url.path())
+        .decode_utf8()
+        .ok()
+        .map(|path| path.into_owned())
+        .map(|path| {
+            // On Windows the file URI can produce a path like:
+            // `/C:\Users` which is valid in a file URI, but the leading slash
+            // is not liked by the windows file APIs, so we strip it off here.
+            let bytes = path.as_bytes();
+            if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
+                path[1..].to_owned()
+            } else {
+                path
+            }
+        })
+}
+
 pub struct IdentityHolder {
     prior: Option<Arc<ClientId>>,
 }
@@ -1396,6 +1957,35 @@ impl Drop for IdentityHolder {
     }
 }
 
+/// Resolves the profile of a pane that is about to be spawned: the
+/// `requested` profile, which must be defined in `config`, or otherwise
+/// the profile `inherited` from the pane from which it is spawned
+fn resolve_spawn_profile(
+    config: &config::Config,
+    requested: Option<&str>,
+    inherited: Option<String>,
+) -> anyhow::Result<Option<String>> {
+    match requested {
+        Some(name) => {
+            config.profile(name)?;
+            Ok(Some(name.to_string()))
+        }
+        // The inherited profile may since have been removed
+        // from the config
+        None => Ok(inherited.filter(|name| config.profiles.contains_key(name))),
+    }
+}
+
+pub struct SpawnCommandHolder {}
+
+impl Drop for SpawnCommandHolder {
+    fn drop(&mut self) {
+        if let Some(mux) = Mux::try_get() {
+            mux.pending_spawn_command.write().take();
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 #[allow(dead_code)]
 pub enum SessionTerminated {
@@ -1449,3 +2039,86 @@ impl wezterm_term::DownloadHandler for MuxDownloader {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spawn_profile() {
+        let mut config = config::Config::default_config();
+        config
+            .profiles
+            .insert("jump".to_string(), wezterm_dynamic::Value::default());
+
+        // A requested profile takes precedence over the inherited one
+        assert_eq!(
+            resolve_spawn_profile(&config, Some("jump"), Some("other".to_string())).unwrap(),
+            Some("jump".to_string())
+        );
+        assert!(resolve_spawn_profile(&config, Some("other"), None).is_err());
+
+        assert_eq!(
+            resolve_spawn_profile(&config, None, Some("jump".to_string())).unwrap(),
+            Some("jump".to_string())
+        );
+        // An inherited profile that was removed from the config is dropped
+        assert_eq!(
+            resolve_spawn_profile(&config, None, Some("other".to_string())).unwrap(),
+            None
+        );
+        assert_eq!(resolve_spawn_profile(&config, None, None).unwrap(), None);
+    }
+
+    fn client_id(id: usize) -> Arc<ClientId> {
+        Arc::new(ClientId {
+            hostname: "host".to_string(),
+            username: "me".to_string(),
+            pid: 42,
+            epoch: 0,
+            id,
+            ssh_auth_sock: None,
+        })
+    }
+
+    #[test]
+    fn kill_client() {
+        config::use_test_configuration();
+        let mux = Mux::new(None);
+        let first = client_id(1);
+        let second = client_id(2);
+        mux.register_client(Arc::clone(&first));
+        mux.register_client(Arc::clone(&second));
+        mux.set_client_connection_info(&second, Some("10.0.0.2:4567".to_string()), 7);
+
+        let info = |client_id: &ClientId| {
+            mux.iter_clients()
+                .into_iter()
+                .find(|info| *info.client_id == *client_id)
+                .unwrap()
+        };
+        let second_info = info(&second);
+        assert_eq!(second_info.peer.as_deref(), Some("10.0.0.2:4567"));
+        assert_eq!(second_info.protocol_version, Some(7));
+        assert_eq!(info(&first).protocol_version, None);
+        assert_ne!(info(&first).session_id, second_info.session_id);
+
+        let killed = Arc::new(Mutex::new(vec![]));
+        mux.subscribe({
+            let killed = Arc::clone(&killed);
+            move |notif| {
+                if let MuxNotification::KillClient(client_id) = notif {
+                    killed.lock().push(client_id);
+                }
+                true
+            }
+        });
+
+        mux.kill_client(second_info.session_id).unwrap();
+        assert_eq!(*killed.lock(), vec![second]);
+
+        let unused = info(&first).session_id.max(second_info.session_id) + 1;
+        assert!(mux.kill_client(unused).is_err());
+        assert_eq!(killed.lock().len(), 1);
+    }
+}
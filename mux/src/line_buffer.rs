@@ -0,0 +1,183 @@
+//! Collects the lines of output of a pane as they are completed, for
+//! `output_stream` and `pane_watch`.  A line is complete once the cursor
+//! has moved below it.  The text is taken from the terminal model, so
+//! escape sequences have already been processed, and lines that wrapped
+//! are joined back together.
+use crate::pane::Pane;
+use std::collections::VecDeque;
+use wezterm_term::{Line, StableRowIndex};
+
+#[derive(Debug)]
+pub(crate) struct LineBuffer {
+    /// The next row to be taken from the pane
+    pub next_row: StableRowIndex,
+    /// The text of a line that wrapped onto rows that
+    /// haven't been taken yet
+    partial: String,
+    /// The row on which `partial` starts
+    partial_row: StableRowIndex,
+    /// Each completed line, along with the row on which it starts
+    lines: VecDeque<(StableRowIndex, String)>,
+    capacity: usize,
+    /// The number of rows that were pruned from the scrollback
+    /// before they could be taken
+    pub dropped: usize,
+}
+
+impl LineBuffer {
+    pub fn new(next_row: StableRowIndex, capacity: usize) -> Self {
+        Self {
+            next_row,
+            partial: String::new(),
+            partial_row: next_row,
+            lines: VecDeque::new(),
+            capacity: capacity.max(1),
+            dropped: 0,
+        }
+    }
+
+    /// Discards any partial line and continues from `next_row`
+    pub fn restart(&mut self, next_row: StableRowIndex) {
+        self.next_row = next_row;
+        self.partial.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// When every row ahead of `cursor_row` has been taken, returns the
+    /// row on which the line holding the cursor starts, along with the
+    /// text of the rows of that line that wrapped ahead of `cursor_row`
+    pub fn unfinished(&self, cursor_row: StableRowIndex) -> Option<(StableRowIndex, &str)> {
+        if self.next_row != cursor_row {
+            return None;
+        }
+        let row = if self.partial.is_empty() {
+            self.next_row
+        } else {
+            self.partial_row
+        };
+        Some((row, &self.partial))
+    }
+
+    /// The number of rows that can be taken without exceeding the
+    /// capacity; each row completes at most one line
+    pub fn free(&self) -> usize {
+        self.capacity.saturating_sub(self.lines.len())
+    }
+
+    /// Takes `rows`, which start at `first_row`.  If that is beyond
+    /// the next row, the rows in between were pruned from the
+    /// scrollback and are counted as dropped.
+    pub fn ingest(&mut self, first_row: StableRowIndex, rows: &[Line]) {
+        if first_row > self.next_row {
+            self.dropped += (first_row - self.next_row) as usize;
+            self.partial.clear();
+        }
+        for (idx, row) in rows.iter().enumerate() {
+            if self.partial.is_empty() {
+                self.partial_row = first_row + idx as StableRowIndex;
+            }
+            let text = row.as_str();
+            if row.last_cell_was_wrapped() {
+                self.partial.push_str(&text);
+            } else {
+                self.partial.push_str(text.trim_end());
+                self.lines
+                    .push_back((self.partial_row, std::mem::take(&mut self.partial)));
+            }
+        }
+        self.next_row = first_row + rows.len() as StableRowIndex;
+    }
+
+    /// Takes the rows of `pane` that are complete, as far as
+    /// the capacity allows.  Returns false if there were none.
+    pub fn pump(&mut self, pane: &dyn Pane) -> bool {
+        if pane.is_alt_screen_active() {
+            // The alternate screen is redrawn in place rather
+            // than producing lines of output
+            return false;
+        }
+        let cursor = pane.get_cursor_position();
+        let dims = pane.get_dimensions();
+        let start = self.next_row.max(dims.scrollback_top);
+        let end = cursor.y.min(start + self.free() as StableRowIndex);
+        if start >= end {
+            return false;
+        }
+        let (first_row, rows) = pane.get_lines(start..end);
+        self.ingest(first_row, &rows);
+        true
+    }
+
+    /// Removes and returns up to `max` lines, along with the row
+    /// on which each of them starts
+    pub fn take_with_rows(&mut self, max: usize) -> Vec<(StableRowIndex, String)> {
+        let n = max.min(self.lines.len());
+        self.lines.drain(..n).collect()
+    }
+
+    pub fn take(&mut self, max: usize) -> Vec<String> {
+        self.take_with_rows(max)
+            .into_iter()
+            .map(|(_row, line)| line)
+            .collect()
+    }
+}
+
+/// Returns a row holding `text`, which continues on the next row
+/// if `wrapped` is true
+#[cfg(test)]
+pub(crate) fn test_row(text: &str, wrapped: bool) -> Line {
+    let mut line = Line::from_text(text, &wezterm_term::CellAttributes::blank(), 1, None);
+    line.set_last_cell_was_wrapped(wrapped, 1);
+    line
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn joins_wrapped_rows() {
+        let mut buffer = LineBuffer::new(0, 10);
+        buffer.ingest(0, &[test_row("hello ", true), test_row("world   ", false)]);
+        buffer.ingest(2, &[test_row("next", false)]);
+        assert_eq!(
+            buffer.take_with_rows(10),
+            vec![(0, "hello world".to_string()), (2, "next".to_string())]
+        );
+        assert_eq!(buffer.next_row, 3);
+        assert_eq!(buffer.dropped, 0);
+    }
+
+    #[test]
+    fn unfinished_line_follows_wrapped_rows() {
+        let mut buffer = LineBuffer::new(0, 10);
+        buffer.ingest(0, &[test_row("done", false), test_row("Pass", true)]);
+        assert_eq!(buffer.unfinished(2), Some((1, "Pass")));
+        // The cursor is further down than the rows that were taken
+        assert_eq!(buffer.unfinished(3), None);
+        buffer.ingest(2, &[test_row("word:", false)]);
+        assert_eq!(buffer.unfinished(3), Some((3, "")));
+    }
+
+    #[test]
+    fn counts_pruned_rows() {
+        let mut buffer = LineBuffer::new(5, 10);
+        buffer.ingest(8, &[test_row("after", false)]);
+        assert_eq!(buffer.dropped, 3);
+        assert_eq!(buffer.take(10), vec!["after"]);
+    }
+
+    #[test]
+    fn bounded_by_capacity() {
+        let mut buffer = LineBuffer::new(0, 2);
+        assert_eq!(buffer.free(), 2);
+        buffer.ingest(0, &[test_row("a", false), test_row("b", false)]);
+        assert_eq!(buffer.free(), 0);
+        assert_eq!(buffer.take(1), vec!["a"]);
+        assert_eq!(buffer.free(), 1);
+    }
+}
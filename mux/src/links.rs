@@ -0,0 +1,131 @@
+//! Links between pairs of panes that belong together, such as an
+//! editor and a repl, so that closing or activating one of them
+//! also affects its partner.
+use crate::pane::PaneId;
+use config::keyassignment::PaneLinkOptions;
+use luahelper::impl_lua_conversion_dynamic;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use wezterm_dynamic::{FromDynamic, ToDynamic};
+
+/// A link as seen from one of the panes that it joins.
+/// This type is used directly by the codec, take care to bump
+/// the codec version if you change this
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromDynamic, ToDynamic)]
+pub struct PaneLink {
+    /// The partner pane
+    pub pane_id: PaneId,
+    pub close_together: bool,
+    pub activate_together: bool,
+}
+impl_lua_conversion_dynamic!(PaneLink);
+
+#[derive(Debug, Default)]
+pub struct PaneLinks {
+    /// Keyed by the pair of pane ids, lowest first
+    links: HashMap<(PaneId, PaneId), PaneLinkOptions>,
+}
+
+fn pair(a: PaneId, b: PaneId) -> (PaneId, PaneId) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+impl PaneLinks {
+    /// Links `a` with `b`, replacing the options of any existing link
+    /// between them. If `options` has neither option set, any existing
+    /// link is removed instead.
+    pub fn link(&mut self, a: PaneId, b: PaneId, options: PaneLinkOptions) {
+        if options.is_empty() {
+            self.links.remove(&pair(a, b));
+        } else {
+            self.links.insert(pair(a, b), options);
+        }
+    }
+
+    /// Returns the links of `pane_id`, ordered by partner pane id
+    pub fn links_of(&self, pane_id: PaneId) -> Vec<PaneLink> {
+        let mut links: Vec<PaneLink> = self
+            .links
+            .iter()
+            .filter_map(|(&(a, b), options)| {
+                let partner = if a == pane_id {
+                    b
+                } else if b == pane_id {
+                    a
+                } else {
+                    return None;
+                };
+                Some(PaneLink {
+                    pane_id: partner,
+                    close_together: options.close_together,
+                    activate_together: options.activate_together,
+                })
+            })
+            .collect();
+        links.sort_by_key(|link| link.pane_id);
+        links
+    }
+
+    /// Severs all of the links of `pane_id`
+    pub fn remove_pane(&mut self, pane_id: PaneId) {
+        self.links.retain(|&(a, b), _| a != pane_id && b != pane_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn links() {
+        let mut links = PaneLinks::default();
+        links.link(3, 1, PaneLinkOptions::default());
+        links.link(
+            1,
+            2,
+            PaneLinkOptions {
+                close_together: false,
+                activate_together: true,
+            },
+        );
+
+        assert_eq!(
+            links.links_of(1),
+            vec![
+                PaneLink {
+                    pane_id: 2,
+                    close_together: false,
+                    activate_together: true,
+                },
+                PaneLink {
+                    pane_id: 3,
+                    close_together: true,
+                    activate_together: false,
+                },
+            ]
+        );
+        assert_eq!(links.links_of(3)[0].pane_id, 1);
+
+        // Linking again replaces the options rather than adding a link
+        links.link(1, 3, PaneLinkOptions::default());
+        assert_eq!(links.links_of(3).len(), 1);
+
+        // A link without any options is removed
+        links.link(
+            2,
+            1,
+            PaneLinkOptions {
+                close_together: false,
+                activate_together: false,
+            },
+        );
+        assert!(links.links_of(2).is_empty());
+
+        links.remove_pane(3);
+        assert!(links.links_of(1).is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+//! The representation of the panes in the mux that is output by
+//! `wezterm cli list --format json`, and that is also served by the
+//! `mux_http_status` endpoint.
+//! As such it is intended to be a stable output format,
+//! Thus we need to be careful about both the fields and their types,
+//! herein as they are directly reflected in the output.
+use crate::pane::PaneId;
+use crate::tab::{PaneEntry, PaneNode, TabId};
+use crate::window::WindowId;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use wezterm_term::TerminalSize;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PaneListSize {
+    pub rows: usize,
+    pub cols: usize,
+    /// Pixel width of the pane, if known (can be zero)
+    pub pixel_width: usize,
+    /// Pixel height of the pane, if known (can be zero)
+    pub pixel_height: usize,
+    /// dpi of the pane, if known (can be zero)
+    pub dpi: u32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PaneListItem {
+    pub window_id: WindowId,
+    pub tab_id: TabId,
+    pub pane_id: PaneId,
+    pub workspace: String,
+    pub size: PaneListSize,
+    pub title: String,
+    pub cwd: String,
+    /// Cursor x coordinate from top left of non-scrollback pane area
+    pub cursor_x: usize,
+    /// Cursor y coordinate from top left of non-scrollback pane area
+    pub cursor_y: usize,
+    pub cursor_shape: termwiz::surface::CursorShape,
+    pub cursor_visibility: termwiz::surface::CursorVisibility,
+    /// Number of cols from the left of the tab area to the left of this pane
+    pub left_col: usize,
+    /// Number of rows from the top of the tab area to the top of this pane
+    pub top_row: usize,
+    pub tab_title: String,
+    pub window_title: String,
+    /// true if the tab title was set by the user, rather than
+    /// by an application in the tab
+    pub tab_title_is_override: bool,
+    /// true if the window title was set by the user, rather than
+    /// by an application in the window
+    pub window_title_is_override: bool,
+    pub is_active: bool,
+    pub is_zoomed: bool,
+    /// true if the pane floats above the other panes of its tab
+    pub is_floating: bool,
+    pub tty_name: Option<String>,
+}
+
+impl PaneListItem {
+    pub fn from_entry(
+        pane: PaneEntry,
+        tab_title: &str,
+        tab_title_is_override: bool,
+        window_title: &str,
+        window_title_is_override: bool,
+    ) -> Self {
+        let PaneEntry {
+            window_id,
+            tab_id,
+            pane_id,
+            workspace,
+            title,
+            working_dir,
+            cursor_pos,
+            physical_top,
+            left_col,
+            top_row,
+            is_active_pane,
+            is_zoomed_pane,
+            is_floating,
+            tty_name,
+            size:
+                TerminalSize {
+                    rows,
+                    cols,
+                    pixel_width,
+                    pixel_height,
+                    dpi,
+                },
+            ..
+        } = pane;
+
+        Self {
+            window_id,
+            tab_id,
+            pane_id,
+            workspace,
+            size: PaneListSize {
+                rows,
+                cols,
+                pixel_width,
+                pixel_height,
+                dpi,
+            },
+            title,
+            cwd: working_dir
+                .as_ref()
+                .map(|url| url.url.as_str())
+                .unwrap_or("")
+                .to_string(),
+            cursor_x: cursor_pos.x,
+            cursor_y: cursor_pos.y.saturating_sub(physical_top) as usize,
+            cursor_shape: cursor_pos.shape,
+            cursor_visibility: cursor_pos.visibility,
+            left_col,
+            top_row,
+            tab_title: tab_title.to_string(),
+            window_title: window_title.to_string(),
+            tab_title_is_override,
+            window_title_is_override,
+            is_active: is_active_pane,
+            is_zoomed: is_zoomed_pane,
+            is_floating,
+            tty_name,
+        }
+    }
+}
+
+/// Flattens the pane trees of the tabs, as returned by the `ListPanes`
+/// PDU, into a list of items in the order that the panes appear in
+/// each tab.  The floating panes of a tab follow its other panes.
+pub fn pane_list_items(
+    tabs: Vec<PaneNode>,
+    floating_panes: Vec<Vec<PaneEntry>>,
+    tab_titles: &[String],
+    window_titles: &HashMap<WindowId, String>,
+    tab_title_overrides: &[bool],
+    window_title_overrides: &HashSet<WindowId>,
+) -> Vec<PaneListItem> {
+    let mut items = vec![];
+    let mut floating_panes = floating_panes.into_iter();
+    for (idx, (tabroot, tab_title)) in tabs.into_iter().zip(tab_titles.iter()).enumerate() {
+        let tab_title_is_override = tab_title_overrides.get(idx).copied().unwrap_or(false);
+        let item = |entry: PaneEntry| {
+            let window_title = window_titles
+                .get(&entry.window_id)
+                .map(|s| s.as_str())
+                .unwrap_or("");
+            let window_title_is_override = window_title_overrides.contains(&entry.window_id);
+            PaneListItem::from_entry(
+                entry,
+                tab_title,
+                tab_title_is_override,
+                window_title,
+                window_title_is_override,
+            )
+        };
+        let mut cursor = tabroot.into_tree().cursor();
+
+        loop {
+            if let Some(entry) = cursor.leaf_mut() {
+                items.push(item(entry.clone()));
+            }
+            match cursor.preorder_next() {
+                Ok(c) => cursor = c,
+                Err(_) => break,
+            }
+        }
+
+        for entry in floating_panes.next().unwrap_or_default() {
+            items.push(item(entry));
+        }
+    }
+    items
+}
@@ -1,18 +1,25 @@
-use crate::domain::DomainId;
+use crate::annotation::PaneAnnotations;
+use crate::domain::{DomainId, WriterWrapper};
+use crate::highlight::PaneHighlights;
+use crate::location::{choose_location, CwdTracker, PaneLocation};
+use crate::monitor::{OutputAlerts, OutputMonitor};
+use crate::outputpause::OutputPause;
+use crate::outputrate::OutputRate;
 use crate::pane::{
     CachePolicy, CloseReason, ForEachPaneLogicalLine, LogicalLine, Pane, PaneId, Pattern,
     SearchResult, WithPaneLines,
 };
+use crate::pane_watch::{PaneWatch, PaneWatches};
 use crate::renderable::*;
 use crate::tmux::{TmuxDomain, TmuxDomainState};
 use crate::{Domain, Mux, MuxNotification};
 use anyhow::Error;
 use async_trait::async_trait;
 use config::keyassignment::ScrollbackEraseMode;
-use config::{configuration, ExitBehavior, ExitBehaviorMessaging};
+use config::{configuration, ColumnGuide, ExitBehavior, ExitBehaviorMessaging, PaneEncoding};
 use fancy_regex::Regex;
 use parking_lot::{MappedMutexGuard, Mutex, MutexGuard};
-use portable_pty::{Child, ChildKiller, ExitStatus, MasterPty, PtySize};
+use portable_pty::{Child, ChildKiller, CommandBuilder, ExitStatus, MasterPty, PtySize};
 use procinfo::LocalProcessInfo;
 use rangeset::RangeSet;
 use smol::channel::{bounded, Receiver, TryRecvError};
@@ -21,21 +28,27 @@ use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryInto;
 use std::io::{Result as IoResult, Write};
 use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use termwiz::escape::csi::{Sgr, CSI};
+use termwiz::escape::osc::Progress;
 use termwiz::escape::{Action, DeviceControlMode};
 use termwiz::input::KeyboardEncoding;
 use termwiz::surface::{Line, SequenceNo};
 use url::Url;
+use wezterm_bidi::ParagraphDirectionHint;
 use wezterm_dynamic::Value;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
     Alert, AlertHandler, Clipboard, DownloadHandler, KeyCode, KeyModifiers, MouseEvent,
-    SemanticZone, StableRowIndex, Terminal, TerminalConfiguration, TerminalSize,
+    MousePointerShape, SemanticZone, StableRowIndex, Terminal, TerminalConfiguration, TerminalSize,
 };
 
 const PROC_INFO_CACHE_TTL: Duration = Duration::from_millis(300);
+/// How long the previous process of a respawned pane has to exit
+/// after being asked to, before it is forcibly killed
+const RESPAWN_KILL_GRACE: Duration = Duration::from_millis(500);
 
 #[derive(Debug)]
 enum ProcessState {
@@ -49,6 +62,8 @@ enum ProcessState {
     DeadPendingClose {
         killed: bool,
     },
+    /// The previous process is being terminated ahead of a respawn
+    Respawning,
     Dead,
 }
 
@@ -132,7 +147,38 @@ pub struct LocalPane {
     proc_list: Mutex<Option<CachedProcInfo>>,
     #[cfg(unix)]
     leader: Arc<Mutex<Option<CachedLeaderInfo>>>,
-    command_description: String,
+    command_description: Mutex<String>,
+    respawn_info: Mutex<Option<RespawnInfo>>,
+    /// The command line that the pane was spawned with, when it was
+    /// spawned with an explicit command rather than the default program
+    spawn_argv: Mutex<Option<Vec<String>>>,
+    pty_generation: AtomicUsize,
+    #[cfg(unix)]
+    reader_suspender: Mutex<Option<ReaderSuspender>>,
+    output_monitor: Mutex<OutputMonitor>,
+    watches: Mutex<PaneWatches>,
+    output_rate: Arc<Mutex<OutputRate>>,
+    output_pause: Arc<OutputPause>,
+    highlights: Arc<Mutex<PaneHighlights>>,
+    annotations: Arc<Mutex<PaneAnnotations>>,
+    encoding: Arc<Mutex<PaneEncoding>>,
+    column_guides: Arc<Mutex<Option<Vec<ColumnGuide>>>>,
+    pinned: AtomicBool,
+    ime_enabled: AtomicBool,
+    cwd_tracker: Mutex<CwdTracker>,
+    /// Set by the notification handler when the shell reports its cwd,
+    /// so that the foreground process can be sampled before the next
+    /// output is processed
+    cwd_reported: Arc<AtomicBool>,
+    timestamp_gutter_cols: AtomicUsize,
+}
+
+/// The state needed to respawn the process in a pane
+struct RespawnInfo {
+    /// The command most recently spawned into the pane
+    command: CommandBuilder,
+    /// The writer shared between the pane and its Terminal
+    writer: WriterWrapper,
 }
 
 #[async_trait(?Send)]
@@ -219,6 +265,42 @@ impl Pane for LocalPane {
         self.terminal.lock().user_vars().clone()
     }
 
+    fn pty_generation(&self) -> usize {
+        self.pty_generation.load(Ordering::SeqCst)
+    }
+
+    fn idle_duration(&self) -> Option<Duration> {
+        self.output_monitor.lock().idle_duration(Instant::now())
+    }
+
+    fn is_pinned(&self) -> bool {
+        self.pinned.load(Ordering::Relaxed)
+    }
+
+    fn set_pinned(&self, pinned: bool) {
+        self.pinned.store(pinned, Ordering::Relaxed);
+    }
+
+    fn is_ime_enabled(&self) -> bool {
+        self.ime_enabled.load(Ordering::Relaxed)
+    }
+
+    fn set_ime_enabled(&self, enabled: bool) {
+        self.ime_enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    fn set_bidi_mode(&self, enabled: Option<bool>, hint: Option<ParagraphDirectionHint>) {
+        self.terminal.lock().set_bidi_mode(enabled, hint);
+    }
+
+    fn timestamp_gutter_cols(&self) -> usize {
+        self.timestamp_gutter_cols.load(Ordering::Relaxed)
+    }
+
+    fn set_timestamp_gutter_cols(&self, cols: usize) {
+        self.timestamp_gutter_cols.store(cols, Ordering::Relaxed);
+    }
+
     fn exit_behavior(&self) -> Option<ExitBehavior> {
         // If we are ssh, and we've not yet fully connected,
         // then override exit_behavior so that we can show
@@ -238,6 +320,8 @@ impl Pane for LocalPane {
     }
 
     fn kill(&self) {
+        // Let the reader drain the pty so that it can observe EOF
+        self.output_pause.release();
         let mut proc = self.process.lock();
         log::debug!(
             "killing process in pane {}, state is {:?}",
@@ -269,7 +353,8 @@ impl Pane for LocalPane {
         let mut terse = String::new();
         let mut brief = String::new();
         let mut trailer = String::new();
-        let cmd = &self.command_description;
+        let cmd = self.command_description.lock().clone();
+        let mut child_exited = false;
 
         match &mut *proc {
             ProcessState::Running {
@@ -321,6 +406,7 @@ impl Pane for LocalPane {
                         (ExitBehavior::Hold, _, true) => *proc = ProcessState::Dead,
                     }
                     log::debug!("child terminated, new state is {:?}", proc);
+                    child_exited = true;
                 }
             }
             ProcessState::DeadPendingClose { killed } => {
@@ -329,7 +415,7 @@ impl Pane for LocalPane {
                     log::debug!("child state -> {:?}", proc);
                 }
             }
-            ProcessState::Dead => {}
+            ProcessState::Respawning | ProcessState::Dead => {}
         }
 
         let mut notify = None;
@@ -360,11 +446,23 @@ impl Pane for LocalPane {
             emit_output_for_pane(self.pane_id, &notify);
         }
 
-        match &*proc {
+        let dead = match &*proc {
             ProcessState::Running { .. } => false,
             ProcessState::DeadPendingClose { .. } => false,
+            ProcessState::Respawning => false,
             ProcessState::Dead => true,
+        };
+        drop(proc);
+
+        if child_exited {
+            // Any keyboard protocol that the child negotiated
+            // no longer applies, and neither does its progress
+            let mut terminal = self.terminal.lock();
+            terminal.reset_keyboard_encoding();
+            terminal.set_progress(Progress::None);
         }
+
+        dead
     }
 
     fn set_clipboard(&self, clipboard: &Arc<dyn Clipboard>) {
@@ -384,16 +482,42 @@ impl Pane for LocalPane {
     }
 
     fn perform_actions(&self, actions: Vec<termwiz::escape::Action>) {
-        self.terminal.lock().perform_actions(actions)
+        let focused = {
+            let mut terminal = self.terminal.lock();
+            terminal.perform_actions(actions);
+            terminal.is_focused()
+        };
+        if self.cwd_reported.swap(false, Ordering::Relaxed) {
+            self.note_cwd_reported();
+        }
+        if self.output_monitor.lock().output(focused, Instant::now()) {
+            Mux::notify_from_any_thread(MuxNotification::Alert {
+                pane_id: self.pane_id,
+                alert: Alert::PaneOutputActivity,
+            });
+        }
+        let matches = self.watches.lock().pump(self);
+        for m in matches {
+            Mux::notify_from_any_thread(MuxNotification::Alert {
+                pane_id: self.pane_id,
+                alert: Alert::PaneMatch {
+                    watch_id: m.watch_id,
+                    text: m.text,
+                    row: m.row,
+                },
+            });
+        }
     }
 
     fn mouse_event(&self, event: MouseEvent) -> Result<(), Error> {
         Mux::get().record_input_for_current_identity();
+        self.output_monitor.lock().input(Instant::now());
         self.terminal.lock().mouse_event(event)
     }
 
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> Result<(), Error> {
         Mux::get().record_input_for_current_identity();
+        self.output_monitor.lock().input(Instant::now());
         if self.tmux_domain.lock().is_some() {
             log::error!("key: {:?}", key);
             if key == KeyCode::Char('q') {
@@ -407,6 +531,7 @@ impl Pane for LocalPane {
 
     fn key_up(&self, key: KeyCode, mods: KeyModifiers) -> Result<(), Error> {
         Mux::get().record_input_for_current_identity();
+        self.output_monitor.lock().input(Instant::now());
         self.terminal.lock().key_up(key, mods)
     }
 
@@ -423,6 +548,7 @@ impl Pane for LocalPane {
 
     fn writer(&self) -> MappedMutexGuard<dyn std::io::Write> {
         Mux::get().record_input_for_current_identity();
+        self.output_monitor.lock().input(Instant::now());
         MutexGuard::map(self.writer.lock(), |writer| {
             let w: &mut dyn std::io::Write = writer;
             w
@@ -430,11 +556,22 @@ impl Pane for LocalPane {
     }
 
     fn reader(&self) -> anyhow::Result<Option<Box<dyn std::io::Read + Send>>> {
-        Ok(Some(self.pty.lock().try_clone_reader()?))
+        let pty = self.pty.lock();
+        let reader = pty.try_clone_reader()?;
+
+        #[cfg(unix)]
+        if let Some(fd) = pty.as_raw_fd() {
+            let (reader, suspender) = SuspendableReader::new(reader, fd)?;
+            self.reader_suspender.lock().replace(suspender);
+            return Ok(Some(Box::new(reader)));
+        }
+
+        Ok(Some(reader))
     }
 
     fn send_paste(&self, text: &str) -> Result<(), Error> {
         Mux::get().record_input_for_current_identity();
+        self.output_monitor.lock().input(Instant::now());
         if self.tmux_domain.lock().is_some() {
             Ok(())
         } else {
@@ -479,12 +616,77 @@ impl Pane for LocalPane {
 
     fn focus_changed(&self, focused: bool) {
         self.terminal.lock().focus_changed(focused);
+        if focused {
+            self.output_monitor.lock().focused();
+        }
     }
 
     fn has_unseen_output(&self) -> bool {
         self.terminal.lock().has_unseen_output()
     }
 
+    fn mark_output_seen(&self) {
+        self.terminal.lock().mark_output_seen();
+    }
+
+    fn get_progress(&self) -> Progress {
+        self.terminal.lock().get_progress()
+    }
+
+    fn output_rate(&self) -> Option<Arc<Mutex<OutputRate>>> {
+        Some(Arc::clone(&self.output_rate))
+    }
+
+    fn output_pause(&self) -> Option<Arc<OutputPause>> {
+        Some(Arc::clone(&self.output_pause))
+    }
+
+    fn highlights(&self) -> Option<Arc<Mutex<PaneHighlights>>> {
+        Some(Arc::clone(&self.highlights))
+    }
+
+    fn annotations(&self) -> Option<Arc<Mutex<PaneAnnotations>>> {
+        Some(Arc::clone(&self.annotations))
+    }
+
+    fn encoding(&self) -> Option<Arc<Mutex<PaneEncoding>>> {
+        Some(Arc::clone(&self.encoding))
+    }
+
+    fn column_guides(&self) -> Option<Arc<Mutex<Option<Vec<ColumnGuide>>>>> {
+        Some(Arc::clone(&self.column_guides))
+    }
+
+    fn get_output_alerts(&self) -> OutputAlerts {
+        self.output_monitor.lock().alerts()
+    }
+
+    fn get_silence_threshold(&self) -> Option<Duration> {
+        self.output_monitor.lock().silence_threshold()
+    }
+
+    fn set_silence_threshold(&self, threshold: Option<Duration>) {
+        self.output_monitor.lock().set_silence_threshold(threshold);
+    }
+
+    fn check_silence(&self) -> Option<Duration> {
+        self.output_monitor.lock().check_silence(Instant::now())
+    }
+
+    fn add_watch(&self, watch: PaneWatch) -> anyhow::Result<()> {
+        let cursor_row = self.get_cursor_position().y;
+        self.watches.lock().add(watch, cursor_row)
+    }
+
+    fn remove_watch(&self, id: &str) -> bool {
+        self.watches.lock().remove(id)
+    }
+
+    fn prepend_scrollback(&self, lines: Vec<Line>) -> anyhow::Result<()> {
+        self.seed_scrollback(lines);
+        Ok(())
+    }
+
     fn is_mouse_grabbed(&self) -> bool {
         if self.tmux_domain.lock().is_some() {
             false
@@ -501,12 +703,28 @@ impl Pane for LocalPane {
         }
     }
 
+    fn is_grapheme_clustering_enabled(&self) -> bool {
+        self.terminal.lock().is_grapheme_clustering_enabled()
+    }
+
+    fn is_bracketed_paste_enabled(&self) -> bool {
+        self.terminal.lock().bracketed_paste_enabled()
+    }
+
+    fn get_mouse_pointer_shape(&self) -> Option<MousePointerShape> {
+        self.terminal.lock().mouse_pointer_shape()
+    }
+
     fn get_current_working_dir(&self, policy: CachePolicy) -> Option<Url> {
-        self.terminal
-            .lock()
-            .get_current_dir()
-            .cloned()
-            .or_else(|| self.divine_current_working_dir(policy))
+        self.get_location(policy).map(|location| location.url)
+    }
+
+    fn get_location(&self, policy: CachePolicy) -> Option<PaneLocation> {
+        let osc7 = self.terminal.lock().get_current_dir().cloned();
+        let stale = osc7.is_some() && self.cwd_tracker.lock().is_stale(self.foreground_pid());
+        choose_location(&configuration().cwd_source_priority, osc7, stale, || {
+            self.divine_current_working_dir(policy)
+        })
     }
 
     fn tty_name(&self) -> Option<String> {
@@ -531,6 +749,10 @@ impl Pane for LocalPane {
         self.divine_foreground_process(policy)
     }
 
+    fn get_foreground_process_env(&self) -> Option<HashMap<String, String>> {
+        LocalProcessInfo::environment(self.foreground_pid()?)
+    }
+
     fn get_foreground_process_name(&self, policy: CachePolicy) -> Option<String> {
         #[cfg(unix)]
         {
@@ -912,10 +1134,14 @@ impl wezterm_term::DeviceControlHandler for LocalPaneDCSHandler {
 
 struct LocalPaneNotifHandler {
     pane_id: PaneId,
+    cwd_reported: Arc<AtomicBool>,
 }
 
 impl AlertHandler for LocalPaneNotifHandler {
     fn alert(&mut self, alert: Alert) {
+        if let Alert::CurrentWorkingDirectoryChanged = alert {
+            self.cwd_reported.store(true, Ordering::Relaxed);
+        }
         let pane_id = self.pane_id;
         promise::spawn::spawn_into_main_thread(async move {
             let mux = Mux::get();
@@ -923,14 +1149,14 @@ impl AlertHandler for LocalPaneNotifHandler {
                 Alert::WindowTitleChanged(title) => {
                     if let Some((_domain, window_id, _tab_id)) = mux.resolve_pane_id(pane_id) {
                         if let Some(mut window) = mux.get_window_mut(window_id) {
-                            window.set_title(title);
+                            window.set_title_from_application(title);
                         }
                     }
                 }
                 Alert::TabTitleChanged(title) => {
                     if let Some((_domain, _window_id, tab_id)) = mux.resolve_pane_id(pane_id) {
                         if let Some(tab) = mux.get_tab(tab_id) {
-                            tab.set_title(title.as_deref().unwrap_or(""));
+                            tab.set_title_from_application(title.as_deref().unwrap_or(""));
                         }
                     }
                 }
@@ -976,6 +1202,47 @@ fn split_child(
     (rx, signaller, pid)
 }
 
+/// Asks the child to terminate, forcibly killing it if it is still
+/// running after `grace`, and waits until the thread that was spawned
+/// by split_child has reaped it.
+async fn terminate_child(
+    child_waiter: Receiver<IoResult<ExitStatus>>,
+    mut signaller: Box<dyn ChildKiller + Sync>,
+    pid: Option<u32>,
+    grace: Duration,
+) {
+    let _ = signaller.kill();
+
+    let exited = smol::future::or(
+        async {
+            child_waiter.recv().await.ok();
+            true
+        },
+        async {
+            smol::Timer::after(grace).await;
+            false
+        },
+    )
+    .await;
+    if exited {
+        return;
+    }
+
+    log::warn!("process {pid:?} did not exit within {grace:?}; killing it");
+    #[cfg(unix)]
+    if let Some(pid) = pid {
+        unsafe {
+            libc::kill(pid as i32, libc::SIGKILL);
+        }
+    }
+    #[cfg(windows)]
+    let _ = signaller.kill();
+
+    // The channel is closed once the waiter has reaped the child,
+    // even if the status was consumed elsewhere
+    child_waiter.recv().await.ok();
+}
+
 impl LocalPane {
     pub fn new(
         pane_id: PaneId,
@@ -992,7 +1259,11 @@ impl LocalPane {
             pane_id,
             tmux_domain: None,
         }));
-        terminal.set_notification_handler(Box::new(LocalPaneNotifHandler { pane_id }));
+        let cwd_reported = Arc::new(AtomicBool::new(false));
+        terminal.set_notification_handler(Box::new(LocalPaneNotifHandler {
+            pane_id,
+            cwd_reported: Arc::clone(&cwd_reported),
+        }));
 
         Self {
             pane_id,
@@ -1010,10 +1281,228 @@ impl LocalPane {
             proc_list: Mutex::new(None),
             #[cfg(unix)]
             leader: Arc::new(Mutex::new(None)),
-            command_description,
+            command_description: Mutex::new(command_description),
+            respawn_info: Mutex::new(None),
+            spawn_argv: Mutex::new(None),
+            pty_generation: AtomicUsize::new(0),
+            #[cfg(unix)]
+            reader_suspender: Mutex::new(None),
+            output_monitor: Mutex::new(OutputMonitor::new(Instant::now())),
+            watches: Mutex::new(PaneWatches::default()),
+            output_rate: Arc::new(Mutex::new(OutputRate::new(Instant::now()))),
+            output_pause: Arc::new(OutputPause::default()),
+            highlights: Arc::new(Mutex::new(PaneHighlights::default())),
+            annotations: Arc::new(Mutex::new(PaneAnnotations::default())),
+            encoding: Arc::new(Mutex::new(PaneEncoding::default())),
+            column_guides: Arc::new(Mutex::new(None)),
+            pinned: AtomicBool::new(false),
+            ime_enabled: AtomicBool::new(true),
+            cwd_tracker: Mutex::new(CwdTracker::default()),
+            cwd_reported,
+            timestamp_gutter_cols: AtomicUsize::new(0),
         }
     }
 
+    /// Shares the encoding of `writer`, so that changing the
+    /// encoding of the pane also changes how its input is encoded
+    pub(crate) fn with_encoding_of(mut self, writer: &WriterWrapper) -> Self {
+        self.encoding = writer.encoding();
+        self
+    }
+
+    /// Retain the command and writer used to spawn this pane,
+    /// so that the pane can later be respawned in place
+    pub(crate) fn set_respawn_info(&self, command: CommandBuilder, writer: WriterWrapper) {
+        self.respawn_info
+            .lock()
+            .replace(RespawnInfo { command, writer });
+    }
+
+    pub(crate) fn set_spawn_argv(&self, argv: Option<Vec<String>>) {
+        *self.spawn_argv.lock() = argv;
+    }
+
+    /// Returns the command line that the pane was spawned with, or
+    /// None if it is running the default program
+    pub fn spawn_argv(&self) -> Option<Vec<String>> {
+        self.spawn_argv.lock().clone()
+    }
+
+    /// Returns the command most recently spawned into this pane
+    pub(crate) fn respawn_command(&self) -> Option<CommandBuilder> {
+        self.respawn_info
+            .lock()
+            .as_ref()
+            .map(|info| info.command.clone())
+    }
+
+    /// Places the provided lines above the existing content of the
+    /// scrollback, returning the stable row index of the first row
+    /// that follows them
+    pub fn seed_scrollback(&self, lines: Vec<Line>) -> StableRowIndex {
+        let row = self.terminal.lock().seed_scrollback(lines);
+        Mux::notify_from_any_thread(MuxNotification::PaneOutput(self.pane_id));
+        row
+    }
+
+    pub(crate) fn pty_size(&self) -> anyhow::Result<PtySize> {
+        self.pty.lock().get_size()
+    }
+
+    /// Terminates the process of this pane and waits until it has
+    /// been reaped, in preparation for `respawn`.  The pane is not
+    /// considered to be dead while this is in progress.
+    pub(crate) async fn terminate_for_respawn(&self) -> anyhow::Result<()> {
+        if self.respawn_info.lock().is_none() {
+            anyhow::bail!("pane {} cannot be respawned", self.pane_id);
+        }
+
+        // Bump the generation before killing the old process, so that
+        // its reader doesn't apply the exit_behavior to this pane
+        self.pty_generation.fetch_add(1, Ordering::SeqCst);
+
+        let previous = std::mem::replace(&mut *self.process.lock(), ProcessState::Respawning);
+        if let ProcessState::Running {
+            child_waiter,
+            signaller,
+            pid,
+            ..
+        } = previous
+        {
+            terminate_child(child_waiter, signaller, pid, RESPAWN_KILL_GRACE).await;
+        }
+        Ok(())
+    }
+
+    /// Called when spawning the replacement process failed after
+    /// `terminate_for_respawn`, so that the pane holds its content
+    /// until it is closed
+    pub(crate) fn abandon_respawn(&self) {
+        let mut proc = self.process.lock();
+        if let ProcessState::Respawning = &*proc {
+            *proc = ProcessState::DeadPendingClose { killed: false };
+        }
+    }
+
+    /// Swap in a newly spawned process and its pty.  The previous
+    /// process must have been stopped by `terminate_for_respawn`.
+    /// The terminal state and scrollback are kept.
+    pub(crate) fn respawn(
+        &self,
+        process: Box<dyn Child + Send>,
+        pty: Box<dyn MasterPty>,
+        writer: Box<dyn Write + Send>,
+        command: CommandBuilder,
+        command_description: String,
+    ) -> anyhow::Result<()> {
+        let mut respawn_info = self.respawn_info.lock();
+        let info = respawn_info
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("pane {} cannot be respawned", self.pane_id))?;
+
+        {
+            let (child_waiter, signaller, pid) = split_child(process);
+            *self.process.lock() = ProcessState::Running {
+                child_waiter,
+                pid,
+                signaller,
+                killed: false,
+            };
+        }
+
+        *self.pty.lock() = pty;
+        info.writer.replace(writer);
+        info.command = command;
+        *self.command_description.lock() = command_description;
+        self.proc_list.lock().take();
+        #[cfg(unix)]
+        self.leader.lock().take();
+
+        // Visually separate the output of the old process from the new
+        let mut parser = termwiz::escape::parser::Parser::new();
+        let mut actions = vec![Action::CSI(CSI::Sgr(Sgr::Reset))];
+        parser.parse(
+            "\r\n\x1b[2m── pane respawned ──\x1b[0m\r\n".as_bytes(),
+            |action| actions.push(action),
+        );
+        self.terminal.lock().perform_actions(actions);
+
+        Ok(())
+    }
+
+    /// Returns true if the pty and process of this pane can be handed
+    /// off to another process via `prepare_handoff`
+    #[cfg(unix)]
+    pub fn can_hand_off(&self) -> bool {
+        self.reader_suspender.lock().is_some()
+            && self.tmux_domain.lock().is_none()
+            && matches!(
+                &*self.process.lock(),
+                ProcessState::Running { pid: Some(_), .. }
+            )
+    }
+
+    /// Stops the thread that reads from the pty, without closing the
+    /// pty, and waits until the output that was read has been applied
+    /// to the terminal.  `Mux::start_pane_reader` resumes reading.
+    #[cfg(unix)]
+    pub async fn suspend_reader(&self) -> anyhow::Result<()> {
+        let suspender = self
+            .reader_suspender
+            .lock()
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("pane {} has no pty reader", self.pane_id))?;
+
+        // Bump the generation so that the reader doesn't treat this
+        // as the process having exited
+        self.pty_generation.fetch_add(1, Ordering::SeqCst);
+
+        let ReaderSuspender {
+            mut suspend,
+            stopped,
+        } = suspender;
+        suspend.write_all(b"x")?;
+        // The channel is closed when the reader thread finishes
+        stopped.recv().await.ok();
+        Ok(())
+    }
+
+    /// Captures what is needed to reconstruct this pane in another
+    /// process.  The reader must have been suspended first, so that
+    /// no output is lost between the snapshot and the handoff.
+    #[cfg(unix)]
+    pub fn prepare_handoff(&self) -> anyhow::Result<crate::handoff::PaneHandoff> {
+        let pid = match &*self.process.lock() {
+            ProcessState::Running { pid: Some(pid), .. } => *pid,
+            _ => anyhow::bail!("pane {} has no running process", self.pane_id),
+        };
+        let pty = self.pty.lock();
+        let fd = pty
+            .as_raw_fd()
+            .ok_or_else(|| anyhow::anyhow!("pane {} has no local pty", self.pane_id))?;
+        let fd = unsafe { std::os::fd::BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()?;
+        let terminal = self.terminal.lock();
+
+        Ok(crate::handoff::PaneHandoff {
+            pane_id: self.pane_id,
+            fd,
+            pid,
+            tty_name: pty.tty_name(),
+            command_description: self.command_description.lock().clone(),
+            size: terminal.get_size(),
+            snapshot: terminal.snapshot(),
+        })
+    }
+
+    /// Called once another process has taken ownership of the pty.
+    /// The pane is treated as dead from here on, but dropping it will
+    /// neither kill the process nor send EOF to it.
+    #[cfg(unix)]
+    pub fn complete_handoff(&self) {
+        self.pty.lock().disable_eof_on_drop();
+        *self.process.lock() = ProcessState::Dead;
+    }
+
     #[cfg(unix)]
     fn get_leader(&self, policy: CachePolicy) -> CachedLeaderInfo {
         let mut leader = self.leader.lock();
@@ -1040,6 +1529,27 @@ impl LocalPane {
         (*leader).clone().unwrap()
     }
 
+    /// Returns the id of the foreground process group, where that
+    /// can be determined
+    fn foreground_pid(&self) -> Option<u32> {
+        #[cfg(unix)]
+        {
+            return self.pty.lock().process_group_leader().map(|pid| pid as u32);
+        }
+
+        #[allow(unreachable_code)]
+        None
+    }
+
+    /// Called when the shell reports its cwd, to remember which
+    /// process was in the foreground at the time.  This is called from
+    /// `perform_actions` rather than later on the main thread, because
+    /// by then the foreground may already have changed.
+    fn note_cwd_reported(&self) {
+        let foreground = self.foreground_pid();
+        self.cwd_tracker.lock().osc7_reported(foreground);
+    }
+
     fn divine_current_working_dir(&self, policy: CachePolicy) -> Option<Url> {
         #[cfg(unix)]
         {
@@ -1052,10 +1562,10 @@ impl LocalPane {
 
         #[cfg(windows)]
         if let Some(fg) = self.divine_foreground_process(policy) {
-            // Since windows paths typically start with something like C:\,
-            // we cannot simply stick `localhost` on the front; we have to
-            // omit the hostname otherwise the url parser is unhappy.
-            return Url::parse(&format!("file://{}", fg.cwd.display())).ok();
+            // Windows paths need their separators and drive letter
+            // normalized, and may be UNC paths, so we cannot simply
+            // stick `file://` on the front of them.
+            return wezterm_term::path_to_file_url(&fg.cwd.to_string_lossy());
         }
 
         #[allow(unreachable_code)]
@@ -1126,8 +1636,84 @@ impl LocalPane {
     }
 }
 
+/// Used to stop a `SuspendableReader`
+#[cfg(unix)]
+struct ReaderSuspender {
+    suspend: filedescriptor::FileDescriptor,
+    stopped: Receiver<()>,
+}
+
+/// Wraps the pty reader so that the reader thread can be stopped
+/// without closing the pty, which is needed in order to hand the
+/// pty off to another process.
+/// The reader waits on both the pty and a pipe that belongs to the
+/// pane, so writing to the pipe wakes it without resorting to signals.
+#[cfg(unix)]
+struct SuspendableReader {
+    reader: Box<dyn std::io::Read + Send>,
+    pty: filedescriptor::FileDescriptor,
+    suspend: filedescriptor::FileDescriptor,
+    /// Closes the channel when the reader thread drops us
+    _stopped: smol::channel::Sender<()>,
+}
+
+#[cfg(unix)]
+impl SuspendableReader {
+    fn new(
+        reader: Box<dyn std::io::Read + Send>,
+        pty_fd: std::os::fd::RawFd,
+    ) -> anyhow::Result<(Self, ReaderSuspender)> {
+        let pty = filedescriptor::FileDescriptor::dup(&unsafe {
+            std::os::fd::BorrowedFd::borrow_raw(pty_fd)
+        })?;
+        let pipe = filedescriptor::Pipe::new()?;
+        let (tx, rx) = bounded(1);
+        Ok((
+            Self {
+                reader,
+                pty,
+                suspend: pipe.read,
+                _stopped: tx,
+            },
+            ReaderSuspender {
+                suspend: pipe.write,
+                stopped: rx,
+            },
+        ))
+    }
+}
+
+#[cfg(unix)]
+impl std::io::Read for SuspendableReader {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        use filedescriptor::{poll, pollfd, AsRawSocketDescriptor, POLLIN};
+        use std::io::Read;
+        let mut pfd = [
+            pollfd {
+                fd: self.pty.as_socket_descriptor(),
+                events: POLLIN,
+                revents: 0,
+            },
+            pollfd {
+                fd: self.suspend.as_socket_descriptor(),
+                events: POLLIN,
+                revents: 0,
+            },
+        ];
+        poll(&mut pfd, None).map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+        if pfd[1].revents != 0 {
+            // Report EOF so that the reader thread winds down
+            return Ok(0);
+        }
+        self.reader.read(buf)
+    }
+}
+
 impl Drop for LocalPane {
     fn drop(&mut self) {
+        // Don't leave the reader thread waiting for a resume
+        // that will never come
+        self.output_pause.release();
         // Avoid lingering zombies if we can, but don't block forever.
         // <https://github.com/wez/wezterm/issues/558>
         if let ProcessState::Running { signaller, .. } = &mut *self.process.lock() {
@@ -1135,3 +1721,71 @@ impl Drop for LocalPane {
         }
     }
 }
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    /// Spawns `script` and waits until it reports that it is ready,
+    /// returning its pid along with the waiter and signaller that
+    /// split_child would have produced
+    fn spawn_ready(
+        script: &str,
+    ) -> (
+        u32,
+        Receiver<IoResult<ExitStatus>>,
+        Box<dyn ChildKiller + Sync>,
+    ) {
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", script])
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        assert_eq!(line, "ready\n");
+
+        let pid = child.id();
+        let signaller = ChildKiller::clone_killer(&child);
+        let (tx, rx) = bounded(1);
+        std::thread::spawn(move || {
+            tx.try_send(Child::wait(&mut child)).ok();
+        });
+        (pid, rx, signaller)
+    }
+
+    fn pid_is_gone(pid: u32) -> bool {
+        let result = unsafe { libc::kill(pid as i32, 0) };
+        result == -1 && std::io::Error::last_os_error().raw_os_error() == Some(libc::ESRCH)
+    }
+
+    #[test]
+    fn terminate_reaps_child() {
+        let (pid, waiter, signaller) = spawn_ready("echo ready; exec sleep 30");
+        let started = Instant::now();
+        smol::block_on(terminate_child(
+            waiter,
+            signaller,
+            Some(pid),
+            Duration::from_secs(30),
+        ));
+        // SIGHUP was enough, so there was no need to wait out the grace period
+        assert!(started.elapsed() < Duration::from_secs(30));
+        assert!(pid_is_gone(pid));
+    }
+
+    #[test]
+    fn terminate_kills_child_that_ignores_sighup() {
+        let (pid, waiter, signaller) = spawn_ready("trap '' HUP; echo ready; exec sleep 30");
+        smol::block_on(terminate_child(
+            waiter,
+            signaller,
+            Some(pid),
+            Duration::from_millis(100),
+        ));
+        assert!(pid_is_gone(pid));
+    }
+}
@@ -0,0 +1,157 @@
+//! Decides which of the available sources provides the current working
+//! directory of a pane.
+//! The shell integration reports the cwd using OSC 7, but that report
+//! goes stale when the user runs something that doesn't have the
+//! integration, such as `sudo -i` or a shell inside a container.
+//! We notice that by remembering which process was in the foreground
+//! when OSC 7 was last reported; if a different process has since
+//! taken the foreground, asking the operating system for the cwd of
+//! that process is preferred, where that is possible.
+use config::CwdSource;
+use url::Url;
+
+/// The current working directory of a pane, along with where it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneLocation {
+    pub url: Url,
+    /// None if the pane cannot tell, such as for remote panes
+    pub source: Option<CwdSource>,
+    /// True if this is an OSC 7 report that predates the current
+    /// foreground process, and no better source was available
+    pub stale: bool,
+}
+
+impl PaneLocation {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            source: None,
+            stale: false,
+        }
+    }
+}
+
+/// Remembers the foreground process at the time of the last OSC 7 report
+#[derive(Debug, Default)]
+pub struct CwdTracker {
+    osc7_foreground: Option<u32>,
+}
+
+impl CwdTracker {
+    pub fn osc7_reported(&mut self, foreground: Option<u32>) {
+        self.osc7_foreground = foreground;
+    }
+
+    /// Returns true if the foreground process has changed since OSC 7
+    /// was last reported.  If either process is unknown, the report is
+    /// given the benefit of the doubt.
+    pub fn is_stale(&self, foreground: Option<u32>) -> bool {
+        match (self.osc7_foreground, foreground) {
+            (Some(reported), Some(current)) => reported != current,
+            _ => false,
+        }
+    }
+}
+
+/// Consults the sources in the order given by `priority`, skipping
+/// `osc7` if it is stale.  A stale OSC 7 value is returned only if none
+/// of the other sources had an answer.
+pub fn choose_location<F: FnOnce() -> Option<Url>>(
+    priority: &[CwdSource],
+    osc7: Option<Url>,
+    osc7_stale: bool,
+    process: F,
+) -> Option<PaneLocation> {
+    let mut process = Some(process);
+    for source in priority {
+        let url = match source {
+            CwdSource::Osc7 if !osc7_stale => osc7.clone(),
+            CwdSource::Osc7 => None,
+            CwdSource::Process => process.take().and_then(|process| process()),
+        };
+        if let Some(url) = url {
+            return Some(PaneLocation {
+                url,
+                source: Some(*source),
+                stale: false,
+            });
+        }
+    }
+
+    if osc7_stale && priority.contains(&CwdSource::Osc7) {
+        osc7.map(|url| PaneLocation {
+            url,
+            source: Some(CwdSource::Osc7),
+            stale: true,
+        })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn url(path: &str) -> Url {
+        Url::parse(&format!("file://localhost{path}")).unwrap()
+    }
+
+    const DEFAULT: &[CwdSource] = &[CwdSource::Osc7, CwdSource::Process];
+
+    #[test]
+    fn osc7_then_process_change() {
+        let mut tracker = CwdTracker::default();
+
+        // The shell reports its cwd at the prompt
+        tracker.osc7_reported(Some(100));
+        assert!(!tracker.is_stale(Some(100)));
+        let location = choose_location(DEFAULT, Some(url("/home/me")), false, || {
+            Some(url("/home/me/other"))
+        })
+        .unwrap();
+        assert_eq!(location.url, url("/home/me"));
+        assert_eq!(location.source, Some(CwdSource::Osc7));
+
+        // `sudo -i` takes the foreground; the report is now stale and
+        // the process cwd wins
+        assert!(tracker.is_stale(Some(200)));
+        let location =
+            choose_location(DEFAULT, Some(url("/home/me")), true, || Some(url("/root"))).unwrap();
+        assert_eq!(location.url, url("/root"));
+        assert_eq!(location.source, Some(CwdSource::Process));
+        assert!(!location.stale);
+
+        // If the process cwd can't be queried, the stale report is
+        // still better than nothing
+        let location = choose_location(DEFAULT, Some(url("/home/me")), true, || None).unwrap();
+        assert_eq!(location.url, url("/home/me"));
+        assert!(location.stale);
+
+        // A report from the new foreground process is fresh again
+        tracker.osc7_reported(Some(200));
+        assert!(!tracker.is_stale(Some(200)));
+
+        // Unknown processes don't make the report stale
+        assert!(!tracker.is_stale(None));
+        assert!(!CwdTracker::default().is_stale(Some(100)));
+    }
+
+    #[test]
+    fn priority() {
+        let process_first = &[CwdSource::Process, CwdSource::Osc7];
+        let location =
+            choose_location(process_first, Some(url("/a")), false, || Some(url("/b"))).unwrap();
+        assert_eq!(location.source, Some(CwdSource::Process));
+
+        let location = choose_location(process_first, Some(url("/a")), false, || None).unwrap();
+        assert_eq!(location.source, Some(CwdSource::Osc7));
+
+        let osc7_only = &[CwdSource::Osc7];
+        assert_eq!(
+            choose_location(osc7_only, None, false, || Some(url("/b"))),
+            None
+        );
+        assert_eq!(choose_location(&[], Some(url("/a")), false, || None), None);
+    }
+}
@@ -0,0 +1,249 @@
+//! A read-only pane that presents a second view of the terminal model
+//! of another pane. The mirror has no pty of its own; it borrows the
+//! lines of its source and ignores any input that is sent to it, which
+//! makes it useful for teaching and demos.
+use crate::domain::DomainId;
+use crate::pane::{
+    alloc_pane_id, CachePolicy, ForEachPaneLogicalLine, LogicalLine, Pane, PaneId, WithPaneLines,
+};
+use crate::renderable::*;
+use crate::{Mux, MuxNotification};
+use parking_lot::{MappedMutexGuard, Mutex, MutexGuard};
+use rangeset::RangeSet;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use termwiz::surface::SequenceNo;
+use url::Url;
+use wezterm_term::color::ColorPalette;
+use wezterm_term::{KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex, TerminalSize};
+
+pub struct MirrorPane {
+    pane_id: PaneId,
+    source: Arc<dyn Pane>,
+    size: Mutex<TerminalSize>,
+    closed: Arc<AtomicBool>,
+    writer: Mutex<std::io::Sink>,
+}
+
+impl MirrorPane {
+    /// Creates a mirror of `source` with the specified initial size.
+    /// The mirror forwards the output notifications of its source
+    /// so that it is repainted along with it.
+    pub fn new(source: Arc<dyn Pane>, size: TerminalSize) -> Self {
+        let pane_id = alloc_pane_id();
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let source_id = source.pane_id();
+        let sub_closed = Arc::clone(&closed);
+        Mux::get().subscribe(move |n| {
+            if sub_closed.load(Ordering::Relaxed) {
+                return false;
+            }
+            match n {
+                MuxNotification::PaneOutput(id) if id == source_id => {
+                    // We're called with the subscriber list locked,
+                    // so defer our own notification
+                    promise::spawn::spawn_into_main_thread(async move {
+                        if let Some(mux) = Mux::try_get() {
+                            mux.notify(MuxNotification::PaneOutput(pane_id));
+                        }
+                    })
+                    .detach();
+                    true
+                }
+                MuxNotification::PaneRemoved(id) if id == source_id || id == pane_id => {
+                    sub_closed.store(true, Ordering::Relaxed);
+                    false
+                }
+                _ => true,
+            }
+        });
+
+        Self {
+            pane_id,
+            source,
+            size: Mutex::new(size),
+            closed,
+            writer: Mutex::new(std::io::sink()),
+        }
+    }
+
+    pub fn source_pane_id(&self) -> PaneId {
+        self.source.pane_id()
+    }
+}
+
+/// Adapts the dimensions of the source to the size of the mirror.
+/// The bottom of the mirror lines up with the bottom of the source
+/// screen, so that the cursor remains visible when the mirror is
+/// shorter than its source.
+fn mirror_dimensions(source: RenderableDimensions, size: &TerminalSize) -> RenderableDimensions {
+    let source_bottom = source.physical_top + source.viewport_rows as StableRowIndex;
+    let physical_top = (source_bottom - size.rows as StableRowIndex).max(source.scrollback_top);
+    RenderableDimensions {
+        cols: size.cols,
+        viewport_rows: size.rows,
+        physical_top,
+        pixel_width: size.pixel_width,
+        pixel_height: size.pixel_height,
+        ..source
+    }
+}
+
+impl Pane for MirrorPane {
+    fn pane_id(&self) -> PaneId {
+        self.pane_id
+    }
+
+    fn get_cursor_position(&self) -> StableCursorPosition {
+        self.source.get_cursor_position()
+    }
+
+    fn get_current_seqno(&self) -> SequenceNo {
+        self.source.get_current_seqno()
+    }
+
+    fn get_changed_since(
+        &self,
+        lines: Range<StableRowIndex>,
+        seqno: SequenceNo,
+    ) -> RangeSet<StableRowIndex> {
+        self.source.get_changed_since(lines, seqno)
+    }
+
+    fn with_lines_mut(&self, lines: Range<StableRowIndex>, with_lines: &mut dyn WithPaneLines) {
+        self.source.with_lines_mut(lines, with_lines)
+    }
+
+    fn for_each_logical_line_in_stable_range_mut(
+        &self,
+        lines: Range<StableRowIndex>,
+        for_line: &mut dyn ForEachPaneLogicalLine,
+    ) {
+        self.source
+            .for_each_logical_line_in_stable_range_mut(lines, for_line)
+    }
+
+    fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
+        self.source.get_lines(lines)
+    }
+
+    fn get_logical_lines(&self, lines: Range<StableRowIndex>) -> Vec<LogicalLine> {
+        self.source.get_logical_lines(lines)
+    }
+
+    fn get_dimensions(&self) -> RenderableDimensions {
+        mirror_dimensions(self.source.get_dimensions(), &self.size.lock())
+    }
+
+    fn get_title(&self) -> String {
+        format!("MIRROR: {}", self.source.get_title())
+    }
+
+    fn send_paste(&self, _text: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn reader(&self) -> anyhow::Result<Option<Box<dyn std::io::Read + Send>>> {
+        Ok(None)
+    }
+
+    fn writer(&self) -> MappedMutexGuard<dyn std::io::Write> {
+        MutexGuard::map(self.writer.lock(), |writer| {
+            let w: &mut dyn std::io::Write = writer;
+            w
+        })
+    }
+
+    fn resize(&self, size: TerminalSize) -> anyhow::Result<()> {
+        *self.size.lock() = size;
+        Ok(())
+    }
+
+    fn key_down(&self, _key: KeyCode, _mods: KeyModifiers) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_up(&self, _key: KeyCode, _mods: KeyModifiers) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn mouse_event(&self, _event: MouseEvent) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn is_dead(&self) -> bool {
+        // `closed` is also set when the source is removed from the mux
+        self.closed.load(Ordering::Relaxed) || self.source.is_dead()
+    }
+
+    fn kill(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+
+    fn palette(&self) -> ColorPalette {
+        self.source.palette()
+    }
+
+    fn domain_id(&self) -> DomainId {
+        self.source.domain_id()
+    }
+
+    fn is_mouse_grabbed(&self) -> bool {
+        false
+    }
+
+    fn is_alt_screen_active(&self) -> bool {
+        self.source.is_alt_screen_active()
+    }
+
+    fn get_current_working_dir(&self, policy: CachePolicy) -> Option<Url> {
+        self.source.get_current_working_dir(policy)
+    }
+
+    fn get_location(&self, policy: CachePolicy) -> Option<crate::location::PaneLocation> {
+        self.source.get_location(policy)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dimensions() {
+        let source = RenderableDimensions {
+            cols: 80,
+            viewport_rows: 24,
+            scrollback_rows: 124,
+            physical_top: 100,
+            scrollback_top: 0,
+            dpi: 96,
+            pixel_width: 800,
+            pixel_height: 480,
+            reverse_video: false,
+        };
+        let size = TerminalSize {
+            rows: 10,
+            cols: 40,
+            ..Default::default()
+        };
+
+        let dims = mirror_dimensions(source, &size);
+        assert_eq!(dims.cols, 40);
+        assert_eq!(dims.viewport_rows, 10);
+        // The bottom row matches that of the source
+        assert_eq!(dims.physical_top, 114);
+        assert_eq!(dims.scrollback_rows, 124);
+
+        // A taller mirror reveals some of the scrollback, but
+        // never more than is available
+        let size = TerminalSize {
+            rows: 200,
+            cols: 40,
+            ..Default::default()
+        };
+        assert_eq!(mirror_dimensions(source, &size).physical_top, 0);
+    }
+}
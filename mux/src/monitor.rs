@@ -0,0 +1,220 @@
+//! Tracks pane output in order to raise activity and silence alerts,
+//! in the spirit of tmux's monitor-activity and monitor-silence options.
+use crate::{Mux, MuxNotification};
+use std::time::{Duration, Instant};
+use wezterm_term::Alert;
+
+/// How often panes are checked for silence
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The activity and silence alerts that have been raised for a pane
+/// since it was last focused
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OutputAlerts {
+    pub activity: bool,
+    pub silence: bool,
+}
+
+/// Per-pane output tracking state
+#[derive(Debug, Default)]
+pub struct OutputMonitor {
+    last_output: Option<Instant>,
+    /// The most recent input or output, for `idle_pane_policy`
+    last_activity: Option<Instant>,
+    last_activity_alert: Option<Instant>,
+    /// Overrides `monitor_silence` when set
+    silence_threshold: Option<Duration>,
+    alerts: OutputAlerts,
+}
+
+impl OutputMonitor {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            last_activity: Some(now),
+            ..Self::default()
+        }
+    }
+
+    /// Records that input was sent to the pane
+    pub fn input(&mut self, now: Instant) {
+        self.last_activity.replace(now);
+    }
+
+    /// Returns how long it has been since the last input or output
+    pub fn idle_duration(&self, now: Instant) -> Option<Duration> {
+        self.last_activity
+            .map(|last| now.saturating_duration_since(last))
+    }
+
+    /// Records that output arrived.
+    /// Returns true if a `PaneOutputActivity` alert should be raised.
+    pub fn output(&mut self, focused: bool, now: Instant) -> bool {
+        self.last_output.replace(now);
+        self.last_activity.replace(now);
+
+        let config = config::configuration();
+        if focused || !config.monitor_activity {
+            return false;
+        }
+
+        let interval = Duration::from_millis(config.monitor_activity_interval);
+        match self.last_activity_alert {
+            Some(last) if now.duration_since(last) < interval => false,
+            _ => {
+                self.last_activity_alert.replace(now);
+                self.alerts.activity = true;
+                true
+            }
+        }
+    }
+
+    /// Returns the silence threshold for this pane, or None if
+    /// silence monitoring is disabled
+    pub fn silence_threshold(&self) -> Option<Duration> {
+        let threshold = self
+            .silence_threshold
+            .unwrap_or_else(|| Duration::from_secs(config::configuration().monitor_silence));
+        if threshold.is_zero() {
+            None
+        } else {
+            Some(threshold)
+        }
+    }
+
+    /// Overrides the `monitor_silence` configuration for this pane.
+    /// `None` reverts to the configured value.
+    pub fn set_silence_threshold(&mut self, threshold: Option<Duration>) {
+        self.silence_threshold = threshold;
+    }
+
+    /// Checks whether output has stopped for at least the silence threshold.
+    /// Returns the threshold if a `PaneSilence` alert should be raised.
+    /// The alert is raised only once for each burst of output.
+    pub fn check_silence(&mut self, now: Instant) -> Option<Duration> {
+        let threshold = self.silence_threshold()?;
+        let last_output = self.last_output?;
+        if now.duration_since(last_output) < threshold {
+            return None;
+        }
+        self.last_output.take();
+        self.alerts.silence = true;
+        Some(threshold)
+    }
+
+    /// Records an alert that was raised elsewhere, such as by
+    /// the mux server for a remote pane
+    pub fn record_alert(&mut self, alert: &Alert) {
+        match alert {
+            Alert::PaneOutputActivity => self.alerts.activity = true,
+            Alert::PaneSilence { .. } => self.alerts.silence = true,
+            _ => {}
+        }
+    }
+
+    /// Clears the pending alerts; called when the pane gains focus
+    pub fn focused(&mut self) {
+        self.alerts = OutputAlerts::default();
+    }
+
+    pub fn alerts(&self) -> OutputAlerts {
+        self.alerts
+    }
+}
+
+/// Starts the periodic check for silent panes, and for panes
+/// whose output has started or stopped being rate limited.
+/// This must be called on the main thread after the mux has been set up.
+pub fn start_output_monitor() {
+    promise::spawn::spawn(async move {
+        loop {
+            smol::Timer::after(CHECK_INTERVAL).await;
+            let mux = match Mux::try_get() {
+                Some(mux) => mux,
+                None => break,
+            };
+            for pane in mux.iter_panes() {
+                if let Some(threshold) = pane.check_silence() {
+                    mux.notify(MuxNotification::Alert {
+                        pane_id: pane.pane_id(),
+                        alert: Alert::PaneSilence {
+                            seconds: threshold.as_secs(),
+                        },
+                    });
+                }
+                let limited_change = pane
+                    .output_rate()
+                    .and_then(|rate| rate.lock().check_limited_change(Instant::now()));
+                if let Some(limited) = limited_change {
+                    mux.notify(MuxNotification::Alert {
+                        pane_id: pane.pane_id(),
+                        alert: Alert::OutputRateLimited(limited),
+                    });
+                }
+            }
+        }
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn silence_once_per_burst() {
+        let start = Instant::now();
+        let mut monitor = OutputMonitor::default();
+        monitor.set_silence_threshold(Some(Duration::from_secs(5)));
+
+        // No output yet, so nothing to be silent about
+        assert_eq!(monitor.check_silence(start), None);
+
+        monitor.last_output.replace(start);
+        assert_eq!(monitor.check_silence(start + Duration::from_secs(4)), None);
+        assert_eq!(
+            monitor.check_silence(start + Duration::from_secs(5)),
+            Some(Duration::from_secs(5))
+        );
+        assert!(monitor.alerts().silence);
+        assert_eq!(monitor.check_silence(start + Duration::from_secs(60)), None);
+
+        monitor.focused();
+        assert_eq!(monitor.alerts(), OutputAlerts::default());
+
+        monitor.set_silence_threshold(Some(Duration::ZERO));
+        monitor.last_output.replace(start);
+        assert_eq!(monitor.check_silence(start + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn idle_duration_tracks_input_and_output() {
+        let start = Instant::now();
+        assert_eq!(OutputMonitor::default().idle_duration(start), None);
+
+        let mut monitor = OutputMonitor::new(start);
+        assert_eq!(
+            monitor.idle_duration(start + Duration::from_secs(10)),
+            Some(Duration::from_secs(10))
+        );
+
+        monitor.input(start + Duration::from_secs(10));
+        assert_eq!(
+            monitor.idle_duration(start + Duration::from_secs(15)),
+            Some(Duration::from_secs(5))
+        );
+
+        // Silence alerts don't reset the idle time
+        monitor.last_output.replace(start + Duration::from_secs(20));
+        monitor
+            .last_activity
+            .replace(start + Duration::from_secs(20));
+        monitor.set_silence_threshold(Some(Duration::from_secs(1)));
+        assert!(monitor
+            .check_silence(start + Duration::from_secs(30))
+            .is_some());
+        assert_eq!(
+            monitor.idle_duration(start + Duration::from_secs(30)),
+            Some(Duration::from_secs(10))
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Detects when wezterm is started from within a pane of another
+//! wezterm instance.  The environment variables that are set for
+//! panes by the outer instance would otherwise leak into the panes
+//! of the inner instance, causing eg: `wezterm cli` to address the
+//! wrong instance.
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How many wezterm instances enclose this one.
+/// 0 when not nested.
+pub const NESTING_DEPTH_VAR: &str = "WEZTERM_NESTING_DEPTH";
+/// The WEZTERM_PANE value inherited from the enclosing instance
+pub const OUTER_PANE_VAR: &str = "WEZTERM_OUTER_PANE";
+/// The WEZTERM_UNIX_SOCKET value inherited from the enclosing instance
+pub const OUTER_UNIX_SOCKET_VAR: &str = "WEZTERM_OUTER_UNIX_SOCKET";
+
+/// When set to a non-empty value other than `0`, `wezterm cli`
+/// addresses the outer instance unless `--prefer-inner` is passed
+pub const CLI_PREFER_OUTER_VAR: &str = "WEZTERM_CLI_PREFER_OUTER";
+
+/// Variables that are set for each pane and are only meaningful
+/// to the instance that spawned the pane
+const PANE_VARS: &[&str] = &["WEZTERM_PANE", "WEZTERM_UNIX_SOCKET", "WEZTERM_REMOTE_PANE"];
+
+static NESTING_DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the nesting depth of this instance, as determined
+/// by detect_nesting
+pub fn nesting_depth() -> usize {
+    NESTING_DEPTH.load(Ordering::Relaxed)
+}
+
+/// Examines the environment inherited by this process to determine
+/// whether it was started from a pane of another wezterm instance.
+/// If so, the inherited per-pane variables are moved aside to their
+/// WEZTERM_OUTER_XXX equivalents so that the panes spawned by this
+/// instance are given our own values, and the nesting depth is
+/// incremented.
+/// This must be called before any panes are spawned.
+pub fn detect_nesting() {
+    let outer_pane = std::env::var("WEZTERM_PANE").ok();
+    let outer_socket = std::env::var_os("WEZTERM_UNIX_SOCKET").filter(|s| !s.is_empty());
+
+    let depth = if outer_pane.is_some() || outer_socket.is_some() {
+        let depth = std::env::var(NESTING_DEPTH_VAR)
+            .ok()
+            .and_then(|depth| depth.parse::<usize>().ok())
+            .unwrap_or(0)
+            + 1;
+
+        log::warn!(
+            "wezterm is running inside pane {} of another wezterm instance \
+             (WEZTERM_UNIX_SOCKET={}); nesting depth is {depth}. \
+             The inherited WEZTERM_* variables will not be passed to panes \
+             spawned by this instance. Use `wezterm cli --prefer-outer` \
+             to address the outer instance from within those panes.",
+            outer_pane.as_deref().unwrap_or("?"),
+            outer_socket
+                .as_ref()
+                .map(|s| s.to_string_lossy())
+                .unwrap_or_else(|| "?".into()),
+        );
+
+        match &outer_pane {
+            Some(pane) => std::env::set_var(OUTER_PANE_VAR, pane),
+            None => std::env::remove_var(OUTER_PANE_VAR),
+        }
+        match &outer_socket {
+            Some(socket) => std::env::set_var(OUTER_UNIX_SOCKET_VAR, socket),
+            None => std::env::remove_var(OUTER_UNIX_SOCKET_VAR),
+        }
+        for name in PANE_VARS {
+            std::env::remove_var(name);
+        }
+
+        depth
+    } else {
+        0
+    };
+
+    std::env::set_var(NESTING_DEPTH_VAR, depth.to_string());
+    NESTING_DEPTH.store(depth, Ordering::Relaxed);
+}
@@ -0,0 +1,198 @@
+//! Streams the lines of output of a pane as they are completed;
+//! see `line_buffer` for how lines are collected.
+//!
+//! Each stream buffers a bounded number of lines.  When the buffer is
+//! full, no more rows are taken from the pane until the consumer has
+//! read some; the rows remain in the scrollback meanwhile.  Rows that
+//! are pruned from the scrollback before the stream gets to them are
+//! lost, and are counted so that the consumer can tell.
+use crate::line_buffer::LineBuffer;
+use crate::pane::{Pane, PaneId};
+use crate::{Mux, MuxNotification};
+use anyhow::bail;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+
+/// The number of streams that may be open on a single pane
+pub const MAX_STREAMS_PER_PANE: usize = 8;
+
+pub const DEFAULT_BUFFER_LINES: usize = 1000;
+
+lazy_static::lazy_static! {
+    static ref OPEN_STREAMS: Mutex<HashMap<PaneId, usize>> = Mutex::new(HashMap::new());
+}
+
+/// Counts an open stream against the limit of its pane until dropped
+#[derive(Debug)]
+struct StreamSlot {
+    pane_id: PaneId,
+}
+
+impl StreamSlot {
+    fn acquire(pane_id: PaneId) -> anyhow::Result<Self> {
+        let mut open = OPEN_STREAMS.lock();
+        let count = open.entry(pane_id).or_insert(0);
+        if *count >= MAX_STREAMS_PER_PANE {
+            bail!("pane {pane_id} already has {MAX_STREAMS_PER_PANE} open output streams");
+        }
+        *count += 1;
+        Ok(Self { pane_id })
+    }
+}
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        let mut open = OPEN_STREAMS.lock();
+        if let Some(count) = open.get_mut(&self.pane_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                open.remove(&self.pane_id);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamStart {
+    /// Begin with the line that holds the cursor
+    Now,
+    /// Begin with the oldest line in the scrollback
+    StartOfScrollback,
+}
+
+struct StreamState {
+    pane_id: PaneId,
+    buffer: LineBuffer,
+    slot: Option<StreamSlot>,
+    closed: bool,
+}
+
+impl StreamState {
+    fn pump(&mut self, pane: &Arc<dyn Pane>) {
+        if !self.closed {
+            self.buffer.pump(&**pane);
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        self.slot.take();
+    }
+}
+
+/// A stream of the lines of output of a pane
+#[derive(Clone)]
+pub struct OutputStream {
+    state: Arc<Mutex<StreamState>>,
+    wake: smol::channel::Receiver<()>,
+}
+
+impl OutputStream {
+    /// Opens a stream on `pane` that buffers up to `capacity` lines
+    pub fn open(pane: &Arc<dyn Pane>, start: StreamStart, capacity: usize) -> anyhow::Result<Self> {
+        let pane_id = pane.pane_id();
+        let slot = StreamSlot::acquire(pane_id)?;
+
+        let next_row = match start {
+            StreamStart::Now => pane.get_cursor_position().y,
+            StreamStart::StartOfScrollback => pane.get_dimensions().scrollback_top,
+        };
+        let state = Arc::new(Mutex::new(StreamState {
+            pane_id,
+            buffer: LineBuffer::new(next_row, capacity),
+            slot: Some(slot),
+            closed: false,
+        }));
+
+        let (tx, rx) = smol::channel::bounded(1);
+        let weak: Weak<Mutex<StreamState>> = Arc::downgrade(&state);
+        Mux::get().subscribe(move |n| {
+            let state = match weak.upgrade() {
+                Some(state) => state,
+                None => return false,
+            };
+            let mut state = state.lock();
+            if state.closed {
+                return false;
+            }
+            match n {
+                MuxNotification::PaneOutput(id) if id == pane_id => {
+                    // Take the new lines now, while they are certain
+                    // to still be in the scrollback
+                    if let Some(pane) = Mux::get().get_pane(pane_id) {
+                        state.pump(&pane);
+                    }
+                    tx.try_send(()).ok();
+                    true
+                }
+                MuxNotification::PaneRemoved(id) if id == pane_id => {
+                    state.close();
+                    tx.try_send(()).ok();
+                    false
+                }
+                _ => true,
+            }
+        });
+
+        Ok(Self { state, wake: rx })
+    }
+
+    /// Waits for at least one line to be available, then returns
+    /// up to `max` lines.  Returns None once the stream has been
+    /// closed and all of its lines have been read.
+    pub async fn read_lines(&self, max: usize) -> Option<Vec<String>> {
+        loop {
+            {
+                let mut state = self.state.lock();
+                if !state.closed {
+                    if let Some(pane) = Mux::get().get_pane(state.pane_id) {
+                        state.pump(&pane);
+                    } else {
+                        state.close();
+                    }
+                }
+                if !state.buffer.is_empty() {
+                    return Some(state.buffer.take(max.max(1)));
+                }
+                if state.closed {
+                    return None;
+                }
+            }
+            if self.wake.recv().await.is_err() {
+                self.state.lock().close();
+            }
+        }
+    }
+
+    /// The number of lines that were lost because the scrollback
+    /// discarded them before the stream could take them
+    pub fn dropped_count(&self) -> usize {
+        self.state.lock().buffer.dropped
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().closed
+    }
+
+    /// Closes the stream.  Lines that are already buffered
+    /// can still be read.
+    pub fn close(&self) {
+        self.state.lock().close();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn limited_per_pane() {
+        let slots: Vec<_> = (0..MAX_STREAMS_PER_PANE)
+            .map(|_| StreamSlot::acquire(9999).unwrap())
+            .collect();
+        assert!(StreamSlot::acquire(9999).is_err());
+        drop(slots);
+        assert!(StreamSlot::acquire(9999).is_ok());
+    }
+}
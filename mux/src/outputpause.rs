@@ -0,0 +1,110 @@
+//! Allows reading the output of a pane to be paused, so that the
+//! user can catch up with output that is scrolling too quickly.
+//! While paused, the thread that reads from the pty stops reading;
+//! the kernel buffer for the pty fills up and applies backpressure
+//! to the program in the pane, rather than its output being
+//! buffered without bound.
+use parking_lot::{Condvar, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Default)]
+struct PauseState {
+    paused: bool,
+    /// Set when the pane is being closed; the reader must not
+    /// be paused again after that point
+    released: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct OutputPause {
+    state: Mutex<PauseState>,
+    cond: Condvar,
+}
+
+impl OutputPause {
+    /// Pauses or resumes reading.
+    /// Returns true if that changed the state.
+    pub fn set_paused(&self, paused: bool) -> bool {
+        let mut state = self.state.lock();
+        if state.released || state.paused == paused {
+            return false;
+        }
+        state.paused = paused;
+        self.cond.notify_all();
+        true
+    }
+
+    pub fn is_paused(&self) -> bool {
+        let state = self.state.lock();
+        state.paused && !state.released
+    }
+
+    /// Permanently resumes reading, so that a reader that is
+    /// waiting can observe EOF when the pane is closed
+    pub fn release(&self) {
+        let mut state = self.state.lock();
+        state.released = true;
+        self.cond.notify_all();
+    }
+
+    /// Blocks the calling thread for as long as reading is paused.
+    /// `keep_waiting` is consulted every `interval`, and returning
+    /// false from it stops waiting; this allows the reader to notice
+    /// that its pane has gone away.
+    pub fn wait_while_paused(&self, interval: Duration, mut keep_waiting: impl FnMut() -> bool) {
+        let mut state = self.state.lock();
+        while state.paused && !state.released {
+            if self.cond.wait_for(&mut state, interval).timed_out() && !keep_waiting() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn pause_and_resume() {
+        let pause = Arc::new(OutputPause::default());
+        assert!(!pause.is_paused());
+        assert!(pause.set_paused(true));
+        assert!(!pause.set_paused(true));
+        assert!(pause.is_paused());
+
+        let waiter = std::thread::spawn({
+            let pause = Arc::clone(&pause);
+            move || pause.wait_while_paused(Duration::from_secs(60), || true)
+        });
+        assert!(pause.set_paused(false));
+        waiter.join().unwrap();
+    }
+
+    #[test]
+    fn release_wakes_the_reader() {
+        let pause = Arc::new(OutputPause::default());
+        pause.set_paused(true);
+
+        let waiter = std::thread::spawn({
+            let pause = Arc::clone(&pause);
+            move || pause.wait_while_paused(Duration::from_secs(60), || true)
+        });
+        pause.release();
+        waiter.join().unwrap();
+
+        // Once released, it cannot be paused again
+        assert!(!pause.set_paused(true));
+        assert!(!pause.is_paused());
+    }
+
+    #[test]
+    fn stop_waiting() {
+        let pause = OutputPause::default();
+        pause.set_paused(true);
+        // Returns once keep_waiting says so, even though still paused
+        pause.wait_while_paused(Duration::from_millis(1), || false);
+        assert!(pause.is_paused());
+    }
+}
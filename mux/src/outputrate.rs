@@ -0,0 +1,217 @@
+//! Measures the rate at which a pane produces output so that
+//! runaway output can be limited according to `output_rate_limit`.
+use config::{
+    configuration, ConfigHandle, ConfigSubscription, OutputRateLimit, OutputRateLimitAction,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The period over which the output rate is measured
+const WINDOW: Duration = Duration::from_millis(250);
+
+/// How long a pane continues to be reported as limited
+/// after the limit was last applied
+const LIMITED_HOLD: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub struct OutputRate {
+    window_start: Instant,
+    window_bytes: u64,
+    /// The rate measured over the most recently completed window
+    bytes_per_second: u64,
+    last_limited: Option<Instant>,
+    reported_limited: bool,
+}
+
+impl OutputRate {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            window_start: now,
+            window_bytes: 0,
+            bytes_per_second: 0,
+            last_limited: None,
+            reported_limited: false,
+        }
+    }
+
+    /// Records that `bytes` of output were read from the pty
+    pub fn record(&mut self, bytes: usize, now: Instant) {
+        let elapsed = now.duration_since(self.window_start);
+        if elapsed >= WINDOW {
+            self.bytes_per_second = (self.window_bytes as f64 / elapsed.as_secs_f64()) as u64;
+            self.window_start = now;
+            self.window_bytes = 0;
+        }
+        self.window_bytes += bytes as u64;
+    }
+
+    /// Returns the recent output rate in bytes per second
+    pub fn bytes_per_second(&self, now: Instant) -> u64 {
+        let elapsed = now.duration_since(self.window_start);
+        if elapsed >= WINDOW {
+            // The current window is overdue to be rolled over, which
+            // means that output has slowed or stopped; account for that
+            (self.window_bytes as f64 / elapsed.as_secs_f64()) as u64
+        } else {
+            self.bytes_per_second
+        }
+    }
+
+    /// Returns how long reading should pause in order to keep the
+    /// output within `limit` bytes per second, or None if it is
+    /// within budget.
+    pub fn throttle_delay(&mut self, limit: u64, now: Instant) -> Option<Duration> {
+        if limit == 0 {
+            return None;
+        }
+        let allowed = Duration::from_secs_f64(self.window_bytes as f64 / limit as f64);
+        let delay = allowed.checked_sub(now.duration_since(self.window_start))?;
+        if delay.is_zero() {
+            return None;
+        }
+        self.last_limited.replace(now);
+        Some(delay)
+    }
+
+    /// Returns true if the recent output rate exceeds `limit`
+    /// bytes per second, marking the pane as limited if so
+    pub fn exceeds(&mut self, limit: u64, now: Instant) -> bool {
+        if limit != 0 && self.bytes_per_second(now) > limit {
+            self.last_limited.replace(now);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if output has recently been limited
+    pub fn is_limited(&self, now: Instant) -> bool {
+        matches!(self.last_limited, Some(t) if now.duration_since(t) < LIMITED_HOLD)
+    }
+
+    /// Returns the new limited state if it has changed since
+    /// the last call
+    pub fn check_limited_change(&mut self, now: Instant) -> Option<bool> {
+        let limited = self.is_limited(now);
+        if limited == self.reported_limited {
+            None
+        } else {
+            self.reported_limited = limited;
+            Some(limited)
+        }
+    }
+}
+
+/// The `output_rate_limit` settings for the threads that read and
+/// parse the output of a pane.  They are cached so that the config
+/// isn't consulted for every chunk of output, and are refreshed after
+/// the config is reloaded.
+pub struct OutputRateConfig {
+    limit: OutputRateLimit,
+    snapshot_interval: Option<Duration>,
+    stale: Arc<AtomicBool>,
+    _subscription: ConfigSubscription,
+}
+
+impl OutputRateConfig {
+    pub fn new() -> Self {
+        let stale = Arc::new(AtomicBool::new(false));
+        // The config is locked while subscribers are notified,
+        // so the settings are fetched when they are next used
+        let subscription = config::subscribe_to_config_reload({
+            let stale = Arc::clone(&stale);
+            move || {
+                stale.store(true, Ordering::Relaxed);
+                true
+            }
+        });
+        let config = configuration();
+        Self {
+            limit: config.output_rate_limit,
+            snapshot_interval: snapshot_interval(&config),
+            stale,
+            _subscription: subscription,
+        }
+    }
+
+    fn refresh(&mut self) {
+        if self.stale.swap(false, Ordering::Relaxed) {
+            let config = configuration();
+            self.limit = config.output_rate_limit;
+            self.snapshot_interval = snapshot_interval(&config);
+        }
+    }
+
+    pub fn limit(&mut self) -> OutputRateLimit {
+        self.refresh();
+        self.limit
+    }
+
+    /// Returns the interval at which output should be applied to the
+    /// terminal model when the `DropRender` action is in effect
+    pub fn snapshot_interval(&mut self) -> Option<Duration> {
+        self.refresh();
+        self.snapshot_interval
+    }
+}
+
+/// Returns the interval at which output should be applied to the
+/// terminal model when the `DropRender` action is in effect
+fn snapshot_interval(config: &ConfigHandle) -> Option<Duration> {
+    match config.output_rate_limit.action {
+        OutputRateLimitAction::Throttle => None,
+        OutputRateLimitAction::DropRender => {
+            Some(Duration::from_secs_f64(1.0 / config.max_fps.max(1) as f64))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn throttle() {
+        let start = Instant::now();
+        let mut rate = OutputRate::new(start);
+
+        rate.record(1000, start);
+        assert_eq!(rate.throttle_delay(0, start), None);
+        assert_eq!(
+            rate.throttle_delay(16_000, start + Duration::from_millis(100)),
+            None
+        );
+        assert!(!rate.is_limited(start));
+
+        rate.record(1000, start + Duration::from_millis(100));
+        assert_eq!(
+            rate.throttle_delay(16_000, start + Duration::from_millis(100)),
+            Some(Duration::from_millis(25))
+        );
+        assert!(rate.is_limited(start + Duration::from_millis(100)));
+        assert_eq!(
+            rate.check_limited_change(start + Duration::from_millis(100)),
+            Some(true)
+        );
+        assert_eq!(
+            rate.check_limited_change(start + Duration::from_millis(200)),
+            None
+        );
+
+        // Rolling over the window measures the rate
+        rate.record(0, start + Duration::from_millis(250));
+        assert_eq!(
+            rate.bytes_per_second(start + Duration::from_millis(250)),
+            8000
+        );
+        assert!(!rate.exceeds(16_000, start + Duration::from_millis(250)));
+
+        // And it decays when output stops
+        assert_eq!(rate.bytes_per_second(start + Duration::from_secs(10)), 0);
+        assert_eq!(
+            rate.check_limited_change(start + Duration::from_secs(10)),
+            Some(false)
+        );
+    }
+}
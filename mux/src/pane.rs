@@ -1,24 +1,35 @@
+use crate::annotation::PaneAnnotations;
 use crate::domain::DomainId;
+use crate::highlight::PaneHighlights;
+use crate::location::PaneLocation;
+use crate::monitor::OutputAlerts;
+use crate::outputpause::OutputPause;
+use crate::outputrate::OutputRate;
+use crate::pane_watch::PaneWatch;
 use crate::renderable::*;
-use crate::ExitBehavior;
+use crate::{ExitBehavior, Mux, MuxNotification};
 use async_trait::async_trait;
-use config::keyassignment::{KeyAssignment, ScrollbackEraseMode};
+use config::keyassignment::{KeyAssignment, PaneLinkOptions, ScrollbackEraseMode};
+use config::{ColumnGuide, PaneEncoding};
 use downcast_rs::{impl_downcast, Downcast};
-use parking_lot::MappedMutexGuard;
+use parking_lot::{MappedMutexGuard, Mutex};
 use rangeset::RangeSet;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::ops::Range;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use termwiz::escape::osc::Progress;
 use termwiz::hyperlink::Rule;
 use termwiz::input::KeyboardEncoding;
 use termwiz::surface::{Line, SequenceNo};
 use url::Url;
+use wezterm_bidi::ParagraphDirectionHint;
 use wezterm_dynamic::Value;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    Clipboard, DownloadHandler, KeyCode, KeyModifiers, MouseEvent, SemanticZone, StableRowIndex,
-    TerminalConfiguration, TerminalSize,
+    Alert, Clipboard, DownloadHandler, KeyCode, KeyModifiers, MouseEvent, MousePointerShape,
+    SemanticZone, StableRowIndex, TerminalConfiguration, TerminalSize,
 };
 
 static PANE_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
@@ -272,6 +283,139 @@ pub trait Pane: Downcast + Send + Sync {
         false
     }
 
+    /// Treats any output since the pane lost focus as having been
+    /// seen, without otherwise affecting its focus
+    fn mark_output_seen(&self) {}
+
+    /// Called by the mux after it has linked this pane with `other`,
+    /// or removed the link if `options` is empty, so that a domain
+    /// that keeps its own record of links can be advised
+    fn link_with(&self, _other: &Arc<dyn Pane>, _options: PaneLinkOptions) {}
+
+    /// Returns the most recent OSC 9;4 progress report for this pane
+    fn get_progress(&self) -> Progress {
+        Progress::None
+    }
+
+    /// Returns the meter that the mux uses to measure and limit
+    /// the rate of output from the pty, if the pane has one
+    fn output_rate(&self) -> Option<Arc<Mutex<OutputRate>>> {
+        None
+    }
+
+    /// Returns the persistent highlights that apply to this pane,
+    /// if it supports them
+    fn highlights(&self) -> Option<Arc<Mutex<PaneHighlights>>> {
+        None
+    }
+
+    /// Returns the annotations of the lines of this pane,
+    /// if it supports them
+    fn annotations(&self) -> Option<Arc<Mutex<PaneAnnotations>>> {
+        None
+    }
+
+    /// Returns the character encoding used to talk to the program
+    /// running in the pane, if the pane supports changing it
+    fn encoding(&self) -> Option<Arc<Mutex<PaneEncoding>>> {
+        None
+    }
+
+    /// Returns the column guides that override the `column_guides`
+    /// configuration for this pane, if the pane supports them.
+    /// A `None` value inside the mutex means that the configured
+    /// guides apply.
+    fn column_guides(&self) -> Option<Arc<Mutex<Option<Vec<ColumnGuide>>>>> {
+        None
+    }
+
+    /// Returns the recent output rate, in bytes per second,
+    /// if it is known
+    fn get_output_rate(&self) -> Option<u64> {
+        self.output_rate()
+            .map(|rate| rate.lock().bytes_per_second(Instant::now()))
+    }
+
+    /// Returns true if output from this pane is currently
+    /// being limited by `output_rate_limit`
+    fn is_output_rate_limited(&self) -> bool {
+        self.output_rate()
+            .map(|rate| rate.lock().is_limited(Instant::now()))
+            .unwrap_or(false)
+    }
+
+    /// Returns the state used to pause reading the output of
+    /// this pane, if it supports that
+    fn output_pause(&self) -> Option<Arc<OutputPause>> {
+        None
+    }
+
+    /// Returns true if reading the output of this pane is paused
+    fn is_output_paused(&self) -> bool {
+        self.output_pause()
+            .map(|pause| pause.is_paused())
+            .unwrap_or(false)
+    }
+
+    /// Pauses or resumes reading the output of this pane.
+    /// Panes that don't support pausing ignore this.
+    fn set_output_paused(&self, paused: bool) {
+        if let Some(pause) = self.output_pause() {
+            if pause.set_paused(paused) {
+                if let Some(mux) = Mux::try_get() {
+                    mux.notify(MuxNotification::Alert {
+                        pane_id: self.pane_id(),
+                        alert: Alert::OutputPaused(paused),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Returns the activity and silence alerts raised for this
+    /// pane since it was last focused
+    fn get_output_alerts(&self) -> OutputAlerts {
+        OutputAlerts::default()
+    }
+
+    /// Returns the number of seconds of quiet after output that
+    /// raise a `PaneSilence` alert, or None if disabled
+    fn get_silence_threshold(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Overrides the `monitor_silence` configuration for this pane.
+    /// `None` reverts to the configured value; a zero duration
+    /// disables silence alerts for this pane.
+    fn set_silence_threshold(&self, _threshold: Option<Duration>) {}
+
+    /// Called periodically by the mux; returns the silence threshold
+    /// if a `PaneSilence` alert should be raised for this pane
+    fn check_silence(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Starts watching the output of this pane for `watch.pattern`,
+    /// replacing any watch with the same id.  Matches raise an
+    /// `Alert::PaneMatch`.
+    fn add_watch(&self, _watch: PaneWatch) -> anyhow::Result<()> {
+        anyhow::bail!("pane {} doesn't support watches", self.pane_id());
+    }
+
+    /// Stops the watch named `id`; returns false if there was none
+    fn remove_watch(&self, _id: &str) -> bool {
+        false
+    }
+
+    /// Places `lines` above the existing content of the scrollback,
+    /// as is done when a closed pane is reopened
+    fn prepend_scrollback(&self, _lines: Vec<Line>) -> anyhow::Result<()> {
+        anyhow::bail!(
+            "pane {} doesn't support restoring scrollback",
+            self.pane_id()
+        );
+    }
+
     /// Certain panes are OK to be closed with impunity (no prompts)
     fn can_close_without_prompting(&self, _reason: CloseReason) -> bool {
         false
@@ -296,6 +440,17 @@ pub trait Pane: Downcast + Send + Sync {
         Ok(vec![])
     }
 
+    /// Like get_lines, but waits for the lines to be retrieved from
+    /// their source if they are not available locally.
+    /// This is used when the content must be complete, such as when
+    /// saving the scrollback to a file, rather than for rendering.
+    async fn fetch_lines(
+        &self,
+        lines: Range<StableRowIndex>,
+    ) -> anyhow::Result<(StableRowIndex, Vec<Line>)> {
+        Ok(self.get_lines(lines))
+    }
+
     /// Retrieve the set of semantic zones
     fn get_semantic_zones(&self) -> anyhow::Result<Vec<SemanticZone>> {
         Ok(vec![])
@@ -308,6 +463,25 @@ pub trait Pane: Downcast + Send + Sync {
     fn is_mouse_grabbed(&self) -> bool;
     fn is_alt_screen_active(&self) -> bool;
 
+    /// Returns true if the width of newly printed text is measured
+    /// by grapheme cluster, as controlled by DEC private mode 2027
+    fn is_grapheme_clustering_enabled(&self) -> bool {
+        true
+    }
+
+    /// Returns true if the application has enabled bracketed paste,
+    /// so that it inserts the newlines in pasted text rather than
+    /// treating them as pressing enter
+    fn is_bracketed_paste_enabled(&self) -> bool {
+        false
+    }
+
+    /// Returns the shape of the mouse pointer that the application
+    /// requested using OSC 22, or None to use the default shapes
+    fn get_mouse_pointer_shape(&self) -> Option<MousePointerShape> {
+        None
+    }
+
     fn set_clipboard(&self, _clipboard: &Arc<dyn Clipboard>) {}
     fn set_download_handler(&self, _handler: &Arc<dyn DownloadHandler>) {}
     fn set_config(&self, _config: Arc<dyn TerminalConfiguration>) {}
@@ -316,6 +490,11 @@ pub trait Pane: Downcast + Send + Sync {
     }
 
     fn get_current_working_dir(&self, policy: CachePolicy) -> Option<Url>;
+    /// Returns the current working directory along with the source
+    /// that provided it
+    fn get_location(&self, policy: CachePolicy) -> Option<PaneLocation> {
+        self.get_current_working_dir(policy).map(PaneLocation::new)
+    }
     fn get_foreground_process_name(&self, _policy: CachePolicy) -> Option<String> {
         None
     }
@@ -325,14 +504,68 @@ pub trait Pane: Downcast + Send + Sync {
     ) -> Option<procinfo::LocalProcessInfo> {
         None
     }
+    /// Returns the environment of the foreground process, where
+    /// it is accessible
+    fn get_foreground_process_env(&self) -> Option<HashMap<String, String>> {
+        None
+    }
 
     fn tty_name(&self) -> Option<String> {
         None
     }
 
+    /// Returns the number of wezterm instances that enclose the
+    /// instance that hosts this pane
+    fn get_nesting_depth(&self) -> usize {
+        crate::nesting::nesting_depth()
+    }
+
     fn exit_behavior(&self) -> Option<ExitBehavior> {
         None
     }
+
+    /// Returns a counter that is incremented each time the process
+    /// in this pane is respawned.  The mux uses this to recognize
+    /// that EOF on a pty belongs to a process that was replaced.
+    fn pty_generation(&self) -> usize {
+        0
+    }
+
+    /// Returns how long it has been since the pane last had any
+    /// input or output, if the pane keeps track of that.
+    /// This is used to evaluate `idle_pane_policy`.
+    fn idle_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    /// A pinned pane is never closed by `idle_pane_policy`
+    fn is_pinned(&self) -> bool {
+        false
+    }
+    fn set_pinned(&self, _pinned: bool) {}
+
+    /// Whether the GUI should route key presses for this pane
+    /// through the input method editor
+    fn is_ime_enabled(&self) -> bool {
+        true
+    }
+    fn set_ime_enabled(&self, _enabled: bool) {}
+
+    /// Overrides whether the bidi algorithm is applied to the lines
+    /// of this pane, and the paragraph direction hint for them.
+    /// None leaves that part of the setting unchanged.
+    /// Panes that don't implement bidi ignore this.
+    fn set_bidi_mode(&self, _enabled: Option<bool>, _hint: Option<ParagraphDirectionHint>) {}
+
+    /// Returns the number of columns at the left of the pane that are
+    /// occupied by the line timestamps gutter rather than by its
+    /// terminal.  Zero when the gutter is hidden.
+    fn timestamp_gutter_cols(&self) -> usize {
+        0
+    }
+    /// Panes that can't show the gutter ignore this.
+    /// The tab must be resized for the change to take effect.
+    fn set_timestamp_gutter_cols(&self, _cols: usize) {}
 }
 impl_downcast!(Pane);
 
@@ -0,0 +1,311 @@
+//! Watches the output of a pane for a pattern, on behalf of `pane:watch`.
+//! Only lines that are completed after the watch was added are matched;
+//! see `line_buffer` for how lines are collected.  Each line is matched
+//! once, as it completes, so the cost is proportional to the amount of
+//! new output rather than to the size of the scrollback.
+//! The line that holds the cursor is matched as well, so that prompts
+//! such as `Password:`, which wait for input at the end of their line,
+//! are seen; its matches are not reported again when it completes.
+//! A match raises an `Alert::PaneMatch`, which the mux server forwards
+//! to its clients along with the other alerts of the pane.
+use crate::line_buffer::LineBuffer;
+use crate::pane::Pane;
+use anyhow::bail;
+use fancy_regex::Regex;
+use std::collections::HashMap;
+use wezterm_term::StableRowIndex;
+
+/// The number of watches that may be added to a single pane
+pub const MAX_WATCHES_PER_PANE: usize = 16;
+
+/// The most rows that are taken from the pane at a time
+const BATCH_ROWS: usize = 1000;
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Literal(String),
+    Regex(Regex),
+}
+
+/// A pattern to look for in the output of a pane
+#[derive(Debug, Clone)]
+pub struct PaneWatch {
+    pub id: String,
+    pub pattern: String,
+    pub regex: bool,
+    matcher: Matcher,
+}
+
+impl PaneWatch {
+    /// Creates a watch, failing if `pattern` is not a valid regex
+    pub fn new(id: String, pattern: String, regex: bool) -> anyhow::Result<Self> {
+        if pattern.is_empty() {
+            bail!("the pattern of pane watch `{id}` is empty");
+        }
+        let matcher = if regex {
+            Matcher::Regex(Regex::new(&pattern)?)
+        } else {
+            Matcher::Literal(pattern.clone())
+        };
+        Ok(Self {
+            id,
+            pattern,
+            regex,
+            matcher,
+        })
+    }
+
+    /// Returns the text of each match of the pattern in `line`
+    fn find_all(&self, line: &str) -> Vec<String> {
+        match &self.matcher {
+            Matcher::Literal(s) => line.matches(s.as_str()).map(str::to_string).collect(),
+            Matcher::Regex(re) => re
+                .find_iter(line)
+                .filter_map(|m| m.ok())
+                .filter(|m| !m.as_str().is_empty())
+                .map(|m| m.as_str().to_string())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneMatch {
+    pub watch_id: String,
+    pub text: String,
+    pub row: StableRowIndex,
+}
+
+/// The watches of a pane, and how far through its output they have got
+#[derive(Debug)]
+pub struct PaneWatches {
+    watches: Vec<PaneWatch>,
+    buffer: LineBuffer,
+    /// The row on which the line holding the cursor starts, and the
+    /// number of matches of each watch that have been reported for it
+    unfinished: Option<(StableRowIndex, HashMap<String, usize>)>,
+}
+
+impl Default for PaneWatches {
+    fn default() -> Self {
+        Self {
+            watches: vec![],
+            buffer: LineBuffer::new(0, BATCH_ROWS),
+            unfinished: None,
+        }
+    }
+}
+
+impl PaneWatches {
+    /// Adds `watch`, replacing any watch with the same id.
+    /// `cursor_row` is the row that holds the cursor; if this is the
+    /// first watch, matching starts with that row.
+    pub fn add(&mut self, watch: PaneWatch, cursor_row: StableRowIndex) -> anyhow::Result<()> {
+        if let Some(existing) = self.watches.iter_mut().find(|w| w.id == watch.id) {
+            *existing = watch;
+            return Ok(());
+        }
+        if self.watches.len() >= MAX_WATCHES_PER_PANE {
+            bail!("a pane can have at most {MAX_WATCHES_PER_PANE} watches");
+        }
+        if self.watches.is_empty() {
+            self.buffer.restart(cursor_row);
+            self.unfinished.take();
+        }
+        self.watches.push(watch);
+        Ok(())
+    }
+
+    /// Removes the watch named `id`, returning false if there was none
+    pub fn remove(&mut self, id: &str) -> bool {
+        let len = self.watches.len();
+        self.watches.retain(|w| w.id != id);
+        self.watches.len() != len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.watches.iter().map(|w| w.id.clone()).collect()
+    }
+
+    /// Matches `line`, which starts at `row`, against the watches,
+    /// skipping the first `reported` matches of each watch.
+    /// Returns the number of matches of each watch that have now
+    /// been reported.
+    fn match_line(
+        &self,
+        row: StableRowIndex,
+        line: &str,
+        reported: &HashMap<String, usize>,
+        matches: &mut Vec<PaneMatch>,
+    ) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for watch in &self.watches {
+            let found = watch.find_all(line);
+            let skip = reported.get(&watch.id).copied().unwrap_or(0);
+            counts.insert(watch.id.clone(), found.len().max(skip));
+            for text in found.into_iter().skip(skip) {
+                matches.push(PaneMatch {
+                    watch_id: watch.id.clone(),
+                    text,
+                    row,
+                });
+            }
+        }
+        counts
+    }
+
+    /// Takes the matches that were reported for the line starting at
+    /// `row` while it held the cursor
+    fn take_reported(&mut self, row: StableRowIndex) -> HashMap<String, usize> {
+        match self.unfinished.take() {
+            Some((unfinished_row, reported)) if unfinished_row == row => reported,
+            unfinished => {
+                self.unfinished = unfinished;
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Matches the lines that the buffer has collected against the watches
+    fn match_lines(&mut self) -> Vec<PaneMatch> {
+        let mut matches = vec![];
+        for (row, line) in self.buffer.take_with_rows(usize::MAX) {
+            let reported = self.take_reported(row);
+            self.match_line(row, &line, &reported, &mut matches);
+        }
+        matches
+    }
+
+    /// Matches `line`, the text of the line holding the cursor, which
+    /// starts at `row`.  Only matches that weren't reported by earlier
+    /// calls for the same line are returned.
+    fn match_unfinished(&mut self, row: StableRowIndex, line: &str) -> Vec<PaneMatch> {
+        let mut matches = vec![];
+        let reported = self.take_reported(row);
+        let counts = self.match_line(row, line, &reported, &mut matches);
+        self.unfinished.replace((row, counts));
+        matches
+    }
+
+    /// Matches the rows of `pane` that have been completed since
+    /// the last call
+    pub fn pump(&mut self, pane: &dyn Pane) -> Vec<PaneMatch> {
+        let mut matches = vec![];
+        if self.watches.is_empty() {
+            return matches;
+        }
+        while self.buffer.pump(pane) {
+            matches.append(&mut self.match_lines());
+        }
+
+        if pane.is_alt_screen_active() {
+            return matches;
+        }
+        let cursor_row = pane.get_cursor_position().y;
+        if let Some((row, partial)) = self.buffer.unfinished(cursor_row) {
+            let partial = partial.to_string();
+            let (_first, lines) = pane.get_lines(cursor_row..cursor_row + 1);
+            if let Some(line) = lines.first() {
+                let text = format!("{partial}{}", line.as_str().trim_end());
+                matches.append(&mut self.match_unfinished(row, &text));
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::line_buffer::test_row as row;
+
+    fn watch(id: &str, pattern: &str, regex: bool) -> PaneWatch {
+        PaneWatch::new(id.to_string(), pattern.to_string(), regex).unwrap()
+    }
+
+    #[test]
+    fn matches_completed_lines() {
+        let mut watches = PaneWatches::default();
+        watches.add(watch("pw", "Password:", false), 10).unwrap();
+        watches
+            .add(watch("done", r"build (\w+)", true), 10)
+            .unwrap();
+
+        watches.buffer.ingest(
+            10,
+            &[
+                row("compiling... build ", true),
+                row("finished", false),
+                row("Password: ", false),
+            ],
+        );
+        let matches = watches.match_lines();
+        assert_eq!(
+            matches,
+            vec![
+                PaneMatch {
+                    watch_id: "done".to_string(),
+                    text: "build finished".to_string(),
+                    row: 10,
+                },
+                PaneMatch {
+                    watch_id: "pw".to_string(),
+                    text: "Password:".to_string(),
+                    row: 12,
+                },
+            ]
+        );
+        assert_eq!(watches.buffer.next_row, 13);
+    }
+
+    #[test]
+    fn matches_the_line_holding_the_cursor() {
+        let mut watches = PaneWatches::default();
+        watches.add(watch("pw", "Password:", false), 10).unwrap();
+        let pw = |text: &str, row| PaneMatch {
+            watch_id: "pw".to_string(),
+            text: text.to_string(),
+            row,
+        };
+
+        // The prompt is matched while the cursor waits after it
+        assert_eq!(
+            watches.match_unfinished(10, "Password:"),
+            vec![pw("Password:", 10)]
+        );
+        assert_eq!(watches.match_unfinished(10, "Password: **"), vec![]);
+
+        // and isn't matched again when its line completes
+        watches.buffer.ingest(10, &[row("Password: ***", false)]);
+        assert_eq!(watches.match_lines(), vec![]);
+
+        // A later prompt on another line is matched
+        watches.buffer.ingest(11, &[row("Password: wrong", false)]);
+        assert_eq!(watches.match_lines(), vec![pw("Password:", 11)]);
+        assert_eq!(
+            watches.match_unfinished(12, "Password:"),
+            vec![pw("Password:", 12)]
+        );
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut watches = PaneWatches::default();
+        assert!(PaneWatch::new("bad".to_string(), "(".to_string(), true).is_err());
+
+        for i in 0..MAX_WATCHES_PER_PANE {
+            watches.add(watch(&i.to_string(), "x", false), 0).unwrap();
+        }
+        assert!(watches.add(watch("extra", "x", false), 0).is_err());
+        // Replacing an existing watch doesn't count against the limit
+        watches.add(watch("0", "y", false), 0).unwrap();
+
+        assert!(watches.remove("0"));
+        assert!(!watches.remove("0"));
+        assert_eq!(watches.ids().len(), MAX_WATCHES_PER_PANE - 1);
+    }
+}
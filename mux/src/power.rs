@@ -0,0 +1,517 @@
+//! Watches for the system suspending and resuming, and for changes
+//! in how it is powered, and reports them as the `power-event` event
+//! and as MuxNotification::PowerEvent.
+//!
+//! Suspend and resume are reported by logind on Linux, by IOKit on
+//! macOS and by WM_POWERBROADCAST on Windows.  Where those aren't
+//! available, a resume is detected by the wall clock having advanced
+//! much further than the monotonic clock, which doesn't advance while
+//! the system is asleep; a suspend can't be detected that way.  The
+//! power source and battery level are polled.
+//!
+//! On resume, the connections of ssh domains are checked, as they
+//! have most likely been dropped while the system was asleep.  Client
+//! domains check their own connections when they see the event.
+use crate::domain::Domain;
+use crate::ssh::RemoteSshDomain;
+use crate::{Mux, MuxNotification};
+use smol::channel::Sender;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the clocks are compared
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// How much further the wall clock must have advanced than the
+/// monotonic clock for the system to be considered to have slept.
+/// This is large enough to ignore most adjustments of the wall clock.
+const SLEEP_THRESHOLD: Duration = Duration::from_secs(30);
+/// How often the batteries are checked
+const BATTERY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// The state of charge below which a battery is considered low
+const BATTERY_LOW_CHARGE: f32 = 0.1;
+/// How long an ssh server has to respond to a health check
+const SSH_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerEvent {
+    /// The system is about to sleep
+    Suspend,
+    /// The system has woken up
+    Resume,
+    /// The battery is running low while the system is not on AC power
+    BatteryLow,
+    /// The system has been plugged in to AC power
+    OnAcPower,
+    /// The system has been unplugged from AC power
+    OnBattery,
+}
+
+/// Starts watching for power events.
+/// This must be called on the main thread after the mux has been
+/// set up.
+pub fn start_power_monitor() {
+    let (tx, rx) = smol::channel::unbounded();
+
+    {
+        let tx = tx.clone();
+        std::thread::Builder::new()
+            .name("power-monitor".to_string())
+            .spawn(move || watch_sleep(tx))
+            .ok();
+    }
+    std::thread::Builder::new()
+        .name("battery-monitor".to_string())
+        .spawn(move || watch_battery(tx))
+        .ok();
+
+    promise::spawn::spawn(async move {
+        while let Ok(event) = rx.recv().await {
+            dispatch_power_event(event);
+        }
+    })
+    .detach();
+}
+
+fn dispatch_power_event(event: PowerEvent) {
+    log::info!("power event: {:?}", event);
+    if let Some(mux) = Mux::try_get() {
+        mux.notify(MuxNotification::PowerEvent(event));
+        if event == PowerEvent::Resume {
+            check_ssh_domains(&mux);
+        }
+    }
+
+    promise::spawn::spawn(async move {
+        let result = config::with_lua_config_on_main_thread(|lua| async move {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi(format!("{:?}", event))?;
+                config::lua::emit_event(&lua, ("power-event".to_string(), args)).await?;
+            }
+            Ok(())
+        })
+        .await;
+        if let Err(err) = result {
+            log::error!("while processing power-event event: {:#}", err);
+        }
+    })
+    .detach();
+}
+
+/// Checks the sessions of the ssh domains, so that one that died while
+/// the system was asleep is discarded now, rather than when it is next
+/// used to spawn a pane
+fn check_ssh_domains(mux: &Mux) {
+    for domain in mux.iter_domains() {
+        if domain.downcast_ref::<RemoteSshDomain>().is_none() {
+            continue;
+        }
+        promise::spawn::spawn(async move {
+            if let Some(ssh) = domain.downcast_ref::<RemoteSshDomain>() {
+                if let Err(err) = ssh.check_health(SSH_HEALTH_CHECK_TIMEOUT).await {
+                    log::warn!(
+                        "ssh domain {} failed a health check after resuming: {:#}",
+                        ssh.domain_name(),
+                        err
+                    );
+                }
+            }
+        })
+        .detach();
+    }
+}
+
+fn watch_sleep(tx: Sender<PowerEvent>) {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let result = watch_logind(&tx);
+    #[cfg(target_os = "macos")]
+    let result = watch_iokit(&tx);
+    #[cfg(windows)]
+    let result = watch_power_broadcast(&tx);
+    #[cfg(not(any(unix, windows)))]
+    let result: anyhow::Result<()> = Err(anyhow::anyhow!("unsupported system"));
+
+    match result {
+        Ok(()) => return,
+        Err(err) => log::debug!(
+            "power notifications are not available ({:#}); watching the clock",
+            err
+        ),
+    }
+    watch_clock(&tx);
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn watch_logind(tx: &Sender<PowerEvent>) -> anyhow::Result<()> {
+    let connection = zbus::blocking::Connection::system()?;
+    let proxy = zbus::blocking::Proxy::new(
+        &connection,
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )?;
+    // PrepareForSleep is true before sleeping, and false after waking
+    for message in proxy.receive_signal("PrepareForSleep")? {
+        let sleeping: bool = message.body().deserialize()?;
+        let event = if sleeping {
+            PowerEvent::Suspend
+        } else {
+            PowerEvent::Resume
+        };
+        if tx.send_blocking(event).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// <IOKit/IOMessage.h>
+#[cfg(any(target_os = "macos", test))]
+const IOKIT_CAN_SYSTEM_SLEEP: u32 = 0xe0000270;
+#[cfg(any(target_os = "macos", test))]
+const IOKIT_SYSTEM_WILL_SLEEP: u32 = 0xe0000280;
+#[cfg(any(target_os = "macos", test))]
+const IOKIT_SYSTEM_HAS_POWERED_ON: u32 = 0xe0000300;
+
+/// Returns the event for an IOKit system power message
+#[cfg(any(target_os = "macos", test))]
+fn iokit_message_event(message_type: u32) -> Option<PowerEvent> {
+    match message_type {
+        IOKIT_SYSTEM_WILL_SLEEP => Some(PowerEvent::Suspend),
+        IOKIT_SYSTEM_HAS_POWERED_ON => Some(PowerEvent::Resume),
+        _ => None,
+    }
+}
+
+/// Returns true if the IOKit message must be acknowledged with
+/// IOAllowPowerChange, or else the system waits 30 seconds for us
+#[cfg(target_os = "macos")]
+fn iokit_message_needs_ack(message_type: u32) -> bool {
+    matches!(
+        message_type,
+        IOKIT_CAN_SYSTEM_SLEEP | IOKIT_SYSTEM_WILL_SLEEP
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn watch_iokit(tx: &Sender<PowerEvent>) -> anyhow::Result<()> {
+    use core_foundation::runloop::{
+        kCFRunLoopDefaultMode, CFRunLoopAddSource, CFRunLoopGetCurrent, CFRunLoopRun,
+        CFRunLoopSourceRef,
+    };
+    use std::os::raw::{c_long, c_void};
+
+    type IoConnect = u32;
+    type IoObject = u32;
+    type IoNotificationPortRef = *mut c_void;
+    type IoServiceInterestCallback = extern "C" fn(
+        refcon: *mut c_void,
+        service: IoObject,
+        message_type: u32,
+        message_argument: *mut c_void,
+    );
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IORegisterForSystemPower(
+            refcon: *mut c_void,
+            port: *mut IoNotificationPortRef,
+            callback: IoServiceInterestCallback,
+            notifier: *mut IoObject,
+        ) -> IoConnect;
+        fn IONotificationPortGetRunLoopSource(port: IoNotificationPortRef) -> CFRunLoopSourceRef;
+        fn IOAllowPowerChange(root_port: IoConnect, notification_id: c_long) -> i32;
+    }
+
+    struct Context {
+        root_port: IoConnect,
+        tx: Sender<PowerEvent>,
+    }
+
+    extern "C" fn callback(
+        refcon: *mut c_void,
+        _service: IoObject,
+        message_type: u32,
+        message_argument: *mut c_void,
+    ) {
+        let context = unsafe { &*(refcon as *const Context) };
+        if let Some(event) = iokit_message_event(message_type) {
+            context.tx.send_blocking(event).ok();
+        }
+        if iokit_message_needs_ack(message_type) {
+            unsafe {
+                IOAllowPowerChange(context.root_port, message_argument as c_long);
+            }
+        }
+    }
+
+    // The context lives for as long as the run loop, which is the
+    // rest of the life of this thread
+    let context = Box::into_raw(Box::new(Context {
+        root_port: 0,
+        tx: tx.clone(),
+    }));
+    let mut port: IoNotificationPortRef = std::ptr::null_mut();
+    let mut notifier: IoObject = 0;
+    unsafe {
+        let root_port =
+            IORegisterForSystemPower(context as *mut c_void, &mut port, callback, &mut notifier);
+        if root_port == 0 {
+            drop(Box::from_raw(context));
+            anyhow::bail!("IORegisterForSystemPower failed");
+        }
+        // The callback is only called from the run loop, below
+        (*context).root_port = root_port;
+        CFRunLoopAddSource(
+            CFRunLoopGetCurrent(),
+            IONotificationPortGetRunLoopSource(port),
+            kCFRunLoopDefaultMode,
+        );
+        CFRunLoopRun();
+    }
+    Ok(())
+}
+
+// <winuser.h>
+#[cfg(any(windows, test))]
+const PBT_APMSUSPEND: u32 = 0x4;
+#[cfg(any(windows, test))]
+const PBT_APMRESUMESUSPEND: u32 = 0x7;
+#[cfg(any(windows, test))]
+const PBT_APMRESUMEAUTOMATIC: u32 = 0x12;
+
+/// Returns the event for the wParam of WM_POWERBROADCAST
+#[cfg(any(windows, test))]
+fn power_broadcast_event(wparam: u32) -> Option<PowerEvent> {
+    match wparam {
+        PBT_APMSUSPEND => Some(PowerEvent::Suspend),
+        // This is sent on every resume.  PBT_APMRESUMESUSPEND follows
+        // it when the resume was caused by the user, so it is ignored
+        // in order to report the resume only once.
+        PBT_APMRESUMEAUTOMATIC => Some(PowerEvent::Resume),
+        PBT_APMRESUMESUSPEND => None,
+        _ => None,
+    }
+}
+
+#[cfg(windows)]
+fn watch_power_broadcast(tx: &Sender<PowerEvent>) -> anyhow::Result<()> {
+    use std::cell::RefCell;
+    use winapi::shared::minwindef::{LPARAM, LRESULT, TRUE, UINT, WPARAM};
+    use winapi::shared::windef::HWND;
+    use winapi::um::libloaderapi::GetModuleHandleW;
+    use winapi::um::winuser::{
+        CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassW,
+        TranslateMessage, MSG, WM_POWERBROADCAST, WNDCLASSW,
+    };
+
+    thread_local! {
+        static SENDER: RefCell<Option<Sender<PowerEvent>>> = RefCell::new(None);
+    }
+
+    unsafe extern "system" fn wnd_proc(
+        hwnd: HWND,
+        msg: UINT,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        if msg == WM_POWERBROADCAST {
+            if let Some(event) = power_broadcast_event(wparam as u32) {
+                SENDER.with(|tx| {
+                    if let Some(tx) = tx.borrow().as_ref() {
+                        tx.send_blocking(event).ok();
+                    }
+                });
+            }
+            return TRUE as LRESULT;
+        }
+        DefWindowProcW(hwnd, msg, wparam, lparam)
+    }
+
+    SENDER.with(|sender| sender.borrow_mut().replace(tx.clone()));
+
+    let class_name: Vec<u16> = "WezTermPowerMonitor\0".encode_utf16().collect();
+    unsafe {
+        let instance = GetModuleHandleW(std::ptr::null());
+        let mut class: WNDCLASSW = std::mem::zeroed();
+        class.lpfnWndProc = Some(wnd_proc);
+        class.hInstance = instance;
+        class.lpszClassName = class_name.as_ptr();
+        if RegisterClassW(&class) == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        // Message-only windows don't receive broadcasts, so this
+        // is a top level window that is never shown
+        let hwnd = CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            instance,
+            std::ptr::null_mut(),
+        );
+        if hwnd.is_null() {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let mut msg: MSG = std::mem::zeroed();
+        while GetMessageW(&mut msg, hwnd, 0, 0) > 0 {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if the difference between the clocks shows that the
+/// system slept while `monotonic` elapsed
+fn slept_during(monotonic: Duration, wall: Duration) -> bool {
+    wall.saturating_sub(monotonic) >= SLEEP_THRESHOLD
+}
+
+fn watch_clock(tx: &Sender<PowerEvent>) {
+    loop {
+        let monotonic = Instant::now();
+        let wall = SystemTime::now();
+        std::thread::sleep(CLOCK_CHECK_INTERVAL);
+        let wall = wall.elapsed().unwrap_or_default();
+        if slept_during(monotonic.elapsed(), wall) && tx.send_blocking(PowerEvent::Resume).is_err()
+        {
+            break;
+        }
+    }
+}
+
+/// What was last seen of the batteries
+#[derive(Debug, Default)]
+struct BatteryState {
+    on_ac: Option<bool>,
+    low: bool,
+}
+
+impl BatteryState {
+    /// Records the current state of the batteries, returning the
+    /// events that the change from the prior state produces
+    fn update(&mut self, on_ac: bool, charge: f32) -> Vec<PowerEvent> {
+        let mut events = vec![];
+        match self.on_ac {
+            // The initial state is not a change
+            None => {}
+            Some(was_on_ac) if was_on_ac == on_ac => {}
+            Some(_) if on_ac => events.push(PowerEvent::OnAcPower),
+            Some(_) => events.push(PowerEvent::OnBattery),
+        }
+        self.on_ac = Some(on_ac);
+
+        let low = !on_ac && charge < BATTERY_LOW_CHARGE;
+        // Only report once for each time that the battery runs low
+        if low && !self.low {
+            events.push(PowerEvent::BatteryLow);
+        }
+        self.low = low;
+        events
+    }
+}
+
+fn watch_battery(tx: Sender<PowerEvent>) {
+    use starship_battery::{Manager, State};
+
+    let manager = match Manager::new() {
+        Ok(manager) => manager,
+        Err(err) => {
+            log::debug!("unable to query batteries: {:#}", err);
+            return;
+        }
+    };
+    let mut state = BatteryState::default();
+    loop {
+        let batteries = match manager.batteries() {
+            Ok(batteries) => batteries.filter_map(Result::ok).collect::<Vec<_>>(),
+            Err(err) => {
+                log::debug!("unable to query batteries: {:#}", err);
+                return;
+            }
+        };
+        if batteries.is_empty() {
+            // Nothing to report on a system that has no battery
+            return;
+        }
+        let on_ac = !batteries
+            .iter()
+            .any(|battery| matches!(battery.state(), State::Discharging));
+        let charge = batteries
+            .iter()
+            .map(|battery| battery.state_of_charge().value)
+            .sum::<f32>()
+            / batteries.len() as f32;
+        for event in state.update(on_ac, charge) {
+            if tx.send_blocking(event).is_err() {
+                return;
+            }
+        }
+        std::thread::sleep(BATTERY_CHECK_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detect_sleep() {
+        let five = Duration::from_secs(5);
+        assert!(!slept_during(five, five));
+        assert!(!slept_during(five, five + Duration::from_secs(2)));
+        assert!(slept_during(five, Duration::from_secs(3600)));
+        // The wall clock being set back is not a sleep
+        assert!(!slept_during(five, Duration::ZERO));
+    }
+
+    #[test]
+    fn iokit_messages() {
+        assert_eq!(
+            iokit_message_event(IOKIT_SYSTEM_WILL_SLEEP),
+            Some(PowerEvent::Suspend)
+        );
+        assert_eq!(
+            iokit_message_event(IOKIT_SYSTEM_HAS_POWERED_ON),
+            Some(PowerEvent::Resume)
+        );
+        // Being asked whether the system may sleep is not a suspend
+        assert_eq!(iokit_message_event(IOKIT_CAN_SYSTEM_SLEEP), None);
+    }
+
+    #[test]
+    fn power_broadcasts() {
+        assert_eq!(
+            power_broadcast_event(PBT_APMSUSPEND),
+            Some(PowerEvent::Suspend)
+        );
+        // A resume that the user caused is reported twice by windows,
+        // but must only produce a single event
+        let events: Vec<_> = [PBT_APMRESUMEAUTOMATIC, PBT_APMRESUMESUSPEND]
+            .into_iter()
+            .filter_map(power_broadcast_event)
+            .collect();
+        assert_eq!(events, vec![PowerEvent::Resume]);
+        // PBT_APMPOWERSTATUSCHANGE; the power source is polled instead
+        assert_eq!(power_broadcast_event(0xa), None);
+    }
+
+    #[test]
+    fn battery_transitions() {
+        let mut state = BatteryState::default();
+        assert_eq!(state.update(true, 0.5), vec![]);
+        assert_eq!(state.update(false, 0.5), vec![PowerEvent::OnBattery]);
+        assert_eq!(state.update(false, 0.05), vec![PowerEvent::BatteryLow]);
+        assert_eq!(state.update(false, 0.04), vec![]);
+        assert_eq!(state.update(true, 0.04), vec![PowerEvent::OnAcPower]);
+        assert_eq!(
+            state.update(false, 0.04),
+            vec![PowerEvent::OnBattery, PowerEvent::BatteryLow]
+        );
+    }
+}
@@ -0,0 +1,455 @@
+//! Saving the contents of a pane, including its scrollback, to a file,
+//! and copying it into another pane
+use crate::localpane::LocalPane;
+use crate::pane::Pane;
+use crate::renderable::RenderableDimensions;
+use anyhow::Context;
+use config::keyassignment::ScrollbackExportFormat;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use termwiz::cell::{CellAttributes, Intensity, Underline};
+use termwiz::color::ColorAttribute;
+use termwiz::surface::Line;
+use termwiz_funcs::EscapeEncoder;
+use wezterm_term::color::ColorPalette;
+use wezterm_term::StableRowIndex;
+
+/// How many rows to request from the pane at a time
+const CHUNK_SIZE: usize = 1000;
+
+/// Iterates the stable rows of a pane from the top of its scrollback
+/// through to the bottom of its viewport, yielding ranges of at most
+/// `chunk_size` rows.  This allows walking the entire scrollback
+/// while only holding one chunk of lines in memory at a time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StableRowChunks {
+    next: StableRowIndex,
+    end: StableRowIndex,
+    chunk_size: usize,
+}
+
+impl StableRowChunks {
+    pub fn new(dims: &RenderableDimensions, chunk_size: usize) -> Self {
+        Self {
+            next: dims.scrollback_top,
+            end: dims.physical_top + dims.viewport_rows as StableRowIndex,
+            chunk_size: chunk_size.max(1),
+        }
+    }
+}
+
+impl Iterator for StableRowChunks {
+    type Item = Range<StableRowIndex>;
+
+    fn next(&mut self) -> Option<Range<StableRowIndex>> {
+        if self.next >= self.end {
+            return None;
+        }
+        let start = self.next;
+        let end = start
+            .saturating_add(self.chunk_size as StableRowIndex)
+            .min(self.end);
+        self.next = end;
+        Some(start..end)
+    }
+}
+
+/// Write the complete scrollback of the pane to the specified path.
+/// The lines are retrieved from the pane here, while the file is
+/// written by a separate thread, so that a slow disk doesn't block
+/// the caller's executor.
+pub async fn save_scrollback(
+    pane: &Arc<dyn Pane>,
+    path: &Path,
+    format: ScrollbackExportFormat,
+) -> anyhow::Result<()> {
+    let (tx, rx) = smol::channel::bounded::<Vec<Line>>(2);
+    let title = pane.get_title();
+    let palette = pane.palette();
+    let path = path.to_path_buf();
+
+    let writer = promise::spawn::spawn_into_new_thread(move || {
+        let file = File::create(&path).with_context(|| format!("creating {}", path.display()))?;
+        let mut exporter = Exporter::new(BufWriter::new(file), format);
+        exporter.begin(&title, &palette)?;
+        while let Ok(lines) = rx.recv_blocking() {
+            for line in lines {
+                exporter.line(&line)?;
+            }
+        }
+        exporter
+            .finish()
+            .with_context(|| format!("writing scrollback to {}", path.display()))?;
+        Ok(())
+    });
+
+    for range in StableRowChunks::new(&pane.get_dimensions(), CHUNK_SIZE) {
+        let (_first_row, lines) = pane
+            .fetch_lines(range.clone())
+            .await
+            .with_context(|| format!("retrieving lines {range:?}"))?;
+        if tx.send(lines).await.is_err() {
+            // The writer failed; its error is reported below
+            break;
+        }
+    }
+    drop(tx);
+    writer.await
+}
+
+/// Copies the lines of `source` from the top of its scrollback up to,
+/// but not including, the stable row `end` into the scrollback of
+/// `target`, which must be a local pane.  The lines are shared with the
+/// source until either pane modifies them.
+/// Returns the stable row index in `target` that corresponds to `end`.
+pub async fn inherit_scrollback(
+    source: &Arc<dyn Pane>,
+    target: &Arc<dyn Pane>,
+    end: StableRowIndex,
+) -> anyhow::Result<StableRowIndex> {
+    let local = target.downcast_ref::<LocalPane>().ok_or_else(|| {
+        anyhow::anyhow!(
+            "cannot copy scrollback into pane {} because it is not a local pane",
+            target.pane_id()
+        )
+    })?;
+
+    let dims = source.get_dimensions();
+    let (_first_row, lines) = source
+        .fetch_lines(dims.scrollback_top..end)
+        .await
+        .context("retrieving lines from the source pane")?;
+    Ok(local.seed_scrollback(lines))
+}
+
+/// Serializes a sequence of lines in one of the export formats
+struct Exporter<W: Write> {
+    out: W,
+    format: ScrollbackExportFormat,
+    /// Used to produce SGR sequences for the Ansi format
+    escapes: EscapeEncoder,
+    /// Blank lines are deferred until a non-blank line is seen,
+    /// so that the empty portion of the screen below the last
+    /// output isn't included in the export
+    pending_blank_lines: usize,
+}
+
+impl<W: Write> Exporter<W> {
+    fn new(out: W, format: ScrollbackExportFormat) -> Self {
+        Self {
+            out,
+            format,
+            escapes: EscapeEncoder::new(),
+            pending_blank_lines: 0,
+        }
+    }
+
+    fn begin(&mut self, title: &str, palette: &ColorPalette) -> anyhow::Result<()> {
+        if self.format != ScrollbackExportFormat::Html {
+            return Ok(());
+        }
+
+        writeln!(self.out, "<!DOCTYPE html>")?;
+        writeln!(self.out, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+        writeln!(self.out, "<title>{}</title>", html_escape(title))?;
+        writeln!(self.out, "<style>\n:root {{")?;
+        writeln!(self.out, "  --fg: {};", palette.foreground.to_rgb_string())?;
+        writeln!(self.out, "  --bg: {};", palette.background.to_rgb_string())?;
+        for (idx, color) in palette.colors.0.iter().enumerate() {
+            writeln!(self.out, "  --color-{idx}: {};", color.to_rgb_string())?;
+        }
+        writeln!(self.out, "}}")?;
+        writeln!(
+            self.out,
+            "body {{ background: var(--bg); color: var(--fg); margin: 0; }}"
+        )?;
+        writeln!(
+            self.out,
+            "pre {{ font-family: monospace; margin: 0; padding: 1em; }}"
+        )?;
+        writeln!(self.out, "a {{ color: inherit; }}")?;
+        writeln!(self.out, "</style>\n</head>\n<body>\n<pre>")?;
+        Ok(())
+    }
+
+    fn line(&mut self, line: &Line) -> anyhow::Result<()> {
+        if is_blank(line, self.format) {
+            self.pending_blank_lines += 1;
+            return Ok(());
+        }
+        for _ in 0..self.pending_blank_lines {
+            self.out.write_all(b"\n")?;
+        }
+        self.pending_blank_lines = 0;
+
+        match self.format {
+            ScrollbackExportFormat::Text => {
+                let mut text = String::new();
+                for cell in line.visible_cells() {
+                    text.push_str(cell.str());
+                }
+                writeln!(self.out, "{}", text.trim_end())?;
+            }
+            ScrollbackExportFormat::Ansi => {
+                self.escapes.line(&mut self.out, line, "\n")?;
+            }
+            ScrollbackExportFormat::Html => {
+                for cluster in line.cluster(None) {
+                    let text = html_escape(&cluster.text);
+                    let link = cluster.attrs.hyperlink();
+                    if let Some(link) = link {
+                        write!(self.out, "<a href=\"{}\">", html_escape(link.uri()))?;
+                    }
+                    let style = html_style(&cluster.attrs);
+                    if style.is_empty() {
+                        write!(self.out, "{text}")?;
+                    } else {
+                        write!(self.out, "<span style=\"{style}\">{text}</span>")?;
+                    }
+                    if link.is_some() {
+                        write!(self.out, "</a>")?;
+                    }
+                }
+                writeln!(self.out)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> anyhow::Result<W> {
+        match self.format {
+            ScrollbackExportFormat::Text => {}
+            ScrollbackExportFormat::Ansi => {
+                self.escapes.finish(&mut self.out)?;
+            }
+            ScrollbackExportFormat::Html => {
+                writeln!(self.out, "</pre>\n</body>\n</html>")?;
+            }
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Returns true if the line has no visible content.
+/// For the formats that preserve attributes, a line with
+/// a colored background is considered to be visible.
+fn is_blank(line: &Line, format: ScrollbackExportFormat) -> bool {
+    line.visible_cells().all(|cell| {
+        cell.str().trim().is_empty()
+            && (format == ScrollbackExportFormat::Text
+                || cell.attrs().background() == ColorAttribute::Default)
+    })
+}
+
+fn html_escape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Returns the css color for the attribute, or None for the default
+fn css_color(color: ColorAttribute) -> Option<String> {
+    match color {
+        ColorAttribute::Default => None,
+        ColorAttribute::PaletteIndex(idx) => Some(format!("var(--color-{idx})")),
+        ColorAttribute::TrueColorWithPaletteFallback(color, _)
+        | ColorAttribute::TrueColorWithDefaultFallback(color) => Some(color.to_rgb_string()),
+    }
+}
+
+/// Computes the inline css to represent the cell attributes.
+/// Palette colors refer to the css variables emitted in the
+/// document header, so the exported palette is used consistently.
+fn html_style(attrs: &CellAttributes) -> String {
+    let mut style = vec![];
+
+    let mut fg = css_color(attrs.foreground());
+    let mut bg = css_color(attrs.background());
+    if attrs.reverse() {
+        let new_fg = bg.take().unwrap_or_else(|| "var(--bg)".to_string());
+        bg = Some(fg.take().unwrap_or_else(|| "var(--fg)".to_string()));
+        fg = Some(new_fg);
+    }
+    if let Some(fg) = fg {
+        style.push(format!("color: {fg}"));
+    }
+    if let Some(bg) = bg {
+        style.push(format!("background: {bg}"));
+    }
+
+    match attrs.intensity() {
+        Intensity::Normal => {}
+        Intensity::Bold => style.push("font-weight: bold".to_string()),
+        Intensity::Half => style.push("opacity: 0.5".to_string()),
+    }
+    if attrs.italic() {
+        style.push("font-style: italic".to_string());
+    }
+
+    let mut decorations = vec![];
+    let underline_style = match attrs.underline() {
+        Underline::None => None,
+        Underline::Single => Some("solid"),
+        Underline::Double => Some("double"),
+        Underline::Curly => Some("wavy"),
+        Underline::Dotted => Some("dotted"),
+        Underline::Dashed => Some("dashed"),
+    };
+    if underline_style.is_some() {
+        decorations.push("underline");
+    }
+    if attrs.overline() {
+        decorations.push("overline");
+    }
+    if attrs.strikethrough() {
+        decorations.push("line-through");
+    }
+    if !decorations.is_empty() {
+        style.push(format!("text-decoration: {}", decorations.join(" ")));
+    }
+    if let Some(underline_style) = underline_style {
+        if underline_style != "solid" {
+            style.push(format!("text-decoration-style: {underline_style}"));
+        }
+        if let Some(color) = css_color(attrs.underline_color()) {
+            style.push(format!("text-decoration-color: {color}"));
+        }
+    }
+
+    if attrs.invisible() {
+        style.push("visibility: hidden".to_string());
+    }
+
+    style.join("; ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::surface::SEQ_ZERO;
+
+    fn dims(scrollback_top: StableRowIndex, physical_top: StableRowIndex) -> RenderableDimensions {
+        RenderableDimensions {
+            cols: 80,
+            viewport_rows: 24,
+            scrollback_rows: 24 + (physical_top - scrollback_top) as usize,
+            physical_top,
+            scrollback_top,
+            dpi: 96,
+            pixel_width: 800,
+            pixel_height: 600,
+            reverse_video: false,
+        }
+    }
+
+    #[test]
+    fn chunks() {
+        let chunks: Vec<_> = StableRowChunks::new(&dims(10, 40), 25).collect();
+        assert_eq!(chunks, vec![10..35, 35..60, 60..64]);
+
+        let chunks: Vec<_> = StableRowChunks::new(&dims(0, 0), 1000).collect();
+        assert_eq!(chunks, vec![0..24]);
+    }
+
+    fn export(format: ScrollbackExportFormat, lines: &[Line]) -> String {
+        let mut exporter = Exporter::new(vec![], format);
+        for line in lines {
+            exporter.line(line).unwrap();
+        }
+        String::from_utf8(exporter.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn text_trims_trailing_blank_lines() {
+        let lines = vec![
+            Line::from_text("hello  ", &CellAttributes::blank(), SEQ_ZERO, None),
+            Line::from_text("", &CellAttributes::blank(), SEQ_ZERO, None),
+            Line::from_text("world", &CellAttributes::blank(), SEQ_ZERO, None),
+            Line::from_text("   ", &CellAttributes::blank(), SEQ_ZERO, None),
+        ];
+        assert_eq!(
+            export(ScrollbackExportFormat::Text, &lines),
+            "hello\n\nworld\n"
+        );
+    }
+
+    fn styled_lines() -> Vec<Line> {
+        let warn = CellAttributes::default()
+            .set_intensity(Intensity::Bold)
+            .set_foreground(ColorAttribute::PaletteIndex(1))
+            .clone();
+        let reversed = CellAttributes::default().set_reverse(true).clone();
+        vec![
+            Line::from_text("a<b & c", &CellAttributes::blank(), SEQ_ZERO, None),
+            Line::from_text("", &CellAttributes::blank(), SEQ_ZERO, None),
+            Line::from_text("warn", &warn, SEQ_ZERO, None),
+            Line::from_text("rev", &reversed, SEQ_ZERO, None),
+            Line::from_text("done", &CellAttributes::blank(), SEQ_ZERO, None),
+            Line::from_text("", &CellAttributes::blank(), SEQ_ZERO, None),
+        ]
+    }
+
+    #[test]
+    fn ansi_golden() {
+        assert_eq!(
+            export(ScrollbackExportFormat::Ansi, &styled_lines()),
+            "a<b & c\n\
+             \n\
+             \x1b(B\x1b[0;1m\x1b[31mwarn\n\
+             \x1b(B\x1b[0;7mrev\n\
+             \x1b(B\x1b[0mdone\n"
+        );
+    }
+
+    #[test]
+    fn html_golden() {
+        assert_eq!(
+            export(ScrollbackExportFormat::Html, &styled_lines()),
+            "a&lt;b &amp; c\n\
+             \n\
+             <span style=\"color: var(--color-1); font-weight: bold\">warn</span>\n\
+             <span style=\"color: var(--bg); background: var(--fg)\">rev</span>\n\
+             done\n\
+             </pre>\n</body>\n</html>\n"
+        );
+    }
+
+    #[test]
+    fn html_header_declares_palette() {
+        let palette = ColorPalette::default();
+        let mut exporter = Exporter::new(vec![], ScrollbackExportFormat::Html);
+        exporter.begin("<title>", &palette).unwrap();
+        let html = String::from_utf8(exporter.finish().unwrap()).unwrap();
+
+        let expected_prefix = format!(
+            "<!DOCTYPE html>\n\
+             <html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>&lt;title&gt;</title>\n\
+             <style>\n:root {{\n  \
+             --fg: {};\n  \
+             --bg: {};\n  \
+             --color-0: {};\n",
+            palette.foreground.to_rgb_string(),
+            palette.background.to_rgb_string(),
+            palette.colors.0[0].to_rgb_string(),
+        );
+        assert!(html.starts_with(&expected_prefix), "{html}");
+        assert!(html.contains(&format!(
+            "  --color-255: {};\n}}\n",
+            palette.colors.0[255].to_rgb_string()
+        )));
+        assert!(html.ends_with("<pre>\n</pre>\n</body>\n</html>\n"));
+    }
+}
@@ -0,0 +1,599 @@
+//! Saves the layout of the windows, tabs and panes of the mux to a
+//! file so that it can be replayed later by spawning panes into
+//! matching splits. Only the layout is preserved; the processes that
+//! run in the panes and their scrollback are not.
+use crate::domain::SplitSource;
+use crate::localpane::LocalPane;
+use crate::pane::{Pane, PaneId};
+use crate::tab::{
+    PaneNode, SplitDirection, SplitDirectionAndSize, SplitRequest, SplitSize, Tab, TabId,
+};
+use crate::window::{WindowGeometry, WindowId};
+use crate::{Mux, MuxNotification};
+use anyhow::Context;
+use config::keyassignment::SpawnTabDomain;
+use parking_lot::Mutex;
+use portable_pty::CommandBuilder;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use wezterm_term::TerminalSize;
+
+lazy_static::lazy_static! {
+    static ref LAST_SESSION: Mutex<Option<SessionState>> = Mutex::new(None);
+}
+
+/// Incremented by each notification that the tracker sees, so that
+/// a pending capture can tell that it has been superseded
+static CAPTURE_GENERATION: AtomicUsize = AtomicUsize::new(0);
+static TRACKER_STOPPED: AtomicBool = AtomicBool::new(false);
+
+/// How long the layout must remain unchanged before it is captured
+const CAPTURE_DELAY: Duration = Duration::from_secs(1);
+
+/// The file that is written by `save_session_on_exit`, and that is
+/// restored when no other file is specified
+pub fn default_session_path() -> PathBuf {
+    config::RUNTIME_DIR.join("session.json")
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    pub windows: Vec<SessionWindow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionWindow {
+    pub workspace: String,
+    pub title: String,
+    /// Whether the title was set by the user
+    #[serde(default)]
+    pub title_is_override: bool,
+    /// The size of the tabs in the window
+    pub size: TerminalSize,
+    /// The position and pixel size of the gui window
+    #[serde(default)]
+    pub geometry: WindowGeometry,
+    pub active_tab: usize,
+    pub tabs: Vec<SessionTab>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionTab {
+    pub title: String,
+    /// Whether the title was set by the user
+    #[serde(default)]
+    pub title_is_override: bool,
+    pub root: SessionNode,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SessionNode {
+    Split {
+        direction: SplitDirection,
+        /// The proportion of the split that is occupied by `first`
+        ratio: f32,
+        first: Box<SessionNode>,
+        second: Box<SessionNode>,
+    },
+    Pane(SessionPane),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionPane {
+    pub domain: String,
+    pub cwd: Option<String>,
+    /// The command that the pane was spawned with, if it was
+    /// spawned with an explicit command rather than the default
+    /// program
+    pub argv: Option<Vec<String>>,
+    pub is_active: bool,
+}
+
+impl SessionNode {
+    fn first_pane(&self) -> &SessionPane {
+        match self {
+            Self::Split { first, .. } => first.first_pane(),
+            Self::Pane(pane) => pane,
+        }
+    }
+
+    /// Removes the panes whose domain no longer exists, collapsing
+    /// the splits that held them
+    fn prune(self, mux: &Mux) -> Option<Self> {
+        match self {
+            Self::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => match (first.prune(mux), second.prune(mux)) {
+                (Some(first), Some(second)) => Some(Self::Split {
+                    direction,
+                    ratio,
+                    first: Box::new(first),
+                    second: Box::new(second),
+                }),
+                (first, second) => first.or(second),
+            },
+            Self::Pane(pane) => {
+                if mux.get_domain_by_name(&pane.domain).is_some() {
+                    Some(Self::Pane(pane))
+                } else {
+                    log::warn!(
+                        "restore_session: skipping pane in domain {} as that domain no longer exists",
+                        pane.domain
+                    );
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl SessionPane {
+    fn command(&self) -> Option<CommandBuilder> {
+        self.argv
+            .as_ref()
+            .map(|argv| CommandBuilder::from_argv(argv.iter().map(Into::into).collect()))
+    }
+}
+
+/// Returns the proportion of the split that is occupied by its first child
+fn split_ratio(node: &SplitDirectionAndSize) -> f32 {
+    let (first, second) = match node.direction {
+        SplitDirection::Horizontal => (node.first.cols, node.second.cols),
+        SplitDirection::Vertical => (node.first.rows, node.second.rows),
+    };
+    if first + second == 0 {
+        0.5
+    } else {
+        first as f32 / (first + second) as f32
+    }
+}
+
+/// Returns the size of the second child of a split as a percentage
+fn second_percent(ratio: f32) -> u8 {
+    ((1.0 - ratio) * 100.).round().clamp(1., 99.) as u8
+}
+
+fn capture_node(mux: &Mux, node: PaneNode) -> Option<SessionNode> {
+    match node {
+        PaneNode::Empty => None,
+        PaneNode::Split { left, right, node } => {
+            match (capture_node(mux, *left), capture_node(mux, *right)) {
+                (Some(first), Some(second)) => Some(SessionNode::Split {
+                    direction: node.direction,
+                    ratio: split_ratio(&node),
+                    first: Box::new(first),
+                    second: Box::new(second),
+                }),
+                (first, second) => first.or(second),
+            }
+        }
+        PaneNode::Leaf(entry) => {
+            let pane = mux.get_pane(entry.pane_id)?;
+            let domain = mux.get_domain(pane.domain_id())?;
+            if !domain.spawnable() {
+                // Overlays and other internal panes can't be re-created
+                return None;
+            }
+            Some(SessionNode::Pane(SessionPane {
+                domain: domain.domain_name().to_string(),
+                cwd: entry
+                    .working_dir
+                    .as_ref()
+                    .and_then(|dir| crate::url_to_cwd(&dir.url)),
+                argv: pane
+                    .downcast_ref::<LocalPane>()
+                    .and_then(|local| local.spawn_argv()),
+                is_active: entry.is_active_pane,
+            }))
+        }
+    }
+}
+
+impl SessionState {
+    /// Captures the layout of all of the windows in the mux
+    pub fn capture(mux: &Mux) -> Self {
+        let mut windows = vec![];
+        for window_id in mux.iter_windows() {
+            let (workspace, title, title_is_override, geometry, active_tab, tabs) =
+                match mux.get_window(window_id) {
+                    Some(window) => (
+                        window.get_workspace().to_string(),
+                        window.get_title().to_string(),
+                        window.has_title_override(),
+                        window.get_geometry(),
+                        window.get_active_idx(),
+                        window.iter().cloned().collect::<Vec<Arc<Tab>>>(),
+                    ),
+                    None => continue,
+                };
+            let size = match tabs.first() {
+                Some(tab) => tab.get_size(),
+                None => continue,
+            };
+
+            let mut session_tabs = vec![];
+            let mut session_active_tab = 0;
+            for (idx, tab) in tabs.iter().enumerate() {
+                if let Some(root) = capture_node(mux, tab.codec_pane_tree()) {
+                    if idx == active_tab {
+                        session_active_tab = session_tabs.len();
+                    }
+                    session_tabs.push(SessionTab {
+                        title: tab.get_title(),
+                        title_is_override: tab.has_title_override(),
+                        root,
+                    });
+                }
+            }
+            if !session_tabs.is_empty() {
+                windows.push(SessionWindow {
+                    workspace,
+                    title,
+                    title_is_override,
+                    size,
+                    geometry,
+                    active_tab: session_active_tab,
+                    tabs: session_tabs,
+                });
+            }
+        }
+        Self { windows }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("reading session file {}", path.display()))?;
+        serde_json::from_slice(&data)
+            .with_context(|| format!("parsing session file {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let data = serde_json::to_string_pretty(self)?;
+        config::statefile::write(path, data.as_bytes())
+            .with_context(|| format!("writing session file {}", path.display()))
+    }
+
+    /// Spawns the windows of the session into the mux, returning the
+    /// ids of the newly created windows.
+    /// Panes whose domain no longer exists are skipped with a warning,
+    /// as are any tabs and windows that are left without panes.
+    pub async fn restore(&self) -> anyhow::Result<Vec<WindowId>> {
+        let mux = Mux::get();
+        let mut window_ids = vec![];
+
+        for window in &self.windows {
+            let mut window_id = None;
+            let mut active_tab = None;
+            for (idx, tab) in window.tabs.iter().enumerate() {
+                let root = match tab.root.clone().prune(&mux) {
+                    Some(root) => root,
+                    None => continue,
+                };
+                match restore_tab(&mux, window, window_id, tab, &root).await {
+                    Ok((tab_id, id)) => {
+                        window_id.replace(id);
+                        if idx == window.active_tab {
+                            active_tab.replace(tab_id);
+                        }
+                    }
+                    Err(err) => log::warn!("restore_session: failed to restore tab: {err:#}"),
+                }
+            }
+
+            if let Some(window_id) = window_id {
+                if let Some(mut mux_window) = mux.get_window_mut(window_id) {
+                    if let Some(idx) = active_tab.and_then(|id| mux_window.idx_by_id(id)) {
+                        mux_window.set_active_without_saving(idx);
+                    }
+                    if !window.title.is_empty() {
+                        mux_window.set_title_with_override(&window.title, window.title_is_override);
+                    }
+                }
+                window_ids.push(window_id);
+            }
+        }
+
+        Ok(window_ids)
+    }
+}
+
+async fn restore_tab(
+    mux: &Arc<Mux>,
+    window: &SessionWindow,
+    window_id: Option<WindowId>,
+    session_tab: &SessionTab,
+    root: &SessionNode,
+) -> anyhow::Result<(TabId, WindowId)> {
+    let first_tab_in_window = window_id.is_none();
+    let first = root.first_pane();
+    let (tab, pane, window_id) = mux
+        .spawn_tab_or_window(
+            window_id,
+            SpawnTabDomain::DomainName(first.domain.clone()),
+            first.command(),
+            first.cwd.clone(),
+            window.size,
+            None,
+            window.workspace.clone(),
+            None,
+        )
+        .await?;
+    if !session_tab.title.is_empty() {
+        tab.set_title_with_override(&session_tab.title, session_tab.title_is_override);
+    }
+    if first_tab_in_window {
+        // This must happen before the next await, so that the gui
+        // sees it when it creates the window
+        if let Some(mut mux_window) = mux.get_window_mut(window_id) {
+            mux_window.set_geometry(window.geometry);
+        }
+    }
+
+    // Each entry is a node of the tree together with the pane that
+    // currently occupies the whole of the area of that node
+    let mut stack: Vec<(&SessionNode, PaneId)> = vec![(root, pane.pane_id())];
+    let mut active_pane: Option<Arc<dyn Pane>> = None;
+    while let Some((node, pane_id)) = stack.pop() {
+        match node {
+            SessionNode::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let spawn = second.first_pane();
+                let result = mux
+                    .split_pane(
+                        pane_id,
+                        SplitRequest {
+                            direction: *direction,
+                            target_is_second: true,
+                            top_level: false,
+                            size: SplitSize::Percent(second_percent(*ratio)),
+                        },
+                        SplitSource::Spawn {
+                            command: spawn.command(),
+                            command_dir: spawn.cwd.clone(),
+                            inherit_env: Default::default(),
+                        },
+                        SpawnTabDomain::DomainName(spawn.domain.clone()),
+                    )
+                    .await;
+                match result {
+                    Ok((new_pane, _size)) => {
+                        stack.push((second, new_pane.pane_id()));
+                    }
+                    Err(err) => {
+                        log::warn!("restore_session: failed to split pane {pane_id}: {err:#}")
+                    }
+                }
+                stack.push((first, pane_id));
+            }
+            SessionNode::Pane(session_pane) => {
+                if session_pane.is_active {
+                    active_pane = mux.get_pane(pane_id);
+                }
+            }
+        }
+    }
+
+    if let Some(pane) = active_pane {
+        tab.set_active_pane(&pane);
+    }
+
+    Ok((tab.tab_id(), window_id))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum TrackerAction {
+    /// The layout changed; capture it once it settles
+    Capture,
+    /// A window is going away, either because the user closed it
+    /// or because the gui is shutting down; keep the layout that
+    /// was captured before it started to close
+    Cancel,
+    Ignore,
+}
+
+fn tracker_action(n: &MuxNotification) -> TrackerAction {
+    match n {
+        MuxNotification::PaneAdded(_)
+        | MuxNotification::PaneRemoved(_)
+        | MuxNotification::PaneFocused(_)
+        | MuxNotification::WindowCreated(_)
+        | MuxNotification::WindowGeometryChanged(_)
+        | MuxNotification::WindowWorkspaceChanged(_)
+        | MuxNotification::TabAddedToWindow { .. }
+        | MuxNotification::TabResized(_)
+        | MuxNotification::TabTitleChanged { .. }
+        | MuxNotification::WindowTitleChanged { .. }
+        | MuxNotification::Alert {
+            alert: wezterm_term::Alert::CurrentWorkingDirectoryChanged,
+            ..
+        } => TrackerAction::Capture,
+        MuxNotification::WindowRemoved(_) => TrackerAction::Cancel,
+        _ => TrackerAction::Ignore,
+    }
+}
+
+fn track_session(generation: usize) {
+    if TRACKER_STOPPED.load(Ordering::Relaxed)
+        || CAPTURE_GENERATION.load(Ordering::Relaxed) != generation
+        || !config::configuration().save_session_on_exit
+    {
+        return;
+    }
+    if let Some(mux) = Mux::try_get() {
+        let state = SessionState::capture(&mux);
+        if !state.windows.is_empty() {
+            LAST_SESSION.lock().replace(state);
+        }
+    }
+}
+
+/// Keeps track of the most recent non-empty layout of the mux, so
+/// that it can be saved when the gui exits, even when the last
+/// window was closed before the gui decided to exit.
+/// The layout is captured once it has been stable for CAPTURE_DELAY,
+/// and closing a window discards any pending capture, so that the
+/// windows being torn down one by one during shutdown don't replace
+/// the layout that is saved.
+pub fn start_session_tracker() {
+    Mux::get().subscribe(|n| {
+        let action = tracker_action(&n);
+        if action == TrackerAction::Ignore || TRACKER_STOPPED.load(Ordering::Relaxed) {
+            return true;
+        }
+        let generation = CAPTURE_GENERATION.fetch_add(1, Ordering::Relaxed) + 1;
+        if action == TrackerAction::Capture {
+            // We're called with the subscriber list locked,
+            // and possibly a window too, so defer the capture
+            promise::spawn::spawn_into_main_thread(async move {
+                smol::Timer::after(CAPTURE_DELAY).await;
+                track_session(generation);
+            })
+            .detach();
+        }
+        true
+    });
+}
+
+/// Stops tracking the layout of the mux; called when the gui begins
+/// to shut down so that the layout it tears down is not recorded
+pub fn stop_session_tracker() {
+    TRACKER_STOPPED.store(true, Ordering::Relaxed);
+}
+
+/// Saves the layout of the mux to the default session file, if
+/// `save_session_on_exit` is enabled
+pub fn save_session_on_exit() -> anyhow::Result<()> {
+    if !config::configuration().save_session_on_exit {
+        return Ok(());
+    }
+    let mut state = match Mux::try_get() {
+        Some(mux) => SessionState::capture(&mux),
+        None => return Ok(()),
+    };
+    if state.windows.is_empty() {
+        match LAST_SESSION.lock().take() {
+            Some(last) => state = last,
+            None => return Ok(()),
+        }
+    }
+    state.save(&default_session_path())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ratios() {
+        let node = SplitDirectionAndSize {
+            direction: SplitDirection::Horizontal,
+            first: TerminalSize {
+                cols: 59,
+                rows: 24,
+                ..Default::default()
+            },
+            second: TerminalSize {
+                cols: 20,
+                rows: 24,
+                ..Default::default()
+            },
+        };
+        assert_eq!(second_percent(split_ratio(&node)), 25);
+
+        let node = SplitDirectionAndSize {
+            direction: SplitDirection::Vertical,
+            ..node
+        };
+        assert_eq!(second_percent(split_ratio(&node)), 50);
+
+        // Degenerate splits still produce a usable size
+        assert_eq!(second_percent(1.0), 1);
+        assert_eq!(second_percent(0.0), 99);
+    }
+
+    #[test]
+    fn tracker_actions() {
+        assert_eq!(
+            tracker_action(&MuxNotification::PaneAdded(1)),
+            TrackerAction::Capture
+        );
+        assert_eq!(
+            tracker_action(&MuxNotification::PaneRemoved(1)),
+            TrackerAction::Capture
+        );
+        assert_eq!(
+            tracker_action(&MuxNotification::Alert {
+                pane_id: 1,
+                alert: wezterm_term::Alert::CurrentWorkingDirectoryChanged,
+            }),
+            TrackerAction::Capture
+        );
+        assert_eq!(
+            tracker_action(&MuxNotification::WindowRemoved(1)),
+            TrackerAction::Cancel
+        );
+        assert_eq!(
+            tracker_action(&MuxNotification::PaneOutput(1)),
+            TrackerAction::Ignore
+        );
+        assert_eq!(
+            tracker_action(&MuxNotification::Alert {
+                pane_id: 1,
+                alert: wezterm_term::Alert::Bell,
+            }),
+            TrackerAction::Ignore
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let pane = |domain: &str, argv: Option<Vec<String>>| {
+            SessionNode::Pane(SessionPane {
+                domain: domain.to_string(),
+                cwd: Some("/tmp".to_string()),
+                argv,
+                is_active: false,
+            })
+        };
+        let state = SessionState {
+            windows: vec![SessionWindow {
+                workspace: "default".to_string(),
+                title: String::new(),
+                title_is_override: false,
+                size: TerminalSize::default(),
+                geometry: WindowGeometry {
+                    position: Some((-20, 40)),
+                    pixel_size: Some((800, 600)),
+                },
+                active_tab: 0,
+                tabs: vec![SessionTab {
+                    title: "editor".to_string(),
+                    title_is_override: true,
+                    root: SessionNode::Split {
+                        direction: SplitDirection::Vertical,
+                        ratio: 0.75,
+                        first: Box::new(pane("local", Some(vec!["vim".to_string()]))),
+                        second: Box::new(pane("SSH:host", None)),
+                    },
+                }],
+            }],
+        };
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert_eq!(serde_json::from_str::<SessionState>(&json).unwrap(), state);
+        assert_eq!(
+            state.windows[0].tabs[0].root.first_pane().argv,
+            Some(vec!["vim".to_string()])
+        );
+    }
+}
@@ -2,10 +2,11 @@ use crate::connui::ConnectionUI;
 use crate::domain::{alloc_domain_id, Domain, DomainId, DomainState, WriterWrapper};
 use crate::localpane::LocalPane;
 use crate::pane::{alloc_pane_id, Pane, PaneId};
-use crate::Mux;
+use crate::terminfo_assist::{self, HostChoice, FALLBACK_TERM, TERMINFO_PROMPT};
+use crate::{Mux, MuxNotification};
 use anyhow::{anyhow, bail, Context};
 use async_trait::async_trait;
-use config::{Shell, SshBackend, SshDomain};
+use config::{Shell, SshBackend, SshDomain, TerminfoAssist};
 use filedescriptor::{poll, pollfd, socketpair, AsRawSocketDescriptor, FileDescriptor, POLLIN};
 use portable_pty::cmdbuilder::CommandBuilder;
 use portable_pty::{ChildKiller, ExitStatus, MasterPty, PtySize};
@@ -23,7 +24,8 @@ use termwiz::render::terminfo::TerminfoRenderer;
 use termwiz::surface::{Change, LineAttribute};
 use termwiz::terminal::{ScreenSize, Terminal, TerminalWaker};
 use wezterm_ssh::{
-    ConfigMap, HostVerificationFailed, Session, SessionEvent, SshChildProcess, SshPty,
+    ConfigMap, HostVerificationAnswer, HostVerificationEvent, HostVerificationFailed, Session,
+    SessionEvent, SshChildProcess, SshPty,
 };
 use wezterm_term::TerminalSize;
 
@@ -59,6 +61,7 @@ impl LineEditorHost for PasswordPromptHost {
 
 pub fn ssh_connect_with_ui(
     ssh_config: wezterm_ssh::ConfigMap,
+    jump_hosts: Vec<wezterm_ssh::ConfigMap>,
     ui: &mut ConnectionUI,
 ) -> anyhow::Result<Session> {
     let cloned_ui = ui.clone();
@@ -67,7 +70,7 @@ pub fn ssh_connect_with_ui(
             .get("hostname")
             .expect("ssh config to always set hostname");
         ui.output_str(&format!("Connecting to {} using SSH\n", remote_address));
-        let (session, events) = Session::connect(ssh_config.clone())?;
+        let (session, events) = Session::connect_with_jump_hosts(ssh_config.clone(), jump_hosts)?;
 
         while let Ok(event) = smol::block_on(events.recv()) {
             match event {
@@ -77,16 +80,19 @@ pub fn ssh_connect_with_ui(
                     }
                 }
                 SessionEvent::HostVerify(verify) => {
-                    ui.output_str(&format!("{}\n", verify.message));
-                    let ok = if let Ok(line) = ui.input("Enter [y/n]> ") {
-                        match line.as_ref() {
-                            "y" | "Y" | "yes" | "YES" => true,
-                            "n" | "N" | "no" | "NO" | _ => false,
+                    let verify = match dispatch_host_verification(None, verify) {
+                        Some(verify) => verify,
+                        None => {
+                            ui.output_str("Waiting for host key confirmation\n");
+                            continue;
                         }
-                    } else {
-                        false
                     };
-                    smol::block_on(verify.answer(ok)).context("send verify response")?;
+                    ui.output_str(&format!("{}\n", format_host_verification_prompt(&verify)));
+                    let answer = match ui.input(HOST_VERIFICATION_PROMPT) {
+                        Ok(line) => parse_host_verification_answer(&line),
+                        Err(_) => HostVerificationAnswer::Reject,
+                    };
+                    smol::block_on(verify.answer_with(answer)).context("send verify response")?;
                 }
                 SessionEvent::Authenticate(auth) => {
                     if !auth.username.is_empty() {
@@ -130,6 +136,101 @@ pub fn ssh_connect_with_ui(
     })
 }
 
+/// A function that presents an unknown host key to the user.
+/// It must not block; the answer is delivered later via the
+/// event, which resumes the ssh session thread that is waiting on it.
+pub type HostVerificationHandler = Arc<dyn Fn(HostVerificationRequest) + Send + Sync>;
+
+/// An unknown host key that is passed to the `HostVerificationHandler`.
+/// The handler either takes the event in order to answer it, or hands
+/// it back so that the session prompts for the answer itself.
+pub struct HostVerificationRequest {
+    /// The pane that is being connected, if any.  The prompt should
+    /// be shown in the window that holds it.
+    pub pane_id: Option<PaneId>,
+    verify: HostVerificationEvent,
+    fallback: smol::channel::Sender<HostVerificationEvent>,
+}
+
+impl HostVerificationRequest {
+    pub fn event(&self) -> &HostVerificationEvent {
+        &self.verify
+    }
+
+    /// Takes ownership of the event; the caller is responsible
+    /// for answering it
+    pub fn into_event(self) -> HostVerificationEvent {
+        self.verify
+    }
+
+    /// Hands the event back to the session, which will prompt for
+    /// the answer in the pane or connection window that is connecting
+    pub fn prompt_inline(self) {
+        if let Err(err) = self.fallback.try_send(self.verify) {
+            log::error!("unable to prompt for the host key: {err:#}");
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref HOST_VERIFICATION_HANDLER: Mutex<Option<HostVerificationHandler>> = Mutex::new(None);
+}
+
+/// Registers the handler used to confirm unknown host keys in place
+/// of the inline text prompt.  The GUI uses this to show an overlay.
+pub fn set_host_verification_handler(handler: HostVerificationHandler) {
+    HOST_VERIFICATION_HANDLER.lock().unwrap().replace(handler);
+}
+
+/// Passes the event to the registered handler, if any.
+/// Returns the event back to the caller if it needs to prompt for itself,
+/// either because there is no handler, or because the handler had nowhere
+/// to show it.  This blocks the ssh session thread, but not the handler,
+/// until the handler has decided.
+fn dispatch_host_verification(
+    pane_id: Option<PaneId>,
+    verify: HostVerificationEvent,
+) -> Option<HostVerificationEvent> {
+    let handler = HOST_VERIFICATION_HANDLER.lock().unwrap().clone();
+    match handler {
+        Some(handler) => {
+            let (fallback, rx) = bounded(1);
+            handler(HostVerificationRequest {
+                pane_id,
+                verify,
+                fallback,
+            });
+            // The sender is dropped without sending when the
+            // handler has taken the event
+            rx.recv_blocking().ok()
+        }
+        None => Some(verify),
+    }
+}
+
+const HOST_VERIFICATION_PROMPT: &str = "Enter [y]es and save, accept [o]nce, or [n]o> ";
+
+fn format_host_verification_prompt(verify: &HostVerificationEvent) -> String {
+    let mut text = String::new();
+    if let Some(randomart) = &verify.randomart {
+        text.push_str(randomart);
+        text.push('\n');
+    }
+    if let Some(file) = &verify.known_hosts_file {
+        text.push_str(&format!("Saving will add the key to {}\n", file.display()));
+    }
+    text.push_str(&verify.message);
+    text
+}
+
+fn parse_host_verification_answer(line: &str) -> HostVerificationAnswer {
+    match line.trim().to_lowercase().as_str() {
+        "y" | "yes" => HostVerificationAnswer::AcceptAndSave,
+        "o" | "once" => HostVerificationAnswer::AcceptOnce,
+        _ => HostVerificationAnswer::Reject,
+    }
+}
+
 fn format_host_verification_for_terminal(failed: HostVerificationFailed) -> Vec<Change> {
     vec![
         AttributeChange::Intensity(Intensity::Bold).into(),
@@ -187,16 +288,9 @@ pub struct RemoteSshDomain {
 pub fn ssh_domain_to_ssh_config(ssh_dom: &SshDomain) -> anyhow::Result<ConfigMap> {
     let mut ssh_config = wezterm_ssh::Config::new();
     ssh_config.add_default_config_files();
+    ssh_config.set_allow_exec(config::configuration().ssh_config_allow_exec);
 
-    let (remote_host_name, port) = {
-        let parts: Vec<&str> = ssh_dom.remote_address.split(':').collect();
-
-        if parts.len() == 2 {
-            (parts[0], Some(parts[1].parse::<u16>()?))
-        } else {
-            (ssh_dom.remote_address.as_str(), None)
-        }
-    };
+    let (remote_host_name, port) = split_remote_address(&ssh_dom.remote_address)?;
 
     let mut ssh_config = ssh_config.for_host(&remote_host_name);
     ssh_config.insert(
@@ -213,6 +307,12 @@ pub fn ssh_domain_to_ssh_config(ssh_dom: &SshDomain) -> anyhow::Result<ConfigMap
     for (k, v) in &ssh_dom.ssh_option {
         ssh_config.insert(k.to_string(), v.to_string());
     }
+    if let Some(strict) = ssh_dom.strict_host_key_checking {
+        ssh_config.insert(
+            "stricthostkeychecking".to_string(),
+            strict.as_ssh_option().to_string(),
+        );
+    }
 
     if let Some(username) = &ssh_dom.username {
         ssh_config.insert("user".to_string(), username.to_string());
@@ -229,6 +329,54 @@ pub fn ssh_domain_to_ssh_config(ssh_dom: &SshDomain) -> anyhow::Result<ConfigMap
     Ok(ssh_config)
 }
 
+/// Split "host:port" into its constituent parts
+fn split_remote_address(remote_address: &str) -> anyhow::Result<(&str, Option<u16>)> {
+    let parts: Vec<&str> = remote_address.split(':').collect();
+
+    if parts.len() == 2 {
+        Ok((parts[0], Some(parts[1].parse::<u16>()?)))
+    } else {
+        Ok((remote_address, None))
+    }
+}
+
+/// Computes the ssh configuration for each of the `proxy_jump`
+/// hosts of an SshDomain, in the order that they should be traversed.
+pub fn ssh_domain_jump_hosts(ssh_dom: &SshDomain) -> anyhow::Result<Vec<ConfigMap>> {
+    if ssh_dom.proxy_jump.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut ssh_config = wezterm_ssh::Config::new();
+    ssh_config.add_default_config_files();
+    ssh_config.set_allow_exec(config::configuration().ssh_config_allow_exec);
+
+    let mut jump_hosts = vec![];
+    for hop in &ssh_dom.proxy_jump {
+        let (host_name, port) = split_remote_address(&hop.remote_address)
+            .with_context(|| format!("parsing proxy_jump host {}", hop.remote_address))?;
+        let mut hop_config = ssh_config.for_host(host_name);
+        for (k, v) in &hop.ssh_option {
+            hop_config.insert(k.to_string(), v.to_string());
+        }
+        if let Some(username) = &hop.username {
+            hop_config.insert("user".to_string(), username.to_string());
+        }
+        if let Some(port) = port {
+            hop_config.insert("port".to_string(), port.to_string());
+        }
+        hop_config.insert(
+            "forwardagent".to_string(),
+            if hop.forward_agent { "yes" } else { "no" }.to_string(),
+        );
+        if ssh_dom.no_agent_auth {
+            hop_config.insert("identitiesonly".to_string(), "yes".to_string());
+        }
+        jump_hosts.push(hop_config);
+    }
+    Ok(jump_hosts)
+}
+
 impl RemoteSshDomain {
     pub fn with_ssh_domain(dom: &SshDomain) -> anyhow::Result<Self> {
         let id = alloc_domain_id();
@@ -244,13 +392,43 @@ impl RemoteSshDomain {
         ssh_domain_to_ssh_config(&self.dom)
     }
 
+    /// Returns the ssh session of this domain once it has been
+    /// established, so that files can be transferred over it
+    /// Checks that the session, if there is one, is still usable by
+    /// running a trivial command.  If that fails, or the server doesn't
+    /// respond within `timeout`, the session is discarded so that the
+    /// next spawn connects afresh rather than using a dead connection.
+    pub async fn check_health(&self, timeout: Duration) -> anyhow::Result<()> {
+        let session = match self.session() {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+        let probe = async { Some(session.exec("true", None).await.map(|_| ())) };
+        let expired = async {
+            smol::Timer::after(timeout).await;
+            None
+        };
+        let result = match smol::future::or(probe, expired).await {
+            Some(result) => result,
+            None => Err(anyhow!("the server did not respond within {:?}", timeout)),
+        };
+        if result.is_err() {
+            self.session.lock().unwrap().take();
+        }
+        result
+    }
+
+    pub fn session(&self) -> Option<Session> {
+        self.session.lock().unwrap().clone()
+    }
+
     fn build_command(
         &self,
         pane_id: PaneId,
         command: Option<CommandBuilder>,
         command_dir: Option<String>,
     ) -> anyhow::Result<(Option<String>, HashMap<String, String>)> {
-        let config = config::configuration();
+        let config = Mux::get().spawn_config();
         let cmd = match command {
             Some(mut cmd) => {
                 config.apply_cmd_defaults(&mut cmd, None);
@@ -326,12 +504,18 @@ impl RemoteSshDomain {
 
     async fn start_new_session(
         &self,
+        pane_id: PaneId,
         command_line: Option<String>,
         env: HashMap<String, String>,
         size: TerminalSize,
     ) -> anyhow::Result<StartNewSessionResult> {
-        let (session, events) = Session::connect(self.ssh_config().context("obtain ssh config")?)
-            .context("connect to ssh server")?;
+        let ssh_config = self.ssh_config().context("obtain ssh config")?;
+        let host = terminfo_assist::host_key(&ssh_config);
+        let (session, events) = Session::connect_with_jump_hosts(
+            ssh_config,
+            ssh_domain_jump_hosts(&self.dom).context("obtain proxy_jump ssh config")?,
+        )
+        .context("connect to ssh server")?;
         self.session.lock().unwrap().replace(session.clone());
 
         // We get to establish the session!
@@ -391,6 +575,8 @@ impl RemoteSshDomain {
             if let Err(err) = connect_ssh_session(
                 session,
                 events,
+                pane_id,
+                &host,
                 stdin_read,
                 writer_tx,
                 &mut stdout_write,
@@ -421,6 +607,8 @@ struct StartNewSessionResult {
 fn connect_ssh_session(
     session: Session,
     events: smol::channel::Receiver<SessionEvent>,
+    pane_id: PaneId,
+    host: &str,
     mut stdin_read: FileDescriptor,
     stdin_tx: Sender<BoxedWriter>,
     stdout_write: &mut BufWriter<FileDescriptor>,
@@ -598,20 +786,23 @@ fn connect_ssh_session(
                 }
             }
             SessionEvent::HostVerify(verify) => {
-                shim.output_line(&verify.message)?;
+                let verify = match dispatch_host_verification(Some(pane_id), verify) {
+                    Some(verify) => verify,
+                    None => {
+                        shim.output_line("Waiting for host key confirmation")?;
+                        continue;
+                    }
+                };
+                shim.output_line(&format_host_verification_prompt(&verify))?;
                 let mut editor = LineEditor::new(&mut shim);
                 let mut host = PasswordPromptHost::default();
                 host.echo = true;
-                editor.set_prompt("Enter [y/n]> ");
-                let ok = if let Some(line) = editor.read_line(&mut host)? {
-                    match line.as_ref() {
-                        "y" | "Y" | "yes" | "YES" => true,
-                        "n" | "N" | "no" | "NO" | _ => false,
-                    }
-                } else {
-                    false
+                editor.set_prompt(HOST_VERIFICATION_PROMPT);
+                let answer = match editor.read_line(&mut host)? {
+                    Some(line) => parse_host_verification_answer(&line),
+                    None => HostVerificationAnswer::Reject,
                 };
-                smol::block_on(verify.answer(ok)).context("send verify response")?;
+                smol::block_on(verify.answer_with(answer)).context("send verify response")?;
             }
             SessionEvent::Authenticate(auth) => {
                 if !auth.username.is_empty() {
@@ -647,10 +838,12 @@ fn connect_ssh_session(
                 shim.render(&message)?;
             }
             SessionEvent::Authenticated => {
+                let term = assist_terminfo(&session, host, pane_id, &mut shim)?;
+
                 // Our session has been authenticated: we can now
                 // set up the real pty for the pane
                 match smol::block_on(session.request_pty(
-                    &config::configuration().term,
+                    &term,
                     crate::terminal_size_to_pty_size(*size.lock().unwrap())?,
                     command_line.as_ref().map(|s| s.as_str()),
                     Some(env),
@@ -697,6 +890,115 @@ fn connect_ssh_session(
     Ok(())
 }
 
+fn output_line<T: Terminal>(term: &mut T, s: &str) -> termwiz::Result<()> {
+    let mut s = s.replace("\n", "\r\n");
+    s.push_str("\r\n");
+    term.render(&[Change::Text(s)])
+}
+
+/// Decides which TERM to use for a newly authenticated session.
+/// If the wezterm terminfo entry is missing on the remote host, then,
+/// depending on `terminfo_assist`, this either installs it, or asks
+/// whether to install it or to fall back to xterm-256color.
+fn assist_terminfo<T: Terminal>(
+    session: &Session,
+    host: &str,
+    pane_id: PaneId,
+    term: &mut T,
+) -> anyhow::Result<String> {
+    let config = config::configuration();
+    let term_name = config.term.clone();
+    if term_name != "wezterm" || config.terminfo_assist == TerminfoAssist::Off {
+        return Ok(term_name);
+    }
+
+    let remembered = terminfo_assist::remembered_choice(host);
+    match remembered {
+        Some(HostChoice::UseFallback) => return Ok(FALLBACK_TERM.to_string()),
+        Some(HostChoice::Ignore) => return Ok(term_name),
+        Some(HostChoice::Install) | None => {}
+    }
+
+    match terminfo_assist::probe(session, &term_name) {
+        Ok(true) => return Ok(term_name),
+        Ok(false) => {}
+        Err(err) => {
+            log::warn!("Unable to check for the {term_name} terminfo on {host}: {err:#}");
+            return Ok(term_name);
+        }
+    }
+
+    let choice = match (remembered, config.terminfo_assist) {
+        (Some(choice), _) => choice,
+        (None, TerminfoAssist::Auto) => HostChoice::Install,
+        (None, _) => {
+            if terminfo_assist::should_toast(pane_id) {
+                Mux::notify_from_any_thread(MuxNotification::Alert {
+                    pane_id,
+                    alert: wezterm_term::Alert::ToastNotification {
+                        title: Some("wezterm terminfo is missing".to_string()),
+                        body: format!(
+                            "{host} doesn't have the wezterm terminfo entry; \
+                             see the pane that is connecting to it to fix this"
+                        ),
+                        focus: true,
+                        actions: vec![],
+                    },
+                });
+            }
+            output_line(
+                term,
+                &format!(
+                    "{host} doesn't have the {term_name} terminfo entry, \
+                     so colors and keys may not work correctly.\n\
+                     wezterm can install it into ~/.terminfo on {host}, \
+                     or use TERM={FALLBACK_TERM} for {host} instead."
+                ),
+            )?;
+            let mut editor = LineEditor::new(term);
+            let mut prompt_host = PasswordPromptHost::default();
+            prompt_host.echo = true;
+            editor.set_prompt(TERMINFO_PROMPT);
+            let answer = editor.read_line(&mut prompt_host)?;
+            match answer.as_deref().and_then(terminfo_assist::parse_answer) {
+                Some(choice) => {
+                    if let Err(err) = terminfo_assist::remember_choice(host, choice) {
+                        log::error!("Failed to save terminfo choice for {host}: {err:#}");
+                    }
+                    choice
+                }
+                // Ask again next time
+                None => return Ok(term_name),
+            }
+        }
+    };
+
+    match choice {
+        HostChoice::Install => match terminfo_assist::install(session) {
+            Ok(()) => {
+                output_line(
+                    term,
+                    &format!("Installed the {term_name} terminfo entry on {host}"),
+                )?;
+                Ok(term_name)
+            }
+            Err(err) => {
+                log::error!("Failed to install terminfo on {host}: {err:#}");
+                output_line(
+                    term,
+                    &format!(
+                        "Failed to install the {term_name} terminfo entry: {err:#}\n\
+                         Using TERM={FALLBACK_TERM} for this session"
+                    ),
+                )?;
+                Ok(FALLBACK_TERM.to_string())
+            }
+        },
+        HostChoice::UseFallback => Ok(FALLBACK_TERM.to_string()),
+        HostChoice::Ignore => Ok(term_name),
+    }
+}
+
 #[async_trait(?Send)]
 impl Domain for RemoteSshDomain {
     async fn spawn_pane(
@@ -716,9 +1018,13 @@ impl Domain for RemoteSshDomain {
         let mut session: Option<Session> = self.session.lock().unwrap().as_ref().cloned();
 
         let StartNewSessionResult { pty, child, writer } = if let Some(session) = session.take() {
+            let term = terminfo_assist::term_for_host(
+                &Mux::get().spawn_config().term,
+                &terminfo_assist::host_key(&self.ssh_config()?),
+            );
             match session
                 .request_pty(
-                    &config::configuration().term,
+                    &term,
                     crate::terminal_size_to_pty_size(size)
                         .context("compute pty size from terminal size")?,
                     command_line.as_ref().map(|s| s.as_str()),
@@ -742,7 +1048,8 @@ impl Domain for RemoteSshDomain {
                     {
                         // Session died (perhaps they closed the initial tab?)
                         // So we'll try making a new one
-                        self.start_new_session(command_line, env, size).await?
+                        self.start_new_session(pane_id, command_line, env, size)
+                            .await?
                     } else {
                         log::error!("{err:#?}");
                         return Err(err);
@@ -750,7 +1057,8 @@ impl Domain for RemoteSshDomain {
                 }
             }
         } else {
-            self.start_new_session(command_line, env, size).await?
+            self.start_new_session(pane_id, command_line, env, size)
+                .await?
         };
 
         // Wrap up the pty etc. in a LocalPane.  That allows for
@@ -758,6 +1066,9 @@ impl Domain for RemoteSshDomain {
         // session without duplicating a lot of logic over here.
 
         let writer = WriterWrapper::new(writer);
+        if let Some(encoding) = self.dom.encoding {
+            *writer.encoding().lock() = encoding;
+        }
 
         let terminal = wezterm_term::Terminal::new(
             size,
@@ -767,15 +1078,18 @@ impl Domain for RemoteSshDomain {
             Box::new(writer.clone()),
         );
 
-        let pane: Arc<dyn Pane> = Arc::new(LocalPane::new(
-            pane_id,
-            terminal,
-            child,
-            pty,
-            Box::new(writer),
-            self.id,
-            "RemoteSshDomain".to_string(),
-        ));
+        let pane: Arc<dyn Pane> = Arc::new(
+            LocalPane::new(
+                pane_id,
+                terminal,
+                child,
+                pty,
+                Box::new(writer.clone()),
+                self.id,
+                "RemoteSshDomain".to_string(),
+            )
+            .with_encoding_of(&writer),
+        );
         let mux = Mux::get();
         mux.add_pane(&pane)?;
 
@@ -790,6 +1104,10 @@ impl Domain for RemoteSshDomain {
         &self.name
     }
 
+    fn domain_type(&self) -> &'static str {
+        "ssh"
+    }
+
     async fn attach(&self, _window_id: Option<crate::WindowId>) -> anyhow::Result<()> {
         Ok(())
     }
@@ -1146,3 +1464,27 @@ impl std::io::Read for PtyReader {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn host_verification_answer() {
+        use HostVerificationAnswer::*;
+        for (line, answer) in [
+            ("y", AcceptAndSave),
+            ("yes", AcceptAndSave),
+            ("  YES\n", AcceptAndSave),
+            ("o", AcceptOnce),
+            ("Once", AcceptOnce),
+            ("n", Reject),
+            ("no", Reject),
+            ("", Reject),
+            ("yep", Reject),
+            ("oh no", Reject),
+        ] {
+            assert_eq!(parse_host_verification_answer(line), answer, "{line:?}");
+        }
+    }
+}
@@ -1,10 +1,11 @@
 use crate::domain::DomainId;
+use crate::links::PaneLink;
 use crate::pane::*;
 use crate::renderable::StableCursorPosition;
 use crate::{Mux, MuxNotification, WindowId};
 use bintree::PathBranch;
 use config::configuration;
-use config::keyassignment::PaneDirection;
+use config::keyassignment::{FloatingPanePosition, PaneDirection};
 use parking_lot::Mutex;
 use rangeset::intersects_range;
 use serde::{Deserialize, Serialize};
@@ -45,7 +46,21 @@ struct TabInner {
     active: usize,
     zoomed: Option<Arc<dyn Pane>>,
     title: String,
+    /// true if `title` was set by the user rather than by an
+    /// application in the tab
+    title_is_override: bool,
     recency: Recency,
+    /// The panes that float above the split tree, from the
+    /// bottom-most to the top-most
+    floating: Vec<FloatingPane>,
+    floating_hidden: bool,
+    /// The floating pane that has focus.  While the floating panes
+    /// are shown, it is the active pane of the tab.
+    floating_focus: Option<PaneId>,
+    /// The number of rows at the top of each pane of the split tree
+    /// that the front end occupies with decorations, such as a title
+    /// bar, rather than with the terminal of the pane
+    pane_decoration_rows: usize,
 }
 
 /// A Tab is a container of Panes
@@ -62,6 +77,10 @@ pub struct PositionedPane {
     pub is_active: bool,
     /// true if this pane is zoomed
     pub is_zoomed: bool,
+    /// true if this pane floats above the split tree rather than
+    /// being part of it.  The index of a floating pane is its
+    /// position in the stacking order of the floating panes.
+    pub is_floating: bool,
     /// The offset from the top left corner of the containing tab to the top
     /// left corner of this pane, in cells.
     pub left: usize,
@@ -83,6 +102,7 @@ impl std::fmt::Debug for PositionedPane {
         fmt.debug_struct("PositionedPane")
             .field("index", &self.index)
             .field("is_active", &self.is_active)
+            .field("is_floating", &self.is_floating)
             .field("left", &self.left)
             .field("top", &self.top)
             .field("width", &self.width)
@@ -189,6 +209,63 @@ impl SplitDirectionAndSize {
     }
 }
 
+/// The size and placement of a floating pane within its tab.
+/// This type is used directly by the codec, take care to bump
+/// the codec version if you change this
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct FloatingPaneGeometry {
+    pub width: SplitSize,
+    pub height: SplitSize,
+    pub position: FloatingPanePosition,
+}
+
+impl Default for FloatingPaneGeometry {
+    fn default() -> Self {
+        Self {
+            width: SplitSize::Percent(60),
+            height: SplitSize::Percent(60),
+            position: FloatingPanePosition::Center,
+        }
+    }
+}
+
+impl FloatingPaneGeometry {
+    /// Returns the (left, top, cols, rows) of the pane within a tab
+    /// of `tab_cols` by `tab_rows` cells.  Where there is room, a
+    /// margin of a cell is kept around the pane for its border.
+    pub fn place(&self, tab_cols: usize, tab_rows: usize) -> (usize, usize, usize, usize) {
+        fn extent(size: SplitSize, available: usize) -> usize {
+            let wanted = match size {
+                SplitSize::Cells(n) => n,
+                SplitSize::Percent(n) => available * n.min(100) as usize / 100,
+            };
+            wanted.clamp(1, available.saturating_sub(2).max(1))
+        }
+        let cols = extent(self.width, tab_cols);
+        let rows = extent(self.height, tab_rows);
+        let spare_cols = tab_cols.saturating_sub(cols);
+        let spare_rows = tab_rows.saturating_sub(rows);
+
+        use FloatingPanePosition::*;
+        let left = match self.position {
+            TopLeft | Left | BottomLeft => spare_cols.min(1),
+            TopRight | Right | BottomRight => spare_cols.saturating_sub(1),
+            Top | Center | Bottom => spare_cols / 2,
+        };
+        let top = match self.position {
+            TopLeft | Top | TopRight => spare_rows.min(1),
+            BottomLeft | Bottom | BottomRight => spare_rows.saturating_sub(1),
+            Left | Center | Right => spare_rows / 2,
+        };
+        (left, top, cols, rows)
+    }
+}
+
+struct FloatingPane {
+    pane: Arc<dyn Pane>,
+    geometry: FloatingPaneGeometry,
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct PositionedSplit {
     /// The topological node index that can be used to reference this split
@@ -252,34 +329,52 @@ fn pane_tree(
                 node: data,
             }
         }
-        Tree::Leaf(pane) => {
-            let dims = pane.get_dimensions();
-            let working_dir = pane.get_current_working_dir(CachePolicy::AllowStale);
-            let cursor_pos = pane.get_cursor_position();
+        Tree::Leaf(pane) => PaneNode::Leaf(pane_entry(
+            pane, tab_id, window_id, active, zoomed, workspace, left_col, top_row,
+        )),
+    }
+}
 
-            PaneNode::Leaf(PaneEntry {
-                window_id,
-                tab_id,
-                pane_id: pane.pane_id(),
-                title: pane.get_title(),
-                is_active_pane: is_pane(pane, &active),
-                is_zoomed_pane: is_pane(pane, &zoomed),
-                size: TerminalSize {
-                    cols: dims.cols,
-                    rows: dims.viewport_rows,
-                    pixel_height: dims.pixel_height,
-                    pixel_width: dims.pixel_width,
-                    dpi: dims.dpi,
-                },
-                working_dir: working_dir.map(Into::into),
-                workspace: workspace.to_string(),
-                cursor_pos,
-                physical_top: dims.physical_top,
-                left_col,
-                top_row,
-                tty_name: pane.tty_name(),
-            })
-        }
+fn pane_entry(
+    pane: &Arc<dyn Pane>,
+    tab_id: TabId,
+    window_id: WindowId,
+    active: Option<&Arc<dyn Pane>>,
+    zoomed: Option<&Arc<dyn Pane>>,
+    workspace: &str,
+    left_col: usize,
+    top_row: usize,
+) -> PaneEntry {
+    let dims = pane.get_dimensions();
+    let working_dir = pane.get_current_working_dir(CachePolicy::AllowStale);
+    let cursor_pos = pane.get_cursor_position();
+
+    PaneEntry {
+        window_id,
+        tab_id,
+        pane_id: pane.pane_id(),
+        title: pane.get_title(),
+        is_active_pane: is_pane(pane, &active),
+        is_zoomed_pane: is_pane(pane, &zoomed),
+        is_floating: false,
+        size: TerminalSize {
+            cols: dims.cols,
+            rows: dims.viewport_rows,
+            pixel_height: dims.pixel_height,
+            pixel_width: dims.pixel_width,
+            dpi: dims.dpi,
+        },
+        working_dir: working_dir.map(Into::into),
+        workspace: workspace.to_string(),
+        cursor_pos,
+        physical_top: dims.physical_top,
+        left_col,
+        top_row,
+        tty_name: pane.tty_name(),
+        links: Mux::try_get()
+            .map(|mux| mux.get_pane_links(pane.pane_id()))
+            .unwrap_or_default(),
+        nesting_depth: pane.get_nesting_depth(),
     }
 }
 
@@ -316,7 +411,7 @@ where
 
 /// Computes the minimum (x, y) size based on the panes in this portion
 /// of the tree.
-fn compute_min_size(tree: &mut Tree) -> (usize, usize) {
+fn compute_min_size(tree: &mut Tree, decoration_rows: usize) -> (usize, usize) {
     match tree {
         Tree::Node { data: None, .. } | Tree::Empty => (1, 1),
         Tree::Node {
@@ -324,19 +419,20 @@ fn compute_min_size(tree: &mut Tree) -> (usize, usize) {
             right,
             data: Some(data),
         } => {
-            let (left_x, left_y) = compute_min_size(&mut *left);
-            let (right_x, right_y) = compute_min_size(&mut *right);
+            let (left_x, left_y) = compute_min_size(&mut *left, decoration_rows);
+            let (right_x, right_y) = compute_min_size(&mut *right, decoration_rows);
             match data.direction {
                 SplitDirection::Vertical => (left_x.max(right_x), left_y + right_y + 1),
                 SplitDirection::Horizontal => (left_x + right_x + 1, left_y.max(right_y)),
             }
         }
-        Tree::Leaf(_) => (1, 1),
+        Tree::Leaf(_) => (1, 1 + decoration_rows),
     }
 }
 
 fn adjust_x_size(tree: &mut Tree, mut x_adjust: isize, cell_dimensions: &TerminalSize) {
-    let (min_x, _) = compute_min_size(tree);
+    // The decorations don't affect the width
+    let (min_x, _) = compute_min_size(tree, 0);
     while x_adjust != 0 {
         match tree {
             Tree::Empty | Tree::Leaf(_) => return,
@@ -405,8 +501,13 @@ fn adjust_x_size(tree: &mut Tree, mut x_adjust: isize, cell_dimensions: &Termina
     }
 }
 
-fn adjust_y_size(tree: &mut Tree, mut y_adjust: isize, cell_dimensions: &TerminalSize) {
-    let (_, min_y) = compute_min_size(tree);
+fn adjust_y_size(
+    tree: &mut Tree,
+    mut y_adjust: isize,
+    cell_dimensions: &TerminalSize,
+    decoration_rows: usize,
+) {
+    let (_, min_y) = compute_min_size(tree, decoration_rows);
     while y_adjust != 0 {
         match tree {
             Tree::Empty | Tree::Leaf(_) => return,
@@ -426,25 +527,25 @@ fn adjust_y_size(tree: &mut Tree, mut y_adjust: isize, cell_dimensions: &Termina
                         y_adjust = new_rows.saturating_sub(data.first.rows as isize);
 
                         if y_adjust != 0 {
-                            adjust_y_size(&mut *left, y_adjust, cell_dimensions);
+                            adjust_y_size(&mut *left, y_adjust, cell_dimensions, decoration_rows);
                             data.first.rows = new_rows.try_into().unwrap();
                             data.first.pixel_height =
                                 data.first.rows.saturating_mul(cell_dimensions.pixel_height);
 
-                            adjust_y_size(&mut *right, y_adjust, cell_dimensions);
+                            adjust_y_size(&mut *right, y_adjust, cell_dimensions, decoration_rows);
                             data.second.rows = data.first.rows;
                             data.second.pixel_height = data.first.pixel_height;
                         }
                         return;
                     }
                     SplitDirection::Vertical if y_adjust > 0 => {
-                        adjust_y_size(&mut *left, 1, cell_dimensions);
+                        adjust_y_size(&mut *left, 1, cell_dimensions, decoration_rows);
                         data.first.rows += 1;
                         data.first.pixel_height =
                             data.first.rows.saturating_mul(cell_dimensions.pixel_height);
                         y_adjust -= 1;
                         if y_adjust > 0 {
-                            adjust_y_size(&mut *right, 1, cell_dimensions);
+                            adjust_y_size(&mut *right, 1, cell_dimensions, decoration_rows);
                             data.second.rows += 1;
                             data.second.pixel_height = data
                                 .second
@@ -455,15 +556,16 @@ fn adjust_y_size(tree: &mut Tree, mut y_adjust: isize, cell_dimensions: &Termina
                     }
                     SplitDirection::Vertical => {
                         // y_adjust is negative
-                        if data.first.rows > 1 {
-                            adjust_y_size(&mut *left, -1, cell_dimensions);
+                        let min_rows = 1 + decoration_rows;
+                        if data.first.rows > min_rows {
+                            adjust_y_size(&mut *left, -1, cell_dimensions, decoration_rows);
                             data.first.rows -= 1;
                             data.first.pixel_height =
                                 data.first.rows.saturating_mul(cell_dimensions.pixel_height);
                             y_adjust += 1;
                         }
-                        if y_adjust < 0 && data.second.rows > 1 {
-                            adjust_y_size(&mut *right, -1, cell_dimensions);
+                        if y_adjust < 0 && data.second.rows > min_rows {
+                            adjust_y_size(&mut *right, -1, cell_dimensions, decoration_rows);
                             data.second.rows -= 1;
                             data.second.pixel_height = data
                                 .second
@@ -478,7 +580,7 @@ fn adjust_y_size(tree: &mut Tree, mut y_adjust: isize, cell_dimensions: &Termina
     }
 }
 
-fn apply_sizes_from_splits(tree: &Tree, size: &TerminalSize) {
+fn apply_sizes_from_splits(tree: &Tree, size: &TerminalSize, decoration_rows: usize) {
     match tree {
         Tree::Empty => return,
         Tree::Node { data: None, .. } => return,
@@ -487,15 +589,63 @@ fn apply_sizes_from_splits(tree: &Tree, size: &TerminalSize) {
             right,
             data: Some(data),
         } => {
-            apply_sizes_from_splits(&*left, &data.first);
-            apply_sizes_from_splits(&*right, &data.second);
+            apply_sizes_from_splits(&*left, &data.first, decoration_rows);
+            apply_sizes_from_splits(&*right, &data.second, decoration_rows);
         }
         Tree::Leaf(pane) => {
-            pane.resize(*size).ok();
+            pane.resize(pane_content_size(&**pane, *size, decoration_rows))
+                .ok();
         }
     }
 }
 
+/// Given the area allotted to a pane, returns the size of the
+/// terminal that fits beneath the `decoration` rows of the pane
+/// and beside its timestamps gutter
+fn pane_content_size(pane: &dyn Pane, mut size: TerminalSize, decoration: usize) -> TerminalSize {
+    if decoration != 0 && size.rows > decoration {
+        let cell_height = size.pixel_height.checked_div(size.rows).unwrap_or(0);
+        size.rows -= decoration;
+        size.pixel_height = size.pixel_height.saturating_sub(decoration * cell_height);
+    }
+    let gutter = pane.timestamp_gutter_cols();
+    if gutter != 0 && size.cols > gutter {
+        let cell_width = size.pixel_width.checked_div(size.cols).unwrap_or(0);
+        size.cols -= gutter;
+        size.pixel_width = size.pixel_width.saturating_sub(gutter * cell_width);
+    }
+    size
+}
+
+/// Like pane_content_size, but for a floating pane, which
+/// has a border rather than decorations
+fn floating_content_size(pane: &dyn Pane, mut size: TerminalSize) -> TerminalSize {
+    let gutter = pane.timestamp_gutter_cols();
+    if gutter != 0 && size.cols > gutter {
+        let cell_width = size.pixel_width.checked_div(size.cols).unwrap_or(0);
+        size.cols -= gutter;
+        size.pixel_width = size.pixel_width.saturating_sub(gutter * cell_width);
+    }
+    size
+}
+
+/// The inverse of pane_content_size: given the size of the terminal
+/// in a pane, returns the area that the pane occupies
+fn pane_area_size(pane: &dyn Pane, mut size: TerminalSize, decoration: usize) -> TerminalSize {
+    if decoration != 0 {
+        let cell_height = size.pixel_height.checked_div(size.rows).unwrap_or(0);
+        size.rows += decoration;
+        size.pixel_height += decoration * cell_height;
+    }
+    let gutter = pane.timestamp_gutter_cols();
+    if gutter != 0 {
+        let cell_width = size.pixel_width.checked_div(size.cols).unwrap_or(0);
+        size.cols += gutter;
+        size.pixel_width += gutter * cell_width;
+    }
+    size
+}
+
 fn cell_dimensions(size: &TerminalSize) -> TerminalSize {
     TerminalSize {
         rows: 1,
@@ -520,14 +670,39 @@ impl Tab {
         self.inner.lock().title.clone()
     }
 
+    /// Returns true if the title was set by the user, in which case
+    /// titles set by applications are ignored until it is cleared
+    pub fn has_title_override(&self) -> bool {
+        self.inner.lock().title_is_override
+    }
+
+    /// Sets the title on behalf of the user.
+    /// An empty title removes the override, leaving the title to be
+    /// set by applications and derived by the `format-tab-title` event.
     pub fn set_title(&self, title: &str) {
+        self.set_title_with_override(title, !title.is_empty());
+    }
+
+    /// Sets the title on behalf of an application in the tab, unless
+    /// the user has overridden it
+    pub fn set_title_from_application(&self, title: &str) {
+        if !self.has_title_override() {
+            self.set_title_with_override(title, false);
+        }
+    }
+
+    /// Sets both the title and whether it is an override; used to
+    /// mirror the state of a tab in a remote mux
+    pub fn set_title_with_override(&self, title: &str, user_override: bool) {
         let mut inner = self.inner.lock();
-        if inner.title != title {
+        if inner.title != title || inner.title_is_override != user_override {
             inner.title = title.to_string();
+            inner.title_is_override = user_override;
             Mux::try_get().map(|mux| {
                 mux.notify(MuxNotification::TabTitleChanged {
                     tab_id: inner.id,
                     title: title.to_string(),
+                    user_override,
                 })
             });
         }
@@ -611,6 +786,25 @@ impl Tab {
         self.inner.lock().resize(size)
     }
 
+    /// Returns the number of rows at the top of each pane of the split
+    /// tree that are occupied by decorations rather than by its terminal
+    pub fn pane_decoration_rows(&self) -> usize {
+        self.inner.lock().pane_decoration_rows
+    }
+
+    /// Called by the front end to reserve `rows` at the top of each
+    /// pane of the split tree for its decorations, such as a title bar.
+    /// The panes are resized to make room for, or to reclaim the space
+    /// of, the decorations.
+    pub fn set_pane_decoration_rows(&self, rows: usize) {
+        let mut inner = self.inner.lock();
+        if inner.pane_decoration_rows != rows {
+            inner.pane_decoration_rows = rows;
+            let size = inner.size;
+            inner.resize(size);
+        }
+    }
+
     /// Called when running in the mux server after an individual pane
     /// has been resized.
     /// Because the split manipulation happened on the GUI we "lost"
@@ -751,6 +945,77 @@ impl Tab {
     pub fn get_zoomed_pane(&self) -> Option<Arc<dyn Pane>> {
         self.inner.lock().get_zoomed_pane()
     }
+
+    /// Adds `pane` as a floating pane on top of any others, resizing
+    /// it to fit `geometry`, shows the floating panes and focuses it
+    pub fn add_floating_pane(&self, pane: &Arc<dyn Pane>, geometry: FloatingPaneGeometry) {
+        self.inner.lock().add_floating_pane(pane, geometry)
+    }
+
+    /// Returns the floating panes, from the bottom-most to the top-most,
+    /// along with their positions.  Returns nothing while they are hidden.
+    pub fn iter_floating_panes(&self) -> Vec<PositionedPane> {
+        self.inner.lock().iter_floating_panes()
+    }
+
+    /// Returns all of the floating panes, including hidden ones
+    pub fn floating_panes(&self) -> Vec<Arc<dyn Pane>> {
+        self.inner
+            .lock()
+            .floating
+            .iter()
+            .map(|f| Arc::clone(&f.pane))
+            .collect()
+    }
+
+    pub fn is_floating_pane(&self, pane_id: PaneId) -> bool {
+        self.inner.lock().is_floating_pane(pane_id)
+    }
+
+    /// Hides the floating panes if they are shown, returning focus
+    /// to the split tree, otherwise shows them.  Returns false if the
+    /// tab has no floating panes.
+    pub fn toggle_floating_panes(&self) -> bool {
+        self.inner.lock().toggle_floating_panes()
+    }
+
+    /// Describes the floating panes for the codec
+    pub fn codec_floating_panes(&self) -> Vec<PaneEntry> {
+        self.inner.lock().codec_floating_panes()
+    }
+
+    /// Returns the geometry of each of the floating panes, in the
+    /// same order as codec_floating_panes
+    pub fn floating_pane_geometries(&self) -> Vec<FloatingPaneGeometry> {
+        self.inner
+            .lock()
+            .floating
+            .iter()
+            .map(|f| f.geometry)
+            .collect()
+    }
+
+    /// Replaces the floating panes with `floating`, ordered from the
+    /// bottom-most to the top-most, and focuses `focus`; used to mirror
+    /// the floating panes of a tab in a remote mux
+    pub fn sync_floating_panes(
+        &self,
+        floating: Vec<(Arc<dyn Pane>, FloatingPaneGeometry)>,
+        focus: Option<PaneId>,
+    ) {
+        self.inner.lock().sync_floating_panes(floating, focus)
+    }
+
+    /// Activates the pane at `pos`, as returned by either
+    /// iter_panes or iter_floating_panes
+    pub fn activate_positioned_pane(&self, pos: &PositionedPane) {
+        let mut inner = self.inner.lock();
+        if pos.is_floating {
+            inner.focus_floating_pane(pos.pane.pane_id());
+        } else {
+            inner.set_active_idx(pos.index);
+        }
+    }
 }
 
 impl TabInner {
@@ -763,7 +1028,12 @@ impl TabInner {
             active: 0,
             zoomed: None,
             title: String::new(),
+            title_is_override: false,
             recency: Recency::default(),
+            floating: vec![],
+            floating_hidden: false,
+            floating_focus: None,
+            pane_decoration_rows: 0,
         }
     }
 
@@ -840,7 +1110,7 @@ impl TabInner {
             }
         };
 
-        let active = self.get_active_pane();
+        let active = self.get_active_split_pane();
         let zoomed = self.zoomed.as_ref();
         if let Some(root) = self.pane.as_ref() {
             pane_tree(
@@ -904,7 +1174,8 @@ impl TabInner {
             self.size_before_zoom = size;
             if let Some(pane) = self.get_active_pane() {
                 pane.set_zoomed(true);
-                pane.resize(size).ok();
+                pane.resize(pane_content_size(&*pane, size, self.pane_decoration_rows))
+                    .ok();
                 self.zoomed.replace(pane);
             }
         }
@@ -961,7 +1232,11 @@ impl TabInner {
                 Err(c) => {
                     self.pane.replace(c.tree());
                     let size = self.size;
-                    apply_sizes_from_splits(self.pane.as_mut().unwrap(), &size);
+                    apply_sizes_from_splits(
+                        self.pane.as_mut().unwrap(),
+                        &size,
+                        self.pane_decoration_rows,
+                    );
                     break;
                 }
             }
@@ -992,7 +1267,11 @@ impl TabInner {
                 Err(c) => {
                     self.pane.replace(c.tree());
                     let size = self.size;
-                    apply_sizes_from_splits(self.pane.as_mut().unwrap(), &size);
+                    apply_sizes_from_splits(
+                        self.pane.as_mut().unwrap(),
+                        &size,
+                        self.pane_decoration_rows,
+                    );
                     break;
                 }
             }
@@ -1002,14 +1281,17 @@ impl TabInner {
 
     fn iter_panes_impl(&mut self, respect_zoom_state: bool) -> Vec<PositionedPane> {
         let mut panes = vec![];
+        // While a floating pane has focus, none of the splits are active
+        let float_focused = self.focused_floating_pane().is_some();
 
         if respect_zoom_state {
             if let Some(zoomed) = self.zoomed.as_ref() {
                 let size = self.size;
                 panes.push(PositionedPane {
                     index: 0,
-                    is_active: true,
+                    is_active: !float_focused,
                     is_zoomed: true,
+                    is_floating: false,
                     left: 0,
                     top: 0,
                     width: size.cols.into(),
@@ -1054,8 +1336,9 @@ impl TabInner {
 
                 panes.push(PositionedPane {
                     index,
-                    is_active: index == active_idx,
+                    is_active: index == active_idx && !float_focused,
                     is_zoomed: zoomed_id == Some(pane.pane_id()),
+                    is_floating: false,
                     left,
                     top,
                     width: dims.cols as _,
@@ -1144,10 +1427,17 @@ impl TabInner {
 
         if let Some(zoomed) = &self.zoomed {
             self.size = size;
-            zoomed.resize(size).ok();
+            zoomed
+                .resize(pane_content_size(
+                    &**zoomed,
+                    size,
+                    self.pane_decoration_rows,
+                ))
+                .ok();
         } else {
             let dims = cell_dimensions(&size);
-            let (min_x, min_y) = compute_min_size(self.pane.as_mut().unwrap());
+            let (min_x, min_y) =
+                compute_min_size(self.pane.as_mut().unwrap(), self.pane_decoration_rows);
             let current_size = self.size;
 
             // Constrain the new size to the minimum possible dimensions
@@ -1171,13 +1461,19 @@ impl TabInner {
                 self.pane.as_mut().unwrap(),
                 rows as isize - current_size.rows as isize,
                 &dims,
+                self.pane_decoration_rows,
             );
 
             self.size = size;
 
             // And then resize the individual panes to match
-            apply_sizes_from_splits(self.pane.as_mut().unwrap(), &size);
+            apply_sizes_from_splits(
+                self.pane.as_mut().unwrap(),
+                &size,
+                self.pane_decoration_rows,
+            );
         }
+        self.resize_floating_panes();
 
         Mux::try_get().map(|mux| mux.notify(MuxNotification::TabResized(self.id)));
     }
@@ -1220,7 +1516,7 @@ impl TabInner {
             return;
         }
 
-        fn compute_size(node: &mut Tree) -> Option<TerminalSize> {
+        fn compute_size(node: &mut Tree, decoration_rows: usize) -> Option<TerminalSize> {
             match node {
                 Tree::Empty => None,
                 Tree::Leaf(pane) => {
@@ -1232,14 +1528,14 @@ impl TabInner {
                         pixel_width: dims.pixel_width,
                         dpi: dims.dpi,
                     };
-                    Some(size)
+                    Some(pane_area_size(&**pane, size, decoration_rows))
                 }
                 Tree::Node { left, right, data } => {
                     if let Some(data) = data {
-                        if let Some(first) = compute_size(left) {
+                        if let Some(first) = compute_size(left, decoration_rows) {
                             data.first = first;
                         }
-                        if let Some(second) = compute_size(right) {
+                        if let Some(second) = compute_size(right, decoration_rows) {
                             data.second = second;
                         }
                         Some(data.size())
@@ -1251,7 +1547,7 @@ impl TabInner {
         }
 
         if let Some(root) = self.pane.as_mut() {
-            if let Some(size) = compute_size(root) {
+            if let Some(size) = compute_size(root, self.pane_decoration_rows) {
                 self.size = size;
             }
         }
@@ -1314,11 +1610,14 @@ impl TabInner {
                 SplitDirection::Vertical => {
                     let height = node.height();
 
+                    // Leave room for the terminal beneath the
+                    // decorations of the panes on either side
+                    let min_rows = 1 + self.pane_decoration_rows as isize;
                     let mut rows = node.first.rows as isize;
                     rows = rows
                         .saturating_add(delta)
-                        .max(1)
-                        .min((height as isize).saturating_sub(2));
+                        .max(min_rows)
+                        .min((height as isize).saturating_sub(1 + min_rows));
                     node.first.rows = rows as usize;
                     node.first.pixel_height =
                         node.first.rows.saturating_mul(cell_dimensions.pixel_height);
@@ -1343,6 +1642,7 @@ impl TabInner {
             }
         }
         let root_size = self.size;
+        let decoration_rows = self.pane_decoration_rows;
 
         loop {
             // Figure out the available size by looking at our immediate parent node.
@@ -1359,7 +1659,9 @@ impl TabInner {
 
             if cursor.is_leaf() {
                 // Apply our size to the tty
-                cursor.leaf_mut().map(|pane| pane.resize(pane_size));
+                cursor.leaf_mut().map(|pane| {
+                    pane.resize(pane_content_size(&**pane, pane_size, decoration_rows))
+                });
             } else {
                 self.apply_pane_size(pane_size, &mut cursor);
             }
@@ -1559,7 +1861,10 @@ impl TabInner {
 
     fn prune_dead_panes(&mut self) -> bool {
         let mux = Mux::get();
-        !self
+        let is_dead =
+            |pane: &Arc<dyn Pane>| pane.is_dead() || mux.get_pane(pane.pane_id()).is_none();
+        let pruned_floating = !self.remove_floating_if(is_dead, true).is_empty();
+        let pruned = !self
             .remove_pane_if(
                 |_, pane| {
                     // If the pane is no longer known to the mux, then its liveness
@@ -1578,22 +1883,37 @@ impl TabInner {
                 },
                 true,
             )
-            .is_empty()
+            .is_empty();
+        pruned_floating || pruned
     }
 
     fn kill_pane(&mut self, pane_id: PaneId) -> bool {
+        if self.is_floating_pane(pane_id) {
+            return !self
+                .remove_floating_if(|pane| pane.pane_id() == pane_id, true)
+                .is_empty();
+        }
         !self
             .remove_pane_if(|_, pane| pane.pane_id() == pane_id, true)
             .is_empty()
     }
 
     fn kill_panes_in_domain(&mut self, domain: DomainId) -> bool {
-        !self
+        let killed_floating = !self
+            .remove_floating_if(|pane| pane.domain_id() == domain, true)
+            .is_empty();
+        let killed = !self
             .remove_pane_if(|_, pane| pane.domain_id() == domain, true)
-            .is_empty()
+            .is_empty();
+        killed_floating || killed
     }
 
     fn remove_pane(&mut self, pane_id: PaneId) -> Option<Arc<dyn Pane>> {
+        if self.is_floating_pane(pane_id) {
+            return self
+                .remove_floating_if(|pane| pane.pane_id() == pane_id, false)
+                .pop();
+        }
         let panes = self.remove_pane_if(|_, pane| pane.pane_id() == pane_id, false);
         for pane in panes {
             return Some(pane);
@@ -1666,13 +1986,24 @@ impl TabInner {
                         };
 
                         if let Some(unsplit) = cursor.leaf_mut() {
-                            unsplit.resize(size).ok();
+                            unsplit
+                                .resize(pane_content_size(
+                                    &**unsplit,
+                                    size,
+                                    self.pane_decoration_rows,
+                                ))
+                                .ok();
                         } else {
                             self.apply_pane_size(size, &mut cursor);
                         }
                     } else if !dead_panes.is_empty() {
                         // Apply our revised size to the tty
-                        pane.resize(pane_size).ok();
+                        pane.resize(pane_content_size(
+                            &*pane,
+                            pane_size,
+                            self.pane_decoration_rows,
+                        ))
+                        .ok();
                     }
 
                     pane_index += 1;
@@ -1733,6 +2064,15 @@ impl TabInner {
     }
 
     fn get_active_pane(&mut self) -> Option<Arc<dyn Pane>> {
+        if let Some(float) = self.focused_floating_pane() {
+            return Some(float);
+        }
+        self.get_active_split_pane()
+    }
+
+    /// Returns the active pane of the split tree, ignoring
+    /// any floating pane that has focus
+    fn get_active_split_pane(&mut self) -> Option<Arc<dyn Pane>> {
         if let Some(zoomed) = self.zoomed.as_ref() {
             return Some(Arc::clone(zoomed));
         }
@@ -1748,6 +2088,11 @@ impl TabInner {
     }
 
     fn set_active_pane(&mut self, pane: &Arc<dyn Pane>) {
+        if self.is_floating_pane(pane.pane_id()) {
+            self.focus_floating_pane(pane.pane_id());
+            return;
+        }
+
         if self.zoomed.is_some() {
             if !configuration().unzoom_on_switch_pane {
                 return;
@@ -1761,6 +2106,7 @@ impl TabInner {
             .find(|p| p.pane.pane_id() == pane.pane_id())
         {
             let prior = self.get_active_pane();
+            self.floating_focus.take();
             self.active = item.index;
             self.recency.tag(item.index);
             self.advise_focus_change(prior);
@@ -1791,6 +2137,7 @@ impl TabInner {
 
     fn set_active_idx(&mut self, pane_index: usize) {
         let prior = self.get_active_pane();
+        self.floating_focus.take();
         self.active = pane_index;
         self.recency.tag(pane_index);
         self.advise_focus_change(prior);
@@ -1801,6 +2148,11 @@ impl TabInner {
             Ok(c) => self.pane = Some(c.tree()),
             Err(_) => panic!("tried to assign root pane to non-empty tree"),
         }
+        let size = pane_content_size(&**pane, self.size, self.pane_decoration_rows);
+        if size != self.size {
+            // Make room for the decorations and gutter of the pane
+            pane.resize(size).ok();
+        }
     }
 
     fn cell_dimensions(&self) -> TerminalSize {
@@ -1849,7 +2201,11 @@ impl TabInner {
 
             // Advise the panes of their new sizes
             let size = self.size;
-            apply_sizes_from_splits(self.pane.as_mut().unwrap(), &size);
+            apply_sizes_from_splits(
+                self.pane.as_mut().unwrap(),
+                &size,
+                self.pane_decoration_rows,
+            );
         }
 
         // And update focus
@@ -2052,8 +2408,16 @@ impl TabInner {
                 (pane, existing_pane)
             };
 
-            pane1.resize(split_info.first)?;
-            pane2.resize(split_info.second.clone())?;
+            pane1.resize(pane_content_size(
+                &*pane1,
+                split_info.first,
+                self.pane_decoration_rows,
+            ))?;
+            pane2.resize(pane_content_size(
+                &*pane2,
+                split_info.second.clone(),
+                self.pane_decoration_rows,
+            ))?;
 
             *cursor.leaf_mut().unwrap() = pane1;
 
@@ -2090,6 +2454,209 @@ impl TabInner {
     fn get_zoomed_pane(&self) -> Option<Arc<dyn Pane>> {
         self.zoomed.clone()
     }
+
+    fn is_floating_pane(&self, pane_id: PaneId) -> bool {
+        self.floating.iter().any(|f| f.pane.pane_id() == pane_id)
+    }
+
+    /// Returns the floating pane that has focus, if it is shown
+    fn focused_floating_pane(&self) -> Option<Arc<dyn Pane>> {
+        if self.floating_hidden {
+            return None;
+        }
+        let focus = self.floating_focus?;
+        self.floating
+            .iter()
+            .find(|f| f.pane.pane_id() == focus)
+            .map(|f| Arc::clone(&f.pane))
+    }
+
+    /// Returns the (left, top, size) of a floating pane with `geometry`
+    fn place_floating_pane(&self, geometry: &FloatingPaneGeometry) -> (usize, usize, TerminalSize) {
+        let (left, top, cols, rows) = geometry.place(self.size.cols, self.size.rows);
+        let dims = self.cell_dimensions();
+        let size = TerminalSize {
+            rows,
+            cols,
+            pixel_width: cols * dims.pixel_width,
+            pixel_height: rows * dims.pixel_height,
+            dpi: dims.dpi,
+        };
+        (left, top, size)
+    }
+
+    fn resize_floating_panes(&mut self) {
+        for float in &self.floating {
+            let (_, _, size) = self.place_floating_pane(&float.geometry);
+            float
+                .pane
+                .resize(floating_content_size(&*float.pane, size))
+                .ok();
+        }
+    }
+
+    fn add_floating_pane(&mut self, pane: &Arc<dyn Pane>, geometry: FloatingPaneGeometry) {
+        let (_, _, size) = self.place_floating_pane(&geometry);
+        pane.resize(floating_content_size(&**pane, size)).ok();
+        self.floating.push(FloatingPane {
+            pane: Arc::clone(pane),
+            geometry,
+        });
+        self.focus_floating_pane(pane.pane_id());
+    }
+
+    /// Shows the floating panes and focuses `pane_id`,
+    /// raising it above the other floating panes
+    fn focus_floating_pane(&mut self, pane_id: PaneId) {
+        let idx = match self
+            .floating
+            .iter()
+            .position(|f| f.pane.pane_id() == pane_id)
+        {
+            Some(idx) => idx,
+            None => return,
+        };
+        let prior = self.get_active_pane();
+        let float = self.floating.remove(idx);
+        self.floating.push(float);
+        self.floating_hidden = false;
+        self.floating_focus = Some(pane_id);
+        self.advise_focus_change(prior);
+        Mux::try_get().map(|mux| mux.notify(MuxNotification::TabResized(self.id)));
+    }
+
+    fn iter_floating_panes(&self) -> Vec<PositionedPane> {
+        if self.floating_hidden {
+            return vec![];
+        }
+        self.positioned_floating_panes()
+    }
+
+    /// Returns all of the floating panes, whether or not they are shown,
+    /// from the bottom-most to the top-most
+    fn positioned_floating_panes(&self) -> Vec<PositionedPane> {
+        self.floating
+            .iter()
+            .enumerate()
+            .map(|(index, float)| {
+                let (left, top, size) = self.place_floating_pane(&float.geometry);
+                PositionedPane {
+                    index,
+                    is_active: self.floating_focus == Some(float.pane.pane_id()),
+                    is_zoomed: false,
+                    is_floating: true,
+                    left,
+                    top,
+                    width: size.cols,
+                    height: size.rows,
+                    pixel_width: size.pixel_width,
+                    pixel_height: size.pixel_height,
+                    pane: Arc::clone(&float.pane),
+                }
+            })
+            .collect()
+    }
+
+    fn toggle_floating_panes(&mut self) -> bool {
+        if self.floating.is_empty() {
+            return false;
+        }
+        let prior = self.get_active_pane();
+        self.floating_hidden = !self.floating_hidden;
+        if !self.floating_hidden {
+            // Showing them focuses the top-most one
+            self.floating_focus = self.floating.last().map(|f| f.pane.pane_id());
+        }
+        self.advise_focus_change(prior);
+        Mux::try_get().map(|mux| mux.notify(MuxNotification::TabResized(self.id)));
+        true
+    }
+
+    fn sync_floating_panes(
+        &mut self,
+        floating: Vec<(Arc<dyn Pane>, FloatingPaneGeometry)>,
+        focus: Option<PaneId>,
+    ) {
+        self.floating = floating
+            .into_iter()
+            .map(|(pane, geometry)| FloatingPane { pane, geometry })
+            .collect();
+        self.floating_focus = focus.filter(|focus| self.is_floating_pane(*focus));
+        if self.floating_focus.is_some() || self.floating.is_empty() {
+            self.floating_hidden = false;
+        }
+    }
+
+    /// Removes the floating panes for which `f` returns true.  If the
+    /// focused one is removed, focus returns to the split tree.
+    fn remove_floating_if<F>(&mut self, f: F, kill: bool) -> Vec<Arc<dyn Pane>>
+    where
+        F: Fn(&Arc<dyn Pane>) -> bool,
+    {
+        let prior = self.get_active_pane();
+        let mut removed = vec![];
+        self.floating.retain(|float| {
+            if f(&float.pane) {
+                removed.push(Arc::clone(&float.pane));
+                false
+            } else {
+                true
+            }
+        });
+        if removed.is_empty() {
+            return removed;
+        }
+
+        if removed
+            .iter()
+            .any(|pane| Some(pane.pane_id()) == self.floating_focus)
+        {
+            self.floating_focus.take();
+        }
+        self.advise_focus_change(prior);
+
+        if kill {
+            let to_kill: Vec<_> = removed.iter().map(|p| p.pane_id()).collect();
+            promise::spawn::spawn_into_main_thread(async move {
+                let mux = Mux::get();
+                for pane_id in to_kill.into_iter() {
+                    mux.remove_pane(pane_id);
+                }
+            })
+            .detach();
+        }
+        removed
+    }
+
+    fn codec_floating_panes(&mut self) -> Vec<PaneEntry> {
+        let mux = Mux::get();
+        let window_id = match mux.window_containing_tab(self.id) {
+            Some(w) => w,
+            None => return vec![],
+        };
+        let workspace = match mux.get_window(window_id) {
+            Some(window) => window.get_workspace().to_string(),
+            None => return vec![],
+        };
+        let active = self.get_active_pane();
+        self.positioned_floating_panes()
+            .into_iter()
+            .map(|pos| {
+                let mut entry = pane_entry(
+                    &pos.pane,
+                    self.id,
+                    window_id,
+                    active.as_ref(),
+                    None,
+                    &workspace,
+                    pos.left,
+                    pos.top,
+                );
+                entry.is_floating = true;
+                entry
+            })
+            .collect()
+    }
 }
 
 /// This type is used directly by the codec, take care to bump
@@ -2150,12 +2717,17 @@ pub struct PaneEntry {
     pub working_dir: Option<SerdeUrl>,
     pub is_active_pane: bool,
     pub is_zoomed_pane: bool,
+    /// true if the pane floats above the split tree of its tab
+    pub is_floating: bool,
     pub workspace: String,
     pub cursor_pos: StableCursorPosition,
     pub physical_top: StableRowIndex,
     pub top_row: usize,
     pub left_col: usize,
     pub tty_name: Option<String>,
+    pub links: Vec<PaneLink>,
+    /// The nesting depth of the instance that hosts the pane
+    pub nesting_depth: usize,
 }
 
 #[derive(Deserialize, Clone, Serialize, PartialEq, Debug)]
@@ -2311,6 +2883,36 @@ mod test {
         }
     }
 
+    #[test]
+    fn title_override() {
+        let tab = Tab::new(&TerminalSize::default());
+        tab.set_title_from_application("vim");
+        assert_eq!(tab.get_title(), "vim");
+        assert!(!tab.has_title_override());
+
+        // Titles from applications don't replace the user's title
+        tab.set_title("mine");
+        assert!(tab.has_title_override());
+        tab.set_title_from_application("bash");
+        assert_eq!(tab.get_title(), "mine");
+
+        // Clearing the override lets them through again
+        tab.set_title("");
+        assert!(!tab.has_title_override());
+        tab.set_title_from_application("bash");
+        assert_eq!(tab.get_title(), "bash");
+        assert!(!tab.has_title_override());
+
+        // Mirroring or restoring a tab takes the state as given
+        tab.set_title_with_override("remote", true);
+        assert_eq!(tab.get_title(), "remote");
+        assert!(tab.has_title_override());
+        tab.set_title_with_override("remote", false);
+        assert!(!tab.has_title_override());
+        tab.set_title_from_application("top");
+        assert_eq!(tab.get_title(), "top");
+    }
+
     #[test]
     fn tab_splitting() {
         let size = TerminalSize {
@@ -2512,6 +3114,146 @@ mod test {
         assert_eq!(600, panes[2].pixel_height);
     }
 
+    #[test]
+    fn pane_decoration_rows() {
+        let size = TerminalSize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+            dpi: 96,
+        };
+        let rows_of = |pane: &Arc<FakePane>| {
+            let size = *pane.size.lock();
+            (size.rows, size.pixel_height)
+        };
+
+        let tab = Tab::new(&size);
+        assert_eq!(tab.pane_decoration_rows(), 0);
+        let first = Arc::new(FakePane {
+            id: 1,
+            size: Mutex::new(size),
+        });
+        tab.assign_pane(&(Arc::clone(&first) as Arc<dyn Pane>));
+        assert_eq!(rows_of(&first), (24, 600));
+
+        // The terminal of the pane makes room for its decorations,
+        // but the pane keeps its place in the tab
+        tab.set_pane_decoration_rows(1);
+        assert_eq!(rows_of(&first), (23, 575));
+        let panes = tab.iter_panes();
+        assert_eq!((panes[0].top, panes[0].height), (0, 24));
+
+        let request = SplitRequest {
+            direction: SplitDirection::Vertical,
+            ..Default::default()
+        };
+        let split = tab.compute_split_size(0, request).unwrap();
+        let second = Arc::new(FakePane {
+            id: 2,
+            size: Mutex::new(split.second),
+        });
+        tab.split_and_insert(0, request, Arc::clone(&second) as Arc<dyn Pane>)
+            .unwrap();
+        assert_eq!(rows_of(&first), (10, 250));
+        assert_eq!(rows_of(&second), (11, 275));
+
+        // Each pane keeps a row for its terminal below its decorations
+        tab.resize(TerminalSize {
+            rows: 2,
+            pixel_height: 50,
+            ..size
+        });
+        assert_eq!(tab.get_size().rows, 5);
+        assert_eq!(rows_of(&first), (1, 25));
+        assert_eq!(rows_of(&second), (1, 25));
+
+        tab.resize(size);
+        tab.set_pane_decoration_rows(0);
+        assert_eq!(tab.pane_decoration_rows(), 0);
+        let panes = tab.iter_panes();
+        assert_eq!(rows_of(&first), (panes[0].height, panes[0].pixel_height));
+        assert_eq!(rows_of(&second), (panes[1].height, panes[1].pixel_height));
+        assert_eq!(panes[0].height + panes[1].height + 1, 24);
+
+        // Without decorations, panes can shrink to a single row
+        tab.resize(TerminalSize {
+            rows: 2,
+            pixel_height: 50,
+            ..size
+        });
+        assert_eq!(tab.get_size().rows, 3);
+    }
+
+    #[test]
+    fn floating_pane_geometry() {
+        let centered = FloatingPaneGeometry::default();
+        assert_eq!(centered.place(80, 24), (16, 5, 48, 14));
+
+        let corner = FloatingPaneGeometry {
+            width: SplitSize::Cells(20),
+            height: SplitSize::Cells(5),
+            position: FloatingPanePosition::BottomRight,
+        };
+        assert_eq!(corner.place(80, 24), (59, 18, 20, 5));
+
+        // Too large to fit: a margin is kept for the border
+        let full = FloatingPaneGeometry {
+            width: SplitSize::Percent(100),
+            height: SplitSize::Cells(100),
+            position: FloatingPanePosition::TopLeft,
+        };
+        assert_eq!(full.place(80, 24), (1, 1, 78, 22));
+    }
+
+    #[test]
+    fn sync_floating_panes() {
+        let size = TerminalSize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+            dpi: 96,
+        };
+        let tab = Tab::new(&size);
+        let geometry = FloatingPaneGeometry::default();
+        let corner = FloatingPaneGeometry {
+            width: SplitSize::Cells(20),
+            height: SplitSize::Cells(5),
+            position: FloatingPanePosition::BottomRight,
+        };
+        let bottom = FakePane::new(1, size);
+        let top = FakePane::new(2, size);
+
+        tab.sync_floating_panes(
+            vec![(Arc::clone(&bottom), geometry), (Arc::clone(&top), corner)],
+            Some(1),
+        );
+        let floating = tab.iter_floating_panes();
+        assert_eq!(
+            floating
+                .iter()
+                .map(|p| p.pane.pane_id())
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert!(floating[0].is_active);
+        assert!(!floating[1].is_active);
+        assert_eq!((floating[1].left, floating[1].top), (59, 18));
+        assert_eq!(tab.get_active_pane().map(|p| p.pane_id()), Some(1));
+        assert_eq!(tab.floating_pane_geometries(), vec![geometry, corner]);
+
+        // A focus that isn't one of the floating panes is ignored
+        tab.sync_floating_panes(vec![(Arc::clone(&top), corner)], Some(1));
+        assert!(!tab.is_floating_pane(1));
+        assert!(tab.is_floating_pane(2));
+        assert!(tab.iter_floating_panes().iter().all(|p| !p.is_active));
+        assert!(tab.get_active_pane().is_none());
+
+        tab.sync_floating_panes(vec![], None);
+        assert!(tab.floating_panes().is_empty());
+    }
+
     fn is_send_and_sync<T: Send + Sync>() -> bool {
         true
     }
@@ -0,0 +1,194 @@
+//! Helps with the most common cause of broken colors and key handling
+//! over ssh: the remote host not having the wezterm terminfo entry.
+//! When an ssh session is established, the remote host is probed for
+//! the entry, and depending on `terminfo_assist`, the user is offered
+//! the choice of installing it from the copy embedded in wezterm, or
+//! of using xterm-256color for that host instead.
+//! The choice is remembered per host.
+use crate::pane::PaneId;
+use anyhow::Context;
+use config::TerminfoAssist;
+use parking_lot::Mutex;
+use portable_pty::Child;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use wezterm_ssh::{ConfigMap, Session};
+
+const WEZTERM_TERMINFO: &str = include_str!("../../termwiz/data/wezterm.terminfo");
+
+/// The TERM that is used in place of wezterm on hosts that lack its
+/// terminfo entry
+pub const FALLBACK_TERM: &str = "xterm-256color";
+
+pub const TERMINFO_PROMPT: &str =
+    "Enter [i]nstall it, use [x]term-256color, [n]ever ask for this host, or press enter to skip> ";
+
+/// What to do about a host that doesn't have the wezterm terminfo entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HostChoice {
+    Install,
+    UseFallback,
+    Ignore,
+}
+
+pub fn choices_file_name() -> PathBuf {
+    config::DATA_DIR.join("terminfo-assist.json")
+}
+
+fn parse_choices(data: &[u8]) -> BTreeMap<String, HostChoice> {
+    serde_json::from_slice(data).unwrap_or_default()
+}
+
+fn load_choices() -> BTreeMap<String, HostChoice> {
+    match std::fs::read(choices_file_name()) {
+        Ok(data) => parse_choices(&data),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+/// Returns the choice that was previously made for `host`
+pub fn remembered_choice(host: &str) -> Option<HostChoice> {
+    load_choices().get(host).copied()
+}
+
+pub fn remember_choice(host: &str, choice: HostChoice) -> anyhow::Result<()> {
+    config::statefile::update(&choices_file_name(), |data| {
+        let mut choices = data.map(|data| parse_choices(&data)).unwrap_or_default();
+        choices.insert(host.to_string(), choice);
+        Ok(serde_json::to_vec_pretty(&choices)?)
+    })
+}
+
+/// Identifies the remote host of an ssh session, for the purpose
+/// of remembering the choice that was made for it
+pub fn host_key(ssh_config: &ConfigMap) -> String {
+    let host = ssh_config
+        .get("hostname")
+        .map(|s| s.as_str())
+        .unwrap_or("localhost");
+    let mut key = match ssh_config.get("user") {
+        Some(user) => format!("{user}@{host}"),
+        None => host.to_string(),
+    };
+    if let Some(port) = ssh_config.get("port") {
+        if port != "22" {
+            key.push_str(&format!(":{port}"));
+        }
+    }
+    key
+}
+
+lazy_static::lazy_static! {
+    static ref TOASTED_PANES: Mutex<HashSet<PaneId>> = Mutex::new(HashSet::new());
+}
+
+/// Returns true the first time that it is called for `pane_id`, so
+/// that a pane that prompts again, such as when its session is
+/// re-established, doesn't raise the toast every time
+pub fn should_toast(pane_id: PaneId) -> bool {
+    TOASTED_PANES.lock().insert(pane_id)
+}
+
+pub fn parse_answer(line: &str) -> Option<HostChoice> {
+    match line.trim().to_lowercase().as_str() {
+        "i" | "install" => Some(HostChoice::Install),
+        "x" | "xterm" | "xterm-256color" => Some(HostChoice::UseFallback),
+        "n" | "never" => Some(HostChoice::Ignore),
+        _ => None,
+    }
+}
+
+/// Returns the TERM to use for `host`, taking into account the choice
+/// that was previously made for it
+pub fn term_for_host(term: &str, host: &str) -> String {
+    if term == "wezterm"
+        && config::configuration().terminfo_assist != TerminfoAssist::Off
+        && remembered_choice(host) == Some(HostChoice::UseFallback)
+    {
+        FALLBACK_TERM.to_string()
+    } else {
+        term.to_string()
+    }
+}
+
+/// Runs `command_line` on the remote host, passing it `input` on its
+/// stdin, and returns its output if it succeeded
+fn run(session: &Session, command_line: &str, input: &str) -> anyhow::Result<String> {
+    let mut exec = smol::block_on(session.exec(command_line, None))
+        .with_context(|| format!("running `{command_line}`"))?;
+    exec.stdin
+        .write_all(input.as_bytes())
+        .context("writing to stdin")?;
+    drop(exec.stdin);
+
+    let mut output = String::new();
+    exec.stdout
+        .read_to_string(&mut output)
+        .context("reading stdout")?;
+    let status = exec.child.wait()?;
+    anyhow::ensure!(
+        status.success(),
+        "`{command_line}` failed: {}",
+        output.trim()
+    );
+    Ok(output)
+}
+
+/// Returns true if the terminfo entry for `term` is present on the
+/// remote host
+pub fn probe(session: &Session, term: &str) -> anyhow::Result<bool> {
+    let output = run(
+        session,
+        &format!(
+            "infocmp {} >/dev/null 2>&1 && echo present || echo missing",
+            shell_words::quote(term)
+        ),
+        "",
+    )?;
+    Ok(output.trim() == "present")
+}
+
+/// Compiles the embedded wezterm terminfo into the terminfo
+/// directory of the remote user
+pub fn install(session: &Session) -> anyhow::Result<()> {
+    run(
+        session,
+        "tempfile=$(mktemp) && cat > \"$tempfile\" && \
+         tic -x -o \"$HOME/.terminfo\" \"$tempfile\" 2>&1; \
+         rc=$?; rm -f \"$tempfile\"; exit $rc",
+        WEZTERM_TERMINFO,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn answers_and_keys() {
+        assert_eq!(parse_answer(" I\n"), Some(HostChoice::Install));
+        assert_eq!(parse_answer("x"), Some(HostChoice::UseFallback));
+        assert_eq!(parse_answer("never"), Some(HostChoice::Ignore));
+        assert_eq!(parse_answer(""), None);
+
+        let mut ssh_config = ConfigMap::new();
+        ssh_config.insert("hostname".to_string(), "example.com".to_string());
+        ssh_config.insert("port".to_string(), "22".to_string());
+        assert_eq!(host_key(&ssh_config), "example.com");
+        ssh_config.insert("user".to_string(), "me".to_string());
+        ssh_config.insert("port".to_string(), "2222".to_string());
+        assert_eq!(host_key(&ssh_config), "me@example.com:2222");
+    }
+
+    #[test]
+    fn toast_once_per_pane() {
+        let pane_id = usize::MAX - 1;
+        assert!(should_toast(pane_id));
+        assert!(!should_toast(pane_id));
+        assert!(should_toast(pane_id - 1));
+        assert!(!should_toast(pane_id - 1));
+    }
+}
@@ -68,6 +68,9 @@ impl Domain for TermWizTerminalDomain {
     fn domain_name(&self) -> &str {
         "TermWizTerminalDomain"
     }
+    fn domain_type(&self) -> &'static str {
+        "termwiz"
+    }
     async fn attach(&self, _window_id: Option<WindowId>) -> anyhow::Result<()> {
         Ok(())
     }
@@ -1,9 +1,10 @@
 use crate::domain::{alloc_domain_id, Domain, DomainId, DomainState};
 use crate::pane::{Pane, PaneId};
 use crate::tab::TabId;
-use crate::tmux_commands::{ListAllPanes, TmuxCommand};
+use crate::tmux_commands::{ListAllPanes, SetBuffer, ShowBuffer, TmuxCommand};
 use crate::{Mux, MuxWindowBuilder};
 use async_trait::async_trait;
+use config::{configuration, TmuxClipboardSync};
 use filedescriptor::FileDescriptor;
 use parking_lot::{Condvar, Mutex};
 use portable_pty::CommandBuilder;
@@ -13,6 +14,62 @@ use std::sync::Arc;
 use termwiz::tmux_cc::*;
 use wezterm_term::TerminalSize;
 
+/// How many paste buffers are remembered; tmux keeps 50 by default
+const MAX_KNOWN_BUFFERS: usize = 64;
+
+/// Tracks the content of the tmux paste buffers by name, so that the
+/// clipboard is synced per buffer: a change to a buffer is only placed
+/// on the local clipboard when its content differs from what is known
+/// for that buffer, and wasn't stored there by wezterm itself
+#[derive(Debug, Default)]
+pub(crate) struct TmuxPasteBuffers {
+    /// Text that was sent with `set-buffer`, for which tmux has not
+    /// yet announced the buffer that holds it
+    pending: VecDeque<String>,
+    /// The known content of each buffer, least recently changed first
+    known: VecDeque<(String, String)>,
+}
+
+impl TmuxPasteBuffers {
+    /// Records that `text` is being stored in a new paste buffer
+    pub fn set(&mut self, text: &str) {
+        self.pending.push_back(text.to_string());
+        if self.pending.len() > MAX_KNOWN_BUFFERS {
+            self.pending.pop_front();
+        }
+    }
+
+    /// Records `text` as the content of the buffer `name`, which tmux
+    /// announced as changed.  Returns the text to place on the local
+    /// clipboard, if any.
+    pub fn changed(&mut self, name: &str, text: String) -> Option<String> {
+        let ours = match self.pending.iter().position(|pending| *pending == text) {
+            Some(idx) => {
+                self.pending.remove(idx);
+                true
+            }
+            None => false,
+        };
+        let previous = self
+            .known
+            .iter()
+            .position(|(known, _)| known == name)
+            .and_then(|idx| self.known.remove(idx));
+        let unchanged = matches!(&previous, Some((_, previous)) if *previous == text);
+
+        self.known.push_back((name.to_string(), text.clone()));
+        if self.known.len() > MAX_KNOWN_BUFFERS {
+            self.known.pop_front();
+        }
+
+        if ours || unchanged {
+            None
+        } else {
+            Some(text)
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 enum State {
     WaitForInitialGuard,
@@ -60,6 +117,10 @@ pub(crate) struct TmuxDomainState {
     pub gui_tabs: Mutex<Vec<TmuxTab>>,
     pub remote_panes: Mutex<HashMap<TmuxPaneId, RefTmuxRemotePane>>,
     pub tmux_session: Mutex<Option<TmuxSessionId>>,
+    /// The content of the tmux paste buffers, so that buffer change
+    /// notifications don't bounce our own copies back to the local
+    /// clipboard
+    pub paste_buffers: Mutex<TmuxPasteBuffers>,
 }
 
 pub struct TmuxDomain {
@@ -105,6 +166,13 @@ impl TmuxDomainState {
                 Event::WindowAdd { window: _ } => {
                     self.create_gui_window();
                 }
+                Event::PasteBufferChanged { name } => {
+                    if configuration().sync_tmux_clipboard == TmuxClipboardSync::Bidirectional {
+                        self.cmd_queue
+                            .lock()
+                            .push_back(Box::new(ShowBuffer { name: name.clone() }));
+                    }
+                }
                 Event::SessionChanged { session, name: _ } => {
                     *self.tmux_session.lock() = Some(*session);
                     log::info!("tmux session changed:{}", session);
@@ -162,6 +230,15 @@ impl TmuxDomainState {
         .detach();
     }
 
+    /// Replace the content of the tmux paste buffer with `text`
+    fn set_paste_buffer(&self, text: &str) {
+        self.paste_buffers.lock().set(text);
+        self.cmd_queue.lock().push_back(Box::new(SetBuffer {
+            text: text.to_string(),
+        }));
+        TmuxDomainState::schedule_send_next_command(self.domain_id);
+    }
+
     /// create a standalone window for tmux tabs
     pub fn create_gui_window(&self) {
         if self.gui_window.lock().is_none() {
@@ -194,6 +271,7 @@ impl TmuxDomain {
             gui_tabs: Mutex::new(Vec::default()),
             remote_panes: Mutex::new(HashMap::default()),
             tmux_session: Mutex::new(None),
+            paste_buffers: Mutex::new(TmuxPasteBuffers::default()),
         });
 
         Self { inner }
@@ -223,6 +301,10 @@ impl Domain for TmuxDomain {
         "tmux"
     }
 
+    fn domain_type(&self) -> &'static str {
+        "tmux"
+    }
+
     async fn attach(&self, _window_id: Option<crate::WindowId>) -> anyhow::Result<()> {
         Ok(())
     }
@@ -238,4 +320,45 @@ impl Domain for TmuxDomain {
     fn state(&self) -> DomainState {
         DomainState::Attached
     }
+
+    fn text_copied_from_pane(&self, _pane_id: PaneId, text: &str) {
+        if configuration().sync_tmux_clipboard != TmuxClipboardSync::Disabled {
+            self.inner.set_paste_buffer(text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn paste_buffer_sync() {
+        let mut buffers = TmuxPasteBuffers::default();
+
+        // Changes made in tmux are placed on the clipboard
+        assert_eq!(
+            buffers.changed("buffer0", "yanked".to_string()),
+            Some("yanked".to_string())
+        );
+        // but announcing the same content again doesn't repeat that
+        assert_eq!(buffers.changed("buffer0", "yanked".to_string()), None);
+
+        // Text that we set isn't bounced back, whichever buffer
+        // tmux puts it in
+        buffers.set("copied");
+        buffers.set("copied again");
+        assert_eq!(buffers.changed("buffer2", "copied again".to_string()), None);
+        assert_eq!(buffers.changed("buffer1", "copied".to_string()), None);
+
+        // Each buffer is tracked separately
+        assert_eq!(
+            buffers.changed("buffer1", "yanked".to_string()),
+            Some("yanked".to_string())
+        );
+        assert_eq!(
+            buffers.changed("buffer0", "edited".to_string()),
+            Some("edited".to_string())
+        );
+    }
 }
@@ -4,7 +4,7 @@ use crate::pane::alloc_pane_id;
 use crate::tab::{Tab, TabId};
 use crate::tmux::{TmuxDomain, TmuxDomainState, TmuxRemotePane, TmuxTab};
 use crate::tmux_pty::{TmuxChild, TmuxPty};
-use crate::{Mux, Pane};
+use crate::{Mux, MuxNotification, Pane};
 use anyhow::{anyhow, Context};
 use parking_lot::{Condvar, Mutex};
 use portable_pty::{MasterPty, PtySize};
@@ -13,7 +13,7 @@ use std::fmt::{Debug, Write};
 use std::io::Write as _;
 use std::sync::Arc;
 use termwiz::tmux_cc::*;
-use wezterm_term::TerminalSize;
+use wezterm_term::{ClipboardSelection, TerminalSize};
 
 pub(crate) trait TmuxCommand: Send + Debug {
     fn get_command(&self) -> String;
@@ -343,3 +343,136 @@ impl TmuxCommand for SendKeys {
         Ok(())
     }
 }
+
+/// Quote `s` as a double quoted tmux command argument.
+/// Any byte that isn't printable ASCII is written as an octal
+/// escape, so that arbitrary content, including newlines and
+/// control characters, survives the line based control channel.
+fn quote_tmux_arg(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for &byte in s.as_bytes() {
+        match byte {
+            b'"' | b'\\' | b'$' => {
+                quoted.push('\\');
+                quoted.push(byte as char);
+            }
+            0x20..=0x7e => quoted.push(byte as char),
+            _ => write!(&mut quoted, "\\{:03o}", byte).expect("unable to write byte"),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[derive(Debug)]
+pub(crate) struct SetBuffer {
+    pub text: String,
+}
+impl TmuxCommand for SetBuffer {
+    fn get_command(&self) -> String {
+        format!("set-buffer -- {}\n", quote_tmux_arg(&self.text))
+    }
+
+    fn process_result(&self, domain_id: DomainId, result: &Guarded) -> anyhow::Result<()> {
+        if result.error {
+            log::error!(
+                "Error setting paste buffer: domain_id={} result={:?}",
+                domain_id,
+                result
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Returns the content of a paste buffer from the output of show-buffer
+fn buffer_text(output: &str) -> String {
+    // Guarded output always has a trailing '\n'
+    output.strip_suffix('\n').unwrap_or(output).to_string()
+}
+
+#[derive(Debug)]
+pub(crate) struct ShowBuffer {
+    pub name: String,
+}
+impl TmuxCommand for ShowBuffer {
+    fn get_command(&self) -> String {
+        format!("show-buffer -b {}\n", quote_tmux_arg(&self.name))
+    }
+
+    fn process_result(&self, domain_id: DomainId, result: &Guarded) -> anyhow::Result<()> {
+        if result.error {
+            log::error!(
+                "Error fetching paste buffer {}: domain_id={} result={:?}",
+                self.name,
+                domain_id,
+                result
+            );
+            return Ok(());
+        }
+
+        let mux = Mux::get();
+        let domain = match mux.get_domain(domain_id) {
+            Some(d) => d,
+            None => anyhow::bail!("Tmux domain lost"),
+        };
+        let tmux_domain = match domain.downcast_ref::<TmuxDomain>() {
+            Some(t) => t,
+            None => anyhow::bail!("Tmux domain lost"),
+        };
+
+        // The content is sent as-is, rather than escaped as the
+        // output of panes is
+        let text = buffer_text(&result.output);
+        let changed = tmux_domain
+            .inner
+            .paste_buffers
+            .lock()
+            .changed(&self.name, text);
+
+        if let Some(text) = changed {
+            mux.notify(MuxNotification::AssignClipboard {
+                pane_id: tmux_domain.inner.pane_id,
+                selection: ClipboardSelection::Clipboard,
+                clipboard: Some(text),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quote_arg() {
+        assert_eq!(quote_tmux_arg("hello"), "\"hello\"");
+        assert_eq!(
+            quote_tmux_arg("say \"$HOME\"\\\n"),
+            "\"say \\\"\\$HOME\\\"\\\\\\012\""
+        );
+        assert_eq!(quote_tmux_arg("caf\u{e9}\t"), "\"caf\\303\\251\\011\"");
+    }
+
+    #[test]
+    fn set_buffer() {
+        let cmd = SetBuffer {
+            text: "two\nlines".to_string(),
+        };
+        assert_eq!(cmd.get_command(), "set-buffer -- \"two\\012lines\"\n");
+    }
+
+    #[test]
+    fn show_buffer() {
+        let cmd = ShowBuffer {
+            name: "buffer0".to_string(),
+        };
+        assert_eq!(cmd.get_command(), "show-buffer -b \"buffer0\"\n");
+
+        // The content is not unescaped, so backslashes survive
+        assert_eq!(buffer_text("C:\\Users\\me\n"), "C:\\Users\\me");
+        assert_eq!(buffer_text("two\nlines\n"), "two\nlines");
+    }
+}
@@ -1,11 +1,22 @@
 use crate::pane::CloseReason;
 use crate::{Mux, MuxNotification, Tab, TabId};
 use config::GuiPosition;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 static WIN_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
 pub type WindowId = usize;
 
+/// The placement of the gui window that shows a mux window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    /// The screen coordinates of the top left of the client area,
+    /// if the windowing system reports them
+    pub position: Option<(isize, isize)>,
+    /// The width and height of the client area in pixels
+    pub pixel_size: Option<(usize, usize)>,
+}
+
 pub struct Window {
     id: WindowId,
     tabs: Vec<Arc<Tab>>,
@@ -13,7 +24,15 @@ pub struct Window {
     last_active: Option<TabId>,
     workspace: String,
     title: String,
+    /// true if `title` was set by the user rather than by an
+    /// application in the window
+    title_is_override: bool,
     initial_position: Option<GuiPosition>,
+    /// As most recently reported by the gui, or as requested
+    /// for a window that doesn't have a gui window yet
+    geometry: WindowGeometry,
+    /// The profile whose overrides apply to the whole window
+    profile: Option<String>,
 }
 
 impl Window {
@@ -24,8 +43,11 @@ impl Window {
             active: 0,
             last_active: None,
             title: String::new(),
+            title_is_override: false,
             workspace: workspace.unwrap_or_else(|| Mux::get().active_workspace()),
             initial_position,
+            geometry: WindowGeometry::default(),
+            profile: None,
         }
     }
 
@@ -33,22 +55,84 @@ impl Window {
         &self.initial_position
     }
 
+    pub fn get_geometry(&self) -> WindowGeometry {
+        self.geometry
+    }
+
+    /// Sets the placement of the gui window; the gui reports it here
+    /// as the window is moved and resized
+    pub fn set_geometry(&mut self, geometry: WindowGeometry) {
+        if self.geometry != geometry {
+            self.geometry = geometry;
+            if let Some(mux) = Mux::try_get() {
+                mux.notify(MuxNotification::WindowGeometryChanged(self.id));
+            }
+        }
+    }
+
+    pub fn set_gui_position(&mut self, x: isize, y: isize) {
+        self.set_geometry(WindowGeometry {
+            position: Some((x, y)),
+            ..self.geometry
+        });
+    }
+
+    pub fn set_gui_pixel_size(&mut self, pixel_width: usize, pixel_height: usize) {
+        self.set_geometry(WindowGeometry {
+            pixel_size: Some((pixel_width, pixel_height)),
+            ..self.geometry
+        });
+    }
+
+    pub fn get_profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    pub fn set_profile(&mut self, profile: Option<String>) {
+        self.profile = profile;
+    }
+
     pub fn get_workspace(&self) -> &str {
         &self.workspace
     }
 
+    /// Sets the title on behalf of the user.
+    /// An empty title removes the override, leaving the title to be
+    /// set by applications.
     pub fn set_title(&mut self, title: &str) {
-        if self.title != title {
+        self.set_title_with_override(title, !title.is_empty());
+    }
+
+    /// Sets the title on behalf of an application in the window,
+    /// unless the user has overridden it
+    pub fn set_title_from_application(&mut self, title: &str) {
+        if !self.title_is_override {
+            self.set_title_with_override(title, false);
+        }
+    }
+
+    /// Sets both the title and whether it is an override; used to
+    /// mirror the state of a window in a remote mux
+    pub fn set_title_with_override(&mut self, title: &str, user_override: bool) {
+        if self.title != title || self.title_is_override != user_override {
             self.title = title.to_string();
+            self.title_is_override = user_override;
             Mux::try_get().map(|mux| {
                 mux.notify(MuxNotification::WindowTitleChanged {
                     window_id: self.id,
                     title: title.to_string(),
+                    user_override,
                 })
             });
         }
     }
 
+    /// Returns true if the title was set by the user, in which case
+    /// titles set by applications are ignored until it is cleared
+    pub fn has_title_override(&self) -> bool {
+        self.title_is_override
+    }
+
     pub fn get_title(&self) -> &str {
         &self.title
     }
@@ -266,3 +350,38 @@ impl Window {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn title_override() {
+        let mut window = Window::new(Some("default".to_string()), None);
+        window.set_title_from_application("vim");
+        assert_eq!(window.get_title(), "vim");
+        assert!(!window.has_title_override());
+
+        // Titles from applications don't replace the user's title
+        window.set_title("mine");
+        assert!(window.has_title_override());
+        window.set_title_from_application("bash");
+        assert_eq!(window.get_title(), "mine");
+
+        // Clearing the override lets them through again
+        window.set_title("");
+        assert!(!window.has_title_override());
+        window.set_title_from_application("bash");
+        assert_eq!(window.get_title(), "bash");
+        assert!(!window.has_title_override());
+
+        // Mirroring or restoring a window takes the state as given
+        window.set_title_with_override("remote", true);
+        assert_eq!(window.get_title(), "remote");
+        assert!(window.has_title_override());
+        window.set_title_with_override("remote", false);
+        assert!(!window.has_title_override());
+        window.set_title_from_application("top");
+        assert_eq!(window.get_title(), "top");
+    }
+}
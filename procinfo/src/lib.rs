@@ -96,4 +96,25 @@ impl LocalProcessInfo {
     pub fn executable_path(_pid: u32) -> Option<PathBuf> {
         None
     }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    pub fn environment(_pid: u32) -> Option<HashMap<String, String>> {
+        None
+    }
+}
+
+/// Parses `NAME=value` entries, as found in the environment
+/// block of a process, into a map
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn parse_environment<'a>(entries: impl Iterator<Item = &'a [u8]>) -> HashMap<String, String> {
+    entries
+        .filter_map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (name, value) = entry.split_once('=')?;
+            if name.is_empty() {
+                return None;
+            }
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
 }
@@ -28,6 +28,14 @@ impl LocalProcessInfo {
         std::fs::read_link(format!("/proc/{}/exe", pid)).ok()
     }
 
+    /// Returns the environment of the process, if it is accessible
+    /// to us.  This is the environment that the process was started
+    /// with; changes that it has made since are not visible.
+    pub fn environment(pid: u32) -> Option<HashMap<String, String>> {
+        let data = std::fs::read(format!("/proc/{}/environ", pid)).ok()?;
+        Some(parse_environ(&data))
+    }
+
     pub fn with_root_pid(pid: u32) -> Option<Self> {
         use libc::pid_t;
 
@@ -137,3 +145,40 @@ impl LocalProcessInfo {
         }
     }
 }
+
+/// Parses the contents of `/proc/<pid>/environ`, which holds
+/// NUL terminated `NAME=value` entries
+fn parse_environ(data: &[u8]) -> HashMap<String, String> {
+    parse_environment(data.split(|&c| c == 0).filter(|entry| !entry.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_environ;
+
+    #[test]
+    fn environ() {
+        let env = parse_environ(b"HOME=/home/me\0EMPTY=\0EQ=a=b\0\0=nameless\0BARE\0LAST=1");
+        let mut entries: Vec<_> = env
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        entries.sort();
+        assert_eq!(
+            entries,
+            vec![
+                ("EMPTY", ""),
+                ("EQ", "a=b"),
+                ("HOME", "/home/me"),
+                ("LAST", "1")
+            ]
+        );
+    }
+
+    #[test]
+    fn environ_invalid_utf8() {
+        let env = parse_environ(b"LANG=C\0BAD=\xff\xfe\0");
+        assert_eq!(env.get("LANG").map(String::as_str), Some("C"));
+        assert_eq!(env.get("BAD").map(String::as_str), Some("\u{fffd}\u{fffd}"));
+    }
+}
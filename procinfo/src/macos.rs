@@ -65,6 +65,13 @@ impl LocalProcessInfo {
         Some(OsString::from_vec(buffer).into())
     }
 
+    /// Returns the environment of the process, if it is accessible
+    /// to us.  This is the environment that the process was started
+    /// with; changes that it has made since are not visible.
+    pub fn environment(pid: u32) -> Option<HashMap<String, String>> {
+        parse_env_sysctl(procargs_for_pid(pid as _)?)
+    }
+
     pub fn with_root_pid(pid: u32) -> Option<Self> {
         /// Enumerate all current process identifiers
         fn all_pids() -> Vec<libc::pid_t> {
@@ -130,31 +137,7 @@ impl LocalProcessInfo {
         }
 
         fn exe_and_args_for_pid_sysctl(pid: libc::pid_t) -> Option<(PathBuf, Vec<String>)> {
-            use libc::c_int;
-            let mut size = 64 * 1024;
-            let mut buf: Vec<u8> = Vec::with_capacity(size);
-            let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid as c_int];
-
-            let res = unsafe {
-                libc::sysctl(
-                    mib.as_mut_ptr(),
-                    mib.len() as _,
-                    buf.as_mut_ptr() as *mut _,
-                    &mut size,
-                    std::ptr::null_mut(),
-                    0,
-                )
-            };
-            if res == -1 {
-                return None;
-            }
-            if size < (std::mem::size_of::<c_int>() * 2) {
-                // Not big enough
-                return None;
-            }
-            unsafe { buf.set_len(size) };
-
-            parse_exe_and_argv_sysctl(buf)
+            parse_exe_and_argv_sysctl(procargs_for_pid(pid)?)
         }
 
         fn exe_for_pid(pid: libc::pid_t) -> PathBuf {
@@ -199,6 +182,78 @@ impl LocalProcessInfo {
     }
 }
 
+/// Returns the KERN_PROCARGS2 data for the process
+fn procargs_for_pid(pid: libc::pid_t) -> Option<Vec<u8>> {
+    use libc::c_int;
+    let mut size = 64 * 1024;
+    let mut buf: Vec<u8> = Vec::with_capacity(size);
+    let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid as c_int];
+
+    let res = unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            mib.len() as _,
+            buf.as_mut_ptr() as *mut _,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if res == -1 {
+        return None;
+    }
+    if size < (std::mem::size_of::<c_int>() * 2) {
+        // Not big enough
+        return None;
+    }
+    unsafe { buf.set_len(size) };
+    Some(buf)
+}
+
+fn consume_cstr(ptr: &mut &[u8]) -> Option<String> {
+    // Parse to the end of a null terminated string
+    let nul = ptr.iter().position(|&c| c == 0)?;
+    let s = String::from_utf8_lossy(&ptr[0..nul]).to_owned().to_string();
+    *ptr = ptr.get(nul + 1..)?;
+
+    // Find the position of the first non null byte. `.position()`
+    // will return None if we run off the end.
+    if let Some(not_nul) = ptr.iter().position(|&c| c != 0) {
+        // If there are no trailing nulls, not_nul will be 0
+        // and this call will be a noop
+        *ptr = ptr.get(not_nul..)?;
+    }
+
+    Some(s)
+}
+
+/// Parses the environment that follows the argv in KERN_PROCARGS2 data
+fn parse_env_sysctl(buf: Vec<u8>) -> Option<HashMap<String, String>> {
+    use libc::c_int;
+
+    let mut ptr = &buf[0..buf.len()];
+
+    let argc: c_int = unsafe { std::ptr::read(ptr.as_ptr() as *const c_int) };
+    ptr = &ptr[std::mem::size_of::<c_int>()..];
+
+    // Skip the exe_path and argv
+    for _ in 0..=argc {
+        consume_cstr(&mut ptr)?;
+    }
+
+    // The environment ends with an empty string
+    let mut entries = vec![];
+    while let Some(nul) = ptr.iter().position(|&c| c == 0) {
+        if nul == 0 {
+            break;
+        }
+        entries.push(&ptr[0..nul]);
+        ptr = &ptr[nul + 1..];
+    }
+
+    Some(parse_environment(entries.into_iter()))
+}
+
 fn parse_exe_and_argv_sysctl(buf: Vec<u8>) -> Option<(PathBuf, Vec<String>)> {
     use libc::c_int;
 
@@ -217,23 +272,6 @@ fn parse_exe_and_argv_sysctl(buf: Vec<u8>) -> Option<(PathBuf, Vec<String>)> {
     let argc: c_int = unsafe { std::ptr::read(ptr.as_ptr() as *const c_int) };
     ptr = &ptr[std::mem::size_of::<c_int>()..];
 
-    fn consume_cstr(ptr: &mut &[u8]) -> Option<String> {
-        // Parse to the end of a null terminated string
-        let nul = ptr.iter().position(|&c| c == 0)?;
-        let s = String::from_utf8_lossy(&ptr[0..nul]).to_owned().to_string();
-        *ptr = ptr.get(nul + 1..)?;
-
-        // Find the position of the first non null byte. `.position()`
-        // will return None if we run off the end.
-        if let Some(not_nul) = ptr.iter().position(|&c| c != 0) {
-            // If there are no trailing nulls, not_nul will be 0
-            // and this call will be a noop
-            *ptr = ptr.get(not_nul..)?;
-        }
-
-        Some(s)
-    }
-
     let exe_path = consume_cstr(&mut ptr)?.into();
 
     let mut args = vec![];
@@ -248,7 +286,7 @@ fn parse_exe_and_argv_sysctl(buf: Vec<u8>) -> Option<(PathBuf, Vec<String>)> {
 mod tests {
     use std::path::Path;
 
-    use super::parse_exe_and_argv_sysctl;
+    use super::{parse_env_sysctl, parse_exe_and_argv_sysctl};
 
     #[test]
     fn test_trailing_zeros() {
@@ -309,6 +347,21 @@ mod tests {
         assert_eq!(argv, vec!["sleep".to_string(), "5".to_string()]);
     }
 
+    #[test]
+    fn test_environment() {
+        // 'sleep 5' with A=1 and B=x=y in its environment
+        let mut buf = vec![
+            2, 0, 0, 0, 47, 98, 105, 110, 47, 115, 108, 101, 101, 112, 0, 0, 0, 115, 108, 101, 101,
+            112, 0, 53, 0,
+        ];
+        buf.extend_from_slice(b"A=1\0B=x=y\0\0ptr_munge=\0");
+
+        let env = parse_env_sysctl(buf).unwrap();
+        assert_eq!(env.len(), 2);
+        assert_eq!(env["A"], "1");
+        assert_eq!(env["B"], "x=y");
+    }
+
     #[test]
     fn test_malformed() {
         // Example data generated from running 'sleep 5' on the commit author's local machine,
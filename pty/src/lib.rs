@@ -122,6 +122,12 @@ pub trait MasterPty: Downcast + Send {
     fn get_termios(&self) -> Option<nix::sys::termios::Termios> {
         None
     }
+
+    /// Prevent the writer from sending EOF to the slave end when it
+    /// is dropped.  This is used when the pty has been handed off to
+    /// another process that continues to service it.
+    #[cfg(unix)]
+    fn disable_eof_on_drop(&self) {}
 }
 impl_downcast!(MasterPty);
 
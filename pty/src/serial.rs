@@ -5,6 +5,10 @@
 //! `openpty` method.
 //! On most (all?) systems, attempting to open multiple instances of
 //! the same serial port will fail.
+//! When reconnection is enabled, the loss of the device (for example,
+//! when a USB serial adapter is unplugged) doesn't terminate the
+//! connection; instead the port is periodically re-opened with the
+//! same settings until it reappears, or until the "child" is killed.
 use crate::{
     Child, ChildKiller, CommandBuilder, ExitStatus, MasterPty, PtyPair, PtySize, PtySystem,
     SlavePty,
@@ -19,10 +23,10 @@ use std::ffi::{OsStr, OsString};
 use std::io::{Read, Result as IoResult, Write};
 #[cfg(unix)]
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
 use std::time::Duration;
 
-type Handle = Arc<Mutex<SystemPort>>;
+type Handle = Arc<SharedPort>;
 
 pub struct SerialTty {
     port: OsString,
@@ -31,6 +35,82 @@ pub struct SerialTty {
     parity: Parity,
     stop_bits: StopBits,
     flow_control: FlowControl,
+    reconnect_interval: Option<Duration>,
+}
+
+/// The state shared between the various pty and child
+/// objects that reference the same serial port
+struct SharedPort {
+    name: OsString,
+    settings: PortSettings,
+    /// If set, how often to try to re-open the port
+    /// after it has gone away
+    reconnect_interval: Option<Duration>,
+    /// None while the device is disconnected
+    port: Mutex<Option<SystemPort>>,
+    /// Set when the connection has been explicitly terminated
+    disconnected: Mutex<bool>,
+    disconnected_cond: Condvar,
+}
+
+impl SharedPort {
+    fn open(name: &OsStr, settings: &PortSettings) -> anyhow::Result<SystemPort> {
+        let mut port =
+            serial::open(name).with_context(|| format!("openpty on serial port {:?}", name))?;
+
+        log::debug!("serial settings: {:#?}", settings);
+        port.configure(settings)?;
+
+        // The timeout needs to be rather short because, at least on Windows,
+        // a read with a long timeout will block a concurrent write from
+        // happening.  In wezterm we tend to have a thread looping on read
+        // while writes happen occasionally from the gui thread, and if we
+        // make this timeout too long we can block the gui thread.
+        port.set_timeout(Duration::from_millis(50))?;
+
+        Ok(port)
+    }
+
+    /// Attempts to re-open the port after it has gone away.
+    fn reopen(&self) -> anyhow::Result<()> {
+        let port = Self::open(&self.name, &self.settings)?;
+        self.port.lock().unwrap().replace(port);
+        Ok(())
+    }
+
+    /// Marks the port as having gone away
+    fn lost(&self) {
+        self.port.lock().unwrap().take();
+    }
+
+    fn is_disconnected(&self) -> bool {
+        *self.disconnected.lock().unwrap()
+    }
+
+    /// Explicitly terminates the connection, stopping any
+    /// attempts to reconnect
+    fn disconnect(&self) {
+        *self.disconnected.lock().unwrap() = true;
+        self.disconnected_cond.notify_all();
+    }
+
+    /// Waits for up to `duration` for the connection to be
+    /// explicitly terminated.  Returns true if it was terminated.
+    fn wait_for_disconnect(&self, duration: Duration) -> bool {
+        let disconnected = self.disconnected.lock().unwrap();
+        let (disconnected, _) = self
+            .disconnected_cond
+            .wait_timeout_while(disconnected, duration, |disconnected| !*disconnected)
+            .unwrap();
+        *disconnected
+    }
+
+    fn dup_fd(&self) -> anyhow::Result<FileDescriptor> {
+        match self.port.lock().unwrap().as_ref() {
+            Some(port) => Ok(FileDescriptor::dup(port)?),
+            None => anyhow::bail!("serial port {:?} is not connected", self.name),
+        }
+    }
 }
 
 impl SerialTty {
@@ -42,9 +122,16 @@ impl SerialTty {
             parity: Parity::ParityNone,
             stop_bits: StopBits::Stop1,
             flow_control: FlowControl::FlowSoftware,
+            reconnect_interval: None,
         }
     }
 
+    /// When set, the port is re-opened every `interval` after it
+    /// goes away, rather than terminating the connection
+    pub fn set_reconnect_interval(&mut self, interval: Option<Duration>) {
+        self.reconnect_interval = interval;
+    }
+
     pub fn set_baud_rate(&mut self, baud: BaudRate) {
         self.baud = baud;
     }
@@ -68,9 +155,6 @@ impl SerialTty {
 
 impl PtySystem for SerialTty {
     fn openpty(&self, _size: PtySize) -> anyhow::Result<PtyPair> {
-        let mut port = serial::open(&self.port)
-            .with_context(|| format!("openpty on serial port {:?}", self.port))?;
-
         let settings = PortSettings {
             baud_rate: self.baud,
             char_size: self.char_size,
@@ -78,17 +162,16 @@ impl PtySystem for SerialTty {
             stop_bits: self.stop_bits,
             flow_control: self.flow_control,
         };
-        log::debug!("serial settings: {:#?}", settings);
-        port.configure(&settings)?;
-
-        // The timeout needs to be rather short because, at least on Windows,
-        // a read with a long timeout will block a concurrent write from
-        // happening.  In wezterm we tend to have a thread looping on read
-        // while writes happen occasionally from the gui thread, and if we
-        // make this timeout too long we can block the gui thread.
-        port.set_timeout(Duration::from_millis(50))?;
+        let port = SharedPort::open(&self.port, &settings)?;
 
-        let port: Handle = Arc::new(Mutex::new(port));
+        let port: Handle = Arc::new(SharedPort {
+            name: self.port.clone(),
+            settings,
+            reconnect_interval: self.reconnect_interval,
+            port: Mutex::new(Some(port)),
+            disconnected: Mutex::new(false),
+            disconnected_cond: Condvar::new(),
+        });
 
         Ok(PtyPair {
             slave: Box::new(Slave {
@@ -146,13 +229,22 @@ impl Child for SerialChild {
         // the CD value itself and just look for an error state.
         // We could potentially also decide to call CD==false the
         // same thing as the "child" completing.
+        // When reconnection is enabled, losing the device doesn't
+        // complete the "child"; only an explicit disconnect does.
         loop {
-            std::thread::sleep(Duration::from_secs(5));
+            if self.port.wait_for_disconnect(Duration::from_secs(5)) {
+                return Ok(ExitStatus::with_exit_code(0));
+            }
 
-            let mut port = self.port.lock().unwrap();
-            if let Err(err) = port.read_cd() {
-                log::error!("Error reading carrier detect: {:#}", err);
-                return Ok(ExitStatus::with_exit_code(1));
+            let mut port = self.port.port.lock().unwrap();
+            if let Some(p) = port.as_mut() {
+                if let Err(err) = p.read_cd() {
+                    log::error!("Error reading carrier detect: {:#}", err);
+                    if self.port.reconnect_interval.is_none() {
+                        return Ok(ExitStatus::with_exit_code(1));
+                    }
+                    port.take();
+                }
             }
         }
     }
@@ -169,24 +261,40 @@ impl Child for SerialChild {
 
 impl ChildKiller for SerialChild {
     fn kill(&mut self) -> IoResult<()> {
+        self.port.disconnect();
         Ok(())
     }
 
     fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
-        Box::new(SerialChildKiller)
+        Box::new(SerialChildKiller {
+            port: Arc::clone(&self.port),
+        })
     }
 }
 
-#[derive(Debug)]
-struct SerialChildKiller;
+/// Killing the "child" terminates the connection,
+/// which stops any attempts to reconnect
+struct SerialChildKiller {
+    port: Handle,
+}
+
+// An anemic impl of Debug to satisfy some indirect trait bounds
+impl std::fmt::Debug for SerialChildKiller {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        fmt.debug_struct("SerialChildKiller").finish()
+    }
+}
 
 impl ChildKiller for SerialChildKiller {
     fn kill(&mut self) -> IoResult<()> {
+        self.port.disconnect();
         Ok(())
     }
 
     fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
-        Box::new(SerialChildKiller)
+        Box::new(SerialChildKiller {
+            port: Arc::clone(&self.port),
+        })
     }
 }
 
@@ -201,11 +309,22 @@ struct MasterWriter {
 
 impl Write for MasterWriter {
     fn write(&mut self, buf: &[u8]) -> Result<usize, std::io::Error> {
-        self.port.lock().unwrap().write(buf)
+        match self.port.port.lock().unwrap().as_mut() {
+            Some(port) => port.write(buf),
+            // Input is discarded while we wait for the device to reappear
+            None if self.port.reconnect_interval.is_some() => Ok(buf.len()),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "serial port is not connected",
+            )),
+        }
     }
 
     fn flush(&mut self) -> Result<(), std::io::Error> {
-        self.port.lock().unwrap().flush()
+        match self.port.port.lock().unwrap().as_mut() {
+            Some(port) => port.flush(),
+            None => Ok(()),
+        }
     }
 }
 
@@ -224,8 +343,13 @@ impl MasterPty for Master {
         // We rely on the fact that SystemPort implements the traits
         // that expose the underlying file descriptor, and that direct
         // reads from that return the raw data that we want
-        let fd = FileDescriptor::dup(&*self.port.lock().unwrap())?;
-        Ok(Box::new(Reader { fd }))
+        let fd = self.port.dup_fd()?;
+        Ok(Box::new(Reader {
+            fd,
+            port: Arc::clone(&self.port),
+            pending: vec![],
+            lost: None,
+        }))
     }
 
     fn take_writer(&self) -> anyhow::Result<Box<dyn std::io::Write + Send>> {
@@ -256,10 +380,90 @@ impl MasterPty for Master {
 
 struct Reader {
     fd: FileDescriptor,
+    port: Handle,
+    /// Status text that is yet to be returned to the caller
+    pending: Vec<u8>,
+    /// Set when the port has gone away and we are
+    /// yet to wait for it to reappear
+    lost: Option<std::io::Error>,
+}
+
+impl Reader {
+    /// Handles the loss of the port when reading.
+    /// The status is returned as data so that it is displayed in
+    /// the terminal, and the reconnection is deferred to the
+    /// subsequent read.
+    fn disconnected(
+        &mut self,
+        err: std::io::Error,
+        buf: &mut [u8],
+    ) -> Result<usize, std::io::Error> {
+        if self.port.reconnect_interval.is_none() || self.port.is_disconnected() {
+            return Err(err);
+        }
+        log::warn!(
+            "serial port {:?}: {}; waiting for it to reappear",
+            self.port.name,
+            err
+        );
+        self.port.lost();
+        self.pending = self.status_line("waiting for");
+        self.lost.replace(err);
+        Ok(self.take_pending(buf))
+    }
+
+    fn status_line(&self, status: &str) -> Vec<u8> {
+        format!(
+            "\r\n\x1b[2m── {status} {} ──\x1b[0m\r\n",
+            self.port.name.to_string_lossy()
+        )
+        .into_bytes()
+    }
+
+    fn take_pending(&mut self, buf: &mut [u8]) -> usize {
+        let len = buf.len().min(self.pending.len());
+        buf[..len].copy_from_slice(&self.pending[..len]);
+        self.pending.drain(..len);
+        len
+    }
+
+    /// Waits until the port can be re-opened, or until the connection
+    /// is explicitly terminated, in which case err is returned.
+    fn reconnect(&mut self, err: std::io::Error) -> Result<(), std::io::Error> {
+        let interval = match self.port.reconnect_interval {
+            Some(interval) => interval,
+            None => return Err(err),
+        };
+
+        loop {
+            if self.port.wait_for_disconnect(interval) {
+                return Err(err);
+            }
+            match self.port.reopen().and_then(|_| self.port.dup_fd()) {
+                Ok(fd) => {
+                    log::info!("serial port {:?}: reconnected", self.port.name);
+                    self.fd = fd;
+                    self.pending = self.status_line("reconnected to");
+                    return Ok(());
+                }
+                Err(err) => {
+                    log::trace!("serial port {:?}: {:#}", self.port.name, err);
+                }
+            }
+        }
+    }
 }
 
 impl Read for Reader {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        if !self.pending.is_empty() {
+            return Ok(self.take_pending(buf));
+        }
+        if let Some(err) = self.lost.take() {
+            self.reconnect(err)?;
+            return Ok(self.take_pending(buf));
+        }
+
         // On windows, this self.fd.read will block for up to the time we set
         // as the timeout when we set up the port, but on unix it will
         // never block.
@@ -285,10 +489,11 @@ impl Read for Reader {
                         // loop and try again.
                         continue;
                     }
-                    return Err(std::io::Error::new(
+                    let err = std::io::Error::new(
                         std::io::ErrorKind::UnexpectedEof,
                         "EOF on serial port",
-                    ));
+                    );
+                    return self.disconnected(err, buf);
                 }
                 Ok(size) => {
                     return Ok(size);
@@ -298,7 +503,7 @@ impl Read for Reader {
                         continue;
                     }
                     log::error!("serial read error: {}", e);
-                    return Err(e);
+                    return self.disconnected(e, buf);
                 }
             }
         }
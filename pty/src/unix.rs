@@ -12,6 +12,8 @@ use std::os::unix::ffi::OsStrExt;
 use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::os::unix::process::CommandExt;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::{io, mem, ptr};
 
 pub use std::os::unix::io::RawFd;
@@ -52,6 +54,7 @@ fn openpty(size: PtySize) -> anyhow::Result<(UnixMasterPty, UnixSlavePty)> {
         fd: PtyFd(unsafe { FileDescriptor::from_raw_fd(master) }),
         took_writer: RefCell::new(false),
         tty_name,
+        eof_on_drop: Arc::new(AtomicBool::new(true)),
     };
     let slave = UnixSlavePty {
         fd: PtyFd(unsafe { FileDescriptor::from_raw_fd(slave) }),
@@ -67,6 +70,22 @@ fn openpty(size: PtySize) -> anyhow::Result<(UnixMasterPty, UnixSlavePty)> {
     Ok((master, slave))
 }
 
+/// Wraps an already open pty master, such as one that was received from
+/// another process, so that it can be used as a `MasterPty`.
+/// The descriptor is set to close-on-exec.
+pub fn master_pty_from_fd(
+    fd: FileDescriptor,
+    tty_name: Option<PathBuf>,
+) -> Result<Box<dyn MasterPty>, Error> {
+    cloexec(fd.as_raw_fd())?;
+    Ok(Box::new(UnixMasterPty {
+        fd: PtyFd(fd),
+        took_writer: RefCell::new(false),
+        tty_name,
+        eof_on_drop: Arc::new(AtomicBool::new(true)),
+    }))
+}
+
 impl PtySystem for UnixPtySystem {
     fn openpty(&self, size: PtySize) -> anyhow::Result<PtyPair> {
         let (master, slave) = openpty(size)?;
@@ -304,6 +323,9 @@ struct UnixMasterPty {
     fd: PtyFd,
     took_writer: RefCell<bool>,
     tty_name: Option<PathBuf>,
+    /// Shared with the writer; controls whether dropping the writer
+    /// sends EOF to the slave
+    eof_on_drop: Arc<AtomicBool>,
 }
 
 /// Represents the slave end of a pty.
@@ -360,7 +382,10 @@ impl MasterPty for UnixMasterPty {
         }
         *self.took_writer.borrow_mut() = true;
         let fd = PtyFd(self.fd.try_clone()?);
-        Ok(Box::new(UnixMasterWriter { fd }))
+        Ok(Box::new(UnixMasterWriter {
+            fd,
+            eof_on_drop: Arc::clone(&self.eof_on_drop),
+        }))
     }
 
     fn as_raw_fd(&self) -> Option<RawFd> {
@@ -381,6 +406,10 @@ impl MasterPty for UnixMasterPty {
     fn get_termios(&self) -> Option<nix::sys::termios::Termios> {
         nix::sys::termios::tcgetattr(self.fd.0.as_fd()).ok()
     }
+
+    fn disable_eof_on_drop(&self) {
+        self.eof_on_drop.store(false, Ordering::SeqCst);
+    }
 }
 
 /// Represents the master end of a pty.
@@ -388,10 +417,14 @@ impl MasterPty for UnixMasterPty {
 /// the Pty is dropped.
 struct UnixMasterWriter {
     fd: PtyFd,
+    eof_on_drop: Arc<AtomicBool>,
 }
 
 impl Drop for UnixMasterWriter {
     fn drop(&mut self) {
+        if !self.eof_on_drop.load(Ordering::SeqCst) {
+            return;
+        }
         let mut t: libc::termios = unsafe { std::mem::MaybeUninit::zeroed().assume_init() };
         if unsafe { libc::tcgetattr(self.fd.0.as_raw_fd(), &mut t) } == 0 {
             // EOF is only interpreted after a newline, so if it is set,
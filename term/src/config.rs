@@ -200,6 +200,13 @@ pub trait TerminalConfiguration: Downcast + std::fmt::Debug + Send + Sync {
         false
     }
 
+    /// The initial state of DEC private mode 2027; when it is enabled,
+    /// the width of text is measured by grapheme cluster rather than
+    /// by codepoint
+    fn grapheme_clustering(&self) -> bool {
+        true
+    }
+
     fn debug_key_events(&self) -> bool {
         false
     }
@@ -222,6 +229,12 @@ pub trait TerminalConfiguration: Downcast + std::fmt::Debug + Send + Sync {
     fn log_unknown_escape_sequences(&self) -> bool {
         false
     }
+
+    /// Whether the iTerm2 proprietary `SetColors` and
+    /// `RequestAttention` directives are honored
+    fn accept_iterm2_osc(&self) -> bool {
+        true
+    }
 }
 impl_downcast!(TerminalConfiguration);
 
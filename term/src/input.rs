@@ -45,6 +45,62 @@ pub struct MouseEvent {
     pub modifiers: KeyModifiers,
 }
 
+/// A shape for the mouse pointer that an application may request
+/// using OSC 22.  Only these shapes are honored.
+#[cfg_attr(feature = "use_serde", derive(Deserialize, Serialize))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MousePointerShape {
+    Default,
+    Text,
+    Pointer,
+    Crosshair,
+    Wait,
+    Progress,
+    Help,
+    NotAllowed,
+    Move,
+    Grab,
+    Grabbing,
+    EwResize,
+    NsResize,
+    NeswResize,
+    NwseResize,
+}
+
+impl MousePointerShape {
+    /// Resolves a shape from its CSS name, or from the name of
+    /// the equivalent X11 cursor font glyph, as used by xterm
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "default" | "left_ptr" | "top_left_arrow" | "arrow" => Self::Default,
+            "text" | "xterm" | "ibeam" => Self::Text,
+            "pointer" | "hand" | "hand2" | "pointing_hand" => Self::Pointer,
+            "crosshair" | "cross" | "tcross" => Self::Crosshair,
+            "wait" | "watch" => Self::Wait,
+            "progress" | "left_ptr_watch" => Self::Progress,
+            "help" | "question_arrow" => Self::Help,
+            "not-allowed" | "crossed_circle" | "forbidden" => Self::NotAllowed,
+            "move" | "all-scroll" | "fleur" => Self::Move,
+            "grab" | "openhand" | "hand1" => Self::Grab,
+            "grabbing" | "closedhand" => Self::Grabbing,
+            "ew-resize" | "col-resize" | "e-resize" | "w-resize" | "sb_h_double_arrow" => {
+                Self::EwResize
+            }
+            "ns-resize" | "row-resize" | "n-resize" | "s-resize" | "sb_v_double_arrow" => {
+                Self::NsResize
+            }
+            "nesw-resize" | "ne-resize" | "sw-resize" | "top_right_corner"
+            | "bottom_left_corner" => Self::NeswResize,
+            "nwse-resize"
+            | "nw-resize"
+            | "se-resize"
+            | "top_left_corner"
+            | "bottom_right_corner" => Self::NwseResize,
+            _ => return None,
+        })
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct ClickPosition {
     pub column: usize,
@@ -55,9 +111,9 @@ pub struct ClickPosition {
 
 /// This is a little helper that keeps track of the "click streak",
 /// which is the number of successive clicks of the same mouse button
-/// within the `CLICK_INTERVAL`.  The streak is reset to 1 each time
+/// within the click interval.  The streak is reset to 1 each time
 /// the mouse button differs from the last click, or when the elapsed
-/// time exceeds `CLICK_INTERVAL`, or when the cursor position
+/// time exceeds the click interval, or when the cursor position
 /// changes to a different character cell.
 #[derive(Debug, Clone)]
 pub struct LastMouseClick {
@@ -67,8 +123,9 @@ pub struct LastMouseClick {
     pub streak: usize,
 }
 
-/// The multi-click interval, measured in milliseconds
-const CLICK_INTERVAL: u64 = 500;
+/// The multi-click interval, measured in milliseconds, that is used
+/// when the system doesn't specify one
+pub const CLICK_INTERVAL: u64 = 500;
 
 impl LastMouseClick {
     pub fn new(button: MouseButton, position: ClickPosition) -> Self {
@@ -81,10 +138,21 @@ impl LastMouseClick {
     }
 
     pub fn add(&self, button: MouseButton, position: ClickPosition) -> Self {
+        self.add_within(button, position, Duration::from_millis(CLICK_INTERVAL))
+    }
+
+    /// Like `add`, but continues the streak only if the click
+    /// happened within `interval` of the prior click
+    pub fn add_within(
+        &self,
+        button: MouseButton,
+        position: ClickPosition,
+        interval: Duration,
+    ) -> Self {
         let now = Instant::now();
         let streak = if button == self.button
             && position == self.position
-            && now.duration_since(self.time) <= Duration::from_millis(CLICK_INTERVAL)
+            && now.duration_since(self.time) <= interval
         {
             self.streak + 1
         } else {
@@ -121,6 +121,76 @@ pub struct SemanticZone {
     pub end_y: StableRowIndex,
     pub end_x: usize,
     pub semantic_type: SemanticType,
+    /// The completion status of the command that this zone is a part
+    /// of, as reported via OSC 133;D.  None if the command is still
+    /// running, or if the shell doesn't report command completion.
+    #[dynamic(default)]
+    pub command_status: Option<CommandStatus>,
+}
+
+#[cfg_attr(feature = "use_serde", derive(Deserialize, Serialize))]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, FromDynamic, ToDynamic)]
+pub enum CommandStatus {
+    /// The command completed, but the shell didn't report its exit status
+    Unknown,
+    Exited(i32),
+}
+
+impl CommandStatus {
+    pub fn from_exit_status(status: Option<i32>) -> Self {
+        match status {
+            Some(status) => Self::Exited(status),
+            None => Self::Unknown,
+        }
+    }
+
+    pub fn failed(&self) -> bool {
+        matches!(self, Self::Exited(status) if *status != 0)
+    }
+}
+
+/// A command that was run in the terminal, as delimited by the
+/// semantic zones that the shell reported via OSC 133
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CommandRecord {
+    /// The zone holding the command line that was entered
+    pub input: SemanticZone,
+    /// The zone holding the output of the command, if it produced any
+    pub output: Option<SemanticZone>,
+    /// The completion status of the command; None if it is still running
+    pub status: Option<CommandStatus>,
+}
+
+impl CommandRecord {
+    /// Groups zones, in the order returned by `get_semantic_zones`,
+    /// into the commands that they belong to.  A prompt that hasn't
+    /// had a command entered yet doesn't produce a record.
+    pub fn from_zones(zones: &[SemanticZone]) -> Vec<Self> {
+        let mut records: Vec<Self> = vec![];
+        let mut in_command = false;
+        for zone in zones {
+            match zone.semantic_type {
+                SemanticType::Prompt => in_command = false,
+                SemanticType::Input => {
+                    records.push(Self {
+                        input: *zone,
+                        output: None,
+                        status: zone.command_status,
+                    });
+                    in_command = true;
+                }
+                SemanticType::Output => {
+                    if let Some(record) = records.last_mut().filter(|_| in_command) {
+                        if record.output.is_none() {
+                            record.output.replace(*zone);
+                            record.status = record.status.or(zone.command_status);
+                        }
+                    }
+                }
+            }
+        }
+        records
+    }
 }
 
 pub mod color;
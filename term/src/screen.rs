@@ -357,11 +357,51 @@ impl Screen {
     }
 
     /// Returns a copy of the lines in the screen (including scrollback)
-    #[cfg(test)]
     pub fn all_lines(&self) -> Vec<Line> {
         self.lines.iter().map(|l| l.clone()).collect()
     }
 
+    /// Replaces the content of the screen, including scrollback, with
+    /// the provided lines.  The last `physical_rows` lines become the
+    /// visible portion of the screen; if there are fewer lines than
+    /// that, blank lines are added at the bottom.
+    pub(crate) fn restore_lines(&mut self, lines: Vec<Line>, seqno: SequenceNo) {
+        let capacity = self.physical_rows + self.scrollback_size();
+        let skip = lines.len().saturating_sub(capacity);
+
+        self.lines.clear();
+        for mut line in lines.into_iter().skip(skip) {
+            line.update_last_change_seqno(seqno);
+            self.lines.push_back(line);
+        }
+        while self.lines.len() < self.physical_rows {
+            self.lines.push_back(Line::new(seqno));
+        }
+    }
+
+    /// Inserts the provided lines above the existing content of the
+    /// screen, making them the oldest part of the scrollback.  If there
+    /// isn't room for all of them, the oldest of the provided lines are
+    /// discarded.  Returns the number of lines that were inserted.
+    pub(crate) fn prepend_lines(&mut self, lines: Vec<Line>, seqno: SequenceNo) -> usize {
+        let capacity = self.physical_rows + self.scrollback_size();
+        let room = capacity.saturating_sub(self.lines.len());
+        let skip = lines.len().saturating_sub(room);
+        let count = lines.len() - skip;
+
+        // The existing lines are about to move to different stable
+        // indices, so make sure that they are considered to be changed
+        for line in self.lines.iter_mut() {
+            line.update_last_change_seqno(seqno);
+        }
+        for mut line in lines.into_iter().skip(skip).rev() {
+            line.compress_for_scrollback();
+            line.update_last_change_seqno(seqno);
+            self.lines.push_front(line);
+        }
+        count
+    }
+
     pub fn insert_cell(
         &mut self,
         x: usize,
@@ -419,6 +459,40 @@ impl Screen {
         line.set_cell_grapheme(x, text, width, attr, seqno);
     }
 
+    /// Records `secs` as the arrival time of the line at `y`, if it
+    /// doesn't already have one.
+    /// Arrival times are stored sparsely: the line is left alone when
+    /// the nearest line above it on the screen that has an arrival time
+    /// has the same one, as the line shares it.
+    pub fn mark_arrival(&mut self, y: VisibleRowIndex, secs: u32) {
+        let line_idx = self.phys_row(y);
+        if self.lines[line_idx].arrival_time().is_some() {
+            return;
+        }
+        let top = self.phys_row(0);
+        let shared = (top..line_idx)
+            .rev()
+            .find_map(|idx| self.lines[idx].arrival_time());
+        if shared == Some(secs) {
+            return;
+        }
+
+        // The line below shares the arrival time in effect for this
+        // line; it keeps it by recording it for itself
+        let next_shares = self
+            .lines
+            .get(line_idx + 1)
+            .map(|next| next.arrival_time().is_none())
+            .unwrap_or(false);
+        if next_shares {
+            let inherited = (0..line_idx)
+                .rev()
+                .find_map(|idx| self.lines[idx].arrival_time());
+            self.line_mut(line_idx + 1).set_arrival_time(inherited);
+        }
+        self.line_mut(line_idx).set_arrival_time(Some(secs));
+    }
+
     pub fn cell_mut(&mut self, x: usize, y: VisibleRowIndex) -> Option<&mut Cell> {
         let line_idx = self.phys_row(y);
         let line = self.lines.get_mut(line_idx)?;
@@ -1,6 +1,7 @@
 use super::*;
 use crate::terminalstate::performer::Performer;
 use std::sync::Arc;
+use termwiz::escape::osc::Progress;
 use termwiz::escape::parser::Parser;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -32,6 +33,42 @@ pub trait DeviceControlHandler: Send + Sync {
     fn handle_device_control(&mut self, _control: termwiz::escape::DeviceControlMode);
 }
 
+/// A button that an application asked to have shown on its
+/// notification; see `Alert::ToastNotification`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub struct NotificationAction {
+    /// Reported back to the application when the button is clicked
+    pub id: String,
+    pub label: String,
+}
+
+impl NotificationAction {
+    /// Returns the sequence that reports a click on the button `id`
+    /// back to the application
+    pub fn reply(id: &str) -> String {
+        format!("\x1b]777;notify-reply;{}\x1b\\", id)
+    }
+}
+
+/// Reports a click on the notification button `id` by writing its
+/// reply to `writer`, which is `None` when the pane that raised the
+/// notification has since gone away.
+/// Returns whether the reply was sent.
+pub fn send_notification_reply<W: std::io::Write + ?Sized>(
+    writer: Option<&mut W>,
+    id: &str,
+) -> std::io::Result<bool> {
+    match writer {
+        Some(writer) => {
+            writer.write_all(NotificationAction::reply(id).as_bytes())?;
+            writer.flush()?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 pub enum Alert {
@@ -44,6 +81,8 @@ pub enum Alert {
         /// Whether clicking on the notification should focus the
         /// window/tab/pane that generated it
         focus: bool,
+        /// Buttons to show on the notification
+        actions: Vec<NotificationAction>,
     },
     CurrentWorkingDirectoryChanged,
     IconTitleChanged(Option<String>),
@@ -59,6 +98,32 @@ pub enum Alert {
     /// When something bumps the seqno in the terminal model and
     /// the terminal is not focused
     OutputSinceFocusLost,
+    /// Output arrived in a pane that doesn't have focus.
+    /// Raised by the mux, subject to `monitor_activity`.
+    PaneOutputActivity,
+    /// A pane that had been producing output has been quiet
+    /// for the configured number of seconds.
+    /// Raised by the mux, subject to `monitor_silence`.
+    PaneSilence {
+        seconds: u64,
+    },
+    /// The OSC 9;4 progress report changed
+    Progress(Progress),
+    /// Output from the pane started or stopped being limited
+    /// by `output_rate_limit`.  Raised by the mux.
+    OutputRateLimited(bool),
+    /// Reading the output of the pane was paused or resumed.
+    /// Raised by the mux.
+    OutputPaused(bool),
+    /// A line of output matched a pattern that is being watched
+    /// with `pane:watch`.  Raised by the mux.
+    PaneMatch {
+        watch_id: String,
+        /// The text that matched
+        text: String,
+        /// The row on which the matching line starts
+        row: StableRowIndex,
+    },
 }
 
 pub trait AlertHandler: Send + Sync {
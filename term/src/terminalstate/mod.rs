@@ -18,6 +18,7 @@ use termwiz::escape::csi::{
     EraseInLine, Mode, Sgr, TabulationClear, TerminalMode, TerminalModeCode, Window, XtSmGraphics,
     XtSmGraphicsAction, XtSmGraphicsItem, XtSmGraphicsStatus, XtermKeyModifierResource,
 };
+use termwiz::escape::osc::Progress;
 use termwiz::escape::{OneBased, OperatingSystemCommand, CSI};
 use termwiz::image::ImageData;
 use termwiz::input::KeyboardEncoding;
@@ -29,11 +30,13 @@ mod image;
 mod iterm;
 mod keyboard;
 mod kitty;
+pub(crate) mod modes;
 mod mouse;
 pub(crate) mod performer;
 mod sixel;
 use crate::terminalstate::image::*;
 use crate::terminalstate::kitty::*;
+use crate::terminalstate::modes::ModeState;
 
 lazy_static::lazy_static! {
     static ref DB: Database = {
@@ -150,6 +153,12 @@ struct ScreenOrAlt {
     alt_screen_is_active: bool,
     saved_cursor: Option<SavedCursor>,
     alt_saved_cursor: Option<SavedCursor>,
+    /// Whether text is measured by grapheme cluster (DEC private
+    /// mode 2027) rather than one code point at a time
+    grapheme_clustering: bool,
+    /// The state of grapheme_clustering when the alternate screen
+    /// was activated, which is restored when it is deactivated
+    primary_grapheme_clustering: bool,
 }
 
 impl Deref for ScreenOrAlt {
@@ -190,6 +199,8 @@ impl ScreenOrAlt {
             alt_screen_is_active: false,
             saved_cursor: None,
             alt_saved_cursor: None,
+            grapheme_clustering: config.grapheme_clustering(),
+            primary_grapheme_clustering: config.grapheme_clustering(),
         }
     }
 
@@ -207,11 +218,20 @@ impl ScreenOrAlt {
     }
 
     pub fn activate_alt_screen(&mut self, seqno: SequenceNo) {
+        if !self.alt_screen_is_active {
+            self.primary_grapheme_clustering = self.grapheme_clustering;
+        }
         self.alt_screen_is_active = true;
         self.dirty_top_phys_rows(seqno);
     }
 
     pub fn activate_primary_screen(&mut self, seqno: SequenceNo) {
+        if self.alt_screen_is_active {
+            // The keyboard protocol flags pushed by the application
+            // that was using the alternate screen must not outlive it
+            self.alt_screen.keyboard_stack.clear();
+            self.grapheme_clustering = self.primary_grapheme_clustering;
+        }
         self.alt_screen_is_active = false;
         self.dirty_top_phys_rows(seqno);
     }
@@ -247,6 +267,24 @@ impl ScreenOrAlt {
     }
 }
 
+/// Captures the content and the user-visible state of a terminal so
+/// that it can be reconstructed elsewhere; this is used when handing
+/// a pane off to a mux server.
+/// Modes set by the application (mouse reporting, keyboard encoding
+/// and so on) are not captured.
+#[derive(Debug, Clone)]
+pub struct TerminalSnapshot {
+    /// The lines of the primary screen, including scrollback
+    pub lines: Vec<Line>,
+    /// The lines of the alternate screen, if it is active
+    pub alt_lines: Option<Vec<Line>>,
+    pub cursor_x: usize,
+    pub cursor_y: VisibleRowIndex,
+    pub title: String,
+    pub current_dir: Option<Url>,
+    pub user_vars: HashMap<String, String>,
+}
+
 /// Manages the state for the terminal
 pub struct TerminalState {
     config: Arc<dyn TerminalConfiguration>,
@@ -276,6 +314,12 @@ pub struct TerminalState {
 
     /// Reverse video mode
     reverse_video_mode: bool,
+    /// Whether the application has begun a synchronized update
+    /// (DEC private mode 2026)
+    synchronized_output: bool,
+    /// The shape of the mouse pointer that the application
+    /// requested using OSC 22
+    mouse_pointer_shape: Option<MousePointerShape>,
 
     /// https://vt100.net/docs/vt510-rm/DECOM.html
     /// When OriginMode is enabled, cursor is constrained to the
@@ -352,6 +396,9 @@ pub struct TerminalState {
     download_handler: Option<Arc<dyn DownloadHandler>>,
 
     current_dir: Option<Url>,
+    /// Set once the application has used OSC 7, after which
+    /// OSC 9;9 no longer changes `current_dir`
+    current_dir_from_osc7: bool,
 
     term_program: String,
     term_version: String,
@@ -385,6 +432,9 @@ pub struct TerminalState {
     lost_focus_alerted_seqno: SequenceNo,
     focused: bool,
 
+    /// The most recent OSC 9;4 progress report
+    progress: Progress,
+
     /// True if lines should be marked as bidi-enabled, and thus
     /// have the renderer apply the bidi algorithm.
     /// true is equivalent to "implicit" bidi mode as described in
@@ -403,6 +453,51 @@ struct UnicodeVersionStackEntry {
     label: Option<String>,
 }
 
+/// Converts a native path, such as those reported by `OSC 9;9` or
+/// by querying a process on Windows, into a `file://` url.
+/// Drive letters are normalized to upper case and UNC paths become
+/// urls with the server as their host.
+pub fn path_to_file_url(path: &str) -> Option<Url> {
+    let path = path.trim();
+    let path = path
+        .strip_prefix('"')
+        .and_then(|p| p.strip_suffix('"'))
+        .unwrap_or(path);
+    if path.is_empty() {
+        return None;
+    }
+
+    let (unc, path) = if let Some(rest) = path.strip_prefix(r"\\?\UNC\") {
+        (true, rest)
+    } else if let Some(rest) = path.strip_prefix(r"\\?\") {
+        (false, rest)
+    } else if let Some(rest) = path.strip_prefix(r"\\") {
+        (true, rest)
+    } else {
+        (false, path)
+    };
+    let path = path.replace('\\', "/");
+
+    if unc {
+        let (host, rest) = path.split_once('/').unwrap_or((&path, ""));
+        let mut url = Url::parse(&format!("file://{}/", host)).ok()?;
+        url.set_path(&format!("/{}", rest));
+        return Some(url);
+    }
+
+    let path = match path.as_bytes() {
+        [drive, b':', ..] if drive.is_ascii_alphabetic() => {
+            format!("/{}{}", (*drive as char).to_ascii_uppercase(), &path[1..])
+        }
+        [b'/', ..] => path,
+        _ => return None,
+    };
+
+    let mut url = Url::parse("file:///").ok()?;
+    url.set_path(&path);
+    Some(url)
+}
+
 fn default_color_map() -> HashMap<u16, RgbColor> {
     let mut color_map = HashMap::new();
     // Match colors to the VT340 color table:
@@ -530,6 +625,8 @@ impl TerminalState {
             dec_auto_wrap: true,
             reverse_wraparound_mode: false,
             reverse_video_mode: false,
+            synchronized_output: false,
+            mouse_pointer_shape: None,
             dec_origin_mode: false,
             insert: false,
             application_cursor_keys: false,
@@ -566,6 +663,7 @@ impl TerminalState {
             alert_handler: None,
             download_handler: None,
             current_dir: None,
+            current_dir_from_osc7: false,
             term_program: term_program.to_string(),
             term_version: term_version.to_string(),
             writer,
@@ -581,6 +679,7 @@ impl TerminalState {
             lost_focus_seqno: seqno,
             lost_focus_alerted_seqno: seqno,
             focused: true,
+            progress: Progress::None,
             bidi_enabled: None,
             bidi_hint: None,
         }
@@ -623,6 +722,24 @@ impl TerminalState {
         self.download_handler.replace(handler.clone());
     }
 
+    /// Overrides the bidi mode from the config, in the same way as the
+    /// BDSM and SCP escape sequences do; None leaves that part of the
+    /// mode unchanged.  The lines that are already on the screen are
+    /// updated to match.
+    pub fn set_bidi_mode(&mut self, enabled: Option<bool>, hint: Option<ParagraphDirectionHint>) {
+        if let Some(enabled) = enabled {
+            self.bidi_enabled.replace(enabled);
+        }
+        if let Some(hint) = hint {
+            self.bidi_hint.replace(hint);
+        }
+        let mode = self.get_bidi_mode();
+        self.increment_seqno();
+        let seqno = self.seqno;
+        self.screen_mut()
+            .for_each_phys_line_mut(|_, line| mode.apply_to_line(line, seqno));
+    }
+
     /// Returns the title text associated with the terminal session.
     /// The title can be changed by the application using a number
     /// of escape sequences:
@@ -646,7 +763,8 @@ impl TerminalState {
 
     /// Returns the current working directory associated with the
     /// terminal session.  The working directory can be changed by
-    /// the applicaiton using the OSC 7 escape sequence.
+    /// the applicaiton using the OSC 7 escape sequence, or the
+    /// OSC 9;9 sequence used by ConEmu and Windows Terminal.
     pub fn get_current_dir(&self) -> Option<&Url> {
         self.current_dir.as_ref()
     }
@@ -754,6 +872,18 @@ impl TerminalState {
         self.screen.is_alt_screen_active()
     }
 
+    /// Returns true if newly printed text is measured by grapheme
+    /// cluster, which the application controls with DEC private mode 2027
+    pub fn is_grapheme_clustering_enabled(&self) -> bool {
+        self.screen.grapheme_clustering
+    }
+
+    /// Returns the shape of the mouse pointer that the application
+    /// requested using OSC 22, if any
+    pub fn mouse_pointer_shape(&self) -> Option<MousePointerShape> {
+        self.mouse_pointer_shape
+    }
+
     /// Returns true if the associated application has enabled
     /// bracketed paste mode, which can be helpful to the hosting
     /// GUI application to decide about fragmenting a large paste.
@@ -792,12 +922,38 @@ impl TerminalState {
         }
     }
 
+    /// Returns the most recently reported OSC 9;4 progress
+    pub fn get_progress(&self) -> Progress {
+        self.progress
+    }
+
+    /// Updates the progress, alerting if it changed
+    pub fn set_progress(&mut self, progress: Progress) {
+        if self.progress != progress {
+            self.progress = progress;
+            if let Some(handler) = self.alert_handler.as_mut() {
+                handler.alert(Alert::Progress(progress));
+            }
+        }
+    }
+
+    /// Returns true if the terminal has focus
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
     /// Returns true if there is new output since the terminal
     /// lost focus
     pub fn has_unseen_output(&self) -> bool {
         !self.focused && self.seqno > self.lost_focus_seqno
     }
 
+    /// Treats the output produced so far as having been seen,
+    /// as though the terminal had lost focus just now
+    pub fn mark_output_seen(&mut self) {
+        self.lost_focus_seqno = self.seqno;
+    }
+
     pub(crate) fn trigger_unseen_output_notif(&mut self) {
         if self.has_unseen_output() {
             // We want to avoid over-notifying about output events,
@@ -961,6 +1117,61 @@ impl TerminalState {
         &self.user_vars
     }
 
+    /// Captures the screen content and user-visible state
+    pub fn snapshot(&self) -> TerminalSnapshot {
+        TerminalSnapshot {
+            lines: self.screen.screen.all_lines(),
+            alt_lines: if self.screen.is_alt_screen_active() {
+                Some(self.screen.alt_screen.all_lines())
+            } else {
+                None
+            },
+            cursor_x: self.cursor.x,
+            cursor_y: self.cursor.y,
+            title: self.title.clone(),
+            current_dir: self.current_dir.clone(),
+            user_vars: self.user_vars.clone(),
+        }
+    }
+
+    /// Replaces the screen content and user-visible state with
+    /// that of a snapshot produced by `snapshot()`
+    pub fn restore_snapshot(&mut self, snapshot: TerminalSnapshot) {
+        self.increment_seqno();
+        let seqno = self.seqno;
+
+        self.screen.screen.restore_lines(snapshot.lines, seqno);
+        match snapshot.alt_lines {
+            Some(lines) => {
+                self.screen.alt_screen.restore_lines(lines, seqno);
+                self.screen.activate_alt_screen(seqno);
+            }
+            None => self.screen.activate_primary_screen(seqno),
+        }
+
+        let rows = self.screen().physical_rows as VisibleRowIndex;
+        let cols = self.screen().physical_cols;
+        self.cursor.x = snapshot.cursor_x.min(cols.saturating_sub(1));
+        self.cursor.y = snapshot.cursor_y.max(0).min(rows - 1);
+        self.cursor.seqno = seqno;
+        self.wrap_next = false;
+
+        self.title = snapshot.title;
+        self.current_dir = snapshot.current_dir;
+        self.user_vars = snapshot.user_vars;
+    }
+
+    /// Seeds the scrollback of the primary screen with the provided
+    /// lines, placing them above any existing content.  Returns the
+    /// stable row index of the first line that follows the seeded lines.
+    pub fn seed_scrollback(&mut self, lines: Vec<Line>) -> StableRowIndex {
+        self.increment_seqno();
+        let seqno = self.seqno;
+        let screen = &mut self.screen.screen;
+        let count = screen.prepend_lines(lines, seqno);
+        screen.phys_to_stable_row_index(count)
+    }
+
     fn clear_semantic_attribute_due_to_movement(&mut self) {
         if self.clear_semantic_attribute_on_newline {
             self.clear_semantic_attribute_on_newline = false;
@@ -1219,13 +1430,17 @@ impl TerminalState {
                     if let Some(value) = DB.raw(name) {
                         res.push_str("1+r");
                         res.push_str(&encoded_name);
-                        res.push('=');
+                        // Boolean capabilities are reported by name
+                        // alone, as xterm does
                         let value = match value {
-                            Value::True => hex::encode_upper("1"),
-                            Value::Number(n) => hex::encode_upper(&n.to_string()),
-                            Value::String(s) => hex::encode_upper(s),
+                            Value::True => None,
+                            Value::Number(n) => Some(hex::encode_upper(&n.to_string())),
+                            Value::String(s) => Some(hex::encode_upper(s)),
                         };
-                        res.push_str(&value);
+                        if let Some(value) = value {
+                            res.push('=');
+                            res.push_str(&value);
+                        }
                     } else {
                         log::trace!("xt_get_tcap: unknown name {}", name);
                         res.push_str("0+r");
@@ -1274,6 +1489,7 @@ impl TerminalState {
 
                 self.reverse_wraparound_mode = false;
                 self.reverse_video_mode = false;
+                self.synchronized_output = false;
                 self.bidi_enabled.take();
                 self.bidi_hint.take();
 
@@ -1376,67 +1592,48 @@ impl TerminalState {
         }
     }
 
-    /// Indicates that mode is permanently enabled
-    fn decqrm_response_permanent(&mut self, mode: Mode) {
-        let (is_dec, number) = match &mode {
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(code)) => (true, code.to_u16().unwrap()),
-            Mode::QueryDecPrivateMode(DecPrivateMode::Unspecified(code)) => (true, *code),
-            Mode::QueryMode(TerminalMode::Code(code)) => (false, code.to_u16().unwrap()),
-            Mode::QueryMode(TerminalMode::Unspecified(code)) => (false, *code),
+    fn decqrm_response(&mut self, mode: &Mode, state: ModeState) {
+        let (prefix, number) = match mode {
+            Mode::QueryDecPrivateMode(DecPrivateMode::Code(code)) => ("?", code.to_u16().unwrap()),
+            Mode::QueryDecPrivateMode(DecPrivateMode::Unspecified(code)) => ("?", *code),
+            Mode::QueryMode(TerminalMode::Code(code)) => ("", code.to_u16().unwrap()),
+            Mode::QueryMode(TerminalMode::Unspecified(code)) => ("", *code),
             _ => unreachable!(),
         };
 
-        let prefix = if is_dec { "?" } else { "" };
-
-        write!(self.writer, "\x1b[{prefix}{number};3$y").ok();
+        log::trace!("{:?} -> {:?}", mode, state);
+        write!(self.writer, "\x1b[{}{};{}$y", prefix, number, state as u8).ok();
         self.writer.flush().ok();
     }
 
-    fn decqrm_response(&mut self, mode: Mode, mut recognized: bool, enabled: bool) {
-        let (is_dec, number) = match &mode {
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(code)) => (true, code.to_u16().unwrap()),
-            Mode::QueryDecPrivateMode(DecPrivateMode::Unspecified(code)) => {
-                recognized = false;
-                (true, *code)
-            }
-            Mode::QueryMode(TerminalMode::Code(code)) => (false, code.to_u16().unwrap()),
-            Mode::QueryMode(TerminalMode::Unspecified(code)) => {
-                recognized = false;
-                (false, *code)
-            }
-            _ => unreachable!(),
+    fn perform_csi_mode(&mut self, mode: Mode) {
+        // Only the modes that are listed in the tables in modes.rs are
+        // acted upon, so that the replies to DECRQM cannot disagree
+        // with what the terminal actually does
+        let supported = match &mode {
+            Mode::SetDecPrivateMode(DecPrivateMode::Code(code))
+            | Mode::ResetDecPrivateMode(DecPrivateMode::Code(code)) => {
+                Self::is_dec_private_mode_supported(code)
+            }
+            Mode::SetMode(TerminalMode::Code(code)) | Mode::ResetMode(TerminalMode::Code(code)) => {
+                Self::is_terminal_mode_supported(code)
+            }
+            _ => true,
         };
-
-        let prefix = if is_dec { "?" } else { "" };
-
-        let status = if recognized {
-            if enabled {
-                1 // set
-            } else {
-                2 // reset
+        if !supported {
+            if self.config.log_unknown_escape_sequences() {
+                log::warn!("unhandled {:?}", mode);
             }
-        } else {
-            0
-        };
-
-        log::trace!("{:?} -> recognized={} status={}", mode, recognized, status);
-        write!(self.writer, "\x1b[{}{};{}$y", prefix, number, status).ok();
-        self.writer.flush().ok();
-    }
+            return;
+        }
 
-    fn perform_csi_mode(&mut self, mode: Mode) {
-        match mode {
+        match &mode {
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::StartBlinkingCursor,
             ))
             | Mode::ResetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::StartBlinkingCursor,
             )) => {}
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
-                DecPrivateModeCode::StartBlinkingCursor,
-            )) => {
-                self.decqrm_response(mode, true, false);
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::AutoRepeat))
             | Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::AutoRepeat)) => {
@@ -1451,14 +1648,6 @@ impl TerminalState {
                 self.keyboard_encoding = KeyboardEncoding::Xterm;
             }
 
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::Win32InputMode)) => {
-                self.decqrm_response(
-                    mode,
-                    true,
-                    self.keyboard_encoding == KeyboardEncoding::Win32,
-                );
-            }
-
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::ReverseWraparound,
             )) => {
@@ -1471,12 +1660,6 @@ impl TerminalState {
                 self.reverse_wraparound_mode = false;
             }
 
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
-                DecPrivateModeCode::ReverseWraparound,
-            )) => {
-                self.decqrm_response(mode, true, self.reverse_wraparound_mode);
-            }
-
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::LeftRightMarginMode,
             )) => {
@@ -1490,25 +1673,16 @@ impl TerminalState {
                 self.left_and_right_margins = 0..self.screen().physical_cols;
             }
 
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
-                DecPrivateModeCode::LeftRightMarginMode,
-            )) => {
-                self.decqrm_response(mode, true, self.left_and_right_margin_mode);
-            }
-
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::GraphemeClustering,
-            ))
-            | Mode::ResetDecPrivateMode(DecPrivateMode::Code(
-                DecPrivateModeCode::GraphemeClustering,
             )) => {
-                // Permanently enabled
+                self.screen.grapheme_clustering = true;
             }
 
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
+            Mode::ResetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::GraphemeClustering,
             )) => {
-                self.decqrm_response_permanent(mode);
+                self.screen.grapheme_clustering = false;
             }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SaveCursor)) => {
@@ -1527,10 +1701,6 @@ impl TerminalState {
                 self.dec_auto_wrap = false;
             }
 
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::AutoWrap)) => {
-                self.decqrm_response(mode, true, self.dec_auto_wrap);
-            }
-
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::OriginMode)) => {
                 self.dec_origin_mode = true;
                 self.set_cursor_pos(&Position::Absolute(0), &Position::Absolute(0));
@@ -1541,10 +1711,6 @@ impl TerminalState {
                 self.set_cursor_pos(&Position::Absolute(0), &Position::Absolute(0));
             }
 
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::OriginMode)) => {
-                self.decqrm_response(mode, true, self.dec_origin_mode);
-            }
-
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::UsePrivateColorRegistersForEachGraphic,
             )) => {
@@ -1555,32 +1721,18 @@ impl TerminalState {
             )) => {
                 self.use_private_color_registers_for_each_graphic = false;
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
-                DecPrivateModeCode::UsePrivateColorRegistersForEachGraphic,
-            )) => {
-                self.decqrm_response(
-                    mode,
-                    true,
-                    self.use_private_color_registers_for_each_graphic,
-                );
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::SynchronizedOutput,
             )) => {
-                // This is handled in wezterm's mux
+                // The output is held back by wezterm's mux;
+                // we only track the state for DECRQM
+                self.synchronized_output = true;
             }
             Mode::ResetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::SynchronizedOutput,
             )) => {
-                // This is handled in wezterm's mux
-            }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
-                DecPrivateModeCode::SynchronizedOutput,
-            )) => {
-                // This is handled in wezterm's mux; if we get here, then it isn't enabled,
-                // so we always report false
-                self.decqrm_response(mode, true, false);
+                self.synchronized_output = false;
             }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SmoothScroll))
@@ -1614,11 +1766,6 @@ impl TerminalState {
                 self.set_cursor_pos(&Position::Absolute(0), &Position::Absolute(0));
                 self.erase_in_display(EraseInDisplay::EraseDisplay);
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
-                DecPrivateModeCode::Select132Columns,
-            )) => {
-                self.decqrm_response(mode, true, false);
-            }
 
             Mode::SetMode(TerminalMode::Code(TerminalModeCode::BiDirectionalSupportMode)) => {
                 self.bidi_enabled.replace(true);
@@ -1626,13 +1773,14 @@ impl TerminalState {
             Mode::ResetMode(TerminalMode::Code(TerminalModeCode::BiDirectionalSupportMode)) => {
                 self.bidi_enabled.replace(false);
             }
-            Mode::QueryMode(TerminalMode::Code(TerminalModeCode::BiDirectionalSupportMode)) => {
-                self.decqrm_response(
-                    mode,
-                    true,
-                    self.bidi_enabled
-                        .unwrap_or_else(|| self.config.bidi_mode().enabled),
-                );
+
+            Mode::SetMode(TerminalMode::Code(TerminalModeCode::KeyboardAction))
+            | Mode::ResetMode(TerminalMode::Code(TerminalModeCode::KeyboardAction)) => {
+                // The keyboard is never locked
+            }
+            Mode::SetMode(TerminalMode::Code(TerminalModeCode::SendReceive))
+            | Mode::ResetMode(TerminalMode::Code(TerminalModeCode::SendReceive)) => {
+                // Input is never echoed locally
             }
 
             Mode::SetMode(TerminalMode::Code(TerminalModeCode::Insert)) => {
@@ -1641,9 +1789,6 @@ impl TerminalState {
             Mode::ResetMode(TerminalMode::Code(TerminalModeCode::Insert)) => {
                 self.insert = false;
             }
-            Mode::QueryMode(TerminalMode::Code(TerminalModeCode::Insert)) => {
-                self.decqrm_response(mode, true, self.insert);
-            }
 
             Mode::SetMode(TerminalMode::Code(TerminalModeCode::AutomaticNewline)) => {
                 self.newline_mode = true;
@@ -1651,9 +1796,6 @@ impl TerminalState {
             Mode::ResetMode(TerminalMode::Code(TerminalModeCode::AutomaticNewline)) => {
                 self.newline_mode = false;
             }
-            Mode::QueryMode(TerminalMode::Code(TerminalModeCode::AutomaticNewline)) => {
-                self.decqrm_response(mode, true, self.newline_mode);
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::BracketedPaste)) => {
                 self.bracketed_paste = true;
@@ -1661,9 +1803,6 @@ impl TerminalState {
             Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::BracketedPaste)) => {
                 self.bracketed_paste = false;
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::BracketedPaste)) => {
-                self.decqrm_response(mode, true, self.bracketed_paste);
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::OptEnableAlternateScreen,
@@ -1705,11 +1844,6 @@ impl TerminalState {
             )) => {
                 self.application_cursor_keys = false;
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
-                DecPrivateModeCode::ApplicationCursorKeys,
-            )) => {
-                self.decqrm_response(mode, true, self.application_cursor_keys);
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SixelDisplayMode)) => {
                 self.sixel_display_mode = true;
@@ -1719,11 +1853,6 @@ impl TerminalState {
             )) => {
                 self.sixel_display_mode = false;
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
-                DecPrivateModeCode::SixelDisplayMode,
-            )) => {
-                self.decqrm_response(mode, true, self.sixel_display_mode);
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::DecAnsiMode)) => {
                 self.dec_ansi_mode = true;
@@ -1731,9 +1860,6 @@ impl TerminalState {
             Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::DecAnsiMode)) => {
                 self.dec_ansi_mode = false;
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::DecAnsiMode)) => {
-                self.decqrm_response(mode, true, self.dec_ansi_mode);
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::ShowCursor)) => {
                 self.cursor_visible = true;
@@ -1741,9 +1867,6 @@ impl TerminalState {
             Mode::ResetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::ShowCursor)) => {
                 self.cursor_visible = false;
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::ShowCursor)) => {
-                self.decqrm_response(mode, true, self.cursor_visible);
-            }
             Mode::SetMode(TerminalMode::Code(TerminalModeCode::ShowCursor)) => {
                 self.cursor_visible = true;
             }
@@ -1759,9 +1882,6 @@ impl TerminalState {
                 self.mouse_tracking = false;
                 self.last_mouse_move.take();
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::MouseTracking)) => {
-                self.decqrm_response(mode, true, self.mouse_tracking);
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::HighlightMouseTracking,
@@ -1780,11 +1900,6 @@ impl TerminalState {
                 self.button_event_mouse = false;
                 self.last_mouse_move.take();
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
-                DecPrivateModeCode::ButtonEventMouse,
-            )) => {
-                self.decqrm_response(mode, true, self.button_event_mouse);
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::AnyEventMouse)) => {
                 self.any_event_mouse = true;
@@ -1794,9 +1909,6 @@ impl TerminalState {
                 self.any_event_mouse = false;
                 self.last_mouse_move.take();
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::AnyEventMouse)) => {
-                self.decqrm_response(mode, true, self.any_event_mouse);
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::FocusTracking)) => {
                 self.focus_tracking = true;
@@ -1806,9 +1918,6 @@ impl TerminalState {
                 self.focus_tracking = false;
                 self.last_mouse_move.take();
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::FocusTracking)) => {
-                self.decqrm_response(mode, true, self.focus_tracking);
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SGRMouse)) => {
                 self.mouse_encoding = MouseEncoding::SGR;
@@ -1818,16 +1927,6 @@ impl TerminalState {
                 self.mouse_encoding = MouseEncoding::X10;
                 self.last_mouse_move.take();
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SGRMouse)) => {
-                self.decqrm_response(
-                    mode,
-                    true,
-                    match self.mouse_encoding {
-                        MouseEncoding::SGR => true,
-                        _ => false,
-                    },
-                );
-            }
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SGRPixelsMouse)) => {
                 self.mouse_encoding = MouseEncoding::SgrPixels;
                 self.last_mouse_move.take();
@@ -1836,16 +1935,6 @@ impl TerminalState {
                 self.mouse_encoding = MouseEncoding::X10;
                 self.last_mouse_move.take();
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::SGRPixelsMouse)) => {
-                self.decqrm_response(
-                    mode,
-                    true,
-                    match self.mouse_encoding {
-                        MouseEncoding::SgrPixels => true,
-                        _ => false,
-                    },
-                );
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::Utf8Mouse)) => {
                 self.mouse_encoding = MouseEncoding::Utf8;
@@ -1855,16 +1944,6 @@ impl TerminalState {
                 self.mouse_encoding = MouseEncoding::X10;
                 self.last_mouse_move.take();
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(DecPrivateModeCode::Utf8Mouse)) => {
-                self.decqrm_response(
-                    mode,
-                    true,
-                    match self.mouse_encoding {
-                        MouseEncoding::Utf8 => true,
-                        _ => false,
-                    },
-                );
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::SixelScrollsRight,
@@ -1876,11 +1955,6 @@ impl TerminalState {
             )) => {
                 self.sixel_scrolls_right = false;
             }
-            Mode::QueryDecPrivateMode(DecPrivateMode::Code(
-                DecPrivateModeCode::SixelScrollsRight,
-            )) => {
-                self.decqrm_response(mode, true, self.sixel_scrolls_right);
-            }
 
             Mode::SetDecPrivateMode(DecPrivateMode::Code(
                 DecPrivateModeCode::ClearAndEnableAlternateScreen,
@@ -1948,7 +2022,7 @@ impl TerminalState {
             } => {
                 self.modify_other_keys = match value {
                     Some(0) => None,
-                    _ => value,
+                    _ => *value,
                 };
                 log::debug!("XtermKeyMode OtherKeys -> {:?}", self.modify_other_keys);
             }
@@ -1959,8 +2033,17 @@ impl TerminalState {
                 }
             }
 
-            Mode::QueryDecPrivateMode(_) | Mode::QueryMode(_) => {
-                self.decqrm_response(mode, false, false);
+            Mode::QueryDecPrivateMode(DecPrivateMode::Code(code)) => {
+                let state = self.dec_private_mode_state(code);
+                self.decqrm_response(&mode, state);
+            }
+            Mode::QueryMode(TerminalMode::Code(code)) => {
+                let state = self.terminal_mode_state(code);
+                self.decqrm_response(&mode, state);
+            }
+            Mode::QueryDecPrivateMode(DecPrivateMode::Unspecified(_))
+            | Mode::QueryMode(TerminalMode::Unspecified(_)) => {
+                self.decqrm_response(&mode, ModeState::NotRecognized);
             }
         }
     }
@@ -2039,7 +2122,9 @@ impl TerminalState {
                 self.writer.flush().ok();
             }
 
-            Window::ReportTextAreaSizePixels => {
+            Window::ReportTextAreaSizePixels | Window::ReportWindowSizePixels => {
+                // We don't know the size of the window decorations,
+                // so both report the size of the text area
                 let response = Box::new(Window::ResizeWindowPixels {
                     width: Some(self.pixel_width as i64),
                     height: Some(self.pixel_height as i64),
@@ -2048,6 +2133,51 @@ impl TerminalState {
                 self.writer.flush().ok();
             }
 
+            Window::ReportScreenSizePixels => {
+                // The size of the screen isn't known to the terminal
+                // model; the text area is the most useful answer.
+                write!(
+                    self.writer,
+                    "\x1b[5;{};{}t",
+                    self.pixel_height, self.pixel_width
+                )
+                .ok();
+                self.writer.flush().ok();
+            }
+
+            Window::ReportScreenSizeCells => {
+                let screen = self.screen();
+                write!(
+                    self.writer,
+                    "\x1b[9;{};{}t",
+                    screen.physical_rows, screen.physical_cols
+                )
+                .ok();
+                self.writer.flush().ok();
+            }
+
+            Window::ReportWindowState => {
+                // Never iconified, as far as the application can tell
+                write!(self.writer, "\x1b[1t").ok();
+                self.writer.flush().ok();
+            }
+
+            Window::ReportIconLabel => {
+                if self.config.enable_title_reporting() {
+                    let label = self
+                        .icon_title
+                        .clone()
+                        .unwrap_or_else(|| self.title.clone());
+                    write!(
+                        self.writer,
+                        "{}",
+                        OperatingSystemCommand::SetIconNameSun(label)
+                    )
+                    .ok();
+                    self.writer.flush().ok();
+                }
+            }
+
             Window::ReportWindowTitle => {
                 if self.config.enable_title_reporting() {
                     write!(
@@ -2707,12 +2837,47 @@ impl TerminalState {
 
         let mut current_zone: Option<SemanticZone> = None;
         let mut zones = vec![];
+        // The index in zones of the prompt of the most recent command
+        let mut command_start: Option<usize> = None;
+
+        // Applies the status reported via OSC 133;D to the zones
+        // of the command that it completes: those from the most
+        // recent prompt up to and including the current zone
+        fn complete_command(
+            zones: &mut [SemanticZone],
+            current_zone: &mut Option<SemanticZone>,
+            command_start: &mut Option<usize>,
+            status: CommandStatus,
+        ) {
+            let start = command_start.take().unwrap_or(zones.len());
+            for zone in &mut zones[start..] {
+                zone.command_status.get_or_insert(status);
+            }
+            if let Some(zone) = current_zone.as_mut() {
+                zone.command_status.get_or_insert(status);
+            }
+        }
 
         let first_stable_row = screen.phys_to_stable_row_index(0);
         screen.for_each_phys_line_mut(|idx, line| {
             let stable_row = first_stable_row + idx as StableRowIndex;
+            let mut command_status = line.command_status();
 
             for zone_range in line.semantic_zone_ranges() {
+                // Zones that start at or after the point at which the
+                // command completed belong to the next command
+                if let Some(mark) = command_status {
+                    if zone_range.range.start as usize >= mark.x {
+                        complete_command(
+                            &mut zones,
+                            &mut current_zone,
+                            &mut command_start,
+                            CommandStatus::from_exit_status(mark.status),
+                        );
+                        command_status = None;
+                    }
+                }
+
                 let new_zone = match current_zone.as_ref() {
                     None => true,
                     Some(zone) => zone.semantic_type != zone_range.semantic_type,
@@ -2723,12 +2888,17 @@ impl TerminalState {
                         zones.push(zone);
                     }
 
+                    if zone_range.semantic_type == SemanticType::Prompt {
+                        command_start.replace(zones.len());
+                    }
+
                     current_zone.replace(SemanticZone {
                         start_x: zone_range.range.start as usize,
                         start_y: stable_row,
                         end_x: zone_range.range.end as usize,
                         end_y: stable_row,
                         semantic_type: zone_range.semantic_type,
+                        command_status: None,
                     });
                 }
 
@@ -2737,6 +2907,15 @@ impl TerminalState {
                     zone.end_y = stable_row;
                 }
             }
+
+            if let Some(mark) = command_status {
+                complete_command(
+                    &mut zones,
+                    &mut current_zone,
+                    &mut command_start,
+                    CommandStatus::from_exit_status(mark.status),
+                );
+            }
         });
         if let Some(zone) = current_zone.take() {
             zones.push(zone);
@@ -2757,4 +2936,13 @@ impl TerminalState {
             .copied()
             .unwrap_or(self.keyboard_encoding)
     }
+
+    /// Discards any keyboard encoding state that was negotiated
+    /// by the application, for both the primary and alternate screens.
+    /// This is used when the child process has exited.
+    pub fn reset_keyboard_encoding(&mut self) {
+        self.screen.screen.keyboard_stack.clear();
+        self.screen.alt_screen.keyboard_stack.clear();
+        self.keyboard_encoding = KeyboardEncoding::Xterm;
+    }
 }
@@ -0,0 +1,200 @@
+//! The modes that the terminal implements, and how the state of each
+//! is reported in reply to DECRQM.
+//! These tables are the single source of truth for which modes are
+//! supported: setting or resetting a mode that is not listed here is
+//! logged as unhandled, and querying one reports it as not recognized.
+use super::MouseEncoding;
+use crate::TerminalState;
+use termwiz::escape::csi::{DecPrivateModeCode, TerminalModeCode};
+use termwiz::input::KeyboardEncoding;
+
+/// The state of a mode, as reported by DECRQM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModeState {
+    NotRecognized = 0,
+    Set = 1,
+    Reset = 2,
+    PermanentlySet = 3,
+    PermanentlyReset = 4,
+}
+
+impl ModeState {
+    fn from_bool(enabled: bool) -> Self {
+        if enabled {
+            Self::Set
+        } else {
+            Self::Reset
+        }
+    }
+}
+
+/// Computes the current state of a mode
+pub(crate) type ModeQuery = fn(&TerminalState) -> ModeState;
+
+pub(crate) const DEC_PRIVATE_MODES: &[(DecPrivateModeCode, ModeQuery)] = &[
+    (DecPrivateModeCode::ApplicationCursorKeys, |t| {
+        ModeState::from_bool(t.application_cursor_keys)
+    }),
+    (DecPrivateModeCode::DecAnsiMode, |t| {
+        ModeState::from_bool(t.dec_ansi_mode)
+    }),
+    // Setting or resetting DECCOLM clears the screen, but the
+    // number of columns is never changed
+    (DecPrivateModeCode::Select132Columns, |_| {
+        ModeState::PermanentlyReset
+    }),
+    // We always output at our "best" rate
+    (DecPrivateModeCode::SmoothScroll, |_| {
+        ModeState::PermanentlyReset
+    }),
+    (DecPrivateModeCode::ReverseVideo, |t| {
+        ModeState::from_bool(t.reverse_video_mode)
+    }),
+    (DecPrivateModeCode::OriginMode, |t| {
+        ModeState::from_bool(t.dec_origin_mode)
+    }),
+    (DecPrivateModeCode::AutoWrap, |t| {
+        ModeState::from_bool(t.dec_auto_wrap)
+    }),
+    // Key repeat is left to the GUI layer prefs
+    (DecPrivateModeCode::AutoRepeat, |_| {
+        ModeState::PermanentlySet
+    }),
+    (DecPrivateModeCode::StartBlinkingCursor, |_| {
+        ModeState::Reset
+    }),
+    (DecPrivateModeCode::ShowCursor, |t| {
+        ModeState::from_bool(t.cursor_visible)
+    }),
+    (DecPrivateModeCode::ReverseWraparound, |t| {
+        ModeState::from_bool(t.reverse_wraparound_mode)
+    }),
+    (DecPrivateModeCode::LeftRightMarginMode, |t| {
+        ModeState::from_bool(t.left_and_right_margin_mode)
+    }),
+    (DecPrivateModeCode::SixelDisplayMode, |t| {
+        ModeState::from_bool(t.sixel_display_mode)
+    }),
+    (DecPrivateModeCode::MouseTracking, |t| {
+        ModeState::from_bool(t.mouse_tracking)
+    }),
+    (DecPrivateModeCode::HighlightMouseTracking, |_| {
+        ModeState::PermanentlyReset
+    }),
+    (DecPrivateModeCode::ButtonEventMouse, |t| {
+        ModeState::from_bool(t.button_event_mouse)
+    }),
+    (DecPrivateModeCode::AnyEventMouse, |t| {
+        ModeState::from_bool(t.any_event_mouse)
+    }),
+    (DecPrivateModeCode::FocusTracking, |t| {
+        ModeState::from_bool(t.focus_tracking)
+    }),
+    (DecPrivateModeCode::Utf8Mouse, |t| {
+        ModeState::from_bool(t.mouse_encoding == MouseEncoding::Utf8)
+    }),
+    (DecPrivateModeCode::SGRMouse, |t| {
+        ModeState::from_bool(t.mouse_encoding == MouseEncoding::SGR)
+    }),
+    (DecPrivateModeCode::SGRPixelsMouse, |t| {
+        ModeState::from_bool(t.mouse_encoding == MouseEncoding::SgrPixels)
+    }),
+    // Alt and Meta always send escape-prefixed keys, subject to the
+    // GUI's configuration of composed keys
+    (DecPrivateModeCode::XTermMetaSendsEscape, |_| {
+        ModeState::PermanentlySet
+    }),
+    (DecPrivateModeCode::XTermAltSendsEscape, |_| {
+        ModeState::PermanentlySet
+    }),
+    // Saving and restoring the cursor is an action rather than a state
+    (DecPrivateModeCode::SaveCursor, |_| ModeState::Reset),
+    (DecPrivateModeCode::ClearAndEnableAlternateScreen, |t| {
+        ModeState::from_bool(t.screen.is_alt_screen_active())
+    }),
+    (DecPrivateModeCode::EnableAlternateScreen, |t| {
+        ModeState::from_bool(t.screen.is_alt_screen_active())
+    }),
+    (DecPrivateModeCode::OptEnableAlternateScreen, |t| {
+        ModeState::from_bool(t.screen.is_alt_screen_active())
+    }),
+    (DecPrivateModeCode::BracketedPaste, |t| {
+        ModeState::from_bool(t.bracketed_paste)
+    }),
+    (DecPrivateModeCode::GraphemeClustering, |t| {
+        ModeState::from_bool(t.screen.grapheme_clustering)
+    }),
+    (
+        DecPrivateModeCode::UsePrivateColorRegistersForEachGraphic,
+        |t| ModeState::from_bool(t.use_private_color_registers_for_each_graphic),
+    ),
+    // The output is held back by wezterm's mux while this is set,
+    // so a query arrives along with the output that follows the set
+    (DecPrivateModeCode::SynchronizedOutput, |t| {
+        ModeState::from_bool(t.synchronized_output)
+    }),
+    (DecPrivateModeCode::MinTTYApplicationEscapeKeyMode, |_| {
+        ModeState::PermanentlyReset
+    }),
+    (DecPrivateModeCode::SixelScrollsRight, |t| {
+        ModeState::from_bool(t.sixel_scrolls_right)
+    }),
+    (DecPrivateModeCode::Win32InputMode, |t| {
+        ModeState::from_bool(t.keyboard_encoding == KeyboardEncoding::Win32)
+    }),
+];
+
+pub(crate) const TERMINAL_MODES: &[(TerminalModeCode, ModeQuery)] = &[
+    // The keyboard is never locked
+    (TerminalModeCode::KeyboardAction, |_| {
+        ModeState::PermanentlyReset
+    }),
+    (TerminalModeCode::Insert, |t| ModeState::from_bool(t.insert)),
+    (TerminalModeCode::BiDirectionalSupportMode, |t| {
+        ModeState::from_bool(
+            t.bidi_enabled
+                .unwrap_or_else(|| t.config.bidi_mode().enabled),
+        )
+    }),
+    // Input is never echoed locally
+    (TerminalModeCode::SendReceive, |_| ModeState::PermanentlySet),
+    (TerminalModeCode::AutomaticNewline, |t| {
+        ModeState::from_bool(t.newline_mode)
+    }),
+    (TerminalModeCode::ShowCursor, |t| {
+        ModeState::from_bool(t.cursor_visible)
+    }),
+];
+
+fn lookup<C: PartialEq>(table: &[(C, ModeQuery)], code: &C) -> Option<ModeQuery> {
+    table
+        .iter()
+        .find(|(candidate, _)| candidate == code)
+        .map(|(_, query)| *query)
+}
+
+impl TerminalState {
+    /// Returns true if the terminal implements the DEC private mode `code`
+    pub(crate) fn is_dec_private_mode_supported(code: &DecPrivateModeCode) -> bool {
+        lookup(DEC_PRIVATE_MODES, code).is_some()
+    }
+
+    /// Returns true if the terminal implements the ANSI mode `code`
+    pub(crate) fn is_terminal_mode_supported(code: &TerminalModeCode) -> bool {
+        lookup(TERMINAL_MODES, code).is_some()
+    }
+
+    pub(crate) fn dec_private_mode_state(&self, code: &DecPrivateModeCode) -> ModeState {
+        match lookup(DEC_PRIVATE_MODES, code) {
+            Some(query) => query(self),
+            None => ModeState::NotRecognized,
+        }
+    }
+
+    pub(crate) fn terminal_mode_state(&self, code: &TerminalModeCode) -> ModeState {
+        match lookup(TERMINAL_MODES, code) {
+            Some(query) => query(self),
+            None => ModeState::NotRecognized,
+        }
+    }
+}
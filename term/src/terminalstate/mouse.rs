@@ -30,12 +30,72 @@ impl TerminalState {
         }
     }
 
-    fn encode_x10_or_utf8(&mut self, event: MouseEvent, button: i8) -> anyhow::Result<()> {
-        let mut buf = vec![b'\x1b', b'[', b'M', (32 + button) as u8];
-        self.encode_coord(event.x as i64, &mut buf);
-        self.encode_coord(event.y, &mut buf);
-        log::trace!("{event:?} {buf:?}");
-        self.writer.write(&buf)?;
+    /// Computes the 1-based pixel position of `event` for SGR-Pixels
+    /// reporting, clamped to the pixel dimensions of the terminal.
+    /// The cell origin is derived from the full pixel size rather than
+    /// a truncated per-cell size so that the reported position doesn't
+    /// drift away from the pointer towards the right and bottom edges.
+    fn pixel_coords(&self, event: &MouseEvent) -> (i64, i64) {
+        let cols = self.screen.physical_cols.max(1) as i64;
+        let rows = self.screen.physical_rows.max(1) as i64;
+        let width = self.pixel_width as i64;
+        let height = self.pixel_height as i64;
+
+        let x = (event.x as i64 * width / cols) + event.x_pixel_offset as i64;
+        let y = (event.y * height / rows) + event.y_pixel_offset as i64;
+
+        (
+            x.clamp(0, (width - 1).max(0)) + 1,
+            y.clamp(0, (height - 1).max(0)) + 1,
+        )
+    }
+
+    /// Encodes a single mouse report for `event` using the current
+    /// mouse encoding.  `button` is the already-computed button code,
+    /// and `release` selects the release form of the report.
+    pub(crate) fn encode_mouse_report(
+        &self,
+        button: i8,
+        event: &MouseEvent,
+        release: bool,
+    ) -> Vec<u8> {
+        let final_byte = if release { 'm' } else { 'M' };
+        match self.mouse_encoding {
+            MouseEncoding::SGR => format!(
+                "\x1b[<{};{};{}{}",
+                button,
+                event.x + 1,
+                event.y + 1,
+                final_byte
+            )
+            .into_bytes(),
+            MouseEncoding::SgrPixels => {
+                let (x, y) = self.pixel_coords(event);
+                format!("\x1b[<{};{};{}{}", button, x, y, final_byte).into_bytes()
+            }
+            MouseEncoding::X10 | MouseEncoding::Utf8 => {
+                // These encodings cannot express which button was released
+                let button = if release { 3 } else { button };
+                let mut buf = vec![b'\x1b', b'[', b'M', (32 + button) as u8];
+                self.encode_coord(event.x as i64, &mut buf);
+                self.encode_coord(event.y, &mut buf);
+                buf
+            }
+        }
+    }
+
+    fn write_mouse_report(
+        &mut self,
+        button: i8,
+        event: &MouseEvent,
+        release: bool,
+    ) -> anyhow::Result<()> {
+        let report = self.encode_mouse_report(button, event, release);
+        log::trace!(
+            "{event:?} -> {}",
+            String::from_utf8_lossy(&report).escape_debug()
+        );
+        self.writer.write_all(&report)?;
         self.writer.flush()?;
         Ok(())
     }
@@ -76,48 +136,24 @@ impl TerminalState {
     fn mouse_wheel(&mut self, event: MouseEvent) -> anyhow::Result<()> {
         let (button, _button) = self.mouse_report_button_number(&event);
 
-        if self.mouse_encoding == MouseEncoding::SGR
-            && (self.mouse_tracking || self.button_event_mouse || self.any_event_mouse)
-        {
-            log::trace!(
-                "wheel {event:?} ESC [<{};{};{}M",
-                button,
-                event.x + 1,
-                event.y + 1
-            );
-            write!(
-                self.writer,
-                "\x1b[<{};{};{}M",
-                button,
-                event.x + 1,
-                event.y + 1
-            )?;
-            self.writer.flush()?;
-        } else if self.mouse_encoding == MouseEncoding::SgrPixels
-            && (self.mouse_tracking || self.button_event_mouse || self.any_event_mouse)
-        {
-            let height = self.screen.physical_rows as usize;
-            let width = self.screen.physical_cols as usize;
-            log::trace!(
-                "wheel {event:?} ESC [<{};{};{}M",
-                button,
-                (event.x * (self.pixel_width / width)) + event.x_pixel_offset.max(0) as usize + 1,
-                (event.y as usize * (self.pixel_height / height))
-                    + event.y_pixel_offset.max(0) as usize
-                    + 1
-            );
-            write!(
-                self.writer,
-                "\x1b[<{};{};{}M",
-                button,
-                (event.x * (self.pixel_width / width)) + event.x_pixel_offset.max(0) as usize + 1,
-                (event.y as usize * (self.pixel_height / height))
-                    + event.y_pixel_offset.max(0) as usize
-                    + 1
-            )?;
-            self.writer.flush()?;
-        } else if self.mouse_tracking || self.button_event_mouse || self.any_event_mouse {
-            self.encode_x10_or_utf8(event, button)?;
+        if self.mouse_tracking || self.button_event_mouse || self.any_event_mouse {
+            // In SGR-Pixels mode, emit a report for each unit of the
+            // accumulated wheel delta so that the application sees the
+            // full extent of a precise (eg: touchpad) scroll rather
+            // than a single quantized click.
+            let repeat = match (self.mouse_encoding, event.button) {
+                (
+                    MouseEncoding::SgrPixels,
+                    MouseButton::WheelUp(amount)
+                    | MouseButton::WheelDown(amount)
+                    | MouseButton::WheelLeft(amount)
+                    | MouseButton::WheelRight(amount),
+                ) => amount.max(1),
+                _ => 1,
+            };
+            for _ in 0..repeat {
+                self.write_mouse_report(button, &event, false)?;
+            }
         } else if self.screen.is_alt_screen_active() {
             // Send cursor keys instead (equivalent to xterm's alternateScroll mode)
             for _ in 0..self.config.alternate_buffer_wheel_scroll_speed() {
@@ -145,45 +181,7 @@ impl TerminalState {
             return Ok(());
         }
 
-        if self.mouse_encoding == MouseEncoding::SGR {
-            log::trace!(
-                "press {event:?} ESC [<{};{};{}M",
-                button,
-                event.x + 1,
-                event.y + 1
-            );
-            write!(
-                self.writer,
-                "\x1b[<{};{};{}M",
-                button,
-                event.x + 1,
-                event.y + 1
-            )?;
-            self.writer.flush()?;
-        } else if self.mouse_encoding == MouseEncoding::SgrPixels {
-            let height = self.screen.physical_rows as usize;
-            let width = self.screen.physical_cols as usize;
-            log::trace!(
-                "press {event:?} ESC [<{};{};{}M",
-                button,
-                (event.x * (self.pixel_width / width)) + event.x_pixel_offset.max(0) as usize + 1,
-                (event.y as usize * (self.pixel_height / height))
-                    + event.y_pixel_offset.max(0) as usize
-                    + 1
-            );
-            write!(
-                self.writer,
-                "\x1b[<{};{};{}M",
-                button,
-                (event.x * (self.pixel_width / width)) + event.x_pixel_offset.max(0) as usize + 1,
-                (event.y as usize * (self.pixel_height / height))
-                    + event.y_pixel_offset.max(0) as usize
-                    + 1
-            )?;
-            self.writer.flush()?;
-        } else {
-            self.encode_x10_or_utf8(event, button)?;
-        }
+        self.write_mouse_report(button, &event, false)?;
 
         Ok(())
     }
@@ -193,50 +191,7 @@ impl TerminalState {
         if !self.current_mouse_buttons.is_empty() {
             self.current_mouse_buttons.retain(|&b| b != button);
             if self.mouse_tracking || self.button_event_mouse || self.any_event_mouse {
-                if self.mouse_encoding == MouseEncoding::SGR {
-                    log::trace!(
-                        "release {event:?} ESC [<{};{};{}m",
-                        release_button,
-                        event.x + 1,
-                        event.y + 1
-                    );
-                    write!(
-                        self.writer,
-                        "\x1b[<{};{};{}m",
-                        release_button,
-                        event.x + 1,
-                        event.y + 1
-                    )?;
-                    self.writer.flush()?;
-                } else if self.mouse_encoding == MouseEncoding::SgrPixels {
-                    let height = self.screen.physical_rows as usize;
-                    let width = self.screen.physical_cols as usize;
-                    log::trace!(
-                        "release {event:?} ESC [<{};{};{}m",
-                        release_button,
-                        (event.x * (self.pixel_width / width))
-                            + event.x_pixel_offset.max(0) as usize
-                            + 1,
-                        (event.y as usize * (self.pixel_height / height))
-                            + event.y_pixel_offset.max(0) as usize
-                            + 1
-                    );
-                    write!(
-                        self.writer,
-                        "\x1b[<{};{};{}m",
-                        release_button,
-                        (event.x * (self.pixel_width / width))
-                            + event.x_pixel_offset.max(0) as usize
-                            + 1,
-                        (event.y as usize * (self.pixel_height / height))
-                            + event.y_pixel_offset.max(0) as usize
-                            + 1
-                    )?;
-                    self.writer.flush()?;
-                } else {
-                    let release_button = 3;
-                    self.encode_x10_or_utf8(event, release_button)?;
-                }
+                self.write_mouse_report(release_button, &event, true)?;
             }
         }
 
@@ -269,49 +224,7 @@ impl TerminalState {
             let (button, _button) = self.mouse_report_button_number(&event);
             let button = 32 + button;
 
-            if self.mouse_encoding == MouseEncoding::SGR {
-                log::trace!(
-                    "move {event:?} ESC [<{};{};{}M",
-                    button,
-                    event.x + 1,
-                    event.y + 1
-                );
-                write!(
-                    self.writer,
-                    "\x1b[<{};{};{}M",
-                    button,
-                    event.x + 1,
-                    event.y + 1
-                )?;
-                self.writer.flush()?;
-            } else if self.mouse_encoding == MouseEncoding::SgrPixels {
-                let height = self.screen.physical_rows as usize;
-                let width = self.screen.physical_cols as usize;
-                log::trace!(
-                    "move {event:?} ESC [<{};{};{}M",
-                    button,
-                    (event.x * (self.pixel_width / width))
-                        + event.x_pixel_offset.max(0) as usize
-                        + 1,
-                    (event.y as usize * (self.pixel_height / height))
-                        + event.y_pixel_offset.max(0) as usize
-                        + 1
-                );
-                write!(
-                    self.writer,
-                    "\x1b[<{};{};{}M",
-                    button,
-                    (event.x * (self.pixel_width / width))
-                        + event.x_pixel_offset.max(0) as usize
-                        + 1,
-                    (event.y as usize * (self.pixel_height / height))
-                        + event.y_pixel_offset.max(0) as usize
-                        + 1
-                )?;
-                self.writer.flush()?;
-            } else {
-                self.encode_x10_or_utf8(event, button)?;
-            }
+            self.write_mouse_report(button, &event, false)?;
         }
         Ok(())
     }
@@ -1,27 +1,37 @@
-use crate::terminal::Alert;
+use crate::color::SrgbaTuple;
+use crate::terminal::{Alert, NotificationAction};
 use crate::terminalstate::{
-    default_color_map, CharSet, MouseEncoding, TabStop, UnicodeVersionStackEntry,
+    default_color_map, path_to_file_url, CharSet, MouseEncoding, TabStop, UnicodeVersionStackEntry,
+};
+use crate::{
+    ClipboardSelection, MousePointerShape, Position, TerminalState, VisibleRowIndex, DCS, ST,
 };
-use crate::{ClipboardSelection, Position, TerminalState, VisibleRowIndex, DCS, ST};
 use finl_unicode::grapheme_clusters::Graphemes;
 use log::{debug, error};
 use num_traits::FromPrimitive;
 use ordered_float::NotNan;
+use std::collections::HashSet;
 use std::fmt::Write;
 use std::io::Write as _;
 use std::ops::{Deref, DerefMut};
-use termwiz::cell::{grapheme_column_width, Cell, CellAttributes, SemanticType};
+use std::sync::Mutex;
+use termwiz::cell::{
+    grapheme_column_width, split_grapheme_by_codepoint, Blink, Cell, CellAttributes, Intensity,
+    SemanticType, Underline, VerticalAlign,
+};
+use termwiz::color::ColorAttribute;
 use termwiz::escape::csi::{
-    CharacterPath, EraseInDisplay, Keyboard, KittyKeyboardFlags, KittyKeyboardMode,
+    CharacterPath, EraseInDisplay, Keyboard, KittyKeyboardFlags, KittyKeyboardMode, Sgr,
 };
 use termwiz::escape::osc::{
     ChangeColorPair, ColorOrQuery, FinalTermSemanticPrompt, ITermProprietary,
-    ITermUnicodeVersionOp, Selection,
+    ITermRequestAttention, ITermUnicodeVersionOp, Progress, Selection,
 };
 use termwiz::escape::{
     Action, ControlCode, DeviceControlMode, Esc, EscCode, OperatingSystemCommand, CSI,
 };
 use termwiz::input::KeyboardEncoding;
+use termwiz::surface::CursorShape;
 use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
 use url::Url;
 use wezterm_bidi::ParagraphDirectionHint;
@@ -129,86 +139,111 @@ impl<'a> Performer<'a> {
             p.as_str()
         };
 
+        // Arrival times are coarse, so one reading of the clock
+        // serves the whole batch
+        let arrival = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as u32)
+            .unwrap_or(0);
+        let mut marked_row = None;
+
+        let grapheme_clustering = self.screen.grapheme_clustering;
         for g in Graphemes::new(text) {
             let g = self.remap_grapheme(g);
+            let unicode_version = Some(self.unicode_version);
+            let cluster =
+                grapheme_clustering.then(|| (g, grapheme_column_width(g, unicode_version)));
+            // Mode 2027 is reset: each codepoint is
+            // given cells of its own
+            let codepoints = (!grapheme_clustering)
+                .then(|| split_grapheme_by_codepoint(g, unicode_version))
+                .into_iter()
+                .flatten();
 
-            let print_width = grapheme_column_width(g, Some(self.unicode_version));
-            if print_width == 0 {
-                // We got a zero-width grapheme.
-                // We used to force them into a cell to guarantee that we
-                // preserved them in the model, but it introduces presentation
-                // problems, such as <https://github.com/wez/wezterm/issues/1422>
-                log::trace!("Eliding zero-width grapheme {:?}", g);
-                continue;
-            }
-
-            if self.wrap_next {
-                // Since we're implicitly moving the cursor to the next
-                // line, we need to tag the current position as wrapped
-                // so that we can correctly reflow it if the window is
-                // resized.
-                {
-                    let y = self.cursor.y;
-                    let is_conpty = self.state.enable_conpty_quirks;
-                    let screen = self.screen_mut();
-                    let y = screen.phys_row(y);
+            for (g, print_width) in cluster.into_iter().chain(codepoints) {
+                if print_width == 0 {
+                    // We got a zero-width grapheme.
+                    // We used to force them into a cell to guarantee that we
+                    // preserved them in the model, but it introduces presentation
+                    // problems, such as <https://github.com/wez/wezterm/issues/1422>
+                    log::trace!("Eliding zero-width grapheme {:?}", g);
+                    continue;
+                }
+
+                if self.wrap_next {
+                    // Since we're implicitly moving the cursor to the next
+                    // line, we need to tag the current position as wrapped
+                    // so that we can correctly reflow it if the window is
+                    // resized.
+                    {
+                        let y = self.cursor.y;
+                        let is_conpty = self.state.enable_conpty_quirks;
+                        let screen = self.screen_mut();
+                        let y = screen.phys_row(y);
 
-                    fn makes_sense_to_wrap(s: &str) -> bool {
-                        let len = s.len();
-                        match (len, s.chars().next()) {
-                            (1, Some(c)) => c.is_alphanumeric() || c.is_ascii_punctuation(),
-                            _ => true,
+                        fn makes_sense_to_wrap(s: &str) -> bool {
+                            let len = s.len();
+                            match (len, s.chars().next()) {
+                                (1, Some(c)) => c.is_alphanumeric() || c.is_ascii_punctuation(),
+                                _ => true,
+                            }
                         }
-                    }
 
-                    let should_mark_wrapped = !is_conpty
-                        || screen
-                            .line_mut(y)
-                            .visible_cells()
-                            .last()
-                            .map(|cell| makes_sense_to_wrap(cell.str()))
-                            .unwrap_or(false);
-                    if should_mark_wrapped {
-                        screen.line_mut(y).set_last_cell_was_wrapped(true, seqno);
+                        let should_mark_wrapped = !is_conpty
+                            || screen
+                                .line_mut(y)
+                                .visible_cells()
+                                .last()
+                                .map(|cell| makes_sense_to_wrap(cell.str()))
+                                .unwrap_or(false);
+                        if should_mark_wrapped {
+                            screen.line_mut(y).set_last_cell_was_wrapped(true, seqno);
+                        }
                     }
+                    self.new_line(true);
+                    // Scrolling may have put a fresh line at the same row
+                    marked_row = None;
                 }
-                self.new_line(true);
-            }
 
-            let x = self.cursor.x;
-            let y = self.cursor.y;
-            let width = self.left_and_right_margins.end;
+                let x = self.cursor.x;
+                let y = self.cursor.y;
+                let width = self.left_and_right_margins.end;
 
-            let pen = self.pen.clone();
+                let pen = self.pen.clone();
 
-            let wrappable = x + print_width >= width;
+                let wrappable = x + print_width >= width;
 
-            if self.insert {
-                let margin = self.left_and_right_margins.end;
-                let screen = self.screen_mut();
-                for _ in x..x + print_width as usize {
-                    screen.insert_cell(x, y, margin, seqno);
+                if self.insert {
+                    let margin = self.left_and_right_margins.end;
+                    let screen = self.screen_mut();
+                    for _ in x..x + print_width as usize {
+                        screen.insert_cell(x, y, margin, seqno);
+                    }
                 }
-            }
 
-            // Assign the cell
-            log::trace!(
-                "print x={} y={} print_width={} width={} cell={} {:?}",
-                x,
-                y,
-                print_width,
-                width,
-                g,
-                self.pen
-            );
-            self.screen_mut()
-                .set_cell_grapheme(x, y, g, print_width, pen, seqno);
+                // Assign the cell
+                log::trace!(
+                    "print x={} y={} print_width={} width={} cell={} {:?}",
+                    x,
+                    y,
+                    print_width,
+                    width,
+                    g,
+                    self.pen
+                );
+                self.screen_mut()
+                    .set_cell_grapheme(x, y, g, print_width, pen, seqno);
+                if marked_row != Some(y) {
+                    self.screen_mut().mark_arrival(y, arrival);
+                    marked_row = Some(y);
+                }
 
-            if !wrappable {
-                self.cursor.x += print_width;
-                self.wrap_next = false;
-            } else {
-                self.wrap_next = self.dec_auto_wrap;
+                if !wrappable {
+                    self.cursor.x += print_width;
+                    self.wrap_next = false;
+                } else {
+                    self.wrap_next = self.dec_auto_wrap;
+                }
             }
         }
 
@@ -230,6 +265,17 @@ impl<'a> Performer<'a> {
         }
     }
 
+    /// Records the completion of a command, as reported by OSC 133;D,
+    /// at the cursor position
+    fn set_command_status(&mut self, status: Option<i32>) {
+        let seqno = self.seqno;
+        let x = self.cursor.x;
+        let idx = self.screen.phys_row(self.cursor.y);
+        self.screen
+            .line_mut(idx)
+            .set_command_status(x, status, seqno);
+    }
+
     pub fn perform(&mut self, action: Action) {
         debug!("perform {:?}", action);
         if self.suppress_initial_title_change {
@@ -301,6 +347,26 @@ impl<'a> Performer<'a> {
                                 .ok();
                                 self.writer.flush().ok();
                             }
+                            &[b'm'] => {
+                                // SGR - the current graphic rendition
+                                write!(self.writer, "{}1$r{}m{}", DCS, sgr_report(&self.pen), ST)
+                                    .ok();
+                                self.writer.flush().ok();
+                            }
+                            &[b' ', b'q'] => {
+                                // DECSCUSR - cursor style
+                                let style = match self.cursor.shape {
+                                    CursorShape::Default => 0,
+                                    CursorShape::BlinkingBlock => 1,
+                                    CursorShape::SteadyBlock => 2,
+                                    CursorShape::BlinkingUnderline => 3,
+                                    CursorShape::SteadyUnderline => 4,
+                                    CursorShape::BlinkingBar => 5,
+                                    CursorShape::SteadyBar => 6,
+                                };
+                                write!(self.writer, "{}1$r{} q{}", DCS, style, ST).ok();
+                                self.writer.flush().ok();
+                            }
                             &[b's'] => {
                                 // DECSLRM - left and right margins
                                 let margins = self.left_and_right_margins.clone();
@@ -667,6 +733,8 @@ impl<'a> Performer<'a> {
                 self.dec_auto_wrap = true;
                 self.reverse_wraparound_mode = false;
                 self.reverse_video_mode = false;
+                self.synchronized_output = false;
+                self.mouse_pointer_shape = None;
                 self.dec_origin_mode = false;
                 self.use_private_color_registers_for_each_graphic = false;
                 self.color_map = default_color_map();
@@ -694,8 +762,10 @@ impl<'a> Performer<'a> {
                 self.left_and_right_margins = 0..self.screen().physical_cols;
                 self.unicode_version = self.config.unicode_version();
                 self.unicode_version_stack.clear();
+                self.screen.grapheme_clustering = self.config.grapheme_clustering();
                 self.suppress_initial_title_change = false;
                 self.accumulating_title.take();
+                self.set_progress(Progress::None);
 
                 self.screen.full_reset();
                 self.screen.activate_alt_screen(seqno);
@@ -833,9 +903,30 @@ impl<'a> Performer<'a> {
                         }
                     }
                 }
+                ITermProprietary::SetColors { key, value } if self.config.accept_iterm2_osc() => {
+                    self.set_iterm2_color(&key, &value);
+                }
+                ITermProprietary::RequestAttention(attention)
+                    if self.config.accept_iterm2_osc() =>
+                {
+                    match attention {
+                        ITermRequestAttention::Yes
+                        | ITermRequestAttention::Once
+                        | ITermRequestAttention::Fireworks => {
+                            // We don't have a separate notion of urgency,
+                            // so treat this the same way as the bell
+                            if let Some(handler) = self.alert_handler.as_mut() {
+                                handler.alert(Alert::Bell);
+                            }
+                        }
+                        ITermRequestAttention::No => {}
+                    }
+                }
                 _ => {
                     if self.config.log_unknown_escape_sequences() {
                         log::warn!("unhandled iterm2: {:?}", iterm);
+                    } else {
+                        log_ignored_iterm2(&iterm);
                     }
                 }
             },
@@ -878,8 +969,15 @@ impl<'a> Performer<'a> {
             }
 
             OperatingSystemCommand::FinalTermSemanticPrompt(
-                FinalTermSemanticPrompt::CommandStatus { .. },
-            ) => {}
+                FinalTermSemanticPrompt::CommandStatus { status, .. },
+            ) => {
+                self.set_command_status(Some(status));
+            }
+            OperatingSystemCommand::FinalTermSemanticPrompt(
+                FinalTermSemanticPrompt::CommandStatusUnknown { .. },
+            ) => {
+                self.set_command_status(None);
+            }
 
             OperatingSystemCommand::SystemNotification(message) => {
                 if let Some(handler) = self.alert_handler.as_mut() {
@@ -887,11 +985,15 @@ impl<'a> Performer<'a> {
                         title: None,
                         body: message,
                         focus: true,
+                        actions: vec![],
                     });
                 } else {
                     log::info!("Application sends SystemNotification: {}", message);
                 }
             }
+            OperatingSystemCommand::ConEmuProgress(progress) => {
+                self.set_progress(progress);
+            }
             OperatingSystemCommand::RxvtExtension(params) => {
                 if let Some("notify") = params.get(0).map(String::as_str) {
                     let title = params.get(1);
@@ -904,21 +1006,61 @@ impl<'a> Performer<'a> {
                             return;
                         }
                     };
+                    let actions = parse_notification_actions(params.get(3..).unwrap_or(&[]));
                     if let Some(handler) = self.alert_handler.as_mut() {
                         handler.alert(Alert::ToastNotification {
                             title,
                             body,
                             focus: true,
+                            actions,
                         });
                     }
                 }
             }
+            OperatingSystemCommand::SetMousePointerShape(name) => {
+                if let Some(names) = name.strip_prefix('?') {
+                    // Report whether each of the shapes is supported
+                    let supported = names
+                        .split(',')
+                        .map(|name| match MousePointerShape::from_name(name) {
+                            Some(_) => "1",
+                            None => "0",
+                        })
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    let response = OperatingSystemCommand::SetMousePointerShape(supported);
+                    write!(self.writer, "{}", response).ok();
+                    self.writer.flush().ok();
+                    return;
+                }
+                // kitty prefixes the name with `=` to set it
+                let name = name.strip_prefix('=').unwrap_or(&name);
+                if name.is_empty() {
+                    self.mouse_pointer_shape = None;
+                } else {
+                    match MousePointerShape::from_name(name) {
+                        Some(shape) => self.mouse_pointer_shape = Some(shape),
+                        None => debug!("ignoring request for mouse pointer shape {:?}", name),
+                    }
+                }
+            }
             OperatingSystemCommand::CurrentWorkingDirectory(url) => {
                 self.current_dir = Url::parse(&url).ok();
+                self.current_dir_from_osc7 = true;
                 if let Some(handler) = self.alert_handler.as_mut() {
                     handler.alert(Alert::CurrentWorkingDirectoryChanged);
                 }
             }
+            OperatingSystemCommand::ConEmuCurrentWorkingDirectory(path) => {
+                // OSC 7 carries the hostname too, so once the shell
+                // has used it we ignore this less precise form
+                if !self.current_dir_from_osc7 {
+                    self.current_dir = path_to_file_url(&path);
+                    if let Some(handler) = self.alert_handler.as_mut() {
+                        handler.alert(Alert::CurrentWorkingDirectoryChanged);
+                    }
+                }
+            }
             OperatingSystemCommand::ChangeColorNumber(specs) => {
                 log::trace!("ChangeColorNumber: {:?}", specs);
                 for pair in specs {
@@ -1059,6 +1201,198 @@ impl<'a> Performer<'a> {
     }
 }
 
+/// Logs that an iTerm2 directive is being ignored, but only the
+/// first time that each kind of directive is seen, so that tools
+/// that emit them frequently don't spam the log
+fn log_ignored_iterm2(iterm: &ITermProprietary) {
+    lazy_static::lazy_static! {
+        static ref LOGGED: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+    }
+    let debug = format!("{:?}", iterm);
+    let name: String = debug
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect();
+    if LOGGED.lock().unwrap().insert(name.clone()) {
+        log::info!(
+            "Ignoring iTerm2 directive {}; subsequent {} directives \
+             will be ignored without logging",
+            debug,
+            name
+        );
+    }
+}
+
+/// Parses the color value of an iTerm2 `SetColors` directive.
+/// This is a hex color of the form `RGB` or `RRGGBB`, optionally
+/// prefixed by a color space; we treat all color spaces as sRGB.
+/// Named presets are not supported.
+fn parse_iterm2_color(value: &str) -> Option<SrgbaTuple> {
+    let hex = match value.split_once(':') {
+        Some(("srgb" | "rgb" | "p3", hex)) => hex,
+        Some(_) => return None,
+        None => value,
+    };
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return None,
+    };
+    format!("#{}", hex).parse().ok()
+}
+
+/// The most buttons that a notification may have
+const MAX_NOTIFICATION_ACTIONS: usize = 8;
+
+/// The parameter that introduces the buttons of an OSC 777
+/// notification; anything else that follows the body is ignored,
+/// as it is by rxvt
+const NOTIFICATION_ACTIONS_MARKER: &str = "actions";
+
+/// Ids that the notification backends use for their own purposes:
+/// `default` is the DBus key for clicking on the notification itself,
+/// and `show` opens the url of a notification
+const RESERVED_NOTIFICATION_ACTION_IDS: &[&str] = &["default", "show"];
+
+/// Parses the parameters that follow the body of an OSC 777
+/// notification.  When the first of them is `actions`, the rest are
+/// `id=label` buttons; an `id` without a label is used as its own
+/// label.  Ids must be non-empty, must not be reserved and must not
+/// contain control characters, as they are echoed back in the reply.
+fn parse_notification_actions(params: &[String]) -> Vec<NotificationAction> {
+    let params = match params.split_first() {
+        Some((marker, params)) if marker == NOTIFICATION_ACTIONS_MARKER => params,
+        Some(_) => {
+            log::debug!("ignoring extra notification parameters: {:?}", params);
+            return vec![];
+        }
+        None => return vec![],
+    };
+    let mut actions = vec![];
+    for param in params {
+        let (id, label) = param.split_once('=').unwrap_or((param, param));
+        if id.is_empty()
+            || id.chars().any(char::is_control)
+            || RESERVED_NOTIFICATION_ACTION_IDS.contains(&id)
+        {
+            log::warn!("ignoring invalid notification action: {:?}", param);
+            continue;
+        }
+        if actions.len() == MAX_NOTIFICATION_ACTIONS {
+            log::warn!(
+                "ignoring notification actions beyond the first {}",
+                MAX_NOTIFICATION_ACTIONS
+            );
+            break;
+        }
+        actions.push(NotificationAction {
+            id: id.to_string(),
+            label: label.to_string(),
+        });
+    }
+    actions
+}
+
+impl<'a> Performer<'a> {
+    /// Applies an iTerm2 `SetColors` directive to the palette
+    fn set_iterm2_color(&mut self, key: &str, value: &str) {
+        let color = match parse_iterm2_color(value) {
+            Some(color) => color,
+            None => {
+                log::debug!("Ignoring iTerm2 SetColors {}={}", key, value);
+                return;
+            }
+        };
+
+        const ANSI: [&str; 8] = [
+            "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+        ];
+        let palette = self.palette_mut();
+        match key {
+            "fg" => palette.foreground = color,
+            "bg" => palette.background = color,
+            "selbg" => palette.selection_bg = color,
+            "selfg" => palette.selection_fg = color,
+            "curbg" => {
+                // As for OSC 12, the border follows the cursor color
+                palette.cursor_bg = color;
+                palette.cursor_border = color;
+            }
+            "curfg" => palette.cursor_fg = color,
+            _ => {
+                let (bright, name) = match key.strip_prefix("br_") {
+                    Some(name) => (true, name),
+                    None => (false, key),
+                };
+                match ANSI.iter().position(|&ansi| ansi == name) {
+                    Some(idx) => palette.colors.0[idx + if bright { 8 } else { 0 }] = color,
+                    None => {
+                        log::debug!("Ignoring iTerm2 SetColors for unsupported key {}", key);
+                        return;
+                    }
+                }
+            }
+        }
+        self.implicit_palette_reset_if_same_as_configured();
+        self.palette_did_change();
+    }
+}
+
+/// Returns the SGR parameters that reproduce `pen`, for the DECRQSS
+/// response.  The parameters start with a reset, as xterm's do, so
+/// that an application can replay them as-is.
+fn sgr_report(pen: &CellAttributes) -> String {
+    let mut sgrs = vec![Sgr::Reset];
+    if pen.intensity() != Intensity::Normal {
+        sgrs.push(Sgr::Intensity(pen.intensity()));
+    }
+    if pen.underline() != Underline::None {
+        sgrs.push(Sgr::Underline(pen.underline()));
+    }
+    if pen.blink() != Blink::None {
+        sgrs.push(Sgr::Blink(pen.blink()));
+    }
+    if pen.italic() {
+        sgrs.push(Sgr::Italic(true));
+    }
+    if pen.reverse() {
+        sgrs.push(Sgr::Inverse(true));
+    }
+    if pen.invisible() {
+        sgrs.push(Sgr::Invisible(true));
+    }
+    if pen.strikethrough() {
+        sgrs.push(Sgr::StrikeThrough(true));
+    }
+    if pen.overline() {
+        sgrs.push(Sgr::Overline(true));
+    }
+    if pen.vertical_align() != VerticalAlign::BaseLine {
+        sgrs.push(Sgr::VerticalAlign(pen.vertical_align()));
+    }
+    if pen.foreground() != ColorAttribute::Default {
+        sgrs.push(Sgr::Foreground(pen.foreground().into()));
+    }
+    if pen.background() != ColorAttribute::Default {
+        sgrs.push(Sgr::Background(pen.background().into()));
+    }
+    if pen.underline_color() != ColorAttribute::Default {
+        sgrs.push(Sgr::UnderlineColor(pen.underline_color().into()));
+    }
+
+    sgrs.iter()
+        .map(|sgr| {
+            let sgr = sgr.to_string();
+            sgr.strip_suffix('m').unwrap_or(&sgr).to_string()
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
 fn selection_to_selection(sel: Selection) -> ClipboardSelection {
     match sel {
         Selection::CLIPBOARD => ClipboardSelection::Clipboard,
@@ -8,9 +8,14 @@ use super::*;
 fn test_789() {
     let mut term = TestTerm::new(1, 8, 0);
     term.print("\x1b[40m\x1b[Kfoo\x1b[2P");
+    // The arrival time depends on when the test runs
+    let mut lines = term.screen().visible_lines();
+    for line in &mut lines {
+        line.set_arrival_time(None);
+    }
 
     k9::snapshot!(
-        term.screen().visible_lines(),
+        lines,
         r#"
 [
     Line {
@@ -201,6 +206,7 @@ fn test_789() {
         bits: LineBits(
             0x0,
         ),
+        marks: None,
         appdata: Mutex {
             data: None,
             poisoned: false,
@@ -401,3 +407,48 @@ fn test_ed_erase_scrollback() {
     term.print("b");
     assert_all_contents(&term, file!(), line!(), &["111", "222", "ab"]);
 }
+
+#[test]
+fn kitty_keyboard_stack_per_screen() {
+    use termwiz::escape::csi::KittyKeyboardFlags;
+    use termwiz::input::KeyboardEncoding;
+
+    let mut term = TestTerm::with_kitty_keyboard(3, 4, 0);
+    let disambiguate = KeyboardEncoding::Kitty(KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES);
+    let all = KeyboardEncoding::Kitty(KittyKeyboardFlags::from_bits_truncate(15));
+
+    term.print("\x1b[>1u");
+    assert_eq!(term.get_keyboard_encoding(), disambiguate);
+
+    // The alternate screen has its own stack
+    term.print("\x1b[?1049h");
+    assert_eq!(term.get_keyboard_encoding(), KeyboardEncoding::Xterm);
+    term.print("\x1b[>15u");
+    assert_eq!(term.get_keyboard_encoding(), all);
+
+    // Leaving the alternate screen restores the primary stack
+    // and discards what was pushed on the alternate screen
+    term.print("\x1b[?1049l");
+    assert_eq!(term.get_keyboard_encoding(), disambiguate);
+    term.print("\x1b[?1049h");
+    assert_eq!(term.get_keyboard_encoding(), KeyboardEncoding::Xterm);
+    term.print("\x1b[?1049l");
+
+    term.print("\x1b[<u");
+    assert_eq!(term.get_keyboard_encoding(), KeyboardEncoding::Xterm);
+
+    term.print("\x1b[>1u");
+    term.reset_keyboard_encoding();
+    assert_eq!(term.get_keyboard_encoding(), KeyboardEncoding::Xterm);
+}
+
+#[test]
+fn kitty_keyboard_disabled_by_default() {
+    use termwiz::input::KeyboardEncoding;
+
+    let mut term = TestTerm::new(3, 4, 0);
+    term.print("\x1b[>1u");
+    assert_eq!(term.get_keyboard_encoding(), KeyboardEncoding::Xterm);
+    term.print("\x1b[=1u");
+    assert_eq!(term.get_keyboard_encoding(), KeyboardEncoding::Xterm);
+}
@@ -0,0 +1,46 @@
+//! Testing the OSC 7 and OSC 9;9 working directory sequences
+
+use super::*;
+
+fn cwd_path(term: &TestTerm) -> Option<String> {
+    term.get_current_dir().map(|url| url.path().to_string())
+}
+
+#[test]
+fn test_conemu_cwd_precedence() {
+    let mut term = TestTerm::new(3, 10, 0);
+    assert_eq!(cwd_path(&term), None);
+
+    term.print("\x1b]9;9;\"C:\\Users\\wez\"\x1b\\");
+    assert_eq!(cwd_path(&term), Some("/C:/Users/wez".to_string()));
+
+    term.print("\x1b]7;file://host/C:/Windows\x1b\\");
+    assert_eq!(cwd_path(&term), Some("/C:/Windows".to_string()));
+
+    // Once OSC 7 has been used, OSC 9;9 is ignored
+    term.print("\x1b]9;9;\"D:\\other\"\x1b\\");
+    assert_eq!(cwd_path(&term), Some("/C:/Windows".to_string()));
+}
+
+#[test]
+fn test_path_to_file_url() {
+    let url = path_to_file_url("c:\\Program Files\\app").unwrap();
+    assert_eq!(url.as_str(), "file:///C:/Program%20Files/app");
+
+    let url = path_to_file_url("\\\\?\\C:\\long\\path").unwrap();
+    assert_eq!(url.path(), "/C:/long/path");
+
+    let url = path_to_file_url("\\\\server\\share\\dir").unwrap();
+    assert_eq!(url.host_str(), Some("server"));
+    assert_eq!(url.path(), "/share/dir");
+
+    let url = path_to_file_url("\\\\?\\UNC\\server\\share").unwrap();
+    assert_eq!(url.host_str(), Some("server"));
+    assert_eq!(url.path(), "/share");
+
+    let url = path_to_file_url("/home/wez").unwrap();
+    assert_eq!(url.path(), "/home/wez");
+
+    assert_eq!(path_to_file_url("relative\\dir"), None);
+    assert_eq!(path_to_file_url(""), None);
+}
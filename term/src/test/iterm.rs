@@ -0,0 +1,21 @@
+//! Testing the iTerm2 proprietary OSC 1337 directives
+
+use super::*;
+use crate::color::SrgbaTuple;
+
+#[test]
+fn test_set_colors() {
+    let mut term = TestTerm::new(3, 10, 0);
+
+    term.print("\x1b]1337;SetColors=fg=ff0000\x07");
+    assert_eq!(term.palette().foreground, SrgbaTuple::from((255, 0, 0)));
+
+    term.print("\x1b]1337;SetColors=br_blue=\"srgb:0f0\"\x07");
+    assert_eq!(term.palette().colors.0[12], SrgbaTuple::from((0, 255, 0)));
+
+    // Presets and unknown keys are ignored
+    let before = term.palette();
+    term.print("\x1b]1337;SetColors=preset=Solarized Dark\x07");
+    term.print("\x1b]1337;SetColors=fg=notacolor\x07");
+    assert_eq!(term.palette(), before);
+}
@@ -6,6 +6,12 @@ mod c0;
 use bitflags::bitflags;
 mod c1;
 mod csi;
+mod cwd;
+mod iterm;
+mod mouse;
+mod notify;
+mod reports;
+mod snapshot;
 // mod selection; FIXME: port to render layer
 use crate::color::ColorPalette;
 use k9::assert_equal as assert_eq;
@@ -38,13 +44,53 @@ impl Clipboard for LocalClip {
     }
 }
 
+/// Collects the replies that the terminal sends to the application.
+/// The terminal writes from a separate thread, so readers wait for
+/// the flush that completes each reply.
+#[derive(Clone, Default)]
+struct ReplyBuffer {
+    inner: Arc<(Mutex<(Vec<u8>, usize)>, std::sync::Condvar)>,
+}
+
+impl std::io::Write for ReplyBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.0.lock().unwrap().0.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.0.lock().unwrap().1 += 1;
+        self.inner.1.notify_all();
+        Ok(())
+    }
+}
+
+impl ReplyBuffer {
+    /// Waits for `flushes` replies to be flushed, then takes
+    /// everything that was written
+    fn take(&self, flushes: usize) -> String {
+        let (lock, cvar) = &*self.inner;
+        let (mut state, _) = cvar
+            .wait_timeout_while(
+                lock.lock().unwrap(),
+                std::time::Duration::from_secs(5),
+                |(_, flushed)| *flushed < flushes,
+            )
+            .unwrap();
+        state.1 = state.1.saturating_sub(flushes);
+        String::from_utf8(std::mem::take(&mut state.0)).unwrap()
+    }
+}
+
 struct TestTerm {
     term: Terminal,
+    replies: ReplyBuffer,
 }
 
 #[derive(Debug)]
 struct TestTermConfig {
     scrollback: usize,
+    kitty_keyboard: bool,
 }
 impl TerminalConfiguration for TestTermConfig {
     fn scrollback_size(&self) -> usize {
@@ -54,15 +100,43 @@ impl TerminalConfiguration for TestTermConfig {
     fn color_palette(&self) -> ColorPalette {
         ColorPalette::default()
     }
+
+    fn enable_kitty_keyboard(&self) -> bool {
+        self.kitty_keyboard
+    }
 }
 
 impl TestTerm {
     fn new(height: usize, width: usize, scrollback: usize) -> Self {
+        Self::with_config(
+            height,
+            width,
+            TestTermConfig {
+                scrollback,
+                kitty_keyboard: false,
+            },
+        )
+    }
+
+    /// Like `new`, but with `enable_kitty_keyboard` turned on
+    fn with_kitty_keyboard(height: usize, width: usize, scrollback: usize) -> Self {
+        Self::with_config(
+            height,
+            width,
+            TestTermConfig {
+                scrollback,
+                kitty_keyboard: true,
+            },
+        )
+    }
+
+    fn with_config(height: usize, width: usize, config: TestTermConfig) -> Self {
         let _ = env_logger::Builder::new()
             .is_test(true)
             .filter_level(log::LevelFilter::Trace)
             .try_init();
 
+        let replies = ReplyBuffer::default();
         let mut term = Terminal::new(
             TerminalSize {
                 rows: height,
@@ -71,15 +145,15 @@ impl TestTerm {
                 pixel_height: height * 16,
                 dpi: 0,
             },
-            Arc::new(TestTermConfig { scrollback }),
+            Arc::new(config),
             "WezTerm",
             "O_o",
-            Box::new(Vec::new()),
+            Box::new(replies.clone()),
         );
         let clip: Arc<dyn Clipboard> = Arc::new(LocalClip::new());
         term.set_clipboard(&clip);
 
-        let mut term = Self { term };
+        let mut term = Self { term, replies };
 
         term.set_auto_wrap(true);
 
@@ -333,6 +407,7 @@ fn test_semantic_1539() {
         end_y: 0,
         end_x: 5,
         semantic_type: Input,
+        command_status: None,
     },
     SemanticZone {
         start_y: 1,
@@ -340,6 +415,7 @@ fn test_semantic_1539() {
         end_y: 1,
         end_x: 3,
         semantic_type: Output,
+        command_status: None,
     },
 ]
 "
@@ -382,6 +458,7 @@ fn test_semantic() {
         end_y: 2,
         end_x: 4,
         semantic_type: Output,
+        command_status: None,
     },
 ]
 "
@@ -438,6 +515,7 @@ fn test_semantic() {
         end_y: 2,
         end_x: 4,
         semantic_type: Output,
+        command_status: None,
     },
     SemanticZone {
         start_y: 3,
@@ -445,6 +523,7 @@ fn test_semantic() {
         end_y: 3,
         end_x: 1,
         semantic_type: Prompt,
+        command_status: None,
     },
     SemanticZone {
         start_y: 3,
@@ -452,6 +531,7 @@ fn test_semantic() {
         end_y: 3,
         end_x: 6,
         semantic_type: Input,
+        command_status: None,
     },
     SemanticZone {
         start_y: 4,
@@ -459,6 +539,7 @@ fn test_semantic() {
         end_y: 4,
         end_x: 8,
         semantic_type: Output,
+        command_status: None,
     },
 ]
 "
@@ -479,6 +560,88 @@ fn test_semantic() {
     );
 }
 
+#[test]
+fn test_semantic_command_status() {
+    use termwiz::escape::osc::FinalTermSemanticPrompt;
+    let mut term = TestTerm::new(10, 10, 0);
+
+    let mut run_command = |command: &str, status: Option<i32>| {
+        term.print(format!(
+            "{}> {}{}\r\n{}output\r\n{}",
+            OperatingSystemCommand::FinalTermSemanticPrompt(
+                FinalTermSemanticPrompt::FreshLineAndStartPrompt {
+                    aid: None,
+                    cl: None
+                }
+            ),
+            OperatingSystemCommand::FinalTermSemanticPrompt(
+                FinalTermSemanticPrompt::MarkEndOfPromptAndStartOfInputUntilNextMarker
+            ),
+            command,
+            OperatingSystemCommand::FinalTermSemanticPrompt(
+                FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { aid: None }
+            ),
+            OperatingSystemCommand::FinalTermSemanticPrompt(match status {
+                Some(status) => FinalTermSemanticPrompt::CommandStatus { status, aid: None },
+                None => FinalTermSemanticPrompt::CommandStatusUnknown { aid: None },
+            }),
+        ));
+    };
+
+    run_command("false", Some(1));
+    run_command("true", Some(0));
+    run_command("what", None);
+    // The prompt for the next command has no status yet
+    term.print(format!(
+        "{}> ",
+        OperatingSystemCommand::FinalTermSemanticPrompt(
+            FinalTermSemanticPrompt::FreshLineAndStartPrompt {
+                aid: None,
+                cl: None
+            }
+        )
+    ));
+
+    let zones: Vec<(SemanticType, Option<CommandStatus>)> = term
+        .get_semantic_zones()
+        .unwrap()
+        .into_iter()
+        .map(|zone| (zone.semantic_type, zone.command_status))
+        .collect();
+
+    use CommandStatus::*;
+    use SemanticType::*;
+    assert_eq!(
+        zones,
+        vec![
+            (Prompt, Some(Exited(1))),
+            (Input, Some(Exited(1))),
+            (Output, Some(Exited(1))),
+            (Prompt, Some(Exited(0))),
+            (Input, Some(Exited(0))),
+            (Output, Some(Exited(0))),
+            (Prompt, Some(Unknown)),
+            (Input, Some(Unknown)),
+            (Output, Some(Unknown)),
+            (Prompt, None),
+        ]
+    );
+
+    let history: Vec<(StableRowIndex, bool, Option<CommandStatus>)> =
+        CommandRecord::from_zones(&term.get_semantic_zones().unwrap())
+            .into_iter()
+            .map(|record| (record.input.start_y, record.output.is_some(), record.status))
+            .collect();
+    assert_eq!(
+        history,
+        vec![
+            (0, true, Some(Exited(1))),
+            (2, true, Some(Exited(0))),
+            (4, true, Some(Unknown)),
+        ]
+    );
+}
+
 #[test]
 fn issue_1161() {
     let mut term = TestTerm::new(1, 5, 0);
@@ -1162,6 +1325,62 @@ fn test_emoji_with_modifier() {
     );
 }
 
+#[test]
+fn test_grapheme_clustering_mode() {
+    // man ZWJ woman ZWJ girl
+    let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+    let flag = "\u{1f1fa}\u{1f1f8}";
+    let version = Some(UnicodeVersion {
+        version: 9,
+        ambiguous_are_wide: false,
+    });
+    let flag_cluster_width = grapheme_column_width(flag, version);
+    let flag_codepoint_width = split_grapheme_by_codepoint(flag, version)
+        .into_iter()
+        .map(|(_piece, width)| width)
+        .sum::<usize>();
+
+    let mut term = TestTerm::new(4, 20, 0);
+    term.print("\x1b[?2027$p");
+    assert_eq!(term.replies.take(1), "\x1b[?2027;1$y");
+
+    term.print(family);
+    term.assert_cursor_pos(2, 0, Some("family is one cluster"), None);
+    term.print("\r\n");
+    term.print(flag);
+    term.assert_cursor_pos(flag_cluster_width, 1, Some("flag is one cluster"), None);
+
+    term.set_mode("?2027", false);
+    term.print("\x1b[?2027$p");
+    assert_eq!(term.replies.take(1), "\x1b[?2027;2$y");
+
+    term.print("\r\n");
+    term.print(family);
+    term.assert_cursor_pos(6, 2, Some("each member takes its own cells"), None);
+    term.print("\r\n");
+    term.print(flag);
+    term.assert_cursor_pos(flag_codepoint_width, 3, Some("each indicator"), None);
+
+    // Text that was printed under the mode keeps its width
+    assert_visible_contents(&term, file!(), line!(), &[family, flag, family, flag]);
+}
+
+#[test]
+fn test_grapheme_clustering_mode_alt_screen() {
+    let mut term = TestTerm::new(3, 10, 0);
+    term.set_mode("?2027", false);
+
+    term.set_mode("?1049", true);
+    assert!(!term.is_grapheme_clustering_enabled());
+    term.set_mode("?2027", true);
+    assert!(term.is_grapheme_clustering_enabled());
+
+    // Leaving the alternate screen restores the mode that
+    // the primary screen had
+    term.set_mode("?1049", false);
+    assert!(!term.is_grapheme_clustering_enabled());
+}
+
 #[test]
 fn test_1573() {
     let sequence = "\u{1112}\u{1161}\u{11ab}";
@@ -1265,3 +1484,34 @@ fn test_hyperlinks() {
         Compare::TEXT | Compare::ATTRS,
     );
 }
+
+#[test]
+fn test_sparse_arrival_times() {
+    let mut term = TestTerm::new(5, 5, 0);
+    let screen = term.screen_mut();
+    let arrivals = |screen: &Screen| -> Vec<Option<u32>> {
+        screen
+            .visible_lines()
+            .iter()
+            .map(|line| line.arrival_time())
+            .collect()
+    };
+
+    screen.mark_arrival(0, 100);
+    screen.mark_arrival(1, 100);
+    screen.mark_arrival(2, 200);
+    // Only the lines that start a new arrival time record it
+    assert_eq!(
+        arrivals(screen),
+        vec![Some(100), None, Some(200), None, None]
+    );
+
+    // Marking a line whose arrival time is shared by the line below
+    // leaves the line below with the one it had
+    screen.mark_arrival(4, 200);
+    screen.mark_arrival(3, 300);
+    assert_eq!(
+        arrivals(screen),
+        vec![Some(100), None, Some(200), Some(300), Some(200)]
+    );
+}
@@ -0,0 +1,135 @@
+//! Testing mouse report encoding and mouse pointer shape requests
+
+use super::*;
+
+fn event(x: usize, y: i64, x_pixel_offset: isize, y_pixel_offset: isize) -> MouseEvent {
+    MouseEvent {
+        kind: MouseEventKind::Press,
+        x,
+        y,
+        x_pixel_offset,
+        y_pixel_offset,
+        button: MouseButton::Left,
+        modifiers: KeyModifiers::NONE,
+    }
+}
+
+fn encode(term: &TestTerm, button: i8, event: MouseEvent, release: bool) -> String {
+    String::from_utf8_lossy(&term.encode_mouse_report(button, &event, release)).to_string()
+}
+
+#[test]
+fn test_mouse_encodings() {
+    // 80x24 with 8x16 pixel cells
+    let mut term = TestTerm::new(24, 80, 0);
+    term.set_mode("?1000", true);
+
+    let ev = event(5, 2, 3, 7);
+
+    // X10: button and 1-based coordinates offset by 32
+    assert_eq!(encode(&term, 0, ev, false), "\x1b[M &#");
+    assert_eq!(encode(&term, 0, ev, true), "\x1b[M#&#");
+    assert_eq!(encode(&term, 32, ev, false), "\x1b[M@&#");
+    assert_eq!(encode(&term, 64, ev, false), "\x1b[M`&#");
+
+    term.set_mode("?1006", true);
+    assert_eq!(encode(&term, 0, ev, false), "\x1b[<0;6;3M");
+    assert_eq!(encode(&term, 0, ev, true), "\x1b[<0;6;3m");
+    assert_eq!(encode(&term, 32, ev, false), "\x1b[<32;6;3M");
+    assert_eq!(encode(&term, 64, ev, false), "\x1b[<64;6;3M");
+
+    // The same events are reported with pixel precision in 1016
+    term.set_mode("?1016", true);
+    assert_eq!(encode(&term, 0, ev, false), "\x1b[<0;44;40M");
+    assert_eq!(encode(&term, 0, ev, true), "\x1b[<0;44;40m");
+    assert_eq!(encode(&term, 32, ev, false), "\x1b[<32;44;40M");
+    assert_eq!(encode(&term, 64, ev, false), "\x1b[<64;44;40M");
+
+    // Moving within a cell changes the 1016 report, but not the others
+    let nudged = event(5, 2, 4, 8);
+    assert_eq!(encode(&term, 32, nudged, false), "\x1b[<32;45;41M");
+    term.set_mode("?1006", true);
+    assert_eq!(encode(&term, 32, nudged, false), "\x1b[<32;6;3M");
+}
+
+#[test]
+fn test_mouse_sgr_pixels_clamped() {
+    let mut term = TestTerm::new(24, 80, 0);
+    term.set_mode("?1000", true);
+    term.set_mode("?1016", true);
+
+    // Positions outside of the terminal, such as when dragging
+    // beyond the edge of the pane, are clamped to the edges
+    assert_eq!(
+        encode(&term, 32, event(0, 0, -4, -9), false),
+        "\x1b[<32;1;1M"
+    );
+    assert_eq!(
+        encode(&term, 32, event(79, 23, 20, 30), false),
+        "\x1b[<32;640;384M"
+    );
+}
+
+#[test]
+fn test_mouse_sgr_pixels_fractional_cells() {
+    let mut term = TestTerm::new(24, 80, 0);
+    term.set_mode("?1000", true);
+    term.set_mode("?1016", true);
+
+    // Cells that are 12.5 pixels wide shouldn't cause the reported
+    // position to drift away from the pointer towards the right edge
+    term.resize(TerminalSize {
+        rows: 24,
+        cols: 80,
+        pixel_width: 1000,
+        pixel_height: 384,
+        dpi: 0,
+    });
+    assert_eq!(
+        encode(&term, 0, event(79, 0, 0, 0), false),
+        "\x1b[<0;988;1M"
+    );
+    assert_eq!(
+        encode(&term, 0, event(40, 0, 6, 0), false),
+        "\x1b[<0;507;1M"
+    );
+}
+
+#[test]
+fn test_mouse_pointer_shape() {
+    let mut term = TestTerm::new(3, 10, 0);
+    assert_eq!(term.mouse_pointer_shape(), None);
+
+    term.print("\x1b]22;pointer\x1b\\");
+    assert_eq!(term.mouse_pointer_shape(), Some(MousePointerShape::Pointer));
+
+    // The X11 glyph names used by xterm are understood too
+    term.print("\x1b]22;sb_h_double_arrow\x1b\\");
+    assert_eq!(
+        term.mouse_pointer_shape(),
+        Some(MousePointerShape::EwResize)
+    );
+
+    // Shapes that are not in the allowlist are ignored
+    term.print("\x1b]22;some-custom-shape\x1b\\");
+    assert_eq!(
+        term.mouse_pointer_shape(),
+        Some(MousePointerShape::EwResize)
+    );
+
+    term.print("\x1b]22;=crosshair\x1b\\");
+    assert_eq!(
+        term.mouse_pointer_shape(),
+        Some(MousePointerShape::Crosshair)
+    );
+
+    term.print("\x1b]22;\x1b\\");
+    assert_eq!(term.mouse_pointer_shape(), None);
+
+    term.print("\x1b]22;?text,bogus,grab\x1b\\");
+    assert_eq!(term.replies.take(1), "\x1b]22;1,0,1\x1b\\");
+
+    term.print("\x1b]22;wait\x1b\\");
+    term.print("\x1bc");
+    assert_eq!(term.mouse_pointer_shape(), None);
+}
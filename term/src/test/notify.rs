@@ -0,0 +1,106 @@
+//! Testing OSC 777 notifications and their buttons
+
+use super::*;
+
+#[derive(Clone, Default)]
+struct Alerts(Arc<Mutex<Vec<Alert>>>);
+
+impl AlertHandler for Alerts {
+    fn alert(&mut self, alert: Alert) {
+        self.0.lock().unwrap().push(alert);
+    }
+}
+
+/// Prints an OSC 777 notify sequence with the given parameters and
+/// returns the buttons of the resulting notification
+fn notify(params: &[&str]) -> Vec<NotificationAction> {
+    let mut term = TestTerm::new(3, 10, 0);
+    let alerts = Alerts::default();
+    term.set_notification_handler(Box::new(alerts.clone()));
+    term.print(format!("\x1b]777;notify;{}\x1b\\", params.join(";")));
+
+    let alerts = alerts.0.lock().unwrap();
+    match alerts.as_slice() {
+        [Alert::ToastNotification {
+            title,
+            body,
+            actions,
+            ..
+        }] => {
+            assert_eq!(title.as_deref(), Some("title"));
+            assert_eq!(body, "body");
+            actions.clone()
+        }
+        alerts => panic!("expected a single notification, got {:?}", alerts),
+    }
+}
+
+fn action(id: &str, label: &str) -> NotificationAction {
+    NotificationAction {
+        id: id.to_string(),
+        label: label.to_string(),
+    }
+}
+
+#[test]
+fn test_notification_actions() {
+    assert_eq!(notify(&["title", "body"]), vec![]);
+    assert_eq!(
+        notify(&["title", "body", "actions", "yes=Run tests", "no"]),
+        vec![action("yes", "Run tests"), action("no", "no")]
+    );
+}
+
+#[test]
+fn test_notification_actions_need_marker() {
+    // Without the marker, extra parameters are not buttons
+    assert_eq!(notify(&["title", "body", "yes=Run tests", "no"]), vec![]);
+    assert_eq!(notify(&["title", "body", "actions"]), vec![]);
+}
+
+#[test]
+fn test_notification_actions_invalid_ids() {
+    assert_eq!(
+        notify(&[
+            "title",
+            "body",
+            "actions",
+            "=Empty",
+            "default=Reserved",
+            "show",
+            "ok=Fine"
+        ]),
+        vec![action("ok", "Fine")]
+    );
+}
+
+#[test]
+fn test_notification_actions_limit() {
+    let ids: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+    let mut params = vec!["title", "body", "actions"];
+    params.extend(ids.iter().map(String::as_str));
+    let actions = notify(&params);
+    assert_eq!(actions.len(), 8);
+    assert_eq!(actions.last(), Some(&action("7", "7")));
+}
+
+#[test]
+fn test_notification_reply() {
+    assert_eq!(
+        NotificationAction::reply("yes"),
+        "\x1b]777;notify-reply;yes\x1b\\"
+    );
+
+    let mut writer = vec![];
+    assert!(send_notification_reply(Some(&mut writer), "yes").unwrap());
+    assert_eq!(
+        String::from_utf8(writer).unwrap(),
+        "\x1b]777;notify-reply;yes\x1b\\"
+    );
+}
+
+#[test]
+fn test_notification_reply_to_dead_pane() {
+    // The pane that raised the notification has gone away
+    assert!(!send_notification_reply::<Vec<u8>>(None, "yes").unwrap());
+}
@@ -0,0 +1,130 @@
+//! Testing the replies to DECRQM, DECRQSS, XTGETTCAP and XTWINOPS queries
+
+use super::*;
+use crate::terminalstate::modes::{DEC_PRIVATE_MODES, TERMINAL_MODES};
+use num_traits::ToPrimitive;
+
+/// Sends `query` and returns the reply, which is expected to be
+/// completed by a single flush
+fn query(term: &mut TestTerm, query: &str) -> String {
+    term.print(query);
+    term.replies.take(1)
+}
+
+#[test]
+fn test_decrqss_sgr() {
+    let mut term = TestTerm::new(3, 10, 0);
+    assert_eq!(query(&mut term, "\x1bP$qm\x1b\\"), "\x1bP1$r0m\x1b\\");
+
+    term.print("\x1b[1;3;4;31;48;5;100m");
+    assert_eq!(
+        query(&mut term, "\x1bP$qm\x1b\\"),
+        "\x1bP1$r0;1;4;3;31;48:5:100m\x1b\\"
+    );
+
+    term.print("\x1b[0;7;38:2::1:2:3m");
+    assert_eq!(
+        query(&mut term, "\x1bP$qm\x1b\\"),
+        "\x1bP1$r0;7;38:2::1:2:3m\x1b\\"
+    );
+}
+
+#[test]
+fn test_decrqss_cursor_style_and_margins() {
+    let mut term = TestTerm::new(3, 10, 0);
+    assert_eq!(query(&mut term, "\x1bP$q q\x1b\\"), "\x1bP1$r0 q\x1b\\");
+
+    term.print("\x1b[6 q");
+    assert_eq!(query(&mut term, "\x1bP$q q\x1b\\"), "\x1bP1$r6 q\x1b\\");
+
+    term.set_scroll_region(1, 2);
+    assert_eq!(query(&mut term, "\x1bP$qr\x1b\\"), "\x1bP1$r2;3r\x1b\\");
+
+    term.set_mode("?69", true);
+    term.set_left_and_right_margins(2, 7);
+    assert_eq!(query(&mut term, "\x1bP$qs\x1b\\"), "\x1bP1$r3;8s\x1b\\");
+
+    assert_eq!(query(&mut term, "\x1bP$qz\x1b\\"), "\x1bP0$r\x1b\\");
+}
+
+#[test]
+fn test_xtgettcap() {
+    let mut term = TestTerm::new(3, 10, 0);
+    // Co (colors) is numeric, am (auto margins) is boolean,
+    // and xyz is unknown
+    assert_eq!(
+        query(&mut term, "\x1bP+q436F;616D;78797A\x1b\\"),
+        "\x1bP1+r436F=323536\x1b\\\x1bP1+r616D\x1b\\\x1bP0+r78797A\x1b\\"
+    );
+}
+
+#[test]
+fn test_xtwinops_sizes() {
+    // 10 columns by 3 rows of 8x16 pixel cells
+    let mut term = TestTerm::new(3, 10, 0);
+    assert_eq!(query(&mut term, "\x1b[14t"), "\x1b[4;48;80t");
+    assert_eq!(query(&mut term, "\x1b[14;2t"), "\x1b[4;48;80t");
+    assert_eq!(query(&mut term, "\x1b[15t"), "\x1b[5;48;80t");
+    assert_eq!(query(&mut term, "\x1b[16t"), "\x1b[6;16;8t");
+    assert_eq!(query(&mut term, "\x1b[18t"), "\x1b[8;3;10t");
+    assert_eq!(query(&mut term, "\x1b[19t"), "\x1b[9;3;10t");
+    assert_eq!(query(&mut term, "\x1b[11t"), "\x1b[1t");
+
+    term.resize(TerminalSize {
+        rows: 4,
+        cols: 20,
+        pixel_width: 200,
+        pixel_height: 80,
+        dpi: 0,
+    });
+    assert_eq!(query(&mut term, "\x1b[14t"), "\x1b[4;80;200t");
+    assert_eq!(query(&mut term, "\x1b[16t"), "\x1b[6;20;10t");
+    assert_eq!(query(&mut term, "\x1b[18t"), "\x1b[8;4;20t");
+}
+
+#[test]
+fn test_decrqm_implemented_modes() {
+    let mut term = TestTerm::new(3, 10, 0);
+
+    for (code, _) in DEC_PRIVATE_MODES {
+        let number = code.to_u16().unwrap();
+        let reply = query(&mut term, &format!("\x1b[?{number}$p"));
+        assert!(
+            reply.starts_with(&format!("\x1b[?{number};")) && !reply.ends_with(";0$y"),
+            "{code:?} answered {reply:?}"
+        );
+    }
+    for (code, _) in TERMINAL_MODES {
+        let number = code.to_u16().unwrap();
+        let reply = query(&mut term, &format!("\x1b[{number}$p"));
+        assert!(
+            reply.starts_with(&format!("\x1b[{number};")) && !reply.ends_with(";0$y"),
+            "{code:?} answered {reply:?}"
+        );
+    }
+
+    assert_eq!(query(&mut term, "\x1b[?9999$p"), "\x1b[?9999;0$y");
+    assert_eq!(query(&mut term, "\x1b[99$p"), "\x1b[99;0$y");
+}
+
+#[test]
+fn test_decrqm_tracks_state() {
+    let mut term = TestTerm::new(3, 10, 0);
+    assert_eq!(query(&mut term, "\x1b[?2026$p"), "\x1b[?2026;2$y");
+    term.set_mode("?2026", true);
+    assert_eq!(query(&mut term, "\x1b[?2026$p"), "\x1b[?2026;1$y");
+    term.set_mode("?2026", false);
+
+    assert_eq!(query(&mut term, "\x1b[?1049$p"), "\x1b[?1049;2$y");
+    term.set_mode("?1049", true);
+    assert_eq!(query(&mut term, "\x1b[?1049$p"), "\x1b[?1049;1$y");
+    assert_eq!(query(&mut term, "\x1b[?47$p"), "\x1b[?47;1$y");
+    term.set_mode("?1049", false);
+    assert_eq!(query(&mut term, "\x1b[?1047$p"), "\x1b[?1047;2$y");
+
+    term.set_mode("?1004", true);
+    assert_eq!(query(&mut term, "\x1b[?1004$p"), "\x1b[?1004;1$y");
+
+    assert_eq!(query(&mut term, "\x1b[?4$p"), "\x1b[?4;4$y");
+    assert_eq!(query(&mut term, "\x1b[12$p"), "\x1b[12;3$y");
+}
@@ -0,0 +1,70 @@
+//! Testing that terminal snapshots can be restored into a fresh terminal
+
+use super::*;
+
+#[test]
+fn test_snapshot_round_trip() {
+    let mut term = TestTerm::new(3, 10, 5);
+    term.print("one\r\ntwo\r\nthree\r\nfour\r\nfi");
+    term.print("\x1b]2;the title\x1b\\");
+
+    let snapshot = term.snapshot();
+    assert!(snapshot.alt_lines.is_none());
+
+    let mut restored = TestTerm::new(3, 10, 5);
+    restored.restore_snapshot(snapshot);
+
+    assert_all_contents(
+        &restored,
+        file!(),
+        line!(),
+        &["one", "two", "three", "four", "fi"],
+    );
+    assert_eq!(restored.get_title(), "the title");
+    restored.assert_cursor_pos(2, 2, None, None);
+}
+
+#[test]
+fn test_snapshot_alt_screen() {
+    let mut term = TestTerm::new(3, 10, 5);
+    term.print("shell\r\n");
+    term.set_mode("?1049", true);
+    term.print("editor");
+
+    let snapshot = term.snapshot();
+    assert!(snapshot.alt_lines.is_some());
+
+    let mut restored = TestTerm::new(3, 10, 5);
+    restored.restore_snapshot(snapshot);
+
+    assert!(restored.is_alt_screen_active());
+    assert_visible_contents(&restored, file!(), line!(), &["editor", "", ""]);
+
+    restored.set_mode("?1049", false);
+    assert_visible_contents(&restored, file!(), line!(), &["shell", "", ""]);
+}
+
+#[test]
+fn test_seed_scrollback() {
+    let mut source = TestTerm::new(3, 10, 5);
+    source.print("one\r\ntwo\r\nthree\r\nfour");
+    let lines = source.screen().all_lines();
+
+    let mut term = TestTerm::new(3, 10, 5);
+    term.print("prompt");
+    assert_eq!(term.seed_scrollback(lines[..3].to_vec()), 3);
+    assert_all_contents(
+        &term,
+        file!(),
+        line!(),
+        &["one", "two", "three", "prompt", "", ""],
+    );
+    term.assert_cursor_pos(6, 0, None, None);
+
+    // Only as many lines as fit in the scrollback are kept,
+    // discarding the oldest of them
+    let mut term = TestTerm::new(3, 10, 2);
+    term.print("prompt");
+    assert_eq!(term.seed_scrollback(lines[..3].to_vec()), 2);
+    assert_all_contents(&term, file!(), line!(), &["two", "three", "prompt", "", ""]);
+}
@@ -1006,6 +1006,36 @@ pub fn grapheme_column_width(s: &str, version: Option<UnicodeVersion>) -> usize
     width.min(2)
 }
 
+/// Splits the grapheme cluster `s` into the pieces that occupy cells
+/// when text is measured one code point at a time, as it is by terminals
+/// that don't implement grapheme clustering (DEC private mode 2027).
+/// Each code point that has a width starts a new piece, and zero width
+/// code points join the piece before them.
+/// Yields each piece along with its width, without allocating, as
+/// this is used for every grapheme that is printed.
+pub fn split_grapheme_by_codepoint(
+    s: &str,
+    version: Option<UnicodeVersion>,
+) -> impl Iterator<Item = (&str, usize)> {
+    let version = version.unwrap_or(LATEST_UNICODE_VERSION);
+    let mut chars = s.char_indices().peekable();
+    std::iter::from_fn(move || {
+        let (start, _) = *chars.peek()?;
+        let mut end = start;
+        let mut width = 0;
+        while let Some(&(idx, c)) = chars.peek() {
+            let c_width = version.width(WCWIDTH_TABLE.classify(c));
+            if c_width > 0 && width > 0 {
+                break;
+            }
+            width += c_width;
+            end = idx + c.len_utf8();
+            chars.next();
+        }
+        Some((&s[start..end], width))
+    })
+}
+
 /// Models a change in the attributes of a cell in a stream of changes.
 /// Each variant specifies one of the possible attributes; the corresponding
 /// value holds the new value to be used for that attribute.
@@ -1223,4 +1253,39 @@ mod test {
         assert_eq!(unicode_column_width(sequence2, None), 2);
         assert_eq!(grapheme_column_width(sequence2, None), 2);
     }
+
+    #[test]
+    fn split_by_codepoint() {
+        // man ZWJ woman ZWJ girl
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        assert_eq!(grapheme_column_width(family, None), 2);
+        assert_eq!(
+            split_grapheme_by_codepoint(family, None).collect::<Vec<_>>(),
+            vec![
+                ("\u{1f468}\u{200d}", 2),
+                ("\u{1f469}\u{200d}", 2),
+                ("\u{1f467}", 2)
+            ]
+        );
+
+        // The regional indicators of a flag each take a cell
+        let flag = "\u{1f1fa}\u{1f1f8}";
+        let pieces = split_grapheme_by_codepoint(flag, None)
+            .into_iter()
+            .map(|(piece, _width)| piece)
+            .collect::<Vec<_>>();
+        assert_eq!(pieces, vec!["\u{1f1fa}", "\u{1f1f8}"]);
+
+        // Combining marks stay with their base
+        assert_eq!(
+            split_grapheme_by_codepoint("e\u{301}", None).collect::<Vec<_>>(),
+            vec![("e\u{301}", 1)]
+        );
+        // A zero width code point with nothing before it is kept
+        assert_eq!(
+            split_grapheme_by_codepoint("\u{301}a", None).collect::<Vec<_>>(),
+            vec![("\u{301}a", 1)]
+        );
+        assert_eq!(split_grapheme_by_codepoint("", None).count(), 0);
+    }
 }
@@ -2,6 +2,7 @@ use crate::cell::CellAttributes;
 use crate::emoji::Presentation;
 use crate::surface::line::CellRef;
 use std::borrow::Cow;
+use std::ops::Range;
 use wezterm_bidi::{BidiContext, Direction, ParagraphDirectionHint};
 
 /// A `CellCluster` is another representation of a Line.
@@ -148,82 +149,103 @@ impl CellCluster {
         }
 
         if let Some(hint) = bidi_hint {
-            let mut resolved_clusters = vec![];
-
-            let mut context = BidiContext::new();
-            for cluster in clusters {
-                Self::resolve_bidi(&mut context, hint, cluster, &mut resolved_clusters);
-            }
-
-            resolved_clusters
+            Self::resolve_bidi(hint, &clusters)
         } else {
             clusters
         }
     }
 
-    fn resolve_bidi(
-        context: &mut BidiContext,
-        hint: ParagraphDirectionHint,
-        cluster: CellCluster,
-        resolved: &mut Vec<Self>,
-    ) {
-        let mut paragraph = Vec::with_capacity(cluster.text.len());
-        let mut codepoint_index_to_byte_idx = Vec::with_capacity(cluster.text.len());
-        for (byte_idx, c) in cluster.text.char_indices() {
-            codepoint_index_to_byte_idx.push(byte_idx);
-            paragraph.push(c);
+    /// Applies the bidi algorithm to the whole of the line that
+    /// `clusters` were made from, so that the paragraph direction and
+    /// the levels of the runs are resolved consistently across the
+    /// line, and then splits each run at the boundaries of `clusters`
+    /// so that each piece has uniform attributes.
+    /// The result is in visual order, from left to right.
+    fn resolve_bidi(hint: ParagraphDirectionHint, clusters: &[CellCluster]) -> Vec<Self> {
+        let mut paragraph = vec![];
+        // The cluster and the byte index within it of each codepoint
+        let mut codepoints = vec![];
+        for (cluster_idx, cluster) in clusters.iter().enumerate() {
+            for (byte_idx, c) in cluster.text.char_indices() {
+                paragraph.push(c);
+                codepoints.push((cluster_idx, byte_idx));
+            }
         }
 
+        let mut context = BidiContext::new();
         context.resolve_paragraph(&paragraph, hint);
+
+        let mut resolved = vec![];
         for run in context.reordered_runs(0..paragraph.len()) {
-            let mut text = String::with_capacity(run.range.end - run.range.start);
-            let mut byte_to_cell_idx = vec![];
-            let mut byte_to_cell_width = vec![];
-            let mut width = 0usize;
-            let mut first_cell_idx = None;
-
-            // Note: if we wanted the actual bidi-re-ordered
-            // text we should iterate over run.indices here,
-            // however, cluster.text will be fed into harfbuzz
-            // and that requires the original logical order
-            // for the text, so we look at run.range instead.
-            for cp_idx in run.range.clone() {
-                let cp = paragraph[cp_idx];
-                text.push(cp);
-
-                let original_byte = codepoint_index_to_byte_idx[cp_idx];
-                let cell_width = cluster.byte_to_cell_width(original_byte);
-                width += cell_width as usize;
-
-                let cell_idx = cluster.byte_to_cell_idx(original_byte);
-                if first_cell_idx.is_none() {
-                    first_cell_idx.replace(cell_idx);
+            let mut pieces = vec![];
+            let mut start = run.range.start;
+            while start < run.range.end {
+                let cluster_idx = codepoints[start].0;
+                let mut end = start + 1;
+                while end < run.range.end && codepoints[end].0 == cluster_idx {
+                    end += 1;
                 }
+                pieces.push(start..end);
+                start = end;
+            }
+            // The logically later pieces of a RTL run are to its left
+            if run.direction == Direction::RightToLeft {
+                pieces.reverse();
+            }
+
+            for piece in pieces {
+                let cluster = &clusters[codepoints[piece.start].0];
+                let mut text = String::with_capacity(piece.end - piece.start);
+                let mut byte_to_cell_idx = vec![];
+                let mut byte_to_cell_width = vec![];
+                let mut width = 0usize;
+                let mut first_cell_idx = None;
+
+                // Note: if we wanted the actual bidi-re-ordered
+                // text we should iterate over run.indices here,
+                // however, cluster.text will be fed into harfbuzz
+                // and that requires the original logical order
+                // for the text, so we look at the range instead.
+                for cp_idx in piece {
+                    let cp = paragraph[cp_idx];
+                    text.push(cp);
 
-                if !cluster.byte_to_cell_width.is_empty() {
-                    for _ in 0..cp.len_utf8() {
-                        byte_to_cell_width.push(cell_width);
+                    let original_byte = codepoints[cp_idx].1;
+                    let cell_width = cluster.byte_to_cell_width(original_byte);
+                    width += cell_width as usize;
+
+                    let cell_idx = cluster.byte_to_cell_idx(original_byte);
+                    if first_cell_idx.is_none() {
+                        first_cell_idx.replace(cell_idx);
+                    }
+
+                    if !cluster.byte_to_cell_width.is_empty() {
+                        for _ in 0..cp.len_utf8() {
+                            byte_to_cell_width.push(cell_width);
+                        }
                     }
-                }
 
-                if !cluster.byte_to_cell_idx.is_empty() {
-                    for _ in 0..cp.len_utf8() {
-                        byte_to_cell_idx.push(cell_idx);
+                    if !cluster.byte_to_cell_idx.is_empty() {
+                        for _ in 0..cp.len_utf8() {
+                            byte_to_cell_idx.push(cell_idx);
+                        }
                     }
                 }
-            }
 
-            resolved.push(CellCluster {
-                attrs: cluster.attrs.clone(),
-                text,
-                width,
-                direction: run.direction,
-                presentation: cluster.presentation,
-                byte_to_cell_width,
-                byte_to_cell_idx,
-                first_cell_idx: first_cell_idx.unwrap(),
-            });
+                resolved.push(CellCluster {
+                    attrs: cluster.attrs.clone(),
+                    text,
+                    width,
+                    direction: run.direction,
+                    presentation: cluster.presentation,
+                    byte_to_cell_width,
+                    byte_to_cell_idx,
+                    first_cell_idx: first_cell_idx.unwrap(),
+                });
+            }
         }
+
+        resolved
     }
 
     /// Start off a new cluster with some initial data
@@ -303,3 +325,123 @@ impl CellCluster {
         self.text.push_str(text);
     }
 }
+
+/// Maps between the logical column of each cell of a line, which is
+/// its position in the line, and the visual column at which it is
+/// displayed once the bidi algorithm has reordered the line.
+/// The default map is the identity, which is correct for lines that
+/// don't have bidi enabled.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VisualColumnMap {
+    logical_to_visual: Vec<usize>,
+    visual_to_logical: Vec<usize>,
+    first_column: usize,
+}
+
+impl VisualColumnMap {
+    /// Lays out `clusters`, which must be in visual order as produced by
+    /// `CellCluster::make_cluster` with a bidi hint, across `num_cols`
+    /// columns.  The clusters of a RTL paragraph are aligned with the
+    /// right edge.
+    pub fn new<'a>(
+        clusters: impl IntoIterator<Item = &'a CellCluster>,
+        direction: Direction,
+        num_cols: usize,
+    ) -> Self {
+        let clusters: Vec<&CellCluster> = clusters.into_iter().collect();
+        let total_width: usize = clusters.iter().map(|cluster| cluster.width).sum();
+        let first_column = match direction {
+            Direction::LeftToRight => 0,
+            Direction::RightToLeft => num_cols.saturating_sub(total_width),
+        };
+
+        let mut logical_to_visual = vec![None; num_cols];
+        let mut visual_used = vec![false; num_cols];
+        let mut visual = first_column;
+        for cluster in clusters {
+            let logical = cluster.first_cell_idx..cluster.first_cell_idx + cluster.width;
+            for logical in cluster.direction.iter(logical) {
+                if logical < num_cols && visual < num_cols && logical_to_visual[logical].is_none() {
+                    logical_to_visual[logical] = Some(visual);
+                    visual_used[visual] = true;
+                }
+                visual += 1;
+            }
+        }
+
+        // The columns beyond the end of the line are blank; they fill
+        // the remaining space in the direction of the paragraph
+        let mut unused = visual_used
+            .iter()
+            .enumerate()
+            .filter_map(|(visual, used)| if *used { None } else { Some(visual) })
+            .collect::<Vec<_>>();
+        if direction == Direction::RightToLeft {
+            unused.reverse();
+        }
+        let mut unused = unused.into_iter();
+
+        let logical_to_visual: Vec<usize> = logical_to_visual
+            .into_iter()
+            .enumerate()
+            .map(|(logical, visual)| visual.or_else(|| unused.next()).unwrap_or(logical))
+            .collect();
+        let mut visual_to_logical = vec![0; num_cols];
+        for (logical, &visual) in logical_to_visual.iter().enumerate() {
+            visual_to_logical[visual] = logical;
+        }
+
+        Self {
+            logical_to_visual,
+            visual_to_logical,
+            first_column,
+        }
+    }
+
+    /// Returns the visual column at which the first cluster is placed
+    pub fn first_column(&self) -> usize {
+        self.first_column
+    }
+
+    /// Returns the visual column at which the cell at `logical` is displayed
+    pub fn visual_column(&self, logical: usize) -> usize {
+        self.logical_to_visual
+            .get(logical)
+            .copied()
+            .unwrap_or(logical)
+    }
+
+    /// Returns the logical column of the cell that is displayed at `visual`
+    pub fn logical_column(&self, visual: usize) -> usize {
+        self.visual_to_logical
+            .get(visual)
+            .copied()
+            .unwrap_or(visual)
+    }
+
+    /// Returns the visual columns that display the cells in the
+    /// `logical` range, as contiguous ranges in ascending order.
+    /// A range that spans runs of differing direction may be
+    /// displayed in several pieces.
+    pub fn visual_ranges(&self, logical: Range<usize>) -> Vec<Range<usize>> {
+        if self.logical_to_visual.is_empty() {
+            return if logical.is_empty() {
+                vec![]
+            } else {
+                vec![logical]
+            };
+        }
+
+        let mut columns: Vec<usize> = logical.map(|col| self.visual_column(col)).collect();
+        columns.sort_unstable();
+
+        let mut ranges: Vec<Range<usize>> = vec![];
+        for col in columns {
+            match ranges.last_mut() {
+                Some(range) if range.end == col => range.end += 1,
+                _ => ranges.push(col..col + 1),
+            }
+        }
+        ranges
+    }
+}
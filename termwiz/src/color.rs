@@ -208,6 +208,11 @@ impl ToDynamic for RgbColor {
 }
 
 impl FromDynamic for RgbColor {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        let _ = registry;
+        wezterm_dynamic::Schema::String
+    }
+
     fn from_dynamic(
         value: &Value,
         options: FromDynamicOptions,
@@ -286,6 +291,17 @@ impl From<AnsiColor> for ColorAttribute {
     }
 }
 
+impl From<ColorAttribute> for ColorSpec {
+    fn from(attr: ColorAttribute) -> Self {
+        match attr {
+            ColorAttribute::Default => ColorSpec::Default,
+            ColorAttribute::PaletteIndex(idx) => ColorSpec::PaletteIndex(idx),
+            ColorAttribute::TrueColorWithPaletteFallback(color, _)
+            | ColorAttribute::TrueColorWithDefaultFallback(color) => ColorSpec::TrueColor(color),
+        }
+    }
+}
+
 impl From<ColorSpec> for ColorAttribute {
     fn from(spec: ColorSpec) -> Self {
         match spec {
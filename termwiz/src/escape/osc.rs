@@ -6,6 +6,8 @@ use bitflags::bitflags;
 use num_derive::*;
 use num_traits::FromPrimitive;
 use ordered_float::NotNan;
+#[cfg(feature = "use_serde")]
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::{Display, Error as FmtError, Formatter, Result as FmtResult};
 use std::str;
@@ -38,6 +40,10 @@ pub enum OperatingSystemCommand {
     QuerySelection(Selection),
     SetSelection(Selection, String),
     SystemNotification(String),
+    ConEmuProgress(Progress),
+    /// `OSC 9;9;path`, the ConEmu/Windows Terminal way of reporting
+    /// the current directory as a native path rather than a URL
+    ConEmuCurrentWorkingDirectory(String),
     ITermProprietary(ITermProprietary),
     FinalTermSemanticPrompt(FinalTermSemanticPrompt),
     ChangeColorNumber(Vec<ChangeColorPair>),
@@ -46,10 +52,68 @@ pub enum OperatingSystemCommand {
     CurrentWorkingDirectory(String),
     ResetColors(Vec<u8>),
     RxvtExtension(Vec<String>),
+    /// xterm: request a shape for the mouse pointer.
+    /// The string is the name of the shape, possibly prefixed
+    /// by `?` to query whether shapes are supported.
+    SetMousePointerShape(String),
 
     Unspecified(Vec<Vec<u8>>),
 }
 
+/// ConEmu's `OSC 9;4;st;pr` progress report, which is also
+/// supported by Windows Terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+pub enum Progress {
+    /// `st=0`: remove the progress indicator
+    None,
+    /// `st=1`: progress is `pr` percent complete
+    SetPercentage(u8),
+    /// `st=2`: the operation failed, having reached `pr` percent
+    SetError(u8),
+    /// `st=3`: progress is being made, but its extent is unknown
+    Indeterminate,
+    /// `st=4`: the operation is paused at `pr` percent
+    Paused(u8),
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl Progress {
+    fn parse(osc: &[&[u8]]) -> Result<Self> {
+        let param = |idx: usize| -> Result<u8> {
+            match osc.get(idx) {
+                Some(p) if !p.is_empty() => Ok(str::from_utf8(p)?.parse::<u16>()?.min(100) as u8),
+                _ => Ok(0),
+            }
+        };
+        Ok(match param(2)? {
+            0 => Self::None,
+            1 => Self::SetPercentage(param(3)?),
+            2 => Self::SetError(param(3)?),
+            3 => Self::Indeterminate,
+            4 => Self::Paused(param(3)?),
+            st => bail!("invalid OSC 9;4 state {}", st),
+        })
+    }
+}
+
+impl Display for Progress {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            Self::None => write!(f, "9;4;0"),
+            Self::SetPercentage(pr) => write!(f, "9;4;1;{pr}"),
+            Self::SetError(pr) => write!(f, "9;4;2;{pr}"),
+            Self::Indeterminate => write!(f, "9;4;3"),
+            Self::Paused(pr) => write!(f, "9;4;4;{pr}"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 #[repr(u8)]
 pub enum DynamicColorNumber {
@@ -178,6 +242,33 @@ impl OperatingSystemCommand {
         }
     }
 
+    /// OSC 9 is used both for system notifications (iTerm2)
+    /// and for a family of ConEmu specific sequences
+    fn parse_system_notification_or_conemu(osc: &[&[u8]]) -> Result<Self> {
+        match osc.get(1) {
+            Some(b"4") if osc.len() >= 3 => Ok(OperatingSystemCommand::ConEmuProgress(
+                Progress::parse(osc)?,
+            )),
+            Some(b"9") if osc.len() >= 3 => {
+                // The path may itself contain semicolons
+                let path = osc[2..].join(&b';');
+                let path = String::from_utf8(path)?;
+                let path = match path.strip_prefix('"').and_then(|p| p.strip_suffix('"')) {
+                    Some(unquoted) => unquoted.to_string(),
+                    None => path,
+                };
+                Ok(OperatingSystemCommand::ConEmuCurrentWorkingDirectory(path))
+            }
+            _ => {
+                if osc.len() != 2 {
+                    bail!("wrong param count");
+                }
+                let s = String::from_utf8(osc[1].to_vec())?;
+                Ok(OperatingSystemCommand::SystemNotification(s))
+            }
+        }
+    }
+
     fn parse_reset_colors(osc: &[&[u8]]) -> Result<Self> {
         let mut colors = vec![];
         let mut iter = osc.iter();
@@ -311,7 +402,7 @@ impl OperatingSystemCommand {
             )),
             SetHyperlink => Ok(OperatingSystemCommand::SetHyperlink(Hyperlink::parse(osc)?)),
             ManipulateSelectionData => Self::parse_selection(osc),
-            SystemNotification => single_string!(SystemNotification),
+            SystemNotification => Self::parse_system_notification_or_conemu(osc),
             SetCurrentWorkingDirectory => single_string!(CurrentWorkingDirectory),
             ITermProprietary => {
                 self::ITermProprietary::parse(osc).map(OperatingSystemCommand::ITermProprietary)
@@ -355,6 +446,15 @@ impl OperatingSystemCommand {
                 Self::parse_change_dynamic_color_number(p1str.parse::<u8>().unwrap(), osc)
             }
 
+            SetMousePointerShape => {
+                // An absent name is a request for the default shape
+                let name = match osc.get(1) {
+                    Some(name) => String::from_utf8(name.to_vec())?,
+                    None => String::new(),
+                };
+                Ok(OperatingSystemCommand::SetMousePointerShape(name))
+            }
+
             osc_code => bail!("{:?} not impl", osc_code),
         }
     }
@@ -419,6 +519,8 @@ osc_entries!(
     SetHighlightBackgroundColor = "17",
     SetTektronixCursorColor = "18",
     SetHighlightForegroundColor = "19",
+    /// xterm
+    SetMousePointerShape = "22",
     SetLogFileName = "46",
     SetFont = "50",
     EmacsShell = "51",
@@ -509,6 +611,8 @@ impl Display for OperatingSystemCommand {
             QuerySelection(s) => write!(f, "52;{};?", s)?,
             SetSelection(s, val) => write!(f, "52;{};{}", s, base64_encode(val))?,
             SystemNotification(s) => write!(f, "9;{}", s)?,
+            ConEmuProgress(p) => p.fmt(f)?,
+            ConEmuCurrentWorkingDirectory(path) => write!(f, "9;9;\"{}\"", path)?,
             ITermProprietary(i) => i.fmt(f)?,
             FinalTermSemanticPrompt(i) => i.fmt(f)?,
             ResetColors(colors) => {
@@ -533,6 +637,7 @@ impl Display for OperatingSystemCommand {
                 write!(f, "{}", 100 + *color as u8)?;
             }
             CurrentWorkingDirectory(s) => write!(f, "7;{}", s)?,
+            SetMousePointerShape(s) => write!(f, "22;{}", s)?,
         };
         // Use the longer form ST as neovim doesn't like the BEL version
         write!(f, "\x1b\\")?;
@@ -665,6 +770,12 @@ pub enum FinalTermSemanticPrompt {
         status: i32,
         aid: Option<String>,
     },
+
+    /// Indicates that the command finished, but the shell didn't
+    /// report its exit status
+    CommandStatusUnknown {
+        aid: Option<String>,
+    },
 }
 
 impl FinalTermSemanticPrompt {
@@ -687,7 +798,11 @@ impl FinalTermSemanticPrompt {
         let mut params = HashMap::new();
         use std::convert::TryInto;
 
-        for s in osc.iter().skip(if param == "D" { 3 } else { 2 }) {
+        // The exit status is optional; if it is omitted then
+        // a key=value parameter may immediately follow the D
+        let has_status = param == "D" && osc.get(2).map(|s| !s.contains(&b'=')).unwrap_or(false);
+
+        for s in osc.iter().skip(if has_status { 3 } else { 2 }) {
             if let Some(equal) = s.iter().position(|c| *c == b'=') {
                 let key = &s[..equal];
                 let value = &s[equal + 1..];
@@ -714,18 +829,16 @@ impl FinalTermSemanticPrompt {
         }
 
         if param == "D" {
-            let status = match osc.get(2).map(|&p| p) {
-                Some(s) => match str::from_utf8(s) {
-                    Ok(s) => s.parse().unwrap_or(0),
-                    _ => 0,
-                },
+            let aid = params.get("aid").map(|&s| s.to_owned());
+            if !has_status {
+                return Ok(Self::CommandStatusUnknown { aid });
+            }
+            let status = match str::from_utf8(osc[2]) {
+                Ok(s) => s.parse().unwrap_or(0),
                 _ => 0,
             };
 
-            return Ok(Self::CommandStatus {
-                status,
-                aid: params.get("aid").map(|&s| s.to_owned()),
-            });
+            return Ok(Self::CommandStatus { status, aid });
         }
 
         if param == "N" {
@@ -796,6 +909,12 @@ impl Display for FinalTermSemanticPrompt {
             Self::CommandStatus { status, aid: None } => {
                 write!(f, "D;{}", status)?;
             }
+            Self::CommandStatusUnknown { aid: Some(aid) } => {
+                write!(f, "D;aid={}", aid)?;
+            }
+            Self::CommandStatusUnknown { aid: None } => {
+                write!(f, "D")?;
+            }
         }
         Ok(())
     }
@@ -849,6 +968,61 @@ pub enum ITermProprietary {
 
     /// Configure unicode version
     UnicodeVersion(ITermUnicodeVersionOp),
+
+    /// Change one of the colors of the session.  The key is one of
+    /// `fg`, `bg`, `selbg`, `selfg`, `curbg`, `curfg`, `black`, `red`,
+    /// `green`, `yellow`, `blue`, `magenta`, `cyan`, `white` or one of
+    /// those names prefixed with `br_` (or some other iTerm2 specific
+    /// keys), and the value is typically a hex color such as `ff0000`,
+    /// optionally prefixed by a color space such as `srgb:`.
+    SetColors {
+        key: String,
+        value: String,
+    },
+
+    /// Request that the user's attention is drawn to the session
+    RequestAttention(ITermRequestAttention),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ITermRequestAttention {
+    /// Request attention until the application is activated
+    Yes,
+    /// Cancel a prior request for attention
+    No,
+    /// Request attention just once
+    Once,
+    /// Show a fireworks animation at the cursor position
+    Fireworks,
+}
+
+impl ITermRequestAttention {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "yes" => Some(Self::Yes),
+            "no" => Some(Self::No),
+            "once" => Some(Self::Once),
+            "fireworks" => Some(Self::Fireworks),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Yes => "yes",
+            Self::No => "no",
+            Self::Once => "once",
+            Self::Fireworks => "fireworks",
+        }
+    }
+}
+
+/// Some tools surround the value of `key=value` style parameters
+/// with double quotes; remove them
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -1137,9 +1311,29 @@ impl ITermProprietary {
         const_arg!(HighlightCursorLine, "HighlightCursorLine", "yes", true);
         const_arg!(HighlightCursorLine, "HighlightCursorLine", "no", false);
         one_str!(CurrentDir, "CurrentDir");
-        one_str!(SetProfile, "SetProfile");
         one_str!(CopyToClipboard, "CopyToClipboard");
 
+        if osc.len() == 2 && keyword == "SetProfile" {
+            if let Some(p1) = p1 {
+                return Ok(ITermProprietary::SetProfile(unquote(p1).into()));
+            }
+        }
+
+        if osc.len() == 2 && keyword == "SetColors" {
+            if let Some((key, value)) = p1.and_then(|p1| p1.split_once('=')) {
+                return Ok(ITermProprietary::SetColors {
+                    key: unquote(key).to_string(),
+                    value: unquote(value).to_string(),
+                });
+            }
+        }
+
+        if osc.len() == 2 && keyword == "RequestAttention" {
+            if let Some(attention) = p1.and_then(|p1| ITermRequestAttention::parse(unquote(p1))) {
+                return Ok(ITermProprietary::RequestAttention(attention));
+            }
+        }
+
         let p1_empty = match p1 {
             Some(p1) if p1 == "" => true,
             None => true,
@@ -1286,6 +1480,8 @@ impl Display for ITermProprietary {
                 write!(f, "UnicodeVersion=pop {}", label)?
             }
             UnicodeVersion(ITermUnicodeVersionOp::Pop(None)) => write!(f, "UnicodeVersion=pop")?,
+            SetColors { key, value } => write!(f, "SetColors={}={}", key, value)?,
+            RequestAttention(attention) => write!(f, "RequestAttention={}", attention.as_str())?,
         }
         Ok(())
     }
@@ -1311,6 +1507,67 @@ mod test {
         result
     }
 
+    #[test]
+    fn conemu_progress() {
+        assert_eq!(
+            parse(&["9", "4", "1", "42"], "\x1b]9;4;1;42\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::SetPercentage(42))
+        );
+        assert_eq!(
+            parse(&["9", "4", "2"], "\x1b]9;4;2;0\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::SetError(0))
+        );
+        assert_eq!(
+            parse(&["9", "4", "3"], "\x1b]9;4;3\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::Indeterminate)
+        );
+        assert_eq!(
+            parse(&["9", "4", "4", "300"], "\x1b]9;4;4;100\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::Paused(100))
+        );
+        assert_eq!(
+            parse(&["9", "4", "0"], "\x1b]9;4;0\x1b\\"),
+            OperatingSystemCommand::ConEmuProgress(Progress::None)
+        );
+        // A plain notification that happens to be "4"
+        assert_eq!(
+            parse(&["9", "4"], "\x1b]9;4\x1b\\"),
+            OperatingSystemCommand::SystemNotification("4".into())
+        );
+    }
+
+    #[test]
+    fn conemu_cwd() {
+        assert_eq!(
+            parse(
+                &["9", "9", "\"C:\\Users\\wez\""],
+                "\x1b]9;9;\"C:\\Users\\wez\"\x1b\\"
+            ),
+            OperatingSystemCommand::ConEmuCurrentWorkingDirectory("C:\\Users\\wez".into())
+        );
+        // Unquoted, and with a semicolon in the path
+        assert_eq!(
+            parse(&["9", "9", "C:\\a", "b"], "\x1b]9;9;\"C:\\a;b\"\x1b\\"),
+            OperatingSystemCommand::ConEmuCurrentWorkingDirectory("C:\\a;b".into())
+        );
+    }
+
+    #[test]
+    fn mouse_pointer_shape() {
+        assert_eq!(
+            parse(&["22", "pointer"], "\x1b]22;pointer\x1b\\"),
+            OperatingSystemCommand::SetMousePointerShape("pointer".into())
+        );
+        assert_eq!(
+            parse(&["22"], "\x1b]22;\x1b\\"),
+            OperatingSystemCommand::SetMousePointerShape(String::new())
+        );
+        assert_eq!(
+            parse(&["22", "?text,bogus"], "\x1b]22;?text,bogus\x1b\\"),
+            OperatingSystemCommand::SetMousePointerShape("?text,bogus".into())
+        );
+    }
+
     #[test]
     fn reset_colors() {
         assert_eq!(
@@ -1463,6 +1720,22 @@ mod test {
             )
         );
 
+        assert_eq!(
+            parse(&["133", "D"], "\x1b]133;D\x1b\\"),
+            OperatingSystemCommand::FinalTermSemanticPrompt(
+                FinalTermSemanticPrompt::CommandStatusUnknown { aid: None }
+            )
+        );
+
+        assert_eq!(
+            parse(&["133", "D", "aid=23"], "\x1b]133;D;aid=23\x1b\\"),
+            OperatingSystemCommand::FinalTermSemanticPrompt(
+                FinalTermSemanticPrompt::CommandStatusUnknown {
+                    aid: Some("23".to_owned())
+                }
+            )
+        );
+
         assert_eq!(
             parse(&["133", "P"], "\x1b]133;P;k=i\x1b\\"),
             OperatingSystemCommand::FinalTermSemanticPrompt(FinalTermSemanticPrompt::StartPrompt(
@@ -1611,6 +1884,67 @@ mod test {
         )
     }
 
+    #[test]
+    fn iterm_key_value() {
+        assert_eq!(
+            parse(
+                &["1337", "SetColors=fg=ff0000"],
+                "\x1b]1337;SetColors=fg=ff0000\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::SetColors {
+                key: "fg".into(),
+                value: "ff0000".into()
+            })
+        );
+
+        // Quoted values, and a color space prefix
+        assert_eq!(
+            parse(
+                &["1337", "SetColors=br_red=\"srgb:f00\""],
+                "\x1b]1337;SetColors=br_red=srgb:f00\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::SetColors {
+                key: "br_red".into(),
+                value: "srgb:f00".into()
+            })
+        );
+
+        assert_eq!(
+            parse(
+                &["1337", "SetProfile=\"My Profile\""],
+                "\x1b]1337;SetProfile=My Profile\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::SetProfile(
+                "My Profile".into()
+            ))
+        );
+
+        assert_eq!(
+            parse(
+                &["1337", "RequestAttention=once"],
+                "\x1b]1337;RequestAttention=once\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::RequestAttention(
+                ITermRequestAttention::Once
+            ))
+        );
+        assert_eq!(
+            parse(
+                &["1337", "RequestAttention=\"yes\""],
+                "\x1b]1337;RequestAttention=yes\x1b\\"
+            ),
+            OperatingSystemCommand::ITermProprietary(ITermProprietary::RequestAttention(
+                ITermRequestAttention::Yes
+            ))
+        );
+
+        // A SetColors without a value isn't something that we understand
+        assert_eq!(
+            parse(&["1337", "SetColors=fg"], "\x1b]1337;SetColors=fg\x1b\\"),
+            OperatingSystemCommand::Unspecified(vec![b"1337".to_vec(), b"SetColors=fg".to_vec()])
+        );
+    }
+
     #[test]
     fn iterm() {
         assert_eq!(
@@ -155,10 +155,12 @@ pub struct Rule {
     /// the matched text with a `mailto:` prefix.  More formally,
     /// each instance of `$N` (where N is a number) in the `format`
     /// string is replaced by the capture number N from the regex.
-    /// The replacements are carried out in reverse order, starting
-    /// with the highest numbered capture first.  This avoids issues
-    /// with ambiguous replacement of `$11` vs `$1` in the case of
-    /// more complex regexes.
+    /// When the digits following `$` are ambiguous, the longest
+    /// prefix that names an existing capture is used, so that `$11`
+    /// expands to capture 1 followed by a literal `1` when the regex
+    /// has fewer than 12 captures.
+    /// `${N}` and `${name}` can be used to unambiguously refer to
+    /// a numbered or named capture respectively.
     pub format: String,
 
     /// Which capture to highlight
@@ -169,6 +171,11 @@ pub struct Rule {
 struct RegexWrap(Regex);
 
 impl FromDynamic for RegexWrap {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        let _ = registry;
+        wezterm_dynamic::Schema::String
+    }
+
     fn from_dynamic(
         value: &Value,
         options: FromDynamicOptions,
@@ -251,19 +258,100 @@ impl<'t> Match<'t> {
     /// Expand replacements in the format string to yield the URL
     /// The replacement is as described on Rule::format.
     fn expand(&self) -> String {
-        let mut result = self.rule.format.clone();
-        // Start with the highest numbered capture and decrement.
-        // This avoids ambiguity when replacing $11 vs $1.
-        for n in (0..self.captures.len()).rev() {
-            let search = format!("${}", n);
+        let format = &self.rule.format;
+        let mut result = String::with_capacity(format.len());
+        let mut remain = format.as_str();
+
+        while let Some(dollar) = remain.find('$') {
+            result.push_str(&remain[..dollar]);
+            let after = &remain[dollar + 1..];
+
+            if let Some(braced) = after.strip_prefix('{') {
+                if let Some(close) = braced.find('}') {
+                    let key = &braced[..close];
+                    let capture = match key.parse::<usize>() {
+                        Ok(n) => self.captures.get(n),
+                        Err(_) => self.captures.name(key),
+                    };
+                    if let Some(rep) = capture {
+                        result.push_str(rep.as_str());
+                    }
+                    remain = &braced[close + 1..];
+                    continue;
+                }
+            }
+
+            let num_digits = after
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(after.len());
+            // Use the longest run of digits that refers to a capture
+            // that exists in the regex; any trailing digits are literal
+            let mut len = num_digits;
+            while len > 0 {
+                match after[..len].parse::<usize>() {
+                    Ok(n) if n < self.captures.len() => break,
+                    _ => len -= 1,
+                }
+            }
+
+            if len == 0 {
+                // Not a reference to a capture; keep it literally
+                result.push('$');
+                remain = after;
+                continue;
+            }
+
+            let n: usize = after[..len].parse().unwrap();
             if let Some(rep) = self.captures.get(n) {
-                result = result.replace(&search, rep.as_str());
-            } else {
-                result = result.replace(&search, "");
+                result.push_str(rep.as_str());
             }
+            remain = &after[len..];
         }
+
+        result.push_str(remain);
         result
     }
+
+    fn to_capture_match(&self) -> RuleCaptureMatch {
+        let captures = (0..self.captures.len())
+            .map(|n| self.captures.get(n).map(|c| c.as_str().to_string()))
+            .collect();
+        let named = self
+            .rule
+            .regex
+            .capture_names()
+            .flatten()
+            .filter_map(|name| {
+                self.captures
+                    .name(name)
+                    .map(|c| (name.to_string(), c.as_str().to_string()))
+            })
+            .collect();
+        RuleCaptureMatch {
+            range: self.range(),
+            uri: self.expand(),
+            text: self.highlight().unwrap().as_str().to_string(),
+            captures,
+            named,
+        }
+    }
+}
+
+/// Holds a rule match along with the text that was captured by the
+/// rule regex.  This is used to provide additional context to
+/// code that wants to resolve an implicit link into a different URI.
+#[derive(Debug, PartialEq)]
+pub struct RuleCaptureMatch {
+    /// Holds the span (measured in bytes) of the matched text
+    pub range: Range<usize>,
+    /// The URI produced by expanding the rule format string
+    pub uri: String,
+    /// The highlighted text
+    pub text: String,
+    /// The numbered captures; index 0 is the entire match
+    pub captures: Vec<Option<String>>,
+    /// The named captures that participated in the match
+    pub named: HashMap<String, String>,
 }
 
 impl Rule {
@@ -280,9 +368,7 @@ impl Rule {
         })
     }
 
-    /// Given a line of text from the terminal screen, and a set of
-    /// rules, return the set of RuleMatches.
-    pub fn match_hyperlinks(line: &str, rules: &[Rule]) -> Vec<RuleMatch> {
+    fn collect_matches<'t>(line: &'t str, rules: &'t [Rule]) -> Vec<Match<'t>> {
         let mut matches = Vec::new();
         for rule in rules.iter() {
             for capture_result in rule.regex.captures_iter(line) {
@@ -298,8 +384,13 @@ impl Rule {
         // This is to avoid confusion if multiple rules match the
         // same sections of text.
         matches.sort_by(|a, b| b.len().cmp(&a.len()));
-
         matches
+    }
+
+    /// Given a line of text from the terminal screen, and a set of
+    /// rules, return the set of RuleMatches.
+    pub fn match_hyperlinks(line: &str, rules: &[Rule]) -> Vec<RuleMatch> {
+        Self::collect_matches(line, rules)
             .into_iter()
             .map(|m| {
                 let url = m.expand();
@@ -311,6 +402,15 @@ impl Rule {
             })
             .collect()
     }
+
+    /// Like match_hyperlinks, but returns the captured text for
+    /// each match rather than a Hyperlink.
+    pub fn match_hyperlink_captures(line: &str, rules: &[Rule]) -> Vec<RuleCaptureMatch> {
+        Self::collect_matches(line, rules)
+            .into_iter()
+            .map(|m| m.to_capture_match())
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -347,4 +447,57 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn expand_captures() {
+        let rules = vec![
+            Rule::new(r"\b(\w+)-(?P<num>\d+)\b", "https://bugs/$1/${num}?c=${2}").unwrap(),
+            Rule::new(r"\bcase(\d)\b", "case:$11$2$").unwrap(),
+        ];
+
+        assert_eq!(
+            Rule::match_hyperlinks("see FOO-123 now", &rules),
+            vec![RuleMatch {
+                range: 4..11,
+                link: Arc::new(Hyperlink::new_implicit("https://bugs/FOO/123?c=123")),
+            }]
+        );
+
+        // $11 has no capture 11, so it is capture 1 followed by a literal 1.
+        // There is no capture 2, so $2 and the trailing $ are preserved.
+        assert_eq!(
+            Rule::match_hyperlinks("case7", &rules),
+            vec![RuleMatch {
+                range: 0..5,
+                link: Arc::new(Hyperlink::new_implicit("case:71$2$")),
+            }]
+        );
+    }
+
+    #[test]
+    fn capture_matches() {
+        let rules = vec![Rule::with_highlight(
+            r"\b(?P<file>[\w./]+):(?P<line>\d+)\b",
+            "file://${file}#${line}",
+            1,
+        )
+        .unwrap()];
+
+        let matches = Rule::match_hyperlink_captures("at src/main.rs:42", &rules);
+        assert_eq!(matches.len(), 1);
+        let m = &matches[0];
+        assert_eq!(m.range, 3..14);
+        assert_eq!(m.uri, "file://src/main.rs#42");
+        assert_eq!(m.text, "src/main.rs");
+        assert_eq!(
+            m.captures,
+            vec![
+                Some("src/main.rs:42".to_string()),
+                Some("src/main.rs".to_string()),
+                Some("42".to_string()),
+            ]
+        );
+        assert_eq!(m.named.get("file").map(String::as_str), Some("src/main.rs"));
+        assert_eq!(m.named.get("line").map(String::as_str), Some("42"));
+    }
 }
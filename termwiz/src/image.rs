@@ -554,11 +554,12 @@ impl ImageData {
         }
     }
 
-    /// Returns the in-memory footprint
+    /// Returns the size of the data; for leased data this is the
+    /// size held by the blob store rather than in memory
     pub fn len(&self) -> usize {
         match &*self.data() {
             ImageDataType::EncodedFile(d) => d.len(),
-            ImageDataType::EncodedLease(_) => 0,
+            ImageDataType::EncodedLease(lease) => lease.len(),
             ImageDataType::Rgba8 { data, .. } => data.len(),
             ImageDataType::AnimRgba8 { frames, .. } => frames.len() * frames[0].len(),
         }
@@ -1,5 +1,7 @@
-use crate::cell::{Cell, CellAttributes, SemanticType, UnicodeVersion};
-use crate::cellcluster::CellCluster;
+use crate::cell::{
+    split_grapheme_by_codepoint, Cell, CellAttributes, SemanticType, UnicodeVersion,
+};
+use crate::cellcluster::{CellCluster, VisualColumnMap};
 use crate::hyperlink::Rule;
 use crate::surface::line::cellref::CellRef;
 use crate::surface::line::clusterline::ClusteredLine;
@@ -25,6 +27,29 @@ pub struct ZoneRange {
     pub range: Range<u16>,
 }
 
+/// Records the completion of a command, as reported by the shell
+/// via OSC 133;D, at the position of the cursor at that time.
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandStatusMark {
+    /// The column at which the command completed
+    pub x: usize,
+    /// The exit status of the command, if the shell reported it
+    pub status: Option<i32>,
+}
+
+/// Data that only a few lines carry, which is kept out of line so
+/// that it doesn't add to the size of every line in the scrollback
+#[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct LineMarks {
+    command_status: Option<CommandStatusMark>,
+    /// When output was first printed to the line, in seconds since
+    /// the unix epoch.  See `Line::arrival_time` for how lines that
+    /// have none share the arrival time of the lines above them.
+    arrival: Option<u32>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum DoubleClickRange {
     Range(Range<usize>),
@@ -38,6 +63,8 @@ pub struct Line {
     zones: Vec<ZoneRange>,
     seqno: SequenceNo,
     bits: LineBits,
+    /// Boxed as very few lines have any
+    marks: Option<Box<LineMarks>>,
     #[cfg_attr(feature = "use_serde", serde(skip))]
     appdata: Mutex<Option<Weak<dyn Any + Send + Sync>>>,
 }
@@ -49,6 +76,7 @@ impl Clone for Line {
             zones: self.zones.clone(),
             seqno: self.seqno,
             bits: self.bits,
+            marks: self.marks.clone(),
             appdata: Mutex::new(self.appdata.lock().unwrap().clone()),
         }
     }
@@ -70,6 +98,7 @@ impl Line {
             cells: CellStorage::V(VecStorage::new(cells)),
             seqno,
             zones: vec![],
+            marks: None,
             appdata: Mutex::new(None),
         }
     }
@@ -81,6 +110,7 @@ impl Line {
             cells: CellStorage::V(VecStorage::new(cells)),
             seqno,
             zones: vec![],
+            marks: None,
             appdata: Mutex::new(None),
         }
     }
@@ -92,9 +122,10 @@ impl Line {
     pub fn new(seqno: SequenceNo) -> Self {
         Self {
             bits: LineBits::NONE,
-            cells: CellStorage::C(ClusteredLine::new()),
+            cells: CellStorage::C(Arc::new(ClusteredLine::new())),
             seqno,
             zones: vec![],
+            marks: None,
             appdata: Mutex::new(None),
         }
     }
@@ -125,6 +156,7 @@ impl Line {
             cells: CellStorage::V(VecStorage::new(cells)),
             seqno,
             zones: vec![],
+            marks: None,
             appdata: Mutex::new(None),
         }
     }
@@ -135,15 +167,36 @@ impl Line {
         seqno: SequenceNo,
         unicode_version: Option<UnicodeVersion>,
     ) -> Line {
-        let mut cells = Vec::new();
+        Self::from_text_with_clustering(s, attrs, seqno, unicode_version, true)
+    }
 
-        for sub in Graphemes::new(s) {
-            let cell = Cell::new_grapheme(sub, attrs.clone(), unicode_version);
+    /// Like `from_text`, but when `grapheme_clustering` is false the
+    /// text is measured one code point at a time, as it is when DEC
+    /// private mode 2027 is reset
+    pub fn from_text_with_clustering(
+        s: &str,
+        attrs: &CellAttributes,
+        seqno: SequenceNo,
+        unicode_version: Option<UnicodeVersion>,
+        grapheme_clustering: bool,
+    ) -> Line {
+        let mut cells = Vec::new();
+        let mut push = |cell: Cell| {
             let width = cell.width();
             cells.push(cell);
             for _ in 1..width {
                 cells.push(Cell::new(' ', attrs.clone()));
             }
+        };
+
+        for sub in Graphemes::new(s) {
+            if grapheme_clustering {
+                push(Cell::new_grapheme(sub, attrs.clone(), unicode_version));
+            } else {
+                for (piece, width) in split_grapheme_by_codepoint(sub, unicode_version) {
+                    push(Cell::new_grapheme_with_width(piece, width, attrs.clone()));
+                }
+            }
         }
 
         Line {
@@ -151,6 +204,7 @@ impl Line {
             bits: LineBits::NONE,
             seqno,
             zones: vec![],
+            marks: None,
             appdata: Mutex::new(None),
         }
     }
@@ -184,6 +238,7 @@ impl Line {
         self.update_last_change_seqno(seqno);
         self.invalidate_zones();
         self.bits = LineBits::NONE;
+        self.marks = None;
     }
 
     pub fn resize(&mut self, width: usize, seqno: SequenceNo) {
@@ -201,6 +256,7 @@ impl Line {
 
             let mut lines: Vec<Self> = vec![];
             let mut delta = 0;
+            let mut status_line = None;
             for cell in cells {
                 let need_new_line = lines
                     .last_mut()
@@ -212,6 +268,11 @@ impl Line {
                         .map(|line| line.set_last_cell_was_wrapped(true, seqno));
                     lines.push(Line::new(seqno));
                     delta = cell.cell_index();
+                    if let Some(mark) = self.command_status() {
+                        if mark.x >= delta {
+                            status_line.replace((lines.len() - 1, delta));
+                        }
+                    }
                 }
                 let line = lines.last_mut().unwrap();
                 line.set_cell_grapheme(
@@ -223,12 +284,69 @@ impl Line {
                 );
             }
 
+            if let (Some(mark), Some((idx, delta))) = (self.command_status(), status_line) {
+                lines[idx].update_marks(|marks| {
+                    marks.command_status.replace(CommandStatusMark {
+                        x: mark.x - delta,
+                        status: mark.status,
+                    });
+                });
+            }
+            // The rest of the lines share the arrival time of the first
+            lines[0].set_arrival_time(self.arrival_time());
+
             lines
         } else {
             vec![self]
         }
     }
 
+    /// Records the completion of a command at column x
+    pub fn set_command_status(&mut self, x: usize, status: Option<i32>, seqno: SequenceNo) {
+        self.update_marks(|marks| {
+            marks
+                .command_status
+                .replace(CommandStatusMark { x, status });
+        });
+        self.update_last_change_seqno(seqno);
+    }
+
+    /// Returns the command completion recorded on this line, if any
+    pub fn command_status(&self) -> Option<CommandStatusMark> {
+        self.marks.as_ref().and_then(|marks| marks.command_status)
+    }
+
+    /// Returns the time at which output was first printed to this line,
+    /// in seconds since the unix epoch, if it was recorded on this line.
+    /// Arrival times are stored sparsely: a line that has none shares
+    /// the arrival time of the nearest line above it that does.
+    pub fn arrival_time(&self) -> Option<u32> {
+        self.marks.as_ref().and_then(|marks| marks.arrival)
+    }
+
+    /// Records `secs` as the arrival time of the line, unless one was
+    /// recorded already
+    pub fn mark_arrival(&mut self, secs: u32) {
+        if self.arrival_time().is_none() {
+            self.set_arrival_time(Some(secs));
+        }
+    }
+
+    /// Replaces the arrival time of the line
+    pub fn set_arrival_time(&mut self, arrival: Option<u32>) {
+        self.update_marks(|marks| marks.arrival = arrival);
+    }
+
+    /// Applies `func` to the marks of the line, dropping them
+    /// once there are none left
+    fn update_marks<F: FnOnce(&mut LineMarks)>(&mut self, func: F) {
+        let mut marks = self.marks.take().map(|marks| *marks).unwrap_or_default();
+        func(&mut marks);
+        if marks != LineMarks::default() {
+            self.marks.replace(Box::new(marks));
+        }
+    }
+
     /// Set arbitrary application specific data for the line.
     /// Only one piece of appdata can be tracked per line,
     /// so this is only suitable for the overall application
@@ -372,7 +490,7 @@ impl Line {
         self.bits.set(LineBits::BIDI_ENABLED, enabled);
         let (auto, rtl) = match direction {
             ParagraphDirectionHint::AutoRightToLeft => (true, true),
-            ParagraphDirectionHint::AutoLeftToRight => (true, false),
+            ParagraphDirectionHint::Auto | ParagraphDirectionHint::AutoLeftToRight => (true, false),
             ParagraphDirectionHint::LeftToRight => (false, false),
             ParagraphDirectionHint::RightToLeft => (false, true),
         };
@@ -587,10 +705,12 @@ impl Line {
             let wrapped = phys.last_cell_was_wrapped();
             let is_cluster = matches!(&phys.cells, CellStorage::C(_));
             let len = phys.len();
+            let arrival = phys.arrival_time();
             let remainder = logical.split_off(len, seq);
             **phys = logical;
             logical = remainder;
             phys.set_last_cell_was_wrapped(wrapped, seq);
+            phys.set_arrival_time(arrival);
             phys.clear_appdata();
             if is_cluster {
                 phys.compress_for_scrollback();
@@ -626,11 +746,27 @@ impl Line {
         // <https://github.com/wez/wezterm/issues/2355>
         let idx = idx.min(my_cells.len());
         let cells = my_cells.split_off(idx);
+        // The new line shares the arrival time of this one, so only
+        // the command status, if it lies within the new line, moves
+        let marks = match self.command_status() {
+            Some(mark) if mark.x >= idx => {
+                self.update_marks(|marks| marks.command_status = None);
+                Some(Box::new(LineMarks {
+                    command_status: Some(CommandStatusMark {
+                        x: mark.x - idx,
+                        status: mark.status,
+                    }),
+                    arrival: None,
+                }))
+            }
+            _ => None,
+        };
         Self {
             bits: self.bits,
             cells: CellStorage::V(VecStorage::new(cells)),
             seqno,
             zones: vec![],
+            marks,
             appdata: Mutex::new(None),
         }
     }
@@ -699,6 +835,9 @@ impl Line {
         s
     }
 
+    /// Returns a new line holding the cells in the specified range of
+    /// columns.  A double-width cell is included when its first column
+    /// lies within the range, even if its second column does not.
     pub fn columns_as_line(&self, range: Range<usize>) -> Self {
         let mut cells = vec![];
         for c in self.visible_cells() {
@@ -709,12 +848,23 @@ impl Line {
                 break;
             }
             cells.push(c.as_cell());
+            // Vec storage expects the cells that are occupied by
+            // a double-width cell to follow it
+            for _ in 1..c.width() {
+                cells.push(Cell::blank_with_attrs(c.attrs().clone()));
+            }
         }
         Self {
             bits: LineBits::NONE,
             cells: CellStorage::V(VecStorage::new(cells)),
             seqno: self.current_seqno(),
             zones: vec![],
+            marks: self.arrival_time().map(|arrival| {
+                Box::new(LineMarks {
+                    command_status: None,
+                    arrival: Some(arrival),
+                })
+            }),
             appdata: Mutex::new(None),
         }
     }
@@ -751,6 +901,7 @@ impl Line {
                 // implicitly blank
                 return;
             }
+            let cl = Arc::make_mut(cl);
             while cl.len() < idx {
                 // Fill out any implied blanks until we can append
                 // their intended cell content
@@ -804,6 +955,7 @@ impl Line {
                 // implicitly blank
                 return;
             }
+            let cl = Arc::make_mut(cl);
             while cl.len() < idx {
                 // Fill out any implied blanks until we can append
                 // their intended cell content
@@ -953,7 +1105,7 @@ impl Line {
 
     pub fn prune_trailing_blanks(&mut self, seqno: SequenceNo) {
         if let CellStorage::C(cl) = &mut self.cells {
-            if cl.prune_trailing_blanks() {
+            if Arc::make_mut(cl).prune_trailing_blanks() {
                 self.update_last_change_seqno(seqno);
                 self.invalidate_zones();
             }
@@ -1020,6 +1172,73 @@ impl Line {
         CellCluster::make_cluster(self.len(), self.visible_cells(), bidi_hint)
     }
 
+    /// Returns the paragraph direction of this line if it has bidi
+    /// enabled.  An auto-detected direction considers only the text of
+    /// this line; use `Line::resolve_bidi_directions` to consider the
+    /// whole of a wrapped logical line.
+    pub fn paragraph_direction(&self) -> Option<Direction> {
+        Self::resolve_bidi_directions(std::iter::once(self))
+            .pop()
+            .flatten()
+    }
+
+    /// Resolves the paragraph direction of each of `lines`, which are
+    /// consecutive physical lines.  Following the HL1 higher-level
+    /// protocol of UAX#9, each wrapped logical line is treated as a
+    /// paragraph, so that an auto-detected direction comes from the
+    /// first strong character of the logical line and applies to all of
+    /// its physical lines.
+    /// The result holds the direction of each line, or None for the
+    /// lines that don't have bidi enabled.
+    pub fn resolve_bidi_directions<'a>(
+        lines: impl IntoIterator<Item = &'a Line>,
+    ) -> Vec<Option<Direction>> {
+        let lines: Vec<&Line> = lines.into_iter().collect();
+        let mut directions = Vec::with_capacity(lines.len());
+
+        let mut start = 0;
+        while start < lines.len() {
+            let mut end = start;
+            while end + 1 < lines.len() && lines[end].last_cell_was_wrapped() {
+                end += 1;
+            }
+            let logical = &lines[start..=end];
+            start = end + 1;
+
+            if !logical.iter().any(|line| line.bidi_info().0) {
+                directions.extend(logical.iter().map(|_| None));
+                continue;
+            }
+
+            let (_enabled, hint) = logical[0].bidi_info();
+            let direction = if hint.is_auto() {
+                let mut text = String::new();
+                for line in logical {
+                    for cell in line.visible_cells() {
+                        text.push_str(cell.str());
+                    }
+                }
+                hint.resolve_direction(text.chars())
+            } else {
+                hint.direction()
+            };
+
+            for line in logical {
+                let (enabled, _hint) = line.bidi_info();
+                directions.push(if enabled { Some(direction) } else { None });
+            }
+        }
+
+        directions
+    }
+
+    /// Returns the mapping between the logical and visual columns of this
+    /// line when it is displayed as part of a paragraph of `direction`
+    /// that is `num_cols` wide
+    pub fn visual_column_map(&self, direction: Direction, num_cols: usize) -> VisualColumnMap {
+        VisualColumnMap::new(&self.cluster(Some(direction.into())), direction, num_cols)
+    }
+
     fn make_cells(&mut self) {
         let cells = match &self.cells {
             CellStorage::V(_) => return,
@@ -1047,7 +1266,7 @@ impl Line {
             CellStorage::V(v) => ClusteredLine::from_cell_vec(v.len(), self.visible_cells()),
             CellStorage::C(_) => return,
         };
-        self.cells = CellStorage::C(cv);
+        self.cells = CellStorage::C(Arc::new(cv));
     }
 
     pub fn cells_mut(&mut self) -> &mut [Cell] {
@@ -1062,6 +1281,14 @@ impl Line {
     /// Return true if the last cell in the line has the wrapped attribute,
     /// indicating that the following line is logically a part of this one.
     pub fn last_cell_was_wrapped(&self) -> bool {
+        if let CellStorage::V(cells) = &self.cells {
+            // When the line ends with a double-width cell, the
+            // wrapped attribute may be on its trailing spacer cell
+            // rather than on the visible cell
+            if cells.last().map(|c| c.attrs().wrapped()).unwrap_or(false) {
+                return true;
+            }
+        }
         self.visible_cells()
             .last()
             .map(|c| c.attrs().wrapped())
@@ -1073,6 +1300,7 @@ impl Line {
     pub fn set_last_cell_was_wrapped(&mut self, wrapped: bool, seqno: SequenceNo) {
         self.update_last_change_seqno(seqno);
         if let CellStorage::C(cl) = &mut self.cells {
+            let cl = Arc::make_mut(cl);
             if cl.len() == 0 {
                 // Need to mark that implicit space as wrapped, so
                 // explicitly add it
@@ -1093,6 +1321,15 @@ impl Line {
     /// This function is used by rewrapping logic when joining wrapped
     /// lines back together.
     pub fn append_line(&mut self, other: Line, seqno: SequenceNo) {
+        if let Some(mark) = other.command_status() {
+            let x = mark.x + self.len();
+            self.update_marks(|marks| {
+                marks.command_status.replace(CommandStatusMark {
+                    x,
+                    status: mark.status,
+                });
+            });
+        }
         match &mut self.cells {
             CellStorage::V(cells) => {
                 for cell in other.visible_cells() {
@@ -1103,11 +1340,15 @@ impl Line {
                 }
             }
             CellStorage::C(cl) => {
+                let cl = Arc::make_mut(cl);
                 for cell in other.visible_cells() {
                     cl.append(cell.as_cell());
                 }
             }
         }
+        if self.arrival_time().is_none() {
+            self.set_arrival_time(other.arrival_time());
+        }
         self.update_last_change_seqno(seqno);
         self.invalidate_zones();
     }
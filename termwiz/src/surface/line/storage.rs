@@ -3,12 +3,15 @@ use crate::surface::line::clusterline::{ClusterLineCellIter, ClusteredLine};
 use crate::surface::line::vecstorage::{VecStorage, VecStorageIter};
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 #[cfg_attr(feature = "use_serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum CellStorage {
     V(VecStorage),
-    C(ClusteredLine),
+    /// Clustered storage is shared between clones of a line, so that
+    /// copies of the scrollback share its memory until they are mutated
+    C(Arc<ClusteredLine>),
 }
 
 pub(crate) enum VisibleCellIter<'a> {
@@ -34,7 +37,7 @@ mod test {
     #[test]
     #[cfg(target_pointer_width = "64")]
     fn memory_usage() {
-        assert_eq!(std::mem::size_of::<CellStorage>(), 64);
+        assert_eq!(std::mem::size_of::<CellStorage>(), 24);
         assert_eq!(std::mem::size_of::<VecStorage>(), 24);
     }
 }
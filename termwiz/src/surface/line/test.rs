@@ -2,11 +2,14 @@
 
 use super::*;
 use crate::cell::{Cell, CellAttributes};
+use crate::cellcluster::VisualColumnMap;
 use crate::hyperlink::{Hyperlink, Rule};
 use crate::surface::line::clusterline::ClusteredLine;
+use crate::surface::line::storage::CellStorage;
 use crate::surface::SEQ_ZERO;
 use k9::assert_equal as assert_eq;
 use std::sync::Arc;
+use wezterm_bidi::{Direction, ParagraphDirectionHint};
 
 /// There are 4 double-wide graphemes that occupy 2 cells each.
 /// When we join the lines, we must preserve the invisible blank
@@ -106,6 +109,19 @@ fn double_click_range_bounds() {
     assert_eq!(r, DoubleClickRange::Range(200..200));
 }
 
+#[test]
+fn columns_as_line_double_width() {
+    // The double-width character occupies columns 1 and 2
+    let line: Line = "a\u{30b0}bc".into();
+    let cols = |range: std::ops::Range<usize>| line.columns_as_line(range).as_str().into_owned();
+
+    assert_eq!(cols(0..5), "a\u{30b0}bc");
+    // Starting on its second column excludes the double-width character
+    assert_eq!(cols(2..5), "bc");
+    // Ending on its first column includes it
+    assert_eq!(cols(0..2), "a\u{30b0}");
+}
+
 #[test]
 fn cluster_representation_basic() {
     let line: Line = "hello".into();
@@ -291,6 +307,7 @@ Line {
     bits: LineBits(
         0x0,
     ),
+    marks: None,
     appdata: Mutex {
         data: None,
         poisoned: false,
@@ -419,6 +436,26 @@ C(
     assert_eq!(line, compressed);
 }
 
+#[test]
+fn cluster_clone_is_shared() {
+    let mut line: Line = "hello".into();
+    line.compress_for_scrollback();
+    let mut copy = line.clone();
+    match (&line.cells, &copy.cells) {
+        (CellStorage::C(a), CellStorage::C(b)) => assert!(Arc::ptr_eq(a, b)),
+        _ => panic!("expected clustered storage"),
+    }
+
+    // Mutating the copy must not affect the original
+    copy.set_last_cell_was_wrapped(true, 1);
+    match (&line.cells, &copy.cells) {
+        (CellStorage::C(a), CellStorage::C(b)) => assert!(!Arc::ptr_eq(a, b)),
+        _ => panic!("expected clustered storage"),
+    }
+    assert!(!line.last_cell_was_wrapped());
+    assert!(copy.last_cell_was_wrapped());
+}
+
 #[test]
 fn cluster_append() {
     let mut cl = ClusteredLine::new();
@@ -603,6 +640,7 @@ Line {
     bits: LineBits(
         0x0,
     ),
+    marks: None,
     appdata: Mutex {
         data: None,
         poisoned: false,
@@ -612,3 +650,153 @@ Line {
 "#
     );
 }
+
+#[test]
+fn wrapped_after_double_wide() {
+    let attrs = CellAttributes::default();
+
+    let mut line = Line::from_text("ab日", &attrs, SEQ_ZERO, None);
+    assert!(!line.last_cell_was_wrapped());
+    line.set_last_cell_was_wrapped(true, SEQ_ZERO);
+    assert!(line.last_cell_was_wrapped());
+
+    let mut line = Line::new(SEQ_ZERO);
+    line.set_cell_grapheme(0, "a", 1, attrs.clone(), SEQ_ZERO);
+    line.set_cell_grapheme(1, "日", 2, attrs.clone(), SEQ_ZERO);
+    line.set_last_cell_was_wrapped(true, SEQ_ZERO);
+    assert!(line.last_cell_was_wrapped());
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn memory_usage() {
+    // Arrival times and command status marks are boxed together,
+    // so that lines don't grow to hold them
+    assert_eq!(std::mem::size_of::<Line>(), 96);
+}
+
+#[test]
+fn arrival_time_survives_rewrap() {
+    let attrs = CellAttributes::default();
+
+    let mut line = Line::from_text("hello world", &attrs, SEQ_ZERO, None);
+    assert_eq!(line.arrival_time(), None);
+    line.mark_arrival(100);
+    // Only the first arrival is recorded
+    line.mark_arrival(200);
+    assert_eq!(line.arrival_time(), Some(100));
+
+    // Only the first line records it; the others share it
+    let lines = line.wrap(4, SEQ_ZERO);
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0].arrival_time(), Some(100));
+    assert_eq!(lines[1].arrival_time(), None);
+    assert_eq!(lines[2].arrival_time(), None);
+
+    let mut logical = Line::from_text("abc", &attrs, SEQ_ZERO, None);
+    logical.set_last_cell_was_wrapped(true, SEQ_ZERO);
+    let mut continuation = Line::from_text("def", &attrs, SEQ_ZERO, None);
+    continuation.mark_arrival(300);
+    logical.append_line(continuation, SEQ_ZERO);
+    assert_eq!(logical.arrival_time(), Some(300));
+
+    let mut cleared = lines[0].clone();
+    cleared.resize_and_clear(4, SEQ_ZERO, attrs);
+    assert_eq!(cleared.arrival_time(), None);
+
+    // The arrival time and command status are boxed together, but
+    // clearing one leaves the other
+    let mut line = Line::from_text("abc", &attrs, SEQ_ZERO, None);
+    line.mark_arrival(100);
+    line.set_command_status(1, Some(0), SEQ_ZERO);
+    line.set_arrival_time(None);
+    assert_eq!(line.arrival_time(), None);
+    assert_eq!(line.command_status().map(|mark| mark.x), Some(1));
+}
+
+fn bidi_line(text: &str, hint: ParagraphDirectionHint) -> Line {
+    let mut line = Line::from_text(text, &CellAttributes::default(), SEQ_ZERO, None);
+    line.set_bidi_info(true, hint, SEQ_ZERO);
+    line
+}
+
+/// Returns the logical column that is displayed at each visual column
+fn visual_order(line: &Line, num_cols: usize) -> Vec<usize> {
+    let direction = line.paragraph_direction().unwrap();
+    let map = line.visual_column_map(direction, num_cols);
+    (0..num_cols)
+        .map(|visual| map.logical_column(visual))
+        .collect()
+}
+
+/// These cases are taken from BidiCharacterTest.txt; the expected
+/// visual order is the reordering given there
+#[test]
+fn bidi_visual_order_conformance() {
+    // א 1-2 with an LTR paragraph
+    let line = bidi_line("\u{5d0} 1-2", ParagraphDirectionHint::LeftToRight);
+    assert_eq!(visual_order(&line, 5), vec![2, 3, 4, 1, 0]);
+
+    // ت1/2 with a RTL paragraph, both explicit and detected
+    let line = bidi_line("\u{62a}1/2", ParagraphDirectionHint::RightToLeft);
+    assert_eq!(visual_order(&line, 4), vec![1, 2, 3, 0]);
+    let line = bidi_line("\u{62a}1/2", ParagraphDirectionHint::Auto);
+    assert_eq!(line.paragraph_direction(), Some(Direction::RightToLeft));
+    assert_eq!(visual_order(&line, 4), vec![1, 2, 3, 0]);
+
+    // a 1 2-3 with a RTL paragraph
+    let line = bidi_line("a 1 2-3", ParagraphDirectionHint::RightToLeft);
+    assert_eq!(visual_order(&line, 7), vec![0, 1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn bidi_cursor_columns() {
+    // The cursor at the end of RTL text that has been typed so far is
+    // shown to its left, and the blank columns are on the left edge
+    let line = bidi_line("\u{5d0}\u{5d1}\u{5d2}", ParagraphDirectionHint::Auto);
+    let map = line.visual_column_map(Direction::RightToLeft, 6);
+    assert_eq!(map.first_column(), 3);
+    assert_eq!(
+        (0..6).map(|col| map.visual_column(col)).collect::<Vec<_>>(),
+        vec![5, 4, 3, 2, 1, 0]
+    );
+
+    // Hebrew within an LTR command line
+    let line = bidi_line("ls \u{5d0}\u{5d1}\u{5d2} x", ParagraphDirectionHint::Auto);
+    assert_eq!(line.paragraph_direction(), Some(Direction::LeftToRight));
+    let map = line.visual_column_map(Direction::LeftToRight, 10);
+    assert_eq!(map.visual_column(3), 5);
+    assert_eq!(map.visual_column(5), 3);
+    assert_eq!(map.logical_column(3), 5);
+    assert_eq!(map.visual_column(7), 7);
+    assert_eq!(map.visual_column(9), 9);
+    // A selection from the space up into the Hebrew word
+    assert_eq!(map.visual_ranges(2..5), vec![2..3, 4..6]);
+
+    // Lines without bidi are displayed in logical order
+    let map = VisualColumnMap::default();
+    assert_eq!(map.visual_column(4), 4);
+    assert_eq!(map.visual_ranges(2..5), vec![2..5]);
+}
+
+#[test]
+fn bidi_direction_per_logical_line() {
+    let mut first = bidi_line("123 ", ParagraphDirectionHint::Auto);
+    first.set_last_cell_was_wrapped(true, SEQ_ZERO);
+    let second = bidi_line("\u{5d0}\u{5d1} abc", ParagraphDirectionHint::Auto);
+    let third = bidi_line("abc \u{5d0}", ParagraphDirectionHint::Auto);
+    let plain = Line::from_text("\u{5d0}", &CellAttributes::default(), SEQ_ZERO, None);
+
+    // The first strong character of the logical line decides,
+    // even when it is on a later physical line
+    assert_eq!(
+        Line::resolve_bidi_directions([&first, &second, &third, &plain].iter().copied()),
+        vec![
+            Some(Direction::RightToLeft),
+            Some(Direction::RightToLeft),
+            Some(Direction::LeftToRight),
+            None
+        ]
+    );
+    assert_eq!(first.paragraph_direction(), Some(Direction::LeftToRight));
+}
@@ -78,6 +78,9 @@ pub enum Event {
     PaneModeChanged {
         pane: TmuxPaneId,
     },
+    PasteBufferChanged {
+        name: String,
+    },
     WindowAdd {
         window: TmuxWindowId,
     },
@@ -276,6 +279,11 @@ fn parse_line(line: &str) -> anyhow::Result<Event> {
             let name = unvis(pairs.next().unwrap().as_str())?;
             Ok(Event::SessionRenamed { name })
         }
+        Rule::paste_buffer_changed => {
+            let mut pairs = pair.into_inner();
+            let name = unvis(pairs.next().unwrap().as_str())?;
+            Ok(Event::PasteBufferChanged { name })
+        }
         Rule::session_window_changed => {
             let mut pairs = pair.into_inner();
             let session = parse_session_id(pairs.next().unwrap())?;
@@ -668,6 +676,7 @@ here
 %session-changed $1 1
 %client-session-changed /dev/pts/5 $1 home
 %client-detached /dev/pts/10
+%paste-buffer-changed buffer0
 %layout-change @1 b25d,80x24,0,0,0
 %layout-change @1 cafd,120x29,0,0,0 cafd,120x29,0,0,0 *
 %output %1 \\033[1m\\033[7m%\\033[27m\\033[1m\\033[0m    \\015 \\015
@@ -705,6 +714,9 @@ here
                 Event::ClientDetached {
                     client_name: "/dev/pts/10".to_owned()
                 },
+                Event::PasteBufferChanged {
+                    name: "buffer0".to_owned()
+                },
                 Event::LayoutChange {
                     window: 1,
                     layout: WindowLayout {
@@ -16,18 +16,29 @@ pub struct BlobLease {
 struct LeaseInner {
     pub content_id: ContentId,
     pub lease_id: LeaseId,
+    pub len: usize,
 }
 
 impl BlobLease {
-    pub(crate) fn make_lease(content_id: ContentId, lease_id: LeaseId) -> Self {
+    pub(crate) fn make_lease(content_id: ContentId, lease_id: LeaseId, len: usize) -> Self {
         Self {
             inner: Arc::new(LeaseInner {
                 content_id,
                 lease_id,
+                len,
             }),
         }
     }
 
+    /// Returns the size of the data, in bytes
+    pub fn len(&self) -> usize {
+        self.inner.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.len == 0
+    }
+
     /// Returns a copy of the data, owned by the caller
     pub fn get_data(&self) -> Result<Vec<u8>, Error> {
         let storage = get_storage()?;
@@ -1,4 +1,5 @@
 use crate::{get_storage, BlobLease, ContentId, Error, LeaseId};
+use std::io::{Seek, SeekFrom};
 
 pub struct BlobManager {}
 
@@ -13,7 +14,7 @@ impl BlobManager {
 
         storage.store(content_id, data, lease_id)?;
 
-        Ok(BlobLease::make_lease(content_id, lease_id))
+        Ok(BlobLease::make_lease(content_id, lease_id, data.len()))
     }
 
     /// Attempt to resolve by content id
@@ -22,7 +23,10 @@ impl BlobManager {
 
         let lease_id = LeaseId::new();
         storage.lease_by_content(content_id, lease_id)?;
+        let len = storage
+            .get_reader(content_id, lease_id)?
+            .seek(SeekFrom::End(0))? as usize;
 
-        Ok(BlobLease::make_lease(content_id, lease_id))
+        Ok(BlobLease::make_lease(content_id, lease_id, len))
     }
 }
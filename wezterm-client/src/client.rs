@@ -9,7 +9,7 @@ use filedescriptor::FileDescriptor;
 use futures::FutureExt;
 use mux::client::ClientId;
 use mux::connui::ConnectionUI;
-use mux::domain::DomainId;
+use mux::domain::{ConnectionStats, DomainId};
 use mux::pane::PaneId;
 use mux::ssh::ssh_connect_with_ui;
 use mux::Mux;
@@ -30,8 +30,9 @@ use std::os::unix::process::CommandExt;
 #[cfg(windows)]
 use std::os::windows::io::{AsRawSocket, AsSocket, BorrowedSocket, RawSocket};
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 use wezterm_uds::UnixStream;
 
@@ -49,6 +50,9 @@ enum ReaderMessage {
         promise: Sender<anyhow::Result<Pdu>>,
     },
     Readable,
+    /// Drop the connection, which is then re-established
+    /// if the domain reconnects
+    Reset(String),
 }
 
 #[derive(Clone)]
@@ -59,6 +63,7 @@ pub struct Client {
     client_domain_config: ClientDomainConfig,
     pub is_reconnectable: bool,
     pub is_local: bool,
+    stats: Arc<Mutex<ConnectionStats>>,
 }
 
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
@@ -240,9 +245,14 @@ fn process_unilateral(
 
             return Ok(());
         }
-        Pdu::WindowTitleChanged(WindowTitleChanged { window_id, title }) => {
+        Pdu::WindowTitleChanged(WindowTitleChanged {
+            window_id,
+            title,
+            user_override,
+        }) => {
             let title = title.to_string();
             let window_id = *window_id;
+            let user_override = *user_override;
             promise::spawn::spawn_into_main_thread(async move {
                 let mux = Mux::try_get().ok_or_else(|| anyhow!("no more mux"))?;
                 let client_domain = mux
@@ -255,7 +265,7 @@ fn process_unilateral(
                             anyhow!("domain {} is not a ClientDomain instance", local_domain_id)
                         })?;
 
-                client_domain.process_remote_window_title_change(window_id, title);
+                client_domain.process_remote_window_title_change(window_id, title, user_override);
                 anyhow::Result::<()>::Ok(())
             })
             .detach();
@@ -276,9 +286,14 @@ fn process_unilateral(
             .detach();
             return Ok(());
         }
-        Pdu::TabTitleChanged(TabTitleChanged { tab_id, title }) => {
+        Pdu::TabTitleChanged(TabTitleChanged {
+            tab_id,
+            title,
+            user_override,
+        }) => {
             let title = title.to_string();
             let tab_id = *tab_id;
+            let user_override = *user_override;
             promise::spawn::spawn_into_main_thread(async move {
                 let mux = Mux::try_get().ok_or_else(|| anyhow!("no more mux"))?;
                 let client_domain = mux
@@ -291,7 +306,7 @@ fn process_unilateral(
                             anyhow!("domain {} is not a ClientDomain instance", local_domain_id)
                         })?;
 
-                client_domain.process_remote_tab_title_change(tab_id, title);
+                client_domain.process_remote_tab_title_change(tab_id, title, user_override);
                 anyhow::Result::<()>::Ok(())
             })
             .detach();
@@ -335,20 +350,32 @@ fn process_unilateral(
 enum NotReconnectableError {
     #[error("Client was destroyed")]
     ClientWasDestroyed,
+    #[error("Disconnected: {0}")]
+    DisconnectedByServer(String),
 }
 
 fn client_thread(
     reconnectable: &mut Reconnectable,
     local_domain_id: Option<DomainId>,
     rx: &mut Receiver<ReaderMessage>,
+    stats: &Mutex<ConnectionStats>,
 ) -> anyhow::Result<()> {
-    block_on(client_thread_async(reconnectable, local_domain_id, rx))
+    stats.lock().unwrap().connected = true;
+    let result = block_on(client_thread_async(
+        reconnectable,
+        local_domain_id,
+        rx,
+        stats,
+    ));
+    stats.lock().unwrap().connected = false;
+    result
 }
 
 async fn client_thread_async(
     reconnectable: &mut Reconnectable,
     local_domain_id: Option<DomainId>,
     rx: &mut Receiver<ReaderMessage>,
+    stats: &Mutex<ConnectionStats>,
 ) -> anyhow::Result<()> {
     let mut next_serial = 1u64;
 
@@ -396,12 +423,18 @@ async fn client_thread_async(
             Ok(ReaderMessage::Readable) => {
                 match Pdu::decode_async(&mut stream, Some(next_serial)).await {
                     Ok(decoded) => {
+                        stats.lock().unwrap().last_traffic = Some(Instant::now());
                         log::debug!(
                             "decoded serial {} {}",
                             decoded.serial,
                             decoded.pdu.pdu_name()
                         );
-                        if decoded.serial == 0 {
+                        if let Pdu::DisconnectedByServer(DisconnectedByServer { reason }) =
+                            decoded.pdu
+                        {
+                            promises.fail_all(&reason);
+                            return Err(NotReconnectableError::DisconnectedByServer(reason).into());
+                        } else if decoded.serial == 0 {
                             process_unilateral(local_domain_id, decoded)
                                 .context("processing unilateral PDU from server")
                                 .map_err(|e| {
@@ -427,6 +460,10 @@ async fn client_thread_async(
                     }
                 }
             }
+            Ok(ReaderMessage::Reset(reason)) => {
+                promises.fail_all(&reason);
+                anyhow::bail!("{}", reason);
+            }
             Err(_) => {
                 return Err(NotReconnectableError::ClientWasDestroyed.into());
             }
@@ -537,6 +574,7 @@ where
 
 #[derive(Debug)]
 struct Reconnectable {
+    local_domain_id: Option<DomainId>,
     config: ClientDomainConfig,
     stream: Option<Box<dyn AsyncReadAndWrite>>,
     tls_creds: Option<GetTlsCredsResponse>,
@@ -599,8 +637,13 @@ impl Write for SshStream {
 }
 
 impl Reconnectable {
-    fn new(config: ClientDomainConfig, stream: Option<Box<dyn AsyncReadAndWrite>>) -> Self {
+    fn new(
+        local_domain_id: Option<DomainId>,
+        config: ClientDomainConfig,
+        stream: Option<Box<dyn AsyncReadAndWrite>>,
+    ) -> Self {
         Self {
+            local_domain_id,
             config,
             stream,
             tls_creds: None,
@@ -681,8 +724,9 @@ impl Reconnectable {
         ui: &mut ConnectionUI,
     ) -> anyhow::Result<()> {
         let ssh_config = mux::ssh::ssh_domain_to_ssh_config(&ssh_dom)?;
+        let jump_hosts = mux::ssh::ssh_domain_jump_hosts(&ssh_dom)?;
 
-        let sess = ssh_connect_with_ui(ssh_config, ui)?;
+        let sess = ssh_connect_with_ui(ssh_config, jump_hosts, ui)?;
         let proxy_bin = Self::wezterm_bin_path(&ssh_dom.remote_wezterm_path);
 
         let cmd = if let Some(cmd) = ssh_dom.override_proxy_command.clone() {
@@ -862,6 +906,7 @@ impl Reconnectable {
 
                 let mut ssh_config = wezterm_ssh::Config::new();
                 ssh_config.add_default_config_files();
+                ssh_config.set_allow_exec(configuration().ssh_config_allow_exec);
 
                 let mut fields = ssh_params.host_and_port.split(':');
                 let host = fields
@@ -877,7 +922,7 @@ impl Reconnectable {
                     ssh_config.insert("port".to_string(), port.to_string());
                 }
 
-                let sess = ssh_connect_with_ui(ssh_config, ui)?;
+                let sess = ssh_connect_with_ui(ssh_config, vec![], ui)?;
 
                 let creds = ui.run_and_log_error(|| {
                     // The `tlscreds` command will start the server if needed and then
@@ -1016,22 +1061,28 @@ impl Reconnectable {
         stream.set_write_timeout(Some(tls_client.write_timeout))?;
         stream.set_read_timeout(Some(tls_client.read_timeout))?;
 
-        let stream = Box::new(Async::new(AsyncSslStream::new(
-            connector
-                .connect(
-                    tls_client
-                        .expected_cn
-                        .as_deref()
-                        .unwrap_or(remote_host_name),
-                    stream,
+        let ssl_stream = connector
+            .connect(
+                tls_client
+                    .expected_cn
+                    .as_deref()
+                    .unwrap_or(remote_host_name),
+                stream,
+            )
+            .with_context(|| {
+                format!(
+                    "SslConnector for {} with host name {}",
+                    remote_address, remote_host_name,
                 )
-                .with_context(|| {
-                    format!(
-                        "SslConnector for {} with host name {}",
-                        remote_address, remote_host_name,
-                    )
-                })?,
-        ))?);
+            })?;
+        crate::pinning::verify_server_certificate(
+            self.local_domain_id,
+            tls_client,
+            ssl_stream.ssl().peer_certificate().as_deref(),
+            ui,
+        )?;
+
+        let stream = Box::new(Async::new(AsyncSslStream::new(ssl_stream))?);
         ui.output_str("TLS Connected!\n");
         Ok(stream)
     }
@@ -1044,6 +1095,8 @@ impl Client {
         let is_local = reconnectable.is_local();
         let (sender, mut receiver) = unbounded();
         let client_id = ClientId::new();
+        let stats = Arc::new(Mutex::new(ConnectionStats::default()));
+        let thread_stats = Arc::clone(&stats);
 
         thread::spawn(move || {
             const BASE_INTERVAL: Duration = Duration::from_secs(1);
@@ -1051,7 +1104,26 @@ impl Client {
 
             let mut backoff = BASE_INTERVAL;
             loop {
-                if let Err(e) = client_thread(&mut reconnectable, local_domain_id, &mut receiver) {
+                if let Err(e) = client_thread(
+                    &mut reconnectable,
+                    local_domain_id,
+                    &mut receiver,
+                    &thread_stats,
+                ) {
+                    if let Some(NotReconnectableError::DisconnectedByServer(reason)) =
+                        e.root_cause().downcast_ref::<NotReconnectableError>()
+                    {
+                        log::error!("server disconnected this client: {reason}");
+                        if local_domain_id.is_some() {
+                            let ui = ConnectionUI::new();
+                            ui.title("wezterm: Detached");
+                            ui.output_str(&format!(
+                                "The mux server disconnected this client: {reason}\n"
+                            ));
+                        }
+                        break;
+                    }
+
                     if !reconnectable.reconnectable() || local_domain_id.is_none() {
                         log::debug!("client thread ended: {}", e);
                         break;
@@ -1086,6 +1158,7 @@ impl Client {
                         match reconnectable.connect(initial, &mut ui, no_auto_start) {
                             Ok(_) => {
                                 backoff = BASE_INTERVAL;
+                                thread_stats.lock().unwrap().reconnects += 1;
                                 log::error!("Reconnected!");
                                 promise::spawn::spawn_into_main_thread(async move {
                                     ClientDomain::reattach(local_domain_id, ui).await.ok();
@@ -1138,9 +1211,25 @@ impl Client {
             is_local,
             client_id,
             client_domain_config,
+            stats,
         }
     }
 
+    /// Returns the health of the connection, as last observed
+    pub fn connection_stats(&self) -> ConnectionStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    /// Measures the round trip time to the server, recording it in
+    /// the connection stats
+    pub async fn measure_latency(&self) -> anyhow::Result<Duration> {
+        let start = Instant::now();
+        self.ping().await?;
+        let rtt = start.elapsed();
+        self.stats.lock().unwrap().rtt.replace(rtt);
+        Ok(rtt)
+    }
+
     pub fn into_client_domain_config(self) -> ClientDomainConfig {
         self.client_domain_config
     }
@@ -1166,6 +1255,7 @@ impl Client {
                 self.set_client_id(SetClientId {
                     client_id: self.client_id.clone(),
                     is_proxy: false,
+                    codec_vers: CODEC_VERSION,
                 })
                 .await?;
                 Ok(info)
@@ -1272,8 +1362,11 @@ impl Client {
         ui: &mut ConnectionUI,
         no_auto_start: bool,
     ) -> anyhow::Result<Self> {
-        let mut reconnectable =
-            Reconnectable::new(ClientDomainConfig::Unix(unix_dom.clone()), None);
+        let mut reconnectable = Reconnectable::new(
+            local_domain_id,
+            ClientDomainConfig::Unix(unix_dom.clone()),
+            None,
+        );
         reconnectable.connect(initial, ui, no_auto_start)?;
         Ok(Self::new(local_domain_id, reconnectable))
     }
@@ -1283,8 +1376,11 @@ impl Client {
         tls_client: &TlsDomainClient,
         ui: &mut ConnectionUI,
     ) -> anyhow::Result<Self> {
-        let mut reconnectable =
-            Reconnectable::new(ClientDomainConfig::Tls(tls_client.clone()), None);
+        let mut reconnectable = Reconnectable::new(
+            Some(local_domain_id),
+            ClientDomainConfig::Tls(tls_client.clone()),
+            None,
+        );
         let no_auto_start = true;
         reconnectable.connect(true, ui, no_auto_start)?;
         Ok(Self::new(Some(local_domain_id), reconnectable))
@@ -1295,7 +1391,11 @@ impl Client {
         ssh_dom: &SshDomain,
         ui: &mut ConnectionUI,
     ) -> anyhow::Result<Self> {
-        let mut reconnectable = Reconnectable::new(ClientDomainConfig::Ssh(ssh_dom.clone()), None);
+        let mut reconnectable = Reconnectable::new(
+            Some(local_domain_id),
+            ClientDomainConfig::Ssh(ssh_dom.clone()),
+            None,
+        );
         let no_auto_start = true;
         reconnectable.connect(true, ui, no_auto_start)?;
         Ok(Self::new(Some(local_domain_id), reconnectable))
@@ -1311,6 +1411,28 @@ impl Client {
         rx.recv().await.context("send_pdu recv")?
     }
 
+    /// Checks that the server is still responding.  If it doesn't
+    /// respond within `timeout`, the connection is dropped so that it
+    /// is re-established now, rather than when the next request fails.
+    pub async fn check_health(&self, timeout: Duration) -> anyhow::Result<()> {
+        let ping = async { Some(self.ping().await) };
+        let expired = async {
+            smol::Timer::after(timeout).await;
+            None
+        };
+        match smol::future::or(ping, expired).await {
+            Some(result) => result.map(|_| ()),
+            None => {
+                let reason = format!("the server did not respond within {:?}", timeout);
+                self.sender
+                    .send(ReaderMessage::Reset(reason.clone()))
+                    .await
+                    .ok();
+                bail!("{}", reason)
+            }
+        }
+    }
+
     pub async fn resolve_pane_id(&self, pane_id: Option<PaneId>) -> anyhow::Result<PaneId> {
         let pane_id: PaneId = match pane_id {
             Some(p) => p,
@@ -1342,6 +1464,7 @@ impl Client {
     rpc!(list_panes, ListPanes = (), ListPanesResponse);
     rpc!(spawn_v2, SpawnV2, SpawnResponse);
     rpc!(split_pane, SplitPane, SpawnResponse);
+    rpc!(spawn_floating_pane, SpawnFloatingPane, SpawnResponse);
     rpc!(
         move_pane_to_new_tab,
         MovePaneToNewTab,
@@ -1375,9 +1498,11 @@ impl Client {
     rpc!(kill_pane, KillPane, UnitResponse);
     rpc!(set_client_id, SetClientId, UnitResponse);
     rpc!(list_clients, GetClientList = (), GetClientListResponse);
+    rpc!(kill_client, KillClient, UnitResponse);
     rpc!(set_window_workspace, SetWindowWorkspace, UnitResponse);
     rpc!(set_focused_pane_id, SetFocusedPane, UnitResponse);
     rpc!(get_image_cell, GetImageCell, GetImageCellResponse);
+    rpc!(fetch_image_data, FetchImageData, FetchImageDataResponse);
     rpc!(set_configured_palette_for_pane, SetPalette, UnitResponse);
     rpc!(set_tab_title, TabTitleChanged, UnitResponse);
     rpc!(set_window_title, WindowTitleChanged, UnitResponse);
@@ -1389,4 +1514,34 @@ impl Client {
         GetPaneDirectionResponse
     );
     rpc!(adjust_pane_size, AdjustPaneSize, UnitResponse);
+    rpc!(respawn_pane, RespawnPane, UnitResponse);
+    rpc!(adopt_tab, AdoptTab, AdoptTabResponse);
+    rpc!(move_tab_to_domain, MoveTabToDomain, UnitResponse);
+    rpc!(
+        set_pane_silence_threshold,
+        SetPaneSilenceThreshold,
+        UnitResponse
+    );
+    rpc!(list_domains, ListDomains, ListDomainsResponse);
+    rpc!(attach_domain, AttachDomain, DomainStateChangeResponse);
+    rpc!(detach_domain, DetachDomain, DomainStateChangeResponse);
+    rpc!(link_panes, LinkPanes, UnitResponse);
+    rpc!(pause_output, PauseOutput, UnitResponse);
+    rpc!(add_pane_watch, AddPaneWatch, UnitResponse);
+    rpc!(remove_pane_watch, RemovePaneWatch, UnitResponse);
+    rpc!(prepend_scrollback, PrependScrollback, UnitResponse);
+    rpc!(
+        resolve_inherited_env,
+        ResolveInheritedEnv,
+        ResolveInheritedEnvResponse
+    );
+    rpc!(get_pane_palette, GetPanePalette, GetPanePaletteResponse);
+    rpc!(list_closed, ListClosed, ListClosedResponse);
+    rpc!(get_log_entries, GetLogEntries, GetLogEntriesResponse);
+    rpc!(
+        get_semantic_zones,
+        GetSemanticZones,
+        GetSemanticZonesResponse
+    );
+    rpc!(set_pane_subscription, SetPaneSubscription, UnitResponse);
 }
@@ -301,9 +301,31 @@ mod unix {
 
         pub fn new(path: &Path, class_name: &str) -> anyhow::Result<Self> {
             let name = Self::compute_path(class_name);
-            std::fs::remove_file(&name).ok();
-            std::os::unix::fs::symlink(path, &name)
+            if let Ok(previous) = std::fs::read_link(&name) {
+                if previous != path && !is_sock_dead(&previous) {
+                    log::info!(
+                        "{} pointed to {}, which is still running; \
+                         it now points to this instance",
+                        name.display(),
+                        previous.display()
+                    );
+                }
+            }
+            // Create the link under a temporary name and then rename it
+            // into place, so that other processes never observe the
+            // name as missing or racing with our update
+            let mut temp_name = name.as_os_str().to_owned();
+            temp_name.push(format!(".tmp-{}", std::process::id()));
+            let temp_name = PathBuf::from(temp_name);
+            std::fs::remove_file(&temp_name).ok();
+            std::os::unix::fs::symlink(path, &temp_name)
                 .with_context(|| format!("pointing {} -> {}", name.display(), path.display()))?;
+            if let Err(err) = std::fs::rename(&temp_name, &name) {
+                std::fs::remove_file(&temp_name).ok();
+                return Err(err).with_context(|| {
+                    format!("renaming {} to {}", temp_name.display(), name.display())
+                });
+            }
             Ok(Self {
                 published: path.to_path_buf(),
                 name,
@@ -336,6 +358,43 @@ pub fn resolve_gui_sock_path(class_name: &str) -> anyhow::Result<PathBuf> {
     NameHolder::resolve(class_name)
 }
 
+lazy_static::lazy_static! {
+    static ref GUI_SOCK_PATH: PathBuf = allocate_gui_sock_path(&config::RUNTIME_DIR);
+}
+
+/// Returns the path of the mux socket for this gui process.
+/// The path is chosen on the first call, and remains the same
+/// for the life of the process.
+pub fn gui_sock_path() -> PathBuf {
+    GUI_SOCK_PATH.clone()
+}
+
+/// Processes in different pid namespaces, such as a sandboxed
+/// instance and a regular one, can have the same pid while sharing
+/// the same runtime dir.  Rather than replacing the socket of another
+/// live instance, pick a name with a numeric suffix.
+fn allocate_gui_sock_path(runtime_dir: &Path) -> PathBuf {
+    let base = format!("gui-sock-{}", std::process::id());
+    let preferred = runtime_dir.join(&base);
+    let mut path = preferred.clone();
+    let mut suffix = 1;
+    while !is_sock_dead(&path) {
+        path = runtime_dir.join(format!("{base}-{suffix}"));
+        suffix += 1;
+    }
+    if path != preferred {
+        log::warn!(
+            "{} is in use by another running wezterm process that shares \
+             the runtime directory {}; using {} instead.  The other process \
+             is probably running in a different pid namespace",
+            preferred.display(),
+            runtime_dir.display(),
+            path.display()
+        );
+    }
+    path
+}
+
 /// This function returns a list of the gui-sock- paths in
 /// the runtime dir.  These represent the locally running
 /// instances of wezterm-gui.
@@ -2,13 +2,20 @@ use crate::client::Client;
 use crate::pane::ClientPane;
 use anyhow::{anyhow, bail};
 use async_trait::async_trait;
-use codec::{ListPanesResponse, SpawnV2, SplitPane};
-use config::keyassignment::SpawnTabDomain;
+use codec::{
+    GetPaneRenderChanges, ListPanesResponse, SetPaneSubscription, SpawnFloatingPane, SpawnV2,
+    SplitPane,
+};
+use config::keyassignment::{PaneLinkOptions, SpawnTabDomain};
 use config::{SshDomain, TlsDomainClient, UnixDomain};
 use mux::connui::{ConnectionUI, ConnectionUIParams};
-use mux::domain::{alloc_domain_id, Domain, DomainId, DomainState, SplitSource};
+use mux::domain::{
+    alloc_domain_id, ConnectionStats, Domain, DomainId, DomainState, InheritEnvironment,
+    SplitSource,
+};
 use mux::pane::{Pane, PaneId};
-use mux::tab::{SplitRequest, Tab, TabId};
+use mux::power::PowerEvent;
+use mux::tab::{FloatingPaneGeometry, PaneEntry, SplitRequest, Tab, TabId};
 use mux::window::WindowId;
 use mux::{Mux, MuxNotification};
 use portable_pty::CommandBuilder;
@@ -26,6 +33,10 @@ pub struct ClientInner {
     remote_to_local_tab: Mutex<HashMap<TabId, TabId>>,
     remote_to_local_pane: Mutex<HashMap<PaneId, PaneId>>,
     pub focused_remote_pane_id: Mutex<Option<PaneId>>,
+    /// The remote pane ids that we last advised the server that
+    /// we are rendering
+    subscribed_panes: Mutex<Option<HashSet<PaneId>>>,
+    subscription_update_pending: Mutex<bool>,
 }
 
 impl ClientInner {
@@ -246,8 +257,104 @@ impl ClientInner {
             remote_to_local_tab: Mutex::new(HashMap::new()),
             remote_to_local_pane: Mutex::new(HashMap::new()),
             focused_remote_pane_id: Mutex::new(None),
+            subscribed_panes: Mutex::new(None),
+            subscription_update_pending: Mutex::new(false),
         }
     }
+
+    /// Arrange to tell the server which of its panes we are rendering,
+    /// so that it only sends us the full output for those panes.
+    pub(crate) fn schedule_pane_subscription_update(self: &Arc<Self>) {
+        {
+            let mut pending = self.subscription_update_pending.lock().unwrap();
+            if *pending {
+                return;
+            }
+            *pending = true;
+        }
+        let inner = Arc::clone(self);
+        // Mux::get_window() may trigger a borrow error if called
+        // immediately, and the notifications that lead here tend
+        // to arrive in bursts, so defer and coalesce the work.
+        promise::spawn::spawn_into_main_thread(async move {
+            *inner.subscription_update_pending.lock().unwrap() = false;
+            inner.update_pane_subscription().await
+        })
+        .detach();
+    }
+
+    /// Computes the set of remote panes that are visible in the
+    /// active workspace, along with those in the tabs adjacent to
+    /// the active tab and the previously active tab, as those are the
+    /// most likely to be shown next.
+    fn compute_pane_subscription(&self) -> HashSet<PaneId> {
+        let mux = Mux::get();
+        let mut panes = HashSet::new();
+        for window_id in mux.iter_windows_in_workspace(&mux.active_workspace()) {
+            let window = match mux.get_window(window_id) {
+                Some(window) => window,
+                None => continue,
+            };
+            let active = window.get_active_idx();
+            let mut candidates = vec![active, active + 1];
+            if active > 0 {
+                candidates.push(active - 1);
+            }
+            candidates.extend(window.get_last_active_idx());
+
+            for idx in candidates {
+                if let Some(tab) = window.get_by_idx(idx) {
+                    for pos in tab.iter_panes_ignoring_zoom() {
+                        if pos.pane.domain_id() != self.local_domain_id {
+                            continue;
+                        }
+                        if let Some(pane) = pos.pane.downcast_ref::<ClientPane>() {
+                            panes.insert(pane.remote_pane_id);
+                        }
+                    }
+                }
+            }
+        }
+        panes
+    }
+
+    async fn update_pane_subscription(&self) -> anyhow::Result<()> {
+        let panes = self.compute_pane_subscription();
+        let newly_visible: Vec<PaneId> = {
+            let mut subscribed = self.subscribed_panes.lock().unwrap();
+            if subscribed.as_ref() == Some(&panes) {
+                return Ok(());
+            }
+            let newly_visible = match subscribed.as_ref() {
+                Some(prior) => panes.difference(prior).copied().collect(),
+                // We were implicitly subscribed to everything
+                None => vec![],
+            };
+            subscribed.replace(panes.clone());
+            newly_visible
+        };
+
+        log::trace!(
+            "domain {}: subscribing to remote panes {:?}",
+            self.local_domain_id,
+            panes
+        );
+        self.client
+            .set_pane_subscription(SetPaneSubscription {
+                pane_ids: panes.into_iter().collect(),
+            })
+            .await?;
+
+        // We've only been receiving summaries for these panes;
+        // ask for a catch-up so that their viewports are current
+        // by the time that they are rendered.
+        for pane_id in newly_visible {
+            self.client
+                .get_pane_render_changes(GetPaneRenderChanges { pane_id })
+                .await?;
+        }
+        Ok(())
+    }
 }
 
 pub struct ClientDomain {
@@ -278,8 +385,18 @@ fn mux_notify_client_domain(local_domain_id: DomainId, notif: MuxNotification) -
     };
 
     match notif {
-        MuxNotification::ActiveWorkspaceChanged(_client_id) => {
-            // TODO: advice remote host of interesting workspaces
+        MuxNotification::ActiveWorkspaceChanged(_)
+        | MuxNotification::WindowCreated(_)
+        | MuxNotification::WindowRemoved(_)
+        | MuxNotification::WindowInvalidated(_)
+        | MuxNotification::TabAddedToWindow { .. }
+        | MuxNotification::TabResized(_)
+        | MuxNotification::PaneAdded(_)
+        | MuxNotification::PaneRemoved(_)
+        | MuxNotification::PaneFocused(_) => {
+            if let Some(inner) = client_domain.inner() {
+                inner.schedule_pane_subscription_update();
+            }
         }
         MuxNotification::WorkspaceRenamed {
             old_workspace,
@@ -338,7 +455,11 @@ fn mux_notify_client_domain(local_domain_id: DomainId, notif: MuxNotification) -
             })
             .detach();
         }
-        MuxNotification::TabTitleChanged { tab_id, title } => {
+        MuxNotification::TabTitleChanged {
+            tab_id,
+            title,
+            user_override,
+        } => {
             if let Some(remote_tab_id) = client_domain.local_to_remote_tab_id(tab_id) {
                 if let Some(inner) = client_domain.inner() {
                     promise::spawn::spawn(async move {
@@ -347,6 +468,7 @@ fn mux_notify_client_domain(local_domain_id: DomainId, notif: MuxNotification) -
                             .set_tab_title(codec::TabTitleChanged {
                                 tab_id: remote_tab_id,
                                 title,
+                                user_override,
                             })
                             .await
                     })
@@ -354,10 +476,7 @@ fn mux_notify_client_domain(local_domain_id: DomainId, notif: MuxNotification) -
                 }
             }
         }
-        MuxNotification::WindowTitleChanged {
-            window_id,
-            title: _,
-        } => {
+        MuxNotification::WindowTitleChanged { window_id, .. } => {
             if let Some(remote_window_id) = client_domain.local_to_remote_window_id(window_id) {
                 if let Some(inner) = client_domain.inner() {
                     promise::spawn::spawn_into_main_thread(async move {
@@ -373,13 +492,14 @@ fn mux_notify_client_domain(local_domain_id: DomainId, notif: MuxNotification) -
                         if let Some(mux) = Mux::try_get() {
                             let title = mux
                                 .get_window(window_id)
-                                .map(|win| win.get_title().to_string());
-                            if let Some(title) = title {
+                                .map(|win| (win.get_title().to_string(), win.has_title_override()));
+                            if let Some((title, user_override)) = title {
                                 inner
                                     .client
                                     .set_window_title(codec::WindowTitleChanged {
                                         window_id: remote_window_id,
                                         title,
+                                        user_override,
                                     })
                                     .await?;
                             }
@@ -390,6 +510,25 @@ fn mux_notify_client_domain(local_domain_id: DomainId, notif: MuxNotification) -
                 }
             }
         }
+        MuxNotification::PowerEvent(PowerEvent::Resume) => {
+            // The connection has most likely been dropped while the
+            // system was asleep, so rather than waiting for the next
+            // request to fail, check on it now
+            if let Some(inner) = client_domain.inner() {
+                if !inner.client.is_local {
+                    promise::spawn::spawn_into_main_thread(async move {
+                        if let Err(err) = inner.client.check_health(HEALTH_CHECK_TIMEOUT).await {
+                            log::warn!(
+                                "domain {} failed a health check after resuming: {:#}",
+                                inner.local_domain_id,
+                                err
+                            );
+                        }
+                    })
+                    .detach();
+                }
+            }
+        }
         _ => {}
     }
     true
@@ -467,7 +606,12 @@ impl ClientDomain {
         let inner = Self::get_client_inner_for_domain(domain_id)?;
 
         let panes = inner.client.list_panes().await?;
-        Self::process_pane_list(inner, panes, None)?;
+        Self::process_pane_list(Arc::clone(&inner), panes, None)?;
+
+        // The server has forgotten our subscription along with
+        // the prior session, so we need to tell it again
+        inner.subscribed_panes.lock().unwrap().take();
+        inner.schedule_pane_subscription_update();
 
         ui.close();
         Ok(())
@@ -481,21 +625,31 @@ impl ClientDomain {
         Ok(())
     }
 
-    pub fn process_remote_window_title_change(&self, remote_window_id: WindowId, title: String) {
+    pub fn process_remote_window_title_change(
+        &self,
+        remote_window_id: WindowId,
+        title: String,
+        user_override: bool,
+    ) {
         if let Some(inner) = self.inner() {
             if let Some(local_window_id) = inner.remote_to_local_window(remote_window_id) {
                 if let Some(mut window) = Mux::get().get_window_mut(local_window_id) {
-                    window.set_title(&title);
+                    window.set_title_with_override(&title, user_override);
                 }
             }
         }
     }
 
-    pub fn process_remote_tab_title_change(&self, remote_tab_id: TabId, title: String) {
+    pub fn process_remote_tab_title_change(
+        &self,
+        remote_tab_id: TabId,
+        title: String,
+        user_override: bool,
+    ) {
         if let Some(inner) = self.inner() {
             if let Some(local_tab_id) = inner.remote_to_local_tab_id(remote_tab_id) {
                 if let Some(tab) = Mux::get().get_tab(local_tab_id) {
-                    tab.set_title(&title);
+                    tab.set_title_with_override(&title, user_override);
                 }
             }
         }
@@ -536,8 +690,63 @@ impl ClientDomain {
             .keys()
             .copied()
             .collect();
+        let mut remote_links = vec![];
 
-        for (tabroot, tab_title) in panes.tabs.into_iter().zip(panes.tab_titles.iter()) {
+        // Returns the local pane that represents the remote pane
+        // described by `entry`, making one if there isn't one yet
+        let mut local_pane_for_entry = |entry: &PaneEntry| -> Arc<dyn Pane> {
+            if !entry.links.is_empty() {
+                remote_links.push((entry.pane_id, entry.links.clone()));
+            }
+            remote_panes_to_forget.remove(&entry.pane_id);
+            let pane = if let Some(pane_id) = inner.remote_to_local_pane_id(entry.pane_id) {
+                match mux.get_pane(pane_id) {
+                    Some(pane) => pane,
+                    None => {
+                        // We likely decided that we hit EOF on the tab and
+                        // removed it from the mux.  Let's add it back, but
+                        // with a new id.
+                        inner.remove_old_pane_mapping(entry.pane_id);
+                        let pane: Arc<dyn Pane> = Arc::new(ClientPane::new(
+                            &inner,
+                            entry.tab_id,
+                            entry.pane_id,
+                            entry.size,
+                            &entry.title,
+                        ));
+                        mux.add_pane(&pane).expect("failed to add pane to mux");
+                        pane
+                    }
+                }
+            } else {
+                let pane: Arc<dyn Pane> = Arc::new(ClientPane::new(
+                    &inner,
+                    entry.tab_id,
+                    entry.pane_id,
+                    entry.size,
+                    &entry.title,
+                ));
+                log::debug!(
+                    "domain: {} attaching to remote pane {:?} -> local pane_id {}",
+                    inner.local_domain_id,
+                    entry,
+                    pane.pane_id()
+                );
+                mux.add_pane(&pane).expect("failed to add pane to mux");
+                pane
+            };
+            if let Some(client_pane) = pane.downcast_ref::<ClientPane>() {
+                client_pane.set_nesting_depth(entry.nesting_depth);
+            }
+            pane
+        };
+
+        for (idx, (tabroot, tab_title)) in panes
+            .tabs
+            .into_iter()
+            .zip(panes.tab_titles.iter())
+            .enumerate()
+        {
             let root_size = match tabroot.root_size() {
                 Some(size) => size,
                 None => continue,
@@ -573,51 +782,42 @@ impl ClientDomain {
                     inner.record_remote_to_local_tab_mapping(remote_tab_id, tab.tab_id());
                 }
 
-                tab.set_title(tab_title);
+                tab.set_title_with_override(
+                    tab_title,
+                    panes.tab_title_overrides.get(idx).copied().unwrap_or(false),
+                );
 
                 log::debug!("domain: {} tree: {:#?}", inner.local_domain_id, tabroot);
                 let mut workspace = None;
                 tab.sync_with_pane_tree(root_size, tabroot, |entry| {
                     workspace.replace(entry.workspace.clone());
-                    remote_panes_to_forget.remove(&entry.pane_id);
-                    if let Some(pane_id) = inner.remote_to_local_pane_id(entry.pane_id) {
-                        match mux.get_pane(pane_id) {
-                            Some(pane) => pane,
-                            None => {
-                                // We likely decided that we hit EOF on the tab and
-                                // removed it from the mux.  Let's add it back, but
-                                // with a new id.
-                                inner.remove_old_pane_mapping(entry.pane_id);
-                                let pane: Arc<dyn Pane> = Arc::new(ClientPane::new(
-                                    &inner,
-                                    entry.tab_id,
-                                    entry.pane_id,
-                                    entry.size,
-                                    &entry.title,
-                                ));
-                                mux.add_pane(&pane).expect("failed to add pane to mux");
-                                pane
-                            }
-                        }
-                    } else {
-                        let pane: Arc<dyn Pane> = Arc::new(ClientPane::new(
-                            &inner,
-                            entry.tab_id,
-                            entry.pane_id,
-                            entry.size,
-                            &entry.title,
-                        ));
-                        log::debug!(
-                            "domain: {} attaching to remote pane {:?} -> local pane_id {}",
-                            inner.local_domain_id,
-                            entry,
-                            pane.pane_id()
-                        );
-                        mux.add_pane(&pane).expect("failed to add pane to mux");
-                        pane
-                    }
+                    local_pane_for_entry(&entry)
                 });
 
+                let mut floating_focus = None;
+                let floating = panes
+                    .floating_panes
+                    .get(idx)
+                    .map(|entries| entries.as_slice())
+                    .unwrap_or_default()
+                    .iter()
+                    .zip(
+                        panes
+                            .floating_pane_geometries
+                            .get(idx)
+                            .map(|geometries| geometries.as_slice())
+                            .unwrap_or_default(),
+                    )
+                    .map(|(entry, geometry)| {
+                        let pane = local_pane_for_entry(entry);
+                        if entry.is_active_pane {
+                            floating_focus.replace(pane.pane_id());
+                        }
+                        (pane, *geometry)
+                    })
+                    .collect::<Vec<(Arc<dyn Pane>, FloatingPaneGeometry)>>();
+                tab.sync_floating_panes(floating, floating_focus);
+
                 if let Some(local_window_id) = inner.remote_to_local_window(remote_window_id) {
                     let mut window = mux
                         .get_window_mut(local_window_id)
@@ -669,12 +869,33 @@ impl ClientDomain {
             }
         }
 
+        // Mirror the links between panes that the server knows about
+        for (remote_pane_id, links) in remote_links {
+            if let Some(pane_id) = inner.remote_to_local_pane_id(remote_pane_id) {
+                for link in links {
+                    if let Some(other_pane_id) = inner.remote_to_local_pane_id(link.pane_id) {
+                        mux.record_pane_link(
+                            pane_id,
+                            other_pane_id,
+                            PaneLinkOptions {
+                                close_together: link.close_together,
+                                activate_together: link.activate_together,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
         for (remote_window_id, window_title) in panes.window_titles {
             if let Some(local_window_id) = inner.remote_to_local_window(remote_window_id) {
                 let mut window = mux
                     .get_window_mut(local_window_id)
                     .expect("no such window!?");
-                window.set_title(&window_title);
+                window.set_title_with_override(
+                    &window_title,
+                    panes.window_title_overrides.contains(&remote_window_id),
+                );
             }
         }
 
@@ -707,6 +928,126 @@ impl ClientDomain {
         Ok(())
     }
 
+    /// Moves a tab of local panes into this domain.  The ptys of the
+    /// panes are handed off to the mux server, along with a snapshot of
+    /// their terminal state, so that the programs running in them keep
+    /// running.  The local tab is replaced by one that holds ClientPanes
+    /// for the adopted panes.
+    /// This is only possible for unix domains on the local machine.
+    #[cfg(unix)]
+    pub async fn adopt_local_tab(&self, tab_id: TabId) -> anyhow::Result<Arc<Tab>> {
+        use mux::localpane::LocalPane;
+
+        match &self.config {
+            ClientDomainConfig::Unix(unix) if unix.proxy_command.is_none() => {}
+            _ => bail!(
+                "Tabs can only be moved into a unix domain on the local machine, \
+                 but {} is a {}",
+                self.config.name(),
+                self.label
+            ),
+        }
+
+        let mux = Mux::get();
+        let tab = mux
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {tab_id} not found"))?;
+        let window_id = mux
+            .window_containing_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {tab_id} is not in a window"))?;
+        let tab_idx = mux
+            .get_window(window_id)
+            .and_then(|window| window.idx_by_id(tab_id));
+
+        let panes: Vec<Arc<dyn Pane>> = tab
+            .iter_panes_ignoring_zoom()
+            .into_iter()
+            .map(|pos| pos.pane)
+            .collect();
+        for pane in &panes {
+            match pane.downcast_ref::<LocalPane>() {
+                Some(local) if local.can_hand_off() => {}
+                _ => bail!(
+                    "Pane {} cannot be moved; only panes running a local \
+                     program can be moved into a mux server",
+                    pane.pane_id()
+                ),
+            }
+        }
+        let local = |pane: &Arc<dyn Pane>| pane.downcast_ref::<LocalPane>().unwrap();
+
+        self.attach(Some(window_id)).await?;
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+
+        let mut suspended = vec![];
+        let result: anyhow::Result<codec::AdoptTabResponse> = async {
+            for pane in &panes {
+                local(pane).suspend_reader().await?;
+                suspended.push(Arc::clone(pane));
+            }
+            let handoffs = panes
+                .iter()
+                .map(|pane| local(pane).prepare_handoff())
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            send_handoff(&inner, &tab, window_id, handoffs).await
+        }
+        .await;
+
+        let response = match result {
+            Ok(response) => response,
+            Err(err) => {
+                // The server didn't take the panes; resume them here
+                for pane in suspended {
+                    mux.start_pane_reader(&pane, None).ok();
+                }
+                return Err(err);
+            }
+        };
+
+        for pane in &panes {
+            local(pane).complete_handoff();
+        }
+
+        // Have the adopted tab show up in place of the local one
+        inner.record_remote_to_local_window_mapping(response.window_id, window_id);
+        let panes = inner.client.list_panes().await?;
+        Self::process_pane_list(Arc::clone(&inner), panes, None)?;
+        mux.remove_tab(tab_id);
+
+        let new_tab = inner
+            .remote_to_local_tab_id(response.tab_id)
+            .and_then(|tab_id| mux.get_tab(tab_id))
+            .ok_or_else(|| anyhow!("adopted tab {} was not synced", response.tab_id))?;
+        if let Some(mut window) = mux.get_window_mut(window_id) {
+            if let Some(idx) = window.idx_by_id(new_tab.tab_id()) {
+                let idx = match tab_idx {
+                    Some(tab_idx) if tab_idx < window.len() => {
+                        window.remove_by_idx(idx);
+                        window.insert(tab_idx, &new_tab);
+                        tab_idx
+                    }
+                    _ => idx,
+                };
+                window.save_and_then_set_active(idx);
+            }
+        }
+
+        Ok(new_tab)
+    }
+
+    /// Handing a pty off to another process relies on passing its file
+    /// descriptor over a unix socket, which is not possible here
+    #[cfg(not(unix))]
+    pub async fn adopt_local_tab(&self, _tab_id: TabId) -> anyhow::Result<Arc<Tab>> {
+        bail!(
+            "Moving tabs into a mux server is not supported on this system, \
+             so the tab cannot be moved into {}",
+            self.config.name()
+        )
+    }
+
     fn finish_attach(
         domain_id: DomainId,
         client: Client,
@@ -730,6 +1071,7 @@ impl ClientDomain {
             overlay_lag_indicator,
         ));
         *domain.inner.lock().unwrap() = Some(Arc::clone(&inner));
+        start_latency_monitor(&inner);
 
         Self::process_pane_list(inner, panes, primary_window_id)?;
 
@@ -737,6 +1079,134 @@ impl ClientDomain {
     }
 }
 
+/// How long the server has to respond to a health check
+const HEALTH_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How often the latency of a connection is measured while it is in use
+const LATENCY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// How often the latency is measured once the connection has gone idle
+const MAX_LATENCY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Measures the latency of the connection of `inner` for as long as its
+/// domain remains attached to it.  The measurements back off while the
+/// connection carries no traffic other than their own, so that an idle
+/// connection is left mostly quiet.
+fn start_latency_monitor(inner: &Arc<ClientInner>) {
+    let inner = Arc::downgrade(inner);
+    promise::spawn::spawn(async move {
+        let mut interval = LATENCY_INTERVAL;
+        // When the response to our most recent measurement arrived
+        let mut own_traffic = None;
+        loop {
+            smol::Timer::after(interval).await;
+            let inner = match inner.upgrade() {
+                Some(inner) => inner,
+                None => return,
+            };
+            match ClientDomain::get_client_inner_for_domain(inner.local_domain_id) {
+                Ok(current) if Arc::ptr_eq(&current, &inner) => {}
+                _ => return,
+            }
+
+            let last_traffic = inner.client.connection_stats().last_traffic;
+            interval = if last_traffic.is_some() && last_traffic != own_traffic {
+                LATENCY_INTERVAL
+            } else {
+                (interval * 2).min(MAX_LATENCY_INTERVAL)
+            };
+
+            match inner.client.measure_latency().await {
+                Ok(_) => own_traffic = inner.client.connection_stats().last_traffic,
+                Err(err) => log::debug!(
+                    "measuring latency of domain {}: {:#}",
+                    inner.local_domain_id,
+                    err
+                ),
+            }
+        }
+    })
+    .detach();
+}
+
+/// How long to wait for the mux server to collect the ptys
+#[cfg(unix)]
+const HANDOFF_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Listens on a private socket and passes the ptys of `handoffs` to the
+/// mux server when it connects, while asking the server to adopt them.
+#[cfg(unix)]
+async fn send_handoff(
+    inner: &Arc<ClientInner>,
+    tab: &Arc<Tab>,
+    window_id: WindowId,
+    handoffs: Vec<mux::handoff::PaneHandoff>,
+) -> anyhow::Result<codec::AdoptTabResponse> {
+    use std::os::fd::AsRawFd;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let panes = handoffs
+        .iter()
+        .map(codec::AdoptedPane::from_handoff)
+        .collect();
+    let fds: Vec<_> = handoffs.into_iter().map(|handoff| handoff.fd).collect();
+
+    let handoff_path =
+        config::RUNTIME_DIR.join(format!("handoff-{}-{}", std::process::id(), tab.tab_id()));
+    std::fs::remove_file(&handoff_path).ok();
+    let listener = wezterm_uds::UnixListener::bind(&handoff_path)?;
+    listener.set_nonblocking(true)?;
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    let sender = std::thread::spawn({
+        let cancel = Arc::clone(&cancel);
+        move || -> anyhow::Result<()> {
+            let deadline = std::time::Instant::now() + HANDOFF_TIMEOUT;
+            loop {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        stream.set_nonblocking(false)?;
+                        let fds: Vec<_> = fds.iter().map(|fd| fd.as_raw_fd()).collect();
+                        stream.send_with_fds(b"wezterm-handoff", &fds)?;
+                        return Ok(());
+                    }
+                    Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                        if cancel.load(Ordering::Relaxed) {
+                            bail!("the mux server did not collect the panes");
+                        }
+                        if std::time::Instant::now() > deadline {
+                            bail!("timed out waiting for the mux server to collect the panes");
+                        }
+                        std::thread::sleep(std::time::Duration::from_millis(20));
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        }
+    });
+
+    let response = inner
+        .client
+        .adopt_tab(codec::AdoptTab {
+            window_id: inner.local_to_remote_window(window_id),
+            workspace: Mux::get().active_workspace(),
+            size: tab.get_size(),
+            tree: tab.codec_pane_tree(),
+            panes,
+            handoff_path: handoff_path.clone(),
+        })
+        .await;
+
+    cancel.store(true, Ordering::Relaxed);
+    let sent = sender
+        .join()
+        .map_err(|_| anyhow!("handoff thread panicked"))?;
+    std::fs::remove_file(&handoff_path).ok();
+
+    let response = response?;
+    sent?;
+    Ok(response)
+}
+
 #[async_trait(?Send)]
 impl Domain for ClientDomain {
     fn domain_id(&self) -> DomainId {
@@ -747,6 +1217,14 @@ impl Domain for ClientDomain {
         self.config.name()
     }
 
+    fn domain_type(&self) -> &'static str {
+        match &self.config {
+            ClientDomainConfig::Unix(_) => "unix",
+            ClientDomainConfig::Tls(_) => "tls",
+            ClientDomainConfig::Ssh(_) => "ssh-mux",
+        }
+    }
+
     async fn domain_label(&self) -> String {
         self.label.to_string()
     }
@@ -760,6 +1238,45 @@ impl Domain for ClientDomain {
         anyhow::bail!("spawn_pane not implemented for ClientDomain")
     }
 
+    /// Forward the request to the remote, which places the pane in
+    /// its tab, then resync so that the floating pane is mirrored here
+    async fn spawn_floating_pane(
+        &self,
+        tab_id: TabId,
+        geometry: FloatingPaneGeometry,
+        _size: TerminalSize,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+    ) -> anyhow::Result<Arc<dyn Pane>> {
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+
+        let remote_tab_id = inner
+            .local_to_remote_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {tab_id} is not a tab of domain {}", self.domain_name()))?;
+
+        let result = inner
+            .client
+            .spawn_floating_pane(SpawnFloatingPane {
+                tab_id: remote_tab_id,
+                geometry,
+                command,
+                command_dir,
+                domain: SpawnTabDomain::CurrentPaneDomain,
+            })
+            .await?;
+
+        self.resync().await?;
+
+        let pane_id = inner
+            .remote_to_local_pane_id(result.pane_id)
+            .ok_or_else(|| anyhow!("remote pane {} didn't resolve after resync", result.pane_id))?;
+        Mux::get()
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("local pane {pane_id} is invalid"))
+    }
+
     /// Forward the request to the remote; we need to translate the local ids
     /// to those that match the remote for the request, resync the changed
     /// structure, and then translate the results back to local
@@ -814,6 +1331,63 @@ impl Domain for ClientDomain {
         Ok(Some((tab, local_win_id)))
     }
 
+    /// The foreground process of a remote pane is only accessible to
+    /// the remote, so ask it to resolve the environment
+    async fn resolve_inherited_env(
+        &self,
+        pane: &Arc<dyn Pane>,
+        inherit_env: &InheritEnvironment,
+    ) -> anyhow::Result<HashMap<String, String>> {
+        if inherit_env.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+        let pane = pane
+            .downcast_ref::<ClientPane>()
+            .ok_or_else(|| anyhow!("pane_id {} is not a ClientPane", pane.pane_id()))?;
+        let response = inner
+            .client
+            .resolve_inherited_env(codec::ResolveInheritedEnv {
+                pane_id: pane.remote_pane_id,
+                inherit_env: inherit_env.clone(),
+            })
+            .await?;
+        Ok(response.env)
+    }
+
+    /// Asks the remote to respawn its pane in place; the pane keeps
+    /// its remote id, so there is no structure to resync
+    async fn respawn_pane(
+        &self,
+        pane_id: PaneId,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+    ) -> anyhow::Result<()> {
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+
+        let local_pane = Mux::get()
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane_id {} is invalid", pane_id))?;
+        let pane = local_pane
+            .downcast_ref::<ClientPane>()
+            .ok_or_else(|| anyhow!("pane_id {} is not a ClientPane", pane_id))?;
+
+        inner
+            .client
+            .respawn_pane(codec::RespawnPane {
+                pane_id: pane.remote_pane_id,
+                command,
+                command_dir,
+            })
+            .await?;
+
+        Ok(())
+    }
+
     async fn spawn(
         &self,
         size: TerminalSize,
@@ -836,6 +1410,8 @@ impl Domain for ClientDomain {
                 command,
                 command_dir,
                 workspace,
+                // Profiles are applied by the local mux
+                profile: None,
             })
             .await?;
 
@@ -883,12 +1459,15 @@ impl Domain for ClientDomain {
             .downcast_ref::<ClientPane>()
             .ok_or_else(|| anyhow!("pane_id {} is not a ClientPane", pane_id))?;
 
-        let (command, command_dir, move_pane_id) = match source {
+        let (command, command_dir, inherit_env, move_pane_id) = match source {
             SplitSource::Spawn {
                 command,
                 command_dir,
-            } => (command, command_dir, None),
-            SplitSource::MovePane(move_pane_id) => (None, None, Some(move_pane_id)),
+                inherit_env,
+            } => (command, command_dir, inherit_env, None),
+            SplitSource::MovePane(move_pane_id) => {
+                (None, None, Default::default(), Some(move_pane_id))
+            }
         };
 
         let result = inner
@@ -899,6 +1478,7 @@ impl Domain for ClientDomain {
                 split_request,
                 command,
                 command_dir,
+                inherit_env,
                 move_pane_id,
             })
             .await?;
@@ -1005,4 +1585,11 @@ impl Domain for ClientDomain {
             DomainState::Detached
         }
     }
+
+    fn connection_stats(&self) -> Option<ConnectionStats> {
+        Some(match self.inner() {
+            Some(inner) => inner.client.connection_stats(),
+            None => ConnectionStats::default(),
+        })
+    }
 }
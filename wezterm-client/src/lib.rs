@@ -2,3 +2,4 @@ pub mod client;
 pub mod discovery;
 pub mod domain;
 pub mod pane;
+pub mod pinning;
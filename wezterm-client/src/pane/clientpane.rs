@@ -5,12 +5,16 @@ use anyhow::bail;
 use async_trait::async_trait;
 use codec::*;
 use config::configuration;
-use config::keyassignment::ScrollbackEraseMode;
+use config::keyassignment::{PaneLinkOptions, ScrollbackEraseMode};
+use mux::annotation::PaneAnnotations;
 use mux::domain::DomainId;
+use mux::highlight::PaneHighlights;
+use mux::monitor::{OutputAlerts, OutputMonitor};
 use mux::pane::{
     alloc_pane_id, CachePolicy, CloseReason, ForEachPaneLogicalLine, LogicalLine, Pane, PaneId,
     Pattern, SearchResult, WithPaneLines,
 };
+use mux::pane_watch::{PaneWatch, PaneWatches};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
 use mux::{Mux, MuxNotification};
@@ -21,16 +25,29 @@ use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
 use std::ops::Range;
 use std::sync::Arc;
-use termwiz::input::KeyEvent;
+use std::time::{Duration, Instant};
+use termwiz::escape::csi::KittyKeyboardFlags;
+use termwiz::escape::osc::Progress;
+use termwiz::input::{KeyEvent, KeyboardEncoding};
 use termwiz::surface::SequenceNo;
 use url::Url;
 use wezterm_dynamic::Value;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
-    Alert, Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex,
-    TerminalConfiguration, TerminalSize,
+    Alert, Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, MousePointerShape, SemanticZone,
+    StableRowIndex, TerminalConfiguration, TerminalSize,
 };
 
+/// The semantic zones most recently fetched from the server,
+/// along with the seqno of the pane at the time that they
+/// were requested
+#[derive(Default)]
+struct SemanticZoneCache {
+    seqno: SequenceNo,
+    zones: Vec<SemanticZone>,
+    fetching: bool,
+}
+
 pub struct ClientPane {
     client: Arc<ClientInner>,
     local_pane_id: PaneId,
@@ -44,10 +61,29 @@ pub struct ClientPane {
     mouse: Arc<Mutex<MouseState>>,
     clipboard: Mutex<Option<Arc<dyn Clipboard>>>,
     mouse_grabbed: Mutex<bool>,
+    bracketed_paste: Mutex<bool>,
+    mouse_pointer_shape: Mutex<Option<MousePointerShape>>,
+    kitty_keyboard_flags: Mutex<Option<u16>>,
+    ime_enabled: Mutex<bool>,
     ignore_next_kill: Mutex<bool>,
     user_vars: Mutex<HashMap<String, String>>,
     config: Mutex<Option<Arc<dyn TerminalConfiguration>>>,
     unseen_output: Mutex<bool>,
+    /// Records alerts raised by the server and any local
+    /// override of the silence threshold
+    output_monitor: Mutex<OutputMonitor>,
+    progress: Mutex<Progress>,
+    output_rate_limited: Mutex<bool>,
+    output_paused: Mutex<bool>,
+    /// Mirrors the watches that have been added on the server,
+    /// which is where the matching happens
+    watches: Mutex<PaneWatches>,
+    highlights: Arc<Mutex<PaneHighlights>>,
+    annotations: Arc<Mutex<PaneAnnotations>>,
+    semantic_zones: Arc<Mutex<SemanticZoneCache>>,
+    timestamp_gutter_cols: Mutex<usize>,
+    /// The nesting depth of the server, as reported in the pane tree
+    nesting_depth: Mutex<usize>,
 }
 
 impl ClientPane {
@@ -125,10 +161,24 @@ impl ClientPane {
             palette: Mutex::new(palette),
             clipboard: Mutex::new(None),
             mouse_grabbed: Mutex::new(false),
+            bracketed_paste: Mutex::new(false),
+            mouse_pointer_shape: Mutex::new(None),
+            kitty_keyboard_flags: Mutex::new(None),
+            ime_enabled: Mutex::new(true),
             ignore_next_kill: Mutex::new(false),
             unseen_output: Mutex::new(false),
+            output_monitor: Mutex::new(OutputMonitor::default()),
+            progress: Mutex::new(Progress::None),
+            output_rate_limited: Mutex::new(false),
+            output_paused: Mutex::new(false),
+            watches: Mutex::new(PaneWatches::default()),
+            highlights: Arc::new(Mutex::new(PaneHighlights::default())),
+            annotations: Arc::new(Mutex::new(PaneAnnotations::default())),
             user_vars: Mutex::new(HashMap::new()),
             config: Mutex::new(None),
+            semantic_zones: Arc::new(Mutex::new(SemanticZoneCache::default())),
+            timestamp_gutter_cols: Mutex::new(0),
+            nesting_depth: Mutex::new(0),
         }
     }
 
@@ -136,6 +186,9 @@ impl ClientPane {
         match pdu {
             Pdu::GetPaneRenderChangesResponse(mut delta) => {
                 *self.mouse_grabbed.lock() = delta.mouse_grabbed;
+                *self.bracketed_paste.lock() = delta.bracketed_paste;
+                *self.mouse_pointer_shape.lock() = delta.mouse_pointer_shape;
+                *self.kitty_keyboard_flags.lock() = delta.kitty_keyboard_flags;
 
                 let bonus_lines = std::mem::take(&mut delta.bonus_lines);
                 let client = { Arc::clone(&self.renderable.lock().inner.borrow().client) };
@@ -147,6 +200,26 @@ impl ClientPane {
                     .borrow_mut()
                     .apply_changes_to_surface(delta, bonus_lines);
             }
+            Pdu::PaneOutputSummary(PaneOutputSummary { title, .. }) => {
+                // We're not subscribed to the full output of this pane,
+                // so there is nothing to render; just keep the title
+                // current for the tab bar.  The catch-up when the pane
+                // becomes visible again will bring the lines up to date.
+                let title_changed = {
+                    let renderable = self.renderable.lock();
+                    let mut inner = renderable.inner.borrow_mut();
+                    inner.last_recv_time = Instant::now();
+                    if inner.title != title {
+                        inner.title = title;
+                        true
+                    } else {
+                        false
+                    }
+                };
+                if title_changed {
+                    Mux::get().notify(MuxNotification::PaneOutput(self.local_pane_id));
+                }
+            }
             Pdu::SetClipboard(SetClipboard {
                 clipboard,
                 selection,
@@ -190,6 +263,18 @@ impl ClientPane {
                             alert: Alert::OutputSinceFocusLost,
                         });
                     }
+                    Alert::PaneOutputActivity | Alert::PaneSilence { .. } => {
+                        self.output_monitor.lock().record_alert(&alert);
+                    }
+                    Alert::Progress(progress) => {
+                        *self.progress.lock() = *progress;
+                    }
+                    Alert::OutputRateLimited(limited) => {
+                        *self.output_rate_limited.lock() = *limited;
+                    }
+                    Alert::OutputPaused(paused) => {
+                        *self.output_paused.lock() = *paused;
+                    }
                     _ => {}
                 }
                 mux.notify(MuxNotification::Alert {
@@ -241,6 +326,10 @@ impl ClientPane {
     pub fn ignore_next_kill(&self) {
         *self.ignore_next_kill.lock() = true;
     }
+
+    pub fn set_nesting_depth(&self, depth: usize) {
+        *self.nesting_depth.lock() = depth;
+    }
 }
 
 #[async_trait(?Send)]
@@ -434,6 +523,30 @@ impl Pane for ClientPane {
         }
     }
 
+    async fn fetch_lines(
+        &self,
+        lines: Range<StableRowIndex>,
+    ) -> anyhow::Result<(StableRowIndex, Vec<Line>)> {
+        let result = self
+            .client
+            .client
+            .get_lines(GetLines {
+                pane_id: self.remote_pane_id,
+                lines: vec![lines.clone()],
+            })
+            .await?;
+        let lines_with_idx =
+            hydrate_lines(Arc::clone(&self.client), self.remote_pane_id, result.lines).await;
+        let first_row = lines_with_idx
+            .first()
+            .map(|(idx, _)| *idx)
+            .unwrap_or(lines.start);
+        Ok((
+            first_row,
+            lines_with_idx.into_iter().map(|(_, line)| line).collect(),
+        ))
+    }
+
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
         let input_serial;
         {
@@ -532,11 +645,87 @@ impl Pane for ClientPane {
         *self.mouse_grabbed.lock()
     }
 
+    fn get_mouse_pointer_shape(&self) -> Option<MousePointerShape> {
+        *self.mouse_pointer_shape.lock()
+    }
+
+    fn get_keyboard_encoding(&self) -> KeyboardEncoding {
+        match *self.kitty_keyboard_flags.lock() {
+            Some(flags) => KeyboardEncoding::Kitty(KittyKeyboardFlags::from_bits_truncate(flags)),
+            None => KeyboardEncoding::Xterm,
+        }
+    }
+
+    fn is_ime_enabled(&self) -> bool {
+        *self.ime_enabled.lock()
+    }
+
+    fn set_ime_enabled(&self, enabled: bool) {
+        *self.ime_enabled.lock() = enabled;
+    }
+
+    fn timestamp_gutter_cols(&self) -> usize {
+        *self.timestamp_gutter_cols.lock()
+    }
+
+    fn set_timestamp_gutter_cols(&self, cols: usize) {
+        *self.timestamp_gutter_cols.lock() = cols;
+    }
+
+    fn is_grapheme_clustering_enabled(&self) -> bool {
+        self.renderable.lock().inner.borrow().grapheme_clustering
+    }
+
+    fn is_bracketed_paste_enabled(&self) -> bool {
+        *self.bracketed_paste.lock()
+    }
+
     fn is_alt_screen_active(&self) -> bool {
         // FIXME: retrieve this from the remote
         false
     }
 
+    fn get_semantic_zones(&self) -> anyhow::Result<Vec<SemanticZone>> {
+        let seqno = self.get_current_seqno();
+        let mut cache = self.semantic_zones.lock();
+        if cache.seqno != seqno && !cache.fetching {
+            // Return the stale zones for now, and refresh them in
+            // the background; we'll be asked again once they arrive
+            cache.fetching = true;
+            let client = Arc::clone(&self.client);
+            let remote_pane_id = self.remote_pane_id;
+            let local_pane_id = self.local_pane_id;
+            let semantic_zones = Arc::clone(&self.semantic_zones);
+            promise::spawn::spawn(async move {
+                let result = client
+                    .client
+                    .get_semantic_zones(GetSemanticZones {
+                        pane_id: remote_pane_id,
+                    })
+                    .await;
+                let mut cache = semantic_zones.lock();
+                cache.fetching = false;
+                match result {
+                    Ok(GetSemanticZonesResponse { zones }) => {
+                        cache.seqno = seqno;
+                        cache.zones = zones;
+                        drop(cache);
+                        Mux::get().notify(MuxNotification::PaneOutput(local_pane_id));
+                    }
+                    Err(err) => {
+                        // Don't keep retrying a server that doesn't
+                        // know how to answer
+                        cache.seqno = seqno;
+                        log::debug!("get_semantic_zones failed: {err:#}");
+                    }
+                }
+                anyhow::Result::<()>::Ok(())
+            })
+            .detach();
+        }
+        Ok(cache.zones.clone())
+    }
+
     fn get_current_working_dir(&self, _policy: CachePolicy) -> Option<Url> {
         self.renderable.lock().inner.borrow().working_dir.clone()
     }
@@ -545,7 +734,105 @@ impl Pane for ClientPane {
         if focused {
             self.advise_focus();
             *self.unseen_output.lock() = false;
+            self.output_monitor.lock().focused();
+        }
+    }
+
+    fn get_progress(&self) -> Progress {
+        *self.progress.lock()
+    }
+
+    fn is_output_rate_limited(&self) -> bool {
+        *self.output_rate_limited.lock()
+    }
+
+    fn is_output_paused(&self) -> bool {
+        *self.output_paused.lock()
+    }
+
+    /// The server stops reading from the pty.  The state is only
+    /// updated by the alert that the server raises in response, so
+    /// that it doesn't show as paused if the request fails.
+    fn set_output_paused(&self, paused: bool) {
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            if let Err(err) = client
+                .client
+                .pause_output(PauseOutput {
+                    pane_id: remote_pane_id,
+                    paused,
+                })
+                .await
+            {
+                log::error!("failed to pause output of pane {remote_pane_id}: {err:#}");
+            }
+        })
+        .detach();
+    }
+
+    fn add_watch(&self, watch: PaneWatch) -> anyhow::Result<()> {
+        let request = AddPaneWatch {
+            pane_id: self.remote_pane_id,
+            id: watch.id.clone(),
+            pattern: watch.pattern.clone(),
+            regex: watch.regex,
+        };
+        self.watches.lock().add(watch, 0)?;
+        let client = Arc::clone(&self.client);
+        promise::spawn::spawn(async move {
+            if let Err(err) = client.client.add_pane_watch(request).await {
+                log::error!("failed to add pane watch: {:#}", err);
+            }
+        })
+        .detach();
+        Ok(())
+    }
+
+    fn remove_watch(&self, id: &str) -> bool {
+        if !self.watches.lock().remove(id) {
+            return false;
         }
+        let client = Arc::clone(&self.client);
+        let request = RemovePaneWatch {
+            pane_id: self.remote_pane_id,
+            id: id.to_string(),
+        };
+        promise::spawn::spawn(async move { client.client.remove_pane_watch(request).await })
+            .detach();
+        true
+    }
+
+    fn highlights(&self) -> Option<Arc<Mutex<PaneHighlights>>> {
+        Some(Arc::clone(&self.highlights))
+    }
+
+    fn annotations(&self) -> Option<Arc<Mutex<PaneAnnotations>>> {
+        Some(Arc::clone(&self.annotations))
+    }
+
+    fn get_output_alerts(&self) -> OutputAlerts {
+        self.output_monitor.lock().alerts()
+    }
+
+    fn get_silence_threshold(&self) -> Option<Duration> {
+        self.output_monitor.lock().silence_threshold()
+    }
+
+    fn set_silence_threshold(&self, threshold: Option<Duration>) {
+        self.output_monitor.lock().set_silence_threshold(threshold);
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .set_pane_silence_threshold(SetPaneSilenceThreshold {
+                    pane_id: remote_pane_id,
+                    seconds: threshold.map(|t| t.as_secs()),
+                })
+                .await
+        })
+        .detach();
     }
 
     fn erase_scrollback(&self, erase_mode: ScrollbackEraseMode) {
@@ -563,6 +850,26 @@ impl Pane for ClientPane {
         .detach();
     }
 
+    fn prepend_scrollback(&self, lines: Vec<Line>) -> anyhow::Result<()> {
+        let request = PrependScrollback {
+            pane_id: self.remote_pane_id,
+            lines: lines
+                .into_iter()
+                .enumerate()
+                .map(|(idx, line)| (idx as StableRowIndex, line))
+                .collect::<Vec<_>>()
+                .into(),
+        };
+        let client = Arc::clone(&self.client);
+        promise::spawn::spawn(async move {
+            if let Err(err) = client.client.prepend_scrollback(request).await {
+                log::error!("failed to restore scrollback: {:#}", err);
+            }
+        })
+        .detach();
+        Ok(())
+    }
+
     fn advise_focus(&self) {
         let mut focused_pane = self.client.focused_remote_pane_id.lock().unwrap();
         if *focused_pane != Some(self.remote_pane_id) {
@@ -585,6 +892,32 @@ impl Pane for ClientPane {
         *self.unseen_output.lock()
     }
 
+    fn mark_output_seen(&self) {
+        *self.unseen_output.lock() = false;
+    }
+
+    fn link_with(&self, other: &Arc<dyn Pane>, options: PaneLinkOptions) {
+        // The server can only record links between panes that it hosts
+        let other = match other.downcast_ref::<ClientPane>() {
+            Some(other) if other.client.local_domain_id == self.client.local_domain_id => other,
+            _ => return,
+        };
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        let other_remote_pane_id = other.remote_pane_id;
+        promise::spawn::spawn(async move {
+            client
+                .client
+                .link_panes(LinkPanes {
+                    pane_id: remote_pane_id,
+                    other_pane_id: other_remote_pane_id,
+                    options,
+                })
+                .await
+        })
+        .detach();
+    }
+
     fn can_close_without_prompting(&self, reason: CloseReason) -> bool {
         match reason {
             CloseReason::Window => true,
@@ -597,6 +930,10 @@ impl Pane for ClientPane {
         self.user_vars.lock().clone()
     }
 
+    fn get_nesting_depth(&self) -> usize {
+        *self.nesting_depth.lock()
+    }
+
     fn set_config(&self, config: Arc<dyn TerminalConfiguration>) {
         let palette = config.color_palette();
         // If the application running in the pane hasn't changed the
@@ -69,6 +69,8 @@ pub struct RenderableInner {
     pub title: String,
     pub working_dir: Option<Url>,
     pub seqno: SequenceNo,
+    /// Whether the remote terminal has DEC private mode 2027 enabled
+    pub grapheme_clustering: bool,
 
     fetch_limiter: RateLimiter,
 
@@ -117,6 +119,7 @@ impl RenderableInner {
             last_input_rtt: 0,
             input_serial: InputSerial::empty(),
             seqno: SEQ_ZERO,
+            grapheme_clustering: true,
         }
     }
 
@@ -252,7 +255,10 @@ impl RenderableInner {
             .set_underline(Underline::Double)
             .clone();
 
-        let text_line = Line::from_text(text, &attrs, SEQ_ZERO, None);
+        // Measure the prediction the same way that the
+        // remote terminal will measure the pasted text
+        let text_line =
+            Line::from_text_with_clustering(text, &attrs, SEQ_ZERO, None, self.grapheme_clustering);
 
         if row == 0 {
             for cell in text_line.visible_cells() {
@@ -351,6 +357,7 @@ impl RenderableInner {
         }
         self.dimensions = delta.dimensions;
         self.title = delta.title;
+        self.grapheme_clustering = delta.grapheme_clustering;
         self.working_dir = delta.working_dir.map(Into::into);
         log::trace!(
             "server says: seqno from {} -> {} for local_pane_id={}",
@@ -636,8 +643,84 @@ impl RenderableInner {
     }
 }
 
+/// Caches image data fetched from the server, keyed by its hash.
+/// The least recently used images are evicted to keep the total
+/// size within `mux_image_cache_memory_mb`.
+struct ImageDataCache {
+    lru: LruCache<[u8; 32], Arc<ImageData>>,
+    size: usize,
+}
+
+impl ImageDataCache {
+    fn get(&mut self, hash: &[u8; 32]) -> Option<Arc<ImageData>> {
+        self.lru.get(hash).map(Arc::clone)
+    }
+
+    fn put(&mut self, data: Arc<ImageData>) {
+        let len = data.len();
+        if let Some(prior) = self.lru.put(data.hash(), data) {
+            self.size -= prior.len();
+        }
+        self.size += len;
+
+        let budget = configuration().mux_image_cache_memory_mb * 1024 * 1024;
+        // Always retain the most recently added image, even if it
+        // exceeds the budget by itself, so that it can be displayed
+        while self.size > budget && self.lru.len() > 1 {
+            match self.lru.pop_lru() {
+                Some((_, evicted)) => self.size -= evicted.len(),
+                None => break,
+            }
+        }
+    }
+}
+
 lazy_static::lazy_static! {
-    static ref IMAGE_LRU: Mutex<LruCache<[u8;32], Arc<ImageData>>> = Mutex::new(LruCache::new(NonZeroUsize::new(128).unwrap()));
+    static ref IMAGE_CACHE: Mutex<ImageDataCache> = Mutex::new(ImageDataCache {
+        lru: LruCache::unbounded(),
+        size: 0,
+    });
+}
+
+/// Retrieves image data from the server in chunks of up
+/// to IMAGE_DATA_CHUNK_SIZE bytes
+async fn fetch_image_data(
+    client: &Arc<ClientInner>,
+    pane_id: PaneId,
+    im: &SerializedImageCell,
+) -> anyhow::Result<Arc<ImageData>> {
+    let mut bytes = vec![];
+    loop {
+        let response = client
+            .client
+            .fetch_image_data(FetchImageData {
+                pane_id,
+                line_idx: im.line_idx,
+                cell_idx: im.cell_idx,
+                data_hash: im.data_hash,
+                offset: bytes.len(),
+            })
+            .await?;
+        let total_len = response
+            .total_len
+            .ok_or_else(|| anyhow!("server no longer has the image data"))?;
+        anyhow::ensure!(
+            response.offset == bytes.len() && !response.chunk.is_empty(),
+            "unexpected image data chunk at offset {} of {total_len}",
+            response.offset
+        );
+        bytes.extend_from_slice(&response.chunk);
+        if bytes.len() >= total_len {
+            break;
+        }
+    }
+
+    let data = deserialize_image_data(&bytes)?;
+    anyhow::ensure!(
+        data.hash() == im.data_hash,
+        "received image data doesn't match the requested hash"
+    );
+    Ok(Arc::new(data))
 }
 
 pub(crate) async fn hydrate_lines(
@@ -654,35 +737,19 @@ pub(crate) async fn hydrate_lines(
     let mut requests = HashMap::new();
     let mut data_by_hash = HashMap::new();
     for im in &image_cells {
-        if let Some(data) = IMAGE_LRU.lock().unwrap().get(&im.data_hash) {
-            data_by_hash.insert(im.data_hash, Arc::clone(data));
+        if let Some(data) = IMAGE_CACHE.lock().unwrap().get(&im.data_hash) {
+            data_by_hash.insert(im.data_hash, data);
         } else {
-            requests
-                .entry(&im.data_hash)
-                .or_insert_with(|| GetImageCell {
-                    pane_id,
-                    line_idx: im.line_idx,
-                    cell_idx: im.cell_idx,
-                    data_hash: im.data_hash,
-                });
+            requests.entry(im.data_hash).or_insert(im);
         }
     }
 
-    for (_, request) in requests {
-        match client.client.get_image_cell(request).await {
-            Ok(GetImageCellResponse {
-                data: Some(data), ..
-            }) => {
-                IMAGE_LRU
-                    .lock()
-                    .unwrap()
-                    .put(data.hash(), Arc::clone(&data));
+    for (_, im) in requests {
+        match fetch_image_data(&client, pane_id, im).await {
+            Ok(data) => {
+                IMAGE_CACHE.lock().unwrap().put(Arc::clone(&data));
                 data_by_hash.insert(data.hash(), data);
             }
-            Ok(GetImageCellResponse { data: None, .. }) => {
-                log::error!("no image data!");
-            }
-
             Err(err) => {
                 log::error!("failed to retrieve image {err:#}");
             }
@@ -0,0 +1,481 @@
+//! Trust-on-first-use pinning of the certificates that are presented
+//! by the servers of TLS domains.
+//! The fingerprint of the certificate that a server presents the first
+//! time that we connect to it is saved, keyed by its remote address.
+//! If it later presents a different certificate, the user is asked
+//! whether to trust it, in the gui window that holds the domain if the
+//! gui is running, or otherwise in the connection window or terminal.
+//! Alternatively, `expected_cert_fingerprints` pins the certificates
+//! non-interactively.
+use anyhow::{anyhow, bail, Context};
+use config::TlsDomainClient;
+use mux::connui::ConnectionUI;
+use mux::domain::DomainId;
+use openssl::hash::MessageDigest;
+use openssl::x509::X509Ref;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use termwiz::istty::IsTty;
+
+pub fn known_hosts_file_name() -> PathBuf {
+    config::RUNTIME_DIR.join("tls-known-hosts")
+}
+
+/// Returns the SHA-256 fingerprint of `cert`, formatted the same way
+/// as `openssl x509 -noout -fingerprint -sha256`
+pub fn fingerprint(cert: &X509Ref) -> anyhow::Result<String> {
+    let digest = cert.digest(MessageDigest::sha256())?;
+    Ok(format_fingerprint(&digest))
+}
+
+fn format_fingerprint(digest: &[u8]) -> String {
+    let hex: Vec<String> = digest.iter().map(|b| format!("{b:02X}")).collect();
+    format!("SHA256:{}", hex.join(":"))
+}
+
+/// Reduces a fingerprint to lowercase hex digits, so that fingerprints
+/// that are written with or without the digest prefix and separators
+/// compare equal
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    let fingerprint = fingerprint.trim();
+    let fingerprint = match fingerprint.split_once(':') {
+        Some((prefix, rest)) if prefix.eq_ignore_ascii_case("sha256") => rest,
+        _ => fingerprint,
+    };
+    fingerprint
+        .chars()
+        .filter(|c| c.is_ascii_hexdigit())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+pub fn same_fingerprint(a: &str, b: &str) -> bool {
+    normalize_fingerprint(a) == normalize_fingerprint(b)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedHost {
+    pub remote_address: String,
+    pub fingerprint: String,
+}
+
+/// Parses the known hosts file, which has one `address fingerprint`
+/// pair per line
+fn parse_known_hosts(text: &str) -> Vec<TrustedHost> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.starts_with('#') {
+                return None;
+            }
+            let mut fields = line.split_whitespace();
+            Some(TrustedHost {
+                remote_address: fields.next()?.to_string(),
+                fingerprint: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn format_known_hosts(hosts: &[TrustedHost]) -> String {
+    hosts
+        .iter()
+        .map(|host| format!("{} {}\n", host.remote_address, host.fingerprint))
+        .collect()
+}
+
+pub fn load_trusted_hosts() -> anyhow::Result<Vec<TrustedHost>> {
+    let file_name = known_hosts_file_name();
+    match std::fs::read_to_string(&file_name) {
+        Ok(text) => Ok(parse_known_hosts(&text)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(err).context(format!("reading {}", file_name.display())),
+    }
+}
+
+fn update_trusted_hosts<F: FnOnce(&mut Vec<TrustedHost>)>(f: F) -> anyhow::Result<()> {
+    config::statefile::update(&known_hosts_file_name(), |data| {
+        let text = data.map(String::from_utf8).transpose()?.unwrap_or_default();
+        let mut hosts = parse_known_hosts(&text);
+        f(&mut hosts);
+        Ok(format_known_hosts(&hosts).into_bytes())
+    })
+}
+
+/// Records `fingerprint` as the trusted certificate of `remote_address`
+pub fn trust_host(remote_address: &str, fingerprint: &str) -> anyhow::Result<()> {
+    update_trusted_hosts(|hosts| {
+        hosts.retain(|host| host.remote_address != remote_address);
+        hosts.push(TrustedHost {
+            remote_address: remote_address.to_string(),
+            fingerprint: fingerprint.to_string(),
+        });
+    })
+}
+
+/// Forgets the certificate of `remote_address`, so that the next
+/// certificate that it presents is trusted on first use.
+/// Returns false if it wasn't known.
+pub fn remove_trusted_host(remote_address: &str) -> anyhow::Result<bool> {
+    let mut removed = false;
+    update_trusted_hosts(|hosts| {
+        let before = hosts.len();
+        hosts.retain(|host| host.remote_address != remote_address);
+        removed = hosts.len() != before;
+    })?;
+    Ok(removed)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertificateStatus {
+    /// The certificate matches the pinned or expected certificate
+    Trusted,
+    /// We have not seen this server before
+    FirstUse,
+    /// The server presented a different certificate last time
+    Changed { previous: String },
+    /// The certificate is not one of `expected_cert_fingerprints`
+    NotExpected,
+}
+
+pub fn check_certificate(
+    expected: &[String],
+    known: Option<&str>,
+    fingerprint: &str,
+) -> CertificateStatus {
+    if !expected.is_empty() {
+        if expected.iter().any(|e| same_fingerprint(e, fingerprint)) {
+            CertificateStatus::Trusted
+        } else {
+            CertificateStatus::NotExpected
+        }
+    } else {
+        match known {
+            None => CertificateStatus::FirstUse,
+            Some(known) if same_fingerprint(known, fingerprint) => CertificateStatus::Trusted,
+            Some(known) => CertificateStatus::Changed {
+                previous: known.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateAnswer {
+    AcceptAndSave,
+    AcceptOnce,
+    Reject,
+}
+
+/// A request to confirm a changed server certificate.
+/// Dropping it without answering rejects the certificate.
+pub struct CertificateChange {
+    pub remote_address: String,
+    pub previous_fingerprint: String,
+    pub fingerprint: String,
+    reply: smol::channel::Sender<CertificateAnswer>,
+}
+
+impl CertificateChange {
+    pub fn answer(self, answer: CertificateAnswer) {
+        self.reply.try_send(answer).ok();
+    }
+
+    /// Returns the lines of text that explain the change to the user
+    pub fn describe(&self) -> Vec<String> {
+        vec![
+            format!(
+                "The TLS certificate presented by {} has changed.",
+                self.remote_address
+            ),
+            String::new(),
+            format!("Previous fingerprint: {}", self.previous_fingerprint),
+            format!("New fingerprint:      {}", self.fingerprint),
+            String::new(),
+            "This is expected if the server was reinstalled or its certificate".to_string(),
+            "was replaced, but it may also mean that someone is intercepting".to_string(),
+            "the connection.".to_string(),
+            String::new(),
+            "Do you trust the new certificate?".to_string(),
+        ]
+    }
+}
+
+pub const CERTIFICATE_PROMPT: &str = "Enter [a]ccept and save, accept [o]nce, or [r]eject> ";
+
+pub fn parse_answer(line: &str) -> CertificateAnswer {
+    match line.trim().to_lowercase().as_str() {
+        "a" | "accept" | "y" | "yes" => CertificateAnswer::AcceptAndSave,
+        "o" | "once" => CertificateAnswer::AcceptOnce,
+        _ => CertificateAnswer::Reject,
+    }
+}
+
+/// Called with certificate changes that need to be confirmed.
+/// It must not block; the connection waits until the change is
+/// answered or dropped, or handed back via `prompt_inline`.
+pub type CertificateChangeHandler = Arc<dyn Fn(CertificateChangeRequest) + Send + Sync>;
+
+/// A certificate change that is passed to the `CertificateChangeHandler`.
+/// The handler either takes the change in order to answer it, or hands
+/// it back so that the connection prompts for the answer itself.
+pub struct CertificateChangeRequest {
+    /// The domain that is connecting, if it has been assigned one.
+    /// The prompt should be shown in a window that holds its panes.
+    pub domain_id: Option<DomainId>,
+    change: CertificateChange,
+    fallback: smol::channel::Sender<CertificateChange>,
+}
+
+impl CertificateChangeRequest {
+    pub fn change(&self) -> &CertificateChange {
+        &self.change
+    }
+
+    /// Takes ownership of the change; the caller is responsible
+    /// for answering it
+    pub fn into_change(self) -> CertificateChange {
+        self.change
+    }
+
+    /// Hands the change back to the connection, which will prompt for
+    /// the answer in its connection window or on the terminal
+    pub fn prompt_inline(self) {
+        if let Err(err) = self.fallback.try_send(self.change) {
+            log::error!("unable to prompt for the changed certificate: {err:#}");
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CERTIFICATE_CHANGE_HANDLER: Mutex<Option<CertificateChangeHandler>> =
+        Mutex::new(None);
+}
+
+/// Registers the handler used to confirm changed certificates in place
+/// of the terminal prompt.  The GUI uses this to show a modal.
+pub fn set_certificate_change_handler(handler: CertificateChangeHandler) {
+    CERTIFICATE_CHANGE_HANDLER.lock().unwrap().replace(handler);
+}
+
+/// Passes the change to the registered handler, if any.
+/// Returns the change back to the caller if it needs to prompt for itself,
+/// either because there is no handler, or because the handler had nowhere
+/// to show it.
+fn dispatch_certificate_change(
+    domain_id: Option<DomainId>,
+    change: CertificateChange,
+) -> Option<CertificateChange> {
+    let handler = CERTIFICATE_CHANGE_HANDLER.lock().unwrap().clone();
+    match handler {
+        Some(handler) => {
+            let (fallback, rx) = smol::channel::bounded(1);
+            handler(CertificateChangeRequest {
+                domain_id,
+                change,
+                fallback,
+            });
+            // The sender is dropped without sending when the
+            // handler has taken the change
+            rx.recv_blocking().ok()
+        }
+        None => Some(change),
+    }
+}
+
+/// Asks about the change in the connection window.  A headless
+/// connection can't take input, so we ask on the controlling terminal.
+fn prompt_inline(change: &CertificateChange, ui: &mut ConnectionUI) -> CertificateAnswer {
+    let prompt = format!("{}\n{CERTIFICATE_PROMPT}", change.describe().join("\n"));
+    match ui.input(&prompt) {
+        Ok(line) => parse_answer(&line),
+        Err(err) => {
+            log::debug!("{err:#}, prompting on the terminal instead");
+            prompt_on_terminal(change)
+        }
+    }
+}
+
+/// Asks about the change on the controlling terminal, if there is one.
+/// Without a terminal, the change is rejected.
+fn prompt_on_terminal(change: &CertificateChange) -> CertificateAnswer {
+    if !std::io::stdin().is_tty() {
+        log::error!(
+            "No terminal is available to confirm the changed certificate for {}",
+            change.remote_address
+        );
+        return CertificateAnswer::Reject;
+    }
+    let mut stderr = std::io::stderr();
+    for line in change.describe() {
+        writeln!(stderr, "{line}").ok();
+    }
+    write!(stderr, "{CERTIFICATE_PROMPT}").ok();
+    stderr.flush().ok();
+
+    let mut line = String::new();
+    match std::io::stdin().lock().read_line(&mut line) {
+        Ok(_) => parse_answer(&line),
+        Err(_) => CertificateAnswer::Reject,
+    }
+}
+
+/// Asks whether to trust a changed certificate, and waits for the answer
+fn confirm_certificate_change(
+    domain_id: Option<DomainId>,
+    remote_address: &str,
+    previous_fingerprint: &str,
+    fingerprint: &str,
+    ui: &mut ConnectionUI,
+) -> CertificateAnswer {
+    let (reply, answer) = smol::channel::bounded(1);
+    let change = CertificateChange {
+        remote_address: remote_address.to_string(),
+        previous_fingerprint: previous_fingerprint.to_string(),
+        fingerprint: fingerprint.to_string(),
+        reply,
+    };
+    match dispatch_certificate_change(domain_id, change) {
+        Some(change) => prompt_inline(&change, ui),
+        None => smol::block_on(answer.recv()).unwrap_or(CertificateAnswer::Reject),
+    }
+}
+
+/// Checks the certificate that was presented by the server of
+/// `tls_client` against the pinned or expected certificates.
+/// `domain_id` is the local domain that is connecting, if any.
+pub fn verify_server_certificate(
+    domain_id: Option<DomainId>,
+    tls_client: &TlsDomainClient,
+    cert: Option<&X509Ref>,
+    ui: &mut ConnectionUI,
+) -> anyhow::Result<()> {
+    let remote_address = &tls_client.remote_address;
+    let cert = cert.ok_or_else(|| anyhow!("{remote_address} did not present a certificate"))?;
+    let fingerprint = fingerprint(cert)?;
+
+    let known = load_trusted_hosts()?
+        .into_iter()
+        .find(|host| &host.remote_address == remote_address)
+        .map(|host| host.fingerprint);
+
+    match check_certificate(
+        &tls_client.expected_cert_fingerprints,
+        known.as_deref(),
+        &fingerprint,
+    ) {
+        CertificateStatus::Trusted => Ok(()),
+        CertificateStatus::FirstUse => {
+            ui.output_str(&format!(
+                "Trusting certificate {fingerprint} for {remote_address}\n"
+            ));
+            if let Err(err) = trust_host(remote_address, &fingerprint) {
+                log::error!("Failed to save certificate for {remote_address}: {err:#}");
+            }
+            Ok(())
+        }
+        CertificateStatus::NotExpected => bail!(
+            "The certificate presented by {remote_address} has fingerprint \
+             {fingerprint}, which is not one of its expected_cert_fingerprints"
+        ),
+        CertificateStatus::Changed { previous } => {
+            ui.output_str(&format!(
+                "The certificate presented by {remote_address} has changed; \
+                 waiting for confirmation\n"
+            ));
+            match confirm_certificate_change(domain_id, remote_address, &previous, &fingerprint, ui)
+            {
+                CertificateAnswer::AcceptAndSave => {
+                    trust_host(remote_address, &fingerprint)?;
+                    Ok(())
+                }
+                CertificateAnswer::AcceptOnce => Ok(()),
+                CertificateAnswer::Reject => bail!(
+                    "The certificate presented by {remote_address} changed from \
+                     {previous} to {fingerprint} and was rejected. \
+                     Use `wezterm cli show-trusted-hosts --remove {remote_address}` \
+                     to forget the previous certificate"
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fingerprints() {
+        assert_eq!(format_fingerprint(&[0xab, 0x01, 0xff]), "SHA256:AB:01:FF");
+        assert!(same_fingerprint("SHA256:AB:01:FF", "ab01ff"));
+        assert!(same_fingerprint("sha256:ab:01:ff", "AB:01:FF"));
+        assert!(!same_fingerprint("SHA256:AB:01:FF", "AB:01:FE"));
+    }
+
+    #[test]
+    fn known_hosts_round_trip() {
+        let hosts = vec![
+            TrustedHost {
+                remote_address: "host:8080".to_string(),
+                fingerprint: "SHA256:AB".to_string(),
+            },
+            TrustedHost {
+                remote_address: "other:8080".to_string(),
+                fingerprint: "SHA256:CD".to_string(),
+            },
+        ];
+        let text = format_known_hosts(&hosts);
+        assert_eq!(text, "host:8080 SHA256:AB\nother:8080 SHA256:CD\n");
+        assert_eq!(parse_known_hosts(&format!("# comment\n\n{text}")), hosts);
+    }
+
+    #[test]
+    fn status() {
+        let expected = vec!["SHA256:AB:CD".to_string()];
+        assert_eq!(
+            check_certificate(&expected, None, "SHA256:AB:CD"),
+            CertificateStatus::Trusted
+        );
+        assert_eq!(
+            check_certificate(&expected, Some("SHA256:EF"), "SHA256:EF"),
+            CertificateStatus::NotExpected
+        );
+        assert_eq!(
+            check_certificate(&[], None, "SHA256:AB"),
+            CertificateStatus::FirstUse
+        );
+        assert_eq!(
+            check_certificate(&[], Some("SHA256:AB"), "SHA256:AB"),
+            CertificateStatus::Trusted
+        );
+        assert_eq!(
+            check_certificate(&[], Some("SHA256:AB"), "SHA256:CD"),
+            CertificateStatus::Changed {
+                previous: "SHA256:AB".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn answers() {
+        for line in ["a", "accept\n", " Y ", "YES"] {
+            assert_eq!(
+                parse_answer(line),
+                CertificateAnswer::AcceptAndSave,
+                "{line:?}"
+            );
+        }
+        for line in ["o", "Once\r\n"] {
+            assert_eq!(
+                parse_answer(line),
+                CertificateAnswer::AcceptOnce,
+                "{line:?}"
+            );
+        }
+        for line in ["", "\n", "r", "reject", "n", "accept once", "ao"] {
+            assert_eq!(parse_answer(line), CertificateAnswer::Reject, "{line:?}");
+        }
+    }
+}
@@ -98,6 +98,49 @@ impl<'a> FieldInfo<'a> {
         }
     }
 
+    /// Generates code that appends the description of the field
+    /// to `fields`, a Vec<FieldSchema>
+    pub fn describe_schema(&self) -> TokenStream {
+        let name = &self.name;
+        let ty = &self.field.ty;
+        if self.skip {
+            quote!()
+        } else if self.flatten {
+            quote!(
+                {
+                    let flattened = <#ty as wezterm_dynamic::FromDynamic>::describe_schema(registry);
+                    fields.extend(registry.struct_fields(&flattened));
+                }
+            )
+        } else {
+            let schema = match &self.try_from {
+                Some(try_from) => {
+                    quote!(<#try_from as wezterm_dynamic::FromDynamic>::describe_schema(registry))
+                }
+                None => quote!(<#ty as wezterm_dynamic::FromDynamic>::describe_schema(registry)),
+            };
+            let has_default = !matches!(self.allow_default, DefValue::None);
+            let deprecated = match &self.deprecated {
+                Some(reason) => quote!(Some(#reason)),
+                None => quote!(None),
+            };
+            quote!(
+                {
+                    let schema = #schema;
+                    // A missing field is converted from null
+                    let required = !#has_default
+                        && !matches!(schema, wezterm_dynamic::Schema::Optional(_));
+                    fields.push(wezterm_dynamic::FieldSchema {
+                        name: #name,
+                        schema,
+                        required,
+                        deprecated: #deprecated,
+                    });
+                }
+            )
+        }
+    }
+
     pub fn from_dynamic(&self, struct_name: &str) -> TokenStream {
         let name = &self.name;
         let ident = &self.field.ident;
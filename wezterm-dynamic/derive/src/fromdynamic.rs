@@ -67,6 +67,11 @@ fn derive_struct(input: &DeriveInput, fields: &FieldsNamed) -> Result<TokenStrea
         &[ #( #field_names, )* ]
     );
 
+    let field_schemas = placements
+        .iter()
+        .map(|f| f.describe_schema())
+        .collect::<Vec<_>>();
+
     let placements = placements
         .into_iter()
         .map(|f| f.from_dynamic(&literal))
@@ -94,6 +99,21 @@ fn derive_struct(input: &DeriveInput, fields: &FieldsNamed) -> Result<TokenStrea
         )
     };
 
+    let describe_schema = match &info.try_from {
+        Some(try_from) => quote!(
+            wezterm_dynamic::TypeSchema::Alias(
+                <#try_from as wezterm_dynamic::FromDynamic>::describe_schema(registry)
+            )
+        ),
+        None => quote!(
+            {
+                let mut fields: Vec<wezterm_dynamic::FieldSchema> = vec![];
+                #( #field_schemas )*
+                wezterm_dynamic::TypeSchema::Struct(fields)
+            }
+        ),
+    };
+
     let from_dynamic = match info.try_from {
         Some(try_from) => {
             quote!(
@@ -128,6 +148,11 @@ fn derive_struct(input: &DeriveInput, fields: &FieldsNamed) -> Result<TokenStrea
                     #from_dynamic
                 }
 
+                fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+                    registry.define(#literal, std::any::type_name::<Self>(), |registry| {
+                        #describe_schema
+                    })
+                }
             }
             impl #impl_generics #ident #ty_generics #bounded_where_clause {
                 pub const fn possible_field_names() -> &'static [&'static str] {
@@ -165,6 +190,60 @@ fn derive_enum(input: &DeriveInput, enumeration: &DataEnum) -> Result<TokenStrea
         .map(|variant| variant.ident.to_string())
         .collect::<Vec<_>>();
 
+    let describe_schema = match &info.try_from {
+        Some(try_from) => quote!(
+            wezterm_dynamic::TypeSchema::Alias(
+                <#try_from as wezterm_dynamic::FromDynamic>::describe_schema(registry)
+            )
+        ),
+        None => {
+            let variants = enumeration
+                .variants
+                .iter()
+                .map(|variant| {
+                    let literal = variant.ident.to_string();
+                    Ok(match &variant.fields {
+                        Fields::Unit => quote!(wezterm_dynamic::VariantSchema::Unit(#literal)),
+                        Fields::Named(fields) => {
+                            let field_schemas = fields
+                                .named
+                                .iter()
+                                .map(|f| Ok(attr::field_info(f)?.describe_schema()))
+                                .collect::<Result<Vec<_>>>()?;
+                            quote!(
+                                wezterm_dynamic::VariantSchema::Struct(#literal, {
+                                    let mut fields: Vec<wezterm_dynamic::FieldSchema> = vec![];
+                                    #( #field_schemas )*
+                                    fields
+                                })
+                            )
+                        }
+                        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                            let ty = &fields.unnamed.first().unwrap().ty;
+                            quote!(
+                                wezterm_dynamic::VariantSchema::Newtype(
+                                    #literal,
+                                    <#ty as wezterm_dynamic::FromDynamic>::describe_schema(registry),
+                                )
+                            )
+                        }
+                        Fields::Unnamed(fields) => {
+                            let tys = fields.unnamed.iter().map(|f| &f.ty);
+                            quote!(
+                                wezterm_dynamic::VariantSchema::Tuple(#literal, vec![
+                                    #( <#tys as wezterm_dynamic::FromDynamic>::describe_schema(registry), )*
+                                ])
+                            )
+                        }
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            quote!(wezterm_dynamic::TypeSchema::Enum(vec![
+                #( #variants, )*
+            ]))
+        }
+    };
+
     let from_dynamic = match info.try_from {
         Some(try_from) => {
             quote!(
@@ -328,6 +407,12 @@ fn derive_enum(input: &DeriveInput, enumeration: &DataEnum) -> Result<TokenStrea
                     use wezterm_dynamic::{Value, BorrowedKey, ObjectKeyTrait};
                     #from_dynamic
                 }
+
+                fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+                    registry.define(#literal, std::any::type_name::<Self>(), |registry| {
+                        #describe_schema
+                    })
+                }
             }
 
             impl #ident {
@@ -1,4 +1,5 @@
 use crate::error::Error;
+use crate::schema::{Schema, SchemaRegistry};
 use crate::value::Value;
 use ordered_float::OrderedFloat;
 use std::collections::HashMap;
@@ -40,6 +41,18 @@ pub trait FromDynamic {
     fn from_dynamic(value: &Value, options: FromDynamicOptions) -> Result<Self, Error>
     where
         Self: Sized;
+
+    /// Describes the values that from_dynamic accepts, registering
+    /// any named types in `registry`.  This is provided by the derive;
+    /// types that implement FromDynamic by hand accept any value
+    /// unless they say otherwise.
+    fn describe_schema(registry: &mut SchemaRegistry) -> Schema
+    where
+        Self: Sized,
+    {
+        let _ = registry;
+        Schema::Any
+    }
 }
 
 impl FromDynamic for Value {
@@ -53,6 +66,10 @@ impl FromDynamic for ordered_float::NotNan<f64> {
         let f = f64::from_dynamic(value, options)?;
         Ok(ordered_float::NotNan::new(f).map_err(|e| Error::Message(e.to_string()))?)
     }
+
+    fn describe_schema(_registry: &mut SchemaRegistry) -> Schema {
+        Schema::Number
+    }
 }
 
 impl FromDynamic for std::time::Duration {
@@ -60,6 +77,10 @@ impl FromDynamic for std::time::Duration {
         let f = f64::from_dynamic(value, options)?;
         Ok(std::time::Duration::from_secs_f64(f))
     }
+
+    fn describe_schema(_registry: &mut SchemaRegistry) -> Schema {
+        Schema::Number
+    }
 }
 
 impl<T: FromDynamic> FromDynamic for Box<T> {
@@ -67,6 +88,10 @@ impl<T: FromDynamic> FromDynamic for Box<T> {
         let value = T::from_dynamic(value, options)?;
         Ok(Box::new(value))
     }
+
+    fn describe_schema(registry: &mut SchemaRegistry) -> Schema {
+        T::describe_schema(registry)
+    }
 }
 
 impl<T: FromDynamic> FromDynamic for std::sync::Arc<T> {
@@ -74,6 +99,10 @@ impl<T: FromDynamic> FromDynamic for std::sync::Arc<T> {
         let value = T::from_dynamic(value, options)?;
         Ok(std::sync::Arc::new(value))
     }
+
+    fn describe_schema(registry: &mut SchemaRegistry) -> Schema {
+        T::describe_schema(registry)
+    }
 }
 
 impl<T: FromDynamic> FromDynamic for Option<T> {
@@ -83,6 +112,10 @@ impl<T: FromDynamic> FromDynamic for Option<T> {
             value => Ok(Some(T::from_dynamic(value, options)?)),
         }
     }
+
+    fn describe_schema(registry: &mut SchemaRegistry) -> Schema {
+        Schema::Optional(Box::new(T::describe_schema(registry)))
+    }
 }
 
 impl<T: FromDynamic, const N: usize> FromDynamic for [T; N] {
@@ -104,6 +137,10 @@ impl<T: FromDynamic, const N: usize> FromDynamic for [T; N] {
             }),
         }
     }
+
+    fn describe_schema(registry: &mut SchemaRegistry) -> Schema {
+        Schema::Array(Box::new(T::describe_schema(registry)))
+    }
 }
 
 impl<K: FromDynamic + Eq + Hash, T: FromDynamic> FromDynamic for HashMap<K, T> {
@@ -122,6 +159,13 @@ impl<K: FromDynamic + Eq + Hash, T: FromDynamic> FromDynamic for HashMap<K, T> {
             }),
         }
     }
+
+    fn describe_schema(registry: &mut SchemaRegistry) -> Schema {
+        Schema::Map(
+            Box::new(K::describe_schema(registry)),
+            Box::new(T::describe_schema(registry)),
+        )
+    }
 }
 
 impl<T: FromDynamic> FromDynamic for Vec<T> {
@@ -141,6 +185,10 @@ impl<T: FromDynamic> FromDynamic for Vec<T> {
             }),
         }
     }
+
+    fn describe_schema(registry: &mut SchemaRegistry) -> Schema {
+        Schema::Array(Box::new(T::describe_schema(registry)))
+    }
 }
 
 impl FromDynamic for () {
@@ -153,6 +201,10 @@ impl FromDynamic for () {
             }),
         }
     }
+
+    fn describe_schema(_registry: &mut SchemaRegistry) -> Schema {
+        Schema::Null
+    }
 }
 
 impl FromDynamic for bool {
@@ -165,6 +217,10 @@ impl FromDynamic for bool {
             }),
         }
     }
+
+    fn describe_schema(_registry: &mut SchemaRegistry) -> Schema {
+        Schema::Bool
+    }
 }
 
 impl FromDynamic for std::path::PathBuf {
@@ -177,6 +233,10 @@ impl FromDynamic for std::path::PathBuf {
             }),
         }
     }
+
+    fn describe_schema(_registry: &mut SchemaRegistry) -> Schema {
+        Schema::String
+    }
 }
 
 impl FromDynamic for char {
@@ -197,6 +257,10 @@ impl FromDynamic for char {
             }),
         }
     }
+
+    fn describe_schema(_registry: &mut SchemaRegistry) -> Schema {
+        Schema::String
+    }
 }
 
 impl FromDynamic for String {
@@ -209,6 +273,10 @@ impl FromDynamic for String {
             }),
         }
     }
+
+    fn describe_schema(_registry: &mut SchemaRegistry) -> Schema {
+        Schema::String
+    }
 }
 
 macro_rules! int {
@@ -231,6 +299,10 @@ impl FromDynamic for $ty {
             })
         }
     }
+
+    fn describe_schema(_registry: &mut SchemaRegistry) -> Schema {
+        Schema::Integer
+    }
 }
         )*
     }
@@ -250,6 +322,10 @@ impl FromDynamic for f32 {
             }),
         }
     }
+
+    fn describe_schema(_registry: &mut SchemaRegistry) -> Schema {
+        Schema::Number
+    }
 }
 
 impl FromDynamic for f64 {
@@ -264,4 +340,8 @@ impl FromDynamic for f64 {
             }),
         }
     }
+
+    fn describe_schema(_registry: &mut SchemaRegistry) -> Schema {
+        Schema::Number
+    }
 }
@@ -6,6 +6,7 @@ mod drop;
 mod error;
 mod fromdynamic;
 mod object;
+mod schema;
 mod todynamic;
 mod value;
 
@@ -13,6 +14,7 @@ pub use array::Array;
 pub use error::Error;
 pub use fromdynamic::{FromDynamic, FromDynamicOptions, UnknownFieldAction};
 pub use object::{BorrowedKey, Object, ObjectKeyTrait};
+pub use schema::{FieldSchema, Schema, SchemaRegistry, TypeSchema, VariantSchema};
 pub use todynamic::{PlaceDynamic, ToDynamic};
 pub use value::Value;
 pub use wezterm_dynamic_derive::{FromDynamic, ToDynamic};
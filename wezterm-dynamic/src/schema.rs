@@ -0,0 +1,151 @@
+//! Describes the shape of the values that FromDynamic accepts.
+//! The descriptions are produced by the FromDynamic derive from the
+//! same field and variant information that it uses to convert values
+//! and to report unknown fields, so they can't drift from what is
+//! actually accepted.
+//!
+//! Named types are described once, in a SchemaRegistry, and are
+//! referenced by name elsewhere; this allows for recursive types such
+//! as a KeyAssignment that holds other KeyAssignments.
+use std::collections::BTreeMap;
+
+/// The shape of a value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schema {
+    /// Any value; used for types that don't describe themselves
+    Any,
+    Null,
+    Bool,
+    Integer,
+    Number,
+    String,
+    /// Either null or the inner value
+    Optional(Box<Schema>),
+    Array(Box<Schema>),
+    /// An object with keys and values of the given shapes
+    Map(Box<Schema>, Box<Schema>),
+    /// Any one of the listed shapes
+    OneOf(Vec<Schema>),
+    /// A type described in the registry under this name
+    Named(String),
+}
+
+impl Schema {
+    /// Returns the non-optional shape of self
+    pub fn strip_optional(&self) -> &Schema {
+        match self {
+            Self::Optional(inner) => inner.strip_optional(),
+            other => other,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldSchema {
+    pub name: &'static str,
+    pub schema: Schema,
+    /// false if the field may be omitted
+    pub required: bool,
+    /// The reason given for deprecating the field, if any
+    pub deprecated: Option<&'static str>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VariantSchema {
+    /// Specified as the string name of the variant
+    Unit(&'static str),
+    /// Specified as `{Name = value}`
+    Newtype(&'static str, Schema),
+    /// Specified as `{Name = {value, ...}}`
+    Tuple(&'static str, Vec<Schema>),
+    /// Specified as `{Name = {field = value, ...}}`
+    Struct(&'static str, Vec<FieldSchema>),
+}
+
+impl VariantSchema {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Unit(name)
+            | Self::Newtype(name, _)
+            | Self::Tuple(name, _)
+            | Self::Struct(name, _) => name,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TypeSchema {
+    /// An object with the listed fields, in declaration order
+    Struct(Vec<FieldSchema>),
+    Enum(Vec<VariantSchema>),
+    /// A type that is converted from a value of another shape
+    Alias(Schema),
+}
+
+#[derive(Default, Debug)]
+pub struct SchemaRegistry {
+    /// The types by name.  A type is None while it is being described.
+    types: BTreeMap<String, Option<TypeSchema>>,
+    /// Maps the rust type name to the name that it is registered under
+    names: BTreeMap<&'static str, String>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Describes `T`, registering it and any types that it contains,
+    /// and returns a reference to it
+    pub fn describe<T: crate::FromDynamic>(&mut self) -> Schema {
+        T::describe_schema(self)
+    }
+
+    /// Registers the type whose rust type name is `type_name` as `name`,
+    /// calling `describe` to describe it the first time, and returns
+    /// a reference to it.  If a different type has already taken `name`,
+    /// the full rust type name is used instead.
+    pub fn define<F>(&mut self, name: &str, type_name: &'static str, describe: F) -> Schema
+    where
+        F: FnOnce(&mut Self) -> TypeSchema,
+    {
+        if let Some(existing) = self.names.get(type_name) {
+            return Schema::Named(existing.clone());
+        }
+        let name = if self.types.contains_key(name) {
+            type_name.replace("::", ".")
+        } else {
+            name.to_string()
+        };
+        self.names.insert(type_name, name.clone());
+        // Registered before describing it, so that a type that
+        // contains itself refers to itself by name
+        self.types.insert(name.clone(), None);
+        let schema = describe(self);
+        self.types.insert(name.clone(), Some(schema));
+        Schema::Named(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TypeSchema> {
+        self.types.get(name).and_then(|t| t.as_ref())
+    }
+
+    /// Returns the fields of `schema` if it names a struct; used to
+    /// describe a flattened field as part of its containing struct
+    pub fn struct_fields(&self, schema: &Schema) -> Vec<FieldSchema> {
+        match schema {
+            Schema::Named(name) => match self.get(name) {
+                Some(TypeSchema::Struct(fields)) => fields.clone(),
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Iterates the registered types, ordered by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &TypeSchema)> {
+        self.types
+            .iter()
+            .filter_map(|(name, t)| t.as_ref().map(|t| (name.as_str(), t)))
+    }
+}
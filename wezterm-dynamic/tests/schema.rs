@@ -0,0 +1,110 @@
+use wezterm_dynamic::{
+    FieldSchema, FromDynamic, Schema, SchemaRegistry, TypeSchema, VariantSchema,
+};
+
+#[derive(FromDynamic, Debug, PartialEq)]
+struct Inner {
+    name: String,
+    #[dynamic(default)]
+    count: u32,
+}
+
+#[derive(FromDynamic, Debug, PartialEq)]
+enum Action {
+    Nop,
+    Send(String),
+    Move(i32, i32),
+    Spawn {
+        args: Vec<String>,
+        cwd: Option<String>,
+    },
+    Multiple(Vec<Action>),
+}
+
+#[derive(FromDynamic, Debug, PartialEq)]
+struct Outer {
+    #[dynamic(flatten)]
+    inner: Inner,
+    #[dynamic(skip)]
+    skipped: bool,
+    #[dynamic(deprecated = "use action instead")]
+    old: Option<bool>,
+    action: Action,
+}
+
+#[test]
+fn describe_struct() {
+    let mut registry = SchemaRegistry::new();
+    let schema = registry.describe::<Outer>();
+    assert_eq!(schema, Schema::Named("Outer".to_string()));
+
+    assert_eq!(
+        registry.get("Outer"),
+        Some(&TypeSchema::Struct(vec![
+            FieldSchema {
+                name: "name",
+                schema: Schema::String,
+                required: true,
+                deprecated: None,
+            },
+            FieldSchema {
+                name: "count",
+                schema: Schema::Integer,
+                required: false,
+                deprecated: None,
+            },
+            FieldSchema {
+                name: "old",
+                schema: Schema::Optional(Box::new(Schema::Bool)),
+                required: false,
+                deprecated: Some("use action instead"),
+            },
+            FieldSchema {
+                name: "action",
+                schema: Schema::Named("Action".to_string()),
+                required: true,
+                deprecated: None,
+            },
+        ]))
+    );
+}
+
+#[test]
+fn describe_recursive_enum() {
+    let mut registry = SchemaRegistry::new();
+    registry.describe::<Action>();
+
+    assert_eq!(
+        registry.get("Action"),
+        Some(&TypeSchema::Enum(vec![
+            VariantSchema::Unit("Nop"),
+            VariantSchema::Newtype("Send", Schema::String),
+            VariantSchema::Tuple("Move", vec![Schema::Integer, Schema::Integer]),
+            VariantSchema::Struct(
+                "Spawn",
+                vec![
+                    FieldSchema {
+                        name: "args",
+                        schema: Schema::Array(Box::new(Schema::String)),
+                        required: true,
+                        deprecated: None,
+                    },
+                    FieldSchema {
+                        name: "cwd",
+                        schema: Schema::Optional(Box::new(Schema::String)),
+                        required: false,
+                        deprecated: None,
+                    },
+                ]
+            ),
+            VariantSchema::Newtype(
+                "Multiple",
+                Schema::Array(Box::new(Schema::Named("Action".to_string())))
+            ),
+        ]))
+    );
+    assert_eq!(
+        registry.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        vec!["Action"]
+    );
+}
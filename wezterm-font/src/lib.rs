@@ -262,6 +262,15 @@ impl LoadedFont {
         }
     }
 
+    /// Returns the per-font `line_height` override for the font at
+    /// the specified index, if any.
+    pub fn line_height_for_idx(&self, font_idx: usize) -> Option<f64> {
+        self.handles
+            .borrow()
+            .get(font_idx)
+            .and_then(|p| p.line_height)
+    }
+
     pub fn rasterize_glyph(
         &self,
         glyph_pos: u32,
@@ -440,6 +449,8 @@ impl FallbackResolveInfo {
                     ),
                     url: Some(url.to_string()),
                     timeout: Some(Duration::from_secs(15)),
+                    actions: vec![],
+                    on_action: None,
                 }
                 .show();
             } else {
@@ -465,6 +476,7 @@ struct FontConfigInner {
     pane_select_font: RefCell<Option<Rc<LoadedFont>>>,
     char_select_font: RefCell<Option<Rc<LoadedFont>>>,
     command_palette_font: RefCell<Option<Rc<LoadedFont>>>,
+    tab_overview_font: RefCell<Option<Rc<LoadedFont>>>,
     fallback_channel: RefCell<Option<Sender<FallbackResolveInfo>>>,
 }
 
@@ -486,6 +498,7 @@ impl FontConfigInner {
             pane_select_font: RefCell::new(None),
             char_select_font: RefCell::new(None),
             command_palette_font: RefCell::new(None),
+            tab_overview_font: RefCell::new(None),
             font_scale: RefCell::new(1.0),
             dpi: RefCell::new(dpi),
             config: RefCell::new(config.clone()),
@@ -504,6 +517,7 @@ impl FontConfigInner {
         self.pane_select_font.borrow_mut().take();
         self.char_select_font.borrow_mut().take();
         self.command_palette_font.borrow_mut().take();
+        self.tab_overview_font.borrow_mut().take();
         self.metrics.borrow_mut().take();
         *self.font_dirs.borrow_mut() = Arc::new(FontDatabase::with_font_dirs(config)?);
         Ok(())
@@ -597,6 +611,16 @@ impl FontConfigInner {
 
         let text_style = config.window_frame.font.as_ref().unwrap_or(&sys_font);
 
+        self.make_font_impl(myself, &config, text_style, font_size)
+    }
+
+    fn make_font_impl(
+        &self,
+        myself: &Rc<Self>,
+        config: &ConfigHandle,
+        text_style: &TextStyle,
+        font_size: f64,
+    ) -> anyhow::Result<Rc<LoadedFont>> {
         let dpi = *self.dpi.borrow() as u32;
         let pixel_size = (font_size * dpi as f64 / 72.0) as u16;
 
@@ -679,6 +703,24 @@ impl FontConfigInner {
         Ok(loaded)
     }
 
+    /// The terminal font, scaled down for the tab overview thumbnails
+    fn tab_overview_font(&self, myself: &Rc<Self>) -> anyhow::Result<Rc<LoadedFont>> {
+        let config = self.config.borrow();
+
+        let mut tab_overview_font = self.tab_overview_font.borrow_mut();
+
+        if let Some(entry) = tab_overview_font.as_ref() {
+            return Ok(Rc::clone(entry));
+        }
+
+        let loaded =
+            self.make_font_impl(myself, &config, &config.font, config.tab_overview_font_size)?;
+
+        tab_overview_font.replace(Rc::clone(&loaded));
+
+        Ok(loaded)
+    }
+
     fn pane_select_font(&self, myself: &Rc<Self>) -> anyhow::Result<Rc<LoadedFont>> {
         let config = self.config.borrow();
 
@@ -1065,6 +1107,10 @@ impl FontConfiguration {
         self.inner.char_select_font(&self.inner)
     }
 
+    pub fn tab_overview_font(&self) -> anyhow::Result<Rc<LoadedFont>> {
+        self.inner.tab_overview_font(&self.inner)
+    }
+
     /// Given a text style, load (with caching) the font that best
     /// matches according to the fontconfig pattern.
     pub fn resolve_font(&self, style: &TextStyle) -> anyhow::Result<Rc<LoadedFont>> {
@@ -285,6 +285,7 @@ fn build_fallback_list_impl() -> anyhow::Result<Vec<ParsedFont>> {
         freetype_render_target: None,
         freetype_load_flags: None,
         scale: None,
+        line_height: None,
         assume_emoji_presentation: None,
     };
     if let Ok(descriptors) = descriptor_from_attr(&symbols) {
@@ -326,6 +326,7 @@ impl FontLocator for GdiFontLocator {
                         freetype_render_target: None,
                         freetype_load_flags: None,
                         scale: None,
+                        line_height: None,
                         assume_emoji_presentation: None,
                     };
 
@@ -42,6 +42,7 @@ pub struct ParsedFont {
     pub freetype_render_target: Option<FreeTypeLoadTarget>,
     pub freetype_load_flags: Option<FreeTypeLoadFlags>,
     pub scale: Option<f64>,
+    pub line_height: Option<f64>,
 }
 
 impl std::fmt::Debug for ParsedFont {
@@ -63,6 +64,7 @@ impl std::fmt::Debug for ParsedFont {
             .field("freetype_render_target", &self.freetype_render_target)
             .field("freetype_load_flags", &self.freetype_load_flags)
             .field("scale", &self.scale)
+            .field("line_height", &self.line_height)
             .finish()
     }
 }
@@ -88,6 +90,7 @@ impl Clone for ParsedFont {
             freetype_load_flags: self.freetype_load_flags,
             is_built_in_fallback: self.is_built_in_fallback,
             scale: self.scale,
+            line_height: self.line_height,
             palettes: self.palettes.clone(),
         }
     }
@@ -337,6 +340,7 @@ impl ParsedFont {
                 && p.freetype_load_flags.is_none()
                 && p.harfbuzz_features.is_none()
                 && p.scale.is_none()
+                && p.line_height.is_none()
             {
                 code.push_str(&format!("  \"{}\",\n", p.names.family));
             } else {
@@ -353,6 +357,9 @@ impl ParsedFont {
                 if let Some(scale) = p.scale {
                     code.push_str(&format!(", scale={}", scale));
                 }
+                if let Some(line_height) = p.line_height {
+                    code.push_str(&format!(", line_height={}", line_height));
+                }
                 if let Some(item) = p.freetype_load_flags {
                     code.push_str(&format!(", freetype_load_flags=\"{}\"", item.to_string()));
                 }
@@ -529,6 +536,7 @@ impl ParsedFont {
             freetype_load_target: None,
             freetype_load_flags: None,
             scale: None,
+            line_height: None,
             palettes,
         })
     }
@@ -781,6 +789,7 @@ impl ParsedFont {
         self.freetype_load_target = attr.freetype_load_target;
         self.freetype_load_flags = attr.freetype_load_flags;
         self.scale = attr.scale.map(|f| *f);
+        self.line_height = attr.line_height.map(|f| *f);
 
         self.synthesize_italic = self.style == FontStyle::Normal && attr.style != FontStyle::Normal;
         self.synthesize_bold = attr.weight >= FontWeight::DEMIBOLD
@@ -861,6 +861,7 @@ mod test {
                     freetype_render_target: None,
                     harfbuzz_features: None,
                     scale: None,
+                    line_height: None,
                     assume_emoji_presentation: None,
                 },
                 14,
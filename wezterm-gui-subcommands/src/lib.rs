@@ -101,6 +101,16 @@ pub struct StartCommand {
     #[arg(long, requires = "domain")]
     pub attach: bool,
 
+    /// Apply the overrides of the named entry of the `profiles`
+    /// configuration to the window and the panes spawned in it.
+    #[arg(long, conflicts_with = "attach")]
+    pub profile: Option<String>,
+
+    /// Restore the windows, tabs and panes that were saved to FILE
+    /// rather than spawning PROG.  See also `wezterm restore`.
+    #[arg(long, value_name = "FILE", value_hint=ValueHint::FilePath, conflicts_with = "prog")]
+    pub restore_session: Option<PathBuf>,
+
     /// Instead of executing your shell, run PROG.
     /// For example: `wezterm start -- bash -l` will spawn bash
     /// as if it were a login shell. [aliases: -e]
@@ -174,6 +184,16 @@ pub struct SerialCommand {
     #[arg(long = "baud")]
     pub baud: Option<usize>,
 
+    /// Rather than closing the window when the device goes away,
+    /// keep it open and periodically try to re-open the port
+    #[arg(long = "reconnect")]
+    pub reconnect: bool,
+
+    /// How often to try to re-open the port when --reconnect
+    /// is used.  The default is 1000 milliseconds.
+    #[arg(long = "reconnect-interval-ms", requires = "reconnect")]
+    pub reconnect_interval_ms: Option<u64>,
+
     /// Override the default windowing system class.
     /// The default is "org.wezfurlong.wezterm".
     /// Under X11 and Windows this changes the window class.
@@ -243,6 +263,15 @@ pub struct ConnectCommand {
     pub prog: Vec<OsString>,
 }
 
+#[derive(Debug, Parser, Clone)]
+pub struct RestoreCommand {
+    /// The session file to restore.
+    /// The default is the file that is written when
+    /// `save_session_on_exit` is enabled.
+    #[arg(value_hint=ValueHint::FilePath)]
+    pub file: Option<PathBuf>,
+}
+
 #[derive(Debug, Parser, Clone)]
 pub struct LsFontsCommand {
     /// Whether to list all fonts available to the system
@@ -260,6 +289,11 @@ pub struct LsFontsCommand {
     /// Show rasterized glyphs for the text in --text or --codepoints using ascii blocks.
     #[arg(long, requires = "text")]
     pub rasterize_ascii: bool,
+
+    /// Show a sample of common Nerd Font icons, along with the font
+    /// that provides them and the scale computed by nerd_font_scale
+    #[arg(long, conflicts_with_all = &["list_system", "text", "codepoints"])]
+    pub icons: bool,
 }
 
 #[derive(Debug, Parser, Clone)]
@@ -270,4 +304,24 @@ pub struct ShowKeysCommand {
     /// In lua mode, show only the named key table
     #[arg(long)]
     pub key_table: Option<String>,
+
+    /// Show the kitty keyboard protocol encoding of KEY instead of the key assignments
+    #[arg(long, value_name = "KEY", conflicts_with_all = &["lua", "key_table"])]
+    pub encode: Option<String>,
+
+    /// In encode mode, the modifiers that are held down, eg: CTRL|SHIFT
+    #[arg(long, requires = "encode", default_value = "NONE")]
+    pub mods: String,
+
+    /// In encode mode, the progressive enhancement flags requested by the application
+    #[arg(long, requires = "encode", default_value_t = 1)]
+    pub kitty_flags: u16,
+
+    /// In encode mode, encode a key release rather than a key press
+    #[arg(long, requires = "encode", conflicts_with = "repeat")]
+    pub release: bool,
+
+    /// In encode mode, encode a key repeat rather than a key press
+    #[arg(long, requires = "encode")]
+    pub repeat: bool,
 }
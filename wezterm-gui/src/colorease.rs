@@ -9,7 +9,6 @@ pub struct ColorEase {
     out_duration: f32,
     out_function: EasingFunction,
     start: Option<Instant>,
-    last_render: Instant,
 }
 
 impl ColorEase {
@@ -26,7 +25,6 @@ impl ColorEase {
             out_duration: Duration::from_millis(out_duration_ms).as_secs_f32(),
             out_function,
             start,
-            last_render: Instant::now(),
         }
     }
 
@@ -47,19 +45,41 @@ impl ColorEase {
     }
 
     pub fn intensity_continuous(&mut self) -> (f32, Instant) {
-        match self.intensity_one_shot() {
+        self.intensity_continuous_at(Instant::now(), self.fps())
+    }
+
+    pub fn intensity_one_shot(&mut self) -> Option<(f32, Instant)> {
+        self.intensity_one_shot_at(Instant::now(), self.fps())
+    }
+
+    /// The rate at which frames need to be painted to show the easing
+    fn fps(&self) -> u64 {
+        if self.in_function == EasingFunction::Constant
+            && self.out_function == EasingFunction::Constant
+        {
+            // Only the transitions between on and off need a frame
+            1
+        } else {
+            config::configuration().animation_fps as u64
+        }
+    }
+
+    fn intensity_continuous_at(&mut self, now: Instant, fps: u64) -> (f32, Instant) {
+        match self.intensity_one_shot_at(now, fps) {
             Some(intensity) => intensity,
             None => {
                 // Start a new cycle
-                self.start.replace(Instant::now());
-                self.intensity_one_shot().expect("just started")
+                self.start.replace(now);
+                self.intensity_one_shot_at(now, fps).expect("just started")
             }
         }
     }
 
-    pub fn intensity_one_shot(&mut self) -> Option<(f32, Instant)> {
+    /// Returns the intensity at `now` along with the time at which
+    /// the next frame is due, or None if the easing has completed
+    fn intensity_one_shot_at(&mut self, now: Instant, fps: u64) -> Option<(f32, Instant)> {
         let start = self.start?;
-        let elapsed = start.elapsed().as_secs_f32();
+        let elapsed = now.saturating_duration_since(start).as_secs_f32();
 
         let intensity = if elapsed < self.in_duration {
             Some(
@@ -77,33 +97,21 @@ impl ColorEase {
 
         match intensity {
             Some(i) => {
-                let now = Instant::now();
-                let fps = if self.in_function == EasingFunction::Constant
-                    && self.out_function == EasingFunction::Constant
-                {
-                    1
-                } else {
-                    config::configuration().animation_fps as u64
-                };
                 let next = match fps {
                     1 if elapsed < self.in_duration => {
                         start + Duration::from_secs_f32(self.in_duration)
                     }
                     1 => start + Duration::from_secs_f32(self.in_duration + self.out_duration),
                     _ => {
-                        let frame_interval = 1000 / fps as u64;
-                        let elapsed = (elapsed * 1000.).ceil() as u64;
-                        let remain = elapsed % frame_interval;
-                        if remain != 0
-                            && self.last_render.elapsed() >= Duration::from_millis(frame_interval)
-                        {
-                            now + Duration::from_millis(remain)
-                        } else {
-                            now + Duration::from_millis(frame_interval)
-                        }
+                        // Align the frames to the start of the cycle, so
+                        // that the timer fires exactly once per frame
+                        // rather than drifting with the time taken to paint
+                        let frame_interval = (1000 / fps.max(1)).max(1);
+                        let elapsed = (elapsed * 1000.) as u64;
+                        let frame = elapsed / frame_interval + 1;
+                        (start + Duration::from_millis(frame * frame_interval)).max(now)
                     }
                 };
-                self.last_render = now;
                 Some((i, next))
             }
             None => {
@@ -140,3 +148,110 @@ impl<'a> UniformStruct<'a> for ColorEaseUniform {
         builder.add_struct_field(struct_name, "out_duration_ms", &self.out_duration_ms);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn not_started() {
+        let mut ease = ColorEase::new(
+            1000,
+            EasingFunction::Linear,
+            1000,
+            EasingFunction::Linear,
+            None,
+        );
+        assert_eq!(ease.intensity_one_shot_at(Instant::now(), 10), None);
+    }
+
+    #[test]
+    fn frames_are_aligned_to_the_start() {
+        let start = Instant::now();
+        let mut ease = ColorEase::new(
+            1000,
+            EasingFunction::Linear,
+            1000,
+            EasingFunction::Linear,
+            Some(start),
+        );
+
+        let (intensity, next) = ease.intensity_one_shot_at(start + ms(250), 10).unwrap();
+        assert_eq!(intensity, EasingFunction::Linear.evaluate_at_position(0.25));
+        assert_eq!(next, start + ms(300));
+
+        // A late frame doesn't push the following frames back
+        let (_, next) = ease.intensity_one_shot_at(start + ms(370), 10).unwrap();
+        assert_eq!(next, start + ms(400));
+
+        let (intensity, next) = ease.intensity_one_shot_at(start + ms(1500), 10).unwrap();
+        assert_eq!(
+            intensity,
+            1.0 - EasingFunction::Linear.evaluate_at_position(0.5)
+        );
+        assert_eq!(next, start + ms(1600));
+
+        assert_eq!(ease.intensity_one_shot_at(start + ms(2000), 10), None);
+        // and it stays finished
+        assert_eq!(ease.intensity_one_shot_at(start + ms(100), 10), None);
+    }
+
+    #[test]
+    fn constant_only_schedules_the_transitions() {
+        let start = Instant::now();
+        let mut ease = ColorEase::new(
+            500,
+            EasingFunction::Constant,
+            500,
+            EasingFunction::Constant,
+            Some(start),
+        );
+        assert_eq!(ease.fps(), 1);
+
+        assert_eq!(
+            ease.intensity_one_shot_at(start + ms(100), 1),
+            Some((0.0, start + ms(500)))
+        );
+        assert_eq!(
+            ease.intensity_one_shot_at(start + ms(600), 1),
+            Some((1.0, start + ms(1000)))
+        );
+        assert_eq!(ease.intensity_one_shot_at(start + ms(1000), 1), None);
+    }
+
+    #[test]
+    fn continuous_starts_a_new_cycle() {
+        let start = Instant::now();
+        let mut ease = ColorEase::new(
+            500,
+            EasingFunction::Constant,
+            500,
+            EasingFunction::Constant,
+            Some(start),
+        );
+
+        let now = start + ms(1200);
+        assert_eq!(ease.intensity_continuous_at(now, 1), (0.0, now + ms(500)));
+        assert_eq!(ease.start, Some(now));
+    }
+
+    #[test]
+    fn update_start_keeps_the_latest() {
+        let start = Instant::now();
+        let mut ease = ColorEase::new(
+            500,
+            EasingFunction::Linear,
+            500,
+            EasingFunction::Linear,
+            Some(start + ms(100)),
+        );
+        ease.update_start(start);
+        assert_eq!(ease.start, Some(start + ms(100)));
+        ease.update_start(start + ms(200));
+        assert_eq!(ease.start, Some(start + ms(200)));
+    }
+}
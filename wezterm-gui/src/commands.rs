@@ -598,6 +598,9 @@ fn english_ordinal(n: isize) -> String {
 fn spawn_command_from_action(action: &KeyAssignment) -> Option<&SpawnCommand> {
     match action {
         SplitPane(config::keyassignment::SplitPane { command, .. }) => Some(command),
+        SpawnFloatingPane(config::keyassignment::SpawnFloatingPane { command, .. }) => {
+            Some(command)
+        }
         SplitHorizontal(command)
         | SplitVertical(command)
         | SpawnCommandInNewWindow(command)
@@ -632,7 +635,10 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             text: _,
             destination: ClipboardCopyDestination::PrimarySelection,
         }
-        | CopyTo(ClipboardCopyDestination::PrimarySelection) => CommandDef {
+        | CopyTo(CopyToOptions {
+            destination: ClipboardCopyDestination::PrimarySelection,
+            ..
+        }) => CommandDef {
             brief: "Copy to primary selection".into(),
             doc: "Copies text to the primary selection".into(),
             keys: vec![(Modifiers::CTRL, "Insert".into())],
@@ -644,7 +650,10 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             text: _,
             destination: ClipboardCopyDestination::Clipboard,
         }
-        | CopyTo(ClipboardCopyDestination::Clipboard) => CommandDef {
+        | CopyTo(CopyToOptions {
+            destination: ClipboardCopyDestination::Clipboard,
+            ..
+        }) => CommandDef {
             brief: "Copy to clipboard".into(),
             doc: "Copies text to the clipboard".into(),
             keys: vec![
@@ -659,7 +668,10 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             text: _,
             destination: ClipboardCopyDestination::ClipboardAndPrimarySelection,
         }
-        | CopyTo(ClipboardCopyDestination::ClipboardAndPrimarySelection) => CommandDef {
+        | CopyTo(CopyToOptions {
+            destination: ClipboardCopyDestination::ClipboardAndPrimarySelection,
+            ..
+        }) => CommandDef {
             brief: "Copy to clipboard and primary selection".into(),
             doc: "Copies text to the clipboard and the primary selection".into(),
             keys: vec![(Modifiers::CTRL, "Insert".into())],
@@ -678,6 +690,22 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Edit"],
             icon: Some("md_content_paste"),
         },
+        PasteAsBlock(source) => CommandDef {
+            brief: "Paste as block".into(),
+            doc: format!(
+                "Pastes the lines from the {} as a block whose left \
+                 edge is at the column of the cursor",
+                match source {
+                    ClipboardPasteSource::Clipboard => "clipboard",
+                    ClipboardPasteSource::PrimarySelection => "primary selection",
+                }
+            )
+            .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Edit"],
+            icon: Some("md_content_paste"),
+        },
         ToggleFullScreen => CommandDef {
             brief: "Toggle full screen mode".into(),
             doc: "Switch between normal and full screen mode".into(),
@@ -686,6 +714,46 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["View"],
             icon: Some("md_fullscreen"),
         },
+        TogglePresentationMode => CommandDef {
+            brief: "Toggle presentation mode".into(),
+            doc: "Enlarges the font, emphasizes the cursor and displays \
+                  key presses, to make screen sharing easier to follow"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["View"],
+            icon: Some("md_presentation"),
+        },
+        ToggleZenMode => CommandDef {
+            brief: "Toggle zen mode".into(),
+            doc: "Hides the tab bar, scroll bar and window padding \
+                  so that the terminal fills the window"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["View"],
+            icon: Some("md_meditation"),
+        },
+        ToggleIntegerScaling => CommandDef {
+            brief: "Toggle snapping to the pixel grid".into(),
+            doc: "Rounds the position of glyphs, the cursor and underlines \
+                  to whole device pixels, so that screen recordings scale cleanly"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["View"],
+            icon: Some("md_grid"),
+        },
+        ToggleDropDownWindow => CommandDef {
+            brief: "Toggle the drop-down window".into(),
+            doc: "Slides the drop-down window into view, or hides it \
+                  if it is already visible"
+                .into(),
+            keys: vec![],
+            args: &[],
+            menubar: &["Window"],
+            icon: None,
+        },
         ToggleAlwaysOnTop => CommandDef {
             brief: "Toggle always on Top".into(),
             doc: "Toggles the window between floating and non-floating states to stay on top of other windows.".into(),
@@ -803,6 +871,14 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Help"],
             icon: Some("cod_debug"),
         },
+        ShowUpdateOverlay => CommandDef {
+            brief: "Show release notes".into(),
+            doc: "Shows the release notes of the latest available version of wezterm".into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["Help"],
+            icon: Some("md_update"),
+        },
         InputSelector(_) => CommandDef {
             brief: "Prompt the user to choose from a list".into(),
             doc: "Activates the selector overlay and wait for input".into(),
@@ -898,6 +974,20 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Window"],
             icon: Some("cod_multiple_windows"),
         },
+        LinkPanes(_)
+        | PaneSelect(PaneSelectArguments {
+            mode: PaneSelectMode::LinkWithActive(_),
+            ..
+        }) => CommandDef {
+            brief: "Link the active pane with another pane".into(),
+            doc: "Activates the pane selection UI to pick a pane to link \
+            with the active pane, so that they are closed together"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Window"],
+            icon: Some("md_link_variant"),
+        },
         DecreaseFontSize => CommandDef {
             brief: "Decrease font size".into(),
             doc: "Scales the font size smaller by 10%".into(),
@@ -1100,6 +1190,26 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &[],
             icon: Some("md_close_box_outline"),
         },
+        ReopenClosedPane => CommandDef {
+            brief: "Reopen closed Pane".into(),
+            doc: "Reopens the pane that was most recently closed in \
+            this window, in its former place and with its scrollback"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["Shell"],
+            icon: Some("md_restore"),
+        },
+        ReopenClosedTab => CommandDef {
+            brief: "Reopen closed Tab".into(),
+            doc: "Reopens the tab that was most recently closed in \
+            this window, with its panes and their scrollback"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["Shell"],
+            icon: Some("md_restore"),
+        },
         ActivateWindow(n) => {
             let n = *n;
             let ordinal = english_ordinal(n as isize + 1);
@@ -1188,6 +1298,16 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
                 icon: None,
             }
         }
+        ActivateLastWindow => CommandDef {
+            brief: "Activate the last active window".into(),
+            doc: "Activates the most recently focused window, other \
+            than the current window"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["Window", "Select Window"],
+            icon: None,
+        },
         ActivateTabRelative(-1) => CommandDef {
             brief: "Activate the tab to the left".into(),
             doc: "Activates the tab to the left. If this is the left-most \
@@ -1276,6 +1396,16 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["WezTerm"],
             icon: Some("oct_stop"),
         },
+        RestoreSession => CommandDef {
+            brief: "Restore saved session".into(),
+            doc: "Restores the windows, tabs and panes that were saved \
+            by save_session_on_exit"
+                .into(),
+            keys: vec![],
+            args: &[],
+            menubar: &[],
+            icon: Some("md_restore"),
+        },
         MoveTabRelative(-1) => CommandDef {
             brief: "Move tab one place to the left".into(),
             doc: "Rearranges the tabs so that the current tab moves \
@@ -1407,6 +1537,28 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
                 icon: Some("oct_terminal"),
             }
         }
+        ScrollToMark(mark) => {
+            let n = mark.amount;
+            let (direction, amount) = if n < 0 { ("up", -n) } else { ("down", n) };
+            let ordinal = english_ordinal(amount);
+            let (what, zone) = match mark.kind {
+                ScrollMarkKind::Prompt => ("prompt(s)", "semantic prompt zone"),
+                ScrollMarkKind::FailedCommand => ("failed command(s)", "failed command"),
+                ScrollMarkKind::Annotation => ("annotation(s)", "annotated line"),
+            };
+            CommandDef {
+                brief: format!("Scroll {direction} {amount} {what}").into(),
+                doc: format!(
+                    "Scrolls the viewport {direction} to the \
+                             {ordinal} {zone} in that direction"
+                )
+                .into(),
+                keys: vec![],
+                args: &[ArgType::ActivePane],
+                menubar: &[],
+                icon: Some("oct_terminal"),
+            }
+        }
         ScrollByCurrentEventWheelDelta => CommandDef {
             brief: "Scrolls based on the mouse wheel position \
                 in the current mouse event"
@@ -1587,6 +1739,50 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Window", "Select Pane"],
             icon: Some("fa_long_arrow_down"),
         },
+        ToggleBroadcastInput(BroadcastInputScope::Tab) => CommandDef {
+            brief: "Toggle broadcasting input to the panes in this tab".into(),
+            doc: "Sends the keys typed into the active pane to all \
+            of the panes in the current tab"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Window"],
+            icon: Some("md_broadcast"),
+        },
+        ToggleBroadcastInput(BroadcastInputScope::Window) => CommandDef {
+            brief: "Toggle broadcasting input to the panes in this window".into(),
+            doc: "Sends the keys typed into the active pane to all \
+            of the panes in all of the tabs in the current window"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Window"],
+            icon: Some("md_broadcast"),
+        },
+        ToggleBroadcastInput(BroadcastInputScope::SelectPanes)
+        | PaneSelect(PaneSelectArguments {
+            mode: PaneSelectMode::ToggleBroadcastInput,
+            ..
+        }) => CommandDef {
+            brief: "Select panes to receive broadcast input".into(),
+            doc: "Activates the pane selection UI to pick the panes \
+            that receive the keys typed into the active pane"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Window"],
+            icon: Some("md_broadcast"),
+        },
+        ToggleIME => CommandDef {
+            brief: "Toggle the input method for the current pane".into(),
+            doc: "Enables or disables the input method editor (IME) \
+            for the current pane"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &[],
+            icon: Some("md_keyboard"),
+        },
         TogglePaneZoomState => CommandDef {
             brief: "Toggle Pane Zoom".into(),
             doc: "Toggles the zoom state for the current pane".into(),
@@ -1595,6 +1791,27 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Window"],
             icon: Some("md_fullscreen"),
         },
+        TogglePaneOutputPause => CommandDef {
+            brief: "Pause/Resume Pane Output".into(),
+            doc: "Toggles whether output from the current pane is read. \
+            While paused, the program in the pane is blocked when it \
+            fills the pty buffer"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Window"],
+            icon: Some("md_pause"),
+        },
+        ToggleLineTimestamps => CommandDef {
+            brief: "Toggle Line Timestamps".into(),
+            doc: "Shows or hides the time at which each line of the \
+            current pane arrived, in a gutter to its left"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["View"],
+            icon: Some("md_clock_outline"),
+        },
         ActivateLastTab => CommandDef {
             brief: "Activate the last active tab".into(),
             doc: "If there was no prior active tab, has no effect.".into(),
@@ -1627,6 +1844,32 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Shell"],
             icon: None,
         },
+        ShowWorkspaceSelector => CommandDef {
+            brief: "Select workspace".into(),
+            doc: "Shows the workspace selector".into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["Window"],
+            icon: Some("cod_window"),
+        },
+        ShowTabOverview => CommandDef {
+            brief: "Tab overview".into(),
+            doc: "Shows thumbnails of all of the tabs in the window".into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["Window", "Select Tab"],
+            icon: Some("cod_layout"),
+        },
+        FocusTabBar => CommandDef {
+            brief: "Focus the tab bar".into(),
+            doc: "Moves the keyboard focus to the tab bar, so that tabs \
+                  can be selected with the arrow keys and activated with Enter"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActiveWindow],
+            menubar: &["Window", "Select Tab"],
+            icon: Some("md_tab"),
+        },
         ShowTabNavigator => CommandDef {
             brief: "Navigate tabs".into(),
             doc: "Shows the tab navigator".into(),
@@ -1774,6 +2017,16 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &[],
             icon: None,
         },
+        HighlightSelection => CommandDef {
+            brief: "Toggle a persistent highlight of the selected text".into(),
+            doc: "Highlights all occurrences of the selected text in the \
+            current pane until the highlight is toggled off again"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Edit"],
+            icon: Some("md_format_color_highlight"),
+        },
         ClearSelection => CommandDef {
             brief: "Clears the selection in the current pane".into(),
             doc: "Clears the selection in the current pane".into(),
@@ -1934,6 +2187,34 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
                 icon: None,
             }
         }
+        SwitchToLastWorkspace(depth) => {
+            let depth = (*depth).max(1);
+            let (brief, doc) = if depth == 1 {
+                (
+                    "Switch to the last active workspace".to_string(),
+                    "Switch to the most recently used workspace, \
+                     other than the current workspace"
+                        .to_string(),
+                )
+            } else {
+                let ordinal = english_ordinal(depth as isize);
+                (
+                    format!("Switch to the {ordinal} most recent workspace"),
+                    format!(
+                        "Switch to the {ordinal} most recently used workspace, \
+                         not counting the current workspace"
+                    ),
+                )
+            };
+            CommandDef {
+                brief: brief.into(),
+                doc: doc.into(),
+                keys: vec![],
+                args: &[ArgType::ActivePane],
+                menubar: &["Window", "Workspace"],
+                icon: None,
+            }
+        }
         ActivateKeyTable { name, .. } => CommandDef {
             brief: format!("Activate key table `{name}`").into(),
             doc: format!("Activate key table `{name}`").into(),
@@ -1958,6 +2239,18 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Shell", "Attach"],
             icon: Some("md_pipe"),
         },
+        MoveTabToDomain(name) => CommandDef {
+            brief: format!("Move tab to domain `{name}`").into(),
+            doc: format!(
+                "Moves the active tab into the multiplexer domain `{name}` \
+                 without restarting its programs"
+            )
+            .into(),
+            keys: vec![],
+            args: &[ArgType::ActiveTab],
+            menubar: &["Shell"],
+            icon: Some("md_tab_unselected"),
+        },
         CopyMode(copy_mode) => CommandDef {
             brief: format!("{copy_mode:?}").into(),
             doc: "".into(),
@@ -1992,6 +2285,22 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
                 },
             }
         }
+        SpawnFloatingPane(_) => CommandDef {
+            brief: label_string(action, "Spawn a floating pane".to_string()).into(),
+            doc: "Spawns a pane that floats above the other panes of the tab".into(),
+            keys: vec![],
+            args: &[ArgType::ActiveTab],
+            menubar: &["Window"],
+            icon: Some("md_dock_window"),
+        },
+        ToggleFloatingPane => CommandDef {
+            brief: "Show or hide the floating panes".into(),
+            doc: "Toggles the visibility of the floating panes of the tab".into(),
+            keys: vec![],
+            args: &[ArgType::ActiveTab],
+            menubar: &["Window"],
+            icon: Some("cod_layers"),
+        },
         ResetTerminal => CommandDef {
             brief: "Reset the terminal emulation state in the current pane".into(),
             doc: "Reset the terminal emulation state in the current pane".into(),
@@ -2000,6 +2309,34 @@ pub fn derive_command_from_key_assignment(action: &KeyAssignment) -> Option<Comm
             menubar: &["Shell"],
             icon: None,
         },
+        SaveScrollback(_) => CommandDef {
+            brief: "Save the scrollback of the current pane to a file".into(),
+            doc: "Writes the entire scrollback of the current pane to a file".into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Edit"],
+            icon: None,
+        },
+        RespawnPane(_) => CommandDef {
+            brief: "Respawn the process in the current pane".into(),
+            doc: "Kills the process running in the current pane and \
+                  starts it again, keeping the pane and its scrollback"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Shell"],
+            icon: None,
+        },
+        Disconnect => CommandDef {
+            brief: "Disconnect the current pane".into(),
+            doc: "Terminates the connection in the current pane, \
+                  stopping any attempt to reconnect to a serial port"
+                .into(),
+            keys: vec![],
+            args: &[ArgType::ActivePane],
+            menubar: &["Shell"],
+            icon: None,
+        },
         ActivateCommandPalette => CommandDef {
             brief: "Activate Command Palette".into(),
             doc: "Shows the command palette modal".into(),
@@ -2035,20 +2372,26 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
         }),
         CloseCurrentTab { confirm: true },
         CloseCurrentPane { confirm: true },
+        ReopenClosedTab,
+        ReopenClosedPane,
         DetachDomain(SpawnTabDomain::CurrentPaneDomain),
+        RespawnPane(config::keyassignment::RespawnPane::default()),
         ResetTerminal,
         // ----------------- Edit
         #[cfg(not(target_os = "macos"))]
         PasteFrom(ClipboardPasteSource::PrimarySelection),
         #[cfg(not(target_os = "macos"))]
-        CopyTo(ClipboardCopyDestination::PrimarySelection),
-        CopyTo(ClipboardCopyDestination::Clipboard),
+        CopyTo(ClipboardCopyDestination::PrimarySelection.into()),
+        CopyTo(ClipboardCopyDestination::Clipboard.into()),
         PasteFrom(ClipboardPasteSource::Clipboard),
+        PasteAsBlock(ClipboardPasteSource::Clipboard),
         ClearScrollback(ScrollbackEraseMode::ScrollbackOnly),
         ClearScrollback(ScrollbackEraseMode::ScrollbackAndViewport),
+        SaveScrollback(config::keyassignment::SaveScrollback::default()),
         QuickSelect,
         CharSelect(CharSelectArguments::default()),
         ActivateCopyMode,
+        HighlightSelection,
         ClearKeyTableStack,
         ActivateCommandPalette,
         // ----------------- View
@@ -2060,8 +2403,31 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
         ScrollByPage(NotNan::new(1.0).unwrap()),
         ScrollToTop,
         ScrollToBottom,
+        ScrollToMark(config::keyassignment::ScrollToMark {
+            kind: ScrollMarkKind::FailedCommand,
+            amount: -1,
+        }),
+        ScrollToMark(config::keyassignment::ScrollToMark {
+            kind: ScrollMarkKind::FailedCommand,
+            amount: 1,
+        }),
+        ScrollToMark(config::keyassignment::ScrollToMark {
+            kind: ScrollMarkKind::Annotation,
+            amount: -1,
+        }),
+        ScrollToMark(config::keyassignment::ScrollToMark {
+            kind: ScrollMarkKind::Annotation,
+            amount: 1,
+        }),
         // ----------------- Window
         ToggleFullScreen,
+        TogglePresentationMode,
+        ToggleZenMode,
+        ToggleIntegerScaling,
+        ToggleDropDownWindow,
+        LinkPanes(PaneLinkOptions::default()),
+        SpawnFloatingPane(config::keyassignment::SpawnFloatingPane::default()),
+        ToggleFloatingPane,
         ToggleAlwaysOnTop,
         ToggleAlwaysOnBottom,
         SetWindowLevel(WindowLevel::AlwaysOnBottom),
@@ -2094,6 +2460,9 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
             mode: PaneSelectMode::MoveToNewWindow,
             show_pane_ids: false,
         }),
+        ToggleBroadcastInput(BroadcastInputScope::Tab),
+        ToggleBroadcastInput(BroadcastInputScope::Window),
+        ToggleBroadcastInput(BroadcastInputScope::SelectPanes),
         RotatePanes(RotationDirection::Clockwise),
         RotatePanes(RotationDirection::CounterClockwise),
         ActivateTab(0),
@@ -2119,6 +2488,7 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
         ActivateWindow(9),
         ActivateWindowRelative(-1),
         ActivateWindowRelative(1),
+        ActivateLastWindow,
         MoveTabRelative(-1),
         MoveTabRelative(1),
         AdjustPaneSize(PaneDirection::Left, 1),
@@ -2130,14 +2500,20 @@ fn compute_default_actions() -> Vec<KeyAssignment> {
         ActivatePaneDirection(PaneDirection::Up),
         ActivatePaneDirection(PaneDirection::Down),
         TogglePaneZoomState,
+        TogglePaneOutputPause,
+        ToggleLineTimestamps,
         ActivateLastTab,
         ShowLauncher,
         ShowTabNavigator,
+        ShowTabOverview,
+        FocusTabBar,
+        ShowWorkspaceSelector,
         // ----------------- Help
         OpenUri("https://wezfurlong.org/wezterm/".to_string()),
         OpenUri("https://github.com/wez/wezterm/discussions/".to_string()),
         OpenUri("https://github.com/wez/wezterm/issues/".to_string()),
         ShowDebugOverlay,
+        ShowUpdateOverlay,
         // ----------------- Misc
         OpenLinkAtMouseCursor,
     ];
@@ -1,7 +1,7 @@
 use anyhow::Context;
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 /// Simple heuristics to try to avoid obvious trickery with
 /// the name provided by the remote system
@@ -56,6 +56,28 @@ fn resolve_file_name(name: Option<&str>) -> anyhow::Result<(PathBuf, File)> {
     );
 }
 
+/// Returns a path in the user's download folder, named after the
+/// current time, for saving the scrollback of a pane
+pub fn scrollback_export_path(extension: &str) -> anyhow::Result<PathBuf> {
+    let download_dir = dirs_next::download_dir()
+        .ok_or_else(|| anyhow::anyhow!("unable to locate download directory"))?;
+    Ok(download_dir.join(format!(
+        "wezterm-scrollback-{}.{extension}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    )))
+}
+
+pub fn notify_scrollback_saved(path: &Path) {
+    let url = format!("file://{}", path.display());
+    wezterm_toast_notification::persistent_toast_notification_with_click_to_open_url(
+        "Scrollback saved",
+        &format!("Saved scrollback to {}", path.display()),
+        &url,
+    );
+
+    log::info!("Saved scrollback to {}", path.display());
+}
+
 pub fn save_to_downloads(orig_name: Option<String>, data: &[u8]) -> anyhow::Result<()> {
     let (name, mut file) = resolve_file_name(orig_name.as_deref())?;
     file.write_all(data)
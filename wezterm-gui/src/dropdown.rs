@@ -0,0 +1,281 @@
+//! The quake-style window that is toggled by `ToggleDropDownWindow`.
+//! It slides down from the top edge of a screen and keeps its tabs
+//! in a workspace of its own, so that they don't mix with those of
+//! the normal windows.
+use crate::frontend::front_end;
+use ::window::{
+    Connection, ConnectionOps, GeometryOrigin, ScreenPoint, ScreenRect, Window, WindowOps,
+};
+use config::keyassignment::SpawnTabDomain;
+use config::DropDownWindowConfig;
+use mux::window::WindowId as MuxWindowId;
+use mux::Mux;
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+/// How often the window is moved while it slides
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+#[derive(Default)]
+struct DropDown {
+    mux_window_id: Option<MuxWindowId>,
+    spawning: bool,
+    visible: bool,
+    /// Where the window was most recently shown
+    rect: Option<ScreenRect>,
+    /// Incremented whenever the window starts to show or hide, so that
+    /// a slide that is superseded by a later toggle stops moving it
+    generation: usize,
+}
+
+thread_local! {
+    static DROP_DOWN: RefCell<DropDown> = RefCell::new(DropDown::default());
+}
+
+/// Returns the mux window that holds the drop-down window's tabs,
+/// if it has been created
+pub fn mux_window_id() -> Option<MuxWindowId> {
+    DROP_DOWN.with(|d| d.borrow().mux_window_id)
+}
+
+pub fn is_drop_down(mux_window_id: MuxWindowId) -> bool {
+    self::mux_window_id() == Some(mux_window_id)
+}
+
+/// Shows the drop-down window if it is hidden, or hides it if it is
+/// visible. The window is created the first time that it is shown.
+pub fn toggle() {
+    let (mux_window_id, spawning, visible) = DROP_DOWN.with(|d| {
+        let d = d.borrow();
+        (d.mux_window_id, d.spawning, d.visible)
+    });
+    if spawning {
+        return;
+    }
+
+    let mux = Mux::get();
+    match mux_window_id.filter(|&id| mux.get_window(id).is_some()) {
+        Some(mux_window_id) => {
+            // The gui window may not have been created just yet
+            if let Some(window) = front_end().gui_window_for_mux_window(mux_window_id) {
+                if visible {
+                    hide(&window);
+                } else {
+                    show(&window);
+                }
+            }
+        }
+        None => spawn(),
+    }
+}
+
+/// Called when the gui window for the drop-down mux window has been
+/// created, in place of showing it in the usual way
+pub fn window_created(window: &Window) {
+    show(window);
+}
+
+/// Called when a gui window gains or loses focus
+pub fn focus_changed(mux_window_id: MuxWindowId, focused: bool) {
+    if focused || !is_drop_down(mux_window_id) {
+        return;
+    }
+    let visible = DROP_DOWN.with(|d| d.borrow().visible);
+    if visible && config::configuration().drop_down_window.hide_on_focus_loss {
+        if let Some(window) = front_end().gui_window_for_mux_window(mux_window_id) {
+            hide(&window);
+        }
+    }
+}
+
+fn spawn() {
+    DROP_DOWN.with(|d| d.borrow_mut().spawning = true);
+    promise::spawn::spawn(async move {
+        let result = spawn_mux_window().await;
+        DROP_DOWN.with(|d| {
+            let mut d = d.borrow_mut();
+            d.spawning = false;
+            d.visible = false;
+            d.mux_window_id = result.as_ref().ok().copied();
+        });
+        match result {
+            // Now that the window is known to be the drop-down window,
+            // create its gui window even though its workspace is not
+            // the active workspace
+            Ok(_) => {
+                front_end().reconcile_workspace();
+            }
+            Err(err) => log::error!("Failed to spawn the drop-down window: {err:#}"),
+        }
+    })
+    .detach();
+}
+
+async fn spawn_mux_window() -> anyhow::Result<MuxWindowId> {
+    let config = config::configuration();
+    let dpi = config.dpi.unwrap_or_else(|| ::window::default_dpi());
+    let size = config.initial_size(dpi as u32, crate::cell_pixel_dims(&config, dpi).ok());
+    let mux = Mux::get();
+    let (_tab, _pane, mux_window_id) = mux
+        .spawn_tab_or_window(
+            None,
+            SpawnTabDomain::DefaultDomain,
+            None,
+            None,
+            size,
+            None,
+            config.drop_down_window.workspace.clone(),
+            None,
+        )
+        .await?;
+    Ok(mux_window_id)
+}
+
+/// Returns the bounds of the screen selected by `origin`
+fn screen_rect(origin: &GeometryOrigin) -> anyhow::Result<ScreenRect> {
+    let conn = Connection::get().ok_or_else(|| anyhow::anyhow!("no gui connection"))?;
+    let screens = conn.screens()?;
+    Ok(match origin {
+        GeometryOrigin::ScreenCoordinateSystem => screens.virtual_rect,
+        GeometryOrigin::MainScreen => screens.main.rect,
+        GeometryOrigin::ActiveScreen => screens.active.rect,
+        GeometryOrigin::Named(name) => match screens.by_name.get(name) {
+            Some(info) => info.rect,
+            None => {
+                log::warn!("drop_down_window: screen {name} was not found; using the main screen");
+                screens.main.rect
+            }
+        },
+    })
+}
+
+/// Returns the bounds of the drop-down window when it is fully shown
+/// along the top edge of `screen`
+fn drop_down_rect(screen: ScreenRect, config: &DropDownWindowConfig) -> ScreenRect {
+    let fraction = config.height.clamp(0.1, 1.0);
+    let height = ((screen.height() as f32 * fraction) as isize).max(1);
+    euclid::rect(screen.min_x(), screen.min_y(), screen.width(), height)
+}
+
+/// Returns the y coordinate of the top of the window, `progress` of
+/// the way through sliding from `from` to `to`
+fn slide_position(from: isize, to: isize, progress: f32) -> isize {
+    // Ease out, so that the window decelerates as it arrives
+    let eased = 1.0 - (1.0 - progress.clamp(0.0, 1.0)).powi(3);
+    from + ((to - from) as f32 * eased).round() as isize
+}
+
+fn show(window: &Window) {
+    let config = config::configuration();
+    let drop_down = &config.drop_down_window;
+    // The screen geometry is queried each time, as the monitors
+    // may have been rearranged since the window was last shown
+    let rect = match screen_rect(&drop_down.screen) {
+        Ok(screen) => drop_down_rect(screen, drop_down),
+        Err(err) => {
+            log::error!("drop_down_window: unable to determine screen geometry: {err:#}");
+            window.show();
+            window.focus();
+            return;
+        }
+    };
+    let generation = DROP_DOWN.with(|d| {
+        let mut d = d.borrow_mut();
+        d.visible = true;
+        d.rect.replace(rect);
+        d.generation += 1;
+        d.generation
+    });
+
+    window.set_inner_size(rect.width() as usize, rect.height() as usize);
+    window.set_window_position(ScreenPoint::new(rect.min_x(), rect.min_y() - rect.height()));
+    window.show();
+    window.focus();
+    slide(
+        window.clone(),
+        rect,
+        rect.min_y() - rect.height(),
+        rect.min_y(),
+        Duration::from_millis(drop_down.animation_duration_ms),
+        generation,
+    );
+}
+
+fn hide(window: &Window) {
+    let config = config::configuration();
+    let (rect, generation) = DROP_DOWN.with(|d| {
+        let mut d = d.borrow_mut();
+        d.visible = false;
+        d.generation += 1;
+        (d.rect, d.generation)
+    });
+    match rect {
+        Some(rect) => slide(
+            window.clone(),
+            rect,
+            rect.min_y(),
+            rect.min_y() - rect.height(),
+            Duration::from_millis(config.drop_down_window.animation_duration_ms),
+            generation,
+        ),
+        None => window.hide(),
+    }
+}
+
+/// Moves the window vertically from `from` to `to` over `duration`.
+/// If the window is sliding out of view, it is hidden at the end.
+fn slide(
+    window: Window,
+    rect: ScreenRect,
+    from: isize,
+    to: isize,
+    duration: Duration,
+    generation: usize,
+) {
+    let hide_when_done = to < from;
+    promise::spawn::spawn(async move {
+        let start = Instant::now();
+        loop {
+            if DROP_DOWN.with(|d| d.borrow().generation) != generation {
+                // A later toggle has taken over
+                return;
+            }
+            let progress = if duration.is_zero() {
+                1.0
+            } else {
+                start.elapsed().as_secs_f32() / duration.as_secs_f32()
+            };
+            window.set_window_position(ScreenPoint::new(
+                rect.min_x(),
+                slide_position(from, to, progress),
+            ));
+            if progress >= 1.0 {
+                break;
+            }
+            smol::Timer::after(FRAME_INTERVAL).await;
+        }
+        if hide_when_done {
+            window.hide();
+        }
+    })
+    .detach();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn geometry() {
+        let config = DropDownWindowConfig::default();
+        let screen: ScreenRect = euclid::rect(1920, 0, 2560, 1440);
+        let rect = drop_down_rect(screen, &config);
+        assert_eq!(rect, euclid::rect(1920, 0, 2560, 576));
+
+        assert_eq!(slide_position(-576, 0, 0.0), -576);
+        assert_eq!(slide_position(-576, 0, 0.5), -72);
+        assert_eq!(slide_position(-576, 0, 1.0), 0);
+        assert_eq!(slide_position(-576, 0, 2.0), 0);
+        assert_eq!(slide_position(0, -576, 1.0), -576);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::lifecycle::LifecycleEvent;
 use crate::scripting::guiwin::GuiWin;
 use crate::spawn::SpawnWhere;
 use crate::termwindow::TermWindowNotif;
@@ -7,16 +8,58 @@ use anyhow::{Context, Error};
 use config::keyassignment::{KeyAssignment, SpawnCommand};
 use config::{ConfigSubscription, NotificationHandling};
 use mux::client::ClientId;
+use mux::pane::PaneId;
 use mux::window::WindowId as MuxWindowId;
 use mux::{Mux, MuxNotification};
 use promise::{Future, Promise};
 use std::cell::RefCell;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashSet, VecDeque};
 use std::rc::Rc;
 use std::sync::Arc;
-use wezterm_term::{Alert, ClipboardSelection};
+use std::time::{Duration, Instant};
+use wezterm_term::{send_notification_reply, Alert, ClipboardSelection, NotificationAction};
 use wezterm_toast_notification::*;
 
+/// Shows a notification whose buttons report back to `pane_id`
+/// by writing `OSC 777 ; notify-reply ; <id> ST` to it
+fn show_notification_with_replies(
+    pane_id: PaneId,
+    title: &str,
+    message: &str,
+    actions: Vec<NotificationAction>,
+) {
+    let on_action = ActionHandler(Arc::new(move |id: &str| {
+        let id = id.to_string();
+        promise::spawn::spawn_into_main_thread(async move {
+            let pane = Mux::get().get_pane(pane_id);
+            let mut writer = pane.as_ref().map(|pane| pane.writer());
+            match send_notification_reply(writer.as_deref_mut(), &id) {
+                Ok(true) => {}
+                Ok(false) => {
+                    log::debug!("Not replying to notification of dead pane {}", pane_id)
+                }
+                Err(err) => log::error!("Failed to send notification reply: {:#}", err),
+            }
+        })
+        .detach();
+    }));
+    ToastNotification {
+        title: title.to_string(),
+        message: message.to_string(),
+        url: None,
+        timeout: None,
+        actions: actions
+            .into_iter()
+            .map(|action| ToastAction {
+                id: action.id,
+                label: action.label,
+            })
+            .collect(),
+        on_action: Some(on_action),
+    }
+    .show();
+}
+
 pub struct GuiFrontEnd {
     connection: Rc<Connection>,
     switching_workspaces: RefCell<bool>,
@@ -24,6 +67,85 @@ pub struct GuiFrontEnd {
     known_windows: RefCell<BTreeMap<Window, MuxWindowId>>,
     client_id: Arc<ClientId>,
     config_subscription: RefCell<Option<ConfigSubscription>>,
+    focus_history: RefCell<FocusHistory>,
+}
+
+/// How many focus events are retained by the `FocusHistory`
+const FOCUS_HISTORY_SIZE: usize = 32;
+
+/// Records a focus change in the GUI.
+/// Mux window ids are used rather than gui windows, as the gui
+/// windows are repurposed for different mux windows when switching
+/// workspaces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FocusEvent {
+    pub mux_window_id: MuxWindowId,
+    pub workspace: String,
+    pub pane_id: Option<PaneId>,
+    pub time: chrono::DateTime<chrono::Utc>,
+}
+
+/// A bounded, most-recent-first record of focus events
+#[derive(Debug, Default)]
+pub struct FocusHistory {
+    events: VecDeque<FocusEvent>,
+}
+
+impl FocusHistory {
+    pub fn record(&mut self, event: FocusEvent) {
+        if let Some(front) = self.events.front_mut() {
+            if front.mux_window_id == event.mux_window_id
+                && front.workspace == event.workspace
+                && front.pane_id == event.pane_id
+            {
+                // Just refresh the timestamp of the current entry
+                front.time = event.time;
+                return;
+            }
+        }
+        self.events.push_front(event);
+        self.events.truncate(FOCUS_HISTORY_SIZE);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FocusEvent> {
+        self.events.iter()
+    }
+
+    /// Returns the most recently focused mux window other than
+    /// `current` that satisfies `is_valid`
+    pub fn last_window(
+        &self,
+        current: MuxWindowId,
+        is_valid: impl Fn(MuxWindowId) -> bool,
+    ) -> Option<MuxWindowId> {
+        self.events
+            .iter()
+            .map(|event| event.mux_window_id)
+            .find(|&id| id != current && is_valid(id))
+    }
+
+    /// Returns the workspace that was active `depth` distinct
+    /// workspaces ago, not counting `current`.
+    /// `depth` of 1 is the most recently used other workspace.
+    pub fn last_workspace(
+        &self,
+        current: &str,
+        depth: usize,
+        is_valid: impl Fn(&str) -> bool,
+    ) -> Option<String> {
+        let mut seen: Vec<&str> = vec![current];
+        for event in &self.events {
+            let workspace = event.workspace.as_str();
+            if seen.contains(&workspace) || !is_valid(workspace) {
+                continue;
+            }
+            seen.push(workspace);
+            if seen.len() > depth {
+                return Some(workspace.to_string());
+            }
+        }
+        None
+    }
 }
 
 impl Drop for GuiFrontEnd {
@@ -47,6 +169,7 @@ impl GuiFrontEnd {
             known_windows: RefCell::new(BTreeMap::new()),
             client_id: client_id.clone(),
             config_subscription: RefCell::new(None),
+            focus_history: RefCell::new(FocusHistory::default()),
         });
 
         mux.subscribe(move |n| {
@@ -89,11 +212,20 @@ impl GuiFrontEnd {
                 MuxNotification::TabTitleChanged { .. } => {}
                 MuxNotification::WindowTitleChanged { .. } => {}
                 MuxNotification::TabResized(_) => {}
-                MuxNotification::TabAddedToWindow { .. } => {}
-                MuxNotification::PaneRemoved(_) => {}
+                MuxNotification::TabAddedToWindow { tab_id, window_id } => {
+                    crate::lifecycle::notify(LifecycleEvent::TabCreated { tab_id, window_id });
+                }
+                MuxNotification::PaneRemoved(pane_id) => {
+                    crate::lifecycle::notify(LifecycleEvent::PaneRemoved(pane_id));
+                }
                 MuxNotification::WindowInvalidated(_) => {}
+                MuxNotification::WindowGeometryChanged(_) => {}
+                MuxNotification::KillClient(_) => {}
+                MuxNotification::PowerEvent(_) => {}
                 MuxNotification::PaneOutput(_) => {}
-                MuxNotification::PaneAdded(_) => {}
+                MuxNotification::PaneAdded(pane_id) => {
+                    crate::lifecycle::notify(LifecycleEvent::PaneCreated(pane_id));
+                }
                 MuxNotification::Alert {
                     pane_id,
                     alert:
@@ -101,6 +233,7 @@ impl GuiFrontEnd {
                             title,
                             body,
                             focus: _,
+                            actions,
                         },
                 } => {
                     let mux = Mux::get();
@@ -121,13 +254,20 @@ impl GuiFrontEnd {
                                 }
                             };
 
-                            if show {
+                            if show && !crate::termwindow::presentation::notifications_suppressed()
+                            {
                                 let message = if title.is_none() { "" } else { &body };
                                 let title = title.as_ref().unwrap_or(&body);
                                 // FIXME: if notification.focus is true, we should do
                                 // something here to arrange to focus pane_id when the
                                 // notification is clicked
-                                persistent_toast_notification(title, message);
+                                if config.notification_replies && !actions.is_empty() {
+                                    show_notification_with_replies(
+                                        pane_id, title, message, actions,
+                                    );
+                                } else {
+                                    persistent_toast_notification(title, message);
+                                }
                             }
                         }
                     }
@@ -147,7 +287,13 @@ impl GuiFrontEnd {
                         | Alert::WindowTitleChanged(_)
                         | Alert::TabTitleChanged(_)
                         | Alert::IconTitleChanged(_)
-                        | Alert::SetUserVar { .. },
+                        | Alert::SetUserVar { .. }
+                        | Alert::PaneOutputActivity
+                        | Alert::PaneSilence { .. }
+                        | Alert::PaneMatch { .. }
+                        | Alert::Progress(_)
+                        | Alert::OutputRateLimited(_)
+                        | Alert::OutputPaused(_),
                 } => {}
                 MuxNotification::Empty => {
                     if config::configuration().quit_when_all_windows_are_closed {
@@ -211,6 +357,7 @@ impl GuiFrontEnd {
         // And build the initial menu bar.
         // TODO: arrange for this to happen on config reload.
         crate::commands::CommandDef::recreate_menubar(&config::configuration());
+        register_global_keys(&config::configuration());
 
         Ok(front_end)
     }
@@ -272,10 +419,9 @@ impl GuiFrontEnd {
                 .detach();
             }
             ApplicationEvent::PerformKeyAssignment(action) => {
-                // We should only get here when there are no windows open
-                // and the user picks an action from the menubar.
-                // This is not currently possible, but could be in the
-                // future.
+                // We get here when one of the global_keys is pressed,
+                // or, when there are no windows open, if the user picks
+                // an action from the menubar.
 
                 fn spawn_command(spawn: &SpawnCommand, spawn_where: SpawnWhere) {
                     let config = config::configuration();
@@ -312,6 +458,9 @@ impl GuiFrontEnd {
                     KeyAssignment::SpawnCommandInNewWindow(spawn) => {
                         spawn_command(&spawn, SpawnWhere::NewWindow);
                     }
+                    KeyAssignment::ToggleDropDownWindow => {
+                        crate::dropdown::toggle();
+                    }
                     _ => {
                         log::warn!("unhandled perform: {action:?}");
                     }
@@ -352,8 +501,9 @@ impl GuiFrontEnd {
                 promise.ok(());
                 return promise.get_future().unwrap();
             }
+            let drop_down_workspace = config::configuration().drop_down_window.workspace.clone();
             for workspace in mux.iter_workspaces() {
-                if !mux.is_workspace_empty(&workspace) {
+                if !mux.is_workspace_empty(&workspace) && workspace != drop_down_workspace {
                     mux.set_active_workspace_for_client(&self.client_id, &workspace);
                     log::debug!("using {} instead, as it is not empty", workspace);
                     break;
@@ -366,6 +516,11 @@ impl GuiFrontEnd {
 
         let mut mux_windows = mux.iter_windows_in_workspace(&workspace);
 
+        // The drop-down window lives in a workspace of its own, and is
+        // kept regardless of which workspace is active
+        let mut drop_down = crate::dropdown::mux_window_id()
+            .filter(|id| !mux_windows.contains(id) && mux.get_window(*id).is_some());
+
         // First, repurpose existing windows.
         // Note that both iter_windows_in_workspace and self.known_windows have a
         // deterministic iteration order, so switching back and forth should result
@@ -375,7 +530,10 @@ impl GuiFrontEnd {
         let mut unused = BTreeMap::new();
 
         for (window, window_id) in known_windows.into_iter() {
-            if let Some(idx) = mux_windows.iter().position(|&id| id == window_id) {
+            if drop_down == Some(window_id) {
+                windows.insert(window, window_id);
+                drop_down.take();
+            } else if let Some(idx) = mux_windows.iter().position(|&id| id == window_id) {
                 // it already points to the desired mux window
                 windows.insert(window, window_id);
                 mux_windows.remove(idx);
@@ -401,6 +559,9 @@ impl GuiFrontEnd {
         log::trace!("reconcile: windows -> {:?}", windows);
         *self.known_windows.borrow_mut() = windows;
 
+        // The drop-down window is never repurposed from another window
+        let mut mux_windows = mux_windows.chain(drop_down);
+
         let future = promise.get_future().unwrap();
 
         // then spawn any new windows that are needed
@@ -436,6 +597,14 @@ impl GuiFrontEnd {
         future
     }
 
+    pub fn gui_window_for_mux_window(&self, mux_window_id: MuxWindowId) -> Option<Window> {
+        self.known_windows
+            .borrow()
+            .iter()
+            .find(|(_, &mux_id)| mux_id == mux_window_id)
+            .map(|(window, _)| window.clone())
+    }
+
     fn has_mux_window(&self, mux_window_id: MuxWindowId) -> bool {
         for &mux_id in self.known_windows.borrow().values() {
             if mux_id == mux_window_id {
@@ -472,6 +641,67 @@ impl GuiFrontEnd {
         *self.switching_workspaces.borrow()
     }
 
+    /// Called by the gui windows when they gain focus, or when
+    /// the focused window switches to a different mux window or pane
+    pub fn record_focus(&self, mux_window_id: MuxWindowId) {
+        let mux = Mux::get();
+        let window = match mux.get_window(mux_window_id) {
+            Some(window) => window,
+            None => return,
+        };
+        let workspace = window.get_workspace().to_string();
+        let pane_id = window
+            .get_active()
+            .and_then(|tab| tab.get_active_pane())
+            .map(|pane| pane.pane_id());
+        drop(window);
+        self.focus_history.borrow_mut().record(FocusEvent {
+            mux_window_id,
+            workspace,
+            pane_id,
+            time: chrono::Utc::now(),
+        });
+    }
+
+    /// Returns the recorded focus events, most recent first
+    pub fn focus_history(&self) -> Vec<FocusEvent> {
+        self.focus_history.borrow().iter().cloned().collect()
+    }
+
+    /// Returns the most recently focused gui window other than
+    /// the one associated with `current`
+    pub fn last_active_window(&self, current: MuxWindowId) -> Option<GuiWin> {
+        let mux_window_id = self
+            .focus_history
+            .borrow()
+            .last_window(current, |id| self.has_mux_window(id))?;
+        self.gui_window_for_mux_window(mux_window_id)
+    }
+
+    /// Returns the name of the workspace that was active `depth`
+    /// distinct workspaces ago
+    pub fn last_workspace(&self, depth: usize) -> Option<String> {
+        let mux = Mux::get();
+        let current = mux.active_workspace_for_client(&self.client_id);
+        let workspaces = mux.iter_workspaces();
+        self.focus_history
+            .borrow()
+            .last_workspace(&current, depth, |name| workspaces.iter().any(|w| w == name))
+    }
+
+    /// Returns the most recently focused gui window,
+    /// falling back to any known window
+    fn most_recent_window(&self) -> Option<GuiWin> {
+        let mux_window_id = self
+            .focus_history
+            .borrow()
+            .iter()
+            .map(|event| event.mux_window_id)
+            .find(|&id| self.has_mux_window(id))
+            .or_else(|| self.known_windows.borrow().values().next().copied())?;
+        self.gui_window_for_mux_window(mux_window_id)
+    }
+
     pub fn gui_window_for_mux_window(&self, mux_window_id: MuxWindowId) -> Option<GuiWin> {
         let windows = self.known_windows.borrow();
         for (window, v) in windows.iter() {
@@ -490,6 +720,24 @@ thread_local! {
     static FRONT_END: RefCell<Option<Rc<GuiFrontEnd>>> = RefCell::new(None);
 }
 
+/// Registers the `global_keys` with the system
+fn register_global_keys(config: &config::ConfigHandle) {
+    let hotkeys: Vec<GlobalHotkey> = config
+        .global_keys
+        .iter()
+        .map(|key| GlobalHotkey {
+            key: key.key.key.resolve(config::KeyMapPreference::Mapped),
+            mods: key.key.mods,
+            action: key.action.clone(),
+        })
+        .collect();
+    if let Some(conn) = Connection::get() {
+        if let Err(err) = conn.set_global_hotkeys(&hotkeys) {
+            log::error!("global_keys: {err:#}");
+        }
+    }
+}
+
 pub fn try_front_end() -> Option<Rc<GuiFrontEnd>> {
     FRONT_END.with(|f| f.borrow().as_ref().map(Rc::clone))
 }
@@ -527,6 +775,100 @@ pub fn shutdown() {
     FRONT_END.with(|f| drop(f.borrow_mut().take()));
 }
 
+/// How long to wait for a gui window to appear in order
+/// to present a host key or certificate confirmation
+const HOST_KEY_WINDOW_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returns the gui window that holds pane_id
+fn window_for_pane(fe: &GuiFrontEnd, pane_id: PaneId) -> Option<GuiWin> {
+    let (_domain_id, mux_window_id, _tab_id) = Mux::get().resolve_pane_id(pane_id)?;
+    fe.gui_window_for_mux_window(mux_window_id)
+}
+
+/// Returns the window chosen by `find`, waiting a little while
+/// for it to appear if it doesn't exist yet
+async fn wait_for_window<F: Fn(&GuiFrontEnd) -> Option<GuiWin>>(find: F) -> Option<GuiWin> {
+    let deadline = Instant::now() + HOST_KEY_WINDOW_TIMEOUT;
+    loop {
+        let gui_win = try_front_end().and_then(|fe| find(&fe));
+        if let Some(gui_win) = gui_win {
+            return Some(gui_win);
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        smol::Timer::after(Duration::from_millis(100)).await;
+    }
+}
+
+/// Shows the host key confirmation overlay in the gui window that
+/// holds the pane that is connecting.  When connecting for `wezterm ssh`
+/// the window may not exist yet, so we wait a little while for it.
+/// If it doesn't appear, the session prompts for the answer itself.
+async fn confirm_host_key(request: mux::ssh::HostVerificationRequest) {
+    let pane_id = request.pane_id;
+    let find = |fe: &GuiFrontEnd| match pane_id {
+        Some(pane_id) => window_for_pane(fe, pane_id),
+        None => fe.most_recent_window(),
+    };
+    match wait_for_window(find).await {
+        Some(gui_win) => {
+            let verify = request.into_event();
+            gui_win
+                .window
+                .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    term_window.show_host_key_confirmation(verify, pane_id);
+                })))
+        }
+        None => {
+            log::warn!(
+                "No window is available to confirm the host key for {}, \
+                 prompting in the terminal instead",
+                request.event().remote_address
+            );
+            request.prompt_inline();
+        }
+    }
+}
+
+/// Shows the changed certificate modal for a TLS domain in a gui window
+/// that holds one of its panes; when first attaching it has none yet,
+/// so we use the most recently focused window, from which the attach
+/// was most likely started.  If no window appears, the connection
+/// prompts for the answer itself.
+async fn confirm_certificate_change(request: wezterm_client::pinning::CertificateChangeRequest) {
+    let domain_id = request.domain_id;
+    let find = |fe: &GuiFrontEnd| {
+        domain_id
+            .and_then(|domain_id| {
+                Mux::get()
+                    .iter_panes()
+                    .into_iter()
+                    .filter(|pane| pane.domain_id() == domain_id)
+                    .find_map(|pane| window_for_pane(fe, pane.pane_id()))
+            })
+            .or_else(|| fe.most_recent_window())
+    };
+    match wait_for_window(find).await {
+        Some(gui_win) => {
+            let change = request.into_change();
+            gui_win
+                .window
+                .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    term_window.show_certificate_confirmation(change);
+                })))
+        }
+        None => {
+            log::warn!(
+                "No window is available to confirm the changed certificate for {}, \
+                 prompting in the connection window instead",
+                request.change().remote_address
+            );
+            request.prompt_inline();
+        }
+    }
+}
+
 pub fn try_new() -> Result<Rc<GuiFrontEnd>, Error> {
     let front_end = GuiFrontEnd::try_new()?;
     FRONT_END.with(|f| *f.borrow_mut() = Some(Rc::clone(&front_end)));
@@ -535,6 +877,7 @@ pub fn try_new() -> Result<Rc<GuiFrontEnd>, Error> {
         move || {
             promise::spawn::spawn_into_main_thread(async {
                 crate::commands::CommandDef::recreate_menubar(&config::configuration());
+                register_global_keys(&config::configuration());
             })
             .detach();
             true
@@ -545,5 +888,61 @@ pub fn try_new() -> Result<Rc<GuiFrontEnd>, Error> {
         .borrow_mut()
         .replace(config_subscription);
 
+    // Present unknown ssh host keys in an overlay; the ssh session
+    // thread blocks on the answer rather than the gui thread.
+    mux::ssh::set_host_verification_handler(Arc::new(|request| {
+        promise::spawn::spawn_into_main_thread(confirm_host_key(request)).detach();
+    }));
+    wezterm_client::pinning::set_certificate_change_handler(Arc::new(|request| {
+        promise::spawn::spawn_into_main_thread(confirm_certificate_change(request)).detach();
+    }));
+
     Ok(front_end)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn event(mux_window_id: MuxWindowId, workspace: &str) -> FocusEvent {
+        FocusEvent {
+            mux_window_id,
+            workspace: workspace.to_string(),
+            pane_id: None,
+            time: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn focus_history() {
+        let mut history = FocusHistory::default();
+        history.record(event(1, "default"));
+        history.record(event(2, "default"));
+        history.record(event(2, "default"));
+        history.record(event(3, "work"));
+        history.record(event(4, "play"));
+
+        assert_eq!(history.iter().count(), 4);
+        assert_eq!(history.last_window(4, |_| true), Some(3));
+        assert_eq!(history.last_window(4, |id| id != 3), Some(2));
+
+        assert_eq!(
+            history.last_workspace("play", 1, |_| true),
+            Some("work".to_string())
+        );
+        assert_eq!(
+            history.last_workspace("play", 2, |_| true),
+            Some("default".to_string())
+        );
+        assert_eq!(history.last_workspace("play", 3, |_| true), None);
+        assert_eq!(
+            history.last_workspace("play", 1, |w| w != "work"),
+            Some("default".to_string())
+        );
+
+        for i in 0..100 {
+            history.record(event(i, "default"));
+        }
+        assert_eq!(history.iter().count(), FOCUS_HISTORY_SIZE);
+    }
+}
@@ -1,5 +1,6 @@
 use super::utilsprites::RenderMetrics;
 use crate::customglyph::*;
+use crate::nerdicons::{centered_bearing_y, icon_scale, is_nerd_font_icon};
 use crate::renderstate::RenderContext;
 use crate::termwindow::render::paint::AllowImage;
 use ::window::bitmaps::atlas::{Atlas, OutOfTextureSpace, Sprite};
@@ -639,7 +640,7 @@ impl GlyphCache {
         }
         metrics::histogram!("glyph_cache.glyph_cache.miss.rate").record(1.);
 
-        let glyph = match self.load_glyph(info, font, followed_by_space, num_cells) {
+        let glyph = match self.load_glyph(info, font, followed_by_space, metrics, num_cells) {
             Ok(g) => g,
             Err(err) => {
                 if err
@@ -691,11 +692,13 @@ impl GlyphCache {
         info: &GlyphInfo,
         font: &Rc<LoadedFont>,
         followed_by_space: bool,
+        metrics: &RenderMetrics,
         num_cells: u8,
     ) -> anyhow::Result<Rc<CachedGlyph>> {
         let base_metrics;
         let idx_metrics;
         let brightness_adjust;
+        let idx_line_height;
         let glyph;
 
         {
@@ -704,6 +707,7 @@ impl GlyphCache {
 
             idx_metrics = font.metrics_for_idx(info.font_idx)?;
             brightness_adjust = font.brightness_adjust(info.font_idx);
+            idx_line_height = font.line_height_for_idx(info.font_idx);
         }
 
         let aspect = (idx_metrics.cell_width / idx_metrics.cell_height).get();
@@ -733,7 +737,7 @@ impl GlyphCache {
         // the dimensions of a cell
         let max_pixel_width = base_metrics.cell_width.get() * (num_cells + 0.25);
 
-        let scale;
+        let mut scale;
 
         // This helps to compensate for the !idx_metrics.is_scaled && glyph.is_scaled
         // case which happens when using the harfbuzz rasterizer with a bitmap font.
@@ -798,6 +802,46 @@ impl GlyphCache {
             }
         };
 
+        // A fallback font with its own line_height is constrained to that
+        // fraction of the height of the primary font, so that tall glyphs
+        // (eg: CJK) are shrunk to fit rather than forcing a taller line.
+        // The line_height of the primary font is applied to the cell
+        // metrics by RenderMetrics instead.
+        if info.font_idx != 0 && glyph.height > 0 {
+            if let Some(line_height) = idx_line_height {
+                let max_pixel_height = base_metrics.cell_height.get() * line_height;
+                if glyph.height as f64 * scale > max_pixel_height {
+                    scale = max_pixel_height / glyph.height as f64;
+                }
+            }
+        }
+
+        // Nerd Font icons are sized relative to the cell, rather than
+        // to the em box of the font that supplied them
+        let nerd_font_scale = match self.fonts.config().nerd_font_scale {
+            Some(nerd_font_scale)
+                if glyph.width > 0
+                    && glyph.height > 0
+                    && info.only_char.map(is_nerd_font_icon).unwrap_or(false) =>
+            {
+                let max_width = base_metrics.cell_width.get()
+                    * if allow_width_overflow {
+                        num_cells + 1.0
+                    } else {
+                        num_cells
+                    };
+                scale = icon_scale(
+                    glyph.width as f64,
+                    glyph.height as f64,
+                    max_width,
+                    base_metrics.cell_height.get(),
+                    nerd_font_scale,
+                );
+                Some(nerd_font_scale)
+            }
+            _ => None,
+        };
+
         let descender_adjust = if info.font_idx == 0 {
             PixelLength::new(0.0)
         } else {
@@ -831,7 +875,17 @@ impl GlyphCache {
             // No metrics_only_scale adjustment to bearing_y is needed because
             // the value comes from the rasterized glyph and not from the
             // shaper stage.
-            let bearing_y = descender_adjust + (glyph.bearing_y * scale);
+            let mut bearing_y = descender_adjust + (glyph.bearing_y * scale);
+            if nerd_font_scale.is_some() {
+                if let Some(centered) = centered_bearing_y(
+                    bearing_y.get(),
+                    glyph.height as f64 * scale,
+                    metrics.cell_size.height as f64,
+                    metrics.descender.get(),
+                ) {
+                    bearing_y = PixelLength::new(centered);
+                }
+            }
             let x_offset = info.x_offset * scale * metrics_only_scale;
             let y_offset = info.y_offset * scale * metrics_only_scale;
             let x_advance = info.x_advance * scale * metrics_only_scale;
@@ -0,0 +1,215 @@
+//! Emits the `pane-created`, `pane-removed` and `tab-created` events.
+//! The mux tells the frontend about these transitions from whichever
+//! thread made them.  The events are queued on the main thread and
+//! emitted, in the order that the transitions happened, by a single
+//! task, so that a handler that takes a while delays the events that
+//! follow it rather than racing them.
+//! Until the gui has finished starting up, which includes running the
+//! `gui-startup` event, the events are held, so that the handlers for
+//! the initial panes see the windows that `gui-startup` arranged.
+use mux::pane::PaneId;
+use mux::tab::TabId;
+use mux::window::WindowId;
+use mux::Mux;
+use mux_lua::{MuxPane, MuxTab, MuxWindow};
+use std::cell::RefCell;
+use std::collections::{HashSet, VecDeque};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    PaneCreated(PaneId),
+    PaneRemoved(PaneId),
+    TabCreated { tab_id: TabId, window_id: WindowId },
+}
+
+#[derive(Debug, Default)]
+struct LifecycleQueue {
+    started: bool,
+    emitting: bool,
+    events: VecDeque<LifecycleEvent>,
+    /// The mux also reports a tab being added to a window when it
+    /// is moved to a different window, which doesn't create it
+    seen_tabs: HashSet<TabId>,
+}
+
+impl LifecycleQueue {
+    /// Queues `event`.  Returns true if the caller needs to start
+    /// a task to emit the queued events.
+    fn push(&mut self, event: LifecycleEvent) -> bool {
+        if let LifecycleEvent::TabCreated { tab_id, .. } = &event {
+            if !self.seen_tabs.insert(*tab_id) {
+                return false;
+            }
+        }
+        self.events.push_back(event);
+        self.should_start_emitting()
+    }
+
+    /// Marks the gui as started.  Returns true if the caller needs
+    /// to start a task to emit the events that were held until now.
+    fn start(&mut self) -> bool {
+        self.started = true;
+        self.should_start_emitting()
+    }
+
+    fn should_start_emitting(&mut self) -> bool {
+        if self.started && !self.emitting && !self.events.is_empty() {
+            self.emitting = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the next event to emit.  Once the queue has been
+    /// drained, the emitting task is done, and pushing another event
+    /// will start a new one.
+    fn pop(&mut self) -> Option<LifecycleEvent> {
+        let event = self.events.pop_front();
+        if event.is_none() {
+            self.emitting = false;
+        }
+        event
+    }
+}
+
+thread_local! {
+    static QUEUE: RefCell<LifecycleQueue> = RefCell::new(LifecycleQueue::default());
+}
+
+/// Queues `event` to be emitted.  May be called from any thread.
+pub fn notify(event: LifecycleEvent) {
+    promise::spawn::spawn_into_main_thread(async move {
+        if QUEUE.with(|queue| queue.borrow_mut().push(event)) {
+            promise::spawn::spawn(emit_queued_events()).detach();
+        }
+    })
+    .detach();
+}
+
+/// Called once the gui has started up and the `gui-startup`
+/// event has completed
+pub fn gui_started() {
+    if QUEUE.with(|queue| queue.borrow_mut().start()) {
+        promise::spawn::spawn(emit_queued_events()).detach();
+    }
+}
+
+async fn emit_queued_events() {
+    while let Some(event) = QUEUE.with(|queue| queue.borrow_mut().pop()) {
+        let name = event_name(&event);
+        if let Err(err) =
+            config::with_lua_config_on_main_thread(move |lua| emit_event(lua, event)).await
+        {
+            log::error!("while processing {name} event: {err:#}");
+        }
+    }
+}
+
+fn event_name(event: &LifecycleEvent) -> &'static str {
+    match event {
+        LifecycleEvent::PaneCreated(_) => "pane-created",
+        LifecycleEvent::PaneRemoved(_) => "pane-removed",
+        LifecycleEvent::TabCreated { .. } => "tab-created",
+    }
+}
+
+async fn emit_event(lua: Option<Rc<mlua::Lua>>, event: LifecycleEvent) -> anyhow::Result<()> {
+    let lua = match lua {
+        Some(lua) => lua,
+        None => return Ok(()),
+    };
+    let name = event_name(&event);
+    if !config::lua::has_event_handler(&lua, name)? {
+        return Ok(());
+    }
+
+    let mux = Mux::get();
+    let args = match event {
+        LifecycleEvent::PaneCreated(pane_id) => {
+            if mux.get_pane(pane_id).is_none() {
+                // It has already gone away; pane-removed follows
+                return Ok(());
+            }
+            let spawn_command = mux.get_spawn_command(pane_id);
+            lua.pack_multi((MuxPane(pane_id), spawn_command))?
+        }
+        LifecycleEvent::PaneRemoved(pane_id) => lua.pack_multi(pane_id)?,
+        LifecycleEvent::TabCreated { tab_id, window_id } => {
+            lua.pack_multi((MuxTab(tab_id), MuxWindow(window_id)))?
+        }
+    };
+    config::lua::emit_event(&lua, (name.to_string(), args)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn drain(queue: &mut LifecycleQueue) -> Vec<LifecycleEvent> {
+        std::iter::from_fn(|| queue.pop()).collect()
+    }
+
+    #[test]
+    fn held_until_started() {
+        let mut queue = LifecycleQueue::default();
+
+        // The panes and tab created by gui-startup are held
+        assert!(!queue.push(LifecycleEvent::PaneCreated(1)));
+        assert!(!queue.push(LifecycleEvent::TabCreated {
+            tab_id: 1,
+            window_id: 0
+        }));
+        assert!(!queue.push(LifecycleEvent::PaneCreated(2)));
+
+        // and emitted, in order, once it has completed
+        assert!(queue.start());
+        assert_eq!(
+            drain(&mut queue),
+            vec![
+                LifecycleEvent::PaneCreated(1),
+                LifecycleEvent::TabCreated {
+                    tab_id: 1,
+                    window_id: 0
+                },
+                LifecycleEvent::PaneCreated(2),
+            ]
+        );
+
+        // Nothing was held back, so there is nothing to start now
+        assert!(!queue.start());
+    }
+
+    #[test]
+    fn one_task_at_a_time() {
+        let mut queue = LifecycleQueue::default();
+        assert!(!queue.start());
+
+        assert!(queue.push(LifecycleEvent::PaneCreated(1)));
+        // The task started above is still emitting
+        assert!(!queue.push(LifecycleEvent::PaneRemoved(1)));
+        assert_eq!(queue.pop(), Some(LifecycleEvent::PaneCreated(1)));
+        assert_eq!(queue.pop(), Some(LifecycleEvent::PaneRemoved(1)));
+        assert_eq!(queue.pop(), None);
+
+        // Once it has finished, a new one is needed
+        assert!(queue.push(LifecycleEvent::PaneCreated(2)));
+    }
+
+    #[test]
+    fn moving_a_tab_does_not_create_it() {
+        let mut queue = LifecycleQueue::default();
+        queue.start();
+        assert!(queue.push(LifecycleEvent::TabCreated {
+            tab_id: 3,
+            window_id: 0
+        }));
+        assert!(!queue.push(LifecycleEvent::TabCreated {
+            tab_id: 3,
+            window_id: 1
+        }));
+        assert_eq!(drain(&mut queue).len(), 1);
+    }
+}
@@ -12,6 +12,8 @@ use config::keyassignment::{SpawnCommand, SpawnTabDomain};
 use config::{ConfigHandle, SerialDomain, SshDomain, SshMultiplexing};
 use mux::activity::Activity;
 use mux::domain::{Domain, LocalDomain};
+use mux::tab::Tab;
+use mux::window::WindowId as MuxWindowId;
 use mux::Mux;
 use mux_lua::MuxDomain;
 use portable_pty::cmdbuilder::CommandBuilder;
@@ -38,9 +40,12 @@ mod colorease;
 mod commands;
 mod customglyph;
 mod download;
+mod dropdown;
 mod frontend;
 mod glyphcache;
 mod inputmap;
+mod lifecycle;
+mod nerdicons;
 mod overlay;
 mod quad;
 mod renderstate;
@@ -126,6 +131,12 @@ enum SubCommand {
     #[command(name = "connect", about = "Connect to wezterm multiplexer")]
     Connect(ConnectCommand),
 
+    #[command(
+        name = "restore",
+        about = "Start the GUI and restore a previously saved session"
+    )]
+    Restore(RestoreCommand),
+
     #[command(name = "ls-fonts", about = "Display information about fonts")]
     LsFonts(LsFontsCommand),
 
@@ -206,6 +217,8 @@ async fn async_run_serial(opts: SerialCommand) -> anyhow::Result<()> {
         name: format!("Serial Port {}", opts.port),
         port: Some(opts.port.clone()),
         baud: opts.baud,
+        reconnect: opts.reconnect,
+        reconnect_interval_ms: opts.reconnect_interval_ms,
     };
 
     let start_command = StartCommand {
@@ -286,10 +299,20 @@ async fn spawn_tab_in_domain_if_mux_is_empty(
     is_connecting: bool,
     domain: Option<Arc<dyn Domain>>,
     workspace: Option<String>,
+    profile: Option<String>,
 ) -> anyhow::Result<()> {
     let mux = Mux::get();
 
-    let domain = domain.unwrap_or_else(|| mux.default_domain());
+    let profile_domain = profile
+        .as_deref()
+        .and_then(|name| config::configuration().profile_default_domain(name));
+    let domain = match (domain, profile_domain) {
+        (Some(domain), _) => domain,
+        (None, Some(name)) => mux
+            .get_domain_by_name(&name)
+            .ok_or_else(|| anyhow!("invalid domain {name} in profile"))?,
+        (None, None) => mux.default_domain(),
+    };
 
     if !is_connecting {
         if have_panes_in_domain_and_ws(&domain, &workspace) {
@@ -307,6 +330,7 @@ async fn spawn_tab_in_domain_if_mux_is_empty(
         // to detect and adjust the size later on.
         let position = None;
         let builder = mux.new_empty_window(workspace.clone(), position);
+        set_window_profile(*builder, &profile);
         *builder
     };
 
@@ -331,7 +355,8 @@ async fn spawn_tab_in_domain_if_mux_is_empty(
     });
 
     let dpi = config.dpi.unwrap_or_else(|| ::window::default_dpi());
-    let _tab = domain
+    let spawn_command = with_profile_spawn_command(&profile);
+    let tab = domain
         .spawn(
             config.initial_size(dpi as u32, Some(cell_pixel_dims(&config, dpi)?)),
             cmd,
@@ -339,10 +364,43 @@ async fn spawn_tab_in_domain_if_mux_is_empty(
             window_id,
         )
         .await?;
+    drop(spawn_command);
+    set_tab_profile(&tab, &profile)?;
     trigger_and_log_gui_attached(MuxDomain(domain.domain_id())).await;
     Ok(())
 }
 
+/// Arranges for the command that is spawned next to be built with the
+/// config of `profile`, as selected by `wezterm start --profile`
+fn with_profile_spawn_command(profile: &Option<String>) -> Option<mux::SpawnCommandHolder> {
+    profile.as_ref().map(|name| {
+        Mux::get().with_spawn_command(SpawnCommand {
+            profile: Some(name.clone()),
+            ..SpawnCommand::default()
+        })
+    })
+}
+
+/// Applies `profile`, as selected by `wezterm start --profile`,
+/// to the whole of the window `window_id`
+fn set_window_profile(window_id: MuxWindowId, profile: &Option<String>) {
+    if let Some(mut window) = Mux::get().get_window_mut(window_id) {
+        window.set_profile(profile.clone());
+    }
+}
+
+/// Applies `profile`, as selected by `wezterm start --profile`,
+/// to the panes of `tab`
+fn set_tab_profile(tab: &Arc<Tab>, profile: &Option<String>) -> anyhow::Result<()> {
+    if let Some(name) = profile {
+        let mux = Mux::get();
+        for pos in tab.iter_panes_ignoring_zoom() {
+            mux.set_pane_profile(&pos.pane, name)?;
+        }
+    }
+    Ok(())
+}
+
 async fn connect_to_auto_connect_domains() -> anyhow::Result<()> {
     let mux = Mux::get();
     let domains = mux.iter_domains();
@@ -410,8 +468,7 @@ async fn async_run_terminal_gui(
     opts: StartCommand,
     should_publish: bool,
 ) -> anyhow::Result<()> {
-    let unix_socket_path =
-        config::RUNTIME_DIR.join(format!("gui-sock-{}", unsafe { libc::getpid() }));
+    let unix_socket_path = wezterm_client::discovery::gui_sock_path();
     std::env::set_var("WEZTERM_UNIX_SOCKET", unix_socket_path.clone());
     wezterm_blob_leases::register_storage(Arc::new(
         wezterm_blob_leases::simple_tempdir::SimpleTempDir::new()?,
@@ -420,6 +477,10 @@ async fn async_run_terminal_gui(
     if let Err(err) = spawn_mux_server(unix_socket_path, should_publish) {
         log::warn!("{:#}", err);
     }
+    mux::idle::start_idle_pane_monitor();
+    mux::monitor::start_output_monitor();
+    mux::power::start_power_monitor();
+    mux::session::start_session_tracker();
 
     if !opts.no_auto_connect {
         connect_to_auto_connect_domains().await?;
@@ -453,9 +514,19 @@ async fn async_run_terminal_gui(
         None
     };
 
-    if !opts.attach {
+    if let Some(path) = &opts.restore_session {
+        // If nothing could be restored, the usual initial tab
+        // is spawned below
+        if let Err(err) = restore_session(path).await {
+            log::error!("{err:#}");
+        }
+    } else if !opts.attach {
         trigger_and_log_gui_startup(spawn_command).await;
     }
+    // `wezterm start`, `wezterm ssh` and `wezterm serial` all come
+    // through here, so this releases the queued lifecycle events for
+    // each of them once gui-startup has run.
+    crate::lifecycle::gui_started();
 
     let is_connecting = opts.attach;
 
@@ -467,12 +538,14 @@ async fn async_run_terminal_gui(
                 let workspace = None;
                 let position = None;
                 let builder = mux.new_empty_window(workspace, position);
+                set_window_profile(*builder, &opts.profile);
                 *builder
             };
 
             domain.attach(Some(window_id)).await?;
             let config = config::configuration();
             let dpi = config.dpi.unwrap_or_else(|| ::window::default_dpi());
+            let spawn_command = with_profile_spawn_command(&opts.profile);
             let tab = domain
                 .spawn(
                     config.initial_size(dpi as u32, Some(cell_pixel_dims(&config, dpi)?)),
@@ -481,6 +554,8 @@ async fn async_run_terminal_gui(
                     window_id,
                 )
                 .await?;
+            drop(spawn_command);
+            set_tab_profile(&tab, &opts.profile)?;
             let mut window = mux
                 .get_window_mut(window_id)
                 .ok_or_else(|| anyhow!("failed to get mux window id {window_id}"))?;
@@ -490,7 +565,19 @@ async fn async_run_terminal_gui(
             trigger_and_log_gui_attached(MuxDomain(domain.domain_id())).await;
         }
     }
-    spawn_tab_in_domain_if_mux_is_empty(cmd, is_connecting, domain, opts.workspace).await
+    spawn_tab_in_domain_if_mux_is_empty(cmd, is_connecting, domain, opts.workspace, opts.profile)
+        .await
+}
+
+async fn restore_session(path: &std::path::Path) -> anyhow::Result<()> {
+    let session = mux::session::SessionState::load(path)?;
+    let window_ids = session.restore().await?;
+    log::info!(
+        "restored {} windows from {}",
+        window_ids.len(),
+        path.display()
+    );
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -540,6 +627,7 @@ impl Publish {
         workspace: Option<&str>,
         domain: SpawnTabDomain,
         new_tab: bool,
+        profile: Option<String>,
     ) -> anyhow::Result<bool> {
         if let Publish::TryPathOrPublish(gui_sock) = &self {
             let dom = config::UnixDomain {
@@ -613,6 +701,7 @@ impl Publish {
                                         .as_deref()
                                         .unwrap_or(mux::DEFAULT_WORKSPACE)
                                 ).to_string(),
+                                profile,
                             })
                             .await
                     }));
@@ -719,6 +808,8 @@ fn build_initial_mux(
 }
 
 fn run_terminal_gui(opts: StartCommand, default_domain_name: Option<String>) -> anyhow::Result<()> {
+    mux::nesting::detect_nesting();
+
     if let Some(cls) = opts.class.as_ref() {
         crate::set_window_class(cls);
     }
@@ -754,13 +845,17 @@ fn run_terminal_gui(opts: StartCommand, default_domain_name: Option<String>) ->
         opts.workspace.as_deref(),
     )?;
 
+    if let Some(name) = &opts.profile {
+        config.profile(name)?;
+    }
+
     // First, let's see if we can ask an already running wezterm to do this.
     // We must do this before we start the gui frontend as the scheduler
     // requirements are different.
     let mut publish = Publish::resolve(
         &mux,
         &config,
-        opts.always_new_process || opts.position.is_some(),
+        opts.always_new_process || opts.position.is_some() || opts.restore_session.is_some(),
     );
     log::trace!("{:?}", publish);
     if publish.try_spawn(
@@ -772,6 +867,7 @@ fn run_terminal_gui(opts: StartCommand, default_domain_name: Option<String>) ->
             None => SpawnTabDomain::DefaultDomain,
         },
         opts.new_tab,
+        opts.profile.clone(),
     )? {
         return Ok(());
     }
@@ -788,7 +884,12 @@ fn run_terminal_gui(opts: StartCommand, default_domain_name: Option<String>) ->
     .detach();
 
     maybe_show_configuration_error_window();
-    gui.run_forever()
+    let res = gui.run_forever();
+    mux::session::stop_session_tracker();
+    if let Err(err) = mux::session::save_session_on_exit() {
+        log::error!("save_session_on_exit: {err:#}");
+    }
+    res
 }
 
 fn fatal_toast_notification(title: &str, message: &str) {
@@ -850,6 +951,9 @@ fn maybe_show_configuration_error_window() {
 }
 
 fn run_show_keys(config: config::ConfigHandle, cmd: &ShowKeysCommand) -> anyhow::Result<()> {
+    if let Some(key) = &cmd.encode {
+        return show_key_encoding(&config, key, cmd);
+    }
     let map = crate::inputmap::InputMap::new(&config);
     if cmd.lua {
         map.dump_config(cmd.key_table.as_deref());
@@ -859,6 +963,106 @@ fn run_show_keys(config: config::ConfigHandle, cmd: &ShowKeysCommand) -> anyhow:
     Ok(())
 }
 
+fn show_key_encoding(
+    config: &config::ConfigHandle,
+    key: &str,
+    cmd: &ShowKeysCommand,
+) -> anyhow::Result<()> {
+    let key = config::DeferredKeyCode::try_from(key)?.resolve(config.key_map_preference);
+    let modifiers = Modifiers::try_from(cmd.mods.clone()).map_err(|err| anyhow!("{err}"))?;
+    let flags = KittyKeyboardFlags::from_bits(cmd.kitty_flags)
+        .ok_or_else(|| anyhow!("{} is not a valid set of kitty flags", cmd.kitty_flags))?;
+
+    let event = KeyEvent {
+        key,
+        modifiers,
+        leds: KeyboardLedStatus::empty(),
+        repeat_count: if cmd.repeat { 2 } else { 1 },
+        key_is_down: !cmd.release,
+        raw: None,
+        #[cfg(windows)]
+        win32_uni_char: None,
+    };
+
+    let encoded = event.encode_kitty(flags);
+    if encoded.is_empty() {
+        println!("(nothing is sent)");
+        return Ok(());
+    }
+
+    let mut escaped = String::new();
+    for c in encoded.chars() {
+        if c.is_control() {
+            escaped.push_str(&format!("\\x{:02x}", c as u32));
+        } else {
+            escaped.push(c);
+        }
+    }
+    println!("{escaped}");
+    Ok(())
+}
+
+/// Renders a grid of sample icons, followed by a table that explains
+/// how each of them is sized, to help with tuning nerd_font_scale
+fn show_nerd_font_icons(
+    config: &config::ConfigHandle,
+    font_config: &Rc<wezterm_font::FontConfiguration>,
+    render_metrics: &crate::utilsprites::RenderMetrics,
+) -> anyhow::Result<()> {
+    let style = font_config.match_style(config, &CellAttributes::default());
+    let font = font_config.resolve_font(style)?;
+    let cell_width = render_metrics.cell_size.width as f64;
+    let cell_height = render_metrics.cell_size.height as f64;
+    let nerd_font_scale = config.nerd_font_scale.unwrap_or(1.0);
+
+    match config.nerd_font_scale {
+        Some(scale) => println!("nerd_font_scale = {scale}"),
+        None => println!(
+            "nerd_font_scale is not set, so icons are shown at their natural size. \
+             The scale shown below is what nerd_font_scale = 1.0 would use."
+        ),
+    }
+    println!();
+
+    for row in nerdicons::SAMPLE_ICONS.chunks(8) {
+        let icons: Vec<String> = row.iter().map(|(c, _)| format!("{c} ")).collect();
+        println!("{}", icons.join(" "));
+    }
+    println!();
+
+    for (c, name) in nerdicons::SAMPLE_ICONS {
+        let text = c.to_string();
+        let infos = font.blocking_shape(&text, None, Direction::LeftToRight, None, None)?;
+        // We must grab the handles after shaping, so that we get the
+        // revised list that includes system fallbacks!
+        let handles = font.clone_handles();
+        for info in &infos {
+            let glyph = font.rasterize_glyph(info.glyph_pos, info.font_idx)?;
+            let scale = if glyph.width == 0 || glyph.height == 0 {
+                "-".to_string()
+            } else {
+                let scale = nerdicons::icon_scale(
+                    glyph.width as f64,
+                    glyph.height as f64,
+                    cell_width * info.num_cells.max(1) as f64,
+                    cell_height,
+                    nerd_font_scale,
+                );
+                format!("{scale:.2}")
+            };
+            println!(
+                "U+{:05X} {c}  {name:18} {}x{}px scale={scale:5} {}",
+                *c as u32,
+                glyph.width,
+                glyph.height,
+                handles[info.font_idx].lua_name(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
 pub fn run_ls_fonts(config: config::ConfigHandle, cmd: &LsFontsCommand) -> anyhow::Result<()> {
     use wezterm_font::parser::ParsedFont;
 
@@ -889,6 +1093,10 @@ pub fn run_ls_fonts(config: config::ConfigHandle, cmd: &LsFontsCommand) -> anyho
         ambiguous_are_wide: config.treat_east_asian_ambiguous_width_as_wide,
     };
 
+    if cmd.icons {
+        return show_nerd_font_icons(&config, &font_config, &render_metrics);
+    }
+
     let text = match (&cmd.text, &cmd.codepoints) {
         (Some(text), _) => Some(text.to_string()),
         (_, Some(codepoints)) => {
@@ -1273,9 +1481,23 @@ fn run() -> anyhow::Result<()> {
                 _cmd: false,
                 no_auto_connect: false,
                 cwd: None,
+                restore_session: None,
+                profile: None,
             },
             Some(connect.domain_name),
         ),
+        SubCommand::Restore(restore) => run_terminal_gui(
+            StartCommand {
+                always_new_process: true,
+                restore_session: Some(
+                    restore
+                        .file
+                        .unwrap_or_else(mux::session::default_session_path),
+                ),
+                ..Default::default()
+            },
+            None,
+        ),
         SubCommand::LsFonts(cmd) => run_ls_fonts(config, &cmd),
         SubCommand::ShowKeys(cmd) => run_show_keys(config, &cmd),
     }
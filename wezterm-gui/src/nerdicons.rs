@@ -0,0 +1,113 @@
+//! Sizing of the Nerd Font icons that live in the unicode private use
+//! areas. Icon fonts are designed around their own em box, which
+//! means that their icons can appear tiny, or be clipped, when they
+//! are used as a fallback for the primary font.
+//! This module computes the adjustments that are enabled by the
+//! `nerd_font_scale` option.
+
+/// Some well known icons, used by `wezterm ls-fonts --icons`
+pub const SAMPLE_ICONS: &[(char, &str)] = &[
+    ('\u{e0a0}', "pl-branch"),
+    ('\u{e0a2}', "pl-line_number"),
+    ('\u{e615}', "seti-config"),
+    ('\u{e718}', "dev-nodejs_small"),
+    ('\u{e795}', "dev-terminal"),
+    ('\u{e7a8}', "dev-rust"),
+    ('\u{f00c}', "fa-check"),
+    ('\u{f00d}', "fa-close"),
+    ('\u{f015}', "fa-home"),
+    ('\u{f017}', "fa-clock_o"),
+    ('\u{f07b}', "fa-folder"),
+    ('\u{f0e7}', "fa-bolt"),
+    ('\u{f0f3}', "fa-bell"),
+    ('\u{f113}', "fa-github_alt"),
+    ('\u{f120}', "fa-terminal"),
+    ('\u{f121}', "fa-code"),
+    ('\u{f179}', "fa-apple"),
+    ('\u{f17c}', "fa-linux"),
+    ('\u{f1eb}', "fa-wifi"),
+    ('\u{f240}', "fa-battery_full"),
+    ('\u{f489}', "oct-terminal"),
+    ('\u{f024b}', "md-folder"),
+    ('\u{f02a2}', "md-git"),
+];
+
+/// Returns true if `c` is in one of the private use areas that Nerd
+/// Fonts places its icons in.
+/// The powerline separators are excluded; they are designed to fill
+/// the cell, and adjusting them leaves gaps in prompts and status lines.
+pub fn is_nerd_font_icon(c: char) -> bool {
+    match c as u32 {
+        0xe0b0..=0xe0d7 => false,
+        0xe000..=0xf8ff | 0xf0000..=0xffffd => true,
+        _ => false,
+    }
+}
+
+/// Computes the scale to apply to an icon that is `width` x `height`
+/// pixels at its natural size. The icon is scaled by `nerd_font_scale`,
+/// but is shrunk further if that would make it larger than the box of
+/// `max_width` x `cell_height` pixels that is available to it.
+pub fn icon_scale(
+    width: f64,
+    height: f64,
+    max_width: f64,
+    cell_height: f64,
+    nerd_font_scale: f64,
+) -> f64 {
+    let fit = (max_width / width).min(cell_height / height);
+    nerd_font_scale.min(fit)
+}
+
+/// Returns the bearing_y that vertically centers a glyph that is
+/// `height` pixels tall within a cell, if its natural position,
+/// given by `bearing_y`, would place any part of it outside the cell.
+/// `descender` is the (typically negative) descender of the primary font.
+pub fn centered_bearing_y(
+    bearing_y: f64,
+    height: f64,
+    cell_height: f64,
+    descender: f64,
+) -> Option<f64> {
+    // Glyphs are positioned with their top edge at bearing_y
+    // above the baseline
+    let baseline = cell_height + descender;
+    let top = baseline - bearing_y;
+    let bottom = top + height;
+    if top < 0. || bottom > cell_height {
+        Some(baseline - (cell_height - height) / 2.)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn icon_ranges() {
+        assert!(is_nerd_font_icon('\u{f015}'));
+        assert!(is_nerd_font_icon('\u{f024b}'));
+        assert!(!is_nerd_font_icon('\u{e0b0}'));
+        assert!(!is_nerd_font_icon('a'));
+        assert!(!is_nerd_font_icon('\u{1f600}'));
+    }
+
+    #[test]
+    fn scale_and_center() {
+        // An icon that is already a good fit is scaled as requested
+        assert_eq!(icon_scale(8., 16., 10., 20., 1.0), 1.0);
+        assert_eq!(icon_scale(8., 16., 10., 20., 0.5), 0.5);
+        // but is never allowed to grow out of the cell
+        assert_eq!(icon_scale(8., 16., 10., 20., 2.0), 1.25);
+        assert_eq!(icon_scale(20., 10., 10., 20., 1.0), 0.5);
+
+        // A glyph that sits within the cell is left alone
+        assert_eq!(centered_bearing_y(14., 16., 20., -4.), None);
+        // one that pokes above the cell is centered
+        assert_eq!(centered_bearing_y(18., 16., 20., -4.), Some(14.));
+        assert_eq!(centered_bearing_y(4., 8., 20., -4.), None);
+        assert_eq!(centered_bearing_y(0., 8., 20., -4.), Some(10.));
+    }
+}
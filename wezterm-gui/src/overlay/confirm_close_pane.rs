@@ -1,16 +1,50 @@
 use crate::TermWindow;
-use mux::pane::PaneId;
+use mux::pane::{Pane, PaneId};
 use mux::tab::TabId;
 use mux::termwiztermtab::TermWizTerminal;
 use mux::window::WindowId;
 use mux::Mux;
+use std::sync::Arc;
 use termwiz::cell::AttributeChange;
 use termwiz::color::ColorAttribute;
 use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
 use termwiz::surface::{Change, CursorVisibility, Position};
 use termwiz::terminal::Terminal;
+use wezterm_term::unicode_column_width;
 
-fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Result<bool> {
+/// A button that is shown by `run_choice_app`
+pub(crate) struct Choice {
+    /// Pressing this key, in either case, picks the choice
+    pub key: char,
+    pub label: &'static str,
+}
+
+pub(crate) fn run_confirmation_app(
+    message: &str,
+    term: &mut TermWizTerminal,
+) -> anyhow::Result<bool> {
+    let choices = [
+        Choice {
+            key: 'y',
+            label: " [Y]es ",
+        },
+        Choice {
+            key: 'n',
+            label: " [N]o ",
+        },
+    ];
+    Ok(run_choice_app(message, &choices, term)? == Some(0))
+}
+
+/// Shows `message` above a row of buttons and waits for one of them
+/// to be picked, returning its index.
+/// Returns None if the prompt is dismissed with Escape or with a
+/// click outside of the buttons.
+pub(crate) fn run_choice_app(
+    message: &str,
+    choices: &[Choice],
+    term: &mut TermWizTerminal,
+) -> anyhow::Result<Option<usize>> {
     term.set_raw_mode()?;
 
     let size = term.get_screen_size()?;
@@ -42,25 +76,21 @@ fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Re
     // Now we want to vertically center the prompt in the view.
     // After the prompt there will be a blank line and then the "buttons",
     // so we add two to the number of rows.
-    let top_row = (size.rows - (message_rows + 2)) / 2;
+    let top_row = size.rows.saturating_sub(message_rows + 2) / 2;
 
     let button_row = top_row + message_rows + 1;
-    let mut active = ActiveButton::None;
-
-    let yes_x = x_pos;
-    let yes_w = 7;
-
-    let no_x =  yes_x + yes_w + 8 /* spacer */;
-    let no_w = 6;
+    let mut active = None;
 
-    #[derive(Copy, Clone, PartialEq, Eq)]
-    enum ActiveButton {
-        None,
-        Yes,
-        No,
+    const SPACER: &str = "        ";
+    let mut buttons = vec![];
+    let mut x = x_pos;
+    for choice in choices {
+        let width = unicode_column_width(choice.label, None);
+        buttons.push(x..x + width);
+        x += width + SPACER.len();
     }
 
-    let render = |term: &mut TermWizTerminal, active: ActiveButton| -> termwiz::Result<()> {
+    let render = |term: &mut TermWizTerminal, active: Option<usize>| -> termwiz::Result<()> {
         let mut changes = vec![
             Change::ClearScreen(ColorAttribute::Default),
             Change::CursorVisibility(CursorVisibility::Hidden),
@@ -89,22 +119,17 @@ fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Re
             y: Position::Absolute(button_row),
         });
 
-        if active == ActiveButton::Yes {
-            changes.push(AttributeChange::Reverse(true).into());
-        }
-        changes.push(" [Y]es ".into());
-        if active == ActiveButton::Yes {
-            changes.push(AttributeChange::Reverse(false).into());
-        }
-
-        changes.push("        ".into());
-
-        if active == ActiveButton::No {
-            changes.push(AttributeChange::Reverse(true).into());
-        }
-        changes.push(" [N]o ".into());
-        if active == ActiveButton::No {
-            changes.push(AttributeChange::Reverse(false).into());
+        for (idx, choice) in choices.iter().enumerate() {
+            if idx > 0 {
+                changes.push(SPACER.into());
+            }
+            if active == Some(idx) {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            changes.push(choice.label.into());
+            if active == Some(idx) {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
         }
 
         term.render(&changes)?;
@@ -116,20 +141,21 @@ fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Re
     while let Ok(Some(event)) = term.poll_input(None) {
         match event {
             InputEvent::Key(KeyEvent {
-                key: KeyCode::Char('y' | 'Y'),
+                key: KeyCode::Char(c),
                 ..
             }) => {
-                return Ok(true);
+                if let Some(idx) = choices
+                    .iter()
+                    .position(|choice| choice.key.eq_ignore_ascii_case(&c))
+                {
+                    return Ok(Some(idx));
+                }
             }
             InputEvent::Key(KeyEvent {
-                key: KeyCode::Char('n' | 'N'),
-                ..
-            })
-            | InputEvent::Key(KeyEvent {
                 key: KeyCode::Escape,
                 ..
             }) => {
-                return Ok(false);
+                return Ok(None);
             }
             InputEvent::Mouse(MouseEvent {
                 x,
@@ -139,23 +165,18 @@ fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Re
             }) => {
                 let x = x as usize;
                 let y = y as usize;
-                if y == button_row && x >= yes_x && x < yes_x + yes_w {
-                    active = ActiveButton::Yes;
-                    if mouse_buttons == MouseButtons::LEFT {
-                        return Ok(true);
-                    }
-                } else if y == button_row && x >= no_x && x < no_x + no_w {
-                    active = ActiveButton::No;
-                    if mouse_buttons == MouseButtons::LEFT {
-                        return Ok(false);
-                    }
+                active = if y == button_row {
+                    buttons.iter().position(|button| button.contains(&x))
                 } else {
-                    active = ActiveButton::None;
-                }
+                    None
+                };
 
+                if active.is_some() && mouse_buttons == MouseButtons::LEFT {
+                    return Ok(active);
+                }
                 if mouse_buttons != MouseButtons::NONE {
                     // Treat any other mouse button as cancel
-                    return Ok(false);
+                    return Ok(None);
                 }
             }
             _ => {}
@@ -164,23 +185,38 @@ fn run_confirmation_app(message: &str, term: &mut TermWizTerminal) -> anyhow::Re
         render(term, active)?;
     }
 
-    Ok(false)
+    Ok(None)
 }
 
 pub fn confirm_close_pane(
     pane_id: PaneId,
+    linked: Vec<Arc<dyn Pane>>,
     mut term: TermWizTerminal,
     mux_window_id: WindowId,
     window: ::window::Window,
 ) -> anyhow::Result<()> {
-    if run_confirmation_app("🛑 Really kill this pane?", &mut term)? {
+    let message = if linked.is_empty() {
+        "🛑 Really kill this pane?".to_string()
+    } else {
+        let mut message =
+            "🛑 Really kill this pane and the panes that are linked with it?\n".to_string();
+        for pane in &linked {
+            message.push_str(&format!("\n  {}: {}", pane.pane_id(), pane.get_title()));
+        }
+        message
+    };
+    if run_confirmation_app(&message, &mut term)? {
         promise::spawn::spawn_into_main_thread(async move {
             let mux = Mux::get();
             let tab = match mux.get_active_tab_for_window(mux_window_id) {
                 Some(tab) => tab,
                 None => return,
             };
+            mux.retain_closed_pane(pane_id);
             tab.kill_pane(pane_id);
+            for pane in linked {
+                mux.remove_pane(pane.pane_id());
+            }
         })
         .detach();
     }
@@ -201,6 +237,7 @@ pub fn confirm_close_tab(
     )? {
         promise::spawn::spawn_into_main_thread(async move {
             let mux = Mux::get();
+            mux.retain_closed_tab(tab_id);
             mux.remove_tab(tab_id);
         })
         .detach();
@@ -0,0 +1,132 @@
+//! Asks before text is pasted into a pane whose input is broadcast
+//! to other panes, so that a paste doesn't reach more shells than
+//! intended; see `broadcast_paste_confirmation`.
+use crate::overlay::confirm_close_pane::{run_choice_app, Choice};
+use mux::pane::Pane;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::sync::Arc;
+
+/// The most target panes that are listed by name
+const MAX_LISTED_PANES: usize = 8;
+
+/// The most characters of the first line of the paste that are shown
+const MAX_PREVIEW_CHARS: usize = 60;
+
+/// Returns the first line of `text`, shortened and with control
+/// characters made visible, along with the number of lines in `text`
+fn paste_preview(text: &str) -> (String, usize) {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    let text = text.strip_suffix('\r').unwrap_or(text);
+    let line_count = text.split('\n').count();
+    let first = text.split('\n').next().unwrap_or("");
+    let first = first.strip_suffix('\r').unwrap_or(first);
+
+    let mut preview: String = first
+        .chars()
+        .take(MAX_PREVIEW_CHARS)
+        .map(|c| if c.is_control() { '\u{fffd}' } else { c })
+        .collect();
+    if first.chars().count() > MAX_PREVIEW_CHARS {
+        preview.push('…');
+    }
+    (preview, line_count)
+}
+
+fn describe_pane(pane: &Arc<dyn Pane>) -> String {
+    let domain = Mux::get()
+        .get_domain(pane.domain_id())
+        .map(|domain| domain.domain_name().to_string())
+        .unwrap_or_default();
+    format!("{}: {} ({})", pane.pane_id(), pane.get_title(), domain)
+}
+
+/// Returns the message of the confirmation for pasting `text` into
+/// `pane` and `targets`
+pub fn broadcast_paste_message(
+    pane: &Arc<dyn Pane>,
+    targets: &[Arc<dyn Pane>],
+    text: &str,
+) -> String {
+    let mut message = format!(
+        "⚠️ Really paste into {} panes that are receiving broadcast input?\n",
+        targets.len() + 1
+    );
+    message.push_str(&format!("\n  {} (focused)", describe_pane(pane)));
+    for target in targets.iter().take(MAX_LISTED_PANES - 1) {
+        message.push_str(&format!("\n  {}", describe_pane(target)));
+    }
+    if targets.len() >= MAX_LISTED_PANES {
+        message.push_str(&format!(
+            "\n  and {} more",
+            targets.len() + 1 - MAX_LISTED_PANES
+        ));
+    }
+
+    let (preview, line_count) = paste_preview(text);
+    if line_count > 1 {
+        message.push_str(&format!(
+            "\n\nThe first of {line_count} lines to be pasted:\n  {preview}"
+        ));
+    } else {
+        message.push_str(&format!("\n\nThe text to be pasted:\n  {preview}"));
+    }
+    message
+}
+
+pub fn confirm_broadcast_paste(
+    message: String,
+    pane: Arc<dyn Pane>,
+    targets: Vec<Arc<dyn Pane>>,
+    text: String,
+    mut term: TermWizTerminal,
+) -> anyhow::Result<()> {
+    let choices = [
+        Choice {
+            key: 'a',
+            label: " [A]ll panes ",
+        },
+        Choice {
+            key: 'f',
+            label: " [F]ocused pane only ",
+        },
+        Choice {
+            key: 'c',
+            label: " [C]ancel ",
+        },
+    ];
+    let broadcast = match run_choice_app(&message, &choices, &mut term)? {
+        Some(0) => true,
+        Some(1) => false,
+        _ => return Ok(()),
+    };
+
+    promise::spawn::spawn_into_main_thread(async move {
+        pane.send_paste(&text).ok();
+        if broadcast {
+            for target in targets {
+                target.send_paste(&text).ok();
+            }
+        }
+    })
+    .detach();
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn preview() {
+        assert_eq!(paste_preview("ls -l\n"), ("ls -l".to_string(), 1));
+        assert_eq!(
+            paste_preview("cd /tmp\r\nrm -rf *\r\n"),
+            ("cd /tmp".to_string(), 2)
+        );
+        assert_eq!(paste_preview("a\tb"), ("a\u{fffd}b".to_string(), 1));
+
+        let (preview, _) = paste_preview(&"x".repeat(MAX_PREVIEW_CHARS + 1));
+        assert_eq!(preview, format!("{}…", "x".repeat(MAX_PREVIEW_CHARS)));
+    }
+}
@@ -3,8 +3,9 @@ use crate::termwindow::keyevent::KeyTableArgs;
 use crate::termwindow::{TermWindow, TermWindowNotif};
 use config::keyassignment::{
     ClipboardCopyDestination, CopyModeAssignment, KeyAssignment, KeyTable, KeyTableEntry,
-    ScrollbackEraseMode, SelectionMode,
+    ScrollbackEraseMode, SelectionMode, TextObject,
 };
+use mux::annotation::{Annotation, PaneAnnotations, DEFAULT_ANNOTATION_KIND};
 use mux::domain::DomainId;
 use mux::pane::{
     CachePolicy, ForEachPaneLogicalLine, LogicalLine, Pane, PaneId, Pattern, PatternType,
@@ -22,9 +23,11 @@ use std::time::Duration;
 use termwiz::cell::{Cell, CellAttributes};
 use termwiz::color::AnsiColor;
 use termwiz::lineedit::{LineEditBuffer, Movement};
+use termwiz::surface::line::CellRef;
 use termwiz::surface::{CursorVisibility, SequenceNo, SEQ_ZERO};
 use unicode_segmentation::*;
 use url::Url;
+use wezterm_client::pane::ClientPane;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{
     unicode_column_width, Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, SemanticType,
@@ -85,6 +88,9 @@ struct CopyRenderable {
     searching: Option<Searching>,
     pending_jump: Option<PendingJump>,
     last_jump: Option<Jump>,
+    /// Set to the `around` flag of a SelectTextObject that
+    /// is waiting for the next key press to specify the object
+    pending_text_object: Option<bool>,
 }
 
 struct Searching {
@@ -162,6 +168,7 @@ impl CopyOverlay {
             searching: None,
             pending_jump: None,
             last_jump: None,
+            pending_text_object: None,
         };
 
         let search_row = render.compute_search_row();
@@ -202,6 +209,12 @@ impl CopyOverlay {
         render.dirty_results.add(search_row);
     }
 
+    /// Returns the rows that contain search matches, along with
+    /// whether the row holds the current match
+    pub fn match_rows(&self) -> Vec<(StableRowIndex, bool)> {
+        self.render.lock().match_rows()
+    }
+
     pub fn viewport_changed(&self, viewport: Option<StableRowIndex>) {
         let mut render = self.render.lock();
         if render.viewport != viewport {
@@ -641,6 +654,48 @@ impl CopyRenderable {
         }
     }
 
+    /// Returns the text shown in the search bar, including the
+    /// position of the current match and the total number of matches
+    fn search_bar_text(&self, pattern: &Pattern) -> String {
+        let mode = match pattern {
+            Pattern::CaseSensitiveString(_) => "case-sensitive",
+            Pattern::CaseInSensitiveString(_) => "ignore-case",
+            Pattern::Regex(_) => "regex",
+        };
+        let current = self.result_pos.map(|x| x + 1).unwrap_or(0);
+
+        let remain = match &self.searching {
+            Some(Searching { remain, .. }) => format!(" searching {remain} lines"),
+            None => String::new(),
+        };
+
+        // Each chunk of a remote pane is searched by the mux server,
+        // so the running total can lag far behind the true total;
+        // show it as unknown until the search has completed
+        let total =
+            if self.searching.is_some() && self.delegate.downcast_ref::<ClientPane>().is_some() {
+                "?".to_string()
+            } else {
+                self.results.len().to_string()
+            };
+
+        format!(
+            "Search: {} ({current}/{total} matches. {mode}{remain})",
+            **pattern
+        )
+    }
+
+    /// Returns the rows that contain matches, along with whether the
+    /// row holds the current match, so that they can be marked in the
+    /// scrollbar
+    fn match_rows(&self) -> Vec<(StableRowIndex, bool)> {
+        self.results
+            .iter()
+            .enumerate()
+            .map(|(idx, res)| (res.start_y, Some(idx) == self.result_pos))
+            .collect()
+    }
+
     fn get_pattern(&self) -> Pattern {
         let pattern = self.search_line.get_line().to_string();
         match self.pattern_type {
@@ -1099,6 +1154,106 @@ impl CopyRenderable {
         self.start.take();
         self.clear_selection();
     }
+
+    /// Adds an annotation to the line of the cursor, or
+    /// removes the annotations that it already has
+    fn toggle_annotation(&mut self) {
+        let annotations = match self.delegate.annotations() {
+            Some(annotations) => annotations,
+            None => return self.bell(),
+        };
+        let result = annotations.lock().toggle(Annotation {
+            id: 0,
+            line: self.cursor.y,
+            kind: DEFAULT_ANNOTATION_KIND.to_string(),
+            color: config::configuration().pane_annotation_color,
+            note: None,
+            tint: false,
+        });
+        match result {
+            Ok(_) => self.window.invalidate(),
+            Err(err) => {
+                log::error!("{:#}", err);
+                self.bell();
+            }
+        }
+    }
+
+    fn bell(&self) {
+        self.delegate
+            .perform_actions(vec![termwiz::escape::Action::Control(
+                termwiz::escape::ControlCode::Bell,
+            )]);
+    }
+
+    fn logical_line_containing(&self, y: StableRowIndex) -> Option<LogicalLine> {
+        self.delegate
+            .get_logical_lines(y..y + 1)
+            .into_iter()
+            .find(|logical| logical.contains_y(y))
+    }
+
+    /// Selects the inclusive range, placing the cursor at its end
+    fn select_range(&mut self, range: SelectionRange) {
+        self.selection_mode = SelectionMode::Cell;
+        self.start.replace(range.start);
+        self.cursor.y = range.end.y;
+        self.cursor.x = match range.end.x {
+            SelectionX::Cell(x) => x,
+            SelectionX::BeforeZero => 0,
+        };
+        self.select_to_cursor_pos();
+    }
+
+    fn select_text_object(&mut self, object: Option<TextObject>, around: bool) {
+        let cursor = SelectionCoordinate::x_y(self.cursor.x, self.cursor.y);
+        let range = match object {
+            None => {
+                self.pending_text_object.replace(around);
+                return;
+            }
+            Some(TextObject::SemanticZone) => {
+                Some(SelectionRange::zone_around(cursor, &*self.delegate))
+            }
+            Some(object) => self
+                .logical_line_containing(self.cursor.y)
+                .and_then(|logical| text_object_in_logical_line(&logical, cursor, object, around)),
+        };
+        match range {
+            Some(range) => self.select_range(range),
+            None => self.bell(),
+        }
+    }
+
+    fn expand_selection(&mut self) {
+        let current = match self.start {
+            Some(start) => SelectionRange {
+                start,
+                end: SelectionCoordinate::x_y(self.cursor.x, self.cursor.y),
+            }
+            .normalize(),
+            None => return self.select_text_object(Some(TextObject::Word), false),
+        };
+
+        if self.selection_mode != SelectionMode::Line && self.selection_mode != SelectionMode::Block
+        {
+            if let Some(logical) = self.logical_line_containing(current.start.y) {
+                if let Some(range) = expand_in_logical_line(&logical, current) {
+                    return self.select_range(range);
+                }
+            }
+        }
+
+        // Nothing encloses the selection within its line, so fall
+        // back to the semantic zone(s) around it
+        let zone = SelectionRange::zone_around(current.start, &*self.delegate)
+            .extend_with(SelectionRange::zone_around(current.end, &*self.delegate));
+        if range_encloses(&zone, &current) {
+            self.select_range(zone);
+        } else {
+            self.bell();
+        }
+    }
 }
 
 impl Pane for CopyOverlay {
@@ -1110,6 +1265,10 @@ impl Pane for CopyOverlay {
         format!("Copy mode: {}", self.delegate.get_title())
     }
 
+    fn annotations(&self) -> Option<Arc<Mutex<PaneAnnotations>>> {
+        self.delegate.annotations()
+    }
+
     fn send_paste(&self, text: &str) -> anyhow::Result<()> {
         // paste into the search bar
         let mut r = self.render.lock();
@@ -1162,6 +1321,18 @@ impl Pane for CopyOverlay {
             return Ok(());
         }
 
+        if let Some(around) = render.pending_text_object.take() {
+            match (key, mods) {
+                (KeyCode::Char(c), KeyModifiers::NONE)
+                | (KeyCode::Char(c), KeyModifiers::SHIFT) => match TextObject::from_char(c) {
+                    Some(object) => render.select_text_object(Some(object), around),
+                    None => render.bell(),
+                },
+                _ => render.bell(),
+            }
+            return Ok(());
+        }
+
         if render.editing_search {
             match (key, mods) {
                 (KeyCode::Char(c), KeyModifiers::NONE)
@@ -1242,7 +1413,7 @@ impl Pane for CopyOverlay {
     fn perform_assignment(&self, assignment: &KeyAssignment) -> PerformAssignmentResult {
         use CopyModeAssignment::*;
         let mut render = self.render.lock();
-        if render.pending_jump.is_some() {
+        if render.pending_jump.is_some() || render.pending_text_object.is_some() {
             // Block key assignments until key_down is called
             // and resolves the next state
             return PerformAssignmentResult::BlockAssignmentAndRouteToKeyDown;
@@ -1290,6 +1461,11 @@ impl Pane for CopyOverlay {
                     JumpBackward { prev_char } => render.jump(false, *prev_char),
                     JumpAgain => render.jump_again(false),
                     JumpReverse => render.jump_again(true),
+                    SelectTextObject { object, around } => {
+                        render.select_text_object(*object, *around)
+                    }
+                    ExpandSelection => render.expand_selection(),
+                    ToggleAnnotation => render.toggle_annotation(),
                 }
                 PerformAssignmentResult::Handled
             }
@@ -1338,6 +1514,18 @@ impl Pane for CopyOverlay {
         self.delegate.get_current_working_dir(policy)
     }
 
+    fn get_location(&self, policy: CachePolicy) -> Option<mux::location::PaneLocation> {
+        self.delegate.get_location(policy)
+    }
+
+    fn timestamp_gutter_cols(&self) -> usize {
+        self.delegate.timestamp_gutter_cols()
+    }
+
+    fn set_timestamp_gutter_cols(&self, cols: usize) {
+        self.delegate.set_timestamp_gutter_cols(cols)
+    }
+
     fn get_cursor_position(&self) -> StableCursorPosition {
         let renderer = self.render.lock();
         if renderer.editing_search {
@@ -1431,28 +1619,9 @@ impl Pane for CopyOverlay {
                         // Replace with search UI
                         let rev = CellAttributes::default().set_reverse(true).clone();
                         line.fill_range(0..self.dims.cols, &Cell::new(' ', rev.clone()), SEQ_ZERO);
-                        let mode = &match pattern {
-                            Pattern::CaseSensitiveString(_) => "case-sensitive",
-                            Pattern::CaseInSensitiveString(_) => "ignore-case",
-                            Pattern::Regex(_) => "regex",
-                        };
-
-                        let remain = match &self.renderer.searching {
-                            Some(Searching { remain, .. }) => {
-                                format!(" searching {remain} lines")
-                            }
-                            None => String::new(),
-                        };
-
                         line.overlay_text_with_attribute(
                             0,
-                            &format!(
-                                "Search: {} ({}/{} matches. {}{remain})",
-                                *pattern,
-                                self.renderer.result_pos.map(|x| x + 1).unwrap_or(0),
-                                self.renderer.results.len(),
-                                mode
-                            ),
+                            &self.renderer.search_bar_text(&pattern),
                             rev,
                             SEQ_ZERO,
                         );
@@ -1532,20 +1701,9 @@ impl Pane for CopyOverlay {
                 // Replace with search UI
                 let rev = CellAttributes::default().set_reverse(true).clone();
                 line.fill_range(0..dims.cols, &Cell::new(' ', rev.clone()), SEQ_ZERO);
-                let mode = &match pattern {
-                    Pattern::CaseSensitiveString(_) => "case-sensitive",
-                    Pattern::CaseInSensitiveString(_) => "ignore-case",
-                    Pattern::Regex(_) => "regex",
-                };
                 line.overlay_text_with_attribute(
                     0,
-                    &format!(
-                        "Search: {} ({}/{} matches. {})",
-                        *pattern,
-                        renderer.result_pos.map(|x| x + 1).unwrap_or(0),
-                        renderer.results.len(),
-                        mode
-                    ),
+                    &renderer.search_bar_text(&pattern),
                     rev,
                     SEQ_ZERO,
                 );
@@ -1625,6 +1783,280 @@ fn is_whitespace_word(word: &str) -> bool {
     }
 }
 
+/// Character classes that determine the extent of word text objects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+fn char_class(s: &str, big_word: bool) -> CharClass {
+    if s.is_empty() || is_whitespace_word(s) {
+        CharClass::Blank
+    } else if big_word || s.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// Returns the range of cells occupied by the run of cells with
+/// the same class as the cell at idx
+fn word_object(cells: &[CellRef], idx: usize, big_word: bool, around: bool) -> Range<usize> {
+    let class = |i: usize| char_class(cells[i].str(), big_word);
+    let run_start = |mut start: usize, c: CharClass| {
+        while start > 0 && class(start - 1) == c {
+            start -= 1;
+        }
+        start
+    };
+    let run_end = |mut end: usize, c: CharClass| {
+        while end < cells.len() && class(end) == c {
+            end += 1;
+        }
+        end
+    };
+
+    let c = class(idx);
+    let mut start = run_start(idx, c);
+    let mut end = run_end(idx + 1, c);
+
+    if around {
+        if c == CharClass::Blank {
+            // Blanks plus the word that follows them
+            if end < cells.len() {
+                end = run_end(end, class(end));
+            }
+        } else {
+            // The word plus trailing blanks, or leading
+            // blanks if there are no trailing blanks
+            let trailing = run_end(end, CharClass::Blank);
+            if trailing > end {
+                end = trailing;
+            } else {
+                start = run_start(start, CharClass::Blank);
+            }
+        }
+    }
+
+    start..end
+}
+
+/// Returns the quoted string around or, failing that, after idx.
+/// Quotes are paired up from the start of the line, skipping over
+/// those that are escaped with a backslash.
+fn quote_object(cells: &[CellRef], idx: usize, quote: &str, around: bool) -> Option<Range<usize>> {
+    let quotes: Vec<usize> = (0..cells.len())
+        .filter(|&i| cells[i].str() == quote && (i == 0 || cells[i - 1].str() != "\\"))
+        .collect();
+    let pairs = quotes.chunks_exact(2).map(|pair| (pair[0], pair[1]));
+    let (open, close) = pairs
+        .clone()
+        .find(|&(open, close)| open <= idx && idx <= close)
+        .or_else(|| pairs.clone().find(|&(open, _)| open > idx))?;
+
+    Some(if around {
+        open..close + 1
+    } else {
+        open + 1..close
+    })
+}
+
+/// Returns the innermost bracketed region around idx
+fn bracket_object(
+    cells: &[CellRef],
+    idx: usize,
+    open: &str,
+    close: &str,
+    around: bool,
+) -> Option<Range<usize>> {
+    let mut depth = 0;
+    let mut start = idx;
+    loop {
+        let s = cells[start].str();
+        if s == close && start != idx {
+            depth += 1;
+        } else if s == open {
+            if depth == 0 {
+                break;
+            }
+            depth -= 1;
+        }
+        start = start.checked_sub(1)?;
+    }
+
+    let mut depth = 0;
+    let mut end = start + 1;
+    loop {
+        let s = cells.get(end)?.str();
+        if s == open {
+            depth += 1;
+        } else if s == close {
+            if depth == 0 {
+                break;
+            }
+            depth -= 1;
+        }
+        end += 1;
+    }
+
+    Some(if around {
+        start..end + 1
+    } else {
+        start + 1..end
+    })
+}
+
+/// Computes the range of cell indices spanned by the text object
+/// around cell index x in line.  Returns None if there is no such
+/// object, or if it is empty.
+/// SemanticZone is not a property of the line, and is not handled here.
+fn text_object_range(
+    line: &Line,
+    x: usize,
+    object: TextObject,
+    around: bool,
+) -> Option<Range<usize>> {
+    let cells: Vec<CellRef> = line.visible_cells().collect();
+    let idx = cells
+        .iter()
+        .rposition(|cell| cell.cell_index() <= x)
+        .filter(|&idx| x < cells[idx].cell_index() + cells[idx].width())?;
+
+    let range = match object {
+        TextObject::Word => word_object(&cells, idx, false, around),
+        TextObject::BigWord => word_object(&cells, idx, true, around),
+        TextObject::DoubleQuote => quote_object(&cells, idx, "\"", around)?,
+        TextObject::SingleQuote => quote_object(&cells, idx, "'", around)?,
+        TextObject::Backtick => quote_object(&cells, idx, "`", around)?,
+        TextObject::Parentheses => bracket_object(&cells, idx, "(", ")", around)?,
+        TextObject::Brackets => bracket_object(&cells, idx, "[", "]", around)?,
+        TextObject::Braces => bracket_object(&cells, idx, "{", "}", around)?,
+        TextObject::SemanticZone => return None,
+    };
+
+    if range.is_empty() {
+        return None;
+    }
+    let last = &cells[range.end - 1];
+    Some(cells[range.start].cell_index()..last.cell_index() + last.width())
+}
+
+/// Returns the smallest text object that strictly encloses
+/// the range of cell indices sel
+fn expand_range(line: &Line, sel: Range<usize>) -> Option<Range<usize>> {
+    let encloses =
+        |r: &Range<usize>| r.start <= sel.start && r.end >= sel.end && r.len() > sel.len();
+
+    let mut candidates = vec![];
+    for object in [
+        TextObject::Word,
+        TextObject::DoubleQuote,
+        TextObject::SingleQuote,
+        TextObject::Backtick,
+        TextObject::Parentheses,
+        TextObject::Brackets,
+        TextObject::Braces,
+    ] {
+        let is_bracket = matches!(
+            object,
+            TextObject::Parentheses | TextObject::Brackets | TextObject::Braces
+        );
+        for around in [false, true] {
+            if object == TextObject::Word && around {
+                continue;
+            }
+            let mut x = sel.start;
+            while let Some(r) = text_object_range(line, x, object, around) {
+                if encloses(&r) {
+                    candidates.push(r);
+                    break;
+                }
+                // Brackets nest, so look for the next pair out
+                let open = if around { r.start } else { r.start - 1 };
+                if !is_bracket || open == 0 {
+                    break;
+                }
+                x = open - 1;
+            }
+        }
+    }
+
+    // The content of the line as a whole
+    let content_end = line
+        .visible_cells()
+        .filter(|cell| !is_whitespace_word(cell.str()))
+        .last()
+        .map(|cell| cell.cell_index() + cell.width())
+        .unwrap_or(0);
+    candidates.push(0..content_end);
+
+    candidates
+        .into_iter()
+        .filter(encloses)
+        .min_by_key(|r| r.len())
+}
+
+/// Converts a physical coordinate into a cell index within logical,
+/// clamping it to the physical line
+fn logical_x(logical: &LogicalLine, coord: SelectionCoordinate) -> usize {
+    let x = match coord.x {
+        SelectionX::Cell(x) => x,
+        SelectionX::BeforeZero => 0,
+    };
+    let row = (coord.y - logical.first_row).max(0) as usize;
+    let x = match logical.physical_lines.get(row) {
+        Some(line) => x.min(line.len()),
+        None => x,
+    };
+    logical.xy_to_logical_x(x, coord.y)
+}
+
+fn logical_range_to_selection(logical: &LogicalLine, range: Range<usize>) -> SelectionRange {
+    let (start_y, start_x) = logical.logical_x_to_physical_coord(range.start);
+    let (end_y, end_x) = logical.logical_x_to_physical_coord(range.end - 1);
+    SelectionRange {
+        start: SelectionCoordinate::x_y(start_x, start_y),
+        end: SelectionCoordinate::x_y(end_x, end_y),
+    }
+}
+
+/// Resolves a text object around the physical coordinate coord.
+/// The object is computed from the logical line, so that objects
+/// that span wrapped lines are selected in their entirety.
+fn text_object_in_logical_line(
+    logical: &LogicalLine,
+    coord: SelectionCoordinate,
+    object: TextObject,
+    around: bool,
+) -> Option<SelectionRange> {
+    let x = logical_x(logical, coord);
+    let range = text_object_range(&logical.logical, x, object, around)?;
+    Some(logical_range_to_selection(logical, range))
+}
+
+/// Grows the normalized, inclusive selection sel to the next text
+/// object that encloses it within logical
+fn expand_in_logical_line(logical: &LogicalLine, sel: SelectionRange) -> Option<SelectionRange> {
+    if !logical.contains_y(sel.start.y) || !logical.contains_y(sel.end.y) {
+        return None;
+    }
+    let start = logical_x(logical, sel.start);
+    let end = logical_x(logical, sel.end) + 1;
+    let range = expand_range(&logical.logical, start..end)?;
+    Some(logical_range_to_selection(logical, range))
+}
+
+/// Returns true if the outer range contains the inner range and
+/// is larger than it
+fn range_encloses(outer: &SelectionRange, inner: &SelectionRange) -> bool {
+    let outer = outer.normalize();
+    (outer.start.y, outer.start.x) <= (inner.start.y, inner.start.x)
+        && (outer.end.y, outer.end.x) >= (inner.end.y, inner.end.x)
+        && outer != *inner
+}
+
 pub fn search_key_table() -> KeyTable {
     let mut table = KeyTable::default();
     for (key, mods, action) in [
@@ -1956,7 +2388,9 @@ pub fn copy_key_table() -> KeyTable {
             WKeyCode::Char('y'),
             Modifiers::NONE,
             KeyAssignment::Multiple(vec![
-                KeyAssignment::CopyTo(ClipboardCopyDestination::ClipboardAndPrimarySelection),
+                KeyAssignment::CopyTo(
+                    ClipboardCopyDestination::ClipboardAndPrimarySelection.into(),
+                ),
                 KeyAssignment::CopyMode(CopyModeAssignment::Close),
             ]),
         ),
@@ -2000,6 +2434,22 @@ pub fn copy_key_table() -> KeyTable {
             Modifiers::NONE,
             KeyAssignment::CopyMode(CopyModeAssignment::JumpForward { prev_char: true }),
         ),
+        (
+            WKeyCode::Char('i'),
+            Modifiers::NONE,
+            KeyAssignment::CopyMode(CopyModeAssignment::SelectTextObject {
+                object: None,
+                around: false,
+            }),
+        ),
+        (
+            WKeyCode::Char('a'),
+            Modifiers::NONE,
+            KeyAssignment::CopyMode(CopyModeAssignment::SelectTextObject {
+                object: None,
+                around: true,
+            }),
+        ),
         (
             WKeyCode::Home,
             Modifiers::NONE,
@@ -2015,3 +2465,160 @@ pub fn copy_key_table() -> KeyTable {
     }
     table
 }
+
+#[cfg(test)]
+mod text_object_test {
+    use super::*;
+    use crate::selection::test_util::{logical_line, range};
+
+    /// Wraps text at width columns in the same way that the terminal
+    /// would, and returns the resulting logical line
+    fn wrapped(text: &str, width: usize) -> LogicalLine {
+        let chars: Vec<char> = text.chars().collect();
+        let rows: Vec<String> = chars
+            .chunks(width)
+            .map(|chunk| chunk.iter().collect())
+            .collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        logical_line(0, &rows)
+    }
+
+    fn select(
+        logical: &LogicalLine,
+        x: usize,
+        y: StableRowIndex,
+        object: TextObject,
+        around: bool,
+    ) -> Option<SelectionRange> {
+        text_object_in_logical_line(logical, SelectionCoordinate::x_y(x, y), object, around)
+    }
+
+    #[test]
+    fn quoted_string_spanning_wrapped_lines() {
+        // echo "hel
+        // lo wrappe
+        // d world"
+        // done
+        let logical = wrapped("echo \"hello wrapped world\" done", 9);
+
+        assert_eq!(
+            select(&logical, 3, 1, TextObject::DoubleQuote, false),
+            Some(range((6, 0), (6, 2)))
+        );
+        assert_eq!(
+            select(&logical, 3, 1, TextObject::DoubleQuote, true),
+            Some(range((5, 0), (8, 2)))
+        );
+    }
+
+    #[test]
+    fn word_spanning_wrapped_lines() {
+        // one tw
+        // othree
+        //  four
+        let logical = wrapped("one twothree four", 6);
+
+        assert_eq!(
+            select(&logical, 1, 1, TextObject::Word, false),
+            Some(range((4, 0), (5, 1)))
+        );
+        // The trailing blank is on the third line
+        assert_eq!(
+            select(&logical, 1, 1, TextObject::Word, true),
+            Some(range((4, 0), (0, 2)))
+        );
+    }
+
+    #[test]
+    fn words() {
+        let logical = wrapped("foo.bar baz", 80);
+
+        assert_eq!(
+            select(&logical, 5, 0, TextObject::Word, false),
+            Some(range((4, 0), (6, 0)))
+        );
+        assert_eq!(
+            select(&logical, 5, 0, TextObject::BigWord, false),
+            Some(range((0, 0), (6, 0)))
+        );
+        // No trailing blanks, so the leading blanks are included
+        assert_eq!(
+            select(&logical, 9, 0, TextObject::Word, true),
+            Some(range((7, 0), (10, 0)))
+        );
+    }
+
+    #[test]
+    fn quotes_are_paired_from_the_start_of_the_line() {
+        let logical = wrapped("'a' x 'b' \\'c", 80);
+
+        assert_eq!(
+            select(&logical, 1, 0, TextObject::SingleQuote, false),
+            Some(range((1, 0), (1, 0)))
+        );
+        // Between quoted strings, the next one is selected
+        assert_eq!(
+            select(&logical, 4, 0, TextObject::SingleQuote, false),
+            Some(range((7, 0), (7, 0)))
+        );
+        // The escaped quote is not paired
+        assert_eq!(
+            select(&logical, 12, 0, TextObject::SingleQuote, false),
+            None
+        );
+    }
+
+    #[test]
+    fn nested_brackets() {
+        let logical = wrapped("f(a, [b, (c)], {})", 80);
+
+        assert_eq!(
+            select(&logical, 10, 0, TextObject::Parentheses, false),
+            Some(range((10, 0), (10, 0)))
+        );
+        assert_eq!(
+            select(&logical, 10, 0, TextObject::Brackets, false),
+            Some(range((6, 0), (11, 0)))
+        );
+        // On the closing bracket of the inner pair
+        assert_eq!(
+            select(&logical, 11, 0, TextObject::Parentheses, true),
+            Some(range((9, 0), (11, 0)))
+        );
+        // On a comma between the outer parentheses
+        assert_eq!(
+            select(&logical, 3, 0, TextObject::Parentheses, false),
+            Some(range((2, 0), (16, 0)))
+        );
+        // Empty braces have no inner object
+        assert_eq!(select(&logical, 16, 0, TextObject::Braces, false), None);
+    }
+
+    #[test]
+    fn expand_selection_to_enclosing_objects() {
+        let logical = wrapped("x = f(a, [b, \"c d\"]) ", 10);
+        let (y, x) = logical.logical_x_to_physical_coord(14);
+
+        let mut sel = select(&logical, x, y, TextObject::Word, false).unwrap();
+        let mut expansions = vec![];
+        while let Some(expanded) = expand_in_logical_line(&logical, sel) {
+            sel = expanded;
+            let start = logical_x(&logical, sel.start);
+            let end = logical_x(&logical, sel.end) + 1;
+            expansions.push(logical.logical.columns_as_str(start..end));
+        }
+
+        assert_eq!(
+            expansions,
+            vec![
+                "c d",
+                "\"c d\"",
+                "b, \"c d\"",
+                "[b, \"c d\"]",
+                "a, [b, \"c d\"]",
+                "(a, [b, \"c d\"])",
+                "x = f(a, [b, \"c d\"])",
+            ]
+        );
+    }
+}
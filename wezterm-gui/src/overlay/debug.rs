@@ -1,5 +1,6 @@
 use crate::scripting::guiwin::GuiWin;
-use chrono::prelude::*;
+use crate::termwindow::animation::FrameStats;
+use crate::termwindow::render::RenderCacheStats;
 use futures::FutureExt;
 use log::Level;
 use luahelper::ValuePrinter;
@@ -7,17 +8,17 @@ use mlua::Value;
 use mux::termwiztermtab::TermWizTerminal;
 use std::io::Write;
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
 use termwiz::cell::{AttributeChange, CellAttributes, Intensity};
-use termwiz::color::AnsiColor;
+use termwiz::color::{AnsiColor, ColorAttribute};
 use termwiz::input::{InputEvent, KeyCode, KeyEvent};
 use termwiz::lineedit::*;
 use termwiz::surface::Change;
 use termwiz::terminal::Terminal;
 
-lazy_static::lazy_static! {
-    static ref LATEST_LOG_ENTRY: Mutex<Option<DateTime<Local>>> = Mutex::new(None);
-}
+/// The seqno of the most recent log entry that has been shown
+static LATEST_LOG_SEQNO: AtomicU64 = AtomicU64::new(0);
 
 struct LuaReplHost {
     history: BasicHistory,
@@ -128,11 +129,125 @@ impl LineEditorHost for LuaReplHost {
     }
 }
 
+fn render_log_entry(entry: &env_bootstrap::ringlog::Entry, changes: &mut Vec<Change>) {
+    LATEST_LOG_SEQNO.fetch_max(entry.seqno, Ordering::Relaxed);
+
+    changes.push(Change::AllAttributes(CellAttributes::default()));
+    changes.push(Change::Text(entry.then.format("%H:%M:%S%.3f ").to_string()));
+
+    changes.push(
+        AttributeChange::Foreground(match entry.level {
+            Level::Error => AnsiColor::Maroon.into(),
+            Level::Warn => AnsiColor::Red.into(),
+            Level::Info => AnsiColor::Green.into(),
+            Level::Debug => AnsiColor::Blue.into(),
+            Level::Trace => AnsiColor::Fuchsia.into(),
+        })
+        .into(),
+    );
+    changes.push(Change::Text(entry.level.as_str().to_string()));
+    changes.push(Change::AllAttributes(CellAttributes::default()));
+    changes.push(AttributeChange::Intensity(Intensity::Bold).into());
+    changes.push(Change::Text(format!(" {}", entry.target)));
+    changes.push(Change::AllAttributes(CellAttributes::default()));
+    changes.push(Change::Text(format!(
+        " > {}\r\n",
+        entry.msg.replace("\n", "\r\n")
+    )));
+}
+
+fn print_new_log_entries(term: &mut TermWizTerminal) -> termwiz::Result<()> {
+    let entries =
+        env_bootstrap::ringlog::get_entries_after(LATEST_LOG_SEQNO.load(Ordering::Relaxed));
+    let mut changes = vec![];
+    for entry in entries {
+        render_log_entry(&entry, &mut changes);
+    }
+    term.render(&changes)
+}
+
+/// Recognizes the `log` command, which is `log` optionally followed
+/// by a level, returning the level to filter by.
+/// Anything else is left to be evaluated as lua.
+fn parse_log_command(line: &str) -> Option<Level> {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("log") {
+        return None;
+    }
+    let level = match words.next() {
+        Some(level) => level.parse().ok()?,
+        None => Level::Trace,
+    };
+    if words.next().is_some() {
+        return None;
+    }
+    Some(level)
+}
+
+/// Shows the retained log entries that are at least as severe as
+/// `level`, a screenful at a time
+fn page_log_entries(term: &mut TermWizTerminal, level: Level) -> termwiz::Result<()> {
+    let entries: Vec<_> = env_bootstrap::ringlog::get_entries()
+        .into_iter()
+        .filter(|entry| entry.level <= level)
+        .collect();
+    if entries.is_empty() {
+        return term.render(&[Change::Text(format!(
+            "No {} or more severe log entries have been retained\r\n",
+            level.as_str()
+        ))]);
+    }
+
+    let page_size = term.get_screen_size()?.rows.saturating_sub(1).max(1);
+    let mut pages = entries.chunks(page_size).peekable();
+    while let Some(page) = pages.next() {
+        let mut changes = vec![];
+        for entry in page {
+            render_log_entry(entry, &mut changes);
+        }
+        term.render(&changes)?;
+
+        if pages.peek().is_none() {
+            break;
+        }
+        term.render(&[
+            AttributeChange::Reverse(true).into(),
+            Change::Text("-- More: Space or Enter to continue, q or ESC to stop --".to_string()),
+            Change::AllAttributes(CellAttributes::default()),
+        ])?;
+        let more = loop {
+            match term.poll_input(None)? {
+                Some(InputEvent::Key(KeyEvent {
+                    key: KeyCode::Char(' ') | KeyCode::Enter,
+                    ..
+                })) => break true,
+                Some(InputEvent::Key(KeyEvent {
+                    key: KeyCode::Char('q') | KeyCode::Escape,
+                    ..
+                })) => break false,
+                Some(_) => {}
+                None => break false,
+            }
+        };
+        term.render(&[
+            Change::Text("\r".to_string()),
+            Change::ClearToEndOfLine(ColorAttribute::Default),
+        ])?;
+        if !more {
+            break;
+        }
+    }
+    Ok(())
+}
+
 pub fn show_debug_overlay(
     mut term: TermWizTerminal,
     gui_win: GuiWin,
     opengl_info: String,
     connection_info: String,
+    cache_stats: RenderCacheStats,
+    input_latency: Option<Duration>,
+    frame_stats: FrameStats,
 ) -> anyhow::Result<()> {
     term.no_grab_mouse_in_raw_mode();
 
@@ -155,44 +270,12 @@ pub fn show_debug_overlay(
 
     term.render(&[Change::Title("Debug".to_string())])?;
 
-    fn print_new_log_entries(term: &mut TermWizTerminal) -> termwiz::Result<()> {
-        let entries = env_bootstrap::ringlog::get_entries();
-        let mut changes = vec![];
-        for entry in entries {
-            if let Some(latest) = LATEST_LOG_ENTRY.lock().unwrap().as_ref() {
-                if entry.then <= *latest {
-                    // already seen this one
-                    continue;
-                }
-            }
-            LATEST_LOG_ENTRY.lock().unwrap().replace(entry.then);
-
-            changes.push(Change::AllAttributes(CellAttributes::default()));
-            changes.push(Change::Text(entry.then.format("%H:%M:%S%.3f ").to_string()));
-
-            changes.push(
-                AttributeChange::Foreground(match entry.level {
-                    Level::Error => AnsiColor::Maroon.into(),
-                    Level::Warn => AnsiColor::Red.into(),
-                    Level::Info => AnsiColor::Green.into(),
-                    Level::Debug => AnsiColor::Blue.into(),
-                    Level::Trace => AnsiColor::Fuchsia.into(),
-                })
-                .into(),
-            );
-            changes.push(Change::Text(entry.level.as_str().to_string()));
-            changes.push(Change::AllAttributes(CellAttributes::default()));
-            changes.push(AttributeChange::Intensity(Intensity::Bold).into());
-            changes.push(Change::Text(format!(" {}", entry.target)));
-            changes.push(Change::AllAttributes(CellAttributes::default()));
-            changes.push(Change::Text(format!(
-                " > {}\r\n",
-                entry.msg.replace("\n", "\r\n")
-            )));
-        }
-        term.render(&changes)
-    }
-
+    let line_quads = cache_stats.line_quads;
+    let line_shapes = cache_stats.line_shapes;
+    let input_latency = match input_latency {
+        Some(latency) => format!("{latency:?}"),
+        None => "not yet measured".to_string(),
+    };
     let version = config::wezterm_version();
     let triple = config::wezterm_target_triple();
 
@@ -202,7 +285,14 @@ pub fn show_debug_overlay(
          Window Environment: {connection_info}\r\n\
          Lua Version: {lua_version}\r\n\
          {opengl_info}\r\n\
+         Line quad cache: {line_quads}\r\n\
+         Line shape cache: {line_shapes}\r\n\
+         Input latency: {input_latency}\r\n\
+         Frame rate: {frame_stats}\r\n\
+         Evaluate window:render_cache_stats() or window:frame_stats() to refresh these counters.\r\n\
          Enter lua statements or expressions and hit Enter.\r\n\
+         Enter `log` to page through the recent log entries, or `log warn` to\r\n\
+         show only those at that level or more severe.\r\n\
          Press ESC or CTRL-D to exit\r\n",
     ))])?;
 
@@ -216,6 +306,11 @@ pub fn show_debug_overlay(
             }
             host.as_mut().unwrap().add_history(&line);
 
+            if let Some(level) = parse_log_command(&line) {
+                page_log_entries(&mut term, level)?;
+                continue;
+            }
+
             let passed_host = host.take().unwrap();
 
             let (host_res, text) =
@@ -280,3 +375,23 @@ async fn evaluate(host: LuaReplHost, expr: String) -> (LuaReplHost, String) {
     let result = do_it(&host, &expr).await;
     (host, result)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn log_command() {
+        assert_eq!(parse_log_command("log"), Some(Level::Trace));
+        assert_eq!(parse_log_command("  log  "), Some(Level::Trace));
+        assert_eq!(parse_log_command("log warn"), Some(Level::Warn));
+        assert_eq!(parse_log_command("log DEBUG"), Some(Level::Debug));
+
+        // Anything else is lua
+        assert_eq!(parse_log_command("log bogus"), None);
+        assert_eq!(parse_log_command("log warn extra"), None);
+        assert_eq!(parse_log_command("logs"), None);
+        assert_eq!(parse_log_command("log(1)"), None);
+        assert_eq!(parse_log_command(""), None);
+    }
+}
@@ -0,0 +1,168 @@
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::{AttributeChange, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+use wezterm_ssh::{HostVerificationAnswer, HostVerificationEvent};
+
+struct Button {
+    label: &'static str,
+    answer: HostVerificationAnswer,
+    x: usize,
+}
+
+fn build_buttons(x_pos: usize) -> Vec<Button> {
+    let mut buttons = vec![];
+    let mut x = x_pos;
+    for (label, answer) in [
+        (" [A]ccept and save ", HostVerificationAnswer::AcceptAndSave),
+        (" Accept [O]nce ", HostVerificationAnswer::AcceptOnce),
+        (" [R]eject ", HostVerificationAnswer::Reject),
+    ] {
+        buttons.push(Button { label, answer, x });
+        x += label.len() + 4 /* spacer */;
+    }
+    buttons
+}
+
+fn describe_host_key(verify: &HostVerificationEvent) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "The authenticity of {} can't be established.",
+            verify.remote_address
+        ),
+        String::new(),
+    ];
+    if let Some(key_type) = &verify.key_type {
+        lines.push(format!("Key type:         {key_type}"));
+    }
+    lines.push(format!("Fingerprint:      {}", verify.fingerprint));
+    if let Some(file) = &verify.known_hosts_file {
+        lines.push(format!("Known hosts file: {}", file.display()));
+    }
+    if let Some(randomart) = &verify.randomart {
+        lines.push(String::new());
+        lines.extend(randomart.lines().map(|l| l.to_string()));
+    }
+    lines.push(String::new());
+    lines.push("Do you trust this host?".to_string());
+    lines
+}
+
+fn run_host_key_app(
+    verify: &HostVerificationEvent,
+    term: &mut TermWizTerminal,
+) -> anyhow::Result<HostVerificationAnswer> {
+    term.set_raw_mode()?;
+
+    let size = term.get_screen_size()?;
+    let lines = describe_host_key(verify);
+
+    let x_pos = size.cols * 10 / 100;
+    // Vertically center the text, plus a blank line and the buttons
+    let top_row = size.rows.saturating_sub(lines.len() + 2) / 2;
+    let button_row = top_row + lines.len() + 1;
+    let buttons = build_buttons(x_pos);
+    let mut active: Option<usize> = None;
+
+    let render = |term: &mut TermWizTerminal, active: Option<usize>| -> termwiz::Result<()> {
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+        ];
+
+        for (y, row) in lines.iter().enumerate() {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(x_pos),
+                y: Position::Absolute(top_row + y),
+            });
+            if y == 0 {
+                changes.push(AttributeChange::Intensity(Intensity::Bold).into());
+            }
+            changes.push(Change::Text(row.to_string()));
+            if y == 0 {
+                changes.push(AttributeChange::Intensity(Intensity::Normal).into());
+            }
+        }
+
+        for (idx, button) in buttons.iter().enumerate() {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(button.x),
+                y: Position::Absolute(button_row),
+            });
+            if active == Some(idx) {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+            changes.push(button.label.into());
+            if active == Some(idx) {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+
+        term.render(&changes)?;
+        term.flush()
+    };
+
+    render(term, active)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('a' | 'A' | 'y' | 'Y'),
+                ..
+            }) => {
+                return Ok(HostVerificationAnswer::AcceptAndSave);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('o' | 'O'),
+                ..
+            }) => {
+                return Ok(HostVerificationAnswer::AcceptOnce);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('r' | 'R' | 'n' | 'N'),
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                return Ok(HostVerificationAnswer::Reject);
+            }
+            InputEvent::Mouse(MouseEvent {
+                x,
+                y,
+                mouse_buttons,
+                ..
+            }) => {
+                let x = x as usize;
+                let y = y as usize;
+                active = buttons.iter().position(|button| {
+                    y == button_row && x >= button.x && x < button.x + button.label.len()
+                });
+
+                if mouse_buttons == MouseButtons::LEFT {
+                    if let Some(idx) = active {
+                        return Ok(buttons[idx].answer);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        render(term, active)?;
+    }
+
+    Ok(HostVerificationAnswer::Reject)
+}
+
+/// Asks the user whether to trust an unknown ssh host key and
+/// delivers the answer to the ssh session that is waiting on it.
+pub fn confirm_host_key(
+    verify: HostVerificationEvent,
+    mut term: TermWizTerminal,
+) -> anyhow::Result<()> {
+    let answer = run_host_key_app(&verify, &mut term)?;
+    verify.try_answer_with(answer)
+}
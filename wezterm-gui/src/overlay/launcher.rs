@@ -47,6 +47,7 @@ pub struct LauncherDomainEntry {
     pub domain_id: DomainId,
     pub name: String,
     pub state: DomainState,
+    pub detachable: bool,
     pub label: String,
 }
 
@@ -141,6 +142,7 @@ impl LauncherArgs {
                     domain_id: dom.domain_id(),
                     name: name.to_string(),
                     state: dom.state(),
+                    detachable: dom.detachable(),
                     label,
                 });
             }
@@ -233,10 +235,32 @@ impl LauncherState {
             }
         }
 
+        if args.flags.contains(LauncherFlags::PROFILES) {
+            let mut names: Vec<&String> = config.profiles.keys().collect();
+            names.sort();
+            for name in names {
+                self.entries.push(Entry {
+                    label: format!("New Tab (profile `{name}`)"),
+                    action: KeyAssignment::SpawnCommandInNewTab(SpawnCommand {
+                        profile: Some(name.clone()),
+                        ..SpawnCommand::default()
+                    }),
+                });
+            }
+        }
+
         for domain in &args.domains {
+            // Only domains that can be detached have a state
+            // that is worth showing
+            let label = if domain.detachable {
+                format!("{} ({:?})", domain.label, domain.state)
+            } else {
+                domain.label.clone()
+            };
+
             let entry = if domain.state == DomainState::Attached {
                 Entry {
-                    label: format!("New Tab ({})", domain.label),
+                    label: format!("New Tab ({label})"),
                     action: KeyAssignment::SpawnCommandInNewTab(SpawnCommand {
                         domain: SpawnTabDomain::DomainName(domain.name.to_string()),
                         ..SpawnCommand::default()
@@ -244,7 +268,7 @@ impl LauncherState {
                 }
             } else {
                 Entry {
-                    label: format!("Attach {}", domain.label),
+                    label: format!("Attach {label}"),
                     action: KeyAssignment::AttachDomain(domain.name.to_string()),
                 }
             };
@@ -258,6 +282,17 @@ impl LauncherState {
             self.entries.push(entry);
         }
 
+        for domain in &args.domains {
+            if domain.detachable && domain.state == DomainState::Attached {
+                self.entries.push(Entry {
+                    label: format!("Detach {}", domain.label),
+                    action: KeyAssignment::DetachDomain(SpawnTabDomain::DomainName(
+                        domain.name.to_string(),
+                    )),
+                });
+            }
+        }
+
         if args.flags.contains(LauncherFlags::WORKSPACES) {
             for ws in &args.workspaces {
                 if *ws != args.active_workspace {
@@ -7,20 +7,27 @@ use std::sync::Arc;
 use wezterm_term::{TerminalConfiguration, TerminalSize};
 
 pub mod confirm_close_pane;
+pub mod confirm_paste;
 pub mod copy;
 pub mod debug;
+pub mod hostkey;
 pub mod launcher;
 pub mod prompt;
 pub mod quickselect;
+pub mod release_notes;
 pub mod selector;
+pub mod upload;
 
 pub use confirm_close_pane::{
     confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program,
 };
+pub use confirm_paste::{broadcast_paste_message, confirm_broadcast_paste};
 pub use copy::{CopyModeParams, CopyOverlay};
 pub use debug::show_debug_overlay;
 pub use launcher::{launcher, LauncherArgs, LauncherFlags};
 pub use quickselect::QuickSelectOverlay;
+pub use release_notes::show_update_overlay;
+pub use upload::{upload_dropped_files, Upload, UploadRequest};
 
 pub fn start_overlay<T, F>(
     term_window: &TermWindow,
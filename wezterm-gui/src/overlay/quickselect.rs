@@ -477,6 +477,18 @@ impl Pane for QuickSelectOverlay {
         self.delegate.get_current_working_dir(policy)
     }
 
+    fn get_location(&self, policy: CachePolicy) -> Option<mux::location::PaneLocation> {
+        self.delegate.get_location(policy)
+    }
+
+    fn timestamp_gutter_cols(&self) -> usize {
+        self.delegate.timestamp_gutter_cols()
+    }
+
+    fn set_timestamp_gutter_cols(&self, cols: usize) {
+        self.delegate.set_timestamp_gutter_cols(cols)
+    }
+
     fn get_cursor_position(&self) -> StableCursorPosition {
         // move to the search box
         let renderer = self.renderer.lock();
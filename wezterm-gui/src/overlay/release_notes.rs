@@ -0,0 +1,345 @@
+//! Shows the release notes of the latest available version.
+//! The notes are markdown; only the parts that release notes use
+//! are understood: headings are shown in bold, bullets are indented
+//! and links become hyperlinks.  Everything else is shown as-is.
+use crate::update::{current_channel, get_release_info, load_last_release_info, Release};
+use config::wezterm_version;
+use mux::termwiztermtab::TermWizTerminal;
+use std::sync::Arc;
+use termwiz::cell::{unicode_column_width, AttributeChange, Hyperlink, Intensity};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub link: Option<String>,
+}
+
+impl Span {
+    fn plain(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            bold: false,
+            link: None,
+        }
+    }
+}
+
+/// Splits `text` into spans, turning `[label](url)` into a span
+/// that links to `url`
+fn parse_links(text: &str, bold: bool) -> Vec<Span> {
+    let mut spans = vec![];
+    let mut rest = text;
+
+    let mut push = |spans: &mut Vec<Span>, text: &str, link: Option<String>| {
+        if !text.is_empty() {
+            spans.push(Span {
+                text: text.to_string(),
+                bold,
+                link,
+            });
+        }
+    };
+
+    while let Some(open) = rest.find('[') {
+        let link = rest[open..].find("](").and_then(|close| {
+            let close = open + close;
+            let url_start = close + 2;
+            rest[url_start..]
+                .find(')')
+                .map(|end| (close, url_start, url_start + end))
+        });
+        match link {
+            Some((close, url_start, url_end)) => {
+                push(&mut spans, &rest[..open], None);
+                push(
+                    &mut spans,
+                    &rest[open + 1..close],
+                    Some(rest[url_start..url_end].to_string()),
+                );
+                rest = &rest[url_end + 1..];
+            }
+            None => break,
+        }
+    }
+    push(&mut spans, rest, None);
+    spans
+}
+
+/// Appends `text` to `row`, extending the last span if it has the
+/// same attributes
+fn append(row: &mut Vec<Span>, text: &str, span: &Span) {
+    if let Some(last) = row.last_mut() {
+        if last.bold == span.bold && last.link == span.link {
+            last.text.push_str(text);
+            return;
+        }
+    }
+    row.push(Span {
+        text: text.to_string(),
+        ..span.clone()
+    });
+}
+
+/// Word wraps `spans` to `width` columns.  The first row starts with
+/// `prefix`, and the rows that follow are indented to line up with
+/// the text after it.
+fn wrap_spans(prefix: &str, spans: &[Span], width: usize) -> Vec<Vec<Span>> {
+    let indent = unicode_column_width(prefix, None);
+    let mut rows = vec![];
+    let mut row = vec![];
+    if !prefix.is_empty() {
+        row.push(Span::plain(prefix));
+    }
+    let mut col = indent;
+
+    for span in spans {
+        for word in span.text.split_inclusive(' ') {
+            let word_width = unicode_column_width(word.trim_end(), None);
+            if col > indent && col + word_width > width {
+                rows.push(std::mem::take(&mut row));
+                row.push(Span::plain(&" ".repeat(indent)));
+                col = indent;
+            }
+            append(&mut row, word, span);
+            col += unicode_column_width(word, None);
+        }
+    }
+    rows.push(row);
+    rows
+}
+
+/// Converts release notes into rows of at most `width` columns
+pub fn markdown_lite(notes: &str, width: usize) -> Vec<Vec<Span>> {
+    let mut rows = vec![];
+    for line in notes.lines() {
+        let line = line.trim_end();
+        let trimmed = line.trim_start();
+        let depth = (line.len() - trimmed.len()) / 2;
+
+        if trimmed.starts_with('#') {
+            let heading = trimmed.trim_start_matches('#').trim_start();
+            rows.extend(wrap_spans("", &parse_links(heading, true), width));
+        } else if let Some(item) = trimmed
+            .strip_prefix("* ")
+            .or_else(|| trimmed.strip_prefix("- "))
+        {
+            let prefix = format!("{}• ", "  ".repeat(depth + 1));
+            rows.extend(wrap_spans(&prefix, &parse_links(item, false), width));
+        } else {
+            rows.extend(wrap_spans("", &parse_links(line, false), width));
+        }
+    }
+    rows
+}
+
+fn render_row(row: &[Span], changes: &mut Vec<Change>) {
+    for span in row {
+        if span.bold {
+            changes.push(AttributeChange::Intensity(Intensity::Bold).into());
+        }
+        if let Some(url) = &span.link {
+            changes.push(
+                AttributeChange::Hyperlink(Some(Arc::new(Hyperlink::new(url.to_string())))).into(),
+            );
+        }
+        changes.push(span.text.as_str().into());
+        if span.link.is_some() {
+            changes.push(AttributeChange::Hyperlink(None).into());
+        }
+        if span.bold {
+            changes.push(AttributeChange::Intensity(Intensity::Normal).into());
+        }
+    }
+}
+
+fn run_release_notes_app(release: &Release, term: &mut TermWizTerminal) -> anyhow::Result<()> {
+    let mut size = term.get_screen_size()?;
+    let current = wezterm_version();
+
+    let header = if release.is_newer_than(current) {
+        format!(
+            "WezTerm {} is available; you are running {current}",
+            release.version()
+        )
+    } else {
+        format!("You are running the latest version of WezTerm, {current}")
+    };
+    let footer = " [O]pen release page   [Q]uit   Up/Down/PageUp/PageDown to scroll ";
+    let url = release.release_page_url();
+
+    let mut rows = markdown_lite(&release.body, size.cols);
+    let mut top = 0;
+
+    loop {
+        // The header and a blank line above the notes,
+        // and the footer below them
+        let page = size.rows.saturating_sub(3).max(1);
+        top = top.min(rows.len().saturating_sub(page));
+
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+            AttributeChange::Intensity(Intensity::Bold).into(),
+            header.as_str().into(),
+            AttributeChange::Intensity(Intensity::Normal).into(),
+        ];
+        for (y, row) in rows.iter().skip(top).take(page).enumerate() {
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(y + 2),
+            });
+            render_row(row, &mut changes);
+        }
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(size.rows.saturating_sub(1)),
+        });
+        changes.push(AttributeChange::Reverse(true).into());
+        changes.push(footer.into());
+        changes.push(AttributeChange::Reverse(false).into());
+        term.render(&changes)?;
+        term.flush()?;
+
+        match term.poll_input(None)? {
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('o' | 'O'),
+                ..
+            })) => {
+                wezterm_open_url::open_url(&url);
+            }
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('q' | 'Q') | KeyCode::Escape,
+                ..
+            })) => return Ok(()),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow | KeyCode::Char('k'),
+                ..
+            })) => top = top.saturating_sub(1),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow | KeyCode::Char('j'),
+                ..
+            })) => top += 1,
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            })) => top = top.saturating_sub(page),
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown | KeyCode::Char(' '),
+                ..
+            })) => top += page,
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Home, ..
+            })) => top = 0,
+            Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::End, ..
+            })) => top = rows.len(),
+            Some(InputEvent::Mouse(MouseEvent { mouse_buttons, .. }))
+                if mouse_buttons.contains(MouseButtons::VERT_WHEEL) =>
+            {
+                if mouse_buttons.contains(MouseButtons::WHEEL_POSITIVE) {
+                    top = top.saturating_sub(3);
+                } else {
+                    top += 3;
+                }
+            }
+            Some(InputEvent::Resized { cols, rows: height }) => {
+                size.cols = cols;
+                size.rows = height;
+                rows = markdown_lite(&release.body, cols);
+            }
+            Some(_) => {}
+            None => return Ok(()),
+        }
+    }
+}
+
+pub fn show_update_overlay(mut term: TermWizTerminal) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+
+    let channel = current_channel();
+    let release = match load_last_release_info(channel) {
+        Some(release) => release,
+        None => {
+            term.render(&[
+                Change::CursorVisibility(CursorVisibility::Hidden),
+                "Checking for updates...".into(),
+            ])?;
+            term.flush()?;
+            match get_release_info(channel) {
+                Ok(release) => release,
+                Err(err) => {
+                    term.render(&[
+                        Change::ClearScreen(ColorAttribute::Default),
+                        format!("Unable to check for updates: {err:#}\r\n").into(),
+                        "Press any key to close".into(),
+                    ])?;
+                    term.flush()?;
+                    term.poll_input(None)?;
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    run_release_notes_app(&release, &mut term)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn text(rows: &[Vec<Span>]) -> Vec<String> {
+        rows.iter()
+            .map(|row| row.iter().map(|span| span.text.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn headings_bullets_and_links() {
+        let notes = "#### New\n\
+                     * Added [ShowUpdateOverlay](https://example.com/a)\n  \
+                     * nested item\n\
+                     plain text";
+        let rows = markdown_lite(notes, 80);
+        assert_eq!(
+            text(&rows),
+            vec![
+                "New",
+                "  • Added ShowUpdateOverlay",
+                "    • nested item",
+                "plain text"
+            ]
+        );
+        assert!(rows[0][0].bold);
+        assert_eq!(
+            rows[1],
+            vec![
+                Span::plain("  • Added "),
+                Span {
+                    text: "ShowUpdateOverlay".to_string(),
+                    bold: false,
+                    link: Some("https://example.com/a".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn wraps_bullets_with_hanging_indent() {
+        let rows = markdown_lite("* one two three four", 12);
+        assert_eq!(text(&rows), vec!["  • one two ", "    three ", "    four"]);
+    }
+
+    #[test]
+    fn unterminated_link_is_plain() {
+        assert_eq!(
+            parse_links("see [the docs](nowhere", false),
+            vec![Span::plain("see [the docs](nowhere")]
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! Uploads files that were dropped onto a pane in an ssh domain to the
+//! remote host via SFTP, then pastes their remote paths into the pane.
+//! The upload is confirmed in an overlay first; it then runs in the
+//! background, with its progress shown in the status area of the
+//! window, where it can be cancelled with a middle-click.
+use super::confirm_close_pane::run_confirmation_app;
+use crate::termwindow::TermWindowNotif;
+use anyhow::Context;
+use config::DroppedFileQuoting;
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use termwiz::color::ColorAttribute;
+use termwiz::input::InputEvent;
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+use wezterm_ssh::Session;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+/// How often the status area is refreshed while uploading
+const STATUS_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct UploadRequest {
+    pub pane_id: PaneId,
+    pub session: Session,
+    pub domain_name: String,
+    pub paths: Vec<PathBuf>,
+    /// The working directory of the pane on the remote host, if known
+    pub remote_cwd: Option<String>,
+    /// The window whose status area shows the progress
+    pub window: ::window::Window,
+}
+
+#[derive(Clone)]
+struct LocalFile {
+    path: PathBuf,
+    name: String,
+    size: u64,
+}
+
+/// An upload that is in progress
+pub struct Upload {
+    files: Vec<LocalFile>,
+    /// Where the files are going, as `domain:directory`
+    destination: String,
+    progress: UploadProgress,
+}
+
+#[derive(Default)]
+struct UploadProgress {
+    /// The index of the file that is being uploaded
+    file_idx: AtomicUsize,
+    /// The number of bytes of that file that have been uploaded
+    bytes: AtomicU64,
+    cancel: AtomicBool,
+}
+
+fn remote_path(dir: &str, name: &str) -> String {
+    format!("{}/{}", dir.trim_end_matches('/'), name)
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024. && unit < UNITS.len() - 1 {
+        size /= 1024.;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Uploads `files` into `dir`, returning their remote paths.
+/// A file whose upload is cancelled is removed from the remote host.
+/// `on_progress` is called as the upload progresses, at most once
+/// per `STATUS_INTERVAL`.
+async fn upload_files(
+    session: &Session,
+    files: &[LocalFile],
+    dir: &str,
+    progress: &UploadProgress,
+    on_progress: &dyn Fn(),
+) -> anyhow::Result<Vec<String>> {
+    use smol::io::{AsyncReadExt, AsyncWriteExt};
+
+    let sftp = session.sftp();
+    let mut uploaded = vec![];
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut last_progress = Instant::now();
+
+    for (idx, file) in files.iter().enumerate() {
+        progress.file_idx.store(idx, Ordering::Relaxed);
+        progress.bytes.store(0, Ordering::Relaxed);
+
+        let target = remote_path(dir, &file.name);
+        let mut local = smol::fs::File::open(&file.path)
+            .await
+            .with_context(|| format!("opening {}", file.path.display()))?;
+        let mut remote = sftp
+            .create(target.clone())
+            .await
+            .with_context(|| format!("creating {target}"))?;
+
+        loop {
+            if progress.cancel.load(Ordering::Relaxed) {
+                remote.close().await.ok();
+                sftp.remove_file(target.clone()).await.ok();
+                anyhow::bail!("cancelled");
+            }
+            let len = local
+                .read(&mut buf)
+                .await
+                .with_context(|| format!("reading {}", file.path.display()))?;
+            if len == 0 {
+                break;
+            }
+            remote
+                .write_all(&buf[..len])
+                .await
+                .with_context(|| format!("writing {target}"))?;
+            progress.bytes.fetch_add(len as u64, Ordering::Relaxed);
+            if last_progress.elapsed() >= STATUS_INTERVAL {
+                last_progress = Instant::now();
+                on_progress();
+            }
+        }
+        remote
+            .close()
+            .await
+            .with_context(|| format!("closing {target}"))?;
+        uploaded.push(target);
+    }
+
+    Ok(uploaded)
+}
+
+impl Upload {
+    /// Returns the text for the status area, which describes the
+    /// progress of the upload and how to cancel it
+    pub fn status(&self) -> String {
+        if self.progress.cancel.load(Ordering::Relaxed) {
+            return "Cancelling upload... ".to_string();
+        }
+        let idx = self
+            .progress
+            .file_idx
+            .load(Ordering::Relaxed)
+            .min(self.files.len() - 1);
+        let file = &self.files[idx];
+        let bytes = self.progress.bytes.load(Ordering::Relaxed).min(file.size);
+        let percent = if file.size == 0 {
+            100
+        } else {
+            bytes * 100 / file.size
+        };
+        let count = if self.files.len() == 1 {
+            String::new()
+        } else {
+            format!(" ({} of {})", idx + 1, self.files.len())
+        };
+        format!(
+            "Uploading {}{count} to {}: {percent}% of {}; middle-click to cancel ",
+            file.name,
+            self.destination,
+            format_size(file.size),
+        )
+    }
+
+    /// Asks the upload to stop; the file that is being uploaded
+    /// is removed from the remote host
+    pub fn cancel(&self) {
+        self.progress.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Shows `message` until a key is pressed
+fn show_message(term: &mut TermWizTerminal, message: &str) -> anyhow::Result<()> {
+    let size = term.get_screen_size()?;
+    let wrapped = textwrap::fill(message, size.cols.saturating_sub(4).max(1));
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorVisibility(CursorVisibility::Hidden),
+    ];
+    for (y, line) in wrapped.lines().enumerate() {
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(2),
+            y: Position::Absolute(1 + y),
+        });
+        changes.push(Change::Text(line.to_string()));
+    }
+    changes.push(Change::CursorPosition {
+        x: Position::Absolute(2),
+        y: Position::Absolute(wrapped.lines().count() + 2),
+    });
+    changes.push(Change::Text("Press any key to close".to_string()));
+    term.render(&changes)?;
+    term.flush()?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        if let InputEvent::Key(_) = event {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub fn upload_dropped_files(
+    request: UploadRequest,
+    mut term: TermWizTerminal,
+) -> anyhow::Result<()> {
+    term.set_raw_mode()?;
+
+    let mut files = vec![];
+    for path in &request.paths {
+        let is_file = std::fs::metadata(path)
+            .map(|meta| meta.is_file())
+            .unwrap_or(false);
+        let name = path.file_name().map(|name| name.to_string_lossy());
+        match (is_file, name) {
+            (true, Some(name)) => files.push(LocalFile {
+                path: path.clone(),
+                name: name.into_owned(),
+                size: std::fs::metadata(path)?.len(),
+            }),
+            _ => {
+                return show_message(
+                    &mut term,
+                    &format!(
+                        "{} can't be uploaded; only files can be uploaded",
+                        path.display()
+                    ),
+                )
+            }
+        }
+    }
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let dir = match request.remote_cwd {
+        Some(dir) => dir,
+        None => match smol::block_on(request.session.sftp().canonicalize(".")) {
+            Ok(dir) => dir.to_string(),
+            Err(err) => {
+                return show_message(
+                    &mut term,
+                    &format!("Unable to determine the remote directory: {err:#}"),
+                )
+            }
+        },
+    };
+    let destination = format!("{}:{}", request.domain_name, dir);
+
+    let mut message = format!(
+        "Upload {} to {}?\n",
+        if files.len() == 1 {
+            "this file".to_string()
+        } else {
+            format!("these {} files", files.len())
+        },
+        destination
+    );
+    for file in &files {
+        message.push_str(&format!("\n  {} ({})", file.name, format_size(file.size)));
+    }
+    if !run_confirmation_app(&message, &mut term)? {
+        return Ok(());
+    }
+
+    let upload = Arc::new(Upload {
+        files,
+        destination,
+        progress: UploadProgress::default(),
+    });
+    let window = request.window;
+    let pane_id = request.pane_id;
+    let session = request.session;
+
+    window.notify(TermWindowNotif::Apply(Box::new({
+        let upload = Arc::clone(&upload);
+        move |term_window| term_window.start_upload(upload)
+    })));
+
+    std::thread::spawn(move || {
+        let on_progress = || {
+            window.notify(TermWindowNotif::Apply(Box::new(|term_window| {
+                term_window.update_title_post_status();
+            })))
+        };
+        let result = smol::block_on(upload_files(
+            &session,
+            &upload.files,
+            &dir,
+            &upload.progress,
+            &on_progress,
+        ));
+        let cancelled = upload.progress.cancel.load(Ordering::Relaxed);
+        window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+            term_window.finish_upload(&upload);
+            match result {
+                Ok(uploaded) => paste_uploaded_paths(pane_id, &uploaded),
+                Err(_) if cancelled => {}
+                Err(err) => {
+                    log::error!("uploading to {}: {:#}", upload.destination, err);
+                    wezterm_toast_notification::persistent_toast_notification(
+                        "Upload failed",
+                        &format!("Uploading to {}: {:#}", upload.destination, err),
+                    );
+                }
+            }
+        })));
+    });
+
+    Ok(())
+}
+
+/// Pastes the remote paths of the uploaded files into the pane
+fn paste_uploaded_paths(pane_id: PaneId, uploaded: &[String]) {
+    // The remote shell is assumed to be a POSIX shell,
+    // as it is for the other operations of ssh domains
+    let text = uploaded
+        .iter()
+        .map(|path| DroppedFileQuoting::Posix.escape(path))
+        .collect::<Vec<_>>()
+        .join(" ")
+        + " ";
+    if let Some(pane) = Mux::get().get_pane(pane_id) {
+        if let Err(err) = pane.send_paste(&text) {
+            log::error!("pasting uploaded paths: {:#}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn upload(sizes: &[u64]) -> Upload {
+        Upload {
+            files: sizes
+                .iter()
+                .enumerate()
+                .map(|(idx, &size)| LocalFile {
+                    path: PathBuf::from(format!("/tmp/file{idx}")),
+                    name: format!("file{idx}"),
+                    size,
+                })
+                .collect(),
+            destination: "host:/home/me".to_string(),
+            progress: UploadProgress::default(),
+        }
+    }
+
+    #[test]
+    fn status() {
+        let single = upload(&[4096]);
+        single.progress.bytes.store(1024, Ordering::Relaxed);
+        assert_eq!(
+            single.status(),
+            "Uploading file0 to host:/home/me: 25% of 4.0 KiB; middle-click to cancel "
+        );
+
+        let several = upload(&[10, 0]);
+        several.progress.file_idx.store(1, Ordering::Relaxed);
+        assert_eq!(
+            several.status(),
+            "Uploading file1 (2 of 2) to host:/home/me: 100% of 0 B; middle-click to cancel "
+        );
+
+        several.cancel();
+        assert_eq!(several.status(), "Cancelling upload... ");
+    }
+}
@@ -1,6 +1,8 @@
 //! GuiWin represents a Gui TermWindow (as opposed to a Mux window) in lua code
 use super::luaerr;
+use crate::termwindow::render::CacheCounter;
 use crate::termwindow::TermWindowNotif;
+use crate::utilsprites::CellMetricsOverride;
 use crate::TermWindow;
 use config::keyassignment::{ClipboardCopyDestination, KeyAssignment};
 use luahelper::*;
@@ -14,6 +16,15 @@ use wezterm_dynamic::{FromDynamic, ToDynamic};
 use wezterm_toast_notification::ToastNotification;
 use window::{Connection, ConnectionOps, DeadKeyStatus, WindowOps, WindowState};
 
+#[derive(Debug, Default, FromDynamic, ToDynamic)]
+struct CellMetricsArgs {
+    #[dynamic(default)]
+    line_height: Option<f64>,
+    #[dynamic(default)]
+    cell_width: Option<f64>,
+}
+impl_lua_conversion_dynamic!(CellMetricsArgs);
+
 #[derive(Clone)]
 pub struct GuiWin {
     pub mux_window_id: MuxWindowId,
@@ -45,6 +56,23 @@ impl UserData for GuiWin {
         methods.add_method("mux_window", |_, this, _: ()| {
             Ok(mux_lua::MuxWindow(this.mux_window_id))
         });
+        methods.add_async_method("effective_workspace_style", |lua, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window
+                .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    tx.try_send(term_window.workspace_style.clone()).ok();
+                })));
+            let style = rx
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .map_err(luaerr)?;
+
+            match style {
+                Some(style) => dynamic_to_lua_value(lua, style.to_dynamic()),
+                None => Ok(mlua::Value::Nil),
+            }
+        });
         methods.add_method("active_tab", |_, this, _: ()| {
             let mux = Mux::try_get().ok_or_else(|| mlua::Error::external("cannot get Mux!?"))?;
             let window = mux.get_window(this.mux_window_id).ok_or_else(|| {
@@ -61,6 +89,44 @@ impl UserData for GuiWin {
                 Ok(())
             },
         );
+        methods.add_method(
+            "set_cell_metrics",
+            |_, this, metrics: Option<CellMetricsArgs>| {
+                let overrides = match metrics {
+                    Some(metrics) => {
+                        for (name, value) in [
+                            ("line_height", metrics.line_height),
+                            ("cell_width", metrics.cell_width),
+                        ] {
+                            if let Some(value) = value {
+                                if !value.is_finite() || value <= 0.0 {
+                                    return Err(mlua::Error::external(format!(
+                                        "{name} must be a positive number, got {value}"
+                                    )));
+                                }
+                            }
+                        }
+                        CellMetricsOverride {
+                            line_height: metrics.line_height,
+                            cell_width: metrics.cell_width,
+                        }
+                    }
+                    None => CellMetricsOverride::default(),
+                };
+                this.window
+                    .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                        term_window.set_cell_metrics_override(overrides);
+                    })));
+                Ok(())
+            },
+        );
+        methods.add_method("set_integer_scaling", |_, this, enabled: Option<bool>| {
+            this.window
+                .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    term_window.set_integer_scaling(enabled);
+                })));
+            Ok(())
+        });
         methods.add_method("set_position", |_, this, (x, y): (isize, isize)| {
             this.window.set_window_position(euclid::point2(x, y));
             Ok(())
@@ -88,7 +154,9 @@ impl UserData for GuiWin {
                     title,
                     message,
                     url,
-                    timeout: timeout.map(std::time::Duration::from_millis)
+                    timeout: timeout.map(std::time::Duration::from_millis),
+                    actions: vec![],
+                    on_action: None,
                 });
                 Ok(())
             },
@@ -131,6 +199,20 @@ impl UserData for GuiWin {
             };
             Ok(dims)
         });
+        methods.add_async_method("active_screen", |_, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window
+                .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    tx.try_send(term_window.active_screen()).ok();
+                })));
+            let screen = rx
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .map_err(luaerr)?
+                .map_err(luaerr)?;
+            Ok(window_funcs::ScreenInfo::from(screen))
+        });
         methods.add_async_method(
             "get_selection_text_for_pane",
             |_, this, pane: UserDataRef<MuxPane>| async move {
@@ -212,6 +294,75 @@ impl UserData for GuiWin {
 
             Ok(result)
         });
+        methods.add_async_method("render_cache_stats", |_, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window
+                .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    tx.try_send(term_window.render_cache_stats()).ok();
+                })));
+            let stats = rx
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .map_err(luaerr)?;
+
+            #[derive(FromDynamic, ToDynamic)]
+            struct Counter {
+                hits: u64,
+                misses: u64,
+                invalidated: u64,
+                hit_rate: f64,
+            }
+            #[derive(FromDynamic, ToDynamic)]
+            struct Stats {
+                line_quads: Counter,
+                line_shapes: Counter,
+            }
+            impl_lua_conversion_dynamic!(Stats);
+
+            fn counter(c: CacheCounter) -> Counter {
+                Counter {
+                    hits: c.hits,
+                    misses: c.misses,
+                    invalidated: c.invalidated,
+                    hit_rate: c.hit_rate(),
+                }
+            }
+
+            Ok(Stats {
+                line_quads: counter(stats.line_quads),
+                line_shapes: counter(stats.line_shapes),
+            })
+        });
+        methods.add_async_method("frame_stats", |_, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window
+                .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    tx.try_send(term_window.frame_stats()).ok();
+                })));
+            let stats = rx
+                .recv()
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))
+                .map_err(luaerr)?;
+
+            #[derive(FromDynamic, ToDynamic)]
+            struct Stats {
+                fps: f32,
+                animation_sources: Vec<String>,
+            }
+            impl_lua_conversion_dynamic!(Stats);
+
+            Ok(Stats {
+                fps: stats.fps,
+                animation_sources: stats
+                    .animation_sources
+                    .names()
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            })
+        });
         methods.add_async_method("leader_is_active", |_, this, _: ()| async move {
             let (tx, rx) = smol::channel::bounded(1);
             this.window
@@ -232,7 +383,7 @@ impl UserData for GuiWin {
                 .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
                     tx.try_send(match term_window.composition_status() {
                         DeadKeyStatus::None => None,
-                        DeadKeyStatus::Composing(s) => Some(s.clone()),
+                        DeadKeyStatus::Composing(s) => Some(s.text.clone()),
                     })
                     .ok();
                 })));
@@ -258,6 +409,16 @@ impl UserData for GuiWin {
 
             Ok(result)
         });
+        methods.add_async_method("active_key_tables", |lua, this, _: ()| async move {
+            let (tx, rx) = smol::channel::bounded(1);
+            this.window
+                .notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    tx.try_send(term_window.active_key_tables().to_dynamic())
+                        .ok();
+                })));
+            let result = rx.recv().await.map_err(mlua::Error::external)?;
+            dynamic_to_lua_value(lua, result)
+        });
         methods.add_async_method("keyboard_modifiers", |_, this, _: ()| async move {
             let (tx, rx) = smol::channel::bounded(1);
             this.window
@@ -7,6 +7,7 @@ use config::{DeferredKeyCode, GpuInfo, Key, KeyNoAction};
 use luahelper::dynamic_to_lua_value;
 use mux::window::WindowId as MuxWindowId;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use wezterm_dynamic::ToDynamic;
 
 pub mod guiwin;
@@ -56,6 +57,26 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
         })?,
     )?;
 
+    window_mod.set(
+        "get_focus_history",
+        lua.create_function(|lua, _: ()| {
+            let fe =
+                try_front_end().ok_or_else(|| mlua::Error::external("not called on gui thread"))?;
+            let mut result = vec![];
+            for event in fe.focus_history() {
+                let entry = lua.create_table()?;
+                entry.set("window", mux_lua::MuxWindow(event.mux_window_id))?;
+                entry.set("workspace", event.workspace)?;
+                if let Some(pane_id) = event.pane_id {
+                    entry.set("pane", mux_lua::MuxPane(pane_id))?;
+                }
+                entry.set("time", event.time.to_rfc3339())?;
+                result.push(entry);
+            }
+            Ok(result)
+        })?,
+    )?;
+
     window_mod.set(
         "default_keys",
         lua.create_function(|lua, _: ()| {
@@ -78,6 +99,40 @@ pub fn register(lua: &Lua) -> anyhow::Result<()> {
         })?,
     )?;
 
+    window_mod.set(
+        "get_runtime_paths",
+        lua.create_function(|lua, _: ()| {
+            let paths = lua.create_table()?;
+            let set =
+                |name: &str, path: PathBuf| paths.set(name, path.to_string_lossy().to_string());
+            set("runtime_dir", config::RUNTIME_DIR.clone())?;
+            set("data_dir", config::DATA_DIR.clone())?;
+            set("gui_socket", wezterm_client::discovery::gui_sock_path())?;
+            if let Some(log_file) = env_bootstrap::ringlog::log_file_name() {
+                set("log_file", log_file)?;
+            }
+            set("session_file", mux::session::default_session_path())?;
+            set(
+                "recent_commands_file",
+                crate::termwindow::palette::recent_file_name(),
+            )?;
+            set(
+                "recent_emoji_file",
+                crate::termwindow::charselect::recent_file_name(),
+            )?;
+            set("update_check_file", crate::update::update_file_name())?;
+            set(
+                "terminfo_assist_file",
+                mux::terminfo_assist::choices_file_name(),
+            )?;
+            set(
+                "trusted_hosts_file",
+                wezterm_client::pinning::known_hosts_file_name(),
+            )?;
+            Ok(paths)
+        })?,
+    )?;
+
     window_mod.set(
         "enumerate_gpus",
         lua.create_function(|_, _: ()| {
@@ -47,6 +47,15 @@ impl ScrollHit {
         }
     }
 
+    /// Compute the y-coordinate of a row within the scrollback,
+    /// relative to the top of the scrollbar track.
+    pub fn row_to_offset(pane: &dyn Pane, row: StableRowIndex, max_thumb_height: usize) -> usize {
+        let render_dims = pane.get_dimensions();
+        let scroll_size = render_dims.scrollback_rows.max(1) as f32;
+        let row = row.saturating_sub(render_dims.scrollback_top).max(0) as f32;
+        ((row / scroll_size).min(1.0) * max_thumb_height as f32) as usize
+    }
+
     /// Given a new thumb top coordinate (produced by dragging the thumb),
     /// compute the equivalent viewport offset.
     pub fn thumb_top_to_scroll_top(
@@ -5,7 +5,7 @@ use mux::pane::Pane;
 use std::cmp::Ordering;
 use std::ops::Range;
 use termwiz::surface::line::DoubleClickRange;
-use termwiz::surface::SequenceNo;
+use termwiz::surface::{Line, SequenceNo};
 use wezterm_term::{SemanticZone, StableRowIndex};
 
 #[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
@@ -164,14 +164,35 @@ pub struct SelectionRange {
     pub end: SelectionCoordinate,
 }
 
-fn is_double_click_word(s: &str) -> bool {
+fn is_double_click_word(s: &str, boundary: &str) -> bool {
     match s.chars().count() {
-        1 => !config::configuration().selection_word_boundary.contains(s),
+        1 => !boundary.contains(s),
         0 => false,
         _ => true,
     }
 }
 
+/// Returns the range of cells of `line` that hold the same hyperlink
+/// as the cell at `idx`, if that cell has one
+fn hyperlink_range(line: &Line, idx: usize) -> Option<Range<usize>> {
+    let cells = line.visible_cells().collect::<Vec<_>>();
+    let pos = cells
+        .iter()
+        .position(|cell| cell.cell_index() <= idx && idx < cell.cell_index() + cell.width())?;
+    let link = cells[pos].attrs().hyperlink()?;
+    let same_link = |pos: usize| cells[pos].attrs().hyperlink() == Some(link);
+
+    let mut lower = pos;
+    while lower > 0 && same_link(lower - 1) {
+        lower -= 1;
+    }
+    let mut upper = pos;
+    while upper + 1 < cells.len() && same_link(upper + 1) {
+        upper += 1;
+    }
+    Some(cells[lower].cell_index()..cells[upper].cell_index() + cells[upper].width())
+}
+
 impl SelectionRange {
     /// Create a new range that starts at the specified location
     pub fn start(start: SelectionCoordinate) -> Self {
@@ -236,8 +257,9 @@ impl SelectionRange {
         }
     }
 
-    /// Computes the selection range for the word around the specified coords
-    pub fn word_around(start: SelectionCoordinate, pane: &dyn Pane) -> Self {
+    /// Computes the selection range for the word around the specified coords,
+    /// where words are separated by the characters in `boundary`
+    pub fn word_around(start: SelectionCoordinate, pane: &dyn Pane, boundary: &str) -> Self {
         for logical in pane.get_logical_lines(start.y..start.y + 1) {
             if !logical.contains_y(start.y) {
                 continue;
@@ -247,7 +269,7 @@ impl SelectionRange {
                 let start_idx = logical.xy_to_logical_x(start_x, start.y);
                 return match logical
                     .logical
-                    .compute_double_click_range(start_idx, is_double_click_word)
+                    .compute_double_click_range(start_idx, |s| is_double_click_word(s, boundary))
                 {
                     DoubleClickRange::RangeWithWrap(click_range)
                     | DoubleClickRange::Range(click_range) => {
@@ -268,6 +290,31 @@ impl SelectionRange {
         Self { start, end: start }
     }
 
+    /// Computes the selection range for the hyperlink around the specified
+    /// coords, if there is one.  The hyperlink rules must already have been
+    /// applied to the line, so that this finds the same link that clicking
+    /// on it would open.
+    pub fn hyperlink_around(start: SelectionCoordinate, pane: &dyn Pane) -> Option<Self> {
+        let start_x = match start.x {
+            SelectionX::Cell(x) => x,
+            _ => return None,
+        };
+        for logical in pane.get_logical_lines(start.y..start.y + 1) {
+            if !logical.contains_y(start.y) {
+                continue;
+            }
+            let start_idx = logical.xy_to_logical_x(start_x, start.y);
+            let link_range = hyperlink_range(&logical.logical, start_idx)?;
+            let (start_y, start_x) = logical.logical_x_to_physical_coord(link_range.start);
+            let (end_y, end_x) = logical.logical_x_to_physical_coord(link_range.end - 1);
+            return Some(Self {
+                start: SelectionCoordinate::x_y(start_x, start_y),
+                end: SelectionCoordinate::x_y(end_x, end_y),
+            });
+        }
+        None
+    }
+
     /// Extends the current selection by unioning it with another selection range
     pub fn extend_with(&self, other: Self) -> Self {
         let norm = self.normalize();
@@ -355,3 +402,66 @@ impl SelectionRange {
         }
     }
 }
+
+/// Builds the wrapped lines and selections that the tests of
+/// copying and of copy mode operate on
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::*;
+    use mux::pane::LogicalLine;
+    use termwiz::cell::CellAttributes;
+    use termwiz::surface::SEQ_ZERO;
+
+    /// Builds a logical line starting at first_row from
+    /// rows, all but the last of which are marked as wrapped
+    pub fn logical_line(first_row: StableRowIndex, rows: &[&str]) -> LogicalLine {
+        let physical_lines: Vec<Line> = rows
+            .iter()
+            .enumerate()
+            .map(|(idx, text)| {
+                let mut line = Line::from_text(text, &CellAttributes::default(), SEQ_ZERO, None);
+                if idx + 1 < rows.len() {
+                    line.set_last_cell_was_wrapped(true, SEQ_ZERO);
+                }
+                line
+            })
+            .collect();
+
+        let mut logical = physical_lines[0].clone();
+        for line in &physical_lines[1..] {
+            logical.set_last_cell_was_wrapped(false, SEQ_ZERO);
+            logical.append_line(line.clone(), SEQ_ZERO);
+        }
+
+        LogicalLine {
+            physical_lines,
+            logical,
+            first_row,
+        }
+    }
+
+    pub fn range(start: (usize, StableRowIndex), end: (usize, StableRowIndex)) -> SelectionRange {
+        SelectionRange {
+            start: SelectionCoordinate::x_y(start.0, start.1),
+            end: SelectionCoordinate::x_y(end.0, end.1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use termwiz::hyperlink::Rule;
+
+    #[test]
+    fn hyperlink_range_spans_link() {
+        let rules = vec![Rule::new(r"\b\w+://(?:[\w.-]+)\.[a-z]{2,15}\S*\b", "$0").unwrap()];
+        let mut line: Line = "see http://example.com/a-b.txt now".into();
+        line.scan_and_create_hyperlinks(&rules);
+
+        assert_eq!(hyperlink_range(&line, 4), Some(4..30));
+        assert_eq!(hyperlink_range(&line, 29), Some(4..30));
+        assert_eq!(hyperlink_range(&line, 1), None);
+        assert_eq!(hyperlink_range(&line, 30), None);
+    }
+}
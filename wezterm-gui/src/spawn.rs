@@ -2,11 +2,13 @@ use anyhow::{anyhow, bail, Context};
 use config::keyassignment::SpawnCommand;
 use config::TermConfig;
 use mux::activity::Activity;
-use mux::domain::SplitSource;
-use mux::tab::SplitRequest;
+use mux::domain::{InheritEnvironment, SplitSource};
+use mux::pane::Pane;
+use mux::tab::{FloatingPaneGeometry, SplitRequest};
 use mux::window::WindowId as MuxWindowId;
 use mux::Mux;
 use portable_pty::CommandBuilder;
+use std::collections::HashMap;
 use std::sync::Arc;
 use wezterm_term::TerminalSize;
 
@@ -15,6 +17,7 @@ pub enum SpawnWhere {
     NewWindow,
     NewTab,
     SplitPane(SplitRequest),
+    FloatingPane(FloatingPaneGeometry),
 }
 
 pub fn spawn_command_impl(
@@ -36,13 +39,14 @@ pub fn spawn_command_impl(
     .detach();
 }
 
+/// Spawns the command, returning the newly created pane
 pub async fn spawn_command_internal(
     spawn: SpawnCommand,
     spawn_where: SpawnWhere,
     size: TerminalSize,
     src_window_id: Option<MuxWindowId>,
     term_config: Arc<TermConfig>,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<Arc<dyn Pane>> {
     let mux = Mux::get();
     let activity = Activity::new();
 
@@ -57,6 +61,16 @@ pub async fn spawn_command_internal(
         None => None,
     };
 
+    // The profile is resolved first, as it can change the program,
+    // its environment and the domain in which it is spawned
+    let profile = mux.resolve_spawn_profile(spawn.profile.as_deref(), current_pane_id)?;
+    let spawn = SpawnCommand {
+        domain: mux.resolve_profile_domain(profile.as_deref(), spawn.domain),
+        profile: profile.clone(),
+        ..spawn
+    };
+    let spawn_command = mux.with_spawn_command(spawn.clone());
+
     let cwd = if let Some(cwd) = spawn.cwd.as_ref() {
         Some(cwd.to_str().map(|s| s.to_owned()).ok_or_else(|| {
             anyhow!(
@@ -82,8 +96,27 @@ pub async fn spawn_command_internal(
     };
 
     let workspace = mux.active_workspace().clone();
+    let inherit_env = InheritEnvironment::from_spawn_command(&spawn);
+    // Splits take their environment from the pane that they split,
+    // which is resolved as part of the split.  Otherwise it comes from
+    // the current pane, and its domain resolves it, as its foreground
+    // process may only be accessible to a remote mux.
+    let inherited_env = match current_pane_id.and_then(|id| mux.get_pane(id)) {
+        Some(pane)
+            if !inherit_env.is_empty() && !matches!(spawn_where, SpawnWhere::SplitPane(_)) =>
+        {
+            match mux.get_domain(pane.domain_id()) {
+                Some(domain) => domain
+                    .resolve_inherited_env(&pane, &inherit_env)
+                    .await
+                    .context("resolve_inherited_env")?,
+                None => HashMap::new(),
+            }
+        }
+        _ => HashMap::new(),
+    };
 
-    match spawn_where {
+    let pane = match spawn_where {
         SpawnWhere::SplitPane(direction) => {
             let src_window_id = match src_window_id {
                 Some(id) => id,
@@ -103,16 +136,39 @@ pub async fn spawn_command_internal(
                         SplitSource::Spawn {
                             command: cmd_builder,
                             command_dir: cwd,
+                            inherit_env: inherit_env.clone(),
                         },
                         spawn.domain,
                     )
                     .await
                     .context("split_pane")?;
                 pane.set_config(term_config);
+                pane
             } else {
                 bail!("there is no active tab while splitting pane!?");
             }
         }
+        SpawnWhere::FloatingPane(geometry) => {
+            let src_window_id = match src_window_id {
+                Some(id) => id,
+                None => anyhow::bail!("no src window when spawning a floating pane?"),
+            };
+            let tab = mux
+                .get_active_tab_for_window(src_window_id)
+                .ok_or_else(|| anyhow!("there is no active tab to float a pane over"))?;
+            let pane = mux
+                .spawn_floating_pane(
+                    tab.tab_id(),
+                    geometry,
+                    InheritEnvironment::merge(inherited_env, cmd_builder),
+                    cwd,
+                    spawn.domain,
+                )
+                .await
+                .context("spawn_floating_pane")?;
+            pane.set_config(term_config);
+            pane
+        }
         _ => {
             let (_tab, pane, window_id) = mux
                 .spawn_tab_or_window(
@@ -121,7 +177,7 @@ pub async fn spawn_command_internal(
                         _ => src_window_id,
                     },
                     spawn.domain,
-                    cmd_builder,
+                    InheritEnvironment::merge(inherited_env, cmd_builder),
                     cwd,
                     size,
                     current_pane_id,
@@ -136,11 +192,20 @@ pub async fn spawn_command_internal(
             // the new window being created.
             if Some(window_id) == src_window_id {
                 pane.set_config(term_config);
+            } else if let Some(mut window) = mux.get_window_mut(window_id) {
+                // The profile applies to the whole of a new window
+                window.set_profile(profile.clone());
             }
+            pane
         }
     };
 
+    if let Some(profile) = &profile {
+        mux.set_pane_profile(&pane, profile)?;
+    }
+
+    drop(spawn_command);
     drop(activity);
 
-    Ok(())
+    Ok(pane)
 }
@@ -1,7 +1,8 @@
 use crate::termwindow::{PaneInformation, TabInformation, UIItem, UIItemType};
-use config::{ConfigHandle, TabBarColors};
+use config::{ConfigHandle, TabBarColors, TabBarOverflow, TabBarPosition};
 use finl_unicode::grapheme_clusters::Graphemes;
 use mlua::FromLua;
+use std::ops::Range;
 use termwiz::cell::{unicode_column_width, Cell, CellAttributes};
 use termwiz::color::ColorSpec;
 use termwiz::escape::csi::Sgr;
@@ -14,8 +15,20 @@ use window::{IntegratedTitleButton, IntegratedTitleButtonAlignment, IntegratedTi
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct TabBarState {
-    line: Line,
+    lines: Vec<Line>,
     items: Vec<TabEntry>,
+    first_tab: usize,
+}
+
+/// The scroll position of the tab bar when tab_bar_overflow
+/// is set to Scroll
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TabBarScroll {
+    /// The index of the first tab to display
+    pub first_tab: usize,
+    /// If true, first_tab is adjusted so that the
+    /// active tab is visible
+    pub reveal_active: bool,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -25,6 +38,8 @@ pub enum TabBarItem {
     RightStatus,
     Tab { tab_idx: usize, active: bool },
     NewTabButton,
+    ScrollLeft,
+    ScrollRight,
     WindowButton(IntegratedTitleButton),
 }
 
@@ -34,6 +49,7 @@ pub struct TabEntry {
     pub title: Line,
     x: usize,
     width: usize,
+    pub row: usize,
 }
 
 #[derive(Clone, Debug)]
@@ -122,6 +138,19 @@ fn compute_tab_title(
                 } else {
                     tab.tab_title.clone()
                 };
+                // Mark tabs with pending activity or silence alerts,
+                // using the same flags as tmux
+                if pane.has_silence_alert {
+                    title = format!("~ {title}");
+                } else if pane.has_activity_alert {
+                    title = format!("# {title}");
+                }
+                if pane.is_output_rate_limited {
+                    title = format!("{title} (output throttled)");
+                }
+                if tab.has_paused_pane {
+                    title = format!("{title} (paused)");
+                }
                 let classic_spacing = if config.use_fancy_tab_bar { "" } else { " " };
                 if config.show_tab_index_in_tab_bar {
                     title = format!(
@@ -159,6 +188,103 @@ fn compute_tab_title(
     }
 }
 
+fn call_format_pane_title(
+    pane: &PaneInformation,
+    config: &ConfigHandle,
+    hover: bool,
+    max_width: usize,
+) -> Option<String> {
+    match config::run_immediate_with_lua_config(|lua| {
+        if let Some(lua) = lua {
+            let v = config::lua::emit_sync_callback(
+                &*lua,
+                (
+                    "format-pane-title".to_string(),
+                    (pane.clone(), (**config).clone(), hover, max_width),
+                ),
+            )?;
+            match &v {
+                mlua::Value::Nil => Ok(None),
+                mlua::Value::Table(_) => {
+                    let items = <Vec<FormatItem>>::from_lua(v, &*lua)?;
+                    Ok(Some(format_as_escapes(items)?))
+                }
+                _ => Ok(Some(String::from_lua(v, &*lua)?)),
+            }
+        } else {
+            Ok(None)
+        }
+    }) {
+        Ok(s) => s,
+        Err(err) => {
+            log::warn!("format-pane-title: {}", err);
+            None
+        }
+    }
+}
+
+/// Computes the text shown in the title bar of a pane, which is
+/// at most `max_width` cells wide
+pub fn compute_pane_title(
+    pane: &PaneInformation,
+    domain_name: &str,
+    config: &ConfigHandle,
+    hover: bool,
+    max_width: usize,
+) -> Line {
+    let title = call_format_pane_title(pane, config, hover, max_width).unwrap_or_else(|| {
+        let mut title = pane.title.clone();
+        if domain_name != "local" {
+            title = format!("{title} ({domain_name})");
+        }
+        if pane.is_zoomed {
+            title = format!("[Z] {title}");
+        }
+        title
+    });
+    let mut line = parse_status_text(&title, CellAttributes::default());
+    if line.len() > max_width {
+        line.resize(max_width, SEQ_ZERO);
+    }
+    line
+}
+
+/// Computes the range of tabs that can be displayed in `available`
+/// cells, starting from the scroll position, and adjusting it so
+/// that the active tab is visible when requested.
+fn visible_tab_range(
+    widths: &[usize],
+    available: usize,
+    active_tab: usize,
+    scroll: TabBarScroll,
+) -> Range<usize> {
+    if widths.is_empty() {
+        return 0..0;
+    }
+    let width_of = |range: Range<usize>| -> usize { widths[range].iter().sum() };
+
+    let mut first = scroll.first_tab.min(widths.len() - 1);
+    if scroll.reveal_active && active_tab < widths.len() {
+        first = first.min(active_tab);
+        while first < active_tab && width_of(first..active_tab + 1) > available {
+            first += 1;
+        }
+    }
+
+    // Don't leave a gap after the last tab
+    while first > 0 && width_of(first - 1..widths.len()) <= available {
+        first -= 1;
+    }
+
+    // Always show at least one tab
+    let mut last = first + 1;
+    while last < widths.len() && width_of(first..last + 1) <= available {
+        last += 1;
+    }
+
+    first..last
+}
+
 fn is_tab_hover(mouse_x: Option<usize>, x: usize, tab_title_len: usize) -> bool {
     return mouse_x
         .map(|mouse_x| mouse_x >= x && mouse_x < x + tab_title_len)
@@ -168,18 +294,30 @@ fn is_tab_hover(mouse_x: Option<usize>, x: usize, tab_title_len: usize) -> bool
 impl TabBarState {
     pub fn default() -> Self {
         Self {
-            line: Line::with_width(1, SEQ_ZERO),
+            lines: vec![Line::with_width(1, SEQ_ZERO)],
             items: vec![TabEntry {
                 item: TabBarItem::None,
                 title: Line::from_text(" ", &CellAttributes::blank(), 1, None),
                 x: 1,
                 width: 1,
+                row: 0,
             }],
+            first_tab: 0,
         }
     }
 
-    pub fn line(&self) -> &Line {
-        &self.line
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// Returns the number of rows occupied by the tab bar
+    pub fn rows(&self) -> usize {
+        self.lines.len().max(1)
+    }
+
+    /// Returns the index of the first tab that is displayed
+    pub fn first_visible_tab(&self) -> usize {
+        self.first_tab
     }
 
     pub fn items(&self) -> &[TabEntry] {
@@ -267,54 +405,145 @@ impl TabBarState {
                 title: title.to_owned(),
                 x: *x,
                 width,
+                row: 0,
             });
 
             *x += width;
         }
     }
 
+    /// Returns the number of cells occupied by the integrated title
+    /// buttons, so that space can be reserved for them
+    fn integrated_title_buttons_width(config: &ConfigHandle) -> usize {
+        let window_hide =
+            parse_status_text(&config.tab_bar_style.window_hide, CellAttributes::default());
+        let window_hide_hover = parse_status_text(
+            &config.tab_bar_style.window_hide_hover,
+            CellAttributes::default(),
+        );
+
+        let window_maximize = parse_status_text(
+            &config.tab_bar_style.window_maximize,
+            CellAttributes::default(),
+        );
+        let window_maximize_hover = parse_status_text(
+            &config.tab_bar_style.window_maximize_hover,
+            CellAttributes::default(),
+        );
+        let window_close = parse_status_text(
+            &config.tab_bar_style.window_close,
+            CellAttributes::default(),
+        );
+        let window_close_hover = parse_status_text(
+            &config.tab_bar_style.window_close_hover,
+            CellAttributes::default(),
+        );
+
+        let hide_len = window_hide.len().max(window_hide_hover.len());
+        let maximize_len = window_maximize.len().max(window_maximize_hover.len());
+        let close_len = window_close.len().max(window_close_hover.len());
+
+        let mut width_to_reserve = 0;
+        for button in &config.integrated_title_buttons {
+            use IntegratedTitleButton as Button;
+            let button_len = match button {
+                Button::Hide => hide_len,
+                Button::Maximize => maximize_len,
+                Button::Close => close_len,
+            };
+            width_to_reserve += button_len;
+        }
+        width_to_reserve
+    }
+
+    /// Returns true if an item that is `width` cells wide must start
+    /// a new row rather than being placed at `x` on the current one.
+    /// An item that is wider than the row is placed on a row of its own.
+    fn needs_new_row(
+        x: usize,
+        width: usize,
+        row_width: usize,
+        overflow: TabBarOverflow,
+        vertical: bool,
+    ) -> bool {
+        x > 0 && (vertical || (overflow == TabBarOverflow::Wrap && x + width > row_width))
+    }
+
+    /// Pads out the current row and starts a new one
+    fn wrap_row(
+        lines: &mut Vec<Line>,
+        line: &mut Line,
+        x: &mut usize,
+        row: &mut usize,
+        row_width: usize,
+        blank: &Cell,
+    ) {
+        while line.len() < row_width {
+            line.insert_cell(line.len(), blank.clone(), row_width, SEQ_ZERO);
+        }
+        lines.push(std::mem::replace(line, Line::with_width(0, SEQ_ZERO)));
+        *x = 0;
+        *row += 1;
+    }
+
     /// Build a new tab bar from the current state
-    /// mouse_x is some if the mouse is on the same row as the tab bar.
+    /// mouse_x is some if the mouse is on the same row as the tab bar;
+    /// mouse_row is the row of the tab bar that the mouse is over.
     /// title_width is the total number of cell columns in the window.
     /// window allows access to the tabs associated with the window.
+    /// scroll is the scroll position to use when tab_bar_overflow
+    /// is set to Scroll.
+    /// When the tab bar is positioned at the left or right of the
+    /// window, title_width is the width of that strip and each
+    /// item is placed on its own row.
     pub fn new(
         title_width: usize,
         mouse_x: Option<usize>,
+        mouse_row: usize,
         tab_info: &[TabInformation],
         pane_info: &[PaneInformation],
         colors: Option<&TabBarColors>,
         config: &ConfigHandle,
         left_status: &str,
         right_status: &str,
+        scroll: TabBarScroll,
     ) -> Self {
         let colors = colors.cloned().unwrap_or_else(TabBarColors::default);
 
         let active_cell_attrs = colors.active_tab().as_cell_attributes();
         let inactive_hover_attrs = colors.inactive_tab_hover().as_cell_attributes();
         let inactive_cell_attrs = colors.inactive_tab().as_cell_attributes();
+        let bell_cell_attrs = colors.bell_tab().as_cell_attributes();
         let new_tab_hover_attrs = colors.new_tab_hover().as_cell_attributes();
         let new_tab_attrs = colors.new_tab().as_cell_attributes();
 
-        let new_tab = parse_status_text(
-            &config.tab_bar_style.new_tab,
+        let button_attrs = |hover: bool| {
             if config.use_fancy_tab_bar {
                 CellAttributes::default()
+            } else if hover {
+                new_tab_hover_attrs.clone()
             } else {
                 new_tab_attrs.clone()
-            },
-        );
-        let new_tab_hover = parse_status_text(
-            &config.tab_bar_style.new_tab_hover,
-            if config.use_fancy_tab_bar {
-                CellAttributes::default()
-            } else {
-                new_tab_hover_attrs.clone()
-            },
-        );
+            }
+        };
 
-        let use_integrated_title_buttons = config
-            .window_decorations
-            .contains(window::WindowDecorations::INTEGRATED_BUTTONS);
+        let new_tab = parse_status_text(&config.tab_bar_style.new_tab, button_attrs(false));
+        let new_tab_hover =
+            parse_status_text(&config.tab_bar_style.new_tab_hover, button_attrs(true));
+
+        let scroll_left = parse_status_text(" \u{2039} ", button_attrs(false));
+        let scroll_left_hover = parse_status_text(" \u{2039} ", button_attrs(true));
+        let scroll_right = parse_status_text(" \u{203a} ", button_attrs(false));
+        let scroll_right_hover = parse_status_text(" \u{203a} ", button_attrs(true));
+
+        let vertical = config.effective_tab_bar_position().is_vertical();
+
+        // The integrated buttons belong in a title bar, so they are
+        // not shown when the tabs are arranged vertically
+        let use_integrated_title_buttons = !vertical
+            && config
+                .window_decorations
+                .contains(window::WindowDecorations::INTEGRATED_BUTTONS);
 
         // We ultimately want to produce a line looking like this:
         // ` | tab1-title x | tab2-title x |  +      . - X `
@@ -349,7 +578,10 @@ impl TabBarState {
 
         let available_cells =
             title_width.saturating_sub(number_of_tabs.saturating_sub(1) + new_tab.len());
-        let tab_width_max = if config.use_fancy_tab_bar || available_cells >= titles_len {
+        let tab_width_max = if config.use_fancy_tab_bar
+            || config.tab_bar_overflow != TabBarOverflow::Shrink
+            || available_cells >= titles_len
+        {
             // We can render each title with its full width
             usize::max_value()
         } else {
@@ -357,8 +589,60 @@ impl TabBarState {
             available_cells / number_of_tabs
         }
         .min(config.tab_max_width);
+        let tab_width_min = match config.tab_bar_overflow {
+            TabBarOverflow::Shrink => 0,
+            TabBarOverflow::Scroll | TabBarOverflow::Wrap => {
+                config.tab_min_width.min(config.tab_max_width)
+            }
+        };
+
+        // Reserve place for integrated title buttons
+        let reserved_width = if use_integrated_title_buttons
+            && config.integrated_title_button_style != IntegratedTitleButtonStyle::MacOsNative
+            && config.integrated_title_button_alignment == IntegratedTitleButtonAlignment::Right
+        {
+            Self::integrated_title_buttons_width(config)
+        } else {
+            0
+        };
+        let row_width = title_width.saturating_sub(reserved_width);
+
+        // The fancy tab bar adds padding around the titles and draws
+        // the new tab button as a glyph, so we approximate the number
+        // of cells that those occupy when deciding which tabs fit.
+        let (tab_overhead, new_tab_width) = if config.use_fancy_tab_bar {
+            (
+                if config.show_close_tab_button_in_tabs {
+                    3
+                } else {
+                    1
+                },
+                3,
+            )
+        } else {
+            (0, new_tab.len())
+        };
+        let new_tab_width = if config.show_new_tab_button_in_tab_bar {
+            new_tab_width
+        } else {
+            0
+        };
+
+        // In a vertical tab bar, each tab fills the width of the strip
+        let (tab_width_max, tab_width_min) = if vertical {
+            let width = row_width.saturating_sub(tab_overhead);
+            (width, width)
+        } else {
+            (tab_width_max, tab_width_min)
+        };
+        let tab_widths: Vec<usize> = tab_titles
+            .iter()
+            .map(|title| title.len.min(tab_width_max).max(tab_width_min) + tab_overhead)
+            .collect();
 
         let mut line = Line::with_width(0, SEQ_ZERO);
+        let mut lines = vec![];
+        let mut row = 0;
 
         let mut x = 0;
         let mut items = vec![];
@@ -372,7 +656,7 @@ impl TabBarState {
         if use_integrated_title_buttons
             && config.integrated_title_button_style == IntegratedTitleButtonStyle::MacOsNative
             && config.use_fancy_tab_bar == false
-            && config.tab_bar_at_bottom == false
+            && config.effective_tab_bar_position() == TabBarPosition::Top
         {
             for _ in 0..10 as usize {
                 line.insert_cell(0, black_cell.clone(), title_width, SEQ_ZERO);
@@ -387,22 +671,82 @@ impl TabBarState {
             Self::integrated_title_buttons(mouse_x, &mut x, config, &mut items, &mut line, &colors);
         }
 
-        let left_status_line = parse_status_text(left_status, black_cell.attrs().clone());
+        let mut left_status_line = parse_status_text(left_status, black_cell.attrs().clone());
+        if vertical && left_status_line.len() > row_width {
+            left_status_line.resize(row_width, SEQ_ZERO);
+        }
         if left_status_line.len() > 0 {
             items.push(TabEntry {
                 item: TabBarItem::LeftStatus,
                 title: left_status_line.clone(),
                 x,
                 width: left_status_line.len(),
+                row,
             });
             x += left_status_line.len();
             line.append_line(left_status_line, SEQ_ZERO);
         }
 
-        for (tab_idx, tab_title) in tab_titles.iter().enumerate() {
+        let visible_tabs = if config.tab_bar_overflow == TabBarOverflow::Scroll && !vertical {
+            let available = row_width.saturating_sub(x + new_tab_width);
+            if tab_widths.iter().sum::<usize>() <= available {
+                0..number_of_tabs
+            } else {
+                visible_tab_range(
+                    &tab_widths,
+                    available.saturating_sub(scroll_left.len() + scroll_right.len()),
+                    active_tab_no,
+                    scroll,
+                )
+            }
+        } else {
+            0..number_of_tabs
+        };
+        let scrolling = visible_tabs != (0..number_of_tabs);
+        let first_tab = visible_tabs.start;
+
+        if scrolling {
+            let hover = is_tab_hover(mouse_x, x, scroll_left.len());
+            let button = if hover {
+                &scroll_left_hover
+            } else {
+                &scroll_left
+            };
+            items.push(TabEntry {
+                item: TabBarItem::ScrollLeft,
+                title: button.clone(),
+                x,
+                width: button.len(),
+                row,
+            });
+            x += button.len();
+            line.append_line(button.clone(), SEQ_ZERO);
+        }
+
+        for tab_idx in visible_tabs {
+            let tab_title = &tab_titles[tab_idx];
+
+            if Self::needs_new_row(
+                x,
+                tab_widths[tab_idx],
+                row_width,
+                config.tab_bar_overflow,
+                vertical,
+            ) {
+                Self::wrap_row(
+                    &mut lines,
+                    &mut line,
+                    &mut x,
+                    &mut row,
+                    row_width,
+                    &black_cell,
+                );
+            }
+            let row_mouse_x = mouse_x.filter(|_| mouse_row == row);
+
             let tab_title_len = tab_title.len.min(tab_width_max);
             let active = tab_idx == active_tab_no;
-            let hover = !active && is_tab_hover(mouse_x, x, tab_title_len);
+            let hover = !active && is_tab_hover(row_mouse_x, x, tab_title_len.max(tab_width_min));
 
             // Recompute the title so that it factors in both the hover state
             // and the adjusted maximum tab width based on available space.
@@ -415,7 +759,10 @@ impl TabBarState {
                 tab_title_len,
             );
 
-            let cell_attrs = if active {
+            let bell = tab_info[tab_idx].is_bell_ringing;
+            let cell_attrs = if bell {
+                &bell_cell_attrs
+            } else if active {
                 &active_cell_attrs
             } else if hover {
                 &inactive_hover_attrs
@@ -428,7 +775,9 @@ impl TabBarState {
             let esc = format_as_escapes(tab_title.items.clone()).expect("already parsed ok above");
             let mut tab_line = parse_status_text(
                 &esc,
-                if config.use_fancy_tab_bar {
+                // The fancy tab bar takes its colors from the first cell
+                // of the title, so the bell highlight is applied there too
+                if config.use_fancy_tab_bar && !bell {
                     CellAttributes::default()
                 } else {
                     cell_attrs.clone()
@@ -439,6 +788,16 @@ impl TabBarState {
             if tab_line.len() > tab_width_max {
                 tab_line.resize(tab_width_max, SEQ_ZERO);
             }
+            if !config.use_fancy_tab_bar {
+                while tab_line.len() < tab_width_min {
+                    tab_line.insert_cell(
+                        tab_line.len(),
+                        Cell::blank_with_attrs(cell_attrs.clone()),
+                        tab_width_min,
+                        SEQ_ZERO,
+                    );
+                }
+            }
 
             let width = tab_line.len();
 
@@ -447,15 +806,51 @@ impl TabBarState {
                 title,
                 x: tab_start_idx,
                 width,
+                row,
             });
 
             line.append_line(tab_line, SEQ_ZERO);
             x += width;
         }
 
+        if scrolling {
+            let hover = is_tab_hover(mouse_x, x, scroll_right.len());
+            let button = if hover {
+                &scroll_right_hover
+            } else {
+                &scroll_right
+            };
+            items.push(TabEntry {
+                item: TabBarItem::ScrollRight,
+                title: button.clone(),
+                x,
+                width: button.len(),
+                row,
+            });
+            x += button.len();
+            line.append_line(button.clone(), SEQ_ZERO);
+        }
+
         // New tab button
         if config.show_new_tab_button_in_tab_bar {
-            let hover = is_tab_hover(mouse_x, x, new_tab_hover.len());
+            if Self::needs_new_row(
+                x,
+                new_tab_width,
+                row_width,
+                config.tab_bar_overflow,
+                vertical,
+            ) {
+                Self::wrap_row(
+                    &mut lines,
+                    &mut line,
+                    &mut x,
+                    &mut row,
+                    row_width,
+                    &black_cell,
+                );
+            }
+            let row_mouse_x = mouse_x.filter(|_| mouse_row == row);
+            let hover = is_tab_hover(row_mouse_x, x, new_tab_hover.len());
 
             let new_tab_button = if hover { &new_tab_hover } else { &new_tab };
 
@@ -469,68 +864,31 @@ impl TabBarState {
                 title: new_tab_button.clone(),
                 x: button_start,
                 width,
+                row,
             });
 
             x += width;
         }
 
-        // Reserve place for integrated title buttons
-        let title_width = if use_integrated_title_buttons
-            && config.integrated_title_button_style != IntegratedTitleButtonStyle::MacOsNative
-            && config.integrated_title_button_alignment == IntegratedTitleButtonAlignment::Right
-        {
-            let window_hide =
-                parse_status_text(&config.tab_bar_style.window_hide, CellAttributes::default());
-            let window_hide_hover = parse_status_text(
-                &config.tab_bar_style.window_hide_hover,
-                CellAttributes::default(),
-            );
-
-            let window_maximize = parse_status_text(
-                &config.tab_bar_style.window_maximize,
-                CellAttributes::default(),
-            );
-            let window_maximize_hover = parse_status_text(
-                &config.tab_bar_style.window_maximize_hover,
-                CellAttributes::default(),
-            );
-            let window_close = parse_status_text(
-                &config.tab_bar_style.window_close,
-                CellAttributes::default(),
-            );
-            let window_close_hover = parse_status_text(
-                &config.tab_bar_style.window_close_hover,
-                CellAttributes::default(),
+        let mut right_status_line = parse_status_text(right_status, black_cell.attrs().clone());
+        if vertical && x > 0 && right_status_line.len() > 0 {
+            Self::wrap_row(
+                &mut lines,
+                &mut line,
+                &mut x,
+                &mut row,
+                row_width,
+                &black_cell,
             );
+        }
 
-            let hide_len = window_hide.len().max(window_hide_hover.len());
-            let maximize_len = window_maximize.len().max(window_maximize_hover.len());
-            let close_len = window_close.len().max(window_close_hover.len());
-
-            let mut width_to_reserve = 0;
-            for button in &config.integrated_title_buttons {
-                use IntegratedTitleButton as Button;
-                let button_len = match button {
-                    Button::Hide => hide_len,
-                    Button::Maximize => maximize_len,
-                    Button::Close => close_len,
-                };
-                width_to_reserve += button_len;
-            }
-
-            title_width.saturating_sub(width_to_reserve)
-        } else {
-            title_width
-        };
-
-        let status_space_available = title_width.saturating_sub(x);
-
-        let mut right_status_line = parse_status_text(right_status, black_cell.attrs().clone());
+        let status_space_available = row_width.saturating_sub(x);
         items.push(TabEntry {
             item: TabBarItem::RightStatus,
             title: right_status_line.clone(),
             x,
             width: status_space_available,
+            row,
         });
 
         while right_status_line.len() > status_space_available {
@@ -538,29 +896,54 @@ impl TabBarState {
         }
 
         line.append_line(right_status_line, SEQ_ZERO);
-        while line.len() < title_width {
-            line.insert_cell(x, black_cell.clone(), title_width, SEQ_ZERO);
+        while line.len() < row_width {
+            line.insert_cell(x, black_cell.clone(), row_width, SEQ_ZERO);
         }
+        lines.push(line);
 
         if use_integrated_title_buttons
             && config.integrated_title_button_style != IntegratedTitleButtonStyle::MacOsNative
             && config.integrated_title_button_alignment == IntegratedTitleButtonAlignment::Right
         {
-            x = title_width;
-            Self::integrated_title_buttons(mouse_x, &mut x, config, &mut items, &mut line, &colors);
+            x = row_width;
+            Self::integrated_title_buttons(
+                mouse_x.filter(|_| mouse_row == 0),
+                &mut x,
+                config,
+                &mut items,
+                &mut lines[0],
+                &colors,
+            );
+        }
+
+        // Any additional rows span the full width of the window
+        for line in lines.iter_mut().skip(1) {
+            while line.len() < title_width {
+                line.insert_cell(line.len(), black_cell.clone(), title_width, SEQ_ZERO);
+            }
         }
 
-        Self { line, items }
+        Self {
+            lines,
+            items,
+            first_tab,
+        }
     }
 
-    pub fn compute_ui_items(&self, y: usize, cell_height: usize, cell_width: usize) -> Vec<UIItem> {
+    pub fn compute_ui_items(
+        &self,
+        x: usize,
+        y: usize,
+        cell_height: usize,
+        cell_width: usize,
+    ) -> Vec<UIItem> {
         let mut items = vec![];
 
         for entry in self.items.iter() {
             items.push(UIItem {
-                x: entry.x * cell_width,
+                x: x + entry.x * cell_width,
                 width: entry.width * cell_width,
-                y,
+                y: y + entry.row * cell_height,
                 height: cell_height,
                 item_type: UIItemType::TabBar(entry.item),
             });
@@ -674,3 +1057,128 @@ pub fn parse_status_text(text: &str, default_cell: CellAttributes) -> Line {
     flush_print(&mut print_buffer, &mut cells, &pen);
     Line::from_cells(cells, SEQ_ZERO)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn scroll(first_tab: usize, reveal_active: bool) -> TabBarScroll {
+        TabBarScroll {
+            first_tab,
+            reveal_active,
+        }
+    }
+
+    #[test]
+    fn scroll_reveals_active_tab() {
+        // Two of these tabs fit in the available space
+        let widths = [4; 6];
+
+        // Active tab in the first position
+        assert_eq!(visible_tab_range(&widths, 10, 0, scroll(0, true)), 0..2);
+        assert_eq!(visible_tab_range(&widths, 10, 0, scroll(3, true)), 0..2);
+        // Active tab in the last position
+        assert_eq!(visible_tab_range(&widths, 10, 5, scroll(0, true)), 4..6);
+        // Active tab ahead of the scroll position
+        assert_eq!(visible_tab_range(&widths, 10, 1, scroll(3, true)), 1..3);
+        // Active tab that is already visible
+        assert_eq!(visible_tab_range(&widths, 10, 3, scroll(2, true)), 2..4);
+    }
+
+    #[test]
+    fn scroll_offsets() {
+        let widths = [4; 6];
+
+        // Without reveal_active, the scroll position is kept even
+        // when the active tab is not visible
+        assert_eq!(visible_tab_range(&widths, 10, 0, scroll(2, false)), 2..4);
+        // Scrolling doesn't leave a gap after the last tab
+        assert_eq!(visible_tab_range(&widths, 10, 0, scroll(5, false)), 4..6);
+        assert_eq!(visible_tab_range(&widths, 10, 0, scroll(100, false)), 4..6);
+        // Tabs of different widths
+        assert_eq!(
+            visible_tab_range(&[2, 8, 3, 3, 5], 10, 0, scroll(1, false)),
+            1..2
+        );
+        assert_eq!(
+            visible_tab_range(&[2, 8, 3, 3, 5], 10, 0, scroll(2, false)),
+            2..4
+        );
+        assert_eq!(
+            visible_tab_range(&[2, 8, 3, 3, 5], 10, 0, scroll(3, false)),
+            3..5
+        );
+        assert_eq!(visible_tab_range(&[], 10, 0, scroll(0, true)), 0..0);
+    }
+
+    #[test]
+    fn scroll_with_tab_wider_than_bar() {
+        // A single tab is always shown, even if it doesn't fit
+        assert_eq!(visible_tab_range(&[30], 10, 0, scroll(0, true)), 0..1);
+        assert_eq!(visible_tab_range(&[3, 30, 3], 10, 1, scroll(0, true)), 1..2);
+        assert_eq!(visible_tab_range(&[3, 30, 3], 10, 2, scroll(0, true)), 2..3);
+    }
+
+    /// Returns the row on which each of the items is placed
+    /// when they wrap onto rows of row_width cells
+    fn wrapped_rows(widths: &[usize], row_width: usize) -> Vec<usize> {
+        let mut x = 0;
+        let mut row = 0;
+        let mut rows = vec![];
+        for &width in widths {
+            if TabBarState::needs_new_row(x, width, row_width, TabBarOverflow::Wrap, false) {
+                x = 0;
+                row += 1;
+            }
+            rows.push(row);
+            x += width;
+        }
+        rows
+    }
+
+    #[test]
+    fn wrap_rows() {
+        assert_eq!(wrapped_rows(&[4, 4, 2], 10), vec![0, 0, 0]);
+        assert_eq!(wrapped_rows(&[4, 4, 4, 4, 4], 10), vec![0, 0, 1, 1, 2]);
+        // A tab wider than the bar is placed on a row of its own
+        assert_eq!(wrapped_rows(&[30], 10), vec![0]);
+        assert_eq!(wrapped_rows(&[4, 30, 4], 10), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn wrap_only_when_requested() {
+        for overflow in [TabBarOverflow::Shrink, TabBarOverflow::Scroll] {
+            assert!(!TabBarState::needs_new_row(8, 4, 10, overflow, false));
+        }
+        // Every item after the first starts a new row when vertical
+        assert!(TabBarState::needs_new_row(
+            1,
+            1,
+            10,
+            TabBarOverflow::Shrink,
+            true
+        ));
+        assert!(!TabBarState::needs_new_row(
+            0,
+            1,
+            10,
+            TabBarOverflow::Shrink,
+            true
+        ));
+    }
+
+    #[test]
+    fn wrap_row_pads_line() {
+        let mut lines = vec![];
+        let mut line = Line::from_text("ab", &CellAttributes::default(), SEQ_ZERO, None);
+        let mut x = 2;
+        let mut row = 0;
+        TabBarState::wrap_row(&mut lines, &mut line, &mut x, &mut row, 5, &Cell::blank());
+
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].len(), 5);
+        assert_eq!(lines[0].as_str(), "ab   ");
+        assert_eq!(line.len(), 0);
+        assert_eq!((x, row), (0, 1));
+    }
+}
@@ -0,0 +1,129 @@
+//! Tracks the reasons that the window needs to be repainted without
+//! any new output or input having arrived.
+//! Each frame, the things being painted that animate report when they
+//! next need a frame, along with which kind of animation they are.
+//! When none of them do, no timer is scheduled at all and the window
+//! is only repainted when something invalidates it.
+use std::time::Instant;
+
+bitflags::bitflags! {
+    #[derive(Default)]
+    pub struct AnimationSources: u16 {
+        const CURSOR_BLINK = 1<<0;
+        const TEXT_BLINK = 1<<1;
+        const VISUAL_BELL = 1<<2;
+        /// Animated images, either in a pane or the window background
+        const IMAGE = 1<<3;
+        const SCROLL_INERTIA = 1<<4;
+        /// Color transitions in the fancy tab bar and modals
+        const TRANSITION = 1<<5;
+        /// The pulse of an indeterminate progress bar in the tab bar
+        const PROGRESS = 1<<6;
+        /// The expiry of the leader key or of a key table
+        const KEY_TIMEOUT = 1<<7;
+    }
+}
+
+impl AnimationSources {
+    const NAMES: &'static [(AnimationSources, &'static str)] = &[
+        (Self::CURSOR_BLINK, "cursor_blink"),
+        (Self::TEXT_BLINK, "text_blink"),
+        (Self::VISUAL_BELL, "visual_bell"),
+        (Self::IMAGE, "image"),
+        (Self::SCROLL_INERTIA, "scroll_inertia"),
+        (Self::TRANSITION, "transition"),
+        (Self::PROGRESS, "progress"),
+        (Self::KEY_TIMEOUT, "key_timeout"),
+    ];
+
+    pub fn names(&self) -> Vec<&'static str> {
+        Self::NAMES
+            .iter()
+            .filter(|(source, _)| self.contains(*source))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+/// The recent frame rate of a window and what, if anything, is
+/// keeping it painting
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStats {
+    pub fps: f32,
+    pub animation_sources: AnimationSources,
+}
+
+impl std::fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:.1} fps, ", self.fps)?;
+        if self.animation_sources.is_empty() {
+            write!(f, "idle")
+        } else {
+            write!(
+                f,
+                "animating: {}",
+                self.animation_sources.names().join(", ")
+            )
+        }
+    }
+}
+
+/// When the next frame is due, and which animations want it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NextFrame {
+    pub due: Option<Instant>,
+    pub sources: AnimationSources,
+}
+
+impl NextFrame {
+    pub fn new(source: AnimationSources, due: Option<Instant>) -> Self {
+        Self {
+            due,
+            sources: if due.is_some() {
+                source
+            } else {
+                AnimationSources::empty()
+            },
+        }
+    }
+
+    /// Combines the requests, keeping the earlier due time
+    pub fn merge(&mut self, other: NextFrame) {
+        if let Some(due) = other.due {
+            self.due = Some(self.due.map_or(due, |prior| prior.min(due)));
+            self.sources |= other.sources;
+        }
+    }
+
+    /// Returns true if the frame was due at or before `now`.
+    /// Cached renderings with such an expiry need to be rebuilt.
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.due.map(|due| now >= due).unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn merge_keeps_earliest() {
+        let now = Instant::now();
+        let soon = now + Duration::from_millis(10);
+        let later = now + Duration::from_millis(500);
+
+        let mut next = NextFrame::default();
+        assert!(!next.is_expired(later));
+
+        next.merge(NextFrame::new(AnimationSources::CURSOR_BLINK, Some(later)));
+        next.merge(NextFrame::new(AnimationSources::IMAGE, Some(soon)));
+        // A source without a due time isn't animating
+        next.merge(NextFrame::new(AnimationSources::VISUAL_BELL, None));
+
+        assert_eq!(next.due, Some(soon));
+        assert_eq!(next.sources.names(), vec!["cursor_blink", "image"]);
+        assert!(!next.is_expired(now));
+        assert!(next.is_expired(soon));
+    }
+}
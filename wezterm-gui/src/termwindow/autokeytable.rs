@@ -0,0 +1,168 @@
+//! Activates the entries of `auto_key_tables` whose condition matches
+//! the active pane, beneath any explicitly activated key tables.
+//! The conditions are evaluated whenever the title is updated, which
+//! happens when the active pane changes and along with each status
+//! update.  The foreground process is taken from the cached process
+//! information, and nothing is matched unless the pane, its domain or
+//! its process differ from the last evaluation.
+use crate::TermWindow;
+use config::ConfigHandle;
+use mux::pane::{CachePolicy, PaneId};
+use mux::Mux;
+use regex::Regex;
+
+struct AutoKeyTableRule {
+    table: String,
+    process: Option<Regex>,
+    domain: Option<Regex>,
+}
+
+/// What the conditions are matched against
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Subject {
+    pane_id: PaneId,
+    process: Option<String>,
+    domain: Option<String>,
+}
+
+#[derive(Default)]
+pub struct AutoKeyTables {
+    rules: Vec<AutoKeyTableRule>,
+    /// The subject of the last evaluation
+    subject: Option<Subject>,
+}
+
+/// Compiles `pattern` so that it must match the whole of a name
+fn compile(pattern: &Option<String>) -> Result<Option<Regex>, regex::Error> {
+    pattern
+        .as_ref()
+        .map(|pattern| Regex::new(&format!("^(?:{})$", pattern)))
+        .transpose()
+}
+
+/// Returns the file name of the executable at `path`, without any
+/// `.exe` extension
+fn process_file_name(path: &str) -> &str {
+    let name = path
+        .rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .unwrap_or(path);
+    name.strip_suffix(".exe").unwrap_or(name)
+}
+
+fn is_match(pattern: &Option<Regex>, value: &Option<String>) -> bool {
+    match (pattern, value) {
+        (None, _) => true,
+        (Some(pattern), Some(value)) => pattern.is_match(value),
+        (Some(_), None) => false,
+    }
+}
+
+impl AutoKeyTables {
+    pub fn new(config: &ConfigHandle) -> Self {
+        let rules = config
+            .auto_key_tables
+            .iter()
+            .filter_map(|entry| {
+                let compiled = compile(&entry.when.process)
+                    .and_then(|process| Ok((process, compile(&entry.when.domain)?)));
+                match compiled {
+                    Ok((process, domain)) => Some(AutoKeyTableRule {
+                        table: entry.table.clone(),
+                        process,
+                        domain,
+                    }),
+                    Err(err) => {
+                        log::error!("auto_key_tables entry for {}: {:#}", entry.table, err);
+                        None
+                    }
+                }
+            })
+            .collect();
+        Self {
+            rules,
+            subject: None,
+        }
+    }
+
+    /// Returns the tables whose conditions match `subject`, in the
+    /// order of their precedence
+    fn matching(&self, subject: &Subject) -> Vec<String> {
+        let mut tables: Vec<String> = vec![];
+        for rule in &self.rules {
+            if is_match(&rule.process, &subject.process)
+                && is_match(&rule.domain, &subject.domain)
+                && !tables.contains(&rule.table)
+            {
+                tables.push(rule.table.clone());
+            }
+        }
+        tables
+    }
+}
+
+impl TermWindow {
+    /// Activates the automatic key tables that match the active pane,
+    /// and deactivates those that no longer match
+    pub fn update_auto_key_tables(&mut self) {
+        if self.auto_key_tables.rules.is_empty() {
+            self.key_table_state.set_auto_tables(&[]);
+            return;
+        }
+
+        let mux = Mux::get();
+        let subject = self.get_active_pane_no_overlay().map(|pane| Subject {
+            pane_id: pane.pane_id(),
+            process: pane
+                .get_foreground_process_name(CachePolicy::AllowStale)
+                .map(|path| process_file_name(&path).to_string()),
+            domain: mux
+                .get_domain(pane.domain_id())
+                .map(|domain| domain.domain_name().to_string()),
+        });
+        if subject == self.auto_key_tables.subject {
+            return;
+        }
+
+        let tables = match &subject {
+            Some(subject) => self
+                .auto_key_tables
+                .matching(subject)
+                .into_iter()
+                .filter(|table| {
+                    let known = self.input_map.has_table(table);
+                    if !known {
+                        log::warn!("auto_key_tables: no key_table named {}", table);
+                    }
+                    known
+                })
+                .collect(),
+            None => vec![],
+        };
+        self.auto_key_tables.subject = subject;
+        if self.key_table_state.set_auto_tables(&tables) {
+            self.schedule_status_update();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn process_names() {
+        assert_eq!(process_file_name("/usr/bin/kubectl"), "kubectl");
+        assert_eq!(process_file_name("C:\\bin\\kubectl.exe"), "kubectl");
+        assert_eq!(process_file_name("kubectl"), "kubectl");
+    }
+
+    #[test]
+    fn whole_name_matches() {
+        let pattern = compile(&Some("SSH:.*".to_string())).unwrap();
+        assert!(is_match(&pattern, &Some("SSH:prod".to_string())));
+        assert!(!is_match(&pattern, &Some("local SSH:prod".to_string())));
+        assert!(!is_match(&pattern, &None));
+        assert!(is_match(&None, &None));
+    }
+}
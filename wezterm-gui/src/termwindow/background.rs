@@ -1,6 +1,7 @@
 use crate::color::LinearRgba;
 use crate::glyphcache::LoadState;
 use crate::quad::{QuadAllocator, QuadTrait};
+use crate::termwindow::animation::AnimationSources;
 use crate::termwindow::RenderState;
 use crate::utilsprites::RenderMetrics;
 use crate::Dimensions;
@@ -430,7 +431,7 @@ impl crate::TermWindow {
             None,
             self.allow_images,
         )?;
-        self.update_next_frame_time(next_due);
+        self.update_next_frame_time(AnimationSources::IMAGE, next_due);
 
         if load_state == LoadState::Loading {
             return Ok(false);
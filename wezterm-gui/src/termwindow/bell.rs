@@ -0,0 +1,172 @@
+//! Routes the bell to the various places where it can be surfaced:
+//! the audible bell, the in-pane visual bell, a highlight of the tab
+//! in the tab bar, a desktop notification and the lua `bell` event.
+use crate::termwindow::{TermWindow, TermWindowNotif};
+use ::window::{Connection, ConnectionOps, WindowOps};
+use config::{AudibleBell, NotificationHandling};
+use mux::pane::PaneId;
+use mux::tab::TabId;
+use mux::Mux;
+use smol::Timer;
+use std::time::{Duration, Instant};
+
+/// The minimum time between bell notifications from a window,
+/// so that a bell storm doesn't flood the desktop with them
+const BELL_NOTIFICATION_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Decides whether a bell should post a notification, based on where
+/// it rang relative to the focus of the window
+fn should_notify(
+    handling: NotificationHandling,
+    window_focused: bool,
+    tab_active: bool,
+    pane_active: bool,
+) -> bool {
+    match handling {
+        NotificationHandling::NeverShow => false,
+        NotificationHandling::AlwaysShow => true,
+        NotificationHandling::SuppressFromFocusedPane => {
+            !(window_focused && tab_active && pane_active)
+        }
+        NotificationHandling::SuppressFromFocusedTab => !(window_focused && tab_active),
+        NotificationHandling::SuppressFromFocusedWindow => !window_focused,
+    }
+}
+
+impl TermWindow {
+    pub fn ring_bell(&mut self, pane_id: PaneId) {
+        let mux = Mux::get();
+        // Overlays are not tracked by the mux, so a pane that cannot
+        // be resolved is assumed to belong to this window
+        let tab_id = match mux.resolve_pane_id(pane_id) {
+            Some((_domain, window_id, _tab_id)) if window_id != self.mux_window_id => return,
+            Some((_domain, _window_id, tab_id)) => Some(tab_id),
+            None => None,
+        };
+
+        match self.config.audible_bell {
+            AudibleBell::SystemBeep => {
+                Connection::get().expect("on main thread").beep();
+            }
+            AudibleBell::Disabled => {}
+        }
+
+        log::trace!("Ding! (this is the bell) in pane {}", pane_id);
+        self.emit_window_event("bell", Some(pane_id));
+
+        self.pane_state(pane_id).bell_start.replace(Instant::now());
+
+        if let Some(tab_id) = tab_id {
+            self.flash_tab_for_bell(tab_id);
+            self.notify_bell(pane_id, tab_id);
+        }
+
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Returns true if the tab is highlighted in the tab bar
+    /// because one of its panes rang the bell
+    pub fn is_tab_bell_ringing(&self, tab_id: TabId) -> bool {
+        let duration = Duration::from_millis(self.config.tab_bell_flash_duration_ms);
+        self.tab_state(tab_id)
+            .bell_start
+            .map(|start| start.elapsed() < duration)
+            .unwrap_or(false)
+    }
+
+    fn flash_tab_for_bell(&mut self, tab_id: TabId) {
+        let duration = Duration::from_millis(self.config.tab_bell_flash_duration_ms);
+        if duration.is_zero() {
+            return;
+        }
+        self.tab_state(tab_id).bell_start.replace(Instant::now());
+        self.update_title();
+
+        // Re-render the tab bar once the highlight has expired
+        if let Some(window) = self.window.clone() {
+            promise::spawn::spawn(async move {
+                Timer::after(duration).await;
+                window.notify(TermWindowNotif::Apply(Box::new(|tw| tw.update_title())));
+            })
+            .detach();
+        }
+    }
+
+    fn notify_bell(&mut self, pane_id: PaneId, tab_id: TabId) {
+        let mux = Mux::get();
+        let tab_active = mux
+            .get_active_tab_for_window(self.mux_window_id)
+            .map(|tab| tab.tab_id() == tab_id)
+            .unwrap_or(false);
+        let pane_active = mux
+            .get_tab(tab_id)
+            .and_then(|tab| tab.get_active_pane())
+            .map(|pane| pane.pane_id() == pane_id)
+            .unwrap_or(false);
+
+        if !should_notify(
+            self.config.bell_notification_handling,
+            self.focused.is_some(),
+            tab_active,
+            pane_active,
+        ) || crate::termwindow::presentation::notifications_suppressed()
+        {
+            return;
+        }
+
+        if let Some(last) = self.last_bell_notification {
+            if last.elapsed() < BELL_NOTIFICATION_INTERVAL {
+                log::trace!("suppressing bell notification for pane {}", pane_id);
+                return;
+            }
+        }
+        self.last_bell_notification.replace(Instant::now());
+
+        let title = mux
+            .get_pane(pane_id)
+            .map(|pane| pane.get_title())
+            .unwrap_or_default();
+        wezterm_toast_notification::persistent_toast_notification("Bell", &title);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn notification_handling() {
+        use NotificationHandling::*;
+
+        // Unfocused window: only NeverShow suppresses
+        for handling in [
+            AlwaysShow,
+            SuppressFromFocusedPane,
+            SuppressFromFocusedTab,
+            SuppressFromFocusedWindow,
+        ] {
+            assert!(should_notify(handling, false, true, true), "{handling:?}");
+        }
+        assert!(!should_notify(NeverShow, false, false, false));
+
+        // Focused window, bell from a background tab
+        assert!(should_notify(SuppressFromFocusedPane, true, false, false));
+        assert!(should_notify(SuppressFromFocusedTab, true, false, false));
+        assert!(!should_notify(
+            SuppressFromFocusedWindow,
+            true,
+            false,
+            false
+        ));
+
+        // Focused window, bell from an inactive pane in the active tab
+        assert!(should_notify(SuppressFromFocusedPane, true, true, false));
+        assert!(!should_notify(SuppressFromFocusedTab, true, true, false));
+
+        // Focused pane
+        assert!(!should_notify(SuppressFromFocusedPane, true, true, true));
+        assert!(should_notify(AlwaysShow, true, true, true));
+    }
+}
@@ -3,6 +3,7 @@ use crate::color::LinearRgba;
 use crate::customglyph::{BlockKey, Poly};
 use crate::glyphcache::CachedGlyph;
 use crate::quad::{QuadImpl, QuadTrait, TripleLayerQuadAllocator, TripleLayerQuadAllocatorTrait};
+use crate::termwindow::animation::AnimationSources;
 use crate::termwindow::{
     ColorEase, MouseCapture, RenderState, TermWindowNotif, UIItem, UIItemType,
 };
@@ -963,7 +964,7 @@ impl super::TermWindow {
                 one_shot,
             } => {
                 if let Some((mix_value, next)) = ease.borrow_mut().intensity(*one_shot) {
-                    self.update_next_frame_time(Some(next));
+                    self.update_next_frame_time(AnimationSources::TRANSITION, Some(next));
                     ResolvedColor {
                         color: *color,
                         alt_color: *alt_color,
@@ -994,7 +995,7 @@ impl super::TermWindow {
                 one_shot,
             } => {
                 if let Some((mix_value, next)) = ease.borrow_mut().intensity(*one_shot) {
-                    self.update_next_frame_time(Some(next));
+                    self.update_next_frame_time(AnimationSources::TRANSITION, Some(next));
                     ResolvedColor {
                         color: *color,
                         alt_color: *alt_color,
@@ -0,0 +1,118 @@
+use crate::termwindow::TermWindow;
+use config::keyassignment::{BroadcastInputScope, PaneSelectArguments, PaneSelectMode};
+use mux::pane::{Pane, PaneId};
+use mux::tab::TabId;
+use mux::Mux;
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// The set of panes that receive the input typed into
+/// any one of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BroadcastInput {
+    Tab(TabId),
+    Window,
+    Panes(HashSet<PaneId>),
+}
+
+impl TermWindow {
+    pub fn toggle_broadcast_input(&mut self, pane: &Arc<dyn Pane>, scope: BroadcastInputScope) {
+        let broadcast = match scope {
+            BroadcastInputScope::SelectPanes => {
+                let modal = crate::termwindow::paneselect::PaneSelector::new(
+                    self,
+                    &PaneSelectArguments {
+                        mode: PaneSelectMode::ToggleBroadcastInput,
+                        ..Default::default()
+                    },
+                );
+                self.set_modal(Rc::new(modal));
+                return;
+            }
+            _ if self.broadcast_input.is_some() => None,
+            BroadcastInputScope::Tab => {
+                let mux = Mux::get();
+                match mux.resolve_pane_id(pane.pane_id()) {
+                    Some((_domain_id, _window_id, tab_id)) => Some(BroadcastInput::Tab(tab_id)),
+                    None => return,
+                }
+            }
+            BroadcastInputScope::Window => Some(BroadcastInput::Window),
+        };
+        self.set_broadcast_input(broadcast);
+    }
+
+    pub fn set_broadcast_input(&mut self, broadcast: Option<BroadcastInput>) {
+        let broadcast = match broadcast {
+            Some(BroadcastInput::Panes(panes)) if panes.is_empty() => None,
+            broadcast => broadcast,
+        };
+        if broadcast != self.broadcast_input {
+            self.broadcast_input = broadcast;
+            self.update_title();
+            if let Some(window) = self.window.as_ref() {
+                window.invalidate();
+            }
+        }
+    }
+
+    /// Returns the ids of the panes that were explicitly picked
+    /// to receive broadcast input
+    pub fn broadcast_input_selection(&self) -> HashSet<PaneId> {
+        match &self.broadcast_input {
+            Some(BroadcastInput::Panes(panes)) => panes.clone(),
+            _ => HashSet::new(),
+        }
+    }
+
+    /// Returns the live panes that are receiving broadcast input
+    pub fn broadcast_members(&self) -> Vec<Arc<dyn Pane>> {
+        let mux = Mux::get();
+        let panes: Vec<Arc<dyn Pane>> = match &self.broadcast_input {
+            None => return vec![],
+            Some(BroadcastInput::Tab(tab_id)) => match mux.get_tab(*tab_id) {
+                Some(tab) => tab.iter_panes().into_iter().map(|p| p.pane).collect(),
+                None => vec![],
+            },
+            Some(BroadcastInput::Window) => match mux.get_window(self.mux_window_id) {
+                Some(window) => window
+                    .iter()
+                    .flat_map(|tab| tab.iter_panes().into_iter().map(|p| p.pane))
+                    .collect(),
+                None => vec![],
+            },
+            Some(BroadcastInput::Panes(panes)) => {
+                panes.iter().filter_map(|id| mux.get_pane(*id)).collect()
+            }
+        };
+        panes.into_iter().filter(|p| !p.is_dead()).collect()
+    }
+
+    /// If `pane` is receiving broadcast input, returns the other
+    /// panes that should receive the input that is sent to it
+    pub fn broadcast_targets(&self, pane: &Arc<dyn Pane>) -> Vec<Arc<dyn Pane>> {
+        if self.broadcast_input.is_none() {
+            return vec![];
+        }
+        let mut members = self.broadcast_members();
+        let pane_id = pane.pane_id();
+        if !members.iter().any(|p| p.pane_id() == pane_id) {
+            return vec![];
+        }
+        members.retain(|p| p.pane_id() != pane_id);
+        members
+    }
+
+    /// Silently drops a pane that has gone away from the set of
+    /// panes that were picked to receive broadcast input
+    pub fn prune_broadcast_input(&mut self, pane_id: PaneId) {
+        if let Some(BroadcastInput::Panes(panes)) = &self.broadcast_input {
+            if panes.contains(&pane_id) {
+                let mut panes = panes.clone();
+                panes.remove(&pane_id);
+                self.set_broadcast_input(Some(BroadcastInput::Panes(panes)));
+            }
+        }
+    }
+}
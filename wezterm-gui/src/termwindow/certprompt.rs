@@ -0,0 +1,223 @@
+//! The certificate prompt is a modal that is shown when the server
+//! of a TLS domain presents a different certificate from the one that
+//! was trusted when we first connected to it, and asks whether the
+//! new certificate should be trusted.
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::render::corners::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::{DimensionContext, TermWindow};
+use crate::utilsprites::RenderMetrics;
+use config::{Dimension, TabBarColors};
+use std::cell::{Ref, RefCell};
+use wezterm_client::pinning::{CertificateAnswer, CertificateChange};
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+use window::color::LinearRgba;
+
+pub struct CertificatePrompt {
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    /// Taken when answered; dropping it unanswered rejects the certificate
+    change: RefCell<Option<CertificateChange>>,
+}
+
+impl CertificatePrompt {
+    pub fn new(change: CertificateChange) -> Self {
+        Self {
+            element: RefCell::new(None),
+            change: RefCell::new(Some(change)),
+        }
+    }
+
+    fn answer(&self, answer: CertificateAnswer, term_window: &mut TermWindow) {
+        if let Some(change) = self.change.borrow_mut().take() {
+            change.answer(answer);
+        }
+        term_window.cancel_modal();
+    }
+
+    fn compute(&self, term_window: &mut TermWindow) -> anyhow::Result<Vec<ComputedElement>> {
+        let font = term_window.fonts.title_font()?;
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let colors = term_window
+            .config
+            .colors
+            .as_ref()
+            .and_then(|c| c.tab_bar.as_ref())
+            .cloned()
+            .unwrap_or_else(TabBarColors::default);
+        let background: InheritableColor = colors.background().to_linear().into();
+        let text: InheritableColor = colors.inactive_tab().fg_color.to_linear().into();
+
+        let tab_bar = term_window.tab_bar_reserved_edges()?;
+        let (padding_left, padding_top) = term_window.padding_left_top();
+        let border = term_window.get_os_border();
+        let top_pixel_y = tab_bar.top + padding_top + border.top.get() as f32;
+
+        let lines = self
+            .change
+            .borrow()
+            .as_ref()
+            .map(|change| change.describe())
+            .unwrap_or_default();
+
+        let mut elements: Vec<Element> = lines
+            .into_iter()
+            .map(|line| {
+                Element::new(&font, ElementContent::Text(line))
+                    .colors(ElementColors {
+                        border: BorderColor::default(),
+                        bg: LinearRgba::TRANSPARENT.into(),
+                        text: text.clone(),
+                    })
+                    .padding(BoxDimension {
+                        left: Dimension::Cells(0.25),
+                        right: Dimension::Cells(0.25),
+                        top: Dimension::Cells(0.),
+                        bottom: Dimension::Cells(0.),
+                    })
+                    .display(DisplayType::Block)
+            })
+            .collect();
+
+        elements.push(
+            Element::new(
+                &font,
+                ElementContent::Text("[A]ccept and save  Accept [O]nce  [R]eject".to_string()),
+            )
+            .colors(ElementColors {
+                border: BorderColor::default(),
+                bg: LinearRgba::TRANSPARENT.into(),
+                text: colors.inactive_tab_edge().to_linear().into(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.25),
+                right: Dimension::Cells(0.25),
+                top: Dimension::Cells(0.5),
+                bottom: Dimension::Cells(0.),
+            })
+            .display(DisplayType::Block),
+        );
+
+        let dimensions = term_window.dimensions;
+        let size = term_window.terminal_size;
+
+        // The fingerprints are long; use most of the width
+        let desired_width = (size.cols * 9 / 10).max(80).min(size.cols);
+
+        let avail_pixel_width =
+            size.cols as f32 * term_window.render_metrics.cell_size.width as f32;
+        let desired_pixel_width =
+            desired_width as f32 * term_window.render_metrics.cell_size.width as f32;
+
+        let element = Element::new(&font, ElementContent::Children(elements))
+            .colors(ElementColors {
+                border: BorderColor::new(colors.inactive_tab_edge().to_linear()),
+                bg: background,
+                text,
+            })
+            .margin(BoxDimension::new(Dimension::Cells(0.25)))
+            .padding(BoxDimension::new(Dimension::Cells(0.25)))
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_LEFT_ROUNDED_CORNER,
+                },
+                top_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_RIGHT_ROUNDED_CORNER,
+                },
+                bottom_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_LEFT_ROUNDED_CORNER,
+                },
+                bottom_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_RIGHT_ROUNDED_CORNER,
+                },
+            }))
+            .min_width(Some(Dimension::Pixels(desired_pixel_width)));
+
+        let x_adjust = ((avail_pixel_width - padding_left) - desired_pixel_width) / 2.;
+
+        let computed = term_window.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_height as f32,
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_width as f32,
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(
+                    tab_bar.left + padding_left + x_adjust,
+                    top_pixel_y,
+                    desired_pixel_width,
+                    size.rows as f32 * term_window.render_metrics.cell_size.height as f32,
+                ),
+                metrics: &metrics,
+                gl_state: term_window.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        Ok(vec![computed])
+    }
+}
+
+impl Modal for CertificatePrompt {
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<bool> {
+        let answer = match (key, mods) {
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                match c.to_ascii_lowercase() {
+                    'a' | 'y' => CertificateAnswer::AcceptAndSave,
+                    'o' => CertificateAnswer::AcceptOnce,
+                    'r' | 'n' => CertificateAnswer::Reject,
+                    _ => return Ok(true),
+                }
+            }
+            (KeyCode::Escape, KeyModifiers::NONE) => CertificateAnswer::Reject,
+            // Swallow everything else while we're waiting for an answer
+            _ => return Ok(true),
+        };
+        self.answer(answer, term_window);
+        Ok(true)
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
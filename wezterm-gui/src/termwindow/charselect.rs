@@ -95,7 +95,7 @@ struct Recent {
     frecency: Frecency,
 }
 
-fn recent_file_name() -> PathBuf {
+pub fn recent_file_name() -> PathBuf {
     config::DATA_DIR.join("recent-emoji.json")
 }
 
@@ -108,25 +108,25 @@ fn load_recents() -> anyhow::Result<Vec<Recent>> {
 }
 
 fn save_recent(alias: &Alias) -> anyhow::Result<()> {
-    let mut recents = load_recents().unwrap_or_else(|_| vec![]);
-    let glyph = alias.glyph();
-    if let Some(recent_idx) = recents.iter().position(|r| r.glyph == glyph) {
-        let recent = recents.get_mut(recent_idx).unwrap();
-        recent.frecency.register_access();
-    } else {
-        let mut frecency = Frecency::new();
-        frecency.register_access();
-        recents.push(Recent {
-            glyph,
-            name: alias.name().to_string(),
-            frecency,
-        });
-    }
-
-    let json = serde_json::to_string(&recents)?;
-    let file_name = recent_file_name();
-    std::fs::write(&file_name, json)?;
-    Ok(())
+    config::statefile::update(&recent_file_name(), |data| {
+        let mut recents: Vec<Recent> = data
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_else(|| vec![]);
+        let glyph = alias.glyph();
+        if let Some(recent_idx) = recents.iter().position(|r| r.glyph == glyph) {
+            let recent = recents.get_mut(recent_idx).unwrap();
+            recent.frecency.register_access();
+        } else {
+            let mut frecency = Frecency::new();
+            frecency.register_access();
+            recents.push(Recent {
+                glyph,
+                name: alias.name().to_string(),
+                frecency,
+            });
+        }
+        Ok(serde_json::to_vec(&recents)?)
+    })
 }
 
 fn build_aliases() -> Vec<Alias> {
@@ -392,11 +392,8 @@ impl CharSelector {
             .expect("to resolve char selection font");
         let metrics = RenderMetrics::with_font_metrics(&font.metrics());
 
-        let top_bar_height = if term_window.show_tab_bar && !term_window.config.tab_bar_at_bottom {
-            term_window.tab_bar_pixel_height().unwrap()
-        } else {
-            0.
-        };
+        let tab_bar = term_window.tab_bar_reserved_edges().unwrap();
+        let top_bar_height = tab_bar.top;
         let (padding_left, padding_top) = term_window.padding_left_top();
         let border = term_window.get_os_border();
         let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
@@ -532,7 +529,7 @@ impl CharSelector {
                     pixel_cell: metrics.cell_size.width as f32,
                 },
                 bounds: euclid::rect(
-                    padding_left,
+                    tab_bar.left + padding_left,
                     top_pixel_y,
                     size.cols as f32 * term_window.render_metrics.cell_size.width as f32,
                     size.rows as f32 * term_window.render_metrics.cell_size.height as f32,
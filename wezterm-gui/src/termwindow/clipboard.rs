@@ -1,9 +1,11 @@
+use crate::overlay::{broadcast_paste_message, confirm_broadcast_paste, start_overlay_pane};
 use crate::termwindow::TermWindowNotif;
 use crate::TermWindow;
 use config::keyassignment::{ClipboardCopyDestination, ClipboardPasteSource};
 use mux::pane::Pane;
 use mux::Mux;
 use std::sync::Arc;
+use wezterm_term::unicode_column_width;
 use window::{Clipboard, WindowOps};
 
 impl TermWindow {
@@ -23,7 +25,60 @@ impl TermWindow {
         }
     }
 
+    /// Copies text that was selected in `pane` to the clipboard,
+    /// and lets the domain of that pane know about the copy
+    pub fn copy_selection_to_clipboard(
+        &self,
+        pane: &Arc<dyn Pane>,
+        clipboard: ClipboardCopyDestination,
+        text: String,
+    ) {
+        if let Some(domain) = Mux::get().get_domain(pane.domain_id()) {
+            domain.text_copied_from_pane(pane.pane_id(), &text);
+        }
+        self.copy_to_clipboard(clipboard, text);
+    }
+
     pub fn paste_from_clipboard(&mut self, pane: &Arc<dyn Pane>, clipboard: ClipboardPasteSource) {
+        self.paste_from_clipboard_with(pane, clipboard, Some);
+    }
+
+    /// Pastes the lines from the clipboard as a block whose left edge
+    /// is at the column of the cursor; see `block_paste_text`
+    pub fn paste_block_from_clipboard(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+        clipboard: ClipboardPasteSource,
+    ) {
+        if pane.is_alt_screen_active() {
+            // Full screen applications manage the cursor themselves,
+            // so we can't tell where the following lines will begin
+            log::warn!("PasteAsBlock: pasting normally because the alternate screen is active");
+            return self.paste_from_clipboard(pane, clipboard);
+        }
+        let start_col = pane.get_cursor_position().x;
+        let bracketed = pane.is_bracketed_paste_enabled();
+        self.paste_from_clipboard_with(pane, clipboard, move |clip| {
+            let text = block_paste_text(&clip, start_col, bracketed);
+            if text.is_none() {
+                log::error!(
+                    "PasteAsBlock: not pasting multiple lines because the \
+                     application hasn't enabled bracketed paste, so each \
+                     line would be submitted as it is pasted"
+                );
+            }
+            text
+        });
+    }
+
+    fn paste_from_clipboard_with<F>(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+        clipboard: ClipboardPasteSource,
+        transform: F,
+    ) where
+        F: FnOnce(String) -> Option<String> + 'static,
+    {
         let pane_id = pane.pane_id();
         log::trace!(
             "paste_from_clipboard in pane {} {:?}",
@@ -37,7 +92,7 @@ impl TermWindow {
         };
         let future = window.get_clipboard(clipboard);
         promise::spawn::spawn(async move {
-            if let Ok(clip) = future.await {
+            if let Some(clip) = future.await.ok().and_then(transform) {
                 window.notify(TermWindowNotif::Apply(Box::new(move |myself| {
                     if let Some(pane) = myself
                         .pane_state(pane_id)
@@ -49,7 +104,7 @@ impl TermWindow {
                             mux.get_pane(pane_id)
                         })
                     {
-                        pane.send_paste(&clip).ok();
+                        myself.send_paste_to_broadcast(&pane, clip);
                     }
                 })));
             }
@@ -57,4 +112,102 @@ impl TermWindow {
         .detach();
         self.maybe_scroll_to_bottom_for_input(&pane);
     }
+
+    /// Pastes `text` into `pane` and into the panes that receive its
+    /// broadcast input, asking first if there are any such panes
+    fn send_paste_to_broadcast(&mut self, pane: &Arc<dyn Pane>, text: String) {
+        let targets = self.broadcast_targets(pane);
+        if targets.is_empty() || !self.config.broadcast_paste_confirmation {
+            pane.send_paste(&text).ok();
+            for target in targets {
+                target.send_paste(&text).ok();
+            }
+            return;
+        }
+
+        let message = broadcast_paste_message(pane, &targets, &text);
+        let focused = Arc::clone(pane);
+        let (overlay, future) = start_overlay_pane(self, pane, move |_pane_id, term| {
+            confirm_broadcast_paste(message, focused, targets, text, term)
+        });
+        self.assign_overlay_for_pane(pane.pane_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+}
+
+/// Arranges the lines of `text` so that, when they are typed into a
+/// line oriented program whose cursor is at `start_col`, they line up
+/// as a rectangular block.
+/// Each line after the first is indented by `start_col` spaces, which
+/// assumes that the program starts each new line at the left edge of
+/// the screen, and every line is padded with spaces to the width of the
+/// widest line, so that the block keeps its shape.
+/// Lines may be separated by either LF or CRLF; the result uses LF,
+/// which is canonicalized as usual when it is pasted.
+/// Unless the program has enabled bracketed paste, each newline would
+/// submit the line as though enter had been pressed, so that text with
+/// more than one line returns None.
+pub fn block_paste_text(text: &str, start_col: usize, bracketed: bool) -> Option<String> {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    let text = text.strip_suffix('\r').unwrap_or(text);
+    let lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    if lines.len() > 1 && !bracketed {
+        return None;
+    }
+    let width = lines
+        .iter()
+        .map(|line| unicode_column_width(line, None))
+        .max()
+        .unwrap_or(0);
+
+    let mut result = String::new();
+    for (idx, line) in lines.iter().enumerate() {
+        if idx > 0 {
+            result.push('\n');
+            result.push_str(&" ".repeat(start_col));
+        }
+        result.push_str(line);
+        result.push_str(&" ".repeat(width - unicode_column_width(line, None)));
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn block_lines() {
+        assert_eq!(block_paste_text("ab\nc\n", 4, true).unwrap(), "ab\n    c ");
+        assert_eq!(
+            block_paste_text("ab\r\nc\r\n", 4, true),
+            block_paste_text("ab\nc\n", 4, true)
+        );
+        assert_eq!(block_paste_text("single", 10, true).unwrap(), "single");
+    }
+
+    #[test]
+    fn block_wide_chars() {
+        // Padding is measured in cells, so a line holding a double
+        // width character is as wide as one with two narrow ones
+        assert_eq!(
+            block_paste_text("日x\nabc\nd", 2, true).unwrap(),
+            "日x\n  abc\n  d  "
+        );
+    }
+
+    #[test]
+    fn block_needs_bracketed_paste() {
+        // Without bracketed paste, the newline would submit the first
+        // line and the indentation would be typed at the next prompt
+        assert_eq!(block_paste_text("ab\nc", 4, false), None);
+        assert_eq!(block_paste_text("ab\r\nc\r\n", 4, false), None);
+        // A single line has no newline to submit, with or without
+        // its trailing line break
+        assert_eq!(block_paste_text("single\n", 10, false).unwrap(), "single");
+        assert_eq!(block_paste_text("", 10, false).unwrap(), "");
+    }
 }
@@ -0,0 +1,161 @@
+//! Tracks the output that is caused by key presses so that it can be
+//! repainted promptly when `max_input_latency` is configured, and so
+//! that the time from key press to the presentation of its echo can
+//! be reported in the debug overlay.
+use crate::termwindow::TermWindow;
+use ::window::WindowOps;
+use mux::pane::{Pane, PaneId};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Output that arrives later than this after a key press is not
+/// considered to have been caused by it
+const ECHO_WINDOW: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Default)]
+pub struct InputLatency {
+    /// The pane that most recently received a key press, and when
+    sent: Option<(PaneId, Instant)>,
+    /// When the key press that caused the output that is waiting
+    /// to be presented was made
+    echo_pending: Option<Instant>,
+    /// The most recently measured key press to buffer swap time
+    pub last: Option<Duration>,
+}
+
+impl InputLatency {
+    fn note_input_sent(&mut self, pane_id: PaneId, now: Instant) {
+        self.sent.replace((pane_id, now));
+    }
+
+    /// Called when output arrives in `pane_id` at `now`.
+    /// Returns the time of the key press that caused it, if it is
+    /// recent enough to consider it to be the cause, and remembers
+    /// it so that the latency can be measured when the frame that
+    /// presents it is swapped.  Only the first output following a
+    /// key press is attributed to it.
+    fn note_output(&mut self, pane_id: PaneId, now: Instant) -> Option<Instant> {
+        match self.sent {
+            Some((sent_pane, when)) if sent_pane == pane_id => {
+                self.sent.take();
+                if now.saturating_duration_since(when) <= ECHO_WINDOW {
+                    self.echo_pending.replace(when);
+                    Some(when)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn note_presented(&mut self, now: Instant) -> Option<Duration> {
+        let when = self.echo_pending.take()?;
+        let latency = now.saturating_duration_since(when);
+        self.last.replace(latency);
+        Some(latency)
+    }
+}
+
+impl TermWindow {
+    /// Records that a key press was sent to `pane`
+    pub fn note_input_sent(&mut self, pane: &Arc<dyn Pane>) {
+        self.input_latency
+            .note_input_sent(pane.pane_id(), Instant::now());
+    }
+
+    /// Called when output arrives in a visible pane.
+    /// If the output was caused by a recent key press and
+    /// `max_input_latency` is configured, asks the window to repaint
+    /// without waiting for the frame pacing imposed by `max_fps`.
+    /// Returns true if the repaint was requested.
+    pub(crate) fn invalidate_for_input_echo(&mut self, pane_id: PaneId) -> bool {
+        let now = Instant::now();
+        let when = match self.input_latency.note_output(pane_id, now) {
+            Some(when) => when,
+            None => return false,
+        };
+
+        let max_latency = match self.config.max_input_latency {
+            Some(ms) => Duration::from_millis(ms),
+            None => return false,
+        };
+        if now.saturating_duration_since(when) > max_latency {
+            return false;
+        }
+
+        let window = match self.window.as_ref() {
+            Some(window) => window,
+            None => return false,
+        };
+        metrics::histogram!("gui.paint.input_echo").record(1.);
+        window.invalidate_immediately();
+        true
+    }
+
+    /// Called after a frame has been presented to measure the time
+    /// from the key press that caused it
+    pub(crate) fn record_input_latency(&mut self) {
+        if let Some(latency) = self.input_latency.note_presented(Instant::now()) {
+            metrics::histogram!("gui.input_latency").record(latency);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn attributes_first_output_only() {
+        let mut latency = InputLatency::default();
+        let sent = Instant::now();
+        latency.note_input_sent(1, sent);
+
+        let now = sent + Duration::from_millis(5);
+        assert_eq!(latency.note_output(1, now), Some(sent));
+        assert_eq!(latency.note_output(1, now), None);
+
+        assert_eq!(
+            latency.note_presented(sent + Duration::from_millis(12)),
+            Some(Duration::from_millis(12))
+        );
+        assert_eq!(latency.last, Some(Duration::from_millis(12)));
+        assert_eq!(
+            latency.note_presented(sent + Duration::from_millis(20)),
+            None
+        );
+    }
+
+    #[test]
+    fn ignores_other_panes() {
+        let mut latency = InputLatency::default();
+        let sent = Instant::now();
+        latency.note_input_sent(1, sent);
+
+        let now = sent + Duration::from_millis(5);
+        assert_eq!(latency.note_output(2, now), None);
+        assert_eq!(latency.note_presented(now), None);
+        assert_eq!(latency.note_output(1, now), Some(sent));
+    }
+
+    #[test]
+    fn ignores_late_output() {
+        let mut latency = InputLatency::default();
+        let sent = Instant::now();
+        latency.note_input_sent(1, sent);
+
+        assert_eq!(latency.note_output(1, sent + ECHO_WINDOW), Some(sent));
+        assert_eq!(
+            latency.note_presented(sent + ECHO_WINDOW),
+            Some(ECHO_WINDOW)
+        );
+
+        latency.note_input_sent(1, sent);
+        let late = sent + ECHO_WINDOW + Duration::from_millis(1);
+        assert_eq!(latency.note_output(1, late), None);
+        assert_eq!(latency.note_presented(late), None);
+        // The late output consumed the key press
+        assert_eq!(latency.note_output(1, late), None);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::termwindow::animation::AnimationSources;
 use crate::termwindow::InputMap;
 use ::window::{
     DeadKeyStatus, KeyCode, KeyEvent, KeyboardLedStatus, Modifiers, RawKeyEvent, WindowOps,
@@ -9,6 +10,7 @@ use smol::Timer;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use termwiz::input::KeyboardEncoding;
+use wezterm_dynamic::ToDynamic;
 
 #[derive(Debug, Clone)]
 pub struct KeyTableStateEntry {
@@ -21,6 +23,9 @@ pub struct KeyTableStateEntry {
     prevent_fallback: bool,
     /// The timeout duration; used when updating the expiration
     timeout_milliseconds: Option<u64>,
+    /// Whether this was activated by `auto_key_tables` rather than
+    /// explicitly.  Such entries are kept at the bottom of the stack.
+    auto: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -33,6 +38,12 @@ pub struct KeyTableArgs<'a> {
     pub prevent_fallback: bool,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, ToDynamic)]
+pub struct ActiveKeyTable {
+    pub name: String,
+    pub auto: bool,
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct KeyTableState {
     stack: Vec<KeyTableStateEntry>,
@@ -52,15 +63,61 @@ impl KeyTableState {
             until_unknown: args.until_unknown,
             prevent_fallback: args.prevent_fallback,
             timeout_milliseconds: args.timeout_milliseconds,
+            auto: false,
         });
     }
 
+    /// Pops the most recent explicitly activated table
     pub fn pop(&mut self) {
-        self.stack.pop();
+        if self.stack.last().map(|entry| !entry.auto).unwrap_or(false) {
+            self.stack.pop();
+        }
     }
 
+    /// Pops all of the explicitly activated tables
     pub fn clear_stack(&mut self) {
-        self.stack.clear();
+        self.stack.retain(|entry| entry.auto);
+    }
+
+    /// Replaces the automatically activated tables with `names`,
+    /// the first of which takes precedence over the others.
+    /// Returns true if that changed the stack.
+    pub fn set_auto_tables(&mut self, names: &[String]) -> bool {
+        let current = self
+            .stack
+            .iter()
+            .rev()
+            .filter(|entry| entry.auto)
+            .map(|entry| entry.name.as_str());
+        if current.eq(names.iter().map(String::as_str)) {
+            return false;
+        }
+        self.stack.retain(|entry| !entry.auto);
+        let auto = names.iter().rev().map(|name| KeyTableStateEntry {
+            name: name.to_string(),
+            expiration: None,
+            one_shot: false,
+            until_unknown: false,
+            prevent_fallback: false,
+            timeout_milliseconds: None,
+            auto: true,
+        });
+        self.stack.splice(0..0, auto);
+        true
+    }
+
+    /// Returns the names of the active tables, most recent first,
+    /// along with whether each was activated automatically
+    pub fn active_tables(&mut self) -> Vec<ActiveKeyTable> {
+        while self.process_expiration() {}
+        self.stack
+            .iter()
+            .rev()
+            .map(|entry| ActiveKeyTable {
+                name: entry.name.clone(),
+                auto: entry.auto,
+            })
+            .collect()
     }
 
     pub fn process_expiration(&mut self) -> bool {
@@ -90,9 +147,14 @@ impl KeyTableState {
         }
     }
 
+    /// Returns the most recent explicitly activated table; tables
+    /// activated by `auto_key_tables` are not included
     pub fn current_table(&mut self) -> Option<&str> {
         while self.process_expiration() {}
-        self.stack.last().map(|entry| entry.name.as_str())
+        self.stack
+            .last()
+            .filter(|entry| !entry.auto)
+            .map(|entry| entry.name.as_str())
     }
 
     fn lookup_key(
@@ -208,6 +270,46 @@ impl super::TermWindow {
         }
     }
 
+    /// Sends a key event that was just delivered to `pane` to the
+    /// other panes receiving broadcast input.  Each of them encodes
+    /// the key according to its own keyboard encoding state.
+    fn broadcast_key(
+        &self,
+        pane: &Arc<dyn Pane>,
+        key_event: Option<&KeyEvent>,
+        key: termwiz::input::KeyCode,
+        mods: termwiz::input::Modifiers,
+        is_down: bool,
+    ) {
+        for target in self.broadcast_targets(pane) {
+            let encoded = key_event.and_then(|key_event| {
+                self.encode_win32_input(&target, key_event)
+                    .or_else(|| self.encode_kitty_input(&target, key_event))
+            });
+            let res = if let Some(encoded) = encoded {
+                target
+                    .writer()
+                    .write_all(encoded.as_bytes())
+                    .context("sending encoded data")
+            } else if is_down {
+                target.key_down(key, mods)
+            } else {
+                target.key_up(key, mods)
+            };
+            if let Err(err) = res {
+                log::error!("broadcasting key to pane {}: {err:#}", target.pane_id());
+            }
+        }
+    }
+
+    /// Sends composed text that was just written to `pane` to the
+    /// other panes receiving broadcast input
+    fn broadcast_text(&self, pane: &Arc<dyn Pane>, text: &str) {
+        for target in self.broadcast_targets(pane) {
+            target.writer().write_all(text.as_bytes()).ok();
+        }
+    }
+
     fn lookup_key(
         &mut self,
         pane: &Arc<dyn Pane>,
@@ -305,14 +407,34 @@ impl super::TermWindow {
                 }
 
                 self.key_table_state.did_process_key();
-                let handled = match self.perform_key_assignment(&pane, &entry.action) {
-                    Ok(PerformAssignmentResult::Handled) => true,
-                    Err(_) => true,
-                    Ok(_) => false,
+                let handled = match self.run_pre_assignment_hook(&pane, &entry.action) {
+                    Some(action) => {
+                        self.assignment_key_press =
+                            match self.win_key_code_to_termwiz_key_code(keycode) {
+                                Key::Code(term_key) => Some((term_key, raw_modifiers)),
+                                _ => None,
+                            };
+                        let result = self.perform_key_assignment(&pane, &action);
+                        self.assignment_key_press.take();
+                        match result {
+                            Ok(PerformAssignmentResult::Handled) => true,
+                            Err(_) => true,
+                            Ok(_) => false,
+                        }
+                    }
+                    // The key was bound, so a cancelled assignment
+                    // still consumes the key press
+                    None => true,
                 };
 
                 if handled {
                     context.invalidate();
+                    self.present_key_press(
+                        pane,
+                        keycode,
+                        raw_modifiers | leader_mod,
+                        Some(&entry.action),
+                    );
 
                     if leader_active {
                         // A successful leader key-lookup cancels the leader
@@ -402,6 +524,13 @@ impl super::TermWindow {
                     };
 
                     if did_encode {
+                        self.broadcast_key(pane, key_event, term_key, tw_raw_modifiers, is_down);
+                        if is_down && !keycode.is_modifier() {
+                            self.note_input_sent(pane);
+                        }
+                        if is_down {
+                            self.present_key_press(pane, keycode, raw_modifiers, None);
+                        }
                         if is_down
                             && !keycode.is_modifier()
                             && self.pane_state(pane.pane_id()).overlay.is_none()
@@ -428,6 +557,11 @@ impl super::TermWindow {
     }
 
     pub fn raw_key_event_impl(&mut self, key: RawKeyEvent, context: &dyn WindowOps) {
+        if key.key_is_down {
+            // Any key stops a touchpad fling
+            self.cancel_kinetic_scroll();
+        }
+
         // The leader key is a kind of modal modifier key.
         // It is allowed to be active for up to the leader timeout duration,
         // after which it auto-deactivates.
@@ -534,7 +668,7 @@ impl super::TermWindow {
     pub fn leader_is_active(&self) -> bool {
         match self.leader_is_down.as_ref() {
             Some(expiry) if *expiry > std::time::Instant::now() => {
-                self.update_next_frame_time(Some(*expiry));
+                self.update_next_frame_time(AnimationSources::KEY_TIMEOUT, Some(*expiry));
                 true
             }
             Some(_) => false,
@@ -545,7 +679,7 @@ impl super::TermWindow {
     pub fn leader_is_active_mut(&mut self) -> bool {
         match self.leader_is_down.as_ref() {
             Some(expiry) if *expiry > std::time::Instant::now() => {
-                self.update_next_frame_time(Some(*expiry));
+                self.update_next_frame_time(AnimationSources::KEY_TIMEOUT, Some(*expiry));
                 true
             }
             Some(_) => {
@@ -568,7 +702,7 @@ impl super::TermWindow {
 
                 if let Some(entry) = overlay.key_table_state.stack.last() {
                     if let Some(expiry) = entry.expiration {
-                        self.update_next_frame_time(Some(expiry));
+                        self.update_next_frame_time(AnimationSources::KEY_TIMEOUT, Some(expiry));
                     }
                 }
             }
@@ -578,12 +712,25 @@ impl super::TermWindow {
         }
         if let Some(entry) = self.key_table_state.stack.last() {
             if let Some(expiry) = entry.expiration {
-                self.update_next_frame_time(Some(expiry));
+                self.update_next_frame_time(AnimationSources::KEY_TIMEOUT, Some(expiry));
             }
         }
         name
     }
 
+    /// Returns the active key tables, most recent first; the tables
+    /// of an overlay in the active pane take precedence
+    pub fn active_key_tables(&mut self) -> Vec<ActiveKeyTable> {
+        let mut tables = vec![];
+        if let Some(pane) = self.get_active_pane_or_overlay() {
+            if let Some(overlay) = self.pane_state(pane.pane_id()).overlay.as_mut() {
+                tables = overlay.key_table_state.active_tables();
+            }
+        }
+        tables.extend(self.key_table_state.active_tables());
+        tables
+    }
+
     pub fn composition_status(&self) -> &DeadKeyStatus {
         &self.dead_key_status
     }
@@ -703,6 +850,16 @@ impl super::TermWindow {
                 };
 
                 if res.is_ok() {
+                    self.broadcast_key(
+                        &pane,
+                        Some(&window_key),
+                        key,
+                        modifiers,
+                        window_key.key_is_down,
+                    );
+                    if window_key.key_is_down {
+                        self.present_key_press(&pane, &window_key.key, modifiers, None);
+                    }
                     if window_key.key_is_down
                         && !key.is_modifier()
                         && self.pane_state(pane.pane_id()).overlay.is_none()
@@ -716,6 +873,9 @@ impl super::TermWindow {
                         context.set_cursor(None);
                     }
                     if !key.is_modifier() {
+                        if window_key.key_is_down {
+                            self.note_input_sent(&pane);
+                        }
                         context.invalidate();
                     }
                 }
@@ -736,6 +896,8 @@ impl super::TermWindow {
                     log::info!("send to pane string={:?}", s);
                 }
                 pane.writer().write_all(s.as_bytes()).ok();
+                self.broadcast_text(&pane, &s);
+                self.note_input_sent(&pane);
                 self.maybe_scroll_to_bottom_for_input(&pane);
                 context.invalidate();
             }
@@ -867,3 +1029,90 @@ impl super::TermWindow {
         Key::Code(code)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn activate(state: &mut KeyTableState, name: &str) {
+        state.activate(KeyTableArgs {
+            name,
+            timeout_milliseconds: None,
+            replace_current: false,
+            one_shot: false,
+            until_unknown: false,
+            prevent_fallback: false,
+        });
+    }
+
+    fn names(state: &mut KeyTableState) -> Vec<(String, bool)> {
+        state
+            .active_tables()
+            .into_iter()
+            .map(|table| (table.name, table.auto))
+            .collect()
+    }
+
+    fn table(name: &str, auto: bool) -> (String, bool) {
+        (name.to_string(), auto)
+    }
+
+    #[test]
+    fn auto_tables_sit_below_explicit_tables() {
+        let mut state = KeyTableState::default();
+        activate(&mut state, "resize");
+        assert!(state.set_auto_tables(&["vim".to_string(), "ssh".to_string()]));
+        assert_eq!(
+            names(&mut state),
+            vec![
+                table("resize", false),
+                table("vim", true),
+                table("ssh", true)
+            ]
+        );
+
+        // Setting the same tables again is not a change
+        assert!(!state.set_auto_tables(&["vim".to_string(), "ssh".to_string()]));
+
+        // Activations made later still go on top
+        activate(&mut state, "copy");
+        assert!(state.set_auto_tables(&["ssh".to_string()]));
+        assert_eq!(
+            names(&mut state),
+            vec![
+                table("copy", false),
+                table("resize", false),
+                table("ssh", true)
+            ]
+        );
+        assert_eq!(state.current_table(), Some("copy"));
+    }
+
+    #[test]
+    fn pop_and_clear_keep_auto_tables() {
+        let mut state = KeyTableState::default();
+        state.set_auto_tables(&["vim".to_string()]);
+        assert_eq!(state.current_table(), None);
+
+        activate(&mut state, "resize");
+        activate(&mut state, "copy");
+        state.pop();
+        assert_eq!(
+            names(&mut state),
+            vec![table("resize", false), table("vim", true)]
+        );
+        state.pop();
+        // Popping with only auto tables left doesn't remove them
+        state.pop();
+        assert_eq!(names(&mut state), vec![table("vim", true)]);
+        assert_eq!(state.current_table(), None);
+
+        activate(&mut state, "resize");
+        activate(&mut state, "copy");
+        state.clear_stack();
+        assert_eq!(names(&mut state), vec![table("vim", true)]);
+
+        assert!(state.set_auto_tables(&[]));
+        assert!(names(&mut state).is_empty());
+    }
+}
@@ -0,0 +1,112 @@
+//! The connection health indicator that `show_mux_latency` adds to the
+//! right status when the active pane belongs to a remote mux domain.
+//! It is refreshed along with the status, from the stats that the
+//! domain caches, so it never waits on the connection.
+use crate::termwindow::TermWindow;
+use mux::domain::ConnectionStats;
+use mux::Mux;
+use std::time::Duration;
+
+/// Round trip times at or above this are shown as a warning
+const WARNING_RTT: Duration = Duration::from_millis(150);
+/// Round trip times at or above this are shown as critical
+const CRITICAL_RTT: Duration = Duration::from_millis(500);
+/// A connected domain that has heard nothing from the remote mux for
+/// this long is shown as critical: even an idle connection is checked
+/// more often than this
+const STALE_TRAFFIC: Duration = Duration::from_secs(150);
+
+/// Formats `stats` as a colored dot followed by the latency, using
+/// the ANSI colors so that it follows the color scheme
+fn format_indicator(stats: &ConnectionStats) -> String {
+    const GREEN: u8 = 2;
+    const YELLOW: u8 = 3;
+    const RED: u8 = 1;
+
+    let stale = stats
+        .last_traffic
+        .map(|when| when.elapsed() >= STALE_TRAFFIC)
+        .unwrap_or(false);
+    let (color, text) = if !stats.connected {
+        (RED, "offline".to_string())
+    } else if stale {
+        (RED, "no response".to_string())
+    } else {
+        match stats.rtt {
+            None => (YELLOW, "--".to_string()),
+            Some(rtt) => {
+                let color = if rtt >= CRITICAL_RTT {
+                    RED
+                } else if rtt >= WARNING_RTT {
+                    YELLOW
+                } else {
+                    GREEN
+                };
+                (color, format!("{}ms", rtt.as_millis()))
+            }
+        }
+    };
+    format!("\x1b[3{color}m●\x1b[0m {text} ")
+}
+
+impl TermWindow {
+    fn mux_latency_indicator(&self) -> String {
+        if !self.config.show_mux_latency {
+            return String::new();
+        }
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return String::new(),
+        };
+        Mux::get()
+            .get_domain(pane.domain_id())
+            .and_then(|domain| domain.connection_stats())
+            .map(|stats| format_indicator(&stats))
+            .unwrap_or_default()
+    }
+
+    /// Refreshes the indicator, redrawing the tab bar if it changed
+    pub(super) fn update_mux_latency_status(&mut self) {
+        let status = self.mux_latency_indicator();
+        if status != self.mux_latency_status {
+            self.mux_latency_status = status;
+            self.update_title_post_status();
+        } else if !status.is_empty() {
+            // Keep refreshing even if nothing else updates the status
+            self.schedule_next_status_update();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Instant;
+
+    fn stats(rtt_ms: Option<u64>) -> ConnectionStats {
+        ConnectionStats {
+            rtt: rtt_ms.map(Duration::from_millis),
+            last_traffic: Some(Instant::now()),
+            reconnects: 0,
+            connected: true,
+        }
+    }
+
+    #[test]
+    fn thresholds() {
+        assert_eq!(format_indicator(&stats(Some(20))), "\x1b[32m●\x1b[0m 20ms ");
+        assert_eq!(
+            format_indicator(&stats(Some(150))),
+            "\x1b[33m●\x1b[0m 150ms "
+        );
+        assert_eq!(
+            format_indicator(&stats(Some(900))),
+            "\x1b[31m●\x1b[0m 900ms "
+        );
+        assert_eq!(format_indicator(&stats(None)), "\x1b[33m●\x1b[0m -- ");
+        assert_eq!(
+            format_indicator(&ConnectionStats::default()),
+            "\x1b[31m●\x1b[0m offline "
+        );
+    }
+}
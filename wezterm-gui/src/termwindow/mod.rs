@@ -1,42 +1,49 @@
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::range_plus_one))]
 use super::renderstate::*;
-use super::utilsprites::RenderMetrics;
+use super::utilsprites::{CellMetricsOverride, RenderMetrics};
 use crate::colorease::ColorEase;
 use crate::frontend::{front_end, try_front_end};
 use crate::inputmap::InputMap;
 use crate::overlay::{
     confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program, launcher,
-    start_overlay, start_overlay_pane, CopyModeParams, CopyOverlay, LauncherArgs, LauncherFlags,
-    QuickSelectOverlay,
+    start_overlay, start_overlay_pane, upload_dropped_files, CopyModeParams, CopyOverlay,
+    LauncherArgs, LauncherFlags, QuickSelectOverlay, Upload, UploadRequest,
 };
 use crate::resize_increment_calculator::ResizeIncrementCalculator;
 use crate::scripting::guiwin::GuiWin;
 use crate::scrollbar::*;
 use crate::selection::Selection;
 use crate::shapecache::*;
-use crate::tabbar::{TabBarItem, TabBarState};
+use crate::tabbar::{TabBarItem, TabBarScroll, TabBarState};
+use crate::termwindow::animation::{AnimationSources, FrameStats, NextFrame};
 use crate::termwindow::background::{
     load_background_image, reload_background_image, LoadedBackgroundLayer,
 };
+use crate::termwindow::broadcast::BroadcastInput;
+use crate::termwindow::inputlatency::InputLatency;
 use crate::termwindow::keyevent::{KeyTableArgs, KeyTableState};
 use crate::termwindow::modal::Modal;
+use crate::termwindow::render::line_cache::PaneLineCache;
 use crate::termwindow::render::paint::AllowImage;
+use crate::termwindow::render::preedit::PlacedPreedit;
 use crate::termwindow::render::{
-    CachedLineState, LineQuadCacheKey, LineQuadCacheValue, LineToEleShapeCacheKey,
-    LineToElementShapeItem,
+    CachedLineState, LineToEleShapeCacheKey, LineToElementShapeItem, RenderCacheStats,
 };
+use crate::termwindow::scrolling::{Fling, ScrollAccumulator, VelocityTracker};
+use crate::termwindow::smartnav::{NavigationAction, SmartNavigation};
 use crate::termwindow::webgpu::WebGpuState;
 use ::wezterm_term::input::{ClickPosition, MouseButton as TMB};
 use ::window::*;
 use anyhow::{anyhow, ensure, Context};
 use config::keyassignment::{
-    KeyAssignment, PaneDirection, Pattern, PromptInputLine, QuickSelectArguments,
-    RotationDirection, SpawnCommand, SplitSize,
+    KeyAssignment, PaneDirection, PaneSelectArguments, PaneSelectMode, Pattern, PromptInputLine,
+    QuickSelectArguments, RespawnPane, RotationDirection, SaveScrollback, ScrollMarkKind,
+    ScrollToMark, SpawnCommand, SplitSize,
 };
 use config::window::WindowLevel;
 use config::{
-    configuration, AudibleBell, ConfigHandle, Dimension, DimensionContext, FrontEndSelection,
-    GeometryOrigin, GuiPosition, TermConfig, WindowCloseConfirmation,
+    configuration, ConfigHandle, Dimension, DimensionContext, DropBehavior, DroppedFileQuoting,
+    FrontEndSelection, GeometryOrigin, GuiPosition, TermConfig, WindowCloseConfirmation,
 };
 use lfucache::*;
 use mlua::{FromLua, UserData, UserDataFields};
@@ -45,22 +52,25 @@ use mux::pane::{
 };
 use mux::renderable::RenderableDimensions;
 use mux::tab::{
-    PositionedPane, PositionedSplit, SplitDirection, SplitRequest, SplitSize as MuxSplitSize, Tab,
-    TabId,
+    FloatingPaneGeometry, PositionedPane, PositionedSplit, SplitDirection, SplitRequest,
+    SplitSize as MuxSplitSize, Tab, TabId,
 };
 use mux::window::WindowId as MuxWindowId;
 use mux::{Mux, MuxNotification};
 use mux_lua::MuxPane;
+use portable_pty::CommandBuilder;
 use smol::channel::Sender;
 use smol::Timer;
 use std::cell::{RefCell, RefMut};
 use std::collections::{HashMap, LinkedList};
 use std::ops::Add;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use termwiz::hyperlink::Hyperlink;
+use termwiz::escape::osc::Progress;
+use termwiz::hyperlink::{Hyperlink, Rule, RuleCaptureMatch};
 use termwiz::surface::SequenceNo;
 use wezterm_dynamic::Value;
 use wezterm_font::FontConfiguration;
@@ -68,21 +78,37 @@ use wezterm_term::color::ColorPalette;
 use wezterm_term::input::LastMouseClick;
 use wezterm_term::{Alert, StableRowIndex, TerminalConfiguration, TerminalSize};
 
+pub mod animation;
+mod autokeytable;
 pub mod background;
+mod bell;
 pub mod box_model;
+pub mod broadcast;
+pub mod certprompt;
 pub mod charselect;
 pub mod clipboard;
+mod inputlatency;
 pub mod keyevent;
+mod latency;
 pub mod modal;
 mod mouseevent;
 pub mod palette;
 pub mod paneselect;
+pub mod presentation;
 mod prevcursor;
 pub mod render;
+mod reopen;
 pub mod resize;
+mod scrolling;
 mod selection;
+mod smartnav;
 pub mod spawn;
+pub mod tabbarfocus;
 pub mod webgpu;
+pub mod taboverview;
+pub mod workspaceselect;
+mod workspacestyle;
+pub mod zen;
 use crate::spawn::SpawnWhere;
 use prevcursor::PrevCursorPos;
 
@@ -159,6 +185,9 @@ pub enum UIItemType {
     ScrollThumb,
     BelowScrollThumb,
     Split(PositionedSplit),
+    PaneTitleBar(PaneId),
+    ClosePane(PaneId),
+    TogglePaneZoom(PaneId),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -179,10 +208,18 @@ impl UIItem {
     }
 }
 
+/// The row of a semantic prompt, along with the completion status
+/// of the command that was entered at that prompt
+#[derive(Clone, Copy, Debug)]
+pub struct PromptMark {
+    pub row: StableRowIndex,
+    pub status: Option<wezterm_term::CommandStatus>,
+}
+
 #[derive(Clone, Default)]
 pub struct SemanticZoneCache {
     seqno: SequenceNo,
-    zones: Vec<StableRowIndex>,
+    zones: Vec<PromptMark>,
 }
 
 pub struct OverlayState {
@@ -204,6 +241,18 @@ pub struct PaneState {
 
     bell_start: Option<Instant>,
     pub mouse_terminal_coords: Option<(ClickPosition, StableRowIndex)>,
+    /// The most recent double click selection, which double
+    /// clicking the same spot again expands
+    word_expansion: Option<selection::WordExpansion>,
+
+    /// Fractional cells of touchpad scrolling that have yet to be applied
+    scroll_x: ScrollAccumulator,
+    scroll_y: ScrollAccumulator,
+    /// When a synthesized wheel event was last sent to an application
+    /// that handles the wheel itself
+    last_app_wheel: Option<Instant>,
+    /// The rendered form of the visible lines
+    line_cache: PaneLineCache,
 }
 
 /// Data used when synchronously formatting pane and window titles
@@ -215,6 +264,14 @@ pub struct TabInformation {
     pub active_pane: Option<PaneInformation>,
     pub window_id: MuxWindowId,
     pub tab_title: String,
+    /// The OSC 9;4 progress reported by the panes in this tab
+    pub progress: Progress,
+    /// true while the tab is highlighted because one of its
+    /// panes rang the bell
+    pub is_bell_ringing: bool,
+    /// true if reading the output of any of the panes in this
+    /// tab has been paused
+    pub has_paused_pane: bool,
 }
 
 impl UserData for TabInformation {
@@ -243,6 +300,21 @@ impl UserData for TabInformation {
         });
         fields.add_field_method_get("window_id", |_, this| Ok(this.window_id));
         fields.add_field_method_get("tab_title", |_, this| Ok(this.tab_title.clone()));
+        fields.add_field_method_get("is_bell_ringing", |_, this| Ok(this.is_bell_ringing));
+        fields.add_field_method_get("has_paused_pane", |_, this| Ok(this.has_paused_pane));
+        fields.add_field_method_get("progress", |lua, this| {
+            let (state, percent) = match this.progress {
+                Progress::None => return Ok(mlua::Value::Nil),
+                Progress::SetPercentage(pct) => ("Normal", pct),
+                Progress::SetError(pct) => ("Error", pct),
+                Progress::Indeterminate => ("Indeterminate", 0),
+                Progress::Paused(pct) => ("Paused", pct),
+            };
+            let table = lua.create_table()?;
+            table.set("state", state)?;
+            table.set("percent", percent)?;
+            Ok(mlua::Value::Table(table))
+        });
         fields.add_field_method_get("window_title", |_, this| {
             let mux = Mux::get();
             let window = mux.get_window(this.window_id).ok_or_else(|| {
@@ -261,6 +333,10 @@ pub struct PaneInformation {
     pub is_active: bool,
     pub is_zoomed: bool,
     pub has_unseen_output: bool,
+    pub has_activity_alert: bool,
+    pub has_silence_alert: bool,
+    pub is_output_rate_limited: bool,
+    pub is_output_paused: bool,
     pub left: usize,
     pub top: usize,
     pub width: usize,
@@ -278,6 +354,12 @@ impl UserData for PaneInformation {
         fields.add_field_method_get("is_active", |_, this| Ok(this.is_active));
         fields.add_field_method_get("is_zoomed", |_, this| Ok(this.is_zoomed));
         fields.add_field_method_get("has_unseen_output", |_, this| Ok(this.has_unseen_output));
+        fields.add_field_method_get("has_activity_alert", |_, this| Ok(this.has_activity_alert));
+        fields.add_field_method_get("has_silence_alert", |_, this| Ok(this.has_silence_alert));
+        fields.add_field_method_get("is_output_rate_limited", |_, this| {
+            Ok(this.is_output_rate_limited)
+        });
+        fields.add_field_method_get("is_output_paused", |_, this| Ok(this.is_output_paused));
         fields.add_field_method_get("left", |_, this| Ok(this.left));
         fields.add_field_method_get("top", |_, this| Ok(this.top));
         fields.add_field_method_get("width", |_, this| Ok(this.width));
@@ -341,6 +423,10 @@ pub struct TabState {
     /// contents, we're overlaying a little internal application
     /// tab.  We'll also route input to it.
     pub overlay: Option<OverlayState>,
+
+    /// When a pane in this tab most recently rang the bell,
+    /// for highlighting the tab in the tab bar
+    bell_start: Option<Instant>,
 }
 
 /// Manages the state/queue of lua based event handlers.
@@ -365,11 +451,18 @@ pub struct TermWindow {
     os_parameters: Option<parameters::Parameters>,
     /// When we most recently received keyboard focus
     pub focused: Option<Instant>,
+    /// The pane that `pane-focused` was last emitted for, which is
+    /// cleared when the window loses focus
+    focused_pane_id: Option<PaneId>,
     fonts: Rc<FontConfiguration>,
     /// Window dimensions and dpi
     pub dimensions: Dimensions,
     pub window_state: WindowState,
     pub resizes_pending: usize,
+    /// The most recent dpi change seen while the window was being
+    /// moved or resized, which is applied once the dpi settles
+    pending_dpi_change: Option<(Dimensions, WindowState)>,
+    dpi_change_generation: usize,
     is_repaint_pending: bool,
     pending_scale_changes: LinkedList<resize::ScaleChange>,
     /// Terminal dimensions
@@ -377,23 +470,56 @@ pub struct TermWindow {
     pub mux_window_id: MuxWindowId,
     pub mux_window_id_for_subscriptions: Arc<Mutex<MuxWindowId>>,
     pub render_metrics: RenderMetrics,
+    /// Runtime override for line_height and cell_width
+    cell_metrics_override: CellMetricsOverride,
+    /// Runtime override for snap_glyphs_to_pixel_grid
+    integer_scaling: Option<bool>,
     render_state: Option<RenderState>,
     input_map: InputMap,
     /// If is_some, the LEADER modifier is active until the specified instant.
     leader_is_down: Option<std::time::Instant>,
     dead_key_status: DeadKeyStatus,
+    /// The IME state most recently applied to the window
+    ime_enabled: Option<bool>,
     key_table_state: KeyTableState,
+    auto_key_tables: autokeytable::AutoKeyTables,
     show_tab_bar: bool,
     show_scroll_bar: bool,
     tab_bar: TabBarState,
-    fancy_tab_bar: Option<box_model::ComputedElement>,
+    /// The first visible tab when tab_bar_overflow = "Scroll"
+    tab_bar_first_tab: usize,
+    /// The active tab at the time the tab bar was last computed;
+    /// used to scroll a newly activated tab into view
+    tab_bar_active_tab: Option<usize>,
+    presentation: Option<presentation::PresentationState>,
+    zen: Option<zen::ZenState>,
+    /// The entry of workspace_styles that is applied to the config
+    pub workspace_style: Option<config::WorkspaceStyle>,
+    /// When the most recent bell notification was posted
+    last_bell_notification: Option<Instant>,
+    fancy_tab_bar: Option<render::fancy_tab_bar::FancyTabBar>,
     pub right_status: String,
+    /// Appended to the right status by show_mux_latency
+    mux_latency_status: String,
+    /// The uploads of dropped files that are in progress, whose
+    /// progress is shown in the right status
+    uploads: Vec<Arc<Upload>>,
     pub left_status: String,
     last_ui_item: Option<UIItem>,
     /// Tracks whether the current mouse-down event is part of click-focus.
     /// If so, we ignore mouse events until released
     is_click_to_focus_window: bool,
     last_mouse_coords: (usize, i64),
+    /// The pane that pane_focus_follows_mouse will activate once
+    /// the hover delay has elapsed, and the generation of the timer
+    /// that was started for it
+    focus_follows_mouse_pending: Option<(PaneId, usize)>,
+    /// Incremented each time a focus-follows-mouse timer is started,
+    /// so that a timer that was superseded can tell
+    focus_follows_mouse_generation: usize,
+    /// The kinetic scroll that follows a touchpad fling
+    fling: Option<Fling>,
+    scroll_velocity: VelocityTracker,
     window_drag_position: Option<MouseEvent>,
     current_mouse_event: Option<MouseEvent>,
     prev_cursor: PrevCursorPos,
@@ -401,6 +527,18 @@ pub struct TermWindow {
 
     tab_state: RefCell<HashMap<TabId, TabState>>,
     pane_state: RefCell<HashMap<PaneId, PaneState>>,
+    /// The panes that currently receive broadcast input
+    broadcast_input: Option<BroadcastInput>,
+    /// The key press that triggered the assignment that is
+    /// being performed, if it was triggered by a key
+    assignment_key_press: Option<(termwiz::input::KeyCode, ::window::Modifiers)>,
+    /// The panes whose applications take part in navigation, and
+    /// the navigation that is waiting for one of them to reply
+    smart_navigation: SmartNavigation,
+    input_latency: InputLatency,
+    /// Set while the pre-assignment event is being handled,
+    /// to prevent it from recursing
+    in_pre_assignment_hook: bool,
     semantic_zones: HashMap<PaneId, SemanticZoneCache>,
 
     window_background: Vec<LoadedBackgroundLayer>,
@@ -421,11 +559,12 @@ pub struct TermWindow {
     shape_generation: usize,
     shape_cache: RefCell<LfuCache<ShapeCacheKey, anyhow::Result<Rc<Vec<ShapedInfo>>>>>,
     line_to_ele_shape_cache: RefCell<LfuCache<LineToEleShapeCacheKey, LineToElementShapeItem>>,
+    contrast_cache: RefCell<render::contrast::ContrastCache>,
 
     line_state_cache: RefCell<LfuCacheU64<Arc<CachedLineState>>>,
     next_line_state_id: u64,
 
-    line_quad_cache: RefCell<LfuCache<LineQuadCacheKey, LineQuadCacheValue>>,
+    render_cache_stats: RefCell<RenderCacheStats>,
 
     last_status_call: Instant,
     cursor_blink_state: RefCell<ColorEase>,
@@ -441,11 +580,18 @@ pub struct TermWindow {
 
     event_states: HashMap<String, EventState>,
     pub current_event: Option<Value>,
-    has_animation: RefCell<Option<Instant>>,
+    has_animation: RefCell<NextFrame>,
+    /// The animations that scheduled the frame after the last paint;
+    /// empty when the window is idle
+    animation_sources: AnimationSources,
     /// We use this to attempt to do something reasonable
     /// if we run out of texture space
     allow_images: AllowImage,
     scheduled_animation: RefCell<Option<Instant>>,
+    /// The OSC 9;4 progress for each tab, by tab index
+    tab_progress: Vec<Progress>,
+    /// The progress that was last shown in the taskbar
+    taskbar_progress: Progress,
 
     created: Instant,
 
@@ -525,6 +671,7 @@ impl TermWindow {
         self.load_os_parameters();
 
         if self.focused.is_none() {
+            self.focused_pane_id = None;
             self.last_mouse_click = None;
             self.current_mouse_buttons.clear();
             self.current_mouse_capture = None;
@@ -545,6 +692,12 @@ impl TermWindow {
             pane.focus_changed(focused);
         }
 
+        if focused {
+            front_end().record_focus(self.mux_window_id);
+        }
+        crate::dropdown::focus_changed(self.mux_window_id, focused);
+
+        self.note_focused_pane();
         self.update_title();
         self.emit_window_event("window-focus-changed", None);
     }
@@ -577,13 +730,62 @@ impl TermWindow {
     }
 }
 
+/// Combines the overrides of the profile of the window `mux_window_id`,
+/// if it was spawned with one, with `overrides`, which are those set by
+/// `window:set_config_overrides` and take precedence over the profile
+fn window_config_overrides(
+    mux_window_id: MuxWindowId,
+    overrides: &wezterm_dynamic::Value,
+) -> wezterm_dynamic::Value {
+    let profile = Mux::get()
+        .get_window(mux_window_id)
+        .and_then(|window| window.get_profile().map(str::to_string));
+    let profile_overrides = match profile {
+        Some(name) => match configuration().profile(&name) {
+            Ok(overrides) => overrides.clone(),
+            Err(err) => {
+                log::error!("{:#}", err);
+                return overrides.clone();
+            }
+        },
+        None => return overrides.clone(),
+    };
+    match (profile_overrides, overrides) {
+        (
+            wezterm_dynamic::Value::Object(mut combined),
+            wezterm_dynamic::Value::Object(overrides),
+        ) => {
+            for (key, value) in overrides.iter() {
+                combined.insert(key.clone(), value.clone());
+            }
+            wezterm_dynamic::Value::Object(combined)
+        }
+        (profile_overrides, wezterm_dynamic::Value::Null) => profile_overrides,
+        (_, overrides) => overrides.clone(),
+    }
+}
+
 impl TermWindow {
     pub async fn new_window(mux_window_id: MuxWindowId) -> anyhow::Result<()> {
-        let config = configuration();
+        let mux = Mux::get();
+
+        // A window that was spawned with a profile starts out with
+        // the overrides of that profile
+        let config_overrides = wezterm_dynamic::Value::default();
+        let config = match window_config_overrides(mux_window_id, &config_overrides) {
+            wezterm_dynamic::Value::Null => configuration(),
+            overrides => config::overridden_config(&overrides).unwrap_or_else(|err| {
+                log::error!("Failed to apply profile to window: {:#}", err);
+                configuration()
+            }),
+        };
+        let workspace_style = mux
+            .get_window(mux_window_id)
+            .and_then(|window| config.workspace_style(window.get_workspace()).cloned());
+        let config = workspacestyle::apply_workspace_style(&config, workspace_style.as_ref());
         let dpi = config.dpi.unwrap_or_else(|| ::window::default_dpi()) as usize;
         let fontconfig = Rc::new(FontConfiguration::new(Some(config.clone()), dpi)?);
 
-        let mux = Mux::get();
         let size = match mux.get_active_tab_for_window(mux_window_id) {
             Some(tab) => tab.get_size(),
             None => {
@@ -600,10 +802,19 @@ impl TermWindow {
         // Initially we have only a single tab, so take that into account
         // for the tab bar state.
         let show_tab_bar = config.enable_tab_bar && !config.hide_tab_bar_if_only_one_tab;
-        let tab_bar_height = if show_tab_bar {
-            Self::tab_bar_pixel_height_impl(&config, &fontconfig, &render_metrics)? as usize
+        let (tab_bar_width, tab_bar_height) = if !show_tab_bar {
+            (0, 0)
+        } else if config.effective_tab_bar_position().is_vertical() {
+            (
+                Self::vertical_tab_bar_pixel_width_impl(&config, &fontconfig, &render_metrics)
+                    as usize,
+                0,
+            )
         } else {
-            0
+            (
+                0,
+                Self::tab_bar_pixel_height_impl(&config, &fontconfig, &render_metrics)? as usize,
+            )
         };
 
         let terminal_size = TerminalSize {
@@ -647,7 +858,8 @@ impl TermWindow {
         let padding_bottom = config.window_padding.bottom.evaluate_as_pixels(v_context) as usize;
 
         let mut dimensions = Dimensions {
-            pixel_width: (terminal_size.pixel_width + padding_left + padding_right) as usize,
+            pixel_width: (terminal_size.pixel_width + padding_left + padding_right) as usize
+                + tab_bar_width,
             pixel_height: ((terminal_size.rows * render_metrics.cell_size.height as usize)
                 + padding_top
                 + padding_bottom) as usize
@@ -687,16 +899,21 @@ impl TermWindow {
             window: None,
             window_background,
             config: config.clone(),
-            config_overrides: wezterm_dynamic::Value::default(),
+            config_overrides,
             palette: None,
             focused: None,
+            focused_pane_id: None,
             mux_window_id,
             mux_window_id_for_subscriptions: Arc::new(Mutex::new(mux_window_id)),
             fonts: Rc::clone(&fontconfig),
             render_metrics,
+            cell_metrics_override: CellMetricsOverride::default(),
+            integer_scaling: None,
             dimensions,
             window_state: WindowState::default(),
             resizes_pending: 0,
+            pending_dpi_change: None,
+            dpi_change_generation: 0,
             is_repaint_pending: false,
             pending_scale_changes: LinkedList::new(),
             terminal_size,
@@ -704,13 +921,26 @@ impl TermWindow {
             input_map: InputMap::new(&config),
             leader_is_down: None,
             dead_key_status: DeadKeyStatus::None,
+            ime_enabled: None,
             show_tab_bar,
             show_scroll_bar: config.enable_scroll_bar,
             tab_bar: TabBarState::default(),
+            tab_bar_first_tab: 0,
+            tab_bar_active_tab: None,
+            presentation: None,
+            zen: None,
+            workspace_style,
+            last_bell_notification: None,
             fancy_tab_bar: None,
             right_status: String::new(),
+            mux_latency_status: String::new(),
+            uploads: vec![],
             left_status: String::new(),
             last_mouse_coords: (0, -1),
+            focus_follows_mouse_pending: None,
+            focus_follows_mouse_generation: 0,
+            fling: None,
+            scroll_velocity: VelocityTracker::default(),
             window_drag_position: None,
             current_mouse_event: None,
             current_modifier_and_leds: Default::default(),
@@ -718,6 +948,11 @@ impl TermWindow {
             last_scroll_info: RenderableDimensions::default(),
             tab_state: RefCell::new(HashMap::new()),
             pane_state: RefCell::new(HashMap::new()),
+            broadcast_input: None,
+            assignment_key_press: None,
+            smart_navigation: SmartNavigation::default(),
+            input_latency: InputLatency::default(),
+            in_pre_assignment_hook: false,
             current_mouse_buttons: vec![],
             current_mouse_capture: None,
             last_mouse_click: None,
@@ -737,18 +972,14 @@ impl TermWindow {
                 &config,
             )),
             next_line_state_id: 0,
-            line_quad_cache: RefCell::new(LfuCache::new(
-                "line_quad_cache.hit.rate",
-                "line_quad_cache.miss.rate",
-                |config| config.line_quad_cache_size,
-                &config,
-            )),
+            render_cache_stats: RefCell::new(RenderCacheStats::default()),
             line_to_ele_shape_cache: RefCell::new(LfuCache::new(
                 "line_to_ele_shape_cache.hit.rate",
                 "line_to_ele_shape_cache.miss.rate",
                 |config| config.line_to_ele_shape_cache_size,
                 &config,
             )),
+            contrast_cache: RefCell::new(render::contrast::ContrastCache::default()),
             last_status_call: Instant::now(),
             cursor_blink_state: RefCell::new(ColorEase::new(
                 config.cursor_blink_rate,
@@ -773,8 +1004,11 @@ impl TermWindow {
             )),
             event_states: HashMap::new(),
             current_event: None,
-            has_animation: RefCell::new(None),
+            has_animation: RefCell::new(NextFrame::default()),
+            animation_sources: AnimationSources::empty(),
             scheduled_animation: RefCell::new(None),
+            tab_progress: vec![],
+            taskbar_progress: Progress::None,
             allow_images: AllowImage::Yes,
             semantic_zones: HashMap::new(),
             ui_items: vec![],
@@ -782,6 +1016,7 @@ impl TermWindow {
             last_ui_item: None,
             is_click_to_focus_window: false,
             key_table_state: KeyTableState::default(),
+            auto_key_tables: autokeytable::AutoKeyTables::new(&config),
             modal: RefCell::new(None),
             opengl_info: None,
         };
@@ -793,9 +1028,23 @@ impl TermWindow {
         let mut y = None;
         let mut origin = GeometryOrigin::default();
 
+        // The placement that the window had when it was last shown,
+        // or that was restored from a saved session
+        let last_geometry = mux
+            .get_window(mux_window_id)
+            .map(|window| window.get_geometry())
+            .unwrap_or_default();
+
         if let Some(position) = mux
             .get_window(mux_window_id)
             .and_then(|window| window.get_initial_position().clone())
+            .or_else(|| {
+                last_geometry.position.map(|(x, y)| GuiPosition {
+                    x: Dimension::Pixels(x as f32),
+                    y: Dimension::Pixels(y as f32),
+                    origin: GeometryOrigin::ScreenCoordinateSystem,
+                })
+            })
             .or_else(|| POSITION.lock().unwrap().take())
         {
             x.replace(position.x);
@@ -803,9 +1052,15 @@ impl TermWindow {
             origin = position.origin;
         }
 
+        // If that size doesn't match the size of the tabs, they are
+        // resized when the window reports its size
+        let (pixel_width, pixel_height) = last_geometry
+            .pixel_size
+            .unwrap_or((dimensions.pixel_width, dimensions.pixel_height));
+
         let geometry = RequestedWindowGeometry {
-            width: Dimension::Pixels(dimensions.pixel_width as f32),
-            height: Dimension::Pixels(dimensions.pixel_height as f32),
+            width: Dimension::Pixels(pixel_width as f32),
+            height: Dimension::Pixels(pixel_height as f32),
             x,
             y,
             origin,
@@ -864,6 +1119,7 @@ impl TermWindow {
                         padding_right: padding_right,
                         padding_bottom: padding_bottom,
                         border: border,
+                        tab_bar_width: tab_bar_width,
                         tab_bar_height: tab_bar_height,
                     }
                     .into(),
@@ -879,7 +1135,11 @@ impl TermWindow {
                 myself.created(RenderContext::WebGpu(Rc::clone(&webgpu)))?;
             }
             myself.load_os_parameters();
-            window.show();
+            if crate::dropdown::is_drop_down(mux_window_id) {
+                crate::dropdown::window_created(&window);
+            } else {
+                window.show();
+            }
             myself.subscribe_to_pane_updates();
             myself.emit_window_event("window-config-reloaded", None);
             myself.emit_status_event();
@@ -942,6 +1202,10 @@ impl TermWindow {
                 self.mouse_event_impl(event, window);
                 Ok(true)
             }
+            WindowEvent::PreciseScroll(event) => {
+                self.precise_scroll_impl(event, window);
+                Ok(true)
+            }
             WindowEvent::MouseLeave => {
                 self.mouse_leave_impl(window);
                 Ok(true)
@@ -954,6 +1218,13 @@ impl TermWindow {
                 self.resize(dimensions, window_state, window, live_resizing);
                 Ok(true)
             }
+            WindowEvent::Moved(position) => {
+                if let Some(mut mux_window) = Mux::get().get_window_mut(self.mux_window_id) {
+                    mux_window.set_gui_position(position.x, position.y);
+                }
+                self.check_dpi_for_position(position, window);
+                Ok(true)
+            }
             WindowEvent::SetInnerSizeCompleted => {
                 self.resizes_pending -= 1;
                 if self.is_repaint_pending {
@@ -1024,13 +1295,29 @@ impl TermWindow {
                     Some(pane) => pane,
                     None => return Ok(true),
                 };
-                let urls = urls
+                // Some systems deliver dropped files as file: urls
+                let paths = urls
                     .iter()
-                    .map(|url| self.config.quote_dropped_files.escape(&url.to_string()))
-                    .collect::<Vec<_>>()
-                    .join(" ")
-                    + " ";
-                pane.send_paste(urls.as_str())?;
+                    .map(|url| url.to_file_path().ok())
+                    .collect::<Option<Vec<_>>>();
+                if let Some(paths) = paths {
+                    return self.dropped_files(&pane, paths).map(|()| true);
+                }
+                if self.is_overlay(&pane) {
+                    self.paste_dropped_urls(&pane, &urls)?;
+                } else {
+                    let names = urls.iter().map(|url| url.to_string()).collect();
+                    let pane_id = pane.pane_id();
+                    self.emit_file_dropped_event(&pane, names, move |term_window| {
+                        let pane = match Mux::get().get_pane(pane_id) {
+                            Some(pane) => pane,
+                            None => return,
+                        };
+                        if let Err(err) = term_window.paste_dropped_urls(&pane, &urls) {
+                            log::error!("dropping urls onto pane {}: {:#}", pane_id, err);
+                        }
+                    });
+                }
                 Ok(true)
             }
             WindowEvent::DroppedFile(paths) => {
@@ -1038,17 +1325,7 @@ impl TermWindow {
                     Some(pane) => pane,
                     None => return Ok(true),
                 };
-                let paths = paths
-                    .iter()
-                    .map(|path| {
-                        self.config
-                            .quote_dropped_files
-                            .escape(&path.to_string_lossy())
-                    })
-                    .collect::<Vec<_>>()
-                    .join(" ")
-                    + " ";
-                pane.send_paste(&paths)?;
+                self.dropped_files(&pane, paths)?;
                 Ok(true)
             }
             WindowEvent::DraggedFile(_) => Ok(true),
@@ -1076,7 +1353,9 @@ impl TermWindow {
             ),
         );
         self.paint_impl(&mut RenderFrame::Glium(&mut frame));
-        window.finish_frame(frame).is_ok()
+        let ok = window.finish_frame(frame).is_ok();
+        self.record_input_latency();
+        ok
     }
 
     fn do_paint_webgpu(&mut self) -> anyhow::Result<bool> {
@@ -1098,6 +1377,7 @@ impl TermWindow {
 
     fn do_paint_webgpu_impl(&mut self) -> anyhow::Result<bool> {
         self.paint_impl(&mut RenderFrame::WebGpu);
+        self.record_input_latency();
         Ok(true)
     }
 
@@ -1195,6 +1475,7 @@ impl TermWindow {
                     alert: Alert::SetUserVar { name, value },
                     pane_id,
                 } => {
+                    self.handle_navigation_reply(pane_id, &name, &value);
                     self.emit_user_var_event(pane_id, name, value);
                 }
                 MuxNotification::WindowTitleChanged { .. }
@@ -1204,7 +1485,10 @@ impl TermWindow {
                         | Alert::CurrentWorkingDirectoryChanged
                         | Alert::WindowTitleChanged(_)
                         | Alert::TabTitleChanged(_)
-                        | Alert::IconTitleChanged(_),
+                        | Alert::IconTitleChanged(_)
+                        | Alert::Progress(_)
+                        | Alert::OutputRateLimited(_)
+                        | Alert::OutputPaused(_),
                     ..
                 } => {
                     self.update_title();
@@ -1223,19 +1507,35 @@ impl TermWindow {
                     alert: Alert::Bell,
                     pane_id,
                 } => {
-                    match self.config.audible_bell {
-                        AudibleBell::SystemBeep => {
-                            Connection::get().expect("on main thread").beep();
+                    self.ring_bell(pane_id);
+                }
+                MuxNotification::Alert {
+                    alert: alert @ (Alert::PaneOutputActivity | Alert::PaneSilence { .. }),
+                    pane_id,
+                } => {
+                    let mux = Mux::get();
+                    if let Some((_domain, window_id, _tab_id)) = mux.resolve_pane_id(pane_id) {
+                        if window_id == self.mux_window_id {
+                            let event = match alert {
+                                Alert::PaneSilence { .. } => "pane-silence",
+                                _ => "pane-output-activity",
+                            };
+                            self.emit_window_event(event, Some(pane_id));
                         }
-                        AudibleBell::Disabled => {}
                     }
-
-                    log::trace!("Ding! (this is the bell) in pane {}", pane_id);
-                    self.emit_window_event("bell", Some(pane_id));
-
-                    let mut per_pane = self.pane_state(pane_id);
-                    per_pane.bell_start.replace(Instant::now());
-                    window.invalidate();
+                    // Refresh the tab bar indicators
+                    self.update_title();
+                }
+                MuxNotification::Alert {
+                    alert:
+                        Alert::PaneMatch {
+                            watch_id,
+                            text,
+                            row,
+                        },
+                    pane_id,
+                } => {
+                    self.emit_pane_match_event(pane_id, watch_id, text, row);
                 }
                 MuxNotification::Alert {
                     alert: Alert::ToastNotification { .. },
@@ -1248,6 +1548,7 @@ impl TermWindow {
                     let mux = Mux::get();
                     let mut size = self.terminal_size;
                     if let Some(tab) = mux.get_tab(tab_id) {
+                        tab.set_pane_decoration_rows(self.config.pane_decorations.rows());
                         // If we attached to a remote domain and loaded in
                         // a tab async, we need to fixup its size, either
                         // by resizing it or resizes ourselves.
@@ -1288,8 +1589,13 @@ impl TermWindow {
                 MuxNotification::SaveToDownloads { .. } => {
                     // Handled by frontend
                 }
-                MuxNotification::PaneFocused(_) => {
+                MuxNotification::PaneFocused(pane_id) => {
                     // Also handled by clientpane
+                    if self.focused.is_some() {
+                        front_end().record_focus(self.mux_window_id);
+                    }
+                    self.activate_linked_panes(pane_id);
+                    self.note_focused_pane();
                     self.update_title_post_status();
                 }
                 MuxNotification::TabResized(_) => {
@@ -1299,16 +1605,28 @@ impl TermWindow {
                 MuxNotification::TabTitleChanged { .. } => {
                     self.update_title_post_status();
                 }
+                MuxNotification::PaneRemoved(pane_id) => {
+                    self.prune_broadcast_input(pane_id);
+                    if let Some(state) = self.pane_state.borrow_mut().get_mut(&pane_id) {
+                        state.line_cache = PaneLineCache::default();
+                    }
+                }
+                MuxNotification::WindowWorkspaceChanged(_) => {
+                    self.workspace_style_changed();
+                    self.update_title_post_status();
+                }
                 MuxNotification::PaneAdded(_)
+                | MuxNotification::WindowGeometryChanged(_)
                 | MuxNotification::WorkspaceRenamed { .. }
-                | MuxNotification::PaneRemoved(_)
-                | MuxNotification::WindowWorkspaceChanged(_)
                 | MuxNotification::ActiveWorkspaceChanged(_)
+                | MuxNotification::KillClient(_)
                 | MuxNotification::Empty
+                | MuxNotification::PowerEvent(_)
                 | MuxNotification::WindowCreated(_) => {}
             },
             TermWindowNotif::EmitStatusUpdate => {
                 self.emit_status_event();
+                self.update_mux_latency_status();
             }
             TermWindowNotif::GetSelectionForPane { pane_id, tx } => {
                 let mux = Mux::get();
@@ -1338,6 +1656,10 @@ impl TermWindow {
                         tab.resize(self.terminal_size);
                     }
                 };
+                if self.focused.is_some() {
+                    front_end().record_focus(self.mux_window_id);
+                }
+                self.workspace_style_changed();
                 self.update_title();
                 window.invalidate();
             }
@@ -1424,7 +1746,18 @@ impl TermWindow {
 
     fn mux_pane_output_event(&mut self, pane_id: PaneId) {
         metrics::histogram!("mux.pane_output_event.rate").record(1.);
+        self.cancel_kinetic_scroll_for_output(pane_id);
+        if let Some(modal) = self.get_modal() {
+            if let Some(overview) = modal.downcast_ref::<taboverview::TabOverview>() {
+                if overview.shows_pane(pane_id) {
+                    self.invalidate_modal();
+                }
+            }
+        }
         if self.is_pane_visible(pane_id) {
+            if self.invalidate_for_input_echo(pane_id) {
+                return;
+            }
             if let Some(ref win) = self.window {
                 win.invalidate();
             }
@@ -1452,7 +1785,13 @@ impl TermWindow {
                     | Alert::TabTitleChanged(_)
                     | Alert::IconTitleChanged(_)
                     | Alert::SetUserVar { .. }
-                    | Alert::Bell,
+                    | Alert::Bell
+                    | Alert::PaneOutputActivity
+                    | Alert::PaneSilence { .. }
+                    | Alert::PaneMatch { .. }
+                    | Alert::Progress(_)
+                    | Alert::OutputRateLimited(_)
+                    | Alert::OutputPaused(_),
             }
             | MuxNotification::PaneFocused(pane_id)
             | MuxNotification::PaneRemoved(pane_id)
@@ -1505,10 +1844,17 @@ impl TermWindow {
             | MuxNotification::AssignClipboard { .. }
             | MuxNotification::SaveToDownloads { .. }
             | MuxNotification::WindowCreated(_)
+            | MuxNotification::WindowGeometryChanged(_)
             | MuxNotification::ActiveWorkspaceChanged(_)
+            | MuxNotification::KillClient(_)
             | MuxNotification::WorkspaceRenamed { .. }
             | MuxNotification::Empty
-            | MuxNotification::WindowWorkspaceChanged(_) => return true,
+            | MuxNotification::PowerEvent(_) => return true,
+            MuxNotification::WindowWorkspaceChanged(window_id) => {
+                if window_id != mux_window_id {
+                    return true;
+                }
+            }
             MuxNotification::Alert {
                 alert: Alert::PaletteChanged { .. },
                 ..
@@ -1558,6 +1904,12 @@ impl TermWindow {
             },
         };
         let pane = MuxPane(pane.pane_id());
+        // The status events are told whether zen mode is active,
+        // so that they can slim down their content
+        let zen_mode = match name {
+            "update-status" | "update-right-status" => Some(self.is_zen_mode()),
+            _ => None,
+        };
         let name = name.to_string();
 
         async fn do_event(
@@ -1565,9 +1917,13 @@ impl TermWindow {
             name: String,
             window: GuiWin,
             pane: MuxPane,
+            zen_mode: Option<bool>,
         ) -> anyhow::Result<()> {
             let again = if let Some(lua) = lua {
-                let args = lua.pack_multi((window.clone(), pane))?;
+                let args = match zen_mode {
+                    Some(zen_mode) => lua.pack_multi((window.clone(), pane, zen_mode))?,
+                    None => lua.pack_multi((window.clone(), pane))?,
+                };
 
                 if let Err(err) = config::lua::emit_event(&lua, (name.clone(), args)).await {
                     log::error!("while processing {} event: {:#}", name, err);
@@ -1585,7 +1941,7 @@ impl TermWindow {
         }
 
         promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
-            do_event(lua, name, window, pane)
+            do_event(lua, name, window, pane, zen_mode)
         }))
         .detach();
     }
@@ -1632,6 +1988,11 @@ impl TermWindow {
                 *state = EventState::InProgressWithQueued(pane_id);
                 return;
             }
+            EventState::InProgressWithQueued(other_pane) if name == "pane-focused" => {
+                // Only the most recently focused pane is of interest
+                *other_pane = pane_id;
+                return;
+            }
             EventState::InProgressWithQueued(other_pane) => {
                 // We've already got one copy executing and another
                 // pending dispatch, so don't queue another.
@@ -1655,6 +2016,25 @@ impl TermWindow {
         }
     }
 
+    /// Called when the window focus or its active pane changes.
+    /// Emits `pane-focused` when the active pane of the focused window
+    /// is a different pane than last time.  Overlays are not mux panes
+    /// and don't count.
+    fn note_focused_pane(&mut self) {
+        if self.focused.is_none() {
+            return;
+        }
+        let pane_id = match self.get_active_pane_no_overlay() {
+            Some(pane) => pane.pane_id(),
+            None => return,
+        };
+        if self.focused_pane_id == Some(pane_id) {
+            return;
+        }
+        self.focused_pane_id = Some(pane_id);
+        self.emit_window_event("pane-focused", Some(pane_id));
+    }
+
     fn check_for_dirty_lines_and_invalidate_selection(&mut self, pane: &Arc<dyn Pane>) {
         let dims = pane.get_dimensions();
         let viewport = self
@@ -1710,7 +2090,8 @@ impl TermWindow {
         );
         self.key_table_state.clear_stack();
         self.connection_name = Connection::get().unwrap().name();
-        let config = match config::overridden_config(&self.config_overrides) {
+        let overrides = window_config_overrides(self.mux_window_id, &self.config_overrides);
+        let config = match config::overridden_config(&overrides) {
             Ok(config) => config,
             Err(err) => {
                 log::error!(
@@ -1721,6 +2102,16 @@ impl TermWindow {
                 configuration()
             }
         };
+        let config = match &self.presentation {
+            Some(_) => presentation::apply_presentation_mode(&config),
+            None => config,
+        };
+        let config = match &self.zen {
+            Some(_) => zen::apply_zen_mode(&config),
+            None => config,
+        };
+        self.workspace_style = self.resolve_workspace_style(&config);
+        let config = workspacestyle::apply_workspace_style(&config, self.workspace_style.as_ref());
         self.config = config.clone();
         self.palette.take();
 
@@ -1764,7 +2155,6 @@ impl TermWindow {
             shape_cache.clear();
         }
         self.line_state_cache.borrow_mut().update_config(&config);
-        self.line_quad_cache.borrow_mut().update_config(&config);
         self.line_to_ele_shape_cache
             .borrow_mut()
             .update_config(&config);
@@ -1772,6 +2162,8 @@ impl TermWindow {
         self.invalidate_fancy_tab_bar();
         self.invalidate_modal();
         self.input_map = InputMap::new(&config);
+        self.auto_key_tables = autokeytable::AutoKeyTables::new(&config);
+        self.update_auto_key_tables();
         self.leader_is_down = None;
         self.render_state.as_mut().map(|rs| rs.config_changed());
         let dimensions = self.dimensions;
@@ -1783,9 +2175,47 @@ impl TermWindow {
         if let Some(window) = mux.get_window(self.mux_window_id) {
             let term_config: Arc<dyn TerminalConfiguration> =
                 Arc::new(TermConfig::with_config(config.clone()));
+            let gutter_cols = render::line_timestamps::gutter_cols(&config);
+            // Panes that were spawned with a profile take their
+            // terminal settings from that profile instead
+            let mut profile_configs: HashMap<String, Arc<dyn TerminalConfiguration>> =
+                HashMap::new();
+            let decoration_rows = config.pane_decorations.rows();
             for tab in window.iter() {
+                // Resizes the panes if their title bars came or went
+                tab.set_pane_decoration_rows(decoration_rows);
+                let mut resize = false;
                 for pane in tab.iter_panes_ignoring_zoom() {
-                    pane.pane.set_config(Arc::clone(&term_config));
+                    let pane_config = match mux.get_pane_profile(pane.pane.pane_id()) {
+                        Some(name) => match profile_configs.get(&name) {
+                            Some(config) => Arc::clone(config),
+                            None => match TermConfig::with_profile(&name) {
+                                Ok(config) => {
+                                    let config: Arc<dyn TerminalConfiguration> = Arc::new(config);
+                                    profile_configs.insert(name, Arc::clone(&config));
+                                    config
+                                }
+                                Err(err) => {
+                                    log::error!("{:#}", err);
+                                    Arc::clone(&term_config)
+                                }
+                            },
+                        },
+                        None => Arc::clone(&term_config),
+                    };
+                    pane.pane.set_config(pane_config);
+                    let cols = pane.pane.timestamp_gutter_cols();
+                    if cols != 0 && cols != gutter_cols {
+                        // line_timestamp_format changed the width
+                        pane.pane.set_timestamp_gutter_cols(gutter_cols);
+                        resize = true;
+                    }
+                }
+                if resize {
+                    // Re-apply the size so that the panes make room
+                    // for, or reclaim the space of, their timestamp
+                    // gutters
+                    tab.resize(tab.get_size());
                 }
             }
             for state in self.pane_state.borrow().values() {
@@ -1929,6 +2359,235 @@ impl TermWindow {
         .detach();
     }
 
+    fn emit_pane_match_event(
+        &mut self,
+        pane_id: PaneId,
+        watch_id: String,
+        text: String,
+        row: StableRowIndex,
+    ) {
+        let mux = Mux::get();
+
+        // Only the window which contains the pane emits the event
+        match mux.resolve_pane_id(pane_id) {
+            Some((_domain, window_id, _tab_id)) if window_id == self.mux_window_id => {}
+            _ => return,
+        }
+
+        let window = GuiWin::new(self);
+        let pane = MuxPane(pane_id);
+
+        async fn do_event(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: MuxPane,
+            watch_id: String,
+            text: String,
+            row: StableRowIndex,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, pane, watch_id, text, row))?;
+                if let Err(err) =
+                    config::lua::emit_event(&lua, ("pane-match".to_string(), args)).await
+                {
+                    log::error!("while processing pane-match event: {:#}", err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            do_event(lua, window, pane, watch_id, text, row)
+        }))
+        .detach();
+    }
+
+    /// Returns true if `pane` is an overlay rather than the active pane
+    fn is_overlay(&self, pane: &Arc<dyn Pane>) -> bool {
+        self.get_active_pane_no_overlay()
+            .map(|p| p.pane_id() != pane.pane_id())
+            .unwrap_or(true)
+    }
+
+    /// Handles `paths` being dropped onto `pane`
+    fn dropped_files(&mut self, pane: &Arc<dyn Pane>, paths: Vec<PathBuf>) -> anyhow::Result<()> {
+        if self.is_overlay(pane) {
+            // Overlays, such as the launcher, take the paths as text
+            return self.paste_dropped_paths(pane, &paths);
+        }
+        let names = paths
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+        let pane_id = pane.pane_id();
+        self.emit_file_dropped_event(pane, names, move |term_window| {
+            term_window.drop_files(pane_id, paths);
+        });
+        Ok(())
+    }
+
+    /// Emits the `file-dropped` event with `names`, the dropped paths
+    /// or urls; unless the handler returns false, `default_action`
+    /// is then performed
+    fn emit_file_dropped_event<F>(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+        names: Vec<String>,
+        default_action: F,
+    ) where
+        F: FnOnce(&mut TermWindow) + Send + Sync + 'static,
+    {
+        async fn do_event(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: MuxPane,
+            names: Vec<String>,
+            default_action: Box<dyn FnOnce(&mut TermWindow) + Send + Sync>,
+        ) -> anyhow::Result<()> {
+            let perform_default = match lua {
+                Some(lua) => {
+                    let args = lua.pack_multi((window.clone(), pane, names))?;
+                    config::lua::emit_event(&lua, ("file-dropped".to_string(), args))
+                        .await
+                        .map_err(|e| {
+                            log::error!("while processing file-dropped event: {:#}", e);
+                            e
+                        })?
+                }
+                None => true,
+            };
+            if perform_default {
+                window.window.notify(TermWindowNotif::Apply(default_action));
+            }
+            Ok(())
+        }
+
+        let window = GuiWin::new(self);
+        let pane = MuxPane(pane.pane_id());
+        let default_action = Box::new(default_action);
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            do_event(lua, window, pane, names, default_action)
+        }))
+        .detach();
+    }
+
+    /// Returns the quoting to use for paths that are pasted into `pane`,
+    /// resolving `Auto` from its foreground process
+    fn dropped_file_quoting(&self, pane: &Arc<dyn Pane>) -> DroppedFileQuoting {
+        let process = pane.get_foreground_process_name(CachePolicy::AllowStale);
+        self.config
+            .quote_dropped_files
+            .for_process(process.as_deref())
+    }
+
+    fn paste_dropped_urls(&self, pane: &Arc<dyn Pane>, urls: &[url::Url]) -> anyhow::Result<()> {
+        let quoting = self.dropped_file_quoting(pane);
+        let urls = urls
+            .iter()
+            .map(|url| quoting.escape(url.as_str()))
+            .collect::<Vec<_>>()
+            .join(" ")
+            + " ";
+        pane.send_paste(&urls)
+    }
+
+    fn paste_dropped_paths(&self, pane: &Arc<dyn Pane>, paths: &[PathBuf]) -> anyhow::Result<()> {
+        let quoting = self.dropped_file_quoting(pane);
+        let paths = paths
+            .iter()
+            .map(|path| quoting.escape(&path.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(" ")
+            + " ";
+        pane.send_paste(&paths)
+    }
+
+    /// Drops `paths` onto `pane_id` according to `drop_behavior`
+    fn drop_files(&mut self, pane_id: PaneId, paths: Vec<PathBuf>) {
+        let mux = Mux::get();
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => pane,
+            None => return,
+        };
+
+        let result = match self.config.drop_behavior {
+            DropBehavior::PastePaths => self.paste_dropped_paths(&pane, &paths),
+            DropBehavior::ChangeDirectory => {
+                let dir = paths.first().and_then(|path| {
+                    if path.is_dir() {
+                        Some(path.as_path())
+                    } else {
+                        path.parent()
+                    }
+                });
+                match dir {
+                    Some(dir) => {
+                        let quoting = self.dropped_file_quoting(&pane);
+                        pane.send_paste(&format!("cd {}", quoting.escape(&dir.to_string_lossy())))
+                    }
+                    None => Ok(()),
+                }
+            }
+            DropBehavior::Upload => {
+                let ssh = mux.get_domain(pane.domain_id()).and_then(|domain| {
+                    let ssh = domain.downcast_ref::<mux::ssh::RemoteSshDomain>()?;
+                    Some((ssh.session()?, domain.domain_name().to_string()))
+                });
+                match (ssh, self.window.clone()) {
+                    (Some((session, domain_name)), Some(window)) => {
+                        let remote_cwd = pane
+                            .get_current_working_dir(CachePolicy::AllowStale)
+                            .and_then(|url| mux::url_to_cwd(&url));
+                        let request = UploadRequest {
+                            pane_id,
+                            session,
+                            domain_name,
+                            paths,
+                            remote_cwd,
+                            window,
+                        };
+                        let (overlay, future) =
+                            start_overlay_pane(self, &pane, move |_pane_id, term| {
+                                upload_dropped_files(request, term)
+                            });
+                        self.assign_overlay_for_pane(pane_id, overlay);
+                        promise::spawn::spawn(future).detach();
+                        Ok(())
+                    }
+                    // Only panes on the local machine can see
+                    // the local files, so paste their paths
+                    _ => self.paste_dropped_paths(&pane, &paths),
+                }
+            }
+        };
+        if let Err(err) = result {
+            log::error!("dropping files onto pane {}: {:#}", pane_id, err);
+        }
+    }
+
+    /// Shows the progress of `upload` in the right status
+    pub fn start_upload(&mut self, upload: Arc<Upload>) {
+        self.uploads.push(upload);
+        self.update_title_post_status();
+    }
+
+    /// Removes `upload` from the right status once it has finished
+    pub fn finish_upload(&mut self, upload: &Arc<Upload>) {
+        self.uploads.retain(|u| !Arc::ptr_eq(u, upload));
+        self.update_title_post_status();
+    }
+
+    /// Cancels the uploads that are in progress
+    pub fn cancel_uploads(&mut self) {
+        if self.uploads.is_empty() {
+            return;
+        }
+        for upload in &self.uploads {
+            upload.cancel();
+        }
+        self.update_title_post_status();
+    }
+
     /// Called by window:set_right_status after the status has
     /// been updated; let's update the bar
     pub fn update_title_post_status(&mut self) {
@@ -1936,6 +2595,9 @@ impl TermWindow {
     }
 
     fn update_title_impl(&mut self) {
+        self.update_auto_key_tables();
+        // Switching tabs changes the active pane without a PaneFocused
+        self.note_focused_pane();
         let mux = Mux::get();
         let window = match mux.get_window(self.mux_window_id) {
             Some(window) => window,
@@ -1946,39 +2608,72 @@ impl TermWindow {
         let active_tab = tabs.iter().find(|t| t.is_active).cloned();
         let active_pane = panes.iter().find(|p| p.is_active).cloned();
 
-        let border = self.get_os_border();
-        let tab_bar_height = self.tab_bar_pixel_height().unwrap_or(0.);
-        let tab_bar_y = if self.config.tab_bar_at_bottom {
-            ((self.dimensions.pixel_height as f32) - (tab_bar_height + border.bottom.get() as f32))
-                .max(0.)
-        } else {
-            border.top.get() as f32
-        };
-
-        let tab_bar_height = self.tab_bar_pixel_height().unwrap_or(0.);
-
+        let tab_bar_bounds = self
+            .tab_bar_bounds()
+            .unwrap_or_else(|_| euclid::rect(0., 0., 0., 0.));
+        let tab_bar_row_height =
+            Self::tab_bar_pixel_height_impl(&self.config, &self.fonts, &self.render_metrics)
+                .unwrap_or(1.)
+                .max(1.);
+        let vertical_tab_bar = self.tab_bar_is_vertical();
+
+        // The row of the tab bar under the mouse, and the mouse
+        // column relative to the start of the tab bar
         let hovering_in_tab_bar = match &self.current_mouse_event {
             Some(event) => {
+                let mouse_x = event.coords.x as f32;
                 let mouse_y = event.coords.y as f32;
-                mouse_y >= tab_bar_y as f32 && mouse_y < tab_bar_y as f32 + tab_bar_height
+                if vertical_tab_bar {
+                    if tab_bar_bounds.contains(euclid::point2(mouse_x, mouse_y)) {
+                        let cell_width = (tab_bar_bounds.width()
+                            / self.config.vertical_tab_bar_width.max(1) as f32)
+                            .max(1.);
+                        Some((
+                            ((mouse_y - tab_bar_bounds.min_y()) / tab_bar_row_height) as usize,
+                            ((mouse_x - tab_bar_bounds.min_x()) / cell_width) as usize,
+                        ))
+                    } else {
+                        None
+                    }
+                } else if mouse_y >= tab_bar_bounds.min_y() && mouse_y < tab_bar_bounds.max_y() {
+                    Some((
+                        ((mouse_y - tab_bar_bounds.min_y()) / tab_bar_row_height) as usize,
+                        self.last_mouse_coords.0,
+                    ))
+                } else {
+                    None
+                }
             }
-            None => false,
+            None => None,
         };
 
+        let upload_status: String = self.uploads.iter().map(|upload| upload.status()).collect();
+        let right_status = format!(
+            "{}{}{}",
+            self.right_status, upload_status, self.mux_latency_status
+        );
+
+        let active_tab_idx = active_tab.as_ref().map(|tab| tab.tab_index);
+        let scroll = TabBarScroll {
+            first_tab: self.tab_bar_first_tab,
+            reveal_active: active_tab_idx != self.tab_bar_active_tab,
+        };
+        self.tab_bar_active_tab = active_tab_idx;
+
         let new_tab_bar = TabBarState::new(
-            self.dimensions.pixel_width / self.render_metrics.cell_size.width as usize,
-            if hovering_in_tab_bar {
-                Some(self.last_mouse_coords.0)
-            } else {
-                None
-            },
+            self.tab_bar_columns(),
+            hovering_in_tab_bar.map(|(_row, col)| col),
+            hovering_in_tab_bar.map(|(row, _col)| row).unwrap_or(0),
             &tabs,
             &panes,
             self.config.resolved_palette.tab_bar.as_ref(),
             &self.config,
             &self.left_status,
-            &self.right_status,
+            &right_status,
+            scroll,
         );
+        self.tab_bar_first_tab = new_tab_bar.first_visible_tab();
+        let tab_bar_rows_changed = new_tab_bar.rows() != self.tab_bar.rows();
         if new_tab_bar != self.tab_bar {
             self.tab_bar = new_tab_bar;
             self.invalidate_fancy_tab_bar();
@@ -1988,6 +2683,31 @@ impl TermWindow {
             }
         }
 
+        let tab_progress: Vec<Progress> = tabs.iter().map(|tab| tab.progress).collect();
+        if tab_progress != self.tab_progress {
+            self.tab_progress = tab_progress;
+            self.invalidate_fancy_tab_bar();
+            if let Some(window) = self.window.as_ref() {
+                window.invalidate();
+            }
+        }
+        let taskbar_progress = active_tab
+            .as_ref()
+            .map(|tab| tab.progress)
+            .unwrap_or_default();
+        if taskbar_progress != self.taskbar_progress {
+            self.taskbar_progress = taskbar_progress;
+            if let Some(window) = self.window.as_ref() {
+                window.set_taskbar_progress(match taskbar_progress {
+                    Progress::None => TaskbarProgress::None,
+                    Progress::SetPercentage(pct) => TaskbarProgress::Normal(pct),
+                    Progress::SetError(pct) => TaskbarProgress::Error(pct),
+                    Progress::Indeterminate => TaskbarProgress::Indeterminate,
+                    Progress::Paused(pct) => TaskbarProgress::Paused(pct),
+                });
+            }
+        }
+
         let num_tabs = window.len();
         if num_tabs == 0 {
             return;
@@ -2028,9 +2748,11 @@ impl TermWindow {
         };
 
         let title = match title {
+            // A title from format-window-title is used as-is; the
+            // handler can include the prefix itself
             Some(title) => title,
             None => {
-                if let (Some(pos), Some(tab)) = (active_pane, active_tab) {
+                let title = if let (Some(pos), Some(tab)) = (active_pane, active_tab) {
                     if num_tabs == 1 {
                         format!("{}{}", if pos.is_zoomed { "[Z] " } else { "" }, pos.title)
                     } else {
@@ -2044,7 +2766,8 @@ impl TermWindow {
                     }
                 } else {
                     "".to_string()
-                }
+                };
+                workspacestyle::apply_workspace_title_prefix(self.workspace_style.as_ref(), title)
             }
         };
 
@@ -2061,7 +2784,9 @@ impl TermWindow {
             // hide/show, then we'll need to resize things.  It is simplest
             // to piggy back on the config reloading code for that, so that
             // is what we're doing.
-            if show_tab_bar != self.show_tab_bar {
+            // The same applies when a wrapping tab bar gains or
+            // loses a row.
+            if show_tab_bar != self.show_tab_bar || (show_tab_bar && tab_bar_rows_changed) {
                 self.config_was_reloaded();
             }
         }
@@ -2089,29 +2814,59 @@ impl TermWindow {
     fn update_text_cursor(&mut self, pos: &PositionedPane) {
         if let Some(win) = self.window.as_ref() {
             let cursor = pos.pane.get_cursor_position();
-            let top = pos.pane.get_dimensions().physical_top;
-            let tab_bar_height = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
-                self.tab_bar_pixel_height().unwrap()
-            } else {
-                0.0
-            };
+            let dims = pos.pane.get_dimensions();
+            let top = dims.physical_top;
+            let tab_bar = self.tab_bar_reserved_edges().unwrap_or_default();
             let (padding_left, padding_top) = self.padding_left_top();
 
+            // Cover the text being composed, so that the IME shows
+            // its candidates below it rather than on top of it
+            let columns = match &self.dead_key_status {
+                DeadKeyStatus::Composing(preedit) => {
+                    PlacedPreedit::new(preedit, cursor.x, dims.cols).columns()
+                }
+                DeadKeyStatus::None => cursor.x..cursor.x + 1,
+            };
+
             let r = Rect::new(
                 Point::new(
-                    (((cursor.x + pos.left) as isize).max(0) * self.render_metrics.cell_size.width)
+                    (((columns.start + pos.left) as isize).max(0)
+                        * self.render_metrics.cell_size.width)
+                        .add(tab_bar.left as isize)
                         .add(padding_left as isize),
                     ((cursor.y + pos.top as isize - top).max(0)
                         * self.render_metrics.cell_size.height)
-                        .add(tab_bar_height as isize)
+                        .add(tab_bar.top as isize)
                         .add(padding_top as isize),
                 ),
-                self.render_metrics.cell_size,
+                Size::new(
+                    columns.len().max(1) as isize * self.render_metrics.cell_size.width,
+                    self.render_metrics.cell_size.height,
+                ),
             );
             win.set_text_cursor_position(r);
         }
     }
 
+    /// Enables or disables the IME for the window to match the
+    /// setting of the active pane.  The IME is suspended while a
+    /// key table, such as copy mode or search mode, is active.
+    fn update_ime_enabled(&mut self) {
+        let pane_enabled = self
+            .get_active_pane_no_overlay()
+            .map(|pane| pane.is_ime_enabled())
+            .unwrap_or(true);
+        let enabled = pane_enabled && self.current_key_table_name().is_none();
+        if self.ime_enabled == Some(enabled) {
+            return;
+        }
+        self.ime_enabled.replace(enabled);
+        if let Some(win) = self.window.as_ref() {
+            log::trace!("ime enabled -> {enabled}");
+            win.set_ime_enabled(enabled);
+        }
+    }
+
     fn activate_window(&mut self, window_idx: usize) -> anyhow::Result<()> {
         let windows = front_end().gui_windows();
         if let Some(win) = windows.get(window_idx) {
@@ -2299,6 +3054,59 @@ impl TermWindow {
         promise::spawn::spawn(future).detach();
     }
 
+    pub fn render_cache_stats(&self) -> RenderCacheStats {
+        *self.render_cache_stats.borrow()
+    }
+
+    pub fn frame_stats(&self) -> FrameStats {
+        FrameStats {
+            fps: self.effective_fps(),
+            animation_sources: self.animation_sources,
+        }
+    }
+
+    /// Shows the ssh host key confirmation overlay in the active tab.
+    /// If there is no active tab, the event is dropped, which
+    /// rejects the host key.
+    pub fn show_host_key_confirmation(
+        &mut self,
+        verify: wezterm_ssh::HostVerificationEvent,
+        pane_id: Option<PaneId>,
+    ) {
+        let mux = Mux::get();
+        // Prefer the tab that holds the pane that is connecting
+        let tab = pane_id
+            .and_then(|pane_id| mux.resolve_pane_id(pane_id))
+            .and_then(|(_domain_id, _window_id, tab_id)| mux.get_tab(tab_id))
+            .or_else(|| mux.get_active_tab_for_window(self.mux_window_id));
+        let tab = match tab {
+            Some(tab) => tab,
+            None => {
+                log::error!(
+                    "No tab is available to confirm the host key for {}",
+                    verify.remote_address
+                );
+                return;
+            }
+        };
+
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            crate::overlay::hostkey::confirm_host_key(verify, term)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    /// Shows the modal that asks whether to trust the changed
+    /// certificate of a TLS domain
+    pub fn show_certificate_confirmation(
+        &mut self,
+        change: wezterm_client::pinning::CertificateChange,
+    ) {
+        let modal = crate::termwindow::certprompt::CertificatePrompt::new(change);
+        self.set_modal(Rc::new(modal));
+    }
+
     fn show_debug_overlay(&mut self) {
         let mux = Mux::get();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -2310,9 +3118,34 @@ impl TermWindow {
 
         let opengl_info = self.opengl_info.as_deref().unwrap_or("Unknown").to_string();
         let connection_info = self.connection_name.clone();
+        let cache_stats = *self.render_cache_stats.borrow();
+        let input_latency = self.input_latency.last;
+        let frame_stats = self.frame_stats();
+
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            crate::overlay::show_debug_overlay(
+                term,
+                gui_win,
+                opengl_info,
+                connection_info,
+                cache_stats,
+                input_latency,
+                frame_stats,
+            )
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_update_overlay(&mut self) {
+        let mux = Mux::get();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
 
         let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
-            crate::overlay::show_debug_overlay(term, gui_win, opengl_info, connection_info)
+            crate::overlay::show_update_overlay(term)
         });
         self.assign_overlay(tab.tab_id(), overlay);
         promise::spawn::spawn(future).detach();
@@ -2326,6 +3159,7 @@ impl TermWindow {
         self.show_launcher_impl(
             "Launcher",
             LauncherFlags::LAUNCH_MENU_ITEMS
+                | LauncherFlags::PROFILES
                 | LauncherFlags::WORKSPACES
                 | LauncherFlags::DOMAINS
                 | LauncherFlags::KEY_ASSIGNMENTS
@@ -2385,7 +3219,7 @@ impl TermWindow {
     }
 
     /// Returns the Prompt semantic zones
-    fn get_semantic_prompt_zones(&mut self, pane: &Arc<dyn Pane>) -> &[StableRowIndex] {
+    pub fn get_semantic_prompt_zones(&mut self, pane: &Arc<dyn Pane>) -> &[PromptMark] {
         let cache = self
             .semantic_zones
             .entry(pane.pane_id())
@@ -2394,11 +3228,14 @@ impl TermWindow {
         let seqno = pane.get_current_seqno();
         if cache.seqno != seqno {
             let zones = pane.get_semantic_zones().unwrap_or_else(|_| vec![]);
-            let mut zones: Vec<StableRowIndex> = zones
+            let mut zones: Vec<PromptMark> = zones
                 .into_iter()
                 .filter_map(|zone| {
                     if zone.semantic_type == wezterm_term::SemanticType::Prompt {
-                        Some(zone.start_y)
+                        Some(PromptMark {
+                            row: zone.start_y,
+                            status: zone.command_status,
+                        })
                     } else {
                         None
                     }
@@ -2408,7 +3245,7 @@ impl TermWindow {
             // defined: we only care if there were 1+ prompts on a line,
             // not about how many prompts are on a line.
             // <https://github.com/wez/wezterm/issues/1121>
-            zones.dedup();
+            zones.dedup_by_key(|mark| mark.row);
             cache.zones = zones;
             cache.seqno = seqno;
         }
@@ -2416,16 +3253,46 @@ impl TermWindow {
     }
 
     fn scroll_to_prompt(&mut self, amount: isize, pane: &Arc<dyn Pane>) -> anyhow::Result<()> {
+        self.scroll_to_mark(
+            &ScrollToMark {
+                kind: ScrollMarkKind::Prompt,
+                amount,
+            },
+            pane,
+        )
+    }
+
+    fn scroll_to_mark(&mut self, mark: &ScrollToMark, pane: &Arc<dyn Pane>) -> anyhow::Result<()> {
         let dims = pane.get_dimensions();
         let position = self
             .get_viewport(pane.pane_id())
             .unwrap_or(dims.physical_top);
         let zone = {
-            let zones = self.get_semantic_prompt_zones(&pane);
+            let zones: Vec<StableRowIndex> = match mark.kind {
+                ScrollMarkKind::Annotation => match pane.annotations() {
+                    Some(annotations) => {
+                        let mut annotations = annotations.lock();
+                        annotations.prune(dims.scrollback_top);
+                        annotations.lines()
+                    }
+                    None => vec![],
+                },
+                ScrollMarkKind::Prompt | ScrollMarkKind::FailedCommand => self
+                    .get_semantic_prompt_zones(&pane)
+                    .iter()
+                    .filter(|zone| match mark.kind {
+                        ScrollMarkKind::FailedCommand => {
+                            zone.status.map(|status| status.failed()).unwrap_or(false)
+                        }
+                        _ => true,
+                    })
+                    .map(|zone| zone.row)
+                    .collect(),
+            };
             let idx = match zones.binary_search(&position) {
                 Ok(idx) | Err(idx) => idx,
             };
-            let idx = ((idx as isize) + amount).max(0) as usize;
+            let idx = ((idx as isize) + mark.amount).max(0) as usize;
             zones.get(idx).cloned()
         };
         if let Some(zone) = zone {
@@ -2498,6 +3365,58 @@ impl TermWindow {
         self.move_tab(tab)
     }
 
+    /// Gives the `pre-assignment` event handler the opportunity to
+    /// cancel or replace an assignment that was triggered by a key or
+    /// mouse binding.  Returns the assignment that should be performed,
+    /// or None if it was cancelled.
+    pub fn run_pre_assignment_hook(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+        assignment: &KeyAssignment,
+    ) -> Option<KeyAssignment> {
+        if self.in_pre_assignment_hook {
+            return Some(assignment.clone());
+        }
+
+        self.in_pre_assignment_hook = true;
+        let this = &*self;
+        let result = config::run_immediate_with_lua_config(|lua| {
+            let lua = match lua {
+                Some(lua) => lua,
+                None => return Ok(Some(assignment.clone())),
+            };
+            if !config::lua::has_event_handler(&lua, "pre-assignment")? {
+                return Ok(Some(assignment.clone()));
+            }
+
+            let v = config::lua::emit_sync_callback(
+                &*lua,
+                (
+                    "pre-assignment".to_string(),
+                    (
+                        GuiWin::new(this),
+                        MuxPane(pane.pane_id()),
+                        assignment.clone(),
+                    ),
+                ),
+            )?;
+            match v {
+                mlua::Value::Nil | mlua::Value::Boolean(true) => Ok(Some(assignment.clone())),
+                mlua::Value::Boolean(false) => Ok(None),
+                v => Ok(Some(KeyAssignment::from_lua(v, &*lua)?)),
+            }
+        });
+        self.in_pre_assignment_hook = false;
+
+        match result {
+            Ok(assignment) => assignment,
+            Err(err) => {
+                log::warn!("pre-assignment: {err:#}");
+                Some(assignment.clone())
+            }
+        }
+    }
+
     pub fn perform_key_assignment(
         &mut self,
         pane: &Arc<dyn Pane>,
@@ -2594,6 +3513,19 @@ impl TermWindow {
             ToggleFullScreen => {
                 self.window.as_ref().unwrap().toggle_fullscreen();
             }
+            TogglePresentationMode => {
+                self.toggle_presentation_mode();
+            }
+            ToggleIntegerScaling => {
+                let enabled = !self.snap_to_pixel_grid();
+                self.set_integer_scaling(Some(enabled));
+            }
+            ToggleZenMode => {
+                self.toggle_zen_mode();
+            }
+            ToggleDropDownWindow => {
+                crate::dropdown::toggle();
+            }
             ToggleAlwaysOnTop => {
                 let window = self.window.clone().unwrap();
                 let current_level = self.window_state.as_window_level();
@@ -2624,9 +3556,10 @@ impl TermWindow {
                 let window = self.window.clone().unwrap();
                 window.set_window_level(level.clone());
             }
-            CopyTo(dest) => {
-                let text = self.selection_text(pane);
-                self.copy_to_clipboard(*dest, text);
+            CopyTo(options) => {
+                let text =
+                    self.selection_text_for_copy(pane, options.rewrap_at, options.with_timestamps);
+                self.copy_selection_to_clipboard(pane, options.destination, text);
             }
             CopyTextTo { text, destination } => {
                 self.copy_to_clipboard(*destination, text.clone());
@@ -2634,6 +3567,9 @@ impl TermWindow {
             PasteFrom(source) => {
                 self.paste_from_clipboard(pane, *source);
             }
+            PasteAsBlock(source) => {
+                self.paste_block_from_clipboard(pane, *source);
+            }
             ActivateTabRelative(n) => {
                 self.activate_tab_relative(*n, true)?;
             }
@@ -2661,6 +3597,11 @@ impl TermWindow {
             ActivateWindowRelativeNoWrap(n) => {
                 self.activate_window_relative(*n, false)?;
             }
+            ActivateLastWindow => {
+                if let Some(win) = front_end().last_active_window(self.mux_window_id) {
+                    win.window.focus();
+                }
+            }
             SendString(s) => pane.writer().write_all(s.as_bytes())?,
             SendKey(key) => {
                 use keyevent::Key;
@@ -2683,6 +3624,8 @@ impl TermWindow {
             }
             CloseCurrentTab { confirm } => self.close_current_tab(*confirm),
             CloseCurrentPane { confirm } => self.close_current_pane(*confirm),
+            ReopenClosedTab => self.reopen_closed_tab(),
+            ReopenClosedPane => self.reopen_closed_pane(),
             Nop | DisableDefaultAssignment => {}
             ReloadConfiguration => config::reload(),
             MoveTab(n) => self.move_tab(*n)?,
@@ -2691,10 +3634,12 @@ impl TermWindow {
             ScrollByLine(n) => self.scroll_by_line(*n, pane)?,
             ScrollByCurrentEventWheelDelta => self.scroll_by_current_event_wheel_delta(pane)?,
             ScrollToPrompt(n) => self.scroll_to_prompt(*n, pane)?,
+            ScrollToMark(mark) => self.scroll_to_mark(mark, pane)?,
             ScrollToTop => self.scroll_to_top(pane),
             ScrollToBottom => self.scroll_to_bottom(pane),
             ShowTabNavigator => self.show_tab_navigator(),
             ShowDebugOverlay => self.show_debug_overlay(),
+            ShowUpdateOverlay => self.show_update_overlay(),
             ShowLauncher => self.show_launcher(),
             ShowLauncherArgs(args) => {
                 self.show_launcher_impl(args.title.as_deref().unwrap_or("Launcher"), args.flags)
@@ -2703,6 +3648,19 @@ impl TermWindow {
                 let con = Connection::get().expect("call on gui thread");
                 con.hide_application();
             }
+            RestoreSession => {
+                promise::spawn::spawn(async move {
+                    let path = mux::session::default_session_path();
+                    let result = async {
+                        let session = mux::session::SessionState::load(&path)?;
+                        session.restore().await
+                    };
+                    if let Err(err) = result.await {
+                        log::error!("RestoreSession: {err:#}");
+                    }
+                })
+                .detach();
+            }
             QuitApplication => {
                 let mux = Mux::get();
                 let config = &self.config;
@@ -2735,6 +3693,9 @@ impl TermWindow {
             ClearSelection => {
                 self.clear_selection(pane);
             }
+            HighlightSelection => {
+                self.highlight_selection(pane);
+            }
             StartWindowDrag => {
                 self.window_drag_position = self.current_mouse_event.clone();
             }
@@ -2745,9 +3706,9 @@ impl TermWindow {
                 self.emit_window_event(name, None);
             }
             CompleteSelectionOrOpenLinkAtMouseCursor(dest) => {
-                let text = self.selection_text(pane);
+                let text = self.selection_text_for_copy(pane, None);
                 if !text.is_empty() {
-                    self.copy_to_clipboard(*dest, text);
+                    self.copy_selection_to_clipboard(pane, *dest, text);
                     let window = self.window.as_ref().unwrap();
                     window.invalidate();
                 } else {
@@ -2755,9 +3716,9 @@ impl TermWindow {
                 }
             }
             CompleteSelection(dest) => {
-                let text = self.selection_text(pane);
+                let text = self.selection_text_for_copy(pane, None);
                 if !text.is_empty() {
-                    self.copy_to_clipboard(*dest, text);
+                    self.copy_selection_to_clipboard(pane, *dest, text);
                     let window = self.window.as_ref().unwrap();
                     window.invalidate();
                 }
@@ -2863,7 +3824,13 @@ impl TermWindow {
 
                 let tab_id = tab.tab_id();
 
-                if self.tab_state(tab_id).overlay.is_none() {
+                if self.tab_state(tab_id).overlay.is_none()
+                    && !self.navigate_via_application(
+                        &tab,
+                        *direction,
+                        NavigationAction::Resize(*amount),
+                    )
+                {
                     tab.adjust_pane_size(*direction, *amount);
                 }
             }
@@ -2892,10 +3859,32 @@ impl TermWindow {
 
                 let tab_id = tab.tab_id();
 
-                if self.tab_state(tab_id).overlay.is_none() {
+                if self.tab_state(tab_id).overlay.is_none()
+                    && !self.navigate_via_application(&tab, *direction, NavigationAction::Activate)
+                {
                     tab.activate_pane_direction(*direction);
                 }
             }
+            ToggleIME => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    pane.set_ime_enabled(!pane.is_ime_enabled());
+                    self.update_ime_enabled();
+                }
+            }
+            ToggleBroadcastInput(scope) => {
+                self.toggle_broadcast_input(pane, *scope);
+            }
+            TogglePaneOutputPause => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    pane.set_output_paused(!pane.is_output_paused());
+                }
+                self.update_title();
+            }
+            ToggleLineTimestamps => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    self.toggle_line_timestamps(&pane);
+                }
+            }
             TogglePaneZoomState => {
                 let mux = Mux::get();
                 let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -2928,6 +3917,13 @@ impl TermWindow {
                     front_end().switch_workspace(w);
                 }
             }
+            SwitchToLastWorkspace(depth) => {
+                let fe = front_end();
+                match fe.last_workspace((*depth).max(1)) {
+                    Some(workspace) => fe.switch_workspace(&workspace),
+                    None => log::debug!("SwitchToLastWorkspace: no workspace at depth {depth}"),
+                }
+            }
             SwitchToWorkspace { name, spawn } => {
                 let activity = crate::Activity::new();
                 let mux = Mux::get();
@@ -2966,6 +3962,11 @@ impl TermWindow {
             }
             DetachDomain(domain) => {
                 let domain = Mux::get().resolve_spawn_tab_domain(Some(pane.pane_id()), domain)?;
+                if !domain.detachable() {
+                    anyhow::bail!("domain {} cannot be detached", domain.domain_name());
+                }
+                // The mux removes the panes of the domain and moves the
+                // focus of the affected tabs to their surviving panes
                 domain.detach()?;
             }
             AttachDomain(domain) => {
@@ -3004,6 +4005,37 @@ impl TermWindow {
                 })
                 .detach();
             }
+            MoveTabToDomain(domain) => {
+                let tab_id = match Mux::get().get_active_tab_for_window(self.mux_window_id) {
+                    Some(tab) => tab.tab_id(),
+                    None => return Ok(PerformAssignmentResult::Handled),
+                };
+                let domain = domain.to_string();
+
+                promise::spawn::spawn(async move {
+                    let mux = Mux::get();
+                    let domain = mux
+                        .get_domain_by_name(&domain)
+                        .ok_or_else(|| anyhow!("{} is not a valid domain name", domain))?;
+                    let client_domain = domain
+                        .downcast_ref::<wezterm_client::domain::ClientDomain>()
+                        .ok_or_else(|| {
+                            anyhow!("{} is not a multiplexer domain", domain.domain_name())
+                        })?;
+                    if let Err(err) = client_domain.adopt_local_tab(tab_id).await {
+                        log::error!(
+                            "Failed to move tab {tab_id} to {}: {err:#}",
+                            domain.domain_name()
+                        );
+                        wezterm_toast_notification::persistent_toast_notification(
+                            "Failed to move tab",
+                            &format!("{err:#}"),
+                        );
+                    }
+                    Result::<(), anyhow::Error>::Ok(())
+                })
+                .detach();
+            }
             CopyMode(_) => {
                 // NOP here; handled by the overlay directly
             }
@@ -3020,39 +4052,81 @@ impl TermWindow {
             }
             SplitPane(split) => {
                 log::trace!("SplitPane {:?}", split);
+                let spawn_where = SpawnWhere::SplitPane(SplitRequest {
+                    direction: match split.direction {
+                        PaneDirection::Down | PaneDirection::Up => SplitDirection::Vertical,
+                        PaneDirection::Left | PaneDirection::Right => SplitDirection::Horizontal,
+                        PaneDirection::Next | PaneDirection::Prev => {
+                            log::error!("Invalid direction {:?} for SplitPane", split.direction);
+                            return Ok(PerformAssignmentResult::Handled);
+                        }
+                    },
+                    target_is_second: match split.direction {
+                        PaneDirection::Down | PaneDirection::Right => true,
+                        PaneDirection::Up | PaneDirection::Left => false,
+                        PaneDirection::Next | PaneDirection::Prev => unreachable!(),
+                    },
+                    size: match split.size {
+                        SplitSize::Percent(n) => MuxSplitSize::Percent(n),
+                        SplitSize::Cells(n) => MuxSplitSize::Cells(n),
+                    },
+                    top_level: split.top_level,
+                });
+                if split.inherit_scrollback {
+                    self.split_pane_with_scrollback(pane, &split.command, spawn_where);
+                } else {
+                    self.spawn_command(&split.command, spawn_where);
+                }
+            }
+            SpawnFloatingPane(float) => {
+                log::trace!("SpawnFloatingPane {:?}", float);
+                let to_mux = |size: &SplitSize| match size {
+                    SplitSize::Percent(n) => MuxSplitSize::Percent(*n),
+                    SplitSize::Cells(n) => MuxSplitSize::Cells(*n),
+                };
                 self.spawn_command(
-                    &split.command,
-                    SpawnWhere::SplitPane(SplitRequest {
-                        direction: match split.direction {
-                            PaneDirection::Down | PaneDirection::Up => SplitDirection::Vertical,
-                            PaneDirection::Left | PaneDirection::Right => {
-                                SplitDirection::Horizontal
-                            }
-                            PaneDirection::Next | PaneDirection::Prev => {
-                                log::error!(
-                                    "Invalid direction {:?} for SplitPane",
-                                    split.direction
-                                );
-                                return Ok(PerformAssignmentResult::Handled);
-                            }
-                        },
-                        target_is_second: match split.direction {
-                            PaneDirection::Down | PaneDirection::Right => true,
-                            PaneDirection::Up | PaneDirection::Left => false,
-                            PaneDirection::Next | PaneDirection::Prev => unreachable!(),
-                        },
-                        size: match split.size {
-                            SplitSize::Percent(n) => MuxSplitSize::Percent(n),
-                            SplitSize::Cells(n) => MuxSplitSize::Cells(n),
-                        },
-                        top_level: split.top_level,
+                    &float.command,
+                    SpawnWhere::FloatingPane(FloatingPaneGeometry {
+                        width: to_mux(&float.width),
+                        height: to_mux(&float.height),
+                        position: float.position,
                     }),
                 );
             }
+            ToggleFloatingPane => {
+                let mux = Mux::get();
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    tab.toggle_floating_panes();
+                }
+            }
             PaneSelect(args) => {
                 let modal = crate::termwindow::paneselect::PaneSelector::new(self, args);
                 self.set_modal(Rc::new(modal));
             }
+            LinkPanes(options) => {
+                let modal = crate::termwindow::paneselect::PaneSelector::new(
+                    self,
+                    &PaneSelectArguments {
+                        mode: PaneSelectMode::LinkWithActive(*options),
+                        ..Default::default()
+                    },
+                );
+                self.set_modal(Rc::new(modal));
+            }
+            ShowWorkspaceSelector => {
+                let modal = crate::termwindow::workspaceselect::WorkspaceSelector::new(self);
+                self.set_modal(Rc::new(modal));
+            }
+            ShowTabOverview => {
+                let modal = crate::termwindow::taboverview::TabOverview::new(self);
+                self.set_modal(Rc::new(modal));
+            }
+            FocusTabBar => {
+                if self.show_tab_bar {
+                    let modal = crate::termwindow::tabbarfocus::TabBarFocus::new(self);
+                    self.set_modal(Rc::new(modal));
+                }
+            }
             CharSelect(args) => {
                 let modal = crate::termwindow::charselect::CharSelector::new(self, args);
                 self.set_modal(Rc::new(modal));
@@ -3071,6 +4145,9 @@ impl TermWindow {
             }
             PromptInputLine(args) => self.show_prompt_input_line(args),
             InputSelector(args) => self.show_input_selector(args),
+            RespawnPane(respawn) => self.respawn_pane(pane, respawn),
+            SaveScrollback(args) => self.save_scrollback(pane, args),
+            Disconnect => pane.kill(),
         };
         Ok(PerformAssignmentResult::Handled)
     }
@@ -3085,23 +4162,74 @@ impl TermWindow {
         // handler that can bypass the normal `open_url` functionality.
         if let Some(link) = self.current_highlight.as_ref().cloned() {
             let window = GuiWin::new(self);
+            let rule_match = if link.is_implicit() {
+                self.implicit_link_captures(pane, &link)
+            } else {
+                None
+            };
             let pane = MuxPane(pane.pane_id());
 
+            async fn resolve_hyperlink(
+                lua: &mlua::Lua,
+                window: GuiWin,
+                pane: MuxPane,
+                rule_match: RuleCaptureMatch,
+            ) -> anyhow::Result<Option<String>> {
+                let captures = lua.create_table()?;
+                for (idx, capture) in rule_match.captures.into_iter().enumerate() {
+                    if let Some(capture) = capture {
+                        captures.set(idx, capture)?;
+                    }
+                }
+                for (name, capture) in rule_match.named {
+                    captures.set(name, capture)?;
+                }
+                let info = lua.create_table()?;
+                info.set("uri", rule_match.uri)?;
+                info.set("text", rule_match.text)?;
+                info.set("captures", captures)?;
+
+                let args = lua.pack_multi((window, pane, info))?;
+                let value =
+                    config::lua::emit_async_callback(lua, ("resolve-hyperlink".to_string(), args))
+                        .await?;
+                match value {
+                    mlua::Value::Nil => Ok(None),
+                    value => Ok(Some(String::from_lua(value, lua)?)),
+                }
+            }
+
             async fn open_uri(
                 lua: Option<Rc<mlua::Lua>>,
                 window: GuiWin,
                 pane: MuxPane,
                 link: String,
+                rule_match: Option<RuleCaptureMatch>,
             ) -> anyhow::Result<()> {
+                let link = match (&lua, rule_match) {
+                    (Some(lua), Some(rule_match))
+                        if config::lua::has_event_handler(lua, "resolve-hyperlink")? =>
+                    {
+                        match resolve_hyperlink(lua, window.clone(), pane, rule_match)
+                            .await
+                            .context("while processing resolve-hyperlink event")?
+                        {
+                            Some(link) => link,
+                            None => {
+                                log::debug!("resolve-hyperlink suppressed {}", link);
+                                return Ok(());
+                            }
+                        }
+                    }
+                    _ => link,
+                };
+
                 let default_click = match lua {
                     Some(lua) => {
                         let args = lua.pack_multi((window, pane, link.clone()))?;
                         config::lua::emit_event(&lua, ("open-uri".to_string(), args))
                             .await
-                            .map_err(|e| {
-                                log::error!("while processing open-uri event: {:#}", e);
-                                e
-                            })?
+                            .context("while processing open-uri event")?
                     }
                     None => true,
                 };
@@ -3112,12 +4240,80 @@ impl TermWindow {
                 Ok(())
             }
 
-            promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
-                open_uri(lua, window, pane, link.uri().to_string())
-            }))
+            promise::spawn::spawn(async move {
+                if let Err(err) = config::with_lua_config_on_main_thread(move |lua| {
+                    open_uri(lua, window, pane, link.uri().to_string(), rule_match)
+                })
+                .await
+                {
+                    log::error!("{:#}", err);
+                }
+            })
             .detach();
         }
     }
+
+    /// Re-evaluates the hyperlink rules against the logical line under
+    /// the mouse cursor to recover the text and captures that produced
+    /// the implicit `link`.
+    fn implicit_link_captures(
+        &self,
+        pane: &Arc<dyn Pane>,
+        link: &Hyperlink,
+    ) -> Option<RuleCaptureMatch> {
+        let (_, stable_row) = self.pane_state(pane.pane_id()).mouse_terminal_coords?;
+        let logical = pane
+            .get_logical_lines(stable_row..stable_row + 1)
+            .into_iter()
+            .next()?;
+        let line = logical.logical.as_str();
+        Rule::match_hyperlink_captures(&line, &self.config.hyperlink_rules)
+            .into_iter()
+            .find(|m| m.uri == link.uri())
+    }
+    fn respawn_pane(&mut self, pane: &Arc<dyn Pane>, respawn: &RespawnPane) {
+        let pane_id = pane.pane_id();
+        let command = respawn
+            .args
+            .as_ref()
+            .map(|args| CommandBuilder::from_argv(args.iter().map(Into::into).collect()));
+        let command_dir = respawn
+            .cwd
+            .as_ref()
+            .map(|cwd| cwd.to_string_lossy().to_string());
+
+        promise::spawn::spawn(async move {
+            if let Err(err) = Mux::get().respawn_pane(pane_id, command, command_dir).await {
+                log::error!("Failed to respawn pane {pane_id}: {err:#}");
+            }
+        })
+        .detach();
+    }
+
+    fn save_scrollback(&mut self, pane: &Arc<dyn Pane>, args: &SaveScrollback) {
+        let pane = Arc::clone(pane);
+        let path = args.path.clone();
+        let format = args.format;
+
+        promise::spawn::spawn(async move {
+            let result = async {
+                let path = match path {
+                    Some(path) => path,
+                    None => crate::download::scrollback_export_path(format.extension())?,
+                };
+                mux::scrollback::save_scrollback(&pane, &path, format).await?;
+                anyhow::Result::<PathBuf>::Ok(path)
+            }
+            .await;
+
+            match result {
+                Ok(path) => crate::download::notify_scrollback_saved(&path),
+                Err(err) => log::error!("Failed to save scrollback: {err:#}"),
+            }
+        })
+        .detach();
+    }
+
     fn close_current_pane(&mut self, confirm: bool) {
         let mux_window_id = self.mux_window_id;
         let mux = Mux::get();
@@ -3131,15 +4327,44 @@ impl TermWindow {
         };
 
         let pane_id = pane.pane_id();
-        if confirm && !pane.can_close_without_prompting(CloseReason::Pane) {
+        // Panes linked with close_together are closed along with this one,
+        // and are included in the confirmation
+        let linked = mux.panes_closed_with(pane_id);
+        if confirm
+            && std::iter::once(&pane)
+                .chain(linked.iter())
+                .any(|p| !p.can_close_without_prompting(CloseReason::Pane))
+        {
             let window = self.window.clone().unwrap();
             let (overlay, future) = start_overlay_pane(self, &pane, move |pane_id, term| {
-                confirm_close_pane(pane_id, term, mux_window_id, window)
+                confirm_close_pane(pane_id, linked, term, mux_window_id, window)
             });
             self.assign_overlay_for_pane(pane_id, overlay);
             promise::spawn::spawn(future).detach();
         } else {
-            mux.remove_pane(pane_id);
+            mux.retain_closed_pane(pane_id);
+            mux.remove_pane_and_linked(pane_id);
+        }
+    }
+
+    /// Brings the panes that are linked with `pane_id` by
+    /// `activate_together` up to date now that it has been focused:
+    /// they are scrolled to the bottom and their output is marked
+    /// as having been seen
+    fn activate_linked_panes(&mut self, pane_id: PaneId) {
+        let mux = Mux::get();
+        match mux.resolve_pane_id(pane_id) {
+            Some((_domain_id, window_id, _tab_id)) if window_id == self.mux_window_id => {}
+            _ => return,
+        }
+        for link in mux.get_pane_links(pane_id) {
+            if !link.activate_together {
+                continue;
+            }
+            if let Some(pane) = mux.get_pane(link.pane_id) {
+                pane.mark_output_seen();
+                self.set_viewport(pane.pane_id(), None, pane.get_dimensions());
+            }
         }
     }
 
@@ -3170,6 +4395,7 @@ impl TermWindow {
             self.assign_overlay(tab_id, overlay);
             promise::spawn::spawn(future).detach();
         } else {
+            mux.retain_closed_tab(tab_id);
             mux.remove_tab(tab_id);
         }
     }
@@ -3190,6 +4416,7 @@ impl TermWindow {
             self.assign_overlay(tab_id, overlay);
             promise::spawn::spawn(future).detach();
         } else {
+            mux.retain_closed_tab(tab_id);
             mux.remove_tab(tab_id);
         }
     }
@@ -3276,6 +4503,7 @@ impl TermWindow {
 
     fn maybe_scroll_to_bottom_for_input(&mut self, pane: &Arc<dyn Pane>) {
         if self.config.scroll_to_bottom_on_input {
+            self.cancel_kinetic_scroll();
             self.scroll_to_bottom(pane);
         }
     }
@@ -3345,12 +4573,17 @@ impl TermWindow {
     }
 
     fn pos_pane_to_pane_info(pos: &PositionedPane) -> PaneInformation {
+        let alerts = pos.pane.get_output_alerts();
         PaneInformation {
             pane_id: pos.pane.pane_id(),
             pane_index: pos.index,
             is_active: pos.is_active,
             is_zoomed: pos.is_zoomed,
             has_unseen_output: pos.pane.has_unseen_output(),
+            has_activity_alert: alerts.activity,
+            has_silence_alert: alerts.silence,
+            is_output_rate_limited: pos.pane.is_output_rate_limited(),
+            is_output_paused: pos.pane.is_output_paused(),
             left: pos.left,
             top: pos.top,
             width: pos.width,
@@ -3376,6 +4609,21 @@ impl TermWindow {
             .map(|(idx, tab)| {
                 let panes = self.get_pos_panes_for_tab(tab);
 
+                // Prefer the progress of the active pane, but show
+                // progress from any other pane in the tab otherwise
+                let progress = panes
+                    .iter()
+                    .find(|p| p.is_active)
+                    .map(|p| p.pane.get_progress())
+                    .filter(|progress| *progress != Progress::None)
+                    .or_else(|| {
+                        panes
+                            .iter()
+                            .map(|p| p.pane.get_progress())
+                            .find(|progress| *progress != Progress::None)
+                    })
+                    .unwrap_or_default();
+
                 TabInformation {
                     tab_index: idx,
                     tab_id: tab.tab_id(),
@@ -3386,6 +4634,9 @@ impl TermWindow {
                         .iter()
                         .find(|p| p.is_active)
                         .map(Self::pos_pane_to_pane_info),
+                    progress,
+                    is_bell_ringing: self.is_tab_bell_ringing(tab.tab_id()),
+                    has_paused_pane: panes.iter().any(|p| p.pane.is_output_paused()),
                 }
             })
             .collect()
@@ -3412,6 +4663,7 @@ impl TermWindow {
                 index: 0,
                 is_active: true,
                 is_zoomed: false,
+                is_floating: false,
                 left: 0,
                 top: 0,
                 width: size.cols as _,
@@ -3422,10 +4674,33 @@ impl TermWindow {
             }]
         } else {
             let mut panes = tab.iter_panes();
+            // The floating panes follow the split tree, in stacking order
+            panes.extend(tab.iter_floating_panes());
+            let decoration = tab.pane_decoration_rows();
             for p in &mut panes {
                 if let Some(overlay) = self.pane_state(p.pane.pane_id()).overlay.as_ref() {
                     p.pane = Arc::clone(&overlay.pane);
                 }
+                // Floating panes have a border rather than decorations
+                if decoration != 0 && !p.is_floating {
+                    // The decorations of the pane sit above its
+                    // terminal; report only the terminal portion
+                    p.top += decoration;
+                    p.height = p.height.saturating_sub(decoration);
+                    p.pixel_height = p
+                        .pixel_height
+                        .saturating_sub(decoration * self.render_metrics.cell_size.height as usize);
+                }
+                let gutter = p.pane.timestamp_gutter_cols();
+                if gutter != 0 {
+                    // The timestamps gutter sits to the left of the
+                    // terminal; see paint_pane_timestamps
+                    p.left += gutter;
+                    p.width = p.width.saturating_sub(gutter);
+                    p.pixel_width = p
+                        .pixel_width
+                        .saturating_sub(gutter * self.render_metrics.cell_size.width as usize);
+                }
             }
             panes
         }
@@ -3,13 +3,13 @@ use crate::termwindow::{
     GuiWin, MouseCapture, PositionedSplit, ScrollHit, TermWindowNotif, UIItem, UIItemType, TMB,
 };
 use ::window::{
-    MouseButtons as WMB, MouseCursor, MouseEvent, MouseEventKind as WMEK, MousePress,
-    WindowDecorations, WindowOps, WindowState,
+    Connection, ConnectionOps, IntegratedTitleButton, MouseButtons as WMB, MouseCursor, MouseEvent,
+    MouseEventKind as WMEK, MousePress, WindowDecorations, WindowOps, WindowState,
 };
 use config::keyassignment::{KeyAssignment, MouseEventTrigger, SpawnTabDomain};
-use config::MouseEventAltScreen;
-use mux::pane::{Pane, WithPaneLines};
-use mux::tab::SplitDirection;
+use config::{MouseEventAltScreen, TabBarOverflow};
+use mux::pane::{Pane, PaneId, WithPaneLines};
+use mux::tab::{SplitDirection, Tab};
 use mux::Mux;
 use mux_lua::MuxPane;
 use std::convert::TryInto;
@@ -21,7 +21,7 @@ use termwiz::hyperlink::Hyperlink;
 use termwiz::surface::Line;
 use wezterm_dynamic::ToDynamic;
 use wezterm_term::input::{MouseButton, MouseEventKind as TMEK};
-use wezterm_term::{ClickPosition, LastMouseClick, StableRowIndex};
+use wezterm_term::{ClickPosition, LastMouseClick, MousePointerShape, StableRowIndex};
 
 impl super::TermWindow {
     fn resolve_ui_item(&self, event: &MouseEvent) -> Option<UIItem> {
@@ -43,7 +43,10 @@ impl super::TermWindow {
             | UIItemType::AboveScrollThumb
             | UIItemType::BelowScrollThumb
             | UIItemType::ScrollThumb
-            | UIItemType::Split(_) => {}
+            | UIItemType::Split(_)
+            | UIItemType::PaneTitleBar(_)
+            | UIItemType::ClosePane(_)
+            | UIItemType::TogglePaneZoom(_) => {}
         }
     }
 
@@ -54,7 +57,10 @@ impl super::TermWindow {
             | UIItemType::AboveScrollThumb
             | UIItemType::BelowScrollThumb
             | UIItemType::ScrollThumb
-            | UIItemType::Split(_) => {}
+            | UIItemType::Split(_)
+            | UIItemType::PaneTitleBar(_)
+            | UIItemType::ClosePane(_)
+            | UIItemType::TogglePaneZoom(_) => {}
         }
     }
 
@@ -69,11 +75,8 @@ impl super::TermWindow {
 
         let border = self.get_os_border();
 
-        let first_line_offset = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
-            self.tab_bar_pixel_height().unwrap_or(0.) as isize
-        } else {
-            0
-        } + border.top.get() as isize;
+        let tab_bar = self.tab_bar_reserved_edges().unwrap_or_default();
+        let first_line_offset = tab_bar.top as isize + border.top.get() as isize;
 
         let (padding_left, padding_top) = self.padding_left_top();
 
@@ -88,7 +91,7 @@ impl super::TermWindow {
         let x = (event
             .coords
             .x
-            .sub((padding_left + border.left.get() as f32) as isize)
+            .sub((tab_bar.left + padding_left + border.left.get() as f32) as isize)
             .max(0) as f32)
             / self.render_metrics.cell_size.width as f32;
         let x = if !pane.is_mouse_grabbed() {
@@ -112,7 +115,7 @@ impl super::TermWindow {
         let mut x_pixel_offset = event
             .coords
             .x
-            .sub((padding_left + border.left.get() as f32) as isize);
+            .sub((tab_bar.left + padding_left + border.left.get() as f32) as isize);
         if x > 0 {
             x_pixel_offset = x_pixel_offset.max(0) % self.render_metrics.cell_size.width;
         }
@@ -150,7 +153,12 @@ impl super::TermWindow {
 
                 let click = match self.last_mouse_click.take() {
                     None => LastMouseClick::new(button, click_position),
-                    Some(click) => click.add(button, click_position),
+                    Some(click) => {
+                        match Connection::get().and_then(|c| c.double_click_interval()) {
+                            Some(interval) => click.add_within(button, click_position, interval),
+                            None => click.add(button, click_position),
+                        }
+                    }
                 };
                 self.last_mouse_click = Some(click);
                 self.current_mouse_buttons.retain(|p| p != press);
@@ -246,11 +254,84 @@ impl super::TermWindow {
 
     pub fn mouse_leave_impl(&mut self, context: &dyn WindowOps) {
         self.current_mouse_event = None;
+        self.focus_follows_mouse_pending.take();
         self.update_title();
         context.set_cursor(Some(MouseCursor::Arrow));
         context.invalidate();
     }
 
+    /// Returns true if it is appropriate for pane_focus_follows_mouse
+    /// to change the active pane right now: we don't want to steal
+    /// focus while a button is held (eg: a selection is being dragged
+    /// out) or while a modal or tab-level overlay is active.
+    fn can_focus_follow_mouse(&self) -> bool {
+        if !self.current_mouse_buttons.is_empty() || self.dragging.is_some() {
+            return false;
+        }
+        if self.get_modal().is_some() {
+            return false;
+        }
+        let mux = Mux::get();
+        match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => self.tab_state(tab.tab_id()).overlay.is_none(),
+            None => false,
+        }
+    }
+
+    /// Arrange to activate pane_id once the mouse has rested over
+    /// it for pane_focus_follows_mouse_delay_ms.
+    fn schedule_focus_follows_mouse(&mut self, pane_id: PaneId) {
+        if matches!(self.focus_follows_mouse_pending, Some((pending, _)) if pending == pane_id) {
+            return;
+        }
+        self.focus_follows_mouse_generation += 1;
+        let generation = self.focus_follows_mouse_generation;
+        self.focus_follows_mouse_pending
+            .replace((pane_id, generation));
+
+        if let Some(window) = self.window.clone() {
+            let delay = Duration::from_millis(self.config.pane_focus_follows_mouse_delay_ms);
+            promise::spawn::spawn(async move {
+                smol::Timer::after(delay).await;
+                window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                    term_window.complete_focus_follows_mouse(pane_id, generation);
+                })));
+            })
+            .detach();
+        }
+    }
+
+    fn complete_focus_follows_mouse(&mut self, pane_id: PaneId, generation: usize) {
+        if self.focus_follows_mouse_pending != Some((pane_id, generation)) {
+            // The mouse moved elsewhere in the meantime; if it has
+            // since returned to pane_id, a later timer will handle it
+            return;
+        }
+        self.focus_follows_mouse_pending.take();
+
+        if !self.config.pane_focus_follows_mouse || !self.can_focus_follow_mouse() {
+            return;
+        }
+
+        let mux = Mux::get();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        if let Some(pos) = self
+            .get_panes_to_render()
+            .into_iter()
+            .find(|pos| pos.pane.pane_id() == pane_id && !pos.is_active)
+        {
+            // Use the same path as click-to-focus so that focus
+            // reporting and pane recency are kept up to date
+            tab.activate_positioned_pane(&pos);
+            if let Some(window) = self.window.as_ref() {
+                window.invalidate();
+            }
+        }
+    }
+
     fn drag_split(
         &mut self,
         mut item: UIItem,
@@ -295,16 +376,8 @@ impl super::TermWindow {
         let dims = pane.get_dimensions();
         let current_viewport = self.get_viewport(pane.pane_id());
 
-        let tab_bar_height = if self.show_tab_bar {
-            self.tab_bar_pixel_height().unwrap_or(0.)
-        } else {
-            0.
-        };
-        let (top_bar_height, bottom_bar_height) = if self.config.tab_bar_at_bottom {
-            (0.0, tab_bar_height)
-        } else {
-            (tab_bar_height, 0.0)
-        };
+        let tab_bar = self.tab_bar_reserved_edges().unwrap_or_default();
+        let (top_bar_height, bottom_bar_height) = (tab_bar.top, tab_bar.bottom);
 
         let border = self.get_os_border();
         let y_offset = top_bar_height + border.top.get() as f32;
@@ -382,9 +455,49 @@ impl super::TermWindow {
             UIItemType::CloseTab(idx) => {
                 self.mouse_event_close_tab(idx, event, context);
             }
+            UIItemType::PaneTitleBar(pane_id) => {
+                if let WMEK::Press(MousePress::Left) = event.kind {
+                    self.activate_pane_by_id(pane_id);
+                }
+                context.set_cursor(Some(MouseCursor::Arrow));
+            }
+            UIItemType::ClosePane(pane_id) => {
+                if let WMEK::Press(MousePress::Left) = event.kind {
+                    if self.activate_pane_by_id(pane_id).is_some() {
+                        self.close_current_pane(true);
+                    }
+                }
+                context.set_cursor(Some(MouseCursor::Arrow));
+            }
+            UIItemType::TogglePaneZoom(pane_id) => {
+                if let WMEK::Press(MousePress::Left) = event.kind {
+                    if let Some(tab) = self.activate_pane_by_id(pane_id) {
+                        tab.toggle_zoom();
+                    }
+                }
+                context.set_cursor(Some(MouseCursor::Arrow));
+            }
         }
     }
 
+    /// Makes the pane that is displayed as `pane_id` the active
+    /// pane of its tab, returning that tab
+    fn activate_pane_by_id(&mut self, pane_id: PaneId) -> Option<Arc<Tab>> {
+        let mux = Mux::get();
+        let tab = mux.get_active_tab_for_window(self.mux_window_id)?;
+        let pos = self
+            .get_panes_to_render()
+            .into_iter()
+            .find(|pos| pos.pane.pane_id() == pane_id)?;
+        if !pos.is_active {
+            tab.activate_positioned_pane(&pos);
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+        Some(tab)
+    }
+
     pub fn mouse_event_close_tab(
         &mut self,
         idx: usize,
@@ -401,7 +514,28 @@ impl super::TermWindow {
         context.set_cursor(Some(MouseCursor::Arrow));
     }
 
-    fn do_new_tab_button_click(&mut self, button: MousePress) {
+    /// Performs the action of one of the integrated title buttons
+    pub fn perform_title_button(&mut self, button: IntegratedTitleButton) {
+        use window::IntegratedTitleButton as Button;
+        if let Some(ref window) = self.window {
+            match button {
+                Button::Hide => window.hide(),
+                Button::Maximize => {
+                    let maximized = self
+                        .window_state
+                        .intersects(WindowState::MAXIMIZED | WindowState::FULL_SCREEN);
+                    if maximized {
+                        window.restore();
+                    } else {
+                        window.maximize();
+                    }
+                }
+                Button::Close => self.close_requested(&window.clone()),
+            }
+        }
+    }
+
+    pub fn do_new_tab_button_click(&mut self, button: MousePress) {
         let pane = match self.get_active_pane_or_overlay() {
             Some(pane) => pane,
             None => return,
@@ -467,6 +601,8 @@ impl super::TermWindow {
                 TabBarItem::NewTabButton { .. } => {
                     self.do_new_tab_button_click(MousePress::Left);
                 }
+                TabBarItem::ScrollLeft => self.scroll_tab_bar(-1),
+                TabBarItem::ScrollRight => self.scroll_tab_bar(1),
                 TabBarItem::None | TabBarItem::LeftStatus | TabBarItem::RightStatus => {
                     let maximized = self
                         .window_state
@@ -490,25 +626,7 @@ impl super::TermWindow {
                     }
                     context.request_drag_move();
                 }
-                TabBarItem::WindowButton(button) => {
-                    use window::IntegratedTitleButton as Button;
-                    if let Some(ref window) = self.window {
-                        match button {
-                            Button::Hide => window.hide(),
-                            Button::Maximize => {
-                                let maximized = self
-                                    .window_state
-                                    .intersects(WindowState::MAXIMIZED | WindowState::FULL_SCREEN);
-                                if maximized {
-                                    window.restore();
-                                } else {
-                                    window.maximize();
-                                }
-                            }
-                            Button::Close => self.close_requested(&window.clone()),
-                        }
-                    }
-                }
+                TabBarItem::WindowButton(button) => self.perform_title_button(button),
             },
             WMEK::Press(MousePress::Middle) => match item {
                 TabBarItem::Tab { tab_idx, .. } => {
@@ -517,9 +635,14 @@ impl super::TermWindow {
                 TabBarItem::NewTabButton { .. } => {
                     self.do_new_tab_button_click(MousePress::Middle);
                 }
+                TabBarItem::RightStatus => {
+                    // The right status shows the uploads of dropped files
+                    self.cancel_uploads();
+                }
                 TabBarItem::None
                 | TabBarItem::LeftStatus
-                | TabBarItem::RightStatus
+                | TabBarItem::ScrollLeft
+                | TabBarItem::ScrollRight
                 | TabBarItem::WindowButton(_) => {}
             },
             WMEK::Press(MousePress::Right) => match item {
@@ -532,6 +655,8 @@ impl super::TermWindow {
                 TabBarItem::None
                 | TabBarItem::LeftStatus
                 | TabBarItem::RightStatus
+                | TabBarItem::ScrollLeft
+                | TabBarItem::ScrollRight
                 | TabBarItem::WindowButton(_) => {}
             },
             WMEK::Move => match item {
@@ -550,7 +675,9 @@ impl super::TermWindow {
                 }
                 TabBarItem::WindowButton(_)
                 | TabBarItem::Tab { .. }
-                | TabBarItem::NewTabButton { .. } => {}
+                | TabBarItem::NewTabButton { .. }
+                | TabBarItem::ScrollLeft
+                | TabBarItem::ScrollRight => {}
             },
             WMEK::VertWheel(n) => {
                 if self.config.mouse_wheel_scrolls_tabs {
@@ -558,11 +685,26 @@ impl super::TermWindow {
                         .ok();
                 }
             }
+            WMEK::HorzWheel(n) => {
+                if self.config.tab_bar_overflow == TabBarOverflow::Scroll {
+                    self.scroll_tab_bar(if n > 0 { -1 } else { 1 });
+                }
+            }
             _ => {}
         }
         context.set_cursor(Some(MouseCursor::Arrow));
     }
 
+    /// Scrolls the tab bar by the specified number of tabs
+    pub fn scroll_tab_bar(&mut self, amount: isize) {
+        self.tab_bar_first_tab = if amount < 0 {
+            self.tab_bar_first_tab.saturating_sub(amount.unsigned_abs())
+        } else {
+            self.tab_bar_first_tab + amount as usize
+        };
+        self.update_title_post_status();
+    }
+
     pub fn mouse_event_above_scroll_thumb(
         &mut self,
         _item: UIItem,
@@ -667,7 +809,13 @@ impl super::TermWindow {
             Some(MouseCapture::TerminalPane(_))
         );
 
-        for pos in self.get_panes_to_render() {
+        let mut panes = self.get_panes_to_render();
+        // The floating panes follow the splits, from the bottom-most to
+        // the top-most.  Reversing the order hit-tests them first, from
+        // the top-most down; the splits don't overlap each other.
+        panes.reverse();
+
+        for pos in panes {
             if !is_already_captured
                 && row >= pos.top as i64
                 && row <= (pos.top + pos.height) as i64
@@ -680,19 +828,26 @@ impl super::TermWindow {
                         WMEK::Press(_) => {
                             let mux = Mux::get();
                             mux.get_active_tab_for_window(self.mux_window_id)
-                                .map(|tab| tab.set_active_idx(pos.index));
+                                .map(|tab| tab.activate_positioned_pane(&pos));
 
                             pane = Arc::clone(&pos.pane);
                             is_click_to_focus_pane = true;
                         }
                         WMEK::Move => {
-                            if self.config.pane_focus_follows_mouse {
-                                let mux = Mux::get();
-                                mux.get_active_tab_for_window(self.mux_window_id)
-                                    .map(|tab| tab.set_active_idx(pos.index));
-
-                                pane = Arc::clone(&pos.pane);
-                                context.invalidate();
+                            if self.config.pane_focus_follows_mouse && self.can_focus_follow_mouse()
+                            {
+                                if self.config.pane_focus_follows_mouse_delay_ms == 0 {
+                                    let mux = Mux::get();
+                                    mux.get_active_tab_for_window(self.mux_window_id)
+                                        .map(|tab| tab.activate_positioned_pane(&pos));
+
+                                    pane = Arc::clone(&pos.pane);
+                                    context.invalidate();
+                                } else {
+                                    self.schedule_focus_follows_mouse(pos.pane.pane_id());
+                                }
+                            } else {
+                                self.focus_follows_mouse_pending.take();
                             }
                         }
                         WMEK::Release(_) | WMEK::HorzWheel(_) => {}
@@ -703,6 +858,10 @@ impl super::TermWindow {
                             context.invalidate();
                         }
                     }
+                } else if matches!(&event.kind, WMEK::Move) {
+                    // Resting back over the active pane cancels any
+                    // pending focus change
+                    self.focus_follows_mouse_pending.take();
                 }
                 column = column.saturating_sub(pos.left);
                 row = row.saturating_sub(pos.top as i64);
@@ -768,10 +927,11 @@ impl super::TermWindow {
         );
 
         let dims = pane.get_dimensions();
-        let stable_row = self
+        let viewport_top = self
             .get_viewport(pane.pane_id())
-            .unwrap_or(dims.physical_top)
-            + row as StableRowIndex;
+            .unwrap_or(dims.physical_top);
+        let stable_row = viewport_top + row as StableRowIndex;
+        let column = logical_column_at(&pane, viewport_top, stable_row, column);
 
         self.pane_state(pane.pane_id())
             .mouse_terminal_coords
@@ -837,7 +997,12 @@ impl super::TermWindow {
             // When hovering over a hyperlink, show an appropriate
             // mouse cursor to give the cue that it is clickable
             MouseCursor::Hand
-        } else if pane.is_mouse_grabbed() || outside_window {
+        } else if outside_window {
+            MouseCursor::Arrow
+        } else if let Some(shape) = pane.get_mouse_pointer_shape() {
+            // The application requested a shape using OSC 22
+            pointer_shape_to_cursor(shape)
+        } else if pane.is_mouse_grabbed() {
             MouseCursor::Arrow
         } else {
             MouseCursor::Text
@@ -982,7 +1147,9 @@ impl super::TermWindow {
                 };
 
                 if let Some(action) = self.input_map.lookup_mouse(event_trigger_type, mouse_mods) {
-                    self.perform_key_assignment(&pane, &action).ok();
+                    if let Some(action) = self.run_pre_assignment_hook(&pane, &action) {
+                        self.perform_key_assignment(&pane, &action).ok();
+                    }
                     return;
                 }
             }
@@ -1051,3 +1218,59 @@ fn mouse_press_to_tmb(press: &MousePress) -> TMB {
         MousePress::Middle => TMB::Middle,
     }
 }
+
+fn pointer_shape_to_cursor(shape: MousePointerShape) -> MouseCursor {
+    match shape {
+        MousePointerShape::Default => MouseCursor::Arrow,
+        MousePointerShape::Text => MouseCursor::Text,
+        MousePointerShape::Pointer => MouseCursor::Hand,
+        MousePointerShape::Crosshair => MouseCursor::Crosshair,
+        MousePointerShape::Wait => MouseCursor::Wait,
+        MousePointerShape::Progress => MouseCursor::Progress,
+        MousePointerShape::Help => MouseCursor::Help,
+        MousePointerShape::NotAllowed => MouseCursor::NotAllowed,
+        MousePointerShape::Move => MouseCursor::Move,
+        MousePointerShape::Grab => MouseCursor::Grab,
+        MousePointerShape::Grabbing => MouseCursor::Grabbing,
+        MousePointerShape::EwResize => MouseCursor::SizeLeftRight,
+        MousePointerShape::NsResize => MouseCursor::SizeUpDown,
+        MousePointerShape::NeswResize => MouseCursor::SizeNeSw,
+        MousePointerShape::NwseResize => MouseCursor::SizeNwSe,
+    }
+}
+
+/// Returns the logical column of the cell that is displayed at the
+/// visual `column` of `stable_row`; they differ on lines that have been
+/// reordered by bidi.  The paragraph direction is resolved in the same
+/// way as when rendering, from the rows of the logical line that are
+/// at or below `viewport_top`.
+fn logical_column_at(
+    pane: &Arc<dyn Pane>,
+    viewport_top: StableRowIndex,
+    stable_row: StableRowIndex,
+    column: usize,
+) -> usize {
+    let (_first_row, lines) = pane.get_lines(stable_row..stable_row + 1);
+    match lines.get(0) {
+        Some(line) if line.bidi_info().0 => {}
+        _ => return column,
+    }
+
+    let num_cols = pane.get_dimensions().cols;
+    for logical in pane.get_logical_lines(stable_row..stable_row + 1) {
+        if !logical.contains_y(stable_row) {
+            continue;
+        }
+        let first_row = logical.first_row.max(viewport_top).min(stable_row);
+        let skip = (first_row - logical.first_row) as usize;
+        let idx = (stable_row - first_row) as usize;
+        let physical = &logical.physical_lines[skip..];
+        let directions = Line::resolve_bidi_directions(physical.iter());
+        if let Some(direction) = directions[idx] {
+            return physical[idx]
+                .visual_column_map(direction, num_cols)
+                .logical_column(column);
+        }
+    }
+    column
+}
@@ -47,7 +47,7 @@ struct Recent {
     frecency: Frecency,
 }
 
-fn recent_file_name() -> PathBuf {
+pub fn recent_file_name() -> PathBuf {
     config::DATA_DIR.join("recent-commands.json")
 }
 
@@ -60,23 +60,23 @@ fn load_recents() -> anyhow::Result<Vec<Recent>> {
 }
 
 fn save_recent(command: &ExpandedCommand) -> anyhow::Result<()> {
-    let mut recents = load_recents().unwrap_or_else(|_| vec![]);
-    if let Some(recent_idx) = recents.iter().position(|r| r.brief == command.brief) {
-        let recent = recents.get_mut(recent_idx).unwrap();
-        recent.frecency.register_access();
-    } else {
-        let mut frecency = Frecency::new();
-        frecency.register_access();
-        recents.push(Recent {
-            brief: command.brief.to_string(),
-            frecency,
-        });
-    }
-
-    let json = serde_json::to_string(&recents)?;
-    let file_name = recent_file_name();
-    std::fs::write(&file_name, json)?;
-    Ok(())
+    config::statefile::update(&recent_file_name(), |data| {
+        let mut recents: Vec<Recent> = data
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_else(|| vec![]);
+        if let Some(recent_idx) = recents.iter().position(|r| r.brief == command.brief) {
+            let recent = recents.get_mut(recent_idx).unwrap();
+            recent.frecency.register_access();
+        } else {
+            let mut frecency = Frecency::new();
+            frecency.register_access();
+            recents.push(Recent {
+                brief: command.brief.to_string(),
+                frecency,
+            });
+        }
+        Ok(serde_json::to_vec(&recents)?)
+    })
 }
 
 #[derive(Debug, Clone, FromDynamic, ToDynamic)]
@@ -261,11 +261,8 @@ impl CommandPalette {
             .expect("to resolve command palette font");
         let metrics = RenderMetrics::with_font_metrics(&font.metrics());
 
-        let top_bar_height = if term_window.show_tab_bar && !term_window.config.tab_bar_at_bottom {
-            term_window.tab_bar_pixel_height().unwrap()
-        } else {
-            0.
-        };
+        let tab_bar = term_window.tab_bar_reserved_edges().unwrap();
+        let top_bar_height = tab_bar.top;
         let (padding_left, padding_top) = term_window.padding_left_top();
         let border = term_window.get_os_border();
         let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
@@ -525,7 +522,7 @@ impl CommandPalette {
                     pixel_cell: metrics.cell_size.width as f32,
                 },
                 bounds: euclid::rect(
-                    padding_left + x_adjust,
+                    tab_bar.left + padding_left + x_adjust,
                     top_pixel_y,
                     desired_pixel_width,
                     size.rows as f32 * term_window.render_metrics.cell_size.height as f32,
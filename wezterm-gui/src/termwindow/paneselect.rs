@@ -1,4 +1,5 @@
 use crate::termwindow::box_model::*;
+use crate::termwindow::broadcast::BroadcastInput;
 use crate::termwindow::modal::Modal;
 use crate::termwindow::render::corners::{
     BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
@@ -9,14 +10,18 @@ use crate::utilsprites::RenderMetrics;
 use crate::TermWindow;
 use config::keyassignment::{KeyAssignment, PaneSelectArguments, PaneSelectMode};
 use config::Dimension;
+use mux::pane::PaneId;
 use mux::Mux;
 use std::cell::{Ref, RefCell};
+use std::collections::HashSet;
 use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
 
 pub struct PaneSelector {
     element: RefCell<Option<Vec<ComputedElement>>>,
     labels: RefCell<Vec<String>>,
     selection: RefCell<String>,
+    /// The panes picked so far in ToggleBroadcastInput mode
+    picked: RefCell<HashSet<PaneId>>,
     alphabet: String,
     mode: PaneSelectMode,
     was_zoomed: bool,
@@ -43,6 +48,7 @@ impl PaneSelector {
             element: RefCell::new(None),
             labels: RefCell::new(vec![]),
             selection: RefCell::new(String::new()),
+            picked: RefCell::new(term_window.broadcast_input_selection()),
             alphabet,
             mode: args.mode,
             was_zoomed,
@@ -54,6 +60,7 @@ impl PaneSelector {
         term_window: &mut TermWindow,
         alphabet: &str,
         show_pane_ids: bool,
+        picked: &HashSet<PaneId>,
     ) -> anyhow::Result<(Vec<ComputedElement>, Vec<String>)> {
         let font = term_window
             .fonts
@@ -61,11 +68,8 @@ impl PaneSelector {
             .expect("to resolve pane selection font");
         let metrics = RenderMetrics::with_font_metrics(&font.metrics());
 
-        let top_bar_height = if term_window.show_tab_bar && !term_window.config.tab_bar_at_bottom {
-            term_window.tab_bar_pixel_height().unwrap()
-        } else {
-            0.
-        };
+        let tab_bar = term_window.tab_bar_reserved_edges().unwrap();
+        let top_bar_height = tab_bar.top;
         let (padding_left, padding_top) = term_window.padding_left_top();
         let border = term_window.get_os_border();
         let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
@@ -81,13 +85,22 @@ impl PaneSelector {
             } else {
                 labels[pos.index].clone()
             };
+            let (fg, bg) = if picked.contains(&pos.pane.pane_id()) {
+                (
+                    term_window.config.pane_select_bg_color,
+                    term_window.config.pane_select_fg_color,
+                )
+            } else {
+                (
+                    term_window.config.pane_select_fg_color,
+                    term_window.config.pane_select_bg_color,
+                )
+            };
             let element = Element::new(&font, ElementContent::Text(caption))
                 .colors(ElementColors {
-                    border: BorderColor::new(
-                        term_window.config.pane_select_bg_color.to_linear().into(),
-                    ),
-                    bg: term_window.config.pane_select_bg_color.to_linear().into(),
-                    text: term_window.config.pane_select_fg_color.to_linear().into(),
+                    border: BorderColor::new(bg.to_linear().into()),
+                    bg: bg.to_linear().into(),
+                    text: fg.to_linear().into(),
                 })
                 .padding(BoxDimension {
                     left: Dimension::Cells(0.25),
@@ -135,7 +148,8 @@ impl PaneSelector {
                         pixel_cell: metrics.cell_size.width as f32,
                     },
                     bounds: euclid::rect(
-                        padding_left
+                        tab_bar.left
+                            + padding_left
                             + ((pos.left as f32 + pane_dims.cols as f32 / 2.)
                                 * term_window.render_metrics.cell_size.width as f32),
                         top_pixel_y
@@ -213,15 +227,55 @@ impl PaneSelector {
                         .detach();
                     }
                 }
+                PaneSelectMode::ToggleBroadcastInput => {
+                    if let Some(pos) = panes.iter().find(|p| p.index == pane_index) {
+                        let pane_id = pos.pane.pane_id();
+                        let mut picked = self.picked.borrow_mut();
+                        if !picked.remove(&pane_id) {
+                            picked.insert(pane_id);
+                        }
+                    }
+                    // Keep the selector open so that more panes can be
+                    // picked; the selection is confirmed with Enter
+                    self.selection.borrow_mut().clear();
+                    term_window.invalidate_modal();
+                    return Ok(());
+                }
+                PaneSelectMode::LinkWithActive(options) => {
+                    if let (Some(pos), Some(active)) = (
+                        panes.iter().find(|p| p.index == pane_index),
+                        tab.get_active_pane(),
+                    ) {
+                        let pane_id = pos.pane.pane_id();
+                        if pane_id != active.pane_id() {
+                            if let Err(err) = mux.link_panes(active.pane_id(), pane_id, options) {
+                                log::error!("failed to link panes: {err:#}");
+                            }
+                        }
+                    }
+                }
             }
         }
 
+        self.close(term_window);
+        Ok(())
+    }
+
+    /// Makes the picked panes the broadcast input set
+    fn confirm_broadcast_input(&self, term_window: &mut TermWindow) {
+        let picked = self.picked.borrow().clone();
+        term_window.set_broadcast_input(Some(BroadcastInput::Panes(picked)));
+        self.close(term_window);
+    }
+
+    fn close(&self, term_window: &mut TermWindow) {
         if self.was_zoomed {
-            tab.set_zoomed(true);
+            if let Some(tab) = Mux::get().get_active_tab_for_window(term_window.mux_window_id) {
+                tab.set_zoomed(true);
+            }
         }
 
         term_window.cancel_modal();
-        Ok(())
     }
 }
 
@@ -250,16 +304,23 @@ impl Modal for PaneSelector {
             }
             (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
                 // Type to add to the selection
-                let mut selection = self.selection.borrow_mut();
-                selection.push(c);
+                let pane_index = {
+                    let mut selection = self.selection.borrow_mut();
+                    selection.push(c);
+                    self.labels.borrow().iter().position(|s| s == &*selection)
+                };
 
                 // and if we have a complete match, activate that pane
-                if let Some(pane_index) = self.labels.borrow().iter().position(|s| s == &*selection)
-                {
+                if let Some(pane_index) = pane_index {
                     self.perform_selection(pane_index, term_window)?;
                     return Ok(true);
                 }
             }
+            (KeyCode::Enter, KeyModifiers::NONE)
+                if self.mode == PaneSelectMode::ToggleBroadcastInput =>
+            {
+                self.confirm_broadcast_input(term_window);
+            }
             (KeyCode::Backspace, KeyModifiers::NONE) => {
                 // Backspace to edit the selection
                 let mut selection = self.selection.borrow_mut();
@@ -280,7 +341,12 @@ impl Modal for PaneSelector {
         term_window: &mut TermWindow,
     ) -> anyhow::Result<Ref<[ComputedElement]>> {
         if self.element.borrow().is_none() {
-            let (element, labels) = Self::compute(term_window, &self.alphabet, self.show_pane_ids)?;
+            let (element, labels) = Self::compute(
+                term_window,
+                &self.alphabet,
+                self.show_pane_ids,
+                &self.picked.borrow(),
+            )?;
             self.element.borrow_mut().replace(element);
             *self.labels.borrow_mut() = labels;
         }
@@ -0,0 +1,328 @@
+//! Presentation mode applies a bundle of adjustments that make a
+//! window easier to follow when screen sharing: a larger font, a more
+//! prominent cursor and an on-screen display of the keys that are
+//! being pressed.
+//! Only the delta is recorded so that toggling it off restores the
+//! prior state, even if the configuration was reloaded in between.
+use crate::termwindow::box_model::*;
+use crate::termwindow::resize::ScaleChange;
+use crate::termwindow::DimensionContext;
+use crate::utilsprites::RenderMetrics;
+use crate::TermWindow;
+use ::window::{KeyCode, ModifierToStringArgs, Modifiers, WindowOps};
+use config::keyassignment::KeyAssignment;
+use config::{ConfigHandle, Dimension};
+use mux::pane::{CachePolicy, Pane};
+use smol::Timer;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use wezterm_dynamic::Value;
+
+/// The maximum number of key presses that are displayed at once
+const MAX_KEY_PRESSES: usize = 5;
+
+/// The number of windows that are presenting with
+/// notifications suppressed
+static SUPPRESSING_NOTIFICATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns true if toast notifications should not be shown
+/// because a window is in presentation mode
+pub fn notifications_suppressed() -> bool {
+    SUPPRESSING_NOTIFICATIONS.load(Ordering::Relaxed) > 0
+}
+
+struct KeyPress {
+    label: String,
+    expires: Instant,
+    /// true if this is text that was typed, rather than
+    /// a key combination or a key assignment
+    typed: bool,
+}
+
+pub struct PresentationState {
+    /// The font scale multiplier that was applied when presentation
+    /// mode was enabled.  It is divided out again when presentation
+    /// mode is disabled so that any other font size adjustments that
+    /// were made in the meantime are preserved.
+    font_size_multiplier: f64,
+    suppressing_notifications: bool,
+    key_presses: VecDeque<KeyPress>,
+}
+
+impl PresentationState {
+    fn new(font_size_multiplier: f64, suppress_notifications: bool) -> Self {
+        if suppress_notifications {
+            SUPPRESSING_NOTIFICATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        Self {
+            font_size_multiplier,
+            suppressing_notifications: suppress_notifications,
+            key_presses: VecDeque::new(),
+        }
+    }
+
+    fn push_key_press(&mut self, label: String, typed: bool, expires: Instant) {
+        if typed {
+            if let Some(last) = self.key_presses.back_mut() {
+                if last.typed {
+                    last.label.push_str(&label);
+                    last.expires = expires;
+                    return;
+                }
+            }
+        }
+        self.key_presses.push_back(KeyPress {
+            label,
+            expires,
+            typed,
+        });
+        while self.key_presses.len() > MAX_KEY_PRESSES {
+            self.key_presses.pop_front();
+        }
+    }
+
+    fn expire_key_presses(&mut self, now: Instant) {
+        self.key_presses.retain(|press| press.expires > now);
+    }
+}
+
+impl Drop for PresentationState {
+    fn drop(&mut self) {
+        if self.suppressing_notifications {
+            SUPPRESSING_NOTIFICATIONS.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Applies the cursor adjustments from the presentation_mode
+/// configuration to config
+pub fn apply_presentation_mode(config: &ConfigHandle) -> ConfigHandle {
+    let presentation = config.presentation_mode.clone();
+    config.with_modifications(|config| {
+        if let Some(thickness) = presentation.cursor_thickness {
+            config.cursor_thickness.replace(thickness);
+        }
+        if let Some(color) = presentation.cursor_color {
+            config.resolved_palette.cursor_bg.replace(color);
+            config.resolved_palette.cursor_border.replace(color);
+        }
+    })
+}
+
+impl TermWindow {
+    pub fn toggle_presentation_mode(&mut self) {
+        match self.presentation.take() {
+            Some(state) => {
+                self.pending_scale_changes
+                    .push_back(ScaleChange::Relative(1.0 / state.font_size_multiplier));
+            }
+            None => {
+                let config = &self.config.presentation_mode;
+                let multiplier = if config.font_size_multiplier > 0. {
+                    config.font_size_multiplier
+                } else {
+                    1.0
+                };
+                self.presentation.replace(PresentationState::new(
+                    multiplier,
+                    config.suppress_notifications,
+                ));
+                self.pending_scale_changes
+                    .push_back(ScaleChange::Relative(multiplier));
+            }
+        }
+
+        // Re-derive the configuration with or without the
+        // presentation mode adjustments
+        self.config_was_reloaded();
+        self.apply_pending_scale_changes();
+    }
+
+    /// Returns true if keys sent to pane should not be displayed,
+    /// because they might be part of a password
+    fn key_press_is_private(&self, pane: &Arc<dyn Pane>) -> bool {
+        if let Value::Object(obj) = pane.get_metadata() {
+            if let Some(Value::Bool(true)) = obj.get(&Value::String("password_input".to_string())) {
+                return true;
+            }
+        }
+
+        match pane.get_foreground_process_name(CachePolicy::AllowStale) {
+            Some(name) => {
+                let name = std::path::Path::new(&name)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or(name);
+                self.config
+                    .presentation_mode
+                    .key_press_process_blocklist
+                    .iter()
+                    .any(|blocked| *blocked == name)
+            }
+            None => false,
+        }
+    }
+
+    /// Records a key press for display while presentation mode is
+    /// active.  action is the key assignment that was triggered by
+    /// the key, if any; otherwise the key was sent to pane.
+    pub fn present_key_press(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+        key: &KeyCode,
+        mods: Modifiers,
+        action: Option<&KeyAssignment>,
+    ) {
+        if self.presentation.is_none()
+            || !self.config.presentation_mode.show_key_presses
+            || key.is_modifier()
+        {
+            return;
+        }
+        if action.is_none() && self.key_press_is_private(pane) {
+            return;
+        }
+
+        let mods = mods.remove_positional_mods();
+        let (label, typed) = match (action, key) {
+            (None, KeyCode::Char(c))
+                if !c.is_control() && (mods == Modifiers::NONE || mods == Modifiers::SHIFT) =>
+            {
+                (c.to_string(), true)
+            }
+            _ => {
+                let ui_key_cap_rendering = self.config.ui_key_cap_rendering;
+                let separator = if ui_key_cap_rendering == ::window::UIKeyCapRendering::AppleSymbols
+                {
+                    " "
+                } else {
+                    "-"
+                };
+                let mut label = mods.to_string_with_separator(ModifierToStringArgs {
+                    separator,
+                    want_none: false,
+                    ui_key_cap_rendering: Some(ui_key_cap_rendering),
+                });
+                if !label.is_empty() {
+                    label.push_str(separator);
+                }
+                label.push_str(&crate::inputmap::ui_key(key, ui_key_cap_rendering));
+                if let Some(cmd) =
+                    action.and_then(crate::commands::derive_command_from_key_assignment)
+                {
+                    label = format!("{label}  {}", cmd.brief);
+                }
+                (label, false)
+            }
+        };
+
+        let expires = Instant::now()
+            + Duration::from_millis(self.config.presentation_mode.key_press_duration_ms);
+        if let Some(state) = self.presentation.as_mut() {
+            state.push_key_press(label, typed, expires);
+        }
+
+        // Arrange to repaint once the key press has expired
+        if let Some(window) = self.window.clone() {
+            window.invalidate();
+            promise::spawn::spawn(async move {
+                Timer::at(expires).await;
+                window.invalidate();
+            })
+            .detach();
+        }
+    }
+
+    /// Renders the recent key presses in the bottom right
+    /// corner of the window
+    pub fn paint_presented_keys(&mut self) -> anyhow::Result<()> {
+        let labels: Vec<String> = match self.presentation.as_mut() {
+            Some(state) => {
+                state.expire_key_presses(Instant::now());
+                state
+                    .key_presses
+                    .iter()
+                    .map(|press| press.label.clone())
+                    .collect()
+            }
+            None => return Ok(()),
+        };
+        if labels.is_empty() {
+            return Ok(());
+        }
+
+        let font = self.fonts.command_palette_font()?;
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+        let colors = ElementColors {
+            border: BorderColor::new(self.config.command_palette_bg_color.to_linear()),
+            bg: self.config.command_palette_bg_color.to_linear().into(),
+            text: self.config.command_palette_fg_color.to_linear().into(),
+        };
+
+        let children = labels
+            .into_iter()
+            .map(|label| {
+                Element::new(&font, ElementContent::Text(label))
+                    .display(DisplayType::Block)
+                    .colors(colors.clone())
+                    .padding(BoxDimension::new(Dimension::Cells(0.25)))
+                    .margin(BoxDimension {
+                        left: Dimension::Cells(0.),
+                        right: Dimension::Cells(0.),
+                        top: Dimension::Cells(0.25),
+                        bottom: Dimension::Cells(0.),
+                    })
+                    .border(BoxDimension::new(Dimension::Pixels(1.)))
+            })
+            .collect();
+        let element = Element::new(&font, ElementContent::Children(children));
+
+        let dimensions = self.dimensions;
+        let mut computed = self.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_height as f32,
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_width as f32,
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(
+                    0.,
+                    0.,
+                    dimensions.pixel_width as f32,
+                    dimensions.pixel_height as f32,
+                ),
+                metrics: &metrics,
+                gl_state: self.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        let border = self.get_os_border();
+        let tab_bar = self.tab_bar_reserved_edges()?;
+        let margin = metrics.cell_size.width as f32;
+        computed.translate(euclid::vec2(
+            (dimensions.pixel_width as f32
+                - (computed.bounds.width() + margin + border.right.get() as f32 + tab_bar.right))
+                .max(0.),
+            (dimensions.pixel_height as f32
+                - (computed.bounds.height()
+                    + margin
+                    + border.bottom.get() as f32
+                    + tab_bar.bottom))
+                .max(0.),
+        ));
+
+        let gl_state = self.render_state.as_ref().unwrap();
+        self.render_element(&computed, gl_state, None)?;
+
+        Ok(())
+    }
+}
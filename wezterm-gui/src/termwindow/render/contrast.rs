@@ -0,0 +1,151 @@
+//! Enforces `minimum_contrast_ratio` between the foreground and
+//! background colors of text.
+use std::collections::HashMap;
+use window::color::LinearRgba;
+
+/// The cache is cleared when it grows beyond this many color pairs;
+/// truecolor output can produce a great many distinct pairs
+const MAX_CACHED_PAIRS: usize = 4096;
+
+type PairKey = [u32; 8];
+
+/// Remembers the adjusted foreground color for each (fg, bg) pair
+/// so that the adjustment is computed once per pair
+#[derive(Default)]
+pub struct ContrastCache {
+    ratio: f32,
+    adjusted: HashMap<PairKey, LinearRgba>,
+}
+
+impl ContrastCache {
+    /// Returns `fg`, adjusted if necessary so that it has a contrast
+    /// ratio of at least `ratio` against `bg`
+    pub fn adjust(&mut self, fg: LinearRgba, bg: LinearRgba, ratio: f32) -> LinearRgba {
+        if ratio != self.ratio {
+            self.adjusted.clear();
+            self.ratio = ratio;
+        }
+        let key = pair_key(fg, bg);
+        if let Some(adjusted) = self.adjusted.get(&key) {
+            return *adjusted;
+        }
+        if self.adjusted.len() >= MAX_CACHED_PAIRS {
+            self.adjusted.clear();
+        }
+        let adjusted = ensure_contrast(fg, bg, ratio);
+        self.adjusted.insert(key, adjusted);
+        adjusted
+    }
+}
+
+fn pair_key(fg: LinearRgba, bg: LinearRgba) -> PairKey {
+    let (fr, fg_, fb, fa) = fg.tuple();
+    let (br, bg_, bb, ba) = bg.tuple();
+    [
+        fr.to_bits(),
+        fg_.to_bits(),
+        fb.to_bits(),
+        fa.to_bits(),
+        br.to_bits(),
+        bg_.to_bits(),
+        bb.to_bits(),
+        ba.to_bits(),
+    ]
+}
+
+/// The WCAG relative luminance of a color; the components of
+/// `LinearRgba` are already linear, so no transfer function is needed
+fn luminance(color: LinearRgba) -> f32 {
+    let (r, g, b, _) = color.tuple();
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// The WCAG contrast ratio between two luminance values
+fn contrast_ratio(a: f32, b: f32) -> f32 {
+    let (lighter, darker) = if a > b { (a, b) } else { (b, a) };
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+/// Returns `fg`, moved toward white or black just far enough to have
+/// a contrast ratio of at least `ratio` against `bg`.
+/// The direction that keeps the foreground on the same side of the
+/// background is preferred; if the ratio cannot be met in either
+/// direction, the foreground becomes whichever of white or black
+/// contrasts the most.
+pub fn ensure_contrast(fg: LinearRgba, bg: LinearRgba, ratio: f32) -> LinearRgba {
+    let fg_lum = luminance(fg);
+    let bg_lum = luminance(bg);
+    if contrast_ratio(fg_lum, bg_lum) >= ratio {
+        return fg;
+    }
+
+    // Luminance is linear in the linear color components, so the
+    // luminance needed to meet the ratio can be computed directly
+    let lighter = ratio * (bg_lum + 0.05) - 0.05;
+    let darker = (bg_lum + 0.05) / ratio - 0.05;
+    let can_lighten = lighter <= 1.0;
+    let can_darken = darker >= 0.0;
+    let (r, g, b, a) = fg.tuple();
+
+    // The target is white or black; its luminance is the same as
+    // the value of each of its components
+    let (target, wanted_lum) = if can_lighten && (fg_lum >= bg_lum || !can_darken) {
+        (1.0, lighter)
+    } else if can_darken {
+        (0.0, darker)
+    } else {
+        let target = if contrast_ratio(1.0, bg_lum) >= contrast_ratio(0.0, bg_lum) {
+            1.0
+        } else {
+            0.0
+        };
+        return LinearRgba::with_components(target, target, target, a);
+    };
+
+    let t = ((wanted_lum - fg_lum) / (target - fg_lum)).clamp(0.0, 1.0);
+    LinearRgba::with_components(
+        r + (target - r) * t,
+        g + (target - g) * t,
+        b + (target - b) * t,
+        a,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ratio_of(fg: LinearRgba, bg: LinearRgba) -> f32 {
+        contrast_ratio(luminance(fg), luminance(bg))
+    }
+
+    #[test]
+    fn minimum_contrast() {
+        let black = LinearRgba::with_components(0., 0., 0., 1.);
+        let dark_grey = LinearRgba::with_components(0.02, 0.02, 0.02, 1.);
+        let light_grey = LinearRgba::with_components(0.8, 0.8, 0.8, 1.);
+        let white = LinearRgba::with_components(1., 1., 1., 1.);
+
+        // Already legible text is left alone
+        assert_eq!(ensure_contrast(white, black, 3.0), white);
+
+        // Dark grey on black is lightened just enough
+        let adjusted = ensure_contrast(dark_grey, black, 3.0);
+        assert!((ratio_of(adjusted, black) - 3.0).abs() < 0.001);
+
+        // Light grey on white is darkened, as there is no room to lighten it
+        let adjusted = ensure_contrast(light_grey, white, 4.5);
+        assert!(luminance(adjusted) < luminance(light_grey));
+        assert!((ratio_of(adjusted, white) - 4.5).abs() < 0.001);
+
+        // An impossible ratio picks the better of white and black
+        assert_eq!(ensure_contrast(dark_grey, black, 50.0), white);
+
+        let mut cache = ContrastCache::default();
+        let adjusted = cache.adjust(dark_grey, black, 3.0);
+        assert_eq!(cache.adjust(dark_grey, black, 3.0), adjusted);
+        assert_eq!(cache.adjusted.len(), 1);
+        cache.adjust(dark_grey, black, 4.5);
+        assert_eq!(cache.adjusted.len(), 1);
+    }
+}
@@ -1,16 +1,22 @@
 use crate::customglyph::*;
 use crate::tabbar::{TabBarItem, TabEntry};
+use crate::termwindow::animation::AnimationSources;
 use crate::termwindow::box_model::*;
 use crate::termwindow::render::corners::*;
 
 use crate::termwindow::render::window_buttons::window_button_element;
 use crate::termwindow::{UIItem, UIItemType};
 use crate::utilsprites::RenderMetrics;
-use config::{Dimension, DimensionContext, TabBarColors};
+use config::{Dimension, DimensionContext, TabBarColors, TabBarOverflow, TabBarPosition};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
+use termwiz::escape::osc::Progress;
 use wezterm_font::LoadedFont;
 use wezterm_term::color::{ColorAttribute, ColorPalette};
-use window::{IntegratedTitleButtonAlignment, IntegratedTitleButtonStyle};
+use window::{IntegratedTitleButtonAlignment, IntegratedTitleButtonStyle, RectF};
+
+/// How often to redraw an indeterminate progress bar while it pulses
+const PROGRESS_PULSE_INTERVAL_MS: u64 = 50;
 
 const X_BUTTON: &[Poly] = &[
     Poly {
@@ -50,11 +56,100 @@ const PLUS_BUTTON: &[Poly] = &[
     },
 ];
 
+/// The computed fancy tab bar, along with the dpi that it was
+/// laid out for.
+pub struct FancyTabBar {
+    pub dpi: usize,
+    pub element: ComputedElement,
+}
+
+impl FancyTabBar {
+    /// Returns true if the tab bar was laid out for a different dpi.
+    /// When a window is dragged between monitors with different
+    /// scaling, the tab bar must be laid out again before it is
+    /// painted, otherwise the hit rects would no longer match the
+    /// glyphs that were shaped for the new dpi.
+    pub fn is_stale(&self, dpi: usize) -> bool {
+        self.dpi != dpi
+    }
+
+    pub fn ui_items(&self) -> Vec<UIItem> {
+        self.element.ui_items()
+    }
+}
+
+/// Returns the width and height contexts against which all of the
+/// tab bar elements, including the integrated window buttons, are
+/// resolved.
+fn tab_bar_dimension_contexts(
+    dpi: usize,
+    pixel_cell: (f32, f32),
+    pixel_max: (f32, f32),
+) -> (DimensionContext, DimensionContext) {
+    (
+        DimensionContext {
+            dpi: dpi as f32,
+            pixel_max: pixel_max.0,
+            pixel_cell: pixel_cell.0,
+        },
+        DimensionContext {
+            dpi: dpi as f32,
+            pixel_max: pixel_max.1,
+            pixel_cell: pixel_cell.1,
+        },
+    )
+}
+
 impl crate::TermWindow {
     pub fn invalidate_fancy_tab_bar(&mut self) {
         self.fancy_tab_bar.take();
     }
 
+    /// Returns the fancy tab bar, laying it out again if it hasn't
+    /// been computed yet or if the dpi has changed since it was
+    pub fn fancy_tab_bar(&mut self) -> anyhow::Result<&FancyTabBar> {
+        let dpi = self.fonts.get_dpi();
+        if self
+            .fancy_tab_bar
+            .as_ref()
+            .map_or(true, |bar| bar.is_stale(dpi))
+        {
+            let palette = self.palette().clone();
+            let element = self.build_fancy_tab_bar(&palette)?;
+            self.fancy_tab_bar.replace(FancyTabBar { dpi, element });
+        }
+        Ok(self.fancy_tab_bar.as_ref().unwrap())
+    }
+
+    /// Builds the layout context for the tab bar.
+    /// The dpi is taken from the font configuration rather than the
+    /// window dimensions so that the layout always agrees with the
+    /// title font that was shaped for it, even while a scaling change
+    /// is part way through being applied.
+    fn tab_bar_layout_context<'a>(
+        &'a self,
+        metrics: &'a RenderMetrics,
+        pixel_max: (f32, f32),
+        bounds: RectF,
+    ) -> LayoutContext<'a> {
+        let (width, height) = tab_bar_dimension_contexts(
+            self.fonts.get_dpi(),
+            (
+                metrics.cell_size.width as f32,
+                metrics.cell_size.height as f32,
+            ),
+            pixel_max,
+        );
+        LayoutContext {
+            width,
+            height,
+            bounds,
+            metrics,
+            gl_state: self.render_state.as_ref().unwrap(),
+            zindex: 10,
+        }
+    }
+
     pub fn build_fancy_tab_bar(&self, palette: &ColorPalette) -> anyhow::Result<ComputedElement> {
         let tab_bar_height = self.tab_bar_pixel_height()?;
         let font = self.fonts.title_font()?;
@@ -165,6 +260,32 @@ impl crate::TermWindow {
                     bg: new_tab_hover.bg_color.to_linear().into(),
                     text: new_tab_hover.fg_color.to_linear().into(),
                 })),
+                TabBarItem::ScrollLeft | TabBarItem::ScrollRight => element
+                    .vertical_align(VerticalAlign::Middle)
+                    .item_type(UIItemType::TabBar(item.item.clone()))
+                    .margin(BoxDimension {
+                        left: Dimension::Cells(0.),
+                        right: Dimension::Cells(0.),
+                        top: Dimension::Cells(0.2),
+                        bottom: Dimension::Cells(0.),
+                    })
+                    .padding(BoxDimension {
+                        left: Dimension::Cells(0.25),
+                        right: Dimension::Cells(0.25),
+                        top: Dimension::Cells(0.2),
+                        bottom: Dimension::Cells(0.25),
+                    })
+                    .border(BoxDimension::new(Dimension::Pixels(1.)))
+                    .colors(ElementColors {
+                        border: BorderColor::default(),
+                        bg: new_tab.bg_color.to_linear().into(),
+                        text: new_tab.fg_color.to_linear().into(),
+                    })
+                    .hover_colors(Some(ElementColors {
+                        border: BorderColor::default(),
+                        bg: new_tab_hover.bg_color.to_linear().into(),
+                        text: new_tab_hover.fg_color.to_linear().into(),
+                    })),
                 TabBarItem::Tab { active, .. } if active => element
                     .vertical_align(VerticalAlign::Bottom)
                     .item_type(UIItemType::TabBar(item.item.clone()))
@@ -303,9 +424,32 @@ impl crate::TermWindow {
                 _ => 0.,
             })
             .sum();
-        let max_tab_width = ((self.dimensions.pixel_width as f32 / num_tabs)
-            - (1.5 * metrics.cell_size.width as f32))
-            .max(0.);
+        let vertical = self.tab_bar_is_vertical();
+        // When the tab bar is vertical, each tab fills the width of the strip
+        let vertical_tab_width = Dimension::Pixels(
+            (self.vertical_tab_bar_pixel_width() - metrics.cell_size.width as f32).max(0.),
+        );
+        let max_tab_width = match self.config.tab_bar_overflow {
+            _ if vertical => vertical_tab_width,
+            TabBarOverflow::Shrink => Dimension::Pixels(
+                ((self.dimensions.pixel_width as f32 / num_tabs)
+                    - (1.5 * metrics.cell_size.width as f32))
+                    .max(0.),
+            ),
+            // The tab bar state has already decided which tabs fit,
+            // based on these limits
+            TabBarOverflow::Scroll | TabBarOverflow::Wrap => {
+                Dimension::Cells(self.config.tab_max_width as f32 + 1.)
+            }
+        };
+        let min_tab_width = match self.config.tab_bar_overflow {
+            _ if vertical => Some(vertical_tab_width),
+            TabBarOverflow::Shrink => None,
+            TabBarOverflow::Scroll | TabBarOverflow::Wrap => Some(Dimension::Cells(
+                self.config.tab_min_width.min(self.config.tab_max_width) as f32 + 1.,
+            )),
+        };
+        let mut left_rows = vec![];
 
         // Reserve space for the native titlebar buttons
         if self
@@ -326,6 +470,7 @@ impl crate::TermWindow {
         }
 
         for item in items {
+            let num_left_eles = left_eles.len();
             match item.item {
                 TabBarItem::LeftStatus => left_status.push(item_to_elem(item)),
                 TabBarItem::None | TabBarItem::RightStatus => right_eles.push(item_to_elem(item)),
@@ -340,7 +485,8 @@ impl crate::TermWindow {
                 }
                 TabBarItem::Tab { tab_idx, active } => {
                     let mut elem = item_to_elem(item);
-                    elem.max_width = Some(Dimension::Pixels(max_tab_width));
+                    elem.max_width = Some(max_tab_width);
+                    elem.min_width = min_tab_width;
                     elem.content = match elem.content {
                         ElementContent::Text(_) => unreachable!(),
                         ElementContent::Poly { .. } => unreachable!(),
@@ -355,8 +501,41 @@ impl crate::TermWindow {
                 }
                 _ => left_eles.push(item_to_elem(item)),
             }
+            if left_eles.len() > num_left_eles {
+                left_rows.push(item.row);
+            }
+        }
+
+        if vertical {
+            let mut rows = left_status;
+            rows.append(&mut left_eles);
+            rows.append(&mut right_eles);
+            return self.layout_vertical_fancy_tab_bar(&font, &metrics, rows, bar_colors);
         }
 
+        // When the tabs wrap, each row of tabs is placed into its own
+        // block so that the rows stack vertically
+        let left_eles = if self.tab_bar.rows() > 1 {
+            let row_height = tab_bar_height / self.tab_bar.rows() as f32;
+            let mut rows: Vec<Vec<Element>> = vec![];
+            for (row, elem) in left_rows.into_iter().zip(left_eles) {
+                if rows.len() <= row {
+                    rows.resize_with(row + 1, Vec::new);
+                }
+                rows[row].push(elem);
+            }
+            rows.into_iter()
+                .map(|kids| {
+                    Element::new(&font, ElementContent::Children(kids))
+                        .display(DisplayType::Block)
+                        .colors(bar_colors.clone())
+                        .min_height(Some(Dimension::Pixels(row_height)))
+                })
+                .collect()
+        } else {
+            left_eles
+        };
+
         let mut children = vec![];
 
         if !left_status.is_empty() {
@@ -421,33 +600,25 @@ impl crate::TermWindow {
         let border = self.get_os_border();
 
         let mut computed = self.compute_element(
-            &LayoutContext {
-                height: DimensionContext {
-                    dpi: self.dimensions.dpi as f32,
-                    pixel_max: self.dimensions.pixel_height as f32,
-                    pixel_cell: metrics.cell_size.height as f32,
-                },
-                width: DimensionContext {
-                    dpi: self.dimensions.dpi as f32,
-                    pixel_max: self.dimensions.pixel_width as f32,
-                    pixel_cell: metrics.cell_size.width as f32,
-                },
-                bounds: euclid::rect(
+            &self.tab_bar_layout_context(
+                &metrics,
+                (
+                    self.dimensions.pixel_width as f32,
+                    self.dimensions.pixel_height as f32,
+                ),
+                euclid::rect(
                     border.left.get() as f32,
                     0.,
                     self.dimensions.pixel_width as f32 - (border.left + border.right).get() as f32,
                     tab_bar_height,
                 ),
-                metrics: &metrics,
-                gl_state: self.render_state.as_ref().unwrap(),
-                zindex: 10,
-            },
+            ),
             &tabs,
         )?;
 
         computed.translate(euclid::vec2(
             0.,
-            if self.config.tab_bar_at_bottom {
+            if self.config.effective_tab_bar_position() == TabBarPosition::Bottom {
                 self.dimensions.pixel_height as f32
                     - (computed.bounds.height() + border.bottom.get() as f32)
             } else {
@@ -458,17 +629,134 @@ impl crate::TermWindow {
         Ok(computed)
     }
 
+    /// Stacks the tab bar items so that each occupies its own row
+    /// in a strip along the left or right edge of the window
+    fn layout_vertical_fancy_tab_bar(
+        &self,
+        font: &Rc<LoadedFont>,
+        metrics: &RenderMetrics,
+        items: Vec<Element>,
+        bar_colors: ElementColors,
+    ) -> anyhow::Result<ComputedElement> {
+        let bounds = self.tab_bar_bounds()?;
+
+        let rows = items
+            .into_iter()
+            .map(|item| {
+                Element::new(font, ElementContent::Children(vec![item]))
+                    .display(DisplayType::Block)
+                    .colors(bar_colors.clone())
+                    .padding(BoxDimension {
+                        left: Dimension::Cells(0.5),
+                        right: Dimension::Cells(0.),
+                        top: Dimension::Cells(0.),
+                        bottom: Dimension::Cells(0.),
+                    })
+                    .min_width(Some(Dimension::Pixels(bounds.width())))
+            })
+            .collect();
+
+        let tabs = Element::new(font, ElementContent::Children(rows))
+            .display(DisplayType::Block)
+            .item_type(UIItemType::TabBar(TabBarItem::None))
+            .min_width(Some(Dimension::Pixels(bounds.width())))
+            .min_height(Some(Dimension::Pixels(bounds.height())))
+            .colors(bar_colors);
+
+        let mut computed = self.compute_element(
+            &self.tab_bar_layout_context(
+                metrics,
+                (bounds.width(), bounds.height()),
+                euclid::rect(0., 0., bounds.width(), bounds.height()),
+            ),
+            &tabs,
+        )?;
+
+        computed.translate(euclid::vec2(bounds.min_x(), bounds.min_y()));
+
+        Ok(computed)
+    }
+
     pub fn paint_fancy_tab_bar(&self) -> anyhow::Result<Vec<UIItem>> {
-        let computed = self.fancy_tab_bar.as_ref().ok_or_else(|| {
+        let bar = self.fancy_tab_bar.as_ref().ok_or_else(|| {
             anyhow::anyhow!("paint_fancy_tab_bar called but fancy_tab_bar is None")
         })?;
-        let ui_items = computed.ui_items();
+        let computed = &bar.element;
+        let ui_items = bar.ui_items();
 
         let gl_state = self.render_state.as_ref().unwrap();
-        self.render_element(&computed, gl_state, None)?;
+        self.render_element(computed, gl_state, None)?;
+        self.paint_tab_progress(&ui_items, computed.zindex)?;
 
         Ok(ui_items)
     }
+
+    /// Draws a thin bar along the bottom edge of each tab that
+    /// has reported progress via OSC 9;4
+    fn paint_tab_progress(&self, ui_items: &[UIItem], zindex: i8) -> anyhow::Result<()> {
+        if self.tab_progress.iter().all(|p| *p == Progress::None) {
+            return Ok(());
+        }
+
+        let gl_state = self.render_state.as_ref().unwrap();
+        let layer = gl_state.layer_for_zindex(zindex)?;
+        let mut layers = layer.quad_allocator();
+
+        let default_palette;
+        let palette = match self.palette.as_ref() {
+            Some(palette) => palette,
+            None => {
+                default_palette = ColorPalette::default();
+                &default_palette
+            }
+        };
+        let bar_height = (self.render_metrics.underline_height * 2).max(2) as f32;
+
+        for item in ui_items {
+            let tab_idx = match item.item_type {
+                UIItemType::TabBar(TabBarItem::Tab { tab_idx, .. }) => tab_idx,
+                _ => continue,
+            };
+            let (color_idx, percent) = match self.tab_progress.get(tab_idx) {
+                None | Some(Progress::None) => continue,
+                Some(Progress::SetPercentage(pct)) => (2, *pct),
+                // An error without a percentage fills the whole tab
+                Some(Progress::SetError(0)) => (1, 100),
+                Some(Progress::SetError(pct)) => (1, *pct),
+                Some(Progress::Indeterminate) => (2, 100),
+                Some(Progress::Paused(pct)) => (3, *pct),
+            };
+
+            let mut color = palette.colors.0[color_idx].to_linear();
+            if self.tab_progress[tab_idx] == Progress::Indeterminate {
+                // Pulse the bar over the course of a second
+                let phase = self.created.elapsed().as_secs_f32() * std::f32::consts::TAU;
+                color = color.mul_alpha(0.6 + 0.4 * phase.sin());
+                self.update_next_frame_time(
+                    AnimationSources::PROGRESS,
+                    Some(Instant::now() + Duration::from_millis(PROGRESS_PULSE_INTERVAL_MS)),
+                );
+            }
+
+            let width = item.width as f32 * percent as f32 / 100.;
+            if width <= 0. {
+                continue;
+            }
+            self.filled_rectangle(
+                &mut layers,
+                2,
+                euclid::rect(
+                    item.x as f32,
+                    (item.y + item.height) as f32 - bar_height,
+                    width,
+                    bar_height,
+                ),
+                color,
+            )?;
+        }
+
+        Ok(())
+    }
 }
 
 fn make_x_button(
@@ -530,3 +818,97 @@ fn make_x_button(
         bottom: Dimension::Cells(0.),
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use window::screen::{ScreenInfo, Screens};
+
+    /// Lays out a new tab button in the way that the tab bar does,
+    /// returning the bar and the rect that would be drawn
+    fn layout_button(dpi: usize) -> (FancyTabBar, RectF) {
+        let (width, height) = tab_bar_dimension_contexts(dpi, (8., 16.), (800., 600.));
+        let rect = euclid::rect(
+            Dimension::Cells(0.5).evaluate_as_pixels(width),
+            0.,
+            Dimension::Points(24.).evaluate_as_pixels(width),
+            Dimension::Points(12.).evaluate_as_pixels(height),
+        );
+        let element = ComputedElement {
+            item_type: Some(UIItemType::TabBar(TabBarItem::NewTabButton)),
+            zindex: 10,
+            bounds: rect,
+            border_rect: rect,
+            border: PixelDimension::default(),
+            border_corners: None,
+            colors: ElementColors::default(),
+            hover_colors: None,
+            padding: rect,
+            content_rect: rect,
+            baseline: 0.,
+            content: ComputedElementContent::Children(vec![]),
+        };
+        (FancyTabBar { dpi, element }, rect)
+    }
+
+    fn hit_rect(bar: &FancyTabBar) -> RectF {
+        let item = &bar.ui_items()[0];
+        euclid::rect(
+            item.x as f32,
+            item.y as f32,
+            item.width as f32,
+            item.height as f32,
+        )
+    }
+
+    fn screen(name: &str, x: isize, dpi: f64) -> ScreenInfo {
+        ScreenInfo {
+            name: name.to_string(),
+            rect: euclid::rect(x, 0, 1000, 1000),
+            scale: dpi / 96.,
+            max_fps: None,
+            effective_dpi: Some(dpi),
+        }
+    }
+
+    #[test]
+    fn dpi_change_relayout() {
+        let left = screen("left", 0, 96.);
+        let right = screen("right", 1000, 192.);
+        let screens = Screens {
+            main: left.clone(),
+            active: left.clone(),
+            by_name: [left, right]
+                .into_iter()
+                .map(|info| (info.name.clone(), info))
+                .collect(),
+            virtual_rect: euclid::rect(0, 0, 2000, 1000),
+        };
+        let majority_dpi = |x: isize| {
+            screens
+                .screen_for_rect(&euclid::rect(x, 100, 400, 300))
+                .and_then(|screen| screen.effective_dpi)
+                .unwrap() as usize
+        };
+
+        // Drag a window from the left monitor to the right one,
+        // laying out the bar again whenever it goes stale, in the
+        // same way that paint_tab_bar does
+        let (mut bar, _) = layout_button(majority_dpi(0));
+        let mut relayouts = vec![];
+        for x in (0..=1600).step_by(50) {
+            let dpi = majority_dpi(x);
+            if bar.is_stale(dpi) {
+                relayouts.push(x);
+                bar = layout_button(dpi).0;
+            }
+            // What gets clicked must match what would be drawn for
+            // the screen that holds most of the window right now,
+            // not when the drag ends
+            let (_, drawn) = layout_button(dpi);
+            assert_eq!(hit_rect(&bar), drawn, "at x={x}");
+        }
+        assert_eq!(relayouts, vec![850]);
+        assert_eq!(hit_rect(&bar).size, euclid::size2(64., 32.));
+    }
+}
@@ -0,0 +1,91 @@
+//! Paints floating panes above the split tree.  Each floating pane is
+//! painted into its own layer, in stacking order, so that it covers the
+//! panes of the tab and any floating panes below it.  The layers sit
+//! between the pane decorations and the fancy tab bar.
+//! A floating pane is framed by a border and a drop shadow rather than
+//! by a title bar.
+use crate::quad::{QuadTrait, TripleLayerQuadAllocator};
+use mux::tab::PositionedPane;
+use window::color::LinearRgba;
+
+/// The layer of the bottom-most floating pane
+const FLOATING_ZINDEX: i8 = 2;
+/// The fancy tab bar is painted at zindex 10
+const MAX_FLOATING_ZINDEX: i8 = 9;
+
+/// Returns the zindex of the layer that holds the box model elements,
+/// such as the timestamps gutter, of the pane at `pos`
+pub fn pane_element_zindex(pos: &PositionedPane) -> i8 {
+    if pos.is_floating {
+        floating_zindex(pos)
+    } else {
+        1
+    }
+}
+
+/// Returns the zindex of the layer that the floating pane at `pos`
+/// is painted into; the ones above the highest layer share it
+pub fn floating_zindex(pos: &PositionedPane) -> i8 {
+    let above = pos
+        .index
+        .min((MAX_FLOATING_ZINDEX - FLOATING_ZINDEX) as usize) as i8;
+    FLOATING_ZINDEX + above
+}
+
+impl crate::TermWindow {
+    /// Paints the drop shadow, border and backdrop of the floating
+    /// pane at `pos`.  The backdrop is opaque, so that the panes
+    /// below don't show through a transparent window background.
+    pub fn paint_floating_pane_frame(
+        &mut self,
+        layers: &mut TripleLayerQuadAllocator,
+        pos: &PositionedPane,
+    ) -> anyhow::Result<()> {
+        let palette = pos.pane.palette();
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let thickness = self.render_metrics.underline_height as f32;
+
+        let border = self.get_os_border();
+        let tab_bar = self.tab_bar_reserved_edges()?;
+        let (padding_left, padding_top) = self.padding_left_top();
+
+        // The frame also surrounds the timestamps gutter
+        let gutter = pos.pane.timestamp_gutter_cols();
+        let left = tab_bar.left
+            + padding_left
+            + border.left.get() as f32
+            + (pos.left.saturating_sub(gutter) as f32 * cell_width);
+        let top =
+            tab_bar.top + padding_top + border.top.get() as f32 + (pos.top as f32 * cell_height);
+
+        // Leave half a cell of space between the frame and the text,
+        // as there is between the text and a split
+        let backdrop = euclid::rect(
+            left - (cell_width / 2.),
+            top - (cell_height / 2.),
+            ((pos.width + gutter) as f32 * cell_width) + cell_width,
+            (pos.height as f32 * cell_height) + cell_height,
+        );
+        let frame = backdrop.inflate(thickness, thickness);
+        let shadow = frame.translate(euclid::vec2(cell_width / 2., cell_height / 2.));
+
+        self.filled_rectangle(
+            layers,
+            0,
+            shadow,
+            LinearRgba::with_components(0., 0., 0., 0.4),
+        )?;
+        {
+            let mut quad = self.filled_rectangle(layers, 0, frame, palette.split.to_linear())?;
+            quad.set_hsv(if pos.is_active {
+                None
+            } else {
+                Some(self.config.inactive_pane_hsb)
+            });
+        }
+        self.filled_rectangle(layers, 0, backdrop, palette.background.to_linear())?;
+
+        Ok(())
+    }
+}
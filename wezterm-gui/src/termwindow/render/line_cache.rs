@@ -0,0 +1,387 @@
+//! Caches the rendered form of the visible lines of each pane, so that
+//! a frame only needs to shape and lay out the lines that have changed
+//! since the previous frame; the quads for the others are copied from
+//! the cache.
+use crate::quad::HeapQuadAllocator;
+use crate::termwindow::animation::NextFrame;
+use crate::termwindow::render::preedit::PlacedPreedit;
+use crate::termwindow::render::{same_hyperlink, CursorProperties, LineToElementShape};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+use termwiz::hyperlink::Hyperlink;
+use termwiz::surface::SequenceNo;
+use wezterm_bidi::Direction;
+use wezterm_term::StableRowIndex;
+use window::color::LinearRgba;
+
+/// The window wide state that every cached line depends upon.
+/// `shape_generation` advances when the font configuration changes,
+/// and `quad_generation` when the dpi or the window size changes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LineCacheEpoch {
+    pub config_generation: usize,
+    pub shape_generation: usize,
+    pub quad_generation: usize,
+}
+
+/// Identifies the content of a line.
+/// Overlays such as copy mode change the attributes of the cells
+/// without advancing the seqno, so the shape hash is compared too;
+/// it is itself cached against the seqno, so that is cheap for
+/// ordinary lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineContent {
+    pub seqno: SequenceNo,
+    pub shape_hash: [u8; 16],
+}
+
+/// Everything other than its content that affects how a line is rendered
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineRenderKey {
+    pub top_pixel_y: f32,
+    pub left_pixel_x: f32,
+    pub pane_is_active: bool,
+    /// Only set if the cursor is on this line
+    pub composing: Option<PlacedPreedit>,
+    /// The selected columns of this line
+    pub selection: Range<usize>,
+    /// Only set if the cursor is on this line
+    pub cursor: Option<CursorProperties>,
+    pub reverse_video: bool,
+    pub password_input: bool,
+    /// The paragraph direction resolved for the logical line, which
+    /// may depend on the lines before this one
+    pub bidi_direction: Option<Direction>,
+}
+
+impl LineRenderKey {
+    /// Returns true if glyph runs shaped for `self` can be used to
+    /// render `other`; the shaping depends on the text being composed
+    /// and the paragraph direction, but not on eg: the selection.
+    fn same_shaping(&self, other: &Self) -> bool {
+        self.composing == other.composing && self.bidi_direction == other.bidi_direction
+    }
+}
+
+pub struct CachedLine {
+    pub content: LineContent,
+    pub key: LineRenderKey,
+    /// The generation of the persistent highlights of the pane that
+    /// `highlights` were computed for
+    pub highlight_generation: usize,
+    /// The persistent highlights that cover this line
+    pub highlights: Vec<(Range<usize>, LinearRgba)>,
+    /// None for the bottom half of a double height line
+    pub shaped: Option<Rc<Vec<LineToElementShape>>>,
+    pub layers: HeapQuadAllocator,
+    pub expires: NextFrame,
+    /// Only set if the line contains any hyperlinks, so
+    /// that we can invalidate when it changes
+    pub current_highlight: Option<Arc<Hyperlink>>,
+    pub invalidate_on_hover_change: bool,
+}
+
+/// The glyph runs of a cached line, for rebuilding its quads
+pub struct ShapedLine {
+    pub shaped: Rc<Vec<LineToElementShape>>,
+    pub expires: NextFrame,
+    pub invalidate_on_hover_change: bool,
+}
+
+pub enum LineCacheLookup<'a> {
+    /// Nothing that affects the line has changed,
+    /// so its quads can be used as-is
+    Hit(&'a CachedLine),
+    /// The line is unchanged, but eg: the selection or cursor has
+    /// moved over it; its quads need to be rebuilt from its glyph runs
+    Relayout(ShapedLine),
+    /// The line needs to be shaped and laid out again.
+    /// `invalidated` is true if it was in the cache, but contained an
+    /// animation that has expired or a hyperlink whose hover state changed.
+    Miss { invalidated: bool },
+}
+
+/// The rendered lines of a single pane, keyed by their stable row index
+#[derive(Default)]
+pub struct PaneLineCache {
+    epoch: LineCacheEpoch,
+    lines: HashMap<StableRowIndex, CachedLine>,
+}
+
+impl PaneLineCache {
+    /// Discards everything if the window wide state has changed
+    pub fn set_epoch(&mut self, epoch: LineCacheEpoch) {
+        if epoch != self.epoch {
+            self.lines.clear();
+            self.epoch = epoch;
+        }
+    }
+
+    /// Looks up the line at `row`.
+    /// The persistent highlights are relatively expensive to compute,
+    /// so `highlights` is only called when their generation has changed
+    /// since the line was cached; the entry remains valid if the
+    /// highlights that cover this particular line are unchanged.
+    #[allow(clippy::too_many_arguments)]
+    pub fn lookup(
+        &mut self,
+        row: StableRowIndex,
+        content: LineContent,
+        key: &LineRenderKey,
+        highlight_generation: usize,
+        highlights: impl FnOnce() -> Vec<(Range<usize>, LinearRgba)>,
+        current_highlight: Option<&Arc<Hyperlink>>,
+        now: Instant,
+    ) -> LineCacheLookup<'_> {
+        let expired = match self.lines.get(&row) {
+            Some(entry) if entry.content == content => {
+                let hover_changed = entry.invalidate_on_hover_change
+                    && !same_hyperlink(entry.current_highlight.as_ref(), current_highlight);
+                entry.expires.is_expired(now) || hover_changed
+            }
+            _ => return LineCacheLookup::Miss { invalidated: false },
+        };
+        if expired {
+            self.lines.remove(&row);
+            return LineCacheLookup::Miss { invalidated: true };
+        }
+        let entry = match self.lines.get_mut(&row) {
+            Some(entry) => entry,
+            None => return LineCacheLookup::Miss { invalidated: false },
+        };
+
+        let mut highlights_changed = false;
+        if entry.highlight_generation != highlight_generation {
+            if highlights() == entry.highlights {
+                entry.highlight_generation = highlight_generation;
+            } else {
+                highlights_changed = true;
+            }
+        }
+
+        if entry.key == *key && !highlights_changed {
+            LineCacheLookup::Hit(entry)
+        } else {
+            match &entry.shaped {
+                Some(shaped) if entry.key.same_shaping(key) => {
+                    LineCacheLookup::Relayout(ShapedLine {
+                        shaped: Rc::clone(shaped),
+                        expires: entry.expires,
+                        invalidate_on_hover_change: entry.invalidate_on_hover_change,
+                    })
+                }
+                _ => LineCacheLookup::Miss { invalidated: false },
+            }
+        }
+    }
+
+    pub fn insert(&mut self, row: StableRowIndex, line: CachedLine) {
+        self.lines.insert(row, line);
+    }
+
+    /// Discards the lines that are outside of `rows`, so that the size
+    /// of the cache is bounded by the size of the viewport
+    pub fn retain_rows(&mut self, rows: Range<StableRowIndex>) {
+        self.lines.retain(|row, _| rows.contains(row));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn key() -> LineRenderKey {
+        LineRenderKey {
+            top_pixel_y: 0.,
+            left_pixel_x: 0.,
+            pane_is_active: true,
+            composing: None,
+            selection: 0..0,
+            cursor: None,
+            reverse_video: false,
+            password_input: false,
+            bidi_direction: None,
+        }
+    }
+
+    fn content(seqno: SequenceNo) -> LineContent {
+        LineContent {
+            seqno,
+            shape_hash: [0; 16],
+        }
+    }
+
+    fn cached(seqno: SequenceNo, key: LineRenderKey) -> CachedLine {
+        CachedLine {
+            content: content(seqno),
+            key,
+            highlight_generation: 0,
+            highlights: vec![],
+            shaped: Some(Rc::new(vec![])),
+            layers: HeapQuadAllocator::default(),
+            expires: NextFrame::default(),
+            current_highlight: None,
+            invalidate_on_hover_change: false,
+        }
+    }
+
+    fn lookup(
+        cache: &mut PaneLineCache,
+        row: StableRowIndex,
+        seqno: SequenceNo,
+        key: &LineRenderKey,
+        highlight_generation: usize,
+        highlights: Vec<(Range<usize>, LinearRgba)>,
+    ) -> &'static str {
+        match cache.lookup(
+            row,
+            content(seqno),
+            key,
+            highlight_generation,
+            || highlights,
+            None,
+            Instant::now(),
+        ) {
+            LineCacheLookup::Hit(_) => "hit",
+            LineCacheLookup::Relayout(_) => "relayout",
+            LineCacheLookup::Miss { invalidated: false } => "miss",
+            LineCacheLookup::Miss { invalidated: true } => "invalidated",
+        }
+    }
+
+    #[test]
+    fn seqno_and_epoch() {
+        let mut cache = PaneLineCache::default();
+        assert_eq!(lookup(&mut cache, 0, 1, &key(), 0, vec![]), "miss");
+
+        cache.insert(0, cached(1, key()));
+        assert_eq!(lookup(&mut cache, 0, 1, &key(), 0, vec![]), "hit");
+        assert_eq!(lookup(&mut cache, 0, 2, &key(), 0, vec![]), "miss");
+        assert_eq!(lookup(&mut cache, 1, 1, &key(), 0, vec![]), "miss");
+
+        // Font or dpi changes discard everything
+        cache.set_epoch(LineCacheEpoch::default());
+        assert_eq!(lookup(&mut cache, 0, 1, &key(), 0, vec![]), "hit");
+        cache.set_epoch(LineCacheEpoch {
+            shape_generation: 1,
+            ..LineCacheEpoch::default()
+        });
+        assert_eq!(lookup(&mut cache, 0, 1, &key(), 0, vec![]), "miss");
+
+        cache.insert(0, cached(1, key()));
+        cache.insert(5, cached(1, key()));
+        cache.retain_rows(0..5);
+        assert_eq!(lookup(&mut cache, 0, 1, &key(), 0, vec![]), "hit");
+        assert_eq!(lookup(&mut cache, 5, 1, &key(), 0, vec![]), "miss");
+    }
+
+    #[test]
+    fn selection_reuses_shaping() {
+        let mut cache = PaneLineCache::default();
+        cache.insert(0, cached(1, key()));
+        cache.insert(1, cached(1, key()));
+
+        let selected = LineRenderKey {
+            selection: 2..5,
+            ..key()
+        };
+        assert_eq!(lookup(&mut cache, 0, 1, &selected, 0, vec![]), "relayout");
+        // The other lines are unaffected
+        assert_eq!(lookup(&mut cache, 1, 1, &key(), 0, vec![]), "hit");
+
+        // Text being composed changes the glyphs
+        let composing = LineRenderKey {
+            composing: Some(PlacedPreedit::new(
+                &::window::Preedit {
+                    text: "x".to_string(),
+                    ..Default::default()
+                },
+                0,
+                80,
+            )),
+            ..key()
+        };
+        assert_eq!(lookup(&mut cache, 1, 1, &composing, 0, vec![]), "miss");
+    }
+
+    #[test]
+    fn highlights_invalidate_affected_lines() {
+        let red = LinearRgba::with_components(1., 0., 0., 1.);
+        let mut cache = PaneLineCache::default();
+        cache.insert(0, cached(1, key()));
+        cache.insert(1, cached(1, key()));
+
+        // A new highlight that matches only the first line
+        assert_eq!(
+            lookup(&mut cache, 0, 1, &key(), 1, vec![(0..3, red)]),
+            "relayout"
+        );
+        assert_eq!(lookup(&mut cache, 1, 1, &key(), 1, vec![]), "hit");
+        // Until the first line is cached again, it continues to need
+        // to be relaid out
+        assert_eq!(
+            lookup(&mut cache, 0, 1, &key(), 1, vec![(0..3, red)]),
+            "relayout"
+        );
+
+        // Once the first line is cached with its highlights, the
+        // highlights are not computed again until they change
+        let mut line = cached(1, key());
+        line.highlight_generation = 1;
+        line.highlights = vec![(0..3, red)];
+        cache.insert(0, line);
+        let matched = cache.lookup(
+            0,
+            content(1),
+            &key(),
+            1,
+            || panic!("the highlights are unchanged"),
+            None,
+            Instant::now(),
+        );
+        assert!(matches!(matched, LineCacheLookup::Hit(_)));
+    }
+
+    #[test]
+    fn expired_and_hover() {
+        let now = Instant::now();
+        let mut cache = PaneLineCache::default();
+
+        let mut line = cached(1, key());
+        line.expires.due = Some(now + Duration::from_millis(500));
+        cache.insert(0, line);
+        assert!(matches!(
+            cache.lookup(0, content(1), &key(), 0, Vec::new, None, now),
+            LineCacheLookup::Hit(_)
+        ));
+        assert!(matches!(
+            cache.lookup(
+                0,
+                content(1),
+                &key(),
+                0,
+                Vec::new,
+                None,
+                now + Duration::from_secs(1)
+            ),
+            LineCacheLookup::Miss { invalidated: true }
+        ));
+
+        let link = Arc::new(Hyperlink::new("https://example.com"));
+        let mut line = cached(1, key());
+        line.invalidate_on_hover_change = true;
+        cache.insert(0, line);
+        assert!(matches!(
+            cache.lookup(0, content(1), &key(), 0, Vec::new, None, now),
+            LineCacheLookup::Hit(_)
+        ));
+        assert!(matches!(
+            cache.lookup(0, content(1), &key(), 0, Vec::new, Some(&link), now),
+            LineCacheLookup::Miss { invalidated: true }
+        ));
+    }
+}
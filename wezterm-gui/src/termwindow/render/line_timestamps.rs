@@ -0,0 +1,217 @@
+use crate::termwindow::box_model::*;
+use crate::termwindow::render::floating::pane_element_zindex;
+use crate::termwindow::TermWindow;
+use config::{ConfigHandle, Dimension, DimensionContext, TabBarColors};
+use mux::pane::Pane;
+use mux::tab::PositionedPane;
+use mux::Mux;
+use std::fmt::Write;
+use std::sync::Arc;
+use wezterm_term::{unicode_column_width, StableRowIndex};
+
+/// Formats the arrival time of a line using `format`.
+/// Returns None if the arrival time of the line wasn't recorded,
+/// which is the case for lines that predate its tracking, or if
+/// `format` is not a valid strftime format.
+pub fn format_arrival(arrival: Option<u32>, format: &str) -> Option<String> {
+    use chrono::TimeZone;
+    let time = chrono::Local.timestamp_opt(arrival? as i64, 0).single()?;
+    let mut text = String::new();
+    write!(text, "{}", time.format(format)).ok()?;
+    Some(text)
+}
+
+/// Returns the number of columns needed to show the timestamps
+/// produced by `line_timestamp_format`, including a column that
+/// separates them from the terminal, or 0 if the format is invalid
+pub fn gutter_cols(config: &ConfigHandle) -> usize {
+    let now = chrono::Local::now().timestamp() as u32;
+    match format_arrival(Some(now), &config.line_timestamp_format) {
+        Some(text) => unicode_column_width(&text, None) + 1,
+        None => 0,
+    }
+}
+
+/// Returns the text for the gutter of a line, which is blank when
+/// its arrival time is unknown
+pub fn gutter_text(arrival: Option<u32>, format: &str, cols: usize) -> String {
+    let text = format_arrival(arrival, format).unwrap_or_default();
+    let pad = cols.saturating_sub(unicode_column_width(&text, None));
+    format!("{text}{}", " ".repeat(pad))
+}
+
+/// Returns the arrival time in effect for the line at `row` of `pane`.
+/// Arrival times are stored sparsely, so this is the one recorded by
+/// the nearest line at or above `row` that has one.
+pub fn arrival_at(pane: &dyn Pane, row: StableRowIndex) -> Option<u32> {
+    let dims = pane.get_dimensions();
+    let chunk = dims.viewport_rows.max(1) as StableRowIndex;
+    let mut end = row + 1;
+    while end > dims.scrollback_top {
+        let start = (end - chunk).max(dims.scrollback_top);
+        let (_first, lines) = pane.get_lines(start..end);
+        if let Some(arrival) = lines.iter().rev().find_map(|line| line.arrival_time()) {
+            return Some(arrival);
+        }
+        end = start;
+    }
+    None
+}
+
+impl TermWindow {
+    /// Shows or hides the line timestamps gutter of `pane`, and resizes
+    /// its tab so that the terminal makes room for, or reclaims the
+    /// space of, the gutter
+    pub fn toggle_line_timestamps(&mut self, pane: &Arc<dyn Pane>) {
+        let cols = if pane.timestamp_gutter_cols() == 0 {
+            let cols = gutter_cols(&self.config);
+            if cols == 0 {
+                log::error!(
+                    "line_timestamp_format {:?} is not a valid strftime format",
+                    self.config.line_timestamp_format
+                );
+                return;
+            }
+            cols
+        } else {
+            0
+        };
+        pane.set_timestamp_gutter_cols(cols);
+
+        let mux = Mux::get();
+        if let Some(tab) = mux
+            .resolve_pane_id(pane.pane_id())
+            .and_then(|(_domain, _window, tab_id)| mux.get_tab(tab_id))
+        {
+            tab.resize(tab.get_size());
+        }
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Paints the arrival times of the visible lines of the pane at
+    /// `pos` in the gutter to its left, when ToggleLineTimestamps has
+    /// enabled it for the pane
+    pub fn paint_pane_timestamps(&mut self, pos: &PositionedPane) -> anyhow::Result<()> {
+        let gutter = pos.pane.timestamp_gutter_cols();
+        if gutter == 0 || pos.left < gutter {
+            return Ok(());
+        }
+
+        let font = self.fonts.default_font()?;
+        let metrics = self.render_metrics;
+        let cell_width = metrics.cell_size.width as f32;
+        let cell_height = metrics.cell_size.height as f32;
+
+        let (padding_left, padding_top) = self.padding_left_top();
+        let tab_bar = self.tab_bar_reserved_edges()?;
+        let border = self.get_os_border();
+
+        let bounds = euclid::rect(
+            tab_bar.left
+                + padding_left
+                + border.left.get() as f32
+                + ((pos.left - gutter) as f32 * cell_width),
+            tab_bar.top + padding_top + border.top.get() as f32 + (pos.top as f32 * cell_height),
+            gutter as f32 * cell_width,
+            pos.height as f32 * cell_height,
+        );
+
+        let dims = pos.pane.get_dimensions();
+        let top = self
+            .get_viewport(pos.pane.pane_id())
+            .unwrap_or(dims.physical_top);
+        let (_first, lines) = pos
+            .pane
+            .get_lines(top..top + dims.viewport_rows as StableRowIndex);
+
+        let colors = self
+            .config
+            .colors
+            .as_ref()
+            .and_then(|c| c.tab_bar.as_ref())
+            .cloned()
+            .unwrap_or_else(TabBarColors::default)
+            .inactive_tab();
+        let element_colors = ElementColors {
+            border: BorderColor::default(),
+            bg: colors.bg_color.to_linear().into(),
+            text: colors.fg_color.to_linear().into(),
+        };
+
+        let format = &self.config.line_timestamp_format;
+        let mut arrival = arrival_at(&*pos.pane, top - 1);
+        let children = lines
+            .iter()
+            .map(|line| {
+                if let Some(secs) = line.arrival_time() {
+                    arrival = Some(secs);
+                }
+                Element::new(
+                    &font,
+                    ElementContent::Text(gutter_text(arrival, format, gutter)),
+                )
+                .display(DisplayType::Block)
+            })
+            .collect();
+
+        let element = Element::new(&font, ElementContent::Children(children))
+            .colors(element_colors)
+            .min_width(Some(Dimension::Pixels(bounds.width())))
+            .min_height(Some(Dimension::Pixels(bounds.height())));
+
+        let dimensions = self.dimensions;
+        let mut computed = self.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: bounds.height(),
+                    pixel_cell: cell_height,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: bounds.width(),
+                    pixel_cell: cell_width,
+                },
+                bounds: euclid::rect(0., 0., bounds.width(), bounds.height()),
+                metrics: &metrics,
+                gl_state: self.render_state.as_ref().unwrap(),
+                zindex: pane_element_zindex(pos),
+            },
+            &element,
+        )?;
+        computed.translate(euclid::vec2(bounds.min_x(), bounds.min_y()));
+
+        let gl_state = self.render_state.as_ref().unwrap();
+        self.render_element(&computed, gl_state, None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn blank_when_unknown() {
+        assert_eq!(format_arrival(None, "%H:%M:%S"), None);
+        assert_eq!(gutter_text(None, "%H:%M:%S", 9), " ".repeat(9));
+    }
+
+    #[test]
+    fn formats_local_time() {
+        use chrono::TimeZone;
+        let secs = chrono::Local
+            .with_ymd_and_hms(2024, 3, 1, 13, 5, 9)
+            .single()
+            .unwrap()
+            .timestamp() as u32;
+        assert_eq!(
+            format_arrival(Some(secs), "%H:%M:%S"),
+            Some("13:05:09".to_string())
+        );
+        assert_eq!(gutter_text(Some(secs), "%H:%M", 6), "13:05 ");
+        // An invalid format is treated like an unknown time
+        assert_eq!(format_arrival(Some(secs), "%Q"), None);
+    }
+}
@@ -6,7 +6,9 @@ use crate::quad::{
     TripleLayerQuadAllocatorTrait,
 };
 use crate::shapecache::*;
+use crate::termwindow::animation::{AnimationSources, NextFrame};
 use crate::termwindow::render::paint::AllowImage;
+use crate::termwindow::render::preedit::PlacedPreedit;
 use crate::termwindow::{BorrowedShapeCacheKey, RenderState, ShapedInfo, TermWindowNotif};
 use crate::utilsprites::RenderMetrics;
 use ::window::bitmaps::{TextureCoord, TextureRect, TextureSize};
@@ -14,9 +16,8 @@ use ::window::{DeadKeyStatus, PointF, RectF, SizeF, WindowOps};
 use anyhow::{anyhow, Context};
 use config::{BoldBrightening, ConfigHandle, DimensionContext, TextStyle, VisualBellTarget};
 use euclid::num::Zero;
-use mux::pane::{Pane, PaneId};
+use mux::pane::Pane;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
-use ordered_float::NotNan;
 use std::ops::Range;
 use std::rc::Rc;
 use std::sync::Arc;
@@ -24,6 +25,7 @@ use std::time::Instant;
 use termwiz::cellcluster::CellCluster;
 use termwiz::hyperlink::Hyperlink;
 use termwiz::surface::{CursorShape, CursorVisibility, SequenceNo};
+use wezterm_bidi::Direction;
 use wezterm_font::shaper::PresentationWidth;
 use wezterm_font::units::{IntPixelLength, PixelLength};
 use wezterm_font::{ClearShapeCache, GlyphInfo, LoadedFont};
@@ -32,11 +34,17 @@ use wezterm_term::{CellAttributes, Line, StableRowIndex};
 use window::color::LinearRgba;
 
 pub mod borders;
+pub mod contrast;
 pub mod corners;
 pub mod draw;
 pub mod fancy_tab_bar;
+pub mod floating;
+pub mod line_cache;
+pub mod line_timestamps;
 pub mod paint;
 pub mod pane;
+pub mod pane_title_bar;
+pub mod preedit;
 pub mod screen_line;
 pub mod split;
 pub mod tab_bar;
@@ -50,36 +58,49 @@ pub struct CachedLineState {
     pub shape_hash: [u8; 16],
 }
 
-#[derive(Debug, Hash, Clone, PartialEq, Eq)]
-pub struct LineQuadCacheKey {
-    pub config_generation: usize,
-    pub shape_generation: usize,
-    pub quad_generation: usize,
-    /// Only set if cursor.y == stable_row
-    pub composing: Option<String>,
-    pub selection: Range<usize>,
-    pub shape_hash: [u8; 16],
-    pub top_pixel_y: NotNan<f32>,
-    pub left_pixel_x: NotNan<f32>,
-    pub phys_line_idx: usize,
-    pub pane_id: PaneId,
-    pub pane_is_active: bool,
-    /// A cursor position with the y value fixed at 0.
-    /// Only is_some() if the y value matches this row.
-    pub cursor: Option<CursorProperties>,
-    pub reverse_video: bool,
-    pub password_input: bool,
+/// Hit/miss counters for one of the per-line render caches
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CacheCounter {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries that were found but had to be rebuilt because
+    /// they expired or the hyperlink hover state changed.
+    /// These are also counted as misses.
+    pub invalidated: u64,
 }
 
-pub struct LineQuadCacheValue {
-    /// For resolving hash collisions
-    pub line: Line,
-    pub expires: Option<Instant>,
-    pub layers: HeapQuadAllocator,
-    // Only set if the line contains any hyperlinks, so
-    // that we can invalidate when it changes
-    pub current_highlight: Option<Arc<Hyperlink>>,
-    pub invalidate_on_hover_change: bool,
+impl CacheCounter {
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+impl std::fmt::Display for CacheCounter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1}% hit rate ({} hits, {} misses, {} invalidated)",
+            self.hit_rate() * 100.,
+            self.hits,
+            self.misses,
+            self.invalidated
+        )
+    }
+}
+
+/// Counts the effectiveness of the per-pane line cache, which allows
+/// unchanged lines to skip re-shaping and re-layout, and of the line
+/// shaping cache.
+/// These are shown in the debug overlay.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RenderCacheStats {
+    pub line_quads: CacheCounter,
+    pub line_shapes: CacheCounter,
 }
 
 pub struct LineToElementParams<'a> {
@@ -91,17 +112,19 @@ pub struct LineToElementParams<'a> {
     pub cursor: &'a StableCursorPosition,
     pub reverse_video: bool,
     pub shape_key: &'a Option<LineToEleShapeCacheKey>,
+    pub bidi_direction: Option<Direction>,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
 pub struct LineToEleShapeCacheKey {
     pub shape_hash: [u8; 16],
-    pub composing: Option<(usize, String)>,
+    pub composing: Option<PlacedPreedit>,
     pub shape_generation: usize,
+    pub bidi_direction: Option<Direction>,
 }
 
 pub struct LineToElementShapeItem {
-    pub expires: Option<Instant>,
+    pub expires: NextFrame,
     pub shaped: Rc<Vec<LineToElementShape>>,
     // Only set if the line contains any hyperlinks, so
     // that we can invalidate when it changes
@@ -124,6 +147,8 @@ pub struct LineToElementShape {
 
 pub struct RenderScreenLineResult {
     pub invalidate_on_hover_change: bool,
+    /// The glyph runs that the line was rendered from
+    pub shaped: Option<Rc<Vec<LineToElementShape>>>,
 }
 
 pub struct RenderScreenLineParams<'a> {
@@ -137,6 +162,9 @@ pub struct RenderScreenLineParams<'a> {
     pub stable_line_idx: Option<StableRowIndex>,
     pub line: &'a Line,
     pub selection: Range<usize>,
+    /// Ranges of cells that are covered by persistent highlights,
+    /// and the background color to use for each
+    pub highlights: &'a [(Range<usize>, LinearRgba)],
     pub cursor: &'a StableCursorPosition,
     pub palette: &'a ColorPalette,
     pub dims: &'a RenderableDimensions,
@@ -170,7 +198,13 @@ pub struct RenderScreenLineParams<'a> {
 
     pub render_metrics: RenderMetrics,
     pub shape_key: Option<LineToEleShapeCacheKey>,
+    /// Glyph runs that were previously shaped for this line, which
+    /// are used rather than looking the line up via `shape_key`
+    pub shaped: Option<Rc<Vec<LineToElementShape>>>,
     pub password_input: bool,
+    /// The paragraph direction of the line if it has bidi enabled.
+    /// When None, it is resolved from this line alone.
+    pub bidi_direction: Option<Direction>,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone)]
@@ -225,11 +259,78 @@ pub struct ClusterStyleCache<'a> {
     underline_color: LinearRgba,
 }
 
+/// Rounds quad coordinates, which are relative to the center of the
+/// window, to whole device pixels when snapping is enabled.
+/// The rounding is relative to the top left corner of the window,
+/// which is always pixel aligned even when the center is not.
+#[derive(Debug, Clone, Copy)]
+pub struct PixelSnap {
+    enabled: bool,
+    left: f32,
+    top: f32,
+}
+
+impl PixelSnap {
+    pub fn x(&self, x: f32) -> f32 {
+        if self.enabled {
+            (x - self.left).round() + self.left
+        } else {
+            x
+        }
+    }
+
+    pub fn y(&self, y: f32) -> f32 {
+        if self.enabled {
+            (y - self.top).round() + self.top
+        } else {
+            y
+        }
+    }
+
+    /// Returns how far a quad whose top left corner is at `x`, `y`
+    /// must be moved to place that corner on a pixel.
+    /// Glyphs are moved as a whole rather than having each edge
+    /// rounded, so that they are not stretched.
+    pub fn offset(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.x(x) - x, self.y(y) - y)
+    }
+}
+
 impl crate::TermWindow {
-    pub fn update_next_frame_time(&self, next_due: Option<Instant>) {
-        if next_due.is_some() {
-            update_next_frame_time(&mut *self.has_animation.borrow_mut(), next_due);
+    /// Returns true if quads are to be placed on whole device pixels
+    pub fn snap_to_pixel_grid(&self) -> bool {
+        self.integer_scaling
+            .unwrap_or(self.config.snap_glyphs_to_pixel_grid)
+    }
+
+    pub fn pixel_snap(&self) -> PixelSnap {
+        PixelSnap {
+            enabled: self.snap_to_pixel_grid(),
+            left: self.dimensions.pixel_width as f32 / -2.,
+            top: self.dimensions.pixel_height as f32 / -2.,
+        }
+    }
+
+    /// Overrides snap_glyphs_to_pixel_grid for this window;
+    /// `None` reverts to the configured value
+    pub fn set_integer_scaling(&mut self, enabled: Option<bool>) {
+        if enabled == self.integer_scaling {
+            return;
         }
+        self.integer_scaling = enabled;
+        self.quad_generation += 1;
+        if let Some(window) = self.window.as_ref() {
+            window.invalidate();
+        }
+    }
+
+    /// Requests a frame at `next_due` on behalf of `source`
+    pub fn update_next_frame_time(&self, source: AnimationSources, next_due: Option<Instant>) {
+        self.merge_next_frame(NextFrame::new(source, next_due));
+    }
+
+    pub fn merge_next_frame(&self, next: NextFrame) {
+        self.has_animation.borrow_mut().merge(next);
     }
 
     fn get_intensity_if_bell_target_ringing(
@@ -256,7 +357,7 @@ impl crate::TermWindow {
                         per_pane.bell_start.take();
                     }
                     Some((intensity, next)) => {
-                        self.update_next_frame_time(Some(next));
+                        self.update_next_frame_time(AnimationSources::VISUAL_BELL, Some(next));
                         return Some(intensity);
                     }
                 }
@@ -276,11 +377,14 @@ impl crate::TermWindow {
         let left_offset = self.dimensions.pixel_width as f32 / 2.;
         let top_offset = self.dimensions.pixel_height as f32 / 2.;
         let gl_state = self.render_state.as_ref().unwrap();
+        // Each edge is rounded, so that adjacent rectangles remain
+        // adjacent when snapped
+        let snap = self.pixel_snap();
         quad.set_position(
-            rect.min_x() as f32 - left_offset,
-            rect.min_y() as f32 - top_offset,
-            rect.max_x() as f32 - left_offset,
-            rect.max_y() as f32 - top_offset,
+            snap.x(rect.min_x() as f32 - left_offset),
+            snap.y(rect.min_y() as f32 - top_offset),
+            snap.x(rect.max_x() as f32 - left_offset),
+            snap.y(rect.max_y() as f32 - top_offset),
         );
         quad.set_texture(gl_state.util_sprites.filled_box.texture_coords());
         quad.set_is_background();
@@ -317,11 +421,14 @@ impl crate::TermWindow {
 
         let mut quad = layers.allocate(layer_num)?;
 
+        let (dx, dy) = self
+            .pixel_snap()
+            .offset(point.x - left_offset, point.y - top_offset);
         quad.set_position(
-            point.x - left_offset,
-            point.y - top_offset,
-            (point.x + cell_size.width as f32) - left_offset,
-            (point.y + cell_size.height as f32) - top_offset,
+            point.x - left_offset + dx,
+            point.y - top_offset + dy,
+            (point.x + cell_size.width as f32) - left_offset + dx,
+            (point.y + cell_size.height as f32) - top_offset + dy,
         );
         quad.set_texture(sprite);
         quad.set_fg_color(color);
@@ -438,7 +545,7 @@ impl crate::TermWindow {
             .borrow_mut()
             .cached_image(image.image_data(), Some(padding), self.allow_images)
             .context("cached_image")?;
-        self.update_next_frame_time(next_due);
+        self.update_next_frame_time(AnimationSources::IMAGE, next_due);
         let width = sprite.coords.size.width;
         let height = sprite.coords.size.height;
 
@@ -620,7 +727,9 @@ impl crate::TermWindow {
             _ => (params.fg_color, params.bg_color, params.cursor_border_color),
         };
 
+        // Only a visible cursor in a focused window animates
         let blinking = params.cursor.is_some()
+            && visibility == CursorVisibility::Visible
             && params.is_active_pane
             && cursor_shape.is_blinking()
             && params.config.cursor_blink_rate != 0
@@ -649,7 +758,7 @@ impl crate::TermWindow {
                 fg_color_mix = intensity;
             }
 
-            self.update_next_frame_time(Some(next));
+            self.update_next_frame_time(AnimationSources::CURSOR_BLINK, Some(next));
         }
 
         ComputeCellFgBgResult {
@@ -887,25 +996,77 @@ fn resolve_fg_color_attr(
     .to_linear()
 }
 
-fn update_next_frame_time(storage: &mut Option<Instant>, next_due: Option<Instant>) {
-    if let Some(next_due) = next_due {
-        match storage.take() {
-            None => {
-                storage.replace(next_due);
-            }
-            Some(t) if next_due < t => {
-                storage.replace(next_due);
+fn same_hyperlink(a: Option<&Arc<Hyperlink>>, b: Option<&Arc<Hyperlink>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snap(enabled: bool, pixel_width: usize, pixel_height: usize) -> PixelSnap {
+        PixelSnap {
+            enabled,
+            left: pixel_width as f32 / -2.,
+            top: pixel_height as f32 / -2.,
+        }
+    }
+
+    /// Returns true if `value` is a whole number of pixels from `origin`
+    fn on_pixel(value: f32, origin: f32) -> bool {
+        let pixels = value - origin;
+        (pixels - pixels.round()).abs() < 1e-3
+    }
+
+    #[test]
+    fn snaps_at_fractional_scales() {
+        for scale in [1.0f32, 1.25, 1.5, 1.75, 2.25] {
+            // An odd logical size, so that the center of the
+            // window isn't on a pixel at most scales
+            let pixel_width = (801. * scale).round() as usize;
+            let pixel_height = (603. * scale).round() as usize;
+            let snap = snap(true, pixel_width, pixel_height);
+            let cell_width = 7.2 * scale;
+            let cell_height = 15.3 * scale;
+
+            for col in 0..100 {
+                let x = snap.left + col as f32 * cell_width;
+                let snapped = snap.x(x);
+                assert!(on_pixel(snapped, snap.left), "scale {scale} col {col}");
+                assert!((snapped - x).abs() <= 0.5 + 1e-3, "scale {scale} col {col}");
             }
-            Some(t) => {
-                storage.replace(t);
+            for row in 0..36 {
+                let y = snap.top + row as f32 * cell_height;
+                let snapped = snap.y(y);
+                assert!(on_pixel(snapped, snap.top), "scale {scale} row {row}");
+                assert!((snapped - y).abs() <= 0.5 + 1e-3, "scale {scale} row {row}");
             }
         }
     }
-}
 
-fn same_hyperlink(a: Option<&Arc<Hyperlink>>, b: Option<&Arc<Hyperlink>>) -> bool {
-    match (a, b) {
-        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
-        _ => false,
+    #[test]
+    fn offset_moves_without_stretching() {
+        // 801x601 at a scale of 1.25
+        let snap = snap(true, 1001, 751);
+        let (x, y) = (-123.37, 45.81);
+        let (dx, dy) = snap.offset(x, y);
+        assert!(on_pixel(x + dx, snap.left));
+        assert!(on_pixel(y + dy, snap.top));
+        assert!(dx.abs() <= 0.5 && dy.abs() <= 0.5);
+
+        // A corner that is already on a pixel stays put, even though
+        // the center of this window is half way across a pixel
+        assert_eq!(snap.offset(snap.left + 17., snap.top + 3.), (0., 0.));
+    }
+
+    #[test]
+    fn disabled() {
+        let snap = snap(false, 1001, 751);
+        assert_eq!(snap.x(-123.37), -123.37);
+        assert_eq!(snap.y(45.81), 45.81);
+        assert_eq!(snap.offset(-123.37, 45.81), (0., 0.));
     }
 }
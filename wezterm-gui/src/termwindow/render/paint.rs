@@ -1,3 +1,5 @@
+use crate::termwindow::animation::NextFrame;
+use crate::termwindow::render::floating::floating_zindex;
 use crate::termwindow::{RenderFrame, TermWindowNotif};
 use ::window::bitmaps::atlas::OutOfTextureSpace;
 use ::window::WindowOps;
@@ -18,7 +20,8 @@ impl crate::TermWindow {
         self.num_frames += 1;
         // If nothing on screen needs animating, then we can avoid
         // invalidating as frequently
-        *self.has_animation.borrow_mut() = None;
+        self.has_animation.take();
+        self.step_kinetic_scroll();
         // Start with the assumption that we should allow images to render
         self.allow_images = AllowImage::Yes;
 
@@ -115,34 +118,52 @@ impl crate::TermWindow {
         metrics::histogram!("gui.paint.impl").record(self.last_frame_duration);
         metrics::histogram!("gui.paint.impl.rate").record(1.);
 
-        // If self.has_animation is some, then the last render detected
-        // image attachments with multiple frames, so we also need to
-        // invalidate the viewport when the next frame is due
-        if self.focused.is_some() {
-            if let Some(next_due) = *self.has_animation.borrow() {
-                let prior = self.scheduled_animation.borrow_mut().take();
-                match prior {
-                    Some(prior) if prior <= next_due => {
-                        // Already due before that time
-                    }
-                    _ => {
-                        self.scheduled_animation.borrow_mut().replace(next_due);
-                        let window = self.window.clone().take().unwrap();
-                        promise::spawn::spawn(async move {
-                            Timer::at(next_due).await;
-                            let win = window.clone();
-                            window.notify(TermWindowNotif::Apply(Box::new(move |tw| {
-                                tw.scheduled_animation.borrow_mut().take();
-                                win.invalidate();
-                            })));
-                        })
-                        .detach();
-                    }
+        // If self.has_animation is due, then something that was painted
+        // is animating, so we also need to invalidate the viewport when
+        // the next frame is due.  Otherwise there is no timer at all, and
+        // the next paint happens when output or input invalidates the window.
+        let next = if self.focused.is_some() {
+            *self.has_animation.borrow()
+        } else {
+            NextFrame::default()
+        };
+        self.animation_sources = next.sources;
+        if let Some(next_due) = next.due {
+            let prior = *self.scheduled_animation.borrow();
+            match prior {
+                Some(prior) if prior <= next_due => {
+                    // A timer that is due before that time is already
+                    // pending, and will trigger a paint that reschedules
+                }
+                _ => {
+                    self.scheduled_animation.borrow_mut().replace(next_due);
+                    let window = self.window.clone().take().unwrap();
+                    promise::spawn::spawn(async move {
+                        Timer::at(next_due).await;
+                        let win = window.clone();
+                        window.notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                            tw.scheduled_animation.borrow_mut().take();
+                            win.invalidate();
+                        })));
+                    })
+                    .detach();
                 }
             }
         }
     }
 
+    /// Returns the rate at which frames have recently been painted.
+    /// Unlike `fps`, which is only updated by painting, this decays
+    /// to zero while the window is idle.
+    pub fn effective_fps(&self) -> f32 {
+        let elapsed = self.last_fps_check_time.elapsed();
+        if elapsed > Duration::from_secs(1) {
+            self.num_frames as f32 / elapsed.as_secs_f32()
+        } else {
+            self.fps
+        }
+    }
+
     pub fn paint_modal(&mut self) -> anyhow::Result<()> {
         if let Some(modal) = self.get_modal() {
             for computed in modal.computed_element(self)?.iter() {
@@ -246,15 +267,26 @@ impl crate::TermWindow {
             .context("filled_rectangle for window background")?;
         }
 
-        for pos in panes {
+        for pos in &panes {
             if pos.is_active {
                 self.update_text_cursor(&pos);
+                self.update_ime_enabled();
                 if focused {
                     pos.pane.advise_focus();
                     mux::Mux::get().record_focus_for_current_identity(pos.pane.pane_id());
                 }
             }
+            if pos.is_floating {
+                // Floating panes are painted above the splits; see below
+                continue;
+            }
             self.paint_pane(&pos, &mut layers).context("paint_pane")?;
+            self.paint_pane_title_bar(&pos)
+                .context("paint_pane_title_bar")?;
+            self.paint_pane_timestamps(&pos)
+                .context("paint_pane_timestamps")?;
+            self.paint_pane_paused_indicator(&pos)
+                .context("paint_pane_paused_indicator")?;
         }
 
         if let Some(pane) = self.get_active_pane_or_overlay() {
@@ -265,6 +297,24 @@ impl crate::TermWindow {
             }
         }
 
+        for pos in panes.iter().filter(|pos| pos.is_floating) {
+            let layer = self
+                .render_state
+                .as_ref()
+                .unwrap()
+                .layer_for_zindex(floating_zindex(pos))
+                .context("layer_for_zindex for floating pane")?;
+            let mut float_layers = layer.quad_allocator();
+            self.paint_floating_pane_frame(&mut float_layers, pos)
+                .context("paint_floating_pane_frame")?;
+            self.paint_pane(pos, &mut float_layers)
+                .context("paint_pane")?;
+            self.paint_pane_timestamps(pos)
+                .context("paint_pane_timestamps")?;
+            self.paint_pane_paused_indicator(pos)
+                .context("paint_pane_paused_indicator")?;
+        }
+
         if self.show_tab_bar {
             self.paint_tab_bar(&mut layers).context("paint_tab_bar")?;
         }
@@ -273,6 +323,8 @@ impl crate::TermWindow {
             .context("paint_window_borders")?;
         drop(layers);
         self.paint_modal().context("paint_modal")?;
+        self.paint_presented_keys()
+            .context("paint_presented_keys")?;
 
         Ok(())
     }
@@ -1,23 +1,32 @@
+use crate::overlay::CopyOverlay;
 use crate::quad::{HeapQuadAllocator, QuadTrait, TripleLayerQuadAllocator};
 use crate::selection::SelectionRange;
 use crate::termwindow::box_model::*;
-use crate::termwindow::render::{
-    same_hyperlink, CursorProperties, LineQuadCacheKey, LineQuadCacheValue, LineToEleShapeCacheKey,
-    RenderScreenLineParams,
+use crate::termwindow::render::line_cache::{
+    CachedLine, LineCacheEpoch, LineCacheLookup, LineContent, LineRenderKey, PaneLineCache,
 };
+use crate::termwindow::render::preedit::PlacedPreedit;
+use crate::termwindow::render::{CursorProperties, LineToEleShapeCacheKey, RenderScreenLineParams};
 use crate::termwindow::{ScrollHit, UIItem, UIItemType};
 use ::window::bitmaps::TextureRect;
 use ::window::DeadKeyStatus;
 use anyhow::Context;
 use config::VisualBellTarget;
-use mux::pane::{PaneId, WithPaneLines};
+use mux::highlight::PaneHighlights;
+use mux::pane::WithPaneLines;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::PositionedPane;
-use ordered_float::NotNan;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::Arc;
 use std::time::Instant;
+use termwiz::color::AnsiColor;
+use wezterm_bidi::Direction;
 use wezterm_dynamic::Value;
 use wezterm_term::color::{ColorAttribute, ColorPalette};
-use wezterm_term::{Line, StableRowIndex};
+use wezterm_term::{CommandStatus, Line, StableRowIndex};
 use window::color::LinearRgba;
 
 impl crate::TermWindow {
@@ -62,17 +71,10 @@ impl crate::TermWindow {
 
         let (padding_left, padding_top) = self.padding_left_top();
 
-        let tab_bar_height = if self.show_tab_bar {
-            self.tab_bar_pixel_height()
-                .context("tab_bar_pixel_height")?
-        } else {
-            0.
-        };
-        let (top_bar_height, bottom_bar_height) = if self.config.tab_bar_at_bottom {
-            (0.0, tab_bar_height)
-        } else {
-            (tab_bar_height, 0.0)
-        };
+        let tab_bar = self
+            .tab_bar_reserved_edges()
+            .context("tab_bar_reserved_edges")?;
+        let (top_bar_height, bottom_bar_height) = (tab_bar.top, tab_bar.bottom);
 
         let border = self.get_os_border();
         let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
@@ -111,12 +113,12 @@ impl crate::TermWindow {
             // We want to fill out to the edges of the splits
             let (x, width_delta) = if pos.left == 0 {
                 (
-                    0.,
+                    tab_bar.left,
                     padding_left + border.left.get() as f32 + (cell_width / 2.0),
                 )
             } else {
                 (
-                    padding_left + border.left.get() as f32 - (cell_width / 2.0)
+                    tab_bar.left + padding_left + border.left.get() as f32 - (cell_width / 2.0)
                         + (pos.left as f32 * cell_width),
                     cell_width,
                 )
@@ -138,7 +140,7 @@ impl crate::TermWindow {
                 y,
                 // Go all the way to the right edge if we're right-most
                 if pos.left + pos.width >= self.terminal_size.cols as usize {
-                    self.dimensions.pixel_width as f32 - x
+                    self.dimensions.pixel_width as f32 - (x + tab_bar.right)
                 } else {
                     (pos.width as f32 * cell_width) + width_delta
                 },
@@ -221,6 +223,67 @@ impl crate::TermWindow {
             }
         }
 
+        // Draw the column guides beneath the text, unless the pane is
+        // using the alternate screen, where the application is in
+        // control of the whole display and usually draws its own
+        if !pos.pane.is_alt_screen_active() {
+            let guides = pos
+                .pane
+                .column_guides()
+                .and_then(|guides| guides.lock().clone())
+                .unwrap_or_else(|| config.column_guides.clone());
+            let left_pixel_x = tab_bar.left
+                + padding_left
+                + border.left.get() as f32
+                + (pos.left as f32 * cell_width);
+            let top = top_pixel_y + (pos.top as f32 * cell_height);
+            let thickness = (self.dimensions.dpi as f32 / 96.).max(1.).floor();
+            for guide in guides {
+                if guide.column == 0 || guide.column >= pos.width {
+                    continue;
+                }
+                let mut quad = self
+                    .filled_rectangle(
+                        layers,
+                        0,
+                        euclid::rect(
+                            left_pixel_x + (guide.column as f32 * cell_width),
+                            top,
+                            thickness,
+                            pos.height as f32 * cell_height,
+                        ),
+                        guide.color.to_linear(),
+                    )
+                    .context("filled_rectangle")?;
+                quad.set_hsv(if pos.is_active {
+                    None
+                } else {
+                    Some(config.inactive_pane_hsb)
+                });
+            }
+        }
+
+        if self
+            .broadcast_members()
+            .iter()
+            .any(|p| p.pane_id() == pane_id)
+        {
+            // Outline panes that are receiving broadcast input
+            let color = config.broadcast_input_border_color.to_linear();
+            let thickness = (self.dimensions.dpi as f32 / 96.).max(1.) * 2.;
+            let (x, y) = (background_rect.min_x(), background_rect.min_y());
+            let (width, height) = (background_rect.width(), background_rect.height());
+            for rect in [
+                euclid::rect(x, y, width, thickness),
+                euclid::rect(x, y + height - thickness, width, thickness),
+                euclid::rect(x, y, thickness, height),
+                euclid::rect(x + width - thickness, y, thickness, height),
+            ] {
+                self.filled_rectangle(layers, 2, rect, color)
+                    .context("filled_rectangle")?;
+            }
+        }
+
         // TODO: we only have a single scrollbar in a single position.
         // We only update it for the active pane, but we should probably
         // do a per-pane scrollbar.  That will require more extensive
@@ -231,12 +294,14 @@ impl crate::TermWindow {
 
             let min_height = self.min_scroll_bar_height();
 
+            let max_thumb_height = self
+                .dimensions
+                .pixel_height
+                .saturating_sub(thumb_y_offset + border.bottom.get() + bottom_bar_height as usize);
             let info = ScrollHit::thumb(
                 &*pos.pane,
                 current_viewport,
-                self.dimensions.pixel_height.saturating_sub(
-                    thumb_y_offset + border.bottom.get() + bottom_bar_height as usize,
-                ),
+                max_thumb_height,
                 min_height as usize,
             );
             let abs_thumb_top = thumb_y_offset + info.top;
@@ -247,7 +312,10 @@ impl crate::TermWindow {
             let config = &self.config;
             let padding = self.effective_right_padding(&config) as f32;
 
-            let thumb_x = self.dimensions.pixel_width - padding as usize - border.right.get();
+            let thumb_x = self.dimensions.pixel_width
+                - padding as usize
+                - border.right.get()
+                - tab_bar.right as usize;
 
             // Register the scroll bar location
             self.ui_items.push(UIItem {
@@ -287,6 +355,69 @@ impl crate::TermWindow {
                 color,
             )
             .context("filled_rectangle")?;
+
+            // Mark the prompts of commands that have completed,
+            // colored according to their exit status
+            let marks: Vec<_> = self
+                .get_semantic_prompt_zones(&pos.pane)
+                .iter()
+                .filter_map(|mark| mark.status.map(|status| (mark.row, status)))
+                .collect();
+            let mark_height = (self.render_metrics.underline_height as f32 * 2.).max(2.);
+            for (row, status) in marks {
+                let color = match status {
+                    CommandStatus::Exited(0) => palette.colors.0[2].to_linear(),
+                    CommandStatus::Exited(_) => palette.colors.0[1].to_linear(),
+                    CommandStatus::Unknown => palette.foreground.to_linear().mul_alpha(0.5),
+                };
+                let y =
+                    thumb_y_offset + ScrollHit::row_to_offset(&*pos.pane, row, max_thumb_height);
+                self.filled_rectangle(
+                    layers,
+                    2,
+                    euclid::rect(thumb_x as f32, y as f32, padding, mark_height),
+                    color,
+                )
+                .context("filled_rectangle")?;
+            }
+
+            // Mark the positions of search matches; there may be very
+            // many of them, so only emit one tick per pixel row
+            if let Some(copy_overlay) = pos.pane.downcast_ref::<CopyOverlay>() {
+                let mut ticks: BTreeMap<usize, bool> = BTreeMap::new();
+                for (row, is_current) in copy_overlay.match_rows() {
+                    let y = thumb_y_offset
+                        + ScrollHit::row_to_offset(&*pos.pane, row, max_thumb_height);
+                    *ticks.entry(y).or_default() |= is_current;
+                }
+                let colors = &config.resolved_palette;
+                let inactive = palette
+                    .resolve_bg(
+                        colors
+                            .copy_mode_inactive_highlight_bg
+                            .unwrap_or(AnsiColor::Fuchsia.into())
+                            .into(),
+                    )
+                    .to_linear();
+                let active = palette
+                    .resolve_bg(
+                        colors
+                            .copy_mode_active_highlight_bg
+                            .unwrap_or(AnsiColor::Yellow.into())
+                            .into(),
+                    )
+                    .to_linear();
+                let tick_height = (self.render_metrics.underline_height as f32).max(1.);
+                for (y, is_current) in ticks {
+                    self.filled_rectangle(
+                        layers,
+                        2,
+                        euclid::rect(thumb_x as f32, y as f32, padding, tick_height),
+                        if is_current { active } else { inactive },
+                    )
+                    .context("filled_rectangle")?;
+                }
+            }
         }
 
         let (selrange, rectangular) = {
@@ -311,15 +442,23 @@ impl crate::TermWindow {
             pos.pane
                 .apply_hyperlinks(stable_range.clone(), &self.config.hyperlink_rules);
 
+            let highlights = pos.pane.highlights();
+            let highlight_generation = highlights
+                .as_ref()
+                .map(|h| h.lock().generation())
+                .unwrap_or(0);
+
             struct LineRender<'a, 'b> {
                 term_window: &'a mut crate::TermWindow,
                 selrange: Option<SelectionRange>,
                 rectangular: bool,
+                highlights: Option<Arc<Mutex<PaneHighlights>>>,
+                highlight_generation: usize,
+                line_cache: PaneLineCache,
                 dims: RenderableDimensions,
                 top_pixel_y: f32,
                 left_pixel_x: f32,
                 pos: &'a PositionedPane,
-                pane_id: PaneId,
                 cursor: &'a StableCursorPosition,
                 palette: &'a ColorPalette,
                 default_bg: LinearRgba,
@@ -337,19 +476,29 @@ impl crate::TermWindow {
                 error: Option<anyhow::Error>,
             }
 
-            let left_pixel_x = padding_left
+            let left_pixel_x = tab_bar.left
+                + padding_left
                 + border.left.get() as f32
                 + (pos.left as f32 * self.render_metrics.cell_size.width as f32);
 
+            let mut line_cache = std::mem::take(&mut self.pane_state(pane_id).line_cache);
+            line_cache.set_epoch(LineCacheEpoch {
+                config_generation: self.config.generation(),
+                shape_generation: self.shape_generation,
+                quad_generation: self.quad_generation,
+            });
+
             let mut render = LineRender {
                 term_window: self,
                 selrange,
                 rectangular,
+                highlights,
+                highlight_generation,
+                line_cache,
                 dims,
                 top_pixel_y,
                 left_pixel_x,
                 pos,
-                pane_id,
                 cursor: &cursor,
                 palette: &palette,
                 cursor_border_color,
@@ -373,6 +522,7 @@ impl crate::TermWindow {
                     stable_top: StableRowIndex,
                     line_idx: usize,
                     line: &&mut Line,
+                    bidi_direction: Option<Direction>,
                 ) -> anyhow::Result<()> {
                     let stable_row = stable_top + line_idx as StableRowIndex;
                     let selrange = self
@@ -381,7 +531,18 @@ impl crate::TermWindow {
                     // Constrain to the pane width!
                     let selrange = selrange.start..selrange.end.min(self.dims.cols);
 
-                    let (cursor, composing, password_input) = if self.cursor.y == stable_row {
+                    // The text being composed, placed over this line if it holds the cursor
+                    let composing = match (
+                        self.cursor.y == stable_row && self.pos.is_active,
+                        &self.term_window.dead_key_status,
+                    ) {
+                        (true, DeadKeyStatus::Composing(composing)) => {
+                            Some(PlacedPreedit::new(composing, self.cursor.x, self.dims.cols))
+                        }
+                        _ => None,
+                    };
+
+                    let (cursor, password_input) = if self.cursor.y == stable_row {
                         (
                             Some(CursorProperties {
                                 position: StableCursorPosition {
@@ -396,12 +557,6 @@ impl crate::TermWindow {
                                 cursor_border_color: self.cursor_border_color,
                                 cursor_is_default_color: self.cursor_is_default_color,
                             }),
-                            match (self.pos.is_active, &self.term_window.dead_key_status) {
-                                (true, DeadKeyStatus::Composing(composing)) => {
-                                    Some(composing.to_string())
-                                }
-                                _ => None,
-                            },
                             if self.term_window.config.detect_password_input {
                                 match self.pos.pane.get_metadata() {
                                     Value::Object(obj) => {
@@ -418,85 +573,113 @@ impl crate::TermWindow {
                             },
                         )
                     } else {
-                        (None, None, false)
+                        (None, false)
                     };
 
                     let shape_hash = self.term_window.shape_hash_for_line(line);
+                    let content = LineContent {
+                        seqno: line.current_seqno(),
+                        shape_hash,
+                    };
 
-                    let quad_key = LineQuadCacheKey {
-                        pane_id: self.pane_id,
+                    let key = LineRenderKey {
                         password_input,
                         pane_is_active: self.pos.is_active,
-                        config_generation: self.term_window.config.generation(),
-                        shape_generation: self.term_window.shape_generation,
-                        quad_generation: self.term_window.quad_generation,
                         composing: composing.clone(),
                         selection: selrange.clone(),
                         cursor,
-                        shape_hash,
-                        top_pixel_y: NotNan::new(self.top_pixel_y).unwrap()
+                        top_pixel_y: self.top_pixel_y
                             + (line_idx + self.pos.top) as f32
                                 * self.term_window.render_metrics.cell_size.height as f32,
-                        left_pixel_x: NotNan::new(self.left_pixel_x).unwrap(),
-                        phys_line_idx: line_idx,
+                        left_pixel_x: self.left_pixel_x,
                         reverse_video: self.dims.reverse_video,
+                        bidi_direction,
                     };
 
-                    if let Some(cached_quad) =
-                        self.term_window.line_quad_cache.borrow_mut().get(&quad_key)
-                    {
-                        let expired = cached_quad
-                            .expires
-                            .map(|i| Instant::now() >= i)
-                            .unwrap_or(false);
-                        let hover_changed = if cached_quad.invalidate_on_hover_change {
-                            !same_hyperlink(
-                                cached_quad.current_highlight.as_ref(),
-                                self.term_window.current_highlight.as_ref(),
-                            )
-                        } else {
-                            false
-                        };
-                        if !expired && !hover_changed {
-                            cached_quad
+                    // The persistent highlights are only computed if the
+                    // line isn't cached, or if they have changed since it was
+                    let mut highlights = None;
+                    let pane_highlights = &self.highlights;
+                    let compute_highlights = || -> Vec<(Range<usize>, LinearRgba)> {
+                        match pane_highlights {
+                            Some(h) => h
+                                .lock()
+                                .matches(line)
+                                .into_iter()
+                                .map(|(range, color)| (range, color.to_linear()))
+                                .collect(),
+                            None => vec![],
+                        }
+                    };
+
+                    let shaped = match self.line_cache.lookup(
+                        stable_row,
+                        content,
+                        &key,
+                        self.highlight_generation,
+                        || highlights.insert(compute_highlights()).clone(),
+                        self.term_window.current_highlight.as_ref(),
+                        Instant::now(),
+                    ) {
+                        LineCacheLookup::Hit(cached) => {
+                            cached
                                 .layers
                                 .apply_to(self.layers)
-                                .context("cached_quad.layers.apply_to")?;
-                            self.term_window.update_next_frame_time(cached_quad.expires);
+                                .context("cached.layers.apply_to")?;
+                            self.term_window.merge_next_frame(cached.expires);
+                            self.term_window
+                                .render_cache_stats
+                                .borrow_mut()
+                                .line_quads
+                                .hits += 1;
                             return Ok(());
                         }
-                    }
+                        LineCacheLookup::Relayout(relayout) => Some(relayout),
+                        LineCacheLookup::Miss { invalidated } => {
+                            if invalidated {
+                                self.term_window
+                                    .render_cache_stats
+                                    .borrow_mut()
+                                    .line_quads
+                                    .invalidated += 1;
+                            }
+                            None
+                        }
+                    };
+                    self.term_window
+                        .render_cache_stats
+                        .borrow_mut()
+                        .line_quads
+                        .misses += 1;
+
+                    let highlights = highlights.unwrap_or_else(compute_highlights);
 
                     let mut buf = HeapQuadAllocator::default();
-                    let next_due = self.term_window.has_animation.borrow_mut().take();
+                    let next_due = self.term_window.has_animation.take();
+                    if let Some(relayout) = &shaped {
+                        // Keep any animation of the glyph runs going
+                        self.term_window.merge_next_frame(relayout.expires);
+                    }
 
                     let shape_key = LineToEleShapeCacheKey {
                         shape_hash,
-                        shape_generation: quad_key.shape_generation,
-                        composing: if self.cursor.y == stable_row && self.pos.is_active {
-                            if let DeadKeyStatus::Composing(composing) =
-                                &self.term_window.dead_key_status
-                            {
-                                Some((self.cursor.x, composing.to_string()))
-                            } else {
-                                None
-                            }
-                        } else {
-                            None
-                        },
+                        shape_generation: self.term_window.shape_generation,
+                        composing,
+                        bidi_direction,
                     };
 
                     let render_result = self
                         .term_window
                         .render_screen_line(
                             RenderScreenLineParams {
-                                top_pixel_y: *quad_key.top_pixel_y,
+                                top_pixel_y: key.top_pixel_y,
                                 left_pixel_x: self.left_pixel_x,
                                 pixel_width: self.dims.cols as f32
                                     * self.term_window.render_metrics.cell_size.width as f32,
                                 stable_line_idx: Some(stable_row),
                                 line: &line,
                                 selection: selrange.clone(),
+                                highlights: &highlights,
                                 cursor: &self.cursor,
                                 palette: &self.palette,
                                 dims: &self.dims,
@@ -522,34 +705,43 @@ impl crate::TermWindow {
                                     .experimental_pixel_positioning,
                                 render_metrics: self.term_window.render_metrics,
                                 shape_key: Some(shape_key),
+                                shaped: shaped.as_ref().map(|relayout| Rc::clone(&relayout.shaped)),
                                 password_input,
+                                bidi_direction,
                             },
                             &mut TripleLayerQuadAllocator::Heap(&mut buf),
                         )
                         .context("render_screen_line")?;
 
-                    let expires = self.term_window.has_animation.borrow().as_ref().cloned();
-                    self.term_window.update_next_frame_time(next_due);
+                    let expires = *self.term_window.has_animation.borrow();
+                    self.term_window.merge_next_frame(next_due);
 
                     buf.apply_to(self.layers)
                         .context("HeapQuadAllocator::apply_to")?;
 
-                    let quad_value = LineQuadCacheValue {
-                        layers: buf,
-                        expires,
-                        line: (*line).clone(),
-                        invalidate_on_hover_change: render_result.invalidate_on_hover_change,
-                        current_highlight: if render_result.invalidate_on_hover_change {
-                            self.term_window.current_highlight.clone()
-                        } else {
-                            None
+                    let invalidate_on_hover_change = render_result.invalidate_on_hover_change
+                        || shaped
+                            .as_ref()
+                            .map_or(false, |relayout| relayout.invalidate_on_hover_change);
+
+                    self.line_cache.insert(
+                        stable_row,
+                        CachedLine {
+                            content,
+                            key,
+                            highlight_generation: self.highlight_generation,
+                            highlights,
+                            shaped: render_result.shaped,
+                            layers: buf,
+                            expires,
+                            invalidate_on_hover_change,
+                            current_highlight: if invalidate_on_hover_change {
+                                self.term_window.current_highlight.clone()
+                            } else {
+                                None
+                            },
                         },
-                    };
-
-                    self.term_window
-                        .line_quad_cache
-                        .borrow_mut()
-                        .put(quad_key, quad_value);
+                    );
 
                     Ok(())
                 }
@@ -557,8 +749,16 @@ impl crate::TermWindow {
 
             impl<'a, 'b> WithPaneLines for LineRender<'a, 'b> {
                 fn with_lines_mut(&mut self, stable_top: StableRowIndex, lines: &mut [&mut Line]) {
-                    for (line_idx, line) in lines.iter().enumerate() {
-                        if let Err(err) = self.render_line(stable_top, line_idx, line) {
+                    // A line that wraps from above the viewport is
+                    // considered from its first visible row
+                    let directions =
+                        Line::resolve_bidi_directions(lines.iter().map(|line| &**line));
+                    for ((line_idx, line), bidi_direction) in
+                        lines.iter().enumerate().zip(directions)
+                    {
+                        if let Err(err) =
+                            self.render_line(stable_top, line_idx, line, bidi_direction)
+                        {
                             self.error.replace(err);
                             return;
                         }
@@ -567,9 +767,52 @@ impl crate::TermWindow {
             }
 
             pos.pane.with_lines_mut(stable_range.clone(), &mut render);
-            if let Some(error) = render.error.take() {
+            let error = render.error.take();
+            let mut line_cache = render.line_cache;
+            line_cache.retain_rows(stable_range.clone());
+            self.pane_state(pane_id).line_cache = line_cache;
+            if let Some(error) = error {
                 return Err(error).context("error while calling with_lines_mut");
             }
+
+            // Mark the lines that have annotations with a bar at their
+            // left edge, tinting those that ask for it.  These are not
+            // part of the cached line quads, so that annotating a line
+            // doesn't require it to be rendered again.
+            if let Some(annotations) = pos.pane.annotations() {
+                let mut annotations = annotations.lock();
+                annotations.prune(dims.scrollback_top);
+                let cell_width = self.render_metrics.cell_size.width as f32;
+                let cell_height = self.render_metrics.cell_size.height as f32;
+                let marker_width = (cell_width / 4.).max(2.).floor();
+                for annotation in annotations.in_range(stable_range.clone()) {
+                    let color = annotation.color.to_linear();
+                    let y = top_pixel_y
+                        + ((annotation.line - stable_range.start) as usize + pos.top) as f32
+                            * cell_height;
+                    if annotation.tint {
+                        self.filled_rectangle(
+                            layers,
+                            0,
+                            euclid::rect(
+                                left_pixel_x,
+                                y,
+                                pos.width as f32 * cell_width,
+                                cell_height,
+                            ),
+                            color.mul_alpha(0.25),
+                        )
+                        .context("filled_rectangle")?;
+                    }
+                    self.filled_rectangle(
+                        layers,
+                        2,
+                        euclid::rect(left_pixel_x, y, marker_width, cell_height),
+                        color,
+                    )
+                    .context("filled_rectangle")?;
+                }
+            }
         }
 
         /*
@@ -589,16 +832,8 @@ impl crate::TermWindow {
         let cell_width = self.render_metrics.cell_size.width as f32;
         let cell_height = self.render_metrics.cell_size.height as f32;
         let (padding_left, padding_top) = self.padding_left_top();
-        let tab_bar_height = if self.show_tab_bar {
-            self.tab_bar_pixel_height()?
-        } else {
-            0.
-        };
-        let (top_bar_height, _bottom_bar_height) = if self.config.tab_bar_at_bottom {
-            (0.0, tab_bar_height)
-        } else {
-            (tab_bar_height, 0.0)
-        };
+        let tab_bar = self.tab_bar_reserved_edges()?;
+        let top_bar_height = tab_bar.top;
 
         let border = self.get_os_border();
         let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
@@ -606,12 +841,12 @@ impl crate::TermWindow {
         // We want to fill out to the edges of the splits
         let (x, width_delta) = if pos.left == 0 {
             (
-                0.,
+                tab_bar.left,
                 padding_left + border.left.get() as f32 + (cell_width / 2.0),
             )
         } else {
             (
-                padding_left + border.left.get() as f32 - (cell_width / 2.0)
+                tab_bar.left + padding_left + border.left.get() as f32 - (cell_width / 2.0)
                     + (pos.left as f32 * cell_width),
                 cell_width,
             )
@@ -634,7 +869,7 @@ impl crate::TermWindow {
             y,
             // Go all the way to the right edge if we're right-most
             if pos.left + pos.width >= self.terminal_size.cols as usize {
-                self.dimensions.pixel_width as f32 - x
+                self.dimensions.pixel_width as f32 - (x + tab_bar.right)
             } else {
                 (pos.width as f32 * cell_width) + width_delta
             },
@@ -648,7 +883,7 @@ impl crate::TermWindow {
 
         // Bounds for the terminal cells
         let content_rect = euclid::rect(
-            padding_left + border.left.get() as f32 - (cell_width / 2.0)
+            tab_bar.left + padding_left + border.left.get() as f32 - (cell_width / 2.0)
                 + (pos.left as f32 * cell_width),
             top_pixel_y + (pos.top as f32 * cell_height) - (cell_height / 2.0),
             pos.width as f32 * cell_width,
@@ -0,0 +1,316 @@
+use crate::customglyph::*;
+use crate::tabbar::compute_pane_title;
+use crate::termwindow::box_model::*;
+use crate::termwindow::render::floating::pane_element_zindex;
+use crate::termwindow::{TermWindow, UIItemType};
+use config::{Dimension, DimensionContext, TabBarColors};
+use mux::pane::Pane;
+use mux::tab::PositionedPane;
+use mux::Mux;
+use std::rc::Rc;
+use wezterm_font::units::IntPixelLength;
+use wezterm_font::LoadedFont;
+
+const CLOSE_BUTTON: &[Poly] = &[
+    Poly {
+        path: &[
+            PolyCommand::MoveTo(BlockCoord::One, BlockCoord::Zero),
+            PolyCommand::LineTo(BlockCoord::Zero, BlockCoord::One),
+        ],
+        intensity: BlockAlpha::Full,
+        style: PolyStyle::Outline,
+    },
+    Poly {
+        path: &[
+            PolyCommand::MoveTo(BlockCoord::Zero, BlockCoord::Zero),
+            PolyCommand::LineTo(BlockCoord::One, BlockCoord::One),
+        ],
+        intensity: BlockAlpha::Full,
+        style: PolyStyle::Outline,
+    },
+];
+
+const ZOOM_BUTTON: &[Poly] = &[Poly {
+    path: &[
+        PolyCommand::MoveTo(BlockCoord::Zero, BlockCoord::Zero),
+        PolyCommand::LineTo(BlockCoord::One, BlockCoord::Zero),
+        PolyCommand::LineTo(BlockCoord::One, BlockCoord::One),
+        PolyCommand::LineTo(BlockCoord::Zero, BlockCoord::One),
+        PolyCommand::LineTo(BlockCoord::Zero, BlockCoord::Zero),
+    ],
+    intensity: BlockAlpha::Full,
+    style: PolyStyle::Outline,
+}];
+
+/// While zoomed, the button shows a smaller box to suggest that
+/// clicking it will restore the pane to its usual size
+const UNZOOM_BUTTON: &[Poly] = &[Poly {
+    path: &[
+        PolyCommand::MoveTo(BlockCoord::Frac(1, 4), BlockCoord::Frac(1, 4)),
+        PolyCommand::LineTo(BlockCoord::Frac(3, 4), BlockCoord::Frac(1, 4)),
+        PolyCommand::LineTo(BlockCoord::Frac(3, 4), BlockCoord::Frac(3, 4)),
+        PolyCommand::LineTo(BlockCoord::Frac(1, 4), BlockCoord::Frac(3, 4)),
+        PolyCommand::LineTo(BlockCoord::Frac(1, 4), BlockCoord::Frac(1, 4)),
+    ],
+    intensity: BlockAlpha::Full,
+    style: PolyStyle::Outline,
+}];
+
+impl TermWindow {
+    /// Paints the title bar that sits above the terminal of the pane
+    /// at `pos`, when `pane_decorations` calls for one
+    pub fn paint_pane_title_bar(&mut self, pos: &PositionedPane) -> anyhow::Result<()> {
+        let decoration = match Mux::get().get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab.pane_decoration_rows(),
+            None => return Ok(()),
+        };
+        if decoration == 0 || pos.top < decoration || pos.is_floating {
+            // Either there are no decorations, this is an overlay
+            // that covers the whole tab, or a floating pane, which
+            // has a border instead
+            return Ok(());
+        }
+
+        let font = self.fonts.default_font()?;
+        let metrics = self.render_metrics;
+        let cell_width = metrics.cell_size.width as f32;
+        let cell_height = metrics.cell_size.height as f32;
+
+        let (padding_left, padding_top) = self.padding_left_top();
+        let tab_bar = self.tab_bar_reserved_edges()?;
+        let border = self.get_os_border();
+
+        // The title bar also spans the timestamps gutter
+        let gutter = pos.pane.timestamp_gutter_cols();
+        let bounds = euclid::rect(
+            tab_bar.left
+                + padding_left
+                + border.left.get() as f32
+                + ((pos.left - gutter) as f32 * cell_width),
+            tab_bar.top
+                + padding_top
+                + border.top.get() as f32
+                + ((pos.top - decoration) as f32 * cell_height),
+            (pos.width + gutter) as f32 * cell_width,
+            decoration as f32 * cell_height,
+        );
+
+        let pane_id = pos.pane.pane_id();
+        let hover = matches!(
+            self.last_ui_item.as_ref().map(|item| &item.item_type),
+            Some(UIItemType::PaneTitleBar(id)) if *id == pane_id
+        );
+        let domain_name = Mux::get()
+            .get_domain(pos.pane.domain_id())
+            .map(|domain| domain.domain_name().to_string())
+            .unwrap_or_default();
+        // Leave room for the buttons
+        let max_width = (pos.width + gutter).saturating_sub(5);
+        let title = compute_pane_title(
+            &Self::pos_pane_to_pane_info(pos),
+            &domain_name,
+            &self.config,
+            hover,
+            max_width,
+        );
+
+        let colors = self
+            .config
+            .colors
+            .as_ref()
+            .and_then(|c| c.tab_bar.as_ref())
+            .cloned()
+            .unwrap_or_else(TabBarColors::default);
+        let (normal, hovered) = if pos.is_active {
+            (colors.active_tab(), colors.inactive_tab_hover())
+        } else {
+            (colors.inactive_tab(), colors.inactive_tab_hover())
+        };
+        let bar_colors = ElementColors {
+            border: BorderColor::default(),
+            bg: normal.bg_color.to_linear().into(),
+            text: normal.fg_color.to_linear().into(),
+        };
+        let hover_colors = ElementColors {
+            border: BorderColor::default(),
+            bg: hovered.bg_color.to_linear().into(),
+            text: hovered.fg_color.to_linear().into(),
+        };
+
+        let palette = pos.pane.palette();
+        let children = vec![
+            Element::with_line(&font, &title, &palette)
+                .item_type(UIItemType::PaneTitleBar(pane_id))
+                .padding(BoxDimension {
+                    left: Dimension::Cells(0.5),
+                    right: Dimension::Cells(0.),
+                    top: Dimension::Cells(0.),
+                    bottom: Dimension::Cells(0.),
+                }),
+            make_button(
+                &font,
+                metrics.underline_height,
+                cell_height,
+                CLOSE_BUTTON,
+                UIItemType::ClosePane(pane_id),
+                &hover_colors,
+            ),
+            make_button(
+                &font,
+                metrics.underline_height,
+                cell_height,
+                if pos.is_zoomed {
+                    UNZOOM_BUTTON
+                } else {
+                    ZOOM_BUTTON
+                },
+                UIItemType::TogglePaneZoom(pane_id),
+                &hover_colors,
+            ),
+        ];
+
+        let element = Element::new(&font, ElementContent::Children(children))
+            .display(DisplayType::Block)
+            .item_type(UIItemType::PaneTitleBar(pane_id))
+            .colors(bar_colors)
+            .min_width(Some(Dimension::Pixels(bounds.width())))
+            .min_height(Some(Dimension::Pixels(bounds.height())));
+
+        let dimensions = self.dimensions;
+        let mut computed = self.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: bounds.height(),
+                    pixel_cell: cell_height,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: bounds.width(),
+                    pixel_cell: cell_width,
+                },
+                bounds: euclid::rect(0., 0., bounds.width(), bounds.height()),
+                metrics: &metrics,
+                gl_state: self.render_state.as_ref().unwrap(),
+                zindex: 1,
+            },
+            &element,
+        )?;
+        computed.translate(euclid::vec2(bounds.min_x(), bounds.min_y()));
+
+        let mut ui_items = computed.ui_items();
+        self.ui_items.append(&mut ui_items);
+
+        let gl_state = self.render_state.as_ref().unwrap();
+        self.render_element(&computed, gl_state, None)
+    }
+
+    /// Paints a badge in the top right corner of the pane at `pos`
+    /// while reading its output is paused by TogglePaneOutputPause
+    pub fn paint_pane_paused_indicator(&mut self, pos: &PositionedPane) -> anyhow::Result<()> {
+        const LABEL: &str = "PAUSED";
+        // The label plus half a cell of padding on either side
+        let badge_cells = LABEL.len() + 1;
+        if !pos.pane.is_output_paused() || pos.width < badge_cells {
+            return Ok(());
+        }
+
+        let font = self.fonts.default_font()?;
+        let metrics = self.render_metrics;
+        let cell_width = metrics.cell_size.width as f32;
+        let cell_height = metrics.cell_size.height as f32;
+
+        let (padding_left, padding_top) = self.padding_left_top();
+        let tab_bar = self.tab_bar_reserved_edges()?;
+        let border = self.get_os_border();
+
+        let bounds = euclid::rect(
+            tab_bar.left
+                + padding_left
+                + border.left.get() as f32
+                + ((pos.left + pos.width - badge_cells) as f32 * cell_width),
+            tab_bar.top + padding_top + border.top.get() as f32 + (pos.top as f32 * cell_height),
+            badge_cells as f32 * cell_width,
+            cell_height,
+        );
+
+        let colors = self
+            .config
+            .colors
+            .as_ref()
+            .and_then(|c| c.tab_bar.as_ref())
+            .cloned()
+            .unwrap_or_else(TabBarColors::default)
+            .active_tab();
+
+        let element = Element::new(&font, ElementContent::Text(LABEL.to_string()))
+            .colors(ElementColors {
+                border: BorderColor::default(),
+                bg: colors.bg_color.to_linear().into(),
+                text: colors.fg_color.to_linear().into(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.5),
+                right: Dimension::Cells(0.5),
+                top: Dimension::Cells(0.),
+                bottom: Dimension::Cells(0.),
+            });
+
+        let dimensions = self.dimensions;
+        let mut computed = self.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: bounds.height(),
+                    pixel_cell: cell_height,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: bounds.width(),
+                    pixel_cell: cell_width,
+                },
+                bounds: euclid::rect(0., 0., bounds.width(), bounds.height()),
+                metrics: &metrics,
+                gl_state: self.render_state.as_ref().unwrap(),
+                zindex: pane_element_zindex(pos),
+            },
+            &element,
+        )?;
+        computed.translate(euclid::vec2(bounds.min_x(), bounds.min_y()));
+
+        let gl_state = self.render_state.as_ref().unwrap();
+        self.render_element(&computed, gl_state, None)
+    }
+}
+
+fn make_button(
+    font: &Rc<LoadedFont>,
+    line_width: IntPixelLength,
+    cell_height: f32,
+    poly: &'static [Poly],
+    item_type: UIItemType,
+    hover_colors: &ElementColors,
+) -> Element {
+    Element::new(
+        font,
+        ElementContent::Poly {
+            line_width: line_width.max(2),
+            poly: SizedPoly {
+                poly,
+                width: Dimension::Pixels(cell_height / 2.),
+                height: Dimension::Pixels(cell_height / 2.),
+            },
+        },
+    )
+    .zindex(1)
+    .vertical_align(VerticalAlign::Middle)
+    .float(Float::Right)
+    .item_type(item_type)
+    .padding(BoxDimension {
+        left: Dimension::Cells(0.25),
+        right: Dimension::Cells(0.25),
+        top: Dimension::Cells(0.),
+        bottom: Dimension::Cells(0.),
+    })
+    .hover_colors(Some(hover_colors.clone()))
+}
@@ -0,0 +1,183 @@
+//! Places the text being composed by an input method over the line
+//! that holds the cursor.  The text is shown starting at the cursor,
+//! but is moved to the left as needed so that its end stays within
+//! the pane; if it is wider than the pane, only its end is shown.
+//! Each clause of the composition is underlined, and the selected
+//! clause is double underlined and covered by the cursor.
+use ::window::{Preedit, PreeditClause};
+use std::ops::Range;
+use termwiz::cell::{unicode_column_width, Underline};
+use termwiz::surface::SequenceNo;
+use unicode_segmentation::UnicodeSegmentation;
+use wezterm_term::{CellAttributes, Line};
+
+/// A preedit along with the column at which it is shown
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlacedPreedit {
+    pub start: usize,
+    pub preedit: Preedit,
+}
+
+impl PlacedPreedit {
+    /// Places `preedit`, which is composed at `cursor_x`, within
+    /// a pane that is `cols` columns wide
+    pub fn new(preedit: &Preedit, cursor_x: usize, cols: usize) -> Self {
+        let width = unicode_column_width(&preedit.text, None);
+        if width <= cols {
+            return Self {
+                start: cursor_x.min(cols - width),
+                preedit: preedit.clone(),
+            };
+        }
+
+        // Drop graphemes from the start until the remainder fits
+        let mut skip = 0;
+        let mut remaining = width;
+        for grapheme in preedit.text.graphemes(true) {
+            if remaining <= cols {
+                break;
+            }
+            skip += grapheme.len();
+            remaining -= unicode_column_width(grapheme, None);
+        }
+
+        let clauses = preedit
+            .clauses
+            .iter()
+            .filter(|clause| clause.range.end > skip)
+            .map(|clause| PreeditClause {
+                range: clause.range.start.saturating_sub(skip)..clause.range.end - skip,
+                selected: clause.selected,
+            })
+            .collect();
+
+        Self {
+            start: 0,
+            preedit: Preedit {
+                text: preedit.text[skip..].to_string(),
+                clauses,
+            },
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        unicode_column_width(&self.preedit.text, None)
+    }
+
+    /// The columns occupied by the preedit
+    pub fn columns(&self) -> Range<usize> {
+        self.start..self.start + self.width()
+    }
+
+    fn clause_columns(&self, clause: &PreeditClause) -> Range<usize> {
+        let text = &self.preedit.text;
+        let start = self.start + unicode_column_width(&text[..clause.range.start], None);
+        start..start + unicode_column_width(&text[clause.range.clone()], None)
+    }
+
+    /// The columns to be covered by the cursor: the selected clause
+    /// if there is one, otherwise the whole preedit
+    pub fn cursor_columns(&self) -> Range<usize> {
+        self.preedit
+            .clauses
+            .iter()
+            .find(|clause| clause.selected)
+            .map(|clause| self.clause_columns(clause))
+            .unwrap_or_else(|| self.columns())
+    }
+
+    /// Overlays the preedit onto `line`
+    pub fn overlay(&self, line: &mut Line, seqno: SequenceNo) {
+        let text = &self.preedit.text;
+        line.overlay_text_with_attribute(self.start, text, CellAttributes::blank(), seqno);
+
+        for clause in &self.preedit.clauses {
+            let mut attr = CellAttributes::blank();
+            attr.set_underline(if clause.selected {
+                Underline::Double
+            } else {
+                Underline::Single
+            });
+            line.overlay_text_with_attribute(
+                self.clause_columns(clause).start,
+                &text[clause.range.clone()],
+                attr,
+                seqno,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn placed_at_cursor() {
+        let preedit = Preedit::composition("かな".to_string());
+        let placed = PlacedPreedit::new(&preedit, 3, 80);
+        assert_eq!(placed.start, 3);
+        assert_eq!(placed.columns(), 3..7);
+        assert_eq!(placed.cursor_columns(), 3..7);
+    }
+
+    #[test]
+    fn moved_left_at_right_edge() {
+        let preedit = Preedit::with_selection("かな漢字".to_string(), 3..9);
+        let placed = PlacedPreedit::new(&preedit, 8, 10);
+        assert_eq!(placed.start, 2);
+        assert_eq!(placed.columns(), 2..10);
+        // The selected clause is the second and third characters
+        assert_eq!(placed.cursor_columns(), 4..8);
+    }
+
+    #[test]
+    fn wider_than_pane_shows_end() {
+        let preedit = Preedit::with_selection("abcdefgh".to_string(), 1..3);
+        let placed = PlacedPreedit::new(&preedit, 2, 5);
+        assert_eq!(placed.start, 0);
+        assert_eq!(placed.preedit.text, "defgh");
+        // The selected clause scrolled out of view entirely
+        assert_eq!(
+            placed.preedit.clauses,
+            vec![PreeditClause {
+                range: 0..5,
+                selected: false
+            }]
+        );
+        assert_eq!(placed.cursor_columns(), 0..5);
+    }
+
+    #[test]
+    fn clauses_are_underlined() {
+        let preedit = Preedit::with_selection("abc".to_string(), 1..2);
+        let placed = PlacedPreedit::new(&preedit, 0, 10);
+        let mut line = Line::from_text("xyz1234", &CellAttributes::blank(), 1, None);
+        placed.overlay(&mut line, 2);
+
+        let underlines: Vec<(String, Underline)> = line
+            .visible_cells()
+            .take(4)
+            .map(|cell| (cell.str().to_string(), cell.attrs().underline()))
+            .collect();
+        assert_eq!(
+            underlines,
+            vec![
+                ("a".to_string(), Underline::Single),
+                ("b".to_string(), Underline::Double),
+                ("c".to_string(), Underline::Single),
+                ("1".to_string(), Underline::None),
+            ]
+        );
+    }
+
+    #[test]
+    fn dead_key_is_not_styled() {
+        let placed = PlacedPreedit::new(&"^".to_string().into(), 0, 10);
+        let mut line = Line::from_text("x", &CellAttributes::blank(), 1, None);
+        placed.overlay(&mut line, 2);
+        let cell = line.visible_cells().next().unwrap();
+        assert_eq!(cell.str(), "^");
+        assert_eq!(cell.attrs().underline(), Underline::None);
+    }
+}
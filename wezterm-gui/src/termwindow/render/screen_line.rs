@@ -1,8 +1,10 @@
 use crate::quad::{QuadTrait, TripleLayerQuadAllocator, TripleLayerQuadAllocatorTrait};
+use crate::termwindow::animation::{AnimationSources, NextFrame};
+use crate::termwindow::render::preedit::PlacedPreedit;
 use crate::termwindow::render::{
-    resolve_fg_color_attr, same_hyperlink, update_next_frame_time, ClusterStyleCache,
-    ComputeCellFgBgParams, ComputeCellFgBgResult, LineToElementParams, LineToElementShape,
-    RenderScreenLineParams, RenderScreenLineResult,
+    resolve_fg_color_attr, same_hyperlink, ClusterStyleCache, ComputeCellFgBgParams,
+    ComputeCellFgBgResult, LineToElementParams, LineToElementShape, RenderScreenLineParams,
+    RenderScreenLineResult,
 };
 use crate::termwindow::LineToElementShapeItem;
 use ::window::DeadKeyStatus;
@@ -11,10 +13,11 @@ use config::{HsbTransform, TextStyle};
 use std::ops::Range;
 use std::rc::Rc;
 use std::time::Instant;
-use termwiz::cell::{unicode_column_width, Blink};
+use termwiz::cell::Blink;
+use termwiz::cellcluster::VisualColumnMap;
 use termwiz::color::LinearRgba;
 use termwiz::surface::CursorShape;
-use wezterm_bidi::Direction;
+use wezterm_bidi::{Direction, ParagraphDirectionHint};
 use wezterm_term::color::ColorAttribute;
 use wezterm_term::CellAttributes;
 
@@ -34,6 +37,7 @@ impl crate::TermWindow {
             // rendering the top row, so we have nothing more to do here.
             return Ok(RenderScreenLineResult {
                 invalidate_on_hover_change: false,
+                shaped: None,
             });
         }
 
@@ -63,6 +67,7 @@ impl crate::TermWindow {
         let cell_height = params.render_metrics.cell_size.height as f32 * height_scale;
         let pos_y = (self.dimensions.pixel_height as f32 / -2.) + params.top_pixel_y;
         let gl_x = self.dimensions.pixel_width as f32 / -2.;
+        let snap = self.pixel_snap();
 
         let start = Instant::now();
 
@@ -78,7 +83,7 @@ impl crate::TermWindow {
         // Referencing the text being composed, but only if it belongs to this pane
         let composing = if cursor_idx.is_some() {
             if let DeadKeyStatus::Composing(composing) = &self.dead_key_status {
-                Some(composing)
+                Some(PlacedPreedit::new(composing, params.cursor.x, num_cols))
             } else {
                 None
             }
@@ -86,15 +91,10 @@ impl crate::TermWindow {
             None
         };
 
-        let mut composition_width = 0;
-
-        let (_bidi_enabled, bidi_direction) = params.line.bidi_info();
-        let direction = bidi_direction.direction();
-
-        // Do we need to shape immediately, or can we use the pre-shaped data?
-        if let Some(composing) = composing {
-            composition_width = unicode_column_width(composing, None);
-        }
+        let bidi_direction = params
+            .bidi_direction
+            .or_else(|| params.line.paragraph_direction());
+        let direction = bidi_direction.unwrap_or(Direction::LeftToRight);
 
         let cursor_cell = if params.stable_line_idx == Some(params.cursor.y) {
             params.line.get_cell(params.cursor.x)
@@ -102,24 +102,23 @@ impl crate::TermWindow {
             None
         };
 
-        let cursor_range = if composition_width > 0 {
-            params.cursor.x..params.cursor.x + composition_width
+        let cursor_range = if let Some(composing) = composing.as_ref().filter(|c| c.width() > 0) {
+            composing.cursor_columns()
         } else if params.stable_line_idx == Some(params.cursor.y) {
             params.cursor.x..params.cursor.x + cursor_cell.as_ref().map(|c| c.width()).unwrap_or(1)
         } else {
             0..0
         };
 
-        let cursor_range_pixels = params.left_pixel_x + cursor_range.start as f32 * cell_width
-            ..params.left_pixel_x + cursor_range.end as f32 * cell_width;
-
-        let mut shaped = None;
+        let mut shaped = params.shaped.clone();
         let mut invalidate_on_hover_change = false;
 
-        if let Some(shape_key) = &params.shape_key {
+        if shaped.is_some() {
+            self.render_cache_stats.borrow_mut().line_shapes.hits += 1;
+        } else if let Some(shape_key) = &params.shape_key {
             let mut cache = self.line_to_ele_shape_cache.borrow_mut();
             if let Some(entry) = cache.get(shape_key) {
-                let expired = entry.expires.map(|i| Instant::now() >= i).unwrap_or(false);
+                let expired = entry.expires.is_expired(Instant::now());
                 let hover_changed = if entry.invalidate_on_hover_change {
                     !same_hyperlink(
                         entry.current_highlight.as_ref(),
@@ -130,12 +129,21 @@ impl crate::TermWindow {
                 };
 
                 if !expired && !hover_changed {
-                    self.update_next_frame_time(entry.expires);
+                    self.merge_next_frame(entry.expires);
                     shaped.replace(Rc::clone(&entry.shaped));
+                } else {
+                    self.render_cache_stats.borrow_mut().line_shapes.invalidated += 1;
                 }
 
                 invalidate_on_hover_change = entry.invalidate_on_hover_change;
             }
+
+            let mut stats = self.render_cache_stats.borrow_mut();
+            if shaped.is_some() {
+                stats.line_shapes.hits += 1;
+            } else {
+                stats.line_shapes.misses += 1;
+            }
         }
 
         let shaped = if let Some(shaped) = shaped {
@@ -150,6 +158,7 @@ impl crate::TermWindow {
                 window_is_transparent: params.window_is_transparent,
                 reverse_video: params.dims.reverse_video,
                 shape_key: &params.shape_key,
+                bidi_direction,
             };
 
             let (shaped, invalidate_on_hover) = self.build_line_element_shape(params)?;
@@ -164,12 +173,35 @@ impl crate::TermWindow {
             cell_height,
         );
 
-        fn phys(x: usize, num_cols: usize, direction: Direction) -> usize {
+        // Where each cell is displayed once bidi has reordered the line;
+        // the cursor, selection and highlights are all positioned by
+        // their logical columns
+        let columns = match bidi_direction {
+            Some(direction) => {
+                VisualColumnMap::new(shaped.iter().map(|item| &item.cluster), direction, num_cols)
+            }
+            None => VisualColumnMap::default(),
+        };
+
+        // The clusters of a RTL paragraph are aligned with the right edge
+        let line_x_offset = if params.use_pixel_positioning {
             match direction {
-                Direction::LeftToRight => x,
-                Direction::RightToLeft => num_cols - x,
+                Direction::LeftToRight => 0.,
+                Direction::RightToLeft => (params.pixel_width
+                    - shaped.iter().map(|item| item.pixel_width).sum::<f32>())
+                .max(0.),
             }
-        }
+        } else {
+            columns.first_column() as f32 * cell_width
+        };
+
+        let cursor_range = columns
+            .visual_ranges(cursor_range)
+            .into_iter()
+            .next()
+            .unwrap_or(0..0);
+        let cursor_range_pixels = params.left_pixel_x + cursor_range.start as f32 * cell_width
+            ..params.left_pixel_x + cursor_range.end as f32 * cell_width;
 
         if params.dims.reverse_video {
             let mut quad = self
@@ -230,32 +262,40 @@ impl crate::TermWindow {
             };
 
             if !bg_is_default {
-                let x = params.left_pixel_x
-                    + if params.use_pixel_positioning {
-                        item.x_pos
-                    } else {
-                        phys(cluster.first_cell_idx, num_cols, direction) as f32 * cell_width
-                    };
-
-                let mut width = if params.use_pixel_positioning {
-                    item.pixel_width
+                let spans = if params.use_pixel_positioning {
+                    vec![(line_x_offset + item.x_pos, item.pixel_width)]
                 } else {
-                    cluster_width as f32 * cell_width
+                    columns
+                        .visual_ranges(
+                            cluster.first_cell_idx..cluster.first_cell_idx + cluster_width,
+                        )
+                        .into_iter()
+                        .map(|range| {
+                            (
+                                range.start as f32 * cell_width,
+                                range.len() as f32 * cell_width,
+                            )
+                        })
+                        .collect()
                 };
 
-                // If the tab bar is falling just short of the full width of the
-                // window, extend it to fit.
-                // <https://github.com/wez/wezterm/issues/2210>
-                if is_tab_bar && (x + width + cell_width) > params.pixel_width {
-                    width += cell_width;
-                }
+                for (x, mut width) in spans {
+                    let x = params.left_pixel_x + x;
 
-                let rect = euclid::rect(x, params.top_pixel_y, width, cell_height);
-                if let Some(rect) = rect.intersection(&bounding_rect) {
-                    let mut quad = self
-                        .filled_rectangle(layers, 0, rect, bg_color)
-                        .context("filled_rectangle")?;
-                    quad.set_hsv(hsv);
+                    // If the tab bar is falling just short of the full width of the
+                    // window, extend it to fit.
+                    // <https://github.com/wez/wezterm/issues/2210>
+                    if is_tab_bar && (x + width + cell_width) > params.pixel_width {
+                        width += cell_width;
+                    }
+
+                    let rect = euclid::rect(x, params.top_pixel_y, width, cell_height);
+                    if let Some(rect) = rect.intersection(&bounding_rect) {
+                        let mut quad = self
+                            .filled_rectangle(layers, 0, rect, bg_color)
+                            .context("filled_rectangle")?;
+                        quad.set_hsv(hsv);
+                    }
                 }
             }
 
@@ -268,13 +308,17 @@ impl crate::TermWindow {
                     let x = gl_x
                         + params.left_pixel_x
                         + if params.use_pixel_positioning {
-                            item.x_pos
+                            line_x_offset + item.x_pos
                         } else {
-                            phys(cluster.first_cell_idx + i, num_cols, direction) as f32
-                                * cell_width
+                            columns.visual_column(cluster.first_cell_idx + i) as f32 * cell_width
                         };
 
-                    quad.set_position(x, pos_y, x + cell_width, pos_y + cell_height);
+                    quad.set_position(
+                        snap.x(x),
+                        snap.y(pos_y),
+                        snap.x(x + cell_width),
+                        snap.y(pos_y + cell_height),
+                    );
                     quad.set_hsv(hsv);
                     quad.set_has_color(false);
                     quad.set_texture(item.underline_tex_rect);
@@ -283,12 +327,33 @@ impl crate::TermWindow {
             }
         }
 
+        // Render the persistent highlights; these are drawn beneath
+        // the selection so that the selection remains visible.
+        for (range, color) in params.highlights {
+            let range = range.start..range.end.min(params.dims.cols);
+            for range in columns.visual_ranges(range) {
+                let start = params.left_pixel_x + (range.start as f32 * cell_width);
+                let width = range.len() as f32 * cell_width;
+                let mut quad = self
+                    .filled_rectangle(
+                        layers,
+                        0,
+                        euclid::rect(start, params.top_pixel_y, width, cell_height),
+                        *color,
+                    )
+                    .context("filled_rectangle")?;
+
+                quad.set_hsv(hsv);
+            }
+        }
+
         // Render the selection background color.
-        // This always uses a physical x position, regardles of the line
-        // direction.
-        let selection_pixel_range = if !params.selection.is_empty() {
-            let start = params.left_pixel_x + (params.selection.start as f32 * cell_width);
-            let width = (params.selection.end - params.selection.start) as f32 * cell_width;
+        // The selection covers logical columns, which may be displayed
+        // in several pieces on a line that has been reordered by bidi.
+        let mut selection_pixel_ranges = vec![];
+        for range in columns.visual_ranges(params.selection.clone()) {
+            let start = params.left_pixel_x + (range.start as f32 * cell_width);
+            let width = range.len() as f32 * cell_width;
             let mut quad = self
                 .filled_rectangle(
                     layers,
@@ -300,10 +365,8 @@ impl crate::TermWindow {
 
             quad.set_hsv(hsv);
 
-            start..start + width
-        } else {
-            0.0..0.0
-        };
+            selection_pixel_ranges.push(start..start + width);
+        }
 
         // Consider cursor
         if !cursor_range.is_empty() {
@@ -348,7 +411,7 @@ impl crate::TermWindow {
             });
             let pos_x = (self.dimensions.pixel_width as f32 / -2.)
                 + params.left_pixel_x
-                + (phys(params.cursor.x, num_cols, direction) as f32 * cell_width);
+                + (cursor_range.start as f32 * cell_width);
 
             if let Some(shape) = cursor_shape {
                 let cursor_layer = match shape {
@@ -391,7 +454,13 @@ impl crate::TermWindow {
                                 * height_scale;
 
                         let pos_x = pos_x + (glyph.x_offset + glyph.bearing_x).get() as f32;
-                        quad.set_position(pos_x, pos_y, pos_x + width, pos_y + height);
+                        let (dx, dy) = snap.offset(pos_x, pos_y);
+                        quad.set_position(
+                            pos_x + dx,
+                            pos_y + dy,
+                            pos_x + dx + width,
+                            pos_y + dy + height,
+                        );
                         quad.set_texture(sprite.texture_coords());
                         draw_basic = false;
                     }
@@ -399,10 +468,10 @@ impl crate::TermWindow {
 
                 if draw_basic {
                     quad.set_position(
-                        pos_x,
-                        pos_y,
-                        pos_x + (cursor_range.end - cursor_range.start) as f32 * cell_width,
-                        pos_y + cell_height,
+                        snap.x(pos_x),
+                        snap.y(pos_y),
+                        snap.x(pos_x + (cursor_range.end - cursor_range.start) as f32 * cell_width),
+                        snap.y(pos_y + cell_height),
                     );
                     quad.set_texture(
                         gl_state
@@ -424,13 +493,10 @@ impl crate::TermWindow {
 
         let mut overlay_images = vec![];
 
-        // Number of cells we've rendered, starting from the edge of the line
-        let mut visual_cell_idx = 0;
+        // The visual column of the cell that we are rendering
+        let mut visual_cell_idx = columns.first_column();
 
-        let mut cluster_x_pos = match direction {
-            Direction::LeftToRight => 0.,
-            Direction::RightToLeft => params.pixel_width,
-        };
+        let mut cluster_x_pos = line_x_offset;
 
         for item in shaped.iter() {
             let cluster = &item.cluster;
@@ -446,20 +512,6 @@ impl crate::TermWindow {
                 }
             };
 
-            // TODO: remember logical/visual mapping for selection
-            #[allow(unused_variables)]
-            let mut phys_cell_idx = cluster.first_cell_idx;
-
-            // Pre-decrement by the cluster width when doing RTL,
-            // so that we can render it right-justified
-            if direction == Direction::RightToLeft {
-                cluster_x_pos -= if params.use_pixel_positioning {
-                    item.pixel_width
-                } else {
-                    cluster.width as f32 * cell_width
-                };
-            }
-
             for info in glyph_info.iter() {
                 let glyph = &info.glyph;
 
@@ -587,22 +639,43 @@ impl crate::TermWindow {
                         let adjust = (glyph.x_offset + glyph.bearing_x).get() as f32;
                         let texture_range = pos_x + adjust
                             ..pos_x + adjust + (texture.coords.size.width as f32 * width_scale);
+                        // All of the strips of the glyph move by the same amount,
+                        // so that they stay joined
+                        let (snap_x, snap_y) = snap.offset(gl_x + texture_range.start, pos_y + top);
+
+                        /// Sub-divides `r` at the edges of each of `ranges`
+                        fn split_by(r: Range<f32>, ranges: &[Range<f32>]) -> Vec<Range<f32>> {
+                            let mut strips = vec![r];
+                            for within in ranges {
+                                strips = strips
+                                    .into_iter()
+                                    .flat_map(|strip| {
+                                        let (left, mid, right) = range3(&strip, within);
+                                        vec![left, mid, right]
+                                    })
+                                    .collect();
+                            }
+                            strips
+                        }
 
                         // First bucket the ranges according to cursor position
                         let (left, mid, right) = range3(&texture_range, &cursor_range_pixels);
                         // Then sub-divide the non-cursor ranges according to selection
-                        let (la, lb, lc) = range3(&left, &selection_pixel_range);
-                        let (ra, rb, rc) = range3(&right, &selection_pixel_range);
+                        let mut strips = split_by(left, &selection_pixel_ranges);
+                        strips.push(mid);
+                        strips.extend(split_by(right, &selection_pixel_ranges));
 
                         // and render each of these strips
-                        for range in [la, lb, lc, mid, ra, rb, rc] {
+                        for range in strips {
                             if range.is_empty() {
                                 continue;
                             }
 
                             let is_cursor = cursor_range_pixels.contains(&range.start);
-                            let selected =
-                                !is_cursor && selection_pixel_range.contains(&range.start);
+                            let selected = !is_cursor
+                                && selection_pixel_ranges
+                                    .iter()
+                                    .any(|selection| selection.contains(&range.start));
 
                             let ComputeCellFgBgResult {
                                 fg_color: glyph_color,
@@ -643,10 +716,13 @@ impl crate::TermWindow {
 
                             let mut quad = layers.allocate(1).context("layers.allocate(1)")?;
                             quad.set_position(
-                                gl_x + range.start,
-                                pos_y + top,
-                                gl_x + range.end,
-                                pos_y + top + texture.coords.size.height as f32 * height_scale,
+                                gl_x + range.start + snap_x,
+                                pos_y + top + snap_y,
+                                gl_x + range.end + snap_x,
+                                pos_y
+                                    + top
+                                    + snap_y
+                                    + texture.coords.size.height as f32 * height_scale,
                             );
                             quad.set_fg_color(glyph_color);
                             quad.set_alt_color_and_mix_value(fg_color_alt, fg_color_mix);
@@ -676,7 +752,6 @@ impl crate::TermWindow {
                         }
                     }
                 }
-                phys_cell_idx += info.pos.num_cells as usize;
                 visual_cell_idx += info.pos.num_cells as usize;
                 cluster_x_pos += if params.use_pixel_positioning {
                     glyph.x_advance.get() as f32 * width_scale
@@ -684,18 +759,6 @@ impl crate::TermWindow {
                     info.pos.num_cells as f32 * cell_width
                 };
             }
-
-            match direction {
-                Direction::RightToLeft => {
-                    // And decrement it again
-                    cluster_x_pos -= if params.use_pixel_positioning {
-                        item.pixel_width * width_scale
-                    } else {
-                        cluster.width as f32 * cell_width
-                    };
-                }
-                Direction::LeftToRight => {}
-            }
         }
 
         for (cell_idx, img, glyph_color) in overlay_images {
@@ -704,7 +767,7 @@ impl crate::TermWindow {
                 gl_state,
                 layers,
                 2,
-                phys(cell_idx, num_cols, direction),
+                cell_idx,
                 &params,
                 hsv,
                 glyph_color,
@@ -716,6 +779,7 @@ impl crate::TermWindow {
 
         Ok(RenderScreenLineResult {
             invalidate_on_hover_change,
+            shaped: Some(shaped),
         })
     }
 
@@ -723,29 +787,23 @@ impl crate::TermWindow {
         &self,
         params: LineToElementParams,
     ) -> anyhow::Result<(Rc<Vec<LineToElementShape>>, bool)> {
-        let (bidi_enabled, bidi_direction) = params.line.bidi_info();
-        let bidi_hint = if bidi_enabled {
-            Some(bidi_direction)
-        } else {
-            None
-        };
-        let cell_clusters = if let Some((cursor_x, composing)) =
-            params.shape_key.as_ref().and_then(|k| k.composing.as_ref())
-        {
-            // Create an updated line with the composition overlaid
-            let mut line = params.line.clone();
-            let seqno = line.current_seqno();
-            line.overlay_text_with_attribute(*cursor_x, &composing, CellAttributes::blank(), seqno);
-            line.cluster(bidi_hint)
-        } else {
-            params.line.cluster(bidi_hint)
-        };
+        let bidi_hint = params.bidi_direction.map(ParagraphDirectionHint::from);
+        let cell_clusters =
+            if let Some(composing) = params.shape_key.as_ref().and_then(|k| k.composing.as_ref()) {
+                // Create an updated line with the composition overlaid
+                let mut line = params.line.clone();
+                let seqno = line.current_seqno();
+                composing.overlay(&mut line, seqno);
+                line.cluster(bidi_hint)
+            } else {
+                params.line.cluster(bidi_hint)
+            };
 
         let gl_state = self.render_state.as_ref().unwrap();
         let mut shaped = vec![];
         let mut last_style = None;
         let mut x_pos = 0.;
-        let mut expires = None;
+        let mut expires = NextFrame::default();
         let mut invalidate_on_hover_change = false;
 
         for cluster in &cell_clusters {
@@ -792,6 +850,19 @@ impl crate::TermWindow {
                         bg_default = false;
                     }
 
+                    // Ensure that the text is legible against its background.
+                    // This only affects what we draw; the palette reported
+                    // to applications is unchanged.
+                    if let Some(ratio) = params.config.minimum_contrast_ratio {
+                        let is_default_pair = attrs.foreground() == ColorAttribute::Default
+                            && attrs.background() == ColorAttribute::Default;
+                        if !(is_default_pair
+                            && params.config.minimum_contrast_ignores_default_colors)
+                        {
+                            fg = self.contrast_cache.borrow_mut().adjust(fg, bg, ratio);
+                        }
+                    }
+
                     // Check for blink, and if this is the "not-visible"
                     // part of blinking then set fg = bg.  This is a cheap
                     // means of getting it done without impacting other
@@ -819,8 +890,9 @@ impl crate::TermWindow {
                                 a,
                             );
 
-                            update_next_frame_time(&mut expires, Some(next));
-                            self.update_next_frame_time(Some(next));
+                            let next = NextFrame::new(AnimationSources::TEXT_BLINK, Some(next));
+                            expires.merge(next);
+                            self.merge_next_frame(next);
                         }
                     }
 
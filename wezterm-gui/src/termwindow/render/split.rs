@@ -17,16 +17,20 @@ impl crate::TermWindow {
         let cell_height = self.render_metrics.cell_size.height as f32;
 
         let border = self.get_os_border();
-        let first_row_offset = if self.show_tab_bar && !self.config.tab_bar_at_bottom {
-            self.tab_bar_pixel_height()?
-        } else {
-            0.
-        } + border.top.get() as f32;
+        let tab_bar = self.tab_bar_reserved_edges()?;
+        let first_row_offset = tab_bar.top + border.top.get() as f32;
+        let first_col_offset = tab_bar.left + border.left.get() as f32;
 
         let (padding_left, padding_top) = self.padding_left_top();
+        // In zen mode the split is reduced to a single pixel
+        let thickness = if self.is_zen_mode() {
+            1.0
+        } else {
+            self.render_metrics.underline_height as f32
+        };
 
         let pos_y = split.top as f32 * cell_height + first_row_offset + padding_top;
-        let pos_x = split.left as f32 * cell_width + padding_left + border.left.get() as f32;
+        let pos_x = split.left as f32 * cell_width + padding_left + first_col_offset;
 
         if split.direction == SplitDirection::Horizontal {
             self.filled_rectangle(
@@ -35,13 +39,13 @@ impl crate::TermWindow {
                 euclid::rect(
                     pos_x + (cell_width / 2.0),
                     pos_y - (cell_height / 2.0),
-                    self.render_metrics.underline_height as f32,
+                    thickness,
                     (1. + split.size as f32) * cell_height,
                 ),
                 foreground,
             )?;
             self.ui_items.push(UIItem {
-                x: border.left.get() as usize
+                x: first_col_offset as usize
                     + padding_left as usize
                     + (split.left * cell_width as usize),
                 width: cell_width as usize,
@@ -59,12 +63,12 @@ impl crate::TermWindow {
                     pos_x - (cell_width / 2.0),
                     pos_y + (cell_height / 2.0),
                     (1.0 + split.size as f32) * cell_width,
-                    self.render_metrics.underline_height as f32,
+                    thickness,
                 ),
                 foreground,
             )?;
             self.ui_items.push(UIItem {
-                x: border.left.get() as usize
+                x: first_col_offset as usize
                     + padding_left as usize
                     + (split.left * cell_width as usize),
                 width: split.size * cell_width as usize,
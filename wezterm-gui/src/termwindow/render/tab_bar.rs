@@ -1,37 +1,78 @@
 use crate::quad::TripleLayerQuadAllocator;
+use crate::tabbar::TabBarItem;
 use crate::termwindow::render::RenderScreenLineParams;
+use crate::termwindow::{UIItem, UIItemType};
 use crate::utilsprites::RenderMetrics;
-use config::ConfigHandle;
+use config::{ConfigHandle, TabBarColors, TabBarOverflow, TabBarPosition};
 use mux::renderable::RenderableDimensions;
 use wezterm_term::color::ColorAttribute;
 use window::color::LinearRgba;
+use window::RectF;
+
+/// The number of pixels along each edge of the window that
+/// are occupied by the tab bar
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TabBarEdges {
+    pub top: f32,
+    pub bottom: f32,
+    pub left: f32,
+    pub right: f32,
+}
+
+impl TabBarEdges {
+    /// The total horizontal space occupied by the tab bar
+    pub fn width(&self) -> f32 {
+        self.left + self.right
+    }
+
+    /// The total vertical space occupied by the tab bar
+    pub fn height(&self) -> f32 {
+        self.top + self.bottom
+    }
+}
 
 impl crate::TermWindow {
     pub fn paint_tab_bar(&mut self, layers: &mut TripleLayerQuadAllocator) -> anyhow::Result<()> {
         if self.config.use_fancy_tab_bar {
-            if self.fancy_tab_bar.is_none() {
-                let palette = self.palette().clone();
-                let tab_bar = self.build_fancy_tab_bar(&palette)?;
-                self.fancy_tab_bar.replace(tab_bar);
-            }
-
+            self.fancy_tab_bar()?;
             self.ui_items.append(&mut self.paint_fancy_tab_bar()?);
             return Ok(());
         }
 
-        let border = self.get_os_border();
-
         let palette = self.palette().clone();
-        let tab_bar_height = self.tab_bar_pixel_height()?;
-        let tab_bar_y = if self.config.tab_bar_at_bottom {
-            ((self.dimensions.pixel_height as f32) - (tab_bar_height + border.bottom.get() as f32))
-                .max(0.)
+        let bounds = self.tab_bar_bounds()?;
+        let vertical = self.tab_bar_is_vertical();
+        let (tab_bar_x, tab_bar_y, tab_bar_width) = if vertical {
+            (bounds.min_x(), bounds.min_y(), bounds.width())
         } else {
-            border.top.get() as f32
+            (0., bounds.min_y(), self.dimensions.pixel_width as f32)
         };
+        let tab_bar_cols = (tab_bar_width / self.render_metrics.cell_size.width as f32) as usize;
+
+        if vertical {
+            // Claim the whole strip, so that clicks below the last
+            // tab are treated as being in the tab bar
+            self.ui_items.push(UIItem {
+                x: bounds.min_x() as usize,
+                y: bounds.min_y() as usize,
+                width: bounds.width() as usize,
+                height: bounds.height() as usize,
+                item_type: UIItemType::TabBar(TabBarItem::None),
+            });
+            let background = self
+                .config
+                .resolved_palette
+                .tab_bar
+                .as_ref()
+                .map(|colors| *colors.background())
+                .unwrap_or_else(|| *TabBarColors::default().background())
+                .to_linear();
+            self.filled_rectangle(layers, 0, bounds, background)?;
+        }
 
         // Register the tab bar location
         self.ui_items.append(&mut self.tab_bar.compute_ui_items(
+            tab_bar_x as usize,
             tab_bar_y as usize,
             self.render_metrics.cell_size.height as usize,
             self.render_metrics.cell_size.width as usize,
@@ -51,51 +92,56 @@ impl crate::TermWindow {
                 self.config.text_background_opacity
             });
 
-        self.render_screen_line(
-            RenderScreenLineParams {
-                top_pixel_y: tab_bar_y,
-                left_pixel_x: 0.,
-                pixel_width: self.dimensions.pixel_width as f32,
-                stable_line_idx: None,
-                line: self.tab_bar.line(),
-                selection: 0..0,
-                cursor: &Default::default(),
-                palette: &palette,
-                dims: &RenderableDimensions {
-                    cols: self.dimensions.pixel_width
-                        / self.render_metrics.cell_size.width as usize,
-                    physical_top: 0,
-                    scrollback_rows: 0,
-                    scrollback_top: 0,
-                    viewport_rows: 1,
-                    dpi: self.terminal_size.dpi,
-                    pixel_height: self.render_metrics.cell_size.height as usize,
-                    pixel_width: self.terminal_size.pixel_width,
-                    reverse_video: false,
+        let row_height = self.render_metrics.cell_size.height as f32;
+        for (row, line) in self.tab_bar.lines().iter().enumerate() {
+            self.render_screen_line(
+                RenderScreenLineParams {
+                    top_pixel_y: tab_bar_y + row as f32 * row_height,
+                    left_pixel_x: tab_bar_x,
+                    pixel_width: tab_bar_width,
+                    stable_line_idx: None,
+                    line,
+                    selection: 0..0,
+                    highlights: &[],
+                    cursor: &Default::default(),
+                    palette: &palette,
+                    dims: &RenderableDimensions {
+                        cols: tab_bar_cols,
+                        physical_top: 0,
+                        scrollback_rows: 0,
+                        scrollback_top: 0,
+                        viewport_rows: 1,
+                        dpi: self.terminal_size.dpi,
+                        pixel_height: self.render_metrics.cell_size.height as usize,
+                        pixel_width: self.terminal_size.pixel_width,
+                        reverse_video: false,
+                    },
+                    config: &self.config,
+                    cursor_border_color: LinearRgba::default(),
+                    foreground: palette.foreground.to_linear(),
+                    pane: None,
+                    is_active: true,
+                    selection_fg: LinearRgba::default(),
+                    selection_bg: LinearRgba::default(),
+                    cursor_fg: LinearRgba::default(),
+                    cursor_bg: LinearRgba::default(),
+                    cursor_is_default_color: true,
+                    white_space,
+                    filled_box,
+                    window_is_transparent,
+                    default_bg,
+                    style: None,
+                    font: None,
+                    use_pixel_positioning: self.config.experimental_pixel_positioning,
+                    render_metrics: self.render_metrics,
+                    shape_key: None,
+                    shaped: None,
+                    password_input: false,
+                    bidi_direction: None,
                 },
-                config: &self.config,
-                cursor_border_color: LinearRgba::default(),
-                foreground: palette.foreground.to_linear(),
-                pane: None,
-                is_active: true,
-                selection_fg: LinearRgba::default(),
-                selection_bg: LinearRgba::default(),
-                cursor_fg: LinearRgba::default(),
-                cursor_bg: LinearRgba::default(),
-                cursor_is_default_color: true,
-                white_space,
-                filled_box,
-                window_is_transparent,
-                default_bg,
-                style: None,
-                font: None,
-                use_pixel_positioning: self.config.experimental_pixel_positioning,
-                render_metrics: self.render_metrics,
-                shape_key: None,
-                password_input: false,
-            },
-            layers,
-        )?;
+                layers,
+            )?;
+        }
 
         Ok(())
     }
@@ -113,7 +159,121 @@ impl crate::TermWindow {
         }
     }
 
+    /// Returns the height of the tab bar, taking into account
+    /// the number of rows when tab_bar_overflow is set to Wrap
     pub fn tab_bar_pixel_height(&self) -> anyhow::Result<f32> {
-        Self::tab_bar_pixel_height_impl(&self.config, &self.fonts, &self.render_metrics)
+        let row_height =
+            Self::tab_bar_pixel_height_impl(&self.config, &self.fonts, &self.render_metrics)?;
+        Ok(row_height * self.tab_bar.rows() as f32)
+    }
+
+    pub fn tab_bar_is_vertical(&self) -> bool {
+        self.config.effective_tab_bar_position().is_vertical()
+    }
+
+    pub fn vertical_tab_bar_pixel_width_impl(
+        config: &ConfigHandle,
+        fontconfig: &wezterm_font::FontConfiguration,
+        render_metrics: &RenderMetrics,
+    ) -> f32 {
+        let cell_width = if config.use_fancy_tab_bar {
+            fontconfig
+                .title_font()
+                .map(|font| font.metrics().cell_width.get())
+                .unwrap_or(render_metrics.cell_size.width as f64)
+        } else {
+            render_metrics.cell_size.width as f64
+        };
+        (cell_width * config.vertical_tab_bar_width as f64).ceil() as f32
+    }
+
+    /// Returns the width of the tab bar when it is placed along
+    /// the left or right edge of the window
+    pub fn vertical_tab_bar_pixel_width(&self) -> f32 {
+        Self::vertical_tab_bar_pixel_width_impl(&self.config, &self.fonts, &self.render_metrics)
+    }
+
+    /// Returns the space reserved for the tab bar along each edge
+    /// of the window, assuming that the tab bar is visible when
+    /// `show_tab_bar` is true
+    pub fn tab_bar_reserved_edges_for(&self, show_tab_bar: bool) -> anyhow::Result<TabBarEdges> {
+        let mut edges = TabBarEdges::default();
+        if !show_tab_bar {
+            return Ok(edges);
+        }
+        match self.config.effective_tab_bar_position() {
+            TabBarPosition::Top => edges.top = self.tab_bar_pixel_height()?,
+            TabBarPosition::Bottom => edges.bottom = self.tab_bar_pixel_height()?,
+            TabBarPosition::Left => edges.left = self.vertical_tab_bar_pixel_width(),
+            TabBarPosition::Right => edges.right = self.vertical_tab_bar_pixel_width(),
+        }
+        Ok(edges)
+    }
+
+    /// Returns the space reserved for the tab bar along each edge
+    /// of the window
+    pub fn tab_bar_reserved_edges(&self) -> anyhow::Result<TabBarEdges> {
+        self.tab_bar_reserved_edges_for(self.show_tab_bar)
+    }
+
+    /// Returns the rectangle, in window pixel coordinates, that is
+    /// occupied by the tab bar
+    pub fn tab_bar_bounds(&self) -> anyhow::Result<RectF> {
+        let border = self.get_os_border();
+        let window_width = self.dimensions.pixel_width as f32;
+        let window_height = self.dimensions.pixel_height as f32;
+        let strip_height = (window_height - (border.top + border.bottom).get() as f32).max(0.);
+
+        Ok(match self.config.effective_tab_bar_position() {
+            TabBarPosition::Top => euclid::rect(
+                border.left.get() as f32,
+                border.top.get() as f32,
+                window_width - (border.left + border.right).get() as f32,
+                self.tab_bar_pixel_height()?,
+            ),
+            TabBarPosition::Bottom => {
+                let height = self.tab_bar_pixel_height()?;
+                euclid::rect(
+                    border.left.get() as f32,
+                    (window_height - (height + border.bottom.get() as f32)).max(0.),
+                    window_width - (border.left + border.right).get() as f32,
+                    height,
+                )
+            }
+            TabBarPosition::Left => euclid::rect(
+                border.left.get() as f32,
+                border.top.get() as f32,
+                self.vertical_tab_bar_pixel_width(),
+                strip_height,
+            ),
+            TabBarPosition::Right => {
+                let width = self.vertical_tab_bar_pixel_width();
+                euclid::rect(
+                    (window_width - (width + border.right.get() as f32)).max(0.),
+                    border.top.get() as f32,
+                    width,
+                    strip_height,
+                )
+            }
+        })
+    }
+
+    /// Returns the number of cells that fit across the tab bar.
+    /// When tabs may scroll or wrap, the fancy tab bar is measured
+    /// using the title font so that the tab layout matches what
+    /// is rendered.
+    pub fn tab_bar_columns(&self) -> usize {
+        if self.tab_bar_is_vertical() {
+            return self.config.vertical_tab_bar_width;
+        }
+        let cell_width = match (self.config.use_fancy_tab_bar, self.config.tab_bar_overflow) {
+            (true, TabBarOverflow::Scroll | TabBarOverflow::Wrap) => self
+                .fonts
+                .title_font()
+                .map(|font| font.metrics().cell_width.get())
+                .unwrap_or(self.render_metrics.cell_size.width as f64),
+            _ => self.render_metrics.cell_size.width as f64,
+        };
+        (self.dimensions.pixel_width as f64 / cell_width.max(1.)) as usize
     }
 }
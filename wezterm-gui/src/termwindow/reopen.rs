@@ -0,0 +1,221 @@
+//! Reopens the panes and tabs that the user closed recently; see
+//! `mux::closed` for how they are recorded.
+//!
+//! The command of each pane is spawned afresh, in the same domain and
+//! working directory, and the scrollback that the pane had when it was
+//! closed is placed above the output of the new process.
+use crate::spawn::{spawn_command_internal, SpawnWhere};
+use crate::termwindow::{TermWindow, TermWindowNotif};
+use config::keyassignment::{SpawnCommand, SpawnTabDomain};
+use config::TermConfig;
+use mux::closed::{restore_scrollback, ClosedKind, ClosedLayout, ClosedPane, ClosedTab};
+use mux::pane::Pane;
+use mux::tab::{SplitRequest, SplitSize, TabId};
+use mux::window::WindowId;
+use mux::Mux;
+use std::sync::Arc;
+use wezterm_term::TerminalSize;
+
+/// Returns the command that reopens `closed`
+fn spawn_command_for(closed: &ClosedPane) -> SpawnCommand {
+    let mut spawn = closed.command.clone().unwrap_or_default();
+    spawn.domain = SpawnTabDomain::DomainId(closed.domain_id);
+    if let Some(cwd) = closed.working_dir.as_ref().and_then(mux::url_to_cwd) {
+        spawn.cwd.replace(cwd.into());
+    }
+    spawn
+}
+
+/// Makes `pane` the active pane of the active tab of `window_id`,
+/// so that it is the one that is split by the next spawn
+fn activate_pane(window_id: WindowId, tab_id: TabId, pane: &Arc<dyn Pane>) -> bool {
+    let mux = Mux::get();
+    let tab = match mux.get_tab(tab_id) {
+        Some(tab) => tab,
+        None => return false,
+    };
+    {
+        let mut window = match mux.get_window_mut(window_id) {
+            Some(window) => window,
+            None => return false,
+        };
+        match window.idx_by_id(tab_id) {
+            Some(idx) => window.save_and_then_set_active(idx),
+            None => return false,
+        }
+    }
+    tab.set_active_pane(pane);
+    true
+}
+
+fn restore(pane: &Arc<dyn Pane>, closed: ClosedPane) {
+    if let Err(err) = restore_scrollback(pane, closed.lines) {
+        log::warn!("Not restoring scrollback: {:#}", err);
+    }
+}
+
+impl TermWindow {
+    pub fn reopen_closed_pane(&mut self) {
+        let mux = Mux::get();
+        let item = match mux.take_closed(self.mux_window_id, false) {
+            Some(item) => item,
+            None => return,
+        };
+        let closed = match item.kind {
+            ClosedKind::Pane(closed) => closed,
+            ClosedKind::Tab(_) => return,
+        };
+
+        // Put it back beside the pane that it was split from, if its
+        // tab is still in this window
+        let tab = mux
+            .get_tab(item.tab_id)
+            .filter(|_| mux.window_containing_tab(item.tab_id) == Some(self.mux_window_id));
+        let spawn_where = match tab {
+            Some(tab) => {
+                let neighbour = closed.placement.and_then(|placement| {
+                    tab.iter_panes_ignoring_zoom()
+                        .into_iter()
+                        .find(|pos| pos.pane.pane_id() == placement.neighbour)
+                        .map(|pos| pos.pane)
+                });
+                let target = neighbour.or_else(|| tab.get_active_pane());
+                match target {
+                    Some(target) if activate_pane(self.mux_window_id, tab.tab_id(), &target) => {
+                        SpawnWhere::SplitPane(match closed.placement {
+                            Some(placement) => SplitRequest {
+                                direction: placement.direction,
+                                target_is_second: placement.second,
+                                top_level: placement.top_level,
+                                size: SplitSize::Cells(placement.cells),
+                            },
+                            None => SplitRequest::default(),
+                        })
+                    }
+                    _ => SpawnWhere::NewTab,
+                }
+            }
+            None => SpawnWhere::NewTab,
+        };
+
+        let spawn = spawn_command_for(&closed);
+        let size = self.terminal_size;
+        let src_window_id = self.mux_window_id;
+        let term_config = Arc::new(TermConfig::with_config(self.config.clone()));
+        promise::spawn::spawn(async move {
+            match spawn_command_internal(spawn, spawn_where, size, Some(src_window_id), term_config)
+                .await
+            {
+                Ok(pane) => restore(&pane, closed),
+                Err(err) => log::error!("Failed to reopen pane: {:#}", err),
+            }
+        })
+        .detach();
+    }
+
+    pub fn reopen_closed_tab(&mut self) {
+        let mux = Mux::get();
+        let item = match mux.take_closed(self.mux_window_id, true) {
+            Some(item) => item,
+            None => return,
+        };
+        let closed = match item.kind {
+            ClosedKind::Tab(closed) => closed,
+            ClosedKind::Pane(_) => return,
+        };
+
+        let size = self.terminal_size;
+        let window_id = self.mux_window_id;
+        let term_config = Arc::new(TermConfig::with_config(self.config.clone()));
+        let window = self.window.as_ref().unwrap().clone();
+        promise::spawn::spawn(async move {
+            match reopen_tab(closed, size, window_id, term_config).await {
+                Ok(()) => window.notify(TermWindowNotif::Apply(Box::new(|tw| {
+                    tw.update_title();
+                    tw.update_scrollbar();
+                }))),
+                Err(err) => log::error!("Failed to reopen tab: {:#}", err),
+            }
+        })
+        .detach();
+    }
+}
+
+/// Spawns a tab with the layout of `closed` into `window_id`, at
+/// the position that it used to occupy
+async fn reopen_tab(
+    closed: ClosedTab,
+    size: TerminalSize,
+    window_id: WindowId,
+    term_config: Arc<TermConfig>,
+) -> anyhow::Result<()> {
+    let mux = Mux::get();
+    let pane = spawn_command_internal(
+        spawn_command_for(closed.layout.first_pane()),
+        SpawnWhere::NewTab,
+        size,
+        Some(window_id),
+        Arc::clone(&term_config),
+    )
+    .await?;
+    let tab_id = mux
+        .resolve_pane_id(pane.pane_id())
+        .map(|(_domain_id, _window_id, tab_id)| tab_id)
+        .ok_or_else(|| anyhow::anyhow!("reopened pane has no tab"))?;
+
+    if let Some(mut window) = mux.get_window_mut(window_id) {
+        if let Some(idx) = window.idx_by_id(tab_id) {
+            let tab = window.remove_by_idx(idx);
+            let idx = closed.tab_index.min(window.len());
+            window.insert(idx, &tab);
+            window.set_active_without_saving(idx);
+        }
+    }
+    if !closed.title.is_empty() {
+        if let Some(tab) = mux.get_tab(tab_id) {
+            tab.set_title_with_override(&closed.title, closed.title_is_override);
+        }
+    }
+
+    // Each entry is a part of the layout along with the pane that
+    // occupies all of its space so far
+    let mut pending = vec![(closed.layout, pane)];
+    while let Some((layout, pane)) = pending.pop() {
+        match layout {
+            ClosedLayout::Pane(closed) => restore(&pane, closed),
+            ClosedLayout::Split {
+                direction,
+                second_cells,
+                first,
+                second,
+            } => {
+                anyhow::ensure!(
+                    activate_pane(window_id, tab_id, &pane),
+                    "reopened tab was closed while its panes were being spawned"
+                );
+                let second_pane = spawn_command_internal(
+                    spawn_command_for(second.first_pane()),
+                    SpawnWhere::SplitPane(SplitRequest {
+                        direction,
+                        target_is_second: true,
+                        top_level: false,
+                        size: SplitSize::Cells(second_cells),
+                    }),
+                    size,
+                    Some(window_id),
+                    Arc::clone(&term_config),
+                )
+                .await?;
+                pending.push((*second, second_pane));
+                pending.push((*first, pane));
+            }
+        }
+    }
+
+    if let Some(tab) = mux.get_tab(tab_id) {
+        if let Some(pos) = tab.iter_panes_ignoring_zoom().into_iter().next() {
+            tab.set_active_pane(&pos.pane);
+        }
+    }
+    Ok(())
+}
@@ -1,12 +1,22 @@
 use crate::resize_increment_calculator::ResizeIncrementCalculator;
-use crate::utilsprites::RenderMetrics;
-use ::window::{Dimensions, ResizeIncrement, Window, WindowOps, WindowState};
+use crate::termwindow::TermWindowNotif;
+use crate::utilsprites::{CellMetricsOverride, RenderMetrics};
+use ::window::screen::ScreenInfo;
+use ::window::{
+    Connection, ConnectionOps, Dimensions, ResizeIncrement, ScreenPoint, Window, WindowOps,
+    WindowState,
+};
 use config::{ConfigHandle, DimensionContext};
 use mux::Mux;
 use std::rc::Rc;
+use std::time::Duration;
 use wezterm_font::FontConfiguration;
 use wezterm_term::TerminalSize;
 
+/// How long the dpi must remain unchanged while the window is being
+/// dragged between monitors before the fonts are re-rasterized
+const DPI_CHANGE_DEBOUNCE: Duration = Duration::from_millis(100);
+
 #[derive(Debug, Clone, Copy)]
 pub struct RowsAndCols {
     pub rows: usize,
@@ -41,11 +51,27 @@ impl super::TermWindow {
             log::trace!("new dimensions are zero: NOP!");
             return;
         }
+        if let Some(mut mux_window) = Mux::get().get_window_mut(self.mux_window_id) {
+            mux_window.set_gui_pixel_size(dimensions.pixel_width, dimensions.pixel_height);
+        }
+        if live_resizing && self.dimensions.dpi != dimensions.dpi {
+            // The window is being dragged between monitors with
+            // different dpi. Re-rasterizing the fonts is expensive,
+            // so wait for the dpi to settle rather than thrash
+            // as the window crosses back and forth.
+            self.schedule_dpi_change(dimensions, window_state, window);
+            return;
+        }
+        if self.pending_dpi_change.take().is_some() {
+            // This supersedes the dpi change that was yet to be applied
+            self.dpi_change_generation += 1;
+        }
         if self.dimensions == dimensions && self.window_state == window_state {
             // It didn't really change
             log::trace!("dimensions didn't change NOP!");
             return;
         }
+
         let last_state = self.window_state;
         self.window_state = window_state;
         self.quad_generation += 1;
@@ -70,6 +96,101 @@ impl super::TermWindow {
         self.emit_window_event("window-resized", None);
     }
 
+    /// Called when the window has been moved to `position`.
+    /// If the majority of the window is now on a screen with a
+    /// different dpi, switch to that dpi straight away, rather than
+    /// waiting for the window system to report it once the move is
+    /// complete, so that the tab bar and the hit rects derived from
+    /// it are laid out for the screen that the window appears on.
+    pub fn check_dpi_for_position(&mut self, position: ScreenPoint, window: &Window) {
+        let conn = match Connection::get() {
+            Some(conn) => conn,
+            None => return,
+        };
+        let screens = match conn.screens() {
+            Ok(screens) => screens,
+            Err(err) => {
+                log::trace!("cannot query screens: {err:#}");
+                return;
+            }
+        };
+        let rect = euclid::rect(
+            position.x,
+            position.y,
+            self.dimensions.pixel_width as isize,
+            self.dimensions.pixel_height as isize,
+        );
+        let dpi = match screens
+            .screen_for_rect(&rect)
+            .and_then(|screen| screen.effective_dpi)
+        {
+            Some(dpi) => dpi.round() as usize,
+            None => return,
+        };
+        if dpi != self.dimensions.dpi {
+            log::trace!(
+                "majority of window is now on a screen with dpi {dpi}, was {}",
+                self.dimensions.dpi
+            );
+            let dimensions = Dimensions {
+                dpi,
+                ..self.dimensions
+            };
+            self.resize(dimensions, self.window_state, window, false);
+        }
+    }
+
+    fn schedule_dpi_change(
+        &mut self,
+        dimensions: Dimensions,
+        window_state: WindowState,
+        window: &Window,
+    ) {
+        self.pending_dpi_change.replace((dimensions, window_state));
+        self.dpi_change_generation += 1;
+        let generation = self.dpi_change_generation;
+
+        let window = window.clone();
+        promise::spawn::spawn(async move {
+            smol::Timer::after(DPI_CHANGE_DEBOUNCE).await;
+            window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                term_window.complete_dpi_change(generation);
+            })));
+        })
+        .detach();
+    }
+
+    fn complete_dpi_change(&mut self, generation: usize) {
+        if generation != self.dpi_change_generation {
+            // Another change arrived in the meantime
+            return;
+        }
+        if let (Some((dimensions, window_state)), Some(window)) =
+            (self.pending_dpi_change.take(), self.window.clone())
+        {
+            self.resize(dimensions, window_state, &window, false);
+        }
+    }
+
+    /// Returns information about the screen that holds this window
+    pub fn active_screen(&self) -> anyhow::Result<ScreenInfo> {
+        let conn =
+            Connection::get().ok_or_else(|| anyhow::anyhow!("cannot get window Connection"))?;
+        let screens = conn.screens()?;
+        let rect = Mux::get()
+            .get_window(self.mux_window_id)
+            .and_then(|window| window.get_geometry().position)
+            .map(|(x, y)| {
+                euclid::rect(
+                    x,
+                    y,
+                    self.dimensions.pixel_width as isize,
+                    self.dimensions.pixel_height as isize,
+                )
+            });
+        Ok(screens.screen_for_window(rect.as_ref()).clone())
+    }
+
     pub fn apply_pending_scale_changes(&mut self) {
         while self.resizes_pending == 0 {
             match self.pending_scale_changes.pop_front() {
@@ -105,7 +226,7 @@ impl super::TermWindow {
         }
 
         let (prior_font, prior_dpi) = self.fonts.change_scaling(font_scale, dimensions.dpi);
-        match RenderMetrics::new(&self.fonts) {
+        match RenderMetrics::with_overrides(&self.fonts, &self.cell_metrics_override) {
             Ok(metrics) => {
                 self.render_metrics = metrics;
             }
@@ -124,6 +245,12 @@ impl super::TermWindow {
         if let Err(err) = self.recreate_texture_atlas(None) {
             log::error!("recreate_texture_atlas: {:#}", err);
         }
+        if prior_dpi != dimensions.dpi {
+            // The hit rects were computed for the prior dpi; drop them
+            // rather than resolve mouse events against stale geometry
+            // until the next paint recomputes them
+            self.ui_items.clear();
+        }
         self.invalidate_fancy_tab_bar();
         self.invalidate_modal();
     }
@@ -163,11 +290,9 @@ impl super::TermWindow {
 
         let config = &self.config;
 
-        let tab_bar_height = if self.show_tab_bar {
-            self.tab_bar_pixel_height().unwrap_or(0.)
-        } else {
-            0.
-        };
+        let tab_bar = self.tab_bar_reserved_edges().unwrap_or_default();
+        let tab_bar_width = tab_bar.width() as usize;
+        let tab_bar_height = tab_bar.height() as usize;
 
         let border = self.get_os_border();
 
@@ -204,11 +329,12 @@ impl super::TermWindow {
             let pixel_height = (rows * self.render_metrics.cell_size.height as usize)
                 + (padding_top + padding_bottom)
                 + (border.top + border.bottom).get() as usize
-                + tab_bar_height as usize;
+                + tab_bar_height;
 
             let pixel_width = (cols * self.render_metrics.cell_size.width as usize)
                 + (padding_left + padding_right)
-                + (border.left + border.right).get() as usize;
+                + (border.left + border.right).get() as usize
+                + tab_bar_width;
 
             let dims = Dimensions {
                 pixel_width: pixel_width as usize,
@@ -224,7 +350,8 @@ impl super::TermWindow {
                 padding_right: padding_right,
                 padding_bottom: padding_bottom,
                 border: border,
-                tab_bar_height: tab_bar_height as usize,
+                tab_bar_width,
+                tab_bar_height,
             };
 
             (size, dims, ri_calc)
@@ -249,7 +376,8 @@ impl super::TermWindow {
 
             let avail_width = dimensions.pixel_width.saturating_sub(
                 (padding_left + padding_right) as usize
-                    + (border.left + border.right).get() as usize,
+                    + (border.left + border.right).get() as usize
+                    + tab_bar_width,
             );
             let avail_height = dimensions
                 .pixel_height
@@ -257,10 +385,11 @@ impl super::TermWindow {
                     (padding_top + padding_bottom) as usize
                         + (border.top + border.bottom).get() as usize,
                 )
-                .saturating_sub(tab_bar_height as usize);
+                .saturating_sub(tab_bar_height);
 
-            let rows = avail_height / self.render_metrics.cell_size.height as usize;
-            let cols = avail_width / self.render_metrics.cell_size.width as usize;
+            let (rows, cols) = self
+                .render_metrics
+                .cells_for_pixels(avail_width, avail_height);
 
             let size = TerminalSize {
                 rows,
@@ -282,7 +411,8 @@ impl super::TermWindow {
                 padding_right: padding_right,
                 padding_bottom: padding_bottom,
                 border: border,
-                tab_bar_height: tab_bar_height as usize,
+                tab_bar_width,
+                tab_bar_height,
             };
 
             (size, *dimensions, ri_calc)
@@ -424,6 +554,10 @@ impl super::TermWindow {
             scale_changed_cells
         );
         self.apply_dimensions(&dimensions, scale_changed_cells, window);
+
+        if dpi_changed {
+            self.emit_window_event("window-dpi-changed", None);
+        }
     }
 
     /// Used for applying font size changes only; this takes into account
@@ -472,13 +606,32 @@ impl super::TermWindow {
         self.apply_pending_scale_changes();
     }
 
+    /// Replaces the runtime cell metrics override for this window,
+    /// recomputing the render metrics and resizing the panes to fit
+    /// the unchanged window dimensions.
+    pub fn set_cell_metrics_override(&mut self, overrides: CellMetricsOverride) {
+        if overrides == self.cell_metrics_override {
+            return;
+        }
+        self.cell_metrics_override = overrides;
+
+        let dimensions = self.dimensions;
+        let font_scale = self.fonts.get_font_scale();
+        self.apply_scale_change(&dimensions, font_scale);
+        if let Some(window) = self.window.clone() {
+            self.apply_dimensions(&dimensions, None, &window);
+            window.invalidate();
+        }
+    }
+
     pub fn set_window_size(&mut self, size: TerminalSize, window: &Window) -> anyhow::Result<()> {
         let config = &self.config;
         let fontconfig = Rc::new(FontConfiguration::new(
             Some(config.clone()),
             self.dimensions.dpi,
         )?);
-        let render_metrics = RenderMetrics::new(&fontconfig)?;
+        let render_metrics =
+            RenderMetrics::with_overrides(&fontconfig, &self.cell_metrics_override)?;
 
         let terminal_size = TerminalSize {
             rows: size.rows,
@@ -489,11 +642,7 @@ impl super::TermWindow {
         };
 
         let show_tab_bar = config.enable_tab_bar && !config.hide_tab_bar_if_only_one_tab;
-        let tab_bar_height = if show_tab_bar {
-            self.tab_bar_pixel_height()? as usize
-        } else {
-            0
-        };
+        let tab_bar = self.tab_bar_reserved_edges_for(show_tab_bar)?;
 
         let h_context = DimensionContext {
             dpi: self.dimensions.dpi as f32,
@@ -512,11 +661,12 @@ impl super::TermWindow {
         let dimensions = Dimensions {
             pixel_width: ((terminal_size.cols as usize * render_metrics.cell_size.width as usize)
                 + padding_left
-                + effective_right_padding(&config, h_context)),
+                + effective_right_padding(&config, h_context))
+                + tab_bar.width() as usize,
             pixel_height: ((terminal_size.rows as usize * render_metrics.cell_size.height as usize)
                 + padding_top
                 + padding_bottom) as usize
-                + tab_bar_height,
+                + tab_bar.height() as usize,
             dpi: self.dimensions.dpi,
         };
 
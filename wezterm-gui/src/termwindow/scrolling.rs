@@ -0,0 +1,391 @@
+//! Scrolling for touchpads and touchscreens, which report the distance
+//! scrolled in pixels rather than in wheel notches.
+//! The distance is converted into cells according to `scroll_speed`
+//! and `scroll_acceleration`, carrying the fractional part over to the
+//! next event on a per-pane basis, and is then applied as synthesized
+//! wheel events so that mouse bindings and mouse reporting continue
+//! to work as they do for a wheel.
+//! When the fingers are lifted while they are moving quickly, the
+//! scroll continues and gradually slows down, driven by the frame timer.
+use crate::termwindow::animation::AnimationSources;
+use crate::termwindow::TermWindow;
+use ::window::{MouseEvent, MouseEventKind, PreciseScrollEvent, ScrollPhase, WindowOps};
+use mux::pane::{Pane, PaneId};
+use mux::Mux;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Only the motion within this period before the fingers are lifted
+/// is used to compute the velocity of a fling
+const VELOCITY_WINDOW: Duration = Duration::from_millis(100);
+/// The slowest fling, in cells per second, that starts a kinetic scroll
+const MIN_FLING_VELOCITY: f64 = 10.0;
+/// A kinetic scroll comes to rest once it is slower than this,
+/// in cells per second
+const MIN_KINETIC_VELOCITY: f64 = 2.0;
+/// The rate at which a kinetic scroll slows down. The velocity is
+/// multiplied by exp(-FRICTION) every second.
+const FRICTION: f64 = 3.0;
+/// Applications that handle the wheel themselves, either because they
+/// use the alternate screen or because they grabbed the mouse, are sent
+/// at most one wheel event in this period. Each of those events can turn
+/// into several arrow key presses, so sending one per touchpad event, or
+/// per frame of a kinetic scroll, would flood them with input.
+const APP_WHEEL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Converts a distance of `pixels` into cells that are `cell_size`
+/// pixels in size. `acceleration` makes each event scroll further the
+/// further it moves, so that fast gestures cover more distance while
+/// slow gestures retain their precision.
+pub fn scaled_cells(pixels: f64, cell_size: f64, speed: f64, acceleration: f64) -> f64 {
+    let cells = pixels * speed / cell_size;
+    cells * (1.0 + acceleration.max(0.) * cells.abs())
+}
+
+/// Accumulates fractional cells of scrolling
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ScrollAccumulator {
+    remainder: f64,
+}
+
+impl ScrollAccumulator {
+    pub fn add(&mut self, cells: f64) {
+        if cells.signum() != self.remainder.signum() {
+            // Reset the accumulator when changing scroll direction
+            self.remainder = 0.;
+        }
+        self.remainder += cells;
+    }
+
+    /// Removes and returns the whole cells that have been accumulated
+    pub fn take(&mut self) -> i64 {
+        let whole = self.remainder.trunc();
+        self.remainder -= whole;
+        whole as i64
+    }
+}
+
+/// Estimates the speed of a gesture from its recent motion
+#[derive(Debug, Default)]
+pub struct VelocityTracker {
+    samples: VecDeque<(Instant, f64)>,
+}
+
+impl VelocityTracker {
+    pub fn record(&mut self, when: Instant, cells: f64) {
+        self.samples.push_back((when, cells));
+        self.expire(when);
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some((when, _)) = self.samples.front() {
+            if now.saturating_duration_since(*when) <= VELOCITY_WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Returns the velocity, in cells per second, as of `now`
+    pub fn velocity(&mut self, now: Instant) -> f64 {
+        self.expire(now);
+        let first = match self.samples.front() {
+            Some((when, _)) => *when,
+            None => return 0.,
+        };
+        // The first sample covers the motion that led up to it, so
+        // allow for that rather than dividing by a tiny duration
+        let elapsed = now
+            .saturating_duration_since(first)
+            .max(Duration::from_millis(10))
+            .as_secs_f64();
+        let distance: f64 = self.samples.iter().map(|(_, cells)| cells).sum();
+        distance / elapsed
+    }
+}
+
+/// The motion of a kinetic scroll, which slows down exponentially
+#[derive(Debug, Clone)]
+pub struct KineticScroll {
+    /// In cells per second
+    velocity: f64,
+    last_step: Instant,
+}
+
+impl KineticScroll {
+    pub fn new(velocity: f64, now: Instant) -> Self {
+        Self {
+            velocity,
+            last_step: now,
+        }
+    }
+
+    /// Advances the scroll to `now`, returning the distance in cells
+    /// that it covered since the prior step, or None once it has come
+    /// to rest.
+    pub fn step(&mut self, now: Instant) -> Option<f64> {
+        if self.velocity.abs() < MIN_KINETIC_VELOCITY {
+            return None;
+        }
+        let elapsed = now.saturating_duration_since(self.last_step).as_secs_f64();
+        self.last_step = now;
+        let decay = (-FRICTION * elapsed).exp();
+        let distance = self.velocity * (1. - decay) / FRICTION;
+        self.velocity *= decay;
+        Some(distance)
+    }
+}
+
+/// A kinetic scroll that is in progress in a pane
+pub struct Fling {
+    pane_id: PaneId,
+    /// The event that ended the gesture; its position and modifiers
+    /// are used for the synthesized wheel events
+    event: PreciseScrollEvent,
+    motion: KineticScroll,
+}
+
+impl TermWindow {
+    pub fn precise_scroll_impl(&mut self, event: PreciseScrollEvent, context: &dyn WindowOps) {
+        log::trace!("{:?}", event);
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+        let now = Instant::now();
+
+        if event.phase == ScrollPhase::Momentum && !self.config.kinetic_scrolling {
+            // The system is animating a fling on our behalf
+            return;
+        }
+
+        if event.phase == ScrollPhase::Active {
+            // Touching the surface again stops a fling
+            self.fling.take();
+        }
+
+        let cells_x = scaled_cells(
+            event.delta_x,
+            self.render_metrics.cell_size.width as f64,
+            self.config.scroll_speed,
+            self.config.scroll_acceleration,
+        );
+        let cells_y = scaled_cells(
+            event.delta_y,
+            self.render_metrics.cell_size.height as f64,
+            self.config.scroll_speed,
+            self.config.scroll_acceleration,
+        );
+
+        if event.phase == ScrollPhase::Active {
+            self.scroll_velocity.record(now, cells_y);
+        }
+        self.scroll_by_cells(&pane, &event, cells_x, cells_y, now, context);
+
+        if event.phase == ScrollPhase::Released {
+            let velocity = self.scroll_velocity.velocity(now);
+            self.scroll_velocity.clear();
+            if self.config.kinetic_scrolling && velocity.abs() >= MIN_FLING_VELOCITY {
+                self.fling.replace(Fling {
+                    pane_id: pane.pane_id(),
+                    event,
+                    motion: KineticScroll::new(velocity, now),
+                });
+                context.invalidate();
+            }
+        }
+    }
+
+    /// Stops any kinetic scroll that is in progress
+    pub fn cancel_kinetic_scroll(&mut self) {
+        self.fling.take();
+        self.scroll_velocity.clear();
+    }
+
+    /// Called prior to painting a frame to advance the kinetic scroll,
+    /// if any, and to schedule the next frame while it is in motion.
+    pub fn step_kinetic_scroll(&mut self) {
+        let now = Instant::now();
+        let (pane_id, event, distance) = match self.fling.as_mut() {
+            Some(fling) => match fling.motion.step(now) {
+                Some(distance) => (fling.pane_id, fling.event.clone(), distance),
+                None => {
+                    self.fling.take();
+                    return;
+                }
+            },
+            None => return,
+        };
+
+        let pane = match self.get_active_pane_or_overlay() {
+            Some(pane) if pane.pane_id() == pane_id => pane,
+            _ => {
+                // The pane is no longer active
+                self.fling.take();
+                return;
+            }
+        };
+        let window = match self.window.clone() {
+            Some(window) => window,
+            None => return,
+        };
+
+        self.scroll_by_cells(&pane, &event, 0., distance, now, &window);
+
+        let frame_interval = Duration::from_secs_f64(1. / self.config.max_fps.max(1) as f64);
+        self.update_next_frame_time(AnimationSources::SCROLL_INERTIA, Some(now + frame_interval));
+    }
+
+    /// Cancels a kinetic scroll through the scrollback of `pane_id`
+    /// when it produces output and `scroll_to_bottom_on_input` is
+    /// enabled, so that the new output isn't scrolled out of view.
+    /// Applications that handle the wheel themselves redraw in response
+    /// to it, so their output doesn't stop the scroll.
+    pub fn cancel_kinetic_scroll_for_output(&mut self, pane_id: PaneId) {
+        if !self.config.scroll_to_bottom_on_input
+            || self.fling.as_ref().map(|fling| fling.pane_id) != Some(pane_id)
+        {
+            return;
+        }
+        let app_handles_wheel = match Mux::get().get_pane(pane_id) {
+            Some(pane) => pane.is_alt_screen_active() || pane.is_mouse_grabbed(),
+            None => false,
+        };
+        if !app_handles_wheel {
+            self.cancel_kinetic_scroll();
+        }
+    }
+
+    fn scroll_by_cells(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+        event: &PreciseScrollEvent,
+        cells_x: f64,
+        cells_y: f64,
+        now: Instant,
+        context: &dyn WindowOps,
+    ) {
+        let app_handles_wheel = pane.is_alt_screen_active() || pane.is_mouse_grabbed();
+        let (columns, lines) = {
+            let mut state = self.pane_state(pane.pane_id());
+            state.scroll_x.add(cells_x);
+            state.scroll_y.add(cells_y);
+
+            if app_handles_wheel {
+                if let Some(last) = state.last_app_wheel {
+                    if now.saturating_duration_since(last) < APP_WHEEL_INTERVAL {
+                        return;
+                    }
+                }
+                // Send a single notch, no matter how far the gesture
+                // moved, so that the application doesn't fall behind
+                let columns = state.scroll_x.take().signum();
+                let lines = state.scroll_y.take().signum();
+                if columns != 0 || lines != 0 {
+                    state.last_app_wheel.replace(now);
+                }
+                (columns, lines)
+            } else {
+                (state.scroll_x.take(), state.scroll_y.take())
+            }
+        };
+
+        fn clamp(n: i64) -> i16 {
+            n.clamp(i16::MIN as i64, i16::MAX as i64) as i16
+        }
+
+        for kind in [
+            MouseEventKind::VertWheel(clamp(lines)),
+            MouseEventKind::HorzWheel(clamp(columns)),
+        ] {
+            if matches!(
+                kind,
+                MouseEventKind::VertWheel(0) | MouseEventKind::HorzWheel(0)
+            ) {
+                continue;
+            }
+            self.mouse_event_impl(
+                MouseEvent {
+                    kind,
+                    coords: event.coords,
+                    screen_coords: event.screen_coords,
+                    mouse_buttons: event.mouse_buttons,
+                    modifiers: event.modifiers,
+                },
+                context,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn speed_and_acceleration() {
+        assert_eq!(scaled_cells(40., 20., 1.0, 0.), 2.);
+        assert_eq!(scaled_cells(-40., 20., 0.5, 0.), -1.);
+        // Acceleration grows with the size of the motion,
+        // and preserves its direction
+        assert_eq!(scaled_cells(10., 20., 1.0, 1.0), 0.75);
+        assert_eq!(scaled_cells(-40., 20., 1.0, 1.0), -6.);
+    }
+
+    #[test]
+    fn accumulate() {
+        let mut acc = ScrollAccumulator::default();
+        acc.add(0.6);
+        assert_eq!(acc.take(), 0);
+        acc.add(0.6);
+        assert_eq!(acc.take(), 1);
+        acc.add(1.0);
+        assert_eq!(acc.take(), 1);
+        // Changing direction discards the remainder
+        acc.add(-0.5);
+        assert_eq!(acc.take(), 0);
+        acc.add(-0.6);
+        assert_eq!(acc.take(), -1);
+    }
+
+    #[test]
+    fn fling_velocity() {
+        let start = Instant::now();
+        let mut tracker = VelocityTracker::default();
+        tracker.record(start, 100.);
+        for i in 1..=10 {
+            tracker.record(start + Duration::from_millis(i * 10), 1.);
+        }
+        // The first sample is too old to count
+        let velocity = tracker.velocity(start + Duration::from_millis(110));
+        assert!((velocity - 100.).abs() < 0.001, "{velocity}");
+    }
+
+    #[test]
+    fn kinetic_decay() {
+        let start = Instant::now();
+        let mut kinetic = KineticScroll::new(60., start);
+        let mut total = 0.;
+        let mut now = start;
+        let mut frames = 0;
+        while let Some(distance) = kinetic.step(now) {
+            assert!(distance >= 0.);
+            total += distance;
+            now += Duration::from_millis(16);
+            frames += 1;
+        }
+        // The scroll comes to rest, having covered close to the
+        // distance given by integrating the decaying velocity
+        assert!(frames < 200, "{frames}");
+        let limit = 60. / FRICTION;
+        assert!(
+            total < limit && total > limit - MIN_KINETIC_VELOCITY,
+            "{total}"
+        );
+    }
+}
@@ -1,10 +1,36 @@
 use crate::selection::{Selection, SelectionCoordinate, SelectionMode, SelectionRange, SelectionX};
+use crate::termwindow::render::line_timestamps::{arrival_at, format_arrival, gutter_text};
 use ::window::WindowOps;
-use mux::pane::{Pane, PaneId};
+use config::ConfigHandle;
+use mux::pane::{LogicalLine, Pane, PaneId};
 use std::cell::RefMut;
+use std::ops::Range;
 use std::sync::Arc;
 use termwiz::surface::Line;
-use wezterm_term::StableRowIndex;
+use unicode_segmentation::UnicodeSegmentation;
+use wezterm_term::{unicode_column_width, StableRowIndex};
+
+/// Remembers a double click selection, so that double clicking
+/// the same spot again can select the next wider level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WordExpansion {
+    click: SelectionCoordinate,
+    level: usize,
+    range: SelectionRange,
+}
+
+/// Returns the level to select when the same spot is double clicked
+/// again after selecting `prior`: the next one that selects something
+/// different, or `prior` itself once there is nothing more to expand to
+fn next_expansion_level(levels: &[SelectionRange], prior: usize) -> usize {
+    levels
+        .iter()
+        .enumerate()
+        .skip(prior + 1)
+        .find(|(_, range)| Some(*range) != levels.get(prior))
+        .map(|(level, _)| level)
+        .unwrap_or(prior)
+}
 
 impl super::TermWindow {
     pub fn selection(&self, pane_id: PaneId) -> RefMut<Selection> {
@@ -22,38 +48,29 @@ impl super::TermWindow {
             .as_ref()
             .map(|r| r.normalize())
         {
+            let options = CopyOptions::new(&self.config, rectangular);
             let mut last_was_wrapped = false;
             let first_row = sel.rows().start;
             let last_row = sel.rows().end;
 
             for line in pane.get_logical_lines(sel.rows()) {
-                if result.is_empty() || !last_was_wrapped {
-                    result.push(Line::with_width(0, line.physical_lines[0].current_seqno()));
-                }
-                let last_idx = line.physical_lines.len().saturating_sub(1);
                 for (idx, phys) in line.physical_lines.iter().enumerate() {
                     let this_row = line.first_row + idx as StableRowIndex;
                     if this_row >= first_row && this_row < last_row {
-                        let last_phys_idx = phys.len().saturating_sub(1);
+                        if result.is_empty() || !(options.join_wrapped_lines && last_was_wrapped) {
+                            result.push(Line::with_width(0, phys.current_seqno()));
+                        }
                         let cols = sel.cols_for_row(this_row, rectangular);
-                        let last_col_idx = cols.end.saturating_sub(1).min(last_phys_idx);
+                        last_was_wrapped = selection_reaches_wrap(phys, &cols);
                         let mut col_span = phys.columns_as_line(cols);
                         let seqno = col_span.current_seqno();
-                        // Only trim trailing whitespace if we are the last line
-                        // in a wrapped sequence
-                        if idx == last_idx {
+                        if options.trim_at_end_of(last_was_wrapped) {
                             col_span.prune_trailing_blanks(seqno);
                         }
 
                         result
                             .last_mut()
                             .map(|line| line.append_line(col_span, seqno));
-
-                        last_was_wrapped = last_col_idx == last_phys_idx
-                            && phys
-                                .get_cell(last_col_idx)
-                                .map(|c| c.attrs().wrapped())
-                                .unwrap_or(false);
                     }
                 }
             }
@@ -64,58 +81,128 @@ impl super::TermWindow {
 
     /// Returns the selection text only
     pub fn selection_text(&self, pane: &Arc<dyn Pane>) -> String {
-        let mut s = String::new();
+        self.selection_text_with_options(pane, |options| options)
+    }
+
+    /// Returns the selection text, transformed for copying to the
+    /// clipboard.  `rewrap_at` overrides `copy_rewrap_column`.
+    /// When `with_timestamps` is true, each line is prefixed with
+    /// its arrival time.
+    pub fn selection_text_for_copy(
+        &self,
+        pane: &Arc<dyn Pane>,
+        rewrap_at: Option<usize>,
+        with_timestamps: bool,
+    ) -> String {
+        let rewrap_column = rewrap_at.unwrap_or(self.config.copy_rewrap_column);
+        let timestamp_format = if with_timestamps {
+            Some(self.config.line_timestamp_format.clone())
+        } else {
+            None
+        };
+        self.selection_text_with_options(pane, |options| CopyOptions {
+            rewrap_column,
+            timestamp_format,
+            ..options
+        })
+    }
+
+    fn selection_text_with_options(
+        &self,
+        pane: &Arc<dyn Pane>,
+        adjust: impl FnOnce(CopyOptions) -> CopyOptions,
+    ) -> String {
         let rectangular = self.selection(pane.pane_id()).rectangular;
-        if let Some(sel) = self
+        let sel = match self
             .selection(pane.pane_id())
             .range
             .as_ref()
             .map(|r| r.normalize())
         {
-            let mut last_was_wrapped = false;
-            let first_row = sel.rows().start;
-            let last_row = sel.rows().end;
-
-            for line in pane.get_logical_lines(sel.rows()) {
-                if !s.is_empty() && !last_was_wrapped {
-                    s.push('\n');
-                }
-                let last_idx = line.physical_lines.len().saturating_sub(1);
-                for (idx, phys) in line.physical_lines.iter().enumerate() {
-                    let this_row = line.first_row + idx as StableRowIndex;
-                    if this_row >= first_row && this_row < last_row {
-                        let last_phys_idx = phys.len().saturating_sub(1);
-                        let cols = sel.cols_for_row(this_row, rectangular);
-                        let last_col_idx = cols.end.saturating_sub(1).min(last_phys_idx);
-                        let col_span = phys.columns_as_str(cols);
-                        // Only trim trailing whitespace if we are the last line
-                        // in a wrapped sequence
-                        if idx == last_idx {
-                            s.push_str(col_span.trim_end());
-                        } else {
-                            s.push_str(&col_span);
-                        }
-
-                        last_was_wrapped = last_col_idx == last_phys_idx
-                            && phys
-                                .get_cell(last_col_idx)
-                                .map(|c| c.attrs().wrapped())
-                                .unwrap_or(false);
-                    }
-                }
-            }
+            Some(sel) => sel,
+            None => return String::new(),
+        };
+        let lines = pane.get_logical_lines(sel.rows());
+        let mut options = adjust(CopyOptions::new(&self.config, rectangular));
+        if options.timestamp_format.is_some() {
+            options.inherited_arrival = lines
+                .first()
+                .and_then(|line| arrival_at(&**pane, line.first_row - 1));
         }
-
-        s
+        selection_text_from_lines(&lines, &sel, rectangular, options)
     }
 
     pub fn clear_selection(&mut self, pane: &Arc<dyn Pane>) {
+        self.pane_state(pane.pane_id()).word_expansion.take();
         let mut selection = self.selection(pane.pane_id());
         selection.clear();
         selection.seqno = pane.get_current_seqno();
+        if self.config.clear_highlights_with_selection {
+            if let Some(highlights) = pane.highlights() {
+                highlights.lock().clear();
+            }
+        }
+        self.window.as_ref().unwrap().invalidate();
+    }
+
+    /// Toggles a persistent highlight of all occurrences of the
+    /// selected text in the pane
+    pub fn highlight_selection(&mut self, pane: &Arc<dyn Pane>) {
+        let highlights = match pane.highlights() {
+            Some(highlights) => highlights,
+            None => return,
+        };
+        let text = self.selection_text(pane);
+        let text = text.trim_end_matches('\n');
+        if text.is_empty() || text.contains('\n') {
+            // Highlights are matched line by line
+            return;
+        }
+        let toggled = highlights.lock().toggle(
+            text.to_string(),
+            &regex::escape(text),
+            self.config.pane_highlight_color,
+        );
+        if let Err(err) = toggled {
+            log::error!("HighlightSelection: {err:#}");
+        }
         self.window.as_ref().unwrap().invalidate();
     }
 
+    /// Returns the selection for each level of double click expansion
+    /// around `start`: one for each of the word boundary levels, followed
+    /// by the hyperlink there, if there is one.  The hyperlink is found
+    /// using the same rule matches that clicking on it would use.
+    fn word_expansion_levels(
+        &self,
+        start: SelectionCoordinate,
+        pane: &Arc<dyn Pane>,
+    ) -> Vec<SelectionRange> {
+        let mut levels: Vec<SelectionRange> = self
+            .config
+            .word_boundary_levels()
+            .iter()
+            .map(|boundary| SelectionRange::word_around(start, &**pane, boundary))
+            .collect();
+        pane.apply_hyperlinks(start.y..start.y + 1, &self.config.hyperlink_rules);
+        levels.extend(SelectionRange::hyperlink_around(start, &**pane));
+        levels
+    }
+
+    /// Returns the word boundary for extending the current double click
+    /// selection by dragging.  A hyperlink selection is extended using
+    /// the widest word boundary.
+    fn word_expansion_boundary(&self, pane_id: PaneId) -> String {
+        let mut levels = self.config.word_boundary_levels();
+        let level = self
+            .pane_state(pane_id)
+            .word_expansion
+            .map(|expansion| expansion.level)
+            .unwrap_or(0)
+            .min(levels.len() - 1);
+        levels.swap_remove(level)
+    }
+
     pub fn extend_selection_at_mouse_cursor(&mut self, mode: SelectionMode, pane: &Arc<dyn Pane>) {
         self.selection(pane.pane_id()).seqno = pane.get_current_seqno();
         let (position, y) = match self.pane_state(pane.pane_id()).mouse_terminal_coords {
@@ -182,14 +269,16 @@ impl super::TermWindow {
                     };
             }
             SelectionMode::Word => {
-                let end_word = SelectionRange::word_around(SelectionCoordinate::x_y(x, y), &**pane);
+                let boundary = self.word_expansion_boundary(pane.pane_id());
+                let end_word =
+                    SelectionRange::word_around(SelectionCoordinate::x_y(x, y), &**pane, &boundary);
 
                 let start_coord = self
                     .selection(pane.pane_id())
                     .origin
                     .clone()
                     .unwrap_or(end_word.start);
-                let start_word = SelectionRange::word_around(start_coord, &**pane);
+                let start_word = SelectionRange::word_around(start_coord, &**pane, &boundary);
 
                 let selection_range = start_word.extend_with(end_word);
                 self.selection(pane.pane_id()).range = Some(selection_range);
@@ -245,6 +334,9 @@ impl super::TermWindow {
             Some(coords) => (coords.0.column, coords.1),
             None => return,
         };
+        if mode != SelectionMode::Word && mode != SelectionMode::Cell {
+            self.pane_state(pane.pane_id()).word_expansion.take();
+        }
         match mode {
             SelectionMode::Line => {
                 let start = SelectionCoordinate::x_y(x, y);
@@ -255,8 +347,25 @@ impl super::TermWindow {
                 self.selection(pane.pane_id()).rectangular = false;
             }
             SelectionMode::Word => {
-                let selection_range =
-                    SelectionRange::word_around(SelectionCoordinate::x_y(x, y), &**pane);
+                // Double clicking the same spot again, while what was
+                // selected there is unchanged, selects the next level
+                let start = SelectionCoordinate::x_y(x, y);
+                let levels = self.word_expansion_levels(start, pane);
+                let level = match self.pane_state(pane.pane_id()).word_expansion.take() {
+                    Some(prior)
+                        if prior.click == start
+                            && levels.get(prior.level) == Some(&prior.range) =>
+                    {
+                        next_expansion_level(&levels, prior.level)
+                    }
+                    _ => 0,
+                };
+                let selection_range = levels[level];
+                self.pane_state(pane.pane_id()).word_expansion = Some(WordExpansion {
+                    click: start,
+                    level,
+                    range: selection_range,
+                });
 
                 self.selection(pane.pane_id()).origin = Some(selection_range.start);
                 self.selection(pane.pane_id()).range = Some(selection_range);
@@ -281,3 +390,446 @@ impl super::TermWindow {
         self.window.as_ref().unwrap().invalidate();
     }
 }
+
+/// Controls how the text of a selection is assembled
+/// from the lines of the model
+#[derive(Clone, Debug)]
+struct CopyOptions {
+    trim_trailing_whitespace: bool,
+    join_wrapped_lines: bool,
+    /// Lines wider than this are re-wrapped after they have been
+    /// joined and trimmed; 0 disables re-wrapping
+    rewrap_column: usize,
+    /// When set, each line is prefixed with its arrival time,
+    /// formatted using this strftime format
+    timestamp_format: Option<String>,
+    /// The arrival time in effect for the line above the copied
+    /// lines, which is shared by those that have none of their own
+    inherited_arrival: Option<u32>,
+}
+
+impl CopyOptions {
+    fn new(config: &ConfigHandle, rectangular: bool) -> Self {
+        Self {
+            trim_trailing_whitespace: config.copy_trim_trailing_whitespace,
+            // A block selection is always copied as one line per row
+            join_wrapped_lines: config.copy_join_wrapped_lines && !rectangular,
+            // Re-wrapping only applies when copying
+            rewrap_column: 0,
+            timestamp_format: None,
+            inherited_arrival: None,
+        }
+    }
+
+    /// Returns true if trailing whitespace should be trimmed from
+    /// a row of the selection.  Whitespace immediately ahead of a
+    /// wrap that is being joined is part of the text and is kept.
+    fn trim_at_end_of(&self, wrapped: bool) -> bool {
+        self.trim_trailing_whitespace && !(self.join_wrapped_lines && wrapped)
+    }
+}
+
+/// Returns true if cols extends up to the end of line and line
+/// was wrapped onto the next row.
+/// The last visible cell is used rather than the last column so that
+/// a double-width cell at the wrap boundary is taken into account.
+fn selection_reaches_wrap(line: &Line, cols: &Range<usize>) -> bool {
+    line.last_cell_was_wrapped()
+        && line
+            .visible_cells()
+            .last()
+            .map(|cell| cols.end > cell.cell_index())
+            .unwrap_or(false)
+}
+
+/// Returns true if the last column of line is a blank cell ahead of
+/// a wrap.  This is what the terminal leaves behind when a double-width
+/// character doesn't fit in the last column and is wrapped onto the
+/// next row instead.
+fn ends_with_wrap_padding(line: &Line) -> bool {
+    line.last_cell_was_wrapped()
+        && line
+            .visible_cells()
+            .last()
+            .map(|cell| cell.str() == " " && cell.cell_index() + 1 == line.len())
+            .unwrap_or(false)
+}
+
+fn selection_text_from_lines(
+    lines: &[LogicalLine],
+    sel: &SelectionRange,
+    rectangular: bool,
+    options: CopyOptions,
+) -> String {
+    let mut s = String::new();
+    // The byte ranges of the text of hyperlinks, which must
+    // not be broken up when re-wrapping
+    let mut links: Vec<Range<usize>> = vec![];
+    let mut last_was_wrapped = false;
+    let mut last_was_padded = false;
+    let first_row = sel.rows().start;
+    let last_row = sel.rows().end;
+    // Lines with no recorded arrival time get a blank prefix of the
+    // same width, so that the text stays aligned
+    let timestamp_cols = options.timestamp_format.as_ref().map(|format| {
+        let now = chrono::Local::now().timestamp() as u32;
+        format_arrival(Some(now), format)
+            .map(|text| unicode_column_width(&text, None))
+            .unwrap_or(0)
+    });
+    let mut arrival = options.inherited_arrival;
+
+    for line in lines {
+        for (idx, phys) in line.physical_lines.iter().enumerate() {
+            let this_row = line.first_row + idx as StableRowIndex;
+            if let Some(secs) = phys.arrival_time() {
+                arrival = Some(secs);
+            }
+            if this_row >= first_row && this_row < last_row {
+                let joined = options.join_wrapped_lines && last_was_wrapped;
+                if !s.is_empty() && !joined {
+                    s.push('\n');
+                }
+                let cols = sel.cols_for_row(this_row, rectangular);
+                if joined && last_was_padded {
+                    // Drop the padding if the double-width character
+                    // that caused it is also being copied
+                    let starts_wide = phys
+                        .visible_cells()
+                        .next()
+                        .map(|cell| cell.cell_index() >= cols.start && cell.width() == 2)
+                        .unwrap_or(false);
+                    if starts_wide && s.ends_with(' ') {
+                        s.pop();
+                    }
+                }
+                last_was_wrapped = selection_reaches_wrap(phys, &cols);
+                last_was_padded = last_was_wrapped && ends_with_wrap_padding(phys);
+                let start = s.len();
+                if let (Some(format), Some(width), false) =
+                    (&options.timestamp_format, timestamp_cols, joined)
+                {
+                    s.push_str(&gutter_text(arrival, format, width + 1));
+                }
+                for cell in phys.visible_cells() {
+                    if cell.cell_index() < cols.start {
+                        continue;
+                    }
+                    if cell.cell_index() >= cols.end {
+                        break;
+                    }
+                    let cell_start = s.len();
+                    s.push_str(cell.str());
+                    if cell.attrs().hyperlink().is_some() {
+                        match links.last_mut() {
+                            Some(link) if link.end == cell_start => link.end = s.len(),
+                            _ => links.push(cell_start..s.len()),
+                        }
+                    }
+                }
+                if options.trim_at_end_of(last_was_wrapped) {
+                    let trimmed = start + s[start..].trim_end().len();
+                    s.truncate(trimmed);
+                }
+            }
+        }
+    }
+
+    if options.rewrap_column > 0 {
+        s = rewrap(&s, options.rewrap_column, &links);
+    }
+
+    s
+}
+
+/// Breaks the lines of `text` that are wider than `column` cells,
+/// preferring to break after whitespace.  Whitespace at a break is
+/// removed.  The byte ranges of `text` in `keep_together` are never
+/// broken, so a line may exceed `column` when one of them doesn't fit.
+fn rewrap(text: &str, column: usize, keep_together: &[Range<usize>]) -> String {
+    let inside = |idx: usize| keep_together.iter().any(|r| r.start < idx && idx < r.end);
+    let mut result = String::with_capacity(text.len());
+    let mut line_start = 0;
+
+    for line in text.split('\n') {
+        if line_start > 0 {
+            result.push('\n');
+        }
+        // Byte offsets are relative to `text` so that they can
+        // be compared with `keep_together`
+        let mut start = line_start;
+        let mut width = 0;
+        let mut break_at: Option<usize> = None;
+
+        for (idx, grapheme) in line.grapheme_indices(true) {
+            let idx = line_start + idx;
+            let is_space = grapheme.chars().all(char::is_whitespace);
+            let grapheme_width = unicode_column_width(grapheme, None);
+
+            if !is_space && width + grapheme_width > column && idx > start {
+                let split = match break_at {
+                    Some(b) if b > start => Some(b),
+                    _ if !inside(idx) => Some(idx),
+                    _ => None,
+                };
+                if let Some(split) = split {
+                    result.push_str(text[start..split].trim_end());
+                    result.push('\n');
+                    start = split;
+                    width = unicode_column_width(&text[start..idx], None);
+                    break_at = None;
+                }
+            }
+
+            width += grapheme_width;
+            let next = idx + grapheme.len();
+            if is_space && !inside(next) {
+                break_at = Some(next);
+            }
+        }
+
+        result.push_str(&text[start..line_start + line.len()]);
+        line_start += line.len() + 1;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::selection::test_util::{logical_line, range};
+    use termwiz::cell::CellAttributes;
+    use termwiz::hyperlink::Hyperlink;
+    use termwiz::surface::SEQ_ZERO;
+
+    fn copy(
+        lines: &[LogicalLine],
+        sel: SelectionRange,
+        rectangular: bool,
+        trim_trailing_whitespace: bool,
+        join_wrapped_lines: bool,
+    ) -> String {
+        selection_text_from_lines(
+            lines,
+            &sel,
+            rectangular,
+            CopyOptions {
+                trim_trailing_whitespace,
+                join_wrapped_lines: join_wrapped_lines && !rectangular,
+                rewrap_column: 0,
+                timestamp_format: None,
+                inherited_arrival: None,
+            },
+        )
+    }
+
+    fn copy_rewrapped(lines: &[LogicalLine], sel: SelectionRange, rewrap_column: usize) -> String {
+        selection_text_from_lines(
+            lines,
+            &sel,
+            false,
+            CopyOptions {
+                trim_trailing_whitespace: true,
+                join_wrapped_lines: true,
+                rewrap_column,
+                timestamp_format: None,
+                inherited_arrival: None,
+            },
+        )
+    }
+
+    #[test]
+    fn rewrap_after_join_and_trim() {
+        // The wrapped rows are joined, then the trailing whitespace
+        // is trimmed, and only then is the result re-wrapped
+        let lines = vec![
+            logical_line(0, &["one two th", "ree four  "]),
+            logical_line(2, &["five      "]),
+        ];
+        let sel = range((0, 0), (9, 2));
+
+        assert_eq!(copy_rewrapped(&lines, sel, 0), "one two three four\nfive");
+        assert_eq!(copy_rewrapped(&lines, sel, 8), "one two\nthree\nfour\nfive");
+        assert_eq!(copy_rewrapped(&lines, sel, 13), "one two three\nfour\nfive");
+    }
+
+    #[test]
+    fn rewrap_without_whitespace() {
+        assert_eq!(rewrap("abcdefgh", 3, &[]), "abc\ndef\ngh");
+        // Double-width graphemes are not split across rows
+        assert_eq!(rewrap("ab日本", 3, &[]), "ab\n日\n本");
+        // Lines that already fit are left alone
+        assert_eq!(rewrap("abc\n\nde  ", 3, &[]), "abc\n\nde  ");
+    }
+
+    #[test]
+    fn rewrap_keeps_hyperlinks_together() {
+        let mut link = CellAttributes::default();
+        link.set_hyperlink(Some(Arc::new(Hyperlink::new("https://example.com"))));
+        let mut line = Line::from_text("see ", &CellAttributes::default(), SEQ_ZERO, None);
+        line.append_line(
+            Line::from_text("example com", &link, SEQ_ZERO, None),
+            SEQ_ZERO,
+        );
+        line.append_line(
+            Line::from_text(" ok", &CellAttributes::default(), SEQ_ZERO, None),
+            SEQ_ZERO,
+        );
+        let lines = vec![LogicalLine {
+            physical_lines: vec![line.clone()],
+            logical: line,
+            first_row: 0,
+        }];
+        let sel = range((0, 0), (17, 0));
+
+        // The space within the link text is not a break opportunity,
+        // and the link is never hard-broken, even though it is
+        // wider than the column
+        assert_eq!(copy_rewrapped(&lines, sel, 8), "see\nexample com\nok");
+        assert_eq!(copy_rewrapped(&lines, sel, 5), "see\nexample com\nok");
+    }
+
+    #[test]
+    fn join_wrapped() {
+        let lines = vec![
+            logical_line(0, &["https://", "example.co", "m/  "]),
+            logical_line(3, &["next   "]),
+        ];
+        let sel = range((0, 0), (9, 3));
+
+        assert_eq!(
+            copy(&lines, sel, false, true, true),
+            "https://example.com/\nnext"
+        );
+        assert_eq!(
+            copy(&lines, sel, false, true, false),
+            "https://\nexample.co\nm/\nnext"
+        );
+        assert_eq!(
+            copy(&lines, sel, false, false, true),
+            "https://example.com/  \nnext   "
+        );
+    }
+
+    #[test]
+    fn whitespace_at_wrap_is_kept_when_joining() {
+        let lines = vec![logical_line(0, &["echo ", "hello "])];
+        let sel = range((0, 0), (9, 1));
+
+        assert_eq!(copy(&lines, sel, false, true, true), "echo hello");
+        assert_eq!(copy(&lines, sel, false, true, false), "echo\nhello");
+    }
+
+    #[test]
+    fn double_width_at_wrap() {
+        // The double-width cell occupies the last two columns
+        let lines = vec![logical_line(0, &["abc日", "本x"])];
+        let sel = range((0, 0), (4, 1));
+
+        assert_eq!(copy(&lines, sel, false, true, true), "abc日本x");
+        assert_eq!(copy(&lines, sel, false, true, false), "abc日\n本x");
+
+        // Selecting from the middle of the wrapped text
+        let sel = range((3, 0), (0, 1));
+        assert_eq!(copy(&lines, sel, false, true, true), "日本");
+    }
+
+    #[test]
+    fn double_width_before_padding() {
+        // The double-width cell didn't fit on the first row, leaving
+        // a blank column ahead of the wrap that is not part of the
+        // text when the rows are joined
+        let lines = vec![logical_line(0, &["abcd ", "日本"])];
+        let sel = range((0, 0), (3, 1));
+
+        assert_eq!(copy(&lines, sel, false, true, true), "abcd日本");
+        assert_eq!(copy(&lines, sel, false, false, true), "abcd日本");
+        assert_eq!(copy(&lines, sel, false, true, false), "abcd\n日本");
+        assert_eq!(copy(&lines, sel, false, false, false), "abcd \n日本");
+    }
+
+    #[test]
+    fn block_selection_is_not_joined() {
+        let lines = vec![logical_line(0, &["abc日", "本xyz"])];
+        let sel = range((2, 0), (4, 1));
+
+        assert_eq!(copy(&lines, sel, true, true, true), "c日\nxyz");
+        assert_eq!(copy(&lines, sel, true, true, false), "c日\nxyz");
+    }
+
+    #[test]
+    fn copy_with_timestamps() {
+        use chrono::TimeZone;
+        let at = |h, m, s| {
+            chrono::Local
+                .with_ymd_and_hms(2024, 3, 1, h, m, s)
+                .single()
+                .unwrap()
+                .timestamp() as u32
+        };
+
+        let mut lines = vec![
+            logical_line(0, &["zero"]),
+            logical_line(1, &["one tw", "o"]),
+            logical_line(3, &["three"]),
+        ];
+        // Arrival times are sparse: the lines below share this one
+        lines[1].physical_lines[0].mark_arrival(at(13, 5, 9));
+        let sel = range((0, 0), (5, 3));
+
+        let with_timestamps = |join_wrapped_lines, inherited_arrival| {
+            selection_text_from_lines(
+                &lines,
+                &sel,
+                false,
+                CopyOptions {
+                    trim_trailing_whitespace: true,
+                    join_wrapped_lines,
+                    rewrap_column: 0,
+                    timestamp_format: Some("%H:%M:%S".to_string()),
+                    inherited_arrival,
+                },
+            )
+        };
+
+        // The arrival time of "zero" is unknown, so its prefix is blank;
+        // joined rows share the timestamp of the first
+        assert_eq!(
+            with_timestamps(true, None),
+            "         zero\n13:05:09 one two\n13:05:09 three"
+        );
+        assert_eq!(
+            with_timestamps(false, None),
+            "         zero\n13:05:09 one tw\n13:05:09 o\n13:05:09 three"
+        );
+        // "zero" shares the arrival time of the line above the selection
+        assert_eq!(
+            with_timestamps(true, Some(at(12, 0, 0))),
+            "12:00:00 zero\n13:05:09 one two\n13:05:09 three"
+        );
+        // Timestamps are only included when asked for
+        assert_eq!(copy(&lines, sel, false, true, true), "zero\none two\nthree");
+    }
+
+    #[test]
+    fn expansion_skips_levels_that_select_the_same() {
+        let word = range((4, 0), (6, 0));
+        let path = range((0, 0), (12, 0));
+        let link = range((0, 0), (10, 0));
+
+        // The big word of a plain word is the same word
+        let levels = [word, word, link];
+        assert_eq!(next_expansion_level(&levels, 0), 2);
+
+        let levels = [word, path, link];
+        assert_eq!(next_expansion_level(&levels, 0), 1);
+        assert_eq!(next_expansion_level(&levels, 1), 2);
+        // There is nothing wider than the link
+        assert_eq!(next_expansion_level(&levels, 2), 2);
+
+        // Without a link, the path is as wide as it gets
+        let levels = [word, path];
+        assert_eq!(next_expansion_level(&levels, 1), 1);
+    }
+}
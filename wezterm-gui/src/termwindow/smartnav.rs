@@ -0,0 +1,371 @@
+//! Lets applications that have splits of their own, such as editors,
+//! take part in moving between and resizing panes.
+//!
+//! An application advertises that it takes part by setting the
+//! `WEZTERM_NAV` user var to `1`.  When ActivatePaneDirection or
+//! AdjustPaneSize is triggered by a key while such an application is
+//! in the active pane, the key is passed to the application instead.
+//! If the application can't move any further in that direction it
+//! replies by setting the `WEZTERM_NAV_EDGE` user var to the name of
+//! the direction, and the assignment is then applied to the wezterm
+//! panes.  If no reply arrives within `smart_pane_navigation_timeout_ms`
+//! the application is assumed to have handled the key itself.
+//! Nothing waits for the reply, so panes without the user var are
+//! acted upon immediately.
+//!
+//! The foreground process at the time that the user var is set is
+//! remembered, so that a `WEZTERM_NAV` left behind by an application
+//! that exited without clearing it doesn't swallow the keys: once the
+//! foreground process has changed the user var is disregarded until
+//! it is set again.
+use crate::termwindow::{TermWindow, TermWindowNotif};
+use config::keyassignment::PaneDirection;
+use mux::pane::{CachePolicy, PaneId};
+use mux::tab::Tab;
+use mux::Mux;
+use smol::Timer;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Set to `1` by an application that takes part in navigation
+const NAV_USER_VAR: &str = "WEZTERM_NAV";
+/// Set by the application to the direction in which it is at its edge
+const NAV_EDGE_USER_VAR: &str = "WEZTERM_NAV_EDGE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavigationAction {
+    Activate,
+    Resize(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingNavigation {
+    id: usize,
+    pane_id: PaneId,
+    direction: PaneDirection,
+    action: NavigationAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NavOwner {
+    /// The foreground process of the pane when it set `WEZTERM_NAV`
+    Process(Option<String>),
+    /// The process that set `WEZTERM_NAV` is no longer in the foreground
+    Stale,
+}
+
+/// Tracks which panes take part in navigation and the navigation
+/// that is waiting for a reply
+#[derive(Debug, Default)]
+pub struct SmartNavigation {
+    owners: HashMap<PaneId, NavOwner>,
+    pending: Option<PendingNavigation>,
+    next_id: usize,
+}
+
+impl SmartNavigation {
+    /// Returns true if navigation keys should be passed to the pane,
+    /// given its `user_vars` and current foreground `process`
+    fn takes_part(
+        &mut self,
+        pane_id: PaneId,
+        user_vars: &HashMap<String, String>,
+        process: Option<String>,
+    ) -> bool {
+        if user_vars.get(NAV_USER_VAR).map(String::as_str) != Some("1") {
+            self.owners.remove(&pane_id);
+            return false;
+        }
+        match self.owners.get(&pane_id) {
+            Some(NavOwner::Stale) => false,
+            Some(NavOwner::Process(owner)) if *owner != process => {
+                log::debug!(
+                    "pane {}: {} was set by {:?} which is no longer \
+                     in the foreground; disregarding it",
+                    pane_id,
+                    NAV_USER_VAR,
+                    owner
+                );
+                self.owners.insert(pane_id, NavOwner::Stale);
+                false
+            }
+            Some(NavOwner::Process(_)) => true,
+            None => {
+                // Set before we were watching, eg: prior to attaching
+                // to a mux server; the best we can do is trust it
+                self.owners.insert(pane_id, NavOwner::Process(process));
+                true
+            }
+        }
+    }
+
+    /// Records that navigation was passed to `pane_id`, returning
+    /// the id with which it can be expired
+    fn begin(
+        &mut self,
+        pane_id: PaneId,
+        direction: PaneDirection,
+        action: NavigationAction,
+    ) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.replace(PendingNavigation {
+            id,
+            pane_id,
+            direction,
+            action,
+        });
+        id
+    }
+
+    /// Forgets the pending navigation `id` if no reply has arrived for it
+    fn expire(&mut self, id: usize) {
+        if self.pending.as_ref().map(|pending| pending.id) == Some(id) {
+            self.pending.take();
+        }
+    }
+
+    /// Called when the user var `name` of `pane_id` is set while
+    /// `process` is in the foreground.  Returns the pending navigation
+    /// if this is the reply that it is waiting for.
+    fn user_var_changed(
+        &mut self,
+        pane_id: PaneId,
+        name: &str,
+        value: &str,
+        process: impl FnOnce() -> Option<String>,
+    ) -> Option<PendingNavigation> {
+        match name {
+            NAV_USER_VAR => {
+                if value == "1" {
+                    self.owners.insert(pane_id, NavOwner::Process(process()));
+                } else {
+                    self.owners.remove(&pane_id);
+                }
+                None
+            }
+            NAV_EDGE_USER_VAR => match self.pending {
+                Some(pending)
+                    if pending.pane_id == pane_id
+                        && value.eq_ignore_ascii_case(&format!("{:?}", pending.direction)) =>
+                {
+                    self.pending.take()
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl TermWindow {
+    /// Passes the key that triggered `action` to the application in the
+    /// active pane of `tab` if it takes part in navigation, returning
+    /// true if it did so.  The action is then performed if and when
+    /// the application replies that it is at its edge.
+    pub fn navigate_via_application(
+        &mut self,
+        tab: &Arc<Tab>,
+        direction: PaneDirection,
+        action: NavigationAction,
+    ) -> bool {
+        if !self.config.smart_pane_navigation {
+            return false;
+        }
+        if matches!(direction, PaneDirection::Next | PaneDirection::Prev) {
+            return false;
+        }
+        let (key, mods) = match self.assignment_key_press {
+            Some(key_press) => key_press,
+            None => return false,
+        };
+        let pane = match tab.get_active_pane() {
+            Some(pane) => pane,
+            None => return false,
+        };
+        let user_vars = pane.copy_user_vars();
+        if !user_vars.contains_key(NAV_USER_VAR) {
+            return false;
+        }
+        let process = pane.get_foreground_process_name(CachePolicy::FetchImmediate);
+        if !self
+            .smart_navigation
+            .takes_part(pane.pane_id(), &user_vars, process)
+        {
+            return false;
+        }
+        let window = match self.window.clone() {
+            Some(window) => window,
+            None => return false,
+        };
+
+        if let Err(err) = pane.key_down(key, mods) {
+            log::error!(
+                "passing navigation key to pane {}: {:#}",
+                pane.pane_id(),
+                err
+            );
+            return false;
+        }
+
+        let id = self
+            .smart_navigation
+            .begin(pane.pane_id(), direction, action);
+
+        let timeout = Duration::from_millis(self.config.smart_pane_navigation_timeout_ms);
+        promise::spawn::spawn(async move {
+            Timer::after(timeout).await;
+            window.notify(TermWindowNotif::Apply(Box::new(move |tw| {
+                tw.smart_navigation.expire(id);
+            })));
+        })
+        .detach();
+
+        true
+    }
+
+    /// Called when the user var `name` of `pane_id` is set; performs
+    /// the pending navigation if this is the reply that it is waiting for
+    pub fn handle_navigation_reply(&mut self, pane_id: PaneId, name: &str, value: &str) {
+        let mux = Mux::get();
+        let pending = match self
+            .smart_navigation
+            .user_var_changed(pane_id, name, value, || {
+                mux.get_pane(pane_id)
+                    .and_then(|pane| pane.get_foreground_process_name(CachePolicy::FetchImmediate))
+            }) {
+            Some(pending) => pending,
+            None => return,
+        };
+
+        let tab = match mux
+            .resolve_pane_id(pane_id)
+            .and_then(|(_domain_id, _window_id, tab_id)| mux.get_tab(tab_id))
+        {
+            Some(tab) => tab,
+            None => return,
+        };
+        // The user may have moved elsewhere while the
+        // application was deciding
+        if tab.get_active_pane().map(|pane| pane.pane_id()) != Some(pane_id)
+            || self.tab_state(tab.tab_id()).overlay.is_some()
+        {
+            return;
+        }
+        match pending.action {
+            NavigationAction::Activate => tab.activate_pane_direction(pending.direction),
+            NavigationAction::Resize(amount) => tab.adjust_pane_size(pending.direction, amount),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vars(value: &str) -> HashMap<String, String> {
+        let mut vars = HashMap::new();
+        vars.insert(NAV_USER_VAR.to_string(), value.to_string());
+        vars
+    }
+
+    fn nvim() -> Option<String> {
+        Some("nvim".to_string())
+    }
+
+    fn shell() -> Option<String> {
+        Some("zsh".to_string())
+    }
+
+    #[test]
+    fn takes_part_while_the_setter_is_in_the_foreground() {
+        let mut nav = SmartNavigation::default();
+        assert_eq!(nav.user_var_changed(1, NAV_USER_VAR, "1", nvim), None);
+        assert!(nav.takes_part(1, &vars("1"), nvim()));
+        assert!(nav.takes_part(1, &vars("1"), nvim()));
+        assert!(!nav.takes_part(1, &vars("0"), nvim()));
+        assert!(!nav.takes_part(1, &HashMap::new(), nvim()));
+    }
+
+    #[test]
+    fn stale_user_var_is_disregarded() {
+        let mut nav = SmartNavigation::default();
+        nav.user_var_changed(1, NAV_USER_VAR, "1", nvim);
+
+        // nvim exited without clearing the user var
+        assert!(!nav.takes_part(1, &vars("1"), shell()));
+        // and running it again doesn't revive the var until it sets it
+        assert!(!nav.takes_part(1, &vars("1"), nvim()));
+
+        nav.user_var_changed(1, NAV_USER_VAR, "1", nvim);
+        assert!(nav.takes_part(1, &vars("1"), nvim()));
+
+        // other panes are unaffected
+        nav.user_var_changed(2, NAV_USER_VAR, "1", shell);
+        assert!(nav.takes_part(2, &vars("1"), shell()));
+        assert!(!nav.takes_part(1, &vars("1"), shell()));
+    }
+
+    #[test]
+    fn user_var_set_before_watching_is_trusted() {
+        let mut nav = SmartNavigation::default();
+        assert!(nav.takes_part(1, &vars("1"), nvim()));
+        assert!(!nav.takes_part(1, &vars("1"), shell()));
+    }
+
+    #[test]
+    fn cleared_user_var_forgets_the_owner() {
+        let mut nav = SmartNavigation::default();
+        nav.user_var_changed(1, NAV_USER_VAR, "1", nvim);
+        nav.user_var_changed(1, NAV_USER_VAR, "", || panic!("not needed"));
+        assert!(nav.owners.is_empty());
+    }
+
+    #[test]
+    fn reply_performs_the_pending_navigation() {
+        let mut nav = SmartNavigation::default();
+        nav.begin(1, PaneDirection::Left, NavigationAction::Resize(3));
+
+        // Unrelated vars, other panes and other directions are ignored
+        assert_eq!(nav.user_var_changed(1, "other", "Left", nvim), None);
+        assert_eq!(
+            nav.user_var_changed(2, NAV_EDGE_USER_VAR, "Left", nvim),
+            None
+        );
+        assert_eq!(
+            nav.user_var_changed(1, NAV_EDGE_USER_VAR, "Right", nvim),
+            None
+        );
+
+        let pending = nav
+            .user_var_changed(1, NAV_EDGE_USER_VAR, "left", nvim)
+            .unwrap();
+        assert_eq!(pending.direction, PaneDirection::Left);
+        assert_eq!(pending.action, NavigationAction::Resize(3));
+
+        // Only once
+        assert_eq!(
+            nav.user_var_changed(1, NAV_EDGE_USER_VAR, "Left", nvim),
+            None
+        );
+    }
+
+    #[test]
+    fn reply_after_the_timeout_is_ignored() {
+        let mut nav = SmartNavigation::default();
+        let first = nav.begin(1, PaneDirection::Up, NavigationAction::Activate);
+        nav.expire(first);
+        assert_eq!(nav.user_var_changed(1, NAV_EDGE_USER_VAR, "Up", nvim), None);
+
+        // An expired timer doesn't cancel a later navigation
+        let first = nav.begin(1, PaneDirection::Up, NavigationAction::Activate);
+        let second = nav.begin(1, PaneDirection::Down, NavigationAction::Activate);
+        assert_ne!(first, second);
+        nav.expire(first);
+        assert_eq!(nav.user_var_changed(1, NAV_EDGE_USER_VAR, "Up", nvim), None);
+        assert!(nav
+            .user_var_changed(1, NAV_EDGE_USER_VAR, "Down", nvim)
+            .is_some());
+    }
+}
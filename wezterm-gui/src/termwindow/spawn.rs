@@ -1,7 +1,10 @@
 use crate::spawn::SpawnWhere;
+use crate::termwindow::TermWindowNotif;
 use config::keyassignment::{SpawnCommand, SpawnTabDomain};
 use config::TermConfig;
+use mux::pane::Pane;
 use std::sync::Arc;
+use wezterm_term::StableRowIndex;
 
 impl super::TermWindow {
     pub fn spawn_command(&self, spawn: &SpawnCommand, spawn_where: SpawnWhere) {
@@ -24,6 +27,59 @@ impl super::TermWindow {
         )
     }
 
+    /// Splits `pane` and seeds the new pane with a copy of its
+    /// scrollback, scrolled so that it shows the same lines as the
+    /// viewport of `pane` does right now
+    pub fn split_pane_with_scrollback(
+        &self,
+        pane: &Arc<dyn Pane>,
+        spawn: &SpawnCommand,
+        spawn_where: SpawnWhere,
+    ) {
+        let dims = pane.get_dimensions();
+        let viewport_bottom = self
+            .get_viewport(pane.pane_id())
+            .unwrap_or(dims.physical_top)
+            + dims.viewport_rows as StableRowIndex;
+
+        let source = Arc::clone(pane);
+        let spawn = spawn.clone();
+        let size = self.terminal_size;
+        let src_window_id = self.mux_window_id;
+        let term_config = Arc::new(TermConfig::with_config(self.config.clone()));
+        let window = self.window.as_ref().unwrap().clone();
+
+        promise::spawn::spawn(async move {
+            let pane = match crate::spawn::spawn_command_internal(
+                spawn,
+                spawn_where,
+                size,
+                Some(src_window_id),
+                term_config,
+            )
+            .await
+            {
+                Ok(pane) => pane,
+                Err(err) => {
+                    log::error!("Failed to spawn: {:#}", err);
+                    return;
+                }
+            };
+
+            match mux::scrollback::inherit_scrollback(&source, &pane, viewport_bottom).await {
+                Ok(end) => {
+                    window.notify(TermWindowNotif::Apply(Box::new(move |term_window| {
+                        let dims = pane.get_dimensions();
+                        let top = end - dims.viewport_rows as StableRowIndex;
+                        term_window.set_viewport(pane.pane_id(), Some(top), dims);
+                    })));
+                }
+                Err(err) => log::error!("Failed to copy scrollback: {:#}", err),
+            }
+        })
+        .detach();
+    }
+
     pub fn spawn_tab(&mut self, domain: &SpawnTabDomain) {
         self.spawn_command(
             &SpawnCommand {
@@ -0,0 +1,409 @@
+//! Keyboard focus for the tab bar, activated by `FocusTabBar`, so
+//! that the tabs and the buttons in the tab bar can be used without
+//! a pointer. While the modal is active, the focused item is drawn
+//! with a focus ring over the tab bar.
+use crate::tabbar::TabBarItem;
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::{DimensionContext, TermWindow, UIItemType};
+use config::keyassignment::KeyAssignment;
+use config::Dimension;
+use mux::Mux;
+use std::cell::{Ref, RefCell};
+use std::time::{Duration, Instant};
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+use window::color::LinearRgba;
+use window::{MousePress, RectF};
+
+/// Typing after a pause of this length starts a new prefix,
+/// rather than extending the current one
+const TYPEAHEAD_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The thickness of the focus ring, in pixels
+const RING_WIDTH: f32 = 2.;
+
+pub struct TabBarFocus {
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    focused: RefCell<TabBarItem>,
+    prefix: RefCell<String>,
+    last_typed: RefCell<Option<Instant>>,
+}
+
+/// Returns true for the items that can receive the focus
+fn is_focusable(item: &TabBarItem) -> bool {
+    matches!(
+        item,
+        TabBarItem::Tab { .. }
+            | TabBarItem::NewTabButton
+            | TabBarItem::ScrollLeft
+            | TabBarItem::ScrollRight
+            | TabBarItem::WindowButton(_)
+    )
+}
+
+/// Compares two items, ignoring the active state of tabs as that
+/// changes when tabs are activated
+fn same_item(a: &TabBarItem, b: &TabBarItem) -> bool {
+    match (a, b) {
+        (TabBarItem::Tab { tab_idx: a, .. }, TabBarItem::Tab { tab_idx: b, .. }) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Returns the item that is `delta` steps away from `current`,
+/// stopping at either end of `items`. If `current` is no longer
+/// present, the item at the end that we are moving away from is
+/// returned instead.
+fn step(items: &[TabBarItem], current: &TabBarItem, delta: isize) -> Option<TabBarItem> {
+    if items.is_empty() {
+        return None;
+    }
+    let last = items.len() as isize - 1;
+    let idx = match items.iter().position(|item| same_item(item, current)) {
+        Some(idx) => (idx as isize + delta).clamp(0, last),
+        None if delta < 0 => last,
+        None => 0,
+    };
+    Some(items[idx as usize])
+}
+
+/// Returns the index of the first title that starts with `prefix`,
+/// ignoring case
+fn find_by_prefix(titles: &[String], prefix: &str) -> Option<usize> {
+    let prefix = prefix.to_lowercase();
+    titles
+        .iter()
+        .position(|title| title.to_lowercase().starts_with(&prefix))
+}
+
+impl TabBarFocus {
+    pub fn new(term_window: &mut TermWindow) -> Self {
+        let tab_idx = Mux::get()
+            .get_window(term_window.mux_window_id)
+            .map(|window| window.get_active_idx())
+            .unwrap_or(0);
+        Self {
+            element: RefCell::new(None),
+            focused: RefCell::new(TabBarItem::Tab {
+                tab_idx,
+                active: true,
+            }),
+            prefix: RefCell::new(String::new()),
+            last_typed: RefCell::new(None),
+        }
+    }
+
+    fn focusable_items(term_window: &TermWindow) -> Vec<TabBarItem> {
+        term_window
+            .tab_bar
+            .items()
+            .iter()
+            .map(|entry| entry.item)
+            .filter(is_focusable)
+            .collect()
+    }
+
+    fn set_focus(&self, item: TabBarItem, term_window: &mut TermWindow) {
+        *self.focused.borrow_mut() = item;
+        term_window.invalidate_modal();
+    }
+
+    fn move_focus(&self, delta: isize, term_window: &mut TermWindow) {
+        let items = Self::focusable_items(term_window);
+        let current = *self.focused.borrow();
+        if let Some(item) = step(&items, &current, delta) {
+            self.prefix.borrow_mut().clear();
+            self.set_focus(item, term_window);
+        }
+    }
+
+    fn focus_end(&self, last: bool, term_window: &mut TermWindow) {
+        let items = Self::focusable_items(term_window);
+        let item = if last { items.last() } else { items.first() };
+        if let Some(item) = item.copied() {
+            self.prefix.borrow_mut().clear();
+            self.set_focus(item, term_window);
+        }
+    }
+
+    /// Moves the focus to the first tab whose title matches the
+    /// characters typed so far
+    fn type_ahead(&self, c: char, term_window: &mut TermWindow) {
+        let titles: Vec<String> = match Mux::get().get_window(term_window.mux_window_id) {
+            Some(window) => window
+                .iter()
+                .map(|tab| {
+                    let title = tab.get_title();
+                    if title.is_empty() {
+                        tab.get_active_pane()
+                            .map(|pane| pane.get_title())
+                            .unwrap_or_default()
+                    } else {
+                        title
+                    }
+                })
+                .collect(),
+            None => return,
+        };
+
+        let now = Instant::now();
+        let expired = self
+            .last_typed
+            .borrow_mut()
+            .replace(now)
+            .map(|last| now - last > TYPEAHEAD_TIMEOUT)
+            .unwrap_or(true);
+
+        let mut prefix = self.prefix.borrow_mut();
+        if expired {
+            prefix.clear();
+        }
+        prefix.push(c);
+
+        // If the extended prefix doesn't match, treat the character
+        // as the start of a new prefix
+        let found = find_by_prefix(&titles, &prefix).or_else(|| {
+            prefix.clear();
+            prefix.push(c);
+            find_by_prefix(&titles, &prefix)
+        });
+        drop(prefix);
+
+        if let Some(tab_idx) = found {
+            self.set_focus(
+                TabBarItem::Tab {
+                    tab_idx,
+                    active: false,
+                },
+                term_window,
+            );
+        }
+    }
+
+    fn activate(&self, term_window: &mut TermWindow) {
+        let item = *self.focused.borrow();
+        match item {
+            TabBarItem::Tab { tab_idx, .. } => {
+                term_window.cancel_modal();
+                term_window.activate_tab(tab_idx as isize).ok();
+            }
+            TabBarItem::NewTabButton => {
+                term_window.cancel_modal();
+                term_window.do_new_tab_button_click(MousePress::Left);
+            }
+            TabBarItem::ScrollLeft => {
+                term_window.scroll_tab_bar(-1);
+                term_window.invalidate_modal();
+            }
+            TabBarItem::ScrollRight => {
+                term_window.scroll_tab_bar(1);
+                term_window.invalidate_modal();
+            }
+            TabBarItem::WindowButton(button) => {
+                term_window.cancel_modal();
+                term_window.perform_title_button(button);
+            }
+            TabBarItem::None | TabBarItem::LeftStatus | TabBarItem::RightStatus => {}
+        }
+    }
+
+    fn close_focused_tab(&self, term_window: &mut TermWindow) {
+        let item = *self.focused.borrow();
+        if let TabBarItem::Tab { tab_idx, .. } = item {
+            // The confirmation prompt needs the keyboard, so give
+            // the focus back to the pane first
+            term_window.cancel_modal();
+            term_window.close_specific_tab(tab_idx, true);
+        }
+    }
+
+    fn compute(
+        term_window: &mut TermWindow,
+        focused: &TabBarItem,
+    ) -> anyhow::Result<Vec<ComputedElement>> {
+        // The tab bar has already been painted in this frame, so
+        // its ui items tell us where the focused item is
+        let bounds = term_window
+            .ui_items
+            .iter()
+            .filter(|ui_item| match &ui_item.item_type {
+                UIItemType::TabBar(item) => same_item(item, focused),
+                _ => false,
+            })
+            .map(|ui_item| -> RectF {
+                euclid::rect(
+                    ui_item.x as f32,
+                    ui_item.y as f32,
+                    ui_item.width as f32,
+                    ui_item.height as f32,
+                )
+            })
+            .reduce(|a, b| a.union(&b));
+        let bounds = match bounds {
+            Some(bounds) => bounds,
+            None => return Ok(vec![]),
+        };
+
+        let font = term_window.fonts.title_font()?;
+        let metrics = term_window.render_metrics;
+        let color = term_window.palette().cursor_bg.to_linear();
+
+        let element = Element::new(&font, ElementContent::Text(String::new()))
+            .colors(ElementColors {
+                border: BorderColor::new(color),
+                bg: LinearRgba::TRANSPARENT.into(),
+                text: color.into(),
+            })
+            .border(BoxDimension::new(Dimension::Pixels(RING_WIDTH)))
+            .min_width(Some(Dimension::Pixels(
+                (bounds.width() - 2. * RING_WIDTH).max(0.),
+            )))
+            .min_height(Some(Dimension::Pixels(
+                (bounds.height() - 2. * RING_WIDTH).max(0.),
+            )));
+
+        let dimensions = term_window.dimensions;
+        let mut computed = term_window.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: bounds.height(),
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: bounds.width(),
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(0., 0., bounds.width(), bounds.height()),
+                metrics: &metrics,
+                gl_state: term_window.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+        computed.translate(euclid::vec2(bounds.min_x(), bounds.min_y()));
+
+        Ok(vec![computed])
+    }
+}
+
+impl Modal for TabBarFocus {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        false
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<bool> {
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                term_window.cancel_modal();
+            }
+            (KeyCode::LeftArrow | KeyCode::UpArrow, KeyModifiers::NONE)
+            | (KeyCode::Tab, KeyModifiers::SHIFT) => {
+                self.move_focus(-1, term_window);
+            }
+            (KeyCode::RightArrow | KeyCode::DownArrow | KeyCode::Tab, KeyModifiers::NONE) => {
+                self.move_focus(1, term_window);
+            }
+            (KeyCode::Home, KeyModifiers::NONE) => {
+                self.focus_end(false, term_window);
+            }
+            (KeyCode::End, KeyModifiers::NONE) => {
+                self.focus_end(true, term_window);
+            }
+            (KeyCode::Enter | KeyCode::Char(' '), KeyModifiers::NONE) => {
+                self.activate(term_window);
+            }
+            (KeyCode::Delete, KeyModifiers::NONE) => {
+                self.close_focused_tab(term_window);
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                self.type_ahead(c, term_window);
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        // The position of the focused item depends on the layout
+        // of the tab bar, so this is recomputed for every frame
+        let element = Self::compute(term_window, &self.focused.borrow())?;
+        self.element.borrow_mut().replace(element);
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tab(tab_idx: usize) -> TabBarItem {
+        TabBarItem::Tab {
+            tab_idx,
+            active: false,
+        }
+    }
+
+    #[test]
+    fn step_through_items() {
+        let items = [
+            TabBarItem::Tab {
+                tab_idx: 0,
+                active: true,
+            },
+            tab(1),
+            TabBarItem::NewTabButton,
+        ];
+
+        // The active state is ignored when locating the current item
+        assert_eq!(step(&items, &tab(0), 1), Some(tab(1)));
+        assert_eq!(step(&items, &tab(1), 1), Some(TabBarItem::NewTabButton));
+        // Movement stops at either end
+        assert_eq!(
+            step(&items, &TabBarItem::NewTabButton, 1),
+            Some(TabBarItem::NewTabButton)
+        );
+        assert_eq!(step(&items, &tab(0), -1), Some(items[0]));
+        // A tab that scrolled out of view re-enters from the end
+        // that is being moved away from
+        assert_eq!(step(&items, &tab(7), 1), Some(items[0]));
+        assert_eq!(step(&items, &tab(7), -1), Some(TabBarItem::NewTabButton));
+        assert_eq!(step(&[], &tab(0), 1), None);
+    }
+
+    #[test]
+    fn prefix_matching() {
+        let titles = vec![
+            "zsh".to_string(),
+            "Vim".to_string(),
+            "vim src/main.rs".to_string(),
+        ];
+        assert_eq!(find_by_prefix(&titles, "v"), Some(1));
+        assert_eq!(find_by_prefix(&titles, "vim "), Some(2));
+        assert_eq!(find_by_prefix(&titles, "Z"), Some(0));
+        assert_eq!(find_by_prefix(&titles, "x"), None);
+    }
+}
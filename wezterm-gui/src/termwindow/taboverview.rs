@@ -0,0 +1,514 @@
+//! The tab overview is a modal that shows a grid of thumbnails of the
+//! tabs in the current window, rendered as scaled down text from the
+//! viewport of the active pane of each tab.
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::{DimensionContext, TermWindow};
+use crate::utilsprites::RenderMetrics;
+use config::keyassignment::KeyAssignment;
+use config::{Dimension, TabBarColors};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use mux::pane::{Pane, PaneId};
+use mux::Mux;
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+use std::sync::Arc;
+use wezterm_font::units::PixelUnit;
+use wezterm_font::LoadedFont;
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent, StableRowIndex};
+use window::color::LinearRgba;
+
+/// The number of columns of text that we'd like a thumbnail to show
+const THUMBNAIL_COLS: f32 = 80.;
+
+struct TabEntry {
+    tab_idx: usize,
+    title: String,
+    pane: Option<Arc<dyn Pane>>,
+}
+
+fn collect_tabs(term_window: &TermWindow) -> (Vec<TabEntry>, usize) {
+    let mux = Mux::get();
+    let window = match mux.get_window(term_window.mux_window_id) {
+        Some(window) => window,
+        None => return (vec![], 0),
+    };
+    let tabs = window
+        .iter()
+        .enumerate()
+        .map(|(tab_idx, tab)| {
+            let pane = tab.get_active_pane();
+            let title = tab.get_title();
+            let title = if title.is_empty() {
+                pane.as_ref()
+                    .map(|pane| pane.get_title())
+                    .unwrap_or_default()
+            } else {
+                title
+            };
+            TabEntry {
+                tab_idx,
+                title,
+                pane,
+            }
+        })
+        .collect();
+    (tabs, window.get_active_idx())
+}
+
+/// Returns the indices of the entries that match filter, best match first
+fn compute_matches(filter: &str, tabs: &[TabEntry]) -> Vec<usize> {
+    if filter.is_empty() {
+        return (0..tabs.len()).collect();
+    }
+    let matcher = SkimMatcherV2::default();
+    let mut scores: Vec<(usize, i64)> = tabs
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, entry)| {
+            matcher
+                .fuzzy_match(&entry.title, filter)
+                .map(|score| (idx, score))
+        })
+        .collect();
+    scores.sort_by(|a, b| a.1.cmp(&b.1).reverse());
+    scores.into_iter().map(|(idx, _)| idx).collect()
+}
+
+fn layout_context<'a>(
+    term_window: &'a TermWindow,
+    metrics: &'a RenderMetrics,
+    bounds: euclid::Rect<f32, PixelUnit>,
+    zindex: i8,
+) -> LayoutContext<'a> {
+    let dimensions = term_window.dimensions;
+    LayoutContext {
+        height: DimensionContext {
+            dpi: dimensions.dpi as f32,
+            pixel_max: dimensions.pixel_height as f32,
+            pixel_cell: metrics.cell_size.height as f32,
+        },
+        width: DimensionContext {
+            dpi: dimensions.dpi as f32,
+            pixel_max: dimensions.pixel_width as f32,
+            pixel_cell: metrics.cell_size.width as f32,
+        },
+        bounds,
+        metrics,
+        gl_state: term_window.render_state.as_ref().unwrap(),
+        zindex,
+    }
+}
+
+pub struct TabOverview {
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    tabs: Vec<TabEntry>,
+    active_idx: usize,
+    /// The indices into tabs that are currently displayed
+    matches: RefCell<Vec<usize>>,
+    filter: RefCell<String>,
+    /// Index into matches
+    selected: RefCell<usize>,
+    /// The number of columns in the grid, and the number
+    /// of thumbnails that fit on a page, as of the last layout
+    grid: RefCell<(usize, usize)>,
+}
+
+impl TabOverview {
+    pub fn new(term_window: &mut TermWindow) -> Self {
+        let (tabs, active_idx) = collect_tabs(term_window);
+
+        Self {
+            element: RefCell::new(None),
+            active_idx,
+            matches: RefCell::new((0..tabs.len()).collect()),
+            tabs,
+            filter: RefCell::new(String::new()),
+            selected: RefCell::new(active_idx),
+            grid: RefCell::new((1, 1)),
+        }
+    }
+
+    /// Returns true if the thumbnail of one of the tabs is
+    /// rendered from pane_id, and thus needs to be refreshed
+    /// when that pane produces output
+    pub fn shows_pane(&self, pane_id: PaneId) -> bool {
+        self.tabs.iter().any(|entry| {
+            entry
+                .pane
+                .as_ref()
+                .map_or(false, |pane| pane.pane_id() == pane_id)
+        })
+    }
+
+    fn update_matches(&self) {
+        let matches = compute_matches(&self.filter.borrow(), &self.tabs);
+        *self.matches.borrow_mut() = matches;
+        *self.selected.borrow_mut() = 0;
+    }
+
+    fn move_by(&self, delta: isize) {
+        let limit = self.matches.borrow().len().saturating_sub(1) as isize;
+        let mut selected = self.selected.borrow_mut();
+        *selected = (*selected as isize + delta).max(0).min(limit) as usize;
+    }
+
+    fn activate_selected(&self, term_window: &mut TermWindow) {
+        let tab_idx = match self
+            .matches
+            .borrow()
+            .get(*self.selected.borrow())
+            .and_then(|idx| self.tabs.get(*idx))
+        {
+            Some(entry) => entry.tab_idx,
+            None => return,
+        };
+        term_window.cancel_modal();
+        if let Some(pane) = term_window.get_active_pane_or_overlay() {
+            let action = KeyAssignment::ActivateTab(tab_idx as isize);
+            if let Err(err) = term_window.perform_key_assignment(&pane, &action) {
+                log::error!("Error while performing {action:?}: {err:#}");
+            }
+        }
+    }
+
+    /// Builds the thumbnail for a pane from the text in its viewport.
+    /// For panes in remote domains this is whatever we last received.
+    fn thumbnail(
+        &self,
+        pane: &Arc<dyn Pane>,
+        font: &Rc<LoadedFont>,
+        num_rows: usize,
+    ) -> Vec<Element> {
+        let dims = pane.get_dimensions();
+        let num_rows = num_rows.min(dims.viewport_rows);
+        let bottom = dims.physical_top + dims.viewport_rows as StableRowIndex;
+        let (_first_row, lines) = pane.get_lines(bottom - num_rows as StableRowIndex..bottom);
+        let palette = pane.palette();
+
+        lines
+            .iter()
+            .map(|line| {
+                Element::with_line(font, line, &palette)
+                    .min_height(Some(Dimension::Cells(1.)))
+                    .display(DisplayType::Block)
+            })
+            .collect()
+    }
+
+    fn compute(&self, term_window: &mut TermWindow) -> anyhow::Result<Vec<ComputedElement>> {
+        let title_font = term_window.fonts.title_font()?;
+        let title_metrics = RenderMetrics::with_font_metrics(&title_font.metrics());
+        let thumb_font = term_window.fonts.tab_overview_font()?;
+        let thumb_metrics = RenderMetrics::with_font_metrics(&thumb_font.metrics());
+
+        let colors = term_window
+            .config
+            .colors
+            .as_ref()
+            .and_then(|c| c.tab_bar.as_ref())
+            .cloned()
+            .unwrap_or_else(TabBarColors::default);
+        let active_tab = colors.active_tab();
+        let inactive_tab = colors.inactive_tab();
+        let hover_tab = colors.inactive_tab_hover();
+        let edge = colors.inactive_tab_edge().to_linear();
+        let background: InheritableColor = colors.background().to_linear().into();
+        let text: InheritableColor = inactive_tab.fg_color.to_linear().into();
+
+        let tab_bar = term_window.tab_bar_reserved_edges()?;
+        let top_bar_height = tab_bar.top;
+        let (padding_left, padding_top) = term_window.padding_left_top();
+        let border = term_window.get_os_border();
+        let left = tab_bar.left + padding_left + border.left.get() as f32;
+        let top = top_bar_height + padding_top + border.top.get() as f32;
+
+        let size = term_window.terminal_size;
+        let avail_width = size.cols as f32 * term_window.render_metrics.cell_size.width as f32;
+        let avail_height = size.rows as f32 * term_window.render_metrics.cell_size.height as f32;
+
+        let gap = title_metrics.cell_size.width as f32;
+        let title_height = title_metrics.cell_size.height as f32 * 1.5;
+        let header_height = title_height;
+        let footer_height = title_height;
+        let grid_height = (avail_height - header_height - footer_height).max(1.);
+
+        // Lay out the grid so that each thumbnail shows around
+        // THUMBNAIL_COLS columns, with the aspect ratio of the window
+        let thumb_cell_width = thumb_metrics.cell_size.width as f32;
+        let thumb_cell_height = thumb_metrics.cell_size.height as f32;
+        let cols = ((avail_width - gap) / (thumb_cell_width * THUMBNAIL_COLS + gap))
+            .floor()
+            .max(1.) as usize;
+        let thumb_width = ((avail_width - gap) / cols as f32 - gap).max(1.);
+        let thumb_height = (thumb_width * avail_height / avail_width)
+            .min(grid_height - title_height - gap)
+            .max(thumb_cell_height);
+        let rows = (grid_height / (thumb_height + title_height + gap))
+            .floor()
+            .max(1.) as usize;
+        let per_page = cols * rows;
+        *self.grid.borrow_mut() = (cols, per_page);
+        let thumb_rows = ((thumb_height - 2.) / thumb_cell_height).floor().max(1.) as usize;
+
+        let matches = self.matches.borrow();
+        let selected = *self.selected.borrow();
+        let page = selected / per_page;
+        let num_pages = ((matches.len() + per_page - 1) / per_page).max(1);
+
+        let filter = self.filter.borrow();
+        let header = if filter.is_empty() {
+            "Tab Overview".to_string()
+        } else {
+            format!("Tab Overview: {filter}_")
+        };
+        let footer = if matches.is_empty() {
+            "No matching tabs".to_string()
+        } else {
+            format!(
+                "Page {}/{}  arrows/hjkl: move  Enter: activate  type to filter  Esc: cancel",
+                page + 1,
+                num_pages
+            )
+        };
+
+        let mut computed = vec![];
+
+        let panel = Element::new(
+            &title_font,
+            ElementContent::Children(vec![Element::new(
+                &title_font,
+                ElementContent::Text(header),
+            )
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.5),
+                right: Dimension::Cells(0.5),
+                top: Dimension::Cells(0.25),
+                bottom: Dimension::Cells(0.),
+            })
+            .display(DisplayType::Block)]),
+        )
+        .colors(ElementColors {
+            border: BorderColor::new(edge),
+            bg: background,
+            text: text.clone(),
+        })
+        .border(BoxDimension::new(Dimension::Pixels(1.)))
+        .min_width(Some(Dimension::Pixels(avail_width)))
+        .min_height(Some(Dimension::Pixels(avail_height)));
+        computed.push(term_window.compute_element(
+            &layout_context(
+                term_window,
+                &title_metrics,
+                euclid::rect(left, top, avail_width, avail_height),
+                100,
+            ),
+            &panel,
+        )?);
+
+        let footer = Element::new(&title_font, ElementContent::Text(footer))
+            .colors(ElementColors {
+                border: BorderColor::default(),
+                bg: LinearRgba::TRANSPARENT.into(),
+                text: edge.into(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.5),
+                right: Dimension::Cells(0.5),
+                top: Dimension::Cells(0.),
+                bottom: Dimension::Cells(0.),
+            });
+        computed.push(term_window.compute_element(
+            &layout_context(
+                term_window,
+                &title_metrics,
+                euclid::rect(
+                    left,
+                    top + avail_height - footer_height,
+                    avail_width,
+                    footer_height,
+                ),
+                101,
+            ),
+            &footer,
+        )?);
+
+        // Only the thumbnails on the current page are rendered
+        for (display_idx, entry_idx) in matches
+            .iter()
+            .enumerate()
+            .skip(page * per_page)
+            .take(per_page)
+        {
+            let entry = &self.tabs[*entry_idx];
+            let cell = display_idx - page * per_page;
+            let x = left + gap + (cell % cols) as f32 * (thumb_width + gap);
+            let y =
+                top + header_height + (cell / cols) as f32 * (thumb_height + title_height + gap);
+
+            let is_selected = display_idx == selected;
+            let frame_color = if is_selected {
+                active_tab.bg_color.to_linear()
+            } else {
+                edge
+            };
+
+            let (lines, bg, fg) = match &entry.pane {
+                Some(pane) => {
+                    let palette = pane.palette();
+                    (
+                        self.thumbnail(pane, &thumb_font, thumb_rows),
+                        palette.background.to_linear(),
+                        palette.foreground.to_linear(),
+                    )
+                }
+                None => (vec![], LinearRgba::TRANSPARENT, LinearRgba::TRANSPARENT),
+            };
+
+            let body = Element::new(&thumb_font, ElementContent::Children(lines))
+                .colors(ElementColors {
+                    border: BorderColor::new(frame_color),
+                    bg: bg.into(),
+                    text: fg.into(),
+                })
+                .border(BoxDimension::new(Dimension::Pixels(if is_selected {
+                    2.
+                } else {
+                    1.
+                })))
+                .min_width(Some(Dimension::Pixels(thumb_width)))
+                .max_width(Some(Dimension::Pixels(thumb_width)))
+                .min_height(Some(Dimension::Pixels(thumb_height)));
+            computed.push(term_window.compute_element(
+                &layout_context(
+                    term_window,
+                    &thumb_metrics,
+                    euclid::rect(x, y, thumb_width, thumb_height),
+                    101,
+                ),
+                &body,
+            )?);
+
+            let tab_colors = if is_selected {
+                &hover_tab
+            } else if entry.tab_idx == self.active_idx {
+                &active_tab
+            } else {
+                &inactive_tab
+            };
+            let title = Element::new(
+                &title_font,
+                ElementContent::Text(format!("{}: {}", entry.tab_idx + 1, entry.title)),
+            )
+            .colors(ElementColors {
+                border: BorderColor::default(),
+                bg: tab_colors.bg_color.to_linear().into(),
+                text: tab_colors.fg_color.to_linear().into(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.25),
+                right: Dimension::Cells(0.25),
+                top: Dimension::Cells(0.),
+                bottom: Dimension::Cells(0.),
+            })
+            .max_width(Some(Dimension::Pixels(thumb_width)));
+            computed.push(term_window.compute_element(
+                &layout_context(
+                    term_window,
+                    &title_metrics,
+                    euclid::rect(x, y + thumb_height, thumb_width, title_height),
+                    101,
+                ),
+                &title,
+            )?);
+        }
+
+        Ok(computed)
+    }
+}
+
+impl Modal for TabOverview {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        false
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<bool> {
+        let (cols, per_page) = *self.grid.borrow();
+        let filtering = !self.filter.borrow().is_empty();
+
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                if filtering {
+                    self.filter.borrow_mut().clear();
+                    self.update_matches();
+                } else {
+                    term_window.cancel_modal();
+                    return Ok(true);
+                }
+            }
+            (KeyCode::LeftArrow, KeyModifiers::NONE) => self.move_by(-1),
+            (KeyCode::RightArrow, KeyModifiers::NONE) => self.move_by(1),
+            (KeyCode::UpArrow, KeyModifiers::NONE) => self.move_by(-(cols as isize)),
+            (KeyCode::DownArrow, KeyModifiers::NONE) => self.move_by(cols as isize),
+            (KeyCode::PageUp, KeyModifiers::NONE) => self.move_by(-(per_page as isize)),
+            (KeyCode::PageDown, KeyModifiers::NONE) => self.move_by(per_page as isize),
+            // hjkl navigate, unless the user is typing a filter
+            (KeyCode::Char('h'), KeyModifiers::NONE) if !filtering => self.move_by(-1),
+            (KeyCode::Char('l'), KeyModifiers::NONE) if !filtering => self.move_by(1),
+            (KeyCode::Char('k'), KeyModifiers::NONE) if !filtering => {
+                self.move_by(-(cols as isize))
+            }
+            (KeyCode::Char('j'), KeyModifiers::NONE) if !filtering => self.move_by(cols as isize),
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                self.activate_selected(term_window);
+                return Ok(true);
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                self.filter.borrow_mut().push(c);
+                self.update_matches();
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                self.filter.borrow_mut().pop();
+                self.update_matches();
+            }
+            (KeyCode::Char('u'), KeyModifiers::CTRL) => {
+                self.filter.borrow_mut().clear();
+                self.update_matches();
+            }
+            _ => return Ok(false),
+        }
+        term_window.invalidate_modal();
+        Ok(true)
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
@@ -0,0 +1,759 @@
+//! The workspace selector is a modal that lists the workspaces known
+//! to the mux, and allows switching to one of them by label or by
+//! typing to filter the list, creating a new named workspace, or
+//! removing a workspace that has no tabs.
+use crate::termwindow::box_model::*;
+use crate::termwindow::modal::Modal;
+use crate::termwindow::render::corners::{
+    BOTTOM_LEFT_ROUNDED_CORNER, BOTTOM_RIGHT_ROUNDED_CORNER, TOP_LEFT_ROUNDED_CORNER,
+    TOP_RIGHT_ROUNDED_CORNER,
+};
+use crate::termwindow::{DimensionContext, TermWindow};
+use crate::utilsprites::RenderMetrics;
+use config::keyassignment::KeyAssignment;
+use config::{Dimension, TabBarColors};
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
+use mux::Mux;
+use std::cell::{Ref, RefCell};
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent};
+use window::color::LinearRgba;
+
+struct WorkspaceEntry {
+    name: String,
+    windows: usize,
+    tabs: usize,
+}
+
+impl WorkspaceEntry {
+    fn caption(&self) -> String {
+        let plural = |n: usize, what: &str| {
+            if n == 1 {
+                format!("{n} {what}")
+            } else {
+                format!("{n} {what}s")
+            }
+        };
+        format!(
+            "{}  ({}, {})",
+            self.name,
+            plural(self.windows, "window"),
+            plural(self.tabs, "tab")
+        )
+    }
+}
+
+fn collect_workspaces() -> Vec<WorkspaceEntry> {
+    let mux = Mux::get();
+    mux.iter_workspaces()
+        .into_iter()
+        .map(|name| {
+            let windows = mux.iter_windows_in_workspace(&name);
+            let tabs = windows
+                .iter()
+                .filter_map(|window_id| mux.get_window(*window_id).map(|window| window.len()))
+                .sum();
+            WorkspaceEntry {
+                windows: windows.len(),
+                tabs,
+                name,
+            }
+        })
+        .collect()
+}
+
+/// Returns the indices of the entries that match filter, best match first
+fn compute_matches(filter: &str, workspaces: &[WorkspaceEntry]) -> Vec<usize> {
+    if filter.is_empty() {
+        return (0..workspaces.len()).collect();
+    }
+    let matcher = SkimMatcherV2::default();
+    let mut scores: Vec<(usize, i64)> = workspaces
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, entry)| {
+            matcher
+                .fuzzy_match(&entry.name, filter)
+                .map(|score| (idx, score))
+        })
+        .collect();
+    scores.sort_by(|a, b| a.1.cmp(&b.1).reverse());
+    scores.into_iter().map(|(idx, _)| idx).collect()
+}
+
+/// What the selector needs to do in response to a key press
+#[derive(Debug, PartialEq, Eq)]
+enum KeyAction {
+    /// The key isn't used by the selector
+    Ignored,
+    /// The state changed and needs to be drawn again
+    Updated,
+    Close,
+    Switch(String),
+    DeleteSelected,
+}
+
+pub struct WorkspaceSelector {
+    element: RefCell<Option<Vec<ComputedElement>>>,
+    workspaces: RefCell<Vec<WorkspaceEntry>>,
+    active_workspace: String,
+    alphabet: String,
+    /// The indices into workspaces that are currently displayed
+    matches: RefCell<Vec<usize>>,
+    labels: RefCell<Vec<String>>,
+    /// The label characters typed so far
+    selection: RefCell<String>,
+    /// The text typed to filter the list
+    filter: RefCell<String>,
+    /// Some while the user is typing the name of a new workspace
+    new_workspace: RefCell<Option<String>>,
+    selected_row: RefCell<usize>,
+    top_row: RefCell<usize>,
+    max_rows_on_screen: RefCell<usize>,
+    message: RefCell<Option<String>>,
+}
+
+impl WorkspaceSelector {
+    pub fn new(term_window: &mut TermWindow) -> Self {
+        Self::with_workspaces(
+            collect_workspaces(),
+            Mux::get().active_workspace(),
+            term_window.config.quick_select_alphabet.clone(),
+        )
+    }
+
+    fn with_workspaces(
+        workspaces: Vec<WorkspaceEntry>,
+        active_workspace: String,
+        alphabet: String,
+    ) -> Self {
+        let selected_row = workspaces
+            .iter()
+            .position(|entry| entry.name == active_workspace)
+            .unwrap_or(0);
+
+        let selector = Self {
+            element: RefCell::new(None),
+            workspaces: RefCell::new(workspaces),
+            active_workspace,
+            alphabet,
+            matches: RefCell::new(vec![]),
+            labels: RefCell::new(vec![]),
+            selection: RefCell::new(String::new()),
+            filter: RefCell::new(String::new()),
+            new_workspace: RefCell::new(None),
+            selected_row: RefCell::new(selected_row),
+            top_row: RefCell::new(0),
+            max_rows_on_screen: RefCell::new(0),
+            message: RefCell::new(None),
+        };
+        selector.update_matches(false);
+        selector
+    }
+
+    /// Recomputes the displayed entries and their labels after
+    /// the list or the filter has changed
+    fn update_matches(&self, reset_selection: bool) {
+        let matches = compute_matches(&self.filter.borrow(), &self.workspaces.borrow());
+        *self.labels.borrow_mut() =
+            crate::overlay::quickselect::compute_labels_for_alphabet(&self.alphabet, matches.len());
+        if reset_selection {
+            *self.selected_row.borrow_mut() = 0;
+            *self.top_row.borrow_mut() = 0;
+        } else {
+            let mut row = self.selected_row.borrow_mut();
+            *row = (*row).min(matches.len().saturating_sub(1));
+        }
+        *self.matches.borrow_mut() = matches;
+        self.selection.borrow_mut().clear();
+    }
+
+    fn selected_workspace(&self) -> Option<String> {
+        let idx = *self.matches.borrow().get(*self.selected_row.borrow())?;
+        self.workspaces
+            .borrow()
+            .get(idx)
+            .map(|entry| entry.name.clone())
+    }
+
+    fn move_up(&self) {
+        let mut row = self.selected_row.borrow_mut();
+        *row = row.saturating_sub(1);
+
+        let mut top_row = self.top_row.borrow_mut();
+        if *row < *top_row {
+            *top_row = *row;
+        }
+    }
+
+    fn move_down(&self) {
+        let max_rows_on_screen = (*self.max_rows_on_screen.borrow()).max(1);
+        let limit = self.matches.borrow().len().saturating_sub(1);
+        let mut row = self.selected_row.borrow_mut();
+        *row = row.saturating_add(1).min(limit);
+        let mut top_row = self.top_row.borrow_mut();
+        if *row > *top_row + max_rows_on_screen - 1 {
+            *top_row = row.saturating_sub(max_rows_on_screen - 1);
+        }
+    }
+
+    fn switch_to(&self, name: String, term_window: &mut TermWindow) {
+        term_window.cancel_modal();
+        if let Some(pane) = term_window.get_active_pane_or_overlay() {
+            let action = KeyAssignment::SwitchToWorkspace {
+                name: Some(name),
+                spawn: None,
+            };
+            if let Err(err) = term_window.perform_key_assignment(&pane, &action) {
+                log::error!("Error while performing {action:?}: {err:#}");
+            }
+        }
+    }
+
+    /// Removes the selected workspace, provided that it has no tabs
+    fn delete_selected(&self) {
+        let name = match self.selected_workspace() {
+            Some(name) => name,
+            None => return,
+        };
+        if name == self.active_workspace {
+            self.message
+                .borrow_mut()
+                .replace(format!("Cannot remove the active workspace `{name}`"));
+            return;
+        }
+        let tabs = self
+            .workspaces
+            .borrow()
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.tabs)
+            .unwrap_or(0);
+        if tabs > 0 {
+            self.message
+                .borrow_mut()
+                .replace(format!("Workspace `{name}` is not empty"));
+            return;
+        }
+
+        let mux = Mux::get();
+        for window_id in mux.iter_windows_in_workspace(&name) {
+            mux.kill_window(window_id);
+        }
+        self.message.borrow_mut().take();
+        *self.workspaces.borrow_mut() = collect_workspaces();
+        self.update_matches(false);
+    }
+
+    /// Updates the state in response to a key press.
+    /// Typing filters the list, while a label typed with ALT
+    /// switches to the workspace that it labels.
+    fn handle_key(&self, key: KeyCode, mods: KeyModifiers) -> KeyAction {
+        self.message.borrow_mut().take();
+
+        let creating = self.new_workspace.borrow().is_some();
+
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE) | (KeyCode::Char('g'), KeyModifiers::CTRL) => {
+                if creating {
+                    self.new_workspace.borrow_mut().take();
+                } else if !self.filter.borrow().is_empty() {
+                    self.filter.borrow_mut().clear();
+                    self.update_matches(true);
+                } else {
+                    return KeyAction::Close;
+                }
+            }
+            (KeyCode::Char('n'), KeyModifiers::CTRL) => {
+                self.new_workspace.borrow_mut().replace(String::new());
+            }
+            (KeyCode::Char('d'), KeyModifiers::CTRL) if !creating => {
+                return KeyAction::DeleteSelected;
+            }
+            (KeyCode::UpArrow, KeyModifiers::NONE) | (KeyCode::Char('p'), KeyModifiers::CTRL)
+                if !creating =>
+            {
+                self.move_up();
+            }
+            (KeyCode::DownArrow, KeyModifiers::NONE) if !creating => {
+                self.move_down();
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                let name = if creating {
+                    self.new_workspace
+                        .borrow()
+                        .as_ref()
+                        .map(|name| name.trim().to_string())
+                        .filter(|name| !name.is_empty())
+                } else {
+                    self.selected_workspace()
+                };
+                if let Some(name) = name {
+                    return KeyAction::Switch(name);
+                }
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                if let Some(name) = self.new_workspace.borrow_mut().as_mut() {
+                    name.push(c);
+                } else {
+                    self.filter.borrow_mut().push(c);
+                    self.update_matches(true);
+                }
+            }
+            (KeyCode::Char(c), KeyModifiers::ALT) if !creating => {
+                let mut selection = self.selection.borrow_mut();
+                selection.push(c.to_ascii_lowercase());
+                let labels = self.labels.borrow();
+                match labels.iter().position(|s| s == &*selection) {
+                    Some(display_idx) => {
+                        selection.clear();
+                        let name = self.matches.borrow().get(display_idx).and_then(|idx| {
+                            self.workspaces
+                                .borrow()
+                                .get(*idx)
+                                .map(|entry| entry.name.clone())
+                        });
+                        if let Some(name) = name {
+                            return KeyAction::Switch(name);
+                        }
+                    }
+                    None => {
+                        if !labels.iter().any(|s| s.starts_with(&*selection)) {
+                            selection.clear();
+                        }
+                    }
+                }
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                if let Some(name) = self.new_workspace.borrow_mut().as_mut() {
+                    name.pop();
+                } else if self.filter.borrow_mut().pop().is_some() {
+                    self.update_matches(true);
+                }
+            }
+            (KeyCode::Char('u'), KeyModifiers::CTRL) => {
+                if let Some(name) = self.new_workspace.borrow_mut().as_mut() {
+                    name.clear();
+                } else {
+                    self.filter.borrow_mut().clear();
+                    self.update_matches(true);
+                }
+            }
+            _ => return KeyAction::Ignored,
+        }
+        KeyAction::Updated
+    }
+
+    fn compute(&self, term_window: &mut TermWindow) -> anyhow::Result<Vec<ComputedElement>> {
+        let font = term_window.fonts.title_font()?;
+        let metrics = RenderMetrics::with_font_metrics(&font.metrics());
+
+        let max_rows_on_screen = ((term_window.dimensions.pixel_height * 8 / 10)
+            / metrics.cell_size.height as usize)
+            .saturating_sub(3)
+            .max(1);
+        *self.max_rows_on_screen.borrow_mut() = max_rows_on_screen;
+
+        let colors = term_window
+            .config
+            .colors
+            .as_ref()
+            .and_then(|c| c.tab_bar.as_ref())
+            .cloned()
+            .unwrap_or_else(TabBarColors::default);
+        let active_tab = colors.active_tab();
+        let inactive_tab = colors.inactive_tab();
+        let hover_tab = colors.inactive_tab_hover();
+        let background: InheritableColor = colors.background().to_linear().into();
+        let text: InheritableColor = inactive_tab.fg_color.to_linear().into();
+
+        let tab_bar = term_window.tab_bar_reserved_edges()?;
+        let top_bar_height = tab_bar.top;
+        let (padding_left, padding_top) = term_window.padding_left_top();
+        let border = term_window.get_os_border();
+        let top_pixel_y = top_bar_height + padding_top + border.top.get() as f32;
+
+        let header = if let Some(name) = self.new_workspace.borrow().as_ref() {
+            format!("New workspace: {name}_")
+        } else {
+            format!("Workspaces: {}_", self.filter.borrow())
+        };
+
+        let mut elements = vec![Element::new(&font, ElementContent::Text(header))
+            .colors(ElementColors {
+                border: BorderColor::default(),
+                bg: LinearRgba::TRANSPARENT.into(),
+                text: text.clone(),
+            })
+            .padding(BoxDimension {
+                left: Dimension::Cells(0.25),
+                right: Dimension::Cells(0.25),
+                top: Dimension::Cells(0.),
+                bottom: Dimension::Cells(0.25),
+            })
+            .display(DisplayType::Block)];
+
+        let workspaces = self.workspaces.borrow();
+        let labels = self.labels.borrow();
+        let selected_row = *self.selected_row.borrow();
+        let top_row = *self.top_row.borrow();
+        for (display_idx, entry_idx) in self
+            .matches
+            .borrow()
+            .iter()
+            .enumerate()
+            .skip(top_row)
+            .take(max_rows_on_screen)
+        {
+            let entry = &workspaces[*entry_idx];
+            let tab_colors = if display_idx == selected_row {
+                &hover_tab
+            } else if entry.name == self.active_workspace {
+                &active_tab
+            } else {
+                &inactive_tab
+            };
+            let bg: InheritableColor = tab_colors.bg_color.to_linear().into();
+            let fg: InheritableColor = tab_colors.fg_color.to_linear().into();
+
+            let label = labels.get(display_idx).cloned().unwrap_or_default();
+            let row = vec![
+                Element::new(&font, ElementContent::Text(label))
+                    .min_width(Some(Dimension::Cells(3.)))
+                    .colors(ElementColors {
+                        border: BorderColor::default(),
+                        bg: fg.clone(),
+                        text: bg.clone(),
+                    })
+                    .padding(BoxDimension {
+                        left: Dimension::Cells(0.25),
+                        right: Dimension::Cells(0.25),
+                        top: Dimension::Cells(0.),
+                        bottom: Dimension::Cells(0.),
+                    }),
+                Element::new(&font, ElementContent::Text(entry.caption())).padding(BoxDimension {
+                    left: Dimension::Cells(0.5),
+                    right: Dimension::Cells(0.25),
+                    top: Dimension::Cells(0.),
+                    bottom: Dimension::Cells(0.),
+                }),
+            ];
+
+            elements.push(
+                Element::new(&font, ElementContent::Children(row))
+                    .colors(ElementColors {
+                        border: BorderColor::default(),
+                        bg,
+                        text: fg,
+                    })
+                    .min_width(Some(Dimension::Percent(1.)))
+                    .display(DisplayType::Block),
+            );
+        }
+
+        let footer = match self.message.borrow().as_ref() {
+            Some(message) => message.clone(),
+            None => "Type to filter  Enter or ALT-label: switch  CTRL-n: new  CTRL-d: remove empty"
+                .to_string(),
+        };
+        elements.push(
+            Element::new(&font, ElementContent::Text(footer))
+                .colors(ElementColors {
+                    border: BorderColor::default(),
+                    bg: LinearRgba::TRANSPARENT.into(),
+                    text: colors.inactive_tab_edge().to_linear().into(),
+                })
+                .padding(BoxDimension {
+                    left: Dimension::Cells(0.25),
+                    right: Dimension::Cells(0.25),
+                    top: Dimension::Cells(0.25),
+                    bottom: Dimension::Cells(0.),
+                })
+                .display(DisplayType::Block),
+        );
+
+        let dimensions = term_window.dimensions;
+        let size = term_window.terminal_size;
+
+        // Avoid covering the entire width
+        let desired_width = (size.cols / 2).max(60).min(size.cols);
+
+        // Center it
+        let avail_pixel_width =
+            size.cols as f32 * term_window.render_metrics.cell_size.width as f32;
+        let desired_pixel_width =
+            desired_width as f32 * term_window.render_metrics.cell_size.width as f32;
+
+        let element = Element::new(&font, ElementContent::Children(elements))
+            .colors(ElementColors {
+                border: BorderColor::new(colors.inactive_tab_edge().to_linear()),
+                bg: background,
+                text,
+            })
+            .margin(BoxDimension::new(Dimension::Cells(0.25)))
+            .padding(BoxDimension::new(Dimension::Cells(0.25)))
+            .border(BoxDimension::new(Dimension::Pixels(1.)))
+            .border_corners(Some(Corners {
+                top_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_LEFT_ROUNDED_CORNER,
+                },
+                top_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: TOP_RIGHT_ROUNDED_CORNER,
+                },
+                bottom_left: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_LEFT_ROUNDED_CORNER,
+                },
+                bottom_right: SizedPoly {
+                    width: Dimension::Cells(0.25),
+                    height: Dimension::Cells(0.25),
+                    poly: BOTTOM_RIGHT_ROUNDED_CORNER,
+                },
+            }))
+            .min_width(Some(Dimension::Pixels(desired_pixel_width)));
+
+        let x_adjust = ((avail_pixel_width - padding_left) - desired_pixel_width) / 2.;
+
+        let computed = term_window.compute_element(
+            &LayoutContext {
+                height: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_height as f32,
+                    pixel_cell: metrics.cell_size.height as f32,
+                },
+                width: DimensionContext {
+                    dpi: dimensions.dpi as f32,
+                    pixel_max: dimensions.pixel_width as f32,
+                    pixel_cell: metrics.cell_size.width as f32,
+                },
+                bounds: euclid::rect(
+                    tab_bar.left + padding_left + x_adjust,
+                    top_pixel_y,
+                    desired_pixel_width,
+                    size.rows as f32 * term_window.render_metrics.cell_size.height as f32,
+                ),
+                metrics: &metrics,
+                gl_state: term_window.render_state.as_ref().unwrap(),
+                zindex: 100,
+            },
+            &element,
+        )?;
+
+        Ok(vec![computed])
+    }
+}
+
+impl Modal for WorkspaceSelector {
+    fn perform_assignment(
+        &self,
+        _assignment: &KeyAssignment,
+        _term_window: &mut TermWindow,
+    ) -> bool {
+        false
+    }
+
+    fn mouse_event(&self, _event: MouseEvent, _term_window: &mut TermWindow) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn key_down(
+        &self,
+        key: KeyCode,
+        mods: KeyModifiers,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<bool> {
+        match self.handle_key(key, mods) {
+            KeyAction::Ignored => return Ok(false),
+            KeyAction::Updated => {}
+            KeyAction::Close => {
+                term_window.cancel_modal();
+                return Ok(true);
+            }
+            KeyAction::Switch(name) => {
+                self.switch_to(name, term_window);
+                return Ok(true);
+            }
+            KeyAction::DeleteSelected => self.delete_selected(),
+        }
+        term_window.invalidate_modal();
+        Ok(true)
+    }
+
+    fn computed_element(
+        &self,
+        term_window: &mut TermWindow,
+    ) -> anyhow::Result<Ref<[ComputedElement]>> {
+        if self.element.borrow().is_none() {
+            let element = self.compute(term_window)?;
+            self.element.borrow_mut().replace(element);
+        }
+        Ok(Ref::map(self.element.borrow(), |v| {
+            v.as_ref().unwrap().as_slice()
+        }))
+    }
+
+    fn reconfigure(&self, _term_window: &mut TermWindow) {
+        self.element.borrow_mut().take();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn selector() -> WorkspaceSelector {
+        let entry = |name: &str, tabs| WorkspaceEntry {
+            name: name.to_string(),
+            windows: 1,
+            tabs,
+        };
+        WorkspaceSelector::with_workspaces(
+            vec![entry("default", 2), entry("api", 1), entry("web", 0)],
+            "api".to_string(),
+            "asdfqwerzxcvjklmiuopghtybn".to_string(),
+        )
+    }
+
+    fn type_text(selector: &WorkspaceSelector, text: &str) {
+        for c in text.chars() {
+            assert_eq!(
+                selector.handle_key(KeyCode::Char(c), KeyModifiers::NONE),
+                KeyAction::Updated
+            );
+        }
+    }
+
+    fn displayed(selector: &WorkspaceSelector) -> Vec<String> {
+        let workspaces = selector.workspaces.borrow();
+        selector
+            .matches
+            .borrow()
+            .iter()
+            .map(|idx| workspaces[*idx].name.clone())
+            .collect()
+    }
+
+    fn enter(selector: &WorkspaceSelector) -> KeyAction {
+        selector.handle_key(KeyCode::Enter, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn active_workspace_is_selected() {
+        let selector = selector();
+        assert_eq!(displayed(&selector), vec!["default", "api", "web"]);
+        assert_eq!(enter(&selector), KeyAction::Switch("api".to_string()));
+    }
+
+    #[test]
+    fn typing_filters() {
+        let selector = selector();
+        type_text(&selector, "we");
+        assert_eq!(displayed(&selector), vec!["web"]);
+        assert_eq!(enter(&selector), KeyAction::Switch("web".to_string()));
+
+        // Backspace widens the filter again
+        selector.handle_key(KeyCode::Backspace, KeyModifiers::NONE);
+        selector.handle_key(KeyCode::Backspace, KeyModifiers::NONE);
+        assert_eq!(displayed(&selector), vec!["default", "api", "web"]);
+    }
+
+    #[test]
+    fn label_letters_filter_rather_than_switch() {
+        let selector = selector();
+        // `a` labels the first workspace, but typing it filters
+        assert_eq!(selector.labels.borrow()[0], "a");
+        type_text(&selector, "a");
+        assert_eq!(displayed(&selector), vec!["api", "default"]);
+    }
+
+    #[test]
+    fn alt_label_switches() {
+        let selector = selector();
+        type_text(&selector, "e");
+        let names = displayed(&selector);
+        let labels = selector.labels.borrow().clone();
+        let label = labels[1].clone();
+        let mut result = KeyAction::Updated;
+        for c in label.chars() {
+            result = selector.handle_key(KeyCode::Char(c), KeyModifiers::ALT);
+        }
+        assert_eq!(result, KeyAction::Switch(names[1].clone()));
+
+        // Something that isn't a label is discarded
+        assert_eq!(
+            selector.handle_key(KeyCode::Char('!'), KeyModifiers::ALT),
+            KeyAction::Updated
+        );
+        assert!(selector.selection.borrow().is_empty());
+    }
+
+    #[test]
+    fn escape_clears_the_filter_then_closes() {
+        let selector = selector();
+        type_text(&selector, "web");
+        assert_eq!(
+            selector.handle_key(KeyCode::Escape, KeyModifiers::NONE),
+            KeyAction::Updated
+        );
+        assert_eq!(displayed(&selector).len(), 3);
+        assert_eq!(
+            selector.handle_key(KeyCode::Escape, KeyModifiers::NONE),
+            KeyAction::Close
+        );
+    }
+
+    #[test]
+    fn nothing_matches() {
+        let selector = selector();
+        type_text(&selector, "zzz");
+        assert!(displayed(&selector).is_empty());
+        assert_eq!(enter(&selector), KeyAction::Updated);
+    }
+
+    #[test]
+    fn arrows_move_within_the_matches() {
+        let selector = selector();
+        *selector.max_rows_on_screen.borrow_mut() = 10;
+        selector.handle_key(KeyCode::DownArrow, KeyModifiers::NONE);
+        selector.handle_key(KeyCode::DownArrow, KeyModifiers::NONE);
+        assert_eq!(enter(&selector), KeyAction::Switch("web".to_string()));
+        selector.handle_key(KeyCode::UpArrow, KeyModifiers::NONE);
+        selector.handle_key(KeyCode::UpArrow, KeyModifiers::NONE);
+        selector.handle_key(KeyCode::UpArrow, KeyModifiers::NONE);
+        assert_eq!(enter(&selector), KeyAction::Switch("default".to_string()));
+    }
+
+    #[test]
+    fn new_workspace_name() {
+        let selector = selector();
+        assert_eq!(
+            selector.handle_key(KeyCode::Char('n'), KeyModifiers::CTRL),
+            KeyAction::Updated
+        );
+        // Typing goes into the name rather than the filter
+        type_text(&selector, " docs ");
+        assert_eq!(displayed(&selector).len(), 3);
+        assert_eq!(enter(&selector), KeyAction::Switch("docs".to_string()));
+
+        // An empty name is not accepted
+        selector.handle_key(KeyCode::Char('u'), KeyModifiers::CTRL);
+        assert_eq!(enter(&selector), KeyAction::Updated);
+        // and escape returns to the list
+        selector.handle_key(KeyCode::Escape, KeyModifiers::NONE);
+        assert!(selector.new_workspace.borrow().is_none());
+    }
+
+    #[test]
+    fn delete_is_requested_for_the_selection() {
+        let selector = selector();
+        assert_eq!(
+            selector.handle_key(KeyCode::Char('d'), KeyModifiers::CTRL),
+            KeyAction::DeleteSelected
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Applies the entry of `workspace_styles` that matches the workspace
+//! of a window, so that the tab bar and title of the window show which
+//! workspace is active.
+//! The style is re-resolved whenever the workspace of the window
+//! changes, which happens both when the window is switched to another
+//! workspace and when a mux server reports that it was moved.
+use crate::TermWindow;
+use config::{ConfigHandle, TabBarColors, WorkspaceStyle};
+use mux::Mux;
+
+/// Overlays the colors of `style` on the effective colors of `config`.
+/// The fancy tab bar takes its colors from `colors` and its background
+/// from `window_frame` rather than from the resolved palette, so those
+/// are adjusted too.
+pub fn apply_workspace_style(
+    config: &ConfigHandle,
+    style: Option<&WorkspaceStyle>,
+) -> ConfigHandle {
+    let tab_bar = match style.and_then(|style| style.tab_bar.as_ref()) {
+        Some(tab_bar) => tab_bar.clone(),
+        None => return config.clone(),
+    };
+    fn overlay(base: &Option<TabBarColors>, tab_bar: &TabBarColors) -> Option<TabBarColors> {
+        Some(match base {
+            Some(base) => base.overlay_with(tab_bar),
+            None => tab_bar.clone(),
+        })
+    }
+    config.with_modifications(|config| {
+        config.resolved_palette.tab_bar = overlay(&config.resolved_palette.tab_bar, &tab_bar);
+        let colors = config.colors.get_or_insert_with(Default::default);
+        colors.tab_bar = overlay(&colors.tab_bar, &tab_bar);
+        if let Some(background) = tab_bar.background {
+            config.window_frame.active_titlebar_bg = background;
+            config.window_frame.inactive_titlebar_bg = background;
+        }
+    })
+}
+
+impl TermWindow {
+    /// Returns the style of the workspace of this window, as
+    /// defined by `config`
+    pub fn resolve_workspace_style(&self, config: &ConfigHandle) -> Option<WorkspaceStyle> {
+        let mux = Mux::get();
+        let window = mux.get_window(self.mux_window_id)?;
+        config.workspace_style(window.get_workspace()).cloned()
+    }
+
+    /// Called when the workspace of this window may have changed;
+    /// re-applies the configuration if that changed its style
+    pub fn workspace_style_changed(&mut self) {
+        let style = self.resolve_workspace_style(&self.config);
+        if style != self.workspace_style {
+            log::debug!(
+                "workspace style of window {} changed to {:?}",
+                self.mux_window_id,
+                style
+            );
+            self.config_was_reloaded();
+        }
+    }
+}
+
+/// Returns `title` with the prefix of `style`, if any
+pub fn apply_workspace_title_prefix(style: Option<&WorkspaceStyle>, title: String) -> String {
+    match style.and_then(|style| style.window_title_prefix.as_ref()) {
+        Some(prefix) => format!("{prefix}{title}"),
+        None => title,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config::{Palette, RgbaColor};
+
+    fn style(tab_bar: Option<TabBarColors>, prefix: Option<&str>) -> WorkspaceStyle {
+        WorkspaceStyle {
+            tab_bar,
+            window_title_prefix: prefix.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn overlays_tab_bar_colors() {
+        let red: RgbaColor = (0x70, 0x10, 0x10).into();
+        let edge: RgbaColor = (1, 2, 3).into();
+        let config = ConfigHandle::default_config().with_modifications(|config| {
+            config.colors = Some(Palette {
+                tab_bar: Some(TabBarColors {
+                    inactive_tab_edge: Some(edge),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            });
+        });
+        let frame = config.window_frame.clone();
+
+        let styled = apply_workspace_style(
+            &config,
+            Some(&style(
+                Some(TabBarColors {
+                    background: Some(red),
+                    ..Default::default()
+                }),
+                None,
+            )),
+        );
+        let tab_bar = styled.colors.as_ref().unwrap().tab_bar.as_ref().unwrap();
+        assert_eq!(tab_bar.background, Some(red));
+        assert_eq!(tab_bar.inactive_tab_edge, Some(edge));
+        let resolved = styled.resolved_palette.tab_bar.as_ref().unwrap();
+        assert_eq!(resolved.background, Some(red));
+        assert_eq!(styled.window_frame.active_titlebar_bg, red);
+        assert_eq!(styled.window_frame.inactive_titlebar_bg, red);
+
+        // Without a background, the window frame keeps its colors
+        let styled = apply_workspace_style(
+            &config,
+            Some(&style(
+                Some(TabBarColors {
+                    inactive_tab_edge: Some(red),
+                    ..Default::default()
+                }),
+                None,
+            )),
+        );
+        let tab_bar = styled.colors.as_ref().unwrap().tab_bar.as_ref().unwrap();
+        assert_eq!(tab_bar.inactive_tab_edge, Some(red));
+        assert_eq!(
+            styled.window_frame.active_titlebar_bg,
+            frame.active_titlebar_bg
+        );
+        assert_eq!(
+            styled.window_frame.inactive_titlebar_bg,
+            frame.inactive_titlebar_bg
+        );
+
+        // A style without tab bar colors leaves the config alone
+        let unstyled = apply_workspace_style(&config, Some(&style(None, Some("prod: "))));
+        assert_eq!(unstyled.colors, config.colors);
+        assert_eq!(unstyled.resolved_palette, config.resolved_palette);
+    }
+
+    #[test]
+    fn creates_colors_section() {
+        let red: RgbaColor = (0x70, 0x10, 0x10).into();
+        let config = ConfigHandle::default_config().with_modifications(|config| {
+            config.colors = None;
+        });
+        let styled = apply_workspace_style(
+            &config,
+            Some(&style(
+                Some(TabBarColors {
+                    background: Some(red),
+                    ..Default::default()
+                }),
+                None,
+            )),
+        );
+        let tab_bar = styled.colors.as_ref().unwrap().tab_bar.as_ref().unwrap();
+        assert_eq!(tab_bar.background, Some(red));
+    }
+
+    #[test]
+    fn title_prefix() {
+        assert_eq!(
+            apply_workspace_title_prefix(Some(&style(None, Some("⚠ "))), "vim".to_string()),
+            "⚠ vim"
+        );
+        assert_eq!(
+            apply_workspace_title_prefix(Some(&style(None, None)), "vim".to_string()),
+            "vim"
+        );
+        assert_eq!(apply_workspace_title_prefix(None, "vim".to_string()), "vim");
+    }
+}
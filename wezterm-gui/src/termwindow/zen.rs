@@ -0,0 +1,68 @@
+//! Zen mode hides the chrome of a window (tab bar, scroll bar and
+//! most of the padding) so that the terminal content can take up as
+//! much of the window as possible.
+//! As with presentation mode, only the delta is recorded so that
+//! toggling it off restores the prior state, even if the configuration
+//! was reloaded in between.
+use crate::TermWindow;
+use ::window::{WindowOps, WindowState};
+use config::{ConfigHandle, WindowPadding};
+
+pub struct ZenState {
+    /// true if we made the window full screen when zen mode was
+    /// enabled, and should therefore restore it when disabling it
+    entered_fullscreen: bool,
+}
+
+/// Applies the adjustments from the zen_mode configuration to config
+pub fn apply_zen_mode(config: &ConfigHandle) -> ConfigHandle {
+    let zen = config.zen_mode.clone();
+    config.with_modifications(|config| {
+        if zen.hide_tab_bar {
+            config.enable_tab_bar = false;
+        }
+        if zen.hide_scroll_bar {
+            config.enable_scroll_bar = false;
+        }
+        config.window_padding = WindowPadding {
+            left: zen.padding,
+            right: zen.padding,
+            top: zen.padding,
+            bottom: zen.padding,
+        };
+    })
+}
+
+impl TermWindow {
+    pub fn toggle_zen_mode(&mut self) {
+        let is_full_screen = self.window_state.contains(WindowState::FULL_SCREEN);
+        match self.zen.take() {
+            Some(state) => {
+                if state.entered_fullscreen && is_full_screen {
+                    if let Some(window) = self.window.as_ref() {
+                        window.toggle_fullscreen();
+                    }
+                }
+            }
+            None => {
+                let entered_fullscreen = self.config.zen_mode.fullscreen && !is_full_screen;
+                if entered_fullscreen {
+                    if let Some(window) = self.window.as_ref() {
+                        window.toggle_fullscreen();
+                    }
+                }
+                self.zen.replace(ZenState { entered_fullscreen });
+            }
+        }
+
+        // Re-derive the configuration with or without the
+        // zen mode adjustments
+        self.config_was_reloaded();
+        // Give the status event a chance to slim down its content
+        self.emit_status_event();
+    }
+
+    pub fn is_zen_mode(&self) -> bool {
+        self.zen.is_some()
+    }
+}
@@ -1,11 +1,13 @@
 use crate::ICON_DATA;
 use anyhow::anyhow;
-use config::{configuration, wezterm_version};
+use config::{configuration, wezterm_version, UpdateChannel};
 use http_req::request::{HttpVersion, Request};
 use http_req::uri::Uri;
+use luahelper::impl_lua_conversion_dynamic;
 use mux::connui::ConnectionUI;
 use serde::*;
 use std::convert::TryFrom;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Mutex;
 use std::time::Duration;
@@ -14,6 +16,7 @@ use termwiz::color::AnsiColor;
 use termwiz::escape::csi::{Cursor, Sgr};
 use termwiz::escape::osc::{ITermDimension, ITermFileData, ITermProprietary};
 use termwiz::escape::{OneBased, OperatingSystemCommand, CSI};
+use wezterm_dynamic::ToDynamic;
 use wezterm_toast_notification::*;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -31,8 +34,71 @@ pub struct Asset {
     pub size: usize,
     pub url: String,
     pub browser_download_url: String,
+    #[serde(default)]
+    pub updated_at: String,
 }
 
+impl Release {
+    fn is_nightly(&self) -> bool {
+        self.tag_name == "nightly"
+    }
+
+    /// Returns the version that this release offers.
+    /// The nightly builds replace the assets of a single release
+    /// rather than creating a new one, so its version is the
+    /// `YYYYMMDD` date of its most recently uploaded asset, which
+    /// compares against the date at the start of a version string.
+    pub fn version(&self) -> String {
+        if self.is_nightly() {
+            self.assets
+                .iter()
+                .map(|asset| {
+                    asset
+                        .updated_at
+                        .chars()
+                        .filter(|c| c.is_ascii_digit())
+                        .take(8)
+                        .collect::<String>()
+                })
+                .max()
+                .unwrap_or_default()
+        } else {
+            self.tag_name.clone()
+        }
+    }
+
+    pub fn is_newer_than(&self, current: &str) -> bool {
+        let version = self.version();
+        if self.is_nightly() {
+            version.len() == 8 && version.as_str() > current.get(..8).unwrap_or(current)
+        } else {
+            version.as_str() > current
+        }
+    }
+
+    /// Returns the page that describes what is new in this release
+    pub fn release_page_url(&self) -> String {
+        if self.is_nightly() {
+            self.html_url.clone()
+        } else {
+            format!(
+                "https://wezfurlong.org/wezterm/changelog.html#{}",
+                self.tag_name
+            )
+        }
+    }
+}
+
+/// The argument passed to the `update-available` event
+#[derive(Debug, Clone, ToDynamic)]
+struct UpdateAvailable {
+    version: String,
+    channel: UpdateChannel,
+    notes: String,
+    url: String,
+}
+impl_lua_conversion_dynamic!(UpdateAvailable);
+
 fn get_github_release_info(uri: &str) -> anyhow::Result<Release> {
     let uri = Uri::try_from(uri)?;
 
@@ -56,30 +122,51 @@ pub fn get_latest_release_info() -> anyhow::Result<Release> {
     get_github_release_info("https://api.github.com/repos/wez/wezterm/releases/latest")
 }
 
-#[allow(unused)]
 pub fn get_nightly_release_info() -> anyhow::Result<Release> {
     get_github_release_info("https://api.github.com/repos/wez/wezterm/releases/tags/nightly")
 }
 
+/// Returns the channel of the running build
+pub fn current_channel() -> UpdateChannel {
+    UpdateChannel::detect(wezterm_version(), config::wezterm_is_release())
+}
+
+pub fn get_release_info(channel: UpdateChannel) -> anyhow::Result<Release> {
+    match channel {
+        UpdateChannel::Release => get_latest_release_info(),
+        UpdateChannel::Nightly => get_nightly_release_info(),
+    }
+}
+
+/// Returns the release recorded by the last update check, if it
+/// was made for `channel`
+pub fn load_last_release_info(channel: UpdateChannel) -> Option<Release> {
+    let data = std::fs::read(update_file_name()).ok()?;
+    let release: Release = serde_json::from_slice(&data).ok()?;
+    if release.is_nightly() == (channel == UpdateChannel::Nightly) {
+        Some(release)
+    } else {
+        None
+    }
+}
+
 lazy_static::lazy_static! {
     static ref UPDATER_WINDOW: Mutex<Option<ConnectionUI>> = Mutex::new(None);
 }
 
+/// The file that records the result of the last update check
+pub fn update_file_name() -> PathBuf {
+    config::DATA_DIR.join("check_update")
+}
+
 pub fn load_last_release_info_and_set_banner() {
     if !configuration().check_for_updates {
         return;
     }
 
-    let update_file_name = config::DATA_DIR.join("check_update");
-    if let Ok(data) = std::fs::read(update_file_name) {
-        let latest: Release = match serde_json::from_slice(&data) {
-            Ok(d) => d,
-            Err(_) => return,
-        };
-
-        let current = wezterm_version();
+    if let Some(latest) = load_last_release_info(current_channel()) {
         let force_ui = std::env::var_os("WEZTERM_ALWAYS_SHOW_UPDATE_UI").is_some();
-        if latest.tag_name.as_str() <= current && !force_ui {
+        if !latest.is_newer_than(wezterm_version()) && !force_ui {
             return;
         }
 
@@ -89,10 +176,7 @@ pub fn load_last_release_info_and_set_banner() {
 
 fn set_banner_from_release_info(latest: &Release) {
     let mux = crate::Mux::get();
-    let url = format!(
-        "https://wezfurlong.org/wezterm/changelog.html#{}",
-        latest.tag_name
-    );
+    let url = latest.release_page_url();
 
     let icon = ITermFileData {
         name: None,
@@ -132,8 +216,7 @@ fn set_banner_from_release_info(latest: &Release) {
 }
 
 fn schedule_set_banner_from_release_info(latest: &Release) {
-    let current = wezterm_version();
-    if latest.tag_name.as_str() <= current {
+    if !latest.is_newer_than(wezterm_version()) {
         return;
     }
     promise::spawn::spawn_into_main_thread({
@@ -145,18 +228,62 @@ fn schedule_set_banner_from_release_info(latest: &Release) {
     .detach();
 }
 
+/// Emits the `update-available` event, and shows the toast
+/// notification unless a handler returned false
+fn schedule_update_available(latest: &Release) {
+    let update = UpdateAvailable {
+        version: latest.version(),
+        channel: current_channel(),
+        notes: latest.body.clone(),
+        url: latest.release_page_url(),
+    };
+
+    promise::spawn::spawn_into_main_thread(async move {
+        let url = update.url.clone();
+        let default_action = config::with_lua_config_on_main_thread(move |lua| async move {
+            match lua {
+                Some(lua) => {
+                    let args = lua.pack_multi(update)?;
+                    Ok(
+                        config::lua::emit_event(&lua, ("update-available".to_string(), args))
+                            .await?,
+                    )
+                }
+                None => Ok(true),
+            }
+        })
+        .await;
+
+        match default_action {
+            Ok(true) => {
+                persistent_toast_notification_with_click_to_open_url(
+                    "WezTerm Update Available",
+                    "Click to see what's new",
+                    &url,
+                );
+            }
+            Ok(false) => {}
+            Err(err) => log::error!("while processing update-available event: {err:#}"),
+        }
+    })
+    .detach();
+}
+
 /// Returns true if the provided socket path is dead.
 fn update_checker() {
     // Compute how long we should sleep for;
     // if we've never checked, give it a few seconds after the first
     // launch, otherwise compute the interval based on the time of
     // the last check.
-    let update_interval = Duration::from_secs(configuration().check_for_updates_interval_seconds);
+    let channel = current_channel();
+    let update_interval = configuration()
+        .check_for_updates_interval_seconds
+        .for_channel(channel);
     let initial_interval = Duration::from_secs(10);
 
     let force_ui = std::env::var_os("WEZTERM_ALWAYS_SHOW_UPDATE_UI").is_some();
 
-    let update_file_name = config::DATA_DIR.join("check_update");
+    let update_file_name = update_file_name();
     let delay = update_file_name
         .metadata()
         .and_then(|metadata| metadata.modified())
@@ -169,7 +296,7 @@ fn update_checker() {
 
     std::thread::sleep(if force_ui { initial_interval } else { delay });
 
-    let my_sock = config::RUNTIME_DIR.join(format!("gui-sock-{}", unsafe { libc::getpid() }));
+    let my_sock = wezterm_client::discovery::gui_sock_path();
 
     loop {
         // Figure out which other wezterm-guis are running.
@@ -181,47 +308,36 @@ fn update_checker() {
         let socks = wezterm_client::discovery::discover_gui_socks();
 
         if configuration().check_for_updates {
-            if let Ok(latest) = get_latest_release_info() {
+            if let Ok(latest) = get_release_info(channel) {
                 schedule_set_banner_from_release_info(&latest);
                 let current = wezterm_version();
-                if latest.tag_name.as_str() > current || force_ui {
+                if latest.is_newer_than(current) || force_ui {
                     log::info!(
-                        "latest release {} is newer than current build {}",
-                        latest.tag_name,
+                        "latest {:?} release {} is newer than current build {}",
+                        channel,
+                        latest.version(),
                         current
                     );
 
-                    let url = format!(
-                        "https://wezfurlong.org/wezterm/changelog.html#{}",
-                        latest.tag_name
-                    );
-
                     if force_ui || socks.is_empty() || socks[0] == my_sock {
-                        persistent_toast_notification_with_click_to_open_url(
-                            "WezTerm Update Available",
-                            "Click to see what's new",
-                            &url,
-                        );
+                        schedule_update_available(&latest);
                     }
                 }
 
-                config::create_user_owned_dirs(update_file_name.parent().unwrap()).ok();
-
                 // Record the time of this check
-                if let Ok(f) = std::fs::OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(&update_file_name)
-                {
-                    serde_json::to_writer_pretty(f, &latest).ok();
+                if let Ok(data) = serde_json::to_vec_pretty(&latest) {
+                    if let Err(err) = config::statefile::write(&update_file_name, &data) {
+                        log::warn!("{err:#}");
+                    }
                 }
             }
         }
 
-        std::thread::sleep(Duration::from_secs(
-            configuration().check_for_updates_interval_seconds,
-        ));
+        std::thread::sleep(
+            configuration()
+                .check_for_updates_interval_seconds
+                .for_channel(channel),
+        );
     }
 }
 
@@ -236,3 +352,75 @@ pub fn start_update_checker() {
             .expect("failed to spawn update checker thread");
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn release(tag_name: &str, updated_at: &[&str]) -> Release {
+        Release {
+            url: String::new(),
+            body: String::new(),
+            html_url: "https://github.com/wez/wezterm/releases/tag/nightly".to_string(),
+            tag_name: tag_name.to_string(),
+            assets: updated_at
+                .iter()
+                .map(|updated_at| Asset {
+                    name: String::new(),
+                    size: 0,
+                    url: String::new(),
+                    browser_download_url: String::new(),
+                    updated_at: updated_at.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn detect_channel() {
+        assert_eq!(
+            UpdateChannel::detect("20240203-110809-5046fc22", true),
+            UpdateChannel::Release
+        );
+        // Nightly builds have the same form of version as releases
+        assert_eq!(
+            UpdateChannel::detect("20240203-110809-5046fc22", false),
+            UpdateChannel::Nightly
+        );
+        assert_eq!(
+            UpdateChannel::detect("nightly-20240203-110809-5046fc22", true),
+            UpdateChannel::Nightly
+        );
+        assert_eq!(
+            UpdateChannel::detect("20240203-110809-5046fc22-dirty", true),
+            UpdateChannel::Nightly
+        );
+        assert_eq!(
+            UpdateChannel::detect("someone-else", true),
+            UpdateChannel::Nightly
+        );
+    }
+
+    #[test]
+    fn newer_release() {
+        let latest = release("20240203-110809-5046fc22", &[]);
+        assert!(latest.is_newer_than("20230712-072601-f4abf8fd"));
+        assert!(!latest.is_newer_than("20240203-110809-5046fc22"));
+        assert_eq!(
+            latest.release_page_url(),
+            "https://wezfurlong.org/wezterm/changelog.html#20240203-110809-5046fc22"
+        );
+    }
+
+    #[test]
+    fn newer_nightly() {
+        let latest = release("nightly", &["2024-05-19T23:10:00Z", "2024-05-20T14:02:11Z"]);
+        assert_eq!(latest.version(), "20240520");
+        assert!(latest.is_newer_than("20240519-135708-b8f94c47-dirty"));
+        assert!(!latest.is_newer_than("20240520-135708-b8f94c47-dirty"));
+        assert_eq!(latest.release_page_url(), latest.html_url);
+
+        // A nightly without assets has no version to compare
+        assert!(!release("nightly", &[]).is_newer_than("20240519"));
+    }
+}
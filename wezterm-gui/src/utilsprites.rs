@@ -4,11 +4,69 @@ use ::window::bitmaps::{BitmapImage, Image};
 use ::window::color::SrgbaPixel;
 use ::window::{Point, Rect, Size};
 use anyhow::Context;
-use config::DimensionContext;
+use config::{Dimension, DimensionContext};
 use std::rc::Rc;
 use wezterm_font::units::*;
 use wezterm_font::{FontConfiguration, FontMetrics};
 
+/// Runtime overrides for the `line_height` and `cell_width` of a window,
+/// as set by `window:set_cell_metrics`.  When present, these multiply
+/// the configured values.  Since they are expressed relative to
+/// the font metrics, they scale along with the font size.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct CellMetricsOverride {
+    pub line_height: Option<f64>,
+    pub cell_width: Option<f64>,
+}
+
+/// Computes the number of whole pixels occupied by a cell dimension
+/// of `metric` pixels scaled by `scale`.  This rounds up so that glyphs
+/// are not clipped, and never yields a zero-sized cell.
+fn scaled_cell_dimension(metric: f64, scale: f64) -> usize {
+    (metric * scale).ceil().max(1.) as usize
+}
+
+/// Applies a runtime override on top of a configured scale factor
+fn compose_scale(configured: f64, override_scale: Option<f64>) -> f64 {
+    configured * override_scale.unwrap_or(1.0)
+}
+
+/// Evaluates the `underline_thickness` and `underline_position`
+/// overrides, falling back to the metrics from the font when they are
+/// not set.  Returns the thickness in whole pixels, which is used for
+/// every underline style, along with the position relative to the
+/// descender.
+fn underline_metrics(
+    thickness: Option<&Dimension>,
+    position: Option<&Dimension>,
+    font_thickness: PixelLength,
+    font_position: PixelLength,
+    dpi: f32,
+    cell_height: usize,
+) -> (IntPixelLength, f64) {
+    let underline_height = match thickness {
+        None => font_thickness.get().round().max(1.) as isize,
+        Some(d) => d
+            .evaluate_as_pixels(DimensionContext {
+                dpi,
+                pixel_max: font_thickness.get() as f32,
+                pixel_cell: cell_height as f32,
+            })
+            .max(1.) as isize,
+    };
+
+    let underline_position = match position {
+        None => font_position.get(),
+        Some(d) => d.evaluate_as_pixels(DimensionContext {
+            dpi,
+            pixel_max: font_position.get() as f32,
+            pixel_cell: cell_height as f32,
+        }) as f64,
+    };
+
+    (underline_height, underline_position)
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct RenderMetrics {
     pub descender: PixelLength,
@@ -69,42 +127,56 @@ impl RenderMetrics {
     }
 
     pub fn new(fonts: &Rc<FontConfiguration>) -> anyhow::Result<Self> {
+        Self::with_overrides(fonts, &CellMetricsOverride::default())
+    }
+
+    /// Returns the number of (rows, cols) that fit entirely within the
+    /// specified pixel dimensions.
+    pub fn cells_for_pixels(&self, pixel_width: usize, pixel_height: usize) -> (usize, usize) {
+        (
+            pixel_height / self.cell_size.height.max(1) as usize,
+            pixel_width / self.cell_size.width.max(1) as usize,
+        )
+    }
+
+    pub fn with_overrides(
+        fonts: &Rc<FontConfiguration>,
+        overrides: &CellMetricsOverride,
+    ) -> anyhow::Result<Self> {
         let metrics = fonts
             .default_font_metrics()
             .context("failed to get font metrics!?")?;
 
-        let line_height = fonts.config().line_height;
-        let cell_width = fonts.config().cell_width;
+        let config = fonts.config();
+        // The line_height of the primary font wins over the global
+        // line_height, and the runtime override is applied on top.
+        let line_height = config
+            .font
+            .font
+            .first()
+            .and_then(|attr| attr.line_height)
+            .map(|h| *h)
+            .unwrap_or(config.line_height);
+        let line_height = compose_scale(line_height, overrides.line_height);
+        let cell_width = compose_scale(config.cell_width, overrides.cell_width);
 
         let (cell_height, cell_width) = (
-            (metrics.cell_height.get() * line_height).ceil() as usize,
-            (metrics.cell_width.get() * cell_width).ceil() as usize,
+            scaled_cell_dimension(metrics.cell_height.get(), line_height),
+            scaled_cell_dimension(metrics.cell_width.get(), cell_width),
         );
 
         // When line_height != 1.0, we want to adjust the baseline position
         // such that we are horizontally centered.
         let line_height_y_adjust = (cell_height as f64 - metrics.cell_height.get().ceil()) / 2.;
 
-        let config = fonts.config();
-        let underline_height = match &config.underline_thickness {
-            None => metrics.underline_thickness.get().round().max(1.) as isize,
-            Some(d) => d
-                .evaluate_as_pixels(DimensionContext {
-                    dpi: fonts.get_dpi() as f32,
-                    pixel_max: metrics.underline_thickness.get() as f32,
-                    pixel_cell: cell_height as f32,
-                })
-                .max(1.) as isize,
-        };
-
-        let underline_position = match &config.underline_position {
-            None => metrics.underline_position.get(),
-            Some(d) => d.evaluate_as_pixels(DimensionContext {
-                dpi: fonts.get_dpi() as f32,
-                pixel_max: metrics.underline_position.get() as f32,
-                pixel_cell: cell_height as f32,
-            }) as f64,
-        };
+        let (underline_height, underline_position) = underline_metrics(
+            config.underline_thickness.as_ref(),
+            config.underline_position.as_ref(),
+            metrics.underline_thickness,
+            metrics.underline_position,
+            fonts.get_dpi() as f32,
+            cell_height,
+        );
 
         let descender_row = (cell_height as f64 + (metrics.descender.get() - underline_position)
             - line_height_y_adjust) as isize;
@@ -167,3 +239,126 @@ impl UtilSprites {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn metrics_with_cell_size(width: usize, height: usize) -> RenderMetrics {
+        RenderMetrics {
+            descender: PixelLength::new(0.),
+            descender_row: 0,
+            descender_plus_two: 0,
+            underline_height: 1,
+            strike_row: 0,
+            cell_size: Size::new(width as isize, height as isize),
+        }
+    }
+
+    #[test]
+    fn scaled_dimension_rounds_up() {
+        assert_eq!(scaled_cell_dimension(8.0, 1.0), 8);
+        assert_eq!(scaled_cell_dimension(8.2, 1.0), 9);
+        assert_eq!(scaled_cell_dimension(16.0, 1.1), 18);
+        assert_eq!(scaled_cell_dimension(7.0, 0.5), 4);
+        // A tiny scale must never produce a zero-sized cell
+        assert_eq!(scaled_cell_dimension(7.0, 0.0001), 1);
+        assert_eq!(scaled_cell_dimension(0.0, 1.0), 1);
+    }
+
+    #[test]
+    fn overrides_compose_with_configured_scale() {
+        assert_eq!(compose_scale(1.2, None), 1.2);
+        assert_eq!(compose_scale(1.0, Some(1.1)), 1.1);
+        assert_eq!(compose_scale(2.0, Some(0.5)), 1.0);
+        assert!((compose_scale(1.2, Some(1.1)) - 1.32).abs() < 1e-9);
+    }
+
+    #[test]
+    fn cells_fit_in_pixels() {
+        for &metric in &[6.9, 7.0, 7.3, 8.5, 9.99, 12.01, 17.6] {
+            for &configured in &[0.9, 1.0, 1.2] {
+                for &override_scale in &[None, Some(0.5), Some(1.1), Some(1.333), Some(2.0)] {
+                    let scale = compose_scale(configured, override_scale);
+                    let width = scaled_cell_dimension(metric, scale);
+                    let height = scaled_cell_dimension(metric * 2., scale);
+                    let metrics = metrics_with_cell_size(width, height);
+                    for pixel_width in [1, 99, 640, 1023, 1024, 1921, 3839] {
+                        let pixel_height = pixel_width * 3 / 4;
+                        let (rows, cols) = metrics.cells_for_pixels(pixel_width, pixel_height);
+                        // The glyphs are advanced by the unrounded scaled
+                        // metric, so those must fit in the pixel width too,
+                        // which is only true because the cell rounds up
+                        assert!(
+                            cols as f64 * (metric * scale) <= pixel_width as f64,
+                            "{cols} cols advancing {} exceeds {pixel_width} \
+                             (metric={metric} scale={scale})",
+                            metric * scale
+                        );
+                        assert!(
+                            cols * width <= pixel_width,
+                            "{cols} cols of {width} exceeds {pixel_width} \
+                             (metric={metric} scale={scale})"
+                        );
+                        assert!(
+                            rows as f64 * (metric * 2. * scale) <= pixel_height as f64,
+                            "{rows} rows of {} exceeds {pixel_height} \
+                             (metric={metric} scale={scale})",
+                            metric * 2. * scale
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Evaluates the underline metrics of a font whose 17px tall
+    /// cell at 96 dpi is scaled up to dpi
+    fn underline_at_dpi(
+        thickness: Option<Dimension>,
+        position: Option<Dimension>,
+        dpi: f32,
+    ) -> (IntPixelLength, f64) {
+        let scale = dpi as f64 / 96.;
+        underline_metrics(
+            thickness.as_ref(),
+            position.as_ref(),
+            PixelLength::new(1.2 * scale),
+            PixelLength::new(-1.5 * scale),
+            dpi,
+            (17. * scale) as usize,
+        )
+    }
+
+    #[test]
+    fn underline_metrics_scale_with_dpi() {
+        // Font metrics are used when there are no overrides
+        assert_eq!(underline_at_dpi(None, None, 96.), (1, -1.5));
+        assert_eq!(underline_at_dpi(None, None, 192.), (2, -3.0));
+
+        // Pixels are absolute
+        let px = Some(Dimension::Pixels(2.));
+        assert_eq!(underline_at_dpi(px, px, 96.), (2, 2.0));
+        assert_eq!(underline_at_dpi(px, px, 192.), (2, 2.0));
+
+        // Points and cells scale along with the dpi
+        let pt = Some(Dimension::Points(1.5));
+        assert_eq!(underline_at_dpi(pt, None, 96.).0, 2);
+        assert_eq!(underline_at_dpi(pt, None, 192.).0, 4);
+
+        let cell = Some(Dimension::Cells(0.1));
+        assert_eq!(underline_at_dpi(cell, cell, 96.), (1, 1.0));
+        assert_eq!(underline_at_dpi(cell, cell, 192.), (3, 3.0));
+
+        // The underline never disappears entirely
+        let tiny = Some(Dimension::Cells(0.01));
+        assert_eq!(underline_at_dpi(tiny, None, 96.).0, 1);
+        assert_eq!(underline_at_dpi(tiny, None, 192.).0, 1);
+    }
+
+    #[test]
+    fn cells_for_degenerate_metrics() {
+        let metrics = metrics_with_cell_size(0, 0);
+        assert_eq!(metrics.cells_for_pixels(100, 50), (50, 100));
+    }
+}
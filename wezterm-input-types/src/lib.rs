@@ -1685,12 +1685,15 @@ impl KeyEvent {
         }
         modifiers += 1;
 
-        let event_type =
-            if flags.contains(KittyKeyboardFlags::REPORT_EVENT_TYPES) && !self.key_is_down {
-                ":3"
-            } else {
-                ""
-            };
+        let event_type = if !flags.contains(KittyKeyboardFlags::REPORT_EVENT_TYPES) {
+            ""
+        } else if !self.key_is_down {
+            ":3"
+        } else if self.repeat_count > 1 {
+            ":2"
+        } else {
+            ""
+        };
 
         let is_legacy_key = match &self.key {
             Char(c) => c.is_ascii_alphanumeric() || c.is_ascii_punctuation(),
@@ -2068,6 +2071,11 @@ impl Default for IntegratedTitleButtonStyle {
 }
 
 impl FromDynamic for IntegratedTitleButtonStyle {
+    fn describe_schema(registry: &mut wezterm_dynamic::SchemaRegistry) -> wezterm_dynamic::Schema {
+        let _ = registry;
+        wezterm_dynamic::Schema::String
+    }
+
     fn from_dynamic(
         value: &wezterm_dynamic::Value,
         _options: wezterm_dynamic::FromDynamicOptions,
@@ -2252,6 +2260,30 @@ mod test {
         );
     }
 
+    #[test]
+    fn encode_repeat_event_type() {
+        let flags =
+            KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES | KittyKeyboardFlags::REPORT_EVENT_TYPES;
+
+        let event = |repeat_count| KeyEvent {
+            key: KeyCode::Char('a'),
+            modifiers: Modifiers::CTRL,
+            leds: KeyboardLedStatus::empty(),
+            repeat_count,
+            key_is_down: true,
+            raw: None,
+            #[cfg(windows)]
+            win32_uni_char: None,
+        };
+
+        assert_eq!(event(1).encode_kitty(flags), "\x1b[97;5u".to_string());
+        assert_eq!(event(2).encode_kitty(flags), "\x1b[97;5:2u".to_string());
+        assert_eq!(
+            event(2).encode_kitty(KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES),
+            "\x1b[97;5u".to_string()
+        );
+    }
+
     #[test]
     fn encode_issue_3473() {
         let flags = KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES
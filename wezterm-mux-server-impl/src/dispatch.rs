@@ -23,7 +23,9 @@ enum Item {
     Readable,
 }
 
-pub async fn process<T>(stream: T) -> anyhow::Result<()>
+/// Serves a client connected via `stream`.  `peer` describes
+/// the connection, such as the address of the client.
+pub async fn process<T>(stream: T, peer: String) -> anyhow::Result<()>
 where
     T: 'static,
     T: std::io::Read,
@@ -33,10 +35,10 @@ where
     T: async_io::IoSafe,
 {
     let stream = smol::Async::new(stream)?;
-    process_async(stream).await
+    process_async(stream, peer).await
 }
 
-pub async fn process_async<T>(mut stream: Async<T>) -> anyhow::Result<()>
+pub async fn process_async<T>(mut stream: Async<T>, peer: String) -> anyhow::Result<()>
 where
     T: 'static,
     T: std::io::Read,
@@ -56,7 +58,7 @@ where
                 .map_err(|e| anyhow::anyhow!("{:?}", e))
         }
     });
-    let mut handler = SessionHandler::new(pdu_sender);
+    let mut handler = SessionHandler::new(pdu_sender, peer);
 
     {
         let mux = Mux::get();
@@ -150,6 +152,7 @@ where
             Ok(Item::Notif(MuxNotification::WindowRemoved(_window_id))) => {}
             Ok(Item::Notif(MuxNotification::WindowCreated(_window_id))) => {}
             Ok(Item::Notif(MuxNotification::WindowInvalidated(_window_id))) => {}
+            Ok(Item::Notif(MuxNotification::WindowGeometryChanged(_window_id))) => {}
             Ok(Item::Notif(MuxNotification::WindowWorkspaceChanged(window_id))) => {
                 let workspace = {
                     let mux = Mux::get();
@@ -178,16 +181,32 @@ where
                     .await?;
                 stream.flush().await.context("flushing PDU to client")?;
             }
-            Ok(Item::Notif(MuxNotification::TabTitleChanged { tab_id, title })) => {
-                Pdu::TabTitleChanged(codec::TabTitleChanged { tab_id, title })
-                    .encode_async(&mut stream, 0)
-                    .await?;
+            Ok(Item::Notif(MuxNotification::TabTitleChanged {
+                tab_id,
+                title,
+                user_override,
+            })) => {
+                Pdu::TabTitleChanged(codec::TabTitleChanged {
+                    tab_id,
+                    title,
+                    user_override,
+                })
+                .encode_async(&mut stream, 0)
+                .await?;
                 stream.flush().await.context("flushing PDU to client")?;
             }
-            Ok(Item::Notif(MuxNotification::WindowTitleChanged { window_id, title })) => {
-                Pdu::WindowTitleChanged(codec::WindowTitleChanged { window_id, title })
-                    .encode_async(&mut stream, 0)
-                    .await?;
+            Ok(Item::Notif(MuxNotification::WindowTitleChanged {
+                window_id,
+                title,
+                user_override,
+            })) => {
+                Pdu::WindowTitleChanged(codec::WindowTitleChanged {
+                    window_id,
+                    title,
+                    user_override,
+                })
+                .encode_async(&mut stream, 0)
+                .await?;
                 stream.flush().await.context("flushing PDU to client")?;
             }
             Ok(Item::Notif(MuxNotification::WorkspaceRenamed {
@@ -203,7 +222,19 @@ where
                 stream.flush().await.context("flushing PDU to client")?;
             }
             Ok(Item::Notif(MuxNotification::ActiveWorkspaceChanged(_))) => {}
-            Ok(Item::Notif(MuxNotification::Empty)) => {}
+            Ok(Item::Notif(MuxNotification::KillClient(client_id))) => {
+                if handler.is_client(&client_id) {
+                    Pdu::DisconnectedByServer(codec::DisconnectedByServer {
+                        reason: "detached by server".to_string(),
+                    })
+                    .encode_async(&mut stream, 0)
+                    .await?;
+                    stream.flush().await.context("flushing PDU to client")?;
+                    return Ok(());
+                }
+            }
+            Ok(Item::Notif(MuxNotification::Empty))
+            | Ok(Item::Notif(MuxNotification::PowerEvent(_))) => {}
             Err(err) => {
                 log::error!("process_async Err {}", err);
                 return Ok(());
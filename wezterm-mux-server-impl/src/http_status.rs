@@ -0,0 +1,680 @@
+//! Serves a read-only JSON description of the mux over HTTP, so that
+//! external dashboards can follow what the mux server is doing.
+//! This is a deliberately minimal HTTP/1.1 implementation: each
+//! connection serves a single GET request and is then closed.
+use anyhow::{anyhow, Context};
+use config::MuxHttpStatus;
+use mux::listing::{pane_list_items, PaneListItem};
+use mux::pane::{Pane, PaneId};
+use mux::tab::TabId;
+use mux::window::WindowId;
+use mux::{Mux, MuxNotification};
+use promise::spawn::spawn_into_main_thread;
+use serde::Serialize;
+use smol::prelude::*;
+use smol::Async;
+use std::collections::{HashMap, HashSet};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+use wezterm_term::{SemanticType, SemanticZone};
+
+/// The largest request head that we are prepared to read
+const MAX_REQUEST_HEAD: usize = 8192;
+
+/// How long a client has to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a comment is sent on an idle event stream, so that
+/// clients that have gone away are noticed
+const EVENT_KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// How many events may be queued for an event stream client that
+/// is slow to read them; further events are dropped for that client
+const EVENT_QUEUE: usize = 256;
+
+/// How many of the most recent commands are included in the
+/// summary of the command history of each pane
+const RECENT_COMMANDS: usize = 5;
+
+pub struct HttpStatusListener {
+    listener: TcpListener,
+    token: Arc<String>,
+}
+
+impl HttpStatusListener {
+    pub fn with_config(config: &MuxHttpStatus) -> anyhow::Result<Self> {
+        let token = std::fs::read_to_string(&config.auth_token_file).with_context(|| {
+            format!(
+                "mux_http_status: reading auth_token_file {}",
+                config.auth_token_file.display()
+            )
+        })?;
+        let token = token.trim().to_string();
+        anyhow::ensure!(
+            !token.is_empty(),
+            "mux_http_status: auth_token_file {} is empty",
+            config.auth_token_file.display()
+        );
+
+        let listener = TcpListener::bind(&config.bind)
+            .with_context(|| format!("mux_http_status: failed to bind to {}", config.bind))?;
+        if let Ok(addr) = listener.local_addr() {
+            if !addr.ip().is_loopback() {
+                log::warn!(
+                    "mux_http_status is listening on {addr}, which is not a loopback \
+                     address; requests and their token are not encrypted"
+                );
+            }
+        }
+
+        Ok(Self {
+            listener,
+            token: Arc::new(token),
+        })
+    }
+
+    pub fn run(&mut self) {
+        for stream in self.listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let token = Arc::clone(&self.token);
+                    spawn_into_main_thread(async move {
+                        serve(stream, token).await.map_err(|e| {
+                            log::debug!("mux_http_status: {:#}", e);
+                            e
+                        })
+                    })
+                    .detach();
+                }
+                Err(err) => {
+                    log::error!("mux_http_status: accept failed: {}", err);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+}
+
+impl Request {
+    fn parse(head: &[u8]) -> anyhow::Result<Self> {
+        let head = std::str::from_utf8(head).context("request is not utf8")?;
+        let mut lines = head.split("\r\n");
+        let request_line = lines.next().unwrap_or("");
+        let mut parts = request_line.split(' ');
+        let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => {
+                (method, target)
+            }
+            _ => anyhow::bail!("malformed request line {request_line:?}"),
+        };
+        let path = target.split('?').next().unwrap_or(target);
+
+        let mut authorization = None;
+        for line in lines {
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("authorization") {
+                    authorization.replace(value.trim().to_string());
+                }
+            }
+        }
+
+        Ok(Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            authorization,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Response {
+    status: u16,
+    reason: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn error(status: u16, reason: &'static str) -> Self {
+        Self {
+            status,
+            reason,
+            headers: vec![("Content-Type", "text/plain".to_string())],
+            body: format!("{reason}\n").into_bytes(),
+        }
+    }
+
+    fn json<T: Serialize>(value: &T) -> anyhow::Result<Self> {
+        Ok(Self {
+            status: 200,
+            reason: "OK",
+            headers: vec![("Content-Type", "application/json".to_string())],
+            body: serde_json::to_vec_pretty(value)?,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut result = format!("HTTP/1.1 {} {}\r\n", self.status, self.reason);
+        for (name, value) in &self.headers {
+            result.push_str(&format!("{name}: {value}\r\n"));
+        }
+        result.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        ));
+        let mut result = result.into_bytes();
+        result.extend_from_slice(&self.body);
+        result
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Route {
+    Workspaces,
+    Windows,
+    Tabs,
+    Panes,
+    Events,
+}
+
+/// Compares the tokens in a way that doesn't reveal, via the time
+/// taken, how much of the presented token was correct
+fn token_matches(presented: &str, token: &str) -> bool {
+    let presented = presented.as_bytes();
+    let token = token.as_bytes();
+    if presented.len() != token.len() {
+        return false;
+    }
+    presented
+        .iter()
+        .zip(token.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+/// Decides how to respond to `request`.
+/// Only GET requests are accepted, as the endpoint is read-only,
+/// and every request must present the token.
+fn route(request: &Request, token: &str) -> Result<Route, Response> {
+    if request.method != "GET" {
+        let mut response = Response::error(405, "Method Not Allowed");
+        response.headers.push(("Allow", "GET".to_string()));
+        return Err(response);
+    }
+
+    let authorized = request
+        .authorization
+        .as_deref()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|presented| token_matches(presented.trim(), token))
+        .unwrap_or(false);
+    if !authorized {
+        let mut response = Response::error(401, "Unauthorized");
+        response
+            .headers
+            .push(("WWW-Authenticate", "Bearer".to_string()));
+        return Err(response);
+    }
+
+    match request.path.trim_end_matches('/') {
+        "/workspaces" => Ok(Route::Workspaces),
+        "/windows" => Ok(Route::Windows),
+        "/tabs" => Ok(Route::Tabs),
+        "/panes" => Ok(Route::Panes),
+        "/events" => Ok(Route::Events),
+        _ => Err(Response::error(404, "Not Found")),
+    }
+}
+
+async fn read_request_head(stream: &mut Async<TcpStream>) -> anyhow::Result<Vec<u8>> {
+    let mut head = vec![];
+    let mut buf = [0u8; 1024];
+    loop {
+        let len = stream.read(&mut buf).await?;
+        anyhow::ensure!(len > 0, "client closed the connection");
+        head.extend_from_slice(&buf[..len]);
+        if let Some(end) = head.windows(4).position(|w| w == b"\r\n\r\n") {
+            head.truncate(end);
+            return Ok(head);
+        }
+        anyhow::ensure!(head.len() <= MAX_REQUEST_HEAD, "request head is too large");
+    }
+}
+
+async fn serve(stream: TcpStream, token: Arc<String>) -> anyhow::Result<()> {
+    let mut stream = Async::new(stream)?;
+
+    let head = smol::future::or(read_request_head(&mut stream), async {
+        smol::Timer::after(REQUEST_TIMEOUT).await;
+        Err(anyhow!("timed out reading the request"))
+    })
+    .await?;
+
+    let response = match Request::parse(&head) {
+        Ok(request) => match route(&request, &token) {
+            Ok(Route::Events) => return stream_events(stream).await,
+            Ok(route) => match snapshot(route).and_then(|value| Response::json(&value)) {
+                Ok(response) => response,
+                Err(err) => {
+                    log::error!("mux_http_status: {:#}", err);
+                    Response::error(500, "Internal Server Error")
+                }
+            },
+            Err(response) => response,
+        },
+        Err(err) => {
+            log::debug!("mux_http_status: {:#}", err);
+            Response::error(400, "Bad Request")
+        }
+    };
+    stream.write_all(&response.to_bytes()).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct WorkspaceItem {
+    workspace: String,
+    window_ids: Vec<WindowId>,
+}
+
+#[derive(Serialize)]
+struct WindowItem {
+    window_id: WindowId,
+    workspace: String,
+    title: String,
+    tab_ids: Vec<TabId>,
+    active_tab_id: Option<TabId>,
+}
+
+#[derive(Serialize)]
+struct TabItem {
+    tab_id: TabId,
+    window_id: WindowId,
+    title: String,
+    pane_ids: Vec<PaneId>,
+    active_pane_id: Option<PaneId>,
+}
+
+/// The same information as `wezterm cli list --format json`,
+/// together with a summary of the commands run in the pane
+#[derive(Serialize)]
+struct PaneItem {
+    #[serde(flatten)]
+    item: PaneListItem,
+    command_history: CommandHistory,
+}
+
+/// Summarizes the commands that were run in a pane, as delimited
+/// by the semantic zones that the shell reported via OSC 133
+#[derive(Serialize, Default)]
+struct CommandHistory {
+    /// The number of commands in the scrollback
+    count: usize,
+    /// The number of those commands that exited with a non-zero status
+    failed: usize,
+    /// The most recent commands, oldest first
+    recent: Vec<CommandSummary>,
+}
+
+#[derive(Serialize)]
+struct CommandSummary {
+    command: String,
+    /// The exit status, if the command has finished and the
+    /// shell reported it
+    exit_status: Option<i32>,
+    finished: bool,
+}
+
+fn zone_text(pane: &Arc<dyn Pane>, zone: &SemanticZone) -> String {
+    let (first_row, lines) = pane.get_lines(zone.start_y..zone.end_y + 1);
+    let mut text = vec![];
+    for (idx, line) in lines.iter().enumerate() {
+        let row = first_row + idx as isize;
+        let start = if row == zone.start_y { zone.start_x } else { 0 };
+        let end = if row == zone.end_y {
+            zone.end_x.saturating_add(1)
+        } else {
+            usize::MAX
+        };
+        text.push(line.columns_as_str(start..end).trim_end().to_string());
+    }
+    text.join("\n").trim().to_string()
+}
+
+fn command_history(pane: &Arc<dyn Pane>) -> CommandHistory {
+    let zones: Vec<SemanticZone> = match pane.get_semantic_zones() {
+        Ok(zones) => zones
+            .into_iter()
+            .filter(|zone| zone.semantic_type == SemanticType::Input)
+            .collect(),
+        Err(_) => return CommandHistory::default(),
+    };
+    CommandHistory {
+        count: zones.len(),
+        failed: zones
+            .iter()
+            .filter(|zone| matches!(zone.command_status, Some(status) if status.failed()))
+            .count(),
+        recent: zones[zones.len().saturating_sub(RECENT_COMMANDS)..]
+            .iter()
+            .map(|zone| CommandSummary {
+                command: zone_text(pane, zone),
+                exit_status: match zone.command_status {
+                    Some(wezterm_term::CommandStatus::Exited(status)) => Some(status),
+                    _ => None,
+                },
+                finished: zone.command_status.is_some(),
+            })
+            .collect(),
+    }
+}
+
+/// Captures the current state of the mux for `route`.
+/// Must be called on the main thread.
+fn snapshot(route: Route) -> anyhow::Result<serde_json::Value> {
+    let mux = Mux::get();
+    let value = match route {
+        Route::Workspaces => serde_json::to_value(
+            mux.iter_workspaces()
+                .into_iter()
+                .map(|workspace| WorkspaceItem {
+                    window_ids: mux.iter_windows_in_workspace(&workspace),
+                    workspace,
+                })
+                .collect::<Vec<_>>(),
+        )?,
+        Route::Windows => {
+            let mut windows = vec![];
+            for window_id in mux.iter_windows() {
+                if let Some(window) = mux.get_window(window_id) {
+                    windows.push(WindowItem {
+                        window_id,
+                        workspace: window.get_workspace().to_string(),
+                        title: window.get_title().to_string(),
+                        tab_ids: window.iter().map(|tab| tab.tab_id()).collect(),
+                        active_tab_id: window.get_active().map(|tab| tab.tab_id()),
+                    });
+                }
+            }
+            serde_json::to_value(windows)?
+        }
+        Route::Tabs => {
+            let mut tabs = vec![];
+            for window_id in mux.iter_windows() {
+                if let Some(window) = mux.get_window(window_id) {
+                    for tab in window.iter() {
+                        tabs.push(TabItem {
+                            tab_id: tab.tab_id(),
+                            window_id,
+                            title: tab.get_title(),
+                            pane_ids: tab
+                                .iter_panes()
+                                .into_iter()
+                                .map(|p| p.pane.pane_id())
+                                .collect(),
+                            active_pane_id: tab.get_active_pane().map(|p| p.pane_id()),
+                        });
+                    }
+                }
+            }
+            serde_json::to_value(tabs)?
+        }
+        Route::Panes => {
+            // Gather the same data as the ListPanes PDU, so that the
+            // result matches the output of `wezterm cli list`
+            let mut trees = vec![];
+            let mut floating_panes = vec![];
+            let mut tab_titles = vec![];
+            let mut window_titles = HashMap::new();
+            let mut tab_title_overrides = vec![];
+            let mut window_title_overrides = HashSet::new();
+            for window_id in mux.iter_windows() {
+                if let Some(window) = mux.get_window(window_id) {
+                    window_titles.insert(window_id, window.get_title().to_string());
+                    if window.has_title_override() {
+                        window_title_overrides.insert(window_id);
+                    }
+                    for tab in window.iter() {
+                        trees.push(tab.codec_pane_tree());
+                        floating_panes.push(tab.codec_floating_panes());
+                        tab_titles.push(tab.get_title());
+                        tab_title_overrides.push(tab.has_title_override());
+                    }
+                }
+            }
+            let panes: Vec<PaneItem> = pane_list_items(
+                trees,
+                floating_panes,
+                &tab_titles,
+                &window_titles,
+                &tab_title_overrides,
+                &window_title_overrides,
+            )
+            .into_iter()
+            .map(|item| PaneItem {
+                command_history: mux
+                    .get_pane(item.pane_id)
+                    .map(|pane| command_history(&pane))
+                    .unwrap_or_default(),
+                item,
+            })
+            .collect();
+            serde_json::to_value(panes)?
+        }
+        Route::Events => anyhow::bail!("events are streamed rather than captured"),
+    };
+    Ok(value)
+}
+
+/// The events that are sent to `/events` clients.
+/// Pane output is omitted as it is far too frequent to be useful to
+/// a dashboard, and clipboard and download notifications are omitted
+/// as they may carry sensitive data.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event")]
+enum MuxEvent {
+    PaneAdded {
+        pane_id: PaneId,
+    },
+    PaneRemoved {
+        pane_id: PaneId,
+    },
+    PaneFocused {
+        pane_id: PaneId,
+    },
+    Alert {
+        pane_id: PaneId,
+    },
+    WindowCreated {
+        window_id: WindowId,
+    },
+    WindowRemoved {
+        window_id: WindowId,
+    },
+    WindowWorkspaceChanged {
+        window_id: WindowId,
+    },
+    TabAddedToWindow {
+        tab_id: TabId,
+        window_id: WindowId,
+    },
+    TabResized {
+        tab_id: TabId,
+    },
+    TabTitleChanged {
+        tab_id: TabId,
+        title: String,
+    },
+    WindowTitleChanged {
+        window_id: WindowId,
+        title: String,
+    },
+    WorkspaceRenamed {
+        old_workspace: String,
+        new_workspace: String,
+    },
+}
+
+impl MuxEvent {
+    fn from_notification(notif: MuxNotification) -> Option<Self> {
+        Some(match notif {
+            MuxNotification::PaneAdded(pane_id) => Self::PaneAdded { pane_id },
+            MuxNotification::PaneRemoved(pane_id) => Self::PaneRemoved { pane_id },
+            MuxNotification::PaneFocused(pane_id) => Self::PaneFocused { pane_id },
+            MuxNotification::Alert { pane_id, .. } => Self::Alert { pane_id },
+            MuxNotification::WindowCreated(window_id) => Self::WindowCreated { window_id },
+            MuxNotification::WindowRemoved(window_id) => Self::WindowRemoved { window_id },
+            MuxNotification::WindowWorkspaceChanged(window_id) => {
+                Self::WindowWorkspaceChanged { window_id }
+            }
+            MuxNotification::TabAddedToWindow { tab_id, window_id } => {
+                Self::TabAddedToWindow { tab_id, window_id }
+            }
+            MuxNotification::TabResized(tab_id) => Self::TabResized { tab_id },
+            MuxNotification::TabTitleChanged { tab_id, title, .. } => {
+                Self::TabTitleChanged { tab_id, title }
+            }
+            MuxNotification::WindowTitleChanged {
+                window_id, title, ..
+            } => Self::WindowTitleChanged { window_id, title },
+            MuxNotification::WorkspaceRenamed {
+                old_workspace,
+                new_workspace,
+            } => Self::WorkspaceRenamed {
+                old_workspace,
+                new_workspace,
+            },
+            MuxNotification::PaneOutput(_)
+            | MuxNotification::WindowInvalidated(_)
+            | MuxNotification::WindowGeometryChanged(_)
+            | MuxNotification::ActiveWorkspaceChanged(_)
+            | MuxNotification::KillClient(_)
+            | MuxNotification::Empty
+            | MuxNotification::PowerEvent(_)
+            | MuxNotification::AssignClipboard { .. }
+            | MuxNotification::SaveToDownloads { .. } => return None,
+        })
+    }
+}
+
+/// Streams mux notifications to the client as server-sent events
+/// until the client goes away
+async fn stream_events(mut stream: Async<TcpStream>) -> anyhow::Result<()> {
+    stream
+        .write_all(
+            b"HTTP/1.1 200 OK\r\n\
+              Content-Type: text/event-stream\r\n\
+              Cache-Control: no-cache\r\n\
+              Connection: close\r\n\r\n",
+        )
+        .await?;
+    stream.flush().await?;
+
+    let (tx, rx) = smol::channel::bounded(EVENT_QUEUE);
+    Mux::get().subscribe(move |notif| match MuxEvent::from_notification(notif) {
+        Some(event) => match tx.try_send(event) {
+            Ok(()) | Err(smol::channel::TrySendError::Full(_)) => true,
+            Err(smol::channel::TrySendError::Closed(_)) => false,
+        },
+        None => !tx.is_closed(),
+    });
+
+    loop {
+        let event = smol::future::or(async { rx.recv().await.map(Some) }, async {
+            smol::Timer::after(EVENT_KEEPALIVE).await;
+            Ok(None)
+        })
+        .await?;
+        let data = match event {
+            Some(event) => format!("data: {}\n\n", serde_json::to_string(&event)?),
+            None => ":\n\n".to_string(),
+        };
+        stream.write_all(data.as_bytes()).await?;
+        stream.flush().await?;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use mux::renderable::StableCursorPosition;
+    use mux::tab::PaneEntry;
+    use wezterm_term::TerminalSize;
+
+    fn request(head: &str) -> Request {
+        Request::parse(head.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn auth_rejection() {
+        let token = "s3cret";
+
+        let missing = request("GET /panes HTTP/1.1\r\nHost: localhost");
+        assert_eq!(route(&missing, token).unwrap_err().status, 401);
+
+        let wrong = request("GET /panes HTTP/1.1\r\nAuthorization: Bearer s3cre7");
+        assert_eq!(route(&wrong, token).unwrap_err().status, 401);
+
+        let not_bearer = request("GET /panes HTTP/1.1\r\nAuthorization: Basic s3cret");
+        assert_eq!(route(&not_bearer, token).unwrap_err().status, 401);
+
+        // Unknown paths don't reveal anything without the token
+        let unknown = request("GET /nope HTTP/1.1");
+        assert_eq!(route(&unknown, token).unwrap_err().status, 401);
+
+        let ok = request("GET /panes?x=1 HTTP/1.1\r\nauthorization:  Bearer s3cret");
+        assert_eq!(route(&ok, token), Ok(Route::Panes));
+
+        let unknown = request("GET /nope HTTP/1.1\r\nAuthorization: Bearer s3cret");
+        assert_eq!(route(&unknown, token).unwrap_err().status, 404);
+
+        // The endpoint is read-only
+        let post = request("POST /panes HTTP/1.1\r\nAuthorization: Bearer s3cret");
+        assert_eq!(route(&post, token).unwrap_err().status, 405);
+
+        assert!(Request::parse(b"GET /panes").is_err());
+    }
+
+    #[test]
+    fn pane_schema_matches_cli_list() {
+        let entry = PaneEntry {
+            window_id: 1,
+            tab_id: 2,
+            pane_id: 3,
+            title: "vim".to_string(),
+            size: TerminalSize::default(),
+            working_dir: Some(url::Url::parse("file:///home/user").unwrap().into()),
+            is_active_pane: true,
+            is_zoomed_pane: false,
+            is_floating: false,
+            workspace: "default".to_string(),
+            cursor_pos: StableCursorPosition::default(),
+            physical_top: 0,
+            top_row: 0,
+            left_col: 0,
+            tty_name: None,
+            links: vec![],
+            nesting_depth: 0,
+        };
+        let item = PaneListItem::from_entry(entry, "tab", false, "window", true);
+        let cli = serde_json::to_value(&item).unwrap();
+
+        let http = serde_json::to_value(PaneItem {
+            item,
+            command_history: CommandHistory::default(),
+        })
+        .unwrap();
+        let mut http = http.as_object().unwrap().clone();
+        assert!(http.remove("command_history").is_some());
+        assert_eq!(serde_json::Value::Object(http), cli);
+    }
+}
@@ -6,6 +6,7 @@ use std::sync::Arc;
 use wezterm_client::domain::{ClientDomain, ClientDomainConfig};
 
 pub mod dispatch;
+pub mod http_status;
 pub mod local;
 pub mod pki;
 pub mod sessionhandler;
@@ -1,21 +1,30 @@
 use crate::PKI;
 use anyhow::{anyhow, Context};
 use codec::*;
+use config::keyassignment::{SpawnCommand, SpawnTabDomain};
 use config::TermConfig;
 use mux::client::ClientId;
-use mux::domain::SplitSource;
-use mux::pane::{CachePolicy, Pane, PaneId};
+use mux::domain::{DomainState, SplitSource};
+use mux::pane::{CachePolicy, Pane, PaneId, WithPaneLines};
+use mux::pane_watch::PaneWatch;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
+#[cfg(unix)]
+use mux::tab::PaneNode;
 use mux::tab::TabId;
 use mux::{Mux, MuxNotification};
 use promise::spawn::spawn_into_main_thread;
-use std::collections::HashMap;
+use rangeset::RangeSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
-use termwiz::surface::SequenceNo;
+use std::time::{Duration, Instant};
+use termwiz::image::ImageData;
+use termwiz::input::KeyboardEncoding;
+use termwiz::surface::line::CellRef;
+use termwiz::surface::{Line, SequenceNo};
 use url::Url;
 use wezterm_term::terminal::Alert;
-use wezterm_term::StableRowIndex;
+use wezterm_term::{MousePointerShape, StableRowIndex, TerminalSize};
 
 #[derive(Clone)]
 pub struct PduSender {
@@ -42,10 +51,72 @@ pub(crate) struct PerPane {
     working_dir: Option<Url>,
     dimensions: RenderableDimensions,
     mouse_grabbed: bool,
+    kitty_keyboard_flags: Option<u16>,
+    grapheme_clustering: bool,
+    mouse_pointer_shape: Option<MousePointerShape>,
+    bracketed_paste: bool,
     sent_initial_palette: bool,
     seqno: SequenceNo,
     config_generation: usize,
     pub(crate) notifications: Vec<Alert>,
+    summary_seqno: SequenceNo,
+    summary_title: String,
+    summary_lines: SummaryLines,
+    last_summary: Option<Instant>,
+    summary_scheduled: bool,
+}
+
+/// The minimum interval between `PaneOutputSummary` PDUs for
+/// a pane that the client has not subscribed to
+const SUMMARY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Accumulates the lines that have changed since the last full delta
+/// was sent for a pane, so that each summary only needs to examine
+/// the lines that could have changed since the previous one: those
+/// from the top of the viewport at that time to the bottom of the
+/// current viewport.
+#[derive(Default, Debug)]
+struct SummaryLines {
+    changed: RangeSet<StableRowIndex>,
+    /// The seqno and viewport top as of the most recent scan
+    scanned: Option<(SequenceNo, StableRowIndex)>,
+}
+
+impl SummaryLines {
+    /// Called when a full delta has been sent
+    fn reset(&mut self, seqno: SequenceNo, physical_top: StableRowIndex) {
+        self.changed = RangeSet::new();
+        self.scanned.replace((seqno, physical_top));
+    }
+
+    /// Returns the range of lines to examine, and the seqno to
+    /// examine them against, to bring the summary up to date.
+    /// `delta_seqno` is used when no full delta has been sent.
+    fn scan_range(
+        &self,
+        delta_seqno: SequenceNo,
+        dims: &RenderableDimensions,
+    ) -> (Range<StableRowIndex>, SequenceNo) {
+        let end = dims.physical_top + dims.viewport_rows as StableRowIndex;
+        match self.scanned {
+            Some((seqno, top)) => (top.min(dims.physical_top)..end, seqno),
+            None => (0..end, delta_seqno),
+        }
+    }
+
+    fn add(
+        &mut self,
+        changed: &RangeSet<StableRowIndex>,
+        seqno: SequenceNo,
+        physical_top: StableRowIndex,
+    ) {
+        self.changed.add_set(changed);
+        self.scanned.replace((seqno, physical_top));
+    }
+
+    fn len(&self) -> usize {
+        self.changed.len() as usize
+    }
 }
 
 impl PerPane {
@@ -60,6 +131,29 @@ impl PerPane {
             changed = true;
         }
 
+        let kitty_keyboard_flags = match pane.get_keyboard_encoding() {
+            KeyboardEncoding::Kitty(flags) => Some(flags.bits()),
+            _ => None,
+        };
+        if kitty_keyboard_flags != self.kitty_keyboard_flags {
+            changed = true;
+        }
+
+        let grapheme_clustering = pane.is_grapheme_clustering_enabled();
+        if grapheme_clustering != self.grapheme_clustering {
+            changed = true;
+        }
+
+        let mouse_pointer_shape = pane.get_mouse_pointer_shape();
+        if mouse_pointer_shape != self.mouse_pointer_shape {
+            changed = true;
+        }
+
+        let bracketed_paste = pane.is_bracketed_paste_enabled();
+        if bracketed_paste != self.bracketed_paste {
+            changed = true;
+        }
+
         let dims = pane.get_dimensions();
         if dims != self.dimensions {
             changed = true;
@@ -124,7 +218,12 @@ impl PerPane {
         self.working_dir = working_dir.clone();
         self.dimensions = dims;
         self.mouse_grabbed = mouse_grabbed;
+        self.kitty_keyboard_flags = kitty_keyboard_flags;
+        self.grapheme_clustering = grapheme_clustering;
+        self.mouse_pointer_shape = mouse_pointer_shape;
+        self.bracketed_paste = bracketed_paste;
         self.seqno = pane.get_current_seqno();
+        self.summary_lines.reset(self.seqno, dims.physical_top);
 
         let bonus_lines = bonus_lines.into();
         Some(GetPaneRenderChangesResponse {
@@ -138,6 +237,10 @@ impl PerPane {
             working_dir: working_dir.map(Into::into),
             input_serial: force_with_input_serial,
             seqno: self.seqno,
+            kitty_keyboard_flags,
+            grapheme_clustering,
+            mouse_pointer_shape,
+            bracketed_paste,
         })
     }
 }
@@ -155,44 +258,124 @@ fn maybe_push_pane_changes(
         })?;
     }
 
-    let config = config::configuration();
-    if per_pane.config_generation != config.generation() {
-        per_pane.config_generation = config.generation();
-        // If the config changed, it may have changed colors
-        // in the palette that we need to push down, so we
-        // synthesize a palette change notification to let
-        // the client know
-        per_pane.notifications.push(Alert::PaletteChanged);
-        per_pane.sent_initial_palette = true;
+    per_pane.push_notifications(pane, &sender)
+}
+
+/// Used in place of maybe_push_pane_changes for panes that are not
+/// currently subscribed by the client: the client is told how many
+/// lines it has missed, at most once per SUMMARY_INTERVAL, and alerts
+/// are forwarded as normal so that tab indicators keep working.
+/// Title changes are summarized immediately for the same reason.
+/// The delta seqno is not advanced, so that the next full delta sent
+/// to the client covers everything that changed in the meantime.
+fn maybe_push_pane_summary(
+    pane: &Arc<dyn Pane>,
+    sender: PduSender,
+    per_pane_arc: Arc<Mutex<PerPane>>,
+) -> anyhow::Result<()> {
+    let mut per_pane = per_pane_arc.lock().unwrap();
+
+    let title_changed = pane.get_title() != per_pane.summary_title;
+    if !title_changed {
+        if per_pane.summary_scheduled {
+            return per_pane.push_notifications(pane, &sender);
+        }
+
+        if let Some(elapsed) = per_pane.last_summary.map(|last| last.elapsed()) {
+            if elapsed < SUMMARY_INTERVAL {
+                per_pane.summary_scheduled = true;
+                let pane = Arc::clone(pane);
+                let per_pane_arc = Arc::clone(&per_pane_arc);
+                let sender = sender.clone();
+                spawn_into_main_thread(async move {
+                    smol::Timer::after(SUMMARY_INTERVAL - elapsed).await;
+                    let mut per_pane = per_pane_arc.lock().unwrap();
+                    per_pane.summary_scheduled = false;
+                    per_pane.push_summary(&pane, &sender)
+                })
+                .detach();
+                return per_pane.push_notifications(pane, &sender);
+            }
+        }
     }
 
-    if !per_pane.sent_initial_palette {
-        per_pane.notifications.push(Alert::PaletteChanged);
-        per_pane.sent_initial_palette = true;
+    per_pane.push_summary(pane, &sender)?;
+    per_pane.push_notifications(pane, &sender)
+}
+
+impl PerPane {
+    fn push_summary(&mut self, pane: &Arc<dyn Pane>, sender: &PduSender) -> anyhow::Result<()> {
+        let seqno = pane.get_current_seqno();
+        let title = pane.get_title();
+        if seqno == self.summary_seqno && title == self.summary_title {
+            return Ok(());
+        }
+
+        let dims = pane.get_dimensions();
+        let (range, since) = self.summary_lines.scan_range(self.seqno, &dims);
+        let changed = pane.get_changed_since(range, since);
+        self.summary_lines.add(&changed, seqno, dims.physical_top);
+        let lines_changed = self.summary_lines.len();
+
+        self.summary_seqno = seqno;
+        self.summary_title = title.clone();
+        self.last_summary.replace(Instant::now());
+
+        sender.send(DecodedPdu {
+            pdu: Pdu::PaneOutputSummary(PaneOutputSummary {
+                pane_id: pane.pane_id(),
+                seqno,
+                lines_changed,
+                title,
+            }),
+            serial: 0,
+        })
     }
-    for alert in per_pane.notifications.drain(..) {
-        match alert {
-            Alert::PaletteChanged => {
-                sender.send(DecodedPdu {
-                    pdu: Pdu::SetPalette(SetPalette {
-                        pane_id: pane.pane_id(),
-                        palette: pane.palette(),
-                    }),
-                    serial: 0,
-                })?;
-            }
-            alert => {
-                sender.send(DecodedPdu {
-                    pdu: Pdu::NotifyAlert(NotifyAlert {
-                        pane_id: pane.pane_id(),
-                        alert,
-                    }),
-                    serial: 0,
-                })?;
+
+    fn push_notifications(
+        &mut self,
+        pane: &Arc<dyn Pane>,
+        sender: &PduSender,
+    ) -> anyhow::Result<()> {
+        let config = config::configuration();
+        if self.config_generation != config.generation() {
+            self.config_generation = config.generation();
+            // If the config changed, it may have changed colors
+            // in the palette that we need to push down, so we
+            // synthesize a palette change notification to let
+            // the client know
+            self.notifications.push(Alert::PaletteChanged);
+            self.sent_initial_palette = true;
+        }
+
+        if !self.sent_initial_palette {
+            self.notifications.push(Alert::PaletteChanged);
+            self.sent_initial_palette = true;
+        }
+        for alert in self.notifications.drain(..) {
+            match alert {
+                Alert::PaletteChanged => {
+                    sender.send(DecodedPdu {
+                        pdu: Pdu::SetPalette(SetPalette {
+                            pane_id: pane.pane_id(),
+                            palette: pane.palette(),
+                        }),
+                        serial: 0,
+                    })?;
+                }
+                alert => {
+                    sender.send(DecodedPdu {
+                        pdu: Pdu::NotifyAlert(NotifyAlert {
+                            pane_id: pane.pane_id(),
+                            alert,
+                        }),
+                        serial: 0,
+                    })?;
+                }
             }
         }
+        Ok(())
     }
-    Ok(())
 }
 
 pub struct SessionHandler {
@@ -200,6 +383,15 @@ pub struct SessionHandler {
     per_pane: HashMap<TabId, Arc<Mutex<PerPane>>>,
     client_id: Option<Arc<ClientId>>,
     proxy_client_id: Option<ClientId>,
+    /// Describes the connection, for `wezterm cli list-clients`
+    peer: String,
+    /// The set of panes that the client is rendering.
+    /// None means that the client hasn't told us, and
+    /// is implicitly subscribed to all panes.
+    subscribed_panes: Option<HashSet<PaneId>>,
+    /// The pane watches that the client has added, which are
+    /// removed when it disconnects
+    watches: Arc<Mutex<HashSet<(PaneId, String)>>>,
 }
 
 impl Drop for SessionHandler {
@@ -208,19 +400,46 @@ impl Drop for SessionHandler {
             let mux = Mux::get();
             mux.unregister_client(&client_id);
         }
+        let watches = std::mem::take(&mut *self.watches.lock().unwrap());
+        if !watches.is_empty() {
+            spawn_into_main_thread(async move {
+                let mux = Mux::get();
+                for (pane_id, id) in watches {
+                    if let Some(pane) = mux.get_pane(pane_id) {
+                        pane.remove_watch(&id);
+                    }
+                }
+            })
+            .detach();
+        }
     }
 }
 
 impl SessionHandler {
-    pub fn new(to_write_tx: PduSender) -> Self {
+    pub fn new(to_write_tx: PduSender, peer: String) -> Self {
         Self {
             to_write_tx,
             per_pane: HashMap::new(),
             client_id: None,
             proxy_client_id: None,
+            peer,
+            subscribed_panes: None,
+            watches: Arc::new(Mutex::new(HashSet::new())),
         }
     }
 
+    /// Returns true if `client_id` is the client of this session
+    pub fn is_client(&self, client_id: &ClientId) -> bool {
+        self.client_id.as_deref() == Some(client_id)
+    }
+
+    fn is_subscribed(&self, pane_id: PaneId) -> bool {
+        self.subscribed_panes
+            .as_ref()
+            .map(|panes| panes.contains(&pane_id))
+            .unwrap_or(true)
+    }
+
     pub(crate) fn per_pane(&mut self, pane_id: PaneId) -> Arc<Mutex<PerPane>> {
         Arc::clone(
             self.per_pane
@@ -232,12 +451,17 @@ impl SessionHandler {
     pub fn schedule_pane_push(&mut self, pane_id: PaneId) {
         let sender = self.to_write_tx.clone();
         let per_pane = self.per_pane(pane_id);
+        let subscribed = self.is_subscribed(pane_id);
         spawn_into_main_thread(async move {
             let mux = Mux::get();
             let pane = mux
                 .get_pane(pane_id)
                 .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
-            maybe_push_pane_changes(&pane, sender, per_pane)?;
+            if subscribed {
+                maybe_push_pane_changes(&pane, sender, per_pane)?;
+            } else {
+                maybe_push_pane_summary(&pane, sender, per_pane)?;
+            }
             Ok::<(), anyhow::Error>(())
         })
         .detach();
@@ -297,6 +521,7 @@ impl SessionHandler {
             Pdu::SetClientId(SetClientId {
                 mut client_id,
                 is_proxy,
+                codec_vers,
             }) => {
                 if is_proxy {
                     if self.proxy_client_id.is_none() {
@@ -317,16 +542,29 @@ impl SessionHandler {
                             format!("{} (via proxy pid {})", client_id.hostname, proxy_id.pid);
                     }
 
+                    let peer = match &self.proxy_client_id {
+                        Some(proxy_id) => format!("{} via proxy pid {}", self.peer, proxy_id.pid),
+                        None => self.peer.clone(),
+                    };
+
                     let client_id = Arc::new(client_id);
                     self.client_id.replace(client_id.clone());
                     spawn_into_main_thread(async move {
                         let mux = Mux::get();
-                        mux.register_client(client_id);
+                        mux.register_client(client_id.clone());
+                        mux.set_client_connection_info(&client_id, Some(peer), codec_vers);
                     })
                     .detach();
                 }
                 send_response(Ok(Pdu::UnitResponse(UnitResponse {})))
             }
+            Pdu::SetPaneSubscription(SetPaneSubscription { pane_ids }) => {
+                // Panes that are newly subscribed will be caught up by
+                // the client issuing GetPaneRenderChanges for them.
+                self.subscribed_panes
+                    .replace(pane_ids.into_iter().collect());
+                send_response(Ok(Pdu::UnitResponse(UnitResponse {})))
+            }
             Pdu::SetFocusedPane(SetFocusedPane { pane_id }) => {
                 let client_id = self.client_id.clone();
                 spawn_into_main_thread(async move {
@@ -384,27 +622,99 @@ impl SessionHandler {
                 })
                 .detach();
             }
+            Pdu::KillClient(KillClient { session_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            mux.kill_client(session_id)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+            Pdu::ListDomains(ListDomains {}) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let panes = mux.iter_panes();
+                            let domains = mux
+                                .iter_domains()
+                                .into_iter()
+                                .map(|domain| {
+                                    let domain_id = domain.domain_id();
+                                    DomainInfo {
+                                        domain_id,
+                                        name: domain.domain_name().to_string(),
+                                        domain_type: domain.domain_type().to_string(),
+                                        attached: domain.state() == DomainState::Attached,
+                                        num_panes: panes
+                                            .iter()
+                                            .filter(|p| p.domain_id() == domain_id)
+                                            .count(),
+                                    }
+                                })
+                                .collect();
+                            Ok(Pdu::ListDomainsResponse(ListDomainsResponse { domains }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+            Pdu::GetLogEntries(GetLogEntries { after, level }) => {
+                send_response(get_log_entries(after, &level));
+            }
+            Pdu::ListClosed(ListClosed {}) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let items = Mux::get().list_closed();
+                            Ok(Pdu::ListClosedResponse(ListClosedResponse { items }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
             Pdu::ListPanes(ListPanes {}) => {
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
                             let mux = Mux::get();
                             let mut tabs = vec![];
+                            let mut floating_panes = vec![];
+                            let mut floating_pane_geometries = vec![];
                             let mut tab_titles = vec![];
                             let mut window_titles = HashMap::new();
+                            let mut tab_title_overrides = vec![];
+                            let mut window_title_overrides = HashSet::new();
                             for window_id in mux.iter_windows().into_iter() {
                                 let window = mux.get_window(window_id).unwrap();
                                 window_titles.insert(window_id, window.get_title().to_string());
+                                if window.has_title_override() {
+                                    window_title_overrides.insert(window_id);
+                                }
                                 for tab in window.iter() {
                                     tabs.push(tab.codec_pane_tree());
+                                    floating_panes.push(tab.codec_floating_panes());
+                                    floating_pane_geometries.push(tab.floating_pane_geometries());
                                     tab_titles.push(tab.get_title());
+                                    tab_title_overrides.push(tab.has_title_override());
                                 }
                             }
                             log::trace!("ListPanes {tabs:#?} {tab_titles:?}");
                             Ok(Pdu::ListPanesResponse(ListPanesResponse {
                                 tabs,
+                                floating_panes,
+                                floating_pane_geometries,
                                 tab_titles,
                                 window_titles,
+                                tab_title_overrides,
+                                window_title_overrides,
                             }))
                         },
                         send_response,
@@ -468,6 +778,154 @@ impl SessionHandler {
                 })
                 .detach();
             }
+            Pdu::SetPaneSilenceThreshold(SetPaneSilenceThreshold { pane_id, seconds }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.set_silence_threshold(seconds.map(Duration::from_secs));
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::PauseOutput(PauseOutput { pane_id, paused }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.set_output_paused(paused);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::AddPaneWatch(AddPaneWatch {
+                pane_id,
+                id,
+                pattern,
+                regex,
+            }) => {
+                let watches = Arc::clone(&self.watches);
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.add_watch(PaneWatch::new(id.clone(), pattern, regex)?)?;
+                            watches.lock().unwrap().insert((pane_id, id));
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::ResolveInheritedEnv(ResolveInheritedEnv {
+                pane_id,
+                inherit_env,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            Ok(Pdu::ResolveInheritedEnvResponse(
+                                ResolveInheritedEnvResponse {
+                                    env: inherit_env.resolve(&*pane),
+                                },
+                            ))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::GetPanePalette(GetPanePalette { pane_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            Ok(Pdu::GetPanePaletteResponse(GetPanePaletteResponse {
+                                palette: pane.palette(),
+                            }))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::PrependScrollback(PrependScrollback { pane_id, lines }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            let (lines, _images) = lines.extract_data();
+                            pane.prepend_scrollback(
+                                lines.into_iter().map(|(_, line)| line).collect(),
+                            )?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::RemovePaneWatch(RemovePaneWatch { pane_id, id }) => {
+                let watches = Arc::clone(&self.watches);
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            watches.lock().unwrap().remove(&(pane_id, id.clone()));
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.remove_watch(&id);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
+            Pdu::LinkPanes(LinkPanes {
+                pane_id,
+                other_pane_id,
+                options,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            mux.link_panes(pane_id, other_pane_id, options)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    );
+                })
+                .detach();
+            }
             Pdu::KillPane(KillPane { pane_id }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -478,8 +936,9 @@ impl SessionHandler {
                             let pane = mux
                                 .get_pane(pane_id)
                                 .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            mux.retain_closed_pane(pane_id);
                             pane.kill();
-                            mux.remove_pane(pane_id);
+                            mux.remove_pane_and_linked(pane_id);
                             maybe_push_pane_changes(&pane, sender, per_pane)?;
                             Ok(Pdu::UnitResponse(UnitResponse {}))
                         },
@@ -609,6 +1068,25 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::GetSemanticZones(GetSemanticZones { pane_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            let zones = pane.get_semantic_zones()?;
+                            Ok(Pdu::GetSemanticZonesResponse(GetSemanticZonesResponse {
+                                zones,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::ActivatePaneDirection(ActivatePaneDirection { pane_id, direction }) => {
                 spawn_into_main_thread(async move {
                     catch(
@@ -724,6 +1202,14 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::SpawnFloatingPane(request) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    schedule_spawn_floating_pane(request, send_response, client_id);
+                })
+                .detach();
+            }
+
             Pdu::MovePaneToNewTab(request) => {
                 let client_id = self.client_id.clone();
                 spawn_into_main_thread(async move {
@@ -850,6 +1336,45 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::FetchImageData(FetchImageData {
+                pane_id,
+                line_idx,
+                cell_idx,
+                data_hash,
+                offset,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+
+                            let (total_len, chunk) = match serialized_image_data(
+                                &pane, line_idx, cell_idx, data_hash,
+                            )? {
+                                Some(bytes) => {
+                                    let start = offset.min(bytes.len());
+                                    let end = (start + IMAGE_DATA_CHUNK_SIZE).min(bytes.len());
+                                    (Some(bytes.len()), bytes[start..end].to_vec())
+                                }
+                                None => (None, vec![]),
+                            };
+
+                            Ok(Pdu::FetchImageDataResponse(FetchImageDataResponse {
+                                data_hash,
+                                total_len,
+                                offset,
+                                chunk,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::GetCodecVersion(_) => {
                 match std::env::current_exe().context("resolving current_exe") {
                     Err(err) => send_response(Err(err)),
@@ -878,7 +1403,11 @@ impl SessionHandler {
                     send_response,
                 );
             }
-            Pdu::WindowTitleChanged(WindowTitleChanged { window_id, title }) => {
+            Pdu::WindowTitleChanged(WindowTitleChanged {
+                window_id,
+                title,
+                user_override,
+            }) => {
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
@@ -887,7 +1416,7 @@ impl SessionHandler {
                                 .get_window_mut(window_id)
                                 .ok_or_else(|| anyhow!("no such window {window_id}"))?;
 
-                            window.set_title(&title);
+                            window.set_title_with_override(&title, user_override);
 
                             Ok(Pdu::UnitResponse(UnitResponse {}))
                         },
@@ -896,7 +1425,11 @@ impl SessionHandler {
                 })
                 .detach();
             }
-            Pdu::TabTitleChanged(TabTitleChanged { tab_id, title }) => {
+            Pdu::TabTitleChanged(TabTitleChanged {
+                tab_id,
+                title,
+                user_override,
+            }) => {
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
@@ -905,7 +1438,7 @@ impl SessionHandler {
                                 .get_tab(tab_id)
                                 .ok_or_else(|| anyhow!("no such tab {tab_id}"))?;
 
-                            tab.set_title(&title);
+                            tab.set_title_with_override(&title, user_override);
 
                             Ok(Pdu::UnitResponse(UnitResponse {}))
                         },
@@ -986,6 +1519,65 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::RespawnPane(request) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    schedule_respawn_pane(request, send_response, client_id);
+                })
+                .detach();
+            }
+
+            Pdu::AdoptTab(request) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    schedule_adopt_tab(request, send_response, client_id);
+                })
+                .detach();
+            }
+
+            Pdu::MoveTabToDomain(request) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    schedule_move_tab_to_domain(request, send_response, client_id);
+                })
+                .detach();
+            }
+
+            Pdu::AttachDomain(request) => {
+                let client_id = self.client_id.clone();
+                spawn_into_main_thread(async move {
+                    schedule_attach_domain(request, send_response, client_id);
+                })
+                .detach();
+            }
+
+            Pdu::DetachDomain(DetachDomain { name }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get();
+                            let domain = mux
+                                .get_domain_by_name(&name)
+                                .ok_or_else(|| anyhow!("domain {} not found", name))?;
+                            if domain.state() == DomainState::Detached {
+                                return Ok(Pdu::DomainStateChangeResponse(
+                                    DomainStateChangeResponse { changed: false },
+                                ));
+                            }
+                            if !domain.detachable() {
+                                anyhow::bail!("domain {} cannot be detached", name);
+                            }
+                            domain.detach()?;
+                            Ok(Pdu::DomainStateChangeResponse(DomainStateChangeResponse {
+                                changed: true,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::Invalid { .. } => send_response(Err(anyhow!("invalid PDU {:?}", decoded.pdu))),
             Pdu::Pong { .. }
             | Pdu::ListPanesResponse { .. }
@@ -996,17 +1588,28 @@ impl SessionHandler {
             | Pdu::UnitResponse { .. }
             | Pdu::LivenessResponse { .. }
             | Pdu::GetPaneDirectionResponse { .. }
+            | Pdu::GetSemanticZonesResponse { .. }
+            | Pdu::PaneOutputSummary { .. }
             | Pdu::SearchScrollbackResponse { .. }
             | Pdu::GetLinesResponse { .. }
             | Pdu::GetCodecVersionResponse { .. }
             | Pdu::WindowWorkspaceChanged { .. }
             | Pdu::GetTlsCredsResponse { .. }
             | Pdu::GetClientListResponse { .. }
+            | Pdu::DisconnectedByServer { .. }
             | Pdu::PaneRemoved { .. }
             | Pdu::PaneFocused { .. }
             | Pdu::TabResized { .. }
             | Pdu::GetImageCellResponse { .. }
+            | Pdu::FetchImageDataResponse { .. }
             | Pdu::MovePaneToNewTabResponse { .. }
+            | Pdu::AdoptTabResponse { .. }
+            | Pdu::ListDomainsResponse { .. }
+            | Pdu::ListClosedResponse { .. }
+            | Pdu::ResolveInheritedEnvResponse { .. }
+            | Pdu::GetPanePaletteResponse { .. }
+            | Pdu::GetLogEntriesResponse { .. }
+            | Pdu::DomainStateChangeResponse { .. }
             | Pdu::TabAddedToWindow { .. }
             | Pdu::GetPaneRenderableDimensionsResponse { .. }
             | Pdu::ErrorResponse { .. } => {
@@ -1016,6 +1619,122 @@ impl SessionHandler {
     }
 }
 
+fn get_log_entries(after: u64, level: &str) -> anyhow::Result<Pdu> {
+    let level: log::Level = level
+        .parse()
+        .map_err(|_| anyhow!("invalid log level {:?}", level))?;
+    let entries = env_bootstrap::ringlog::get_entries_after(after)
+        .into_iter()
+        .filter(|entry| entry.level <= level)
+        .map(|entry| LogEntry {
+            seqno: entry.seqno,
+            timestamp_ms: entry.then.timestamp_millis(),
+            level: entry.level.as_str().to_string(),
+            target: entry.target,
+            msg: entry.msg,
+        })
+        .collect();
+    Ok(Pdu::GetLogEntriesResponse(GetLogEntriesResponse {
+        entries,
+    }))
+}
+
+/// The number of serialized images that are retained while
+/// clients fetch them in chunks
+const IMAGE_TRANSFER_CACHE_SIZE: usize = 4;
+
+lazy_static::lazy_static! {
+    static ref IMAGE_TRANSFERS: Mutex<VecDeque<([u8; 32], Arc<Vec<u8>>)>> =
+        Mutex::new(VecDeque::new());
+}
+
+/// Searches lines in place for the image data with a given hash,
+/// so that the lines need not be copied out of the pane
+struct FindImageData {
+    cell_idx: Option<usize>,
+    data_hash: [u8; 32],
+    found: Option<Arc<ImageData>>,
+}
+
+impl WithPaneLines for FindImageData {
+    fn with_lines_mut(&mut self, _first_row: StableRowIndex, lines: &mut [&mut Line]) {
+        let data_hash = self.data_hash;
+        let matches = |cell: CellRef| {
+            cell.attrs()
+                .images()?
+                .into_iter()
+                .find(|im| im.image_data().hash() == data_hash)
+                .map(|im| Arc::clone(im.image_data()))
+        };
+        self.found = lines.iter().find_map(|line| match self.cell_idx {
+            Some(idx) => line.get_cell(idx).and_then(matches),
+            None => line.visible_cells().find_map(matches),
+        });
+    }
+}
+
+/// Locates the image data with the specified hash in the pane,
+/// starting with the cell that the client believes references it,
+/// then falling back to the rest of the pane
+fn find_image_data(
+    pane: &Arc<dyn Pane>,
+    line_idx: StableRowIndex,
+    cell_idx: usize,
+    data_hash: [u8; 32],
+) -> Option<Arc<ImageData>> {
+    let mut finder = FindImageData {
+        cell_idx: Some(cell_idx),
+        data_hash,
+        found: None,
+    };
+    pane.with_lines_mut(line_idx..line_idx + 1, &mut finder);
+    if finder.found.is_some() {
+        return finder.found;
+    }
+
+    // The line has changed since the client saw it; search the
+    // scrollback and the screen
+    let dims = pane.get_dimensions();
+    finder.cell_idx = None;
+    pane.with_lines_mut(
+        dims.scrollback_top..dims.physical_top + dims.viewport_rows as StableRowIndex,
+        &mut finder,
+    );
+    finder.found
+}
+
+/// Returns the serialized form of the image data with the specified
+/// hash, caching it so that subsequent chunks can be sent without
+/// serializing it again
+fn serialized_image_data(
+    pane: &Arc<dyn Pane>,
+    line_idx: StableRowIndex,
+    cell_idx: usize,
+    data_hash: [u8; 32],
+) -> anyhow::Result<Option<Arc<Vec<u8>>>> {
+    if let Some((_, bytes)) = IMAGE_TRANSFERS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(hash, _)| *hash == data_hash)
+    {
+        return Ok(Some(Arc::clone(bytes)));
+    }
+
+    let data = match find_image_data(pane, line_idx, cell_idx, data_hash) {
+        Some(data) => data,
+        None => return Ok(None),
+    };
+    let bytes = Arc::new(serialize_image_data(&data)?);
+
+    let mut transfers = IMAGE_TRANSFERS.lock().unwrap();
+    if transfers.len() >= IMAGE_TRANSFER_CACHE_SIZE {
+        transfers.pop_front();
+    }
+    transfers.push_back((data_hash, Arc::clone(&bytes)));
+    Ok(Some(bytes))
+}
+
 // Dancing around a little bit here; we can't directly spawn_into_main_thread the domain_spawn
 // function below because the compiler thinks that all of its locals then need to be Send.
 // We need to shimmy through this helper to break that aspect of the compiler flow
@@ -1053,6 +1772,7 @@ async fn split_pane(split: SplitPane, client_id: Option<Arc<ClientId>>) -> anyho
         SplitSource::Spawn {
             command: split.command,
             command_dir: split.command_dir,
+            inherit_env: split.inherit_env,
         }
     };
 
@@ -1068,14 +1788,90 @@ async fn split_pane(split: SplitPane, client_id: Option<Arc<ClientId>>) -> anyho
     }))
 }
 
+fn schedule_spawn_floating_pane<SND>(
+    request: SpawnFloatingPane,
+    send_response: SND,
+    client_id: Option<Arc<ClientId>>,
+) where
+    SND: Fn(anyhow::Result<Pdu>) + 'static,
+{
+    promise::spawn::spawn(
+        async move { send_response(spawn_floating_pane(request, client_id).await) },
+    )
+    .detach();
+}
+
+async fn spawn_floating_pane(
+    request: SpawnFloatingPane,
+    client_id: Option<Arc<ClientId>>,
+) -> anyhow::Result<Pdu> {
+    let mux = Mux::get();
+    let _identity = mux.with_identity(client_id);
+
+    let window_id = mux
+        .window_containing_tab(request.tab_id)
+        .ok_or_else(|| anyhow!("tab {} is not in a window", request.tab_id))?;
+
+    let pane = mux
+        .spawn_floating_pane(
+            request.tab_id,
+            request.geometry,
+            request.command,
+            request.command_dir,
+            request.domain,
+        )
+        .await?;
+    let dims = pane.get_dimensions();
+
+    Ok::<Pdu, anyhow::Error>(Pdu::SpawnResponse(SpawnResponse {
+        pane_id: pane.pane_id(),
+        tab_id: request.tab_id,
+        window_id,
+        size: TerminalSize {
+            cols: dims.cols,
+            rows: dims.viewport_rows,
+            pixel_width: dims.pixel_width,
+            pixel_height: dims.pixel_height,
+            dpi: dims.dpi,
+        },
+    }))
+}
+
 async fn domain_spawn_v2(spawn: SpawnV2, client_id: Option<Arc<ClientId>>) -> anyhow::Result<Pdu> {
     let mux = Mux::get();
     let _identity = mux.with_identity(client_id);
 
+    // A new tab takes the profile of the active pane of its window
+    let source_pane_id = spawn
+        .window_id
+        .and_then(|window_id| mux.get_active_tab_for_window(window_id))
+        .and_then(|tab| tab.get_active_pane())
+        .map(|pane| pane.pane_id());
+    let profile = mux.resolve_spawn_profile(spawn.profile.as_deref(), source_pane_id)?;
+    // The profile can change the program, its environment and the
+    // domain in which it is spawned, so it is known before spawning
+    let domain = mux.resolve_profile_domain(profile.as_deref(), spawn.domain);
+    let _spawn_command = mux.with_spawn_command(SpawnCommand {
+        args: spawn
+            .command
+            .as_ref()
+            .filter(|cmd| !cmd.is_default_prog())
+            .map(|cmd| {
+                cmd.get_argv()
+                    .iter()
+                    .map(|arg| arg.to_string_lossy().into_owned())
+                    .collect()
+            }),
+        cwd: spawn.command_dir.as_ref().map(Into::into),
+        domain: domain.clone(),
+        profile: profile.clone(),
+        ..SpawnCommand::default()
+    });
+
     let (tab, pane, window_id) = mux
         .spawn_tab_or_window(
             spawn.window_id,
-            spawn.domain,
+            domain,
             spawn.command,
             spawn.command_dir,
             spawn.size,
@@ -1085,6 +1881,15 @@ async fn domain_spawn_v2(spawn: SpawnV2, client_id: Option<Arc<ClientId>>) -> an
         )
         .await?;
 
+    if spawn.window_id.is_none() {
+        if let Some(mut window) = mux.get_window_mut(window_id) {
+            window.set_profile(profile.clone());
+        }
+    }
+    if let Some(profile) = &profile {
+        mux.set_pane_profile(&pane, profile)?;
+    }
+
     Ok::<Pdu, anyhow::Error>(Pdu::SpawnResponse(SpawnResponse {
         pane_id: pane.pane_id(),
         tab_id: tab.tab_id(),
@@ -1104,6 +1909,239 @@ fn schedule_move_pane<SND>(
         .detach();
 }
 
+fn schedule_respawn_pane<SND>(
+    request: RespawnPane,
+    send_response: SND,
+    client_id: Option<Arc<ClientId>>,
+) where
+    SND: Fn(anyhow::Result<Pdu>) + 'static,
+{
+    promise::spawn::spawn(async move { send_response(respawn_pane(request, client_id).await) })
+        .detach();
+}
+
+async fn respawn_pane(
+    request: RespawnPane,
+    client_id: Option<Arc<ClientId>>,
+) -> anyhow::Result<Pdu> {
+    let mux = Mux::get();
+    let _identity = mux.with_identity(client_id);
+
+    mux.respawn_pane(request.pane_id, request.command, request.command_dir)
+        .await?;
+
+    Ok::<Pdu, anyhow::Error>(Pdu::UnitResponse(UnitResponse {}))
+}
+
+fn schedule_adopt_tab<SND>(request: AdoptTab, send_response: SND, client_id: Option<Arc<ClientId>>)
+where
+    SND: Fn(anyhow::Result<Pdu>) + 'static,
+{
+    promise::spawn::spawn(async move { send_response(adopt_tab(request, client_id).await) })
+        .detach();
+}
+
+#[cfg(unix)]
+fn collect_tree_pane_ids(node: &PaneNode, ids: &mut Vec<PaneId>) {
+    match node {
+        PaneNode::Empty => {}
+        PaneNode::Split { left, right, .. } => {
+            collect_tree_pane_ids(left, ids);
+            collect_tree_pane_ids(right, ids);
+        }
+        PaneNode::Leaf(entry) => ids.push(entry.pane_id),
+    }
+}
+
+#[cfg(unix)]
+async fn adopt_tab(request: AdoptTab, client_id: Option<Arc<ClientId>>) -> anyhow::Result<Pdu> {
+    use mux::tab::Tab;
+    use std::os::fd::OwnedFd;
+
+    let mux = Mux::get();
+    let _identity = mux.with_identity(client_id);
+
+    let domain = mux.default_domain();
+    let domain = domain
+        .downcast_ref::<mux::domain::LocalDomain>()
+        .ok_or_else(|| anyhow!("the default domain of this mux server cannot adopt ptys"))?;
+
+    // Validate the tree before taking ownership of any pty; once a
+    // pane has been adopted the client will no longer read from it.
+    let mut tree_ids = vec![];
+    collect_tree_pane_ids(&request.tree, &mut tree_ids);
+    let mut pane_ids: Vec<PaneId> = request.panes.iter().map(|p| p.pane_id).collect();
+    tree_ids.sort();
+    pane_ids.sort();
+    anyhow::ensure!(
+        !tree_ids.is_empty() && tree_ids == pane_ids,
+        "pane tree does not match the panes being moved"
+    );
+
+    let num_panes = request.panes.len();
+    let handoff_path = request.handoff_path.clone();
+    let fds = smol::unblock(move || -> anyhow::Result<Vec<OwnedFd>> {
+        let stream = wezterm_uds::UnixStream::connect(&handoff_path).with_context(|| {
+            format!(
+                "connecting to {}; tabs can only be moved into a \
+                 mux server running on the same machine",
+                handoff_path.display()
+            )
+        })?;
+        let mut buf = [0u8; 64];
+        let (_, fds) = stream.recv_with_fds(&mut buf, num_panes)?;
+        Ok(fds)
+    })
+    .await?;
+    anyhow::ensure!(
+        fds.len() == num_panes,
+        "expected {num_panes} ptys but received {}",
+        fds.len()
+    );
+
+    let mut adopted: HashMap<PaneId, Arc<dyn Pane>> = HashMap::new();
+    for (pane, fd) in request.panes.into_iter().zip(fds) {
+        let client_pane_id = pane.pane_id;
+        let pane = domain.adopt_pane(pane.into_handoff(fd))?;
+        log::debug!("adopted client pane {client_pane_id} as {}", pane.pane_id());
+        adopted.insert(client_pane_id, pane);
+    }
+    let pane_ids = adopted
+        .iter()
+        .map(|(client_id, pane)| (*client_id, pane.pane_id()))
+        .collect();
+
+    let tab = Arc::new(Tab::new(&request.size));
+    tab.sync_with_pane_tree(request.size, request.tree, |entry| {
+        adopted
+            .remove(&entry.pane_id)
+            .expect("pane tree was validated above")
+    });
+    mux.add_tab_no_panes(&tab);
+
+    let window_builder;
+    let window_id = match request.window_id.filter(|id| mux.get_window(*id).is_some()) {
+        Some(window_id) => window_id,
+        None => {
+            window_builder = mux.new_empty_window(Some(request.workspace), None);
+            *window_builder
+        }
+    };
+    mux.add_tab_to_window(&tab, window_id)?;
+
+    Ok::<Pdu, anyhow::Error>(Pdu::AdoptTabResponse(AdoptTabResponse {
+        window_id,
+        tab_id: tab.tab_id(),
+        pane_ids,
+    }))
+}
+
+#[cfg(not(unix))]
+async fn adopt_tab(_request: AdoptTab, _client_id: Option<Arc<ClientId>>) -> anyhow::Result<Pdu> {
+    anyhow::bail!("moving tabs into the mux server is not supported on this platform");
+}
+
+fn schedule_move_tab_to_domain<SND>(
+    request: MoveTabToDomain,
+    send_response: SND,
+    client_id: Option<Arc<ClientId>>,
+) where
+    SND: Fn(anyhow::Result<Pdu>) + 'static,
+{
+    promise::spawn::spawn(
+        async move { send_response(move_tab_to_domain(request, client_id).await) },
+    )
+    .detach();
+}
+
+#[cfg(unix)]
+async fn move_tab_to_domain(
+    request: MoveTabToDomain,
+    client_id: Option<Arc<ClientId>>,
+) -> anyhow::Result<Pdu> {
+    let mux = Mux::get();
+    let _identity = mux.with_identity(client_id);
+
+    let domain = mux
+        .get_domain_by_name(&request.domain)
+        .ok_or_else(|| anyhow!("domain {} not found", request.domain))?;
+    let domain = domain
+        .downcast_ref::<wezterm_client::domain::ClientDomain>()
+        .ok_or_else(|| {
+            anyhow!(
+                "domain {} is not a multiplexer domain; tabs can \
+                 only be moved into unix domains",
+                request.domain
+            )
+        })?;
+    domain.adopt_local_tab(request.tab_id).await?;
+
+    Ok::<Pdu, anyhow::Error>(Pdu::UnitResponse(UnitResponse {}))
+}
+
+#[cfg(not(unix))]
+async fn move_tab_to_domain(
+    _request: MoveTabToDomain,
+    _client_id: Option<Arc<ClientId>>,
+) -> anyhow::Result<Pdu> {
+    anyhow::bail!("moving tabs into the mux server is not supported on this platform");
+}
+
+fn schedule_attach_domain<SND>(
+    request: AttachDomain,
+    send_response: SND,
+    client_id: Option<Arc<ClientId>>,
+) where
+    SND: Fn(anyhow::Result<Pdu>) + 'static,
+{
+    promise::spawn::spawn(async move { send_response(attach_domain(request, client_id).await) })
+        .detach();
+}
+
+async fn attach_domain(
+    request: AttachDomain,
+    client_id: Option<Arc<ClientId>>,
+) -> anyhow::Result<Pdu> {
+    let mux = Mux::get();
+    let _identity = mux.with_identity(client_id);
+
+    let domain = mux
+        .get_domain_by_name(&request.name)
+        .ok_or_else(|| anyhow!("domain {} not found", request.name))?;
+    if domain.state() == DomainState::Attached {
+        return Ok(Pdu::DomainStateChangeResponse(DomainStateChangeResponse {
+            changed: false,
+        }));
+    }
+
+    domain.attach(None).await?;
+
+    // As with the AttachDomain key assignment, give the user
+    // something to look at if the domain has no panes yet
+    let have_panes_in_domain = mux
+        .iter_panes()
+        .iter()
+        .any(|p| p.domain_id() == domain.domain_id());
+    if !have_panes_in_domain {
+        let config = config::configuration();
+        mux.spawn_tab_or_window(
+            None,
+            SpawnTabDomain::DomainId(domain.domain_id()),
+            None,
+            None,
+            config.initial_size(0, None),
+            None,
+            mux.active_workspace(),
+            None,
+        )
+        .await?;
+    }
+
+    Ok::<Pdu, anyhow::Error>(Pdu::DomainStateChangeResponse(DomainStateChangeResponse {
+        changed: true,
+    }))
+}
+
 async fn move_pane(
     request: MovePaneToNewTab,
     client_id: Option<Arc<ClientId>>,
@@ -1124,3 +2162,65 @@ async fn move_pane(
         window_id,
     }))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dims(physical_top: StableRowIndex, viewport_rows: usize) -> RenderableDimensions {
+        RenderableDimensions {
+            cols: 80,
+            viewport_rows,
+            scrollback_rows: physical_top as usize + viewport_rows,
+            physical_top,
+            scrollback_top: 0,
+            dpi: 96,
+            pixel_width: 800,
+            pixel_height: 600,
+            reverse_video: false,
+        }
+    }
+
+    fn rows(ranges: &[Range<StableRowIndex>]) -> RangeSet<StableRowIndex> {
+        let mut set = RangeSet::new();
+        for r in ranges {
+            set.add_range(r.clone());
+        }
+        set
+    }
+
+    #[test]
+    fn summary_scans_everything_before_first_delta() {
+        let summary = SummaryLines::default();
+        assert_eq!(summary.scan_range(3, &dims(100, 24)), (0..124, 3));
+    }
+
+    #[test]
+    fn summary_scans_from_previous_viewport() {
+        let mut summary = SummaryLines::default();
+        summary.reset(10, 100);
+        assert_eq!(summary.scan_range(10, &dims(100, 24)), (100..124, 10));
+
+        // Output scrolled the viewport down by 5 lines
+        summary.add(&rows(&[110..129]), 20, 105);
+        assert_eq!(summary.len(), 19);
+        assert_eq!(summary.scan_range(10, &dims(105, 24)), (105..129, 20));
+
+        // Lines that change again are only counted once
+        summary.add(&rows(&[120..130]), 30, 106);
+        assert_eq!(summary.len(), 20);
+
+        // A full delta starts the count over
+        summary.reset(30, 106);
+        assert_eq!(summary.len(), 0);
+        assert_eq!(summary.scan_range(10, &dims(106, 24)), (106..130, 30));
+    }
+
+    #[test]
+    fn summary_scans_from_viewport_after_clear() {
+        let mut summary = SummaryLines::default();
+        summary.reset(10, 100);
+        // The scrollback was cleared, moving the viewport up
+        assert_eq!(summary.scan_range(10, &dims(0, 24)), (0..24, 10));
+    }
+}
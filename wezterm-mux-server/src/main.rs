@@ -185,6 +185,11 @@ fn run() -> anyhow::Result<()> {
         }
     }
 
+    // Record whether we were started from a wezterm pane before
+    // the inherited pane variables are removed below, so that our
+    // panes report the correct nesting depth to clients.
+    mux::nesting::detect_nesting();
+
     // Remove some environment variables that aren't super helpful or
     // that are potentially misleading when we're starting up the
     // server.
@@ -274,6 +279,9 @@ async fn async_run(cmd: Option<CommandBuilder>) -> anyhow::Result<()> {
     });
 
     let domain = mux.default_domain();
+    mux::idle::start_idle_pane_monitor();
+    mux::monitor::start_output_monitor();
+    mux::power::start_power_monitor();
 
     {
         if let Err(err) = config::with_lua_config_on_main_thread(trigger_mux_startup).await {
@@ -321,5 +329,13 @@ pub fn spawn_listener() -> anyhow::Result<()> {
         ossl::spawn_tls_listener(tls_server)?;
     }
 
+    if let Some(http_status) = &config.mux_http_status {
+        let mut listener =
+            wezterm_mux_server_impl::http_status::HttpStatusListener::with_config(http_status)?;
+        thread::spawn(move || {
+            listener.run();
+        });
+    }
+
     Ok(())
 }
@@ -74,6 +74,10 @@ impl OpenSSLNetListener {
             match stream {
                 Ok(stream) => {
                     stream.set_nodelay(true).ok();
+                    let peer = match stream.peer_addr() {
+                        Ok(addr) => format!("tls {addr}"),
+                        Err(_) => "tls".to_string(),
+                    };
                     let acceptor = self.acceptor.clone();
 
                     match acceptor.accept(stream) {
@@ -84,9 +88,10 @@ impl OpenSSLNetListener {
                             }
                             spawn_into_main_thread(async move {
                                 log::error!("Making new AsyncSslStream");
-                                wezterm_mux_server_impl::dispatch::process(AsyncSslStream::new(
-                                    stream,
-                                ))
+                                wezterm_mux_server_impl::dispatch::process(
+                                    AsyncSslStream::new(stream),
+                                    peer,
+                                )
                                 .await
                                 .map_err(|e| {
                                     log::error!("process: {:?}", e);
@@ -73,12 +73,19 @@ impl Pattern {
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 enum Criteria {
+    /// The `Host` keyword; matches the name of the host that is
+    /// being resolved
     Host(Vec<Pattern>),
+    /// `Match host`; matches the name of the host after any `HostName`
+    /// substitution that has been applied so far
+    MatchHost(Vec<Pattern>),
     Exec(String),
     OriginalHost(Vec<Pattern>),
     User(Vec<Pattern>),
     LocalUser(Vec<Pattern>),
     All,
+    /// A criteria that was prefixed with `!`
+    Not(Box<Criteria>),
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -88,6 +95,39 @@ enum Context {
     Final,
 }
 
+/// The values that the criteria of a `MatchGroup` are tested against
+struct MatchState<'a> {
+    /// The host name that `Host` patterns are matched against; this
+    /// is the name that was requested in the first pass, and the
+    /// name after `HostName` substitution and canonicalization in the
+    /// final pass
+    host: &'a str,
+    /// The host name that was requested
+    original_host: &'a str,
+    local_user: &'a str,
+    /// The pass that is being applied
+    context: Context,
+}
+
+impl<'a> MatchState<'a> {
+    /// Returns the host name that `Match host` tests, which reflects
+    /// any `HostName` that has been applied so far
+    fn target_host(&self, options: &ConfigMap) -> String {
+        match options.get("hostname") {
+            Some(hostname) => hostname.replace("%h", self.host),
+            None => self.host.to_string(),
+        }
+    }
+
+    /// Returns the remote user name that `Match user` tests
+    fn target_user(&self, options: &ConfigMap) -> String {
+        options
+            .get("user")
+            .cloned()
+            .unwrap_or_else(|| self.local_user.to_string())
+    }
+}
+
 /// Represents `Host pattern,list` stanza in the config,
 /// and the options that it logically contains
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -98,41 +138,81 @@ struct MatchGroup {
 }
 
 impl MatchGroup {
-    fn is_match(&self, hostname: &str, user: &str, local_user: &str, context: Context) -> bool {
-        if self.context != context {
+    /// Returns true if the group is considered during the `context` pass.
+    /// Both `Match canonical` and `Match final` groups are only considered
+    /// in the final pass, which happens after hostname canonicalization,
+    /// while the other groups are considered in both passes.
+    fn applies_in(&self, context: Context) -> bool {
+        self.context == Context::FirstPass || context != Context::FirstPass
+    }
+
+    fn is_match(&self, config: &Config, state: &MatchState, options: &ConfigMap) -> bool {
+        if !self.applies_in(state.context) {
             return false;
         }
-        for c in &self.criteria {
-            match c {
-                Criteria::Host(patterns) => {
-                    if !Pattern::match_group(hostname, patterns) {
-                        return false;
-                    }
-                }
-                Criteria::Exec(_) => {
-                    log::warn!("Match Exec is not implemented");
-                }
-                Criteria::OriginalHost(patterns) => {
-                    if !Pattern::match_group(hostname, patterns) {
-                        return false;
-                    }
-                }
-                Criteria::User(patterns) => {
-                    if !Pattern::match_group(user, patterns) {
-                        return false;
-                    }
-                }
-                Criteria::LocalUser(patterns) => {
-                    if !Pattern::match_group(local_user, patterns) {
-                        return false;
+        // As with ssh, the criteria are tested in order and testing stops
+        // at the first that doesn't match, so that a `Match exec` command
+        // only runs if the criteria that precede it match
+        self.criteria
+            .iter()
+            .all(|c| Self::criteria_matches(c, config, state, options))
+    }
+
+    fn criteria_matches(
+        c: &Criteria,
+        config: &Config,
+        state: &MatchState,
+        options: &ConfigMap,
+    ) -> bool {
+        match c {
+            Criteria::Host(patterns) => Pattern::match_group(state.host, patterns),
+            Criteria::MatchHost(patterns) => {
+                Pattern::match_group(&state.target_host(options), patterns)
+            }
+            Criteria::Exec(command) => config.match_exec(command, state, options),
+            Criteria::OriginalHost(patterns) => Pattern::match_group(state.original_host, patterns),
+            Criteria::User(patterns) => Pattern::match_group(&state.target_user(options), patterns),
+            Criteria::LocalUser(patterns) => Pattern::match_group(state.local_user, patterns),
+            Criteria::All => true,
+            Criteria::Not(c) => !Self::criteria_matches(c, config, state, options),
+        }
+    }
+}
+
+/// How deeply `Include` directives may nest, which is the same limit
+/// that ssh applies.  This prevents a file that includes itself from
+/// recursing forever.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Splits the arguments of a `Match` line into words, in the same way
+/// as ssh; words may be quoted with double or single quotes so that they
+/// can contain spaces.
+fn split_args(s: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut chars = s.chars().peekable();
+    loop {
+        while chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            return args;
+        }
+        let mut arg = String::new();
+        let mut quote = None;
+        while let Some(c) = chars.next() {
+            match (c, quote) {
+                ('\\', _) => {
+                    if let Some(next) = chars.next() {
+                        arg.push(next);
                     }
                 }
-                Criteria::All => {
-                    // Always matches
-                }
+                ('"' | '\'', None) => quote = Some(c),
+                (c, Some(q)) if c == q => quote = None,
+                (c, None) if c.is_whitespace() => break,
+                (c, _) => arg.push(c),
             }
         }
-        true
+        args.push(arg);
     }
 }
 
@@ -159,7 +239,15 @@ impl ParsedConfigFile {
             loaded_files.push(source.to_path_buf());
         }
 
-        Self::parse_impl(s, cwd, &mut options, &mut groups, &mut loaded_files);
+        Self::parse_impl(
+            s,
+            cwd,
+            None,
+            0,
+            &mut options,
+            &mut groups,
+            &mut loaded_files,
+        );
 
         Self {
             options,
@@ -168,70 +256,172 @@ impl ParsedConfigFile {
         }
     }
 
+    /// Parses the files that match `pattern`, in lexical order.
+    /// Relative patterns are relative to `cwd`, which is the directory
+    /// of the file that contains the `Include`.
+    /// If the `Include` is inside a `Host` or `Match` block, then the
+    /// contents of the files only apply where that block matches.
     fn do_include(
         pattern: &str,
         cwd: Option<&Path>,
+        enclosing: Option<&MatchGroup>,
+        depth: usize,
         options: &mut ConfigMap,
         groups: &mut Vec<MatchGroup>,
         loaded_files: &mut Vec<PathBuf>,
     ) {
-        match filenamegen::Glob::new(&pattern) {
-            Ok(g) => {
-                match cwd
-                    .as_ref()
-                    .map(|p| p.to_path_buf())
-                    .or_else(|| std::env::current_dir().ok())
-                {
-                    Some(cwd) => {
-                        for path in g.walk(&cwd) {
-                            let path = if path.is_absolute() {
-                                path
-                            } else {
-                                cwd.join(path)
-                            };
-                            match std::fs::read_to_string(&path) {
-                                Ok(data) => {
-                                    loaded_files.push(path.clone());
-                                    Self::parse_impl(
-                                        &data,
-                                        Some(&cwd),
-                                        options,
-                                        groups,
-                                        loaded_files,
-                                    );
-                                }
-                                Err(err) => {
-                                    log::error!(
-                                        "error expanding `Include {}`: unable to open {}: {:#}",
-                                        pattern,
-                                        path.display(),
-                                        err
-                                    );
-                                }
-                            }
-                        }
+        if depth > MAX_INCLUDE_DEPTH {
+            log::error!(
+                "error expanding `Include {}`: includes are nested more than {} deep",
+                pattern,
+                MAX_INCLUDE_DEPTH
+            );
+            return;
+        }
+
+        for pattern in split_args(pattern) {
+            let pattern = match dirs_next::home_dir() {
+                Some(home) if pattern.starts_with("~/") => {
+                    home.join(&pattern[2..]).to_string_lossy().to_string()
+                }
+                _ => pattern,
+            };
+
+            let g = match filenamegen::Glob::new(&pattern) {
+                Ok(g) => g,
+                Err(err) => {
+                    log::error!("error expanding `Include {}`: {:#}", pattern, err);
+                    continue;
+                }
+            };
+            let cwd = match cwd
+                .as_ref()
+                .map(|p| p.to_path_buf())
+                .or_else(|| std::env::current_dir().ok())
+            {
+                Some(cwd) => cwd,
+                None => {
+                    log::error!(
+                        "error expanding `Include {}`: unable to determine cwd",
+                        pattern
+                    );
+                    continue;
+                }
+            };
+
+            let mut paths: Vec<PathBuf> = g
+                .walk(&cwd)
+                .map(|path| {
+                    if path.is_absolute() {
+                        path
+                    } else {
+                        cwd.join(path)
+                    }
+                })
+                .collect();
+            paths.sort();
+
+            for path in paths {
+                match std::fs::read_to_string(&path) {
+                    Ok(data) => {
+                        loaded_files.push(path.clone());
+                        Self::parse_impl(
+                            &data,
+                            path.parent(),
+                            enclosing,
+                            depth,
+                            options,
+                            groups,
+                            loaded_files,
+                        );
                     }
-                    None => {
+                    Err(err) => {
                         log::error!(
-                            "error expanding `Include {}`: unable to determine cwd",
-                            pattern
+                            "error expanding `Include {}`: unable to open {}: {:#}",
+                            pattern,
+                            path.display(),
+                            err
                         );
                     }
                 }
             }
-            Err(err) => {
-                log::error!("error expanding `Include {}`: {:#}", pattern, err);
+        }
+    }
+
+    /// Parses the `Match` criteria in `v`, returning them along with
+    /// the pass in which they apply
+    fn parse_match_criteria(v: &str) -> (Vec<Criteria>, Context) {
+        fn parse_pattern_list(v: &str) -> Vec<Pattern> {
+            let mut patterns = vec![];
+            for p in v.split(',') {
+                let p = p.trim();
+                if p.starts_with('!') {
+                    patterns.push(Pattern::new(&p[1..], true));
+                } else {
+                    patterns.push(Pattern::new(p, false));
+                }
             }
+            patterns
         }
+
+        let mut criteria = vec![];
+        let mut context = Context::FirstPass;
+
+        let args = split_args(v);
+        let mut tokens = args.iter().map(String::as_str);
+
+        while let Some(cname) = tokens.next() {
+            let (negated, cname) = match cname.strip_prefix('!') {
+                Some(cname) => (true, cname),
+                None => (false, cname),
+            };
+            let c = match cname.to_lowercase().as_str() {
+                "all" => Criteria::All,
+                "canonical" => {
+                    context = Context::Canonical;
+                    continue;
+                }
+                "final" => {
+                    context = Context::Final;
+                    continue;
+                }
+                "exec" => Criteria::Exec(tokens.next().unwrap_or("false").to_string()),
+                "host" => Criteria::MatchHost(parse_pattern_list(tokens.next().unwrap_or(""))),
+                "originalhost" => {
+                    Criteria::OriginalHost(parse_pattern_list(tokens.next().unwrap_or("")))
+                }
+                "user" => Criteria::User(parse_pattern_list(tokens.next().unwrap_or(""))),
+                "localuser" => Criteria::LocalUser(parse_pattern_list(tokens.next().unwrap_or(""))),
+                _ => break,
+            };
+            criteria.push(if negated {
+                Criteria::Not(Box::new(c))
+            } else {
+                c
+            });
+        }
+
+        (criteria, context)
     }
 
     fn parse_impl(
         s: &str,
         cwd: Option<&Path>,
+        enclosing: Option<&MatchGroup>,
+        depth: usize,
         options: &mut ConfigMap,
         groups: &mut Vec<MatchGroup>,
         loaded_files: &mut Vec<PathBuf>,
     ) {
+        // The block that the options that follow belong to; when this
+        // is None they are global options.
+        // When the groups vector is non-empty, its last entry is the
+        // group that holds the options of this block.
+        let mut scope = enclosing.cloned();
+        if let Some(scope) = &scope {
+            groups.push(scope.clone());
+        }
+
         for line in s.lines() {
             let line = line.trim();
             if line.is_empty() || line.starts_with('#') {
@@ -241,26 +431,16 @@ impl ParsedConfigFile {
             if let Some(sep) = line.find(|c: char| c == '=' || c.is_whitespace()) {
                 let (k, v) = line.split_at(sep);
                 let k = k.trim().to_lowercase();
-                let v = v[1..].trim();
+                // Include and Match take a list of arguments that
+                // may be individually quoted
+                let args = v[1..].trim();
 
-                let v = if v.starts_with('"') && v.ends_with('"') {
-                    &v[1..v.len() - 1]
+                let v = if args.starts_with('"') && args.ends_with('"') {
+                    &args[1..args.len() - 1]
                 } else {
-                    v
+                    args
                 };
 
-                fn parse_pattern_list(v: &str) -> Vec<Pattern> {
-                    let mut patterns = vec![];
-                    for p in v.split(',') {
-                        let p = p.trim();
-                        if p.starts_with('!') {
-                            patterns.push(Pattern::new(&p[1..], true));
-                        } else {
-                            patterns.push(Pattern::new(p, false));
-                        }
-                    }
-                    patterns
-                }
                 fn parse_whitespace_pattern_list(v: &str) -> Vec<Pattern> {
                     let mut patterns = vec![];
                     for p in v.split_ascii_whitespace() {
@@ -275,71 +455,64 @@ impl ParsedConfigFile {
                 }
 
                 if k == "include" {
-                    Self::do_include(v, cwd, options, groups, loaded_files);
-                    continue;
-                }
-
-                if k == "host" {
-                    let patterns = parse_whitespace_pattern_list(v);
-                    groups.push(MatchGroup {
-                        criteria: vec![Criteria::Host(patterns)],
-                        options: ConfigMap::new(),
-                        context: Context::FirstPass,
-                    });
+                    let num_groups = groups.len();
+                    Self::do_include(
+                        args,
+                        cwd,
+                        scope.as_ref(),
+                        depth + 1,
+                        options,
+                        groups,
+                        loaded_files,
+                    );
+                    if groups.len() != num_groups {
+                        // The options that follow the `Include` belong
+                        // to the current block, so they need a group of
+                        // their own after those that were included.
+                        // Global options must likewise not be applied
+                        // ahead of the included groups.
+                        let group = scope.get_or_insert_with(|| MatchGroup {
+                            criteria: vec![Criteria::All],
+                            context: Context::FirstPass,
+                            options: ConfigMap::new(),
+                        });
+                        groups.push(group.clone());
+                    }
                     continue;
                 }
 
-                if k == "match" {
-                    let mut criteria = vec![];
-                    let mut context = Context::FirstPass;
-
-                    let mut tokens = v.split_ascii_whitespace();
-
-                    while let Some(cname) = tokens.next() {
-                        match cname.to_lowercase().as_str() {
-                            "all" => {
-                                criteria.push(Criteria::All);
-                            }
-                            "canonical" => {
-                                context = Context::Canonical;
-                            }
-                            "final" => {
-                                context = Context::Final;
-                            }
-                            "exec" => {
-                                criteria.push(Criteria::Exec(
-                                    tokens.next().unwrap_or("false").to_string(),
-                                ));
-                            }
-                            "host" => {
-                                criteria.push(Criteria::Host(parse_pattern_list(
-                                    tokens.next().unwrap_or(""),
-                                )));
-                            }
-                            "originalhost" => {
-                                criteria.push(Criteria::OriginalHost(parse_pattern_list(
-                                    tokens.next().unwrap_or(""),
-                                )));
-                            }
-                            "user" => {
-                                criteria.push(Criteria::User(parse_pattern_list(
-                                    tokens.next().unwrap_or(""),
-                                )));
-                            }
-                            "localuser" => {
-                                criteria.push(Criteria::LocalUser(parse_pattern_list(
-                                    tokens.next().unwrap_or(""),
-                                )));
-                            }
-                            _ => break,
-                        }
-                    }
+                let block = if k == "host" {
+                    Some((
+                        vec![Criteria::Host(parse_whitespace_pattern_list(v))],
+                        Context::FirstPass,
+                    ))
+                } else if k == "match" {
+                    Some(Self::parse_match_criteria(args))
+                } else {
+                    None
+                };
 
-                    groups.push(MatchGroup {
-                        criteria,
-                        options: ConfigMap::new(),
-                        context,
-                    });
+                if let Some((criteria, context)) = block {
+                    // A block in an included file only applies
+                    // where the block that included it matches
+                    let group = match enclosing {
+                        Some(enclosing) => MatchGroup {
+                            criteria: enclosing.criteria.iter().cloned().chain(criteria).collect(),
+                            context: if context == Context::FirstPass {
+                                enclosing.context
+                            } else {
+                                context
+                            },
+                            options: ConfigMap::new(),
+                        },
+                        None => MatchGroup {
+                            criteria,
+                            context,
+                            options: ConfigMap::new(),
+                        },
+                    };
+                    groups.push(group.clone());
+                    scope.replace(group);
                     continue;
                 }
 
@@ -358,26 +531,20 @@ impl ParsedConfigFile {
                         .or_insert_with(|| v.to_string());
                 }
 
-                if let Some(group) = groups.last_mut() {
-                    add_option(&mut group.options, k, v);
-                } else {
-                    add_option(options, k, v);
+                match (&scope, groups.last_mut()) {
+                    (Some(_), Some(group)) => add_option(&mut group.options, k, v),
+                    _ => add_option(options, k, v),
                 }
             }
         }
     }
 
-    /// Apply configuration values that match the specified hostname to target,
-    /// but only if a given key is not already present in target, because the
-    /// semantics are that the first match wins
-    fn apply_matches(
-        &self,
-        hostname: &str,
-        user: &str,
-        local_user: &str,
-        context: Context,
-        target: &mut ConfigMap,
-    ) -> bool {
+    /// Apply configuration values that match to target, but only if a
+    /// given key is not already present in target, because the
+    /// semantics are that the first match wins.
+    /// Returns true if the config has groups that only apply in the
+    /// final pass, which means that a final pass is required.
+    fn apply_matches(&self, config: &Config, state: &MatchState, target: &mut ConfigMap) -> bool {
         let mut needs_reparse = false;
 
         for (k, v) in &self.options {
@@ -387,7 +554,7 @@ impl ParsedConfigFile {
             if group.context != Context::FirstPass {
                 needs_reparse = true;
             }
-            if group.is_match(hostname, user, local_user, context) {
+            if group.is_match(config, state, target) {
                 for (k, v) in &group.options {
                     target.entry(k.to_string()).or_insert_with(|| v.to_string());
                 }
@@ -407,6 +574,8 @@ pub struct Config {
     options: ConfigMap,
     tokens: ConfigMap,
     environment: Option<ConfigMap>,
+    allow_exec: bool,
+    resolvable_hosts: Option<Vec<String>>,
 }
 
 impl Config {
@@ -417,9 +586,24 @@ impl Config {
             options: ConfigMap::new(),
             tokens: ConfigMap::new(),
             environment: None,
+            allow_exec: false,
+            resolvable_hosts: None,
         }
     }
 
+    /// Controls whether the commands of `Match exec` criteria are run.
+    /// This is off by default, in which case those criteria never match.
+    pub fn set_allow_exec(&mut self, allow: bool) {
+        self.allow_exec = allow;
+    }
+
+    /// Assign a fake set of host names that can be resolved, useful for
+    /// testing.  This is used in place of DNS when canonicalizing
+    /// host names.
+    pub fn assign_resolvable_hosts(&mut self, hosts: Vec<String>) {
+        self.resolvable_hosts.replace(hosts);
+    }
+
     /// Assign a fake environment map, useful for testing.
     /// The environment is used to expand certain values
     /// from the config.
@@ -496,43 +680,218 @@ impl Config {
         "unknown-user".to_string()
     }
 
+    /// Applies each of the config files in turn, returning true if
+    /// a final pass is required
+    fn apply_config_files(&self, state: &MatchState, target: &mut ConfigMap) -> bool {
+        let mut needs_reparse = false;
+        for config in &self.config_files {
+            if config.apply_matches(self, state, target) {
+                needs_reparse = true;
+            }
+        }
+        needs_reparse
+    }
+
+    /// Returns true if `name` can be resolved to an address
+    fn can_resolve(&self, name: &str, port: &str) -> bool {
+        if let Some(hosts) = &self.resolvable_hosts {
+            return hosts.iter().any(|host| host == name);
+        }
+        use std::net::ToSocketAddrs;
+        match format!("{}:{}", name, port).to_socket_addrs() {
+            Ok(mut addrs) => addrs.next().is_some(),
+            Err(_) => false,
+        }
+    }
+
+    /// Canonicalizes `host` as specified by the CanonicalizeHostname,
+    /// CanonicalDomains and CanonicalizeMaxDots options, returning the
+    /// canonical name if it is different from `host`
+    fn canonicalize_hostname(&self, host: &str, options: &ConfigMap) -> Option<String> {
+        match options
+            .get("canonicalizehostname")
+            .map(|v| v.to_lowercase())
+            .as_deref()
+        {
+            Some("always") => {}
+            // Hosts that are reached via a proxy are resolved by
+            // the proxy rather than locally
+            Some("yes")
+                if !options.contains_key("proxycommand") && !options.contains_key("proxyjump") => {}
+            _ => return None,
+        }
+
+        let host = host.to_lowercase();
+        if let Some(host) = host.strip_suffix('.') {
+            // Already fully qualified
+            return Some(host.to_string());
+        }
+
+        let max_dots = options
+            .get("canonicalizemaxdots")
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(1);
+        if host.matches('.').count() > max_dots {
+            return None;
+        }
+
+        let port = options.get("port").map(String::as_str).unwrap_or("22");
+        let canonical = options
+            .get("canonicaldomains")
+            .into_iter()
+            .flat_map(|domains| domains.split_whitespace())
+            .map(|domain| format!("{}.{}", host, domain.trim_end_matches('.')))
+            .find(|candidate| self.can_resolve(candidate, port));
+        if canonical.is_none() {
+            log::debug!("unable to canonicalize hostname {}", host);
+        }
+        canonical
+    }
+
+    /// Runs the command of a `Match exec` criteria, returning true if
+    /// it exits successfully.
+    /// Only a safe subset of what ssh allows is supported: the command
+    /// is run directly rather than via the shell, so shell syntax such
+    /// as pipes, redirection and variable expansion is refused, and it
+    /// is killed if it takes too long.
+    fn match_exec(&self, command: &str, state: &MatchState, options: &ConfigMap) -> bool {
+        if !self.allow_exec {
+            log::warn!(
+                "Not running `Match exec {}` because running commands from \
+                 the ssh config is not allowed; see ssh_config_allow_exec",
+                command
+            );
+            return false;
+        }
+        if command.contains(|c: char| "|&;<>()$`\n".contains(c)) {
+            log::warn!(
+                "Not running `Match exec {}` because it uses shell syntax",
+                command
+            );
+            return false;
+        }
+
+        let mut token_map = self.tokens.clone();
+        token_map.insert("%h".to_string(), state.target_host(options));
+        token_map.insert("%n".to_string(), state.original_host.to_string());
+        token_map.insert("%r".to_string(), state.target_user(options));
+        token_map.insert(
+            "%p".to_string(),
+            options
+                .get("port")
+                .cloned()
+                .unwrap_or_else(|| "22".to_string()),
+        );
+        let args: Vec<String> = split_args(command)
+            .into_iter()
+            .map(|mut arg| {
+                self.expand_tokens(
+                    &mut arg,
+                    &["%d", "%h", "%L", "%l", "%n", "%p", "%r", "%u"],
+                    &token_map,
+                );
+                arg
+            })
+            .collect();
+        let (program, args) = match args.split_first() {
+            Some(split) => split,
+            None => return false,
+        };
+
+        const EXEC_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+        let mut child = match std::process::Command::new(program)
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) => {
+                log::warn!("`Match exec {}` failed: {:#}", command, err);
+                return false;
+            }
+        };
+        let deadline = std::time::Instant::now() + EXEC_TIMEOUT;
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) => return status.success(),
+                Ok(None) if std::time::Instant::now() < deadline => {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+                Ok(None) => {
+                    log::warn!(
+                        "`Match exec {}` took longer than {:?}; treating it as not matching",
+                        command,
+                        EXEC_TIMEOUT
+                    );
+                    child.kill().ok();
+                    child.wait().ok();
+                    return false;
+                }
+                Err(err) => {
+                    log::warn!("`Match exec {}` failed: {:#}", command, err);
+                    return false;
+                }
+            }
+        }
+    }
+
     /// Resolve the configuration for a given host.
     /// The returned map will expand environment and tokens for options
     /// where that is specified.
-    /// Note that in some configurations, the config should be parsed once
-    /// to resolve the main configuration, and then based on some options
-    /// (such as CanonicalHostname), the tokens should be updated and
-    /// the config parsed a second time in order for value expansion
-    /// to have the same results as `ssh`.
+    /// As with `ssh`, if the hostname is canonicalized, or the config
+    /// has `Match canonical` or `Match final` blocks, then the config
+    /// is applied a second time, using the final name of the host.
     pub fn for_host<H: AsRef<str>>(&self, host: H) -> ConfigMap {
         let host = host.as_ref();
         let local_user = self.resolve_local_user();
-        let target_user = &local_user;
 
         let mut result = self.options.clone();
-        let mut needs_reparse = false;
-
-        for config in &self.config_files {
-            if config.apply_matches(
+        let needs_reparse = self.apply_config_files(
+            &MatchState {
                 host,
-                target_user,
-                &local_user,
-                Context::FirstPass,
-                &mut result,
-            ) {
-                needs_reparse = true;
-            }
-        }
+                original_host: host,
+                local_user: &local_user,
+                context: Context::FirstPass,
+            },
+            &mut result,
+        );
+
+        let mut token_map = self.tokens.clone();
+        token_map.insert("%h".to_string(), host.to_string());
 
-        if needs_reparse {
-            log::debug!(
-                "ssh configuration uses options that require two-phase \
-                parsing, which isn't supported"
+        // The name that is connected to is the HostName, if any,
+        // which may then be canonicalized
+        let target_host = match result.get("hostname") {
+            Some(hostname) => {
+                let mut hostname = hostname.to_string();
+                self.expand_tokens(&mut hostname, &["%h"], &token_map);
+                hostname
+            }
+            None => host.to_string(),
+        };
+        let canonical = self.canonicalize_hostname(&target_host, &result);
+        if canonical.is_some() || needs_reparse {
+            let final_host = canonical.unwrap_or(target_host);
+            log::trace!("re-applying ssh config for {} as {}", host, final_host);
+            result.insert("hostname".to_string(), final_host.clone());
+            self.apply_config_files(
+                &MatchState {
+                    host: &final_host,
+                    original_host: host,
+                    local_user: &local_user,
+                    context: Context::Final,
+                },
+                &mut result,
             );
         }
 
-        let mut token_map = self.tokens.clone();
-        token_map.insert("%h".to_string(), host.to_string());
+        let target_user = result
+            .get("user")
+            .cloned()
+            .unwrap_or_else(|| local_user.clone());
+
         result
             .entry("hostname".to_string())
             .and_modify(|curr| {
@@ -728,7 +1087,10 @@ impl Config {
         for config in &self.config_files {
             for group in &config.groups {
                 for c in &group.criteria {
-                    if let Criteria::Host(patterns) = c {
+                    if let Criteria::Host(patterns)
+                    | Criteria::MatchHost(patterns)
+                    | Criteria::OriginalHost(patterns) = c
+                    {
                         for pattern in patterns {
                             if pattern.is_literal && !pattern.negated {
                                 if !hosts.contains(&pattern.original) {
@@ -824,6 +1186,8 @@ Config {
     options: {},
     tokens: {},
     environment: None,
+    allow_exec: false,
+    resolvable_hosts: None,
 }
 "#
         );
@@ -887,6 +1251,8 @@ Config {
             "USER": "me",
         },
     ),
+    allow_exec: false,
+    resolvable_hosts: None,
 }
 "#
         );
@@ -1109,7 +1475,7 @@ Config {
             groups: [
                 MatchGroup {
                     criteria: [
-                        Host(
+                        MatchHost(
                             [
                                 Pattern {
                                     negated: false,
@@ -1134,7 +1500,7 @@ Config {
                 },
                 MatchGroup {
                     criteria: [
-                        Host(
+                        MatchHost(
                             [
                                 Pattern {
                                     negated: true,
@@ -1169,7 +1535,7 @@ Config {
                 },
                 MatchGroup {
                     criteria: [
-                        Host(
+                        MatchHost(
                             [
                                 Pattern {
                                     negated: true,
@@ -1232,6 +1598,8 @@ Config {
             "USER": "me",
         },
     ),
+    allow_exec: false,
+    resolvable_hosts: None,
 }
 "#
         );
@@ -1450,6 +1818,8 @@ Config {
             "USER": "me",
         },
     ),
+    allow_exec: false,
+    resolvable_hosts: None,
 }
 "#
         );
@@ -1517,4 +1887,271 @@ Config {
 "#
         );
     }
+
+    const DEFAULT_IDENTITIES: &str =
+        "/home/me/.ssh/id_dsa /home/me/.ssh/id_ecdsa /home/me/.ssh/id_ed25519 /home/me/.ssh/id_rsa";
+
+    fn fixture_dir(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("ssh_config")
+            .join(name)
+    }
+
+    fn fixture_config(name: &str) -> Config {
+        let mut config = Config::new();
+        let mut fake_env = ConfigMap::new();
+        fake_env.insert("HOME".to_string(), "/home/me".to_string());
+        fake_env.insert("USER".to_string(), "me".to_string());
+        config.assign_environment(fake_env);
+        config.add_config_file(fixture_dir(name).join("config"));
+        config
+    }
+
+    /// Resolves each host in `matrix` and compares the values of
+    /// `keys` with those that are expected for it
+    fn check_matrix(config: &Config, keys: &[&str], matrix: &[(&str, &[(&str, &str)])]) {
+        for (host, expected) in matrix {
+            let resolved = config.for_host(host);
+            let actual: BTreeMap<&str, &str> = keys
+                .iter()
+                .filter_map(|&k| resolved.get(k).map(|v| (k, v.as_str())))
+                .collect();
+            let expected: BTreeMap<&str, &str> = expected.iter().cloned().collect();
+            assert_eq!(actual, expected, "resolving {}", host);
+        }
+    }
+
+    #[test]
+    fn include_fixture() {
+        let config = fixture_config("include");
+
+        let dir = fixture_dir("include");
+        let loaded: Vec<String> = config
+            .loaded_config_files()
+            .iter()
+            .map(|p| {
+                p.strip_prefix(&dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
+        assert_eq!(
+            loaded,
+            vec![
+                "config",
+                "conf.d/10-web.conf",
+                "conf.d/20-db.conf",
+                "conf.d/db/extra",
+                "internal.conf",
+            ]
+        );
+
+        check_matrix(
+            &config,
+            &[
+                "hostname",
+                "user",
+                "port",
+                "proxyjump",
+                "forwardagent",
+                "compression",
+                "identityfile",
+                "serveraliveinterval",
+            ],
+            &[
+                (
+                    "web",
+                    &[
+                        ("hostname", "web.example.com"),
+                        ("user", "admin"),
+                        ("port", "22"),
+                        ("identityfile", "/home/me/.ssh/admin_key"),
+                        ("serveraliveinterval", "30"),
+                    ],
+                ),
+                (
+                    // The included options only apply to db, and
+                    // `Match host` sees the HostName rather than db
+                    "db",
+                    &[
+                        ("hostname", "db.example.com"),
+                        ("user", "dbuser"),
+                        ("port", "22"),
+                        ("forwardagent", "yes"),
+                        ("identityfile", DEFAULT_IDENTITIES),
+                        ("serveraliveinterval", "30"),
+                    ],
+                ),
+                (
+                    // Port follows the Include, but is still part
+                    // of the `Host *.internal` block
+                    "foo.internal",
+                    &[
+                        ("hostname", "foo.internal"),
+                        ("user", "fallback"),
+                        ("port", "2222"),
+                        ("proxyjump", "bastion"),
+                        ("identityfile", DEFAULT_IDENTITIES),
+                        ("serveraliveinterval", "30"),
+                    ],
+                ),
+                (
+                    "special.internal",
+                    &[
+                        ("hostname", "special.internal"),
+                        ("user", "special"),
+                        ("port", "2222"),
+                        ("proxyjump", "bastion"),
+                        ("identityfile", DEFAULT_IDENTITIES),
+                        ("serveraliveinterval", "30"),
+                    ],
+                ),
+                (
+                    "short",
+                    &[
+                        ("hostname", "short"),
+                        ("user", "fallback"),
+                        ("port", "22"),
+                        ("compression", "yes"),
+                        ("identityfile", DEFAULT_IDENTITIES),
+                        ("serveraliveinterval", "30"),
+                    ],
+                ),
+                (
+                    "other",
+                    &[
+                        ("hostname", "other"),
+                        ("user", "fallback"),
+                        ("port", "22"),
+                        ("identityfile", DEFAULT_IDENTITIES),
+                        ("serveraliveinterval", "30"),
+                    ],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn canonical_fixture() {
+        let mut config = fixture_config("canonical");
+        config.assign_resolvable_hosts(vec![
+            "srv.example.net".to_string(),
+            "build.example.com".to_string(),
+        ]);
+
+        check_matrix(
+            &config,
+            &["hostname", "user", "port", "proxyjump"],
+            &[
+                (
+                    // `Host` matches the canonical name in the final pass
+                    "srv",
+                    &[
+                        ("hostname", "srv.example.net"),
+                        ("user", "netuser"),
+                        ("port", "22"),
+                    ],
+                ),
+                (
+                    // Options from the first pass take precedence
+                    // over those from the final pass
+                    "build",
+                    &[
+                        ("hostname", "build.example.com"),
+                        ("user", "builder"),
+                        ("port", "2022"),
+                        ("proxyjump", "gateway"),
+                    ],
+                ),
+                (
+                    // Has more dots than CanonicalizeMaxDots allows
+                    "db.local",
+                    &[("hostname", "db.local"), ("user", "me"), ("port", "22")],
+                ),
+                (
+                    // Already fully qualified
+                    "web.",
+                    &[("hostname", "web"), ("user", "me"), ("port", "22")],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn recursive_include_fixture() {
+        let config = fixture_config("recursive");
+        check_matrix(&config, &["user"], &[("looped", &[("user", "looped")])]);
+    }
+
+    #[test]
+    fn match_negated_criteria() {
+        let mut config = Config::new();
+        let mut fake_env = ConfigMap::new();
+        fake_env.insert("HOME".to_string(), "/home/me".to_string());
+        fake_env.insert("USER".to_string(), "me".to_string());
+        config.assign_environment(fake_env);
+        config.add_config_string(
+            r#"
+        Match !host "*.corp" localuser me
+            User outside
+        Match all
+            User inside
+            "#,
+        );
+
+        check_matrix(
+            &config,
+            &["user"],
+            &[
+                ("laptop", &[("user", "outside")]),
+                ("server.corp", &[("user", "inside")]),
+            ],
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn match_exec() {
+        let mut config = Config::new();
+        let mut fake_env = ConfigMap::new();
+        fake_env.insert("HOME".to_string(), "/home/me".to_string());
+        fake_env.insert("USER".to_string(), "me".to_string());
+        config.assign_environment(fake_env);
+        config.add_config_string(
+            r#"
+        Match exec "true | true"
+            User piped
+        Match exec true host a*
+            User exec-true
+        Match exec false
+            User exec-false
+        Match exec "test %n = allowed"
+            Port 2200
+            "#,
+        );
+
+        // Nothing is run unless it is allowed
+        check_matrix(
+            &config,
+            &["user", "port"],
+            &[
+                ("alpha", &[("user", "me"), ("port", "22")]),
+                ("allowed", &[("user", "me"), ("port", "22")]),
+            ],
+        );
+
+        config.set_allow_exec(true);
+        check_matrix(
+            &config,
+            &["user", "port"],
+            &[
+                ("alpha", &[("user", "exec-true"), ("port", "22")]),
+                ("beta", &[("user", "me"), ("port", "22")]),
+                ("allowed", &[("user", "exec-true"), ("port", "2200")]),
+            ],
+        );
+    }
 }
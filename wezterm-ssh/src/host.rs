@@ -1,6 +1,7 @@
 use crate::session::SessionEvent;
 use anyhow::Context;
 use smol::channel::{bounded, Sender};
+use std::path::PathBuf;
 
 #[derive(Debug, thiserror::Error)]
 #[error("host key mismatch for ssh server {remote_address}. Got fingerprint {key} instead of the expected value from your known hosts file {file:?}.")]
@@ -10,22 +11,192 @@ pub struct HostVerificationFailed {
     pub file: Option<std::path::PathBuf>,
 }
 
+/// How the user chose to respond to a HostVerificationEvent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostVerificationAnswer {
+    /// Abandon the connection
+    Reject,
+    /// Continue connecting, but don't record the host key
+    AcceptOnce,
+    /// Continue connecting and add the host key to the known hosts file
+    AcceptAndSave,
+}
+
 #[derive(Debug)]
 pub struct HostVerificationEvent {
     pub message: String,
-    pub(crate) reply: Sender<bool>,
+    /// The host (and port, if not the default) being connected to
+    pub remote_address: String,
+    /// The host key algorithm, if known
+    pub key_type: Option<String>,
+    /// The fingerprint of the host key, eg: `SHA256:...`
+    pub fingerprint: String,
+    /// An OpenSSH style "randomart" rendering of the fingerprint
+    pub randomart: Option<String>,
+    /// The known hosts file that will be updated if the key is saved
+    pub known_hosts_file: Option<PathBuf>,
+    pub(crate) reply: Sender<HostVerificationAnswer>,
 }
 
 impl HostVerificationEvent {
     pub async fn answer(self, trust_host: bool) -> anyhow::Result<()> {
-        Ok(self.reply.send(trust_host).await?)
+        self.answer_with(if trust_host {
+            HostVerificationAnswer::AcceptAndSave
+        } else {
+            HostVerificationAnswer::Reject
+        })
+        .await
     }
     pub fn try_answer(self, trust_host: bool) -> anyhow::Result<()> {
-        Ok(self.reply.try_send(trust_host)?)
+        self.try_answer_with(if trust_host {
+            HostVerificationAnswer::AcceptAndSave
+        } else {
+            HostVerificationAnswer::Reject
+        })
+    }
+    pub async fn answer_with(self, answer: HostVerificationAnswer) -> anyhow::Result<()> {
+        Ok(self.reply.send(answer).await?)
+    }
+    pub fn try_answer_with(self, answer: HostVerificationAnswer) -> anyhow::Result<()> {
+        Ok(self.reply.try_send(answer)?)
+    }
+}
+
+/// Details of a host key that is not yet present in the known hosts file
+struct UnknownHostKey<'a> {
+    remote_address: &'a str,
+    key_type: Option<String>,
+    fingerprint: String,
+    randomart: Option<String>,
+    known_hosts_file: Option<PathBuf>,
+}
+
+/// Produces the OpenSSH "drunken bishop" visualization of a
+/// host key digest, as shown by `ssh -o VisualHostKey=yes`
+pub fn fingerprint_randomart(title: &str, hash_name: &str, digest: &[u8]) -> String {
+    const WIDTH: usize = 17;
+    const HEIGHT: usize = 9;
+    const SYMBOLS: &[u8] = b" .o+=*BOX@%&#/^SE";
+    let len = SYMBOLS.len() - 1;
+
+    let mut field = [[0usize; HEIGHT]; WIDTH];
+    let mut x = WIDTH / 2;
+    let mut y = HEIGHT / 2;
+
+    for byte in digest {
+        let mut input = *byte;
+        for _ in 0..4 {
+            x = if input & 0x1 != 0 {
+                (x + 1).min(WIDTH - 1)
+            } else {
+                x.saturating_sub(1)
+            };
+            y = if input & 0x2 != 0 {
+                (y + 1).min(HEIGHT - 1)
+            } else {
+                y.saturating_sub(1)
+            };
+            if field[x][y] < len - 2 {
+                field[x][y] += 1;
+            }
+            input >>= 2;
+        }
+    }
+
+    field[WIDTH / 2][HEIGHT / 2] = len - 1;
+    field[x][y] = len;
+
+    fn border(label: &str) -> String {
+        let label: String = label.chars().take(WIDTH).collect();
+        let label_len = label.chars().count();
+        let left = (WIDTH - label_len) / 2;
+        format!(
+            "+{}{}{}+",
+            "-".repeat(left),
+            label,
+            "-".repeat(WIDTH - left - label_len)
+        )
+    }
+
+    let mut lines = vec![border(&format!("[{title}]"))];
+    for y in 0..HEIGHT {
+        let mut line = String::from("|");
+        for x in 0..WIDTH {
+            line.push(SYMBOLS[field[x][y].min(len)] as char);
+        }
+        line.push('|');
+        lines.push(line);
     }
+    lines.push(border(&format!("[{hash_name}]")));
+    lines.join("\n")
 }
 
 impl crate::sessioninner::SessionInner {
+    /// Decides whether to trust a host key that is not in the known
+    /// hosts file, consulting StrictHostKeyChecking before asking the user
+    fn confirm_unknown_host(
+        &mut self,
+        key: UnknownHostKey,
+    ) -> anyhow::Result<HostVerificationAnswer> {
+        let strict = self
+            .config
+            .get("stricthostkeychecking")
+            .map(|s| s.to_lowercase());
+        match strict.as_deref() {
+            Some("yes") => anyhow::bail!(
+                "No host key is known for {} and StrictHostKeyChecking is enabled",
+                key.remote_address
+            ),
+            Some("accept-new") | Some("no") | Some("off") => {
+                log::info!(
+                    "Trusting new host key {} for {} because StrictHostKeyChecking={}",
+                    key.fingerprint,
+                    key.remote_address,
+                    strict.as_deref().unwrap_or_default()
+                );
+                return Ok(HostVerificationAnswer::AcceptAndSave);
+            }
+            _ => {}
+        }
+
+        let message = match &key.key_type {
+            Some(key_type) => format!(
+                "SSH host {} is not yet trusted.\n\
+                {} Fingerprint: {}.\n\
+                Trust and continue connecting?",
+                key.remote_address, key_type, key.fingerprint
+            ),
+            None => format!(
+                "SSH host {} is not yet trusted.\n\
+                Fingerprint: {}.\n\
+                Trust and continue connecting?",
+                key.remote_address, key.fingerprint
+            ),
+        };
+
+        let (reply, confirm) = bounded(1);
+        self.tx_event
+            .try_send(SessionEvent::HostVerify(HostVerificationEvent {
+                message,
+                remote_address: key.remote_address.to_string(),
+                key_type: key.key_type,
+                fingerprint: key.fingerprint,
+                randomart: key.randomart,
+                known_hosts_file: key.known_hosts_file,
+                reply,
+            }))
+            .context("sending HostVerify request to user")?;
+
+        let answer = smol::block_on(confirm.recv())
+            .context("waiting for host verification confirmation from user")?;
+
+        if answer == HostVerificationAnswer::Reject {
+            anyhow::bail!("user declined to trust host");
+        }
+
+        Ok(answer)
+    }
+
     #[cfg(feature = "libssh-rs")]
     pub fn host_verification_libssh(
         &mut self,
@@ -33,34 +204,32 @@ impl crate::sessioninner::SessionInner {
         hostname: &str,
         port: u16,
     ) -> anyhow::Result<()> {
-        let key = sess
-            .get_server_public_key()?
-            .get_public_key_hash_hexa(libssh_rs::PublicKeyHashType::Sha256)?;
+        let server_key = sess.get_server_public_key()?;
+        let key = server_key.get_public_key_hash_hexa(libssh_rs::PublicKeyHashType::Sha256)?;
 
         match sess.is_known_server()? {
             libssh_rs::KnownHosts::Ok => Ok(()),
             libssh_rs::KnownHosts::NotFound | libssh_rs::KnownHosts::Unknown => {
-                let (reply, confirm) = bounded(1);
-                self.tx_event
-                    .try_send(SessionEvent::HostVerify(HostVerificationEvent {
-                        message: format!(
-                            "SSH host {}:{} is not yet trusted.\n\
-                                    Fingerprint: {}.\n\
-                                    Trust and continue connecting?",
-                            hostname, port, key
-                        ),
-                        reply,
-                    }))
-                    .context("sending HostVerify request to user")?;
-
-                let trusted = smol::block_on(confirm.recv())
-                    .context("waiting for host verification confirmation from user")?;
-
-                if !trusted {
-                    anyhow::bail!("user declined to trust host");
-                }
+                let digest =
+                    server_key.get_public_key_hash(libssh_rs::PublicKeyHashType::Sha256)?;
+                let remote_address = format!("{hostname}:{port}");
+                let known_hosts_file = self
+                    .config
+                    .get("userknownhostsfile")
+                    .and_then(|kh| kh.split_whitespace().next().map(PathBuf::from));
+
+                let answer = self.confirm_unknown_host(UnknownHostKey {
+                    remote_address: &remote_address,
+                    key_type: None,
+                    fingerprint: format_sha256_fingerprint(&digest),
+                    randomart: Some(fingerprint_randomart("SSH", "SHA256", &digest)),
+                    known_hosts_file,
+                })?;
 
-                Ok(sess.update_known_hosts_file()?)
+                if answer == HostVerificationAnswer::AcceptAndSave {
+                    sess.update_known_hosts_file()?;
+                }
+                Ok(())
             }
             libssh_rs::KnownHosts::Changed => {
                 let mut file = None;
@@ -128,16 +297,9 @@ impl crate::sessioninner::SessionInner {
                 .host_key()
                 .ok_or_else(|| anyhow!("failed to get ssh host key"))?;
 
-            let fingerprint = sess
-                .host_key_hash(ssh2::HashType::Sha256)
-                .map(|fingerprint| {
-                    use base64::Engine;
-                    let engine = base64::engine::general_purpose::GeneralPurpose::new(
-                        &base64::alphabet::STANDARD,
-                        base64::engine::general_purpose::NO_PAD,
-                    );
-                    format!("SHA256:{}", engine.encode(fingerprint))
-                })
+            let sha256 = sess.host_key_hash(ssh2::HashType::Sha256);
+            let fingerprint = sha256
+                .map(format_sha256_fingerprint)
                 .or_else(|| {
                     // Querying for the Sha256 can fail if for example we were linked
                     // against libssh < 1.9, so let's fall back to Sha1 in that case.
@@ -155,24 +317,18 @@ impl crate::sessioninner::SessionInner {
             match known_hosts.check_port(&remote_host_name, port, key) {
                 ssh2::CheckResult::Match => {}
                 ssh2::CheckResult::NotFound => {
-                    let (reply, confirm) = bounded(1);
-                    self.tx_event
-                        .try_send(SessionEvent::HostVerify(HostVerificationEvent {
-                            message: format!(
-                                "SSH host {} is not yet trusted.\n\
-                                {:?} Fingerprint: {}.\n\
-                                Trust and continue connecting?",
-                                remote_address, key_type, fingerprint
-                            ),
-                            reply,
-                        }))
-                        .context("sending HostVerify request to user")?;
-
-                    let trusted = smol::block_on(confirm.recv())
-                        .context("waiting for host verification confirmation from user")?;
-
-                    if !trusted {
-                        anyhow::bail!("user declined to trust host");
+                    let key_type_name = host_key_type_name(key_type);
+                    let answer = self.confirm_unknown_host(UnknownHostKey {
+                        remote_address,
+                        key_type: Some(key_type_name.to_string()),
+                        fingerprint,
+                        randomart: sha256
+                            .map(|digest| fingerprint_randomart(key_type_name, "SHA256", digest)),
+                        known_hosts_file: Some(file.to_path_buf()),
+                    })?;
+
+                    if answer == HostVerificationAnswer::AcceptOnce {
+                        return Ok(());
                     }
 
                     let host_and_port = if port != 22 {
@@ -209,3 +365,56 @@ impl crate::sessioninner::SessionInner {
         Ok(())
     }
 }
+
+/// Formats a SHA256 host key digest in the same way as OpenSSH
+fn format_sha256_fingerprint(digest: &[u8]) -> String {
+    use base64::Engine;
+    let engine = base64::engine::general_purpose::GeneralPurpose::new(
+        &base64::alphabet::STANDARD,
+        base64::engine::general_purpose::NO_PAD,
+    );
+    format!("SHA256:{}", engine.encode(digest))
+}
+
+/// Returns the OpenSSH name for the host key algorithm
+#[cfg(feature = "ssh2")]
+fn host_key_type_name(key_type: ssh2::HostKeyType) -> &'static str {
+    match key_type {
+        ssh2::HostKeyType::Rsa => "ssh-rsa",
+        ssh2::HostKeyType::Dss => "ssh-dss",
+        ssh2::HostKeyType::Ecdsa256 => "ecdsa-sha2-nistp256",
+        ssh2::HostKeyType::Ecdsa384 => "ecdsa-sha2-nistp384",
+        ssh2::HostKeyType::Ecdsa521 => "ecdsa-sha2-nistp521",
+        ssh2::HostKeyType::Ed255219 => "ssh-ed25519",
+        ssh2::HostKeyType::Unknown => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn randomart() {
+        let digest: Vec<u8> = (0u8..32).collect();
+        let art = fingerprint_randomart("ssh-ed25519", "SHA256", &digest);
+        let lines: Vec<&str> = art.split('\n').collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "+--[ssh-ed25519]--+");
+        assert_eq!(lines[10], "+----[SHA256]-----+");
+        for line in &lines[1..10] {
+            assert_eq!(line.len(), 19);
+            assert!(line.starts_with('|') && line.ends_with('|'));
+        }
+        assert_eq!(art.matches('S').count(), 1);
+        assert_eq!(art.matches('E').count(), 1);
+    }
+
+    #[test]
+    fn sha256_fingerprint() {
+        assert_eq!(
+            format_sha256_fingerprint(&[0u8; 32]),
+            "SHA256:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use crate::config::{Config, ConfigMap};
+use crate::session::{Session, SessionEvent};
+use anyhow::Context;
+use socket2::Socket;
+
+/// A single hop parsed from a `ProxyJump` specification,
+/// which takes the form `[user@]host[:port]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpHostSpec {
+    pub user: Option<String>,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl JumpHostSpec {
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let spec = spec.trim();
+        let spec = spec.strip_prefix("ssh://").unwrap_or(spec);
+
+        let (user, host_port) = match spec.rsplit_once('@') {
+            Some((user, host_port)) => (Some(user.to_string()), host_port),
+            None => (None, spec),
+        };
+
+        let (host, port) = if let Some(bracketed) = host_port.strip_prefix('[') {
+            // [ipv6]:port
+            let (host, remainder) = bracketed
+                .split_once(']')
+                .with_context(|| format!("missing `]` in jump host `{spec}`"))?;
+            (host, remainder.strip_prefix(':'))
+        } else {
+            match host_port.rsplit_once(':') {
+                // Don't mistake an unbracketed IPv6 address for host:port
+                Some((host, port)) if !host.contains(':') => (host, Some(port)),
+                _ => (host_port, None),
+            }
+        };
+        let port = match port {
+            Some(port) => Some(
+                port.parse::<u16>()
+                    .with_context(|| format!("parsing port in jump host `{spec}`"))?,
+            ),
+            None => None,
+        };
+
+        anyhow::ensure!(!host.is_empty(), "jump host `{spec}` has no hostname");
+
+        Ok(Self {
+            user,
+            host: host.to_string(),
+            port,
+        })
+    }
+
+    /// Parse a comma separated `ProxyJump` list
+    pub fn parse_list(value: &str) -> anyhow::Result<Vec<Self>> {
+        value
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    /// Resolve this hop against the ssh config, producing the
+    /// ConfigMap that should be used to connect to it
+    pub fn to_config_map(&self, config: &Config) -> ConfigMap {
+        let mut map = config.for_host(&self.host);
+        if let Some(user) = &self.user {
+            map.insert("user".to_string(), user.to_string());
+        }
+        if let Some(port) = self.port {
+            map.insert("port".to_string(), port.to_string());
+        }
+        map
+    }
+}
+
+/// Produces a human readable name for a hop for use in prompts
+/// and error messages
+fn describe_hop(config: &ConfigMap) -> String {
+    let host = config.get("hostname").map(|s| s.as_str()).unwrap_or("?");
+    let port = config.get("port").map(|s| s.as_str()).unwrap_or("22");
+    match config.get("user") {
+        Some(user) => format!("{user}@{host}:{port}"),
+        None => format!("{host}:{port}"),
+    }
+}
+
+impl crate::sessioninner::SessionInner {
+    /// Computes the jump host chain; explicitly configured jump hosts
+    /// take precedence over a `ProxyJump` option in the ssh config,
+    /// which in turn is ignored if a `ProxyCommand` is set.
+    fn resolve_jump_hosts(&self) -> anyhow::Result<Vec<ConfigMap>> {
+        let mut jump_hosts = if !self.jump_hosts.is_empty() {
+            self.jump_hosts.clone()
+        } else {
+            match self.config.get("proxycommand").map(|s| s.as_str()) {
+                Some("none") | None => {}
+                Some(_) => return Ok(vec![]),
+            }
+            match self.config.get("proxyjump").map(|s| s.as_str()) {
+                Some("none") | None => return Ok(vec![]),
+                Some(value) => {
+                    let mut ssh_config = Config::new();
+                    ssh_config.add_default_config_files();
+                    JumpHostSpec::parse_list(value)?
+                        .iter()
+                        .map(|hop| hop.to_config_map(&ssh_config))
+                        .collect()
+                }
+            }
+        };
+
+        for hop in &mut jump_hosts {
+            // The chain is explicit; don't let a hop recurse through
+            // its own ProxyJump setting
+            hop.insert("proxyjump".to_string(), "none".to_string());
+            for key in ["wezterm_ssh_backend", "wezterm_ssh_verbose"] {
+                if let Some(value) = self.config.get(key) {
+                    hop.entry(key.to_string())
+                        .or_insert_with(|| value.to_string());
+                }
+            }
+        }
+
+        Ok(jump_hosts)
+    }
+
+    /// If jump hosts are configured, establish a session with the
+    /// last hop (which in turn tunnels through any prior hops) and
+    /// return a socket that is tunnelled through it to hostname:port.
+    pub fn connect_via_jump_host(
+        &mut self,
+        hostname: &str,
+        port: u16,
+    ) -> anyhow::Result<Option<Socket>> {
+        let mut jump_hosts = self.resolve_jump_hosts()?;
+        let hop = match jump_hosts.pop() {
+            Some(hop) => hop,
+            None => return Ok(None),
+        };
+        let hop_name = describe_hop(&hop);
+
+        smol::block_on(self.tx_event.send(SessionEvent::Banner(Some(format!(
+            "Connecting to {hostname}:{port} via jump host {hop_name}"
+        )))))
+        .context("notifying user of banner")?;
+
+        let (session, events) = Session::connect_with_jump_hosts(hop, jump_hosts)
+            .with_context(|| format!("connecting to jump host {hop_name}"))?;
+
+        self.relay_jump_host_events(&hop_name, events)
+            .with_context(|| format!("connecting to jump host {hop_name}"))?;
+
+        let fd = smol::block_on(session.open_direct_tcpip(hostname, port)).with_context(|| {
+            format!("jump host {hop_name} failed to connect to {hostname}:{port}")
+        })?;
+        self.jump_session.replace(session);
+
+        #[cfg(unix)]
+        unsafe {
+            use std::os::unix::io::{FromRawFd, IntoRawFd};
+            Ok(Some(Socket::from_raw_fd(fd.into_raw_fd())))
+        }
+        #[cfg(windows)]
+        unsafe {
+            use std::os::windows::io::{FromRawSocket, IntoRawSocket};
+            Ok(Some(Socket::from_raw_socket(fd.into_raw_socket())))
+        }
+    }
+
+    /// Pass the events from the jump host session through to our
+    /// own consumer, labelling them with the hop so that the user
+    /// can tell which host a prompt refers to.
+    fn relay_jump_host_events(
+        &mut self,
+        hop_name: &str,
+        events: smol::channel::Receiver<SessionEvent>,
+    ) -> anyhow::Result<()> {
+        while let Ok(event) = smol::block_on(events.recv()) {
+            let event = match event {
+                SessionEvent::Authenticated => return Ok(()),
+                SessionEvent::Error(err) => anyhow::bail!("{err}"),
+                SessionEvent::HostVerify(mut verify) => {
+                    verify.message = format!("Jump host {hop_name}: {}", verify.message);
+                    SessionEvent::HostVerify(verify)
+                }
+                SessionEvent::Authenticate(mut auth) => {
+                    if auth.username.is_empty() {
+                        auth.username = hop_name.to_string();
+                    } else {
+                        auth.username = format!("{} (jump host {hop_name})", auth.username);
+                    }
+                    SessionEvent::Authenticate(auth)
+                }
+                SessionEvent::HostVerificationFailed(mut failed) => {
+                    failed.remote_address = format!("{} (jump host)", failed.remote_address);
+                    smol::block_on(
+                        self.tx_event
+                            .send(SessionEvent::HostVerificationFailed(failed)),
+                    )
+                    .ok();
+                    anyhow::bail!("Host key verification failed");
+                }
+                event @ SessionEvent::Banner(_) => event,
+            };
+            smol::block_on(self.tx_event.send(event))
+                .context("relaying jump host event to user")?;
+        }
+        anyhow::bail!("session ended before authentication completed");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_jump_hosts() {
+        assert_eq!(
+            JumpHostSpec::parse_list("bastion, alice@inner:2222,[::1]:22").unwrap(),
+            vec![
+                JumpHostSpec {
+                    user: None,
+                    host: "bastion".to_string(),
+                    port: None,
+                },
+                JumpHostSpec {
+                    user: Some("alice".to_string()),
+                    host: "inner".to_string(),
+                    port: Some(2222),
+                },
+                JumpHostSpec {
+                    user: None,
+                    host: "::1".to_string(),
+                    port: Some(22),
+                },
+            ]
+        );
+        assert!(JumpHostSpec::parse("host:notaport").is_err());
+    }
+}
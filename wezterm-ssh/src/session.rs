@@ -55,6 +55,7 @@ pub(crate) enum SessionRequest {
     NewPty(NewPty, Sender<anyhow::Result<(SshPty, SshChildProcess)>>),
     ResizePty(ResizePty, Option<Sender<anyhow::Result<()>>>),
     Exec(Exec, Sender<anyhow::Result<ExecResult>>),
+    DirectTcpIp(DirectTcpIp, Sender<anyhow::Result<FileDescriptor>>),
     Sftp(SftpRequest),
     SignalChannel(SignalChannel),
     SessionDropped,
@@ -72,6 +73,12 @@ pub(crate) struct Exec {
     pub env: Option<HashMap<String, String>>,
 }
 
+#[derive(Debug)]
+pub(crate) struct DirectTcpIp {
+    pub host: String,
+    pub port: u16,
+}
+
 #[derive(Clone)]
 pub struct Session {
     tx: SessionSender,
@@ -86,6 +93,19 @@ impl Drop for Session {
 
 impl Session {
     pub fn connect(config: ConfigMap) -> anyhow::Result<(Self, Receiver<SessionEvent>)> {
+        Self::connect_with_jump_hosts(config, vec![])
+    }
+
+    /// Connect to the host described by config, tunnelling through
+    /// each of the jump_hosts in turn; the first element is the
+    /// first hop.  Host verification and authentication events for
+    /// the jump hosts are delivered through the returned receiver.
+    /// If jump_hosts is empty, any `ProxyJump` option in config is
+    /// used to compute the chain instead.
+    pub fn connect_with_jump_hosts(
+        config: ConfigMap,
+        jump_hosts: Vec<ConfigMap>,
+    ) -> anyhow::Result<(Self, Receiver<SessionEvent>)> {
         let (tx_event, rx_event) = bounded(8);
         let (tx_req, rx_req) = bounded(8);
         let (mut sender_write, mut sender_read) = socketpair()?;
@@ -99,6 +119,8 @@ impl Session {
 
         let mut inner = SessionInner {
             config,
+            jump_hosts,
+            jump_session: None,
             tx_event,
             rx_req,
             channels: HashMap::new(),
@@ -161,6 +183,24 @@ impl Session {
         Ok(exec)
     }
 
+    /// Ask the remote host to open a TCP connection to host:port
+    /// on our behalf (a `direct-tcpip` channel).  The returned
+    /// descriptor is connected to that stream.
+    pub async fn open_direct_tcpip(&self, host: &str, port: u16) -> anyhow::Result<FileDescriptor> {
+        let (reply, rx) = bounded(1);
+        self.tx
+            .send(SessionRequest::DirectTcpIp(
+                DirectTcpIp {
+                    host: host.to_string(),
+                    port,
+                },
+                reply,
+            ))
+            .await
+            .map_err(|_| DeadSession)?;
+        rx.recv().await?
+    }
+
     /// Creates a new reference to the sftp channel for filesystem operations
     ///
     /// ### Note
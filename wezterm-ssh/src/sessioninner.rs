@@ -3,7 +3,9 @@ use crate::config::ConfigMap;
 use crate::dirwrap::DirWrap;
 use crate::filewrap::FileWrap;
 use crate::pty::*;
-use crate::session::{Exec, ExecResult, SessionEvent, SessionRequest, SignalChannel};
+use crate::session::{
+    DirectTcpIp, Exec, ExecResult, Session, SessionEvent, SessionRequest, SignalChannel,
+};
 use crate::sessionwrap::SessionWrap;
 use crate::sftp::dir::{Dir, DirId, DirRequest};
 use crate::sftp::file::{File, FileId, FileRequest};
@@ -40,6 +42,10 @@ pub(crate) type ChannelId = usize;
 
 pub(crate) struct SessionInner {
     pub config: ConfigMap,
+    pub jump_hosts: Vec<ConfigMap>,
+    /// Keeps the session to the final jump host alive for as
+    /// long as we are tunnelled through it
+    pub jump_session: Option<Session>,
     pub tx_event: Sender<SessionEvent>,
     pub rx_req: Receiver<SessionRequest>,
     pub channels: HashMap<ChannelId, ChannelInfo>,
@@ -333,6 +339,10 @@ impl SessionInner {
         verbose: bool,
         proxy_command: Option<&String>,
     ) -> anyhow::Result<Socket> {
+        if let Some(sock) = self.connect_via_jump_host(hostname, port)? {
+            return Ok(sock);
+        }
+
         match proxy_command.map(|s| s.as_str()) {
             Some("none") | None => {}
             Some(proxy_command) => {
@@ -627,6 +637,9 @@ impl SessionInner {
                     SessionRequest::Exec(exec, reply) => {
                         dispatch(reply, || self.exec(sess, exec), "exec")
                     }
+                    SessionRequest::DirectTcpIp(msg, reply) => {
+                        dispatch(reply, || self.direct_tcpip(sess, msg), "direct_tcpip")
+                    }
                     SessionRequest::SignalChannel(info) => {
                         if let Err(err) = self.signal_channel(&info) {
                             log::error!("{:?} -> error: {:#}", info, err);
@@ -969,6 +982,51 @@ impl SessionInner {
         Ok(result)
     }
 
+    pub fn direct_tcpip(
+        &mut self,
+        sess: &mut SessionWrap,
+        msg: DirectTcpIp,
+    ) -> anyhow::Result<FileDescriptor> {
+        let channel = sess
+            .open_direct_tcpip(&msg.host, msg.port)
+            .with_context(|| {
+                format!("opening direct-tcpip channel to {}:{}", msg.host, msg.port)
+            })?;
+
+        let (mut ours, theirs) = socketpair()?;
+        ours.set_non_blocking(true)?;
+        let read_from_peer = ours;
+        let write_to_peer = read_from_peer.try_clone()?;
+
+        let channel_id = self.next_channel_id;
+        self.next_channel_id += 1;
+
+        let info = ChannelInfo {
+            channel_id,
+            channel,
+            exit: None,
+            exited: false,
+            descriptors: [
+                DescriptorState {
+                    fd: Some(read_from_peer),
+                    buf: VecDeque::with_capacity(8192),
+                },
+                DescriptorState {
+                    fd: Some(write_to_peer),
+                    buf: VecDeque::with_capacity(8192),
+                },
+                DescriptorState {
+                    fd: None,
+                    buf: VecDeque::with_capacity(8192),
+                },
+            ],
+        };
+
+        self.channels.insert(channel_id, info);
+
+        Ok(theirs)
+    }
+
     /// Open a handle to a file.
     pub fn open_with_mode(
         &mut self,
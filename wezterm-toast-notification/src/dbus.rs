@@ -99,6 +99,18 @@ async fn show_notif_impl(notif: ToastNotification) -> Result<(), Box<dyn std::er
         return Ok(());
     }
 
+    // The actions are a flat list of pairs of identifier and label
+    let mut actions: Vec<&str> = vec![];
+    if caps.iter().any(|cap| cap == "actions") {
+        if notif.url.is_some() {
+            actions.extend_from_slice(&["show", "Show"]);
+        }
+        for action in notif.buttons() {
+            actions.push(&action.id);
+            actions.push(&action.label);
+        }
+    }
+
     let mut hints = HashMap::new();
     hints.insert("urgency", Value::U8(2 /* Critical */));
     let notification = proxy
@@ -108,11 +120,7 @@ async fn show_notif_impl(notif: ToastNotification) -> Result<(), Box<dyn std::er
             "org.wezfurlong.wezterm",
             &notif.title,
             &notif.message,
-            if notif.url.is_some() {
-                &["show", "Show"]
-            } else {
-                &[]
-            },
+            &actions,
             &hints,
             notif.timeout.map(|d| d.as_millis() as _).unwrap_or(0),
         )
@@ -126,11 +134,12 @@ async fn show_notif_impl(notif: ToastNotification) -> Result<(), Box<dyn std::er
             while let Some(signal) = invoked_stream.next().await {
                 let args = signal.args()?;
                 if args.nid == notification {
-                    if let Some(url) = notif.url.as_ref() {
-                        wezterm_open_url::open_url(url);
-                        abort_closed.abort();
-                        break;
+                    match (args.action_key.as_str(), notif.url.as_ref()) {
+                        ("show", Some(url)) => wezterm_open_url::open_url(url),
+                        (id, _) => notif.invoke_action(id),
                     }
+                    abort_closed.abort();
+                    break;
                 }
             }
             Ok::<(), zbus::Error>(())
@@ -2,18 +2,61 @@ mod dbus;
 mod macos;
 mod windows;
 
+use std::sync::Arc;
+
+/// A button that is shown on the notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToastAction {
+    /// Passed to the ActionHandler when the button is clicked
+    pub id: String,
+    pub label: String,
+}
+
+/// Called with the id of the action that the user clicked.
+/// This may be called on any thread.
+#[derive(Clone)]
+pub struct ActionHandler(pub Arc<dyn Fn(&str) + Send + Sync>);
+
+impl std::fmt::Debug for ActionHandler {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt.write_str("ActionHandler")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ToastNotification {
     pub title: String,
     pub message: String,
     pub url: Option<String>,
     pub timeout: Option<std::time::Duration>,
+    /// Buttons to show, on systems whose notifications support them.
+    /// They are only shown if `on_action` is set.
+    pub actions: Vec<ToastAction>,
+    pub on_action: Option<ActionHandler>,
 }
 
 impl ToastNotification {
     pub fn show(self) {
         show(self)
     }
+
+    /// Returns the actions that should be shown as buttons
+    fn buttons(&self) -> &[ToastAction] {
+        match self.on_action {
+            Some(_) => &self.actions,
+            None => &[],
+        }
+    }
+
+    /// Notifies the handler that the action `id` was clicked,
+    /// provided that it is one of the buttons
+    fn invoke_action(&self, id: &str) {
+        if let Some(handler) = &self.on_action {
+            if self.actions.iter().any(|action| action.id == id) {
+                (handler.0)(id);
+            }
+        }
+    }
 }
 
 #[cfg(windows)]
@@ -44,6 +87,8 @@ pub fn persistent_toast_notification_with_click_to_open_url(title: &str, message
         message: message.to_string(),
         url: Some(url.to_string()),
         timeout: None,
+        actions: vec![],
+        on_action: None,
     });
 }
 
@@ -53,5 +98,7 @@ pub fn persistent_toast_notification(title: &str, message: &str) {
         message: message.to_string(),
         url: None,
         timeout: None,
+        actions: vec![],
+        on_action: None,
     });
 }
@@ -9,9 +9,35 @@ use objc::declare::ClassDecl;
 use objc::rc::StrongPtr;
 use objc::runtime::{Class, Object, Protocol, Sel};
 use objc::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 const DELEGATE_CLS_NAME: &str = "WezTermNotifDelegate";
 
+/// NSUserNotificationActivationType values
+const ACTIVATION_ACTION_BUTTON_CLICKED: i64 = 2;
+const ACTIVATION_ADDITIONAL_ACTION_CLICKED: i64 = 4;
+
+/// The notifications that have buttons, keyed by the `actions`
+/// entry of their userInfo, so that the delegate can route clicks
+/// on their buttons back to their handlers
+static PENDING_ACTIONS: Mutex<Option<HashMap<String, ToastNotification>>> = Mutex::new(None);
+static NEXT_ACTIONS_KEY: AtomicUsize = AtomicUsize::new(0);
+
+fn take_pending_actions(key: &str) -> Option<ToastNotification> {
+    PENDING_ACTIONS.lock().unwrap().as_mut()?.remove(key)
+}
+
+/// Converts a cocoa string to a rust string
+unsafe fn nsstring_to_string(s: id) -> Option<String> {
+    if s.is_null() {
+        return None;
+    }
+    let bytes = std::slice::from_raw_parts(s.UTF8String() as *const u8, s.len());
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
 struct NotifDelegate {}
 
 impl NotifDelegate {
@@ -50,6 +76,12 @@ impl NotifDelegate {
 
     extern "C" fn did_dismiss_alert(_: &mut Object, _sel: Sel, center: id, notif: id) {
         unsafe {
+            let info: *mut Object = msg_send![notif, userInfo];
+            if !info.is_null() {
+                if let Some(key) = nsstring_to_string(info.valueForKey_(*nsstring("actions"))) {
+                    take_pending_actions(&key);
+                }
+            }
             let () = msg_send![center, removeDeliveredNotification: notif];
         }
     }
@@ -60,12 +92,39 @@ impl NotifDelegate {
         unsafe {
             let info: *mut Object = msg_send![notif, userInfo];
 
+            if info.is_null() {
+                let () = msg_send![center, removeDeliveredNotification: notif];
+                return;
+            }
+
             // If the notification had an associated URL, open it!
-            let url = info.valueForKey_(*nsstring("url"));
-            if !url.is_null() {
-                let url = std::slice::from_raw_parts(url.UTF8String() as *const u8, url.len());
-                let url = String::from_utf8_lossy(url);
-                wezterm_open_url::open_url(&*url);
+            if let Some(url) = nsstring_to_string(info.valueForKey_(*nsstring("url"))) {
+                wezterm_open_url::open_url(&url);
+            }
+
+            // Route clicks on its buttons to their handler
+            let pending = nsstring_to_string(info.valueForKey_(*nsstring("actions")))
+                .and_then(|key| take_pending_actions(&key));
+            if let Some(toast) = pending {
+                let activation_type: i64 = msg_send![notif, activationType];
+                let action_id = match activation_type {
+                    ACTIVATION_ACTION_BUTTON_CLICKED => {
+                        toast.buttons().first().map(|action| action.id.clone())
+                    }
+                    ACTIVATION_ADDITIONAL_ACTION_CLICKED => {
+                        let action: id = msg_send![notif, additionalActivationAction];
+                        if action.is_null() {
+                            None
+                        } else {
+                            let identifier: id = msg_send![action, identifier];
+                            nsstring_to_string(identifier)
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(action_id) = action_id {
+                    toast.invoke_action(&action_id);
+                }
             }
             let () = msg_send![center, removeDeliveredNotification: notif];
         }
@@ -115,8 +174,38 @@ pub fn show_notif(toast: ToastNotification) -> Result<(), Box<dyn std::error::Er
         let () = msg_send![*notif, setInformativeText: nsstring(&toast.message)];
 
         let mut info = CFMutableDictionary::new();
-        if let Some(url) = toast.url {
-            info.set(CFString::from_static_string("url"), CFString::new(&url));
+        if let Some(url) = &toast.url {
+            info.set(CFString::from_static_string("url"), CFString::new(url));
+        }
+
+        // The first action is the action button, and the others
+        // are offered in the menu that is attached to it
+        if let Some((first, others)) = toast.buttons().split_first() {
+            let () = msg_send![*notif, setHasActionButton: YES];
+            let () = msg_send![*notif, setActionButtonTitle: *nsstring(&first.label)];
+            if !others.is_empty() {
+                let additional: id = msg_send![class!(NSMutableArray), array];
+                for action in others {
+                    let action: id = msg_send![
+                        class!(NSUserNotificationAction),
+                        actionWithIdentifier: *nsstring(&action.id)
+                        title: *nsstring(&action.label)
+                    ];
+                    let () = msg_send![additional, addObject: action];
+                }
+                let () = msg_send![*notif, setAdditionalActions: additional];
+            }
+
+            let key = NEXT_ACTIONS_KEY.fetch_add(1, Ordering::Relaxed).to_string();
+            info.set(CFString::from_static_string("actions"), CFString::new(&key));
+            PENDING_ACTIONS
+                .lock()
+                .unwrap()
+                .get_or_insert_with(HashMap::new)
+                .insert(key, toast.clone());
+        }
+
+        if info.len() > 0 {
             let () = msg_send![*notif, setUserInfo: info];
         }
 
@@ -1,7 +1,7 @@
 #![cfg(windows)]
 
 use crate::ToastNotification as TN;
-use xml::escape::escape_str_pcdata;
+use xml::escape::{escape_str_attribute, escape_str_pcdata};
 
 use windows::core::{Error as WinError, IInspectable, Interface, HSTRING};
 use windows::Data::Xml::Dom::XmlDocument;
@@ -21,14 +21,21 @@ fn unwrap_arg<T>(a: &Option<T>) -> Result<&T, WinError> {
 fn show_notif_impl(toast: TN) -> Result<(), Box<dyn std::error::Error>> {
     let xml = XmlDocument::new()?;
 
-    let url_actions = if toast.url.is_some() {
-        r#"
-        <actions>
-           <action content="Show" arguments="show" />
-        </actions>
-        "#
+    let mut actions = String::new();
+    if toast.url.is_some() {
+        actions.push_str(r#"<action content="Show" arguments="show" />"#);
+    }
+    for action in toast.buttons() {
+        actions.push_str(&format!(
+            r#"<action content="{}" arguments="action:{}" />"#,
+            escape_str_attribute(&action.label),
+            escape_str_attribute(&action.id)
+        ));
+    }
+    let actions = if actions.is_empty() {
+        actions
     } else {
-        ""
+        format!("<actions>{}</actions>", actions)
     };
 
     xml.LoadXml(HSTRING::from(format!(
@@ -43,7 +50,7 @@ fn show_notif_impl(toast: TN) -> Result<(), Box<dyn std::error::Error>> {
     </toast>"#,
         escape_str_pcdata(&toast.title),
         escape_str_pcdata(&toast.message),
-        url_actions
+        actions
     )))?;
 
     let notif = ToastNotification::CreateToastNotification(xml)?;
@@ -59,6 +66,8 @@ fn show_notif_impl(toast: TN) -> Result<(), Box<dyn std::error::Error>> {
                 if let Some(url) = toast.url.as_ref() {
                     wezterm_open_url::open_url(url);
                 }
+            } else if let Some(id) = args.to_string_lossy().strip_prefix("action:") {
+                toast.invoke_action(id);
             }
 
             Ok(())
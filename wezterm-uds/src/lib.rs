@@ -1,6 +1,6 @@
 use std::io::{Read, Write};
 #[cfg(unix)]
-use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, RawFd};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
 #[cfg(unix)]
 use std::os::unix::net::UnixStream as StreamImpl;
 #[cfg(windows)]
@@ -103,6 +103,106 @@ impl UnixStream {
     }
 }
 
+#[cfg(unix)]
+impl UnixStream {
+    /// Sends `data` along with copies of the file descriptors in `fds`,
+    /// which are passed as SCM_RIGHTS ancillary data.
+    pub fn send_with_fds(&self, data: &[u8], fds: &[RawFd]) -> std::io::Result<usize> {
+        let mut iov = libc::iovec {
+            iov_base: data.as_ptr() as *mut _,
+            iov_len: data.len(),
+        };
+        let fd_bytes = std::mem::size_of_val(fds) as u32;
+        let space = unsafe { libc::CMSG_SPACE(fd_bytes) } as usize;
+        // Use u64 storage so that the buffer is suitably aligned for cmsghdr
+        let mut control = vec![0u64; (space + 7) / 8];
+
+        let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        if !fds.is_empty() {
+            msg.msg_control = control.as_mut_ptr() as *mut _;
+            msg.msg_controllen = space as _;
+            unsafe {
+                let cmsg = libc::CMSG_FIRSTHDR(&msg);
+                (*cmsg).cmsg_level = libc::SOL_SOCKET;
+                (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+                (*cmsg).cmsg_len = libc::CMSG_LEN(fd_bytes) as _;
+                std::ptr::copy_nonoverlapping(
+                    fds.as_ptr(),
+                    libc::CMSG_DATA(cmsg) as *mut RawFd,
+                    fds.len(),
+                );
+            }
+        }
+
+        let res = unsafe { libc::sendmsg(self.as_raw_fd(), &msg, 0) };
+        if res < 0 {
+            Err(std::io::Error::last_os_error())
+        } else {
+            Ok(res as usize)
+        }
+    }
+
+    /// Receives data into `buf` along with up to `max_fds` file
+    /// descriptors that were sent via `send_with_fds`.
+    pub fn recv_with_fds(
+        &self,
+        buf: &mut [u8],
+        max_fds: usize,
+    ) -> std::io::Result<(usize, Vec<OwnedFd>)> {
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut _,
+            iov_len: buf.len(),
+        };
+        let fd_bytes = (max_fds * std::mem::size_of::<RawFd>()) as u32;
+        let space = unsafe { libc::CMSG_SPACE(fd_bytes) } as usize;
+        let mut control = vec![0u64; (space + 7) / 8];
+
+        let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut _;
+        msg.msg_controllen = space as _;
+
+        #[cfg(any(target_os = "linux", target_os = "freebsd"))]
+        let flags = libc::MSG_CMSG_CLOEXEC;
+        #[cfg(not(any(target_os = "linux", target_os = "freebsd")))]
+        let flags = 0;
+
+        let res = unsafe { libc::recvmsg(self.as_raw_fd(), &mut msg, flags) };
+        if res < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let mut fds = vec![];
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                    let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                    let len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                    for idx in 0..len / std::mem::size_of::<RawFd>() {
+                        fds.push(OwnedFd::from_raw_fd(std::ptr::read_unaligned(
+                            data.add(idx),
+                        )));
+                    }
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+
+        if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "more file descriptors were sent than were expected",
+            ));
+        }
+
+        Ok((res as usize, fds))
+    }
+}
+
 impl std::ops::Deref for UnixStream {
     type Target = StreamImpl;
     fn deref(&self) -> &StreamImpl {
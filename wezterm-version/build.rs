@@ -3,11 +3,16 @@ fn main() {
 
     // If a file named `.tag` is present, we'll take its contents for the
     // version number that we report in wezterm -h.
+    // Nightly builds have versions of the same form as release tags,
+    // so we separately record whether this build is a tagged release.
     let mut ci_tag = String::new();
+    let mut is_release = false;
     if let Ok(tag) = std::fs::read("../.tag") {
         if let Ok(s) = String::from_utf8(tag) {
             ci_tag = s.trim().to_string();
             println!("cargo:rerun-if-changed=../.tag");
+            // The nightly source archive also marks itself as such
+            is_release = std::fs::metadata("../.nightly").is_err();
         }
     } else {
         // Otherwise we'll derive it from the git information
@@ -43,6 +48,14 @@ fn main() {
                 let info = String::from_utf8_lossy(&output.stdout);
                 ci_tag = info.trim().to_string();
             }
+
+            if let Ok(output) = std::process::Command::new("git")
+                .args(&["tag", "--points-at", "HEAD"])
+                .output()
+            {
+                let tags = String::from_utf8_lossy(&output.stdout);
+                is_release = !ci_tag.is_empty() && tags.lines().any(|tag| tag.trim() == ci_tag);
+            }
         }
     }
 
@@ -50,4 +63,8 @@ fn main() {
 
     println!("cargo:rustc-env=WEZTERM_TARGET_TRIPLE={}", target);
     println!("cargo:rustc-env=WEZTERM_CI_TAG={}", ci_tag);
+    println!(
+        "cargo:rustc-env=WEZTERM_CI_CHANNEL={}",
+        if is_release { "release" } else { "nightly" }
+    );
 }
@@ -3,6 +3,13 @@ pub fn wezterm_version() -> &'static str {
     env!("WEZTERM_CI_TAG")
 }
 
+/// Returns true if this is a build of a tagged release,
+/// rather than a nightly or local build
+pub fn wezterm_is_release() -> bool {
+    // See build.rs
+    env!("WEZTERM_CI_CHANNEL") == "release"
+}
+
 pub fn wezterm_target_triple() -> &'static str {
     // See build.rs
     env!("WEZTERM_TARGET_TRIPLE")
@@ -0,0 +1,49 @@
+use clap::Parser;
+use wezterm_client::client::Client;
+
+/// The exit status used when the domain is already in the
+/// requested state, so that scripts can tell that apart from
+/// a failure to connect
+pub const ALREADY_IN_STATE_EXIT_CODE: i32 = 3;
+
+#[derive(Debug, Parser, Clone)]
+pub struct AttachDomain {
+    /// The name of the domain to attach, for example `unix`
+    name: String,
+}
+
+impl AttachDomain {
+    pub async fn run(self, client: Client) -> anyhow::Result<()> {
+        let response = client
+            .attach_domain(codec::AttachDomain {
+                name: self.name.clone(),
+            })
+            .await?;
+        if !response.changed {
+            eprintln!("domain {} is already attached", self.name);
+            std::process::exit(ALREADY_IN_STATE_EXIT_CODE);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct DetachDomain {
+    /// The name of the domain to detach, for example `unix`
+    name: String,
+}
+
+impl DetachDomain {
+    pub async fn run(self, client: Client) -> anyhow::Result<()> {
+        let response = client
+            .detach_domain(codec::DetachDomain {
+                name: self.name.clone(),
+            })
+            .await?;
+        if !response.changed {
+            eprintln!("domain {} is already detached", self.name);
+            std::process::exit(ALREADY_IN_STATE_EXIT_CODE);
+        }
+        Ok(())
+    }
+}
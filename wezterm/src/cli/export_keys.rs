@@ -0,0 +1,31 @@
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use config::{ConfigHandle, KeyBindingsFile};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser, Clone)]
+pub struct ExportKeysCommand {
+    /// Write the bindings to this file rather than to stdout
+    #[arg(long, short = 'o', value_parser, value_hint=ValueHint::FilePath)]
+    output: Option<PathBuf>,
+}
+
+impl ExportKeysCommand {
+    pub fn run(&self, config: &ConfigHandle) -> anyhow::Result<()> {
+        let file = KeyBindingsFile {
+            keys: config.keys.clone(),
+            key_tables: config
+                .key_tables
+                .iter()
+                .map(|(name, keys)| (name.clone(), keys.clone()))
+                .collect(),
+        };
+        let text = file.to_toml_string()?;
+        match &self.output {
+            Some(path) => std::fs::write(path, text)
+                .with_context(|| format!("writing key bindings to {}", path.display()))?,
+            None => print!("{text}"),
+        }
+        Ok(())
+    }
+}
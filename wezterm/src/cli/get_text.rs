@@ -1,6 +1,11 @@
 use clap::Parser;
 use mux::pane::PaneId;
-use termwiz_funcs::lines_to_escapes;
+use mux::renderable::StableCursorPosition;
+use std::io::Write;
+use std::ops::Range;
+use termwiz::escape::csi::{Cursor, CSI};
+use termwiz::surface::Line;
+use termwiz_funcs::EscapeEncoder;
 use wezterm_client::client::Client;
 use wezterm_term::{ScrollbackOrVisibleRowIndex, StableRowIndex};
 
@@ -28,8 +33,23 @@ pub struct GetText {
     #[arg(long, allow_hyphen_values = true)]
     end_line: Option<ScrollbackOrVisibleRowIndex>,
 
-    /// Include escape sequences that color and style the text.
-    /// If omitted, unattributed text will be returned.
+    /// The first column of the region to return.
+    /// The default is 0, the leftmost column.
+    /// A double-width character is included when its first
+    /// column lies within the region.
+    #[arg(long, default_value_t = 0)]
+    start_col: usize,
+
+    /// The last column of the region to return, inclusive.
+    /// The default is the rightmost column.
+    #[arg(long)]
+    end_col: Option<usize>,
+
+    /// Include escape sequences that color and style the text,
+    /// create hyperlinks and position the cursor, so that the output
+    /// can be replayed to reproduce the region.
+    /// If omitted, unattributed text with trailing whitespace
+    /// removed will be returned.
     #[arg(long)]
     escapes: bool,
 }
@@ -75,19 +95,159 @@ impl GetText {
             })
             .await?;
 
-        let lines = lines
-            .lines
-            .extract_data()
-            .0
+        let mut out = std::io::stdout().lock();
+        self.write_lines(
+            &mut out,
+            lines.lines.extract_data().0,
+            &info.cursor_position,
+        )?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn columns(&self) -> Range<usize> {
+        let end_col = self.end_col.map(|col| col + 1).unwrap_or(usize::MAX);
+        self.start_col..end_col
+    }
+
+    /// Writes the region of `lines`, which are the rows returned
+    /// for the requested range, in the requested format
+    fn write_lines<W: Write>(
+        &self,
+        out: &mut W,
+        lines: Vec<(StableRowIndex, Line)>,
+        cursor: &StableCursorPosition,
+    ) -> anyhow::Result<()> {
+        let first_row = lines.first().map(|(idx, _)| *idx).unwrap_or(0);
+        let lines: Vec<_> = lines
             .into_iter()
-            .map(|(_idx, line)| line)
+            .map(|(_idx, line)| line.columns_as_line(self.columns()))
             .collect();
 
-        if self.escapes {
-            println!("{}", lines_to_escapes(lines)?);
-        } else {
-            lines.iter().for_each(|line| println!("{}", line.as_str()));
+        if !self.escapes {
+            for line in &lines {
+                writeln!(out, "{}", line.as_str().trim_end())?;
+            }
+            return Ok(());
+        }
+
+        let mut encoder = EscapeEncoder::new();
+        for (idx, line) in lines.iter().enumerate() {
+            // Don't terminate the last line, as that could
+            // scroll the display when the output is replayed
+            let eol = if idx + 1 == lines.len() { "" } else { "\r\n" };
+            encoder.line(out, line, eol)?;
         }
+        encoder.finish(out)?;
+
+        let rows = first_row..first_row + lines.len() as StableRowIndex;
+        write!(out, "{}", self.cursor_replay(rows, cursor))?;
         Ok(())
     }
+
+    /// Returns the escapes that move the cursor from the end of the
+    /// last of `rows` to where it is in the pane, relative to the
+    /// region, or nothing if the cursor is outside of the region
+    fn cursor_replay(&self, rows: Range<StableRowIndex>, cursor: &StableCursorPosition) -> String {
+        if !rows.contains(&cursor.y) || !self.columns().contains(&cursor.x) {
+            return String::new();
+        }
+        let mut replay = String::new();
+        let up = (rows.end - 1 - cursor.y) as u32;
+        if up > 0 {
+            replay.push_str(&CSI::Cursor(Cursor::Up(up)).to_string());
+        }
+        replay.push('\r');
+        let right = (cursor.x - self.start_col) as u32;
+        if right > 0 {
+            replay.push_str(&CSI::Cursor(Cursor::Right(right)).to_string());
+        }
+        replay
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_text(args: &[&str]) -> GetText {
+        GetText::try_parse_from(std::iter::once("get-text").chain(args.iter().copied())).unwrap()
+    }
+
+    fn cursor(x: usize, y: StableRowIndex) -> StableCursorPosition {
+        StableCursorPosition {
+            x,
+            y,
+            ..Default::default()
+        }
+    }
+
+    fn lines(text: &[&str]) -> Vec<(StableRowIndex, Line)> {
+        text.iter()
+            .enumerate()
+            .map(|(idx, text)| (10 + idx as StableRowIndex, Line::from(*text)))
+            .collect()
+    }
+
+    fn output(args: &[&str], text: &[&str], cursor: StableCursorPosition) -> String {
+        let mut out = vec![];
+        get_text(args)
+            .write_lines(&mut out, lines(text), &cursor)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn plain_text_is_trimmed() {
+        assert_eq!(
+            output(&[], &["hello   ", "", "  world"], cursor(0, 0)),
+            "hello\n\n  world\n"
+        );
+    }
+
+    #[test]
+    fn plain_text_columns() {
+        let text = &["0123456789", "a\u{30b0}bcdef"];
+        assert_eq!(
+            output(&["--start-col", "2", "--end-col", "4"], text, cursor(0, 0)),
+            "234\nbc\n"
+        );
+        // The double-width character starts in column 1, so it is
+        // excluded when the region starts on its second column, and
+        // included when the region ends on its first
+        assert_eq!(
+            output(&["--end-col", "1"], text, cursor(0, 0)),
+            "01\na\u{30b0}\n"
+        );
+    }
+
+    #[test]
+    fn escapes_leave_the_last_line_unterminated() {
+        let text = output(&["--escapes"], &["one", "two"], cursor(0, 0));
+        assert!(text.contains("one\r\n"), "{text:?}");
+        assert!(!text.contains("two\r\n"), "{text:?}");
+        assert!(!text.ends_with('\n'), "{text:?}");
+    }
+
+    #[test]
+    fn cursor_is_replayed_relative_to_the_region() {
+        let args = get_text(&["--start-col", "2", "--end-col", "9"]);
+        let rows = 10..13;
+        // On the last row, at the left edge of the region
+        assert_eq!(args.cursor_replay(rows.clone(), &cursor(2, 12)), "\r");
+        // Two rows up and three columns in
+        assert_eq!(
+            args.cursor_replay(rows.clone(), &cursor(5, 10)),
+            "\x1b[2A\r\x1b[3C"
+        );
+        assert_eq!(
+            args.cursor_replay(rows.clone(), &cursor(3, 11)),
+            "\x1b[A\r\x1b[C"
+        );
+        // Outside of the region
+        assert_eq!(args.cursor_replay(rows.clone(), &cursor(1, 11)), "");
+        assert_eq!(args.cursor_replay(rows.clone(), &cursor(10, 11)), "");
+        assert_eq!(args.cursor_replay(rows.clone(), &cursor(3, 13)), "");
+        assert_eq!(args.cursor_replay(10..10, &cursor(3, 10)), "");
+    }
 }
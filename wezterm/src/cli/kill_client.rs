@@ -0,0 +1,21 @@
+use clap::Parser;
+use wezterm_client::client::Client;
+
+#[derive(Debug, Parser, Clone)]
+pub struct KillClient {
+    /// The id of the client to disconnect, as shown in the
+    /// ID column of `wezterm cli list-clients`
+    #[arg(long)]
+    client_id: usize,
+}
+
+impl KillClient {
+    pub async fn run(&self, client: Client) -> anyhow::Result<()> {
+        client
+            .kill_client(codec::KillClient {
+                session_id: self.client_id,
+            })
+            .await?;
+        Ok(())
+    }
+}
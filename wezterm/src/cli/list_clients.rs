@@ -29,6 +29,10 @@ impl ListClientsCommand {
             }
             CliOutputFormatKind::Table => {
                 let cols = vec![
+                    Column {
+                        name: "ID".to_string(),
+                        alignment: Alignment::Right,
+                    },
                     Column {
                         name: "USER".to_string(),
                         alignment: Alignment::Left,
@@ -57,6 +61,14 @@ impl ListClientsCommand {
                         name: "FOCUS".to_string(),
                         alignment: Alignment::Right,
                     },
+                    Column {
+                        name: "PEER".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "PROTO".to_string(),
+                        alignment: Alignment::Right,
+                    },
                     Column {
                         name: "SSH_AUTH_SOCK".to_string(),
                         alignment: Alignment::Left,
@@ -90,6 +102,7 @@ impl ListClientsCommand {
                     let connected = now - info.connected_at;
                     let idle = now - info.last_input;
                     data.push(vec![
+                        info.session_id.to_string(),
                         info.client_id.username.to_string(),
                         info.client_id.hostname.to_string(),
                         info.client_id.pid.to_string(),
@@ -99,6 +112,10 @@ impl ListClientsCommand {
                         info.focused_pane_id
                             .map(|id| id.to_string())
                             .unwrap_or_else(String::new),
+                        info.peer.as_deref().unwrap_or("").to_string(),
+                        info.protocol_version
+                            .map(|v| v.to_string())
+                            .unwrap_or_else(String::new),
                         info.client_id
                             .ssh_auth_sock
                             .as_deref()
@@ -120,6 +137,7 @@ impl ListClientsCommand {
 // herein as they are directly reflected in the output.
 #[derive(serde::Serialize)]
 struct CliListClientsResultItem {
+    client_id: usize,
     username: String,
     hostname: String,
     pid: u32,
@@ -128,6 +146,8 @@ struct CliListClientsResultItem {
     workspace: String,
     focused_pane_id: Option<mux::pane::PaneId>,
     ssh_auth_sock: Option<String>,
+    peer: Option<String>,
+    protocol_version: Option<usize>,
 }
 
 impl From<mux::client::ClientInfo> for CliListClientsResultItem {
@@ -135,6 +155,9 @@ impl From<mux::client::ClientInfo> for CliListClientsResultItem {
         let now: DateTime<Utc> = Utc::now();
 
         let mux::client::ClientInfo {
+            session_id,
+            peer,
+            protocol_version,
             connected_at,
             last_input,
             active_workspace,
@@ -155,6 +178,7 @@ impl From<mux::client::ClientInfo> for CliListClientsResultItem {
         let idle_time = now - last_input;
 
         CliListClientsResultItem {
+            client_id: session_id,
             username: username.to_string(),
             hostname: hostname.to_string(),
             pid: *pid,
@@ -165,6 +189,8 @@ impl From<mux::client::ClientInfo> for CliListClientsResultItem {
             workspace: active_workspace.as_deref().unwrap_or("").to_string(),
             focused_pane_id,
             ssh_auth_sock: ssh_auth_sock.as_ref().map(|s| s.to_string()),
+            peer,
+            protocol_version,
         }
     }
 }
@@ -0,0 +1,126 @@
+use crate::cli::CliOutputFormatKind;
+use clap::Parser;
+use serde::Serializer as _;
+use tabout::{tabulate_output, Alignment, Column};
+use wezterm_client::client::Client;
+
+#[derive(Debug, Parser, Clone, Copy)]
+pub struct ListClosedCommand {
+    /// Controls the output format.
+    /// "table" and "json" are possible formats.
+    #[arg(long = "format", default_value = "table")]
+    format: CliOutputFormatKind,
+}
+
+impl ListClosedCommand {
+    pub async fn run(&self, client: Client) -> anyhow::Result<()> {
+        let out = std::io::stdout();
+        let closed = client.list_closed(codec::ListClosed {}).await?;
+        match self.format {
+            CliOutputFormatKind::Json => {
+                let items = closed.items.into_iter().map(CliListClosedResultItem::from);
+                let mut writer = serde_json::Serializer::pretty(out.lock());
+                writer.collect_seq(items)?;
+            }
+            CliOutputFormatKind::Table => {
+                let cols = vec![
+                    Column {
+                        name: "ID".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "WINID".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "KIND".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "PANES".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "LINES".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "CLOSED".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "EXPIRES".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "TITLE".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "CWD".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                ];
+                let data: Vec<Vec<String>> = closed
+                    .items
+                    .into_iter()
+                    .map(CliListClosedResultItem::from)
+                    .map(|item| {
+                        vec![
+                            item.id.to_string(),
+                            item.window_id.to_string(),
+                            item.kind.to_string(),
+                            item.num_panes.to_string(),
+                            item.num_lines.to_string(),
+                            format!("{}s ago", item.closed_secs_ago),
+                            format!("in {}s", item.expires_in_secs),
+                            item.title,
+                            item.cwd,
+                        ]
+                    })
+                    .collect();
+
+                tabulate_output(&cols, &data, &mut out.lock())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// This will be serialized to JSON via the 'ListClosed' command.
+// As such it is intended to be a stable output format,
+// Thus we need to be careful about the stability of the fields and types
+// herein as they are directly reflected in the output.
+#[derive(serde::Serialize)]
+struct CliListClosedResultItem {
+    id: usize,
+    window_id: mux::window::WindowId,
+    tab_id: mux::tab::TabId,
+    /// Either "pane" or "tab"
+    kind: &'static str,
+    title: String,
+    cwd: String,
+    num_panes: usize,
+    num_lines: usize,
+    size_bytes: usize,
+    closed_secs_ago: u64,
+    expires_in_secs: u64,
+}
+
+impl From<mux::closed::ClosedItemInfo> for CliListClosedResultItem {
+    fn from(info: mux::closed::ClosedItemInfo) -> CliListClosedResultItem {
+        CliListClosedResultItem {
+            id: info.id,
+            window_id: info.window_id,
+            tab_id: info.tab_id,
+            kind: if info.is_tab { "tab" } else { "pane" },
+            title: info.title,
+            cwd: info.working_dir.unwrap_or_default(),
+            num_panes: info.num_panes,
+            num_lines: info.num_lines,
+            size_bytes: info.bytes,
+            closed_secs_ago: info.closed_secs_ago,
+            expires_in_secs: info.expires_in_secs,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::cli::CliOutputFormatKind;
+use clap::Parser;
+use serde::Serializer as _;
+use tabout::{tabulate_output, Alignment, Column};
+use wezterm_client::client::Client;
+
+#[derive(Debug, Parser, Clone, Copy)]
+pub struct ListDomainsCommand {
+    /// Controls the output format.
+    /// "table" and "json" are possible formats.
+    #[arg(long = "format", default_value = "table")]
+    format: CliOutputFormatKind,
+}
+
+impl ListDomainsCommand {
+    pub async fn run(&self, client: Client) -> anyhow::Result<()> {
+        let out = std::io::stdout();
+        let domains = client.list_domains(codec::ListDomains {}).await?;
+        match self.format {
+            CliOutputFormatKind::Json => {
+                let domains = domains
+                    .domains
+                    .into_iter()
+                    .map(CliListDomainsResultItem::from);
+                let mut writer = serde_json::Serializer::pretty(out.lock());
+                writer.collect_seq(domains)?;
+            }
+            CliOutputFormatKind::Table => {
+                let cols = vec![
+                    Column {
+                        name: "ID".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                    Column {
+                        name: "NAME".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "TYPE".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "STATE".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "PANES".to_string(),
+                        alignment: Alignment::Right,
+                    },
+                ];
+                let data: Vec<Vec<String>> = domains
+                    .domains
+                    .into_iter()
+                    .map(CliListDomainsResultItem::from)
+                    .map(|info| {
+                        vec![
+                            info.domain_id.to_string(),
+                            info.name,
+                            info.domain_type,
+                            info.state.to_string(),
+                            info.num_panes.to_string(),
+                        ]
+                    })
+                    .collect();
+
+                tabulate_output(&cols, &data, &mut out.lock())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// This will be serialized to JSON via the 'ListDomains' command.
+// As such it is intended to be a stable output format,
+// Thus we need to be careful about the stability of the fields and types
+// herein as they are directly reflected in the output.
+#[derive(serde::Serialize)]
+struct CliListDomainsResultItem {
+    domain_id: mux::domain::DomainId,
+    name: String,
+    domain_type: String,
+    /// Either "attached" or "detached"
+    state: &'static str,
+    num_panes: usize,
+}
+
+impl From<codec::DomainInfo> for CliListDomainsResultItem {
+    fn from(info: codec::DomainInfo) -> CliListDomainsResultItem {
+        CliListDomainsResultItem {
+            domain_id: info.domain_id,
+            name: info.name,
+            domain_type: info.domain_type,
+            state: if info.attached {
+                "attached"
+            } else {
+                "detached"
+            },
+            num_panes: info.num_panes,
+        }
+    }
+}
@@ -7,17 +7,27 @@ mod activate_pane;
 mod activate_pane_direction;
 mod activate_tab;
 mod adjust_pane_size;
+mod attach_domain;
+mod export_keys;
 mod get_pane_direction;
 mod get_text;
+mod kill_client;
 mod kill_pane;
 mod list;
 mod list_clients;
+mod list_closed;
+mod list_domains;
 mod move_pane_to_new_tab;
+mod move_tab;
 mod proxy;
 mod rename_workspace;
+mod render_pane;
+mod respawn_pane;
 mod send_text;
 mod set_tab_title;
 mod set_window_title;
+mod show_logs;
+mod show_trusted_hosts;
 mod spawn_command;
 mod split_pane;
 mod tls_creds;
@@ -69,10 +79,59 @@ pub struct CliCommand {
     #[arg(long = "class")]
     class: Option<String>,
 
+    /// When running in a pane of a wezterm instance that was itself
+    /// started from a pane of another wezterm instance, address the
+    /// inner (most recently started) instance.  This is the default
+    /// unless $WEZTERM_CLI_PREFER_OUTER is set.
+    #[arg(long = "prefer-inner", overrides_with = "prefer_outer")]
+    prefer_inner: bool,
+
+    /// When running in a pane of a wezterm instance that was itself
+    /// started from a pane of another wezterm instance, address the
+    /// outer instance, and the pane in which the inner instance
+    /// was started.
+    #[arg(long = "prefer-outer", overrides_with = "prefer_inner")]
+    prefer_outer: bool,
+
     #[command(subcommand)]
     sub: CliSubCommand,
 }
 
+impl CliCommand {
+    /// Whether to address the outer instance.  The command line
+    /// flags take precedence over $WEZTERM_CLI_PREFER_OUTER.
+    fn wants_outer_instance(&self) -> bool {
+        if self.prefer_inner {
+            false
+        } else if self.prefer_outer {
+            true
+        } else {
+            std::env::var_os(mux::nesting::CLI_PREFER_OUTER_VAR)
+                .map(|value| !value.is_empty() && value != "0")
+                .unwrap_or(false)
+        }
+    }
+}
+
+/// Arrange for the client to address the wezterm instance that
+/// encloses the one in which we are running
+fn use_outer_instance() -> anyhow::Result<()> {
+    let socket = std::env::var_os(mux::nesting::OUTER_UNIX_SOCKET_VAR).ok_or_else(|| {
+        anyhow!(
+            "--prefer-outer or ${} was specified, but this is not running \
+             in a nested wezterm instance (${} is not set)",
+            mux::nesting::CLI_PREFER_OUTER_VAR,
+            mux::nesting::OUTER_UNIX_SOCKET_VAR
+        )
+    })?;
+    std::env::set_var("WEZTERM_UNIX_SOCKET", socket);
+    match std::env::var_os(mux::nesting::OUTER_PANE_VAR) {
+        Some(pane) => std::env::set_var("WEZTERM_PANE", pane),
+        None => std::env::remove_var("WEZTERM_PANE"),
+    }
+    Ok(())
+}
+
 #[derive(Debug, Parser, Clone)]
 enum CliSubCommand {
     #[command(name = "list", about = "list windows, tabs and panes")]
@@ -81,12 +140,54 @@ enum CliSubCommand {
     #[command(name = "list-clients", about = "list clients")]
     ListClients(list_clients::ListClientsCommand),
 
+    /// Disconnect a client from the mux server
+    #[command(name = "kill-client", rename_all = "kebab")]
+    KillClient(kill_client::KillClient),
+
+    /// List the multiplexer domains, and whether they are attached
+    #[command(name = "list-domains", rename_all = "kebab")]
+    ListDomains(list_domains::ListDomainsCommand),
+
+    /// List the recently closed panes and tabs that can be reopened
+    /// by the ReopenClosedPane and ReopenClosedTab key assignments
+    #[command(name = "list-closed", rename_all = "kebab")]
+    ListClosed(list_closed::ListClosedCommand),
+
+    /// Show the recent log entries of the running wezterm GUI or mux
+    /// server, even if its output isn't visible because it was not
+    /// started from a terminal
+    #[command(name = "show-logs", rename_all = "kebab")]
+    ShowLogs(show_logs::ShowLogsCommand),
+
+    /// Attach a domain, as the AttachDomain key assignment does.
+    ///
+    /// Exits with status 3 if the domain is already attached.
+    #[command(name = "attach-domain", rename_all = "kebab")]
+    AttachDomain(attach_domain::AttachDomain),
+
+    /// Detach a domain, as the DetachDomain key assignment does.
+    ///
+    /// Exits with status 3 if the domain is already detached.
+    #[command(name = "detach-domain", rename_all = "kebab")]
+    DetachDomain(attach_domain::DetachDomain),
+
     #[command(name = "proxy", about = "start rpc proxy pipe")]
     Proxy(proxy::ProxyCommand),
 
     #[command(name = "tlscreds", about = "obtain tls credentials")]
     TlsCreds(tls_creds::TlsCredsCommand),
 
+    /// Export the key bindings from your configuration, excluding
+    /// the default bindings, as a TOML file that can be loaded by
+    /// wezterm.import_key_bindings
+    #[command(name = "export-keys", rename_all = "kebab")]
+    ExportKeys(export_keys::ExportKeysCommand),
+
+    /// List the certificates that were trusted for TLS domains on
+    /// first use, or forget one of them
+    #[command(name = "show-trusted-hosts", rename_all = "kebab")]
+    ShowTrustedHosts(show_trusted_hosts::ShowTrustedHostsCommand),
+
     #[command(
         name = "move-pane-to-new-tab",
         rename_all = "kebab",
@@ -94,6 +195,14 @@ enum CliSubCommand {
     )]
     MovePaneToNewTab(move_pane_to_new_tab::MovePaneToNewTab),
 
+    /// Move a local tab into a multiplexer domain, such as `unix`,
+    /// without restarting the programs running in it.
+    ///
+    /// This must be run against the GUI that owns the tab, and the
+    /// domain must be a mux server on the same machine.
+    #[command(name = "move-tab", rename_all = "kebab")]
+    MoveTab(move_tab::MoveTab),
+
     #[command(
         name = "split-pane",
         rename_all = "kebab",
@@ -121,6 +230,12 @@ Outputs the pane-id for the newly created pane on success"
     #[command(name = "get-text", rename_all = "kebab")]
     GetText(get_text::GetText),
 
+    /// Renders the content of a pane to an image.
+    /// The image is an approximation that is drawn with a built-in
+    /// bitmap font, rather than by the GPU with your configured fonts.
+    #[command(name = "render-pane", rename_all = "kebab")]
+    RenderPane(render_pane::RenderPane),
+
     /// Activate an adjacent pane in the specified direction.
     #[command(name = "activate-pane-direction", rename_all = "kebab")]
     ActivatePaneDirection(activate_pane_direction::ActivatePaneDirection),
@@ -136,6 +251,15 @@ Outputs the pane-id for the newly created pane on success"
     #[command(name = "kill-pane", rename_all = "kebab")]
     KillPane(kill_pane::KillPane),
 
+    #[command(
+        name = "respawn-pane",
+        rename_all = "kebab",
+        trailing_var_arg = true,
+        about = "Kill the process in a pane and spawn it again in place,
+preserving the pane and its scrollback"
+    )]
+    RespawnPane(respawn_pane::RespawnPane),
+
     /// Activate (focus) a pane
     #[command(name = "activate-pane", rename_all = "kebab")]
     ActivatePane(activate_pane::ActivatePane),
@@ -149,11 +273,15 @@ Outputs the pane-id for the newly created pane on success"
     ActivateTab(activate_tab::ActivateTab),
 
     /// Change the title of a tab
-    #[command(name = "set-tab-title", rename_all = "kebab")]
+    #[command(name = "set-tab-title", alias = "rename-tab", rename_all = "kebab")]
     SetTabTitle(set_tab_title::SetTabTitle),
 
     /// Change the title of a window
-    #[command(name = "set-window-title", rename_all = "kebab")]
+    #[command(
+        name = "set-window-title",
+        alias = "rename-window",
+        rename_all = "kebab"
+    )]
     SetWindowTitle(set_window_title::SetWindowTitle),
 
     /// Rename a workspace
@@ -166,6 +294,19 @@ Outputs the pane-id for the newly created pane on success"
 }
 
 async fn run_cli_async(opts: &crate::Opt, cli: CliCommand) -> anyhow::Result<()> {
+    if cli.wants_outer_instance() {
+        use_outer_instance()?;
+    }
+
+    // These only look at local state, so there is no need
+    // to connect to the mux
+    if let CliSubCommand::ShowTrustedHosts(cmd) = &cli.sub {
+        return cmd.run();
+    }
+    if let CliSubCommand::ExportKeys(cmd) = &cli.sub {
+        return cmd.run(&crate::init_config(opts)?);
+    }
+
     let mut ui = mux::connui::ConnectionUI::new_headless();
     let initial = true;
 
@@ -181,17 +322,26 @@ async fn run_cli_async(opts: &crate::Opt, cli: CliCommand) -> anyhow::Result<()>
 
     match cli.sub {
         CliSubCommand::ListClients(cmd) => cmd.run(client).await,
+        CliSubCommand::KillClient(cmd) => cmd.run(client).await,
         CliSubCommand::List(cmd) => cmd.run(client).await,
+        CliSubCommand::ListDomains(cmd) => cmd.run(client).await,
+        CliSubCommand::ListClosed(cmd) => cmd.run(client).await,
+        CliSubCommand::ShowLogs(cmd) => cmd.run(client).await,
+        CliSubCommand::AttachDomain(cmd) => cmd.run(client).await,
+        CliSubCommand::DetachDomain(cmd) => cmd.run(client).await,
         CliSubCommand::MovePaneToNewTab(cmd) => cmd.run(client).await,
+        CliSubCommand::MoveTab(cmd) => cmd.run(client).await,
         CliSubCommand::SplitPane(cmd) => cmd.run(client).await,
         CliSubCommand::SendText(cmd) => cmd.run(client).await,
         CliSubCommand::GetText(cmd) => cmd.run(client).await,
+        CliSubCommand::RenderPane(cmd) => cmd.run(client).await,
         CliSubCommand::SpawnCommand(cmd) => cmd.run(client, &crate::init_config(opts)?).await,
         CliSubCommand::Proxy(cmd) => cmd.run(client, &crate::init_config(opts)?).await,
         CliSubCommand::TlsCreds(cmd) => cmd.run(client).await,
         CliSubCommand::ActivatePaneDirection(cmd) => cmd.run(client).await,
         CliSubCommand::GetPaneDirection(cmd) => cmd.run(client).await,
         CliSubCommand::KillPane(cmd) => cmd.run(client).await,
+        CliSubCommand::RespawnPane(cmd) => cmd.run(client).await,
         CliSubCommand::ActivatePane(cmd) => cmd.run(client).await,
         CliSubCommand::AdjustPaneSize(cmd) => cmd.run(client).await,
         CliSubCommand::ActivateTab(cmd) => cmd.run(client).await,
@@ -199,6 +349,8 @@ async fn run_cli_async(opts: &crate::Opt, cli: CliCommand) -> anyhow::Result<()>
         CliSubCommand::SetWindowTitle(cmd) => cmd.run(client).await,
         CliSubCommand::RenameWorkspace(cmd) => cmd.run(client).await,
         CliSubCommand::ZoomPane(cmd) => cmd.run(client).await,
+        CliSubCommand::ShowTrustedHosts(cmd) => cmd.run(),
+        CliSubCommand::ExportKeys(cmd) => cmd.run(&crate::init_config(opts)?),
     }
 }
 
@@ -1,5 +1,5 @@
 use clap::Parser;
-use codec::{Pdu, SetClientId};
+use codec::{Pdu, SetClientId, CODEC_VERSION};
 use config::ConfigHandle;
 use mux::activity::Activity;
 use mux::client::ClientId;
@@ -33,6 +33,7 @@ impl ProxyCommand {
         let pdu = Pdu::SetClientId(SetClientId {
             client_id: ClientId::new(),
             is_proxy: true,
+            codec_vers: CODEC_VERSION,
         });
         let serial = 1;
         pdu.encode(&mut stream, serial)?;
@@ -0,0 +1,306 @@
+//! A built-in 8x16 bitmap font, so that panes can be rendered without
+//! a GPU or any installed fonts.
+//!
+//! The printable ASCII glyphs were rasterized from JetBrains Mono
+//! Regular, which is bundled in `assets/fonts` under the SIL Open Font
+//! License.  Box drawing characters and block elements are drawn from
+//! their geometry, and any other character is drawn as an outlined box.
+
+pub const GLYPH_WIDTH: usize = 8;
+pub const GLYPH_HEIGHT: usize = 16;
+
+/// The rows of a glyph, top first.  The leftmost pixel of each row is
+/// its most significant bit; a double width glyph uses all 16 bits
+/// and a single width glyph uses only the upper 8.
+pub type GlyphRows = [u16; GLYPH_HEIGHT];
+
+/// Each glyph is 16 rows of 8 pixels, with the top row in the most
+/// significant byte
+const ASCII_GLYPHS: [u128; 95] = [
+    0x00000000_00000000_00000000_00000000, // ' '
+    0x00001818_18181818_18001818_00000000, // '!'
+    0x00002424_24240000_00000000_00000000, // '"'
+    0x00001212_367f2424_7e6c4848_00000000, // '#'
+    0x00083c7e_6a68381c_0e4a6e3c_08000000, // '$'
+    0x000061d2_94f46810_3f2949c6_00000000, // '%'
+    0x0000386c_64203059_cec6ce7b_00000000, // '&'
+    0x00001818_18180000_00000000_00000000, // "'"
+    0x00060810_10303030_30301018_0c000000, // '('
+    0x00601008_080c0c0c_0c0c0818_30000000, // ')'
+    0x00000000_18187e18_3c240000_00000000, // '*'
+    0x00000000_0018187e_18180000_00000000, // '+'
+    0x00000000_00000000_00001818_18100000, // ','
+    0x00000000_0000003c_00000000_00000000, // '-'
+    0x00000000_00000000_00001818_00000000, // '.'
+    0x00060404_0c080818_10302020_60400000, // '/'
+    0x00003c66_42425a5a_4242663c_00000000, // '0'
+    0x00001838_48080808_0808087e_00000000, // '1'
+    0x00003c66_4202060c_1830607e_00000000, // '2'
+    0x00007c04_0c181c06_0646663c_00000000, // '3'
+    0x00000c08_18302066_667e0606_00000000, // '4'
+    0x00007c60_60607c06_0242663c_00000000, // '5'
+    0x00000818_10303c66_4242663c_00000000, // '6'
+    0x00007e62_4606040c_08181810_00000000, // '7'
+    0x00003c66_42663c3c_4242663c_00000000, // '8'
+    0x00003c66_4242663e_0c081810_00000000, // '9'
+    0x00000000_00181800_00001818_00000000, // ':'
+    0x00000000_00181800_00001818_10100000, // ';'
+    0x00000000_020c3060_701c0600_00000000, // '<'
+    0x00000000_003c3c00_007e0000_00000000, // '='
+    0x00000000_40700c06_0e386000_00000000, // '>'
+    0x0000383c_06060c18_10001030_00000000, // '?'
+    0x00003c66_424bdfd3_d3d3ce40_40380800, // '@'
+    0x00001818_183c2424_7e7e4242_00000000, // 'A'
+    0x00007c66_62667c7c_6262667c_00000000, // 'B'
+    0x00003c66_62606060_6062663c_00000000, // 'C'
+    0x0000786c_66626262_6266667c_00000000, // 'D'
+    0x00007e60_60607c7c_6060607e_00000000, // 'E'
+    0x00007e60_60607c7e_60606060_00000000, // 'F'
+    0x00003c66_6240404e_4242663c_00000000, // 'G'
+    0x00004266_66667e7e_66666666_00000000, // 'H'
+    0x00003c18_18181818_1818187e_00000000, // 'I'
+    0x00000006_06060606_0646443c_00000000, // 'J'
+    0x00004266_646c7878_6c646662_00000000, // 'K'
+    0x00002020_20202020_2020203e_00000000, // 'L'
+    0x00006666_6e5a5a5a_42424242_00000000, // 'M'
+    0x00006262_7252524a_4a4e4646_00000000, // 'N'
+    0x00003c66_66424242_4242663c_00000000, // 'O'
+    0x00007c66_6262667c_60606060_00000000, // 'P'
+    0x00003c66_42424242_4242663c_0c060200, // 'Q'
+    0x00007c66_6262667c_6c646662_00000000, // 'R'
+    0x00003c66_6260380c_0242663c_00000000, // 'S'
+    0x00007e18_18181818_18181818_00000000, // 'T'
+    0x00004266_66666666_6666663c_00000000, // 'U'
+    0x00004242_66662424_3c3c1818_00000000, // 'V'
+    0x000099db_db5a5a5a_66666666_00000000, // 'W'
+    0x00004266_243c1818_3c246642_00000000, // 'X'
+    0x00004242_66243c18_18181818_00000000, // 'Y'
+    0x00007e06_040c0810_3020607e_00000000, // 'Z'
+    0x001c1010_10101010_10101010_1c1c0000, // '['
+    0x00602020_30101018_080c0404_06020000, // '\\'
+    0x00380808_08080808_08080808_38380000, // ']'
+    0x00001818_3c246642_00000000_00000000, // '^'
+    0x00000000_00000000_00000000_7e000000, // '_'
+    0x00001008_00000000_00000000_00000000, // '`'
+    0x00000000_183c061e_7e46663e_00000000, // 'a'
+    0x00004060_687c6662_6262667c_00000000, // 'b'
+    0x00000000_183c6240_4042663c_00000000, // 'c'
+    0x00000206_163e6646_4646663e_00000000, // 'd'
+    0x00000000_183c6666_7e40663c_00000000, // 'e'
+    0x00000e18_10307e10_10101010_00000000, // 'f'
+    0x00000000_103e6646_46663e16_063c1000, // 'g'
+    0x00004060_687c6666_66666666_00000000, // 'h'
+    0x00001800_00380808_0808087f_00000000, // 'i'
+    0x00000c00_007c0c0c_0c0c0c0c_0c782000, // 'j'
+    0x00004060_6066646c_786c6662_00000000, // 'k'
+    0x00007010_10101010_1010100e_00000000, // 'l'
+    0x00000000_047e5a5a_5a5a5a5a_00000000, // 'm'
+    0x00000000_087c6666_66666666_00000000, // 'n'
+    0x00000000_183c6642_4242663c_00000000, // 'o'
+    0x00000000_087c6662_6262667c_60600000, // 'p'
+    0x00000000_103e6646_4646663e_06060000, // 'q'
+    0x00000000_087e6262_60606060_00000000, // 'r'
+    0x00000000_187c6070_3c02663c_00000000, // 's'
+    0x00001010_307e1010_1010101e_00000000, // 't'
+    0x00000000_00666666_6666663c_00000000, // 'u'
+    0x00000000_00426624_243c1818_00000000, // 'v'
+    0x00000000_00db5a5a_5a6e6666_00000000, // 'w'
+    0x00000000_00663c18_183c2466_00000000, // 'x'
+    0x00000000_00426624_341c1818_10100000, // 'y'
+    0x00000000_007e0408_1830607e_00000000, // 'z'
+    0x000e0808_08081870_08080808_0c040000, // '{'
+    0x00181818_18181818_18181818_18000000, // '|'
+    0x00701010_1010181e_10101010_30200000, // '}'
+    0x00000000_0000725e_4c000000_00000000, // '~'
+];
+
+/// Returns a glyph whose rows are filled by `fill(x, y)`, where `x`
+/// ranges over `width` pixels
+fn draw(width: usize, fill: impl Fn(usize, usize) -> bool) -> GlyphRows {
+    let mut rows = [0u16; GLYPH_HEIGHT];
+    for (y, row) in rows.iter_mut().enumerate() {
+        for x in 0..width {
+            if fill(x, y) {
+                *row |= 0x8000 >> x;
+            }
+        }
+    }
+    rows
+}
+
+/// Returns which of the up, down, left and right arms of a box drawing
+/// character are present.  Heavy and double lines are drawn as light
+/// lines, and rounded corners as square ones.
+fn box_drawing_arms(c: char) -> Option<(bool, bool, bool, bool)> {
+    Some(match c {
+        '─' | '━' | '═' | '╌' | '╍' | '┄' | '┅' | '┈' | '┉' => {
+            (false, false, true, true)
+        }
+        '│' | '┃' | '║' | '╎' | '╏' | '┆' | '┇' | '┊' | '┋' => {
+            (true, true, false, false)
+        }
+        '┌' | '┍' | '┎' | '┏' | '╒' | '╓' | '╔' | '╭' => (false, true, false, true),
+        '┐' | '┑' | '┒' | '┓' | '╕' | '╖' | '╗' | '╮' => (false, true, true, false),
+        '└' | '┕' | '┖' | '┗' | '╘' | '╙' | '╚' | '╰' => (true, false, false, true),
+        '┘' | '┙' | '┚' | '┛' | '╛' | '╜' | '╝' | '╯' => (true, false, true, false),
+        '├' | '┝' | '┞' | '┟' | '┠' | '┡' | '┢' | '┣' | '╞' | '╟' | '╠' => {
+            (true, true, false, true)
+        }
+        '┤' | '┥' | '┦' | '┧' | '┨' | '┩' | '┪' | '┫' | '╡' | '╢' | '╣' => {
+            (true, true, true, false)
+        }
+        '┬' | '┭' | '┮' | '┯' | '┰' | '┱' | '┲' | '┳' | '╤' | '╥' | '╦' => {
+            (false, true, true, true)
+        }
+        '┴' | '┵' | '┶' | '┷' | '┸' | '┹' | '┺' | '┻' | '╧' | '╨' | '╩' => {
+            (true, false, true, true)
+        }
+        '┼' | '┽' | '┾' | '┿' | '╀' | '╁' | '╂' | '╃' | '╄' | '╅' | '╆' | '╇' | '╈' | '╉' | '╊'
+        | '╋' | '╪' | '╫' | '╬' => (true, true, true, true),
+        '╴' | '╸' => (false, false, true, false),
+        '╵' | '╹' => (true, false, false, false),
+        '╶' | '╺' => (false, false, false, true),
+        '╷' | '╻' => (false, true, false, false),
+        _ => return None,
+    })
+}
+
+fn block_element(c: char) -> Option<GlyphRows> {
+    let w = GLYPH_WIDTH;
+    let h = GLYPH_HEIGHT;
+    Some(match c {
+        '▀' => draw(w, |_, y| y < h / 2),
+        '▄' => draw(w, |_, y| y >= h / 2),
+        '█' => draw(w, |_, _| true),
+        '▌' => draw(w, |x, _| x < w / 2),
+        '▐' => draw(w, |x, _| x >= w / 2),
+        '░' => draw(w, |x, y| x % 2 == 0 && y % 2 == 0),
+        '▒' => draw(w, |x, y| (x + y) % 2 == 0),
+        '▓' => draw(w, |x, y| !(x % 2 == 0 && y % 2 == 0)),
+        '▁'..='▇' => {
+            let eighths = c as usize - '▀' as usize;
+            draw(w, |_, y| y >= h - h * eighths / 8)
+        }
+        '▉'..='▏' => {
+            let eighths = '█' as usize + 8 - c as usize;
+            draw(w, |x, _| x < w * eighths / 8)
+        }
+        '▔' => draw(w, |_, y| y < h / 8),
+        '▕' => draw(w, |x, _| x >= w - w / 8),
+        _ => return None,
+    })
+}
+
+/// Returns the glyph for the text of a cell that is `cell_width`
+/// cells wide
+pub fn glyph(text: &str, cell_width: usize) -> GlyphRows {
+    let width = GLYPH_WIDTH * cell_width.clamp(1, 2);
+    let mut chars = text.chars();
+    let c = match (chars.next(), chars.next()) {
+        (Some(c), None) => c,
+        (None, _) => ' ',
+        // A grapheme made of several chars is drawn as a box
+        (Some(_), Some(_)) => '\u{fffd}',
+    };
+
+    if (' '..='~').contains(&c) {
+        let bits = ASCII_GLYPHS[c as usize - ' ' as usize];
+        let mut rows = [0u16; GLYPH_HEIGHT];
+        for (y, row) in rows.iter_mut().enumerate() {
+            *row = (((bits >> (8 * (GLYPH_HEIGHT - 1 - y))) & 0xff) as u16) << 8;
+        }
+        return rows;
+    }
+    if c.is_whitespace() {
+        return [0; GLYPH_HEIGHT];
+    }
+    if let Some((up, down, left, right)) = box_drawing_arms(c) {
+        let (cx, cy) = (GLYPH_WIDTH / 2 - 1, GLYPH_HEIGHT / 2 - 1);
+        return draw(GLYPH_WIDTH, |x, y| {
+            (y == cy && ((left && x <= cx) || (right && x >= cx)))
+                || (x == cx && ((up && y <= cy) || (down && y >= cy)))
+        });
+    }
+    if let Some(rows) = block_element(c) {
+        return rows;
+    }
+    draw(width, |x, y| {
+        let inside_x = x >= 1 && x < width - 1;
+        let inside_y = y >= 2 && y < GLYPH_HEIGHT - 2;
+        inside_x && inside_y && (x == 1 || x == width - 2 || y == 2 || y == GLYPH_HEIGHT - 3)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Returns the rows of a glyph that have the given pixels set, with
+    /// any other rows blank
+    fn rows(set: &[(std::ops::Range<usize>, u16)]) -> GlyphRows {
+        let mut rows = [0; GLYPH_HEIGHT];
+        for (range, bits) in set {
+            rows[range.clone()].fill(*bits);
+        }
+        rows
+    }
+
+    #[test]
+    fn ascii() {
+        assert_eq!(
+            glyph("H", 1),
+            [
+                0x0000, 0x0000, 0x4200, 0x6600, 0x6600, 0x6600, 0x7e00, 0x7e00, 0x6600, 0x6600,
+                0x6600, 0x6600, 0x0000, 0x0000, 0x0000, 0x0000
+            ]
+        );
+        assert_eq!(glyph(" ", 1), [0; GLYPH_HEIGHT]);
+        assert_eq!(glyph("", 1), [0; GLYPH_HEIGHT]);
+        assert_eq!(glyph("\u{3000}", 2), [0; GLYPH_HEIGHT]);
+    }
+
+    #[test]
+    fn box_drawing() {
+        assert_eq!(glyph("─", 1), rows(&[(7..8, 0xff00)]));
+        assert_eq!(glyph("═", 1), glyph("─", 1));
+        assert_eq!(glyph("│", 1), rows(&[(0..16, 0x1000)]));
+        assert_eq!(glyph("┌", 1), rows(&[(7..8, 0x1f00), (8..16, 0x1000)]));
+        assert_eq!(glyph("╯", 1), rows(&[(0..7, 0x1000), (7..8, 0xf000)]));
+        assert_eq!(
+            glyph("┼", 1),
+            rows(&[(0..7, 0x1000), (7..8, 0xff00), (8..16, 0x1000)])
+        );
+        assert_eq!(glyph("╶", 1), rows(&[(7..8, 0x1f00)]));
+    }
+
+    #[test]
+    fn block_elements() {
+        assert_eq!(glyph("█", 1), rows(&[(0..16, 0xff00)]));
+        assert_eq!(glyph("▀", 1), rows(&[(0..8, 0xff00)]));
+        assert_eq!(glyph("▄", 1), rows(&[(8..16, 0xff00)]));
+        assert_eq!(glyph("▐", 1), rows(&[(0..16, 0x0f00)]));
+        assert_eq!(glyph("▔", 1), rows(&[(0..2, 0xff00)]));
+        assert_eq!(glyph("▕", 1), rows(&[(0..16, 0x0100)]));
+        // Lower eighths, from one to seven
+        assert_eq!(glyph("▁", 1), rows(&[(14..16, 0xff00)]));
+        assert_eq!(glyph("▂", 1), rows(&[(12..16, 0xff00)]));
+        assert_eq!(glyph("▇", 1), rows(&[(2..16, 0xff00)]));
+        // Left eighths, from seven to one
+        assert_eq!(glyph("▉", 1), rows(&[(0..16, 0xfe00)]));
+        assert_eq!(glyph("▊", 1), rows(&[(0..16, 0xfc00)]));
+        assert_eq!(glyph("▏", 1), rows(&[(0..16, 0x8000)]));
+    }
+
+    #[test]
+    fn other_characters() {
+        let single = rows(&[(2..3, 0x7e00), (3..13, 0x4200), (13..14, 0x7e00)]);
+        assert_eq!(glyph("\u{2603}", 1), single);
+        // A grapheme of several chars
+        assert_eq!(glyph("e\u{301}", 1), single);
+        // A double width glyph uses all 16 bits
+        assert_eq!(
+            glyph("\u{4e00}", 2),
+            rows(&[(2..3, 0x7ffe), (3..13, 0x4002), (13..14, 0x7ffe)])
+        );
+    }
+}
@@ -0,0 +1,252 @@
+//! Renders the cells of a pane to an image, without a GPU, so that a
+//! snippet of a pane on a headless mux server can be shared as a picture.
+//! The rendering is intentionally approximate: it uses a built-in bitmap
+//! font, but the colors come from the palette of the pane.
+use anyhow::Context;
+use clap::{Parser, ValueEnum, ValueHint};
+use mux::pane::PaneId;
+use std::io::Write;
+use termwiz::cell::{CellAttributes, Intensity, Underline};
+use wezterm_client::client::Client;
+use wezterm_term::color::{ColorAttribute, ColorPalette};
+use wezterm_term::StableRowIndex;
+
+mod font;
+mod sixel;
+
+use font::{GLYPH_HEIGHT, GLYPH_WIDTH};
+
+/// The most rows that are rendered; the bottom rows of a larger
+/// region are kept
+const MAX_ROWS: usize = 500;
+/// The most columns that are rendered; the leftmost columns of a
+/// wider pane are kept
+const MAX_COLS: usize = 500;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum RenderRegion {
+    /// The lines that are visible on the screen
+    Viewport,
+    /// The scrollback as well as the screen
+    Scrollback,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum RenderFormat {
+    Png,
+    /// Sixel graphics, which can be printed straight to a terminal
+    /// that supports them
+    Sixel,
+}
+
+#[derive(Debug, Parser, Clone)]
+pub struct RenderPane {
+    /// Specify the target pane.
+    /// The default is to use the current pane based on the
+    /// environment variable WEZTERM_PANE.
+    #[arg(long)]
+    pane_id: Option<PaneId>,
+
+    /// Which lines of the pane to render
+    #[arg(long, value_enum, default_value_t = RenderRegion::Viewport)]
+    region: RenderRegion,
+
+    /// The format of the image
+    #[arg(long, value_enum, default_value_t = RenderFormat::Png)]
+    format: RenderFormat,
+
+    /// The file to write the image to, or "-" for stdout
+    #[arg(long, default_value = "-", value_hint=ValueHint::FilePath)]
+    out: String,
+}
+
+type Rgb = [u8; 3];
+
+fn rgb(color: wezterm_term::color::SrgbaTuple) -> Rgb {
+    let (r, g, b, _) = color.to_srgb_u8();
+    [r, g, b]
+}
+
+/// Returns the foreground and background colors of a cell
+fn cell_colors(palette: &ColorPalette, attrs: &CellAttributes) -> (Rgb, Rgb) {
+    let mut fg = attrs.foreground();
+    // Bold text in the first 8 colors is shown in their bright versions
+    if let (Intensity::Bold, ColorAttribute::PaletteIndex(idx @ 0..=7)) = (attrs.intensity(), fg) {
+        fg = ColorAttribute::PaletteIndex(idx + 8);
+    }
+    let fg = rgb(palette.resolve_fg(fg));
+    let bg = rgb(palette.resolve_bg(attrs.background()));
+    let (fg, bg) = if attrs.reverse() { (bg, fg) } else { (fg, bg) };
+    let fg = match attrs.intensity() {
+        Intensity::Half => [0, 1, 2].map(|i| ((fg[i] as u16 + bg[i] as u16) / 2) as u8),
+        _ => fg,
+    };
+    (fg, bg)
+}
+
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize, background: Rgb) -> Self {
+        Self {
+            width,
+            height,
+            pixels: background.repeat(width * height),
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: Rgb) {
+        if x < self.width && y < self.height {
+            let idx = (y * self.width + x) * 3;
+            self.pixels[idx..idx + 3].copy_from_slice(&color);
+        }
+    }
+
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        for y in y..y + height {
+            for x in x..x + width {
+                self.set(x, y, color);
+            }
+        }
+    }
+}
+
+impl RenderPane {
+    pub async fn run(self, client: Client) -> anyhow::Result<()> {
+        let pane_id = client.resolve_pane_id(self.pane_id).await?;
+
+        let info = client
+            .get_dimensions(codec::GetPaneRenderableDimensions { pane_id })
+            .await?;
+        let dims = info.dimensions;
+
+        let end_line = dims.physical_top + dims.viewport_rows as StableRowIndex;
+        let start_line = match self.region {
+            RenderRegion::Viewport => dims.physical_top,
+            RenderRegion::Scrollback => dims.scrollback_top,
+        };
+        let start_line = if (end_line - start_line) as usize > MAX_ROWS {
+            eprintln!(
+                "warning: only rendering the last {} of {} lines",
+                MAX_ROWS,
+                end_line - start_line
+            );
+            end_line - MAX_ROWS as StableRowIndex
+        } else {
+            start_line
+        };
+        let cols = if dims.cols > MAX_COLS {
+            eprintln!(
+                "warning: only rendering the first {} of {} columns",
+                MAX_COLS, dims.cols
+            );
+            MAX_COLS
+        } else {
+            dims.cols
+        };
+        let rows = (end_line - start_line) as usize;
+
+        let lines = client
+            .get_lines(codec::GetLines {
+                pane_id: pane_id.into(),
+                lines: vec![start_line..end_line],
+            })
+            .await?;
+        let lines = lines.lines.extract_data().0;
+
+        eprintln!(
+            "note: the image is an approximation, drawn with a built-in \
+             bitmap font that covers ASCII, box drawing and block elements"
+        );
+
+        let palette = client
+            .get_pane_palette(codec::GetPanePalette { pane_id })
+            .await?
+            .palette;
+        let mut canvas = Canvas::new(
+            cols * GLYPH_WIDTH,
+            rows * GLYPH_HEIGHT,
+            rgb(palette.background),
+        );
+
+        for (line_idx, line) in &lines {
+            let top = (*line_idx - start_line) as usize * GLYPH_HEIGHT;
+            for cell in line.visible_cells() {
+                let col = cell.cell_index();
+                if col >= cols {
+                    break;
+                }
+                let left = col * GLYPH_WIDTH;
+                let width = cell.width().clamp(1, 2).min(cols - col) * GLYPH_WIDTH;
+                let attrs = cell.attrs();
+                let (fg, bg) = cell_colors(&palette, attrs);
+                canvas.fill(left, top, width, GLYPH_HEIGHT, bg);
+                if attrs.invisible() {
+                    continue;
+                }
+
+                let bold = attrs.intensity() == Intensity::Bold;
+                for (y, row) in font::glyph(cell.str(), cell.width()).iter().enumerate() {
+                    for x in 0..width {
+                        if row & (0x8000 >> x) != 0 {
+                            canvas.set(left + x, top + y, fg);
+                            if bold {
+                                // Embolden by smearing to the right
+                                canvas.set(left + x + 1, top + y, fg);
+                            }
+                        }
+                    }
+                }
+
+                let mut decorations = vec![];
+                match attrs.underline() {
+                    Underline::None => {}
+                    Underline::Double => decorations.extend([GLYPH_HEIGHT - 3, GLYPH_HEIGHT - 1]),
+                    _ => decorations.push(GLYPH_HEIGHT - 2),
+                }
+                if attrs.strikethrough() {
+                    decorations.push(GLYPH_HEIGHT / 2);
+                }
+                if attrs.overline() {
+                    decorations.push(0);
+                }
+                for y in decorations {
+                    canvas.fill(left, top + y, width, 1, fg);
+                }
+            }
+        }
+
+        let mut data = vec![];
+        match self.format {
+            RenderFormat::Png => {
+                let image = image::RgbImage::from_raw(
+                    canvas.width as u32,
+                    canvas.height as u32,
+                    canvas.pixels,
+                )
+                .context("building image")?;
+                image.write_to(
+                    &mut std::io::Cursor::new(&mut data),
+                    image::ImageFormat::Png,
+                )?;
+            }
+            RenderFormat::Sixel => {
+                sixel::encode(&mut data, canvas.width, canvas.height, &canvas.pixels)?;
+            }
+        }
+
+        if self.out == "-" {
+            let mut out = std::io::stdout().lock();
+            out.write_all(&data)?;
+            out.flush()?;
+        } else {
+            std::fs::write(&self.out, &data)
+                .with_context(|| format!("writing image to {}", self.out))?;
+        }
+        Ok(())
+    }
+}
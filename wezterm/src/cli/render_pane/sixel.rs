@@ -0,0 +1,184 @@
+//! Encodes an RGB image as sixel graphics, which can be displayed by
+//! printing them to a terminal that supports sixel.
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::Write;
+
+/// The most color registers that the output defines
+const MAX_COLORS: usize = 256;
+
+/// Reduces each channel to one of 6 levels, giving at most 216 colors
+fn quantize(rgb: [u8; 3]) -> [u8; 3] {
+    rgb.map(|c| ((c as u16 * 5 + 127) / 255 * 51) as u8)
+}
+
+/// Sixel color registers are specified in percent
+fn percent(c: u8) -> u32 {
+    (c as u32 * 100 + 127) / 255
+}
+
+fn write_run(out: &mut impl Write, sixel: u8, len: usize) -> std::io::Result<()> {
+    if len > 3 {
+        write!(out, "!{}{}", len, sixel as char)
+    } else {
+        for _ in 0..len {
+            out.write_all(&[sixel])?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes `pixels`, which holds `width` x `height` RGB triples, as a
+/// sixel image
+pub fn encode(
+    out: &mut impl Write,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+) -> std::io::Result<()> {
+    // Rendered cells rarely use many colors, but a pane full of true
+    // color gradients is reduced to a color cube
+    let distinct: HashSet<&[u8]> = pixels.chunks_exact(3).collect();
+    let reduce = distinct.len() > MAX_COLORS;
+
+    let mut palette: Vec<[u8; 3]> = vec![];
+    let mut registers: HashMap<[u8; 3], usize> = HashMap::new();
+    let indexed: Vec<usize> = pixels
+        .chunks_exact(3)
+        .map(|px| {
+            let rgb = [px[0], px[1], px[2]];
+            let rgb = if reduce { quantize(rgb) } else { rgb };
+            *registers.entry(rgb).or_insert_with(|| {
+                palette.push(rgb);
+                palette.len() - 1
+            })
+        })
+        .collect();
+
+    // P2=1 leaves pixels that are not set in a band unchanged, and the
+    // raster attributes set a 1:1 aspect ratio and the image size
+    write!(out, "\x1bP0;1;0q\"1;1;{};{}", width, height)?;
+    for (idx, [r, g, b]) in palette.iter().enumerate() {
+        write!(
+            out,
+            "#{};2;{};{};{}",
+            idx,
+            percent(*r),
+            percent(*g),
+            percent(*b)
+        )?;
+    }
+
+    for band_top in (0..height).step_by(6) {
+        let band_rows = (height - band_top).min(6);
+        let band = &indexed[band_top * width..(band_top + band_rows) * width];
+        let colors: BTreeSet<usize> = band.iter().copied().collect();
+
+        for (n, color) in colors.into_iter().enumerate() {
+            if n > 0 {
+                // Return to the start of the band for the next color
+                out.write_all(b"$")?;
+            }
+            write!(out, "#{}", color)?;
+
+            let mut run: Option<(u8, usize)> = None;
+            for x in 0..width {
+                let mut bits = 0u8;
+                for dy in 0..band_rows {
+                    if band[dy * width + x] == color {
+                        bits |= 1 << dy;
+                    }
+                }
+                let sixel = 0x3f + bits;
+                run = match run {
+                    Some((prior, len)) if prior == sixel => Some((prior, len + 1)),
+                    Some((prior, len)) => {
+                        write_run(out, prior, len)?;
+                        Some((sixel, 1))
+                    }
+                    None => Some((sixel, 1)),
+                };
+            }
+            // A trailing run of empty sixels can be omitted
+            if let Some((sixel, len)) = run {
+                if sixel != 0x3f {
+                    write_run(out, sixel, len)?;
+                }
+            }
+        }
+        out.write_all(b"-")?;
+    }
+    out.write_all(b"\x1b\\")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn encode_str(width: usize, height: usize, pixels: &[[u8; 3]]) -> String {
+        let pixels: Vec<u8> = pixels.iter().flatten().copied().collect();
+        let mut out = vec![];
+        encode(&mut out, width, height, &pixels).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    /// Returns the sixel data that follows the color register definitions
+    fn body(encoded: &str) -> &str {
+        let start = encoded.rfind(";2;").unwrap();
+        let start = start + encoded[start..].find('#').unwrap();
+        &encoded[start..encoded.len() - 2]
+    }
+
+    const RED: [u8; 3] = [255, 0, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+
+    #[test]
+    fn header() {
+        assert_eq!(
+            encode_str(2, 1, &[RED, BLUE]),
+            "\x1bP0;1;0q\"1;1;2;1#0;2;100;0;0#1;2;0;0;100#0@$#1?@-\x1b\\"
+        );
+    }
+
+    #[test]
+    fn bands() {
+        // The seventh row starts a second band, which has only one row
+        assert_eq!(body(&encode_str(1, 7, &[RED; 7])), "#0~-#0@-");
+        // Each band returns to its start for each of its colors
+        assert_eq!(
+            body(&encode_str(1, 6, &[RED, BLUE, RED, BLUE, RED, BLUE])),
+            "#0T$#1i-"
+        );
+    }
+
+    #[test]
+    fn runs() {
+        assert_eq!(body(&encode_str(3, 1, &[RED; 3])), "#0@@@-");
+        assert_eq!(body(&encode_str(5, 1, &[RED; 5])), "#0!5@-");
+        // Registers are numbered in order of appearance.  A trailing
+        // run of empty sixels is omitted, but a leading one is
+        // compressed like any other.
+        assert_eq!(
+            body(&encode_str(6, 1, &[BLUE, BLUE, BLUE, BLUE, RED, BLUE])),
+            "#0!4@?@$#1!4?@-"
+        );
+    }
+
+    #[test]
+    fn reduces_colors() {
+        let count_registers = |s: &str| s.matches(";2;").count();
+
+        let exact: Vec<[u8; 3]> = (0..=255).map(|i| [i as u8, 0, 0]).collect();
+        assert_eq!(count_registers(&encode_str(256, 1, &exact)), 256);
+
+        let gradient: Vec<[u8; 3]> = (0..512)
+            .map(|i: usize| [(i % 256) as u8, (i / 2) as u8, (i / 4) as u8])
+            .collect();
+        let encoded = encode_str(512, 1, &gradient);
+        let registers = count_registers(&encoded);
+        assert!(registers <= 216, "{} registers", registers);
+        assert_eq!(quantize([0, 0, 0]), [0, 0, 0]);
+        assert_eq!(quantize([255, 255, 255]), [255, 255, 255]);
+        assert_eq!(quantize([30, 120, 230]), [51, 102, 255]);
+    }
+}
@@ -0,0 +1,45 @@
+use crate::cli::resolve_relative_cwd;
+use clap::{Parser, ValueHint};
+use mux::pane::PaneId;
+use portable_pty::cmdbuilder::CommandBuilder;
+use std::ffi::OsString;
+use wezterm_client::client::Client;
+
+#[derive(Debug, Parser, Clone)]
+pub struct RespawnPane {
+    /// Specify the pane that should be respawned.
+    /// The default is to use the current pane based on the
+    /// environment variable WEZTERM_PANE.
+    #[arg(long)]
+    pane_id: Option<PaneId>,
+
+    /// Specify the current working directory for the respawned
+    /// program
+    #[arg(long, value_parser, value_hint=ValueHint::DirPath)]
+    cwd: Option<OsString>,
+
+    /// Instead of re-running the command that was originally
+    /// spawned into the pane, run PROG.
+    /// For example: `wezterm cli respawn-pane -- bash -l` will respawn
+    /// the pane with bash as if it were a login shell.
+    #[arg(value_parser, value_hint=ValueHint::CommandWithArguments, num_args=1..)]
+    prog: Vec<OsString>,
+}
+
+impl RespawnPane {
+    pub async fn run(self, client: Client) -> anyhow::Result<()> {
+        let pane_id = client.resolve_pane_id(self.pane_id).await?;
+        client
+            .respawn_pane(codec::RespawnPane {
+                pane_id,
+                command: if self.prog.is_empty() {
+                    None
+                } else {
+                    Some(CommandBuilder::from_argv(self.prog))
+                },
+                command_dir: resolve_relative_cwd(self.cwd)?,
+            })
+            .await?;
+        Ok(())
+    }
+}
@@ -17,8 +17,14 @@ pub struct SetTabTitle {
     #[arg(long)]
     pane_id: Option<PaneId>,
 
+    /// Remove the title that was previously set, so that the
+    /// title is once again set by applications in the tab
+    #[arg(long, conflicts_with_all=&["title"])]
+    clear: bool,
+
     /// The new title for the tab
-    title: String,
+    #[arg(required_unless_present = "clear")]
+    title: Option<String>,
 }
 
 impl SetTabTitle {
@@ -55,7 +61,9 @@ impl SetTabTitle {
         client
             .set_tab_title(codec::TabTitleChanged {
                 tab_id,
-                title: self.title,
+                // An empty title removes the override
+                title: self.title.unwrap_or_default(),
+                user_override: true,
             })
             .await?;
         Ok(())
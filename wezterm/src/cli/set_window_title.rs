@@ -18,8 +18,14 @@ pub struct SetWindowTitle {
     #[arg(long)]
     pane_id: Option<PaneId>,
 
+    /// Remove the title that was previously set, so that the
+    /// title is once again set by applications in the window
+    #[arg(long, conflicts_with_all=&["title"])]
+    clear: bool,
+
     /// The new title for the window
-    title: String,
+    #[arg(required_unless_present = "clear")]
+    title: Option<String>,
 }
 
 impl SetWindowTitle {
@@ -56,7 +62,9 @@ impl SetWindowTitle {
         client
             .set_window_title(codec::WindowTitleChanged {
                 window_id,
-                title: self.title,
+                // An empty title removes the override
+                title: self.title.unwrap_or_default(),
+                user_override: true,
             })
             .await?;
         Ok(())
@@ -0,0 +1,54 @@
+use chrono::{DateTime, Local};
+use clap::Parser;
+use std::io::Write;
+use std::time::Duration;
+use wezterm_client::client::Client;
+
+/// How often the server is asked for new entries when following
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Parser, Clone, Copy)]
+pub struct ShowLogsCommand {
+    /// Keep running and print new entries as they are logged
+    #[arg(long, short = 'f')]
+    follow: bool,
+
+    /// Only show entries at least as severe as this level.
+    /// One of "error", "warn", "info", "debug" or "trace".
+    #[arg(long, default_value = "trace")]
+    level: log::Level,
+}
+
+impl ShowLogsCommand {
+    pub async fn run(&self, client: Client) -> anyhow::Result<()> {
+        let mut after = 0;
+        loop {
+            let response = client
+                .get_log_entries(codec::GetLogEntries {
+                    after,
+                    level: self.level.as_str().to_string(),
+                })
+                .await?;
+
+            let out = std::io::stdout();
+            let mut out = out.lock();
+            for entry in response.entries {
+                after = after.max(entry.seqno);
+                let ts = DateTime::from_timestamp_millis(entry.timestamp_ms)
+                    .map(|ts| ts.with_timezone(&Local).format("%H:%M:%S%.3f").to_string())
+                    .unwrap_or_default();
+                writeln!(
+                    out,
+                    "{}  {:6} {} > {}",
+                    ts, entry.level, entry.target, entry.msg
+                )?;
+            }
+            out.flush()?;
+
+            if !self.follow {
+                return Ok(());
+            }
+            smol::Timer::after(FOLLOW_INTERVAL).await;
+        }
+    }
+}
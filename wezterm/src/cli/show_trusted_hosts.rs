@@ -0,0 +1,69 @@
+use crate::cli::CliOutputFormatKind;
+use clap::Parser;
+use serde::Serializer as _;
+use tabout::{tabulate_output, Alignment, Column};
+use wezterm_client::pinning::{load_trusted_hosts, remove_trusted_host};
+
+#[derive(Debug, Parser, Clone)]
+pub struct ShowTrustedHostsCommand {
+    /// Forget the certificate that was trusted for REMOTE_ADDRESS,
+    /// so that the next certificate that it presents is trusted
+    /// on first use.
+    #[arg(long, value_name = "REMOTE_ADDRESS")]
+    remove: Option<String>,
+
+    /// Controls the output format.
+    /// "table" and "json" are possible formats.
+    #[arg(long = "format", default_value = "table")]
+    format: CliOutputFormatKind,
+}
+
+impl ShowTrustedHostsCommand {
+    pub fn run(&self) -> anyhow::Result<()> {
+        if let Some(remote_address) = &self.remove {
+            if !remove_trusted_host(remote_address)? {
+                anyhow::bail!("{remote_address} is not a trusted host");
+            }
+            return Ok(());
+        }
+
+        let out = std::io::stdout();
+        let hosts = load_trusted_hosts()?;
+        match self.format {
+            CliOutputFormatKind::Json => {
+                let hosts = hosts.into_iter().map(|host| CliTrustedHost {
+                    remote_address: host.remote_address,
+                    fingerprint: host.fingerprint,
+                });
+                let mut writer = serde_json::Serializer::pretty(out.lock());
+                writer.collect_seq(hosts)?;
+            }
+            CliOutputFormatKind::Table => {
+                let cols = vec![
+                    Column {
+                        name: "REMOTE_ADDRESS".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                    Column {
+                        name: "FINGERPRINT".to_string(),
+                        alignment: Alignment::Left,
+                    },
+                ];
+                let data: Vec<Vec<String>> = hosts
+                    .into_iter()
+                    .map(|host| vec![host.remote_address, host.fingerprint])
+                    .collect();
+                tabulate_output(&cols, &data, &mut out.lock())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// This will be serialized to JSON via the 'ShowTrustedHosts' command.
+// As such it is intended to be a stable output format.
+#[derive(serde::Serialize)]
+struct CliTrustedHost {
+    remote_address: String,
+    fingerprint: String,
+}
@@ -2,6 +2,7 @@ use crate::cli::resolve_relative_cwd;
 use clap::{Parser, ValueHint};
 use config::keyassignment::SpawnTabDomain;
 use config::ConfigHandle;
+use mux::domain::InheritEnvironment;
 use mux::pane::PaneId;
 use mux::window::WindowId;
 use portable_pty::cmdbuilder::CommandBuilder;
@@ -43,6 +44,26 @@ pub struct SpawnCommand {
     #[arg(long, requires = "new_window")]
     workspace: Option<String>,
 
+    /// Apply the overrides of the named entry of the `profiles`
+    /// configuration of the mux server to the new pane, and to its
+    /// window when used with `--new-window`.
+    /// If omitted, a new tab takes the profile of the active pane
+    /// of its window.
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Set each of the user vars of the current pane
+    /// in the environment of the spawned program
+    #[arg(long)]
+    inherit_user_vars: bool,
+
+    /// Set the environment variable NAME in the spawned program to the
+    /// value of the user var of that name in the current pane, or failing
+    /// that, to its value in the environment of the foreground process of
+    /// that pane. May be specified multiple times.
+    #[arg(long, value_name = "NAME")]
+    inherit_env: Vec<String>,
+
     /// Instead of executing your shell, run PROG.
     /// For example: `wezterm cli spawn -- bash -l` will spawn bash
     /// as if it were a login shell.
@@ -96,6 +117,30 @@ impl SpawnCommand {
 
         let size = config.initial_size(0, None);
 
+        let mut command = if self.prog.is_empty() {
+            None
+        } else {
+            let builder = CommandBuilder::from_argv(self.prog);
+            Some(builder)
+        };
+
+        let inherit_env = InheritEnvironment {
+            user_vars: self.inherit_user_vars,
+            names: self.inherit_env,
+        };
+        if !inherit_env.is_empty() {
+            // The environment of the foreground process of the pane
+            // is only accessible to the mux server
+            let pane_id = client.resolve_pane_id(self.pane_id).await?;
+            let inherited = client
+                .resolve_inherited_env(codec::ResolveInheritedEnv {
+                    pane_id,
+                    inherit_env,
+                })
+                .await?;
+            command = InheritEnvironment::merge(inherited.env, command);
+        }
+
         let spawned = client
             .spawn_v2(codec::SpawnV2 {
                 domain: self
@@ -104,15 +149,11 @@ impl SpawnCommand {
                         SpawnTabDomain::DomainName(name)
                     }),
                 window_id,
-                command: if self.prog.is_empty() {
-                    None
-                } else {
-                    let builder = CommandBuilder::from_argv(self.prog);
-                    Some(builder)
-                },
+                command,
                 command_dir: resolve_relative_cwd(self.cwd)?,
                 size,
                 workspace,
+                profile: self.profile,
             })
             .await?;
 
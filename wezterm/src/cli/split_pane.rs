@@ -1,5 +1,6 @@
 use crate::cli::resolve_relative_cwd;
 use clap::{Parser, ValueHint};
+use mux::domain::InheritEnvironment;
 use mux::pane::PaneId;
 use mux::tab::{SplitDirection, SplitRequest, SplitSize};
 use portable_pty::cmdbuilder::CommandBuilder;
@@ -55,9 +56,21 @@ pub struct SplitPane {
     #[arg(long, value_parser, value_hint=ValueHint::DirPath)]
     cwd: Option<OsString>,
 
+    /// Set each of the user vars of the pane that is being split
+    /// in the environment of the spawned program
+    #[arg(long)]
+    inherit_user_vars: bool,
+
+    /// Set the environment variable NAME in the spawned program to the
+    /// value of the user var of that name in the pane that is being split,
+    /// or failing that, to its value in the environment of the foreground
+    /// process of that pane. May be specified multiple times.
+    #[arg(long, value_name = "NAME")]
+    inherit_env: Vec<String>,
+
     /// Instead of spawning a new command, move the specified
     /// pane into the newly created split.
-    #[arg(long, conflicts_with_all=&["cwd", "prog"])]
+    #[arg(long, conflicts_with_all=&["cwd", "prog", "inherit_user_vars", "inherit_env"])]
     move_pane_id: Option<PaneId>,
 
     /// Instead of executing your shell, run PROG.
@@ -104,6 +117,10 @@ impl SplitPane {
                     Some(builder)
                 },
                 command_dir: resolve_relative_cwd(self.cwd)?,
+                inherit_env: InheritEnvironment {
+                    user_vars: self.inherit_user_vars,
+                    names: self.inherit_env,
+                },
                 move_pane_id: self.move_pane_id,
             })
             .await?;
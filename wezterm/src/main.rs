@@ -110,6 +110,12 @@ enum SubCommand {
     #[command(name = "connect", about = "Connect to wezterm multiplexer")]
     Connect(ConnectCommand),
 
+    #[command(
+        name = "restore",
+        about = "Start the GUI and restore a previously saved session"
+    )]
+    Restore(RestoreCommand),
+
     #[command(name = "ls-fonts", about = "Display information about fonts")]
     LsFonts(LsFontsCommand),
 
@@ -142,6 +148,23 @@ enum SubCommand {
         #[arg(long, value_parser)]
         shell: Shell,
     },
+
+    /// Generate the schema of the configuration for external tools
+    #[command(name = "gen-schema")]
+    GenSchema {
+        /// Which format to generate
+        #[arg(long, value_enum, default_value_t = SchemaFormat::Json)]
+        format: SchemaFormat,
+    },
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum, Default)]
+enum SchemaFormat {
+    /// A JSON Schema document
+    #[default]
+    Json,
+    /// Type annotations for the lua-language-server
+    LuaAnnotations,
 }
 
 use termwiz::escape::osc::{
@@ -742,7 +765,8 @@ fn run() -> anyhow::Result<()> {
         | SubCommand::ShowKeys(_)
         | SubCommand::Ssh(_)
         | SubCommand::Serial(_)
-        | SubCommand::Connect(_) => delegate_to_gui(saver),
+        | SubCommand::Connect(_)
+        | SubCommand::Restore(_) => delegate_to_gui(saver),
         SubCommand::ImageCat(cmd) => cmd.run(),
         SubCommand::SetCwd(cmd) => cmd.run(),
         SubCommand::Cli(cli) => cli::run_cli(&opts, cli),
@@ -755,6 +779,18 @@ fn run() -> anyhow::Result<()> {
             generate_completion(shell, &mut cmd, name, &mut std::io::stdout());
             Ok(())
         }
+        SubCommand::GenSchema { format } => {
+            match format {
+                SchemaFormat::Json => {
+                    let schema = config::schema::json_config_schema();
+                    println!("{}", serde_json::to_string_pretty(&schema)?);
+                }
+                SchemaFormat::LuaAnnotations => {
+                    print!("{}", config::schema::lua_config_annotations());
+                }
+            }
+            Ok(())
+        }
     }
 }
 
@@ -1,11 +1,15 @@
 use crate::screen::Screens;
-use crate::{Appearance, Connection, GeometryOrigin, RequestedWindowGeometry, ResolvedGeometry};
+use crate::{
+    Appearance, Connection, GeometryOrigin, KeyCode, Modifiers, RequestedWindowGeometry,
+    ResolvedGeometry,
+};
 use anyhow::Result as Fallible;
 use config::keyassignment::KeyAssignment;
 use config::DimensionContext;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::Mutex;
+use std::time::Duration;
 
 thread_local! {
     static CONN: RefCell<Option<Rc<Connection>>> = RefCell::new(None);
@@ -26,6 +30,17 @@ pub enum ApplicationEvent {
     PerformKeyAssignment(KeyAssignment),
 }
 
+/// A key combination that is registered with the system so that
+/// it performs `action` even when none of our windows have focus.
+/// When pressed, the action is delivered via
+/// `ApplicationEvent::PerformKeyAssignment`.
+#[derive(Debug, Clone)]
+pub struct GlobalHotkey {
+    pub key: KeyCode,
+    pub mods: Modifiers,
+    pub action: KeyAssignment,
+}
+
 pub trait ConnectionOps {
     fn get() -> Option<Rc<Connection>> {
         let mut res = None;
@@ -76,6 +91,20 @@ pub trait ConnectionOps {
     /// Perform the system beep/notification sound
     fn beep(&self) {}
 
+    /// Returns the maximum time between the clicks of a double click,
+    /// as configured in the system settings, if it is known
+    fn double_click_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Replaces the set of global hotkeys registered with the system
+    fn set_global_hotkeys(&self, hotkeys: &[GlobalHotkey]) -> anyhow::Result<()> {
+        if !hotkeys.is_empty() {
+            anyhow::bail!("global hotkeys are not supported on this system");
+        }
+        Ok(())
+    }
+
     /// Returns information about the screens
     fn screens(&self) -> anyhow::Result<Screens> {
         anyhow::bail!("Unable to query screen information");
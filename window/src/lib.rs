@@ -71,6 +71,18 @@ pub enum MouseCursor {
     Text,
     SizeUpDown,
     SizeLeftRight,
+    /// Resizing towards the top-right or bottom-left corner
+    SizeNeSw,
+    /// Resizing towards the top-left or bottom-right corner
+    SizeNwSe,
+    Move,
+    Crosshair,
+    Wait,
+    Progress,
+    Help,
+    NotAllowed,
+    Grab,
+    Grabbing,
 }
 
 /// Represents the preferred appearance of the windowing
@@ -149,9 +161,138 @@ pub enum WindowKeyEvent {
 pub enum DeadKeyStatus {
     /// Not in a dead key processing hold
     None,
-    /// Holding until composition is done; the string is the uncommitted
+    /// Holding until composition is done; this is the uncommitted
     /// composition text to show as a placeholder
-    Composing(String),
+    Composing(Preedit),
+}
+
+/// A clause of the text being composed by an input method.
+/// IMEs split the text into clauses that are converted separately;
+/// the clause that is being converted is selected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PreeditClause {
+    /// The byte range of the clause within the text
+    pub range: std::ops::Range<usize>,
+    pub selected: bool,
+}
+
+/// Text that is being composed and has not been committed yet
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Preedit {
+    pub text: String,
+    /// The clauses, which together cover the text.  This is empty for
+    /// a placeholder, such as a pending dead key, that isn't styled as
+    /// a composition.
+    pub clauses: Vec<PreeditClause>,
+}
+
+impl From<String> for Preedit {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            clauses: vec![],
+        }
+    }
+}
+
+impl Preedit {
+    /// Composition text from an IME that doesn't report its clauses;
+    /// it is styled as a single clause
+    pub fn composition(text: String) -> Self {
+        let clauses = if text.is_empty() {
+            vec![]
+        } else {
+            vec![PreeditClause {
+                range: 0..text.len(),
+                selected: false,
+            }]
+        };
+        Self { text, clauses }
+    }
+
+    /// Composition text in which the byte range `selected` is the
+    /// selected clause
+    pub fn with_selection(text: String, selected: std::ops::Range<usize>) -> Self {
+        let len = text.len();
+        let start = selected.start.min(len);
+        let end = selected.end.clamp(start, len);
+        if start == end || !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+            return Self::composition(text);
+        }
+
+        let mut clauses = vec![];
+        if start > 0 {
+            clauses.push(PreeditClause {
+                range: 0..start,
+                selected: false,
+            });
+        }
+        clauses.push(PreeditClause {
+            range: start..end,
+            selected: true,
+        });
+        if end < len {
+            clauses.push(PreeditClause {
+                range: end..len,
+                selected: false,
+            });
+        }
+        Self { text, clauses }
+    }
+
+    /// Like `with_selection`, but with the range measured in UTF-16
+    /// code units, as macOS reports it
+    pub fn with_utf16_selection(text: String, selected: std::ops::Range<usize>) -> Self {
+        let start = utf16_to_byte_offset(&text, selected.start);
+        let end = utf16_to_byte_offset(&text, selected.end);
+        Self::with_selection(text, start..end)
+    }
+
+    /// Composition text with one attribute for each UTF-16 code unit,
+    /// as Windows reports it.  Runs of the same attribute form the
+    /// clauses, and `is_selected` decides which of those are selected.
+    pub fn from_utf16_attributes(
+        text: String,
+        attributes: &[u8],
+        is_selected: impl Fn(u8) -> bool,
+    ) -> Self {
+        let mut clauses: Vec<(PreeditClause, u8)> = vec![];
+        let mut units = 0;
+        for (idx, c) in text.char_indices() {
+            let attr = match attributes.get(units) {
+                Some(attr) => *attr,
+                None => return Self::composition(text),
+            };
+            units += c.len_utf16();
+            match clauses.last_mut() {
+                Some((clause, prior)) if *prior == attr => {
+                    clause.range.end = idx + c.len_utf8();
+                }
+                _ => clauses.push((
+                    PreeditClause {
+                        range: idx..idx + c.len_utf8(),
+                        selected: is_selected(attr),
+                    },
+                    attr,
+                )),
+            }
+        }
+        Self {
+            text,
+            clauses: clauses.into_iter().map(|(clause, _)| clause).collect(),
+        }
+    }
+}
+
+fn utf16_to_byte_offset(text: &str, utf16: usize) -> usize {
+    let mut units = 0;
+    for (idx, c) in text.char_indices() {
+        if units >= utf16 {
+            return idx;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
 }
 
 #[derive(Debug)]
@@ -175,6 +316,14 @@ pub enum WindowEvent {
     /// Called when a program-requested set_inner_size() has finished
     SetInnerSizeCompleted,
 
+    /// Called when the window has been moved.
+    /// The coordinates are of the top left pixel of the
+    /// client area, as for `set_window_position`.
+    ///
+    /// This is not sent by backends that can't determine the
+    /// location of their windows (Wayland).
+    Moved(ScreenPoint),
+
     /// Called when the window has been invalidated and needs to
     /// be repainted
     NeedRepaint,
@@ -196,6 +345,13 @@ pub enum WindowEvent {
     MouseEvent(MouseEvent),
     MouseLeave,
 
+    /// Called for scrolling that is reported with pixel precision
+    /// by touchpads and touchscreens, rather than in whole wheel
+    /// notches. Systems that don't distinguish these devices
+    /// report them as `MouseEventKind::VertWheel` and
+    /// `MouseEventKind::HorzWheel` instead.
+    PreciseScroll(PreciseScrollEvent),
+
     AppearanceChanged(Appearance),
 
     Notification(Box<dyn Any + Send + Sync>),
@@ -218,6 +374,40 @@ pub enum WindowEvent {
     AdviseModifiersLedStatus(Modifiers, KeyboardLedStatus),
 }
 
+/// The stage of a scroll gesture that is reported via
+/// `WindowEvent::PreciseScroll`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollPhase {
+    /// The fingers are on the surface and are moving
+    Active,
+    /// The fingers were lifted from the surface. The system won't
+    /// report any momentum, so the application may continue the
+    /// motion itself.
+    Released,
+    /// The gesture has ended and the system takes care of any
+    /// momentum by reporting `Momentum` events.
+    Ended,
+    /// Motion synthesized by the system after the fingers were lifted
+    Momentum,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreciseScrollEvent {
+    /// Horizontal distance in pixels; positive values scroll left,
+    /// matching the sign of `MouseEventKind::HorzWheel`
+    pub delta_x: f64,
+    /// Vertical distance in pixels; positive values scroll up,
+    /// matching the sign of `MouseEventKind::VertWheel`
+    pub delta_y: f64,
+    pub phase: ScrollPhase,
+    /// Coordinates of the mouse relative to the top left of the window
+    pub coords: Point,
+    /// The mouse position in screen coordinates
+    pub screen_coords: ScreenPoint,
+    pub mouse_buttons: MouseButtons,
+    pub modifiers: Modifiers,
+}
+
 pub struct WindowEventSender {
     handler: Box<dyn FnMut(WindowEvent, &Window)>,
     window: Option<Window>,
@@ -247,6 +437,19 @@ impl WindowEventSender {
 #[error("Graphics drivers lost context")]
 pub struct GraphicsDriversLostContext {}
 
+/// Progress to show on the taskbar button for a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskbarProgress {
+    None,
+    /// The percentage complete
+    Normal(u8),
+    /// Failed at the percentage complete
+    Error(u8),
+    Indeterminate,
+    /// Paused at the percentage complete
+    Paused(u8),
+}
+
 #[async_trait(?Send)]
 pub trait WindowOps {
     /// Show a hidden window
@@ -277,6 +480,14 @@ pub trait WindowOps {
     /// be repainted shortly
     fn invalidate(&self);
 
+    /// Invalidate the window so that the entire client area will
+    /// be repainted without waiting for the `max_fps` frame pacing
+    /// to permit it.  Backends that don't pace their frames treat
+    /// this the same as `invalidate`.
+    fn invalidate_immediately(&self) {
+        self.invalidate();
+    }
+
     /// Change the titlebar text for the window
     fn set_title(&self, title: &str);
 
@@ -313,6 +524,15 @@ pub trait WindowOps {
     /// the platform specific input method editor
     fn set_text_cursor_position(&self, _cursor: Rect) {}
 
+    /// Enable or disable the input method editor for the window.
+    /// When disabled, key events are delivered without being
+    /// composed by the IME.
+    fn set_ime_enabled(&self, _enabled: bool) {}
+
+    /// Show progress on the taskbar button for the window.
+    /// Only implemented on Windows.
+    fn set_taskbar_progress(&self, _progress: TaskbarProgress) {}
+
     /// Initiate textual transfer from the clipboard
     fn get_clipboard(&self, clipboard: Clipboard) -> Future<String>;
 
@@ -18,6 +18,7 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::sync::atomic::AtomicUsize;
+use std::time::Duration;
 
 pub struct Connection {
     ns_app: id,
@@ -132,6 +133,11 @@ impl ConnectionOps for Connection {
         }
     }
 
+    fn double_click_interval(&self) -> Option<Duration> {
+        let seconds: f64 = unsafe { msg_send![class!(NSEvent), doubleClickInterval] };
+        Duration::try_from_secs_f64(seconds).ok()
+    }
+
     fn get_appearance(&self) -> Appearance {
         let name = unsafe {
             let appearance: id = msg_send![self.ns_app, effectiveAppearance];
@@ -9,17 +9,18 @@ use crate::os::macos::menu::{MenuItem, RepresentedItem};
 use crate::parameters::{Border, Parameters, TitleBar};
 use crate::{
     Clipboard, Connection, DeadKeyStatus, Dimensions, Handled, KeyCode, KeyEvent, Modifiers,
-    MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, RawKeyEvent, Rect,
-    RequestedWindowGeometry, ResizeIncrement, ResolvedGeometry, ScreenPoint, Size, ULength,
-    WindowDecorations, WindowEvent, WindowEventSender, WindowOps, WindowState,
+    MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, PreciseScrollEvent,
+    Preedit, RawKeyEvent, Rect, RequestedWindowGeometry, ResizeIncrement, ResolvedGeometry,
+    ScreenPoint, ScrollPhase, Size, ULength, WindowDecorations, WindowEvent, WindowEventSender,
+    WindowOps, WindowState,
 };
 use anyhow::{anyhow, bail, ensure};
 use async_trait::async_trait;
 use cocoa::appkit::{
     self, CGFloat, NSApplication, NSApplicationActivateIgnoringOtherApps,
     NSApplicationPresentationOptions, NSBackingStoreBuffered, NSEvent, NSEventModifierFlags,
-    NSOpenGLContext, NSOpenGLPixelFormat, NSPasteboard, NSRunningApplication, NSScreen, NSView,
-    NSViewHeightSizable, NSViewWidthSizable, NSWindow, NSWindowStyleMask,
+    NSEventPhase, NSOpenGLContext, NSOpenGLPixelFormat, NSPasteboard, NSRunningApplication,
+    NSScreen, NSView, NSViewHeightSizable, NSViewWidthSizable, NSWindow, NSWindowStyleMask,
 };
 use cocoa::base::*;
 use cocoa::foundation::{
@@ -501,6 +502,8 @@ impl Window {
                 ime_last_event: None,
                 live_resizing: false,
                 ime_text: String::new(),
+                ime_selected: 0..0,
+                ime_enabled: true,
             }));
 
             let window: id = msg_send![get_window_class(), alloc];
@@ -769,6 +772,13 @@ impl WindowOps for Window {
         });
     }
 
+    fn invalidate_immediately(&self) {
+        Connection::with_window_inner(self.id, |inner| {
+            inner.invalidate_immediately();
+            Ok(())
+        });
+    }
+
     fn set_title(&self, title: &str) {
         let title = title.to_owned();
         Connection::with_window_inner(self.id, move |inner| {
@@ -812,6 +822,13 @@ impl WindowOps for Window {
         });
     }
 
+    fn set_ime_enabled(&self, enabled: bool) {
+        Connection::with_window_inner(self.id, move |inner| {
+            inner.set_ime_enabled(enabled);
+            Ok(())
+        });
+    }
+
     fn get_clipboard(&self, _clipboard: Clipboard) -> Future<String> {
         Future::result(
             ClipboardContext::new()
@@ -1173,6 +1190,19 @@ impl WindowInner {
                     MouseCursor::Hand => msg_send![ns_cursor_cls, pointingHandCursor],
                     MouseCursor::SizeUpDown => msg_send![ns_cursor_cls, resizeUpDownCursor],
                     MouseCursor::SizeLeftRight => msg_send![ns_cursor_cls, resizeLeftRightCursor],
+                    MouseCursor::Crosshair => msg_send![ns_cursor_cls, crosshairCursor],
+                    MouseCursor::NotAllowed => {
+                        msg_send![ns_cursor_cls, operationNotAllowedCursor]
+                    }
+                    MouseCursor::Grab => msg_send![ns_cursor_cls, openHandCursor],
+                    MouseCursor::Grabbing => msg_send![ns_cursor_cls, closedHandCursor],
+                    // AppKit has no public cursors for these
+                    MouseCursor::SizeNeSw
+                    | MouseCursor::SizeNwSe
+                    | MouseCursor::Move
+                    | MouseCursor::Wait
+                    | MouseCursor::Progress
+                    | MouseCursor::Help => msg_send![ns_cursor_cls, arrowCursor],
                 };
                 let () = msg_send![ns_cursor_cls, setHiddenUntilMouseMoves: NO];
                 let () = msg_send![instance, set];
@@ -1190,6 +1220,15 @@ impl WindowInner {
             }
         }
     }
+
+    fn invalidate_immediately(&mut self) {
+        unsafe {
+            if let Some(window_view) = WindowView::get_this(&**self.view) {
+                window_view.inner.borrow_mut().paint_throttled = false;
+            }
+        }
+        self.invalidate();
+    }
     fn set_title(&mut self, title: &str) {
         let title = nsstring(title);
         unsafe {
@@ -1238,6 +1277,28 @@ impl WindowInner {
         }
     }
 
+    fn set_ime_enabled(&mut self, enabled: bool) {
+        if let Some(window_view) = WindowView::get_this(unsafe { &**self.view }) {
+            let mut inner = window_view.inner.borrow_mut();
+            if inner.ime_enabled == enabled {
+                return;
+            }
+            inner.ime_enabled = enabled;
+            if !enabled {
+                inner.ime_state = ImeDisposition::None;
+                inner.ime_text.clear();
+                inner.ime_last_event.take();
+            }
+        }
+        if !enabled && self.config.use_ime {
+            // Abandon any composition that is in progress
+            unsafe {
+                let input_context: id = msg_send![&**self.view, inputContext];
+                let () = msg_send![input_context, discardMarkedText];
+            }
+        }
+    }
+
     fn is_zoomed(&self) -> bool {
         unsafe { msg_send![*self.window, isZoomed] }
     }
@@ -1443,6 +1504,11 @@ struct Inner {
     live_resizing: bool,
 
     ime_text: String,
+    /// The selected clause of ime_text, in UTF-16 code units
+    ime_selected: std::ops::Range<usize>,
+
+    /// When false, key events bypass the IME entirely
+    ime_enabled: bool,
 }
 
 #[repr(C)]
@@ -1596,6 +1662,10 @@ impl Keyboard {
 }
 
 impl Inner {
+    fn ime_preedit(&self) -> Preedit {
+        Preedit::with_utf16_selection(self.ime_text.clone(), self.ime_selected.clone())
+    }
+
     fn enable_opengl(&mut self) -> anyhow::Result<Rc<glium::backend::Context>> {
         let view = self.view_id.as_ref().unwrap().load();
         let glium_context = GlContextPair::create(*view)?;
@@ -1908,6 +1978,9 @@ impl WindowView {
         if let Some(myself) = Self::get_this(this) {
             let mut inner = myself.inner.borrow_mut();
             inner.ime_text = s.to_string();
+            let location = selected_range.0.location as usize;
+            inner.ime_selected =
+                location..location.saturating_add(selected_range.0.length as usize);
 
             /*
             let key_is_down = inner.key_is_down.take().unwrap_or(true);
@@ -2182,7 +2255,13 @@ impl WindowView {
         }
     }
 
-    fn mouse_common(this: &mut Object, nsevent: id, kind: MouseEventKind) {
+    /// Returns the position of the mouse in backing coordinates and in
+    /// screen coordinates, along with the buttons and modifiers
+    /// that are held down.
+    fn mouse_location(
+        this: &mut Object,
+        nsevent: id,
+    ) -> (Point, ScreenPoint, MouseButtons, Modifiers) {
         let view = this as id;
         let coords;
         let mouse_buttons;
@@ -2202,10 +2281,20 @@ impl WindowView {
             modifiers = key_modifiers(nsevent.modifierFlags());
             screen_coords = NSEvent::mouseLocation(nsevent);
         }
+        (
+            Point::new(coords.x as isize, coords.y as isize),
+            cartesian_to_screen_point(screen_coords),
+            mouse_buttons,
+            modifiers,
+        )
+    }
+
+    fn mouse_common(this: &mut Object, nsevent: id, kind: MouseEventKind) {
+        let (coords, screen_coords, mouse_buttons, modifiers) = Self::mouse_location(this, nsevent);
         let event = MouseEvent {
             kind,
-            coords: Point::new(coords.x as isize, coords.y as isize),
-            screen_coords: cartesian_to_screen_point(screen_coords),
+            coords,
+            screen_coords,
             mouse_buttons,
             modifiers,
         };
@@ -2239,23 +2328,67 @@ impl WindowView {
         }
     }
 
-    extern "C" fn scroll_wheel(this: &mut Object, _sel: Sel, nsevent: id) {
-        let precise = unsafe { nsevent.hasPreciseScrollingDeltas() } == YES;
-        let scale = if precise {
-            // Devices with precise deltas report number of pixels scrolled.
-            // At this layer we don't know how many pixels comprise a cell
-            // in the terminal widget, and our abstraction doesn't allow being
-            // told what that amount should be, so we come up with a hard
-            // coded factor based on the likely default font size and dpi
-            // to make the scroll speed feel a bit better.
-            15.0
+    /// Reports scrolling from a touchpad or magic mouse in pixels,
+    /// leaving it to the application to decide how far to scroll.
+    fn precise_scroll(this: &mut Object, nsevent: id) {
+        let view = this as id;
+        let (delta, phase, momentum_phase) = unsafe {
+            let delta = NSSize::new(nsevent.scrollingDeltaX(), nsevent.scrollingDeltaY());
+            // The deltas are in points; scale them to match the pixel
+            // coordinates that we report for the mouse position.
+            // backing_rect computes abs() values, so we need to restore
+            // the sign from the original deltas
+            let rect = NSRect::new(NSPoint::new(0., 0.), delta);
+            let backing_rect = NSView::convertRectToBacking(view, rect);
+            (
+                NSSize::new(
+                    f64::copysign(backing_rect.size.width, delta.width),
+                    f64::copysign(backing_rect.size.height, delta.height),
+                ),
+                nsevent.phase(),
+                nsevent.momentumPhase(),
+            )
+        };
+        let phase = if !momentum_phase.is_empty() {
+            ScrollPhase::Momentum
+        } else if phase
+            .intersects(NSEventPhase::NSEventPhaseEnded | NSEventPhase::NSEventPhaseCancelled)
+        {
+            // The system will follow up with Momentum events
+            ScrollPhase::Ended
         } else {
-            // Whereas imprecise deltas report the number of lines scrolled,
-            // so we want to report those lines here wholesale.
-            1.0
+            ScrollPhase::Active
         };
-        let mut vert_delta = unsafe { nsevent.scrollingDeltaY() } / scale;
-        let mut horz_delta = unsafe { nsevent.scrollingDeltaX() } / scale;
+
+        let (coords, screen_coords, mouse_buttons, modifiers) = Self::mouse_location(this, nsevent);
+        let event = PreciseScrollEvent {
+            delta_x: delta.width,
+            delta_y: delta.height,
+            phase,
+            coords,
+            screen_coords,
+            mouse_buttons,
+            modifiers,
+        };
+
+        if let Some(myself) = Self::get_this(this) {
+            let mut inner = myself.inner.borrow_mut();
+            inner.events.dispatch(WindowEvent::PreciseScroll(event));
+        }
+    }
+
+    extern "C" fn scroll_wheel(this: &mut Object, _sel: Sel, nsevent: id) {
+        let precise = unsafe { nsevent.hasPreciseScrollingDeltas() } == YES;
+        if precise {
+            // Devices with precise deltas report number of pixels scrolled,
+            // which the application converts into cells
+            Self::precise_scroll(this, nsevent);
+            return;
+        }
+        // Whereas imprecise deltas report the number of lines scrolled,
+        // so we want to report those lines here wholesale.
+        let mut vert_delta = unsafe { nsevent.scrollingDeltaY() };
+        let mut horz_delta = unsafe { nsevent.scrollingDeltaX() };
 
         if let Some(myself) = Self::get_this(this) {
             let mut inner = myself.inner.borrow_mut();
@@ -2434,7 +2567,7 @@ impl WindowView {
                     Ok(TranslateStatus::Composing(composing)) => {
                         // Next key press in dead key sequence is pending.
                         inner.events.dispatch(WindowEvent::AdviseDeadKeyStatus(
-                            DeadKeyStatus::Composing(composing),
+                            DeadKeyStatus::Composing(composing.into()),
                         ));
 
                         return;
@@ -2474,7 +2607,10 @@ impl WindowView {
         };
 
         let config_handle = config::configuration();
-        let use_ime = config_handle.use_ime;
+        let use_ime = config_handle.use_ime
+            && Self::get_this(this)
+                .map(|myself| myself.inner.borrow().ime_enabled)
+                .unwrap_or(true);
         let send_composed_key_when_left_alt_is_pressed =
             config_handle.send_composed_key_when_left_alt_is_pressed;
         let send_composed_key_when_right_alt_is_pressed =
@@ -2543,7 +2679,7 @@ impl WindowView {
                             // If it didn't generate an event, then a composition
                             // is pending.
                             let status = if inner.ime_last_event.is_none() {
-                                DeadKeyStatus::Composing(inner.ime_text.clone())
+                                DeadKeyStatus::Composing(inner.ime_preedit())
                             } else {
                                 DeadKeyStatus::None
                             };
@@ -2573,7 +2709,7 @@ impl WindowView {
                             let status = if inner.ime_text.is_empty() {
                                 DeadKeyStatus::None
                             } else {
-                                DeadKeyStatus::Composing(inner.ime_text.clone())
+                                DeadKeyStatus::Composing(inner.ime_preedit())
                             };
                             inner
                                 .events
@@ -2758,6 +2894,28 @@ impl WindowView {
         }
     }
 
+    extern "C" fn did_move(this: &mut Object, _sel: Sel, _notification: id) {
+        if let Some(this) = Self::get_this(this) {
+            let mut inner = this.inner.borrow_mut();
+            let position = inner.window.as_ref().map(|window| {
+                let window = window.load();
+                unsafe {
+                    let frame = NSWindow::frame(*window);
+                    let content_frame = NSWindow::contentRectForFrameRect_(*window, frame);
+                    // The top left of the content area, which is its
+                    // maximum y in the cartesian coordinate system
+                    cartesian_to_screen_point(NSPoint::new(
+                        content_frame.origin.x,
+                        content_frame.origin.y + content_frame.size.height,
+                    ))
+                }
+            });
+            if let Some(position) = position {
+                inner.events.dispatch(WindowEvent::Moved(position));
+            }
+        }
+    }
+
     extern "C" fn will_start_live_resize(this: &mut Object, _sel: Sel, _notification: id) {
         if let Some(this) = Self::get_this(this) {
             let mut inner = this.inner.borrow_mut();
@@ -3135,6 +3293,10 @@ impl WindowView {
                 sel!(windowDidResize:),
                 Self::did_resize as extern "C" fn(&mut Object, Sel, id),
             );
+            cls.add_method(
+                sel!(windowDidMove:),
+                Self::did_move as extern "C" fn(&mut Object, Sel, id),
+            );
             cls.add_method(
                 sel!(windowDidChangeScreen:),
                 Self::did_change_screen as extern "C" fn(&mut Object, Sel, id),
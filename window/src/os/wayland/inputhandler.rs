@@ -16,13 +16,13 @@ use wayland_protocols::wp::text_input::zv3::client::zwp_text_input_v3::{
 };
 use wezterm_input_types::{KeyCode, KeyEvent, KeyboardLedStatus, Modifiers};
 
-use crate::{DeadKeyStatus, WindowEvent};
+use crate::{DeadKeyStatus, Preedit, WindowEvent};
 
 use super::state::WaylandState;
 
 #[derive(Clone, Default, Debug)]
 struct PendingState {
-    pre_edit: Option<String>,
+    pre_edit: Option<Preedit>,
     commit: Option<String>,
 }
 
@@ -166,10 +166,19 @@ impl Dispatch<ZwpTextInputV3, TextInputData, WaylandState> for TextInputState {
         match event {
             TextInputEvent::PreeditString {
                 text,
-                cursor_begin: _,
-                cursor_end: _,
+                cursor_begin,
+                cursor_end,
             } => {
-                pending_state.pre_edit = text;
+                // A range between the cursor positions is the
+                // highlighted part of the text; both are -1 if
+                // the cursor is hidden
+                pending_state.pre_edit = text.map(|text| {
+                    if cursor_begin >= 0 && cursor_end > cursor_begin {
+                        Preedit::with_selection(text, cursor_begin as usize..cursor_end as usize)
+                    } else {
+                        Preedit::composition(text)
+                    }
+                });
             }
             TextInputEvent::CommitString { text } => {
                 pending_state.commit = text;
@@ -189,8 +198,8 @@ impl Dispatch<ZwpTextInputV3, TextInputData, WaylandState> for TextInputState {
                         raw: None,
                     }));
                 }
-                let status = if let Some(text) = pending_state.pre_edit.take() {
-                    DeadKeyStatus::Composing(text)
+                let status = if let Some(preedit) = pending_state.pre_edit.take() {
+                    DeadKeyStatus::Composing(preedit)
                 } else {
                     DeadKeyStatus::None
                 };
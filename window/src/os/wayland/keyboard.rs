@@ -30,10 +30,16 @@ impl Dispatch<WlKeyboard, KeyboardData> for WaylandState {
                 if let Some(sud) = SurfaceUserData::try_from_wl(&surface) {
                     let window_id = sud.window_id;
                     state.keyboard_window_id.borrow_mut().replace(window_id);
+                    let ime_enabled = state
+                        .window_by_id(window_id)
+                        .map(|win| win.borrow().ime_enabled)
+                        .unwrap_or(true);
                     if let Some(text_input) = &state.text_input {
                         if let Some(input) = text_input.get_text_input_for_keyboard(keyboard) {
-                            input.enable();
-                            input.commit();
+                            if ime_enabled {
+                                input.enable();
+                                input.commit();
+                            }
                         }
                         text_input.advise_surface(surface, keyboard);
                     }
@@ -8,7 +8,7 @@ use smithay_client_toolkit::seat::pointer::{
     PointerData, PointerDataExt, PointerEvent, PointerEventKind, PointerHandler,
 };
 use wayland_client::backend::ObjectId;
-use wayland_client::protocol::wl_pointer::{ButtonState, WlPointer};
+use wayland_client::protocol::wl_pointer::{AxisSource, ButtonState, WlPointer};
 use wayland_client::protocol::wl_seat::WlSeat;
 use wayland_client::{Connection, Proxy, QueueHandle};
 use wezterm_input_types::MousePress;
@@ -96,6 +96,9 @@ pub struct PendingMouse {
     surface_coords: Option<(f64, f64)>,
     button: Vec<(MousePress, ButtonState)>,
     scroll: Option<(f64, f64)>,
+    /// Scrolling from a touchpad; the flag is set once the
+    /// fingers have been lifted
+    finger_scroll: Option<(f64, f64, bool)>,
     in_window: bool,
 }
 
@@ -109,6 +112,7 @@ impl PendingMouse {
             copy_and_paste: Arc::clone(copy_and_paste),
             button: vec![],
             scroll: None,
+            finger_scroll: None,
             surface_coords: None,
             in_window: false,
         }))
@@ -154,6 +158,21 @@ impl PendingMouse {
                 self.button.push((button, button_state));
                 changed
             }
+            PointerEventKind::Axis {
+                horizontal,
+                vertical,
+                source: Some(AxisSource::Finger),
+                ..
+            } => {
+                let changed = self.finger_scroll.is_none();
+                let (x, y, stop) = self.finger_scroll.take().unwrap_or((0., 0., false));
+                self.finger_scroll.replace((
+                    x + horizontal.absolute,
+                    y + vertical.absolute,
+                    stop || horizontal.stop || vertical.stop,
+                ));
+                changed
+            }
             PointerEventKind::Axis {
                 horizontal,
                 vertical,
@@ -185,6 +204,10 @@ impl PendingMouse {
         pending.lock().unwrap().scroll.take()
     }
 
+    pub(super) fn finger_scroll(pending: &Arc<Mutex<Self>>) -> Option<(f64, f64, bool)> {
+        pending.lock().unwrap().finger_scroll.take()
+    }
+
     pub(super) fn in_window(pending: &Arc<Mutex<Self>>) -> bool {
         pending.lock().unwrap().in_window
     }
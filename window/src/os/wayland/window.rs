@@ -48,9 +48,9 @@ use wezterm_input_types::{
 use crate::wayland::WaylandConnection;
 use crate::x11::KeyboardWithFallback;
 use crate::{
-    Appearance, Clipboard, Connection, ConnectionOps, Dimensions, MouseCursor, Point, Rect,
-    RequestedWindowGeometry, ResizeIncrement, ResolvedGeometry, Window, WindowEvent,
-    WindowEventSender, WindowKeyEvent, WindowOps, WindowState,
+    Appearance, Clipboard, Connection, ConnectionOps, Dimensions, MouseCursor, Point,
+    PreciseScrollEvent, Rect, RequestedWindowGeometry, ResizeIncrement, ResolvedGeometry,
+    ScrollPhase, Window, WindowEvent, WindowEventSender, WindowKeyEvent, WindowOps, WindowState,
 };
 
 use super::copy_and_paste::CopyAndPaste;
@@ -296,6 +296,7 @@ impl WaylandWindow {
             frame_callback: None,
 
             text_cursor: None,
+            ime_enabled: true,
             appearance,
 
             config,
@@ -390,6 +391,13 @@ impl WindowOps for WaylandWindow {
         });
     }
 
+    fn set_ime_enabled(&self, enabled: bool) {
+        WaylandConnection::with_window_inner(self.0, move |inner| {
+            inner.set_ime_enabled(enabled);
+            Ok(())
+        });
+    }
+
     fn set_title(&self, title: &str) {
         let title = title.to_owned();
         WaylandConnection::with_window_inner(self.0, |inner| {
@@ -528,6 +536,8 @@ pub struct WaylandWindowInner {
     invalidated: bool,
     // font_config: Rc<FontConfiguration>,
     text_cursor: Option<Rect>,
+    /// When false, the text input is left disabled for this window
+    pub(super) ime_enabled: bool,
     appearance: Appearance,
     config: ConfigHandle,
     // cache the title for comparison to avoid spamming
@@ -747,6 +757,27 @@ impl WaylandWindowInner {
             }
         }
 
+        if let Some((value_x, value_y, stop)) = PendingMouse::finger_scroll(&pending_mouse) {
+            let factor = self.get_dpi_factor() as f64;
+            let event = PreciseScrollEvent {
+                delta_x: -value_x * factor,
+                delta_y: -value_y * factor,
+                phase: if stop {
+                    ScrollPhase::Released
+                } else {
+                    ScrollPhase::Active
+                },
+                coords: self.last_mouse_coords,
+                screen_coords: ScreenPoint::new(
+                    self.last_mouse_coords.x + self.dimensions.pixel_width as isize,
+                    self.last_mouse_coords.y + self.dimensions.pixel_height as isize,
+                ),
+                mouse_buttons: self.mouse_buttons,
+                modifiers: self.modifiers,
+            };
+            self.events.dispatch(WindowEvent::PreciseScroll(event));
+        }
+
         if !PendingMouse::in_window(&pending_mouse) {
             self.events.dispatch(WindowEvent::MouseLeave);
             self.refresh_frame();
@@ -930,6 +961,16 @@ impl WaylandWindowInner {
                         MouseCursor::SizeUpDown => CursorIcon::NsResize,
                         MouseCursor::SizeLeftRight => CursorIcon::EwResize,
                         MouseCursor::Text => CursorIcon::Text,
+                        MouseCursor::SizeNeSw => CursorIcon::NeswResize,
+                        MouseCursor::SizeNwSe => CursorIcon::NwseResize,
+                        MouseCursor::Move => CursorIcon::Move,
+                        MouseCursor::Crosshair => CursorIcon::Crosshair,
+                        MouseCursor::Wait => CursorIcon::Wait,
+                        MouseCursor::Progress => CursorIcon::Progress,
+                        MouseCursor::Help => CursorIcon::Help,
+                        MouseCursor::NotAllowed => CursorIcon::NotAllowed,
+                        MouseCursor::Grab => CursorIcon::Grab,
+                        MouseCursor::Grabbing => CursorIcon::Grabbing,
                     },
                 ) {
                     log::error!("set_cursor: {}", err);
@@ -951,6 +992,30 @@ impl WaylandWindowInner {
         self.do_paint().unwrap();
     }
 
+    fn set_ime_enabled(&mut self, enabled: bool) {
+        if self.ime_enabled == enabled {
+            return;
+        }
+        self.ime_enabled = enabled;
+
+        let conn = WaylandConnection::get().unwrap().wayland();
+        let state = conn.wayland_state.borrow();
+        let surface = self.surface().clone();
+        if let Some(text_input) = &state.text_input {
+            if let Some(input) = text_input.get_text_input_for_surface(&surface) {
+                if enabled {
+                    input.enable();
+                    // Ensure that the cursor rectangle is sent again
+                    // to the newly enabled input
+                    self.text_cursor.take();
+                } else {
+                    input.disable();
+                }
+                input.commit();
+            }
+        }
+    }
+
     fn set_text_cursor_position(&mut self, rect: Rect) {
         let conn = WaylandConnection::get().unwrap().wayland();
         let state = conn.wayland_state.borrow();
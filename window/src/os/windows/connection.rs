@@ -12,6 +12,7 @@ use std::ffi::OsString;
 use std::os::windows::ffi::OsStringExt;
 use std::ptr::null_mut;
 use std::rc::Rc;
+use std::time::Duration;
 use winapi::shared::minwindef::*;
 use winapi::shared::windef::*;
 use winapi::shared::winerror::{ERROR_INSUFFICIENT_BUFFER, ERROR_SUCCESS};
@@ -63,6 +64,11 @@ impl ConnectionOps for Connection {
         get_appearance()
     }
 
+    fn double_click_interval(&self) -> Option<Duration> {
+        let ms = unsafe { GetDoubleClickTime() };
+        Some(Duration::from_millis(ms as u64))
+    }
+
     fn name(&self) -> String {
         "Windows".to_string()
     }
@@ -3,9 +3,9 @@ use crate::connection::ConnectionOps;
 use crate::parameters::{self, Parameters};
 use crate::{
     Appearance, Clipboard, DeadKeyStatus, Dimensions, Handled, KeyCode, KeyEvent, Modifiers,
-    MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, RawKeyEvent, Rect,
-    RequestedWindowGeometry, ResolvedGeometry, ScreenPoint, ScreenRect, ULength, WindowDecorations,
-    WindowEvent, WindowEventSender, WindowOps, WindowState,
+    MouseButtons, MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, Preedit, RawKeyEvent,
+    Rect, RequestedWindowGeometry, ResolvedGeometry, ScreenPoint, ScreenRect, TaskbarProgress,
+    ULength, WindowDecorations, WindowEvent, WindowEventSender, WindowOps, WindowState,
 };
 use anyhow::{bail, Context};
 use async_trait::async_trait;
@@ -35,10 +35,16 @@ use winapi::shared::minwindef::*;
 use winapi::shared::ntdef::*;
 use winapi::shared::windef::*;
 use winapi::shared::winerror::S_OK;
+use winapi::um::combaseapi::{CoCreateInstance, CoInitializeEx};
 use winapi::um::imm::*;
 use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::objbase::COINIT_APARTMENTTHREADED;
 use winapi::um::shellapi::{DragAcceptFiles, DragFinish, DragQueryFileW, HDROP};
 use winapi::um::shellscalingapi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI};
+use winapi::um::shobjidl_core::{
+    CLSID_TaskbarList, ITaskbarList3, TBPF_ERROR, TBPF_INDETERMINATE, TBPF_NOPROGRESS, TBPF_NORMAL,
+    TBPF_PAUSED,
+};
 use winapi::um::sysinfoapi::{GetTickCount, GetVersionExW};
 use winapi::um::uxtheme::{
     CloseThemeData, GetThemeFont, GetThemeSysFont, OpenThemeData, SetWindowTheme,
@@ -46,6 +52,8 @@ use winapi::um::uxtheme::{
 use winapi::um::wingdi::{LOGFONTW, MAKEPOINTS};
 use winapi::um::winnt::OSVERSIONINFOW;
 use winapi::um::winuser::*;
+use winapi::um::wtypesbase::CLSCTX_INPROC_SERVER;
+use winapi::Interface;
 use windows::UI::Color as WUIColor;
 use windows::UI::ViewManagement::{UIColorType, UISettings};
 use winreg::enums::HKEY_CURRENT_USER;
@@ -53,6 +61,9 @@ use winreg::RegKey;
 
 const GCS_RESULTSTR: DWORD = 0x800;
 const GCS_COMPSTR: DWORD = 0x8;
+const GCS_COMPATTR: DWORD = 0x10;
+const ATTR_TARGET_CONVERTED: u8 = 0x1;
+const ATTR_TARGET_NOTCONVERTED: u8 = 0x3;
 const ISC_SHOWUICOMPOSITIONWINDOW: DWORD = 0x80000000;
 
 #[allow(non_snake_case)]
@@ -68,7 +79,9 @@ pub type LPCANDIDATEFORM = *mut CANDIDATEFORM;
 extern "system" {
     pub fn ImmGetCompositionStringW(himc: HIMC, index: DWORD, buf: LPVOID, buflen: DWORD) -> LONG;
     pub fn ImmSetCandidateWindow(himc: HIMC, lpCandidate: LPCANDIDATEFORM) -> BOOL;
+    pub fn ImmAssociateContextEx(hwnd: HWND, himc: HIMC, flags: DWORD) -> BOOL;
 }
+const IACE_DEFAULT: DWORD = 0x0010;
 
 lazy_static! {
     static ref IS_WIN10: bool = {
@@ -119,6 +132,8 @@ pub(crate) struct WindowInner {
     track_mouse_leave: bool,
     window_drag_position: Option<ScreenPoint>,
     maximize_button_position: Option<ScreenRect>,
+    /// The position that was most recently reported by a Moved event
+    last_position: Option<ScreenPoint>,
 
     keyboard_info: KeyboardLayoutInfo,
     appearance: Appearance,
@@ -281,6 +296,20 @@ impl WindowInner {
         }
     }
 
+    /// Dispatches a Moved event if the client area is no longer
+    /// where it was when last reported
+    fn check_and_call_moved_if_needed(&mut self) {
+        if unsafe { IsIconic(self.hwnd.0) } != 0 {
+            // Minimized windows are parked far off screen
+            return;
+        }
+        let position = client_to_screen(self.hwnd.0, Point::new(0, 0));
+        if self.last_position != Some(position) {
+            self.last_position.replace(position);
+            self.events.dispatch(WindowEvent::Moved(position));
+        }
+    }
+
     /// Check if we need to generate a resize callback.
     /// Calls resize if needed.
     /// Returns true if we did.
@@ -539,6 +568,7 @@ impl Window {
             track_mouse_leave: false,
             window_drag_position: None,
             maximize_button_position: None,
+            last_position: None,
             config: config.clone(),
             paint_throttled: false,
             invalidated: true,
@@ -673,6 +703,43 @@ impl WindowInner {
         }
     }
 
+    fn set_ime_enabled(&mut self, enabled: bool) {
+        // Associating a NULL context disables the IME for the window,
+        // while IACE_DEFAULT restores the default context
+        unsafe {
+            ImmAssociateContextEx(
+                self.hwnd.0,
+                std::ptr::null_mut(),
+                if enabled { IACE_DEFAULT } else { 0 },
+            );
+        }
+    }
+
+    fn set_taskbar_progress(&mut self, progress: TaskbarProgress) {
+        TASKBAR_LIST.with(|taskbar| {
+            let taskbar = match taskbar {
+                Some(taskbar) => taskbar.0,
+                None => return,
+            };
+            let hwnd = self.hwnd.0;
+            let (state, value) = match progress {
+                TaskbarProgress::None => (TBPF_NOPROGRESS, None),
+                TaskbarProgress::Normal(pct) => (TBPF_NORMAL, Some(pct)),
+                TaskbarProgress::Error(pct) => (TBPF_ERROR, Some(pct)),
+                TaskbarProgress::Indeterminate => (TBPF_INDETERMINATE, None),
+                TaskbarProgress::Paused(pct) => (TBPF_PAUSED, Some(pct)),
+            };
+            unsafe {
+                // Setting the value implicitly switches to the normal state,
+                // so it must come before setting the state
+                if let Some(pct) = value {
+                    (*taskbar).SetProgressValue(hwnd, pct as u64, 100);
+                }
+                (*taskbar).SetProgressState(hwnd, state);
+            }
+        });
+    }
+
     fn config_did_change(&mut self, config: &ConfigHandle) {
         self.config = config.clone();
         self.apply_decoration();
@@ -859,6 +926,16 @@ impl WindowOps for Window {
         }
     }
 
+    fn invalidate_immediately(&self) {
+        Connection::with_window_inner(self.0, |inner| {
+            inner.paint_throttled = false;
+            unsafe {
+                InvalidateRect(inner.hwnd.0, null(), 0);
+            }
+            Ok(())
+        });
+    }
+
     fn set_title(&self, title: &str) {
         let title = title.to_owned();
         Connection::with_window_inner(self.0, move |inner| {
@@ -889,6 +966,20 @@ impl WindowOps for Window {
         });
     }
 
+    fn set_ime_enabled(&self, enabled: bool) {
+        Connection::with_window_inner(self.0, move |inner| {
+            inner.set_ime_enabled(enabled);
+            Ok(())
+        });
+    }
+
+    fn set_taskbar_progress(&self, progress: TaskbarProgress) {
+        Connection::with_window_inner(self.0, move |inner| {
+            inner.set_taskbar_progress(progress);
+            Ok(())
+        });
+    }
+
     fn set_inner_size(&self, width: usize, height: usize) {
         Connection::with_window_inner(self.0, move |inner| {
             let hwnd = inner.hwnd;
@@ -1548,6 +1639,9 @@ unsafe fn wm_windowposchanged(
     _lparam: LPARAM,
 ) -> Option<LRESULT> {
     // let pos = &*(lparam as *const WINDOWPOS);
+    if let Some(inner) = rc_from_hwnd(hwnd) {
+        inner.borrow_mut().check_and_call_moved_if_needed();
+    }
     wm_size(hwnd, 0, 0, 0)?;
     Some(0)
 }
@@ -1714,6 +1808,15 @@ fn apply_mouse_cursor(cursor: Option<MouseCursor>) {
                     MouseCursor::Text => IDC_IBEAM,
                     MouseCursor::SizeUpDown => IDC_SIZENS,
                     MouseCursor::SizeLeftRight => IDC_SIZEWE,
+                    MouseCursor::SizeNeSw => IDC_SIZENESW,
+                    MouseCursor::SizeNwSe => IDC_SIZENWSE,
+                    MouseCursor::Move | MouseCursor::Grabbing => IDC_SIZEALL,
+                    MouseCursor::Crosshair => IDC_CROSS,
+                    MouseCursor::Wait => IDC_WAIT,
+                    MouseCursor::Progress => IDC_APPSTARTING,
+                    MouseCursor::Help => IDC_HELP,
+                    MouseCursor::NotAllowed => IDC_NO,
+                    MouseCursor::Grab => IDC_HAND,
                 },
             ));
         },
@@ -1966,6 +2069,48 @@ unsafe fn mouse_wheel(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
     Some(0)
 }
 
+/// Holds the taskbar interface that shows the progress of windows;
+/// it is released automatically when dropped
+struct TaskbarList(*mut ITaskbarList3);
+
+impl TaskbarList {
+    fn create() -> Option<Self> {
+        unsafe {
+            // COM may already have been initialized on this thread,
+            // in which case this is a harmless no-op
+            CoInitializeEx(null_mut(), COINIT_APARTMENTTHREADED);
+
+            let mut taskbar: *mut ITaskbarList3 = null_mut();
+            let hr = CoCreateInstance(
+                &CLSID_TaskbarList,
+                null_mut(),
+                CLSCTX_INPROC_SERVER,
+                &ITaskbarList3::uuidof(),
+                &mut taskbar as *mut *mut ITaskbarList3 as *mut _,
+            );
+            if hr != S_OK || taskbar.is_null() {
+                log::debug!("Unable to create ITaskbarList3: {:x}", hr);
+                return None;
+            }
+            Some(Self(taskbar))
+        }
+    }
+}
+
+impl Drop for TaskbarList {
+    fn drop(&mut self) {
+        unsafe {
+            (*self.0).Release();
+        }
+    }
+}
+
+thread_local! {
+    /// Created on first use by the gui thread, which initializes
+    /// COM for that thread once
+    static TASKBAR_LIST: Option<TaskbarList> = TaskbarList::create();
+}
+
 /// Helper for managing the IME Manager
 struct ImmContext {
     hwnd: HWND,
@@ -2042,6 +2187,27 @@ impl ImmContext {
             Ok(String::new())
         }
     }
+
+    /// Returns the attribute of each UTF-16 code unit of the
+    /// composition string, which describes its clauses
+    pub fn get_attributes(&self) -> Vec<u8> {
+        let size =
+            unsafe { ImmGetCompositionStringW(self.imc, GCS_COMPATTR, std::ptr::null_mut(), 0) };
+        if size > 0 {
+            let mut buf = vec![0u8; size as usize];
+            unsafe {
+                ImmGetCompositionStringW(
+                    self.imc,
+                    GCS_COMPATTR,
+                    buf.as_mut_ptr() as *mut _,
+                    size as u32,
+                )
+            };
+            buf
+        } else {
+            vec![]
+        }
+    }
 }
 
 impl Drop for ImmContext {
@@ -2120,10 +2286,14 @@ unsafe fn ime_composition(
         // No finished result; continue with the default
         // processing
         if let Ok(composing) = imc.get_str(GCS_COMPSTR) {
+            let preedit =
+                Preedit::from_utf16_attributes(composing, &imc.get_attributes(), |attr| {
+                    attr == ATTR_TARGET_CONVERTED || attr == ATTR_TARGET_NOTCONVERTED
+                });
             inner
                 .events
                 .dispatch(WindowEvent::AdviseDeadKeyStatus(DeadKeyStatus::Composing(
-                    composing,
+                    preedit,
                 )));
         }
         // We will show the composing string ourselves.
@@ -2744,7 +2914,7 @@ unsafe fn key(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<L
                 if inner.config.use_dead_keys {
                     inner.dead_pending.replace((modifiers, vk));
                     inner.events.dispatch(WindowEvent::AdviseDeadKeyStatus(
-                        DeadKeyStatus::Composing(c.to_string()),
+                        DeadKeyStatus::Composing(c.to_string().into()),
                     ));
                     return Some(0);
                 }
@@ -1,12 +1,14 @@
 use super::keyboard::{Keyboard, KeyboardWithFallback};
-use crate::connection::ConnectionOps;
+use crate::connection::{ApplicationEvent, ConnectionOps, GlobalHotkey};
 use crate::os::x11::window::XWindowInner;
 use crate::os::x11::xsettings::*;
+use crate::os::xkeysyms::keysym_to_keycode;
 use crate::os::Connection;
 use crate::screen::{ScreenInfo, Screens};
 use crate::spawn::*;
-use crate::{Appearance, DeadKeyStatus, ScreenRect};
+use crate::{Appearance, DeadKeyStatus, KeyCode, Modifiers, Preedit, ScreenRect};
 use anyhow::{anyhow, bail, Context as _};
+use config::keyassignment::KeyAssignment;
 use mio::event::Source;
 use mio::unix::SourceFd;
 use mio::{Events, Interest, Poll, Registry, Token};
@@ -15,6 +17,7 @@ use std::collections::{HashMap, HashSet};
 use std::os::unix::io::AsRawFd;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use x11::xlib;
 use xcb::x::Atom;
 use xcb::{dri2, Raw, Xid};
@@ -81,6 +84,56 @@ pub struct XConnection {
     pub(crate) atom_names: RefCell<HashMap<Atom, String>>,
     pub(crate) supported: RefCell<HashSet<Atom>>,
     pub(crate) screens: RefCell<Option<Screens>>,
+    global_hotkeys: RefCell<Vec<GrabbedHotkey>>,
+}
+
+/// A global hotkey, resolved to the X keycode and modifiers
+/// that were grabbed on the root window
+struct GrabbedHotkey {
+    keycode: xcb::x::Keycode,
+    mods: xcb::x::ModMask,
+    action: KeyAssignment,
+}
+
+/// The lock modifiers (CapsLock and NumLock) don't change the meaning
+/// of a hotkey, but X only delivers a grabbed key when the modifier
+/// state matches exactly, so each hotkey is grabbed with every
+/// combination of them
+fn lock_mask_combinations() -> [xcb::x::ModMask; 4] {
+    use xcb::x::ModMask;
+    [
+        ModMask::empty(),
+        ModMask::LOCK,
+        ModMask::N2,
+        ModMask::LOCK | ModMask::N2,
+    ]
+}
+
+fn modmask_from_modifiers(mods: Modifiers) -> xcb::x::ModMask {
+    use xcb::x::ModMask;
+    let mut mask = ModMask::empty();
+    if mods.contains(Modifiers::SHIFT) {
+        mask |= ModMask::SHIFT;
+    }
+    if mods.contains(Modifiers::CTRL) {
+        mask |= ModMask::CONTROL;
+    }
+    if mods.contains(Modifiers::ALT) {
+        mask |= ModMask::N1;
+    }
+    if mods.contains(Modifiers::SUPER) {
+        mask |= ModMask::N4;
+    }
+    mask
+}
+
+/// Compares key codes ignoring the case of characters, as the
+/// keysym found for a key is its unshifted form
+fn same_key(a: &KeyCode, b: &KeyCode) -> bool {
+    match (a, b) {
+        (KeyCode::Char(a), KeyCode::Char(b)) => a.to_lowercase().eq(b.to_lowercase()),
+        (a, b) => a == b,
+    }
 }
 
 impl std::ops::Deref for XConnection {
@@ -201,6 +254,13 @@ impl ConnectionOps for XConnection {
         *self.default_dpi.borrow()
     }
 
+    fn double_click_interval(&self) -> Option<Duration> {
+        match self.xsettings.borrow().get("Net/DoubleClickTime") {
+            Some(XSetting::Integer(ms)) if *ms > 0 => Some(Duration::from_millis(*ms as u64)),
+            _ => None,
+        }
+    }
+
     fn get_appearance(&self) -> Appearance {
         match promise::spawn::block_on(crate::os::xdg_desktop_portal::get_appearance()) {
             Ok(Some(appearance)) => return appearance,
@@ -392,6 +452,81 @@ impl ConnectionOps for XConnection {
     fn beep(&self) {
         self.conn.send_request(&xcb::x::Bell { percent: 0 });
     }
+
+    fn set_global_hotkeys(&self, hotkeys: &[GlobalHotkey]) -> anyhow::Result<()> {
+        for grabbed in self.global_hotkeys.borrow_mut().drain(..) {
+            for locks in lock_mask_combinations() {
+                self.conn.send_request(&xcb::x::UngrabKey {
+                    key: grabbed.keycode,
+                    grab_window: self.root,
+                    modifiers: grabbed.mods | locks,
+                });
+            }
+        }
+        if hotkeys.is_empty() {
+            return Ok(());
+        }
+
+        let setup = self.conn.get_setup();
+        let min_keycode = setup.min_keycode();
+        let max_keycode = setup.max_keycode();
+        let mapping = self
+            .send_and_wait_request(&xcb::x::GetKeyboardMapping {
+                first_keycode: min_keycode,
+                count: max_keycode - min_keycode + 1,
+            })
+            .context("GetKeyboardMapping")?;
+        let keysyms_per_keycode = mapping.keysyms_per_keycode() as usize;
+        let keysyms = mapping.keysyms();
+
+        let mut errors = vec![];
+        for hotkey in hotkeys {
+            let keycode = (min_keycode..=max_keycode).find(|&code| {
+                keysyms
+                    .get((code - min_keycode) as usize * keysyms_per_keycode)
+                    .and_then(|&sym| keysym_to_keycode(sym))
+                    .map_or(false, |key| same_key(&key, &hotkey.key))
+            });
+            let keycode = match keycode {
+                Some(keycode) => keycode,
+                None => {
+                    errors.push(format!("{:?} is not present in the keymap", hotkey.key));
+                    continue;
+                }
+            };
+            let mods = modmask_from_modifiers(hotkey.mods);
+            for locks in lock_mask_combinations() {
+                if let Err(err) =
+                    self.conn
+                        .check_request(self.conn.send_request_checked(&xcb::x::GrabKey {
+                            owner_events: false,
+                            grab_window: self.root,
+                            modifiers: mods | locks,
+                            key: keycode,
+                            pointer_mode: xcb::x::GrabMode::Async,
+                            keyboard_mode: xcb::x::GrabMode::Async,
+                        }))
+                {
+                    // Most likely, another application has already
+                    // grabbed this combination
+                    errors.push(format!("{:?} {:?}: {err:#}", hotkey.mods, hotkey.key));
+                    break;
+                }
+            }
+            // Remember it even if only some of the grabs succeeded,
+            // so that they are released when the hotkeys change
+            self.global_hotkeys.borrow_mut().push(GrabbedHotkey {
+                keycode,
+                mods,
+                action: hotkey.action.clone(),
+            });
+        }
+
+        if !errors.is_empty() {
+            bail!("failed to register global hotkeys: {}", errors.join(", "));
+        }
+        Ok(())
+    }
 }
 
 fn compute_default_dpi(xrm: &HashMap<String, String>, xsettings: &XSettingsMap) -> f64 {
@@ -488,13 +623,30 @@ impl XConnection {
         // check for previous errors produced by the IME forward_event callback
         self.ime_process_event_result.replace(Ok(()))?;
 
-        if config::configuration().use_ime && self.ime.borrow_mut().process_event(event) {
+        if config::configuration().use_ime
+            && self.ime_wants_event(event)
+            && self.ime.borrow_mut().process_event(event)
+        {
             self.ime_process_event_result.replace(Ok(()))
         } else {
             self.process_xcb_event(event)
         }
     }
 
+    /// Key events for windows that have disabled the IME
+    /// bypass it and are processed directly
+    fn ime_wants_event(&self, event: &xcb::Event) -> bool {
+        let window_id = match event {
+            xcb::Event::X(xcb::x::Event::KeyPress(e)) => e.event(),
+            xcb::Event::X(xcb::x::Event::KeyRelease(e)) => e.event(),
+            _ => return true,
+        };
+        match self.window_by_id(window_id) {
+            Some(window) => window.lock().unwrap().ime_enabled,
+            None => true,
+        }
+    }
+
     unsafe fn rewire_event(&self, raw_ev: *mut xcb::ffi::xcb_generic_event_t) {
         let ev_type = ((*raw_ev).response_type & 0x7f) as i32;
 
@@ -546,6 +698,21 @@ impl XConnection {
                 // Clear our cache
                 self.screens.borrow_mut().take();
             }
+            xcb::Event::X(xcb::x::Event::KeyPress(e)) if e.event() == self.root => {
+                // One of our global hotkeys
+                let locks = xcb::x::ModMask::LOCK | xcb::x::ModMask::N2;
+                let mods = xcb::x::ModMask::from_bits_truncate(e.state().bits()) - locks;
+                let action = self
+                    .global_hotkeys
+                    .borrow()
+                    .iter()
+                    .find(|hotkey| hotkey.keycode == e.detail() && hotkey.mods == mods)
+                    .map(|hotkey| hotkey.action.clone());
+                if let Some(action) = action {
+                    self.dispatch_app_event(ApplicationEvent::PerformKeyAssignment(action));
+                }
+                return Ok(());
+            }
             _ => {}
         }
 
@@ -825,6 +992,7 @@ impl XConnection {
             atom_names: RefCell::new(HashMap::new()),
             supported: RefCell::new(HashSet::new()),
             screens: RefCell::new(None),
+            global_hotkeys: RefCell::new(vec![]),
         });
 
         {
@@ -849,7 +1017,7 @@ impl XConnection {
                         let mut inner = window.lock().unwrap();
 
                         let text = info.text();
-                        let status = DeadKeyStatus::Composing(text);
+                        let status = DeadKeyStatus::Composing(Preedit::composition(text));
                         inner.dispatch_ime_compose_status(status);
                     }
                 });
@@ -14,11 +14,19 @@ use xcb::x::Cursor;
 use xcb::Xid;
 
 // X11 classic Cursor glyphs
+pub const X_CURSOR: u16 = 0;
+pub const BOTTOM_LEFT_CORNER: u16 = 12;
+pub const BOTTOM_RIGHT_CORNER: u16 = 14;
+pub const CROSSHAIR: u16 = 34;
+pub const FLEUR: u16 = 52;
 pub const HAND1: u16 = 58;
+pub const HAND2: u16 = 60;
+pub const QUESTION_ARROW: u16 = 92;
 pub const SB_H_DOUBLE_ARROW: u16 = 108;
 pub const SB_V_DOUBLE_ARROW: u16 = 116;
 pub const TOP_LEFT_ARROW: u16 = 132;
 pub const TOP_LEFT_CORNER: u16 = 134;
+pub const WATCH: u16 = 150;
 pub const XTERM: u16 = 152;
 
 pub struct XcbCursor {
@@ -317,6 +325,16 @@ impl CursorInfo {
             MouseCursor::Text => &["xterm"],
             MouseCursor::SizeUpDown => &["sb_v_double_arrow"],
             MouseCursor::SizeLeftRight => &["sb_h_double_arrow"],
+            MouseCursor::SizeNeSw => &["nesw-resize", "bottom_left_corner"],
+            MouseCursor::SizeNwSe => &["nwse-resize", "bottom_right_corner"],
+            MouseCursor::Move => &["move", "fleur"],
+            MouseCursor::Crosshair => &["crosshair", "cross"],
+            MouseCursor::Wait => &["wait", "watch"],
+            MouseCursor::Progress => &["progress", "left_ptr_watch"],
+            MouseCursor::Help => &["help", "question_arrow"],
+            MouseCursor::NotAllowed => &["not-allowed", "crossed_circle"],
+            MouseCursor::Grab => &["grab", "openhand", "hand1"],
+            MouseCursor::Grabbing => &["grabbing", "closedhand", "fleur"],
         };
 
         let mut theme_list = vec![theme.to_string()];
@@ -376,6 +394,14 @@ impl CursorInfo {
             MouseCursor::Text => XTERM,
             MouseCursor::SizeUpDown => SB_V_DOUBLE_ARROW,
             MouseCursor::SizeLeftRight => SB_H_DOUBLE_ARROW,
+            MouseCursor::SizeNeSw => BOTTOM_LEFT_CORNER,
+            MouseCursor::SizeNwSe => BOTTOM_RIGHT_CORNER,
+            MouseCursor::Move | MouseCursor::Grabbing => FLEUR,
+            MouseCursor::Crosshair => CROSSHAIR,
+            MouseCursor::Wait | MouseCursor::Progress => WATCH,
+            MouseCursor::Help => QUESTION_ARROW,
+            MouseCursor::NotAllowed => X_CURSOR,
+            MouseCursor::Grab => HAND2,
         };
         log::trace!("loading X11 basic cursor {} for {:?}", id_no, cursor);
 
@@ -324,7 +324,7 @@ impl KeyboardWithFallback {
                         composition
                     );
                     events.dispatch(WindowEvent::AdviseDeadKeyStatus(DeadKeyStatus::Composing(
-                        composition,
+                        composition.into(),
                     )));
                     return None;
                 }
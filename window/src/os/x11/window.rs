@@ -99,12 +99,16 @@ pub(crate) struct XWindowInner {
     pub has_focus: Option<bool>,
     verify_focus: bool,
     last_cursor_position: Rect,
+    /// When false, key events bypass the IME
+    pub(crate) ime_enabled: bool,
     invalidated: bool,
     paint_throttled: bool,
     pending: Vec<WindowEvent>,
     sure_about_geometry: bool,
     current_mouse_event: Option<MouseEvent>,
     window_drag_position: Option<ScreenPoint>,
+    /// The position that was most recently reported by a Moved event
+    last_position: Option<ScreenPoint>,
     dragging: bool,
     outstanding_configure_requests: usize,
     pending_finished_resizes: usize,
@@ -476,6 +480,26 @@ impl XWindowInner {
         self.do_mouse_event(event)
     }
 
+    /// Queues a Moved event if the window is no longer where
+    /// it was when last reported
+    fn check_position(&mut self) -> anyhow::Result<()> {
+        let conn = self.conn();
+        let coords = conn
+            .send_and_wait_request(&xcb::x::TranslateCoordinates {
+                src_window: self.window_id,
+                dst_window: conn.root,
+                src_x: 0,
+                src_y: 0,
+            })
+            .context("querying window coordinates")?;
+        let position = ScreenPoint::new(coords.dst_x().into(), coords.dst_y().into());
+        if self.last_position != Some(position) {
+            self.last_position.replace(position);
+            self.queue_pending(WindowEvent::Moved(position));
+        }
+        Ok(())
+    }
+
     fn configure_notify(&mut self, source: &str, width: u16, height: u16) -> anyhow::Result<()> {
         let conn = self.conn();
 
@@ -500,17 +524,8 @@ impl XWindowInner {
                 height as isize,
             );
             let screen = screens
-                .by_name
-                .values()
-                .filter_map(|screen| {
-                    screen
-                        .rect
-                        .intersection(&window_rect)
-                        .map(|r| (screen, r.area()))
-                })
-                .max_by_key(|s| s.1)
-                .ok_or_else(|| anyhow::anyhow!("window is not in any screen"))?
-                .0;
+                .screen_for_rect(&window_rect)
+                .ok_or_else(|| anyhow::anyhow!("window is not in any screen"))?;
 
             if let Some(value) = self.config.dpi_by_screen.get(&screen.name).copied() {
                 dpi = value;
@@ -701,6 +716,7 @@ impl XWindowInner {
             }
             Event::X(xcb::x::Event::ConfigureNotify(cfg)) => {
                 self.configure_notify("X::ConfigureNotify", cfg.width(), cfg.height())?;
+                self.check_position()?;
                 if self.outstanding_configure_requests > 0 {
                     self.outstanding_configure_requests -= 1;
                     self.pending_finished_resizes += 1;
@@ -1479,6 +1495,7 @@ impl XWindow {
                 has_focus: None,
                 verify_focus: true,
                 last_cursor_position: Rect::default(),
+                ime_enabled: true,
                 paint_throttled: false,
                 last_wm_state: WindowState::default(),
                 invalidated: false,
@@ -1486,6 +1503,7 @@ impl XWindow {
                 sure_about_geometry: false,
                 current_mouse_event: None,
                 window_drag_position: None,
+                last_position: None,
                 dragging: false,
                 outstanding_configure_requests: 0,
                 pending_finished_resizes: 0,
@@ -1661,6 +1679,11 @@ impl XWindowInner {
         self.dispatch_pending_events().ok();
     }
 
+    fn invalidate_immediately(&mut self) {
+        self.paint_throttled = false;
+        self.invalidate();
+    }
+
     fn maximize(&mut self) {
         if let Err(err) = self.set_maximized_hint(true) {
             log::error!("Failed to maximize: {err:#}");
@@ -1827,6 +1850,10 @@ impl XWindowInner {
         self.update_ime_position();
     }
 
+    fn set_ime_enabled(&mut self, enabled: bool) {
+        self.ime_enabled = enabled;
+    }
+
     fn update_ime_position(&mut self) {
         if !self.has_focus.unwrap_or(false) {
             return;
@@ -2033,6 +2060,13 @@ impl WindowOps for XWindow {
         });
     }
 
+    fn invalidate_immediately(&self) {
+        XConnection::with_window_inner(self.0, |inner| {
+            inner.invalidate_immediately();
+            Ok(())
+        });
+    }
+
     fn set_title(&self, title: &str) {
         let title = title.to_owned();
         XConnection::with_window_inner(self.0, move |inner| {
@@ -2086,6 +2120,13 @@ impl WindowOps for XWindow {
         });
     }
 
+    fn set_ime_enabled(&self, enabled: bool) {
+        XConnection::with_window_inner(self.0, move |inner| {
+            inner.set_ime_enabled(enabled);
+            Ok(())
+        });
+    }
+
     fn set_icon(&self, image: Image) {
         XConnection::with_window_inner(self.0, move |inner| {
             inner.set_icon(&image);
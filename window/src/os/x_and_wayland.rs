@@ -1,6 +1,6 @@
 #![cfg(all(unix, not(target_os = "macos")))]
 
-use crate::connection::ConnectionOps;
+use crate::connection::{ConnectionOps, GlobalHotkey};
 #[cfg(feature = "wayland")]
 use crate::os::wayland::connection::WaylandConnection;
 #[cfg(feature = "wayland")]
@@ -20,6 +20,7 @@ use raw_window_handle::{
 };
 use std::any::Any;
 use std::rc::Rc;
+use std::time::Duration;
 use wezterm_font::FontConfiguration;
 
 pub enum Connection {
@@ -167,6 +168,14 @@ impl ConnectionOps for Connection {
         }
     }
 
+    fn double_click_interval(&self) -> Option<Duration> {
+        match self {
+            Self::X11(x) => x.double_click_interval(),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.double_click_interval(),
+        }
+    }
+
     fn screens(&self) -> anyhow::Result<Screens> {
         match self {
             Self::X11(x) => x.screens(),
@@ -174,6 +183,14 @@ impl ConnectionOps for Connection {
             Self::Wayland(w) => w.screens(),
         }
     }
+
+    fn set_global_hotkeys(&self, hotkeys: &[GlobalHotkey]) -> anyhow::Result<()> {
+        match self {
+            Self::X11(x) => x.set_global_hotkeys(hotkeys),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.set_global_hotkeys(hotkeys),
+        }
+    }
 }
 
 impl Window {
@@ -314,6 +331,14 @@ impl WindowOps for Window {
         }
     }
 
+    fn invalidate_immediately(&self) {
+        match self {
+            Self::X11(x) => x.invalidate_immediately(),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.invalidate_immediately(),
+        }
+    }
+
     fn set_resize_increments(&self, incr: ResizeIncrement) {
         match self {
             Self::X11(x11) => x11.set_resize_increments(incr),
@@ -394,6 +419,14 @@ impl WindowOps for Window {
         }
     }
 
+    fn set_ime_enabled(&self, enabled: bool) {
+        match self {
+            Self::X11(x) => x.set_ime_enabled(enabled),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.set_ime_enabled(enabled),
+        }
+    }
+
     fn get_clipboard(&self, clipboard: Clipboard) -> Future<String> {
         match self {
             Self::X11(x) => x.get_clipboard(clipboard),
@@ -17,3 +17,99 @@ pub struct ScreenInfo {
     pub max_fps: Option<usize>,
     pub effective_dpi: Option<f64>,
 }
+
+impl Screens {
+    /// Returns the screen that holds the majority of `rect`; this is
+    /// the screen whose dpi should be used by a window that occupies
+    /// `rect`.  When the area is evenly split, the screen that sorts
+    /// first by name wins so that the choice is stable.
+    /// Returns None if `rect` doesn't overlap any screen.
+    pub fn screen_for_rect(&self, rect: &ScreenRect) -> Option<&ScreenInfo> {
+        let mut names: Vec<&String> = self.by_name.keys().collect();
+        names.sort();
+        let mut best: Option<(&ScreenInfo, isize)> = None;
+        for info in names.into_iter().filter_map(|name| self.by_name.get(name)) {
+            let area = match info.rect.intersection(rect) {
+                Some(r) if r.area() > 0 => r.area(),
+                _ => continue,
+            };
+            if best.map_or(true, |(_, best_area)| area > best_area) {
+                best = Some((info, area));
+            }
+        }
+        best.map(|(info, _)| info)
+    }
+
+    /// Returns the screen that holds the majority of a window that
+    /// occupies `rect`.  Falls back to the active screen when the
+    /// position of the window isn't known, or when it is off screen.
+    pub fn screen_for_window(&self, rect: Option<&ScreenRect>) -> &ScreenInfo {
+        rect.and_then(|rect| self.screen_for_rect(rect))
+            .unwrap_or(&self.active)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn screen(name: &str, x: isize, dpi: f64) -> ScreenInfo {
+        ScreenInfo {
+            name: name.to_string(),
+            rect: euclid::rect(x, 0, 1000, 1000),
+            scale: 1.0,
+            max_fps: None,
+            effective_dpi: Some(dpi),
+        }
+    }
+
+    fn screens() -> Screens {
+        let left = screen("left", 0, 96.);
+        let right = screen("right", 1000, 192.);
+        Screens {
+            main: left.clone(),
+            active: left.clone(),
+            by_name: [left, right]
+                .into_iter()
+                .map(|info| (info.name.clone(), info))
+                .collect(),
+            virtual_rect: euclid::rect(0, 0, 2000, 1000),
+        }
+    }
+
+    fn screen_name(screens: &Screens, x: isize) -> Option<&str> {
+        screens
+            .screen_for_rect(&euclid::rect(x, 100, 400, 300))
+            .map(|info| info.name.as_str())
+    }
+
+    #[test]
+    fn majority_flips_mid_drag() {
+        let screens = screens();
+        assert_eq!(screen_name(&screens, 0), Some("left"));
+        // Mostly on the left
+        assert_eq!(screen_name(&screens, 799), Some("left"));
+        // Evenly split; the choice is stable
+        assert_eq!(screen_name(&screens, 800), Some("left"));
+        // The majority is now on the right
+        assert_eq!(screen_name(&screens, 801), Some("right"));
+        assert_eq!(screen_name(&screens, 1600), Some("right"));
+        // Off screen entirely
+        assert_eq!(screen_name(&screens, 2000), None);
+        assert_eq!(screen_name(&screens, -400), None);
+    }
+
+    #[test]
+    fn window_screen() {
+        let mut screens = screens();
+        screens.active = screens.by_name["right"].clone();
+        let name = |rect: Option<ScreenRect>| screens.screen_for_window(rect.as_ref()).name.clone();
+
+        // The screen is picked by position, not by which one is active
+        assert_eq!(name(Some(euclid::rect(100, 100, 400, 300))), "left");
+        assert_eq!(name(Some(euclid::rect(1100, 100, 400, 300))), "right");
+        // Unknown position, or off screen
+        assert_eq!(name(None), "right");
+        assert_eq!(name(Some(euclid::rect(3000, 100, 400, 300))), "right");
+    }
+}